@@ -7,20 +7,46 @@
 //! veth pair `veth0` and `veth1`.
 //!
 //! * Client: `iperf3 veth0 10.0.0.1/24 ac:ff:ff:ff:ff:ff 10.0.0.2/24 -c 10.0.0.2 5001 -n 10000 -l 1470 --udp`
-//! * Server: `iperf3 veth1 10.0.0.2/24 ac:ff:ff:fe:ff:ff 10.0.0.1/24 -s 5001 --udp`
+//! * Server: `iperf3 veth1 10.0.0.2/24 ac:ff:ff:fe:ff:ff 10.0.0.1/24 -s 5001 --iperf2`
+//!
+//! Without `--iperf2`, the server speaks the iperf3 protocol instead and serves a test with tcp or
+//! udp streams, whichever the client requests. It also works with a stock iperf3 client:
+//!
+//! * Server: `iperf3 veth1 10.0.0.2/24 ac:ff:ff:fe:ff:ff 10.0.0.1/24 -s 5201`
+//! * Client: `iperf3 -c 10.0.0.2 -p 5201 -u -b 10M`
 //!
 //! (This uses a locally administered unicast MAC address)
-pub use ethox_iperf::{config, iperf2};
+//!
+//! Add `-i <seconds>` before the iperf options for periodic interval reports, and `-J` to get the
+//! results as json in the format of iperf3 instead.
+//!
+//! On macOS and FreeBSD the interface is opened through a bpf device instead of a raw socket. On
+//! Windows it names an existing wintun adapter.
+pub use ethox_iperf::{config, iperf2, iperf3, Reporter};
 
 use ethox::managed::{List, Slice};
-use ethox::nic::sys::RawSocket;
+#[cfg(target_os = "linux")]
+use ethox::nic::sys::RawSocket as Interface;
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+use ethox::nic::sys::Bpf as Interface;
+#[cfg(windows)]
+use ethox::nic::wintun;
 use ethox::layer::{arp, eth, ip};
 
 fn main() {
     let config = config::Config::from_args();
 
-    let mut interface = RawSocket::new(&config.tap, vec![0; 1 << 14])
+    #[cfg(unix)]
+    let mut interface = Interface::new(&config.tap, vec![0; 1 << 14])
         .expect("Couldn't initialize interface");
+    #[cfg(windows)]
+    let mut interface = {
+        let library = wintun::Library::load()
+            .expect("Couldn't load wintun.dll");
+        let session = wintun::WintunDesc::open(library, &config.tap, wintun::MIN_RING_CAPACITY*8)
+            .expect("Couldn't initialize interface");
+        wintun::Wintun::new(session, config.hostmac, vec![0; 1 << 14])
+    };
 
     let mut eth = eth::Endpoint::new(config.hostmac);
 
@@ -31,7 +57,11 @@ fn main() {
         ip::Routes::import(List::new_full(routes.as_mut().into())),
         arp::NeighborCache::new(&mut neighbors[..]));
 
-    println!("[+] Configured layers, communicating");
+    let mut report = Reporter::new(&config.report);
+    // Keep the json output clean.
+    if !report.is_json() {
+        println!("[+] Configured layers, communicating");
+    }
 
     let result = match &config.iperf3 {
         config::Iperf3Config::Client(
@@ -43,6 +73,7 @@ fn main() {
                 &mut eth,
                 &mut ip,
                 iperf2::Iperf::new(client),
+                &mut report,
             )
         },
         config::Iperf3Config::Client(
@@ -54,10 +85,11 @@ fn main() {
                 &mut eth,
                 &mut ip,
                 iperf2::IperfTcp::new(client),
+                &mut report,
             )
         },
         config::Iperf3Config::Server(
-            config::IperfServer { kind: config::Transport::Udp, server }
+            config::IperfServer { iperf2: true, server }
         ) => {
             ethox_iperf::server(
                 &mut interface,
@@ -65,11 +97,25 @@ fn main() {
                 &mut eth,
                 &mut ip,
                 iperf2::Server::new(server),
+                &mut report,
             )
-        }
-        _ => unimplemented!("Tcp server is not yet implemented!"),
+        },
+        config::Iperf3Config::Server(
+            config::IperfServer { iperf2: false, server }
+        ) => {
+            ethox_iperf::server(
+                &mut interface,
+                10,
+                &mut eth,
+                &mut ip,
+                iperf3::Server::new(server, config.host.address()),
+                &mut report,
+            )
+        },
     };
 
-    println!("[+] Done\n");
-    println!("{}", result);
+    if !report.is_json() {
+        println!("[+] Done\n");
+    }
+    println!("{}", report.summary(&result));
 }
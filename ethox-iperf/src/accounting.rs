@@ -0,0 +1,89 @@
+//! Loss, reordering and jitter accounting of udp tests.
+//!
+//! The datagrams of a udp test carry a sequence number and the time at which they were sent. A gap
+//! in the sequence numbers counts as lost until a late datagram fills it, which is then counted as
+//! out-of-order instead. The jitter is the smoothed variation of the transit time of consecutive
+//! datagrams as specified for RTP in RFC 3550. The clocks of sender and receiver need not be
+//! synchronized for this, only the differences of transit times are used.
+use core::convert::TryFrom;
+
+use ethox::time::{Duration, Instant};
+
+/// The receiver side counters of a udp test.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct UdpAccounting {
+    /// The sequence number of the first datagram.
+    first: u64,
+    /// The next expected sequence number.
+    expected: u64,
+    received: u64,
+    lost: u64,
+    out_of_order: u64,
+    /// Transit time of the previous datagram in microseconds.
+    transit: Option<i64>,
+    /// The jitter estimate in microseconds.
+    jitter: f64,
+}
+
+impl UdpAccounting {
+    /// Start counting with the sequence number of the first datagram.
+    pub fn new(first: u64) -> Self {
+        UdpAccounting {
+            first,
+            expected: first,
+            received: 0,
+            lost: 0,
+            out_of_order: 0,
+            transit: None,
+            jitter: 0.0,
+        }
+    }
+
+    /// Register an arrived datagram.
+    ///
+    /// The send time is relative to the epoch of the sender's clock.
+    pub fn register(&mut self, seq: u64, sent: Duration, arrived: Instant) {
+        self.received += 1;
+
+        if seq >= self.expected {
+            self.lost += seq - self.expected;
+            self.expected = seq + 1;
+        } else {
+            // Counted as lost when the gap was detected.
+            self.lost = self.lost.saturating_sub(1);
+            self.out_of_order += 1;
+        }
+
+        let sent = i64::try_from(sent.as_micros()).unwrap_or(i64::MAX);
+        let transit = arrived.total_millis().saturating_mul(1000).saturating_sub(sent);
+        if let Some(previous) = self.transit.replace(transit) {
+            let delta = transit.saturating_sub(previous).abs() as f64;
+            self.jitter += (delta - self.jitter) / 16.0;
+        }
+    }
+
+    /// The number of received datagrams.
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// The number of datagrams the sender has sent, as far as known.
+    pub fn total(&self) -> u64 {
+        self.expected - self.first
+    }
+
+    /// The number of datagrams missing from the sequence.
+    pub fn lost(&self) -> u64 {
+        self.lost
+    }
+
+    /// The number of datagrams that arrived after a later one.
+    pub fn out_of_order(&self) -> u64 {
+        self.out_of_order
+    }
+
+    /// The current jitter estimate.
+    pub fn jitter(&self) -> Duration {
+        Duration::from_micros(self.jitter as u64)
+    }
+}
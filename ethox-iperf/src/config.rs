@@ -1,7 +1,6 @@
 use structopt::StructOpt;
-use std::net;
 
-use ethox::wire::{Ipv4Cidr, EthernetAddress};
+use ethox::wire::{Ipv4Address, Ipv4Cidr, EthernetAddress};
 
 #[derive(Clone, StructOpt)]
 pub enum Iperf3Config {
@@ -30,15 +29,16 @@ pub struct IperfClient {
 
 #[derive(Clone, StructOpt)]
 pub struct IperfServer {
-    #[structopt(flatten)]
-    pub kind: Transport,
+    /// Run the plain iperf2 udp server instead of the iperf3 protocol.
+    #[structopt(long = "iperf2")]
+    pub iperf2: bool,
     #[structopt(flatten)]
     pub server: Server,
 }
 
 #[derive(Clone, StructOpt)]
 pub struct Client {
-    pub host: net::Ipv4Addr,
+    pub host: Ipv4Address,
     pub port: u16,
     #[structopt(short = "l")]
     pub buffer_bytes: usize,
@@ -49,10 +49,20 @@ pub struct Client {
 #[derive(Clone, StructOpt)]
 pub struct Server {
     #[structopt(short = "B")]
-    pub host: Option<net::Ipv4Addr>,
+    pub host: Option<Ipv4Address>,
     pub port: u16,
 }
 
+#[derive(Clone, StructOpt)]
+pub struct Report {
+    /// Seconds between periodic bandwidth reports, zero disables them.
+    #[structopt(short = "i", long = "interval", default_value = "0")]
+    pub interval: f32,
+    /// Output the results in json.
+    #[structopt(short = "J", long = "json")]
+    pub json: bool,
+}
+
 #[derive(Clone, StructOpt)]
 pub struct Config {
     pub tap: String,
@@ -60,6 +70,9 @@ pub struct Config {
     pub hostmac: EthernetAddress,
     pub gateway: Ipv4Cidr,
 
+    #[structopt(flatten)]
+    pub report: Report,
+
     #[structopt(subcommand)]
     pub iperf3: Iperf3Config,
 }
@@ -9,12 +9,14 @@
 //! There is no control channel as for iperf3. This may have negative impact on the accuracy of the
 //! measurement but greatly simplifies the independent implementation for udp.
 use core::{mem, ptr};
+use core::convert::TryFrom;
 
 use ethox::layer::{ip, tcp, udp, Error};
 use ethox::time::{Duration, Instant};
 use ethox::wire::{Ipv4Subnet, PayloadMut, TcpSeqNumber};
 use ethox::managed::{Map, Partial, SlotMap};
 
+use super::accounting::UdpAccounting;
 use super::config;
 
 pub struct Iperf {
@@ -72,11 +74,8 @@ struct ServerConnection {
     /// Number of bytes transferred.
     received_bytes: usize,
 
-    /// The maximally observed packet number.
-    max_packet_id: u32,
-
-    /// Number of received packet.
-    received_packets: u32,
+    /// Loss, reordering and jitter of the received packets.
+    accounting: UdpAccounting,
 
     /// The server side result.
     result: Option<ServerResult>,
@@ -127,11 +126,11 @@ pub struct WireResult {
     pub data_len: u32, // 00 02 0a 8a - total data
     pub delta_s: u32, // 00 00 00 01 - delta t (s)
     pub delta_ms: u32, // 00 00 4f ad - delta t (ms)
-    pub e: u32, // 00 00 00 00
-    pub f: u32, // 00 00 00 00
+    pub e: u32, // 00 00 00 00 - lost packets
+    pub f: u32, // 00 00 00 00 - out-of-order packets
     pub packet_count: u32, // 00 00 00 5b - total packets
-    pub h: u32, // 00 00 00 00
-    pub i: u32, // 00 00 00 00
+    pub h: u32, // 00 00 00 00 - jitter (s)
+    pub i: u32, // 00 00 00 00 - jitter (us)
     pub j: u32, // 00 00 00 09
 }
 
@@ -143,6 +142,8 @@ pub(crate) struct Result {
     pub delta_ms: u32,
     pub packet_count: u32,
     pub total_count: u32,
+    pub out_of_order: u32,
+    pub jitter: Duration,
 }
 
 /// A locally created result, **not** sent by the remote.
@@ -165,6 +166,8 @@ pub(crate) struct ServerResult {
     pub packet_count: u32,
     pub received_bytes: u64,
     pub total_count: u32,
+    pub out_of_order: u32,
+    pub jitter: Duration,
     pub duration: Duration,
 }
 
@@ -256,6 +259,7 @@ impl Connection {
             dst_addr: config.host.into(),
             dst_port: config.port,
             payload: config.buffer_bytes,
+            dscp: 0,
         }
     }
 
@@ -285,10 +289,10 @@ impl Connection {
         crate::pattern::init(packet, 0);
 
         let secs = time.secs() as u32;
-        let millis = time.millis() as u32;
+        let micros = time.millis() as u32 * 1000;
         packet[0..4].copy_from_slice(&count.to_be_bytes());
         packet[4..8].copy_from_slice(&secs.to_be_bytes());
-        packet[8..12].copy_from_slice(&millis.to_be_bytes());
+        packet[8..12].copy_from_slice(&micros.to_be_bytes());
         // For some reason, these bytes are always zeroed.
         packet[16..20].copy_from_slice(&[0, 0, 0, 0]);
 
@@ -331,11 +335,11 @@ impl ServerConnection {
                 dst_addr: Default::default(),
                 dst_port: 0,
                 payload: 20 + mem::size_of::<WireResult>(),
+                dscp: 0,
             },
             packet_size: 0,
             received_bytes: 0,
-            max_packet_id: 0,
-            received_packets: 0,
+            accounting: UdpAccounting::new(0),
             result: None,
             begin_ts: Instant::from_millis(0),
             result_sent: false,
@@ -346,8 +350,14 @@ impl ServerConnection {
         // We prepared this packet, so assert is correct.
         assert_eq!(payload.len(), 20 + mem::size_of::<WireResult>());
 
+        let accounting = &self.accounting;
+        let jitter = accounting.jitter();
         let be_result = WireResult {
-            packet_count: u32::to_be(self.received_packets),
+            e: u32::to_be(saturate(accounting.lost())),
+            f: u32::to_be(saturate(accounting.out_of_order())),
+            packet_count: u32::to_be(saturate(accounting.received())),
+            h: u32::to_be(saturate(jitter.as_secs())),
+            i: u32::to_be(jitter.subsec_micros()),
             .. WireResult::default()
         };
 
@@ -361,45 +371,60 @@ impl ServerConnection {
     ///
     /// Panics if the validity invariant of length has not been checked prior.
     fn register(&mut self, payload: &[u8], time: Instant) {
-        use core::convert::TryFrom;
         assert!(payload.len() >= 20);
         self.received_bytes = self.received_bytes.saturating_add(payload.len());
 
-        let id_bytes = <[u8;4]>::try_from(&payload[0..4]).unwrap();
-        let id = u32::from_be_bytes(id_bytes) & 0x7FFF_FFFF;
+        let field = |idx: usize| {
+            let bytes = <[u8; 4]>::try_from(&payload[4*idx..][..4]).unwrap();
+            u32::from_be_bytes(bytes)
+        };
+
+        let id = field(0) & 0x7FFF_FFFF;
         let last = payload[0] & 0x80 != 0;
+        let sent = Duration::from_secs(field(1).into())
+            + Duration::from_micros(field(2).into());
 
         // HACKY: we don't check that all packets but the last have maximum length but we just
         // assume that this setting was supplied and traffic shaping is not done by reducing
         // datagram lengths but by delayed packets.
         self.packet_size = self.packet_size.max(payload.len());
-        self.max_packet_id = self.max_packet_id.max(id);
-        self.received_packets += 1;
+        self.accounting.register(id.into(), sent, time);
 
         if last {
+            let accounting = &self.accounting;
             self.result = Some(ServerResult {
                 packet_size: u32::try_from(self.packet_size)
                     .unwrap_or_else(|_| u32::max_value()),
-                packet_count: self.received_packets,
-                total_count: self.max_packet_id + 1,
+                packet_count: saturate(accounting.received()),
+                total_count: saturate(accounting.total()),
                 received_bytes: u64::try_from(self.received_bytes)
                     .unwrap_or_else(|_| u64::max_value()),
+                out_of_order: saturate(accounting.out_of_order()),
+                jitter: accounting.jitter(),
                 duration: time - self.begin_ts,
             });
         }
     }
 
     fn error_shutdown(&mut self) {
+        let accounting = &self.accounting;
         self.result = Some(ServerResult {
             packet_size: 0,
-            packet_count: self.received_packets,
-            total_count: self.max_packet_id + 1,
+            packet_count: saturate(accounting.received()),
+            total_count: saturate(accounting.total()),
             received_bytes: 0,
+            out_of_order: saturate(accounting.out_of_order()),
+            jitter: accounting.jitter(),
             duration: Duration::from_millis(0),
         })
     }
 }
 
+/// Convert a counter to its 32-bit wire representation.
+fn saturate(count: u64) -> u32 {
+    u32::try_from(count).unwrap_or(u32::MAX)
+}
+
 impl SendRate {
     /// Called after a packet has been sent.
     fn update_sent(&mut self, sent: usize, now: Instant) {
@@ -504,6 +529,20 @@ where
     fn result(&self) -> Option<super::Score> {
         self.connection.result.clone().map(|result| result.into())
     }
+
+    fn progress(&self) -> Option<super::Progress> {
+        let connection = &self.connection;
+        if connection.sent_packets == 0 {
+            return None;
+        }
+
+        let packets = u64::from(connection.sent_packets);
+        Some(super::Progress {
+            bytes: packets * connection.packet_size as u64,
+            packets,
+            .. super::Progress::default()
+        })
+    }
 }
 
 impl<Nic> super::Client<Nic> for IperfTcp
@@ -514,6 +553,20 @@ where
     fn result(&self) -> Option<super::Score> {
         self.result.map(|result| result.into())
     }
+
+    fn progress(&self) -> Option<super::Progress> {
+        let key = self.client.connection_key()?;
+        let info = self.tcp.get(key)?.info();
+        let statistics = self.tcp.get(key)?.statistics();
+        Some(super::Progress {
+            bytes: self.client.send().acked as u64,
+            packets: statistics.segments_sent,
+            retransmits: Some(statistics.retransmits),
+            congestion_window: Some(info.congestion_window),
+            rtt: info.smoothed_rtt,
+            .. super::Progress::default()
+        })
+    }
 }
 
 impl<Nic> super::Client<Nic> for Server
@@ -528,6 +581,24 @@ where
 
         self.connection.result.clone().map(|result| result.into())
     }
+
+    fn progress(&self) -> Option<super::Progress> {
+        let connection = &self.connection;
+        // No client yet.
+        if connection.send_init.dst_port == 0 {
+            return None;
+        }
+
+        let accounting = &connection.accounting;
+        Some(super::Progress {
+            bytes: connection.received_bytes as u64,
+            packets: accounting.received(),
+            lost: Some(accounting.lost()),
+            out_of_order: Some(accounting.out_of_order()),
+            jitter: Some(accounting.jitter()),
+            .. super::Progress::default()
+        })
+    }
 }
 
 impl tcp::SendBuf for PatternBuffer {
@@ -535,6 +606,7 @@ impl tcp::SendBuf for PatternBuffer {
         tcp::AvailableBytes {
             total: self.len - self.acked,
             fin: true,
+            push: false,
         }
     }
 
@@ -634,6 +706,9 @@ impl<P: PayloadMut> udp::Recv<P> for Connection {
             delta_ms: wire_result.delta_ms,
             packet_count: wire_result.packet_count,
             total_count: self.sent_packets,
+            out_of_order: wire_result.f,
+            jitter: Duration::from_secs(wire_result.h.into())
+                + Duration::from_micros(wire_result.i.into()),
         });
     }
 }
@@ -10,14 +10,22 @@
 //! and it ends the transfer (which happens in a separate channel, i.e. UDP here) by sending a
 //! state change message on the control channel. The server the invokes the exchange of results and
 //! the client acknowledges when it has displayed them and terminates the connection.
+//!
+//! The [`Server`] implements the other side of the protocol. It listens for the control connection
+//! of a client and then accepts the data streams requested in the parameter exchange, either a
+//! number of additional tcp connections or a single udp flow to the same port. Two ethox
+//! instances or an ethox instance and a stock iperf3 can be benchmarked against each other.
+//!
+//! [`Server`]: struct.Server.html
 use core::fmt;
 use core::convert::TryFrom;
 
-use ethox::layer::{ip, tcp, udp};
+use ethox::layer::{ip, tcp, udp, Error};
 use ethox::managed::{List, Map, SlotMap};
-use ethox::time::Instant;
-use ethox::wire::{IpProtocol, PayloadMut, TcpSeqNumber};
-use super::config::Client;
+use ethox::time::{Duration, Instant};
+use ethox::wire::{IpAddress, Ipv4Address, IpProtocol, PayloadMut, TcpSeqNumber};
+use super::accounting::UdpAccounting;
+use super::config::{Client, Server as ServerConfig};
 
 pub struct Iperf3 {
     config: Config,
@@ -85,6 +93,86 @@ struct IperfRecv {
     into: tcp::io::RecvInto<Vec<u8>>,
 }
 
+/// An iperf3 server.
+///
+/// Accepts the control connection of a single client and serves one test with either tcp or udp
+/// data streams, as chosen by the client. Only the default direction is supported, where the
+/// client sends and the server receives. Must be restarted for the next test.
+pub struct Server {
+    state: State,
+    /// The address and port of the listening socket.
+    local: IpAddress,
+    port: u16,
+    params: Params,
+    tcp: tcp::Endpoint<'static>,
+    udp: udp::Endpoint<'static>,
+    session: Session,
+    datagrams: Datagrams,
+    /// Time when the client was told to start the test.
+    start: Option<Instant>,
+    /// Time when the client ended the test.
+    end: Option<Instant>,
+    result: Option<ServerResult>,
+}
+
+/// The test parameters requested by the client.
+#[derive(Clone, Copy, Debug, Default)]
+struct Params {
+    udp: bool,
+    reverse: bool,
+    parallel: usize,
+    counters_64bit: bool,
+}
+
+/// The tcp connections of the client being served.
+struct Session {
+    /// The slot waiting for the next connection.
+    listener: Option<tcp::SlotKey>,
+    control: Option<tcp::stream::Stream<IperfRecv, IperfSend>>,
+    /// Whether new connections are data streams.
+    accept_streams: bool,
+    streams: Vec<tcp::stream::Stream<StreamCounter, tcp::io::SendFrom<Vec<u8>>>>,
+    /// A connection that was accepted but is not wanted.
+    rejected: Option<tcp::SlotKey>,
+}
+
+/// Counts the data received on a tcp data stream.
+#[derive(Default)]
+struct StreamCounter {
+    sink: tcp::io::Sink,
+    /// The sequence number of the next expected byte.
+    next: Option<TcpSeqNumber>,
+    /// Received bytes, including the cookie.
+    bytes: u64,
+    segments: u32,
+}
+
+/// The udp data stream of the client being served.
+struct Datagrams {
+    /// Init for sending the connect reply.
+    send_init: udp::Init,
+    /// Whether the client announced its stream.
+    connected: bool,
+    /// True while the connect reply still needs to be sent.
+    reply: bool,
+    counters_64bit: bool,
+    bytes: u64,
+    /// Loss, reordering and jitter of the received datagrams.
+    accounting: UdpAccounting,
+}
+
+/// The statistics of one test.
+#[derive(Clone, Debug)]
+pub(crate) struct ServerResult {
+    pub received_bytes: u64,
+    pub packet_count: u32,
+    pub total_count: u32,
+    pub out_of_order: u32,
+    /// The jitter of udp tests.
+    pub jitter: Option<Duration>,
+    pub duration: Duration,
+}
+
 impl Iperf3 {
     /// Create a new iperf3 client.
     pub fn new(config: &Client) -> Self {
@@ -177,10 +265,10 @@ impl Iperf3 {
     /// Fill the necessary part of the packet.
     fn fill(&mut self, packet: &mut [u8], time: Instant, count: u32) {
         let secs = time.secs() as u32;
-        let millis = time.millis() as u32;
+        let micros = time.millis() as u32 * 1000;
         assert!(packet.len() >= 12);
         packet[0..4].copy_from_slice(&secs.to_be_bytes());
-        packet[4..8].copy_from_slice(&millis.to_be_bytes());
+        packet[4..8].copy_from_slice(&micros.to_be_bytes());
         packet[8..12].copy_from_slice(&count.to_be_bytes());
     }
 
@@ -275,6 +363,391 @@ impl Iperf3 {
     }
 }
 
+impl Server {
+    /// The length of the test cookie, including its terminating nul byte.
+    const COOKIE_LEN: usize = 37;
+    /// Sent by the client to announce a udp stream.
+    const UDP_CONNECT_MSG: u32 = 0x3637_3839;
+    /// Answer to the announcement of a udp stream.
+    const UDP_CONNECT_REPLY: u32 = 0x3938_3736;
+    /// The maximum number of parallel tcp streams.
+    const MAX_STREAMS: usize = 8;
+
+    /// Create a new iperf3 server.
+    ///
+    /// It listens on the address in the configuration, or the default host address if there is
+    /// none.
+    pub fn new(config: &ServerConfig, default_host: Ipv4Address) -> Self {
+        let local: IpAddress = config.host
+            .unwrap_or(default_host)
+            .into();
+        let mut tcp = Self::generate_tcp();
+        let listener = tcp.listen(local, config.port);
+
+        Server {
+            state: State::None,
+            local,
+            port: config.port,
+            params: Params::default(),
+            tcp,
+            udp: udp::Endpoint::new(vec![config.port]),
+            session: Session {
+                listener,
+                control: None,
+                accept_streams: false,
+                streams: Vec::new(),
+                rejected: None,
+            },
+            datagrams: Datagrams::new(local, config.port),
+            start: None,
+            end: None,
+            result: None,
+        }
+    }
+
+    fn generate_tcp() -> tcp::Endpoint<'static> {
+        // The control connection, the data streams and one listening slot.
+        let slots = Self::MAX_STREAMS + 2;
+        tcp::Endpoint::new(
+            Map::Pairs(List::new(vec![Default::default(); slots].into())),
+            SlotMap::new(
+                vec![Default::default(); slots].into(),
+                vec![Default::default(); slots].into()),
+            tcp::IsnGenerator::from_std_hash())
+    }
+
+    /// Open a new listening slot after the previous one accepted a connection.
+    fn accept(&mut self) {
+        if let Some(rejected) = self.session.rejected.take() {
+            self.tcp.remove(rejected);
+        }
+
+        if self.session.listener.is_none() {
+            self.session.listener = self.tcp.listen(self.local, self.port);
+        }
+    }
+
+    /// Advance the protocol as far as the received control messages permit.
+    fn progress(&mut self, time: Instant) {
+        while self.result.is_none() && self.step(time) { }
+    }
+
+    /// Handle the next control message, returns if any progress was made.
+    fn step(&mut self, time: Instant) -> bool {
+        match &self.session.control {
+            Some(control) if control.is_closed() => {
+                // The client went away.
+                self.finish(time);
+                return false;
+            },
+            Some(_) => (),
+            None => return false,
+        }
+
+        match self.state {
+            State::None => {
+                let control = self.control();
+                if control.recv().into.received().len() < Self::COOKIE_LEN {
+                    return false;
+                }
+
+                control.recv_mut().bump(Self::COOKIE_LEN as u32);
+                control.send_mut().send_state(State::ParamExchange);
+                self.state = State::ParamExchange;
+            },
+            State::ParamExchange => {
+                let control = self.control();
+                let params = match control.recv().get_json() {
+                    Some(json) => Params::parse(json),
+                    None => return false,
+                };
+                control.recv_mut().bump_json();
+
+                if params.reverse || params.parallel > Self::MAX_STREAMS {
+                    println!("Unsupported test parameters {:?}", params);
+                    control.send_mut().send_state(State::AccessDenied);
+                    self.state = State::AccessDenied;
+                    self.finish(time);
+                    return false;
+                }
+
+                control.send_mut().send_state(State::CreateStreams);
+                self.params = params;
+                self.session.accept_streams = !params.udp;
+                self.datagrams.counters_64bit = params.counters_64bit;
+                self.state = State::CreateStreams;
+            },
+            State::CreateStreams => {
+                let ready = if self.params.udp {
+                    self.datagrams.connected && !self.datagrams.reply
+                } else {
+                    self.session.streams.len() >= self.params.parallel
+                };
+
+                if !ready {
+                    return false;
+                }
+
+                self.session.accept_streams = false;
+                let send = self.control().send_mut();
+                send.send_state(State::TestStart);
+                send.send_state(State::TestRunning);
+                self.start = Some(time);
+                self.state = State::TestRunning;
+            },
+            State::TestRunning => match self.control().recv_mut().recv_state() {
+                Some(State::TestEnd) => {
+                    self.control().send_mut().send_state(State::ExchangeResults);
+                    self.end = Some(time);
+                    self.state = State::ExchangeResults;
+                },
+                Some(other) => return self.unexpected(other, time),
+                None => return false,
+            },
+            State::ExchangeResults => {
+                if self.control().recv().get_json().is_none() {
+                    return false;
+                }
+
+                // The results of the client are not interesting to us.
+                self.control().recv_mut().bump_json();
+                let report = self.report(time);
+                let send = self.control().send_mut();
+                send.send_json(report.as_bytes());
+                send.send_state(State::DisplayResults);
+                self.state = State::DisplayResults;
+            },
+            State::DisplayResults => match self.control().recv_mut().recv_state() {
+                Some(State::IperfDone) => {
+                    self.finish(time);
+                    return false;
+                },
+                Some(other) => return self.unexpected(other, time),
+                None => return false,
+            },
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// The control connection, which must exist.
+    fn control(&mut self) -> &mut tcp::stream::Stream<IperfRecv, IperfSend> {
+        self.session.control.as_mut().unwrap()
+    }
+
+    fn unexpected(&mut self, state: State, time: Instant) -> bool {
+        if state != State::ClientTerminate {
+            println!("Unexpected state transition from {:?} to {:?}", self.state, state);
+        }
+
+        self.finish(time);
+        false
+    }
+
+    /// Produce the result of the test, it ends the session.
+    fn finish(&mut self, time: Instant) {
+        let start = self.start.unwrap_or(time);
+        let end = self.end.unwrap_or(time);
+
+        let result = if self.params.udp {
+            let accounting = &self.datagrams.accounting;
+            let saturate = |count: u64| u32::try_from(count).unwrap_or(u32::MAX);
+            ServerResult {
+                received_bytes: self.datagrams.bytes,
+                packet_count: saturate(accounting.received()),
+                total_count: saturate(accounting.total()),
+                out_of_order: saturate(accounting.out_of_order()),
+                jitter: Some(accounting.jitter()),
+                duration: end - start,
+            }
+        } else {
+            let streams = self.session.streams.iter().map(|stream| stream.recv());
+            let segments = streams.clone()
+                .fold(0u32, |total, counter| total.saturating_add(counter.segments));
+            ServerResult {
+                received_bytes: streams.map(StreamCounter::data_bytes).sum(),
+                packet_count: segments,
+                total_count: segments,
+                out_of_order: 0,
+                jitter: None,
+                duration: end - start,
+            }
+        };
+
+        self.state = State::IperfDone;
+        self.result = Some(result);
+    }
+
+    /// The json results of the server for the client.
+    fn report(&self, time: Instant) -> String {
+        let start = self.start.unwrap_or(time);
+        let end_time = (self.end.unwrap_or(time) - start).as_secs_f64();
+
+        let streams: Vec<String> = if self.params.udp {
+            let accounting = &self.datagrams.accounting;
+            vec![Self::stream_report(1, self.datagrams.bytes, end_time, Some(accounting))]
+        } else {
+            self.session.streams.iter()
+                .map(|stream| stream.recv())
+                .enumerate()
+                // Stream ids skip 2, as in the original implementation.
+                .map(|(idx, counter)| Self::stream_report(
+                    if idx == 0 { 1 } else { idx + 2 },
+                    counter.data_bytes(),
+                    end_time,
+                    None))
+                .collect()
+        };
+
+        format!("{{\
+            \"cpu_util_total\":0,\
+            \"cpu_util_user\":0,\
+            \"cpu_util_system\":0,\
+            \"sender_has_retransmits\":-1,\
+            \"streams\":[{}]\
+        }}", streams.join(","))
+    }
+
+    fn stream_report(id: usize, bytes: u64, end_time: f64, udp: Option<&UdpAccounting>)
+        -> String
+    {
+        let (jitter, errors, packets, out_of_order) = match udp {
+            Some(accounting) => (
+                accounting.jitter().as_secs_f64(),
+                accounting.lost(),
+                accounting.total(),
+                accounting.out_of_order()),
+            None => (0.0, 0, 0, 0),
+        };
+
+        format!("{{\
+            \"id\":{},\
+            \"bytes\":{},\
+            \"retransmits\":-1,\
+            \"jitter\":{},\
+            \"errors\":{},\
+            \"omitted_errors\":0,\
+            \"packets\":{},\
+            \"omitted_packets\":0,\
+            \"out_of_order\":{},\
+            \"start_time\":0,\
+            \"end_time\":{}\
+        }}", id, bytes, jitter, errors, packets, out_of_order, end_time)
+    }
+
+    /// Check if all control messages have been delivered.
+    fn flushed(&self) -> bool {
+        match &self.session.control {
+            Some(control) if !control.is_closed() => {
+                let from = &control.send().from;
+                from.completed_bytes() == from.get_ref().len()
+            },
+            _ => true,
+        }
+    }
+}
+
+impl Params {
+    /// Extract the supported parameters from the json of the client.
+    ///
+    /// The client does not format its json with whitespace, so a simple search is enough.
+    fn parse(json: &[u8]) -> Self {
+        let json = String::from_utf8_lossy(json);
+        let value = |key: &str| {
+            let key = format!("\"{}\":", key);
+            json.find(&key).map(|idx| {
+                let rest = &json[idx + key.len()..];
+                let end = rest.find([',', '}']).unwrap_or(rest.len());
+                rest[..end].to_owned()
+            })
+        };
+
+        let flag = |key: &str| value(key)
+            .is_some_and(|value| value == "true" || value == "1");
+
+        Params {
+            udp: flag("udp"),
+            reverse: flag("reverse") || flag("bidirectional"),
+            parallel: value("parallel")
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(1),
+            counters_64bit: flag("udp_counters_64bit"),
+        }
+    }
+}
+
+impl Session {
+    /// Decide about a connection accepted on the listening slot.
+    fn accepted(&mut self, key: tcp::SlotKey) {
+        self.listener = None;
+
+        if self.control.is_none() {
+            self.control = Some(tcp::stream::Stream::new(key, IperfRecv::new(), IperfSend::new()));
+        } else if self.accept_streams {
+            let send = tcp::io::SendFrom::new(Vec::new());
+            self.streams.push(tcp::stream::Stream::new(key, StreamCounter::default(), send));
+        } else {
+            self.rejected = Some(key);
+        }
+    }
+}
+
+impl StreamCounter {
+    /// The received data, not counting the cookie.
+    fn data_bytes(&self) -> u64 {
+        self.bytes.saturating_sub(Server::COOKIE_LEN as u64)
+    }
+}
+
+impl Datagrams {
+    fn new(local: IpAddress, port: u16) -> Self {
+        Datagrams {
+            send_init: udp::Init {
+                source: ip::Source::Exact(local),
+                src_port: port,
+                dst_addr: Default::default(),
+                dst_port: 0,
+                payload: 4,
+                dscp: 0,
+            },
+            connected: false,
+            reply: false,
+            counters_64bit: false,
+            bytes: 0,
+            // The client counts from one.
+            accounting: UdpAccounting::new(1),
+        }
+    }
+
+    /// Register a test datagram.
+    ///
+    /// These start with the send time in seconds and microseconds, followed by the packet count.
+    fn register(&mut self, payload: &[u8], time: Instant) {
+        let field = |idx: usize| {
+            let bytes = <[u8; 4]>::try_from(&payload[4*idx..][..4]).unwrap();
+            u32::from_be_bytes(bytes)
+        };
+
+        let count = if self.counters_64bit {
+            payload.get(8..16)
+                .map(|count| u64::from_be_bytes(<[u8; 8]>::try_from(count).unwrap()))
+        } else {
+            payload.get(8..12)
+                .map(|count| u32::from_be_bytes(<[u8; 4]>::try_from(count).unwrap()).into())
+        };
+
+        let count = match count {
+            Some(count) => count,
+            None => return,
+        };
+
+        let sent = Duration::from_secs(field(0).into()) + Duration::from_micros(field(1).into());
+        self.bytes += payload.len() as u64;
+        self.accounting.register(count, sent, time);
+    }
+}
+
 impl Config {
     pub fn new(from: &Client) -> Self {
         let Client {
@@ -354,6 +827,7 @@ impl IperfSend {
         let len = u32::try_from(data.len())
             .expect("json data too long");
         self.from.get_mut().extend_from_slice(&len.to_be_bytes());
+        self.from.get_mut().extend_from_slice(data);
     }
 }
 
@@ -379,6 +853,176 @@ impl<P: PayloadMut> ip::Send<P> for Iperf3 {
     }
 }
 
+impl<P: PayloadMut> ip::Recv<P> for Server {
+    fn receive(&mut self, packet: ip::InPacket<P>) {
+        if self.result.is_some() {
+            return;
+        }
+
+        let time = packet.handle.info().timestamp();
+        match packet.packet.repr().protocol() {
+            IpProtocol::Tcp => {
+                self.tcp.recv(&mut self.session).receive(packet);
+                self.accept();
+            },
+            IpProtocol::Udp if self.params.udp => {
+                self.udp.recv(&mut self.datagrams).receive(packet);
+            },
+            _ => (),
+        }
+
+        self.progress(time);
+    }
+}
+
+impl<P: PayloadMut> ip::Send<P> for Server {
+    fn send(&mut self, packet: ip::RawPacket<P>) {
+        if self.datagrams.reply {
+            self.udp.send(&mut self.datagrams).send(packet)
+        } else {
+            self.tcp.send(&mut self.session).send(packet)
+        }
+    }
+}
+
+impl<P: PayloadMut> tcp::Recv<P> for &'_ mut Session {
+    fn receive(&mut self, packet: tcp::InPacket<P>) {
+        let key = match packet.key() {
+            Some(key) => key,
+            None => return,
+        };
+
+        if Some(key) == self.listener {
+            match packet {
+                // Established, the slot is now the connection.
+                tcp::InPacket::Open(_) => self.accepted(key),
+                _ => return,
+            }
+        }
+
+        if let Some(control) = &mut self.control {
+            if control.connection_key() == Some(key) {
+                return tcp::Recv::receive(&mut &mut *control, packet);
+            }
+        }
+
+        let stream = self.streams.iter_mut()
+            .find(|stream| stream.connection_key() == Some(key));
+        if let Some(stream) = stream {
+            tcp::Recv::receive(&mut &mut *stream, packet)
+        }
+    }
+}
+
+impl<P: PayloadMut> tcp::Send<P> for &'_ mut Session {
+    fn send(&mut self, packet: tcp::RawPacket<P>) {
+        // Only the control connection has data to send, the streams answer incoming data.
+        if let Some(control) = &mut self.control {
+            tcp::Send::send(&mut &mut *control, packet)
+        }
+    }
+}
+
+impl<P: PayloadMut> udp::Recv<P> for Datagrams {
+    fn receive(&mut self, packet: udp::Packet<P>) {
+        let udp::Packet { packet, handle, } = packet;
+
+        let ip_hdr = packet.get_ref().repr();
+        let udp_hdr = packet.repr();
+        let payload = packet.payload_slice();
+
+        if !self.connected {
+            let msg = Server::UDP_CONNECT_MSG;
+            // The client writes the message in its own byte order.
+            if payload == msg.to_be_bytes() || payload == msg.to_le_bytes() {
+                self.send_init.dst_addr = ip_hdr.src_addr();
+                self.send_init.dst_port = udp_hdr.src_port;
+                self.connected = true;
+                self.reply = true;
+            }
+        } else if self.send_init.dst_addr == ip_hdr.src_addr()
+            && self.send_init.dst_port == udp_hdr.src_port
+        {
+            self.register(payload, handle.info().timestamp());
+        }
+    }
+}
+
+impl<P: PayloadMut> udp::Send<P> for Datagrams {
+    fn send(&mut self, packet: udp::RawPacket<P>) {
+        let mut packet = match packet.prepare(self.send_init) {
+            Ok(packet) => packet,
+            // May simply require an arp lookup.
+            Err(Error::Unreachable) => return,
+            Err(_) => return self.reply = false,
+        };
+
+        // Like the original implementation, in host byte order.
+        let reply = Server::UDP_CONNECT_REPLY.to_ne_bytes();
+        packet.packet.payload_mut_slice().copy_from_slice(&reply);
+
+        // The client repeats its message if the reply was lost.
+        let _ = packet.send();
+        self.reply = false;
+    }
+}
+
+impl tcp::RecvBuf for StreamCounter {
+    fn receive(&mut self, buf: &[u8], segment: tcp::ReceivedSegment) {
+        let before = self.next.unwrap_or(segment.begin);
+        self.sink.receive(buf, segment);
+        let after = self.sink.ack();
+        self.bytes += (after - before) as u64;
+        self.next = Some(after);
+        self.segments += 1;
+    }
+
+    fn ack(&mut self) -> TcpSeqNumber {
+        self.sink.ack()
+    }
+
+    fn window(&self) -> usize {
+        self.sink.window()
+    }
+}
+
+impl<Nic> super::Client<Nic> for Server
+where
+    Nic: ethox::nic::Device,
+    Nic::Payload: PayloadMut + Sized,
+{
+    fn result(&self) -> Option<super::Score> {
+        if !self.flushed() {
+            return None;
+        }
+
+        self.result.clone().map(|result| result.into())
+    }
+
+    fn progress(&self) -> Option<super::Progress> {
+        self.start?;
+
+        Some(if self.params.udp {
+            let accounting = &self.datagrams.accounting;
+            super::Progress {
+                bytes: self.datagrams.bytes,
+                packets: accounting.received(),
+                lost: Some(accounting.lost()),
+                out_of_order: Some(accounting.out_of_order()),
+                jitter: Some(accounting.jitter()),
+                .. super::Progress::default()
+            }
+        } else {
+            let streams = self.session.streams.iter().map(|stream| stream.recv());
+            super::Progress {
+                bytes: streams.clone().map(StreamCounter::data_bytes).sum(),
+                packets: streams.map(|counter| u64::from(counter.segments)).sum(),
+                .. super::Progress::default()
+            }
+        })
+    }
+}
+
 impl<P: PayloadMut> udp::Recv<P> for Handshake {
     fn receive(&mut self, packet: udp::Packet<P>) {
         if packet.packet.repr().dst_port == 0 {
@@ -2,14 +2,18 @@
 #[cfg(feature = "bench")]
 extern crate test;
 
+mod accounting;
 mod pattern;
 mod score;
 
+pub mod report;
+
 pub mod config;
 #[allow(unused)]
 pub mod iperf3;
 pub mod iperf2;
 pub use score::Score;
+pub use report::{Progress, Reporter};
 
 pub trait Client<Nic>:
     ethox::layer::ip::Recv<Nic::Payload> +
@@ -19,6 +23,11 @@ where
     Nic::Payload: Sized,
 { 
     fn result(&self) -> Option<Score>;
+
+    /// The cumulative statistics of the running test, for interval reports.
+    fn progress(&self) -> Option<Progress> {
+        None
+    }
 }
 
 pub fn client<Nic>(
@@ -27,6 +36,7 @@ pub fn client<Nic>(
     eth: &mut ethox::layer::eth::Endpoint,
     ip: &mut ethox::layer::ip::Endpoint,
     mut client: impl Client<Nic>,
+    report: &mut Reporter,
 ) -> Score
 where
     Nic: ethox::nic::Device,
@@ -36,6 +46,7 @@ where
     loop {
         let _ = nic.rx(burst, eth.recv(ip.recv(&mut client)));
         let _ = nic.tx(burst, eth.send(ip.send(&mut client)));
+        report.update(client.progress());
 
         if let Some(result) = client.result() {
             return result;
@@ -50,6 +61,7 @@ pub fn server<Nic>(
     eth: &mut ethox::layer::eth::Endpoint,
     ip: &mut ethox::layer::ip::Endpoint,
     mut client: impl Client<Nic>,
+    report: &mut Reporter,
 ) -> Score
 where
     Nic: ethox::nic::Device,
@@ -62,6 +74,7 @@ where
     loop {
         let _ = nic.rx(burst, eth.recv(ip.recv(&mut client)));
         let _ = nic.tx(burst, eth.send(ip.send(&mut client)));
+        report.update(client.progress());
 
         if let Some(result) = client.result() {
             return result;
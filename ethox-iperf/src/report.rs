@@ -0,0 +1,287 @@
+//! Periodic interval reports and the json output.
+//!
+//! The test loops sample the cumulative [`Progress`] of a test and the [`Reporter`] turns the
+//! difference between samples into interval statistics. These are printed as they complete or,
+//! with json output, collected into a final report similar to the `--json` output of iperf3.
+//!
+//! [`Progress`]: struct.Progress.html
+//! [`Reporter`]: struct.Reporter.html
+use core::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ethox::time::{Clock, Duration, Instant, StdClock};
+
+use crate::config;
+use crate::Score;
+
+/// Cumulative counters of a running test.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Progress {
+    /// The transferred payload bytes.
+    pub bytes: u64,
+    /// The transferred packets or segments.
+    pub packets: u64,
+    /// Retransmitted segments, only known to tcp senders.
+    pub retransmits: Option<u64>,
+    /// Packets known to be lost, only known to udp receivers.
+    pub lost: Option<u64>,
+    /// Packets received after a later one, only known to udp receivers.
+    pub out_of_order: Option<u64>,
+    /// The current jitter estimate of udp receivers.
+    pub jitter: Option<Duration>,
+    /// The congestion window of tcp senders, in bytes.
+    pub congestion_window: Option<u32>,
+    /// The smoothed round trip time of tcp senders.
+    pub rtt: Option<Duration>,
+}
+
+/// Collects the interval statistics of a test and formats the final report.
+pub struct Reporter {
+    /// The length of each interval, if they are reported.
+    interval: Option<Duration>,
+    json: bool,
+    /// The time of the first sample.
+    start: Option<Instant>,
+    /// Time and counters at the start of the current interval.
+    last: (Instant, Progress),
+    /// The most recent sample.
+    latest: Progress,
+    /// Completed intervals, only kept for the json output.
+    intervals: Vec<Interval>,
+}
+
+/// The statistics of one reporting interval.
+#[derive(Clone, Copy, Debug)]
+struct Interval {
+    /// Begin relative to the start of the test.
+    begin: Duration,
+    /// End relative to the start of the test.
+    end: Duration,
+    bytes: u64,
+    packets: u64,
+    retransmits: Option<u64>,
+    lost: Option<u64>,
+    out_of_order: Option<u64>,
+    jitter: Option<Duration>,
+    congestion_window: Option<u32>,
+    rtt: Option<Duration>,
+}
+
+/// The json members common to intervals and the summary.
+struct JsonSum<'a>(&'a Interval);
+
+impl Reporter {
+    /// Create a reporter from the command line configuration.
+    pub fn new(config: &config::Report) -> Self {
+        let interval = if config.interval > 0.0 {
+            Some(Duration::from_secs_f32(config.interval))
+        } else {
+            None
+        };
+
+        Reporter {
+            interval,
+            json: config.json,
+            start: None,
+            last: (Instant::from_millis(0), Progress::default()),
+            latest: Progress::default(),
+            intervals: Vec::new(),
+        }
+    }
+
+    /// Whether the report is printed as json.
+    pub fn is_json(&self) -> bool {
+        self.json
+    }
+
+    /// Sample the progress of the test.
+    ///
+    /// Tests report no progress before they have started.
+    pub(crate) fn update(&mut self, progress: Option<Progress>) {
+        let progress = match progress {
+            Some(progress) => progress,
+            None => return,
+        };
+
+        let now = StdClock.now();
+        if self.start.is_none() {
+            self.start = Some(now);
+            self.last = (now, Progress::default());
+        }
+
+        self.latest = progress;
+        match self.interval {
+            Some(interval) if now - self.last.0 >= interval => self.close_interval(now),
+            _ => (),
+        }
+    }
+
+    /// Format the final report of the test.
+    pub fn summary(&mut self, score: &Score) -> String {
+        // Report the final, partial interval as well.
+        if self.interval.is_some() && self.latest != self.last.1 {
+            self.close_interval(StdClock.now());
+        }
+
+        if !self.json {
+            return score.to_string();
+        }
+
+        let lost = u64::from(score.total_count.saturating_sub(score.packet_count));
+        let end = Interval {
+            begin: Duration::from_millis(0),
+            end: score.time,
+            bytes: score.data_len,
+            packets: score.total_count.into(),
+            retransmits: self.latest.retransmits,
+            lost: Some(lost),
+            out_of_order: Some(score.out_of_order.into()),
+            jitter: score.jitter.or(self.latest.jitter),
+            congestion_window: None,
+            rtt: self.latest.rtt,
+        };
+
+        let timesecs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or(0);
+        let intervals: Vec<String> = self.intervals.iter()
+            .map(|interval| format!("{{\
+                \"streams\":[{{{sum}}}],\
+                \"sum\":{{{sum}}}\
+            }}", sum=JsonSum(interval)))
+            .collect();
+
+        format!("{{\
+            \"start\":{{\"timestamp\":{{\"timesecs\":{}}}}},\
+            \"intervals\":[{}],\
+            \"end\":{{\"sum\":{{{}}}}}\
+        }}", timesecs, intervals.join(","), JsonSum(&end))
+    }
+
+    fn close_interval(&mut self, now: Instant) {
+        let start = self.start.unwrap_or(now);
+        let (last_time, last) = self.last;
+        let current = self.latest;
+
+        let interval = Interval {
+            begin: last_time - start,
+            end: now - start,
+            bytes: current.bytes.saturating_sub(last.bytes),
+            packets: current.packets.saturating_sub(last.packets),
+            retransmits: current.retransmits
+                .map(|count| count.saturating_sub(last.retransmits.unwrap_or(0))),
+            lost: current.lost
+                .map(|count| count.saturating_sub(last.lost.unwrap_or(0))),
+            out_of_order: current.out_of_order
+                .map(|count| count.saturating_sub(last.out_of_order.unwrap_or(0))),
+            jitter: current.jitter,
+            congestion_window: current.congestion_window,
+            rtt: current.rtt,
+        };
+
+        if self.json {
+            self.intervals.push(interval);
+        } else {
+            println!("{}", interval);
+        }
+
+        self.last = (now, current);
+    }
+}
+
+impl Interval {
+    fn seconds(&self) -> f64 {
+        (self.end - self.begin).as_secs_f64()
+    }
+
+    fn bits_per_second(&self) -> f64 {
+        match self.seconds() {
+            secs if secs > 0.0 => (self.bytes * 8) as f64 / secs,
+            _ => 0.0,
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{ts}] {begin:.2}-{end:.2} sec\t{total} KBytes\t{rate} Byte/sec",
+            ts=3,
+            begin=self.begin.as_secs_f32(),
+            end=self.end.as_secs_f32(),
+            total=self.bytes/1024,
+            rate=self.bits_per_second()/8.0)?;
+
+        if let Some(retransmits) = self.retransmits {
+            write!(f, "\t{} retr", retransmits)?;
+        }
+
+        if let Some(cwnd) = self.congestion_window {
+            write!(f, "\t{} KBytes cwnd", cwnd/1024)?;
+        }
+
+        if let Some(jitter) = self.jitter {
+            write!(f, "\t{} ms", jitter.as_secs_f32() * 1000.0)?;
+        }
+
+        if let Some(lost) = self.lost {
+            write!(f, "\t{}/\t{}", lost, self.packets)?;
+        }
+
+        if let Some(out_of_order) = self.out_of_order.filter(|&count| count > 0) {
+            write!(f, "\t{} out-of-order", out_of_order)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for JsonSum<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sum = self.0;
+        write!(f, "\
+            \"start\":{},\
+            \"end\":{},\
+            \"seconds\":{},\
+            \"bytes\":{},\
+            \"bits_per_second\":{},\
+            \"packets\":{},\
+            \"omitted\":false",
+            sum.begin.as_secs_f64(),
+            sum.end.as_secs_f64(),
+            sum.seconds(),
+            sum.bytes,
+            sum.bits_per_second(),
+            sum.packets)?;
+
+        if let Some(retransmits) = sum.retransmits {
+            write!(f, ",\"retransmits\":{}", retransmits)?;
+        }
+
+        if let Some(cwnd) = sum.congestion_window {
+            write!(f, ",\"snd_cwnd\":{}", cwnd)?;
+        }
+
+        if let Some(rtt) = sum.rtt {
+            write!(f, ",\"rtt\":{}", rtt.as_micros())?;
+        }
+
+        if let Some(jitter) = sum.jitter {
+            write!(f, ",\"jitter_ms\":{}", jitter.as_secs_f64() * 1000.0)?;
+        }
+
+        if let Some(lost) = sum.lost {
+            let percent = match sum.packets {
+                0 => 0.0,
+                packets => lost as f64 * 100.0 / packets as f64,
+            };
+            write!(f, ",\"lost_packets\":{},\"lost_percent\":{}", lost, percent)?;
+        }
+
+        if let Some(out_of_order) = sum.out_of_order {
+            write!(f, ",\"out_of_order\":{}", out_of_order)?;
+        }
+
+        Ok(())
+    }
+}
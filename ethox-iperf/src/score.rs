@@ -1,6 +1,6 @@
 use core::fmt;
 
-use crate::iperf2;
+use crate::{iperf2, iperf3};
 use ethox::time::Duration;
 
 /// The result of running the benchmark.
@@ -13,6 +13,10 @@ pub struct Score {
     pub(crate) packet_count: u32,
     /// The number of packets that were sent.
     pub(crate) total_count: u32,
+    /// Number of packets that arrived after a later one.
+    pub(crate) out_of_order: u32,
+    /// The jitter of udp tests.
+    pub(crate) jitter: Option<Duration>,
 }
 
 impl Score {
@@ -42,6 +46,8 @@ impl From<iperf2::Result> for Score {
                 u64::from(result.delta_ms)),
             packet_count: result.packet_count,
             total_count: result.total_count,
+            out_of_order: result.out_of_order,
+            jitter: Some(result.jitter),
         }
     }
 }
@@ -54,6 +60,8 @@ impl From<iperf2::TcpResult> for Score {
             time: result.duration,
             packet_count: result.packet_count,
             total_count: result.packet_count,
+            out_of_order: 0,
+            jitter: None,
         }
     }
 }
@@ -65,6 +73,21 @@ impl From<iperf2::ServerResult> for Score {
             time: result.duration,
             packet_count: result.packet_count,
             total_count: result.total_count,
+            out_of_order: result.out_of_order,
+            jitter: Some(result.jitter),
+        }
+    }
+}
+
+impl From<iperf3::ServerResult> for Score {
+    fn from(result: iperf3::ServerResult) -> Score {
+        Score {
+            data_len: result.received_bytes,
+            time: result.duration,
+            packet_count: result.packet_count,
+            total_count: result.total_count,
+            out_of_order: result.out_of_order,
+            jitter: result.jitter,
         }
     }
 }
@@ -77,17 +100,23 @@ impl fmt::Display for Score {
         // [  3]  0.0- 1.0 sec   131 KBytes  1.05 Mbits/sec   0.000 ms    0/   91 (0%)
         // ```
         write!(f,
-           "[{ts}] {begin}-{end} sec\t{total} KBytes\t{rate} Byte/sec\t{dt} ms\t\
+           "[{ts}] {begin}-{end} sec\t{total} KBytes\t{rate} Byte/sec\t{jitter:.3} ms\t\
             {loss}/\t{packets} ({loss_percent})",
            ts=3,
            // Pretend that start was at 0.0 but otherwise accurate.
            begin=0.0, end=self.time.as_secs_f32(),
            total=self.total_kb(),
            rate=self.effective_rate(),
-           dt=self.time.as_secs_f32() / 1000.0,
-           loss=self.total_count - self.packet_count,
+           jitter=self.jitter.unwrap_or_default().as_secs_f64() * 1000.0,
+           loss=self.total_count.saturating_sub(self.packet_count),
            packets=self.total_count,
            loss_percent=self.loss_rate()*100.0,
-        )
+        )?;
+
+        if self.out_of_order > 0 {
+            write!(f, "\n[{ts}] {} datagrams received out-of-order", self.out_of_order, ts=3)?;
+        }
+
+        Ok(())
     }
 }
@@ -0,0 +1,38 @@
+//! Throughput of the internet checksum variants.
+//!
+//! Compares the variant chosen for packets with the scalar one and each vectorized one the cpu
+//! supports, for a minimal frame, the usual Ethernet mtu, and a jumbo frame.
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use ethox::wire::checksum_variants;
+
+const LENGTHS: [usize; 3] = [64, 1500, 9000];
+
+fn variants(c: &mut Criterion) {
+    let mut group = c.benchmark_group("checksum");
+    for &len in LENGTHS.iter() {
+        let data = vec![0xa5; len];
+        group.throughput(Throughput::Bytes(len as u64));
+
+        group.bench_with_input(BenchmarkId::new("data", len), &data, |b, data| {
+            b.iter(|| checksum_variants::data(black_box(data)))
+        });
+        group.bench_with_input(BenchmarkId::new("data_u64", len), &data, |b, data| {
+            b.iter(|| checksum_variants::data_u64(black_box(data)))
+        });
+        if checksum_variants::data_sse2(&data).is_some() {
+            group.bench_with_input(BenchmarkId::new("data_sse2", len), &data, |b, data| {
+                b.iter(|| checksum_variants::data_sse2(black_box(data)))
+            });
+        }
+        if checksum_variants::data_avx2(&data).is_some() {
+            group.bench_with_input(BenchmarkId::new("data_avx2", len), &data, |b, data| {
+                b.iter(|| checksum_variants::data_avx2(black_box(data)))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, variants);
+criterion_main!(benches);
@@ -0,0 +1,174 @@
+//! Per-layer processing cost of the handler plumbing.
+//!
+//! Each benchmark receives a preloaded burst of identical udp packets from an in-memory `External`
+//! nic and stops at a different layer, so that the difference between two of them is the cost of
+//! one layer. The tcp benchmark instead echoes data through an established connection between two
+//! stacks sharing a `Loopback` nic.
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use ethox::managed::{List, Map, Slice, SlotMap};
+use ethox::nic::{external::External, loopback::Loopback, Device};
+use ethox::layer::{arp, eth, ip, tcp, udp};
+use ethox::layer::tcp::stream::{RecvRing, SendRing, Stream};
+use ethox::wire::{EthernetAddress, IpCidr, IpSubnet, Ipv4Address, Ipv4Subnet};
+use ethox::wire::Payload;
+
+const MAC_ADDR_A: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 5]);
+const IP_ADDR_A: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
+const MAC_ADDR_B: EthernetAddress = EthernetAddress([6, 5, 4, 3, 2, 1]);
+const IP_ADDR_B: Ipv4Address = Ipv4Address::new(10, 0, 0, 2);
+
+const BURST: usize = 32;
+const PAYLOAD: usize = 64;
+const ECHO: usize = 1024;
+
+fn ip_endpoint(addr: Ipv4Address, remote: Ipv4Address, remote_mac: EthernetAddress)
+    -> ip::Endpoint<'static>
+{
+    let mut neighbors = arp::NeighborCache::new(vec![arp::Neighbor::default(); 1]);
+    neighbors.fill(remote.into(), remote_mac, None).unwrap();
+    ip::Endpoint::new(IpCidr::new(addr.into(), 24),
+        ip::Routes::new(vec![ip::Route::unspecified(); 1]),
+        neighbors)
+}
+
+fn tcp_endpoint() -> tcp::Endpoint<'static> {
+    tcp::Endpoint::new(
+        Map::Pairs(List::new(Slice::Many(vec![Default::default(); 2]))),
+        SlotMap::new(
+            Slice::Many(vec![Default::default(); 2]),
+            Slice::Many(vec![Default::default(); 2])),
+        tcp::IsnGenerator::from_secret_key_bytes([0; 16]))
+}
+
+/// A nic with a burst of udp packets from `A` to `B`, ready to be received.
+fn udp_burst() -> External<Vec<Vec<u8>>> {
+    let mut nic = External::new_send(vec![vec![0; 128]; BURST]);
+    let mut eth = eth::Endpoint::new(MAC_ADDR_A);
+    let mut ip = ip_endpoint(IP_ADDR_A, IP_ADDR_B, MAC_ADDR_B);
+    let mut udp = udp::Endpoint::new(80);
+
+    let sent = nic.tx(1, eth.send(ip.send(udp.send_with(|raw: udp::RawPacket<_>| {
+        let init = udp::Init {
+            source: IpSubnet::from(Ipv4Subnet::ANY).into(),
+            src_port: 80,
+            dst_addr: IP_ADDR_B.into(),
+            dst_port: 80,
+            payload: PAYLOAD,
+            dscp: 0,
+        };
+        raw.prepare(init).unwrap().send().unwrap();
+    }))));
+    assert_eq!(sent, Ok(1));
+
+    nic.replicate(0);
+    nic.receive_all();
+    nic
+}
+
+fn receive_burst(c: &mut Criterion) {
+    let mut group = c.benchmark_group("receive");
+    group.throughput(Throughput::Elements(BURST as u64));
+
+    let mut nic = udp_burst();
+    let mut eth = eth::Endpoint::new(MAC_ADDR_B);
+    let mut ip = ip_endpoint(IP_ADDR_B, IP_ADDR_A, MAC_ADDR_A);
+    let mut udp = udp::Endpoint::new(80);
+
+    group.bench_function("eth", |b| b.iter(|| {
+        nic.reset_receive();
+        let recv = nic.rx(BURST, eth.recv_with(|packet: eth::InPacket<_>| {
+            black_box(packet.frame.payload());
+        }));
+        assert_eq!(recv, Ok(BURST));
+    }));
+
+    group.bench_function("eth-ip", |b| b.iter(|| {
+        nic.reset_receive();
+        let recv = nic.rx(BURST, eth.recv(ip.recv_with(|packet: ip::InPacket<_>| {
+            black_box(packet.packet.payload());
+        })));
+        assert_eq!(recv, Ok(BURST));
+    }));
+
+    group.bench_function("eth-ip-udp", |b| b.iter(|| {
+        nic.reset_receive();
+        let recv = nic.rx(BURST, eth.recv(ip.recv(udp.recv_with(|packet: udp::Packet<_>| {
+            black_box(packet.packet.payload());
+        }))));
+        assert_eq!(recv, Ok(BURST));
+    }));
+
+    group.finish();
+}
+
+fn tcp_echo(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tcp");
+    group.throughput(Throughput::Bytes(ECHO as u64));
+
+    let mut nic = Loopback::new(Slice::Many(vec![vec![0; 1514]; BURST]));
+    let mut eth_a = eth::Endpoint::new(MAC_ADDR_A);
+    let mut ip_a = ip_endpoint(IP_ADDR_A, IP_ADDR_B, MAC_ADDR_B);
+    let mut tcp_a = tcp_endpoint();
+    let mut eth_b = eth::Endpoint::new(MAC_ADDR_B);
+    let mut ip_b = ip_endpoint(IP_ADDR_B, IP_ADDR_A, MAC_ADDR_A);
+    let mut tcp_b = tcp_endpoint();
+
+    // The listening side advertises a zero window until it has read from the connection, the
+    // echoing side must thus open the connection.
+    let mut stream = Stream::new(tcp_a.listen(IP_ADDR_A.into(), 7).unwrap(),
+        RecvRing::new(vec![0; 4*ECHO]),
+        SendRing::new(vec![0; 4*ECHO]));
+    let mut echo = tcp::Client::new(IP_ADDR_A.into(), 7,
+        RecvRing::new(vec![0; 4*ECHO]),
+        SendRing::new(vec![0; 4*ECHO]));
+
+    // Each side only receives the packets pending from the other one, not its own answers.
+    macro_rules! recv_a {
+        () => { nic.rx(nic.pending(), eth_a.recv(ip_a.recv(tcp_a.recv(&mut stream)))) };
+    }
+    macro_rules! recv_b {
+        () => { nic.rx(nic.pending(), eth_b.recv(ip_b.recv(tcp_b.recv(&mut echo)))) };
+    }
+
+    // The handshake.
+    let _ = nic.tx(1, eth_b.send(ip_b.send(tcp_b.send(&mut echo))));
+    let _ = recv_a!();
+    let _ = recv_b!();
+    let _ = recv_a!();
+    let state = |tcp: &tcp::Endpoint, key| tcp.get(key).unwrap().info().state;
+    assert_eq!(state(&tcp_a, stream.connection_key().unwrap()), tcp::State::Established);
+    assert_eq!(state(&tcp_b, echo.connection_key().unwrap()), tcp::State::Established);
+
+    let data = [0xaa; ECHO];
+    let mut echoed = [0; ECHO];
+    group.bench_function("echo", |b| b.iter(|| {
+        assert_eq!(stream.send_mut().write(&data), ECHO);
+
+        let mut received = 0;
+        for _ in 0..64 {
+            let _ = nic.tx(BURST, eth_a.send(ip_a.send(tcp_a.send(&mut stream))));
+            let _ = recv_b!();
+
+            let mut buffer = [0; ECHO];
+            let len = echo.recv_mut().read(&mut buffer);
+            assert_eq!(echo.send_mut().write(&buffer[..len]), len);
+
+            let _ = nic.tx(BURST, eth_b.send(ip_b.send(tcp_b.send(&mut echo))));
+            let _ = recv_a!();
+
+            received += stream.recv_mut().read(&mut echoed[received..]);
+            if received == ECHO {
+                break;
+            }
+        }
+
+        assert_eq!(received, ECHO);
+        black_box(&echoed);
+    }));
+
+    group.finish();
+}
+
+criterion_group!(benches, receive_burst, tcp_echo);
+criterion_main!(benches);
@@ -7,7 +7,11 @@ use std::net;
 use structopt::StructOpt;
 
 use ethox::managed::{List, Map, SlotMap, Slice};
-use ethox::nic::{Device, sys::RawSocket, Protocol};
+use ethox::nic::{Device, Protocol};
+#[cfg(target_os = "linux")]
+use ethox::nic::sys::RawSocket as Interface;
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+use ethox::nic::sys::Bpf as Interface;
 use ethox::layer::{arp, eth, ip, tcp};
 use ethox::wire::{Ipv4Address, Ipv4Cidr, EthernetAddress};
 
@@ -43,7 +47,7 @@ fn main() {
         tcp::io::RecvInto::new(vec![0; 1 << 20]),
         tcp::io::SendFrom::once(message.as_bytes()));
 
-    let mut interface = RawSocket::new(&name, vec![0; 1 << 14])
+    let mut interface = Interface::new(&name, vec![0; 1 << 14])
         .expect(&format!("Couldn't initialize interface {}", name));
     *interface.capabilities_mut().tcp_mut() = Protocol::offloaded().into();
 
@@ -1,14 +1,20 @@
 //! Debugs all packets coming in on a tap.
+//!
+//! On macOS and FreeBSD this attaches to an existing interface through a bpf device instead.
 use std::{env, process};
 
-use ethox::nic::{Device, sys::TapInterface};
+use ethox::nic::Device;
+#[cfg(target_os = "linux")]
+use ethox::nic::sys::TapInterface as Interface;
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+use ethox::nic::sys::Bpf as Interface;
 use ethox::wire::pretty_print::Formatter;
 
 fn main() {
     let name = env::args().nth(1)
         .unwrap_or_else(usage_and_exit);
 
-    let mut interface = TapInterface::new(&name, vec![0; 1 << 14])
+    let mut interface = Interface::new(&name, vec![0; 1 << 14])
         .expect("Couldn't initialize interface");
     loop {
         // Receive the next packet.
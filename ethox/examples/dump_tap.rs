@@ -0,0 +1,70 @@
+//! Prints a decoded listing of all traffic on a tap, similar to `tcpdump`.
+//!
+//! Each frame is printed with its receive timestamp, followed by all the layers that could be
+//! decoded, nested below the layer that contained them:
+//!
+//! ```text
+//! 1602934217.512s EthernetII src=.. dst=.. type=IPv4
+//! \ IPv4 src=10.0.0.2 dst=10.0.0.1 proto=UDP
+//!  \ UDP src=48896 dst=53 len=32
+//! ```
+//!
+//! The same listings are also available within a configured stack by passing a `Formatter` as the
+//! receiver of the eth, ip, udp, or icmp layer. Those only print the traffic that reached the
+//! respective layer.
+//!
+//! # Usage
+//!
+//! > $ cargo run --example dump_tap -- tap0 --count 10
+//!
+//! On macOS and FreeBSD this attaches to an existing interface through a bpf device instead.
+use structopt::StructOpt;
+
+use ethox::nic::{self, Device};
+#[cfg(target_os = "linux")]
+use ethox::nic::sys::TapInterface as Interface;
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+use ethox::nic::sys::Bpf as Interface;
+use ethox::wire::{Payload, PrettyPrinter, ethernet_frame};
+
+fn main() {
+    let Config { name, count } = Config::from_args();
+
+    let mut interface = Interface::new(&name, vec![0; 1 << 14])
+        .expect("Couldn't initialize interface");
+    let mut dump = Dump { packets: 0 };
+
+    while count.is_none_or(|count| dump.packets < count) {
+        // Receive the next packet.
+        let result = interface.rx(1, &mut dump);
+
+        result.unwrap_or_else(|err| {
+            panic!("Error during receive {:?} {:?}", err, interface.last_err());
+        });
+    }
+}
+
+#[derive(StructOpt)]
+struct Config {
+    /// The name of the interface to attach to.
+    name: String,
+    /// Exit after printing this many packets.
+    #[structopt(short, long)]
+    count: Option<usize>,
+}
+
+/// Prints every received frame.
+struct Dump {
+    packets: usize,
+}
+
+impl<H, P> nic::Recv<H, P> for Dump
+    where H: nic::Handle + ?Sized, P: Payload + ?Sized,
+{
+    fn receive(&mut self, packet: nic::Packet<H, P>) {
+        let timestamp = packet.handle.info().timestamp();
+        let frame = packet.payload.payload().as_slice();
+        println!("{} {}", timestamp, PrettyPrinter::<ethernet_frame>::new("", frame));
+        self.packets += 1;
+    }
+}
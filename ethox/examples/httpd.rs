@@ -0,0 +1,339 @@
+//! A minimal HTTP/1.1 server with static pages on a tap interface.
+//!
+//! The server keeps one connection listening at all times and answers each request of an
+//! accepted connection from a fixed set of pages. Requests are parsed in place in the receive
+//! buffer of the connection, once their headers are complete. Every response closes the
+//! connection, so that a client sees all data before the FIN.
+//!
+//! # Usage
+//!
+//! Set up the tap interface as for the `ping_tap` example, then start the server and request a
+//! page from the host:
+//!
+//! > $ cargo run --example httpd -- tap0 10.0.0.1/24 ab:ff:ff:ff:ff:ff 10.0.0.2/24
+//!
+//! > $ curl -v http://10.0.0.1/
+//!
+//! On macOS and FreeBSD the example attaches to an existing interface through a bpf device
+//! instead, for example one end of an `feth` or `epair` pair.
+use std::io::{stdout, Write};
+use std::str;
+use structopt::StructOpt;
+
+use ethox::managed::{List, Map, Slice, SlotMap};
+use ethox::nic::Device;
+#[cfg(target_os = "linux")]
+use ethox::nic::sys::TapInterface as Interface;
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+use ethox::nic::sys::Bpf as Interface;
+use ethox::layer::{arp, eth, ip, tcp};
+use ethox::layer::tcp::stream::{RecvRing, SendRing, Stream};
+use ethox::wire::{EthernetAddress, Ipv4Cidr, PayloadMut};
+
+/// The storage of each of the stream buffers of a connection.
+const BUFFER: usize = 1 << 12;
+
+/// The pages served, by path.
+const PAGES: &[(&str, &str, &str)] = &[
+    ("/", "text/html", "<!DOCTYPE html>\n<html><body><h1>Hello from ethox</h1></body></html>\n"),
+    ("/robots.txt", "text/plain", "User-agent: *\nDisallow: /\n"),
+];
+
+fn main() {
+    let Config {
+        name,
+        host,
+        hostmac,
+        gateway,
+        port,
+        connections,
+    } = Config::from_args();
+
+    let mut eth = eth::Endpoint::new(hostmac);
+
+    let mut neighbors = vec![arp::Neighbor::default(); connections];
+    let mut routes = [ip::Route::new_ipv4_gateway(gateway.address()); 1];
+    let mut ip = ip::Endpoint::new(Slice::One(host.into()),
+        ip::Routes::import(List::new_full(routes.as_mut().into())),
+        arp::NeighborCache::new(&mut neighbors[..]));
+
+    // Connections linger in the endpoint while closing, after their session has ended.
+    let slots = 2 * connections;
+    let mut tcp = tcp::Endpoint::new(
+        Map::Pairs(List::new(Slice::Many(vec![Default::default(); slots]))),
+        SlotMap::new(
+            Slice::Many(vec![Default::default(); slots]),
+            Slice::Many(vec![Default::default(); slots])),
+        tcp::IsnGenerator::from_std_hash(),
+    );
+
+    let mut server = Server {
+        address: host,
+        port,
+        limit: connections,
+        sessions: Vec::new(),
+        next: 0,
+        served: 0,
+    };
+
+    let mut interface = Interface::new(&name, vec![0; 1 << 14])
+        .expect("Couldn't initialize interface");
+
+    let out = stdout();
+    let mut out = out.lock();
+    writeln!(out, "Serving http on {}:{}", host.address(), port).unwrap();
+
+    loop {
+        // A listening connection accepts a single connection attempt. Receive one packet at a
+        // time so that the next attempt finds the next listening connection.
+        server.listen(&mut tcp);
+
+        let rx = interface.rx(1, eth.recv(ip.recv(tcp.recv(&mut server))));
+        let served = server.served;
+        server.respond();
+        let tx = interface.tx(10, eth.send(ip.send(tcp.send(&mut server))));
+
+        if server.served != served {
+            writeln!(out, "Served {} requests", server.served).unwrap();
+        }
+
+        rx.and(tx).unwrap_or_else(|err| {
+            panic!("Error during processing {:?} {:?}", err, interface.last_err());
+        });
+    }
+}
+
+/// The connections of the server.
+struct Server {
+    address: Ipv4Cidr,
+    port: u16,
+    /// The maximum number of concurrent connections, including the listening one.
+    limit: usize,
+    sessions: Vec<Session>,
+    /// The session that is offered the next packet buffer for sending.
+    next: usize,
+    served: usize,
+}
+
+/// One connection, from listening until the response has been sent.
+struct Session {
+    stream: Stream<RecvRing<'static>, SendRing<'static>>,
+    responded: bool,
+}
+
+/// The request line of a request.
+struct Request<'a> {
+    method: &'a str,
+    path: &'a str,
+    version: &'a str,
+}
+
+/// A static response.
+struct Response<'a> {
+    status: &'a str,
+    content_type: &'a str,
+    body: &'a str,
+    /// Only send the headers, for a `HEAD` request.
+    head: bool,
+}
+
+impl Server {
+    /// Forget all closed connections and make sure that one connection is listening.
+    fn listen(&mut self, tcp: &mut tcp::Endpoint) {
+        self.sessions.retain(|session| !session.stream.is_closed());
+        self.next = self.next.min(self.sessions.len());
+
+        let listening = self.sessions.iter()
+            .filter_map(|session| session.stream.connection_key())
+            .filter_map(|key| tcp.get(key))
+            .any(|slot| slot.info().state == tcp::State::Listen);
+
+        if listening || self.sessions.len() >= self.limit {
+            return;
+        }
+
+        if let Some(key) = tcp.listen(self.address.address().into(), self.port) {
+            self.sessions.push(Session {
+                stream: Stream::new(key,
+                    RecvRing::new(vec![0; BUFFER]),
+                    SendRing::new(vec![0; BUFFER])),
+                responded: false,
+            });
+        }
+    }
+
+    /// Answer all complete requests.
+    fn respond(&mut self) {
+        for session in &mut self.sessions {
+            if session.respond() {
+                self.served += 1;
+            }
+        }
+    }
+}
+
+impl Session {
+    /// Answer the request if its headers are complete, returns if a response was written.
+    fn respond(&mut self) -> bool {
+        if self.responded || self.stream.is_closed() {
+            return false;
+        }
+
+        let recv = self.stream.recv_mut();
+        if recv.is_empty() {
+            // The remote closed without sending a request.
+            if recv.is_closed() {
+                self.responded = true;
+                self.stream.send_mut().close();
+            }
+            return false;
+        }
+
+        // The headers are parsed from a single slice of the buffer.
+        recv.make_contiguous();
+        let mut response = None;
+        recv.read_with(|data| {
+            match find_header_end(data) {
+                Some(end) => {
+                    response = Some(Response::answer(&data[..end]));
+                    data.len()
+                },
+                None => 0,
+            }
+        });
+
+        let response = match response {
+            Some(response) => response,
+            // The request can not be completed any more.
+            None if recv.is_closed() => Response::error("400 Bad Request"),
+            None if recv.len() == BUFFER => Response::error("431 Request Header Fields Too Large"),
+            None => return false,
+        };
+
+        let send = self.stream.send_mut();
+        response.write(send);
+        send.close();
+        self.responded = true;
+        true
+    }
+}
+
+impl<'a> Request<'a> {
+    /// Parse the request line of a request, the headers are not needed for static pages.
+    fn parse(head: &'a [u8]) -> Option<Self> {
+        let head = str::from_utf8(head).ok()?;
+        let line = head.split("\r\n").next()?;
+        let mut parts = line.split(' ');
+        let request = Request {
+            method: parts.next()?,
+            path: parts.next()?,
+            version: parts.next()?,
+        };
+
+        // Every header must have a name.
+        let mut headers = head.split("\r\n").skip(1).filter(|header| !header.is_empty());
+        if parts.next().is_some() || !headers.all(|header| header.contains(':')) {
+            return None;
+        }
+
+        Some(request)
+    }
+}
+
+impl Response<'static> {
+    /// Choose the response to the headers of a request.
+    fn answer(head: &[u8]) -> Self {
+        let request = match Request::parse(head) {
+            Some(request) => request,
+            None => return Response::error("400 Bad Request"),
+        };
+
+        if !request.version.starts_with("HTTP/1.") {
+            return Response::error("505 HTTP Version Not Supported");
+        }
+
+        let head = match request.method {
+            "GET" => false,
+            "HEAD" => true,
+            _ => return Response::error("405 Method Not Allowed"),
+        };
+
+        match PAGES.iter().find(|(path, _, _)| *path == request.path) {
+            Some(&(_, content_type, body)) => Response {
+                status: "200 OK",
+                content_type,
+                body,
+                head,
+            },
+            None => Response { head, ..Response::error("404 Not Found") },
+        }
+    }
+
+    fn error(status: &'static str) -> Self {
+        Response {
+            status,
+            content_type: "text/plain",
+            body: status,
+            head: false,
+        }
+    }
+}
+
+impl Response<'_> {
+    /// Write the response, as much as the buffer can hold.
+    fn write(&self, send: &mut SendRing) {
+        let headers = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status, self.content_type, self.body.len());
+        send.write(headers.as_bytes());
+        if !self.head {
+            send.write(self.body.as_bytes());
+        }
+    }
+}
+
+/// Find the end of the headers, after the empty line.
+fn find_header_end(data: &[u8]) -> Option<usize> {
+    data.windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .map(|pos| pos + 4)
+}
+
+impl<P: PayloadMut> tcp::Recv<P> for &'_ mut Server {
+    fn receive(&mut self, packet: tcp::InPacket<P>) {
+        let key = packet.key();
+        let session = self.sessions.iter_mut()
+            .find(|session| key.is_some() && session.stream.connection_key() == key);
+
+        if let Some(session) = session {
+            let mut stream = &mut session.stream;
+            stream.receive(packet);
+        }
+    }
+}
+
+impl<P: PayloadMut> tcp::Send<P> for &'_ mut Server {
+    fn send(&mut self, packet: tcp::RawPacket<P>) {
+        if self.sessions.is_empty() {
+            return;
+        }
+
+        // Offer the buffers to the connections in turn.
+        let idx = self.next % self.sessions.len();
+        self.next = idx + 1;
+        let mut stream = &mut self.sessions[idx].stream;
+        stream.send(packet);
+    }
+}
+
+#[derive(StructOpt)]
+struct Config {
+    name: String,
+    host: Ipv4Cidr,
+    hostmac: EthernetAddress,
+    gateway: Ipv4Cidr,
+    #[structopt(long = "port", default_value = "80")]
+    port: u16,
+    #[structopt(long = "connections", default_value = "8")]
+    connections: usize,
+}
@@ -25,11 +25,18 @@
 //!    for fun (`-f`).
 //! 
 //!   > $ ping -OI tap0 10.0.0.1
+//!
+//! On macOS and FreeBSD the example attaches to an existing interface through a bpf device
+//! instead, for example one end of an `feth` or `epair` pair.
 use std::io::{stdout, Write};
 use structopt::StructOpt;
 
 use ethox::managed::{List, Slice};
-use ethox::nic::{Device, sys::TapInterface};
+use ethox::nic::Device;
+#[cfg(target_os = "linux")]
+use ethox::nic::sys::TapInterface as Interface;
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+use ethox::nic::sys::Bpf as Interface;
 use ethox::layer::{arp, eth, ip, icmp};
 use ethox::wire::{Ipv4Cidr, EthernetAddress};
 
@@ -53,7 +60,7 @@ fn main() {
 
     let mut icmp = icmp::Endpoint::new();
 
-    let mut interface = TapInterface::new(&name, vec![0; 1 << 14])
+    let mut interface = Interface::new(&name, vec![0; 1 << 14])
         .expect("Couldn't initialize interface");
 
     let out = stdout();
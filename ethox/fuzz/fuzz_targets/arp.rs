@@ -0,0 +1,18 @@
+//! Parse arbitrary bytes as an arp packet.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use ethox::wire::{arp_packet, ArpPacket, ArpRepr};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(packet) = arp_packet::new_checked(data) {
+        let _ = ArpRepr::parse(packet);
+        let _ = packet.source_hardware_addr();
+        let _ = packet.source_protocol_addr();
+        let _ = packet.target_hardware_addr();
+        let _ = packet.target_protocol_addr();
+    }
+
+    if let Ok(packet) = ArpPacket::new_checked(data) {
+        let _ = packet.repr();
+    }
+});
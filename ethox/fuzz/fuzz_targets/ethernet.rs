@@ -0,0 +1,15 @@
+//! Parse arbitrary bytes as an ethernet frame.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use ethox::wire::{ethernet_frame, EthernetFrame, EthernetRepr};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(frame) = ethernet_frame::new_checked(data) {
+        let _ = EthernetRepr::parse(frame);
+        let _ = frame.payload_slice();
+    }
+
+    if let Ok(frame) = EthernetFrame::new_checked(data) {
+        let _ = frame.repr();
+    }
+});
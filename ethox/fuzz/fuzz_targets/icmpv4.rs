@@ -0,0 +1,16 @@
+//! Parse arbitrary bytes as an icmpv4 packet.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use ethox::wire::{icmpv4_packet, Checksum, Icmpv4Packet, Icmpv4Repr};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(packet) = icmpv4_packet::new_checked(data) {
+        let _ = Icmpv4Repr::parse(packet, Checksum::Manual);
+        let _ = Icmpv4Repr::parse(packet, Checksum::Ignored);
+        let _ = packet.payload_slice();
+    }
+
+    if let Ok(packet) = Icmpv4Packet::new_checked(data, Checksum::Ignored) {
+        let _ = packet.repr();
+    }
+});
@@ -0,0 +1,17 @@
+//! Parse arbitrary bytes as an ipv4 packet.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use ethox::wire::{ipv4_packet, Checksum, Ipv4Packet, Ipv4Repr};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(packet) = ipv4_packet::new_checked(data) {
+        let _ = Ipv4Repr::parse(packet, Checksum::Manual);
+        let _ = Ipv4Repr::parse(packet, Checksum::Ignored);
+        let _ = packet.verify_checksum();
+        let _ = packet.payload_slice();
+    }
+
+    if let Ok(packet) = Ipv4Packet::new_checked(data, Checksum::Ignored) {
+        let _ = packet.repr();
+    }
+});
@@ -0,0 +1,17 @@
+//! Parse arbitrary bytes as a tcp packet, including its options.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use ethox::wire::{IpAddress, TcpChecksum, TcpPacket};
+
+const SRC_ADDR: IpAddress = IpAddress::v4(192, 0, 2, 1);
+const DST_ADDR: IpAddress = IpAddress::v4(192, 0, 2, 2);
+
+fuzz_target!(|data: &[u8]| {
+    let checksum = TcpChecksum::Manual { src_addr: SRC_ADDR, dst_addr: DST_ADDR };
+    let _ = TcpPacket::new_checked(data, checksum);
+
+    if let Ok(packet) = TcpPacket::new_checked(data, TcpChecksum::Ignored) {
+        let _ = packet.repr();
+        let _ = packet.payload_slice();
+    }
+});
@@ -0,0 +1,78 @@
+//! Feed a sequence of arbitrary tcp segments to a listening tcp endpoint.
+//!
+//! The input is split into segments, each prefixed by its length in a single byte. They are
+//! wrapped into valid ethernet and ipv4 headers and then received one after another, a
+//! millisecond apart, so that the fuzzer can explore the connection states.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use ethox::managed::{List, Map, Slice, SlotMap};
+use ethox::nic::{external::External, Device};
+use ethox::layer::{arp, eth, ip, tcp};
+use ethox::layer::tcp::io::{Empty, Sink};
+use ethox::layer::tcp::stream::Stream;
+use ethox::time::{Duration, Instant};
+use ethox::wire::{ethernet_frame, ipv4_packet, Checksum};
+use ethox::wire::{EthernetAddress, EthernetProtocol, EthernetRepr, IpCidr, IpProtocol};
+use ethox::wire::{Ipv4Address, Ipv4Repr};
+
+const MAC_ADDR: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 5]);
+const IP_ADDR: Ipv4Address = Ipv4Address::new(192, 0, 2, 1);
+const REMOTE_MAC_ADDR: EthernetAddress = EthernetAddress([6, 5, 4, 3, 2, 1]);
+const REMOTE_IP_ADDR: Ipv4Address = Ipv4Address::new(192, 0, 2, 2);
+
+/// Wrap a tcp segment into an ethernet frame from the remote.
+fn frame(segment: &[u8]) -> Vec<u8> {
+    let mut buffer = vec![0; 14 + 20 + segment.len()];
+    let eth = ethernet_frame::new_unchecked_mut(&mut buffer);
+    EthernetRepr {
+        src_addr: REMOTE_MAC_ADDR,
+        dst_addr: MAC_ADDR,
+        ethertype: EthernetProtocol::Ipv4,
+    }.emit(eth);
+
+    let ip = ipv4_packet::new_unchecked_mut(eth.payload_mut_slice());
+    Ipv4Repr {
+        src_addr: REMOTE_IP_ADDR,
+        dst_addr: IP_ADDR,
+        protocol: IpProtocol::Tcp,
+        payload_len: segment.len(),
+        hop_limit: 64,
+    }.emit(ip, Checksum::Manual);
+    ip.payload_mut_slice().copy_from_slice(segment);
+
+    buffer
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut eth = eth::Endpoint::new(MAC_ADDR);
+    let mut neighbors = arp::NeighborCache::new(vec![arp::Neighbor::default(); 1]);
+    neighbors.fill(REMOTE_IP_ADDR.into(), REMOTE_MAC_ADDR, None).unwrap();
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR.into(), 24),
+        ip::Routes::new(vec![ip::Route::unspecified(); 1]),
+        neighbors);
+    let mut tcp = tcp::Endpoint::new(
+        Map::Pairs(List::new(Slice::Many(vec![Default::default(); 2]))),
+        SlotMap::new(
+            Slice::Many(vec![Default::default(); 2]),
+            Slice::Many(vec![Default::default(); 2])),
+        tcp::IsnGenerator::from_secret_key_bytes([0; 16]));
+
+    let listener = tcp.listen(IP_ADDR.into(), 80).unwrap();
+    let mut stream = Stream::new(listener, Sink::default(), Empty::default());
+
+    let mut remaining = data;
+    let mut time = Instant::from_millis(0);
+    while let Some((&len, rest)) = remaining.split_first() {
+        let len = usize::from(len).min(rest.len());
+        let (segment, rest) = rest.split_at(len);
+        remaining = rest;
+
+        let mut nic = External::new_recv(vec![frame(segment)]);
+        nic.set_current_time(time);
+        let _ = nic.rx(1, eth.recv(ip.recv(tcp.recv(&mut stream))));
+
+        let _ = tcp.poll(time);
+        time = time + Duration::from_millis(1);
+    }
+});
@@ -0,0 +1,21 @@
+//! Parse arbitrary bytes as an udp packet.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use ethox::wire::{udp_packet, IpAddress, UdpChecksum, UdpPacket, UdpRepr};
+
+const SRC_ADDR: IpAddress = IpAddress::v4(192, 0, 2, 1);
+const DST_ADDR: IpAddress = IpAddress::v4(192, 0, 2, 2);
+
+fuzz_target!(|data: &[u8]| {
+    let checksum = UdpChecksum::Manual { src_addr: SRC_ADDR, dst_addr: DST_ADDR };
+
+    if let Ok(packet) = udp_packet::new_checked(data) {
+        let _ = UdpRepr::parse(packet, checksum);
+        let _ = UdpRepr::parse(packet, UdpChecksum::Ignored);
+        let _ = packet.payload_slice();
+    }
+
+    if let Ok(packet) = UdpPacket::new_checked(data, UdpChecksum::Ignored) {
+        let _ = packet.repr();
+    }
+});
@@ -4,12 +4,14 @@
 //! about missing addresses.
 
 use crate::layer::{eth, Result};
+use crate::managed::Slice;
 use crate::wire::{ArpPacket, ArpRepr, ArpOperation, EthernetAddress, EthernetProtocol, Payload, PayloadMut, IpAddress};
-use crate::time::Instant;
+use crate::wire::{Ipv4Address, Ipv4Subnet};
+use crate::time::{Clock, Expiration, Instant};
 use crate::layer::ip;
 
 use super::packet::{Handle, In, Init, Raw};
-use super::neighbor::Cache;
+use super::neighbor::{Cache, Event};
 
 /// The persistent data of an arp layer.
 ///
@@ -20,6 +22,8 @@ use super::neighbor::Cache;
 /// layers for handling their protocol specific arp tasks.
 pub struct Endpoint<'data> {
     neighbors: Cache<'data>,
+    /// Prefixes for which requests are answered in proxy of other hosts.
+    proxy: Slice<'data, Ipv4Subnet>,
 }
 
 /// An endpoint borrowed for receiving.
@@ -49,9 +53,33 @@ impl<'data> Endpoint<'data> {
     {
         Endpoint {
             neighbors: neighbors.into(),
+            proxy: Slice::empty(),
         }
     }
 
+    /// Answer requests for addresses in some prefixes with the own hardware address.
+    ///
+    /// This is proxy ARP (RFC1027), for hosts that are reached through this one but are not on the
+    /// same link as the requesting neighbor. The traffic to those hosts is then sent to us and can
+    /// be forwarded by the ip layer. A request of a host for its own address is not answered, so
+    /// that it does not detect a conflict. Configure an empty slice to disable proxying, which is
+    /// the default.
+    pub fn set_proxy<S>(&mut self, prefixes: S)
+        where S: Into<Slice<'data, Ipv4Subnet>>,
+    {
+        self.proxy = prefixes.into();
+    }
+
+    /// The prefixes for which requests are answered in proxy.
+    pub fn proxy(&self) -> &[Ipv4Subnet] {
+        &self.proxy
+    }
+
+    /// Check if a request for an address is answered in proxy.
+    fn proxies(&self, source: Ipv4Address, target: Ipv4Address) -> bool {
+        target != source && self.proxy.iter().any(|prefix| prefix.contains(target))
+    }
+
     /// A receiver that answers arp requests in stead of an ip endpoint.
     ///
     /// Utilizes the address and routing configuration of the endpoint but handles arp traffic
@@ -81,16 +109,36 @@ impl<'data> Endpoint<'data> {
         }
     }
 
+    /// Drive the timers of the neighbor cache.
+    ///
+    /// Removes expired entries and returns the next time at which the endpoint requires attention.
+    /// If this is not in the future then a query is outstanding and a sender should be run.
+    pub fn poll(&mut self, now: Instant) -> Expiration {
+        self.neighbors.expire(now);
+        self.neighbors.next_timer(now)
+    }
+
+    /// Drive the timers with the current time of a clock.
+    ///
+    /// See [`poll`] for details.
+    ///
+    /// [`poll`]: #method.poll
+    pub fn tick<C: Clock + ?Sized>(&mut self, clock: &C) -> Expiration {
+        self.poll(clock.now())
+    }
+
     /// Get this by mutable reference for a receiver or sender.
     fn get_mut<'a>(&'a mut self, ip: &'a mut ip::Routing<'data>) -> EndpointRef<'a, 'data> {
         EndpointRef { inner: self, ip, }
     }
 
-    pub(crate) fn neighbors(&self) -> &Cache<'data> {
+    /// Get the neighbor cache, e.g. to export its entries.
+    pub fn neighbors(&self) -> &Cache<'data> {
         &self.neighbors
     }
 
-    pub(crate) fn neighbors_mut(&mut self) -> &mut Cache<'data> {
+    /// Get the neighbor cache mutably, e.g. to import entries from another source.
+    pub fn neighbors_mut(&mut self) -> &mut Cache<'data> {
         &mut self.neighbors
     }
 }
@@ -101,7 +149,7 @@ impl EndpointRef<'_, '_> {
     /// See [RFC826] for details.
     ///
     /// [RFC826]: https://tools.ietf.org/html/rfc826
-    fn handle_internally<P: PayloadMut>(&mut self, packet: In<P>) -> Result<()> {
+    fn handle_internally<P: PayloadMut>(&mut self, mut packet: In<P>) -> Result<()> {
         let (operation, source_hardware_addr, source_protocol_addr, target_protocol_addr) =
             match packet.packet.repr() {
                 ArpRepr::EthernetIpv4 {
@@ -116,6 +164,23 @@ impl EndpointRef<'_, '_> {
                 _ => return Ok(()),
             };
 
+        // A tentative address is used by another host if it sends from it or probes for it, see
+        // RFC5227. Our own probes may be looped back by the device.
+        if source_hardware_addr != packet.handle.inner.src_addr() {
+            let probe = source_protocol_addr.is_unspecified()
+                && operation == ArpOperation::Request;
+            if self.ip.duplicate(IpAddress::Ipv4(source_protocol_addr))
+                || (probe && self.ip.duplicate(IpAddress::Ipv4(target_protocol_addr)))
+            {
+                return Ok(());
+            }
+        }
+
+        if let ArpOperation::Reply = operation {
+            let addr = IpAddress::Ipv4(source_protocol_addr);
+            self.inner.neighbors.record(Event::ReplyReceived(addr, source_hardware_addr));
+        }
+
         // Update the address if it already exists in our tables (may be currently looking it up).
         self.update(
             source_hardware_addr,
@@ -125,12 +190,16 @@ impl EndpointRef<'_, '_> {
         // TODO: handle incoming gratuitous ARP ?
 
         // verify that target protocol address is not a multicast address and we accept it.
-        if target_protocol_addr.is_unicast() && self.ip.accepts(IpAddress::Ipv4(target_protocol_addr)) {
+        let accepted = self.ip.accepts(IpAddress::Ipv4(target_protocol_addr))
+            || self.inner.proxies(source_protocol_addr, target_protocol_addr);
+        if target_protocol_addr.is_unicast() && accepted {
             // unsolicited updates fully ignored not enabled.
 
             // send a reply if necessary.
             if let ArpOperation::Request = operation {
                 packet.answer()?.send()?;
+                let addr = IpAddress::Ipv4(source_protocol_addr);
+                self.inner.neighbors.record(Event::RequestAnswered(addr));
             }
         }
 
@@ -138,9 +207,21 @@ impl EndpointRef<'_, '_> {
     }
 
     /// Send oustanding arp requests.
+    ///
+    /// Probes for tentative addresses take precedence over the resolution of neighbors.
     fn send_oustanding<P: PayloadMut>(&mut self, raw: Raw<P>) -> Result<()> {
         let ts = raw.handle.info().timestamp();
 
+        let probe = self.ip.probes(ts)
+            .find_map(|addr| match addr {
+                IpAddress::Ipv4(addr) => Some(addr),
+                _ => None,
+            });
+
+        if let Some(addr) = probe {
+            return self.send_probe(raw, addr, ts);
+        }
+
         // Search through the missing arp entries:
         let unresolved = self.inner.neighbors
             .missing()
@@ -185,6 +266,27 @@ impl EndpointRef<'_, '_> {
         debug_assert!(reset.is_ok());
 
         prepared.send()?;
+        self.inner.neighbors.record(Event::RequestSent(IpAddress::Ipv4(addr)));
+
+        Ok(())
+    }
+
+    /// Probe for another host using a tentative address, see RFC5227.
+    fn send_probe<P: PayloadMut>(&mut self, mut raw: Raw<P>, addr: Ipv4Address, ts: Instant)
+        -> Result<()>
+    {
+        let src = raw.handle.inner.src_addr();
+        let prepared = raw.prepare(Init::EthernetIpv4Request {
+            source_hardware_addr: src,
+            // Ignored by the receivers, all zeros as recommended.
+            target_hardware_addr: EthernetAddress([0; 6]),
+            // Does not pollute the caches of other hosts with an address we might not get.
+            source_protocol_addr: Ipv4Address::UNSPECIFIED,
+            target_protocol_addr: addr,
+        })?;
+
+        prepared.send()?;
+        self.ip.probed(IpAddress::Ipv4(addr), ts);
 
         Ok(())
     }
@@ -214,8 +316,8 @@ impl<P> eth::Recv<P> for Receiver<'_, '_>
         let handle = Handle::new(handle);
         let packet = In::new(handle, packet);
 
-        if let Err(_) = self.endpoint.handle_internally(packet) {
-            // TODO: log error
+        if let Err(err) = self.endpoint.handle_internally(packet) {
+            net_debug!("arp: failed to handle packet: {:?}", err);
         }
     }
 }
@@ -232,8 +334,8 @@ impl<P> eth::Send<P> for Sender<'_, '_>
         let handle = Handle::new(eth_handle.borrow_mut());
         let packet = Raw::new(handle, payload);
 
-        if let Err(_) = self.endpoint.send_oustanding(packet) {
-            // TODO: log error
+        if let Err(err) = self.endpoint.send_oustanding(packet) {
+            net_debug!("arp: failed to send request: {:?}", err);
         }
     }
 }
@@ -5,6 +5,20 @@
 //! its use in IPv4 addressing.
 //!
 //! Its code could be more generic and might in the future be reused for other protocols.
+//!
+//! The neighbor cache counts the requests and replies, the lookups while sending and the entries
+//! it had to drop, see [`NeighborStatistics`]. The same events can be passed to a
+//! [`NeighborEvents`] observer on the cache, which helps to find out why a send failed with
+//! `Unreachable` or `Exhausted`.
+//!
+//! Entries configured with [`NeighborCache::fill_static`] are never aged out, evicted or replaced
+//! by the replies of other hosts. With [`Endpoint::set_proxy`], requests for the addresses of
+//! hosts behind this one are answered with the own hardware address (proxy ARP).
+//!
+//! [`NeighborStatistics`]: struct.NeighborStatistics.html
+//! [`NeighborEvents`]: trait.NeighborEvents.html
+//! [`NeighborCache::fill_static`]: struct.NeighborCache.html#method.fill_static
+//! [`Endpoint::set_proxy`]: struct.Endpoint.html#method.set_proxy
 mod endpoint;
 mod neighbor;
 mod packet;
@@ -18,6 +32,10 @@ pub use neighbor::{
     Answer as NeighborAnswer,
     Mapping as NeighborMapping,
     Cache as NeighborCache,
+    Entries as NeighborEntries,
+    Event as NeighborEvent,
+    Events as NeighborEvents,
+    Statistics as NeighborStatistics,
     Table as NeighborTable,
 };
 
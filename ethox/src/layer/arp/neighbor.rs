@@ -1,6 +1,6 @@
 // Heads up! Before working on this file you should read, at least,
 // the parts of RFC 1122 that discuss ARP.
-use core::slice;
+use core::{fmt, slice};
 use core::ops::Deref;
 
 use crate::managed::Ordered;
@@ -13,11 +13,16 @@ use crate::wire::{EthernetAddress, IpAddress};
 /// and contains the timestamp past which the mapping should be considered invalid. It also
 /// contains a timestamp at which we should try to update the neighbor mapping by sending out
 /// solicitation requests.
+///
+/// A static entry is configured by the user and never expires. Unlike other entries that do not
+/// expire, it is also not evicted for new entries nor replaced by mappings learned from the
+/// network.
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Neighbor {
     protocol_addr: IpAddress,
     hardware_addr: Mapping,
     expires_at:    Expiration,
+    is_static:     bool,
 }
 
 /// An answer to a neighbor cache lookup.
@@ -57,6 +62,87 @@ impl Default for Mapping {
     }
 }
 
+/// Counters of the neighbor resolution with a cache.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Statistics {
+    /// The number of requests sent for missing entries.
+    pub requests_sent: u64,
+
+    /// The number of requests for an own or proxied address that were answered.
+    pub requests_answered: u64,
+
+    /// The number of replies received.
+    pub replies_received: u64,
+
+    /// The number of addresses found in the cache while sending.
+    pub hits: u64,
+
+    /// The number of addresses missing from the cache while sending.
+    pub misses: u64,
+
+    /// The number of entries removed to make room for another one.
+    pub evicted: u64,
+
+    /// The number of entries removed at the end of their lifetime.
+    ///
+    /// This includes entries for which a request was never answered.
+    pub expired: u64,
+
+    /// The number of missing addresses that could not be looked up as the cache was full.
+    pub unresolved: u64,
+}
+
+/// An event of the neighbor resolution, passed to the observer of a cache.
+///
+/// Each event is also counted in the [`Statistics`] of the cache.
+///
+/// [`Statistics`]: struct.NeighborStatistics.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// A request for a missing address was sent.
+    RequestSent(IpAddress),
+
+    /// A request of a neighbor for an own or proxied address was answered.
+    RequestAnswered(IpAddress),
+
+    /// A reply of a neighbor was received.
+    ReplyReceived(IpAddress, EthernetAddress),
+
+    /// An address was found in the cache while sending.
+    ///
+    /// This happens for nearly every packet, keep the observer short.
+    Hit(IpAddress),
+
+    /// An address was missing from the cache while sending.
+    ///
+    /// The packet could not be sent and an error of `Unreachable` was returned.
+    Miss(IpAddress),
+
+    /// An entry was removed to make room for another one.
+    Evicted(Neighbor),
+
+    /// An entry was removed at the end of its lifetime.
+    ///
+    /// Entries without a hardware address were looked up but never answered.
+    Expired(Neighbor),
+
+    /// A missing address could not be looked up as the cache was full.
+    ///
+    /// An error of `Exhausted` was returned when sending.
+    Unresolved(IpAddress),
+}
+
+/// An observer of the neighbor resolution of a cache.
+///
+/// Registered with [`Cache::set_events`], the cache calls it synchronously from within the
+/// layers, for example to log the events or to trace why sending to a neighbor failed.
+///
+/// [`Cache::set_events`]: struct.NeighborCache.html#method.set_events
+pub trait Events {
+    /// An event of the neighbor resolution happened.
+    fn event(&mut self, event: Event);
+}
+
 /// Errors that can occur when adding a new ARP result.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Error {
@@ -103,10 +189,11 @@ pub enum Error {
 /// makes insertion and deletion potentially costly but it is bounded by the size of the slice
 /// which is chosen by the user. If your use case requires a different performance characteristic,
 /// feel free to change the code (and upstream your improvement if possible).
-#[derive(Debug)]
 pub struct Cache<'a> {
     storage:      Ordered<'a, Neighbor>,
     silent_until: Instant,
+    statistics:   Statistics,
+    events:       Option<&'a mut dyn Events>,
 }
 
 /// Iterator over missing entries.
@@ -114,6 +201,11 @@ pub struct Missing<'a> {
     inner: slice::Iter<'a, Neighbor>,
 }
 
+/// Iterator over entries with a known hardware address.
+pub struct Entries<'a> {
+    inner: slice::Iter<'a, Neighbor>,
+}
+
 /// A part of the neighbor table.
 ///
 /// For lookup purposes only. Even without the additional metadata within the cache itself we can
@@ -129,6 +221,17 @@ pub struct Missing<'a> {
 #[repr(transparent)]
 pub struct Table([Neighbor]);
 
+impl fmt::Debug for Cache<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Cache")
+            .field("storage", &self.storage)
+            .field("silent_until", &self.silent_until)
+            .field("statistics", &self.statistics)
+            .field("events", &self.events.is_some())
+            .finish()
+    }
+}
+
 impl<'a> Cache<'a> {
     /// Neighbor entry lifetime, in milliseconds.
     pub(crate) const ENTRY_LIFETIME: Duration = Duration::from_millis(60_000);
@@ -147,9 +250,54 @@ impl<'a> Cache<'a> {
     /// The backing storage is not cleared and can be arbitrarily pre-filled. Be careful as having
     /// duplicate entries for the same protocol address may make some functions panic. This is
     /// currently not checked beforehand!
+    ///
+    /// Unlike `new` this is a `const fn`, for example for a cache in a `static`.
     // TODO: remove duplicate entires, e.g. `slice::partition_dedup_by_key` once stable.
-    pub fn import(storage: Ordered<'a, Neighbor>) -> Self {
-        Cache { storage, silent_until: Instant::from_millis(0) }
+    pub const fn import(storage: Ordered<'a, Neighbor>) -> Self {
+        Cache {
+            storage,
+            silent_until: Instant::ZERO,
+            statistics: Statistics::ZERO,
+            events: None,
+        }
+    }
+
+    /// Get the counters of the neighbor resolution.
+    pub fn statistics(&self) -> Statistics {
+        self.statistics
+    }
+
+    /// Register an observer of the neighbor resolution.
+    ///
+    /// See [`NeighborEvents`] for when it is called.
+    ///
+    /// [`NeighborEvents`]: trait.NeighborEvents.html
+    pub fn set_events(&mut self, events: &'a mut dyn Events) {
+        self.events = Some(events);
+    }
+
+    /// Remove the observer of the neighbor resolution.
+    pub fn clear_events(&mut self) {
+        self.events = None;
+    }
+
+    /// Count an event and pass it to the observer.
+    pub(crate) fn record(&mut self, event: Event) {
+        let counter = match event {
+            Event::RequestSent(_) => &mut self.statistics.requests_sent,
+            Event::RequestAnswered(_) => &mut self.statistics.requests_answered,
+            Event::ReplyReceived(_, _) => &mut self.statistics.replies_received,
+            Event::Hit(_) => &mut self.statistics.hits,
+            Event::Miss(_) => &mut self.statistics.misses,
+            Event::Evicted(_) => &mut self.statistics.evicted,
+            Event::Expired(_) => &mut self.statistics.expired,
+            Event::Unresolved(_) => &mut self.statistics.unresolved,
+        };
+        *counter += 1;
+
+        if let Some(events) = &mut self.events {
+            events.event(event);
+        }
     }
 
     /// Add a lookup entry.
@@ -186,6 +334,54 @@ impl<'a> Cache<'a> {
         self.update_or_insert(protocol_addr, Mapping::Address(hardware_addr), timestamp)
     }
 
+    /// Add a static entry containing a MAC address.
+    ///
+    /// The entry never expires and is neither evicted nor replaced by entries learned from the
+    /// network. Only another static entry for the same protocol address replaces it, or it can be
+    /// removed explicitly.
+    pub fn fill_static(
+        &mut self,
+        protocol_addr: IpAddress,
+        hardware_addr: EthernetAddress,
+    ) -> Result<(), Error> {
+        self.insert_entry(Neighbor::new_static(protocol_addr, hardware_addr), Expiration::Never)
+    }
+
+    /// Add an entry with an explicit expiration.
+    ///
+    /// Unlike `fill` the expiration is not derived from the current time but taken as is from the
+    /// neighbor, e.g. to import the remaining lifetime of an entry from the operating system or a
+    /// central controller. An existing entry for the same protocol address is replaced, unless it
+    /// is static and the new one is not.
+    pub fn insert(&mut self, neighbor: Neighbor) -> Result<(), Error> {
+        self.insert_entry(neighbor, Expiration::Never)
+    }
+
+    /// Add many entries with an explicit expiration.
+    ///
+    /// Stops at the first entry that could not be inserted and returns its error. All entries
+    /// before it have been inserted at that point. See [`insert`] for details.
+    ///
+    /// [`insert`]: #method.insert
+    pub fn insert_all<I>(&mut self, neighbors: I) -> Result<(), Error>
+        where I: IntoIterator<Item=Neighbor>,
+    {
+        neighbors.into_iter().try_for_each(|neighbor| self.insert(neighbor))
+    }
+
+    /// Remove the entry of a protocol address.
+    ///
+    /// Returns the removed entry, regardless of whether it was still alive.
+    pub fn remove(&mut self, protocol_addr: IpAddress) -> Result<Neighbor, Error> {
+        let index = self.storage.ordered_slice()
+            .binary_search_by_key(&protocol_addr, |neighbor| neighbor.protocol_addr)
+            .map_err(|_| Error::EntryNotFound)?;
+        let removed = self.storage[index];
+        self.storage.pop(index)
+            .expect("Entry we just found is valid.");
+        Ok(removed)
+    }
+
     /// Add an entry.
     ///
     /// Provide the current timestamp or `None` to disable expiration.
@@ -195,17 +391,26 @@ impl<'a> Cache<'a> {
         hardware_addr: Mapping,
         timestamp: Option<Instant>,
     ) -> Result<(), Error> {
-        debug_assert!(protocol_addr.is_unicast());
-        if let Mapping::Address(hw_addr) = hardware_addr {
-            debug_assert!(hw_addr.is_unicast());
-        }
-
         let new_neighbor = Neighbor {
             protocol_addr,
             hardware_addr,
             expires_at: timestamp.map(|ts| ts + Self::ENTRY_LIFETIME).into(),
+            is_static: false,
         };
 
+        self.insert_entry(new_neighbor, Expiration::from(timestamp))
+    }
+
+    /// Add a complete entry, as of `now`.
+    ///
+    /// The time is only relevant for not overwriting a running request with a new lookup.
+    fn insert_entry(&mut self, new_neighbor: Neighbor, now: Expiration) -> Result<(), Error> {
+        let protocol_addr = new_neighbor.protocol_addr;
+        debug_assert!(protocol_addr.is_unicast());
+        if let Mapping::Address(hw_addr) = new_neighbor.hardware_addr {
+            debug_assert!(hw_addr.is_unicast());
+        }
+
         // Is this already mapped?
         let exists = self.storage.ordered_slice()
             .binary_search_by_key(&protocol_addr, |neighbor| neighbor.protocol_addr);
@@ -213,8 +418,13 @@ impl<'a> Cache<'a> {
             let old = self.storage[index];
             assert_eq!(old.protocol_addr, new_neighbor.protocol_addr);
 
+            if old.is_static && !new_neighbor.is_static {
+                // Configured by the user, takes precedence over anything learned.
+                return Ok(())
+            }
+
             if let (Mapping::Requesting, Mapping::LookingFor) = (old.hardware_addr, new_neighbor.hardware_addr) {
-                if old.expires_at >= Expiration::from(timestamp) {
+                if old.expires_at >= now {
                     // A not-yet expired request is currently running. Simply do nothing.
                     return Ok(())
                 }
@@ -231,17 +441,20 @@ impl<'a> Cache<'a> {
                 entry
             },
             None => {
-                // find the oldest entry.
+                // find the oldest entry that is not static.
                 let (idx, oldest) = self.storage.ordered_slice()
                     .iter()
                     .enumerate()
+                    .filter(|(_, neighbor)| !neighbor.is_static)
                     .min_by_key(|(_, neighbor)| neighbor.expires_at)
                     .ok_or(Error::NoSpace)?;
                 if oldest.expires_at > new_neighbor.expires_at {
                     return Err(Error::ExpiresTooSoon)
                 }
+                let evicted = *oldest;
                 self.storage.pop(idx)
                     .expect("Entry we just found is valid.");
+                self.record(Event::Evicted(evicted));
                 self.storage.init()
                     .expect("At least one entry is now free")
             },
@@ -256,6 +469,53 @@ impl<'a> Cache<'a> {
     }
 }
 
+impl Cache<'_> {
+    /// Remove all expired entries.
+    ///
+    /// Entries without expiration are kept.
+    pub fn expire(&mut self, timestamp: Instant) {
+        while let Some(idx) = self.storage.ordered_slice()
+            .iter()
+            .position(|neighbor| neighbor.is_expired(timestamp))
+        {
+            let expired = self.storage[idx];
+            self.storage.pop(idx)
+                .expect("Entry we just found is valid.");
+            self.record(Event::Expired(expired));
+        }
+    }
+
+    /// The next point in time at which the cache requires attention.
+    ///
+    /// This is the current time if an entry is waiting for a request to be sent and otherwise the
+    /// earliest time at which an entry expires.
+    pub fn next_timer(&self, timestamp: Instant) -> Expiration {
+        self.storage.ordered_slice()
+            .iter()
+            .filter(|neighbor| neighbor.is_alive(timestamp))
+            .map(|neighbor| if neighbor.looking_for() {
+                Expiration::When(timestamp)
+            } else {
+                neighbor.expires_at
+            })
+            .min()
+            .unwrap_or(Expiration::Never)
+    }
+}
+
+impl Statistics {
+    const ZERO: Statistics = Statistics {
+        requests_sent: 0,
+        requests_answered: 0,
+        replies_received: 0,
+        hits: 0,
+        misses: 0,
+        evicted: 0,
+        expired: 0,
+        unresolved: 0,
+    };
+}
+
 impl Table {
     /// Create a table.
     ///
@@ -313,9 +573,50 @@ impl Table {
             inner: self.0.iter(),
         }
     }
+
+    /// An iterator over all entries mapped to a hardware address.
+    ///
+    /// This includes entries that have expired but were not yet removed, check their expiration
+    /// if those should not be exported.
+    pub fn entries(&self) -> Entries<'_> {
+        Entries {
+            inner: self.0.iter(),
+        }
+    }
 }
 
 impl Neighbor {
+    /// Create an entry mapping a protocol address to a hardware address.
+    ///
+    /// The entry is valid until `expires_at`. Use this to fill a cache from an external source,
+    /// see [`Cache::insert`].
+    ///
+    /// [`Cache::insert`]: struct.NeighborCache.html#method.insert
+    pub fn new(
+        protocol_addr: IpAddress,
+        hardware_addr: EthernetAddress,
+        expires_at: Expiration,
+    ) -> Self {
+        Neighbor {
+            protocol_addr,
+            hardware_addr: Mapping::Address(hardware_addr),
+            expires_at,
+            is_static: false,
+        }
+    }
+
+    /// Create a static entry mapping a protocol address to a hardware address.
+    ///
+    /// See [`Cache::fill_static`].
+    ///
+    /// [`Cache::fill_static`]: struct.NeighborCache.html#method.fill_static
+    pub fn new_static(protocol_addr: IpAddress, hardware_addr: EthernetAddress) -> Self {
+        Neighbor {
+            is_static: true,
+            ..Neighbor::new(protocol_addr, hardware_addr, Expiration::Never)
+        }
+    }
+
     /// Get the protocol address stored in this entry.
     pub fn protocol_addr(&self) -> IpAddress {
         self.protocol_addr
@@ -330,6 +631,11 @@ impl Neighbor {
         }
     }
 
+    /// Get the point in time at which this entry is no longer valid.
+    pub fn expires_at(&self) -> Expiration {
+        self.expires_at
+    }
+
     /// Check if the entry should still be considered valid.
     ///
     /// This is the negation of `is_expired`.
@@ -344,6 +650,11 @@ impl Neighbor {
         !self.is_alive(ts)
     }
 
+    /// Check if the entry was configured as static.
+    pub fn is_static(&self) -> bool {
+        self.is_static
+    }
+
     /// If this address mapping is unknown and should be requested.
     pub fn looking_for(&self) -> bool {
         self.hardware_addr == Mapping::LookingFor
@@ -377,6 +688,16 @@ impl Iterator for Missing<'_> {
     }
 }
 
+impl Iterator for Entries<'_> {
+    type Item = Neighbor;
+
+    fn next(&mut self) -> Option<Neighbor> {
+        self.inner.by_ref()
+            .find(|entry| entry.hardware_addr().is_some())
+            .copied()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -419,6 +740,27 @@ mod test {
                    None);
     }
 
+    #[test]
+    fn timers() {
+        let mut cache_storage = [Default::default(); 3];
+        let mut cache = Cache::new(&mut cache_storage[..]);
+        let start = Instant::from_millis(0);
+        let later = start + Cache::ENTRY_LIFETIME * 2;
+
+        assert_eq!(cache.next_timer(start), Expiration::Never);
+        cache.fill(MOCK_IP_ADDR_1, HADDR_A, Some(start)).unwrap();
+        cache.fill(MOCK_IP_ADDR_2, HADDR_B, None).unwrap();
+        assert_eq!(cache.next_timer(start), Expiration::When(start + Cache::ENTRY_LIFETIME));
+
+        cache.fill_looking(MOCK_IP_ADDR_3, Some(start)).unwrap();
+        assert_eq!(cache.next_timer(start), Expiration::When(start));
+
+        cache.expire(later);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.lookup_pure(MOCK_IP_ADDR_2, later), Some(HADDR_B));
+        assert_eq!(cache.next_timer(later), Expiration::Never);
+    }
+
     #[test]
     fn replace() {
         let mut cache_storage = [Default::default(); 3];
@@ -452,6 +794,77 @@ mod test {
             .unwrap();
         assert_eq!(cache.lookup_pure(MOCK_IP_ADDR_2, Instant::from_millis(1000)), None);
         assert_eq!(cache.lookup_pure(MOCK_IP_ADDR_4, Instant::from_millis(1000)), Some(HADDR_D));
+        assert_eq!(cache.statistics().evicted, 1);
+
+        cache.expire(Instant::from_millis(1000) + Cache::ENTRY_LIFETIME);
+        assert_eq!(cache.len(), 0);
+        assert_eq!(cache.statistics().expired, 3);
+    }
+
+    #[test]
+    fn sync() {
+        let mut cache_storage = [Default::default(); 3];
+        let mut cache = Cache::new(&mut cache_storage[..]);
+        let start = Instant::from_millis(0);
+        let later = Expiration::When(start + Cache::ENTRY_LIFETIME * 2);
+
+        cache.fill(MOCK_IP_ADDR_1, HADDR_A, Some(start)).unwrap();
+        cache.fill_looking(MOCK_IP_ADDR_2, Some(start)).unwrap();
+        cache.insert_all(vec![
+            Neighbor::new(MOCK_IP_ADDR_3, HADDR_C, later),
+            Neighbor::new(MOCK_IP_ADDR_1, HADDR_B, Expiration::Never),
+        ]).unwrap();
+
+        // Only resolved entries are exported, with their expiration.
+        let exported: Vec<_> = cache.entries()
+            .map(|entry| (entry.protocol_addr(), entry.hardware_addr(), entry.expires_at()))
+            .collect();
+        assert_eq!(exported, [
+            (MOCK_IP_ADDR_1, Some(HADDR_B), Expiration::Never),
+            (MOCK_IP_ADDR_3, Some(HADDR_C), later),
+        ]);
+
+        let removed = cache.remove(MOCK_IP_ADDR_3).unwrap();
+        assert_eq!(removed, Neighbor::new(MOCK_IP_ADDR_3, HADDR_C, later));
+        assert_eq!(cache.remove(MOCK_IP_ADDR_3), Err(Error::EntryNotFound));
+        assert_eq!(cache.lookup_pure(MOCK_IP_ADDR_3, start), None);
+
+        // Pending lookups make room for imported entries but permanent ones do not.
+        cache.insert_all(vec![
+            Neighbor::new(MOCK_IP_ADDR_3, HADDR_C, Expiration::Never),
+            Neighbor::new(MOCK_IP_ADDR_4, HADDR_D, Expiration::Never),
+        ]).unwrap();
+        assert_eq!(cache.entries().count(), 3);
+        assert_eq!(cache.insert(Neighbor::new(MOCK_IP_ADDR_2, HADDR_B, later)),
+                   Err(Error::ExpiresTooSoon));
+    }
+
+    #[test]
+    fn static_entries() {
+        let mut cache_storage = [Default::default(); 2];
+        let mut cache = Cache::new(&mut cache_storage[..]);
+        let start = Instant::from_millis(0);
+
+        cache.fill_static(MOCK_IP_ADDR_1, HADDR_A).unwrap();
+        cache.fill(MOCK_IP_ADDR_2, HADDR_B, None).unwrap();
+
+        // Learned and imported mappings do not replace the static entry.
+        cache.fill(MOCK_IP_ADDR_1, HADDR_C, Some(start)).unwrap();
+        cache.insert(Neighbor::new(MOCK_IP_ADDR_1, HADDR_C, Expiration::Never)).unwrap();
+        assert_eq!(cache.lookup_pure(MOCK_IP_ADDR_1, start), Some(HADDR_A));
+        assert!(cache[0].is_static());
+
+        // Only the other permanent entry is evicted.
+        cache.fill(MOCK_IP_ADDR_3, HADDR_C, None).unwrap();
+        assert_eq!(cache.lookup_pure(MOCK_IP_ADDR_2, start), None);
+        assert_eq!(cache.fill_static(MOCK_IP_ADDR_4, HADDR_D), Ok(()));
+        assert_eq!(cache.fill_static(MOCK_IP_ADDR_2, HADDR_B), Err(Error::NoSpace));
+
+        cache.expire(start + Cache::ENTRY_LIFETIME * 2);
+        assert_eq!(cache.lookup_pure(MOCK_IP_ADDR_1, start), Some(HADDR_A));
+        cache.fill_static(MOCK_IP_ADDR_1, HADDR_B).unwrap();
+        assert_eq!(cache.lookup_pure(MOCK_IP_ADDR_1, start), Some(HADDR_B));
+        assert_eq!(cache.remove(MOCK_IP_ADDR_1).map(|entry| entry.is_static()), Ok(true));
     }
 
     #[test]
@@ -85,6 +85,17 @@ impl<'a, P: Payload> In<'a, P> {
         let payload = self.packet.into_inner().into_inner();
         Raw::new(self.handle, payload)
     }
+
+    /// Exchange the buffer of the packet for a spare, see [`eth::InPacket::retain`].
+    ///
+    /// [`eth::InPacket::retain`]: ../eth/struct.InPacket.html#method.retain
+    pub fn retain(self, spare: &mut P) -> Result<()>
+    where
+        P: PayloadMut,
+    {
+        let Raw { mut handle, payload } = self.deinit();
+        handle.inner.retain(payload, spare)
+    }
 }
 
 impl<'a, P: PayloadMut> In<'a, P> {
@@ -1,7 +1,10 @@
+use core::cell::Cell;
+
 use crate::managed::Slice;
 use crate::nic::{external::External, Device};
-use crate::layer::{eth, ip, arp};
-use crate::wire::{EthernetAddress, Ipv4Address, IpCidr};
+use crate::layer::{eth, ip, arp, Detail, Error, Operation};
+use crate::time::{Expiration, Instant};
+use crate::wire::{EthernetAddress, Ipv4Address, Ipv4Cidr, IpCidr, IpProtocol, IpSubnet, Ipv4Subnet};
 use crate::wire::{ethernet_frame, EthernetProtocol, EthernetRepr};
 use crate::wire::{arp_packet, ArpOperation, ArpRepr};
 
@@ -67,3 +70,116 @@ fn simple_arp() {
     assert_eq!(arp.target_hardware_addr(), MAC_ADDR_OTHER);
     assert_eq!(arp.target_protocol_addr(), IP_ADDR_OTHER);
 }
+
+#[test]
+fn resolution_failures() {
+    struct Unresolved<'c>(&'c Cell<usize>);
+
+    impl arp::NeighborEvents for Unresolved<'_> {
+        fn event(&mut self, event: arp::NeighborEvent) {
+            if let arp::NeighborEvent::Unresolved(addr) = event {
+                assert_eq!(addr, IP_ADDR_OTHER.into());
+                self.0.set(self.0.get() + 1);
+            }
+        }
+    }
+
+    let unresolved = Cell::new(0);
+    let mut events = Unresolved(&unresolved);
+
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+    let mut eth = eth::Endpoint::new(MAC_ADDR_HOST);
+    // The only entry of the cache is permanently occupied.
+    let mut neighbors = arp::NeighborCache::new(vec![arp::Neighbor::default(); 1]);
+    let permanent = arp::Neighbor::new(IP_ADDR_HOST.into(), MAC_ADDR_HOST, Expiration::Never);
+    neighbors.insert(permanent).unwrap();
+    neighbors.set_events(&mut events);
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_HOST.into(), 24),
+        ip::Routes::new(vec![ip::Route::unspecified(); 1]),
+        neighbors);
+
+    let sent = nic.tx(1, eth.send(ip.send_with(|packet: ip::RawPacket<_>| {
+        let init = ip::Init {
+            source: IpSubnet::from(Ipv4Subnet::ANY).into(),
+            dst_addr: IP_ADDR_OTHER.into(),
+            protocol: IpProtocol::Unknown(0xEF),
+            payload: 0,
+            hop_limit: None,
+            dscp: 0,
+        };
+        assert_eq!(packet.prepare(init).err(), Some(Error::Exhausted));
+    })));
+    assert_eq!(sent, Ok(0));
+
+    let statistics = ip.neighbors().statistics();
+    assert_eq!(statistics.misses, 1);
+    assert_eq!(statistics.unresolved, 1);
+    assert_eq!(statistics.hits, 0);
+    assert_eq!(unresolved.get(), 1);
+    let failure = ip.last_failure().unwrap();
+    assert_eq!(failure.detail, Detail::NeighborCacheFull(IP_ADDR_OTHER.into()));
+    assert_eq!(failure.operation, Operation::Resolve);
+
+    // Expiring does not touch the permanent entry.
+    ip.neighbors_mut().expire(Instant::from_secs(3600));
+    assert_eq!(ip.neighbors().statistics().expired, 0);
+}
+
+/// Write an arp request of the other host into a buffer.
+fn request(buffer: &mut Vec<u8>, source: Ipv4Address, target: Ipv4Address) {
+    buffer.resize(14 + 28, 0u8);
+    let eth = ethernet_frame::new_unchecked_mut(buffer);
+    EthernetRepr {
+        src_addr: MAC_ADDR_OTHER,
+        dst_addr: EthernetAddress::BROADCAST,
+        ethertype: EthernetProtocol::Arp,
+    }.emit(eth);
+    let arp = arp_packet::new_unchecked_mut(eth.payload_mut_slice());
+    ArpRepr::EthernetIpv4 {
+        operation: ArpOperation::Request,
+        source_hardware_addr: MAC_ADDR_OTHER,
+        source_protocol_addr: source,
+        target_hardware_addr: EthernetAddress([0; 6]),
+        target_protocol_addr: target,
+    }.emit(arp);
+}
+
+#[test]
+fn proxy_arp() {
+    const IP_ADDR_PROXIED: Ipv4Address = Ipv4Address::new(10, 0, 0, 7);
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+    let mut eth = eth::Endpoint::new(MAC_ADDR_HOST);
+    let mut routes = [ip::Route::unspecified(); 2];
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_HOST.into(), 24),
+        ip::Routes::new(&mut routes[..]),
+        arp::NeighborCache::new(Slice::empty()));
+    let mut arp = arp::Endpoint::new(arp::NeighborCache::new(Slice::empty()));
+    let behind = Ipv4Subnet::from_cidr(Ipv4Cidr::new(Ipv4Address::new(10, 0, 0, 0), 24));
+    arp.set_proxy(vec![behind]);
+
+    request(nic.get_mut(0).unwrap(), IP_ADDR_OTHER, IP_ADDR_PROXIED);
+    nic.receive_all();
+    assert_eq!(nic.rx(1, eth.recv(arp.answer(&mut ip))), Ok(1));
+
+    let buffer = nic.get_mut(0).unwrap();
+    let eth_frame = ethernet_frame::new_unchecked_mut(buffer);
+    assert_eq!(eth_frame.dst_addr(), MAC_ADDR_OTHER);
+    let reply = arp_packet::new_unchecked_mut(eth_frame.payload_mut_slice());
+    assert_eq!(reply.operation(), ArpOperation::Reply);
+    assert_eq!(reply.source_hardware_addr(), MAC_ADDR_HOST);
+    assert_eq!(reply.source_protocol_addr(), IP_ADDR_PROXIED);
+
+    // A host of the prefix probing for its own address gets no answer, nor do other prefixes.
+    for &(source, target) in &[
+        (IP_ADDR_PROXIED, IP_ADDR_PROXIED),
+        (IP_ADDR_OTHER, Ipv4Address::new(10, 0, 1, 7)),
+    ] {
+        request(nic.get_mut(0).unwrap(), source, target);
+        nic.receive_all();
+        assert_eq!(nic.rx(1, eth.recv(arp.answer(&mut ip))), Ok(1));
+        let buffer = nic.get_mut(0).unwrap();
+        let eth_frame = ethernet_frame::new_unchecked_mut(buffer);
+        let request = arp_packet::new_unchecked_mut(eth_frame.payload_mut_slice());
+        assert_eq!(request.operation(), ArpOperation::Request);
+    }
+}
@@ -0,0 +1,230 @@
+use crate::layer::{Error, FnHandler, Result};
+use crate::managed::Ordered;
+use crate::nic::{self, Device, Handle};
+use crate::time::{Duration, Expiration, Instant};
+use crate::wire::{ethernet_frame, EthernetAddress, Payload, PayloadMut};
+
+/// An entry of the forwarding database.
+///
+/// Maps the address of a station to the port on which it was seen, until the entry expires.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Station {
+    addr: EthernetAddress,
+    port: usize,
+    expires_at: Expiration,
+}
+
+/// A learning bridge between several devices.
+///
+/// Contains the forwarding database, an ordered table of stations. Its size is bounded by the
+/// storage chosen by the user. When it is full, learning evicts the station that expires first.
+#[derive(Debug)]
+pub struct Bridge<'a> {
+    stations: Ordered<'a, Station>,
+    aging_time: Duration,
+}
+
+impl<'a> Bridge<'a> {
+    /// The default time after which a station that has not been seen is forgotten.
+    pub const DEFAULT_AGING_TIME: Duration = Duration::from_secs(300);
+
+    /// Create a bridge with storage for its forwarding database.
+    ///
+    /// The storage is created logically empty.
+    pub fn new<T>(storage: T) -> Self
+        where T: Into<Ordered<'a, Station>>
+    {
+        Bridge {
+            stations: storage.into(),
+            aging_time: Self::DEFAULT_AGING_TIME,
+        }
+    }
+
+    /// Change the time after which a station that has not been seen is forgotten.
+    ///
+    /// Only affects entries learned afterwards.
+    pub fn set_aging_time(&mut self, aging_time: Duration) {
+        self.aging_time = aging_time;
+    }
+
+    /// The stations in the forwarding database, ordered by address.
+    ///
+    /// This includes expired entries that have not yet been removed.
+    pub fn stations(&self) -> &[Station] {
+        self.stations.ordered_slice()
+    }
+
+    /// Find the port of a station that has not expired.
+    pub fn lookup(&self, addr: EthernetAddress, now: Instant) -> Option<usize> {
+        let idx = self.find(addr)?;
+        let station = &self.stations.ordered_slice()[idx];
+        if station.is_expired(now) {
+            None
+        } else {
+            Some(station.port)
+        }
+    }
+
+    /// Record that a station was seen on a port.
+    ///
+    /// Fails with `Exhausted` if the database is full of entries that live longer.
+    pub fn learn(&mut self, addr: EthernetAddress, port: usize, now: Instant) -> Result<()> {
+        self.insert(Station {
+            addr,
+            port,
+            expires_at: Expiration::When(now + self.aging_time),
+        })
+    }
+
+    /// Add a station that never expires, for example to pin the location of a router.
+    pub fn insert_static(&mut self, addr: EthernetAddress, port: usize) -> Result<()> {
+        self.insert(Station {
+            addr,
+            port,
+            expires_at: Expiration::Never,
+        })
+    }
+
+    /// Forget a station.
+    ///
+    /// Returns the removed entry, regardless of whether it had expired.
+    pub fn remove(&mut self, addr: EthernetAddress) -> Option<Station> {
+        let idx = self.find(addr)?;
+        let removed = self.stations.ordered_slice()[idx];
+        self.stations.pop(idx)?;
+        Some(removed)
+    }
+
+    /// Remove all expired stations.
+    pub fn expire(&mut self, now: Instant) {
+        while let Some(idx) = self.stations.ordered_slice()
+            .iter()
+            .position(|station| station.is_expired(now))
+        {
+            self.stations.pop(idx)
+                .expect("Entry we just found is valid.");
+        }
+    }
+
+    /// Forward frames received on one port to the others.
+    ///
+    /// Receives up to `max` frames from the device at index `ingress` of the ports, learning their
+    /// source addresses. Each frame is copied to the port of its destination or flooded to all
+    /// other ports. Frames addressed to a station on the ingress port itself are dropped. Returns
+    /// the number of received frames.
+    pub fn forward<D>(&mut self, ports: &mut [D], ingress: usize, max: usize) -> Result<usize>
+    where
+        D: Device,
+        D::Payload: PayloadMut,
+    {
+        let (before, rest) = ports.split_at_mut(ingress);
+        let (port, after) = rest.split_first_mut().ok_or(Error::Illegal)?;
+        let fdb = self;
+
+        port.rx(max, FnHandler(|packet: nic::Packet<D::Handle, D::Payload>| {
+            let frame = match ethernet_frame::new_checked(packet.payload.payload()) {
+                Ok(frame) => frame,
+                Err(_) => return,
+            };
+            let now = packet.handle.info().timestamp();
+            let (src_addr, dst_addr) = (frame.src_addr(), frame.dst_addr());
+            let bytes = frame.as_bytes();
+
+            if src_addr.is_unicast() {
+                // A full database only means we flood some more.
+                let _ = fdb.learn(src_addr, ingress, now);
+            }
+
+            let egress = if dst_addr.is_unicast() {
+                fdb.lookup(dst_addr, now)
+            } else {
+                None
+            };
+
+            match egress {
+                Some(egress) if egress == ingress => (),
+                Some(egress) if egress < ingress => transmit(&mut before[egress], bytes),
+                Some(egress) => if let Some(port) = after.get_mut(egress - ingress - 1) {
+                    transmit(port, bytes)
+                },
+                None => before.iter_mut()
+                    .chain(after.iter_mut())
+                    .for_each(|port| transmit(port, bytes)),
+            }
+        }))
+    }
+
+    fn find(&self, addr: EthernetAddress) -> Option<usize> {
+        self.stations.ordered_slice()
+            .binary_search_by_key(&addr, |station| station.addr)
+            .ok()
+    }
+
+    fn insert(&mut self, station: Station) -> Result<()> {
+        if let Some(idx) = self.find(station.addr) {
+            let old = self.stations.ordered_slice()[idx];
+            if old.expires_at == Expiration::Never && station.expires_at != Expiration::Never {
+                // Learning does not move a static entry.
+                return Ok(());
+            }
+
+            self.stations.replace_at(idx, station)
+                .expect("Sorting didn't change since we only have one entry per address");
+            return Ok(());
+        }
+
+        if self.stations.init().is_none() {
+            let (idx, oldest) = self.stations.ordered_slice()
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, station)| station.expires_at)
+                .ok_or(Error::Exhausted)?;
+            if oldest.expires_at > station.expires_at {
+                return Err(Error::Exhausted);
+            }
+            self.stations.pop(idx)
+                .expect("Entry we just found is valid.");
+        }
+
+        *self.stations.init().expect("At least one entry is now free") = station;
+        self.stations.push()
+            .expect("There was one to insert");
+        Ok(())
+    }
+}
+
+impl Station {
+    /// The ethernet address of the station.
+    pub fn addr(&self) -> EthernetAddress {
+        self.addr
+    }
+
+    /// The index of the port on which the station was seen.
+    pub fn port(&self) -> usize {
+        self.port
+    }
+
+    /// The time at which the entry expires.
+    pub fn expires_at(&self) -> Expiration {
+        self.expires_at
+    }
+
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at < Expiration::When(now)
+    }
+}
+
+/// Copy a frame into the next transmit buffer of a port, if it has one.
+fn transmit<D>(port: &mut D, bytes: &[u8])
+where
+    D: Device,
+    D::Payload: PayloadMut,
+{
+    let _ = port.tx(1, FnHandler(|packet: nic::Packet<D::Handle, D::Payload>| {
+        if packet.payload.resize(bytes.len()).is_err() {
+            return;
+        }
+        packet.payload.payload_mut().as_mut_slice().copy_from_slice(bytes);
+        let _ = packet.handle.queue();
+    }));
+}
@@ -0,0 +1,24 @@
+//! A software bridge forwarding frames between devices.
+//!
+//! The bridge is a transparent layer 2 switch. It learns the ethernet source addresses of frames
+//! received on each attached device, its ports, and forwards frames to the port on which their
+//! destination was last seen. Frames to unknown destinations, broadcast and multicast frames are
+//! flooded to all other ports. The learned stations are kept in a forwarding database within
+//! storage provided by the user and age out when they have not been seen for some time.
+//!
+//! The ports are a slice of devices of the same type, which also defines their indices. Poll each
+//! of them in turn with [`Bridge::forward`]. Frames are copied from the receive buffer of one
+//! device into transmit buffers of the others and dropped if no such buffer is available.
+//!
+//! The bridge does not terminate traffic itself and has no address of its own. It also does not
+//! implement the spanning tree protocol, so the ports must not form a loop.
+//!
+//! [`Bridge::forward`]: struct.Bridge.html#method.forward
+mod endpoint;
+#[cfg(test)]
+mod tests;
+
+pub use endpoint::{
+    Bridge,
+    Station,
+};
@@ -0,0 +1,88 @@
+use super::*;
+use crate::managed::Slice;
+use crate::nic::external::External;
+use crate::time::{Duration, Instant};
+use crate::wire::{EthernetAddress, EthernetProtocol, EthernetRepr, ethernet_frame};
+
+type Port = External<Vec<Vec<u8>>>;
+
+const MAC_A: EthernetAddress = EthernetAddress([0x52, 0x54, 0, 0, 0, 1]);
+const MAC_B: EthernetAddress = EthernetAddress([0x52, 0x54, 0, 0, 0, 2]);
+const MAC_C: EthernetAddress = EthernetAddress([0x52, 0x54, 0, 0, 0, 3]);
+
+fn frame(src_addr: EthernetAddress, dst_addr: EthernetAddress) -> Vec<u8> {
+    let mut buffer = vec![0; 64];
+    EthernetRepr {
+        src_addr,
+        dst_addr,
+        ethertype: EthernetProtocol::Unknown(0xBEEF),
+    }.emit(ethernet_frame::new_unchecked_mut(&mut buffer));
+    buffer
+}
+
+/// Ports with one buffer to receive and two to send into.
+fn ports() -> Vec<Port> {
+    (0..3).map(|_| {
+        let mut port = External::new_send(vec![vec![]; 3]);
+        port.set_one_past_receive(1);
+        port
+    }).collect()
+}
+
+/// Let one port receive a frame and forward it, returning the ports on which it was sent.
+fn forward(bridge: &mut Bridge, ports: &mut [Port], ingress: usize, frame: Vec<u8>)
+    -> Vec<usize>
+{
+    for port in ports.iter_mut() {
+        port.reset_send();
+    }
+    *ports[ingress].get_mut(0).unwrap() = frame.clone();
+    ports[ingress].reset_receive();
+    assert_eq!(bridge.forward(ports, ingress, 1), Ok(1));
+
+    ports.iter()
+        .enumerate()
+        .filter(|(_, port)| port.to_send() < 2)
+        .inspect(|(_, port)| assert_eq!(port.get(1), Some(&frame)))
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+#[test]
+fn learning() {
+    let mut bridge = Bridge::new(Slice::from(vec![Station::default(); 4]));
+    let mut ports = ports();
+
+    // Unknown destinations are flooded, their sources learned.
+    assert_eq!(forward(&mut bridge, &mut ports, 0, frame(MAC_A, MAC_B)), [1, 2]);
+    assert_eq!(bridge.lookup(MAC_A, Instant::from_secs(0)), Some(0));
+    assert_eq!(forward(&mut bridge, &mut ports, 1, frame(MAC_B, MAC_A)), [0]);
+    assert_eq!(forward(&mut bridge, &mut ports, 2, frame(MAC_C, MAC_B)), [1]);
+    assert_eq!(forward(&mut bridge, &mut ports, 2, frame(MAC_C, EthernetAddress::BROADCAST)),
+        [0, 1]);
+    // Traffic local to the ingress port is not forwarded.
+    assert_eq!(forward(&mut bridge, &mut ports, 0, frame(MAC_C, MAC_A)), []);
+    assert_eq!(bridge.lookup(MAC_C, Instant::from_secs(0)), Some(0));
+    assert_eq!(bridge.stations().len(), 3);
+}
+
+#[test]
+fn aging() {
+    let mut bridge = Bridge::new(Slice::from(vec![Station::default(); 2]));
+    bridge.set_aging_time(Duration::from_secs(10));
+
+    assert_eq!(bridge.learn(MAC_A, 0, Instant::from_secs(0)), Ok(()));
+    assert_eq!(bridge.insert_static(MAC_B, 1), Ok(()));
+    // The station that expires first is evicted, static ones are not moved by learning.
+    assert_eq!(bridge.learn(MAC_C, 2, Instant::from_secs(1)), Ok(()));
+    assert_eq!(bridge.learn(MAC_B, 2, Instant::from_secs(1)), Ok(()));
+    assert_eq!(bridge.lookup(MAC_A, Instant::from_secs(1)), None);
+    assert_eq!(bridge.lookup(MAC_B, Instant::from_secs(1)), Some(1));
+    assert_eq!(bridge.lookup(MAC_C, Instant::from_secs(11)), Some(2));
+    assert_eq!(bridge.lookup(MAC_C, Instant::from_secs(12)), None);
+
+    bridge.expire(Instant::from_secs(12));
+    assert_eq!(bridge.stations().len(), 1);
+    assert_eq!(bridge.remove(MAC_B).map(|station| station.port()), Some(1));
+    assert!(bridge.stations().is_empty());
+}
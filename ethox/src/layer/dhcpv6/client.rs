@@ -0,0 +1,590 @@
+use crate::layer::{ip, udp, Result};
+use crate::layer::ip::Assignment;
+use crate::rand::Rng;
+use crate::time::{Clock, Duration, Expiration, Instant};
+use crate::wire::{EthernetAddress, IpAddress, IpCidr, IpSubnet, Ipv6Address, Ipv6Cidr};
+use crate::wire::{Payload, PayloadMut};
+use crate::wire::{dhcpv6_packet, Dhcpv6MessageType, Dhcpv6Option, Dhcpv6StatusCode};
+use crate::wire::{Dhcpv6IaAddress, Dhcpv6IaPrefix, Dhcpv6IdentityAssociation};
+use crate::wire::{DHCPV6_ALL_SERVERS, DHCPV6_CLIENT_PORT, DHCPV6_INFINITE, DHCPV6_SERVER_PORT};
+
+/// The longest DUID permitted by rfc8415 section 11.1.
+const MAX_DUID_LEN: usize = 130;
+
+/// The length of a DUID-LL for an ethernet address.
+const DUID_LEN: usize = 10;
+
+/// The length of an IA Address option without encapsulated options.
+const IA_ADDRESS_LEN: usize = 28;
+
+/// The length of an IA Prefix option without encapsulated options.
+const IA_PREFIX_LEN: usize = 29;
+
+/// Retransmission parameters of one message type, rfc8415 section 7.6.
+struct Timing {
+    /// The initial retransmission timeout.
+    initial: Duration,
+    /// The maximum retransmission timeout.
+    max: Duration,
+    /// The maximum number of transmissions before giving up on the exchange.
+    max_count: Option<u8>,
+}
+
+const SOLICIT: Timing = Timing {
+    initial: Duration::from_secs(1),
+    max: Duration::from_secs(3600),
+    max_count: None,
+};
+
+const REQUEST: Timing = Timing {
+    initial: Duration::from_secs(1),
+    max: Duration::from_secs(30),
+    max_count: Some(10),
+};
+
+/// Renew and Rebind share their parameters, both end with the next state's timer instead.
+const RENEW: Timing = Timing {
+    initial: Duration::from_secs(10),
+    max: Duration::from_secs(600),
+    max_count: None,
+};
+
+/// The state of the client.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum State {
+    /// Looking for servers with a Solicit message.
+    Soliciting,
+    /// Requesting the addresses offered by a server.
+    Requesting,
+    /// Holding a valid lease until it should be renewed.
+    Bound,
+    /// Extending the lease with the server that granted it.
+    Renewing,
+    /// Extending the lease with any server as the original one did not respond.
+    Rebinding,
+}
+
+/// The addresses and timers granted by a server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Lease {
+    /// The address assigned to the interface, with a prefix length of 128.
+    pub address: Option<Assignment>,
+    /// The prefix delegated for use on other links.
+    ///
+    /// This is not assigned to the interface. Distribute it to downstream networks, for example
+    /// by assigning addresses from it to other endpoints.
+    pub prefix: Option<Assignment>,
+    /// When to start renewing the lease with the same server.
+    pub renew_at: Expiration,
+    /// When to start rebinding the lease with any server.
+    pub rebind_at: Expiration,
+    /// When the last address or prefix of the lease becomes invalid.
+    pub expires_at: Expiration,
+}
+
+/// A DHCPv6 client for one interface.
+///
+/// The client identifies itself with a DUID-LL based on the ethernet address of the interface and
+/// uses a single identity association for each of addresses and prefixes. Transaction ids and
+/// retransmission jitter are drawn from the provided random number generator.
+pub struct Client<'a> {
+    duid: [u8; DUID_LEN],
+    iaid: u32,
+    rng: &'a mut dyn Rng,
+    request_address: bool,
+    request_prefix: Option<u8>,
+    state: State,
+    transaction_id: u32,
+    exchange: Exchange,
+    server_id: [u8; MAX_DUID_LEN],
+    server_id_len: usize,
+    lease: Option<Lease>,
+}
+
+/// Transmission state of the current message exchange.
+#[derive(Clone, Copy, Default)]
+struct Exchange {
+    /// The first transmission, `None` before the message was sent.
+    started: Option<Instant>,
+    /// When to retransmit the message.
+    retransmit_at: Option<Instant>,
+    /// The current retransmission timeout.
+    timeout: Duration,
+    /// The number of transmissions so far.
+    count: u8,
+}
+
+/// The options of a server message relevant to the client.
+struct Response<'a> {
+    server_id: &'a [u8],
+    address: Option<(Dhcpv6IaAddress<'a>, u32, u32)>,
+    prefix: Option<(Dhcpv6IaPrefix<'a>, u32, u32)>,
+}
+
+impl<'a> Client<'a> {
+    /// Create a client for the interface with some ethernet address.
+    ///
+    /// The client requests a single address but no prefix by default. It starts soliciting with
+    /// the first packet buffer it is offered.
+    pub fn new(hw_addr: EthernetAddress, rng: &'a mut dyn Rng) -> Self {
+        let EthernetAddress([h0, h1, h2, h3, h4, h5]) = hw_addr;
+        let mut client = Client {
+            // DUID-LL (type 3) with hardware type ethernet (1).
+            duid: [0, 3, 0, 1, h0, h1, h2, h3, h4, h5],
+            iaid: u32::from_be_bytes([h2, h3, h4, h5]),
+            rng,
+            request_address: true,
+            request_prefix: None,
+            state: State::Soliciting,
+            transaction_id: 0,
+            exchange: Exchange::default(),
+            server_id: [0; MAX_DUID_LEN],
+            server_id_len: 0,
+            lease: None,
+        };
+        client.enter(State::Soliciting);
+        client
+    }
+
+    /// Set whether to request an address for the interface.
+    ///
+    /// Takes effect with the next message sent.
+    pub fn set_request_address(&mut self, request: bool) {
+        self.request_address = request;
+    }
+
+    /// Request a delegated prefix, with a hint for its length, or stop requesting one.
+    ///
+    /// Takes effect with the next message sent.
+    pub fn set_request_prefix(&mut self, prefix_len: Option<u8>) {
+        self.request_prefix = prefix_len;
+    }
+
+    /// The DUID by which the client identifies itself.
+    pub fn duid(&self) -> &[u8] {
+        &self.duid
+    }
+
+    /// The current state of the client.
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// The current lease, if any.
+    pub fn lease(&self) -> Option<&Lease> {
+        self.lease.as_ref()
+    }
+
+    /// Forget the lease and start soliciting anew.
+    ///
+    /// Addresses assigned through the lease are not removed from the ip endpoint but expire with
+    /// their valid lifetime. Use this after the interface moved to another link, for example.
+    pub fn restart(&mut self) {
+        self.lease = None;
+        self.enter(State::Soliciting);
+    }
+
+    /// Update the timers of the client.
+    ///
+    /// Returns the time at which the client wants to send its next message. Offer it a packet
+    /// buffer at that point.
+    pub fn poll(&mut self, now: Instant) -> Expiration {
+        self.update(now);
+        match self.state {
+            State::Bound => self.lease.map_or(Expiration::Never, |lease| lease.renew_at),
+            _ => Expiration::When(self.exchange.retransmit_at.unwrap_or(now)),
+        }
+    }
+
+    /// Update the timers with the current time of a clock.
+    pub fn tick<C: Clock + ?Sized>(&mut self, clock: &C) -> Expiration {
+        self.poll(clock.now())
+    }
+
+    fn enter(&mut self, state: State) {
+        self.state = state;
+        self.transaction_id = (self.rng.next_u64() & 0xff_ffff) as u32;
+        self.exchange = Exchange::default();
+    }
+
+    /// Advance the state along the timers of the lease.
+    fn update(&mut self, now: Instant) {
+        let lease = match self.lease {
+            Some(lease) => lease,
+            None => return,
+        };
+
+        let now = Expiration::When(now);
+        if now > lease.expires_at {
+            self.restart();
+        } else if self.state == State::Bound && now >= lease.renew_at {
+            self.enter(State::Renewing);
+        } else if self.state == State::Renewing && now >= lease.rebind_at {
+            self.enter(State::Rebinding);
+        }
+    }
+
+    fn timing(&self) -> &'static Timing {
+        match self.state {
+            State::Soliciting => &SOLICIT,
+            State::Requesting => &REQUEST,
+            _ => &RENEW,
+        }
+    }
+
+    /// Schedule the retransmission after a message was sent, rfc8415 section 15.
+    fn transmitted(&mut self, now: Instant) {
+        let timing = self.timing();
+        let timeout = match self.exchange.started {
+            None => timing.initial,
+            Some(_) => self.exchange.timeout * 2,
+        };
+        let timeout = timeout.min(timing.max);
+
+        // Randomize by a factor between -0.1 and 0.1.
+        let millis = timeout.as_millis() as u64;
+        let jitter = self.rng.next_u64() % (millis / 5 + 1);
+        let timeout = Duration::from_millis(millis - millis / 10 + jitter);
+
+        self.exchange.started.get_or_insert(now);
+        self.exchange.retransmit_at = Some(now + timeout);
+        self.exchange.timeout = timeout;
+        self.exchange.count = self.exchange.count.saturating_add(1);
+    }
+
+    fn server_id(&self) -> &[u8] {
+        &self.server_id[..self.server_id_len]
+    }
+
+    fn set_server_id(&mut self, server_id: &[u8]) {
+        self.server_id[..server_id.len()].copy_from_slice(server_id);
+        self.server_id_len = server_id.len();
+    }
+
+    fn send_message<P: PayloadMut>(&self, raw: udp::RawPacket<P>, now: Instant) -> Result<()> {
+        let msg_type = match self.state {
+            State::Soliciting => Dhcpv6MessageType::Solicit,
+            State::Requesting => Dhcpv6MessageType::Request,
+            State::Renewing => Dhcpv6MessageType::Renew,
+            State::Rebinding => Dhcpv6MessageType::Rebind,
+            State::Bound => return Ok(()),
+        };
+
+        let elapsed = match self.exchange.started {
+            None => 0,
+            Some(started) => ((now - started).as_millis() / 10).min(0xffff) as u16,
+        };
+
+        let server_id = match self.state {
+            State::Requesting | State::Renewing => Some(Dhcpv6Option::ServerId(self.server_id())),
+            _ => None,
+        };
+
+        let mut na_options = [0; IA_ADDRESS_LEN];
+        let ia_na = if self.request_address {
+            Some(Dhcpv6Option::IaNa(Dhcpv6IdentityAssociation {
+                iaid: self.iaid,
+                t1: 0,
+                t2: 0,
+                options: self.address_hint(&mut na_options),
+            }))
+        } else {
+            None
+        };
+
+        let mut pd_options = [0; IA_PREFIX_LEN];
+        let ia_pd = self.prefix_hint(&mut pd_options).map(|options| {
+            Dhcpv6Option::IaPd(Dhcpv6IdentityAssociation {
+                iaid: self.iaid,
+                t1: 0,
+                t2: 0,
+                options,
+            })
+        });
+
+        let options = [
+            Some(Dhcpv6Option::ClientId(&self.duid)),
+            server_id,
+            Some(Dhcpv6Option::ElapsedTime(elapsed)),
+            ia_na,
+            ia_pd,
+        ];
+        let options_len = options.iter().flatten().map(Dhcpv6Option::buffer_len).sum();
+
+        let link_local = Ipv6Cidr::new(Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), 10);
+        let init = udp::Init {
+            source: IpSubnet::from(link_local.subnet()).into(),
+            src_port: DHCPV6_CLIENT_PORT,
+            dst_addr: DHCPV6_ALL_SERVERS.into(),
+            dst_port: DHCPV6_SERVER_PORT,
+            payload: dhcpv6_packet::buffer_len(options_len),
+            dscp: 0,
+        };
+
+        let mut packet = raw.prepare(init)?;
+        let message = dhcpv6_packet::new_unchecked_mut(packet.packet.payload_mut_slice());
+        message.set_msg_type(msg_type);
+        message.set_transaction_id(self.transaction_id);
+        let mut buffer = message.options_mut_slice();
+        for option in options.iter().flatten() {
+            option.emit(buffer);
+            buffer = &mut buffer[option.buffer_len()..];
+        }
+        packet.send()
+    }
+
+    /// Encapsulated options of the IA_NA, the leased address when extending it.
+    fn address_hint<'b>(&self, buffer: &'b mut [u8; IA_ADDRESS_LEN]) -> &'b [u8] {
+        let leased = match self.lease.and_then(|lease| lease.address) {
+            Some(leased) => leased,
+            None => return &[],
+        };
+
+        let address = match leased.address() {
+            IpAddress::Ipv6(address) => address,
+            _ => return &[],
+        };
+
+        Dhcpv6Option::IaAddress(Dhcpv6IaAddress {
+            address,
+            preferred_lifetime: 0,
+            valid_lifetime: 0,
+            options: &[],
+        }).emit(&mut buffer[..]);
+        &buffer[..]
+    }
+
+    /// Encapsulated options of the IA_PD, `None` if no prefix is requested.
+    ///
+    /// Contains the delegated prefix when extending it, otherwise the preferred prefix length.
+    fn prefix_hint<'b>(&self, buffer: &'b mut [u8; IA_PREFIX_LEN]) -> Option<&'b [u8]> {
+        let prefix_len = self.request_prefix?;
+        let leased = self.lease.and_then(|lease| lease.prefix);
+        let (prefix, prefix_len) = match leased.map(|leased| leased.cidr) {
+            Some(IpCidr::Ipv6(cidr)) => (cidr.address(), cidr.prefix_len()),
+            _ => (Ipv6Address::UNSPECIFIED, prefix_len),
+        };
+
+        Dhcpv6Option::IaPrefix(Dhcpv6IaPrefix {
+            preferred_lifetime: 0,
+            valid_lifetime: 0,
+            prefix_len,
+            prefix,
+            options: &[],
+        }).emit(&mut buffer[..]);
+        Some(&buffer[..])
+    }
+
+    /// Extract the options of a server message to this client.
+    ///
+    /// Returns `None` if the message is malformed, not directed at the current transaction or
+    /// reports a failure.
+    fn parse<'m>(&self, message: &'m dhcpv6_packet) -> Option<Response<'m>> {
+        if message.transaction_id() != self.transaction_id {
+            return None;
+        }
+
+        let mut client_id = None;
+        let mut server_id = None;
+        let mut address = None;
+        let mut prefix = None;
+        for option in message.options() {
+            match option.ok()? {
+                Dhcpv6Option::ClientId(id) => client_id = Some(id),
+                Dhcpv6Option::ServerId(id) => server_id = Some(id),
+                Dhcpv6Option::Status { code, .. } if code != Dhcpv6StatusCode::Success => {
+                    return None;
+                },
+                Dhcpv6Option::IaNa(ia) if ia.iaid == self.iaid => {
+                    address = identity_association(ia, |option| match option {
+                        Dhcpv6Option::IaAddress(addr) => Some(addr),
+                        _ => None,
+                    }).filter(|(addr, _, _)| {
+                        addr.valid_lifetime > 0
+                            && addr.preferred_lifetime <= addr.valid_lifetime
+                            && addr.address.is_unicast()
+                    });
+                },
+                Dhcpv6Option::IaPd(ia) if ia.iaid == self.iaid => {
+                    prefix = identity_association(ia, |option| match option {
+                        Dhcpv6Option::IaPrefix(prefix) => Some(prefix),
+                        _ => None,
+                    }).filter(|(prefix, _, _)| {
+                        prefix.valid_lifetime > 0
+                            && prefix.preferred_lifetime <= prefix.valid_lifetime
+                    });
+                },
+                _ => (),
+            }
+        }
+
+        if client_id? != &self.duid[..] {
+            return None;
+        }
+
+        let server_id = server_id.filter(|id| id.len() <= MAX_DUID_LEN)?;
+        Some(Response {
+            server_id,
+            address: address.filter(|_| self.request_address),
+            prefix: prefix.filter(|_| self.request_prefix.is_some()),
+        })
+    }
+
+    /// Apply the lease of a Reply.
+    fn bind(&mut self, response: Response, ip: &mut ip::Handle, now: Instant) {
+        let address = response.address.map(|(addr, _, _)| Assignment::with_lifetimes(
+            IpCidr::new(addr.address.into(), 128),
+            lifetime(addr.preferred_lifetime, now),
+            lifetime(addr.valid_lifetime, now)));
+        let prefix = response.prefix.map(|(prefix, _, _)| Assignment::with_lifetimes(
+            IpCidr::new(prefix.prefix.into(), prefix.prefix_len),
+            lifetime(prefix.preferred_lifetime, now),
+            lifetime(prefix.valid_lifetime, now)));
+
+        // A server may also move us to a different address.
+        let previous = self.lease.and_then(|lease| lease.address);
+        if let Some(previous) = previous {
+            if address.map(|new| new.cidr) != Some(previous.cidr) {
+                ip.unassign(previous.address());
+            }
+        }
+
+        if let Some(address) = address {
+            // Without room for the address, the lease is still tracked for its prefix.
+            let _ = ip.assign(address);
+        }
+
+        // The timers of the address take precedence, and the server may leave them to us.
+        let (t1, t2) = response.address
+            .map(|(_, t1, t2)| (t1, t2))
+            .or_else(|| response.prefix.map(|(_, t1, t2)| (t1, t2)))
+            .unwrap_or((0, 0));
+        let preferred = response.address.map(|(addr, _, _)| addr.preferred_lifetime)
+            .into_iter()
+            .chain(response.prefix.map(|(prefix, _, _)| prefix.preferred_lifetime))
+            .min()
+            .unwrap_or(0);
+        let (t1, t2) = match (t1, t2) {
+            (0, 0) if preferred == DHCPV6_INFINITE => (DHCPV6_INFINITE, DHCPV6_INFINITE),
+            (0, 0) => (preferred / 2, preferred / 5 * 4),
+            other => other,
+        };
+
+        let expires_at = address.iter()
+            .chain(prefix.iter())
+            .map(|assignment| assignment.valid_until)
+            .max()
+            .unwrap_or(Expiration::When(now));
+
+        self.set_server_id(response.server_id);
+        self.lease = Some(Lease {
+            address,
+            prefix,
+            renew_at: lifetime(t1, now),
+            rebind_at: lifetime(t2, now),
+            expires_at,
+        });
+        self.enter(State::Bound);
+    }
+}
+
+impl<P: Payload> udp::Recv<P> for Client<'_> {
+    fn receive(&mut self, packet: udp::Packet<P>) {
+        let udp::Packet { mut handle, packet } = packet;
+        if packet.repr().dst_port != DHCPV6_CLIENT_PORT {
+            return;
+        }
+
+        let message = match dhcpv6_packet::new_checked(packet.payload_slice()) {
+            Ok(message) => message,
+            Err(_) => return,
+        };
+
+        let now = handle.info().timestamp();
+        self.update(now);
+
+        let response = match self.parse(message) {
+            Some(response) => response,
+            None => return,
+        };
+
+        let usable = response.address.is_some() || response.prefix.is_some();
+        match (self.state, message.msg_type()) {
+            (State::Soliciting, Dhcpv6MessageType::Advertise) if usable => {
+                self.set_server_id(response.server_id);
+                self.enter(State::Requesting);
+            },
+            (State::Requesting, Dhcpv6MessageType::Reply) if !usable => {
+                self.enter(State::Soliciting);
+            },
+            (State::Requesting, Dhcpv6MessageType::Reply)
+            | (State::Renewing, Dhcpv6MessageType::Reply)
+            | (State::Rebinding, Dhcpv6MessageType::Reply) if usable => {
+                self.bind(response, &mut handle.inner, now);
+            },
+            _ => (),
+        }
+    }
+}
+
+impl<P: PayloadMut> udp::Send<P> for Client<'_> {
+    fn send(&mut self, raw: udp::RawPacket<P>) {
+        let now = raw.handle.info().timestamp();
+        self.update(now);
+
+        if self.state == State::Bound {
+            return;
+        }
+
+        if let Some(retransmit_at) = self.exchange.retransmit_at {
+            if now < retransmit_at {
+                return;
+            }
+        }
+
+        // Give up on an unresponsive server.
+        if let Some(max_count) = self.timing().max_count {
+            if self.exchange.count >= max_count {
+                self.enter(State::Soliciting);
+            }
+        }
+
+        // A failure, such as a missing link-local address, is retried with the next timeout.
+        let _ = self.send_message(raw, now);
+        self.transmitted(now);
+    }
+}
+
+/// Find the first acceptable lease of an identity association, with its timers.
+fn identity_association<'a, T>(
+    ia: Dhcpv6IdentityAssociation<'a>,
+    lease: impl Fn(Dhcpv6Option<'a>) -> Option<T>,
+) -> Option<(T, u32, u32)> {
+    let mut found = None;
+    for option in ia.options() {
+        match option.ok()? {
+            Dhcpv6Option::Status { code, .. } if code != Dhcpv6StatusCode::Success => return None,
+            option => {
+                if found.is_none() {
+                    found = lease(option);
+                }
+            },
+        }
+    }
+
+    // Invalid timers, rfc8415 section 21.4.
+    if ia.t1 > ia.t2 && ia.t2 != 0 {
+        return None;
+    }
+
+    found.map(|lease| (lease, ia.t1, ia.t2))
+}
+
+/// The expiration of a lifetime in seconds.
+fn lifetime(secs: u32, now: Instant) -> Expiration {
+    if secs == DHCPV6_INFINITE {
+        Expiration::Never
+    } else {
+        Expiration::When(now + Duration::from_secs(secs.into()))
+    }
+}
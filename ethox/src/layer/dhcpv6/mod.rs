@@ -0,0 +1,27 @@
+//! A DHCPv6 client, rfc8415.
+//!
+//! Stateful address configuration for networks where stateless autoconfiguration is not
+//! available or not sufficient. The client requests a non-temporary address (IA_NA) for the
+//! interface and may additionally ask for a delegated prefix (IA_PD) to be used on downstream
+//! links. Acquired addresses are assigned to the ip endpoint below with the lifetimes of the
+//! lease, so they expire on their own should the client fail to renew them.
+//!
+//! The client sits on top of the udp layer and implements both its receiving and sending side.
+//! The udp endpoint must accept port 546 ([`DHCPV6_CLIENT_PORT`]) and the ip endpoint needs a
+//! link-local address from which to talk to servers. Drive its timers with [`Client::tick`] and
+//! offer it a packet buffer whenever they have expired.
+//!
+//! Relay agents, reconfiguration and authentication are not supported. The first advertisement
+//! offering addresses is accepted, without waiting for the one with the highest preference.
+//!
+//! [`DHCPV6_CLIENT_PORT`]: ../../wire/constant.DHCPV6_CLIENT_PORT.html
+//! [`Client::tick`]: struct.Client.html#method.tick
+mod client;
+#[cfg(test)]
+mod tests;
+
+pub use client::{
+    Client,
+    Lease,
+    State,
+};
@@ -0,0 +1,195 @@
+use super::*;
+use crate::managed::Slice;
+use crate::nic::{external::External, Device};
+use crate::layer::{arp, eth, ip, loss, udp};
+use crate::time::{Expiration, Instant};
+use crate::wire::{EthernetAddress, EthernetProtocol, EthernetRepr, IpCidr, IpProtocol};
+use crate::wire::{Ipv6Address, Ipv6Cidr, Ipv6Repr, UdpChecksum, UdpRepr};
+use crate::wire::{ethernet_frame, ipv6_packet, udp_packet, dhcpv6_packet};
+use crate::wire::{Dhcpv6IaAddress, Dhcpv6IdentityAssociation, Dhcpv6MessageType, Dhcpv6Option};
+use crate::wire::{DHCPV6_ALL_SERVERS, DHCPV6_CLIENT_PORT, DHCPV6_SERVER_PORT};
+
+const MAC_ADDR_HOST: EthernetAddress = EthernetAddress([0x52, 0x54, 0, 0, 0, 1]);
+const MAC_ADDR_SERVER: EthernetAddress = EthernetAddress([0x52, 0x54, 0, 0, 0, 2]);
+const LINK_LOCAL_HOST: Ipv6Address = Ipv6Address(
+    [0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+const LINK_LOCAL_SERVER: Ipv6Address = Ipv6Address(
+    [0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+const IAID: u32 = 1;
+
+static SERVER_DUID: [u8; 10] = [0x00, 0x03, 0x00, 0x01, 0x52, 0x54, 0x00, 0x00, 0x00, 0x02];
+
+/// A message from the server to the host.
+fn server_message(msg_type: Dhcpv6MessageType, xid: u32, options: &[Dhcpv6Option]) -> Vec<u8> {
+    let options_len: usize = options.iter().map(Dhcpv6Option::buffer_len).sum();
+    let udp_len = 8 + dhcpv6_packet::buffer_len(options_len);
+    let mut buffer = vec![0; 14 + 40 + udp_len];
+
+    let eth = ethernet_frame::new_unchecked_mut(&mut buffer[..]);
+    EthernetRepr {
+        src_addr: MAC_ADDR_SERVER,
+        dst_addr: MAC_ADDR_HOST,
+        ethertype: EthernetProtocol::Ipv6,
+    }.emit(eth);
+    let ip = ipv6_packet::new_unchecked_mut(eth.payload_mut_slice());
+    Ipv6Repr {
+        src_addr: LINK_LOCAL_SERVER,
+        dst_addr: LINK_LOCAL_HOST,
+        next_header: IpProtocol::Udp,
+        payload_len: udp_len,
+        hop_limit: 64,
+    }.emit(ip);
+    let udp = udp_packet::new_unchecked_mut(ip.payload_mut_slice());
+    UdpRepr {
+        src_port: DHCPV6_SERVER_PORT,
+        dst_port: DHCPV6_CLIENT_PORT,
+        length: udp_len as u16,
+        checksum_coverage: None,
+    }.emit(udp, UdpChecksum::Ignored);
+    let message = dhcpv6_packet::new_unchecked_mut(udp.payload_mut_slice());
+    message.set_msg_type(msg_type);
+    message.set_transaction_id(xid);
+    let mut rest = message.options_mut_slice();
+    for option in options {
+        option.emit(rest);
+        rest = &mut rest[option.buffer_len()..];
+    }
+    udp.fill_checksum(LINK_LOCAL_SERVER.into(), LINK_LOCAL_HOST.into());
+    buffer
+}
+
+/// The type, transaction id and whether a server id is contained in a sent message.
+fn client_message(buffer: &mut [u8], duid: &[u8]) -> (Dhcpv6MessageType, u32, bool) {
+    let eth = ethernet_frame::new_unchecked_mut(buffer);
+    assert_eq!(eth.dst_addr(), EthernetAddress([0x33, 0x33, 0, 1, 0, 2]));
+    let ip = ipv6_packet::new_unchecked_mut(eth.payload_mut_slice());
+    assert_eq!(ip.src_addr(), LINK_LOCAL_HOST);
+    assert_eq!(ip.dst_addr(), DHCPV6_ALL_SERVERS);
+    let udp = udp_packet::new_unchecked_mut(ip.payload_mut_slice());
+    assert_eq!(udp.src_port(), DHCPV6_CLIENT_PORT);
+    assert_eq!(udp.dst_port(), DHCPV6_SERVER_PORT);
+    let message = dhcpv6_packet::new_checked(udp.payload_slice()).unwrap();
+
+    let mut server_id = false;
+    let mut client_id = false;
+    for option in message.options() {
+        match option.expect("Well-formed options") {
+            Dhcpv6Option::ClientId(id) => client_id = id == duid,
+            Dhcpv6Option::ServerId(id) => server_id = id == &SERVER_DUID[..],
+            Dhcpv6Option::IaNa(ia) => assert_eq!(ia.iaid, IAID),
+            _ => (),
+        }
+    }
+    assert!(client_id);
+    (message.msg_type(), message.transaction_id(), server_id)
+}
+
+#[test]
+fn lease() {
+    let leased = Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x42);
+    let mut ia_options = [0; 28];
+    Dhcpv6Option::IaAddress(Dhcpv6IaAddress {
+        address: leased,
+        preferred_lifetime: 3600,
+        valid_lifetime: 7200,
+        options: &[],
+    }).emit(&mut ia_options);
+    let ia_na = Dhcpv6Option::IaNa(Dhcpv6IdentityAssociation {
+        iaid: IAID,
+        t1: 1800,
+        t2: 2880,
+        options: &ia_options[..],
+    });
+
+    let mut eth = eth::Endpoint::new(MAC_ADDR_HOST);
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let mut routes = [ip::Route::unspecified(); 1];
+    let mut addresses = [ip::Assignment::unspecified(); 2];
+    let mut ip = ip::Endpoint::new(
+        &mut addresses[..],
+        ip::Routes::new(&mut routes[..]),
+        arp::NeighborCache::new(&mut neighbors[..]));
+    ip.add_address(Ipv6Cidr::new(LINK_LOCAL_HOST, 64).into()).unwrap();
+    let mut udp = udp::Endpoint::new(vec![DHCPV6_CLIENT_PORT]);
+
+    let mut rng = loss::Xoroshiro256::new(42);
+    let mut client = Client::new(MAC_ADDR_HOST, &mut rng);
+    let duid = client.duid().to_vec();
+    assert_eq!(client.state(), State::Soliciting);
+    assert_eq!(client.poll(Instant::from_secs(0)), Expiration::When(Instant::from_secs(0)));
+
+    // Solicit servers.
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+    assert_eq!(nic.tx(1, eth.send(ip.send(udp.send(&mut client)))), Ok(1));
+    let (msg_type, xid, server_id) = client_message(nic.get_mut(0).unwrap(), &duid);
+    assert_eq!((msg_type, server_id), (Dhcpv6MessageType::Solicit, false));
+    // Not retransmitted before the timeout.
+    assert!(client.poll(Instant::from_secs(0)) > Expiration::When(Instant::from_millis(800)));
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+    nic.set_current_time(Instant::from_millis(500));
+    assert_eq!(nic.tx(1, eth.send(ip.send(udp.send(&mut client)))), Ok(0));
+
+    // Advertisements for another transaction are ignored.
+    let options = [
+        Dhcpv6Option::ClientId(&duid),
+        Dhcpv6Option::ServerId(&SERVER_DUID[..]),
+        ia_na,
+    ];
+    let advertise = server_message(Dhcpv6MessageType::Advertise, xid ^ 1, &options);
+    let mut nic = External::new_recv(Slice::One(advertise));
+    assert_eq!(nic.rx(1, eth.recv(ip.recv(udp.recv(&mut client)))), Ok(1));
+    assert_eq!(client.state(), State::Soliciting);
+
+    let advertise = server_message(Dhcpv6MessageType::Advertise, xid, &options);
+    let mut nic = External::new_recv(Slice::One(advertise));
+    assert_eq!(nic.rx(1, eth.recv(ip.recv(udp.recv(&mut client)))), Ok(1));
+    assert_eq!(client.state(), State::Requesting);
+
+    // Request the advertised address from that server.
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+    nic.set_current_time(Instant::from_secs(1));
+    assert_eq!(nic.tx(1, eth.send(ip.send(udp.send(&mut client)))), Ok(1));
+    let (msg_type, xid, server_id) = client_message(nic.get_mut(0).unwrap(), &duid);
+    assert_eq!((msg_type, server_id), (Dhcpv6MessageType::Request, true));
+
+    let reply = server_message(Dhcpv6MessageType::Reply, xid, &options);
+    let mut nic = External::new_recv(Slice::One(reply));
+    nic.set_current_time(Instant::from_secs(2));
+    assert_eq!(nic.rx(1, eth.recv(ip.recv(udp.recv(&mut client)))), Ok(1));
+    assert_eq!(client.state(), State::Bound);
+
+    let assignment = ip::Assignment::with_lifetimes(
+        IpCidr::new(leased.into(), 128),
+        Expiration::When(Instant::from_secs(3602)),
+        Expiration::When(Instant::from_secs(7202)));
+    assert_eq!(ip.addresses()[1], assignment);
+    let lease = *client.lease().unwrap();
+    assert_eq!(lease.address, Some(assignment));
+    assert_eq!(lease.prefix, None);
+    assert_eq!(lease.renew_at, Expiration::When(Instant::from_secs(1802)));
+    assert_eq!(lease.rebind_at, Expiration::When(Instant::from_secs(2882)));
+    assert_eq!(client.poll(Instant::from_secs(2)), lease.renew_at);
+
+    // Renew with the same server at T1.
+    assert_eq!(client.poll(Instant::from_secs(1802)), Expiration::When(Instant::from_secs(1802)));
+    assert_eq!(client.state(), State::Renewing);
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+    nic.set_current_time(Instant::from_secs(1802));
+    assert_eq!(nic.tx(1, eth.send(ip.send(udp.send(&mut client)))), Ok(1));
+    let (msg_type, _, server_id) = client_message(nic.get_mut(0).unwrap(), &duid);
+    assert_eq!((msg_type, server_id), (Dhcpv6MessageType::Renew, true));
+
+    // And with any server at T2.
+    client.poll(Instant::from_secs(2882));
+    assert_eq!(client.state(), State::Rebinding);
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+    nic.set_current_time(Instant::from_secs(2882));
+    assert_eq!(nic.tx(1, eth.send(ip.send(udp.send(&mut client)))), Ok(1));
+    let (msg_type, _, server_id) = client_message(nic.get_mut(0).unwrap(), &duid);
+    assert_eq!((msg_type, server_id), (Dhcpv6MessageType::Rebind, false));
+
+    // Until the lease ends.
+    client.poll(Instant::from_secs(7203));
+    assert_eq!(client.state(), State::Soliciting);
+    assert_eq!(client.lease(), None);
+}
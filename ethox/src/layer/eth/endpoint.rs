@@ -1,6 +1,5 @@
-use core::marker::PhantomData;
-
-use crate::layer::FnHandler;
+use crate::layer::{Error, FnHandler, Result};
+use crate::managed::{Partial, Slice};
 use crate::wire::{EthernetAddress, EthernetFrame, Payload, PayloadMut};
 use crate::nic;
 
@@ -16,19 +15,27 @@ use super::packet::{self, Handle};
 /// Note that the ethernet wire layer does **not yet** support giant frames but if it did these
 /// would need to be explicitely enabled here.
 ///
-/// Otherwise, the endpoint holds no configuration state and options. To preserve future
-/// compatibility it nevertheless has a lifetime parameter like other layer's endpoints. (ARP and
-/// ICMP do not use the same reservation since they are less likely to break upper layer code by
-/// having basically no upper layer). This allows introducing new state, as long as there is a
-/// default value with static lifetime—such as is the case for slices.
+/// Otherwise, the endpoint only configures which destinations it accepts. Apart from its own
+/// address and broadcasts these are the joined multicast groups, kept in storage provided with
+/// [`with_multicast`], or simply all frames in promiscuous mode. The same configuration can be
+/// programmed into the hardware filter of a device, see [`filter`].
+///
+/// [`with_multicast`]: #method.with_multicast
+/// [`filter`]: #method.filter
 pub struct Endpoint<'a> {
     /// Our own address.
     ///
     /// We ignored any packets with mismatching destination.
     addr: EthernetAddress,
 
-    /// TODO: figure out if we need any dynamically sized, non-owned data.
-    data: PhantomData<&'a ()>,
+    /// The joined multicast groups.
+    multicast: Partial<Slice<'a, EthernetAddress>>,
+
+    /// Accept frames to any multicast group.
+    all_multicast: bool,
+
+    /// Accept all frames.
+    promiscuous: bool,
 }
 
 /// An endpoint borrowed for receiving.
@@ -61,9 +68,80 @@ impl<'a> Endpoint<'a> {
     /// The endpoint will filter incoming messages by the hardware address and allows inspection of
     /// that address for sending.
     pub fn new(addr: EthernetAddress) -> Self {
+        Self::with_multicast(addr, Slice::empty())
+    }
+
+    /// Construct a new endpoint with storage for joined multicast groups.
+    ///
+    /// The storage is initially empty, its length bounds the number of groups that can be joined.
+    pub fn with_multicast(addr: EthernetAddress, multicast: Slice<'a, EthernetAddress>) -> Self {
         Endpoint {
             addr,
-            data: PhantomData,
+            multicast: Partial::new(multicast),
+            all_multicast: false,
+            promiscuous: false,
+        }
+    }
+
+    /// Join a multicast group, accepting frames sent to it.
+    ///
+    /// Fails with `Exhausted` when there is no more room in the storage for groups.
+    ///
+    /// # Panics
+    /// This method will panic if the address is not a multicast address.
+    pub fn join_multicast(&mut self, group: EthernetAddress) -> Result<()> {
+        assert!(group.is_multicast());
+        if self.multicast.contains(&group) {
+            return Ok(());
+        }
+
+        let slot = self.multicast.push().ok_or(Error::Exhausted)?;
+        *slot = group;
+        Ok(())
+    }
+
+    /// Leave a multicast group.
+    ///
+    /// Returns whether the group had been joined.
+    pub fn leave_multicast(&mut self, group: EthernetAddress) -> bool {
+        match self.multicast.iter().position(|joined| *joined == group) {
+            Some(idx) => {
+                self.multicast.remove_at(idx);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// The multicast groups that have been joined.
+    pub fn multicast(&self) -> &[EthernetAddress] {
+        &self.multicast
+    }
+
+    /// Choose whether frames to all multicast groups are accepted.
+    pub fn set_all_multicast(&mut self, all_multicast: bool) {
+        self.all_multicast = all_multicast;
+    }
+
+    /// Choose whether all frames are accepted, regardless of their destination.
+    ///
+    /// Upper layers still filter by their own addresses, so this is mostly useful for observing
+    /// traffic with a custom receiver.
+    pub fn set_promiscuous(&mut self, promiscuous: bool) {
+        self.promiscuous = promiscuous;
+    }
+
+    /// Describe the accepted destinations for programming the filter of a device.
+    ///
+    /// See [`nic::Device::set_filter`].
+    ///
+    /// [`nic::Device::set_filter`]: ../../nic/trait.Device.html#method.set_filter
+    pub fn filter(&self) -> nic::Filter<'_> {
+        nic::Filter {
+            addr: self.addr,
+            multicast: &self.multicast,
+            all_multicast: self.all_multicast,
+            promiscuous: self.promiscuous,
         }
     }
 
@@ -94,8 +172,7 @@ impl<'a> Endpoint<'a> {
     }
 
     fn accepts(&self, dst_addr: EthernetAddress) -> bool {
-        // TODO: broadcast and multicast
-        self.addr == dst_addr || dst_addr.is_broadcast()
+        self.filter().accepts(dst_addr)
     }
 }
 
@@ -162,8 +239,9 @@ mod tests {
     use super::*;
     use crate::managed::Slice;
     use crate::nic::{external::External, Device};
-    use crate::layer::eth::Init;
-    use crate::wire::{EthernetAddress, EthernetProtocol};
+    use crate::layer::eth::{Init, LlcInit};
+    use crate::wire::{EthernetAddress, EthernetProtocol, Headroom, LlcRepr};
+    use crate::wire::{wol_packet, WolRepr};
 
     const MAC_ADDR_1: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 5]);
 
@@ -216,4 +294,190 @@ mod tests {
                 .recv_with(simple_recv));
         assert_eq!(recv, Ok(1));
     }
+
+    #[test]
+    fn headroom() {
+        const TAG_LEN: usize = 4;
+
+        let mut endpoint = Endpoint::new(MAC_ADDR_1);
+        let mut nic = External::new_send(Slice::One(Headroom::new(vec![0; 1024])));
+
+        let sent = nic.tx(
+            1,
+            endpoint
+                .send_with(|mut frame: packet::Raw<_>| {
+                    frame.reserve_headroom(TAG_LEN).unwrap();
+                    simple_send(frame)
+                }));
+        assert_eq!(sent, Ok(1));
+
+        // A tag can be prepended in front of the prepared frame without moving it.
+        let buffer = nic.get_mut(0).unwrap();
+        assert_eq!(buffer.headroom(), TAG_LEN);
+        let frame = buffer.payload().as_slice().to_vec();
+        buffer.prepend(TAG_LEN).unwrap();
+        assert_eq!(buffer.headroom(), 0);
+        assert_eq!(&buffer.payload()[TAG_LEN..], &frame[..]);
+        buffer.trim_front(TAG_LEN).unwrap();
+
+        // Answering with a shorter frame grows the headroom, keeping the tail in place.
+        nic.set_one_past_receive(1);
+        let recv = nic.rx(
+            1,
+            endpoint
+                .recv_with(|frame: packet::In<_>| {
+                    let init = Init {
+                        src_addr: MAC_ADDR_1,
+                        dst_addr: MAC_ADDR_1,
+                        ethertype: EthernetProtocol::Unknown(0xBEEF),
+                        payload: 10,
+                    };
+                    let out = frame.reinit(init).unwrap();
+                    assert_eq!(out.into_incoming().frame.payload().as_slice(),
+                        &PAYLOAD_BYTES[40..]);
+                }));
+        assert_eq!(recv, Ok(1));
+        assert_eq!(nic.get_mut(0).unwrap().headroom(), TAG_LEN + 40);
+    }
+
+    #[test]
+    fn llc() {
+        let snap = LlcRepr::Snap {
+            oui: [0x00, 0x00, 0x0c],
+            protocol: EthernetProtocol::Unknown(0x2000),
+        };
+
+        let mut endpoint = Endpoint::new(MAC_ADDR_1);
+        let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+
+        let sent = nic.tx(
+            1,
+            endpoint
+                .send_with(|mut frame: packet::Raw<_>| {
+                    let init = LlcInit {
+                        src_addr: frame.handle.src_addr(),
+                        dst_addr: MAC_ADDR_1,
+                        llc: snap,
+                        payload: PAYLOAD_BYTES.len(),
+                    };
+                    let mut out = frame.prepare_llc(init).unwrap();
+                    out.payload_mut_slice()[snap.header_len()..]
+                        .copy_from_slice(&PAYLOAD_BYTES[..]);
+                    out.send().unwrap();
+                }));
+        assert_eq!(sent, Ok(1));
+
+        // Padding after the length is not part of the payload.
+        nic.get_mut(0).unwrap().extend_from_slice(&[0; 4]);
+        nic.set_one_past_receive(1);
+        let recv = nic.rx(
+            1,
+            endpoint
+                .recv_with(|frame: packet::In<_>| {
+                    assert_eq!(frame.frame.ethertype(), EthernetProtocol::Unknown(58));
+                    let (repr, payload) = frame.llc().unwrap().unwrap();
+                    assert_eq!(repr, snap);
+                    assert_eq!(payload, &PAYLOAD_BYTES[..]);
+                }));
+        assert_eq!(recv, Ok(1));
+
+        // Ethernet II frames have no LLC header.
+        let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+        nic.tx(1, endpoint.send_with(simple_send)).unwrap();
+        nic.set_one_past_receive(1);
+        let recv = nic.rx(
+            1,
+            endpoint
+                .recv_with(|frame: packet::In<_>| assert!(frame.llc().is_none())));
+        assert_eq!(recv, Ok(1));
+    }
+
+    #[test]
+    fn wake_on_lan() {
+        const SLEEPING: EthernetAddress = EthernetAddress([0x52, 0x54, 0, 0x12, 0x34, 0x56]);
+        let mut endpoint = Endpoint::new(MAC_ADDR_1);
+        let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+
+        let sent = nic.tx(
+            1,
+            endpoint
+                .send_with(|frame: packet::Raw<_>| {
+                    frame.prepare_wake(WolRepr::new(SLEEPING)).unwrap().send().unwrap();
+                }));
+        assert_eq!(sent, Ok(1));
+
+        nic.set_one_past_receive(1);
+        let recv = nic.rx(
+            1,
+            endpoint
+                .recv_with(|frame: packet::In<_>| {
+                    assert_eq!(frame.frame.dst_addr(), EthernetAddress::BROADCAST);
+                    assert_eq!(frame.frame.ethertype(), EthernetProtocol::WakeOnLan);
+                    let magic = wol_packet::new_checked(frame.frame.payload_slice()).unwrap();
+                    assert_eq!(WolRepr::parse(magic), Ok(WolRepr::new(SLEEPING)));
+                    assert_eq!(frame.magic_packet(), Some(SLEEPING));
+                }));
+        assert_eq!(recv, Ok(1));
+
+        // Other frames carry no magic packet.
+        let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+        nic.tx(1, endpoint.send_with(simple_send)).unwrap();
+        nic.set_one_past_receive(1);
+        let recv = nic.rx(
+            1,
+            endpoint
+                .recv_with(|frame: packet::In<_>| assert_eq!(frame.magic_packet(), None)));
+        assert_eq!(recv, Ok(1));
+    }
+
+    #[test]
+    fn filter() {
+        const GROUP: EthernetAddress = EthernetAddress([0x01, 0x80, 0xc2, 0, 0, 0]);
+        const OTHER: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 6]);
+
+        fn received(endpoint: &mut Endpoint, dst_addr: EthernetAddress) -> bool {
+            let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+            nic.tx(1, endpoint.send_with(|frame: packet::Raw<_>| {
+                let init = Init {
+                    src_addr: OTHER,
+                    dst_addr,
+                    ethertype: EthernetProtocol::Unknown(0xBEEF),
+                    payload: 0,
+                };
+                frame.prepare(init).unwrap().send().unwrap();
+            })).unwrap();
+            nic.set_one_past_receive(1);
+            let mut received = false;
+            nic.rx(1, endpoint.recv_with(|_: packet::In<_>| received = true)).unwrap();
+            received
+        }
+
+        let mut endpoint = Endpoint::with_multicast(MAC_ADDR_1, Slice::One(Default::default()));
+        assert!(received(&mut endpoint, MAC_ADDR_1));
+        assert!(received(&mut endpoint, EthernetAddress::BROADCAST));
+        assert!(!received(&mut endpoint, GROUP));
+        assert!(!received(&mut endpoint, OTHER));
+
+        assert_eq!(endpoint.join_multicast(GROUP), Ok(()));
+        assert_eq!(endpoint.join_multicast(GROUP), Ok(()));
+        assert_eq!(endpoint.join_multicast(EthernetAddress([0x01, 0, 0, 0, 0, 1])),
+            Err(Error::Exhausted));
+        assert_eq!(endpoint.multicast(), &[GROUP]);
+        assert!(received(&mut endpoint, GROUP));
+        assert!(endpoint.leave_multicast(GROUP));
+        assert!(!endpoint.leave_multicast(GROUP));
+        assert!(!received(&mut endpoint, GROUP));
+
+        endpoint.set_all_multicast(true);
+        assert!(received(&mut endpoint, GROUP));
+        assert!(!received(&mut endpoint, OTHER));
+
+        endpoint.set_promiscuous(true);
+        assert!(received(&mut endpoint, OTHER));
+        assert!(endpoint.filter().promiscuous);
+
+        // Devices without a hardware filter refuse to program one.
+        let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+        assert_eq!(nic.set_filter(endpoint.filter()), Err(Error::Illegal));
+    }
 }
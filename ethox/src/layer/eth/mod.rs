@@ -22,6 +22,7 @@ pub use endpoint::{
 pub use packet::{
     Handle,
     Init,
+    LlcInit,
     In as InPacket,
     Out as OutPacket,
     Raw as RawPacket,
@@ -52,12 +53,13 @@ pub trait Send<P: Payload> {
 
 /// Pretty print all frames that are received.
 ///
-/// Available only on `std` because it prints to standard out.
+/// Available only on `std` because it prints to standard error, or to the logger with the `log`
+/// feature.
 #[cfg(feature = "std")]
 impl<P: Payload> Recv<P> for Formatter<ethernet_frame> {
     fn receive(&mut self, frame: InPacket<P>) {
         let printer = PrettyPrinter::<ethernet_frame>::print(&frame.frame);
-        eprintln!("{}", printer);
+        net_print!("{}", printer);
     }
 }
 
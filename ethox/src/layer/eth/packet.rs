@@ -1,7 +1,10 @@
+use core::mem;
+
 use crate::nic;
 use crate::layer::{Error, Result};
 use crate::wire::{Payload, PayloadResult, PayloadMut, PayloadMutExt, Reframe, ReframePayload, payload};
 use crate::wire::{EthernetAddress, EthernetFrame, EthernetProtocol, EthernetRepr, ethernet_frame};
+use crate::wire::{LlcRepr, llc_header, wol_find, wol_packet, WolRepr};
 
 /// An incoming packet.
 ///
@@ -61,6 +64,22 @@ pub struct Init {
     pub payload: usize,
 }
 
+/// Initializer for an IEEE 802.3 frame with an LLC header.
+///
+/// Such frames carry their length instead of an ethertype, followed by the LLC header and
+/// optionally a SNAP extension. They are still used by protocols such as the spanning tree
+/// protocol and by some industrial equipment.
+pub struct LlcInit {
+    /// The ethernet source address to use.
+    pub src_addr: EthernetAddress,
+    /// The destination address for the frame.
+    pub dst_addr: EthernetAddress,
+    /// The LLC header emitted at the start of the payload.
+    pub llc: LlcRepr,
+    /// The length in bytes that the payload following the LLC header requires.
+    pub payload: usize,
+}
+
 /// The interface to the endpoint.
 pub(crate) trait Endpoint{
     /// Get the default source address.
@@ -100,10 +119,26 @@ impl<'a> Handle<'a> {
         self.nic_handle.info()
     }
 
+    /// Request the hardware timestamp of the packet when it is sent.
+    ///
+    /// The device reports the timestamp after sending, see [`nic::Handle`] for details.
+    ///
+    /// [`nic::Handle`]: ../../nic/trait.Handle.html#method.request_tx_timestamp
+    pub fn request_tx_timestamp(&mut self, id: u32) -> Result<()> {
+        self.nic_handle.request_tx_timestamp(id)
+    }
+
     /// Get the configured (source) address of the ethernet endpoint.
     pub fn src_addr(&mut self) -> EthernetAddress {
         self.endpoint.src_addr()
     }
+
+    /// Exchange a packet buffer for a spare, if the device permits it.
+    pub(crate) fn retain<P>(&mut self, payload: &mut P, spare: &mut P) -> Result<()> {
+        self.nic_handle.retain()?;
+        mem::swap(payload, spare);
+        Ok(())
+    }
 }
 
 impl<'a, P: Payload> In<'a, P> {
@@ -115,13 +150,54 @@ impl<'a, P: Payload> In<'a, P> {
     {
         Raw::new(self.handle, self.frame.into_inner())
     }
+
+    /// Take ownership of the buffer underlying the packet, exchanging it for a spare.
+    ///
+    /// On success `spare` contains the complete received frame, with all headers, while the device
+    /// continues with the previous spare. The upper layers offer the same exchange on their
+    /// packets. This fails if the device does not permit exchanging its buffers, see
+    /// [`nic::Handle::retain`], in which case the packet is dropped. A spare can be leased from a
+    /// [`Pool`] and the retained buffer returned to it later.
+    ///
+    /// [`Pool`]: ../../managed/pool/struct.Pool.html
+    /// [`nic::Handle::retain`]: ../../nic/trait.Handle.html#method.retain
+    pub fn retain(self, spare: &mut P) -> Result<()>
+        where P: PayloadMut,
+    {
+        let Raw { mut handle, payload } = self.deinit();
+        handle.retain(payload, spare)
+    }
+
+    /// Parse the LLC header of an IEEE 802.3 frame.
+    ///
+    /// Returns the header and the payload following it, without any padding of the frame. Returns
+    /// `None` for Ethernet II frames which name their protocol with an ethertype instead.
+    pub fn llc(&self) -> Option<Result<(LlcRepr, &[u8])>> {
+        let payload = match self.frame.llc_payload_slice()? {
+            Ok(payload) => payload,
+            Err(err) => return Some(Err(err.into())),
+        };
+        Some(llc_header::new_checked(payload)
+            .and_then(|header| Ok((LlcRepr::parse(header)?, header.payload_slice())))
+            .map_err(Into::into))
+    }
+
+    /// Recognize a Wake-on-LAN magic packet in the frame.
+    ///
+    /// Like a network card, this finds the pattern anywhere in the payload regardless of the
+    /// protocol of the frame, e.g. also inside a udp datagram. Returns the ethernet address of the
+    /// host that should be woken.
+    pub fn magic_packet(&self) -> Option<EthernetAddress> {
+        wol_find(self.frame.payload_slice())
+    }
 }
 
 impl<'a, P: PayloadMut> In<'a, P> {
     /// Prepare the incoming packet for retransmission, without altering the payload.
     ///
     /// If the length is changed then the longest slice at the end that fits into both
-    /// representations is regarded as the payload of the packet.
+    /// representations is regarded as the payload of the packet. The frame grows or shrinks at its
+    /// front, which need not move the payload when the buffer has headroom.
     pub fn reinit(self, init: Init) -> Result<Out<'a, P>> {
         let In { handle, frame } = self;
         let new_len = ethernet_frame::buffer_len(init.payload);
@@ -138,14 +214,20 @@ impl<'a, P: PayloadMut> In<'a, P> {
 
         // The payload is the common tail.
         let payload = init.payload.min(raw_payload);
-        let old_payload = raw_len - payload..raw_len;
-        let new_payload = new_len - payload..new_len;
+        if raw_buffer.headroom() == 0 {
+            let old_payload = raw_len - payload..raw_len;
+            let new_payload = new_len - payload..new_len;
 
-        raw_buffer.reframe_payload(ReframePayload {
-            length: new_len,
-            old_payload,
-            new_payload,
-        })?;
+            raw_buffer.reframe_payload(ReframePayload {
+                length: new_len,
+                old_payload,
+                new_payload,
+            })?;
+        } else if new_len > raw_len {
+            raw_buffer.prepend(new_len - raw_len)?;
+        } else {
+            raw_buffer.trim_front(raw_len - new_len)?;
+        }
 
         // Now emit the header again:
         new_repr.emit(ethernet_frame::new_unchecked_mut(raw_buffer.payload_mut()));
@@ -210,6 +292,18 @@ impl<'a, P: Payload + PayloadMut> Raw<'a, P> {
         Raw { handle, payload, }
     }
 
+    /// Reserve headroom in front of the frame that is prepared next.
+    ///
+    /// Upper layers can then prepend further headers to the prepared frame, for example to
+    /// encapsulate it in a tunnel, without moving its content. Fails unless the buffer supports
+    /// the requested headroom, see [`PayloadMut::set_headroom`].
+    ///
+    /// [`PayloadMut::set_headroom`]: ../../wire/trait.PayloadMut.html#method.set_headroom
+    pub fn reserve_headroom(&mut self, headroom: usize) -> Result<()> {
+        self.payload.set_headroom(headroom)?;
+        Ok(())
+    }
+
     /// Initialize the raw packet buffer to a valid ethernet frame.
     pub fn prepare(self, init: Init) -> Result<Out<'a, P>> {
         let mut payload = self.payload;
@@ -219,6 +313,44 @@ impl<'a, P: Payload + PayloadMut> Raw<'a, P> {
             frame: EthernetFrame::new_unchecked(payload, repr),
         })
     }
+
+    /// Initialize the raw packet buffer to a Wake-on-LAN magic packet and broadcast it.
+    ///
+    /// The sleeping host has no address configuration, so the frame is sent to all hosts with
+    /// the ethertype of magic packets. Send it as a udp datagram to [`WOL_PORT`] instead if it
+    /// has to cross a router.
+    ///
+    /// [`WOL_PORT`]: ../../wire/constant.WOL_PORT.html
+    pub fn prepare_wake(mut self, magic: WolRepr) -> Result<Out<'a, P>> {
+        let src_addr = self.handle.src_addr();
+        let mut out = self.prepare(Init {
+            src_addr,
+            dst_addr: EthernetAddress::BROADCAST,
+            ethertype: EthernetProtocol::WakeOnLan,
+            payload: magic.buffer_len(),
+        })?;
+        magic.emit(wol_packet::new_unchecked_mut(out.payload_mut_slice()));
+        Ok(out)
+    }
+
+    /// Initialize the raw packet buffer to an IEEE 802.3 frame with an LLC header.
+    ///
+    /// The header is emitted at the start of the payload of the returned frame, the payload
+    /// proper follows after `init.llc.header_len()` bytes. Fails with `BadSize` if the length
+    /// exceeds the maximum payload of such frames.
+    pub fn prepare_llc(self, init: LlcInit) -> Result<Out<'a, P>> {
+        let payload = init.llc.buffer_len(init.payload);
+        let ethertype = EthernetProtocol::from_length(payload)
+            .ok_or(Error::BadSize)?;
+        let mut out = self.prepare(Init {
+            src_addr: init.src_addr,
+            dst_addr: init.dst_addr,
+            ethertype,
+            payload,
+        })?;
+        init.llc.emit(llc_header::new_unchecked_mut(out.payload_mut_slice()));
+        Ok(out)
+    }
 }
 
 impl<P: Payload> Payload for Out<'_, P> {
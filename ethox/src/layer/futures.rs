@@ -0,0 +1,773 @@
+//! Futures-based sockets on top of an interface.
+//!
+//! Available with the `async` feature. A [`Reactor`] owns an [`Interface`] together with the
+//! state of all sockets created on it. Each call to [`Reactor::poll`] receives packets, drives the
+//! timers and sends packets, and then wakes the tasks waiting on a socket that had some activity.
+//! The sockets are handles sharing their state with the reactor, so that they can be moved into
+//! independent tasks of an executor running on the thread of the reactor.
+//!
+//! * [`UdpSocket`] sends and receives datagrams on one local port.
+//! * [`TcpListener`] accepts connections on one local port.
+//! * [`TcpStream`] reads and writes the data of a connection, either accepted or connected with
+//!   [`Reactor::connect`].
+//!
+//! Driving the reactor is left to the application. Poll it in a task of its own, whenever the
+//! device may have received packets or when the expiration it returned has passed. The reactor
+//! uses an unfiltered udp endpoint of the interface, and requires a tcp endpoint for tcp sockets.
+//!
+//! ```
+//! use ethox::layer::{arp, eth, ip, udp};
+//! use ethox::layer::futures::Reactor;
+//! use ethox::layer::stack::{Interface, Stack};
+//! use ethox::managed::Slice;
+//! use ethox::nic::loopback::Loopback;
+//! use ethox::time::Instant;
+//! use ethox::wire::{EthernetAddress, IpCidr, Ipv4Address};
+//!
+//! let stack = Stack::new(
+//!     eth::Endpoint::new(EthernetAddress([0, 1, 2, 3, 4, 5])),
+//!     ip::Endpoint::new(
+//!         IpCidr::new(Ipv4Address::new(10, 0, 0, 1).into(), 24),
+//!         ip::Routes::new(Slice::empty()),
+//!         arp::NeighborCache::new(Slice::empty())));
+//! let nic = Loopback::<Vec<u8>>::new(vec![0; 1 << 12].into());
+//! let mut reactor = Reactor::new(Interface::new(nic, stack));
+//!
+//! let socket = reactor.bind_udp(7).unwrap();
+//! // Hand `socket` to a task that awaits `socket.recv_from(&mut buffer)`, and poll the reactor.
+//! reactor.poll(Instant::ZERO).unwrap();
+//! ```
+//!
+//! [`Interface`]: ../stack/struct.Interface.html
+//! [`Reactor`]: struct.Reactor.html
+//! [`Reactor::connect`]: struct.Reactor.html#method.connect
+//! [`Reactor::poll`]: struct.Reactor.html#method.poll
+//! [`TcpListener`]: struct.TcpListener.html
+//! [`TcpStream`]: struct.TcpStream.html
+//! [`UdpSocket`]: struct.UdpSocket.html
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::poll_fn;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+use std::vec::Vec;
+
+use crate::nic::Device;
+use crate::time::{Expiration, Instant};
+use crate::wire::{IpAddress, IpSubnet, Ipv4Subnet, Ipv6Subnet, PayloadMut};
+
+use super::stack::{Application, Interface};
+use super::tcp::{self, stream::{RecvRing, SendRing, Stream}, Client, SlotKey};
+use super::{udp, Error, Result};
+
+/// The number of received datagrams queued on each udp socket.
+const UDP_QUEUE: usize = 32;
+
+/// The interface and the state of all its sockets.
+pub struct Reactor<'a, D> {
+    interface: Interface<'a, D>,
+    sockets: Rc<RefCell<Sockets>>,
+    buffer: usize,
+}
+
+/// A udp socket bound to a local port.
+///
+/// Received datagrams are queued until they are read, up to a limit after which further datagrams
+/// are dropped. Datagrams to be sent are queued until the reactor is polled.
+pub struct UdpSocket {
+    shared: Rc<RefCell<Sockets>>,
+    idx: usize,
+}
+
+/// A socket accepting tcp connections on a local port.
+///
+/// The listener keeps a listening connection on the tcp endpoint. Each connection attempt is
+/// queued as a new stream, and the listener opens another listening connection on the next poll.
+pub struct TcpListener {
+    shared: Rc<RefCell<Sockets>>,
+    idx: usize,
+}
+
+/// The data stream of a tcp connection.
+///
+/// Dropping the stream closes its sending direction. The connection state is released once the
+/// connection has closed.
+pub struct TcpStream {
+    shared: Rc<RefCell<Sockets>>,
+    idx: usize,
+}
+
+/// The state shared between the reactor and the sockets.
+#[derive(Default)]
+struct Sockets {
+    udp: Vec<Option<UdpState>>,
+    listeners: Vec<Option<ListenerState>>,
+    streams: Vec<Option<StreamState>>,
+    /// The stream offered the next tcp packet buffer.
+    next_stream: usize,
+    /// The buffer size of new streams.
+    buffer: usize,
+}
+
+/// A queued datagram with its remote address and port.
+struct Datagram {
+    addr: IpAddress,
+    port: u16,
+    data: Vec<u8>,
+}
+
+struct UdpState {
+    port: u16,
+    received: VecDeque<Datagram>,
+    queued: VecDeque<Datagram>,
+    waker: Option<Waker>,
+    touched: bool,
+}
+
+struct ListenerState {
+    addr: IpAddress,
+    port: u16,
+    /// The key of the listening connection, if one could be opened.
+    key: Option<SlotKey>,
+    /// The streams of connection attempts that were not yet accepted.
+    backlog: VecDeque<usize>,
+    waker: Option<Waker>,
+    touched: bool,
+}
+
+struct StreamState {
+    conn: Conn,
+    waker: Option<Waker>,
+    touched: bool,
+    /// The stream handle was dropped.
+    dropped: bool,
+}
+
+enum Conn {
+    Connect(Client<RecvRing<'static>, SendRing<'static>>),
+    Accepted(Stream<RecvRing<'static>, SendRing<'static>>),
+}
+
+impl<'a, D> Reactor<'a, D> {
+    /// The default size of the receive and send buffer of each stream.
+    pub const DEFAULT_BUFFER: usize = 1 << 14;
+
+    /// Create a reactor without sockets.
+    ///
+    /// Replaces the udp endpoint of the interface with an unfiltered one, which receives the
+    /// datagrams of all ports bound by a socket.
+    pub fn new(mut interface: Interface<'a, D>) -> Self {
+        interface.udp = Some(udp::Endpoint::new_unfiltered());
+        Reactor {
+            interface,
+            sockets: Rc::default(),
+            buffer: Self::DEFAULT_BUFFER,
+        }
+    }
+
+    /// Get a reference to the interface.
+    pub fn interface(&self) -> &Interface<'a, D> {
+        &self.interface
+    }
+
+    /// Get a mutable reference to the interface, for example to configure its endpoints.
+    pub fn interface_mut(&mut self) -> &mut Interface<'a, D> {
+        &mut self.interface
+    }
+
+    /// Set the size of the receive and send buffer of streams created afterwards.
+    pub fn set_buffer_size(&mut self, buffer: usize) {
+        self.buffer = buffer;
+    }
+
+    /// Create a udp socket on a local port.
+    ///
+    /// Fails with `Illegal` if another socket is bound to the port.
+    pub fn bind_udp(&mut self, port: u16) -> Result<UdpSocket> {
+        let mut sockets = self.sockets.borrow_mut();
+        if sockets.udp.iter().flatten().any(|state| state.port == port) {
+            return Err(Error::Illegal);
+        }
+
+        let idx = insert(&mut sockets.udp, UdpState {
+            port,
+            received: VecDeque::new(),
+            queued: VecDeque::new(),
+            waker: None,
+            touched: false,
+        });
+        Ok(UdpSocket { shared: Rc::clone(&self.sockets), idx })
+    }
+
+    /// Create a listener on a local port.
+    ///
+    /// The address may be unspecified to accept connections to any local address. Fails with
+    /// `Illegal` if another listener uses the same address and port, or if the interface has no
+    /// tcp endpoint.
+    pub fn listen(&mut self, addr: IpAddress, port: u16) -> Result<TcpListener> {
+        if self.interface.tcp.is_none() {
+            return Err(Error::Illegal);
+        }
+
+        let mut sockets = self.sockets.borrow_mut();
+        let bound = |state: &ListenerState| state.addr == addr && state.port == port;
+        if sockets.listeners.iter().flatten().any(bound) {
+            return Err(Error::Illegal);
+        }
+
+        let idx = insert(&mut sockets.listeners, ListenerState {
+            addr,
+            port,
+            key: None,
+            backlog: VecDeque::new(),
+            waker: None,
+            touched: false,
+        });
+        Ok(TcpListener { shared: Rc::clone(&self.sockets), idx })
+    }
+
+    /// Connect to a remote.
+    ///
+    /// The connection is opened on the next poll, the stream can be written to immediately. Fails
+    /// with `Illegal` if the interface has no tcp endpoint.
+    pub fn connect(&mut self, addr: IpAddress, port: u16) -> Result<TcpStream> {
+        if self.interface.tcp.is_none() {
+            return Err(Error::Illegal);
+        }
+
+        let (recv, send) = rings(self.buffer);
+        let mut sockets = self.sockets.borrow_mut();
+        let idx = insert(&mut sockets.streams, StreamState {
+            conn: Conn::Connect(Client::new(addr, port, recv, send)),
+            waker: None,
+            touched: false,
+            dropped: false,
+        });
+        Ok(TcpStream { shared: Rc::clone(&self.sockets), idx })
+    }
+}
+
+impl<'a, D: Device> Reactor<'a, D>
+where
+    D::Handle: Sized,
+    D::Payload: PayloadMut + Sized,
+{
+    /// Poll the interface and wake the tasks of sockets with activity.
+    ///
+    /// Returns the time at which the reactor must be polled again at the latest.
+    pub fn poll(&mut self, now: Instant) -> Result<Expiration> {
+        let wakers = {
+            let mut sockets = self.sockets.borrow_mut();
+            sockets.buffer = self.buffer;
+
+            if let Some(tcp) = &mut self.interface.tcp {
+                for listener in sockets.listeners.iter_mut().flatten() {
+                    if listener.key.is_none() {
+                        listener.key = tcp.listen(listener.addr, listener.port);
+                    }
+                }
+            }
+
+            let next = self.interface.poll(now, &mut *sockets);
+
+            // Release the streams that are no longer referenced.
+            for slot in sockets.streams.iter_mut() {
+                if slot.as_ref().is_some_and(|state| state.dropped && state.conn.is_closed()) {
+                    *slot = None;
+                }
+            }
+
+            let wakers = sockets.wakers();
+            next.map(|next| (next, wakers))
+        };
+
+        // Wake only after the sockets are no longer borrowed.
+        let (next, wakers) = wakers?;
+        wakers.into_iter().for_each(Waker::wake);
+        Ok(next)
+    }
+}
+
+impl UdpSocket {
+    /// The local port of the socket.
+    pub fn port(&self) -> u16 {
+        self.state(|state| state.port)
+    }
+
+    /// Receive a datagram, returning its length and its source address and port.
+    ///
+    /// The datagram is truncated to the length of the buffer.
+    pub fn poll_recv_from(&self, cx: &mut Context, buf: &mut [u8])
+        -> Poll<(usize, IpAddress, u16)>
+    {
+        self.state(|state| match state.received.pop_front() {
+            Some(datagram) => {
+                let len = datagram.data.len().min(buf.len());
+                buf[..len].copy_from_slice(&datagram.data[..len]);
+                Poll::Ready((len, datagram.addr, datagram.port))
+            },
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            },
+        })
+    }
+
+    /// Receive a datagram, returning its length and its source address and port.
+    pub async fn recv_from(&self, buf: &mut [u8]) -> (usize, IpAddress, u16) {
+        poll_fn(|cx| self.poll_recv_from(cx, buf)).await
+    }
+
+    /// Queue a datagram to a remote address and port.
+    ///
+    /// The datagram is sent on the next poll of the reactor.
+    pub fn send_to(&self, data: &[u8], addr: IpAddress, port: u16) {
+        self.state(|state| state.queued.push_back(Datagram {
+            addr,
+            port,
+            data: data.to_vec(),
+        }))
+    }
+
+    fn state<T>(&self, f: impl FnOnce(&mut UdpState) -> T) -> T {
+        let mut sockets = self.shared.borrow_mut();
+        f(sockets.udp[self.idx].as_mut().unwrap())
+    }
+}
+
+impl TcpListener {
+    /// Accept a connection attempt.
+    ///
+    /// The stream may still be in the handshake, reading and writing waits for it to complete.
+    pub fn poll_accept(&self, cx: &mut Context) -> Poll<TcpStream> {
+        let mut sockets = self.shared.borrow_mut();
+        let state = sockets.listeners[self.idx].as_mut().unwrap();
+        match state.backlog.pop_front() {
+            Some(idx) => Poll::Ready(TcpStream { shared: Rc::clone(&self.shared), idx }),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            },
+        }
+    }
+
+    /// Accept a connection attempt.
+    pub async fn accept(&self) -> TcpStream {
+        poll_fn(|cx| self.poll_accept(cx)).await
+    }
+}
+
+impl TcpStream {
+    /// Read received data.
+    ///
+    /// Returns `0` once the remote has closed the connection and all data has been read.
+    pub fn poll_read(&self, cx: &mut Context, buf: &mut [u8]) -> Poll<usize> {
+        self.state(|state| {
+            let closed = state.conn.is_closed();
+            let recv = state.conn.recv_mut();
+            match recv.read(buf) {
+                0 if !buf.is_empty() && !recv.is_closed() && !closed => {
+                    state.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                },
+                read => Poll::Ready(read),
+            }
+        })
+    }
+
+    /// Read received data.
+    pub async fn read(&self, buf: &mut [u8]) -> usize {
+        poll_fn(|cx| self.poll_read(cx, buf)).await
+    }
+
+    /// Write data to the stream, returning the number of bytes written.
+    ///
+    /// Waits while the send buffer is full. Fails with `Illegal` after the stream was closed with
+    /// [`close`] or the connection was terminated.
+    ///
+    /// [`close`]: #method.close
+    pub fn poll_write(&self, cx: &mut Context, data: &[u8]) -> Poll<Result<usize>> {
+        self.state(|state| {
+            let closed = state.conn.is_closed();
+            let send = state.conn.send_mut();
+            if closed || send.is_closed() {
+                return Poll::Ready(Err(Error::Illegal));
+            }
+
+            match send.write(data) {
+                0 if !data.is_empty() => {
+                    state.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                },
+                written => Poll::Ready(Ok(written)),
+            }
+        })
+    }
+
+    /// Write data to the stream, returning the number of bytes written.
+    pub async fn write(&self, data: &[u8]) -> Result<usize> {
+        poll_fn(|cx| self.poll_write(cx, data)).await
+    }
+
+    /// Close the sending direction of the stream.
+    ///
+    /// The FIN is sent after all written data.
+    pub fn close(&self) {
+        self.state(|state| state.conn.send_mut().close())
+    }
+
+    /// Check if the connection was terminated.
+    pub fn is_closed(&self) -> bool {
+        self.state(|state| state.conn.is_closed())
+    }
+
+    fn state<T>(&self, f: impl FnOnce(&mut StreamState) -> T) -> T {
+        let mut sockets = self.shared.borrow_mut();
+        f(sockets.streams[self.idx].as_mut().unwrap())
+    }
+}
+
+impl Drop for UdpSocket {
+    fn drop(&mut self) {
+        self.shared.borrow_mut().udp[self.idx] = None;
+    }
+}
+
+impl Drop for TcpListener {
+    fn drop(&mut self) {
+        // Pending connection attempts are closed with the listener.
+        let mut sockets = self.shared.borrow_mut();
+        let listener = sockets.listeners[self.idx].take().unwrap();
+        for idx in listener.backlog {
+            let state = sockets.streams[idx].as_mut().unwrap();
+            state.conn.send_mut().close();
+            state.dropped = true;
+        }
+    }
+}
+
+impl Drop for TcpStream {
+    fn drop(&mut self) {
+        self.state(|state| {
+            state.conn.send_mut().close();
+            state.dropped = true;
+        })
+    }
+}
+
+impl Sockets {
+    /// Accept a connection attempt on a listening connection.
+    fn accept(&mut self, key: SlotKey) -> Option<usize> {
+        let listener = self.listeners.iter()
+            .position(|state| state.as_ref().is_some_and(|state| state.key == Some(key)))?;
+
+        let (recv, send) = rings(self.buffer);
+        let idx = insert(&mut self.streams, StreamState {
+            conn: Conn::Accepted(Stream::new(key, recv, send)),
+            waker: None,
+            touched: false,
+            dropped: false,
+        });
+
+        let listener = self.listeners[listener].as_mut().unwrap();
+        // A new listening connection is opened on the next poll.
+        listener.key = None;
+        listener.backlog.push_back(idx);
+        listener.touched = true;
+        Some(idx)
+    }
+
+    /// Collect the wakers of all sockets with activity.
+    fn wakers(&mut self) -> Vec<Waker> {
+        fn take(touched: &mut bool, waker: &mut Option<Waker>) -> Option<Waker> {
+            if core::mem::replace(touched, false) {
+                waker.take()
+            } else {
+                None
+            }
+        }
+
+        let udp = self.udp.iter_mut()
+            .flatten()
+            .filter_map(|state| take(&mut state.touched, &mut state.waker));
+        let listeners = self.listeners.iter_mut()
+            .flatten()
+            .filter_map(|state| take(&mut state.touched, &mut state.waker));
+        let streams = self.streams.iter_mut()
+            .flatten()
+            .filter_map(|state| take(&mut state.touched, &mut state.waker));
+        udp.chain(listeners).chain(streams).collect()
+    }
+}
+
+impl<P: PayloadMut> Application<P> for Sockets {
+    fn udp_receive(&mut self, packet: udp::Packet<P>) {
+        let repr = packet.packet.repr();
+        let state = self.udp.iter_mut()
+            .flatten()
+            .find(|state| state.port == repr.dst_port);
+        let state = match state {
+            Some(state) => state,
+            None => return,
+        };
+
+        if state.received.len() < UDP_QUEUE {
+            state.received.push_back(Datagram {
+                addr: packet.packet.get_ref().repr().src_addr(),
+                port: repr.src_port,
+                data: packet.packet.payload_slice().to_vec(),
+            });
+            state.touched = true;
+        }
+    }
+
+    fn udp_send(&mut self, raw: udp::RawPacket<P>) {
+        let state = self.udp.iter_mut()
+            .flatten()
+            .find(|state| !state.queued.is_empty());
+        let (state, datagram) = match state {
+            Some(state) => {
+                let datagram = state.queued.pop_front().unwrap();
+                (state, datagram)
+            },
+            None => return,
+        };
+
+        let source = match datagram.addr {
+            IpAddress::Ipv6(_) => IpSubnet::from(Ipv6Subnet::ANY),
+            _ => IpSubnet::from(Ipv4Subnet::ANY),
+        };
+        let init = udp::Init {
+            source: source.into(),
+            src_port: state.port,
+            dst_addr: datagram.addr,
+            dst_port: datagram.port,
+            payload: datagram.data.len(),
+            dscp: 0,
+        };
+        // Datagrams without a route are dropped, as the network may drop any datagram.
+        if let Ok(mut packet) = raw.prepare(init) {
+            packet.packet.payload_mut_slice().copy_from_slice(&datagram.data);
+            let _ = packet.send();
+        }
+    }
+
+    fn tcp_receive(&mut self, packet: tcp::InPacket<P>) {
+        let key = match packet.key() {
+            Some(key) => key,
+            None => return,
+        };
+
+        let idx = self.streams.iter()
+            .position(|state| state.as_ref().is_some_and(|state| state.conn.key() == Some(key)));
+        let idx = match idx.or_else(|| self.accept(key)) {
+            Some(idx) => idx,
+            None => return,
+        };
+
+        let state = self.streams[idx].as_mut().unwrap();
+        state.conn.receive(packet);
+        state.touched = true;
+    }
+
+    fn tcp_send(&mut self, raw: tcp::RawPacket<P>) {
+        if self.streams.is_empty() {
+            return;
+        }
+
+        // Offer the packet buffers to the streams in turn.
+        for _ in 0..self.streams.len() {
+            let idx = self.next_stream % self.streams.len();
+            self.next_stream = idx + 1;
+            if let Some(state) = &mut self.streams[idx] {
+                if !state.conn.is_closed() {
+                    return state.conn.send(raw);
+                }
+            }
+        }
+    }
+}
+
+impl Conn {
+    fn key(&self) -> Option<SlotKey> {
+        match self {
+            Conn::Connect(client) => client.connection_key(),
+            Conn::Accepted(stream) => stream.connection_key(),
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        match self {
+            Conn::Connect(client) => client.is_closed(),
+            Conn::Accepted(stream) => stream.is_closed(),
+        }
+    }
+
+    fn recv_mut(&mut self) -> &mut RecvRing<'static> {
+        match self {
+            Conn::Connect(client) => client.recv_mut(),
+            Conn::Accepted(stream) => stream.recv_mut(),
+        }
+    }
+
+    fn send_mut(&mut self) -> &mut SendRing<'static> {
+        match self {
+            Conn::Connect(client) => client.send_mut(),
+            Conn::Accepted(stream) => stream.send_mut(),
+        }
+    }
+
+    fn receive<P: PayloadMut>(&mut self, packet: tcp::InPacket<P>) {
+        use tcp::Recv;
+        match self {
+            Conn::Connect(client) => (&mut *client).receive(packet),
+            Conn::Accepted(stream) => (&mut *stream).receive(packet),
+        }
+    }
+
+    fn send<P: PayloadMut>(&mut self, raw: tcp::RawPacket<P>) {
+        use tcp::Send;
+        match self {
+            Conn::Connect(client) => (&mut *client).send(raw),
+            Conn::Accepted(stream) => (&mut *stream).send(raw),
+        }
+    }
+}
+
+/// Insert into the first free slot.
+fn insert<T>(slots: &mut Vec<Option<T>>, value: T) -> usize {
+    match slots.iter().position(Option::is_none) {
+        Some(idx) => {
+            slots[idx] = Some(value);
+            idx
+        },
+        None => {
+            slots.push(Some(value));
+            slots.len() - 1
+        },
+    }
+}
+
+fn rings(buffer: usize) -> (RecvRing<'static>, SendRing<'static>) {
+    (RecvRing::new(vec![0; buffer]), SendRing::new(vec![0; buffer]))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::boxed::Box;
+    use std::future::Future;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::Wake;
+
+    use super::*;
+    use crate::layer::{arp, eth, ip};
+    use crate::layer::stack::Stack;
+    use crate::managed::{List, Map, Slice, SlotMap};
+    use crate::nic::loopback::Loopback;
+    use crate::wire::{EthernetAddress, IpCidr, Ipv4Address};
+
+    const MAC_ADDR_HOST: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 5]);
+    const IP_ADDR_HOST: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
+
+    /// Counts how often it was woken.
+    #[derive(Default)]
+    struct Counter(AtomicUsize);
+
+    impl Wake for Counter {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn reactor() -> Reactor<'static, Loopback<'static, Vec<u8>>> {
+        let mut neighbors = arp::NeighborCache::new(vec![arp::Neighbor::default(); 1]);
+        neighbors.fill(IP_ADDR_HOST.into(), MAC_ADDR_HOST, None).unwrap();
+        let stack = Stack::new(
+            eth::Endpoint::new(MAC_ADDR_HOST),
+            ip::Endpoint::new(IpCidr::new(IP_ADDR_HOST.into(), 24),
+                ip::Routes::new(vec![ip::Route::unspecified(); 1]),
+                neighbors));
+        let nic = Loopback::new(Slice::Many(vec![vec![0; 1 << 11]; 8]));
+        let mut interface = Interface::new(nic, stack);
+        interface.tcp = Some(tcp::Endpoint::new(
+            Map::Pairs(List::new(Slice::Many(vec![Default::default(); 4]))),
+            SlotMap::new(
+                Slice::Many(vec![Default::default(); 4]),
+                Slice::Many(vec![Default::default(); 4])),
+            tcp::IsnGenerator::from_secret_key_bytes([0; 16])));
+        let mut reactor = Reactor::new(interface);
+        reactor.set_buffer_size(1024);
+        reactor
+    }
+
+    #[test]
+    fn udp() {
+        let mut reactor = reactor();
+        let socket = reactor.bind_udp(7).unwrap();
+        assert_eq!(reactor.bind_udp(7).err(), Some(Error::Illegal));
+
+        let counter = Arc::new(Counter::default());
+        let waker = Waker::from(Arc::clone(&counter));
+        let mut cx = Context::from_waker(&waker);
+
+        let mut buffer = [0; 16];
+        let mut recv = Box::pin(socket.recv_from(&mut buffer));
+        assert!(recv.as_mut().poll(&mut cx).is_pending());
+
+        // The datagram is sent in the first poll and received in the second.
+        socket.send_to(b"echo", IP_ADDR_HOST.into(), 7);
+        reactor.poll(Instant::ZERO).unwrap();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+        reactor.poll(Instant::ZERO).unwrap();
+        assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+
+        let received = recv.as_mut().poll(&mut cx);
+        assert_eq!(received, Poll::Ready((4, IP_ADDR_HOST.into(), 7)));
+        drop(recv);
+        assert_eq!(&buffer[..4], b"echo");
+    }
+
+    #[test]
+    fn tcp() {
+        fn run(reactor: &mut Reactor<Loopback<Vec<u8>>>, from: i64) {
+            for millis in (from..from + 1_000).step_by(100) {
+                let now = Instant::from_millis(millis);
+                reactor.interface_mut().device.set_current_time(now);
+                reactor.poll(now).unwrap();
+            }
+        }
+
+        let mut reactor = reactor();
+        let listener = reactor.listen(IP_ADDR_HOST.into(), 80).unwrap();
+        let client = reactor.connect(IP_ADDR_HOST.into(), 80).unwrap();
+
+        let waker = Waker::from(Arc::new(Counter::default()));
+        let mut cx = Context::from_waker(&waker);
+        let mut buffer = [0; 16];
+
+        assert!(listener.poll_accept(&mut cx).is_pending());
+        run(&mut reactor, 0);
+
+        let server = match listener.poll_accept(&mut cx) {
+            Poll::Ready(server) => server,
+            Poll::Pending => panic!("No connection accepted"),
+        };
+        assert!(server.poll_read(&mut cx, &mut buffer).is_pending());
+
+        // The handshake does not open a window, the server speaks first.
+        assert_eq!(server.poll_write(&mut cx, b"hello"), Poll::Ready(Ok(5)));
+        run(&mut reactor, 1_000);
+        assert_eq!(client.poll_read(&mut cx, &mut buffer), Poll::Ready(5));
+        assert_eq!(&buffer[..5], b"hello");
+
+        assert_eq!(client.poll_write(&mut cx, b"world"), Poll::Ready(Ok(5)));
+        client.close();
+        run(&mut reactor, 2_000);
+        assert_eq!(server.poll_read(&mut cx, &mut buffer), Poll::Ready(5));
+        assert_eq!(&buffer[..5], b"world");
+        // The client closed its direction.
+        assert_eq!(server.poll_read(&mut cx, &mut buffer), Poll::Ready(0));
+        assert!(client.poll_read(&mut cx, &mut buffer).is_pending());
+    }
+}
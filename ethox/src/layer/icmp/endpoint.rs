@@ -1,5 +1,6 @@
 use crate::layer::{ip, FnHandler, Result};
-use crate::wire::{Error, Icmpv4Repr, Icmpv4Packet, IpProtocol, Payload, PayloadMut};
+use crate::managed::Slice;
+use crate::wire::{Error, Icmpv4Repr, Icmpv4Packet, IpAddress, IpProtocol, Payload, PayloadMut};
 
 use super::packet::{Handle, In, Raw};
 use super::{Recv, Send};
@@ -20,7 +21,6 @@ enum Empty { }
 /// flag and there is an RFC recommending to do automatic responses where possible without
 /// involving an upper layer. But I suppose there could be some config involved in router
 /// solicitation, timestamps, icmp extended echo authorization, ...
-#[derive(Default)]
 pub struct Endpoint {
     /// Drops echo requests if enabled.
     ///
@@ -32,6 +32,12 @@ pub struct Endpoint {
     ///
     /// If enabled but no handler is configured then these requests are simply dropped.
     manual_echo: bool,
+
+    /// Additional addresses for which echo requests are answered.
+    ///
+    /// These are not local addresses of the ip layer. Only echo requests are accepted for them,
+    /// all other messages addressed to them are dropped.
+    echo_addresses: Slice<'static, IpAddress>,
 }
 
 /// An endpoint borrowed for receiving.
@@ -100,6 +106,24 @@ impl Endpoint {
         self.deny_echo = silent;
     }
 
+    /// Set additional addresses for which to answer echo requests (empty by default).
+    ///
+    /// Echo requests to these addresses are handled exactly as requests to the addresses of the
+    /// ip layer, and answers are sent with the requested address as the source. However, they are
+    /// not installed as local addresses of the ip endpoint. They are never chosen as a source
+    /// address, arp will not answer for them and all other traffic addressed to them is dropped.
+    /// This makes it possible to reflect pings for a large number of test addresses, such as an
+    /// anycast prefix routed towards the host, without otherwise changing the ip configuration.
+    ///
+    /// The addresses should not overlap with the addresses of the ip layer, otherwise non-echo
+    /// messages to that address are dropped as well. Borrowed addresses must be static, such that
+    /// the endpoint need not borrow from its configuration.
+    pub fn echo_addresses<A>(&mut self, addresses: A)
+        where A: Into<Slice<'static, IpAddress>>,
+    {
+        self.echo_addresses = addresses.into();
+    }
+
     /// A receiver that only answers pings in the default manner.
     pub fn answer(&mut self) -> Receiver {
         Receiver { endpoint: self.get_mut(), handler: None, }
@@ -132,7 +156,22 @@ impl Endpoint {
     }
 }
 
+impl Default for Endpoint {
+    fn default() -> Self {
+        Endpoint {
+            deny_echo: false,
+            manual_echo: false,
+            echo_addresses: Slice::empty(),
+        }
+    }
+}
+
 impl EndpointRef<'_> {
+    /// Check if the address is one of the additional echo addresses.
+    fn is_echo_address(&self, dst_addr: IpAddress) -> bool {
+        self.inner.echo_addresses.contains(&dst_addr)
+    }
+
     /// Try to answer or otherwise handle the packet without propagating it upwards.
     fn handle_internally<'a, P: PayloadMut>(&mut self, packet: In<'a, P>)
         -> Result<HandlingKind<'a, P>>
@@ -165,6 +204,7 @@ where
     fn receive(&mut self, ip::InPacket { handle, packet }: ip::InPacket<P>) {
         let capabilities = handle.info().capabilities();
 
+        let dst_addr = packet.repr().dst_addr();
         let icmp = match packet {
             ip::IpPacket::V4(packet) => {
                 if packet.repr().protocol != IpProtocol::Icmp {
@@ -181,6 +221,14 @@ where
             _ => return,
         };
 
+        // Only echo requests are handled for additional echo addresses.
+        if self.endpoint.is_echo_address(dst_addr) {
+            match icmp.repr() {
+                Icmpv4Repr::EchoRequest { .. } => (),
+                _ => return,
+            }
+        }
+
         let handle = Handle::new(handle);
         let packet = In::new(handle, icmp);
 
@@ -197,6 +245,14 @@ where
             _ => (),
         }
     }
+
+    fn accepts_foreign(&self, dst_addr: IpAddress) -> bool {
+        self.endpoint.is_echo_address(dst_addr)
+    }
+
+    fn accepts_protocol(&self, protocol: IpProtocol) -> bool {
+        protocol == IpProtocol::Icmp
+    }
 }
 
 impl<P, T> ip::Send<P> for Sender<'_, T>
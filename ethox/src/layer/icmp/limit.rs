@@ -0,0 +1,100 @@
+use crate::time::{Duration, Instant};
+
+/// The rate limit of generated icmp errors.
+///
+/// Up to `burst` errors are sent back to back, afterwards one more each `interval`. RFC 4443
+/// section 2.4 and RFC 1812 section 4.3.2.8 require such a limit, as errors are sent in reaction
+/// to traffic with a spoofable source address and could otherwise be used for amplification.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RateLimit {
+    /// The maximum number of errors sent in a burst.
+    pub burst: u32,
+
+    /// The interval after which an additional error is permitted.
+    pub interval: Duration,
+}
+
+/// A token bucket enforcing a rate limit.
+///
+/// A single bucket is shared by all kinds of errors that an endpoint generates, so that a flood
+/// of one kind can not be used to circumvent the limit of another.
+#[derive(Clone, Copy, Debug)]
+pub struct Limiter {
+    limit: RateLimit,
+    tokens: u32,
+    last: Option<Instant>,
+}
+
+impl RateLimit {
+    /// Ten errors per second, with bursts of up to eight.
+    pub const DEFAULT: RateLimit = RateLimit {
+        burst: 8,
+        interval: Duration::from_millis(100),
+    };
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        RateLimit::DEFAULT
+    }
+}
+
+impl Limiter {
+    /// Create a limiter with a full burst.
+    pub fn new(limit: RateLimit) -> Self {
+        Limiter {
+            limit,
+            tokens: limit.burst,
+            last: None,
+        }
+    }
+
+    /// Get the enforced rate limit.
+    pub fn limit(&self) -> RateLimit {
+        self.limit
+    }
+
+    /// The number of errors that may currently be sent back to back.
+    pub fn available(&self) -> u32 {
+        self.tokens
+    }
+
+    /// Consume one token if one is available at the given time.
+    pub fn permit(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        match self.tokens.checked_sub(1) {
+            Some(tokens) => {
+                self.tokens = tokens;
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let last = match self.last {
+            Some(last) if last <= now => last,
+            // Also restart if the clock jumped backwards.
+            _ => {
+                self.last = Some(now);
+                return;
+            },
+        };
+
+        let interval = self.limit.interval.as_millis().max(1);
+        let refills = (now - last).as_millis() / interval;
+        if refills == 0 {
+            return;
+        }
+
+        let refills = refills.min(u128::from(self.limit.burst)) as u32;
+        self.tokens = self.tokens.saturating_add(refills).min(self.limit.burst);
+        self.last = Some(now);
+    }
+}
+
+impl Default for Limiter {
+    fn default() -> Self {
+        Limiter::new(RateLimit::DEFAULT)
+    }
+}
@@ -24,13 +24,35 @@
 //! nic, it will try to store it into an internal buffer. If there is not enough space it will try
 //! to forward it to the optional upper layer receiver. If that fails, the packet is discarded.
 //!
+//! Echo requests can also be answered for a set of additional addresses that are not configured in
+//! the ip layer, for example to reflect pings for a whole range of anycast test addresses. See
+//! [`Endpoint::echo_addresses`] for details.
+//!
+//! [`Endpoint::echo_addresses`]: struct.Endpoint.html#method.echo_addresses
+//!
 //! ## Other message types
 //!
 //! All other message types can be received in an upper layer or are simply discarded if there is
 //! no upper handler that is ready to inspect packets.
+//!
+//! ## Rate limit of errors
+//!
+//! Errors such as destination unreachable and time exceeded are generated by the ip layer in
+//! reaction to packets it can not deliver, see [`ip::InPacket::unreachable`] and
+//! [`ip::InPacket::time_exceeded`]. All of them draw from a single token bucket [`Limiter`] of the
+//! ip endpoint, whose [`RateLimit`] is part of its [`ip::IcmpPolicy`].
+//!
+//! [`ip::InPacket::unreachable`]: ../ip/struct.InPacket.html#method.unreachable
+//! [`ip::InPacket::time_exceeded`]: ../ip/struct.InPacket.html#method.time_exceeded
+//! [`ip::IcmpPolicy`]: ../ip/struct.IcmpPolicy.html
+//! [`Limiter`]: struct.Limiter.html
+//! [`RateLimit`]: struct.RateLimit.html
 use crate::wire::Payload;
+#[cfg(feature = "std")]
+use crate::wire::{pretty_print::Formatter, PrettyPrinter, icmpv4_packet, ipv4_packet};
 
 mod endpoint;
+mod limit;
 mod packet;
 #[cfg(test)]
 mod tests;
@@ -41,6 +63,11 @@ pub use endpoint::{
     Sender,
 };
 
+pub use limit::{
+    Limiter,
+    RateLimit,
+};
+
 pub use packet::{
     Handle,
     Init,
@@ -68,3 +95,16 @@ pub trait Send<P: Payload> {
     /// Fill in one available packet buffer.
     fn send(&mut self, raw: RawPacket<P>);
 }
+
+/// Available only on `std` because it prints to standard error, or to the logger with the `log`
+/// feature.
+///
+/// The listing starts at the enclosing ip header so that the addresses are visible as well.
+#[cfg(feature = "std")]
+impl<P: Payload> Recv<P> for Formatter<icmpv4_packet> {
+    fn receive(&mut self, frame: InPacket<P>) {
+        let ip = frame.packet.get_ref().get_ref();
+        let printer = PrettyPrinter::<ipv4_packet>::new("", ip.payload());
+        net_print!("{}", printer);
+    }
+}
@@ -111,6 +111,16 @@ impl<'a, P: Payload> In<'a, P> {
         let payload = self.packet.into_inner().into_inner().into_inner();
         Raw::new(self.handle, payload)
     }
+
+    /// Exchange the buffer of the packet for a spare, see [`eth::InPacket::retain`].
+    ///
+    /// [`eth::InPacket::retain`]: ../eth/struct.InPacket.html#method.retain
+    pub fn retain(self, spare: &mut P) -> Result<()>
+        where P: PayloadMut,
+    {
+        let Raw { mut handle, payload } = self.deinit();
+        handle.inner.retain(payload, spare)
+    }
 }
 
 impl<'a, P: PayloadMut> In<'a, P> {
@@ -137,6 +147,8 @@ impl<'a, P: PayloadMut> In<'a, P> {
             dst_addr: ip_repr.src_addr.into(),
             protocol: IpProtocol::Icmp,
             payload: ip_repr.payload_len,
+            hop_limit: None,
+            dscp: 0,
         })?;
 
         // Temporarily take the packet apart for inner repr.
@@ -252,6 +264,8 @@ impl Init {
                     dst_addr,
                     protocol: IpProtocol::Icmp,
                     payload: len,
+                    hop_limit: None,
+                    dscp: 0,
                 }
             },
         })
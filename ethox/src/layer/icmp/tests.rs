@@ -1,12 +1,13 @@
-use crate::managed::Slice;
 use crate::nic::{loopback::Loopback, Device};
 use crate::layer::{arp, eth, ip, icmp};
-use crate::wire::{EthernetAddress, Ipv4Address, IpCidr, PayloadMut};
+use crate::wire::{EthernetAddress, Ipv4Address, IpAddress, IpCidr, Payload};
+use crate::wire::ipv4_packet;
 
 const MAC_ADDR_HOST: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 5]);
 const IP_ADDR_HOST: Ipv4Address = Ipv4Address::new(127, 0, 0, 1);
 const MAC_ADDR_OTHER: EthernetAddress = EthernetAddress([6, 5, 4, 3, 2, 1]);
 const IP_ADDR_OTHER: Ipv4Address = Ipv4Address::new(127, 0, 0, 2);
+const IP_ADDR_ANYCAST: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
 
 static PING_BYTES: [u8; 50] =
     [   
@@ -23,7 +24,7 @@ static PING_BYTES: [u8; 50] =
 fn answer_ping() {
     let mut nic = Loopback::<Vec<u8>>::new(vec![0; 1 << 12].into());
 
-    queue_ping(&mut nic);
+    queue_ping(&mut nic, IP_ADDR_HOST);
 
     let mut eth = eth::Endpoint::new(MAC_ADDR_HOST);
     
@@ -47,11 +48,57 @@ fn answer_ping() {
    assert_eq!(recv, Ok(1));
 }
 
-fn queue_ping(nic: &mut Loopback<Vec<u8>>) {
-    fn prepare_ping<P: PayloadMut>(packet: icmp::RawPacket<P>) {
+#[test]
+fn answer_echo_address() {
+    let mut nic = Loopback::<Vec<u8>>::new(vec![0; 1 << 12].into());
+
+    let mut eth = eth::Endpoint::new(MAC_ADDR_HOST);
+
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut neighbors[..]);
+        eth_cache.fill(IP_ADDR_OTHER.into(), MAC_ADDR_OTHER, None).unwrap();
+        eth_cache
+    };
+    let mut ip = [ip::Route::unspecified(); 2];
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_HOST.into(), 24),
+        // No routes necessary for local link.
+        ip::Routes::new(&mut ip[..]),
+        neighbors);
+
+    let mut other = eth::Endpoint::new(MAC_ADDR_OTHER);
+    let mut icmp = icmp::Endpoint::new();
+
+    // Not a configured address, the request is dropped.
+    queue_ping(&mut nic, IP_ADDR_ANYCAST);
+    let recv = nic.rx(1, eth.recv(ip.recv(
+        icmp.answer())));
+    assert_eq!(recv, Ok(1));
+    assert_eq!(nic.rx(1, other.recv_with(|_: eth::InPacket<_>| ())), Ok(0));
+
+    icmp.echo_addresses(vec![IpAddress::from(IP_ADDR_ANYCAST)]);
+
+    queue_ping(&mut nic, IP_ADDR_ANYCAST);
+    let recv = nic.rx(1, eth.recv(ip.recv(
+        icmp.answer())));
+    assert_eq!(recv, Ok(1));
+
+    // The answer was queued and uses the anycast address as its source.
+    let mut answered = false;
+    assert_eq!(nic.rx(1, other.recv_with(|packet: eth::InPacket<_>| {
+        let ip = ipv4_packet::new_checked(packet.frame.payload()).unwrap();
+        assert_eq!(ip.src_addr(), IP_ADDR_ANYCAST);
+        assert_eq!(ip.dst_addr(), IP_ADDR_OTHER);
+        answered = true;
+    })), Ok(1));
+    assert!(answered);
+}
+
+fn queue_ping(nic: &mut Loopback<Vec<u8>>, dst_addr: Ipv4Address) {
+    let prepare_ping = |packet: icmp::RawPacket<_>| {
         let init = icmp::Init::EchoRequest {
             source: ip::Source::Exact(IP_ADDR_OTHER.into()),
-            dst_addr: IP_ADDR_HOST.into(),
+            dst_addr: dst_addr.into(),
             ident: 0,
             seq_no: 0,
             payload: PING_BYTES.len(),
@@ -64,7 +111,7 @@ fn queue_ping(nic: &mut Loopback<Vec<u8>>) {
         packet
             .send()
             .expect("Can send the packet");
-    }
+    };
 
     let mut eth = eth::Endpoint::new(MAC_ADDR_OTHER);
 
@@ -74,9 +121,13 @@ fn queue_ping(nic: &mut Loopback<Vec<u8>>) {
         eth_cache.fill(IP_ADDR_HOST.into(), MAC_ADDR_HOST, None).unwrap();
         eth_cache
     };
+    // Anycast addresses are routed via the host.
+    let mut routes = [ip::Route::unspecified(); 1];
+    let mut routes = ip::Routes::new(&mut routes[..]);
+    routes.add_route(ip::Route::new_ipv4_gateway(IP_ADDR_HOST)).unwrap();
     let mut ip = ip::Endpoint::new(
         IpCidr::new(IP_ADDR_OTHER.into(), 24),
-        ip::Routes::new(Slice::empty()),
+        routes,
         neighbors);
 
     let mut icmp = icmp::Endpoint::new();
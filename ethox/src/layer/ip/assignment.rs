@@ -0,0 +1,408 @@
+//! Addresses assigned to an endpoint, with lifetimes.
+//!
+//! Relevant RFCs are rfc4862 for the lifetimes and rfc6724 for the source address selection. A
+//! new address can first be probed for duplicates, following rfc5227 for IPv4 and rfc4862 for
+//! IPv6.
+use core::cmp::Reverse;
+
+use crate::managed::Slice;
+use crate::time::{Duration, Expiration, Instant};
+use crate::wire::{IpAddress, IpCidr, IpSubnet, Ipv4Cidr, Ipv6Cidr};
+
+/// An address assigned to the endpoint.
+///
+/// Besides the address itself and the subnet that is directly reachable through it, an assignment
+/// has two lifetimes. While preferred the address is used for all new communication. Past that
+/// point it is deprecated, it is still accepted and can be used by established connections but it
+/// is only chosen as a source address when no preferred address fits. Past its valid lifetime an
+/// address is no longer used at all and removed by the next poll of the endpoint.
+///
+/// Before all of this, an assignment can be tentative while the endpoint probes whether another
+/// host on the link already uses the address. See [`tentative`].
+///
+/// [`tentative`]: #method.tentative
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Assignment {
+    /// The assigned address and its directly connected subnet.
+    pub cidr: IpCidr,
+
+    /// The point until which the address is preferred.
+    pub preferred_until: Expiration,
+
+    /// The point until which the address is valid.
+    pub valid_until: Expiration,
+
+    /// The state of duplicate address detection, `None` once the address is confirmed.
+    pub probing: Option<Probing>,
+}
+
+/// The duplicate address detection of a tentative assignment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Probing {
+    /// The number of probes still to send.
+    pub remaining: u8,
+
+    /// When the next probe is due or, after the last one, when the address is confirmed.
+    pub next: Instant,
+}
+
+/// The state of an assigned address.
+///
+/// An address starts out as tentative if it is probed for duplicates, otherwise as preferred. It
+/// is deprecated when its preferred lifetime ends and removed when its valid lifetime ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressState {
+    /// The address is probed for duplicates.
+    ///
+    /// It is neither accepted as a destination nor used as a source of any packet.
+    Tentative,
+
+    /// The address is used for all new communication.
+    Preferred,
+
+    /// The address is only used by existing communication or when no preferred one fits.
+    Deprecated,
+}
+
+/// The ranking of an assignment as a source address, greater is better.
+///
+/// The fields are compared in order and correspond to the rules of rfc6724, section 5, that can be
+/// applied without further configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Preference {
+    /// Rule 1, prefer the destination address itself.
+    same_address: bool,
+    /// Rule 2, prefer an address of the scope of the destination.
+    same_scope: bool,
+    /// Rule 3, avoid deprecated addresses.
+    preferred: bool,
+    /// Prefer the address through which the next hop is directly reachable. Not part of the rfc
+    /// but it keeps the choice predictable for addresses of different IPv4 subnets.
+    on_link: bool,
+    /// Rule 8, use the longest matching prefix.
+    common_prefix: u32,
+}
+
+impl Assignment {
+    /// The number of probes for an IPv4 address, `PROBE_NUM` of rfc5227.
+    pub const IPV4_PROBES: u8 = 3;
+
+    /// The number of probes for an IPv6 address, the default `DupAddrDetectTransmits` of rfc4862.
+    pub const IPV6_PROBES: u8 = 1;
+
+    /// The time between probes and after the last one until the address is confirmed.
+    ///
+    /// This is the default `RetransTimer` of rfc4861. Rfc5227 uses a random delay between one and
+    /// two seconds instead, and a longer wait at the end, but a single value suffices here.
+    pub const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// An assignment that is preferred and valid forever.
+    pub fn new(cidr: IpCidr) -> Self {
+        Assignment {
+            cidr,
+            preferred_until: Expiration::Never,
+            valid_until: Expiration::Never,
+            probing: None,
+        }
+    }
+
+    /// An assignment with limited lifetimes.
+    ///
+    /// The preferred lifetime should not end after the valid lifetime, a deprecated address is
+    /// only considered for source selection while it is still valid.
+    pub fn with_lifetimes(
+        cidr: IpCidr,
+        preferred_until: Expiration,
+        valid_until: Expiration,
+    ) -> Self {
+        Assignment {
+            cidr,
+            preferred_until,
+            valid_until,
+            probing: None,
+        }
+    }
+
+    /// Probe for duplicates before using the address.
+    ///
+    /// The endpoint sends the number of probes recommended for the protocol version, the first as
+    /// soon as a sender runs, and confirms the address one interval after the last probe. If
+    /// another host answers or probes for the same address in the meantime, the address is
+    /// removed again. For IPv6, the probes of other hosts are sent to the solicited node multicast
+    /// group of the address which the ethernet endpoint must have joined.
+    pub fn tentative(self) -> Self {
+        let remaining = match self.address() {
+            IpAddress::Ipv4(_) => Self::IPV4_PROBES,
+            _ => Self::IPV6_PROBES,
+        };
+
+        Assignment {
+            probing: Some(Probing { remaining, next: Instant::ZERO }),
+            ..self
+        }
+    }
+
+    /// An unassigned placeholder `0.0.0.0/0`.
+    ///
+    /// May be used as an initializer for the storage of addresses that are added later.
+    pub fn unspecified() -> Self {
+        Assignment::new(IpCidr::new(IpAddress::v4(0, 0, 0, 0), 0))
+    }
+
+    /// Check if this is a placeholder without an actual address.
+    pub fn is_unspecified(&self) -> bool {
+        self.address().is_unspecified()
+    }
+
+    /// The assigned address.
+    pub fn address(&self) -> IpAddress {
+        self.cidr.address()
+    }
+
+    /// The subnet that is directly reachable through this address.
+    pub fn subnet(&self) -> IpSubnet {
+        self.cidr.subnet()
+    }
+
+    /// Check if the address may still be used.
+    pub fn is_valid(&self, now: Instant) -> bool {
+        Expiration::When(now) <= self.valid_until
+    }
+
+    /// Check if the address should be used for new communication.
+    pub fn is_preferred(&self, now: Instant) -> bool {
+        Expiration::When(now) <= self.preferred_until && self.is_valid(now)
+            && !self.is_tentative()
+    }
+
+    /// Check if the address is still probed for duplicates.
+    pub fn is_tentative(&self) -> bool {
+        self.probing.is_some()
+    }
+
+    /// Check if the address may be used at all, i.e. it is valid and not tentative.
+    pub fn is_usable(&self, now: Instant) -> bool {
+        self.is_valid(now) && !self.is_tentative()
+    }
+
+    /// The state of the address, or `None` if it is no longer valid.
+    pub fn state(&self, now: Instant) -> Option<AddressState> {
+        if !self.is_valid(now) {
+            None
+        } else if self.is_tentative() {
+            Some(AddressState::Tentative)
+        } else if self.is_preferred(now) {
+            Some(AddressState::Preferred)
+        } else {
+            Some(AddressState::Deprecated)
+        }
+    }
+
+    /// Check if a probe should be sent now.
+    pub(crate) fn probe_due(&self, now: Instant) -> bool {
+        match self.probing {
+            Some(probing) => probing.remaining > 0 && probing.next <= now,
+            None => false,
+        }
+    }
+
+    /// Account for a sent probe.
+    pub(crate) fn probed(&mut self, now: Instant) {
+        if let Some(probing) = &mut self.probing {
+            probing.remaining = probing.remaining.saturating_sub(1);
+            probing.next = now + Self::PROBE_INTERVAL;
+        }
+    }
+
+    /// Confirm the address if all probes went unanswered.
+    ///
+    /// Returns `true` if the address is no longer tentative as a result.
+    pub(crate) fn confirm(&mut self, now: Instant) -> bool {
+        match self.probing {
+            Some(probing) if probing.remaining == 0 && probing.next <= now => {
+                self.probing = None;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// The next point in time at which the state of the address changes.
+    pub(crate) fn next_timer(&self, now: Instant) -> Expiration {
+        let lifetime = if self.is_preferred(now) {
+            self.preferred_until
+        } else {
+            self.valid_until
+        };
+
+        match self.probing {
+            Some(probing) => lifetime.min(Expiration::When(probing.next)),
+            None => lifetime,
+        }
+    }
+
+    fn preference(&self, dst_addr: IpAddress, next_hop: IpAddress, now: Instant) -> Preference {
+        let address = self.address();
+        Preference {
+            same_address: address == dst_addr,
+            same_scope: address.is_link_local() == is_link_scope(dst_addr),
+            preferred: self.is_preferred(now),
+            on_link: self.subnet().contains(next_hop),
+            common_prefix: common_prefix(address, dst_addr),
+        }
+    }
+}
+
+/// Select the best source address among assignments, for a destination reached via `next_hop`.
+///
+/// Only usable addresses of the same protocol version as the destination are considered. If
+/// several are equally suitable then the one appearing first is chosen, so the primary address
+/// should be the first assignment.
+pub(crate) fn select_source<'a>(
+    assignments: impl IntoIterator<Item=&'a Assignment>,
+    dst_addr: IpAddress,
+    next_hop: IpAddress,
+    now: Instant,
+) -> Option<IpAddress> {
+    assignments.into_iter()
+        .filter(|assigned| assigned.is_usable(now))
+        .filter(|assigned| same_version(assigned.address(), dst_addr))
+        // `min_by_key` returns the first of equal elements, unlike `max_by_key`.
+        .min_by_key(|assigned| Reverse(assigned.preference(dst_addr, next_hop, now)))
+        .map(Assignment::address)
+}
+
+/// Check if an address, unicast or multicast, only reaches the local link.
+fn is_link_scope(addr: IpAddress) -> bool {
+    match addr {
+        IpAddress::Ipv6(addr) if addr.is_multicast() => addr.0[1] & 0x0f <= 2,
+        _ => addr.is_link_local(),
+    }
+}
+
+fn same_version(a: IpAddress, b: IpAddress) -> bool {
+    matches!((a, b),
+        (IpAddress::Ipv4(_), IpAddress::Ipv4(_)) | (IpAddress::Ipv6(_), IpAddress::Ipv6(_)))
+}
+
+/// The number of leading bits two addresses of the same version have in common.
+fn common_prefix(a: IpAddress, b: IpAddress) -> u32 {
+    let mut bits = 0;
+    for (a, b) in a.as_bytes().iter().zip(b.as_bytes()) {
+        let same = (a ^ b).leading_zeros();
+        bits += same;
+        if same < 8 {
+            break;
+        }
+    }
+    bits
+}
+
+impl From<IpCidr> for Assignment {
+    fn from(cidr: IpCidr) -> Self {
+        Assignment::new(cidr)
+    }
+}
+
+impl From<Ipv4Cidr> for Assignment {
+    fn from(cidr: Ipv4Cidr) -> Self {
+        Assignment::new(cidr.into())
+    }
+}
+
+impl From<Ipv6Cidr> for Assignment {
+    fn from(cidr: Ipv6Cidr) -> Self {
+        Assignment::new(cidr.into())
+    }
+}
+
+/// A single address, as the only assignment of an endpoint.
+impl From<IpCidr> for Slice<'_, Assignment> {
+    fn from(cidr: IpCidr) -> Self {
+        Slice::One(cidr.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v6(a0: u16, a7: u16, prefix_len: u8) -> IpCidr {
+        IpCidr::new(IpAddress::v6(a0, 0xdb8, 0, 0, 0, 0, 0, a7), prefix_len)
+    }
+
+    #[test]
+    fn scope_and_lifetime() {
+        let now = Instant::from_secs(10);
+        let link_local = IpCidr::new(IpAddress::v6(0xfe80, 0, 0, 0, 0, 0, 0, 1), 64);
+        let link_local = Assignment::new(link_local);
+        let deprecated = Assignment::with_lifetimes(
+            v6(0x2001, 1, 64),
+            Expiration::When(Instant::from_secs(5)),
+            Expiration::When(Instant::from_secs(20)));
+        let expired = Assignment::with_lifetimes(
+            v6(0x2001, 2, 64),
+            Expiration::When(Instant::from_secs(5)),
+            Expiration::When(Instant::from_secs(5)));
+        let v4 = Assignment::new(IpCidr::new(IpAddress::v4(192, 0, 2, 1), 24));
+        let assigned = [v4, link_local, expired, deprecated];
+
+        let router = IpAddress::v6(0xfe80, 0, 0, 0, 0, 0, 0, 0xff);
+        let global = IpAddress::v6(0x2001, 0xdb9, 0, 0, 0, 0, 0, 1);
+        // A global destination uses the global address even though it is deprecated.
+        assert_eq!(select_source(&assigned, global, router, now), Some(deprecated.address()));
+        // A link-local destination uses the link-local address.
+        assert_eq!(select_source(&assigned, router, router, now), Some(link_local.address()));
+        // Once it is no longer valid, only the link-local address remains.
+        let later = Instant::from_secs(21);
+        assert_eq!(select_source(&assigned, global, router, later), Some(link_local.address()));
+        // Only the same version is used.
+        let v4_dst = IpAddress::v4(192, 0, 2, 2);
+        assert_eq!(select_source(&assigned, v4_dst, v4_dst, now), Some(v4.address()));
+    }
+
+    #[test]
+    fn preferred_and_prefix() {
+        let now = Instant::from_secs(10);
+        let deprecated = Assignment::with_lifetimes(
+            v6(0x2001, 1, 64),
+            Expiration::When(Instant::from_secs(5)),
+            Expiration::Never);
+        let near = Assignment::new(v6(0x2001, 2, 64));
+        let far = Assignment::new(v6(0x3001, 3, 64));
+        let router = IpAddress::v6(0xfe80, 0, 0, 0, 0, 0, 0, 0xff);
+
+        let dst = IpAddress::v6(0x2001, 0xdb8, 0, 0, 0, 0, 0, 9);
+        assert_eq!(select_source(&[deprecated, far, near], dst, router, now), Some(near.address()));
+        let dst = IpAddress::v6(0x3001, 0xdb8, 0, 0, 0, 0, 0, 9);
+        assert_eq!(select_source(&[deprecated, near, far], dst, router, now), Some(far.address()));
+        // The destination itself is always preferred.
+        let dst = deprecated.address();
+        assert_eq!(select_source(&[near, deprecated], dst, router, now), Some(dst));
+        // Ties are broken by order.
+        let dst = IpAddress::v6(0x4001, 0xdb8, 0, 0, 0, 0, 0, 9);
+        assert_eq!(select_source(&[far, near], dst, router, now), Some(far.address()));
+    }
+
+    #[test]
+    fn tentative() {
+        let now = Instant::from_secs(10);
+        let mut tentative = Assignment::new(v6(0x2001, 1, 64)).tentative();
+        let confirmed = Assignment::new(v6(0x2001, 2, 64));
+        let dst = IpAddress::v6(0x2001, 0xdb8, 0, 0, 0, 0, 0, 9);
+        assert_eq!(tentative.state(now), Some(AddressState::Tentative));
+        assert!(!tentative.is_preferred(now));
+        assert_eq!(select_source(&[tentative], dst, dst, now), None);
+        let both = [tentative, confirmed];
+        assert_eq!(select_source(&both, dst, dst, now), Some(confirmed.address()));
+
+        assert!(tentative.probe_due(now));
+        tentative.probed(now);
+        assert!(!tentative.probe_due(now));
+        assert!(!tentative.confirm(now));
+        let later = now + Assignment::PROBE_INTERVAL;
+        assert_eq!(tentative.next_timer(now), Expiration::When(later));
+        assert!(tentative.confirm(later));
+        assert_eq!(tentative.state(later), Some(AddressState::Preferred));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::layer::{Error, Result};
+use crate::wire::{IpAddress, IpProtocol, Payload};
+
+use super::{InPacket, Recv};
+
+/// The number of protocols that can be registered in a table.
+const MAX_HANDLERS: usize = 8;
+
+/// Routes received packets to one of several upper layer handlers by their protocol.
+///
+/// A single ip receiver only takes one upper handler. The table is such a receiver and passes each
+/// packet on to the handler registered for its protocol, so that tcp, udp, icmp and other
+/// protocols are all served in the same receive pass. Packets of protocols without a handler are
+/// given to the raw handler if one is set, and are otherwise not accepted, so that the endpoint
+/// may answer them according to its [`IcmpPolicy`].
+///
+/// Handlers are borrowed for the lifetime of the table which usually spans a single call to
+/// receive packets from the nic.
+///
+/// ## Example
+///
+/// ```
+/// use ethox::layer::ip;
+/// use ethox::wire::{IpProtocol, Payload};
+///
+/// fn dispatch<P: Payload>(tcp: &mut dyn ip::Recv<P>, udp: &mut dyn ip::Recv<P>) {
+///     let mut table = ip::DispatchTable::new();
+///     table.register(IpProtocol::Tcp, tcp).unwrap();
+///     table.register(IpProtocol::Udp, udp).unwrap();
+///     // Hand `&mut table` to the receiver of an ip endpoint.
+/// }
+/// ```
+///
+/// [`IcmpPolicy`]: struct.IcmpPolicy.html
+pub struct DispatchTable<'a, P: Payload> {
+    handlers: [Option<(IpProtocol, &'a mut dyn Recv<P>)>; MAX_HANDLERS],
+    raw: Option<&'a mut dyn Recv<P>>,
+}
+
+impl<'a, P: Payload> DispatchTable<'a, P> {
+    /// Create a table without any handlers.
+    pub fn new() -> Self {
+        DispatchTable {
+            handlers: Default::default(),
+            raw: None,
+        }
+    }
+
+    /// Register the handler of a protocol.
+    ///
+    /// Fails with `Illegal` if the protocol already has a handler and with `Exhausted` if the
+    /// table is full. The table has room for eight protocols.
+    pub fn register(&mut self, protocol: IpProtocol, handler: &'a mut dyn Recv<P>) -> Result<()> {
+        if self.is_registered(protocol) {
+            return Err(Error::Illegal);
+        }
+
+        let slot = self.handlers.iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(Error::Exhausted)?;
+        *slot = Some((protocol, handler));
+        Ok(())
+    }
+
+    /// Set the handler of all protocols without a registered handler.
+    ///
+    /// It is consulted with [`Recv::accepts_protocol`] as well, so it may still decline some.
+    ///
+    /// [`Recv::accepts_protocol`]: trait.Recv.html#method.accepts_protocol
+    pub fn set_raw(&mut self, handler: &'a mut dyn Recv<P>) {
+        self.raw = Some(handler);
+    }
+
+    /// Check if a protocol has a registered handler.
+    pub fn is_registered(&self, protocol: IpProtocol) -> bool {
+        self.handlers.iter()
+            .flatten()
+            .any(|(registered, _)| *registered == protocol)
+    }
+
+    fn handler(&mut self, protocol: IpProtocol) -> Option<&mut (dyn Recv<P> + 'a)> {
+        let registered = self.handlers.iter_mut()
+            .flatten()
+            .find(|(registered, _)| *registered == protocol)
+            .map(|(_, handler)| &mut **handler);
+        match registered {
+            Some(handler) => Some(handler),
+            None => self.raw.as_deref_mut(),
+        }
+    }
+}
+
+impl<P: Payload> Default for DispatchTable<'_, P> {
+    fn default() -> Self {
+        DispatchTable::new()
+    }
+}
+
+impl<P: Payload> Recv<P> for DispatchTable<'_, P> {
+    fn receive(&mut self, frame: InPacket<P>) {
+        let protocol = frame.packet.repr().protocol();
+        if let Some(handler) = self.handler(protocol) {
+            handler.receive(frame)
+        }
+    }
+
+    fn accepts_foreign(&self, dst_addr: IpAddress) -> bool {
+        self.handlers.iter()
+            .flatten()
+            .map(|(_, handler)| &**handler)
+            .chain(self.raw.as_deref())
+            .any(|handler| handler.accepts_foreign(dst_addr))
+    }
+
+    fn accepts_protocol(&self, protocol: IpProtocol) -> bool {
+        let registered = self.handlers.iter()
+            .flatten()
+            .find(|(registered, _)| *registered == protocol);
+        match registered {
+            Some((_, handler)) => handler.accepts_protocol(protocol),
+            None => self.raw.as_ref().is_some_and(|raw| raw.accepts_protocol(protocol)),
+        }
+    }
+}
@@ -1,13 +1,24 @@
 use crate::layer::{arp, eth, FnHandler};
-use crate::layer::{Error, Result};
-use crate::managed::Slice;
-use crate::wire::{EthernetAddress, EthernetProtocol, Payload, PayloadMut};
-use crate::wire::{IpAddress, IpCidr, IpSubnet, Ipv4Packet, Ipv6Packet};
-use crate::time::Instant;
+use crate::layer::{Detail, Error, Failure, Operation, Origin, Result};
+use crate::managed::{List, Slice};
+use crate::wire::{EthernetAddress, EthernetFrame, EthernetProtocol, Payload, PayloadMut};
+use crate::wire::{Icmpv4DstUnreachable, Icmpv4TimeExceeded, IpAddress, IpCidr, IpSubnet};
+use crate::wire::{Ipv4Address, Ipv4Packet};
+use crate::wire::{IpProtocol, Ipv4Subnet, Ipv6Address, Ipv6Packet, Ipv6Repr, Ipv6Scope};
+use crate::wire::{ipv6_packet, ndisc_packet, NdiscMessage};
+use crate::time::{Clock, Expiration, Instant};
 
 use super::{Recv, Send};
-use super::packet::{self, IpPacket, Handle, Route};
-use super::route::Routes;
+use super::assignment::{self, AddressState, Assignment};
+use super::packet::{self, IpPacket, Handle, Route, V6Packet};
+use super::policy::{ExtensionPolicy, IcmpError, IcmpLimiter, IcmpPolicy, OptionPolicy};
+use super::policy::SourceValidation;
+use super::reassembly::Reassembly;
+use super::route::{self, Routes};
+use super::slaac::{self, Slaac};
+#[cfg(feature = "trace")]
+use super::trace::Trace;
+use super::trace::{Header, Record, Verdict};
 
 /// Handles IP connection states.
 ///
@@ -23,6 +34,54 @@ pub struct Endpoint<'a> {
 
     /// Internal ipv4/ipv6 arp state.
     arp: arp::Endpoint<'a>,
+
+    /// Generation and rate limit of icmp errors.
+    icmp: IcmpLimiter,
+
+    /// Handling of received IPv4 packets with header options.
+    options: OptionPolicy,
+
+    /// Handling of the extension headers of received IPv6 packets.
+    extensions: ExtensionPolicy,
+
+    /// Buffers for reassembling IPv6 fragments, if enabled.
+    reassembly: Option<Reassembly<'a>>,
+
+    /// The default hop limit of outgoing packets.
+    hop_limit: u8,
+
+    /// Whether to forward packets to non-local destinations.
+    forwarding: bool,
+
+    /// Stateless address autoconfiguration, if enabled.
+    slaac: Option<Slaac>,
+
+    /// Validation of the source address of received packets.
+    validation: SourceValidation,
+
+    /// Counters of the dropped packets.
+    statistics: Statistics,
+
+    /// Summaries of the last received packets, if enabled.
+    #[cfg(feature = "trace")]
+    trace: Option<Trace<'a>>,
+
+    /// The context of the last failed operation.
+    last_failure: Option<Failure>,
+}
+
+/// Counters of the received packets dropped by an endpoint for their source address.
+///
+/// See [`SourceValidation`] for the checks.
+///
+/// [`SourceValidation`]: enum.SourceValidation.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Statistics {
+    /// The number of packets dropped for a martian source address.
+    pub martian: u64,
+
+    /// The number of packets dropped as their source failed the reverse path check.
+    pub reverse_path: u64,
 }
 
 /// Routing information of an ip endpoint.
@@ -33,11 +92,59 @@ pub struct Endpoint<'a> {
 /// `'data` is the lifetime of the memory referenced for storing the routing data (address
 /// assignments, routing table).
 pub(crate) struct Routing<'data> {
-    /// Our own address.
-    addr: Slice<'data, IpCidr>,
+    /// Our own addresses, the first one is the primary address.
+    addr: List<'data, Assignment>,
 
     /// Routing information.
     routes: Routes<'data>,
+
+    /// Told of each change of the addresses or routes.
+    events: Option<&'data mut dyn ChangeEvents>,
+}
+
+/// An observer of the changes of the addresses and routes of an endpoint.
+///
+/// Registered with [`Endpoint::set_events`], the endpoint calls it synchronously on each change,
+/// for example to log it or to collect the removed addresses. Connections of upper layers are not
+/// reset automatically, act on the collected changes after the call that made them.
+///
+/// [`Endpoint::set_events`]: struct.Endpoint.html#method.set_events
+pub trait ChangeEvents {
+    /// An address or route changed.
+    fn changed(&mut self, change: Change);
+}
+
+/// A change of the addresses or routes of an endpoint, passed to its observer.
+///
+/// Changes are reported regardless of their origin, for example also when address
+/// autoconfiguration adds an address or when an address expires.
+#[derive(Clone, Copy, Debug)]
+pub enum Change {
+    /// An address was assigned.
+    ///
+    /// Not reported when only the lifetimes of an already assigned address are updated.
+    AddressAdded(Assignment),
+
+    /// A tentative address was not found on the link and can now be used.
+    AddressConfirmed(Assignment),
+
+    /// A tentative address is used by another host and was removed.
+    AddressDuplicate(Assignment),
+
+    /// An address was removed or has expired.
+    ///
+    /// Neighbor entries that are no longer on the link of any address have been removed, except
+    /// static ones. Connections of upper layers that use the address as their source are dead,
+    /// e.g. remove them with [`tcp::Endpoint::reset_address`].
+    ///
+    /// [`tcp::Endpoint::reset_address`]: ../tcp/struct.Endpoint.html#method.reset_address
+    AddressRemoved(Assignment),
+
+    /// A route was added or replaced.
+    RouteAdded(route::Route),
+
+    /// A route was removed.
+    RouteRemoved(route::Route),
 }
 
 /// An endpoint borrowed for receiving.
@@ -75,21 +182,36 @@ pub(crate) struct IpEndpoint<'a, 'data> {
 }
 
 impl<'a> Endpoint<'a> {
+    /// The default hop limit of packets sent by an endpoint.
+    ///
+    /// This is the value recommended for the time to live in RFC 1700.
+    pub const DEFAULT_HOP_LIMIT: u8 = 64;
+
     /// Construct a new endpoint handling messages to the specified addresses.
     ///
+    /// The first address is the primary one, which is preferred as a source address when no other
+    /// rule decides between several addresses. Entries created with [`Assignment::unspecified`]
+    /// are not assigned but kept as room for addresses added later with [`add_address`].
+    ///
     /// The neighbors buffer for ARP can be built from an empty slice if it is not needed. This
     /// will however stall send operations indeterminately.
     ///
     /// # Panics
     /// This method will panic if one of the addresses assigned to the interface is not a unicast
     /// address.
+    ///
+    /// [`Assignment::unspecified`]: struct.Assignment.html#method.unspecified
+    /// [`add_address`]: #method.add_address
     pub fn new<A, C, N>(addr: A, routes: C, neighbors: N) -> Self
     where
-        A: Into<Slice<'a, IpCidr>>,
+        A: Into<Slice<'a, Assignment>>,
         C: Into<Routes<'a>>,
         N: Into<arp::NeighborCache<'a>>,
     {
-        let addresses = addr.into();
+        let mut addresses = List::new_full(addr.into());
+        while let Some(idx) = addresses.iter().position(Assignment::is_unspecified) {
+            addresses.remove_at(idx);
+        }
         for addr in addresses.iter() {
             assert!(addr.address().is_unicast());
         }
@@ -97,8 +219,21 @@ impl<'a> Endpoint<'a> {
             routing: Routing {
                 addr: addresses,
                 routes: routes.into(),
+                events: None,
             },
             arp: arp::Endpoint::new(neighbors.into()),
+            icmp: IcmpLimiter::new(IcmpPolicy::default()),
+            options: OptionPolicy::default(),
+            extensions: ExtensionPolicy::default(),
+            reassembly: None,
+            hop_limit: Self::DEFAULT_HOP_LIMIT,
+            forwarding: false,
+            slaac: None,
+            validation: SourceValidation::default(),
+            statistics: Statistics::default(),
+            #[cfg(feature = "trace")]
+            trace: None,
+            last_failure: None,
         }
     }
 
@@ -127,6 +262,120 @@ impl<'a> Endpoint<'a> {
         Layer { endpoint: self.ip() }
     }
 
+    /// Drive the timers of the embedded arp endpoint.
+    ///
+    /// Returns the next time at which the layer internal maintenance should be run. See
+    /// [`arp::Endpoint::poll`] for details.
+    ///
+    /// [`arp::Endpoint::poll`]: ../arp/struct.Endpoint.html#method.poll
+    pub fn poll(&mut self, now: Instant) -> Expiration {
+        let (addresses, removed) = self.routing.expire(now);
+        if removed {
+            self.flush_neighbors();
+        }
+        self.arp.poll(now).min(addresses)
+    }
+
+    /// Drive the timers with the current time of a clock.
+    pub fn tick<C: Clock + ?Sized>(&mut self, clock: &C) -> Expiration {
+        self.poll(clock.now())
+    }
+
+    /// The addresses currently assigned to the endpoint.
+    pub fn addresses(&self) -> &[Assignment] {
+        &self.routing.addr
+    }
+
+    /// Assign an additional address or update the lifetimes of an assigned one.
+    ///
+    /// New addresses are secondary to all existing ones. Fails with `Exhausted` when there is no
+    /// more room in the address storage. A [tentative] assignment is only used once the endpoint
+    /// has confirmed that no other host on the link has the same address.
+    ///
+    /// [tentative]: struct.Assignment.html#method.tentative
+    ///
+    /// # Panics
+    /// This method will panic if the address is not a unicast address.
+    pub fn add_address(&mut self, assignment: Assignment) -> Result<()> {
+        assert!(assignment.address().is_unicast());
+        self.routing.assign(assignment)
+    }
+
+    /// Remove an assigned address.
+    ///
+    /// Neighbor entries that are no longer on the link of any remaining address are removed from
+    /// the cache as well, except for static ones. Returns the removed assignment or `None` if the
+    /// address was not assigned.
+    pub fn remove_address(&mut self, address: IpAddress) -> Option<Assignment> {
+        let removed = self.routing.unassign(address)?;
+        self.flush_neighbors();
+        Some(removed)
+    }
+
+    /// The state of an assigned address.
+    ///
+    /// Returns `None` if the address is not assigned or no longer valid. Upper layers should not
+    /// use a tentative address as the source of their packets, the endpoint refuses to send them.
+    pub fn address_state(&self, address: IpAddress, now: Instant) -> Option<AddressState> {
+        self.routing.assignment(address)?.state(now)
+    }
+
+    /// The routes of the endpoint.
+    pub fn routes(&self) -> &[route::Route] {
+        self.routing.routes.as_slice()
+    }
+
+    /// Add a route.
+    ///
+    /// Fails with `Exhausted` when there is no more room in the routing table.
+    pub fn add_route(&mut self, route: route::Route) -> Result<()> {
+        self.routing.routes.add_route(route)?;
+        self.routing.notify(Change::RouteAdded(route));
+        Ok(())
+    }
+
+    /// Add a route or replace the one for the same network and next hop.
+    ///
+    /// See [`Routes::update_route`] for details.
+    ///
+    /// [`Routes::update_route`]: struct.Routes.html#method.update_route
+    pub fn update_route(&mut self, route: route::Route, now: Instant) -> Result<()> {
+        self.routing.update_route(route, now)
+    }
+
+    /// Remove the route for a network via a next hop.
+    ///
+    /// Returns the removed route or `None` if there was no such route.
+    pub fn remove_route(&mut self, net: IpSubnet, next_hop: IpAddress) -> Option<route::Route> {
+        self.routing.remove_route(net, next_hop)
+    }
+
+    /// Register an observer of the changes of addresses and routes.
+    ///
+    /// See [`ChangeEvents`] for when it is called.
+    ///
+    /// [`ChangeEvents`]: trait.ChangeEvents.html
+    pub fn set_events(&mut self, events: &'a mut dyn ChangeEvents) {
+        self.routing.events = Some(events);
+    }
+
+    /// Remove the observer of changes.
+    pub fn clear_events(&mut self) {
+        self.routing.events = None;
+    }
+
+    /// Remove neighbors that are not on the link of any address, except static ones.
+    fn flush_neighbors(&mut self) {
+        let Endpoint { routing, arp, .. } = self;
+        let neighbors = arp.neighbors_mut();
+        while let Some(stale) = neighbors.iter()
+            .find(|neighbor| !neighbor.is_static() && !routing.covers(neighbor.protocol_addr()))
+            .map(|neighbor| neighbor.protocol_addr())
+        {
+            let _ = neighbors.remove(stale);
+        }
+    }
+
     fn ip(&mut self) -> IpEndpoint<'_, 'a> {
         IpEndpoint {
             inner: self,
@@ -138,14 +387,346 @@ impl<'a> Endpoint<'a> {
         self.routing.accepts(dst_addr)
     }
 
+    /// Get the default hop limit of outgoing packets.
+    pub fn hop_limit(&self) -> u8 {
+        self.hop_limit
+    }
+
+    /// Change the default hop limit of outgoing packets.
+    ///
+    /// Individual packets can override this in their [`Init`].
+    ///
+    /// [`Init`]: struct.Init.html
+    pub fn set_hop_limit(&mut self, hop_limit: u8) {
+        self.hop_limit = hop_limit;
+    }
+
+    /// Check if packets to non-local destinations are forwarded.
+    pub fn forwarding(&self) -> bool {
+        self.forwarding
+    }
+
+    /// Enable or disable forwarding of packets to non-local destinations.
+    ///
+    /// When enabled, received packets whose destination is neither a configured address nor
+    /// claimed by the upper layer handler are forwarded to their next hop on the same device
+    /// instead of being dropped. See [`InPacket::forward`] for details. Disabled by default.
+    ///
+    /// [`InPacket::forward`]: struct.InPacket.html#method.forward
+    pub fn set_forwarding(&mut self, forwarding: bool) {
+        self.forwarding = forwarding;
+    }
+
+    /// Get the policy for received IPv4 packets with header options.
+    pub fn option_policy(&self) -> OptionPolicy {
+        self.options
+    }
+
+    /// Change the policy for received IPv4 packets with header options.
+    ///
+    /// Dropped packets are recorded as the last failure of the endpoint.
+    pub fn set_option_policy(&mut self, policy: OptionPolicy) {
+        self.options = policy;
+    }
+
+    /// Get the policy for the extension headers of received IPv6 packets.
+    pub fn extension_policy(&self) -> ExtensionPolicy {
+        self.extensions
+    }
+
+    /// Change the policy for the extension headers of received IPv6 packets.
+    pub fn set_extension_policy(&mut self, policy: ExtensionPolicy) {
+        self.extensions = policy;
+    }
+
+    /// Get the state of IPv6 fragment reassembly, if enabled.
+    pub fn reassembly(&self) -> Option<&Reassembly<'a>> {
+        self.reassembly.as_ref()
+    }
+
+    /// Enable or disable the reassembly of IPv6 fragments.
+    ///
+    /// Without reassembly buffers fragments are dropped, unless they are the only fragment of
+    /// their packet. Incomplete packets are lost when the buffers are replaced.
+    pub fn set_reassembly(&mut self, reassembly: Option<Reassembly<'a>>) {
+        self.reassembly = reassembly;
+    }
+
+    /// Get the policy for generating icmp errors.
+    pub fn icmp_policy(&self) -> IcmpPolicy {
+        self.icmp.policy()
+    }
+
+    /// Change the policy for generating icmp errors.
+    ///
+    /// This also resets the rate limit to a full burst.
+    pub fn set_icmp_policy(&mut self, policy: IcmpPolicy) {
+        self.icmp = IcmpLimiter::new(policy);
+    }
+
+    /// Get the validation of the source address of received packets.
+    pub fn source_validation(&self) -> SourceValidation {
+        self.validation
+    }
+
+    /// Change the validation of the source address of received packets.
+    ///
+    /// Dropped packets are counted in the [`statistics`] and recorded as the last failure.
+    ///
+    /// [`statistics`]: #method.statistics
+    pub fn set_source_validation(&mut self, validation: SourceValidation) {
+        self.validation = validation;
+    }
+
+    /// Get the counters of dropped packets.
+    pub fn statistics(&self) -> Statistics {
+        self.statistics
+    }
+
+    /// Get the trace of the last received packets, if enabled.
+    #[cfg(feature = "trace")]
+    pub fn trace(&self) -> Option<&Trace<'a>> {
+        self.trace.as_ref()
+    }
+
+    /// Get the trace of the last received packets mutably, for example to clear it.
+    #[cfg(feature = "trace")]
+    pub fn trace_mut(&mut self) -> Option<&mut Trace<'a>> {
+        self.trace.as_mut()
+    }
+
+    /// Enable or disable recording the last received packets.
+    ///
+    /// Each packet of an ip protocol is recorded with its verdict once the layer is done with it.
+    /// A packet delivered to the upper layer counts as delivered even if the handler drops it.
+    #[cfg(feature = "trace")]
+    pub fn set_trace(&mut self, trace: Option<Trace<'a>>) {
+        self.trace = trace;
+    }
+
+    /// Get the configuration of stateless address autoconfiguration, if enabled.
+    pub fn slaac(&self) -> Option<&Slaac> {
+        self.slaac.as_ref()
+    }
+
+    /// Enable or disable stateless address autoconfiguration.
+    ///
+    /// While enabled, router advertisements are consumed by the endpoint. They add the advertising
+    /// router as a default route and assign addresses for the advertised prefixes, including the
+    /// link-local address of the interface. New addresses are tentative until duplicate address
+    /// detection confirms them, see [`Assignment::tentative`]. Addresses and routes are kept until
+    /// their lifetimes end when it is disabled again.
+    ///
+    /// [`Assignment::tentative`]: struct.Assignment.html#method.tentative
+    pub fn set_slaac(&mut self, slaac: Option<Slaac>) {
+        self.slaac = slaac;
+    }
+
+    /// Get the neighbor cache of the embedded arp endpoint.
+    ///
+    /// Together with [`neighbors_mut`] this allows synchronizing the table with the neighbor table
+    /// of the operating system or a central controller.
+    ///
+    /// [`neighbors_mut`]: #method.neighbors_mut
+    pub fn neighbors(&self) -> &arp::NeighborCache<'a> {
+        self.arp.neighbors()
+    }
+
+    /// Get the neighbor cache of the embedded arp endpoint mutably.
+    pub fn neighbors_mut(&mut self) -> &mut arp::NeighborCache<'a> {
+        self.arp.neighbors_mut()
+    }
+
+    /// Answer arp requests for some prefixes in proxy of other hosts.
+    ///
+    /// See [`arp::Endpoint::set_proxy`] for details. Combine this with forwarding to front hosts
+    /// that are not on the link themselves.
+    ///
+    /// [`arp::Endpoint::set_proxy`]: ../arp/struct.Endpoint.html#method.set_proxy
+    pub fn set_proxy_arp<S>(&mut self, prefixes: S)
+        where S: Into<Slice<'a, Ipv4Subnet>>,
+    {
+        self.arp.set_proxy(prefixes);
+    }
+
+    /// The context of the last failure of the endpoint.
+    ///
+    /// Records why routing, resolving a neighbor or forwarding failed and why a received packet
+    /// was dropped as malformed. Kept until the next failure, or until it is cleared.
+    pub fn last_failure(&self) -> Option<Failure> {
+        self.last_failure
+    }
+
+    /// Forget the last failure.
+    pub fn clear_failure(&mut self) {
+        self.last_failure = None;
+    }
+
     pub(crate) fn routing(&mut self) -> &mut Routing<'a> {
         &mut self.routing
     }
 }
 
-impl Routing<'_> {
+impl<'data> Routing<'data> {
     pub(crate) fn accepts(&self, dst_addr: IpAddress) -> bool {
-        self.addr.iter().any(|own_addr| own_addr.accepts(dst_addr))
+        self.addr.iter()
+            .any(|own_addr| !own_addr.is_tentative() && own_addr.cidr.accepts(dst_addr))
+    }
+
+    /// Check if an address is assigned but still probed for duplicates.
+    pub(crate) fn is_tentative(&self, address: IpAddress) -> bool {
+        self.assignment(address).is_some_and(Assignment::is_tentative)
+    }
+
+    /// The tentative addresses for which a probe should be sent now.
+    pub(crate) fn probes(&self, now: Instant) -> impl Iterator<Item=IpAddress> + '_ {
+        self.addr.iter()
+            .filter(move |addr| addr.probe_due(now))
+            .map(Assignment::address)
+    }
+
+    /// Account for a probe sent for a tentative address.
+    pub(crate) fn probed(&mut self, address: IpAddress, now: Instant) {
+        if let Some(assigned) = self.addr.iter_mut().find(|addr| addr.address() == address) {
+            assigned.probed(now);
+        }
+    }
+
+    /// Remove a tentative address that another host uses as well.
+    ///
+    /// Returns `true` if the address was tentative and has been removed.
+    pub(crate) fn duplicate(&mut self, address: IpAddress) -> bool {
+        let idx = match self.addr.iter()
+            .position(|addr| addr.address() == address && addr.is_tentative())
+        {
+            Some(idx) => idx,
+            None => return false,
+        };
+
+        if let Some(removed) = self.addr.remove_at(idx) {
+            let removed = *removed;
+            self.notify(Change::AddressDuplicate(removed));
+        }
+        true
+    }
+
+    /// Get the assignment of an address.
+    pub(crate) fn assignment(&self, address: IpAddress) -> Option<&Assignment> {
+        self.addr.iter().find(|addr| addr.address() == address)
+    }
+
+    /// Add an assignment or replace the one of the same address.
+    pub(crate) fn assign(&mut self, assignment: Assignment) -> Result<()> {
+        let addresses = &mut self.addr;
+        match addresses.iter().position(|addr| addr.address() == assignment.address()) {
+            Some(idx) => addresses[idx] = assignment,
+            None => {
+                *addresses.push().ok_or(Error::Exhausted)? = assignment;
+                self.notify(Change::AddressAdded(assignment));
+            },
+        }
+        Ok(())
+    }
+
+    /// Remove the assignment of an address.
+    pub(crate) fn unassign(&mut self, address: IpAddress) -> Option<Assignment> {
+        let idx = self.addr.iter().position(|addr| addr.address() == address)?;
+        let removed = *self.addr.remove_at(idx)?;
+        self.notify(Change::AddressRemoved(removed));
+        Some(removed)
+    }
+
+    /// Add a route or replace the one for the same network and next hop.
+    pub(crate) fn update_route(&mut self, route: route::Route, now: Instant) -> Result<()> {
+        self.routes.update_route(route, now)?;
+        self.notify(Change::RouteAdded(route));
+        Ok(())
+    }
+
+    /// Remove the route for a network via a next hop.
+    pub(crate) fn remove_route(&mut self, net: IpSubnet, next_hop: IpAddress)
+        -> Option<route::Route>
+    {
+        let removed = self.routes.remove_route(net, next_hop)?;
+        self.notify(Change::RouteRemoved(removed));
+        Some(removed)
+    }
+
+    fn notify(&mut self, change: Change) {
+        if let Some(events) = &mut self.events {
+            events.changed(change);
+        }
+    }
+
+    /// Remove addresses past their valid lifetime and confirm tentative ones.
+    ///
+    /// Returns the next time at which the state of an address changes, and if any address was
+    /// removed.
+    fn expire(&mut self, now: Instant) -> (Expiration, bool) {
+        for idx in 0..self.addr.len() {
+            if self.addr[idx].confirm(now) {
+                let confirmed = self.addr[idx];
+                self.notify(Change::AddressConfirmed(confirmed));
+            }
+        }
+
+        let mut removed = false;
+        while let Some(idx) = self.addr.iter().position(|addr| !addr.is_valid(now)) {
+            if let Some(expired) = self.addr.remove_at(idx) {
+                let expired = *expired;
+                self.notify(Change::AddressRemoved(expired));
+            }
+            removed = true;
+        }
+
+        let next = self.addr.iter()
+            .map(|addr| addr.next_timer(now))
+            .min()
+            .unwrap_or(Expiration::Never);
+        (next, removed)
+    }
+
+    /// Check if an address is the limited broadcast or the broadcast of one of our subnets.
+    pub(crate) fn is_broadcast(&self, addr: IpAddress) -> bool {
+        let addr = match addr {
+            IpAddress::Ipv4(addr) => addr,
+            _ => return false,
+        };
+
+        addr.is_broadcast() || self.addr.iter().any(|own_addr| match own_addr.cidr {
+            IpCidr::Ipv4(cidr) => cidr.broadcast().is_some_and(|cidr| cidr.address() == addr),
+            _ => false,
+        })
+    }
+
+    /// Check if an address is in the subnet of one of our addresses, regardless of lifetimes.
+    fn covers(&self, addr: IpAddress) -> bool {
+        self.addr.iter().any(|own_addr| own_addr.subnet().contains(addr))
+    }
+
+    /// Check if an address is never a valid source of a packet to a destination.
+    pub(crate) fn is_martian(&self, src_addr: IpAddress, dst_addr: IpAddress) -> bool {
+        let to_group = dst_addr.is_multicast() || self.is_broadcast(dst_addr);
+        match src_addr {
+            IpAddress::Ipv4(addr) if addr.is_unspecified() => {
+                addr != Ipv4Address::UNSPECIFIED || !to_group
+            },
+            IpAddress::Ipv4(addr) => {
+                addr.is_multicast()
+                    || addr.as_bytes()[0] >= 240
+                    || self.is_broadcast(src_addr)
+                    || (addr.is_loopback() && !self.covers(src_addr))
+            },
+            IpAddress::Ipv6(addr) if addr.is_unspecified() => !to_group,
+            IpAddress::Ipv6(addr) => addr.is_multicast() || addr.is_loopback(),
+            _ => true,
+        }
+    }
+
+    /// Check if an address is directly reachable through one of our addresses.
+    fn on_link(&self, addr: IpAddress, time: Instant) -> bool {
+        self.addr.iter()
+            .any(|own_addr| own_addr.is_usable(time) && own_addr.subnet().contains(addr))
     }
 
     /// Find the route to use.
@@ -157,6 +738,16 @@ impl Routing<'_> {
     ///
     /// For lack of direct loopback mechanism (TODO) we only implement the second two stages.
     pub(crate) fn route(&self, dst_addr: IpAddress, time: Instant) -> Option<Route> {
+        match dst_addr {
+            IpAddress::Ipv6(addr) if addr.is_multicast() => {
+                return self.find_multicast_route(dst_addr, time);
+            },
+            IpAddress::Ipv4(addr) if addr.is_broadcast() => {
+                return self.find_multicast_route(dst_addr, time);
+            },
+            _ => (),
+        }
+
         if let Some(route) = self.find_local_route(dst_addr, time) {
             return Some(route)
         }
@@ -164,32 +755,50 @@ impl Routing<'_> {
         self.find_outer_route(dst_addr, time)
     }
 
-    pub(crate) fn find_local_route(&self, dst_addr: IpAddress, _: Instant) -> Option<Route> {
-        let matching_src = self.addr
-            .iter()
-            .filter(|addr| addr.subnet().contains(dst_addr))
-            .nth(0)?;
+    pub(crate) fn find_local_route(&self, dst_addr: IpAddress, time: Instant) -> Option<Route> {
+        if !self.on_link(dst_addr, time) {
+            return None;
+        }
+
+        Some(Route {
+            src_addr: assignment::select_source(self.addr.iter(), dst_addr, dst_addr, time)?,
+            next_hop: dst_addr,
+        })
+    }
 
+    /// Multicast groups and the limited broadcast are reached on the link directly.
+    ///
+    /// Only the groups of ipv6 are supported, which have a well-defined mapping to ethernet. The
+    /// broadcast of a subnet is reached like any other address in it.
+    pub(crate) fn find_multicast_route(&self, dst_addr: IpAddress, time: Instant) -> Option<Route> {
         Some(Route {
-            src_addr: matching_src.address(),
+            src_addr: assignment::select_source(self.addr.iter(), dst_addr, dst_addr, time)?,
             next_hop: dst_addr,
         })
     }
 
     pub(crate) fn find_outer_route(&self, dst_addr: IpAddress, time: Instant) -> Option<Route> {
         let next_hop = self.routes.lookup(dst_addr, time)?;
+        if !self.on_link(next_hop, time) {
+            return None;
+        }
 
-        // Which source to use?
-        let src_addr = self.addr
-            .iter()
-            .filter(|addr| addr.subnet().contains(next_hop))
-            .nth(0)?;
-
+        // Which source to use? Any of our addresses can be used with the next hop, not only the
+        // one in its subnet. This matters for a global destination behind a link-local router.
         Some(Route {
+            src_addr: assignment::select_source(self.addr.iter(), dst_addr, next_hop, time)?,
             next_hop,
-            src_addr: src_addr.address(),
         })
     }
+
+    /// Find the best source address within a subnet.
+    fn local_ip(&self, subnet: IpSubnet, time: Instant) -> Option<IpAddress> {
+        self.addr.iter()
+            .filter(|addr| addr.is_usable(time) && subnet.contains(addr.address()))
+            // Prefer the first non-deprecated address.
+            .min_by_key(|addr| !addr.is_preferred(time))
+            .map(Assignment::address)
+    }
 }
 
 impl<'data> IpEndpoint<'_, 'data> {
@@ -201,43 +810,366 @@ impl<'data> IpEndpoint<'_, 'data> {
         self.inner.arp.neighbors_mut()
     }
 
+    /// Process a router advertisement for address autoconfiguration.
+    ///
+    /// Returns `true` if the packet was consumed.
+    fn router_advert<P: Payload>(
+        &mut self,
+        packet: &V6Packet<P>,
+        handle: &mut eth::Handle,
+    ) -> bool {
+        let Endpoint { routing, arp, slaac, .. } = &mut *self.inner;
+        let slaac = match slaac {
+            Some(slaac) => slaac,
+            None => return false,
+        };
+
+        let repr = packet.repr();
+        let all_nodes = Ipv6Address::all_nodes_multicast(Ipv6Scope::LinkLocal);
+        if repr.dst_addr != all_nodes && !routing.accepts(repr.dst_addr.into()) {
+            return false;
+        }
+
+        let advert = match slaac::router_advert(repr, packet.payload_slice()) {
+            Some(advert) => advert,
+            None => return false,
+        };
+
+        let now = handle.info().timestamp();
+        let hw_addr = handle.src_addr();
+        slaac.router_advert(advert, repr.src_addr, hw_addr, routing, arp.neighbors_mut(), now);
+        true
+    }
+
+    /// Check a neighbor solicitation or advertisement for another host with a tentative address.
+    ///
+    /// Returns `true` if the packet revealed a duplicate and was consumed. See RFC4862, section
+    /// 5.4.3 and 5.4.4.
+    fn duplicate_address<P: Payload>(
+        &mut self,
+        packet: &V6Packet<P>,
+        handle: &mut eth::Handle,
+    ) -> bool {
+        let repr = packet.repr();
+        if repr.next_header != IpProtocol::Icmpv6 || repr.hop_limit != 255 {
+            return false;
+        }
+
+        let message = match ndisc_packet::new_checked(packet.payload_slice()) {
+            Ok(message) => message,
+            Err(_) => return false,
+        };
+
+        match message.msg_type() {
+            NdiscMessage::NeighborAdvert => (),
+            // Only the probes of other hosts, which are sent from the unspecified address.
+            NdiscMessage::NeighborSolicit if repr.src_addr.is_unspecified() => (),
+            _ => return false,
+        }
+
+        if message.msg_code() != 0 || !message.verify_checksum(repr.src_addr, repr.dst_addr) {
+            return false;
+        }
+
+        // Our own probes may be looped back by the device.
+        if packet.get_ref().repr().src_addr == handle.src_addr() {
+            return false;
+        }
+
+        self.inner.routing.duplicate(message.target_addr().into())
+    }
+
+    /// Send a neighbor solicitation probing for another host with a tentative address.
+    ///
+    /// See RFC4862, section 5.4.2.
+    fn solicit<P>(&mut self, packet: eth::RawPacket<P>, target: Ipv6Address)
+        where P: Payload + PayloadMut,
+    {
+        const SOLICIT_LEN: usize = 24;
+        let eth::RawPacket { mut handle, payload } = packet;
+        let now = handle.info().timestamp();
+        let dst_addr = target.solicited_node_multicast();
+
+        let init = eth::Init {
+            src_addr: handle.src_addr(),
+            dst_addr: EthernetAddress::from_ipv6_multicast(dst_addr),
+            ethertype: EthernetProtocol::Ipv6,
+            payload: 40 + SOLICIT_LEN,
+        };
+
+        let mut out = match eth::RawPacket::new(handle, payload).prepare(init) {
+            Ok(out) => out,
+            Err(_) => return,
+        };
+
+        let repr = Ipv6Repr {
+            src_addr: Ipv6Address::UNSPECIFIED,
+            dst_addr,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: SOLICIT_LEN,
+            hop_limit: 255,
+        };
+
+        let buffer = out.payload_mut_slice();
+        repr.emit(ipv6_packet::new_unchecked_mut(buffer));
+        let solicit = &mut buffer[40..40 + SOLICIT_LEN];
+        // Clears the reserved word, no options are allowed from the unspecified address.
+        solicit.fill(0);
+        let solicit = ndisc_packet::new_unchecked_mut(solicit);
+        solicit.set_msg_type(NdiscMessage::NeighborSolicit);
+        solicit.set_target_addr(target);
+        solicit.fill_checksum(repr.src_addr, dst_addr);
+
+        if out.send().is_ok() {
+            self.inner.routing.probed(target.into(), now);
+        }
+    }
+
+    /// Process the extension headers of a packet and remove them from its buffer.
+    ///
+    /// Returns the packet with its upper layer protocol directly behind the fixed header, or
+    /// the verdict if it was dropped or is an incomplete fragment.
+    fn extensions<'p, P: PayloadMut>(&mut self, mut packet: V6Packet<'p, P>, now: Instant)
+        -> core::result::Result<V6Packet<'p, P>, Verdict>
+    {
+        loop {
+            let upper = match self.inner.extensions.walk(&packet) {
+                Ok(upper) => upper,
+                Err(err) => return Err(self.malformed(err)),
+            };
+
+            let fragment = match upper.fragment {
+                None if upper.offset == 0 => return Ok(packet),
+                None => return self.strip(packet, upper.next_header, upper.offset),
+                Some(fragment) => fragment,
+            };
+
+            let reassembly = match &mut self.inner.reassembly {
+                Some(reassembly) => reassembly,
+                None => return Err(self.malformed(crate::wire::Error::Unsupported)),
+            };
+
+            let data = &packet.payload_slice()[upper.offset..];
+            let idx = match reassembly.add(&packet.repr(), &fragment, data, now) {
+                Ok(Some(idx)) => idx,
+                Ok(None) => return Err(Verdict::Consumed),
+                Err(err) => {
+                    let failure = Failure::new(err, Origin::Ip, Operation::Receive, Detail::None);
+                    self.inner.last_failure = Some(failure);
+                    return Err(Verdict::Dropped(failure));
+                },
+            };
+
+            // Continue with the headers following the fragment header.
+            packet = self.reassembled(packet, idx)?;
+        }
+    }
+
+    /// Move the upper layer data of a packet directly behind its fixed header.
+    fn strip<'p, P: PayloadMut>(
+        &mut self,
+        packet: V6Packet<'p, P>,
+        next_header: IpProtocol,
+        offset: usize,
+    ) -> core::result::Result<V6Packet<'p, P>, Verdict> {
+        let mut frame = packet.into_inner();
+        let ip = ipv6_packet::new_unchecked_mut(frame.payload_mut().as_mut_slice());
+        let payload_len = ip.payload_slice().len() - offset;
+        ip.payload_mut_slice().copy_within(offset.., 0);
+        ip.set_next_header(next_header);
+        ip.set_payload_len(payload_len as u16);
+        self.reparse(frame)
+    }
+
+    /// Replace the payload of the last fragment of a packet with the reassembled data.
+    fn reassembled<'p, P: PayloadMut>(&mut self, packet: V6Packet<'p, P>, idx: usize)
+        -> core::result::Result<V6Packet<'p, P>, Verdict>
+    {
+        let reassembly = self.inner.reassembly.as_mut().expect("Fragment was reassembled");
+        let (next_header, data) = reassembly.assembled(idx);
+        let header_len = packet.header_len();
+        let mut frame = packet.into_inner();
+
+        let fits = data.len() <= usize::from(u16::MAX)
+            && frame.resize(header_len + data.len()).is_ok();
+        if fits {
+            let ip = ipv6_packet::new_unchecked_mut(frame.payload_mut().as_mut_slice());
+            ip.set_next_header(next_header);
+            ip.set_payload_len(data.len() as u16);
+            ip.payload_mut_slice().copy_from_slice(data);
+        }
+        reassembly.release(idx);
+
+        if !fits {
+            let detail = Detail::None;
+            let failure = Failure::new(Error::BadSize, Origin::Ip, Operation::Receive, detail);
+            self.inner.last_failure = Some(failure);
+            return Err(Verdict::Dropped(failure));
+        }
+
+        self.reparse(frame)
+    }
+
+    fn reparse<'p, P: Payload>(&mut self, frame: EthernetFrame<&'p mut P>)
+        -> core::result::Result<V6Packet<'p, P>, Verdict>
+    {
+        Ipv6Packet::new_checked(frame).map_err(|err| self.malformed(err))
+    }
+
+    /// Check the source of a received packet against the validation of the endpoint.
+    ///
+    /// Counts, records and returns the failure of a rejected packet.
+    fn validate_source(
+        &mut self,
+        src_addr: IpAddress,
+        dst_addr: IpAddress,
+        src_mac: EthernetAddress,
+        time: Instant,
+    ) -> Option<Failure> {
+        let validation = self.inner.validation;
+        if validation == SourceValidation::Off {
+            return None;
+        }
+
+        let routing = &self.inner.routing;
+        if routing.is_martian(src_addr, dst_addr) {
+            net_debug!("ip: dropped packet from martian source {}", src_addr);
+            self.inner.statistics.martian += 1;
+            let detail = Detail::Martian(src_addr);
+            let failure = Failure::new(Error::Illegal, Origin::Ip, Operation::Receive, detail);
+            self.inner.last_failure = Some(failure);
+            return Some(failure);
+        }
+
+        // Hosts without an address yet have no path back to them.
+        if validation == SourceValidation::Martian || src_addr.is_unspecified() {
+            return None;
+        }
+
+        let valid = match routing.route(src_addr, time) {
+            None => false,
+            Some(_) if validation == SourceValidation::Loose => true,
+            Some(route) => self.inner.arp.neighbors()
+                .lookup_pure(route.next_hop, time)
+                .is_none_or(|next_mac| next_mac == src_mac),
+        };
+
+        if valid {
+            return None;
+        }
+
+        net_debug!("ip: dropped packet from {} failing the reverse path check", src_addr);
+        self.inner.statistics.reverse_path += 1;
+        let detail = Detail::ReversePath(src_addr);
+        let failure = Failure::new(Error::Unreachable, Origin::Ip, Operation::Receive, detail);
+        self.inner.last_failure = Some(failure);
+        Some(failure)
+    }
+
+    /// Record a received packet that was dropped as it could not be parsed or was refused.
+    fn malformed(&mut self, err: crate::wire::Error) -> Verdict {
+        net_debug!("ip: dropped malformed packet: {:?}", err);
+        let detail = Detail::Wire(err);
+        let failure = Failure::new(Error::Illegal, Origin::Ip, Operation::Receive, detail);
+        self.inner.last_failure = Some(failure);
+        Verdict::Dropped(failure)
+    }
+
+    /// Add the summary of a received packet to the trace, if enabled.
+    #[cfg(feature = "trace")]
+    fn trace(&mut self, record: Record) {
+        if let Some(trace) = &mut self.inner.trace {
+            trace.record(record);
+        }
+    }
+
+    #[cfg(not(feature = "trace"))]
+    fn trace(&mut self, _: Record) {}
+
     fn into_arp_receiver(&mut self) -> arp::Receiver<'_, 'data> {
-        let Endpoint { routing, arp } = self.inner;
+        let Endpoint { routing, arp, .. } = self.inner;
         arp.answer_for(routing)
     }
 
     fn into_arp_sender(&mut self) -> arp::Sender<'_, 'data> {
-        let Endpoint { routing, arp } = self.inner;
+        let Endpoint { routing, arp, .. } = self.inner;
         arp.query_for(routing)
     }
 }
 
 impl packet::Endpoint for IpEndpoint<'_, '_> {
-    fn local_ip(&self, subnet: IpSubnet) -> Option<IpAddress> {
-        self.inner.routing.addr
-            .iter()
-            .cloned()
-            .map(|cidr| cidr.address())
-            .filter(|&addr| subnet.contains(addr))
-            .nth(0)
+    fn local_ip(&self, subnet: IpSubnet, time: Instant) -> Option<IpAddress> {
+        self.inner.routing.local_ip(subnet, time)
     }
 
     fn route(&self, dst_addr: IpAddress, time: Instant) -> Option<Route> {
         self.inner.routing.route(dst_addr, time)
     }
 
+    fn is_assigned(&self, addr: IpAddress) -> bool {
+        self.inner.routing.assignment(addr).is_some()
+    }
+
+    fn is_tentative(&self, addr: IpAddress) -> bool {
+        self.inner.routing.is_tentative(addr)
+    }
+
+    fn is_broadcast(&self, addr: IpAddress) -> bool {
+        self.inner.routing.is_broadcast(addr)
+    }
+
     fn resolve(&mut self, addr: IpAddress, time: Instant, look: bool) -> Result<EthernetAddress> {
-        match self.neighbors().lookup_pure(addr, time) {
-            Some(addr) => return Ok(addr),
-            None if !look => return Err(Error::Unreachable),
-            None => (),
+        if let IpAddress::Ipv6(addr) = addr {
+            if addr.is_multicast() {
+                return Ok(EthernetAddress::from_ipv6_multicast(addr));
+            }
+        }
+
+        if self.inner.routing.is_broadcast(addr) {
+            return Ok(EthernetAddress::BROADCAST);
+        }
+
+        if let Some(hw_addr) = self.neighbors().lookup_pure(addr, time) {
+            self.neighbors_mut().record(arp::NeighborEvent::Hit(addr));
+            return Ok(hw_addr);
+        }
+
+        self.neighbors_mut().record(arp::NeighborEvent::Miss(addr));
+        let unresolved = Detail::Unresolved(addr);
+        if !look {
+            return Err(self.fail(Error::Unreachable, Operation::Resolve, unresolved));
         }
 
         match self.neighbors_mut().fill_looking(addr, Some(time)) {
-            Ok(()) => Err(Error::Unreachable),
-            Err(_) => Err(Error::Exhausted),
+            Ok(()) => Err(self.fail(Error::Unreachable, Operation::Resolve, unresolved)),
+            Err(_) => {
+                self.neighbors_mut().record(arp::NeighborEvent::Unresolved(addr));
+                let detail = Detail::NeighborCacheFull(addr);
+                Err(self.fail(Error::Exhausted, Operation::Resolve, detail))
+            },
         }
     }
+
+    fn hop_limit(&self) -> u8 {
+        self.inner.hop_limit
+    }
+
+    fn fail(&mut self, error: Error, operation: Operation, detail: Detail) -> Error {
+        self.inner.last_failure = Some(Failure::new(error, Origin::Ip, operation, detail));
+        error
+    }
+
+    fn icmp_error(&mut self, error: IcmpError, time: Instant) -> bool {
+        self.inner.icmp.permits(error, time)
+    }
+
+    fn assign(&mut self, assignment: Assignment) -> Result<()> {
+        self.inner.add_address(assignment)
+    }
+
+    fn unassign(&mut self, addr: IpAddress) -> Option<Assignment> {
+        self.inner.remove_address(addr)
+    }
 }
 
 impl<P, T> eth::Recv<P> for Receiver<'_, '_, T>
@@ -245,35 +1177,128 @@ where
     P: PayloadMut,
     T: Recv<P>,
 {
-    fn receive(&mut self, eth::InPacket { mut handle, frame }: eth::InPacket<P>) {
+    fn receive(&mut self, packet: eth::InPacket<P>) {
+        let timestamp = packet.handle.info().timestamp();
+        let len = packet.frame.payload().len();
+        let mut header = None;
+        if let Some(verdict) = self.dispatch(packet, &mut header) {
+            self.endpoint.trace(Record { timestamp, len, header, verdict });
+        }
+    }
+}
+
+impl<T> Receiver<'_, '_, T> {
+    /// Process a received frame, returning the verdict for packets of an ip protocol.
+    ///
+    /// The header of the packet is filled in once it was parsed.
+    fn dispatch<P>(
+        &mut self,
+        eth::InPacket { mut handle, frame }: eth::InPacket<P>,
+        header: &mut Option<Header>,
+    ) -> Option<Verdict>
+    where
+        P: PayloadMut,
+        T: Recv<P>,
+    {
         let capabilities = handle.info().capabilities();
+        let src_mac = frame.repr().src_addr;
         let packet = match frame.repr().ethertype {
             EthernetProtocol::Ipv4 => {
-                match Ipv4Packet::new_checked(frame, capabilities.ipv4().rx_checksum()) {
-                    Ok(packet) => IpPacket::V4(packet),
-                    Err(_) => return,
+                let checksum = capabilities.ipv4().rx_checksum();
+                let packet = match Ipv4Packet::new_checked(frame, checksum) {
+                    Ok(packet) => packet,
+                    Err(err) => return Some(self.endpoint.malformed(err)),
+                };
+                if let Err(err) = self.endpoint.inner.options.check(&packet) {
+                    return Some(self.endpoint.malformed(err));
                 }
+                IpPacket::V4(packet)
             },
             EthernetProtocol::Ipv6 => {
-                match Ipv6Packet::new_checked(frame) {
+                let packet = match Ipv6Packet::new_checked(frame) {
+                    Ok(packet) => packet,
+                    Err(err) => return Some(self.endpoint.malformed(err)),
+                };
+                match self.endpoint.extensions(packet, handle.info().timestamp()) {
                     Ok(packet) => IpPacket::V6(packet),
-                    Err(_) => return,
+                    Err(verdict) => return Some(verdict),
                 }
             },
             EthernetProtocol::Arp => {
-                return self.endpoint.into_arp_receiver().receive(
-                    eth::InPacket { handle, frame, });
+                let mut arp = self.endpoint.into_arp_receiver();
+                eth::Recv::receive(&mut arp, eth::InPacket { handle, frame, });
+                return None;
             }
-            _ => return,
+            _ => return None,
         };
 
-        if !self.endpoint.inner.accepts(packet.repr().dst_addr()) {
-            return
+        let (src_addr, dst_addr) = (packet.repr().src_addr(), packet.repr().dst_addr());
+        let protocol = packet.repr().protocol();
+        *header = Some(Header { src_addr, dst_addr, protocol });
+
+        let time = handle.info().timestamp();
+        if let Some(failure) = self.endpoint.validate_source(src_addr, dst_addr, src_mac, time) {
+            return Some(Verdict::Dropped(failure));
+        }
+
+        if let IpPacket::V6(ref packet) = packet {
+            if self.endpoint.duplicate_address(packet, &mut handle) {
+                return Some(Verdict::Consumed);
+            }
+
+            if self.endpoint.router_advert(packet, &mut handle) {
+                return Some(Verdict::Consumed);
+            }
+        }
+
+        if !self.endpoint.inner.accepts(dst_addr) && !self.handler.accepts_foreign(dst_addr) {
+            if !self.endpoint.inner.forwarding {
+                let failure = Failure::new(
+                    Error::Unreachable, Origin::Ip, Operation::Receive, Detail::None);
+                return Some(Verdict::Dropped(failure));
+            }
+
+            let exhausted = packet.repr().hop_limit() <= 1;
+            let failure = Failure::new(
+                Error::Unreachable, Origin::Ip, Operation::Forward, Detail::HopLimit);
+            if exhausted {
+                self.endpoint.inner.last_failure = Some(failure);
+            }
+
+            let handle = Handle::new(handle.borrow_mut(), &mut self.endpoint);
+            let packet = packet::In { handle, packet };
+            // Answer if the policy permits, otherwise the packet is silently dropped.
+            let forwarded = if exhausted {
+                packet.time_exceeded(Icmpv4TimeExceeded::TtlExpired)
+            } else {
+                packet.forward()
+            }.and_then(packet::Out::send);
+
+            return Some(match forwarded {
+                _ if exhausted => Verdict::Dropped(failure),
+                Ok(()) => Verdict::Forwarded,
+                Err(err) => {
+                    let failure = Failure::new(err, Origin::Ip, Operation::Forward, Detail::None);
+                    Verdict::Dropped(self.endpoint.inner.last_failure.unwrap_or(failure))
+                },
+            });
         }
 
         let handle = Handle::new(handle.borrow_mut(), &mut self.endpoint);
         let packet = packet::In { handle, packet };
-        self.handler.receive(packet)
+
+        if !self.handler.accepts_protocol(protocol) {
+            // Answer if the policy permits, otherwise the packet is silently dropped.
+            let _ = packet
+                .unreachable(Icmpv4DstUnreachable::ProtoUnreachable)
+                .and_then(packet::Out::send);
+            let failure = Failure::new(
+                Error::Illegal, Origin::Ip, Operation::Receive, Detail::None);
+            return Some(Verdict::Dropped(failure));
+        }
+
+        self.handler.receive(packet);
+        Some(Verdict::Delivered)
     }
 }
 
@@ -283,6 +1308,15 @@ where
     T: Send<P>,
 {
     fn send(&mut self, packet: eth::RawPacket<P>) {
+        // Probes for tentative addresses come first, they are rate limited by their timers.
+        let now = packet.handle.info().timestamp();
+        let probe = self.endpoint.inner.routing.probes(now).next();
+        match probe {
+            Some(IpAddress::Ipv4(_)) => return self.endpoint.into_arp_sender().send(packet),
+            Some(IpAddress::Ipv6(target)) => return self.endpoint.solicit(packet, target),
+            _ => (),
+        }
+
         // FIXME: will *always* intercept, even if we can't actually send any arp.
         if self.endpoint.neighbors().missing().count() > 0 {
             return self.endpoint.into_arp_sender().send(packet);
@@ -11,7 +11,8 @@
 //! addresses ([`IpAddress`]) and a unified [`Init`] structure. This generally enables the layer to
 //! transparently dispatch into the desired underlying layer.
 //!
-//! It does **not yet** provide (transparent) fragment reassembly.
+//! Fragmented IPv6 packets are reassembled transparently once the endpoint has been given
+//! [`Reassembly`] buffers. IPv4 fragments are not supported.
 //!
 //! ## Structure
 //!
@@ -19,24 +20,64 @@
 //! enables it to match received packet destinations against the configured addresses of the
 //! network device and to find next hops for transmitted packets.
 //!
+//! An endpoint can own several addresses, for example a link-local and a global IPv6 address.
+//! Each [`Assignment`] has a preferred and a valid lifetime. Addresses are removed once they are
+//! no longer valid, and deprecated ones are only used as a source when no preferred address fits.
+//! Among the remaining ones the source address is selected following the rules of RFC 6724.
+//!
+//! A new address can first be tentative while the endpoint checks that no other host on the link
+//! uses it, with ARP probes for IPv4 (RFC 5227) and neighbor solicitations for IPv6 (RFC 4862).
+//! Its [`AddressState`] then goes from tentative to preferred to deprecated, and a tentative
+//! address is neither accepted nor used as a source. A duplicate is removed again.
+//!
+//! IPv6 addresses and default routers can be configured automatically from router advertisements
+//! once a [`Slaac`] configuration has been set on the endpoint.
+//!
+//! Addresses and routes can also be changed at runtime between receive and send phases, for
+//! example by a DHCP client or an operator. Removing an address flushes the neighbors that are no
+//! longer on the link. Each [`Change`] is reported to a [`ChangeEvents`] observer on the endpoint.
+//! Connections of upper layers that used a removed address are not reset automatically, the
+//! application does so afterwards, e.g. with [`tcp::Endpoint::reset_address`].
+//!
 //! ## Receiving packets
 //!
 //! The IP endpoint acts as an ethernet receiver. Note that it not only processes IP packets but
-//! also ARP traffic and other relevant protocols for neighbor discovery. (In IPv6 this would also
-//! refer to some protocols wrapped into IPv6 but these have not yet been implemented).
+//! also ARP traffic and other relevant protocols for neighbor discovery. In IPv6 these are wrapped
+//! into IPv6 themselves, currently only router advertisements are handled and only while address
+//! autoconfiguration is enabled.
 //!
 //! For all other packets the destination addresses are checked against the configured addresses of
-//! the receiving endpoint. They are subsequently forwarded to the upper layer handler.
+//! the receiving endpoint. They are subsequently forwarded to the upper layer handler. The handler
+//! may additionally claim some non-local destinations for itself, see [`Recv::accepts_foreign`].
+//! All remaining packets are dropped, unless forwarding has been enabled on the endpoint. Then they
+//! are rewritten in-place towards the next hop and sent again, see [`InPacket::forward`].
+//!
+//! To serve several upper layers in the same receive pass, register their handlers by protocol in
+//! a [`DispatchTable`] and use it as the upper handler.
+//!
+//! The extension headers of IPv6 packets are processed according to the [`ExtensionPolicy`] of
+//! the endpoint and removed from the buffer before the packet is handed to the upper layer, which
+//! hence sees its own protocol directly behind the fixed header. Packets carrying IPv4 options are
+//! checked by the [`OptionPolicy`].
+//!
+//! Packets of a protocol not handled by the receiver, see [`Recv::accepts_protocol`], and udp
+//! datagrams to closed ports can be answered with ICMP destination unreachable messages. This is
+//! configured with an [`IcmpPolicy`] on the endpoint. It is disabled by default and always subject
+//! to a rate limit, as these answers otherwise make the endpoint a reflection vector. The same
+//! limit applies to time exceeded messages, sent instead of forwarding packets whose hop limit is
+//! exhausted.
 //!
 //! ## Transmitting packets
 //!
 //! The basics of transmission work just like described in the general layer structure. A raw
 //! packet buffer is initialized with the help of the endpoint and an [`Init`] descriptor of both
 //! the header data and payload. The source address is selected automatically or provided by the
-//! user, in which case it is *not* checked against the configured addresses. The layer will
-//! translate the desired destination address to a corresponding next hop. Control over extension
-//! headers *is not* supported (but you could rewrite the packet buffer after initialization
-//! yourself).
+//! user, in which case it is *not* checked against the configured addresses, only tentative
+//! addresses are refused. The layer will translate the desired destination address to a
+//! corresponding next hop. The hop limit defaults to the one configured on the endpoint and can be
+//! overridden per packet. IPv4 header options can be added for diagnostic purposes. Control over
+//! IPv6 extension headers *is not* supported (but you could rewrite the packet buffer after
+//! initialization yourself).
 //!
 //! Note that the configured next hop might be missing a resolved link-layer address. In this case,
 //! the init call will return an error but the request for this resolution is stored in an internal
@@ -44,21 +85,66 @@
 //! buffer begin available and an internal rate limit. Only buffers that are not used for the
 //! purpose of neighbor discovery are available to the upper layers.
 //!
+//! The limited broadcast `255.255.255.255` and the broadcast address of the subnet of an own
+//! IPv4 address need no resolution, packets to them are framed with the ethernet broadcast
+//! address. Upper layers may guard against broadcasts, as udp does.
+//!
+//! An endpoint exposed to untrusted networks can drop received packets with spoofed sources, such
+//! as martian addresses or sources without a route back, see [`SourceValidation`].
+//!
+//! With the `trace` feature, an endpoint can keep the summaries and verdicts of the last received
+//! packets in a [`Trace`] ring for diagnosing dropped traffic.
+//!
+//! [`AddressState`]: enum.AddressState.html
+//! [`Assignment`]: struct.Assignment.html
+//! [`Change`]: enum.Change.html
+//! [`ChangeEvents`]: trait.ChangeEvents.html
+//! [`tcp::Endpoint::reset_address`]: ../tcp/struct.Endpoint.html#method.reset_address
+//! [`DispatchTable`]: struct.DispatchTable.html
 //! [`Init`]: struct.Init.html
+//! [`Slaac`]: struct.Slaac.html
+//! [`SourceValidation`]: enum.SourceValidation.html
+//! [`Trace`]: struct.Trace.html
+//! [`Recv::accepts_foreign`]: trait.Recv.html#method.accepts_foreign
+//! [`Recv::accepts_protocol`]: trait.Recv.html#method.accepts_protocol
+//! [`InPacket::forward`]: struct.InPacket.html#method.forward
+//! [`IcmpPolicy`]: struct.IcmpPolicy.html
+//! [`ExtensionPolicy`]: struct.ExtensionPolicy.html
+//! [`OptionPolicy`]: enum.OptionPolicy.html
+//! [`Reassembly`]: struct.Reassembly.html
 //! [`IpAddress`]: ../../wire/enum.IpAddress.html
 //! [`IpPacket`]: enum.IpPacket.html
-use crate::wire::Payload;
+use crate::wire::{IpAddress, IpProtocol, Payload};
+#[cfg(feature = "std")]
+use crate::wire::{pretty_print::Formatter, ipv4_packet, ipv6_packet};
 
+mod assignment;
+mod dispatch;
 mod endpoint;
 mod packet;
+mod policy;
+mod reassembly;
 mod route;
+mod slaac;
 #[cfg(test)]
 mod tests;
+mod trace;
+
+pub use assignment::{
+    AddressState,
+    Assignment,
+    Probing,
+};
+
+pub use dispatch::DispatchTable;
 
 pub use endpoint::{
+    Change,
+    ChangeEvents,
     Endpoint,
     Receiver,
     Sender,
+    Statistics,
 };
 
 pub use packet::{
@@ -73,11 +159,35 @@ pub use packet::{
     Source,
 };
 
+pub use policy::{
+    ExtensionAction,
+    ExtensionPolicy,
+    IcmpPolicy,
+    OptionPolicy,
+    SourceValidation,
+};
+
+pub use reassembly::{
+    Datagram,
+    Reassembly,
+};
+
 pub use route::{
     Route,
     Routes,
 };
 
+pub use slaac::Slaac;
+
+#[cfg(feature = "trace")]
+pub use trace::Trace;
+
+pub use trace::{
+    Header as TraceHeader,
+    Record as TraceRecord,
+    Verdict,
+};
+
 /// A IP receiver.
 ///
 /// Processes incoming TCP traffic and automatic answers and is encouraged to generate additional
@@ -87,6 +197,27 @@ pub trait Recv<P: Payload> {
     ///
     /// The packet might be IPv4 or IPv6 traffic.
     fn receive(&mut self, frame: InPacket<P>);
+
+    /// Check if packets to a non-local destination should be handed to this receiver.
+    ///
+    /// The endpoint drops all packets whose destination does not match one of its configured
+    /// addresses. A handler can claim additional destinations here without them becoming full
+    /// local addresses, i.e. they are never used for source selection or answered by arp. The
+    /// default implementation does not accept any.
+    fn accepts_foreign(&self, _dst_addr: IpAddress) -> bool {
+        false
+    }
+
+    /// Check if this receiver handles the protocol of a packet.
+    ///
+    /// Packets of other protocols are not handed to the receiver but may be answered with an icmp
+    /// protocol unreachable message, depending on the [`IcmpPolicy`] of the endpoint. The default
+    /// implementation accepts all protocols.
+    ///
+    /// [`IcmpPolicy`]: struct.IcmpPolicy.html
+    fn accepts_protocol(&self, _protocol: IpProtocol) -> bool {
+        true
+    }
 }
 
 
@@ -105,12 +236,46 @@ pub trait Send<P: Payload> {
 
 pub(crate) use endpoint::Routing;
 
+/// Available only on `std` because it prints to standard error, or to the logger with the `log`
+/// feature.
+///
+/// Prints IPv4 packets, including all nested layers, and ignores IPv6 traffic.
+#[cfg(feature = "std")]
+impl<P: Payload> Recv<P> for Formatter<ipv4_packet> {
+    fn receive(&mut self, frame: InPacket<P>) {
+        if let IpPacket::V4(_) = frame.packet {
+            net_print!("{}", frame.packet);
+        }
+    }
+}
+
+/// Available only on `std` because it prints to standard error, or to the logger with the `log`
+/// feature.
+///
+/// Prints IPv6 packets, including all nested layers, and ignores IPv4 traffic.
+#[cfg(feature = "std")]
+impl<P: Payload> Recv<P> for Formatter<ipv6_packet> {
+    fn receive(&mut self, frame: InPacket<P>) {
+        if let IpPacket::V6(_) = frame.packet {
+            net_print!("{}", frame.packet);
+        }
+    }
+}
+
 impl<P: Payload, E> Recv<P> for &'_ mut E
     where E: Recv<P>
 {
     fn receive(&mut self, frame: InPacket<P>) {
         (**self).receive(frame)
     }
+
+    fn accepts_foreign(&self, dst_addr: IpAddress) -> bool {
+        (**self).accepts_foreign(dst_addr)
+    }
+
+    fn accepts_protocol(&self, protocol: IpProtocol) -> bool {
+        (**self).accepts_protocol(protocol)
+    }
 }
 
 impl<P: Payload, E> Send<P> for &'_ mut E
@@ -1,9 +1,19 @@
-use crate::layer::{Error, Result, eth};
+use core::convert::TryFrom;
+use core::fmt;
+
+use crate::layer::{Detail, Error, Operation, Result, eth};
 use crate::nic::{self, Info};
 use crate::time::Instant;
-use crate::wire::{Checksum, EthernetAddress, EthernetFrame, EthernetProtocol};
-use crate::wire::{Reframe, Payload, PayloadMut, PayloadResult, payload};
+use crate::wire::{Checksum, EthernetAddress, EthernetFrame, EthernetProtocol, ethernet_frame};
+use crate::wire::{EthernetRepr, Reframe, Payload, PayloadMut, PayloadResult, payload};
+use crate::wire::PayloadVectored;
 use crate::wire::{IpAddress, IpSubnet, IpProtocol, IpRepr, Ipv4Packet, Ipv6Packet};
+use crate::wire::{Icmpv4DstUnreachable, Icmpv4Repr, icmpv4_packet, ipv4_packet, ipv6_packet};
+use crate::wire::{Icmpv4TimeExceeded, Ipv4Option, Ipv4Subnet};
+use crate::wire::pretty_print::{PrettyIndent, PrettyPrint};
+
+use super::Assignment;
+use super::policy::IcmpError;
 
 /// An incoming packet.
 ///
@@ -73,6 +83,14 @@ pub struct Init {
     pub protocol: IpProtocol,
     /// The length to reserved for the payload.
     pub payload: usize,
+    /// Override the hop limit of the endpoint for this packet.
+    ///
+    /// Use `None` for the default hop limit configured on the endpoint.
+    pub hop_limit: Option<u8>,
+    /// The differentiated services code point of the packet.
+    ///
+    /// Only the lower six bits are used. The default class of best-effort traffic is `0`.
+    pub dscp: u8,
 }
 
 /// A source selector specification.
@@ -87,7 +105,9 @@ pub enum Source {
     /// Some preselected address should be used.
     ///
     /// Required for established connections that are identified by an address tuple, such as in
-    /// the case of TCP and UDP.
+    /// the case of TCP and UDP. The address must be assigned to the endpoint, otherwise sending
+    /// fails with `Error::Illegal`. Only an answer to a received packet may also use the
+    /// destination of that packet, which an upper layer such as icmp accepted for itself.
     Exact(IpAddress),
 }
 
@@ -107,11 +127,27 @@ struct EthRoute {
 /// The interface to the endpoint.
 pub(crate) trait Endpoint{
     /// Get the ip to use on a link by providing the subnet in which it should be routed.
-    fn local_ip(&self, subnet: IpSubnet) -> Option<IpAddress>;
+    fn local_ip(&self, subnet: IpSubnet, time: Instant) -> Option<IpAddress>;
     /// Find a Route a destination at the current time.
     fn route(&self, dst_addr: IpAddress, time: Instant) -> Option<Route>;
+    /// Check if an address is assigned to the endpoint, possibly still tentative.
+    fn is_assigned(&self, addr: IpAddress) -> bool;
+    /// Check if an own address is still probed for duplicates.
+    fn is_tentative(&self, addr: IpAddress) -> bool;
+    /// Check if an address is the limited broadcast or the broadcast of an own subnet.
+    fn is_broadcast(&self, addr: IpAddress) -> bool;
     /// Resolve an address. If `look` is true, try to actively lookup it up later.
     fn resolve(&mut self, _: IpAddress, _: Instant, look: bool) -> Result<EthernetAddress>;
+    /// The default hop limit of outgoing packets.
+    fn hop_limit(&self) -> u8;
+    /// Record the context of a failure and return its error.
+    fn fail(&mut self, error: Error, operation: Operation, detail: Detail) -> Error;
+    /// Check the icmp policy for an error, consuming one token of the rate limit if permitted.
+    fn icmp_error(&mut self, error: IcmpError, time: Instant) -> bool;
+    /// Assign an address or update its lifetimes, for address configuration protocols.
+    fn assign(&mut self, assignment: Assignment) -> Result<()>;
+    /// Remove an assigned address.
+    fn unassign(&mut self, addr: IpAddress) -> Option<Assignment>;
 }
 
 impl<'a> Handle<'a> {
@@ -137,6 +173,11 @@ impl<'a> Handle<'a> {
         self.eth.info()
     }
 
+    /// Request the hardware timestamp of the packet when it is sent.
+    pub fn request_tx_timestamp(&mut self, id: u32) -> Result<()> {
+        self.eth.request_tx_timestamp(id)
+    }
+
     /// Proof to the compiler that we can shorten the lifetime arbitrarily.
     pub fn borrow_mut(&mut self) -> Handle {
         Handle {
@@ -147,7 +188,29 @@ impl<'a> Handle<'a> {
 
     /// Get the local endpoint IP to use as source on some subnet.
     pub fn local_ip(&self, subnet: IpSubnet) -> Option<IpAddress> {
-        self.endpoint.local_ip(subnet)
+        let time = self.info().timestamp();
+        self.endpoint.local_ip(subnet, time)
+    }
+
+    /// Check if packets to an address are broadcast on the link.
+    ///
+    /// This is the case for the limited broadcast `255.255.255.255` and the broadcast address of
+    /// the subnet of an own IPv4 address. Such packets are sent to the ethernet broadcast address.
+    pub fn is_broadcast(&self, dst_addr: IpAddress) -> bool {
+        self.endpoint.is_broadcast(dst_addr)
+    }
+
+    /// The largest payload of a packet towards a destination.
+    ///
+    /// This is the maximum transmission unit of the device less the ip header. Larger packets are
+    /// refused when preparing them. `None` if the device does not limit the packet size.
+    pub fn max_payload(&self, dst_addr: IpAddress) -> Option<usize> {
+        let mtu = self.info().capabilities().mtu()?;
+        let header = match dst_addr {
+            IpAddress::Ipv6(_) => 40,
+            _ => 20,
+        };
+        Some(mtu.saturating_sub(header))
     }
 
     /// Try to initialize the destination from an upper layer protocol address.
@@ -161,11 +224,51 @@ impl<'a> Handle<'a> {
         self.endpoint.resolve(dst_addr, time, true)
     }
 
-    fn route_to(&mut self, dst_addr: IpAddress) -> Result<EthRoute> {
+    /// Assign an address to the endpoint, see [`Endpoint::add_address`].
+    ///
+    /// [`Endpoint::add_address`]: struct.Endpoint.html#method.add_address
+    pub(crate) fn assign(&mut self, assignment: Assignment) -> Result<()> {
+        self.endpoint.assign(assignment)
+    }
+
+    /// Remove an address from the endpoint, see [`Endpoint::remove_address`].
+    ///
+    /// [`Endpoint::remove_address`]: struct.Endpoint.html#method.remove_address
+    pub(crate) fn unassign(&mut self, addr: IpAddress) -> Option<Assignment> {
+        self.endpoint.unassign(addr)
+    }
+
+    /// Find the route and source address of an outgoing packet.
+    ///
+    /// An exact source must be an own address, or the destination of the packet being answered
+    /// which an upper layer accepted for itself.
+    fn route_to(&mut self, dst_addr: IpAddress, source: Source, answered: Option<IpAddress>)
+        -> Result<EthRoute>
+    {
+        if let Source::Exact(addr) = source {
+            if !self.endpoint.is_assigned(addr) && answered != Some(addr) {
+                return Err(self.endpoint
+                    .fail(Error::Illegal, Operation::Route, Detail::ForeignSource(addr)));
+            }
+        }
+
         let now = self.eth.info().timestamp();
-        let Route { next_hop, src_addr } = self.endpoint
-            .route(dst_addr, now)
-            .ok_or(Error::Unreachable)?;
+        let Route { next_hop, src_addr } = match self.endpoint.route(dst_addr, now) {
+            Some(route) => route,
+            None => return Err(self.endpoint
+                .fail(Error::Unreachable, Operation::Route, Detail::NoRoute(dst_addr))),
+        };
+        let src_addr = match source {
+            Source::Exact(addr) if !self.endpoint.is_tentative(addr) => addr,
+            Source::Exact(_) => return Err(self.endpoint
+                .fail(Error::Unreachable, Operation::Route, Detail::NoSource(dst_addr))),
+            Source::Mask { subnet } if subnet.contains(src_addr) => src_addr,
+            Source::Mask { subnet } => match self.endpoint.local_ip(subnet, now) {
+                Some(addr) => addr,
+                None => return Err(self.endpoint
+                    .fail(Error::Unreachable, Operation::Route, Detail::NoSource(dst_addr))),
+            },
+        };
         let next_mac = self.resolve(next_hop)?;
         let src_mac = self.eth.src_addr();
 
@@ -175,6 +278,24 @@ impl<'a> Handle<'a> {
             next_mac,
         })
     }
+
+    /// Check that a packet fits the maximum transmission unit of the device.
+    ///
+    /// The payload is the part of the ip packet following the header and its options.
+    fn fit(&mut self, init: &eth::Init, payload: usize) -> Result<()> {
+        let mtu = match self.info().capabilities().mtu() {
+            Some(mtu) if init.payload > mtu => mtu,
+            _ => return Ok(()),
+        };
+
+        let max = mtu.saturating_sub(init.payload - payload);
+        Err(self.endpoint.fail(Error::BadSize, Operation::Send, Detail::Oversized { max }))
+    }
+
+    /// Exchange a packet buffer for a spare, if the device permits it.
+    pub(crate) fn retain<P>(&mut self, payload: &mut P, spare: &mut P) -> Result<()> {
+        self.eth.retain(payload, spare)
+    }
 }
 
 impl<'a, P: Payload> In<'a, P> {
@@ -184,14 +305,28 @@ impl<'a, P: Payload> In<'a, P> {
     {
         Raw::new(self.handle, self.packet.into_raw())
     }
+
+    /// Exchange the buffer of the packet for a spare, see [`eth::InPacket::retain`].
+    ///
+    /// [`eth::InPacket::retain`]: ../eth/struct.InPacket.html#method.retain
+    pub fn retain(self, spare: &mut P) -> Result<()>
+        where P: PayloadMut,
+    {
+        let Raw { mut handle, payload } = self.deinit();
+        handle.retain(payload, spare)
+    }
 }
 
 impl<'a, P: PayloadMut> In<'a, P> {
     /// Reinitialize the buffer with a packet generated by the library.
     // TODO: guarantee payload preserved?
     pub fn reinit(mut self, init: Init) -> Result<Out<'a, P>> {
-        let route = self.handle.route_to(init.dst_addr)?;
+        let hop_limit = init.hop_limit.unwrap_or_else(|| self.handle.endpoint.hop_limit());
+        let answered = Some(self.packet.repr().dst_addr())
+            .filter(|&addr| addr.is_unicast() && !self.handle.endpoint.is_broadcast(addr));
+        let route = self.handle.route_to(init.dst_addr, init.source, answered)?;
         let lower_init = init.init_eth(route, init.payload)?;
+        self.handle.fit(&lower_init, init.payload)?;
 
         let eth_packet = eth::InPacket {
             handle: self.handle.eth,
@@ -201,7 +336,7 @@ impl<'a, P: PayloadMut> In<'a, P> {
         // TODO: optimize in case frame already contains the right IP packet.
         let packet = eth_packet.reinit(lower_init)?;
         let eth::InPacket { handle, mut frame } = packet.into_incoming();
-        let repr = init.initialize(route.src_addr, &mut frame)?;
+        let repr = init.initialize(route.src_addr, hop_limit, &[], &mut frame)?;
 
         // Reconstruct the handle.
         let handle = Handle::new(handle, self.handle.endpoint);
@@ -211,6 +346,145 @@ impl<'a, P: PayloadMut> In<'a, P> {
             packet: IpPacket::new_unchecked(frame, repr),
         })
     }
+
+    /// Answer with an ICMP destination unreachable message in-place.
+    ///
+    /// The message quotes the ip header and the first eight bytes of the original payload, as
+    /// required by RFC 792. It is only generated if the [`IcmpPolicy`] of the endpoint permits the
+    /// reason and the rate limit has not been exhausted, returning `Error::Exhausted` otherwise.
+    /// Following RFC 1122 no error is generated for other icmp messages, non-initial fragments, or
+    /// packets that were not sent between unicast addresses, which fails with `Error::Illegal`.
+    /// The same error is returned for IPv6 packets for which no ICMPv6 is available (yet).
+    ///
+    /// [`IcmpPolicy`]: struct.IcmpPolicy.html
+    pub fn unreachable(self, reason: Icmpv4DstUnreachable) -> Result<Out<'a, P>> {
+        self.icmp_error(IcmpError::Unreachable(reason))
+    }
+
+    /// Answer with an ICMP time exceeded message in-place.
+    ///
+    /// Send this instead of forwarding a packet whose hop limit would be exhausted, or when the
+    /// reassembly of a packet timed out. Just like [`unreachable`], with the same restrictions,
+    /// the message is only generated if the [`IcmpPolicy`] permits it and shares the rate limit
+    /// with all other errors. The endpoint answers automatically when forwarding is enabled.
+    ///
+    /// [`IcmpPolicy`]: struct.IcmpPolicy.html
+    /// [`unreachable`]: #method.unreachable
+    pub fn time_exceeded(self, reason: Icmpv4TimeExceeded) -> Result<Out<'a, P>> {
+        self.icmp_error(IcmpError::TimeExceeded(reason))
+    }
+
+    fn icmp_error(self, error: IcmpError) -> Result<Out<'a, P>> {
+        const QUOTED: usize = 8;
+
+        let (header, quoted) = match &self.packet {
+            IpPacket::V4(packet) => {
+                let header = packet.repr();
+                let link_dst = packet.get_ref().repr().dst_addr;
+                if header.protocol == IpProtocol::Icmp
+                    || packet.frag_offset() != 0
+                    || !link_dst.is_unicast()
+                    || !header.src_addr.is_unicast()
+                    || !header.dst_addr.is_unicast()
+                {
+                    return Err(Error::Illegal);
+                }
+
+                let mut quoted = [0; QUOTED];
+                let payload = packet.payload_slice();
+                let len = payload.len().min(QUOTED);
+                quoted[..len].copy_from_slice(&payload[..len]);
+                (header, quoted)
+            },
+            IpPacket::V6(_) => return Err(Error::Illegal),
+        };
+
+        let time = self.handle.info().timestamp();
+        if !self.handle.endpoint.icmp_error(error, time) {
+            return Err(Error::Exhausted);
+        }
+
+        let (answer, source) = match error {
+            // Be sure to answer from the address the packet was sent to.
+            IcmpError::Unreachable(reason) => (
+                Icmpv4Repr::DstUnreachable { reason, header },
+                IpAddress::from(header.dst_addr).into(),
+            ),
+            // The destination is not local, answer from an own address instead.
+            IcmpError::TimeExceeded(reason) => (
+                Icmpv4Repr::TimeExceeded { reason, header },
+                IpSubnet::from(Ipv4Subnet::ANY).into(),
+            ),
+        };
+        let mut out = self.reinit(Init {
+            source,
+            dst_addr: header.src_addr.into(),
+            protocol: IpProtocol::Icmp,
+            payload: answer.buffer_len(),
+            hop_limit: None,
+            dscp: 0,
+        })?;
+
+        let icmp = icmpv4_packet::new_unchecked_mut(out.payload_mut_slice());
+        icmp.payload_mut_slice()[header.buffer_len()..][..QUOTED].copy_from_slice(&quoted);
+        answer.emit(icmp, Checksum::Manual);
+        Ok(out)
+    }
+}
+
+impl<'a, P: PayloadMut> In<'a, P> {
+    /// Forward the packet in-place towards its destination.
+    ///
+    /// The next hop is looked up in the routes and the neighbor cache of the endpoint, the
+    /// ethernet header is rewritten towards it, and the hop limit is decremented. The checksum is
+    /// fixed when the returned packet is sent. Fails with `Error::Unreachable` if there is no
+    /// route, the next hop has not yet been resolved, or the hop limit is exhausted. Packets that
+    /// were not addressed to a unicast destination are not forwarded, this fails with
+    /// `Error::Illegal`. Check the hop limit beforehand to answer with [`time_exceeded`] instead.
+    ///
+    /// The buffer is sent on the same device it was received on. To forward between interfaces,
+    /// copy the packet into a buffer of the other device instead.
+    ///
+    /// [`time_exceeded`]: #method.time_exceeded
+    pub fn forward(mut self) -> Result<Out<'a, P>> {
+        let ip_repr = self.packet.repr();
+        let link_dst = self.packet.ethernet_repr().dst_addr;
+        if !link_dst.is_unicast() || !ip_repr.dst_addr().is_unicast() {
+            return Err(Error::Illegal);
+        }
+
+        let now = self.handle.info().timestamp();
+        let dst_addr = ip_repr.dst_addr();
+        let Route { next_hop, .. } = match self.handle.endpoint.route(dst_addr, now) {
+            Some(route) => route,
+            None => return Err(self.handle.endpoint
+                .fail(Error::Unreachable, Operation::Forward, Detail::NoRoute(dst_addr))),
+        };
+        let next_mac = self.handle.resolve(next_hop)?;
+        let src_mac = self.handle.eth.src_addr();
+
+        if let Err(err) = self.packet.decrement_hop_limit() {
+            return Err(self.handle.endpoint.fail(err, Operation::Forward, Detail::HopLimit));
+        }
+        let ip_repr = self.packet.repr();
+        let eth_repr = self.packet.ethernet_repr();
+
+        let payload = self.packet.into_raw();
+        let header = ethernet_frame::new_unchecked_mut(payload.payload_mut().as_mut_slice());
+        header.set_src_addr(src_mac);
+        header.set_dst_addr(next_mac);
+
+        let frame = EthernetFrame::new_unchecked(payload, EthernetRepr {
+            src_addr: src_mac,
+            dst_addr: next_mac,
+            ..eth_repr
+        });
+
+        Ok(Out {
+            handle: self.handle,
+            packet: IpPacket::new_unchecked(frame, ip_repr),
+        })
+    }
 }
 
 impl<'a, P: Payload> Out<'a, P> {
@@ -271,6 +545,43 @@ impl<'a, P: PayloadMut> Out<'a, P> {
     }
 }
 
+impl<'a, P: PayloadVectored> Out<'a, P> {
+    /// Send the packet together with the segments of its buffer.
+    ///
+    /// The packet was prepared for the contiguous part of the buffer only, with the payload of the
+    /// upper layer placed in segments. Their length is added to the payload length in the header
+    /// before the packet is sent just like with `send`.
+    pub fn send_vectored(self) -> Result<()> {
+        let Out { handle, packet } = self;
+        let mut repr = packet.repr();
+        let frame = packet.into_inner();
+        let eth_repr = frame.repr();
+        let raw = frame.into_inner();
+
+        let payload_len = repr.payload_len() + raw.segments_len();
+        let header = ethernet_frame::new_unchecked_mut(raw.payload_mut().as_mut_slice())
+            .payload_mut_slice();
+        match repr {
+            IpRepr::Ipv4(_) => {
+                let total_len = u16::try_from(repr.buffer_len() + payload_len)
+                    .map_err(|_| Error::BadSize)?;
+                ipv4_packet::new_unchecked_mut(header).set_total_len(total_len);
+            },
+            IpRepr::Ipv6(_) => {
+                let payload_len = u16::try_from(payload_len)
+                    .map_err(|_| Error::BadSize)?;
+                ipv6_packet::new_unchecked_mut(header).set_payload_len(payload_len);
+            },
+            _ => return Err(Error::Illegal),
+        }
+        repr.set_payload_len(payload_len);
+
+        let frame = EthernetFrame::new_unchecked(raw, eth_repr);
+        Out::new_unchecked(handle, IpPacket::new_unchecked(frame, repr))
+            .send()
+    }
+}
+
 impl<'a, P: Payload + PayloadMut> Raw<'a, P> {
     pub(crate) fn new(
         handle: Handle<'a>,
@@ -282,10 +593,35 @@ impl<'a, P: Payload + PayloadMut> Raw<'a, P> {
         }
     }
 
+    /// Reserve headroom in front of the packet that is prepared next.
+    ///
+    /// See [`eth::RawPacket::reserve_headroom`] for details.
+    ///
+    /// [`eth::RawPacket::reserve_headroom`]: ../eth/struct.RawPacket.html#method.reserve_headroom
+    pub fn reserve_headroom(&mut self, headroom: usize) -> Result<()> {
+        self.payload.set_headroom(headroom)?;
+        Ok(())
+    }
+
     /// Initialize to a valid ip packet.
-    pub fn prepare(mut self, init: Init) -> Result<Out<'a, P>> {
-        let route = self.handle.route_to(init.dst_addr)?;
-        let lower_init = init.init_eth(route, init.payload)?;
+    pub fn prepare(self, init: Init) -> Result<Out<'a, P>> {
+        self.prepare_with_options(init, &[])
+    }
+
+    /// Initialize to a valid IPv4 packet carrying header options.
+    ///
+    /// Meant for diagnostic tooling, such as recording the route of a probe. The options are
+    /// padded to a multiple of four octets, returning `Error::BadSize` if they do not fit into the
+    /// header. Options are only available for IPv4 destinations, requesting some for an IPv6
+    /// destination fails with `Error::Illegal`.
+    pub fn prepare_with_options(mut self, init: Init, options: &[Ipv4Option])
+        -> Result<Out<'a, P>>
+    {
+        let options_len = init.options_len(options)?;
+        let hop_limit = init.hop_limit.unwrap_or_else(|| self.handle.endpoint.hop_limit());
+        let route = self.handle.route_to(init.dst_addr, init.source, None)?;
+        let lower_init = init.init_eth(route, init.payload + options_len)?;
+        self.handle.fit(&lower_init, init.payload)?;
 
         let lower = eth::RawPacket::new(
             self.handle.eth,
@@ -293,7 +629,7 @@ impl<'a, P: Payload + PayloadMut> Raw<'a, P> {
 
         let packet = lower.prepare(lower_init)?;
         let eth::InPacket { handle, mut frame } = packet.into_incoming();
-        let repr = init.initialize(route.src_addr, &mut frame)?;
+        let repr = init.initialize(route.src_addr, hop_limit, options, &mut frame)?;
 
         // Reconstruct the handle.
         let handle = Handle::new(handle, self.handle.endpoint);
@@ -306,26 +642,70 @@ impl<'a, P: Payload + PayloadMut> Raw<'a, P> {
 }
 
 impl Init {
-    fn initialize(&self, src_addr: IpAddress, payload: &mut impl PayloadMut) -> Result<IpRepr> {
-        let repr = self.ip_repr(src_addr)?;
+    fn initialize(
+        &self,
+        src_addr: IpAddress,
+        hop_limit: u8,
+        options: &[Ipv4Option],
+        payload: &mut impl PayloadMut,
+    ) -> Result<IpRepr> {
+        let repr = self.ip_repr(src_addr, hop_limit)?;
         // Emit the packet but ignore the checksum for now. it is filled in later when calling
         // `OutPacket::send`.
-        repr.emit(payload.payload_mut().as_mut_slice(), Checksum::Ignored);
+        let buffer = payload.payload_mut().as_mut_slice();
+        repr.emit(&mut *buffer, Checksum::Ignored);
+        let dscp = self.dscp & 0x3f;
+        match repr {
+            IpRepr::Ipv4(_) => {
+                let packet = ipv4_packet::new_unchecked_mut(buffer);
+                packet.set_dscp(dscp);
+                if !options.is_empty() {
+                    // Grow the header, the payload follows behind the options.
+                    let options_len = Ipv4Option::options_len(options);
+                    packet.set_header_len(packet.header_len() + options_len as u8);
+                    packet.set_total_len(packet.total_len() + options_len as u16);
+                    Ipv4Option::emit_all(options, packet.options_mut())
+                        .map_err(|_| Error::BadSize)?;
+                }
+            },
+            IpRepr::Ipv6(_) => {
+                let packet = ipv6_packet::new_unchecked_mut(buffer);
+                let ecn = packet.traffic_class() & 0x03;
+                packet.set_traffic_class(dscp << 2 | ecn);
+            },
+            _ => (),
+        }
         Ok(repr)
     }
 
     /// Resolve the ip representation without initializing the packet.
-    fn ip_repr(&self, src_addr: IpAddress) -> Result<IpRepr> {
+    fn ip_repr(&self, src_addr: IpAddress, hop_limit: u8) -> Result<IpRepr> {
         let repr = IpRepr::Unspecified {
             src_addr,
             dst_addr: self.dst_addr,
-            hop_limit: u8::max_value(),
+            hop_limit,
             protocol: self.protocol,
             payload_len: self.payload,
         };
         repr.lower(&[]).ok_or(Error::Illegal)
     }
 
+    /// The length of the IPv4 header options, if they are permitted for the destination.
+    fn options_len(&self, options: &[Ipv4Option]) -> Result<usize> {
+        if options.is_empty() {
+            return Ok(0);
+        }
+
+        if !matches!(self.dst_addr, IpAddress::Ipv4(_)) {
+            return Err(Error::Illegal);
+        }
+
+        match Ipv4Option::options_len(options) {
+            len if len <= Ipv4Option::MAX_LEN => Ok(len),
+            _ => Err(Error::BadSize),
+        }
+    }
+
     fn init_eth(&self, route: EthRoute, payload: usize) -> Result<eth::Init> {
         enum Protocol { Ipv4, Ipv6 }
 
@@ -374,6 +754,30 @@ impl<'a, P: Payload> IpPacket<'a, P> {
         }
     }
 
+    /// Get the differentiated services code point of the packet.
+    pub fn dscp(&self) -> u8 {
+        match self {
+            IpPacket::V4(packet) => packet.dscp(),
+            IpPacket::V6(packet) => packet.traffic_class() >> 2,
+        }
+    }
+
+    /// Get the explicit congestion notification bits of the packet.
+    pub fn ecn(&self) -> u8 {
+        match self {
+            IpPacket::V4(packet) => packet.ecn(),
+            IpPacket::V6(packet) => packet.traffic_class() & 0x03,
+        }
+    }
+
+    /// Retrieve the representation of the surrounding ethernet frame.
+    pub fn ethernet_repr(&self) -> EthernetRepr {
+        match self {
+            IpPacket::V4(packet) => packet.get_ref().repr(),
+            IpPacket::V6(packet) => packet.get_ref().repr(),
+        }
+    }
+
     /// Turn the packet into its ethernet layer respresentation.
     pub fn into_inner(self) -> EthernetFrame<&'a mut P> {
         match self {
@@ -390,6 +794,58 @@ impl<'a, P: Payload> IpPacket<'a, P> {
     }
 }
 
+impl<'a, P: PayloadMut> IpPacket<'a, P> {
+    /// Change the hop limit (or time to live) in the header.
+    ///
+    /// For IPv4 packets the checksum is only recalculated when the packet is sent.
+    pub fn set_hop_limit(&mut self, hop_limit: u8) {
+        match self {
+            IpPacket::V4(packet) => packet.set_hop_limit(hop_limit),
+            IpPacket::V6(packet) => packet.set_hop_limit(hop_limit),
+        }
+    }
+
+    /// Change the differentiated services code point in the header.
+    ///
+    /// For IPv4 packets the checksum is only recalculated when the packet is sent.
+    pub fn set_dscp(&mut self, dscp: u8) {
+        match self {
+            IpPacket::V4(packet) => packet.set_dscp(dscp & 0x3f),
+            IpPacket::V6(packet) => {
+                let ecn = packet.traffic_class() & 0x03;
+                packet.set_traffic_class((dscp & 0x3f) << 2 | ecn);
+            },
+        }
+    }
+
+    /// Change the explicit congestion notification bits in the header.
+    ///
+    /// For IPv4 packets the checksum is only recalculated when the packet is sent.
+    pub fn set_ecn(&mut self, ecn: u8) {
+        match self {
+            IpPacket::V4(packet) => packet.set_ecn(ecn),
+            IpPacket::V6(packet) => {
+                let dscp = packet.traffic_class() & !0x03;
+                packet.set_traffic_class(dscp | ecn & 0x03);
+            },
+        }
+    }
+
+    /// Decrement the hop limit of a packet that is to be forwarded.
+    ///
+    /// Returns the new hop limit. A packet whose hop limit would reach zero must not be forwarded,
+    /// this fails with `Error::Unreachable` and leaves the header unchanged.
+    pub fn decrement_hop_limit(&mut self) -> Result<u8> {
+        match self.repr().hop_limit() {
+            0 | 1 => Err(Error::Unreachable),
+            hop_limit => {
+                self.set_hop_limit(hop_limit - 1);
+                Ok(hop_limit - 1)
+            },
+        }
+    }
+}
+
 impl<'a, P: Payload> Payload for IpPacket<'a, P> {
     fn payload(&self) -> &payload {
         match self {
@@ -399,6 +855,25 @@ impl<'a, P: Payload> Payload for IpPacket<'a, P> {
     }
 } 
 
+/// Formats the packet and everything it encapsulates as a nested listing.
+///
+/// This is the same output as a `PrettyPrinter` of the matching ip version would produce.
+impl<'a, P: Payload> fmt::Display for IpPacket<'a, P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut indent = PrettyIndent::new("");
+        match self {
+            IpPacket::V4(packet) => {
+                let buffer = packet.get_ref().payload().as_slice();
+                ipv4_packet::pretty_print(buffer, f, &mut indent)
+            },
+            IpPacket::V6(packet) => {
+                let buffer = packet.get_ref().payload().as_slice();
+                ipv6_packet::pretty_print(buffer, f, &mut indent)
+            },
+        }
+    }
+}
+
 impl<'a, P: PayloadMut> PayloadMut for IpPacket<'a, P> {
     fn payload_mut(&mut self) -> &mut payload {
         match self {
@@ -0,0 +1,413 @@
+use crate::layer::icmp::{Limiter, RateLimit};
+use crate::time::Instant;
+use crate::wire::{self, Icmpv4DstUnreachable, Icmpv4TimeExceeded, Ipv4Option, ipv4_packet};
+use crate::wire::{Ipv6ExtHeader, Ipv6FragmentHeader, Ipv6HopByHopHeader, Ipv6HopByHopRepr};
+use crate::wire::{Ipv6FragmentRepr, Ipv6OptionFailureType, Ipv6OptionRepr, IpProtocol};
+use crate::wire::ipv6_packet;
+
+/// Configures the generation of ICMP errors on behalf of the upper layers.
+///
+/// Since errors are sent in reaction to unsolicited incoming traffic with a spoofable source
+/// address, an open generation would make the endpoint a convenient reflection vector. All
+/// generated errors, including those requested explicitly, are thus subject to a common token
+/// bucket rate limit and the automatic generation by the layers is disabled by default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IcmpPolicy {
+    /// Answer datagrams to closed udp ports with a port unreachable message.
+    pub port_unreachable: bool,
+
+    /// Answer packets not accepted by the upper layer handler with a protocol unreachable message.
+    pub protocol_unreachable: bool,
+
+    /// Answer packets whose hop limit is exhausted when forwarding with a time exceeded message.
+    pub time_exceeded: bool,
+
+    /// The rate limit shared by all generated errors.
+    pub limit: RateLimit,
+}
+
+/// Configures the handling of received IPv4 packets carrying header options.
+///
+/// Options are rare in practice and some of them, such as source routing, are a common tool of
+/// attacks. The packets are delivered without inspecting their options by default. Inspect the
+/// options of a delivered packet with [`ipv4_packet::options`].
+///
+/// [`ipv4_packet::options`]: ../../wire/struct.ipv4_packet.html#method.options
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum OptionPolicy {
+    /// Drop all packets with options.
+    Drop,
+
+    /// Deliver packets without inspecting their options.
+    #[default]
+    Ignore,
+
+    /// Deliver packets whose options are well-formed, dropping the others.
+    Deliver,
+
+    /// Like `Deliver`, but also drop packets with options not recognized by the library.
+    DeliverKnown,
+}
+
+/// Configures the handling of the extension headers of received IPv6 packets.
+///
+/// The headers that were handled are removed from the packet before it is delivered to the upper
+/// layer, which only sees the fixed header followed by its own protocol. By default all headers
+/// are processed as specified in RFC 8200, fragments however are only reassembled if the endpoint
+/// was given a [`Reassembly`] buffer.
+///
+/// [`Reassembly`]: struct.Reassembly.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ExtensionPolicy {
+    /// The handling of hop-by-hop options.
+    pub hop_by_hop: ExtensionAction,
+
+    /// The handling of routing headers.
+    pub routing: ExtensionAction,
+
+    /// The handling of fragment headers.
+    pub fragment: ExtensionAction,
+
+    /// The handling of destination options.
+    pub destination: ExtensionAction,
+}
+
+/// The handling of one kind of IPv6 extension header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ExtensionAction {
+    /// Drop all packets containing the header.
+    Drop,
+
+    /// Remove the header without inspecting its content.
+    ///
+    /// For fragment headers this only delivers packets consisting of a single fragment.
+    Skip,
+
+    /// Process the header.
+    ///
+    /// Options are handled according to their type, any option unknown to the library requiring
+    /// it to be understood drops the packet. Routing headers are only accepted with no segments
+    /// left, as the endpoint does not act as an intermediate node. Fragments are reassembled.
+    Process,
+}
+
+/// Configures the validation of the source address of received packets.
+///
+/// A stack exposed to untrusted networks can drop packets with spoofed sources early, as ingress
+/// filtering of RFC 3704 does. Each mode includes the checks of the previous ones. Dropped packets
+/// are counted in the [`Statistics`] of the endpoint and recorded as its last failure. Packets are
+/// accepted regardless of their source by default.
+///
+/// [`Statistics`]: struct.Statistics.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SourceValidation {
+    /// Accept packets regardless of their source address.
+    #[default]
+    Off,
+
+    /// Drop packets from martian sources, addresses that are never a valid source.
+    ///
+    /// These are multicast and broadcast addresses, the reserved IPv4 range `240.0.0.0/4`, and
+    /// loopback addresses that are not in the subnet of an own address. The unspecified address is
+    /// only valid towards a broadcast or multicast destination, as sent by hosts still acquiring an
+    /// address.
+    Martian,
+
+    /// Also drop packets from sources without a route back to them, a loose reverse path check.
+    ///
+    /// A default route passes all sources.
+    Loose,
+
+    /// Also drop packets not sent by the neighbor through which the source is reached, a strict
+    /// reverse path check.
+    ///
+    /// The sender of the frame must be the source itself if it is on the link, or the gateway of
+    /// the route to it otherwise. As the endpoint serves a single link, this is only checked when
+    /// the neighbor cache knows the link-layer address of that next hop.
+    Strict,
+}
+
+/// The upper layer data of an IPv6 packet, behind its extension headers.
+pub(crate) struct Upper {
+    /// The protocol of the data.
+    pub next_header: IpProtocol,
+    /// The offset of the data in the payload.
+    pub offset: usize,
+    /// The fragment header, unless the data is a complete packet.
+    pub fragment: Option<Ipv6FragmentRepr>,
+}
+
+/// An icmp error generated by the endpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum IcmpError {
+    Unreachable(Icmpv4DstUnreachable),
+    TimeExceeded(Icmpv4TimeExceeded),
+}
+
+/// Token bucket state for the policy.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct IcmpLimiter {
+    policy: IcmpPolicy,
+    bucket: Limiter,
+}
+
+impl IcmpPolicy {
+    /// A policy that does not generate any errors automatically.
+    ///
+    /// Errors requested explicitly are still permitted within the default rate limit.
+    pub const SILENT: IcmpPolicy = IcmpPolicy {
+        port_unreachable: false,
+        protocol_unreachable: false,
+        time_exceeded: false,
+        limit: RateLimit::DEFAULT,
+    };
+
+    /// A policy that generates port and protocol unreachable messages.
+    pub const UNREACHABLE: IcmpPolicy = IcmpPolicy {
+        port_unreachable: true,
+        protocol_unreachable: true,
+        ..IcmpPolicy::SILENT
+    };
+
+    /// A policy that generates all errors, as expected of a router.
+    pub const ALL: IcmpPolicy = IcmpPolicy {
+        time_exceeded: true,
+        ..IcmpPolicy::UNREACHABLE
+    };
+
+    /// Check if an error with this reason should be generated at all.
+    ///
+    /// Only the port and protocol unreachable messages are generated automatically and can be
+    /// disabled. Any other reason is requested explicitly by the caller and always permitted.
+    pub fn permits(&self, reason: Icmpv4DstUnreachable) -> bool {
+        match reason {
+            Icmpv4DstUnreachable::PortUnreachable => self.port_unreachable,
+            Icmpv4DstUnreachable::ProtoUnreachable => self.protocol_unreachable,
+            _ => true,
+        }
+    }
+}
+
+impl Default for IcmpPolicy {
+    fn default() -> Self {
+        IcmpPolicy::SILENT
+    }
+}
+
+impl OptionPolicy {
+    /// Check if a packet is delivered, returning the reason for dropping it otherwise.
+    pub(crate) fn check(self, packet: &ipv4_packet) -> Result<(), wire::Error> {
+        let mut options = packet.options();
+        if options.is_empty() {
+            return Ok(());
+        }
+
+        match self {
+            OptionPolicy::Drop => return Err(wire::Error::Unsupported),
+            OptionPolicy::Ignore => return Ok(()),
+            OptionPolicy::Deliver | OptionPolicy::DeliverKnown => (),
+        }
+
+        while !options.is_empty() {
+            let (tail, option) = Ipv4Option::parse(options)?;
+            match option {
+                Ipv4Option::EndOfList => break,
+                Ipv4Option::Unknown { .. } if self == OptionPolicy::DeliverKnown =>
+                    return Err(wire::Error::Unrecognized),
+                _ => (),
+            }
+            options = tail;
+        }
+
+        Ok(())
+    }
+}
+
+impl ExtensionPolicy {
+    /// Process all headers as specified.
+    pub const PROCESS: ExtensionPolicy = ExtensionPolicy {
+        hop_by_hop: ExtensionAction::Process,
+        routing: ExtensionAction::Process,
+        fragment: ExtensionAction::Process,
+        destination: ExtensionAction::Process,
+    };
+
+    /// Drop all packets with extension headers.
+    pub const DROP: ExtensionPolicy = ExtensionPolicy {
+        hop_by_hop: ExtensionAction::Drop,
+        routing: ExtensionAction::Drop,
+        fragment: ExtensionAction::Drop,
+        destination: ExtensionAction::Drop,
+    };
+
+    /// Get the action for a header.
+    pub fn action(&self, header: &Ipv6ExtHeader) -> ExtensionAction {
+        match header {
+            Ipv6ExtHeader::HopByHop(_) => self.hop_by_hop,
+            Ipv6ExtHeader::Routing(_) => self.routing,
+            Ipv6ExtHeader::Fragment(_) => self.fragment,
+            Ipv6ExtHeader::DestinationOptions(_) => self.destination,
+        }
+    }
+
+    /// Check if a header permits delivering the packet, returning the reason for dropping it
+    /// otherwise.
+    pub(crate) fn check(&self, header: &Ipv6ExtHeader) -> Result<(), wire::Error> {
+        match (self.action(header), header) {
+            (ExtensionAction::Drop, _) => Err(wire::Error::Unsupported),
+            (ExtensionAction::Skip, Ipv6ExtHeader::Fragment(fragment)) => match fragment {
+                _ if is_atomic(fragment) => Ok(()),
+                _ => Err(wire::Error::Unsupported),
+            },
+            (ExtensionAction::Skip, _) => Ok(()),
+            (ExtensionAction::Process, Ipv6ExtHeader::HopByHop(options)) |
+            (ExtensionAction::Process, Ipv6ExtHeader::DestinationOptions(options)) =>
+                check_options(options),
+            (ExtensionAction::Process, Ipv6ExtHeader::Routing(routing)) => {
+                match routing.segments_left() {
+                    0 => Ok(()),
+                    _ => Err(wire::Error::Unsupported),
+                }
+            },
+            // Reassembled by the endpoint.
+            (ExtensionAction::Process, Ipv6ExtHeader::Fragment(_)) => Ok(()),
+        }
+    }
+}
+
+impl ExtensionPolicy {
+    /// Check all extension headers of a packet and find its upper layer data.
+    pub(crate) fn walk(&self, packet: &ipv6_packet) -> Result<Upper, wire::Error> {
+        let mut headers = packet.extension_headers();
+        let mut fragment = None;
+        let mut first = true;
+        for header in &mut headers {
+            let header = header?;
+            // Hop-by-hop options must immediately follow the fixed header.
+            if let (Ipv6ExtHeader::HopByHop(_), false) = (&header, first) {
+                return Err(wire::Error::Malformed);
+            }
+
+            self.check(&header)?;
+            if let Ipv6ExtHeader::Fragment(header) = &header {
+                if !is_atomic(header) {
+                    fragment = Some(Ipv6FragmentRepr::parse(header)?);
+                }
+            }
+            first = false;
+        }
+
+        Ok(Upper {
+            next_header: headers.next_header(),
+            offset: headers.offset(),
+            fragment,
+        })
+    }
+}
+
+impl Default for ExtensionPolicy {
+    fn default() -> Self {
+        ExtensionPolicy::PROCESS
+    }
+}
+
+/// Check if a fragment is the only one of its packet.
+pub(crate) fn is_atomic(fragment: &Ipv6FragmentHeader<&[u8]>) -> bool {
+    fragment.frag_offset() == 0 && !fragment.more_frags()
+}
+
+/// Check that all options of a header may be skipped, RFC 8200 section 4.2.
+fn check_options(header: &Ipv6HopByHopHeader<&[u8]>) -> Result<(), wire::Error> {
+    let repr = Ipv6HopByHopRepr::parse(header)?;
+    for option in repr.options() {
+        if let Ipv6OptionRepr::Unknown { type_, .. } = option? {
+            if Ipv6OptionFailureType::from(type_) != Ipv6OptionFailureType::Skip {
+                return Err(wire::Error::Unrecognized);
+            }
+        }
+    }
+    Ok(())
+}
+
+impl IcmpLimiter {
+    pub(crate) fn new(policy: IcmpPolicy) -> Self {
+        IcmpLimiter {
+            policy,
+            bucket: Limiter::new(policy.limit),
+        }
+    }
+
+    pub(crate) fn policy(&self) -> IcmpPolicy {
+        self.policy
+    }
+
+    /// Check the policy and consume one token of the rate limit.
+    pub(crate) fn permits(&mut self, error: IcmpError, now: Instant) -> bool {
+        let enabled = match error {
+            IcmpError::Unreachable(reason) => self.policy.permits(reason),
+            IcmpError::TimeExceeded(Icmpv4TimeExceeded::TtlExpired) => self.policy.time_exceeded,
+            // Only generated on explicit request.
+            IcmpError::TimeExceeded(_) => true,
+        };
+
+        enabled && self.bucket.permit(now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::Duration;
+
+    #[test]
+    fn rate_limit() {
+        let policy = IcmpPolicy {
+            limit: RateLimit { burst: 2, interval: Duration::from_millis(10) },
+            ..IcmpPolicy::UNREACHABLE
+        };
+        let mut limiter = IcmpLimiter::new(policy);
+        let port = IcmpError::Unreachable(Icmpv4DstUnreachable::PortUnreachable);
+        let start = Instant::from_millis(0);
+
+        assert!(limiter.permits(port, start));
+        assert!(limiter.permits(port, start));
+        assert!(!limiter.permits(port, start));
+        assert!(!limiter.permits(port, start + Duration::from_millis(5)));
+        assert!(limiter.permits(port, start + Duration::from_millis(10)));
+        assert!(!limiter.permits(port, start + Duration::from_millis(10)));
+
+        // Refills are bounded by the burst size.
+        let later = start + Duration::from_secs(10);
+        assert!(limiter.permits(port, later));
+        assert!(limiter.permits(port, later));
+        assert!(!limiter.permits(port, later));
+    }
+
+    #[test]
+    fn silent() {
+        let mut limiter = IcmpLimiter::new(IcmpPolicy::SILENT);
+        let now = Instant::from_millis(0);
+        let unreachable = |reason| IcmpError::Unreachable(reason);
+        assert!(!limiter.permits(unreachable(Icmpv4DstUnreachable::PortUnreachable), now));
+        assert!(!limiter.permits(unreachable(Icmpv4DstUnreachable::ProtoUnreachable), now));
+        assert!(limiter.permits(unreachable(Icmpv4DstUnreachable::HostUnreachable), now));
+    }
+
+    #[test]
+    fn shared_limit() {
+        let policy = IcmpPolicy {
+            limit: RateLimit { burst: 2, interval: Duration::from_secs(1) },
+            ..IcmpPolicy::ALL
+        };
+        let mut limiter = IcmpLimiter::new(policy);
+        let now = Instant::from_millis(0);
+        let port = IcmpError::Unreachable(Icmpv4DstUnreachable::PortUnreachable);
+        let ttl = IcmpError::TimeExceeded(Icmpv4TimeExceeded::TtlExpired);
+
+        assert!(limiter.permits(ttl, now));
+        assert!(limiter.permits(port, now));
+        // Both kinds of errors draw from the same bucket.
+        assert!(!limiter.permits(ttl, now));
+        assert!(!limiter.permits(port, now));
+    }
+}
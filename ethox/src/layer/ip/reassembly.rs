@@ -0,0 +1,224 @@
+use crate::managed::Slice;
+use crate::storage::assembler::{Assembler, Contig};
+use crate::time::{Duration, Instant};
+use crate::wire::{IpProtocol, Ipv6Address, Ipv6FragmentRepr, Ipv6Repr};
+
+use crate::layer::Error;
+
+/// The number of separate ranges of data that can be tracked per packet.
+const HOLES: usize = 8;
+
+/// Buffers for the reassembly of fragmented IPv6 packets.
+///
+/// Each buffer holds the fragments of one packet until all of them have been received or the
+/// timeout has passed. A packet is only reassembled if its data fits into a buffer. Once complete
+/// it is copied into the buffer of its last received fragment and delivered from there, which
+/// hence must be able to grow to the full packet length.
+pub struct Reassembly<'a> {
+    datagrams: Slice<'a, Datagram<'a>>,
+    timeout: Duration,
+    reassembled: u64,
+    expired: u64,
+}
+
+/// The buffer for the fragments of one packet.
+pub struct Datagram<'a> {
+    buffer: Slice<'a, u8>,
+    state: Option<State>,
+}
+
+/// The packet currently reassembled in a buffer.
+struct State {
+    src_addr: Ipv6Address,
+    dst_addr: Ipv6Address,
+    ident: u32,
+    next_header: IpProtocol,
+    /// The length of the data, known from the last fragment.
+    len: Option<usize>,
+    received: Assembler<[Contig; HOLES]>,
+    deadline: Instant,
+}
+
+impl<'a> Reassembly<'a> {
+    /// The time after which an incomplete packet is dropped, RFC 8200 section 4.5.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+    /// Create reassembly state with a set of buffers.
+    pub fn new(datagrams: impl Into<Slice<'a, Datagram<'a>>>) -> Self {
+        Reassembly {
+            datagrams: datagrams.into(),
+            timeout: Self::DEFAULT_TIMEOUT,
+            reassembled: 0,
+            expired: 0,
+        }
+    }
+
+    /// Change the time after which incomplete packets are dropped.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// The number of packets currently being reassembled.
+    pub fn in_progress(&self) -> usize {
+        self.datagrams.iter().filter(|datagram| datagram.state.is_some()).count()
+    }
+
+    /// The number of packets that were reassembled completely.
+    pub fn reassembled(&self) -> u64 {
+        self.reassembled
+    }
+
+    /// The number of incomplete packets dropped after the timeout.
+    pub fn expired(&self) -> u64 {
+        self.expired
+    }
+
+    /// Add the data of a fragment.
+    ///
+    /// Returns the index of the buffer once the packet is complete. Its data can then be copied
+    /// from `assembled` after which the buffer must be freed with `release`.
+    pub(crate) fn add(
+        &mut self,
+        repr: &Ipv6Repr,
+        fragment: &Ipv6FragmentRepr,
+        data: &[u8],
+        now: Instant,
+    ) -> Result<Option<usize>, Error> {
+        self.expire(now);
+
+        let offset = usize::from(fragment.frag_offset) * 8;
+        // All fragments but the last carry a multiple of eight octets.
+        if fragment.more_frags && !data.len().is_multiple_of(8) {
+            return Err(Error::Illegal);
+        }
+
+        let idx = match self.find(repr, fragment) {
+            Some(idx) => idx,
+            None => self.start(repr, fragment, now)?,
+        };
+
+        let datagram = &mut self.datagrams[idx];
+        let result = datagram.add(fragment, offset, data);
+        if result.is_err() {
+            // Drop all fragments, following RFC 5722 for overlapping or inconsistent ones.
+            datagram.state = None;
+        }
+
+        if !result? {
+            return Ok(None);
+        }
+
+        self.reassembled += 1;
+        Ok(Some(idx))
+    }
+
+    /// The next header and data of a complete packet.
+    pub(crate) fn assembled(&self, idx: usize) -> (IpProtocol, &[u8]) {
+        let datagram = &self.datagrams[idx];
+        let state = datagram.state.as_ref().expect("Taken from an empty buffer");
+        let len = state.len.expect("Taken from an incomplete packet");
+        (state.next_header, &datagram.buffer[..len])
+    }
+
+    /// Free the buffer of a packet.
+    pub(crate) fn release(&mut self, idx: usize) {
+        self.datagrams[idx].state = None;
+    }
+
+    fn find(&self, repr: &Ipv6Repr, fragment: &Ipv6FragmentRepr) -> Option<usize> {
+        self.datagrams.iter().position(|datagram| match &datagram.state {
+            Some(state) => state.src_addr == repr.src_addr
+                && state.dst_addr == repr.dst_addr
+                && state.ident == fragment.ident,
+            None => false,
+        })
+    }
+
+    fn start(&mut self, repr: &Ipv6Repr, fragment: &Ipv6FragmentRepr, now: Instant)
+        -> Result<usize, Error>
+    {
+        let idx = self.datagrams.iter()
+            .position(|datagram| datagram.state.is_none())
+            .ok_or(Error::Exhausted)?;
+        self.datagrams[idx].state = Some(State {
+            src_addr: repr.src_addr,
+            dst_addr: repr.dst_addr,
+            ident: fragment.ident,
+            next_header: fragment.next_header,
+            len: None,
+            received: Assembler::new([Contig::default(); HOLES]),
+            deadline: now + self.timeout,
+        });
+        Ok(idx)
+    }
+
+    fn expire(&mut self, now: Instant) {
+        for datagram in self.datagrams.iter_mut() {
+            if let Some(state) = &datagram.state {
+                if state.deadline <= now {
+                    datagram.state = None;
+                    self.expired += 1;
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Datagram<'a> {
+    /// Create an empty buffer for the fragments of one packet.
+    ///
+    /// The buffer must be large enough for the reassembled upper layer data, packets longer than
+    /// it are dropped.
+    pub fn new(buffer: impl Into<Slice<'a, u8>>) -> Self {
+        Datagram {
+            buffer: buffer.into(),
+            state: None,
+        }
+    }
+
+    /// Store the data of a fragment, returning whether the packet is complete.
+    fn add(&mut self, fragment: &Ipv6FragmentRepr, offset: usize, data: &[u8])
+        -> Result<bool, Error>
+    {
+        let state = self.state.as_mut().unwrap();
+        let end = offset + data.len();
+        if end > self.buffer.len() {
+            return Err(Error::BadSize);
+        }
+
+        // Only the first fragment determines the following header.
+        if offset == 0 {
+            state.next_header = fragment.next_header;
+        }
+
+        match state.len {
+            // Data behind the end, or a second differing end.
+            Some(len) if end > len || (!fragment.more_frags && end != len) =>
+                return Err(Error::Illegal),
+            Some(_) => (),
+            None if !fragment.more_frags => {
+                if state.received.iter().any(|(_, stop)| stop as usize > end) {
+                    return Err(Error::Illegal);
+                }
+                state.len = Some(end);
+            },
+            None => (),
+        }
+
+        // Overlapping fragments are not permitted.
+        let overlaps = |(start, stop): (u32, u32)| (start as usize) < end && offset < stop as usize;
+        if state.received.iter().any(overlaps) {
+            return Err(Error::Illegal);
+        }
+
+        state.received.bounded_add(offset as u32, data.len() as u32, 0)
+            .map_err(|_| Error::Exhausted)?;
+        self.buffer[offset..end].copy_from_slice(data);
+
+        let complete = match state.len {
+            Some(len) => state.received.iter().next() == Some((0, len as u32)),
+            None => false,
+        };
+        Ok(complete)
+    }
+}
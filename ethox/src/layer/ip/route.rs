@@ -125,10 +125,17 @@ impl<'a> Routes<'a> {
 
     /// Creates a routing tables. The backing storage is **not** cleared
     /// upon creation.
-    pub fn import(storage: List<'a, Route>) -> Self {
+    ///
+    /// Unlike `new` this is a `const fn`, for example for a table in a `static`.
+    pub const fn import(storage: List<'a, Route>) -> Self {
         Routes { storage }
     }
 
+    /// The routes of the table.
+    pub fn as_slice(&self) -> &[Route] {
+        &self.storage
+    }
+
     /// Update the routes of this node.
     pub fn update<F: FnOnce(&mut [Route])>(&mut self, f: F) {
         f(&mut self.storage);
@@ -144,6 +151,37 @@ impl<'a> Routes<'a> {
         }
     }
 
+    /// Add a route or replace the one for the same network and next hop.
+    ///
+    /// When the table is full, a route that has expired at `now` is replaced instead.
+    pub fn update_route(&mut self, route: Route, now: Instant) -> Result<()> {
+        let same = |other: &Route| other.net == route.net && other.next_hop == route.next_hop;
+        if let Some(existing) = self.storage.iter_mut().find(|other| same(other)) {
+            *existing = route;
+            return Ok(());
+        }
+
+        if let Some(place) = self.storage.push() {
+            *place = route;
+            return Ok(());
+        }
+
+        let expired = self.storage.iter_mut()
+            .find(|other| Expiration::When(now) > other.expires_at)
+            .ok_or(Error::Exhausted)?;
+        *expired = route;
+        Ok(())
+    }
+
+    /// Remove the route for a network via a next hop.
+    ///
+    /// Returns the removed route or `None` if there was no such route.
+    pub fn remove_route(&mut self, net: IpSubnet, next_hop: IpAddress) -> Option<Route> {
+        let idx = self.storage.iter()
+            .position(|route| route.net == net && route.next_hop == next_hop)?;
+        self.storage.remove_at(idx).map(|removed| *removed)
+    }
+
     /// Find the next hop for a destination address.
     ///
     /// The timestamp ensures that only valid entries are used. If multiple matching routes are
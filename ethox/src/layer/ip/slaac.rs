@@ -0,0 +1,243 @@
+//! IPv6 stateless address autoconfiguration, rfc4862.
+//!
+//! Router advertisements, rfc4861, configure the endpoint: the advertising router becomes a
+//! default route for its advertised lifetime and every prefix marked for autonomous configuration
+//! yields an address assignment with the advertised lifetimes. The interface identifier of these
+//! addresses is either derived from the ethernet address (modified EUI-64) or, preferably, a keyed
+//! hash of prefix and ethernet address as recommended by rfc7217.
+//!
+//! New addresses are tentative until duplicate address detection, rfc4862 section 5.4, confirms
+//! them. The probes of other hosts for the same address are only received if the ethernet
+//! endpoint has joined the solicited node multicast group of the address.
+use crate::layer::arp::{Neighbor, NeighborCache};
+use crate::siphash::State as SipHash;
+use crate::time::{Duration, Expiration, Instant};
+use crate::wire::{EthernetAddress, InterfaceId, IpAddress, IpCidr, IpProtocol};
+use crate::wire::{Ipv6Address, Ipv6Cidr, Ipv6Repr, Ipv6Subnet};
+use crate::wire::{ndisc_packet, NdiscMessage, NdiscOption, NdiscPrefixInformation};
+
+use super::assignment::Assignment;
+use super::endpoint::Routing;
+use super::route::Route;
+
+/// Configuration of stateless address autoconfiguration on an ip endpoint.
+///
+/// See [`Endpoint::set_slaac`] for enabling it.
+///
+/// [`Endpoint::set_slaac`]: struct.Endpoint.html#method.set_slaac
+#[derive(Clone)]
+pub struct Slaac {
+    interface_id: Generation,
+}
+
+/// How to form the interface identifier of an address.
+#[derive(Clone, Copy)]
+enum Generation {
+    /// Modified EUI-64 from the ethernet address.
+    Eui64,
+    /// Keyed hash of the prefix and the ethernet address.
+    StablePrivacy {
+        keys: (u64, u64),
+    },
+}
+
+impl Slaac {
+    /// The lifetime below which an advertisement can no longer shorten the valid lifetime of an
+    /// address, see rfc4862 section 5.5.3 (e).
+    pub const MIN_VALID_LIFETIME: Duration = Duration::from_secs(2*60*60);
+
+    /// Form interface identifiers from the ethernet address, as modified EUI-64.
+    ///
+    /// The interface identifier is then the same in every network, making the device trackable
+    /// across networks. Prefer [`stable_privacy`] if a secret key can be provided.
+    ///
+    /// [`stable_privacy`]: #method.stable_privacy
+    pub fn eui64() -> Self {
+        Slaac {
+            interface_id: Generation::Eui64,
+        }
+    }
+
+    /// Form stable, semantically opaque interface identifiers, rfc7217.
+    ///
+    /// The interface identifier is a SipHash-2-4 of the prefix and the ethernet address. It is
+    /// stable within a network but unrelated between networks. The secret key should be random
+    /// but persist across reboots, otherwise the addresses change as well.
+    pub fn stable_privacy(secret_key: [u8; 16]) -> Self {
+        let [a0, a1, a2, a3, a4, a5, a6, a7, b0, b1, b2, b3, b4, b5, b6, b7] = secret_key;
+        let a = u64::from_le_bytes([a0, a1, a2, a3, a4, a5, a6, a7]);
+        let b = u64::from_le_bytes([b0, b1, b2, b3, b4, b5, b6, b7]);
+        Slaac {
+            interface_id: Generation::StablePrivacy { keys: (a, b) },
+        }
+    }
+
+    /// The address to configure within a /64 prefix, for an interface with an ethernet address.
+    ///
+    /// Only the first 64 bits of the prefix are used.
+    pub fn address(&self, prefix: Ipv6Address, hw_addr: EthernetAddress) -> Ipv6Address {
+        let prefix = Ipv6Cidr::new(prefix, 64).subnet();
+        Ipv6Address::from_global_unicast_id(prefix, self.interface_id(prefix, hw_addr))
+            .expect("Prefix has the length of an interface id")
+    }
+
+    fn interface_id(&self, prefix: Ipv6Subnet, hw_addr: EthernetAddress) -> InterfaceId {
+        let keys = match self.interface_id {
+            Generation::Eui64 => return InterfaceId::from_vendor_ether(hw_addr),
+            Generation::StablePrivacy { keys } => keys,
+        };
+
+        let network = Ipv6Address::from_global_unicast_id(prefix, InterfaceId::default())
+            .expect("Prefix has the length of an interface id");
+        let Ipv6Address([p0, p1, p2, p3, p4, p5, p6, p7, ..]) = network;
+        let EthernetAddress([h0, h1, h2, h3, h4, h5]) = hw_addr;
+        // Retry with the next counter for the rare reserved identifiers, like the DAD_Counter.
+        (0u8..).map(|counter| {
+            let mut state = SipHash::init(keys.0, keys.1);
+            state.absorb(u64::from_be_bytes([p0, p1, p2, p3, p4, p5, p6, p7]));
+            // Message length = 15
+            state.absorb(u64::from_be_bytes([15, counter, h5, h4, h3, h2, h1, h0]));
+            InterfaceId::from_generated_bytes(state.finalize().to_be_bytes())
+        })
+        .find(|id| !is_reserved(*id))
+        .unwrap()
+    }
+
+    /// Apply a router advertisement received from `router`, see [`router_advert`].
+    ///
+    /// The link-local address of the interface is configured as well if it has not been yet, the
+    /// advertising router could otherwise not be used as a next hop.
+    pub(crate) fn router_advert(
+        &self,
+        advert: &ndisc_packet,
+        router: Ipv6Address,
+        hw_addr: EthernetAddress,
+        routing: &mut Routing,
+        neighbors: &mut NeighborCache,
+        now: Instant,
+    ) {
+        let link_local = self.address(Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), hw_addr);
+        if routing.assignment(link_local.into()).is_none() {
+            let _ = routing.assign(Assignment::from(Ipv6Cidr::new(link_local, 64)).tentative());
+        }
+
+        let default = IpCidr::new(IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 0), 0).subnet();
+        let router_lifetime = advert.router_lifetime();
+        let router_expires = Expiration::When(now + router_lifetime);
+        if router_lifetime == Duration::from_secs(0) {
+            routing.remove_route(default, router.into());
+        } else {
+            let route = Route {
+                net: default,
+                next_hop: router.into(),
+                expires_at: router_expires,
+            };
+            // Without room the router is not used but addresses are still configured.
+            let _ = routing.update_route(route, now);
+        }
+
+        for option in advert.options().filter_map(Result::ok) {
+            match option {
+                NdiscOption::SourceLinkLayerAddr(router_hw) => {
+                    // Without neighbor solicitation this is our only way to learn the address.
+                    let expires_at = router_expires.max(Expiration::When(now));
+                    let _ = neighbors.insert(Neighbor::new(router.into(), router_hw, expires_at));
+                },
+                NdiscOption::PrefixInformation(info) => {
+                    self.prefix_information(info, hw_addr, routing, now)
+                },
+                _ => (),
+            }
+        }
+    }
+
+    fn prefix_information(
+        &self,
+        info: NdiscPrefixInformation,
+        hw_addr: EthernetAddress,
+        routing: &mut Routing,
+        now: Instant,
+    ) {
+        // Rules from rfc4862 section 5.5.3 (a) through (c), the prefix length must further match
+        // the length of interface identifiers.
+        if !info.autonomous
+            || info.prefix.is_link_local()
+            || info.preferred_lifetime > info.valid_lifetime
+            || info.prefix_len != 64
+        {
+            return;
+        }
+
+        let address = self.address(info.prefix, hw_addr);
+
+        let preferred_until = lifetime(info.preferred_lifetime, now);
+        let advertised = lifetime(info.valid_lifetime, now);
+        let existing = routing.assignment(address.into()).copied();
+        let valid_until = match existing {
+            // A new address is only formed with a non-zero lifetime.
+            None if info.valid_lifetime == 0 => return,
+            None => advertised,
+            Some(existing) => {
+                // The two hour rule protects against a spoofed advertisement invalidating an
+                // address that is in use.
+                let two_hours = Expiration::When(now + Self::MIN_VALID_LIFETIME);
+                if advertised > two_hours || advertised > existing.valid_until {
+                    advertised
+                } else if existing.valid_until <= two_hours {
+                    existing.valid_until
+                } else {
+                    two_hours
+                }
+            },
+        };
+
+        // Prefixes not advertised as on-link are only reachable through the router.
+        let prefix_len = if info.on_link { 64 } else { 128 };
+        let cidr = IpCidr::new(address.into(), prefix_len);
+        let assignment = match existing {
+            // A known address keeps the state of its duplicate address detection.
+            Some(existing) => Assignment { cidr, preferred_until, valid_until, ..existing },
+            None => Assignment::with_lifetimes(cidr, preferred_until, valid_until).tentative(),
+        };
+        let _ = routing.assign(assignment);
+    }
+}
+
+/// Check that a packet is a valid router advertisement, rfc4861 section 6.1.2.
+///
+/// Returns the advertisement if the packet was received with the hop limit unchanged from a
+/// link-local address and is a well-formed router advertisement with valid checksum.
+pub(crate) fn router_advert(repr: Ipv6Repr, payload: &[u8]) -> Option<&ndisc_packet> {
+    if repr.next_header != IpProtocol::Icmpv6
+        || repr.hop_limit != 255
+        || !repr.src_addr.is_link_local()
+    {
+        return None;
+    }
+
+    let advert = ndisc_packet::new_checked(payload).ok()?;
+    if advert.msg_type() != NdiscMessage::RouterAdvert
+        || advert.msg_code() != 0
+        || !advert.verify_checksum(repr.src_addr, repr.dst_addr)
+        || advert.options().any(|option| option.is_err())
+    {
+        return None;
+    }
+
+    Some(advert)
+}
+
+/// Interface identifiers reserved by rfc5453.
+fn is_reserved(InterfaceId(id): InterfaceId) -> bool {
+    let subnet_anycast = id[..7] == [0xfd, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff] && id[7] >= 0x80;
+    id == [0; 8] || subnet_anycast
+}
+
+/// The expiration of an advertised lifetime in seconds.
+fn lifetime(secs: u32, now: Instant) -> Expiration {
+    if secs == NdiscPrefixInformation::INFINITE {
+        Expiration::Never
+    } else {
+        Expiration::When(now + Duration::from_secs(secs.into()))
+    }
+}
@@ -1,9 +1,14 @@
 use super::*;
 use crate::managed::Slice;
-use crate::nic::{external::External, Device};
-use crate::layer::{arp, eth, ip};
+use crate::nic::{external::External, loopback::Loopback, Device};
+use crate::layer::{arp, eth, ip, Detail, Error, Failure, FnHandler, Operation, Origin};
+use crate::time::{Duration, Expiration, Instant};
 use crate::wire::{EthernetAddress, InterfaceId, IpAddress, IpCidr, IpSubnet, Ipv4Address, Ipv4Subnet, Ipv6Address, Ipv6Subnet, IpProtocol};
-use crate::wire::{ethernet_frame, ipv4_packet, ipv6_packet};
+use crate::wire::{ethernet_frame, icmpv4_packet, ipv4_packet, ipv6_packet, ndisc_packet};
+use crate::wire::{arp_packet, ArpOperation, ArpRepr};
+use crate::wire::{EthernetProtocol, EthernetRepr, Ipv6Repr, Ipv6Scope};
+use crate::wire::{NdiscMessage, NdiscOption, NdiscPrefixInformation};
+use crate::wire::{Checksum, Icmpv4DstUnreachable, Icmpv4Repr, Icmpv4TimeExceeded, Ipv4Option};
 use crate::wire::{Payload, PayloadMut};
 
 static PAYLOAD_BYTES: [u8; 50] =
@@ -65,6 +70,22 @@ fn simple_ipv4() {
     let recv = nic.rx(1,
         eth.recv(ip.recv_with(simple_recv)));
    assert_eq!(recv, Ok(1)); 
+    assert_eq!(ip.last_failure(), None);
+
+    // A corrupted header is dropped and its checksum blamed.
+    {
+        let buffer = nic.get_mut(0).unwrap();
+        let eth = ethernet_frame::new_unchecked_mut(buffer);
+        let ip = ipv4_packet::new_unchecked_mut(eth.payload_mut_slice());
+        ip.set_hop_limit(ip.hop_limit() - 1);
+    }
+    nic.receive_all();
+    let recv = nic.rx(1,
+        eth.recv(ip.recv_with(|_: InPacket<_>| panic!("Corrupted packet was received"))));
+    assert_eq!(recv, Ok(1));
+    let failure = ip.last_failure().unwrap();
+    assert_eq!((failure.origin, failure.operation), (Origin::Ip, Operation::Receive));
+    assert_eq!(failure.detail, Detail::Wire(crate::wire::Error::WrongChecksum));
 }
 
 #[test]
@@ -114,6 +135,1216 @@ fn simple_ipv6() {
    assert_eq!(recv, Ok(1)); 
 }
 
+#[test]
+fn hop_limit() {
+    const MAC_ADDR_SRC: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 5]);
+    const IP_ADDR_SRC: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
+    const MAC_ADDR_DST: EthernetAddress = EthernetAddress([6, 5, 4, 3, 2, 1]);
+    const IP_ADDR_DST: Ipv4Address = Ipv4Address::new(10, 0, 0, 2);
+
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+
+    let mut eth = eth::Endpoint::new(MAC_ADDR_SRC);
+
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut neighbors[..]);
+        eth_cache.fill(IP_ADDR_DST.into(), MAC_ADDR_DST, None).unwrap();
+        eth_cache
+    };
+    let mut ip = [ip::Route::unspecified(); 2];
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_SRC.into(), 24),
+        ip::Routes::new(&mut ip[..]),
+        neighbors);
+    assert_eq!(ip.hop_limit(), ip::Endpoint::DEFAULT_HOP_LIMIT);
+    ip.set_hop_limit(16);
+
+    let send_with_limit = |hop_limit: Option<u8>| move |packet: RawPacket<_>| {
+        let init = ip::Init {
+            source: IpSubnet::from(Ipv4Subnet::ANY).into(),
+            dst_addr: IP_ADDR_DST.into(),
+            protocol: IpProtocol::Unknown(0xEF),
+            payload: 0,
+            hop_limit,
+            dscp: 0,
+        };
+        packet.prepare(init).unwrap().send().unwrap();
+    };
+
+    let retarget = |nic: &mut External<Slice<Vec<u8>>>| {
+        let buffer = nic.get_mut(0).unwrap();
+        let eth = ethernet_frame::new_unchecked_mut(buffer);
+        eth.set_dst_addr(MAC_ADDR_SRC);
+        eth.set_src_addr(MAC_ADDR_DST);
+        let ip = ipv4_packet::new_unchecked_mut(eth.payload_mut_slice());
+        ip.set_dst_addr(IP_ADDR_SRC);
+        ip.set_src_addr(IP_ADDR_DST);
+        ip.fill_checksum();
+        nic.receive_all();
+    };
+
+    // The endpoint default is decremented for forwarding.
+    assert_eq!(nic.tx(1, eth.send(ip.send_with(send_with_limit(None)))), Ok(1));
+    retarget(&mut nic);
+    assert_eq!(nic.rx(1, eth.recv(ip.recv_with(|mut frame: InPacket<_>| {
+        assert_eq!(frame.packet.repr().hop_limit(), 16);
+        assert_eq!(frame.packet.decrement_hop_limit(), Ok(15));
+        assert_eq!(frame.packet.repr().hop_limit(), 15);
+    }))), Ok(1));
+
+    // Overridden for the packet, which then must not be forwarded.
+    nic.send_all();
+    assert_eq!(nic.tx(1, eth.send(ip.send_with(send_with_limit(Some(1))))), Ok(1));
+    retarget(&mut nic);
+    assert_eq!(nic.rx(1, eth.recv(ip.recv_with(|mut frame: InPacket<_>| {
+        assert_eq!(frame.packet.repr().hop_limit(), 1);
+        assert_eq!(frame.packet.decrement_hop_limit(), Err(Error::Unreachable));
+        assert_eq!(frame.packet.repr().hop_limit(), 1);
+    }))), Ok(1));
+}
+
+#[test]
+fn header_options() {
+    const MAC_ADDR_SRC: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 5]);
+    const IP_ADDR_SRC: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
+    const MAC_ADDR_DST: EthernetAddress = EthernetAddress([6, 5, 4, 3, 2, 1]);
+    const IP_ADDR_DST: Ipv4Address = Ipv4Address::new(10, 0, 0, 2);
+
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+
+    let mut eth = eth::Endpoint::new(MAC_ADDR_SRC);
+
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut neighbors[..]);
+        eth_cache.fill(IP_ADDR_DST.into(), MAC_ADDR_DST, None).unwrap();
+        eth_cache
+    };
+    let mut ip = [ip::Route::unspecified(); 2];
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_SRC.into(), 24),
+        ip::Routes::new(&mut ip[..]),
+        neighbors);
+    assert_eq!(ip.option_policy(), ip::OptionPolicy::Ignore);
+
+    let send_with_options = |options: &'static [Ipv4Option]| move |packet: RawPacket<_>| {
+        let init = ip::Init {
+            source: IpSubnet::from(Ipv4Subnet::ANY).into(),
+            dst_addr: IP_ADDR_DST.into(),
+            protocol: IpProtocol::Unknown(0xEF),
+            payload: 4,
+            hop_limit: None,
+            dscp: 0,
+        };
+        let mut packet = packet.prepare_with_options(init, options).unwrap();
+        packet.payload_mut_slice().copy_from_slice(b"ping");
+        packet.send().unwrap();
+    };
+
+    let retarget = |nic: &mut External<Slice<Vec<u8>>>| {
+        let buffer = nic.get_mut(0).unwrap();
+        let eth = ethernet_frame::new_unchecked_mut(buffer);
+        eth.set_dst_addr(MAC_ADDR_SRC);
+        eth.set_src_addr(MAC_ADDR_DST);
+        let ip = ipv4_packet::new_unchecked_mut(eth.payload_mut_slice());
+        ip.set_dst_addr(IP_ADDR_SRC);
+        ip.set_src_addr(IP_ADDR_DST);
+        ip.fill_checksum();
+        nic.receive_all();
+    };
+
+    const RECORD_ROUTE: [Ipv4Option; 2] = [
+        Ipv4Option::RouterAlert(0),
+        Ipv4Option::RecordRoute { pointer: 4, data: &[0; 8] },
+    ];
+    assert_eq!(nic.tx(1, eth.send(ip.send_with(send_with_options(&RECORD_ROUTE)))), Ok(1));
+    retarget(&mut nic);
+    {
+        let eth = ethernet_frame::new_unchecked(&nic.get(0).unwrap()[..]);
+        let ip = ipv4_packet::new_unchecked(eth.payload_slice());
+        assert_eq!(ip.header_len(), 36);
+        assert_eq!(ip.payload_slice(), b"ping");
+    }
+
+    let options_of = |frame: InPacket<_>| match frame.packet {
+        IpPacket::V4(packet) => {
+            let (_, option) = Ipv4Option::parse(packet.options()).unwrap();
+            assert_eq!(option, Ipv4Option::RouterAlert(0));
+            assert_eq!(packet.payload_slice(), b"ping");
+        },
+        IpPacket::V6(_) => unreachable!(),
+    };
+    assert_eq!(nic.rx(1, eth.recv(ip.recv_with(options_of))), Ok(1));
+
+    ip.set_option_policy(ip::OptionPolicy::DeliverKnown);
+    nic.receive_all();
+    assert_eq!(nic.rx(1, eth.recv(ip.recv_with(options_of))), Ok(1));
+    assert_eq!(ip.last_failure(), None);
+
+    // Packets with options are refused.
+    ip.set_option_policy(ip::OptionPolicy::Drop);
+    nic.receive_all();
+    let recv = nic.rx(1,
+        eth.recv(ip.recv_with(|_: InPacket<_>| panic!("Packet with options was received"))));
+    assert_eq!(recv, Ok(1));
+    let failure = ip.last_failure().unwrap();
+    assert_eq!(failure.detail, Detail::Wire(crate::wire::Error::Unsupported));
+
+    // Unknown options are only refused when they are required to be known.
+    const UNKNOWN: [Ipv4Option; 1] = [Ipv4Option::Unknown { kind: 0x9e, data: &[1, 2] }];
+    ip.set_option_policy(ip::OptionPolicy::Deliver);
+    nic.send_all();
+    assert_eq!(nic.tx(1, eth.send(ip.send_with(send_with_options(&UNKNOWN)))), Ok(1));
+    retarget(&mut nic);
+    let recv = nic.rx(1, eth.recv(ip.recv_with(|frame: InPacket<_>| {
+        assert_eq!(frame.packet.payload().as_slice(), b"ping");
+    })));
+    assert_eq!(recv, Ok(1));
+
+    ip.set_option_policy(ip::OptionPolicy::DeliverKnown);
+    nic.receive_all();
+    let recv = nic.rx(1,
+        eth.recv(ip.recv_with(|_: InPacket<_>| panic!("Unknown option was received"))));
+    assert_eq!(recv, Ok(1));
+    let failure = ip.last_failure().unwrap();
+    assert_eq!(failure.detail, Detail::Wire(crate::wire::Error::Unrecognized));
+}
+
+#[test]
+fn source_validation() {
+    const MAC_ADDR_SRC: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 5]);
+    const IP_ADDR_SRC: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
+    const MAC_ADDR_DST: EthernetAddress = EthernetAddress([6, 5, 4, 3, 2, 1]);
+    const IP_ADDR_DST: Ipv4Address = Ipv4Address::new(10, 0, 0, 2);
+    const MAC_ADDR_SPOOF: EthernetAddress = EthernetAddress([6, 6, 6, 6, 6, 6]);
+
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+
+    let mut eth = eth::Endpoint::new(MAC_ADDR_SRC);
+
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut neighbors[..]);
+        eth_cache.fill(IP_ADDR_DST.into(), MAC_ADDR_DST, None).unwrap();
+        eth_cache
+    };
+    let mut ip = [ip::Route::unspecified(); 2];
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_SRC.into(), 24),
+        ip::Routes::new(&mut ip[..]),
+        neighbors);
+    assert_eq!(ip.source_validation(), ip::SourceValidation::Off);
+
+    let sent = nic.tx(1, eth.send(ip.send_with(|packet: RawPacket<_>| {
+        let init = ip::Init {
+            source: IpSubnet::from(Ipv4Subnet::ANY).into(),
+            dst_addr: IP_ADDR_DST.into(),
+            protocol: IpProtocol::Unknown(0xEF),
+            payload: 4,
+            hop_limit: None,
+            dscp: 0,
+        };
+        let mut packet = packet.prepare(init).unwrap();
+        packet.payload_mut_slice().copy_from_slice(b"ping");
+        packet.send().unwrap();
+    })));
+    assert_eq!(sent, Ok(1));
+
+    // Receive the sent packet from another source.
+    let mut receive_from = |ip: &mut ip::Endpoint, src_addr: Ipv4Address, src_mac| {
+        let buffer = nic.get_mut(0).unwrap();
+        let frame = ethernet_frame::new_unchecked_mut(buffer);
+        frame.set_dst_addr(MAC_ADDR_SRC);
+        frame.set_src_addr(src_mac);
+        let packet = ipv4_packet::new_unchecked_mut(frame.payload_mut_slice());
+        packet.set_dst_addr(IP_ADDR_SRC);
+        packet.set_src_addr(src_addr);
+        packet.fill_checksum();
+        nic.receive_all();
+
+        let mut received = false;
+        let recv = nic.rx(1, eth.recv(ip.recv_with(|_: InPacket<_>| received = true)));
+        assert_eq!(recv, Ok(1));
+        received
+    };
+
+    let multicast = Ipv4Address::new(224, 0, 0, 1);
+    let remote = Ipv4Address::new(192, 0, 2, 1);
+    assert!(receive_from(&mut ip, multicast, MAC_ADDR_DST));
+
+    ip.set_source_validation(ip::SourceValidation::Martian);
+    assert!(!receive_from(&mut ip, multicast, MAC_ADDR_DST));
+    assert!(!receive_from(&mut ip, Ipv4Address::new(10, 0, 0, 255), MAC_ADDR_DST));
+    assert!(!receive_from(&mut ip, Ipv4Address::new(127, 0, 0, 1), MAC_ADDR_DST));
+    assert!(!receive_from(&mut ip, Ipv4Address::UNSPECIFIED, MAC_ADDR_DST));
+    assert_eq!(ip.last_failure().unwrap().detail, Detail::Martian(Ipv4Address::UNSPECIFIED.into()));
+    assert_eq!(ip.statistics().martian, 4);
+    assert!(receive_from(&mut ip, remote, MAC_ADDR_DST));
+
+    // Without a route back the remote source is refused.
+    ip.set_source_validation(ip::SourceValidation::Loose);
+    assert!(!receive_from(&mut ip, remote, MAC_ADDR_DST));
+    assert_eq!(ip.last_failure().unwrap().detail, Detail::ReversePath(remote.into()));
+    assert!(receive_from(&mut ip, IP_ADDR_DST, MAC_ADDR_SPOOF));
+
+    ip.add_route(ip::Route::new_ipv4_gateway(IP_ADDR_DST)).unwrap();
+    assert!(receive_from(&mut ip, remote, MAC_ADDR_SPOOF));
+
+    // The frame must come from the next hop towards the source.
+    ip.set_source_validation(ip::SourceValidation::Strict);
+    assert!(!receive_from(&mut ip, remote, MAC_ADDR_SPOOF));
+    assert!(!receive_from(&mut ip, IP_ADDR_DST, MAC_ADDR_SPOOF));
+    assert!(receive_from(&mut ip, remote, MAC_ADDR_DST));
+    assert!(receive_from(&mut ip, IP_ADDR_DST, MAC_ADDR_DST));
+    // Without a known link-layer address of the next hop, there is nothing to compare.
+    assert!(receive_from(&mut ip, Ipv4Address::new(10, 0, 0, 3), MAC_ADDR_SPOOF));
+
+    assert_eq!(ip.statistics(), ip::Statistics { martian: 4, reverse_path: 3 });
+}
+
+#[test]
+#[cfg(feature = "trace")]
+fn trace() {
+    const MAC_ADDR_SRC: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 5]);
+    const IP_ADDR_SRC: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
+    const MAC_ADDR_DST: EthernetAddress = EthernetAddress([6, 5, 4, 3, 2, 1]);
+    const IP_ADDR_DST: Ipv4Address = Ipv4Address::new(10, 0, 0, 2);
+
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+
+    let mut eth = eth::Endpoint::new(MAC_ADDR_SRC);
+
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut neighbors[..]);
+        eth_cache.fill(IP_ADDR_DST.into(), MAC_ADDR_DST, None).unwrap();
+        eth_cache
+    };
+    let mut ip = [ip::Route::unspecified(); 2];
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_SRC.into(), 24),
+        ip::Routes::new(&mut ip[..]),
+        neighbors);
+    ip.set_trace(Some(ip::Trace::new(vec![ip::TraceRecord::default(); 2])));
+
+    let sent = nic.tx(1, eth.send(ip.send_with(|packet: RawPacket<_>| {
+        let init = ip::Init {
+            source: IpSubnet::from(Ipv4Subnet::ANY).into(),
+            dst_addr: IP_ADDR_DST.into(),
+            protocol: IpProtocol::Unknown(0xEF),
+            payload: 4,
+            hop_limit: None,
+            dscp: 0,
+        };
+        let mut packet = packet.prepare(init).unwrap();
+        packet.payload_mut_slice().copy_from_slice(b"ping");
+        packet.send().unwrap();
+    })));
+    assert_eq!(sent, Ok(1));
+
+    {
+        let buffer = nic.get_mut(0).unwrap();
+        let frame = ethernet_frame::new_unchecked_mut(buffer);
+        frame.set_dst_addr(MAC_ADDR_SRC);
+        frame.set_src_addr(MAC_ADDR_DST);
+        let packet = ipv4_packet::new_unchecked_mut(frame.payload_mut_slice());
+        packet.set_dst_addr(IP_ADDR_SRC);
+        packet.set_src_addr(IP_ADDR_DST);
+        packet.fill_checksum();
+    }
+
+    nic.receive_all();
+    assert_eq!(nic.rx(1, eth.recv(ip.recv_with(|_: InPacket<_>| ()))), Ok(1));
+    let record = *ip.trace().unwrap().records().last().unwrap();
+    assert_eq!(record.verdict, ip::Verdict::Delivered);
+    assert_eq!(record.len, 24);
+    assert_eq!(record.header, Some(ip::TraceHeader {
+        src_addr: IP_ADDR_DST.into(),
+        dst_addr: IP_ADDR_SRC.into(),
+        protocol: IpProtocol::Unknown(0xEF),
+    }));
+
+    // A packet for another host is dropped, without forwarding.
+    {
+        let buffer = nic.get_mut(0).unwrap();
+        let frame = ethernet_frame::new_unchecked_mut(buffer);
+        let packet = ipv4_packet::new_unchecked_mut(frame.payload_mut_slice());
+        packet.set_dst_addr(Ipv4Address::new(10, 0, 0, 3));
+        packet.fill_checksum();
+    }
+    nic.receive_all();
+    assert_eq!(nic.rx(1, eth.recv(ip.recv_with(|_: InPacket<_>| ()))), Ok(1));
+
+    // A broken checksum drops the packet before its header is known.
+    {
+        let buffer = nic.get_mut(0).unwrap();
+        let frame = ethernet_frame::new_unchecked_mut(buffer);
+        let packet = ipv4_packet::new_unchecked_mut(frame.payload_mut_slice());
+        packet.set_checksum(0);
+    }
+    nic.receive_all();
+    assert_eq!(nic.rx(1, eth.recv(ip.recv_with(|_: InPacket<_>| ()))), Ok(1));
+
+    let trace = ip.trace_mut().unwrap();
+    assert_eq!(trace.total(), 3);
+    let records: Vec<_> = trace.records().copied().collect();
+    assert_eq!(records.len(), 2);
+    match records[0].verdict {
+        ip::Verdict::Dropped(failure) => assert_eq!(failure.error, Error::Unreachable),
+        other => panic!("Unexpected verdict {:?}", other),
+    }
+    assert_eq!(records[1].header, None);
+    assert_eq!(records[1].verdict, ip::Verdict::Dropped(Failure::new(Error::Illegal,
+        Origin::Ip, Operation::Receive, Detail::Wire(crate::wire::Error::WrongChecksum))));
+
+    let mut dump = String::new();
+    trace.dump(&mut dump).unwrap();
+    assert_eq!(dump.lines().count(), 2);
+    trace.clear();
+    assert!(trace.is_empty());
+}
+
+#[test]
+fn extension_headers() {
+    const MAC_ADDR_HOST: EthernetAddress = EthernetAddress([0x52, 0x54, 0, 0, 0, 1]);
+    const MAC_ADDR_PEER: EthernetAddress = EthernetAddress([0x52, 0x54, 0, 0, 0, 2]);
+    let host = Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+    let peer = Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2);
+
+    let frame = |next_header: IpProtocol, payload: &[u8]| {
+        let mut buffer = vec![0; 14 + 40 + payload.len()];
+        let eth = ethernet_frame::new_unchecked_mut(&mut buffer[..]);
+        EthernetRepr {
+            src_addr: MAC_ADDR_PEER,
+            dst_addr: MAC_ADDR_HOST,
+            ethertype: EthernetProtocol::Ipv6,
+        }.emit(eth);
+        let ip = ipv6_packet::new_unchecked_mut(eth.payload_mut_slice());
+        Ipv6Repr {
+            src_addr: peer,
+            dst_addr: host,
+            next_header,
+            payload_len: payload.len(),
+            hop_limit: 64,
+        }.emit(ip);
+        ip.payload_mut_slice().copy_from_slice(payload);
+        buffer
+    };
+
+    fn receive(eth: &mut eth::Endpoint, ip: &mut ip::Endpoint, frame: Vec<u8>)
+        -> Option<(IpProtocol, Vec<u8>)>
+    {
+        let mut nic = External::new_recv(Slice::One(frame));
+        let mut delivered = None;
+        let recv = nic.rx(1, eth.recv(ip.recv_with(|packet: InPacket<_>| {
+            let protocol = packet.packet.repr().protocol();
+            delivered = Some((protocol, packet.packet.payload().as_slice().to_vec()));
+        })));
+        assert_eq!(recv, Ok(1));
+        delivered
+    }
+
+    let mut eth = eth::Endpoint::new(MAC_ADDR_HOST);
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let mut routes = [ip::Route::unspecified(); 1];
+    let mut ip = ip::Endpoint::new(IpCidr::new(host.into(), 64),
+        ip::Routes::new(&mut routes[..]),
+        arp::NeighborCache::new(&mut neighbors[..]));
+    assert_eq!(ip.extension_policy(), ip::ExtensionPolicy::PROCESS);
+    let wire_failure = |ip: &ip::Endpoint| ip.last_failure().unwrap().detail;
+
+    // A router alert and padding are skipped.
+    let hop_by_hop = [&[0x11, 0x00, 0x05, 0x02, 0x00, 0x00, 0x01, 0x00][..], b"datagram"].concat();
+    assert_eq!(receive(&mut eth, &mut ip, frame(IpProtocol::HopByHop, &hop_by_hop)),
+        Some((IpProtocol::Udp, b"datagram".to_vec())));
+
+    // An unknown option that must be understood drops the packet, unless it is skipped.
+    let destination = [&[0x11, 0x00, 0x41, 0x04, 0x00, 0x00, 0x00, 0x00][..], b"datagram"].concat();
+    assert_eq!(receive(&mut eth, &mut ip, frame(IpProtocol::Ipv6Opts, &destination)), None);
+    assert_eq!(wire_failure(&ip), Detail::Wire(crate::wire::Error::Unrecognized));
+    ip.set_extension_policy(ip::ExtensionPolicy {
+        destination: ip::ExtensionAction::Skip,
+        ..ip::ExtensionPolicy::PROCESS
+    });
+    assert_eq!(receive(&mut eth, &mut ip, frame(IpProtocol::Ipv6Opts, &destination)),
+        Some((IpProtocol::Udp, b"datagram".to_vec())));
+
+    // Fragments need reassembly buffers.
+    let first = [&[0x11, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x07][..], b"datagram"].concat();
+    let last = [&[0x11, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x07][..], b"!!"].concat();
+    assert_eq!(receive(&mut eth, &mut ip, frame(IpProtocol::Ipv6Frag, &first)), None);
+    assert_eq!(wire_failure(&ip), Detail::Wire(crate::wire::Error::Unsupported));
+
+    let buffers = vec![ip::Datagram::new(vec![0; 64])];
+    ip.set_reassembly(Some(ip::Reassembly::new(buffers)));
+    assert_eq!(receive(&mut eth, &mut ip, frame(IpProtocol::Ipv6Frag, &last)), None);
+    assert_eq!(ip.reassembly().unwrap().in_progress(), 1);
+    assert_eq!(receive(&mut eth, &mut ip, frame(IpProtocol::Ipv6Frag, &first)),
+        Some((IpProtocol::Udp, b"datagram!!".to_vec())));
+    assert_eq!(ip.reassembly().unwrap().in_progress(), 0);
+    assert_eq!(ip.reassembly().unwrap().reassembled(), 1);
+
+    // Packets with extension headers can be refused entirely.
+    ip.set_extension_policy(ip::ExtensionPolicy::DROP);
+    assert_eq!(receive(&mut eth, &mut ip, frame(IpProtocol::HopByHop, &hop_by_hop)), None);
+    assert_eq!(wire_failure(&ip), Detail::Wire(crate::wire::Error::Unsupported));
+}
+
+#[test]
+fn forward() {
+    const MAC_ADDR_ROUTER: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 5]);
+    const IP_ADDR_ROUTER: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
+    const MAC_ADDR_SRC: EthernetAddress = EthernetAddress([6, 5, 4, 3, 2, 1]);
+    const IP_ADDR_SRC: Ipv4Address = Ipv4Address::new(10, 0, 0, 2);
+    const MAC_ADDR_DST: EthernetAddress = EthernetAddress([6, 5, 4, 3, 2, 2]);
+    const IP_ADDR_DST: Ipv4Address = Ipv4Address::new(10, 0, 1, 2);
+
+    let mut nic = Loopback::<Vec<u8>>::new(vec![0; 1 << 12].into());
+
+    let mut neighbors = [arp::Neighbor::default(); 2];
+    let neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut neighbors[..]);
+        eth_cache.fill(IP_ADDR_DST.into(), MAC_ADDR_DST, None).unwrap();
+        eth_cache.fill(IP_ADDR_SRC.into(), MAC_ADDR_SRC, None).unwrap();
+        eth_cache
+    };
+    let mut routes = [ip::Route::unspecified(); 1];
+    let mut router_eth = eth::Endpoint::new(MAC_ADDR_ROUTER);
+    let mut router = ip::Endpoint::new(IpCidr::new(IP_ADDR_ROUTER.into(), 16),
+        ip::Routes::new(&mut routes[..]),
+        neighbors);
+
+    let mut src_neighbors = [arp::Neighbor::default(); 1];
+    let src_neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut src_neighbors[..]);
+        eth_cache.fill(IP_ADDR_ROUTER.into(), MAC_ADDR_ROUTER, None).unwrap();
+        eth_cache
+    };
+    let mut src_routes = [ip::Route::unspecified(); 1];
+    let mut src_routes = ip::Routes::new(&mut src_routes[..]);
+    src_routes.add_route(ip::Route::new_ipv4_gateway(IP_ADDR_ROUTER)).unwrap();
+    let mut src_eth = eth::Endpoint::new(MAC_ADDR_SRC);
+    let mut src = ip::Endpoint::new(IpCidr::new(IP_ADDR_SRC.into(), 24),
+        src_routes,
+        src_neighbors);
+
+    let mut dst_eth = eth::Endpoint::new(MAC_ADDR_DST);
+    let mut send_to_dst = SimpleSend {
+        dst_addr: IP_ADDR_DST.into(),
+    };
+
+    // Dropped without forwarding.
+    assert_eq!(nic.tx(1, src_eth.send(src.send(&mut send_to_dst))), Ok(1));
+    assert_eq!(nic.rx(1, router_eth.recv(router.recv_with(|_: InPacket<_>| {
+        panic!("Not a local destination");
+    }))), Ok(1));
+    assert_eq!(nic.rx(1, dst_eth.recv_with(|_: eth::InPacket<_>| ())), Ok(0));
+
+    router.set_forwarding(true);
+    assert_eq!(nic.tx(1, src_eth.send(src.send(&mut send_to_dst))), Ok(1));
+    assert_eq!(nic.rx(1, router_eth.recv(router.recv_with(|_: InPacket<_>| {
+        panic!("Not a local destination");
+    }))), Ok(1));
+
+    let mut forwarded = false;
+    assert_eq!(nic.rx(1, dst_eth.recv_with(|packet: eth::InPacket<_>| {
+        let repr = packet.frame.repr();
+        assert_eq!(repr.src_addr, MAC_ADDR_ROUTER);
+        assert_eq!(repr.dst_addr, MAC_ADDR_DST);
+
+        let ip = ipv4_packet::new_checked(packet.frame.payload().as_slice()).unwrap();
+        assert!(ip.verify_checksum());
+        assert_eq!(ip.src_addr(), IP_ADDR_SRC);
+        assert_eq!(ip.dst_addr(), IP_ADDR_DST);
+        assert_eq!(ip.hop_limit(), ip::Endpoint::DEFAULT_HOP_LIMIT - 1);
+        assert_eq!(ip.payload_slice(), &PAYLOAD_BYTES[..]);
+        forwarded = true;
+    })), Ok(1));
+    assert!(forwarded);
+
+    // An exhausted hop limit is silently dropped by default.
+    src.set_hop_limit(1);
+    assert_eq!(nic.tx(1, src_eth.send(src.send(&mut send_to_dst))), Ok(1));
+    assert_eq!(nic.rx(1, router_eth.recv(router.recv_with(|_: InPacket<_>| {
+        panic!("Not a local destination");
+    }))), Ok(1));
+    assert_eq!(router.last_failure().map(|failure| failure.detail), Some(Detail::HopLimit));
+    assert_eq!(nic.rx(1, dst_eth.recv_with(|_: eth::InPacket<_>| ())), Ok(0));
+
+    router.set_icmp_policy(ip::IcmpPolicy::ALL);
+    assert_eq!(nic.tx(1, src_eth.send(src.send(&mut send_to_dst))), Ok(1));
+    assert_eq!(nic.rx(1, router_eth.recv(router.recv_with(|_: InPacket<_>| {
+        panic!("Not a local destination");
+    }))), Ok(1));
+
+    let mut answered = false;
+    assert_eq!(nic.rx(1, src_eth.recv(src.recv_with(|packet: InPacket<_>| {
+        let repr = packet.packet.repr();
+        assert_eq!(repr.src_addr(), IP_ADDR_ROUTER.into());
+        assert_eq!(repr.protocol(), IpProtocol::Icmp);
+
+        let icmp = icmpv4_packet::new_checked(packet.packet.payload().as_slice()).unwrap();
+        match Icmpv4Repr::parse(icmp, Checksum::Manual).unwrap() {
+            Icmpv4Repr::TimeExceeded { reason, header } => {
+                assert_eq!(reason, Icmpv4TimeExceeded::TtlExpired);
+                assert_eq!(header.src_addr, IP_ADDR_SRC);
+                assert_eq!(header.dst_addr, IP_ADDR_DST);
+            },
+            other => panic!("Unexpected icmp message {:?}", other),
+        }
+        answered = true;
+    }))), Ok(1));
+    assert!(answered);
+}
+
+#[test]
+fn protocol_unreachable() {
+    const MAC_ADDR_HOST: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 5]);
+    const IP_ADDR_HOST: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
+    const MAC_ADDR_OTHER: EthernetAddress = EthernetAddress([6, 5, 4, 3, 2, 1]);
+    const IP_ADDR_OTHER: Ipv4Address = Ipv4Address::new(10, 0, 0, 2);
+
+    /// A receiver only handling tcp.
+    struct OnlyTcp;
+
+    impl<P: Payload> ip::Recv<P> for OnlyTcp {
+        fn receive(&mut self, _: InPacket<P>) {
+            panic!("Received a packet of an unhandled protocol");
+        }
+
+        fn accepts_protocol(&self, protocol: IpProtocol) -> bool {
+            protocol == IpProtocol::Tcp
+        }
+    }
+
+    let mut nic = Loopback::<Vec<u8>>::new(vec![0; 1 << 12].into());
+
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut neighbors[..]);
+        eth_cache.fill(IP_ADDR_OTHER.into(), MAC_ADDR_OTHER, None).unwrap();
+        eth_cache
+    };
+    let mut routes = [ip::Route::unspecified(); 1];
+    let mut host_eth = eth::Endpoint::new(MAC_ADDR_HOST);
+    let mut host = ip::Endpoint::new(IpCidr::new(IP_ADDR_HOST.into(), 24),
+        ip::Routes::new(&mut routes[..]),
+        neighbors);
+
+    let mut other_neighbors = [arp::Neighbor::default(); 1];
+    let other_neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut other_neighbors[..]);
+        eth_cache.fill(IP_ADDR_HOST.into(), MAC_ADDR_HOST, None).unwrap();
+        eth_cache
+    };
+    let mut other_routes = [ip::Route::unspecified(); 1];
+    let mut other_eth = eth::Endpoint::new(MAC_ADDR_OTHER);
+    let mut other = ip::Endpoint::new(IpCidr::new(IP_ADDR_OTHER.into(), 24),
+        ip::Routes::new(&mut other_routes[..]),
+        other_neighbors);
+
+    let mut send_to_host = SimpleSend {
+        dst_addr: IP_ADDR_HOST.into(),
+    };
+
+    // Silently dropped by default.
+    assert_eq!(nic.tx(1, other_eth.send(other.send(&mut send_to_host))), Ok(1));
+    assert_eq!(nic.rx(1, host_eth.recv(host.recv(OnlyTcp))), Ok(1));
+    assert_eq!(nic.rx(1, other_eth.recv(other.recv_with(|_: InPacket<_>| ()))), Ok(0));
+
+    host.set_icmp_policy(ip::IcmpPolicy::UNREACHABLE);
+    assert_eq!(nic.tx(1, other_eth.send(other.send(&mut send_to_host))), Ok(1));
+    assert_eq!(nic.rx(1, host_eth.recv(host.recv(OnlyTcp))), Ok(1));
+
+    let mut answered = false;
+    assert_eq!(nic.rx(1, other_eth.recv(other.recv_with(|packet: InPacket<_>| {
+        let repr = packet.packet.repr();
+        assert_eq!(repr.src_addr(), IP_ADDR_HOST.into());
+        assert_eq!(repr.protocol(), IpProtocol::Icmp);
+
+        let icmp = icmpv4_packet::new_checked(packet.packet.payload().as_slice()).unwrap();
+        match Icmpv4Repr::parse(icmp, Checksum::Manual).unwrap() {
+            Icmpv4Repr::DstUnreachable { reason, header } => {
+                assert_eq!(reason, Icmpv4DstUnreachable::ProtoUnreachable);
+                assert_eq!(header.src_addr, IP_ADDR_OTHER);
+                assert_eq!(header.protocol, IpProtocol::Unknown(0xEF));
+            },
+            other => panic!("Unexpected icmp message {:?}", other),
+        }
+        // The first eight bytes of the payload are quoted.
+        assert!(icmp.payload_slice().ends_with(&PAYLOAD_BYTES[..8]));
+        answered = true;
+    }))), Ok(1));
+    assert!(answered);
+}
+
+#[test]
+fn dispatch_table() {
+    const MAC_ADDR_HOST: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 5]);
+    const IP_ADDR_HOST: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
+
+    // Packets are sent to the host itself.
+    let mut nic = Loopback::<Vec<u8>>::new(vec![0; 1 << 12].into());
+
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut neighbors[..]);
+        eth_cache.fill(IP_ADDR_HOST.into(), MAC_ADDR_HOST, None).unwrap();
+        eth_cache
+    };
+    let mut routes = [ip::Route::unspecified(); 1];
+    let mut eth = eth::Endpoint::new(MAC_ADDR_HOST);
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_HOST.into(), 24),
+        ip::Routes::new(&mut routes[..]),
+        neighbors);
+
+    let mut send_to_host = SimpleSend {
+        dst_addr: IP_ADDR_HOST.into(),
+    };
+
+    let (mut tcp, mut other, mut raw) = (0, 0, 0);
+    for with_raw in [false, true] {
+        let mut tcp_handler = FnHandler(|_: InPacket<_>| tcp += 1);
+        let mut other_handler = FnHandler(|frame: InPacket<_>| {
+            simple_recv(frame);
+            other += 1;
+        });
+        let mut raw_handler = FnHandler(|_: InPacket<_>| raw += 1);
+
+        let mut table = ip::DispatchTable::new();
+        table.register(IpProtocol::Tcp, &mut tcp_handler).unwrap();
+        if with_raw {
+            table.set_raw(&mut raw_handler);
+        } else {
+            table.register(IpProtocol::Unknown(0xEF), &mut other_handler).unwrap();
+            assert_eq!(table.register(IpProtocol::Tcp, &mut raw_handler), Err(Error::Illegal));
+        }
+
+        assert_eq!(nic.tx(1, eth.send(ip.send(&mut send_to_host))), Ok(1));
+        assert_eq!(nic.rx(1, eth.recv(ip.recv(&mut table))), Ok(1));
+    }
+
+    assert_eq!((tcp, other, raw), (0, 1, 1));
+}
+
+#[test]
+fn secondary_addresses() {
+    const MAC_ADDR_SRC: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 5]);
+    const MAC_ADDR_ROUTER: EthernetAddress = EthernetAddress([6, 5, 4, 3, 2, 1]);
+    let link_local = Ipv6Address::from_link_local_id(InterfaceId::from_generated_ether(MAC_ADDR_SRC));
+    let router = Ipv6Address::from_link_local_id(InterfaceId::from_generated_ether(MAC_ADDR_ROUTER));
+    let global = Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+    let remote = Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1);
+
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+    let mut eth = eth::Endpoint::new(MAC_ADDR_SRC);
+
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let mut neighbors = arp::NeighborCache::new(&mut neighbors[..]);
+    neighbors.fill(router.into(), MAC_ADDR_ROUTER, None).unwrap();
+    let mut routes = [ip::Route::unspecified(); 1];
+    let mut routes = ip::Routes::new(&mut routes[..]);
+    routes.add_route(ip::Route::new_ipv6_gateway(router)).unwrap();
+    let mut addresses = [ip::Assignment::unspecified(); 2];
+    addresses[0] = IpCidr::new(link_local.into(), 64).into();
+    let mut ip = ip::Endpoint::new(&mut addresses[..], routes, neighbors);
+    assert_eq!(ip.addresses().len(), 1);
+
+    // The global address is preferred for ten seconds and valid for twenty.
+    let global = ip::Assignment::with_lifetimes(
+        IpCidr::new(global.into(), 64),
+        Expiration::When(Instant::from_secs(10)),
+        Expiration::When(Instant::from_secs(20)));
+    ip.add_address(global).unwrap();
+    assert!(ip.accepts(global.address()));
+    assert_eq!(ip.add_address(IpCidr::new(remote.into(), 64).into()), Err(Error::Exhausted));
+    assert_eq!(ip.poll(Instant::from_secs(0)), Expiration::When(Instant::from_secs(10)));
+    assert_eq!(ip.poll(Instant::from_secs(15)), Expiration::When(Instant::from_secs(20)));
+
+    fn sent_from(nic: &mut External<Slice<'_, Vec<u8>>>) -> IpAddress {
+        let buffer = nic.get_mut(0).unwrap();
+        let eth = ethernet_frame::new_unchecked_mut(buffer);
+        ipv6_packet::new_unchecked_mut(eth.payload_mut_slice()).src_addr().into()
+    }
+
+    // Even though the router is link-local, a global destination uses the global address.
+    nic.set_current_time(Instant::from_secs(15));
+    let sent = nic.tx(1, eth.send(ip.send(SimpleSend {
+        dst_addr: remote.into(),
+    })));
+    assert_eq!(sent, Ok(1));
+    assert_eq!(sent_from(&mut nic), global.address());
+
+    // Once it is no longer valid the address is removed and the link-local address used.
+    assert_eq!(ip.poll(Instant::from_secs(21)), Expiration::Never);
+    assert!(!ip.accepts(global.address()));
+    nic.reset_send();
+    nic.set_current_time(Instant::from_secs(21));
+    let sent = nic.tx(1, eth.send(ip.send(SimpleSend {
+        dst_addr: remote.into(),
+    })));
+    assert_eq!(sent, Ok(1));
+    assert_eq!(sent_from(&mut nic), IpAddress::from(link_local));
+
+    assert_eq!(ip.remove_address(link_local.into()).map(|addr| addr.address()),
+               Some(link_local.into()));
+    assert!(ip.addresses().is_empty());
+}
+
+#[test]
+fn reconfiguration() {
+    use core::cell::Cell;
+
+    #[derive(Default)]
+    struct Counts {
+        added: Cell<usize>,
+        removed: Cell<usize>,
+        routes: Cell<usize>,
+    }
+
+    impl ip::ChangeEvents for &'_ Counts {
+        fn changed(&mut self, change: ip::Change) {
+            let counter = match change {
+                ip::Change::AddressAdded(_) => &self.added,
+                ip::Change::AddressRemoved(_) => &self.removed,
+                ip::Change::RouteAdded(_) | ip::Change::RouteRemoved(_) => &self.routes,
+                _ => unreachable!("No tentative addresses"),
+            };
+            counter.set(counter.get() + 1);
+        }
+    }
+
+    let counts = Counts::default();
+    let mut events = &counts;
+
+    const MAC_ADDR_OTHER: EthernetAddress = EthernetAddress([6, 5, 4, 3, 2, 1]);
+    let primary = Ipv4Address::new(10, 0, 0, 1);
+    let learned = Ipv4Address::new(10, 0, 0, 2);
+    let configured = Ipv4Address::new(10, 0, 0, 3);
+    let gateway = Ipv4Address::new(10, 1, 0, 254);
+
+    let mut neighbors = arp::NeighborCache::new(vec![arp::Neighbor::default(); 2]);
+    neighbors.fill(learned.into(), MAC_ADDR_OTHER, Some(Instant::from_secs(0))).unwrap();
+    neighbors.fill_static(configured.into(), MAC_ADDR_OTHER).unwrap();
+    let mut ip = ip::Endpoint::new(
+        vec![IpCidr::new(primary.into(), 24).into(), ip::Assignment::unspecified()],
+        ip::Routes::new(vec![ip::Route::unspecified(); 1]),
+        neighbors);
+    ip.set_events(&mut events);
+
+    // Refreshing the lifetimes of an assigned address is no change.
+    let secondary = ip::Assignment::with_lifetimes(
+        IpCidr::new(Ipv4Address::new(10, 1, 0, 1).into(), 24),
+        Expiration::When(Instant::from_secs(10)),
+        Expiration::When(Instant::from_secs(10)));
+    ip.add_address(secondary).unwrap();
+    ip.add_address(secondary).unwrap();
+    assert_eq!(counts.added.get(), 1);
+
+    let route = ip::Route::new_ipv4_gateway(gateway);
+    ip.add_route(route).unwrap();
+    assert_eq!(ip.routes().len(), 1);
+    assert_eq!(ip.add_route(route).err(), Some(Error::Exhausted));
+    assert!(ip.remove_route(route.net, gateway.into()).is_some());
+    assert!(ip.routes().is_empty());
+    assert_eq!(counts.routes.get(), 2);
+
+    // Neighbors of the removed subnet are flushed, unless configured statically.
+    assert!(ip.remove_address(primary.into()).is_some());
+    assert_eq!(counts.removed.get(), 1);
+    assert_eq!(ip.neighbors().lookup_pure(learned.into(), Instant::from_secs(0)), None);
+    assert_eq!(ip.neighbors().lookup_pure(configured.into(), Instant::from_secs(0)),
+               Some(MAC_ADDR_OTHER));
+
+    // Expiry is reported as well.
+    assert_eq!(ip.poll(Instant::from_secs(11)), Expiration::Never);
+    assert!(ip.addresses().is_empty());
+    assert_eq!(counts.removed.get(), 2);
+}
+
+#[test]
+fn duplicate_detection() {
+    use core::cell::Cell;
+
+    #[derive(Default)]
+    struct Counts {
+        confirmed: Cell<usize>,
+        duplicate: Cell<usize>,
+    }
+
+    impl ip::ChangeEvents for &'_ Counts {
+        fn changed(&mut self, change: ip::Change) {
+            let counter = match change {
+                ip::Change::AddressConfirmed(_) => &self.confirmed,
+                ip::Change::AddressDuplicate(_) => &self.duplicate,
+                _ => return,
+            };
+            counter.set(counter.get() + 1);
+        }
+    }
+
+    let counts = Counts::default();
+    let mut events = &counts;
+
+    const MAC_ADDR_HOST: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 5]);
+    const MAC_ADDR_OTHER: EthernetAddress = EthernetAddress([6, 5, 4, 3, 2, 1]);
+    let unique = Ipv4Address::new(10, 0, 0, 1);
+    let taken = Ipv4Address::new(10, 0, 0, 2);
+    let link_local = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0x0102, 0x0304);
+
+    let mut eth = eth::Endpoint::new(MAC_ADDR_HOST);
+    let mut ip = ip::Endpoint::new(
+        vec![ip::Assignment::unspecified(); 3],
+        ip::Routes::new(vec![ip::Route::unspecified(); 1]),
+        arp::NeighborCache::new(vec![arp::Neighbor::default(); 1]));
+    ip.set_events(&mut events);
+    ip.add_address(ip::Assignment::new(IpCidr::new(unique.into(), 24)).tentative()).unwrap();
+
+    let now = Instant::from_secs(0);
+    assert_eq!(ip.address_state(unique.into(), now), Some(ip::AddressState::Tentative));
+    assert!(!ip.accepts(unique.into()));
+    assert_eq!(ip.poll(now), Expiration::When(now));
+
+    // Probes do not reveal the address as the sender.
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+    for probe in 0..ip::Assignment::IPV4_PROBES {
+        let now = Instant::from_secs(probe);
+        nic.reset_send();
+        nic.set_current_time(now);
+        assert_eq!(nic.tx(1, eth.send(ip.send(SimpleSend {
+            dst_addr: IpAddress::v4(10, 0, 0, 9),
+        }))), Ok(1));
+        let frame = ethernet_frame::new_unchecked_mut(nic.get_mut(0).unwrap());
+        assert_eq!(frame.ethertype(), EthernetProtocol::Arp);
+        let arp = arp_packet::new_unchecked_mut(frame.payload_mut_slice());
+        assert_eq!(ArpRepr::parse(arp).unwrap(), ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr: MAC_ADDR_HOST,
+            source_protocol_addr: Ipv4Address::UNSPECIFIED,
+            target_hardware_addr: EthernetAddress([0; 6]),
+            target_protocol_addr: unique,
+        });
+        assert_eq!(ip.poll(now), Expiration::When(now + ip::Assignment::PROBE_INTERVAL));
+    }
+
+    // A tentative address is never used as a source, not even when chosen explicitly.
+    nic.reset_send();
+    let sent = nic.tx(1, eth.send(ip.send_with(|packet: RawPacket<_>| {
+        let init = ip::Init {
+            source: ip::Source::Exact(unique.into()),
+            dst_addr: IpAddress::v4(10, 0, 0, 9),
+            protocol: IpProtocol::Unknown(0xEF),
+            payload: 0,
+            hop_limit: None,
+            dscp: 0,
+        };
+        assert_eq!(packet.prepare(init).err(), Some(Error::Unreachable));
+    })));
+    assert_eq!(sent, Ok(0));
+
+    // Neither is an address that is not assigned at all.
+    let foreign = IpAddress::v4(10, 0, 0, 77);
+    let sent = nic.tx(1, eth.send(ip.send_with(|packet: RawPacket<_>| {
+        let init = ip::Init {
+            source: ip::Source::Exact(foreign),
+            dst_addr: IpAddress::v4(10, 0, 0, 9),
+            protocol: IpProtocol::Unknown(0xEF),
+            payload: 0,
+            hop_limit: None,
+            dscp: 0,
+        };
+        assert_eq!(packet.prepare(init).err(), Some(Error::Illegal));
+    })));
+    assert_eq!(sent, Ok(0));
+    assert_eq!(ip.last_failure().unwrap().detail, Detail::ForeignSource(foreign));
+
+    // Confirmed one interval after the last probe.
+    let now = Instant::from_secs(3);
+    assert_eq!(ip.poll(now), Expiration::Never);
+    assert_eq!(ip.address_state(unique.into(), now), Some(ip::AddressState::Preferred));
+    assert!(ip.accepts(unique.into()));
+    assert_eq!(counts.confirmed.get(), 1);
+
+    // Another host answering from a tentative address takes it.
+    ip.add_address(ip::Assignment::new(IpCidr::new(taken.into(), 24)).tentative()).unwrap();
+    let mut buffer = vec![0; 14 + 28];
+    let frame = ethernet_frame::new_unchecked_mut(&mut buffer[..]);
+    EthernetRepr {
+        src_addr: MAC_ADDR_OTHER,
+        dst_addr: MAC_ADDR_HOST,
+        ethertype: EthernetProtocol::Arp,
+    }.emit(frame);
+    ArpRepr::EthernetIpv4 {
+        operation: ArpOperation::Reply,
+        source_hardware_addr: MAC_ADDR_OTHER,
+        source_protocol_addr: taken,
+        target_hardware_addr: MAC_ADDR_HOST,
+        target_protocol_addr: Ipv4Address::UNSPECIFIED,
+    }.emit(arp_packet::new_unchecked_mut(frame.payload_mut_slice()));
+    let mut nic = External::new_recv(Slice::One(buffer));
+    nic.set_current_time(now);
+    assert_eq!(nic.rx(1, eth.recv(ip.recv_with(simple_recv))), Ok(1));
+    assert_eq!(ip.address_state(taken.into(), now), None);
+    assert_eq!(counts.duplicate.get(), 1);
+
+    // The same for IPv6, probed with a neighbor solicitation to the solicited node group.
+    ip.add_address(ip::Assignment::new(IpCidr::new(link_local.into(), 64)).tentative()).unwrap();
+    let solicited = link_local.solicited_node_multicast();
+    let mut eth = eth::Endpoint::with_multicast(MAC_ADDR_HOST, Slice::One(Default::default()));
+    eth.join_multicast(EthernetAddress::from_ipv6_multicast(solicited)).unwrap();
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+    nic.set_current_time(now);
+    assert_eq!(nic.tx(1, eth.send(ip.layer_internal())), Ok(1));
+    {
+        let frame = ethernet_frame::new_unchecked_mut(nic.get_mut(0).unwrap());
+        assert_eq!(frame.dst_addr(), EthernetAddress::from_ipv6_multicast(solicited));
+        let packet = ipv6_packet::new_unchecked_mut(frame.payload_mut_slice());
+        assert_eq!(packet.src_addr(), Ipv6Address::UNSPECIFIED);
+        assert_eq!(packet.dst_addr(), solicited);
+        assert_eq!(packet.hop_limit(), 255);
+        let solicit = ndisc_packet::new_checked(packet.payload_slice()).unwrap();
+        assert_eq!(solicit.msg_type(), NdiscMessage::NeighborSolicit);
+        assert_eq!(solicit.target_addr(), link_local);
+        assert!(solicit.verify_checksum(Ipv6Address::UNSPECIFIED, solicited));
+    }
+
+    // Our own probe looped back is no conflict.
+    let own = nic.get(0).unwrap().clone();
+    let mut nic = External::new_recv(Slice::One(own));
+    nic.set_current_time(now);
+    assert_eq!(nic.rx(1, eth.recv(ip.layer_internal())), Ok(1));
+    assert_eq!(ip.address_state(link_local.into(), now), Some(ip::AddressState::Tentative));
+
+    // But the probe of another host is.
+    let frame = ethernet_frame::new_unchecked_mut(nic.get_mut(0).unwrap());
+    frame.set_src_addr(MAC_ADDR_OTHER);
+    nic.receive_all();
+    assert_eq!(nic.rx(1, eth.recv(ip.layer_internal())), Ok(1));
+    assert_eq!(ip.address_state(link_local.into(), now), None);
+    assert_eq!(counts.duplicate.get(), 2);
+    assert_eq!(counts.confirmed.get(), 1);
+}
+
+#[test]
+fn router_advertisement() {
+    const MAC_ADDR_HOST: EthernetAddress = EthernetAddress([0x52, 0x54, 0, 0, 0, 1]);
+    const MAC_ADDR_ROUTER: EthernetAddress = EthernetAddress([0x52, 0x54, 0, 0, 0, 2]);
+    const ROUTER: Ipv6Address = Ipv6Address([0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    let prefix = Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 0);
+    let remote = Ipv6Address::new(0x2001, 0xdb8, 2, 0, 0, 0, 0, 1);
+
+    fn advertise(router_lifetime: u64, valid: u32, preferred: u32) -> Vec<u8> {
+        let all_nodes = Ipv6Address::all_nodes_multicast(Ipv6Scope::LinkLocal);
+        let mut buffer = vec![0; 110];
+        let eth = ethernet_frame::new_unchecked_mut(&mut buffer[..]);
+        EthernetRepr {
+            src_addr: MAC_ADDR_ROUTER,
+            dst_addr: EthernetAddress::IPV6_ALL_NODES,
+            ethertype: EthernetProtocol::Ipv6,
+        }.emit(eth);
+        let ip = ipv6_packet::new_unchecked_mut(eth.payload_mut_slice());
+        Ipv6Repr {
+            src_addr: ROUTER,
+            dst_addr: all_nodes,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: 56,
+            hop_limit: 255,
+        }.emit(ip);
+        let advert = ndisc_packet::new_unchecked_mut(ip.payload_mut_slice());
+        advert.set_msg_type(NdiscMessage::RouterAdvert);
+        advert.set_router_lifetime(Duration::from_secs(router_lifetime));
+        let options = [
+            NdiscOption::SourceLinkLayerAddr(MAC_ADDR_ROUTER),
+            NdiscOption::PrefixInformation(NdiscPrefixInformation {
+                prefix_len: 64,
+                on_link: true,
+                autonomous: true,
+                valid_lifetime: valid,
+                preferred_lifetime: preferred,
+                prefix: Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 0),
+            }),
+        ];
+        options[0].emit(advert.options_mut_slice());
+        options[1].emit(&mut advert.options_mut_slice()[8..]);
+        advert.fill_checksum(ROUTER, all_nodes);
+        buffer
+    }
+
+    fn neighbor_advert(target: Ipv6Address) -> Vec<u8> {
+        const MAC_ADDR_OTHER: EthernetAddress = EthernetAddress([0x52, 0x54, 0, 0, 0, 3]);
+        let other = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 3);
+        let all_nodes = Ipv6Address::all_nodes_multicast(Ipv6Scope::LinkLocal);
+        let mut buffer = vec![0; 14 + 40 + 24];
+        let eth = ethernet_frame::new_unchecked_mut(&mut buffer[..]);
+        EthernetRepr {
+            src_addr: MAC_ADDR_OTHER,
+            dst_addr: EthernetAddress::IPV6_ALL_NODES,
+            ethertype: EthernetProtocol::Ipv6,
+        }.emit(eth);
+        let ip = ipv6_packet::new_unchecked_mut(eth.payload_mut_slice());
+        Ipv6Repr {
+            src_addr: other,
+            dst_addr: all_nodes,
+            next_header: IpProtocol::Icmpv6,
+            payload_len: 24,
+            hop_limit: 255,
+        }.emit(ip);
+        let advert = ndisc_packet::new_unchecked_mut(ip.payload_mut_slice());
+        advert.set_msg_type(NdiscMessage::NeighborAdvert);
+        advert.set_target_addr(target);
+        advert.fill_checksum(other, all_nodes);
+        buffer
+    }
+
+    fn unexpected<P: Payload>(_: InPacket<P>) {
+        panic!("Not an upper layer packet");
+    }
+
+    let slaac = ip::Slaac::stable_privacy([0x42; 16]);
+    let global = slaac.address(prefix, MAC_ADDR_HOST);
+    let link_local = slaac.address(Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), MAC_ADDR_HOST);
+    assert!(global.is_unicast() && !global.is_link_local());
+    assert!(link_local.is_link_local());
+    // The interface id is stable within a prefix and depends on the key.
+    assert_eq!(slaac.address(prefix, MAC_ADDR_HOST), global);
+    assert_ne!(ip::Slaac::stable_privacy([0x43; 16]).address(prefix, MAC_ADDR_HOST), global);
+    // Unlike an EUI-64 it is unrelated between prefixes.
+    assert_ne!(&global.as_bytes()[8..], &link_local.as_bytes()[8..]);
+
+    let mut eth = eth::Endpoint::new(MAC_ADDR_HOST);
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let mut routes = [ip::Route::unspecified(); 1];
+    let mut addresses = [ip::Assignment::unspecified(); 2];
+    let mut ip = ip::Endpoint::new(
+        &mut addresses[..],
+        ip::Routes::new(&mut routes[..]),
+        arp::NeighborCache::new(&mut neighbors[..]));
+
+    // Ignored while autoconfiguration is disabled.
+    let mut nic = External::new_recv(Slice::One(advertise(1800, 7200, 3600)));
+    assert_eq!(nic.rx(1, eth.recv(ip.recv_with(unexpected))), Ok(1));
+    assert!(ip.addresses().is_empty());
+
+    ip.set_slaac(Some(slaac));
+    nic.receive_all();
+    nic.set_current_time(Instant::from_secs(10));
+    assert_eq!(nic.rx(1, eth.recv(ip.recv_with(unexpected))), Ok(1));
+    let addresses: Vec<_> = ip.addresses().iter().map(ip::Assignment::address).collect();
+    assert_eq!(addresses, vec![IpAddress::from(link_local), global.into()]);
+    assert_eq!(ip.addresses()[1].preferred_until, Expiration::When(Instant::from_secs(3610)));
+    assert_eq!(ip.addresses()[1].valid_until, Expiration::When(Instant::from_secs(7210)));
+    assert_eq!(ip.neighbors().lookup_pure(ROUTER.into(), Instant::from_secs(10)), Some(MAC_ADDR_ROUTER));
+
+    // New addresses are probed for duplicates first.
+    let now = Instant::from_secs(10);
+    assert_eq!(ip.address_state(global.into(), now), Some(ip::AddressState::Tentative));
+    assert_eq!(ip.address_state(link_local.into(), now), Some(ip::AddressState::Tentative));
+
+    // Another host defending the global address takes it.
+    let mut nic = External::new_recv(Slice::One(neighbor_advert(global)));
+    nic.set_current_time(now);
+    assert_eq!(nic.rx(1, eth.recv(ip.recv_with(unexpected))), Ok(1));
+    assert_eq!(ip.address_state(global.into(), now), None);
+    assert_eq!(ip.address_state(link_local.into(), now), Some(ip::AddressState::Tentative));
+
+    // The next advertisement forms it again, this time without a conflict.
+    let mut nic = External::new_recv(Slice::One(advertise(1800, 7200, 3600)));
+    nic.set_current_time(now);
+    assert_eq!(nic.rx(1, eth.recv(ip.recv_with(unexpected))), Ok(1));
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+    nic.set_current_time(now);
+    for _ in 0..2 {
+        nic.reset_send();
+        assert_eq!(nic.tx(1, eth.send(ip.layer_internal())), Ok(1));
+    }
+    // Confirmed, the next change is the end of the router lifetime.
+    let now = now + ip::Assignment::PROBE_INTERVAL;
+    assert_eq!(ip.poll(now), Expiration::When(Instant::from_secs(1810)));
+    assert_eq!(ip.address_state(global.into(), now), Some(ip::AddressState::Preferred));
+    assert_eq!(ip.address_state(link_local.into(), now), Some(ip::AddressState::Preferred));
+
+    // The router is used for remote destinations.
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+    nic.set_current_time(now);
+    let sent = nic.tx(1, eth.send(ip.send(SimpleSend {
+        dst_addr: remote.into(),
+    })));
+    assert_eq!(sent, Ok(1));
+    {
+        let buffer = nic.get_mut(0).unwrap();
+        let eth = ethernet_frame::new_unchecked_mut(buffer);
+        assert_eq!(eth.dst_addr(), MAC_ADDR_ROUTER);
+        let ip = ipv6_packet::new_unchecked_mut(eth.payload_mut_slice());
+        assert_eq!(ip.src_addr(), global);
+    }
+
+    // A short valid lifetime does not shorten the remaining lifetime below two hours.
+    let mut nic = External::new_recv(Slice::One(advertise(0, 60, 0)));
+    nic.set_current_time(Instant::from_secs(20));
+    assert_eq!(nic.rx(1, eth.recv(ip.recv_with(unexpected))), Ok(1));
+    assert_eq!(ip.addresses()[1].preferred_until, Expiration::When(Instant::from_secs(20)));
+    assert_eq!(ip.addresses()[1].valid_until, Expiration::When(Instant::from_secs(7210)));
+    // A router lifetime of zero removes the default route.
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+    nic.set_current_time(Instant::from_secs(20));
+    let sent = nic.tx(1, eth.send(ip.send_with(|packet: RawPacket<_>| {
+        let init = ip::Init {
+            source: IpSubnet::from(Ipv6Subnet::ANY).into(),
+            dst_addr: remote.into(),
+            protocol: IpProtocol::Unknown(0xEF),
+            payload: 0,
+            hop_limit: None,
+            dscp: 0,
+        };
+        assert_eq!(packet.prepare(init).err(), Some(Error::Unreachable));
+    })));
+    assert_eq!(sent, Ok(0));
+    assert_eq!(ip.last_failure(), Some(Failure::new(
+        Error::Unreachable, Origin::Ip, Operation::Route, Detail::NoRoute(remote.into()))));
+}
+
+#[test]
+fn mtu() {
+    const MAC_ADDR_SRC: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 5]);
+    const IP_ADDR_SRC: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
+    const MAC_ADDR_DST: EthernetAddress = EthernetAddress([6, 5, 4, 3, 2, 1]);
+    const IP_ADDR_DST: Ipv4Address = Ipv4Address::new(10, 0, 0, 2);
+
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+    let mut eth = eth::Endpoint::new(MAC_ADDR_SRC);
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut neighbors[..]);
+        eth_cache.fill(IP_ADDR_DST.into(), MAC_ADDR_DST, None).unwrap();
+        eth_cache
+    };
+    let mut ip = [ip::Route::unspecified(); 2];
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_SRC.into(), 24),
+        ip::Routes::new(&mut ip[..]),
+        neighbors);
+
+    // The header and payload exceed the transmission unit by ten bytes.
+    nic.set_mtu(Some(60));
+    let sent = nic.tx(1, eth.send(ip.send_with(|packet: RawPacket<_>| {
+        assert_eq!(packet.handle.max_payload(IP_ADDR_DST.into()), Some(40));
+        let init = ip::Init {
+            source: IpSubnet::from(Ipv4Subnet::ANY).into(),
+            dst_addr: IP_ADDR_DST.into(),
+            protocol: IpProtocol::Unknown(0xEF),
+            payload: PAYLOAD_BYTES.len(),
+            hop_limit: None,
+            dscp: 0,
+        };
+        assert_eq!(packet.prepare(init).err(), Some(Error::BadSize));
+    })));
+    assert_eq!(sent, Ok(0));
+    assert_eq!(ip.last_failure(), Some(Failure::new(
+        Error::BadSize, Origin::Ip, Operation::Send, Detail::Oversized { max: 40 })));
+
+    nic.set_mtu(Some(70));
+    let sent = nic.tx(1, eth.send(ip.send(SimpleSend {
+        dst_addr: IP_ADDR_DST.into(),
+    })));
+    assert_eq!(sent, Ok(1));
+}
+
 fn simple_recv<P: Payload>(frame: InPacket<P>) {
     assert_eq!(frame.packet.payload().as_slice(), &PAYLOAD_BYTES[..]);
 }
@@ -129,6 +1360,8 @@ impl<P: PayloadMut> ip::Send<P> for SimpleSend {
             dst_addr: self.dst_addr,
             payload: PAYLOAD_BYTES.len(),
             protocol: IpProtocol::Unknown(0xEF),
+            hop_limit: None,
+            dscp: 0,
         };
         let mut prepared = packet.prepare(init)
             .expect("Found no valid routes");
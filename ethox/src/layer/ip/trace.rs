@@ -0,0 +1,197 @@
+use core::fmt;
+
+use crate::layer::Failure;
+#[cfg(feature = "trace")]
+use crate::managed::Slice;
+use crate::time::Instant;
+use crate::wire::{IpAddress, IpProtocol};
+
+/// A ring of summaries of the last packets received by an endpoint.
+///
+/// Diagnosing dropped traffic in the field usually requires a capture of all packets, which is
+/// often not available or too large. The trace instead keeps a short summary of each received
+/// packet together with the verdict of the ip layer, overwriting the oldest one when the storage
+/// is full. It can be dumped on demand, for example when a connection failed.
+///
+/// Only available with the `trace` feature, so that the layer does not pay for recording
+/// otherwise.
+#[cfg(feature = "trace")]
+pub struct Trace<'a> {
+    records: Slice<'a, Record>,
+    /// The index of the record to overwrite next.
+    next: usize,
+    /// The number of valid records.
+    len: usize,
+    /// The number of records ever written, including overwritten ones.
+    total: u64,
+}
+
+/// The summary of one received packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Record {
+    /// The time at which the packet was received.
+    pub timestamp: Instant,
+    /// The length of the packet, without the ethernet header.
+    pub len: usize,
+    /// The fixed header of the packet, unless it could not be parsed.
+    pub header: Option<Header>,
+    /// What the layer did with the packet.
+    pub verdict: Verdict,
+}
+
+/// The addresses and protocol of a traced packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Header {
+    /// The source address.
+    pub src_addr: IpAddress,
+    /// The destination address.
+    pub dst_addr: IpAddress,
+    /// The protocol of the payload, after extension headers have been handled.
+    pub protocol: IpProtocol,
+}
+
+/// The verdict of the ip layer on a received packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// The packet was passed to the upper layer handler.
+    Delivered,
+
+    /// The packet was handled or kept by the layer itself.
+    ///
+    /// This includes neighbor discovery and fragments of a packet that is still incomplete.
+    Consumed,
+
+    /// The packet was forwarded to its next hop.
+    Forwarded,
+
+    /// The packet was dropped for the reason in the failure.
+    Dropped(Failure),
+}
+
+#[cfg(feature = "trace")]
+impl<'a> Trace<'a> {
+    /// Create a trace recording into some storage.
+    ///
+    /// The trace holds as many records as the storage.
+    pub fn new(records: impl Into<Slice<'a, Record>>) -> Self {
+        Trace {
+            records: records.into(),
+            next: 0,
+            len: 0,
+            total: 0,
+        }
+    }
+
+    /// Add a record, overwriting the oldest one if the storage is full.
+    pub fn record(&mut self, record: Record) {
+        let capacity = self.records.len();
+        if capacity == 0 {
+            return;
+        }
+
+        self.records[self.next] = record;
+        self.next = (self.next + 1) % capacity;
+        self.len = (self.len + 1).min(capacity);
+        self.total += 1;
+    }
+
+    /// The recorded summaries, from the oldest to the newest.
+    pub fn records(&self) -> impl Iterator<Item=&Record> + '_ {
+        let capacity = self.records.len();
+        let start = (self.next + capacity - self.len) % capacity.max(1);
+        (0..self.len).map(move |idx| &self.records[(start + idx) % capacity])
+    }
+
+    /// The number of records currently held.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if no packet has been recorded since the trace was created or cleared.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of records ever added, including those already overwritten.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Forget all records.
+    pub fn clear(&mut self) {
+        self.next = 0;
+        self.len = 0;
+    }
+
+    /// Write all records, one line each and from the oldest to the newest.
+    pub fn dump(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+        for record in self.records() {
+            writeln!(out, "{}", record)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Record {
+    fn default() -> Self {
+        Record {
+            timestamp: Instant::from_millis(0),
+            len: 0,
+            header: None,
+            verdict: Verdict::Consumed,
+        }
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} len={}", self.timestamp, self.len)?;
+        if let Some(header) = self.header {
+            write!(f, " {} > {} {}", header.src_addr, header.dst_addr, header.protocol)?;
+        }
+        match self.verdict {
+            Verdict::Delivered => write!(f, ": delivered"),
+            Verdict::Consumed => write!(f, ": consumed"),
+            Verdict::Forwarded => write!(f, ": forwarded"),
+            Verdict::Dropped(failure) => {
+                write!(f, ": dropped {:?} {:?}", failure.error, failure.detail)
+            },
+        }
+    }
+}
+
+#[cfg(all(test, feature = "trace"))]
+mod tests {
+    use super::*;
+
+    fn record(millis: i64) -> Record {
+        Record { timestamp: Instant::from_millis(millis), ..Record::default() }
+    }
+
+    #[test]
+    fn ring() {
+        let mut trace = Trace::new(vec![Record::default(); 3]);
+        assert!(trace.is_empty());
+        for millis in 0..5 {
+            trace.record(record(millis));
+        }
+
+        let stamps: Vec<_> = trace.records().map(|record| record.timestamp.millis()).collect();
+        assert_eq!(stamps, [2, 3, 4]);
+        assert_eq!(trace.total(), 5);
+
+        let mut dump = String::new();
+        trace.dump(&mut dump).unwrap();
+        assert_eq!(dump.lines().count(), 3);
+
+        trace.clear();
+        assert_eq!(trace.records().count(), 0);
+        trace.record(record(5));
+        assert_eq!(trace.len(), 1);
+
+        // Without storage nothing is recorded.
+        let mut empty = Trace::new(Vec::new());
+        empty.record(record(0));
+        assert!(empty.is_empty());
+    }
+}
@@ -0,0 +1,521 @@
+use crate::layer::{ip, udp, FnHandler, Result};
+use crate::managed::Slice;
+use crate::time::{Clock, Duration, Expiration, Instant};
+use crate::wire::{IpAddress, IpProtocol, IpSubnet, Ipv4Subnet, Ipv6Subnet, Payload, PayloadMut};
+
+use super::flow::{EndReason, Flow, FlowKey};
+
+/// The well-known port of IPFIX collectors, rfc7011 section 10.3.
+pub const IPFIX_PORT: u16 = 4739;
+
+/// The version number of IPFIX messages.
+const VERSION: u16 = 10;
+
+const MESSAGE_HEADER_LEN: usize = 16;
+const SET_HEADER_LEN: usize = 4;
+
+/// The set id of template sets.
+const TEMPLATE_SET: u16 = 2;
+
+/// The template ids of the records of each address family.
+const TEMPLATE_V4: u16 = 256;
+const TEMPLATE_V6: u16 = 257;
+
+/// The information elements of a record as pairs of id and length, rfc5102.
+///
+/// The first two are replaced with the addresses of the family.
+const FIELDS: [(u16, u16); 11] = [
+    // sourceIPv4Address, destinationIPv4Address
+    (8, 4), (12, 4),
+    // protocolIdentifier, sourceTransportPort, destinationTransportPort
+    (4, 1), (7, 2), (11, 2),
+    // packetDeltaCount, octetDeltaCount
+    (2, 8), (1, 8),
+    // flowStartMilliseconds, flowEndMilliseconds
+    (152, 8), (153, 8),
+    // tcpControlBits, flowEndReason
+    (6, 2), (136, 1),
+];
+
+/// sourceIPv6Address, destinationIPv6Address
+const ADDRESSES_V6: [(u16, u16); 2] = [(27, 16), (28, 16)];
+
+const RECORD_V4_LEN: usize = 48;
+const RECORD_V6_LEN: usize = 72;
+
+/// A template set with the templates of both families.
+const TEMPLATE_SET_LEN: usize = SET_HEADER_LEN + 2 * (4 + 4 * FIELDS.len());
+
+/// The tcp flags ending a flow.
+const TCP_FIN: u8 = 0x01;
+const TCP_RST: u8 = 0x04;
+
+/// Meters flows in a table and exports their records to a collector.
+///
+/// The table is a fixed set of entries provided by the caller and searched linearly, size it for
+/// the expected number of concurrent flows. A flow occupies its entry from its first packet until
+/// its record has been exported.
+pub struct Exporter<'a> {
+    flows: Slice<'a, Flow>,
+    collector: IpAddress,
+    src_port: u16,
+    dst_port: u16,
+    domain: u32,
+    active_timeout: Duration,
+    inactive_timeout: Duration,
+    template_interval: Duration,
+    next_template: Option<Instant>,
+    max_len: usize,
+    sequence: u32,
+    unmetered: u64,
+    exported: u64,
+    sent: u64,
+}
+
+/// A receiver metering the packets passed to the wrapped receiver.
+pub struct Meter<'r, 'a, I> {
+    exporter: &'r mut Exporter<'a>,
+    inner: I,
+}
+
+/// Writes big-endian values to a buffer.
+struct Writer<'b> {
+    buffer: &'b mut [u8],
+    pos: usize,
+}
+
+impl<'a> Exporter<'a> {
+    /// The default time after which a long-lasting flow is exported.
+    pub const DEFAULT_ACTIVE_TIMEOUT: Duration = Duration::from_secs(60);
+
+    /// The default time without packets after which a flow is exported.
+    pub const DEFAULT_INACTIVE_TIMEOUT: Duration = Duration::from_secs(15);
+
+    /// The default interval in which templates are repeated.
+    pub const DEFAULT_TEMPLATE_INTERVAL: Duration = Duration::from_secs(60);
+
+    /// The default length of messages.
+    ///
+    /// Messages over udp should not exceed the path mtu, see rfc7011 section 10.3.3. Records that
+    /// do not fit are exported in the next message.
+    pub const DEFAULT_MAX_LEN: usize = 1400;
+
+    /// Create an exporter with a flow table.
+    ///
+    /// Messages are sent from `src_port`, which the udp endpoint must accept. Create the table
+    /// from empty entries, for example `vec![Flow::default(); 256]`.
+    pub fn new(collector: IpAddress, src_port: u16, flows: impl Into<Slice<'a, Flow>>) -> Self {
+        Exporter {
+            flows: flows.into(),
+            collector,
+            src_port,
+            dst_port: IPFIX_PORT,
+            domain: 0,
+            active_timeout: Self::DEFAULT_ACTIVE_TIMEOUT,
+            inactive_timeout: Self::DEFAULT_INACTIVE_TIMEOUT,
+            template_interval: Self::DEFAULT_TEMPLATE_INTERVAL,
+            next_template: None,
+            max_len: Self::DEFAULT_MAX_LEN,
+            sequence: 0,
+            unmetered: 0,
+            exported: 0,
+            sent: 0,
+        }
+    }
+
+    /// Change the port of the collector, [`IPFIX_PORT`] by default.
+    ///
+    /// [`IPFIX_PORT`]: constant.IPFIX_PORT.html
+    pub fn set_collector_port(&mut self, port: u16) {
+        self.dst_port = port;
+    }
+
+    /// Change the observation domain id of messages, `0` by default.
+    pub fn set_observation_domain(&mut self, domain: u32) {
+        self.domain = domain;
+    }
+
+    /// Change the active and inactive timeouts of flows.
+    ///
+    /// A flow is exported once it has lasted for the active timeout, or when no packet was seen
+    /// for the inactive timeout. Further packets start a new flow.
+    pub fn set_timeouts(&mut self, active: Duration, inactive: Duration) {
+        self.active_timeout = active;
+        self.inactive_timeout = inactive;
+    }
+
+    /// Change the interval in which templates are repeated.
+    ///
+    /// Templates are sent with the first message and then again in the first message after the
+    /// interval, so that a restarted collector learns them.
+    pub fn set_template_interval(&mut self, interval: Duration) {
+        self.template_interval = interval;
+    }
+
+    /// Change the maximum length of messages.
+    ///
+    /// Messages must fit into the packet buffers and the mtu of the link. The length is raised to
+    /// fit at least the templates and one record.
+    pub fn set_max_len(&mut self, len: usize) {
+        let min = MESSAGE_HEADER_LEN + TEMPLATE_SET_LEN + SET_HEADER_LEN + RECORD_V6_LEN;
+        self.max_len = len.clamp(min, usize::from(u16::MAX));
+    }
+
+    /// The entries of the flow table.
+    pub fn flows(&self) -> &[Flow] {
+        &self.flows
+    }
+
+    /// The number of packets that were not metered since the table was full.
+    pub fn unmetered(&self) -> u64 {
+        self.unmetered
+    }
+
+    /// The number of exported flow records.
+    pub fn exported(&self) -> u64 {
+        self.exported
+    }
+
+    /// The number of messages sent to the collector.
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    /// End all active flows, for example before shutting down.
+    ///
+    /// Their records are exported with the following messages.
+    pub fn flush(&mut self) {
+        for flow in self.flows.iter_mut().filter(|flow| flow.is_active()) {
+            flow.end(EndReason::ForcedEnd);
+        }
+    }
+
+    /// Meter the packets received by an ip receiver.
+    ///
+    /// Only packets handed to the receiver are metered, that is those to a local address or one
+    /// claimed by the receiver and of a protocol that it accepts.
+    pub fn recv<I>(&mut self, inner: I) -> Meter<'_, 'a, I> {
+        Meter { exporter: self, inner }
+    }
+
+    /// Meter the packets received by a function.
+    pub fn recv_with<F>(&mut self, inner: F) -> Meter<'_, 'a, FnHandler<F>> {
+        self.recv(FnHandler(inner))
+    }
+
+    /// Get the time at which the exporter wants to send its next message.
+    ///
+    /// Offer it a packet buffer at that point.
+    pub fn poll(&self, now: Instant) -> Expiration {
+        let mut next = Expiration::Never;
+        for flow in self.flows.iter() {
+            let deadline = match self.deadline(flow) {
+                _ if flow.end_reason().is_some() => now,
+                Some(deadline) => deadline,
+                None => continue,
+            };
+            next = next.min(Expiration::When(deadline));
+        }
+        next
+    }
+
+    /// Get the time of the next message, with the current time of a clock.
+    pub fn tick<C: Clock + ?Sized>(&self, clock: &C) -> Expiration {
+        self.poll(clock.now())
+    }
+
+    /// Account for a packet of a flow.
+    ///
+    /// When the table is full the packet is not metered. The flow that has been idle for the
+    /// longest time is then ended, so that an entry becomes free once its record was exported.
+    pub fn observe(&mut self, key: FlowKey, octets: usize, tcp_flags: u8, now: Instant) {
+        self.expire(now);
+
+        let idx = match self.flows.iter().position(|flow| flow.matches(&key)) {
+            Some(idx) => idx,
+            None => match self.flows.iter().position(Flow::is_free) {
+                Some(idx) => {
+                    self.flows[idx] = Flow::start(key, now);
+                    idx
+                },
+                None => {
+                    self.unmetered += 1;
+                    self.evict();
+                    return;
+                },
+            },
+        };
+
+        let flow = &mut self.flows[idx];
+        flow.update(octets, tcp_flags, now);
+        if tcp_flags & (TCP_FIN | TCP_RST) != 0 {
+            flow.end(EndReason::EndOfFlow);
+        }
+    }
+
+    /// The time at which an active flow times out.
+    fn deadline(&self, flow: &Flow) -> Option<Instant> {
+        if !flow.is_active() {
+            return None;
+        }
+
+        let idle = flow.end_time()? + self.inactive_timeout;
+        let active = flow.start_time()? + self.active_timeout;
+        Some(idle.min(active))
+    }
+
+    fn expire(&mut self, now: Instant) {
+        let (active, inactive) = (self.active_timeout, self.inactive_timeout);
+        for flow in self.flows.iter_mut().filter(|flow| flow.is_active()) {
+            match (flow.start_time(), flow.end_time()) {
+                (_, Some(end)) if end + inactive <= now => flow.end(EndReason::IdleTimeout),
+                (Some(start), _) if start + active <= now => flow.end(EndReason::ActiveTimeout),
+                _ => (),
+            }
+        }
+    }
+
+    fn evict(&mut self) {
+        let oldest = self.flows.iter_mut()
+            .filter(|flow| flow.is_active())
+            .min_by_key(|flow| flow.end_time());
+        if let Some(flow) = oldest {
+            flow.end(EndReason::LackOfResources);
+        }
+    }
+
+    /// Send a message with ended flows of the same address family as the first one.
+    fn send_message<P: PayloadMut>(&mut self, raw: udp::RawPacket<P>, now: Instant)
+        -> Result<()>
+    {
+        let family = match self.flows.iter().find_map(ended_key) {
+            Some(key) => Family::of(key.src_addr),
+            None => return Ok(()),
+        };
+
+        let template = self.next_template.is_none_or(|next| next <= now);
+        let templates_len = if template { TEMPLATE_SET_LEN } else { 0 };
+        let header_len = MESSAGE_HEADER_LEN + templates_len + SET_HEADER_LEN;
+        let record_len = family.record_len();
+        let count = self.flows.iter()
+            .filter_map(ended_key)
+            .filter(|key| Family::of(key.src_addr) == family)
+            .take((self.max_len - header_len) / record_len)
+            .count();
+        let data_len = SET_HEADER_LEN + count * record_len;
+
+        let source = match Family::of(self.collector) {
+            Family::V4 => IpSubnet::from(Ipv4Subnet::ANY),
+            Family::V6 => IpSubnet::from(Ipv6Subnet::ANY),
+        };
+        let init = udp::Init {
+            source: source.into(),
+            src_port: self.src_port,
+            dst_addr: self.collector,
+            dst_port: self.dst_port,
+            payload: MESSAGE_HEADER_LEN + templates_len + data_len,
+            dscp: 0,
+        };
+
+        let mut packet = raw.prepare(init)?;
+        let mut writer = Writer { buffer: packet.packet.payload_mut_slice(), pos: 0 };
+        let len = writer.buffer.len();
+        writer.u16(VERSION);
+        writer.u16(len as u16);
+        writer.u32(now.secs() as u32);
+        writer.u32(self.sequence);
+        writer.u32(self.domain);
+
+        if template {
+            writer.u16(TEMPLATE_SET);
+            writer.u16(TEMPLATE_SET_LEN as u16);
+            writer.template(TEMPLATE_V4, &FIELDS);
+            writer.u16(TEMPLATE_V6);
+            writer.u16(FIELDS.len() as u16);
+            for &(id, len) in ADDRESSES_V6.iter().chain(&FIELDS[2..]) {
+                writer.u16(id);
+                writer.u16(len);
+            }
+        }
+
+        writer.u16(family.template());
+        writer.u16(data_len as u16);
+        let records = self.flows.iter()
+            .filter(|flow| flow.end_reason().is_some())
+            .filter(|flow| flow.key().map(|key| Family::of(key.src_addr)) == Some(family))
+            .take(count);
+        for flow in records {
+            writer.record(flow);
+        }
+
+        packet.send()?;
+
+        let mut remaining = count;
+        for flow in self.flows.iter_mut() {
+            if remaining == 0 {
+                break;
+            }
+            if flow.end_reason().is_some()
+                && flow.key().map(|key| Family::of(key.src_addr)) == Some(family)
+            {
+                flow.free();
+                remaining -= 1;
+            }
+        }
+
+        self.sequence = self.sequence.wrapping_add(count as u32);
+        self.exported += count as u64;
+        if template {
+            self.next_template = Some(now + self.template_interval);
+        }
+        Ok(())
+    }
+}
+
+/// The address family of a flow, selecting its template.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Family {
+    V4,
+    V6,
+}
+
+impl Family {
+    fn of(addr: IpAddress) -> Self {
+        match addr {
+            IpAddress::Ipv6(_) => Family::V6,
+            _ => Family::V4,
+        }
+    }
+
+    fn template(self) -> u16 {
+        match self {
+            Family::V4 => TEMPLATE_V4,
+            Family::V6 => TEMPLATE_V6,
+        }
+    }
+
+    fn record_len(self) -> usize {
+        match self {
+            Family::V4 => RECORD_V4_LEN,
+            Family::V6 => RECORD_V6_LEN,
+        }
+    }
+}
+
+fn ended_key(flow: &Flow) -> Option<FlowKey> {
+    flow.end_reason().and(flow.key())
+}
+
+impl Writer<'_> {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.buffer[self.pos..][..bytes.len()].copy_from_slice(bytes);
+        self.pos += bytes.len();
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_be_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_be_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_be_bytes());
+    }
+
+    fn template(&mut self, id: u16, fields: &[(u16, u16)]) {
+        self.u16(id);
+        self.u16(fields.len() as u16);
+        for &(id, len) in fields {
+            self.u16(id);
+            self.u16(len);
+        }
+    }
+
+    /// Write a data record in the field order of the templates.
+    fn record(&mut self, flow: &Flow) {
+        let key = flow.key().unwrap();
+        for addr in [key.src_addr, key.dst_addr].iter() {
+            match addr {
+                IpAddress::Ipv4(addr) => self.bytes(addr.as_bytes()),
+                IpAddress::Ipv6(addr) => self.bytes(addr.as_bytes()),
+                _ => self.bytes(&[0; 4]),
+            }
+        }
+        self.bytes(&[key.protocol.into()]);
+        self.u16(key.src_port);
+        self.u16(key.dst_port);
+        self.u64(flow.packets());
+        self.u64(flow.octets());
+        let millis = |time: Option<Instant>| time.map_or(0, |time| time.total_millis() as u64);
+        self.u64(millis(flow.start_time()));
+        self.u64(millis(flow.end_time()));
+        self.u16(flow.tcp_flags().into());
+        self.bytes(&[flow.end_reason().map_or(0, |reason| reason as u8)]);
+    }
+}
+
+impl<P: PayloadMut> udp::Send<P> for Exporter<'_> {
+    fn send(&mut self, raw: udp::RawPacket<P>) {
+        let now = raw.handle.info().timestamp();
+        self.expire(now);
+        if self.flows.iter().all(|flow| flow.end_reason().is_none()) {
+            return;
+        }
+
+        if self.send_message(raw, now).is_ok() {
+            self.sent += 1;
+        }
+    }
+}
+
+impl<P, I> ip::Recv<P> for Meter<'_, '_, I>
+where
+    P: Payload,
+    I: ip::Recv<P>,
+{
+    fn receive(&mut self, packet: ip::InPacket<P>) {
+        let now = packet.handle.info().timestamp();
+        let (key, octets, tcp_flags) = classify(&packet.packet);
+        self.exporter.observe(key, octets, tcp_flags, now);
+        self.inner.receive(packet)
+    }
+
+    fn accepts_foreign(&self, dst_addr: IpAddress) -> bool {
+        self.inner.accepts_foreign(dst_addr)
+    }
+
+    fn accepts_protocol(&self, protocol: IpProtocol) -> bool {
+        self.inner.accepts_protocol(protocol)
+    }
+}
+
+/// Find the flow of a packet, its length and its tcp flags.
+fn classify<P: Payload>(packet: &ip::IpPacket<P>) -> (FlowKey, usize, u8) {
+    let repr = packet.repr();
+    let (octets, initial) = match packet {
+        ip::IpPacket::V4(packet) => (usize::from(packet.total_len()), packet.frag_offset() == 0),
+        ip::IpPacket::V6(_) => (repr.buffer_len(), true),
+    };
+
+    let payload = packet.payload().as_slice();
+    let word = |at: usize| payload.get(at..at + 2)
+        .map_or(0, |bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+    let (src_port, dst_port, tcp_flags) = match repr.protocol() {
+        _ if !initial => (0, 0, 0),
+        IpProtocol::Tcp => (word(0), word(2), payload.get(13).copied().unwrap_or(0)),
+        IpProtocol::Udp => (word(0), word(2), 0),
+        IpProtocol::Icmp | IpProtocol::Icmpv6 => (0, word(0), 0),
+        _ => (0, 0, 0),
+    };
+
+    let key = FlowKey {
+        src_addr: repr.src_addr(),
+        dst_addr: repr.dst_addr(),
+        protocol: repr.protocol(),
+        src_port,
+        dst_port,
+    };
+    (key, octets, tcp_flags)
+}
@@ -0,0 +1,142 @@
+use crate::time::Instant;
+use crate::wire::{IpAddress, IpProtocol};
+
+/// The five-tuple identifying a flow.
+///
+/// The ports are zero for protocols without ports and for non-initial fragments. For icmp the
+/// destination port holds the type and code of the message, as is common practice.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FlowKey {
+    /// The source address of the packets.
+    pub src_addr: IpAddress,
+    /// The destination address of the packets.
+    pub dst_addr: IpAddress,
+    /// The protocol of the packets.
+    pub protocol: IpProtocol,
+    /// The source port of the transport protocol.
+    pub src_port: u16,
+    /// The destination port of the transport protocol.
+    pub dst_port: u16,
+}
+
+/// The reason a flow record was exported, rfc5102 section 5.11.3.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EndReason {
+    /// No packet of the flow was seen for the inactive timeout.
+    IdleTimeout = 1,
+    /// The flow lasted longer than the active timeout.
+    ActiveTimeout = 2,
+    /// A tcp segment with the FIN or RST flag ended the flow.
+    EndOfFlow = 3,
+    /// The flow was ended on request.
+    ForcedEnd = 4,
+    /// The flow was ended to make room for another one.
+    LackOfResources = 5,
+}
+
+/// An entry of the flow table.
+///
+/// Create the table from empty entries with `Flow::default()`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Flow {
+    state: State,
+    key: Option<FlowKey>,
+    start: Option<Instant>,
+    end: Option<Instant>,
+    packets: u64,
+    octets: u64,
+    tcp_flags: u8,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum State {
+    /// The entry is not used.
+    #[default]
+    Free,
+    /// Packets are being metered.
+    Active,
+    /// The flow has ended and waits to be exported.
+    Ended(EndReason),
+}
+
+impl Flow {
+    /// Start metering a flow with its first packet.
+    pub(crate) fn start(key: FlowKey, now: Instant) -> Self {
+        Flow {
+            state: State::Active,
+            key: Some(key),
+            start: Some(now),
+            end: Some(now),
+            packets: 0,
+            octets: 0,
+            tcp_flags: 0,
+        }
+    }
+
+    /// The five-tuple of the flow, `None` for an unused entry.
+    pub fn key(&self) -> Option<FlowKey> {
+        self.key
+    }
+
+    /// The time of the first packet.
+    pub fn start_time(&self) -> Option<Instant> {
+        self.start
+    }
+
+    /// The time of the last packet.
+    pub fn end_time(&self) -> Option<Instant> {
+        self.end
+    }
+
+    /// The number of packets of the flow.
+    pub fn packets(&self) -> u64 {
+        self.packets
+    }
+
+    /// The number of octets of all packets, including their ip header.
+    pub fn octets(&self) -> u64 {
+        self.octets
+    }
+
+    /// The union of the tcp flags of all segments.
+    pub fn tcp_flags(&self) -> u8 {
+        self.tcp_flags
+    }
+
+    /// Check if packets are being metered for this flow.
+    pub fn is_active(&self) -> bool {
+        self.state == State::Active
+    }
+
+    /// The reason the flow ended, if it is waiting to be exported.
+    pub fn end_reason(&self) -> Option<EndReason> {
+        match self.state {
+            State::Ended(reason) => Some(reason),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn is_free(&self) -> bool {
+        self.state == State::Free
+    }
+
+    pub(crate) fn matches(&self, key: &FlowKey) -> bool {
+        self.is_active() && self.key.as_ref() == Some(key)
+    }
+
+    /// Account for one packet.
+    pub(crate) fn update(&mut self, octets: usize, tcp_flags: u8, now: Instant) {
+        self.packets = self.packets.wrapping_add(1);
+        self.octets = self.octets.wrapping_add(octets as u64);
+        self.tcp_flags |= tcp_flags;
+        self.end = Some(now);
+    }
+
+    pub(crate) fn end(&mut self, reason: EndReason) {
+        self.state = State::Ended(reason);
+    }
+
+    pub(crate) fn free(&mut self) {
+        *self = Flow::default();
+    }
+}
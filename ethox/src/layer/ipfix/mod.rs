@@ -0,0 +1,38 @@
+//! Flow metering and export with IPFIX, rfc7011.
+//!
+//! An [`Exporter`] aggregates received packets into flow records, one for each five-tuple of
+//! addresses, protocol and ports. A record counts the packets and octets of its flow, the times of
+//! its first and last packet and the union of its tcp flags. It is exported to a collector once
+//! the flow has been idle for the inactive timeout, has lasted for the active timeout, or ended
+//! with a tcp FIN or RST. This is also what NetFlow version 9 collectors understand as IPFIX is
+//! derived from it.
+//!
+//! The exporter wraps the ip receiver with [`Exporter::recv`], which meters every packet before
+//! it is passed on. The flow table is a [`managed`] slice provided by the caller. Records leave
+//! whenever the exporter is offered a packet buffer on top of the udp layer, drive its timer with
+//! [`Exporter::tick`] so that timed out flows are exported also in the absence of traffic.
+//!
+//! Flow times are exported as milliseconds since the unix epoch, which requires the clock of the
+//! device to be unix time, such as one corrected by an [`sntp`] client.
+//!
+//! [`Exporter`]: struct.Exporter.html
+//! [`Exporter::recv`]: struct.Exporter.html#method.recv
+//! [`Exporter::tick`]: struct.Exporter.html#method.tick
+//! [`managed`]: ../../managed/index.html
+//! [`sntp`]: ../sntp/index.html
+mod exporter;
+mod flow;
+#[cfg(test)]
+mod tests;
+
+pub use exporter::{
+    Exporter,
+    Meter,
+    IPFIX_PORT,
+};
+
+pub use flow::{
+    EndReason,
+    Flow,
+    FlowKey,
+};
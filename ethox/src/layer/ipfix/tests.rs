@@ -0,0 +1,225 @@
+use super::*;
+use crate::layer::{arp, eth, ip};
+use crate::layer::udp::testing::{Host, IP_ADDR_HOST, IP_ADDR_PEER as IP_ADDR_COLLECTOR};
+use crate::layer::udp::testing::{MAC_ADDR_HOST, MAC_ADDR_PEER as MAC_ADDR_COLLECTOR};
+use crate::managed::Slice;
+use crate::nic::{external::External, Device};
+use crate::time::{Duration, Expiration, Instant};
+use crate::wire::{EthernetProtocol, EthernetRepr, IpAddress, IpCidr, IpProtocol};
+use crate::wire::{Checksum, Ipv4Address, Ipv4Repr, Ipv6Address};
+use crate::wire::{ethernet_frame, ipv4_packet};
+
+const IP_ADDR_PEER: Ipv4Address = Ipv4Address::new(10, 0, 0, 3);
+const EXPORTER_PORT: u16 = 4740;
+
+/// Let the exporter send at most one message and return its payload.
+fn send(exporter: &mut Exporter, now: Instant) -> Option<Vec<u8>> {
+    Host::new(vec![EXPORTER_PORT]).send_datagram(now, (EXPORTER_PORT, IPFIX_PORT), exporter)
+}
+
+fn exporter(flows: usize) -> Exporter<'static> {
+    Exporter::new(IP_ADDR_COLLECTOR.into(), EXPORTER_PORT, vec![Flow::default(); flows])
+}
+
+fn key(src_port: u16) -> FlowKey {
+    FlowKey {
+        src_addr: IP_ADDR_PEER.into(),
+        dst_addr: IP_ADDR_HOST.into(),
+        protocol: IpProtocol::Tcp,
+        src_port,
+        dst_port: 80,
+    }
+}
+
+fn half(data: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([data[at], data[at + 1]])
+}
+
+fn word(data: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+fn long(data: &[u8], at: usize) -> u64 {
+    (u64::from(word(data, at)) << 32) | u64::from(word(data, at + 4))
+}
+
+#[test]
+fn timeouts() {
+    let mut exporter = exporter(4);
+    exporter.set_timeouts(Duration::from_secs(10), Duration::from_secs(5));
+
+    let start = Instant::from_secs(100);
+    exporter.observe(key(1000), 60, 0x02, start);
+    exporter.observe(key(2000), 60, 0x02, start);
+    exporter.observe(key(1000), 1500, 0x10, start + Duration::from_secs(1));
+    let flow = exporter.flows()[0];
+    assert_eq!(flow.key(), Some(key(1000)));
+    assert_eq!(flow.packets(), 2);
+    assert_eq!(flow.octets(), 1560);
+    assert_eq!(flow.tcp_flags(), 0x12);
+    assert!(flow.is_active());
+    assert_eq!(exporter.poll(start), Expiration::When(start + Duration::from_secs(5)));
+    assert_eq!(send(&mut exporter, start), None);
+
+    // The second flow went idle, the first one continues.
+    let now = start + Duration::from_secs(5);
+    exporter.observe(key(1000), 100, 0x10, now);
+    assert_eq!(exporter.flows()[1].end_reason(), Some(EndReason::IdleTimeout));
+    assert_eq!(exporter.poll(now), Expiration::When(now));
+    assert!(send(&mut exporter, now).is_some());
+    assert!(exporter.flows()[1].key().is_none());
+    assert_eq!(exporter.exported(), 1);
+
+    // The first flow lasts too long, later packets start another one.
+    let now = start + Duration::from_secs(10);
+    exporter.observe(key(1000), 100, 0x10, now - Duration::from_secs(1));
+    assert_eq!(exporter.poll(now - Duration::from_secs(1)), Expiration::When(now));
+    exporter.observe(key(1000), 100, 0x10, now);
+    assert_eq!(exporter.flows()[0].end_reason(), Some(EndReason::ActiveTimeout));
+    assert!(exporter.flows()[1].is_active());
+    assert_eq!(exporter.flows()[1].packets(), 1);
+}
+
+#[test]
+fn end_of_flow() {
+    let mut exporter = exporter(2);
+    let now = Instant::from_secs(100);
+    exporter.observe(key(1000), 60, 0x02, now);
+    exporter.observe(key(1000), 60, 0x11, now);
+    assert_eq!(exporter.flows()[0].end_reason(), Some(EndReason::EndOfFlow));
+
+    // A retransmitted FIN starts a new flow.
+    exporter.observe(key(1000), 60, 0x11, now);
+    assert_eq!(exporter.flows()[1].end_reason(), Some(EndReason::EndOfFlow));
+    assert_eq!(exporter.flows()[1].packets(), 1);
+
+    let message = send(&mut exporter, now).unwrap();
+    assert_eq!(half(&message, 0), 10);
+    assert_eq!(half(&message, 2) as usize, message.len());
+    assert_eq!(word(&message, 4), 100);
+    assert_eq!(word(&message, 8), 0);
+
+    // The templates precede the data set.
+    assert_eq!(half(&message, 16), 2);
+    let templates = half(&message, 18) as usize;
+    assert_eq!(half(&message, 20), 256);
+    assert_eq!(half(&message, 22), 11);
+    assert_eq!(half(&message, 24), 8);
+
+    let data = &message[16 + templates..];
+    assert_eq!(half(data, 0), 256);
+    assert_eq!(half(data, 2) as usize, data.len());
+    assert_eq!(data.len(), 4 + 2 * 48);
+    let record = &data[4..];
+    assert_eq!(&record[0..4], IP_ADDR_PEER.as_bytes());
+    assert_eq!(&record[4..8], IP_ADDR_HOST.as_bytes());
+    assert_eq!(record[8], 6);
+    assert_eq!(half(record, 9), 1000);
+    assert_eq!(half(record, 11), 80);
+    assert_eq!(long(record, 13), 2);
+    assert_eq!(long(record, 21), 120);
+    assert_eq!(long(record, 29), 100_000);
+    assert_eq!(long(record, 37), 100_000);
+    assert_eq!(half(record, 45), 0x13);
+    assert_eq!(record[47], 3);
+    assert!(exporter.flows().iter().all(|flow| flow.key().is_none()));
+
+    // Templates are only repeated after their interval, the sequence counts records.
+    exporter.observe(key(1000), 60, 0x04, now);
+    let message = send(&mut exporter, now).unwrap();
+    assert_eq!(word(&message, 8), 2);
+    assert_eq!(half(&message, 16), 256);
+    assert_eq!(message.len(), 16 + 4 + 48);
+}
+
+#[test]
+fn table_full() {
+    let mut exporter = exporter(2);
+    let now = Instant::from_secs(0);
+    exporter.observe(key(1000), 60, 0, now);
+    exporter.observe(key(2000), 60, 0, now + Duration::from_secs(1));
+    exporter.observe(key(3000), 60, 0, now + Duration::from_secs(1));
+    assert_eq!(exporter.unmetered(), 1);
+    assert_eq!(exporter.flows()[0].end_reason(), Some(EndReason::LackOfResources));
+    assert!(exporter.flows()[1].is_active());
+
+    // Once exported the entry is free again.
+    assert!(send(&mut exporter, now + Duration::from_secs(1)).is_some());
+    exporter.observe(key(3000), 60, 0, now + Duration::from_secs(1));
+    assert_eq!(exporter.flows()[0].key(), Some(key(3000)));
+
+    exporter.flush();
+    assert!(exporter.flows().iter().all(|flow| flow.end_reason() == Some(EndReason::ForcedEnd)));
+}
+
+#[test]
+fn families() {
+    let mut exporter = exporter(2);
+    let now = Instant::from_secs(0);
+    let v6 = FlowKey {
+        src_addr: Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2).into(),
+        dst_addr: Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1).into(),
+        protocol: IpProtocol::Udp,
+        src_port: 53,
+        dst_port: 53,
+    };
+    exporter.observe(key(1000), 60, 0x04, now);
+    exporter.observe(v6, 80, 0, now);
+    exporter.flush();
+
+    // Each message holds the records of one family.
+    let message = send(&mut exporter, now).unwrap();
+    let templates = half(&message, 18) as usize;
+    assert_eq!(templates, 4 + 2 * (4 + 4 * 11));
+    assert_eq!(half(&message, 20 + 48), 257);
+    assert_eq!(half(&message, 20 + 48 + 4), 27);
+    assert_eq!(half(&message, 16 + templates), 256);
+    assert_eq!(message.len(), 16 + templates + 4 + 48);
+    let message = send(&mut exporter, now).unwrap();
+    assert_eq!(half(&message, 16), 257);
+    assert_eq!(message.len(), 16 + 4 + 72);
+    assert_eq!(&message[20 + 16..][..16], v6.dst_addr.as_bytes());
+    assert_eq!(send(&mut exporter, now), None);
+    assert_eq!(exporter.sent(), 2);
+}
+
+#[test]
+fn meter() {
+    let mut frame = vec![0; 14 + 20 + 20];
+    let eth = ethernet_frame::new_unchecked_mut(&mut frame[..]);
+    EthernetRepr {
+        src_addr: MAC_ADDR_COLLECTOR,
+        dst_addr: MAC_ADDR_HOST,
+        ethertype: EthernetProtocol::Ipv4,
+    }.emit(eth);
+    let packet = ipv4_packet::new_unchecked_mut(eth.payload_mut_slice());
+    Ipv4Repr {
+        src_addr: IP_ADDR_PEER,
+        dst_addr: IP_ADDR_HOST,
+        protocol: IpProtocol::Tcp,
+        payload_len: 20,
+        hop_limit: 64,
+    }.emit(packet, Checksum::Manual);
+    let segment = packet.payload_mut_slice();
+    segment[..4].copy_from_slice(&[0x04, 0x00, 0x00, 0x50]);
+    segment[13] = 0x02;
+
+    let mut exporter = exporter(1);
+    let mut eth = eth::Endpoint::new(MAC_ADDR_HOST);
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_HOST.into(), 24),
+        ip::Routes::new(vec![ip::Route::unspecified(); 1]),
+        arp::NeighborCache::new(vec![arp::Neighbor::default(); 1]));
+    let mut nic = External::new_recv(Slice::One(frame));
+    let mut received = false;
+    let recv = nic.rx(1, eth.recv(ip.recv(exporter.recv_with(|_: ip::InPacket<_>| {
+        received = true;
+    }))));
+    assert_eq!(recv, Ok(1));
+    assert!(received);
+
+    let flow = exporter.flows()[0];
+    assert_eq!(flow.key(), Some(key(1024)));
+    assert_eq!(flow.key().map(|key| key.dst_addr), Some(IpAddress::from(IP_ADDR_HOST)));
+    assert_eq!(flow.octets(), 40);
+    assert_eq!(flow.tcp_flags(), 0x02);
+}
@@ -2,9 +2,15 @@
 //!
 //! The loss layer is a simple wrapper around another layer which simulates a lossy connection.
 //! This works by dropping ingress packets or canceling the sending of egress packets.
-use crate::nic;
+//!
+//! Losses are either independent, pulsed, or follow the bursts of a [`GilbertElliott`] channel.
+//! All of them are driven by a seeded pseudo-random generator so that the same seed reproduces the
+//! exact same sequence of losses.
+//!
+//! [`GilbertElliott`]: struct.GilbertElliott.html
+use crate::{nic, rand};
 use crate::layer::{eth, ip};
-use crate::wire::Payload;
+use crate::wire::{IpAddress, Payload};
 
 /// Simple pseudo-random loss.
 ///
@@ -25,6 +31,29 @@ pub struct PrngLoss {
     ///
     /// Xoroshiro256**, yes this is far too good.
     pub prng: Xoroshiro256,
+    /// A stateful burst loss model replacing the pulse and loss rate, if any.
+    pub burst: Option<GilbertElliott>,
+}
+
+/// The two-state Gilbert–Elliott model of a channel with burst losses.
+///
+/// The channel is either in a good or a bad state, each with its own loss rate. Before each packet
+/// it changes state with the respective transition probability, so that losses cluster while the
+/// channel stays in the bad state. The average length of a burst is the inverse of `bad_to_good`.
+///
+/// All probabilities are (0, 32)-bit fixed point numbers where `u32::MAX` is certain.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GilbertElliott {
+    /// Probability of changing from the good to the bad state.
+    pub good_to_bad: u32,
+    /// Probability of changing from the bad back to the good state.
+    pub bad_to_good: u32,
+    /// Loss rate while in the good state.
+    pub good_loss: u32,
+    /// Loss rate while in the bad state.
+    pub bad_loss: u32,
+    /// If the channel is currently in the bad state.
+    pub bad: bool,
 }
 
 /// An adaptor simulating loss to and from the wrapped layer.
@@ -70,6 +99,19 @@ impl PrngLoss {
             reset: 0,
             lossrate: rate,
             prng: Xoroshiro256::new(seed),
+            burst: None,
+        }
+    }
+
+    /// A burst loss simulator following a Gilbert–Elliott model.
+    pub fn gilbert_elliott(model: GilbertElliott, seed: u64) -> Self {
+        PrngLoss {
+            threshold: 1,
+            count: 0,
+            reset: 0,
+            lossrate: None,
+            prng: Xoroshiro256::new(seed),
+            burst: Some(model),
         }
     }
 
@@ -91,11 +133,18 @@ impl PrngLoss {
             // Packet always lost when pulse condition is true.
             lossrate: Some(u32::max_value()),
             prng: Xoroshiro256::new(0),
+            burst: None,
         }
     }
 
     /// Determine the fate for the next packet.
     pub fn next_pass(&mut self) -> bool {
+        if let Some(mut model) = self.burst {
+            let pass = model.next_pass(|| self.roll());
+            self.burst = Some(model);
+            return pass;
+        }
+
         let in_window = self.count < self.threshold;
         let fate_drop = Some(self.roll()) <= self.lossrate;
 
@@ -112,14 +161,50 @@ impl PrngLoss {
     }
 }
 
+impl GilbertElliott {
+    /// The simple Gilbert model, all packets are lost in the bad state and none in the good one.
+    pub fn gilbert(good_to_bad: u32, bad_to_good: u32) -> Self {
+        GilbertElliott {
+            good_to_bad,
+            bad_to_good,
+            good_loss: 0,
+            bad_loss: u32::MAX,
+            bad: false,
+        }
+    }
+
+    /// Advance the state and determine the fate of the next packet.
+    fn next_pass(&mut self, mut roll: impl FnMut() -> u32) -> bool {
+        let mut happens = |rate: u32| rate == u32::MAX || roll() < rate;
+
+        let change = if self.bad { self.bad_to_good } else { self.good_to_bad };
+        if happens(change) {
+            self.bad = !self.bad;
+        }
+
+        let loss = if self.bad { self.bad_loss } else { self.good_loss };
+        !happens(loss)
+    }
+}
+
 impl Xoroshiro256 {
     /// Initialize from a seed.
     ///
     /// Although the seed is smaller than the internal state it is easily large enough to be almost
-    /// guaranteed to be unique if generated by random.
+    /// guaranteed to be unique if generated by random. It is expanded with SplitMix64, as
+    /// recommended by the authors, so that similar seeds still yield unrelated sequences.
     pub fn new(seed: u64) -> Self {
+        let mut seed = seed;
+        let mut split_mix = || {
+            seed = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        };
+
         Xoroshiro256 {
-            state: [seed, 0, 0, 0],
+            state: [split_mix(), split_mix(), split_mix(), split_mix()],
         }
     }
 
@@ -146,6 +231,12 @@ impl Xoroshiro256 {
     }
 }
 
+impl rand::Rng for Xoroshiro256 {
+    fn next_u64(&mut self) -> u64 {
+        self.next()
+    }
+}
+
 impl<H: ?Sized> LossyHandle<H> {
     /// Instantiate behind a reference with short enough lifetime to ensure it doesn't escape.
     fn new<'a>(
@@ -223,6 +314,14 @@ impl<H: nic::Handle + ?Sized> nic::Handle for LossyHandle<H> {
     fn info(&self) -> &dyn nic::Info {
         unsafe { &*self.handle }.info()
     }
+
+    fn retain(&mut self) -> crate::layer::Result<()> {
+        unsafe { &mut *self.handle }.retain()
+    }
+
+    fn request_tx_timestamp(&mut self, id: u32) -> crate::layer::Result<()> {
+        unsafe { &mut *self.handle }.request_tx_timestamp(id)
+    }
 }
 
 impl<D> nic::Device for Lossy<'_, D>
@@ -318,6 +417,10 @@ where
 
         self.0.receive(packet)
     }
+
+    fn accepts_foreign(&self, dst_addr: IpAddress) -> bool {
+        self.0.accepts_foreign(dst_addr)
+    }
 }
 
 impl<P, I> ip::Send<P> for Lossy<'_, I>
@@ -343,7 +446,7 @@ where
 
 #[cfg(test)]
 mod tests {
-    use super::PrngLoss;
+    use super::{GilbertElliott, PrngLoss};
 
     #[test]
     fn pulsed() {
@@ -369,5 +472,27 @@ mod tests {
             .count();
         assert!(count <= 10);
     }
-}
+    #[test]
+    fn gilbert_elliott() {
+        const HALF: u32 = 1 << 31;
+        let never_recover = GilbertElliott::gilbert(u32::MAX, 0);
+        let mut prng = PrngLoss::gilbert_elliott(never_recover, 0);
+        assert!((0..100).all(|_| !prng.next_pass()));
+
+        // Losses come in bursts of two packets on average.
+        let model = GilbertElliott::gilbert(HALF >> 4, HALF);
+        let losses = |seed| {
+            let mut prng = PrngLoss::gilbert_elliott(model, seed);
+            (0..1000).map(|_| prng.next_pass()).collect::<Vec<_>>()
+        };
+
+        let first = losses(42);
+        assert_eq!(first, losses(42), "Same seed must produce the same losses");
+        assert_ne!(first, losses(43));
 
+        let lost = first.iter().filter(|&&pass| !pass).count();
+        let bursts = first.windows(2).filter(|w| w[0] && !w[1]).count();
+        assert!(lost > 20 && lost < 120, "Unexpected loss count {}", lost);
+        assert!(lost > bursts, "Losses should cluster");
+    }
+}
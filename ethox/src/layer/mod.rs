@@ -31,6 +31,9 @@
 //!   sender can then make use of the layer by borrowing it while supplying the handler for the
 //!   next upper layer.
 //!
+//! The [`stack`] module assembles the usual pipeline of ethernet, ip, and the transport layers
+//! with less boilerplate.
+//!
 //! ## Receiving
 //!
 //! Many layer implementations process packets by routing them to layers conceptually above them.
@@ -123,7 +126,7 @@
 //! zerocopy network stacks. There are some points to make either way but Rust's type system gives
 //! the former choice a sane interface even when the original pointer is placed within structures.
 //! It also works better with the trait design. You can still own the packet buffer but it requires
-//! an explicit method on behalf of the specific nic.
+//! an explicit method on behalf of the specific nic, see `managed::pool` for the common parts.
 //!
 //! Receiving not taking an acknowledgement, dropping packets afterwards if they are not used to
 //! answer. This also drops some packet buffers where filtered packets could be deinitialized and
@@ -132,12 +135,25 @@
 //! The limited vectorization support and duplicate device handles in nic batched rx/tx. Especially
 //! for sending there are overheads in route lookup etc. that could be avoided by batching packet.
 //! Might also save on capability information and timestamp queries.
+use crate::wire::IpAddress;
 
 pub mod arp;
+pub mod bridge;
+pub mod dhcpv6;
 pub mod eth;
+#[cfg(feature = "async")]
+pub mod futures;
 pub mod icmp;
 pub mod ip;
+pub mod ipfix;
 pub mod loss;
+pub mod ports;
+pub mod sctp;
+pub mod sflow;
+pub mod sntp;
+pub mod stack;
+pub mod syslog;
+pub mod tftp;
 pub mod udp;
 pub mod tcp;
 
@@ -176,12 +192,132 @@ pub enum Error {
     // TODO
 }
 
+/// The context of a failed operation, in addition to the kind of error.
+///
+/// Layers return only the plain `Error` so that results stay small and simple to match. Some
+/// endpoints additionally remember the context of their last failure, which tells apart the
+/// different causes of the same error. For example, an ip endpoint distinguishes a missing route
+/// from a neighbor that has not been resolved yet, which both fail with `Unreachable`. Failures on
+/// the receive path, such as a wrong checksum, are otherwise silent and only visible this way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Failure {
+    /// The error that was returned, or would have been for a dropped packet.
+    pub error: Error,
+
+    /// The layer in which the operation failed.
+    pub origin: Origin,
+
+    /// The operation that failed.
+    pub operation: Operation,
+
+    /// Protocol specific detail of the cause.
+    pub detail: Detail,
+}
+
+/// The layer in which an operation failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Origin {
+    /// The ethernet layer.
+    Eth,
+    /// The arp layer.
+    Arp,
+    /// The ip layer.
+    Ip,
+    /// The udp layer.
+    Udp,
+    /// The tcp layer.
+    Tcp,
+}
+
+/// The kind of operation that failed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// An incoming packet was parsed or dispatched.
+    Receive,
+    /// A route for an outgoing packet was looked up.
+    Route,
+    /// The link layer address of a next hop was looked up.
+    Resolve,
+    /// A received packet was forwarded.
+    Forward,
+    /// An outgoing packet was initialized or sent.
+    Send,
+}
+
+/// Protocol specific detail of a failure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Detail {
+    /// No further detail is available.
+    None,
+
+    /// A packet could not be parsed, for example due to a wrong checksum.
+    ///
+    /// The origin of the failure says which header was affected.
+    Wire(crate::wire::Error),
+
+    /// There is no route towards a destination.
+    NoRoute(IpAddress),
+
+    /// None of the own addresses can be used as the source towards a destination.
+    NoSource(IpAddress),
+
+    /// A requested source address is not assigned to the endpoint.
+    ForeignSource(IpAddress),
+
+    /// The link layer address of a next hop is not known yet.
+    ///
+    /// A lookup is pending if one could be started.
+    Unresolved(IpAddress),
+
+    /// The next hop could not be looked up as the neighbor cache is full.
+    NeighborCacheFull(IpAddress),
+
+    /// The hop limit of a forwarded packet was exhausted.
+    HopLimit,
+
+    /// The source address of a received packet is never valid.
+    Martian(IpAddress),
+
+    /// There is no valid reverse path to the source address of a received packet.
+    ReversePath(IpAddress),
+
+    /// The destination port of a datagram was not open.
+    ClosedPort(u16),
+
+    /// An outgoing packet would exceed the maximum transmission unit of the device.
+    ///
+    /// Holds the largest payload permitted in the layer of the failure. For example, a udp
+    /// datagram fails in the ip layer and may carry eight bytes less than this for its header.
+    Oversized {
+        /// The largest permitted payload length.
+        max: usize,
+    },
+}
+
 /// A standard wrapper for a function implementing receive or send traits.
 ///
 /// Keeps the type alias overhead low by providing a single wrapper type that implements the send
 /// and receive traits for all layers, where applicable.
 pub struct FnHandler<F>(pub F);
 
+impl Failure {
+    /// Describe the failure of an operation.
+    pub fn new(error: Error, origin: Origin, operation: Operation, detail: Detail) -> Self {
+        Failure {
+            error,
+            origin,
+            operation,
+            detail,
+        }
+    }
+}
+
+impl From<Failure> for Error {
+    fn from(failure: Failure) -> Self {
+        failure.error
+    }
+}
+
 /// Can convert from a wire error.
 ///
 /// This indicates some layer tried to operate on a packet but failed.
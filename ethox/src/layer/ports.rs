@@ -0,0 +1,170 @@
+//! A registry of the ports bound by the transport layers.
+//!
+//! The udp and tcp endpoints do not know of each other, and neither knows of other endpoints of
+//! the same protocol. A port bound twice by accident then silently routes datagrams or connection
+//! attempts to only one of the handlers. Sharing a [`Registry`] between the endpoints detects this
+//! when binding instead: a port can be reserved once per protocol, so binding udp 5000 and tcp
+//! 5000 works as intended while a second bind of udp 5000 fails with `Error::Illegal`.
+//!
+//! A udp endpoint reserves its open ports with [`udp::Endpoint::reserve_ports`]. The tcp endpoint
+//! keeps a shared reference to the registry in a `RefCell`, see [`tcp::Endpoint::set_registry`],
+//! as it also allocates ephemeral ports during its send phase. Clients of other protocols, such as
+//! a udp request, allocate their ephemeral ports with [`Registry::reserve_ephemeral`] so that they
+//! never collide with a bound port.
+//!
+//! [`Registry`]: struct.Registry.html
+//! [`Registry::reserve_ephemeral`]: struct.Registry.html#method.reserve_ephemeral
+//! [`tcp::Endpoint::set_registry`]: ../tcp/struct.Endpoint.html#method.set_registry
+//! [`udp::Endpoint::reserve_ports`]: ../udp/struct.Endpoint.html#method.reserve_ports
+use crate::managed::{List, Slice};
+use crate::rand::Rng;
+
+use super::{Error, Result};
+
+/// The transport protocol of a port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Transport {
+    /// A tcp port.
+    Tcp,
+    /// A udp port.
+    Udp,
+}
+
+/// One reserved port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Binding {
+    /// The protocol in which the port is reserved.
+    pub transport: Transport,
+    /// The reserved port.
+    pub port: u16,
+}
+
+/// The ports reserved in each transport protocol.
+///
+/// The reservations are kept in a list of fixed capacity, lookups are linear in its length.
+pub struct Registry<'a> {
+    bindings: List<'a, Binding>,
+    rng: Option<&'a mut dyn Rng>,
+    next_port: u16,
+}
+
+impl<'a> Registry<'a> {
+    /// The range of ephemeral ports, inclusive.
+    ///
+    /// This is the dynamic port range assigned by IANA.
+    pub const EPHEMERAL_PORTS: (u16, u16) = (49152, 65535);
+
+    /// Create an empty registry with storage for reservations.
+    pub fn new<S>(storage: S) -> Self
+        where S: Into<Slice<'a, Binding>>,
+    {
+        Registry {
+            bindings: List::new(storage.into()),
+            rng: None,
+            next_port: Self::EPHEMERAL_PORTS.0,
+        }
+    }
+
+    /// Set the random source for selecting ephemeral ports.
+    ///
+    /// Without a random source the ports are assigned sequentially.
+    pub fn set_rng(&mut self, rng: &'a mut dyn Rng) {
+        self.rng = Some(rng);
+    }
+
+    /// Reserve a port in one protocol.
+    ///
+    /// Fails with `Illegal` if the port is already reserved in the same protocol, and with
+    /// `Exhausted` if there is no space left for another reservation.
+    pub fn reserve(&mut self, transport: Transport, port: u16) -> Result<()> {
+        if self.is_reserved(transport, port) {
+            return Err(Error::Illegal);
+        }
+
+        let binding = self.bindings.push().ok_or(Error::Exhausted)?;
+        *binding = Binding { transport, port };
+        Ok(())
+    }
+
+    /// Reserve a free ephemeral port.
+    ///
+    /// Starts at a random offset, or after the last chosen port, and reserves the first port of
+    /// the range that is not reserved in the protocol yet.
+    pub fn reserve_ephemeral(&mut self, transport: Transport) -> Result<u16> {
+        let (first, last) = Self::EPHEMERAL_PORTS;
+        let count = u32::from(last - first) + 1;
+        let start = match &mut self.rng {
+            Some(rng) => (rng.next_u64() % u64::from(count)) as u32,
+            None => u32::from(self.next_port.max(first) - first),
+        };
+
+        let port = (0..count)
+            .map(|offset| first + ((start + offset) % count) as u16)
+            .find(|&port| !self.is_reserved(transport, port))
+            .ok_or(Error::Exhausted)?;
+
+        self.reserve(transport, port)?;
+        self.next_port = port.checked_add(1).unwrap_or(first);
+        Ok(port)
+    }
+
+    /// Release a reserved port, returning whether it had been reserved.
+    pub fn release(&mut self, transport: Transport, port: u16) -> bool {
+        let binding = Binding { transport, port };
+        match self.bindings.as_slice().iter().position(|&other| other == binding) {
+            Some(idx) => {
+                self.bindings.remove_at(idx);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Check if a port is reserved in a protocol.
+    pub fn is_reserved(&self, transport: Transport, port: u16) -> bool {
+        let binding = Binding { transport, port };
+        self.bindings.as_slice().contains(&binding)
+    }
+
+    /// All current reservations.
+    pub fn bindings(&self) -> &[Binding] {
+        self.bindings.as_slice()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn once_per_protocol() {
+        let mut storage = [Binding { transport: Transport::Tcp, port: 0 }; 4];
+        let mut registry = Registry::new(&mut storage[..]);
+
+        assert_eq!(registry.reserve(Transport::Udp, 5000), Ok(()));
+        assert_eq!(registry.reserve(Transport::Tcp, 5000), Ok(()));
+        assert_eq!(registry.reserve(Transport::Udp, 5000), Err(Error::Illegal));
+
+        assert!(registry.release(Transport::Udp, 5000));
+        assert!(!registry.release(Transport::Udp, 5000));
+        assert!(registry.is_reserved(Transport::Tcp, 5000));
+        assert_eq!(registry.reserve(Transport::Udp, 5000), Ok(()));
+
+        assert_eq!(registry.reserve(Transport::Udp, 53), Ok(()));
+        assert_eq!(registry.reserve(Transport::Udp, 54), Ok(()));
+        assert_eq!(registry.reserve(Transport::Udp, 55), Err(Error::Exhausted));
+    }
+
+    #[test]
+    fn ephemeral() {
+        let mut storage = [Binding { transport: Transport::Tcp, port: 0 }; 4];
+        let mut registry = Registry::new(&mut storage[..]);
+        let (first, _) = Registry::EPHEMERAL_PORTS;
+
+        registry.reserve(Transport::Udp, first + 1).unwrap();
+        assert_eq!(registry.reserve_ephemeral(Transport::Udp), Ok(first));
+        // The bound port is skipped.
+        assert_eq!(registry.reserve_ephemeral(Transport::Udp), Ok(first + 2));
+        assert_eq!(registry.reserve_ephemeral(Transport::Tcp), Ok(first + 3));
+    }
+}
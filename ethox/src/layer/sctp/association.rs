@@ -0,0 +1,780 @@
+use byteorder::{ByteOrder, NetworkEndian};
+
+use crate::layer::{ip, Error, FnHandler, Result};
+use crate::managed::Slice;
+use crate::rand::Rng;
+use crate::time::{Duration, Expiration, Instant};
+use crate::wire::{Checksum, IpAddress, IpProtocol, Payload, PayloadMut};
+use crate::wire::{sctp_chunk, sctp_packet, sctp_skip_unrecognized};
+use crate::wire::{SctpChunk, SctpInitParams, SctpRepr};
+
+use super::{Message, Recv};
+
+/// The initial retransmission timeout, rfc4960 section 15.
+const RTO_INITIAL: Duration = Duration::from_secs(3);
+
+/// The upper bound of the backed off retransmission timeout.
+const RTO_MAX: Duration = Duration::from_secs(60);
+
+/// The number of retransmissions after which the association is aborted.
+const MAX_RETRANSMISSIONS: u32 = 10;
+
+/// The receiver window credit that is advertised.
+///
+/// Data is delivered to the handler immediately, the window only bounds the burst of the peer.
+const RECEIVE_WINDOW: u32 = 0xffff;
+
+/// The longest heartbeat information that is answered.
+const MAX_HEARTBEAT_INFO: usize = 128;
+
+/// The length of the state cookie sent by a listening association.
+const COOKIE_LEN: usize = 16;
+
+/// The state of an association, rfc4960 section 4.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum State {
+    /// There is no association.
+    Closed,
+    /// Waiting for the initiation of a peer.
+    Listen,
+    /// The initiation was sent, waiting for its acknowledgment.
+    CookieWait,
+    /// The state cookie was echoed, waiting for its acknowledgment.
+    CookieEchoed,
+    /// Messages can be exchanged.
+    Established,
+    /// A shutdown was requested, waiting for the outstanding message to be acknowledged.
+    ShutdownPending,
+    /// The shutdown was sent, waiting for its acknowledgment.
+    ShutdownSent,
+    /// The peer requested a shutdown, waiting for the outstanding message to be acknowledged.
+    ShutdownReceived,
+    /// The shutdown of the peer was acknowledged, waiting for its completion.
+    ShutdownAckSent,
+}
+
+/// An SCTP association with a single peer.
+///
+/// The association owns a buffer for the outstanding message, which is also used for the state
+/// cookie of the peer while setting up an association. Its length bounds the size of messages.
+pub struct Association<'a> {
+    port: u16,
+    state: State,
+    /// The remote address and port of the association.
+    remote: Option<(IpAddress, u16)>,
+    /// The own address used by the association.
+    local_addr: Option<IpAddress>,
+    local_tag: u32,
+    peer_tag: u32,
+    /// The TSN of the next message to queue.
+    next_tsn: u32,
+    /// The last TSN of the peer received in order.
+    peer_tsn: u32,
+    stream_seq: u16,
+    /// The outstanding message, or the state cookie to echo.
+    buffer: Slice<'a, u8>,
+    buffered: usize,
+    ppid: u32,
+    outstanding: bool,
+    /// A pending setup while listening.
+    cookie: Option<Cookie>,
+    answer: Option<Answer>,
+    sack: bool,
+    heartbeat: [u8; MAX_HEARTBEAT_INFO],
+    heartbeat_len: usize,
+    timer: Timer,
+    retransmissions: u32,
+    aborted: bool,
+    rng: &'a mut dyn Rng,
+}
+
+/// An association borrowed for receiving.
+pub struct Receiver<'a, 'e, H> {
+    association: &'a mut Association<'e>,
+
+    /// The handler of received messages.
+    handler: H,
+}
+
+/// The state of a setup by a peer, remembered while listening.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Cookie {
+    local_tag: u32,
+    peer_tag: u32,
+    local_tsn: u32,
+    peer_tsn: u32,
+    remote: (IpAddress, u16),
+    local_addr: IpAddress,
+}
+
+/// A chunk answering the peer, sent once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Answer {
+    InitAck,
+    CookieAck,
+    HeartbeatAck,
+    ShutdownComplete,
+    Abort,
+}
+
+/// A chunk chosen to be sent next.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Output {
+    Answer(Answer),
+    Sack,
+    Init,
+    CookieEcho,
+    Data,
+    Shutdown,
+    ShutdownAck,
+}
+
+/// The retransmission timer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Timer {
+    /// Nothing to retransmit.
+    Idle,
+    /// The first transmission is due.
+    Due,
+    /// Retransmit at this time.
+    At(Instant),
+}
+
+impl<'a> Association<'a> {
+    /// Create a closed association on a local port.
+    ///
+    /// The random source provides the verification tags and initial sequence numbers, which
+    /// protect the association against off-path attackers. See the [`rand`] module on where to
+    /// get one.
+    ///
+    /// [`rand`]: ../../rand/index.html
+    pub fn new<S>(port: u16, buffer: S, rng: &'a mut dyn Rng) -> Self
+        where S: Into<Slice<'a, u8>>,
+    {
+        Association {
+            port,
+            state: State::Closed,
+            remote: None,
+            local_addr: None,
+            local_tag: 0,
+            peer_tag: 0,
+            next_tsn: 0,
+            peer_tsn: 0,
+            stream_seq: 0,
+            buffer: buffer.into(),
+            buffered: 0,
+            ppid: 0,
+            outstanding: false,
+            cookie: None,
+            answer: None,
+            sack: false,
+            heartbeat: [0; MAX_HEARTBEAT_INFO],
+            heartbeat_len: 0,
+            timer: Timer::Idle,
+            retransmissions: 0,
+            aborted: false,
+            rng,
+        }
+    }
+
+    /// The local port of the association.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// The current state of the association.
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// The remote address and port of the association, once known.
+    pub fn remote(&self) -> Option<(IpAddress, u16)> {
+        self.remote
+    }
+
+    /// Check if the association was closed by an abort or too many retransmissions.
+    pub fn is_aborted(&self) -> bool {
+        self.aborted
+    }
+
+    /// Wait for the setup of an association by a peer.
+    ///
+    /// Fails with `Illegal` unless the association is closed.
+    pub fn listen(&mut self) -> Result<()> {
+        if !matches!(self.state, State::Closed | State::Listen) {
+            return Err(Error::Illegal);
+        }
+
+        self.reset();
+        self.state = State::Listen;
+        Ok(())
+    }
+
+    /// Set up an association with a peer.
+    ///
+    /// The initiation is sent with the next packet buffer offered to the association. Fails with
+    /// `Illegal` unless the association is closed or listening.
+    pub fn connect(&mut self, local_addr: IpAddress, remote: IpAddress, port: u16) -> Result<()> {
+        if !matches!(self.state, State::Closed | State::Listen) {
+            return Err(Error::Illegal);
+        }
+
+        self.reset();
+        self.local_tag = self.random_tag();
+        self.next_tsn = self.rng.next_u64() as u32;
+        self.remote = Some((remote, port));
+        self.local_addr = Some(local_addr);
+        self.state = State::CookieWait;
+        self.timer = Timer::Due;
+        Ok(())
+    }
+
+    /// Queue a message on the stream.
+    ///
+    /// Only one message can be outstanding, fails with `Exhausted` until the previous one has
+    /// been acknowledged. Fails with `BadSize` if the message is empty or does not fit into the
+    /// buffer, and with `Illegal` if the association is not established. The message must also
+    /// fit into a single packet.
+    pub fn queue(&mut self, ppid: u32, data: &[u8]) -> Result<()> {
+        if self.state != State::Established {
+            return Err(Error::Illegal);
+        }
+
+        if self.outstanding {
+            return Err(Error::Exhausted);
+        }
+
+        if data.is_empty() || data.len() > self.buffer.len() {
+            return Err(Error::BadSize);
+        }
+
+        self.buffer[..data.len()].copy_from_slice(data);
+        self.buffered = data.len();
+        self.ppid = ppid;
+        self.outstanding = true;
+        self.next_tsn = self.next_tsn.wrapping_add(1);
+        self.timer = Timer::Due;
+        self.retransmissions = 0;
+        Ok(())
+    }
+
+    /// Check if a new message can be queued.
+    pub fn can_queue(&self) -> bool {
+        self.state == State::Established && !self.outstanding
+    }
+
+    /// Check if the last queued message has been acknowledged.
+    pub fn is_acknowledged(&self) -> bool {
+        !self.outstanding
+    }
+
+    /// Gracefully shut down the association.
+    ///
+    /// The outstanding message is still delivered, afterwards the shutdown is sent. Fails with
+    /// `Illegal` if the association is not established.
+    pub fn shutdown(&mut self) -> Result<()> {
+        if self.state != State::Established {
+            return Err(Error::Illegal);
+        }
+
+        self.state = State::ShutdownPending;
+        Ok(())
+    }
+
+    /// Abort the association.
+    ///
+    /// The abort is sent to the peer with the next packet buffer, if an association had been set
+    /// up far enough for the peer to know it.
+    pub fn abort(&mut self) {
+        self.fail();
+    }
+
+    /// Receive packets using this mutably borrowed association.
+    pub fn recv<H: Recv>(&mut self, handler: H) -> Receiver<'_, 'a, H> {
+        Receiver { association: self, handler }
+    }
+
+    /// Receive packets using this mutably borrowed association and a function.
+    pub fn recv_with<H>(&mut self, handler: H) -> Receiver<'_, 'a, FnHandler<H>>
+        where H: FnMut(Message)
+    {
+        self.recv(FnHandler(handler))
+    }
+
+    /// Get the time at which the association wants to send.
+    ///
+    /// Offer it a packet buffer at that point.
+    pub fn poll(&self, now: Instant) -> Expiration {
+        if self.answer.is_some() || self.sack || self.wants_shutdown().is_some() {
+            return Expiration::When(now);
+        }
+
+        match self.timer {
+            Timer::Idle => Expiration::Never,
+            Timer::Due => Expiration::When(now),
+            Timer::At(at) => Expiration::When(at),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.remote = None;
+        self.local_addr = None;
+        self.buffered = 0;
+        self.outstanding = false;
+        self.stream_seq = 0;
+        self.cookie = None;
+        self.answer = None;
+        self.sack = false;
+        self.timer = Timer::Idle;
+        self.retransmissions = 0;
+        self.aborted = false;
+    }
+
+    /// A random verification tag, which must not be zero.
+    fn random_tag(&mut self) -> u32 {
+        (self.rng.next_u64() as u32).max(1)
+    }
+
+    /// Close the association, notifying the peer if it knows the association.
+    fn fail(&mut self) {
+        if !matches!(self.state, State::Closed | State::Listen | State::CookieWait) {
+            self.answer = Some(Answer::Abort);
+        }
+        self.close();
+        self.aborted = true;
+    }
+
+    fn close(&mut self) {
+        self.state = State::Closed;
+        self.outstanding = false;
+        self.buffered = 0;
+        self.cookie = None;
+        self.sack = false;
+        self.timer = Timer::Idle;
+    }
+
+    /// The current retransmission timeout, backed off exponentially.
+    fn rto(&self) -> Duration {
+        let backoff = 1u32 << self.retransmissions.min(5);
+        (RTO_INITIAL * backoff).min(RTO_MAX)
+    }
+
+    /// A shutdown chunk that can be sent now that no message is outstanding.
+    fn wants_shutdown(&self) -> Option<Output> {
+        match self.state {
+            _ if self.outstanding => None,
+            State::ShutdownPending => Some(Output::Shutdown),
+            State::ShutdownReceived => Some(Output::ShutdownAck),
+            _ => None,
+        }
+    }
+
+    /// The tsn of the outstanding message.
+    fn data_tsn(&self) -> u32 {
+        self.next_tsn.wrapping_sub(1)
+    }
+
+    fn acknowledge(&mut self, cumulative_tsn: u32) {
+        if self.outstanding && cumulative_tsn == self.data_tsn() {
+            self.outstanding = false;
+            self.buffered = 0;
+            self.stream_seq = self.stream_seq.wrapping_add(1);
+            self.timer = Timer::Idle;
+            self.retransmissions = 0;
+        }
+    }
+
+    /// Handle one chunk of a received packet, returning whether to process the next one.
+    fn process<H: Recv>(
+        &mut self,
+        chunk: SctpChunk,
+        tag: u32,
+        from: (IpAddress, u16),
+        local_addr: IpAddress,
+        handler: &mut H,
+    ) -> bool {
+        match chunk {
+            // Must be the only chunk of its packet.
+            SctpChunk::Init(params) => {
+                self.on_init(params, tag, from, local_addr);
+                return false;
+            },
+            SctpChunk::CookieEcho { cookie } => return self.on_cookie_echo(cookie, tag, from),
+            _ => (),
+        }
+
+        if matches!(self.state, State::Closed | State::Listen) || self.remote != Some(from) {
+            return false;
+        }
+
+        let expected = match chunk {
+            SctpChunk::Abort { reflected: true }
+            | SctpChunk::ShutdownComplete { reflected: true } => self.peer_tag,
+            _ => self.local_tag,
+        };
+
+        if tag != expected {
+            return false;
+        }
+
+        match chunk {
+            SctpChunk::InitAck { params, cookie } if self.state == State::CookieWait => {
+                if params.initiate_tag == 0 || cookie.len() > self.buffer.len() {
+                    self.fail();
+                    return false;
+                }
+
+                self.buffer[..cookie.len()].copy_from_slice(cookie);
+                self.buffered = cookie.len();
+                self.peer_tag = params.initiate_tag;
+                self.peer_tsn = params.initial_tsn.wrapping_sub(1);
+                self.state = State::CookieEchoed;
+                self.timer = Timer::Due;
+                self.retransmissions = 0;
+            },
+            SctpChunk::CookieAck if self.state == State::CookieEchoed => {
+                self.state = State::Established;
+                self.buffered = 0;
+                self.timer = Timer::Idle;
+                self.retransmissions = 0;
+            },
+            SctpChunk::Data { tsn, ppid, begin, end, data, .. } => match self.state {
+                State::Established | State::ShutdownPending | State::ShutdownSent => {
+                    // Duplicates are acknowledged again, out of order chunks are dropped.
+                    self.sack = true;
+                    if tsn == self.peer_tsn.wrapping_add(1) {
+                        self.peer_tsn = tsn;
+                        handler.receive(Message { ppid, begin, end, data });
+                    }
+                },
+                _ => (),
+            },
+            SctpChunk::Sack { cumulative_tsn, .. } => self.acknowledge(cumulative_tsn),
+            SctpChunk::Heartbeat { info }
+                if info.len() <= MAX_HEARTBEAT_INFO && self.answer.is_none() =>
+            {
+                self.heartbeat[..info.len()].copy_from_slice(info);
+                self.heartbeat_len = info.len();
+                self.answer = Some(Answer::HeartbeatAck);
+            },
+            SctpChunk::Abort { .. } => {
+                self.close();
+                self.aborted = true;
+                return false;
+            },
+            SctpChunk::Shutdown { cumulative_tsn } => {
+                self.acknowledge(cumulative_tsn);
+                match self.state {
+                    State::Established | State::ShutdownPending => {
+                        self.state = State::ShutdownReceived;
+                    },
+                    // Both sides started the shutdown.
+                    State::ShutdownSent => {
+                        self.state = State::ShutdownAckSent;
+                        self.timer = Timer::Due;
+                        self.retransmissions = 0;
+                    },
+                    _ => (),
+                }
+            },
+            SctpChunk::ShutdownAck => match self.state {
+                State::ShutdownSent | State::ShutdownAckSent => {
+                    self.close();
+                    self.answer = Some(Answer::ShutdownComplete);
+                    return false;
+                },
+                _ => (),
+            },
+            SctpChunk::ShutdownComplete { .. } if self.state == State::ShutdownAckSent => {
+                self.close();
+                return false;
+            },
+            // Unexpected in the current state, or purely informational.
+            _ => (),
+        }
+
+        true
+    }
+
+    fn on_init(
+        &mut self,
+        params: SctpInitParams,
+        tag: u32,
+        remote: (IpAddress, u16),
+        local_addr: IpAddress,
+    ) {
+        if self.state != State::Listen || tag != 0 || params.initiate_tag == 0 {
+            return;
+        }
+
+        self.cookie = Some(Cookie {
+            local_tag: self.random_tag(),
+            peer_tag: params.initiate_tag,
+            local_tsn: self.rng.next_u64() as u32,
+            peer_tsn: params.initial_tsn,
+            remote,
+            local_addr,
+        });
+        self.answer = Some(Answer::InitAck);
+    }
+
+    fn on_cookie_echo(&mut self, echoed: &[u8], tag: u32, from: (IpAddress, u16)) -> bool {
+        match self.state {
+            State::Listen => (),
+            // The acknowledgment was lost, answer again.
+            State::Established if self.remote == Some(from) && tag == self.local_tag => {
+                self.answer = Some(Answer::CookieAck);
+                return true;
+            },
+            _ => return false,
+        }
+
+        let cookie = match self.cookie {
+            Some(cookie) if cookie.remote == from && cookie.local_tag == tag => cookie,
+            _ => return false,
+        };
+
+        if echoed != cookie.to_bytes() {
+            return false;
+        }
+
+        self.cookie = None;
+        self.local_tag = cookie.local_tag;
+        self.peer_tag = cookie.peer_tag;
+        self.next_tsn = cookie.local_tsn;
+        self.peer_tsn = cookie.peer_tsn.wrapping_sub(1);
+        self.remote = Some(cookie.remote);
+        self.local_addr = Some(cookie.local_addr);
+        self.state = State::Established;
+        self.answer = Some(Answer::CookieAck);
+        true
+    }
+
+    /// Choose the chunk to send next.
+    fn select(&mut self, now: Instant) -> Option<Output> {
+        if let Some(answer) = self.answer {
+            return Some(Output::Answer(answer));
+        }
+
+        if self.sack {
+            return Some(Output::Sack);
+        }
+
+        if let Some(shutdown) = self.wants_shutdown() {
+            return Some(shutdown);
+        }
+
+        match self.timer {
+            Timer::Idle => return None,
+            Timer::Due => (),
+            Timer::At(at) if at > now => return None,
+            Timer::At(_) => {
+                if self.retransmissions >= MAX_RETRANSMISSIONS {
+                    self.fail();
+                    return self.answer.map(Output::Answer);
+                }
+                self.retransmissions += 1;
+            },
+        }
+
+        match self.state {
+            State::CookieWait => Some(Output::Init),
+            State::CookieEchoed => Some(Output::CookieEcho),
+            State::Established | State::ShutdownPending | State::ShutdownReceived
+                if self.outstanding => Some(Output::Data),
+            State::ShutdownSent => Some(Output::Shutdown),
+            State::ShutdownAckSent => Some(Output::ShutdownAck),
+            _ => {
+                self.timer = Timer::Idle;
+                None
+            },
+        }
+    }
+
+    /// Update the state after a chunk was sent, or lost on its way.
+    fn sent(&mut self, output: Output, now: Instant) {
+        match output {
+            Output::Answer(_) => self.answer = None,
+            Output::Sack => self.sack = false,
+            Output::Shutdown | Output::ShutdownAck if self.timer == Timer::Idle => {
+                // The first transmission, which starts the timer.
+                self.state = match output {
+                    Output::Shutdown => State::ShutdownSent,
+                    _ => State::ShutdownAckSent,
+                };
+                self.retransmissions = 0;
+                self.timer = Timer::At(now + self.rto());
+            },
+            _ => self.timer = Timer::At(now + self.rto()),
+        }
+    }
+
+    fn send_output<P: PayloadMut>(&self, raw: ip::RawPacket<P>, output: Output) -> Result<()> {
+        let mut cookie_bytes = [0; COOKIE_LEN];
+        let (remote, local_addr) = match (output, self.cookie) {
+            (Output::Answer(Answer::InitAck), Some(cookie)) => {
+                cookie_bytes = cookie.to_bytes();
+                (cookie.remote, cookie.local_addr)
+            },
+            _ => match (self.remote, self.local_addr) {
+                (Some(remote), Some(local_addr)) => (remote, local_addr),
+                _ => return Err(Error::Illegal),
+            },
+        };
+
+        let mut tag = self.peer_tag;
+        let chunk = match output {
+            Output::Answer(Answer::InitAck) => {
+                let cookie = self.cookie.ok_or(Error::Illegal)?;
+                tag = cookie.peer_tag;
+                SctpChunk::InitAck {
+                    params: SctpInitParams {
+                        initiate_tag: cookie.local_tag,
+                        a_rwnd: RECEIVE_WINDOW,
+                        outbound_streams: 1,
+                        inbound_streams: 1,
+                        initial_tsn: cookie.local_tsn,
+                    },
+                    cookie: &cookie_bytes,
+                }
+            },
+            Output::Answer(Answer::CookieAck) => SctpChunk::CookieAck,
+            Output::Answer(Answer::HeartbeatAck) => SctpChunk::HeartbeatAck {
+                info: &self.heartbeat[..self.heartbeat_len],
+            },
+            Output::Answer(Answer::ShutdownComplete) => {
+                SctpChunk::ShutdownComplete { reflected: false }
+            },
+            Output::Answer(Answer::Abort) => SctpChunk::Abort { reflected: false },
+            Output::Sack => SctpChunk::Sack {
+                cumulative_tsn: self.peer_tsn,
+                a_rwnd: RECEIVE_WINDOW,
+            },
+            Output::Init => {
+                tag = 0;
+                SctpChunk::Init(SctpInitParams {
+                    initiate_tag: self.local_tag,
+                    a_rwnd: RECEIVE_WINDOW,
+                    outbound_streams: 1,
+                    inbound_streams: 1,
+                    initial_tsn: self.next_tsn,
+                })
+            },
+            Output::CookieEcho => SctpChunk::CookieEcho {
+                cookie: &self.buffer[..self.buffered],
+            },
+            Output::Data => SctpChunk::Data {
+                tsn: self.data_tsn(),
+                stream: 0,
+                stream_seq: self.stream_seq,
+                ppid: self.ppid,
+                unordered: false,
+                begin: true,
+                end: true,
+                data: &self.buffer[..self.buffered],
+            },
+            Output::Shutdown => SctpChunk::Shutdown { cumulative_tsn: self.peer_tsn },
+            Output::ShutdownAck => SctpChunk::ShutdownAck,
+        };
+
+        let repr = SctpRepr {
+            src_port: self.port,
+            dst_port: remote.1,
+            verification_tag: tag,
+        };
+
+        let init = ip::Init {
+            source: ip::Source::Exact(local_addr),
+            dst_addr: remote.0,
+            protocol: IpProtocol::Sctp,
+            payload: repr.buffer_len() + chunk.padded_len(),
+            hop_limit: None,
+            dscp: 0,
+        };
+
+        let mut out = raw.prepare(init)?;
+        let packet = sctp_packet::new_unchecked_mut(out.payload_mut_slice());
+        repr.emit(packet);
+        chunk.emit(sctp_chunk::new_unchecked_mut(packet.chunks_mut_slice()));
+        packet.fill_checksum();
+        out.send()
+    }
+}
+
+impl Cookie {
+    fn to_bytes(self) -> [u8; COOKIE_LEN] {
+        let mut bytes = [0; COOKIE_LEN];
+        NetworkEndian::write_u32(&mut bytes[0..4], self.local_tag);
+        NetworkEndian::write_u32(&mut bytes[4..8], self.peer_tag);
+        NetworkEndian::write_u32(&mut bytes[8..12], self.local_tsn);
+        NetworkEndian::write_u32(&mut bytes[12..16], self.peer_tsn);
+        bytes
+    }
+}
+
+impl<P, H> ip::Recv<P> for Receiver<'_, '_, H>
+where
+    P: Payload,
+    H: Recv,
+{
+    fn receive(&mut self, ip::InPacket { packet, .. }: ip::InPacket<P>) {
+        let ip_repr = packet.repr();
+        if ip_repr.protocol() != IpProtocol::Sctp {
+            return;
+        }
+
+        let sctp = match sctp_packet::new_checked(packet.payload().as_slice()) {
+            Ok(sctp) => sctp,
+            Err(_) => return,
+        };
+
+        // Devices do not offload the checksum of SCTP.
+        let repr = match SctpRepr::parse(sctp, Checksum::Manual) {
+            Ok(repr) => repr,
+            Err(_) => return,
+        };
+
+        if repr.dst_port != self.association.port || sctp.chunks().next().is_none() {
+            return;
+        }
+
+        let from = (ip_repr.src_addr(), repr.src_port);
+        for chunk in sctp.chunks() {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(_) => return,
+            };
+
+            let parsed = match SctpChunk::parse(chunk) {
+                Ok(parsed) => parsed,
+                Err(crate::wire::Error::Unrecognized)
+                    if sctp_skip_unrecognized(chunk.chunk_type()) => continue,
+                Err(_) => return,
+            };
+
+            let tag = repr.verification_tag;
+            let local_addr = ip_repr.dst_addr();
+            if !self.association.process(parsed, tag, from, local_addr, &mut self.handler) {
+                return;
+            }
+        }
+    }
+
+    fn accepts_protocol(&self, protocol: IpProtocol) -> bool {
+        protocol == IpProtocol::Sctp
+    }
+}
+
+impl<P: PayloadMut> ip::Send<P> for Association<'_> {
+    fn send(&mut self, raw: ip::RawPacket<P>) {
+        let now = raw.handle.info().timestamp();
+        let output = match self.select(now) {
+            Some(output) => output,
+            None => return,
+        };
+
+        // A failed send is treated like a packet lost in the network.
+        let _ = self.send_output(raw, output);
+        self.sent(output, now);
+    }
+}
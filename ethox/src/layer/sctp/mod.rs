@@ -0,0 +1,80 @@
+//! A minimal SCTP layer, rfc4960.
+//!
+//! Some industrial and telecom protocols run on top of SCTP instead of tcp. This layer provides a
+//! single [`Association`] with one stream in each direction, which is enough to talk to a peer
+//! that exchanges messages on a single stream. The association is set up with the four-way
+//! handshake of INIT and a state cookie, exchanges DATA chunks that are acknowledged with
+//! selective acknowledgments, and is closed with a graceful shutdown or an abort.
+//!
+//! The association sits directly on top of the ip layer. Received messages are handed to a
+//! [`Recv`] handler while borrowing the association with [`Association::recv`]. The association
+//! implements the sending side itself: queue a message with [`Association::queue`] and offer it
+//! a packet buffer whenever [`Association::poll`] says it wants to send.
+//!
+//! Many features of the standard are not supported:
+//!
+//! * Only one message is in flight at any time and it must fit into a single packet, there is no
+//!   fragmentation of large messages and no congestion control beyond that.
+//! * Received chunks are delivered immediately in order of their TSN. Chunks that arrive out of
+//!   order are dropped and later retransmitted by the peer, so no gap ack blocks are reported.
+//! * The association has a single remote address, multi-homing is not supported and no
+//!   heartbeats are sent. Heartbeats of the peer are answered.
+//! * The retransmission timeout starts at the initial value of three seconds and is only backed
+//!   off, round trip times are not measured.
+//! * A listening association keeps the state of a pending setup itself instead of signing it into
+//!   the state cookie, and answers a single pending setup at a time.
+//!
+//! [`Association`]: struct.Association.html
+//! [`Association::recv`]: struct.Association.html#method.recv
+//! [`Association::queue`]: struct.Association.html#method.queue
+//! [`Association::poll`]: struct.Association.html#method.poll
+//! [`Recv`]: trait.Recv.html
+use crate::layer::FnHandler;
+
+mod association;
+#[cfg(test)]
+mod tests;
+
+pub use association::{
+    Association,
+    Receiver,
+    State,
+};
+
+/// A message received on an association.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Message<'a> {
+    /// The payload protocol identifier chosen by the sender, opaque to SCTP.
+    pub ppid: u32,
+    /// The chunk contains the first fragment of a message.
+    pub begin: bool,
+    /// The chunk contains the last fragment of a message.
+    ///
+    /// Messages that the peer did not fragment have both flags set. Fragments are delivered in
+    /// order and can be reassembled by the handler.
+    pub end: bool,
+    /// The user data.
+    pub data: &'a [u8],
+}
+
+/// An SCTP receiver.
+///
+/// Processes the messages received on an association.
+pub trait Recv {
+    /// Inspect one received message.
+    fn receive(&mut self, message: Message);
+}
+
+impl<C: Recv> Recv for &'_ mut C {
+    fn receive(&mut self, message: Message) {
+        (**self).receive(message)
+    }
+}
+
+impl<F> Recv for FnHandler<F>
+    where F: FnMut(Message)
+{
+    fn receive(&mut self, message: Message) {
+        self.0(message)
+    }
+}
@@ -0,0 +1,231 @@
+use super::*;
+use crate::layer::{arp, eth, ip, Error};
+use crate::layer::loss::Xoroshiro256;
+use crate::nic::{external::External, Device};
+use crate::time::{Duration, Expiration, Instant};
+use crate::wire::{EthernetAddress, IpCidr, Ipv4Address};
+use crate::wire::{ethernet_frame, ipv4_packet, sctp_packet, SctpChunk};
+
+const MAC_ADDR_CLIENT: EthernetAddress = EthernetAddress([0x52, 0x54, 0, 0, 0, 1]);
+const MAC_ADDR_SERVER: EthernetAddress = EthernetAddress([0x52, 0x54, 0, 0, 0, 2]);
+const IP_ADDR_CLIENT: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
+const IP_ADDR_SERVER: Ipv4Address = Ipv4Address::new(10, 0, 0, 2);
+const CLIENT_PORT: u16 = 40000;
+const SERVER_PORT: u16 = 2905;
+
+/// The layers of one host below its association.
+struct Host<'a> {
+    eth: eth::Endpoint<'a>,
+    ip: ip::Endpoint<'a>,
+    nic: External<Vec<Vec<u8>>>,
+    received: Vec<Vec<u8>>,
+}
+
+impl Host<'_> {
+    fn new(mac: EthernetAddress, addr: Ipv4Address, peer: (EthernetAddress, Ipv4Address))
+        -> Self
+    {
+        let mut neighbors = arp::NeighborCache::new(vec![arp::Neighbor::default(); 1]);
+        neighbors.fill(peer.1.into(), peer.0, None).unwrap();
+        Host {
+            eth: eth::Endpoint::new(mac),
+            ip: ip::Endpoint::new(
+                IpCidr::new(addr.into(), 24),
+                ip::Routes::new(vec![ip::Route::unspecified(); 1]),
+                neighbors),
+            nic: External::new_send(vec![vec![0; 128]]),
+            received: Vec::new(),
+        }
+    }
+
+    fn set_time(&mut self, time: Instant) {
+        self.nic.set_current_time(time);
+    }
+
+    /// Offer a packet buffer to the association, returning the sent frame.
+    fn send(&mut self, association: &mut Association) -> Option<Vec<u8>> {
+        self.nic.send_all();
+        let sent = self.nic.tx(1, self.eth.send(self.ip.send(association))).unwrap();
+        if sent == 0 {
+            None
+        } else {
+            Some(self.nic.get(0).unwrap().clone())
+        }
+    }
+
+    fn receive(&mut self, association: &mut Association, frame: Vec<u8>) {
+        let Host { eth, ip, nic, received } = self;
+        *nic.get_mut(0).unwrap() = frame;
+        nic.receive_all();
+        let handler = association.recv_with(|message: Message| {
+            assert!(message.begin && message.end);
+            received.push(message.data.to_vec());
+        });
+        assert_eq!(nic.rx(1, eth.recv(ip.recv(handler))), Ok(1));
+    }
+}
+
+/// The chunks of a sent frame.
+fn chunk_types(frame: &[u8]) -> Vec<crate::wire::SctpChunkType> {
+    let eth = ethernet_frame::new_checked(frame).unwrap();
+    let ip = ipv4_packet::new_checked(eth.payload_slice()).unwrap();
+    let sctp = sctp_packet::new_checked(ip.payload_slice()).unwrap();
+    assert!(sctp.verify_checksum());
+    sctp.chunks()
+        .map(|chunk| SctpChunk::parse(chunk.unwrap()).unwrap().chunk_type())
+        .collect()
+}
+
+/// Pass packets between the hosts until neither wants to send.
+fn exchange(
+    client: (&mut Host, &mut Association),
+    server: (&mut Host, &mut Association),
+) -> usize {
+    let (client, client_assoc) = client;
+    let (server, server_assoc) = server;
+    let mut count = 0;
+    loop {
+        let mut idle = true;
+        if let Some(frame) = client.send(client_assoc) {
+            server.receive(server_assoc, frame);
+            idle = false;
+            count += 1;
+        }
+        if let Some(frame) = server.send(server_assoc) {
+            client.receive(client_assoc, frame);
+            idle = false;
+            count += 1;
+        }
+        if idle {
+            return count;
+        }
+    }
+}
+
+#[test]
+fn association() {
+    let mut client_rng = Xoroshiro256::new(1);
+    let mut server_rng = Xoroshiro256::new(2);
+    let mut client_assoc = Association::new(CLIENT_PORT, vec![0; 64], &mut client_rng);
+    let mut server_assoc = Association::new(SERVER_PORT, vec![0; 64], &mut server_rng);
+    let mut client = Host::new(MAC_ADDR_CLIENT, IP_ADDR_CLIENT, (MAC_ADDR_SERVER, IP_ADDR_SERVER));
+    let mut server = Host::new(MAC_ADDR_SERVER, IP_ADDR_SERVER, (MAC_ADDR_CLIENT, IP_ADDR_CLIENT));
+
+    server_assoc.listen().unwrap();
+    client_assoc.connect(IP_ADDR_CLIENT.into(), IP_ADDR_SERVER.into(), SERVER_PORT).unwrap();
+    assert_eq!(client_assoc.queue(0, b"early"), Err(Error::Illegal));
+
+    // INIT, INIT-ACK, COOKIE-ECHO, COOKIE-ACK.
+    let init = client.send(&mut client_assoc).unwrap();
+    assert_eq!(chunk_types(&init), [crate::wire::SctpChunkType::Init]);
+    server.receive(&mut server_assoc, init);
+    assert_eq!(server_assoc.state(), State::Listen);
+    assert_eq!(exchange((&mut client, &mut client_assoc), (&mut server, &mut server_assoc)), 3);
+    assert_eq!(client_assoc.state(), State::Established);
+    assert_eq!(server_assoc.state(), State::Established);
+    assert_eq!(server_assoc.remote(), Some((IP_ADDR_CLIENT.into(), CLIENT_PORT)));
+
+    // DATA and SACK in both directions.
+    client_assoc.queue(51, b"request").unwrap();
+    assert_eq!(client_assoc.queue(51, b"another"), Err(Error::Exhausted));
+    assert_eq!(exchange((&mut client, &mut client_assoc), (&mut server, &mut server_assoc)), 2);
+    assert!(client_assoc.is_acknowledged());
+    assert_eq!(server.received, [b"request".to_vec()]);
+
+    server_assoc.queue(51, b"response").unwrap();
+    assert_eq!(exchange((&mut client, &mut client_assoc), (&mut server, &mut server_assoc)), 2);
+    assert!(server_assoc.can_queue());
+    assert_eq!(client.received, [b"response".to_vec()]);
+
+    // SHUTDOWN, SHUTDOWN-ACK, SHUTDOWN-COMPLETE.
+    client_assoc.shutdown().unwrap();
+    assert_eq!(client_assoc.queue(51, b"late"), Err(Error::Illegal));
+    assert_eq!(exchange((&mut client, &mut client_assoc), (&mut server, &mut server_assoc)), 3);
+    assert_eq!(client_assoc.state(), State::Closed);
+    assert_eq!(server_assoc.state(), State::Closed);
+    assert!(!client_assoc.is_aborted());
+    assert!(!server_assoc.is_aborted());
+}
+
+#[test]
+fn retransmit() {
+    let mut client_rng = Xoroshiro256::new(3);
+    let mut server_rng = Xoroshiro256::new(4);
+    let mut client_assoc = Association::new(CLIENT_PORT, vec![0; 64], &mut client_rng);
+    let mut server_assoc = Association::new(SERVER_PORT, vec![0; 64], &mut server_rng);
+    let mut client = Host::new(MAC_ADDR_CLIENT, IP_ADDR_CLIENT, (MAC_ADDR_SERVER, IP_ADDR_SERVER));
+    let mut server = Host::new(MAC_ADDR_SERVER, IP_ADDR_SERVER, (MAC_ADDR_CLIENT, IP_ADDR_CLIENT));
+
+    server_assoc.listen().unwrap();
+    client_assoc.connect(IP_ADDR_CLIENT.into(), IP_ADDR_SERVER.into(), SERVER_PORT).unwrap();
+    exchange((&mut client, &mut client_assoc), (&mut server, &mut server_assoc));
+    assert_eq!(client_assoc.state(), State::Established);
+
+    // The first transmission of the message is lost.
+    client_assoc.queue(0, b"lost").unwrap();
+    assert!(client.send(&mut client_assoc).is_some());
+    assert_eq!(client.send(&mut client_assoc), None);
+    let retransmit_at = Instant::from_secs(3);
+    assert_eq!(client_assoc.poll(Instant::ZERO), Expiration::When(retransmit_at));
+
+    client.set_time(retransmit_at);
+    let data = client.send(&mut client_assoc).unwrap();
+    assert_eq!(chunk_types(&data), [crate::wire::SctpChunkType::Data]);
+    server.receive(&mut server_assoc, data.clone());
+    // A duplicate is acknowledged but not delivered again.
+    server.receive(&mut server_assoc, data);
+    exchange((&mut client, &mut client_assoc), (&mut server, &mut server_assoc));
+    assert_eq!(server.received, [b"lost".to_vec()]);
+    assert!(client_assoc.is_acknowledged());
+    assert_eq!(client_assoc.poll(retransmit_at), Expiration::Never);
+
+    // The peer disappears, the association is aborted eventually.
+    client_assoc.queue(0, b"unanswered").unwrap();
+    let (mut now, mut sent) = (retransmit_at, 0);
+    let mut last = None;
+    while client_assoc.state() == State::Established {
+        client.set_time(now);
+        last = client.send(&mut client_assoc);
+        now += Duration::from_secs(60);
+        sent += 1;
+    }
+    // The first transmission and ten retransmissions, then the abort.
+    assert_eq!(sent, 12);
+    assert!(client_assoc.is_aborted());
+
+    // The abort closes the association of the server.
+    let abort = last.unwrap();
+    assert_eq!(chunk_types(&abort), [crate::wire::SctpChunkType::Abort]);
+    server.receive(&mut server_assoc, abort);
+    assert_eq!(server_assoc.state(), State::Closed);
+    assert!(server_assoc.is_aborted());
+}
+
+#[test]
+fn wrong_tag() {
+    let mut client_rng = Xoroshiro256::new(5);
+    let mut server_rng = Xoroshiro256::new(6);
+    let mut client_assoc = Association::new(CLIENT_PORT, vec![0; 64], &mut client_rng);
+    let mut server_assoc = Association::new(SERVER_PORT, vec![0; 64], &mut server_rng);
+    let mut client = Host::new(MAC_ADDR_CLIENT, IP_ADDR_CLIENT, (MAC_ADDR_SERVER, IP_ADDR_SERVER));
+    let mut server = Host::new(MAC_ADDR_SERVER, IP_ADDR_SERVER, (MAC_ADDR_CLIENT, IP_ADDR_CLIENT));
+
+    server_assoc.listen().unwrap();
+    client_assoc.connect(IP_ADDR_CLIENT.into(), IP_ADDR_SERVER.into(), SERVER_PORT).unwrap();
+    exchange((&mut client, &mut client_assoc), (&mut server, &mut server_assoc));
+
+    // An abort with a guessed verification tag is ignored.
+    client_assoc.abort();
+    let mut abort = client.send(&mut client_assoc).unwrap();
+    {
+        let eth = ethernet_frame::new_unchecked_mut(&mut abort);
+        let ip = ipv4_packet::new_unchecked_mut(eth.payload_mut_slice());
+        let sctp = sctp_packet::new_unchecked_mut(ip.payload_mut_slice());
+        let tag = sctp.verification_tag();
+        sctp.set_verification_tag(tag ^ 1);
+        sctp.fill_checksum();
+    }
+    server.receive(&mut server_assoc, abort);
+    assert_eq!(server_assoc.state(), State::Established);
+    assert!(client_assoc.is_aborted());
+}
@@ -0,0 +1,553 @@
+use crate::layer::{eth, udp, Error, FnHandler, Result};
+use crate::rand::Rng;
+use crate::storage::ByteRing;
+use crate::time::{Clock, Duration, Expiration, Instant};
+use crate::wire::{EthernetAddress, IpAddress, Payload, PayloadMut};
+
+/// The well-known port of sFlow collectors.
+pub const SFLOW_PORT: u16 = 6343;
+
+/// The version of the datagram format, sFlow version 5.
+const VERSION: u32 = 5;
+
+/// The longest header of a sampled frame that is exported.
+const MAX_HEADER: usize = 256;
+
+/// The fixed part of a flow sample with one raw packet header record.
+const FLOW_SAMPLE_LEN: usize = 8 + 32 + 8 + 16;
+
+/// The longest flow sample that is queued.
+const MAX_SAMPLE: usize = FLOW_SAMPLE_LEN + MAX_HEADER;
+
+/// The length of the generic interface counters record.
+const GENERIC_COUNTERS_LEN: usize = 88;
+
+/// A counter sample with the generic interface counters as its only record.
+const COUNTER_SAMPLE_LEN: usize = 8 + 12 + 8 + GENERIC_COUNTERS_LEN;
+
+/// The longest datagram header, with an IPv6 agent address.
+const MAX_DATAGRAM_HEADER: usize = 28 + 12;
+
+/// Formats of samples and records, all of the standard enterprise.
+const FORMAT_FLOW_SAMPLE: u32 = 1;
+const FORMAT_COUNTER_SAMPLE: u32 = 2;
+const FORMAT_RAW_HEADER: u32 = 1;
+const FORMAT_GENERIC_COUNTERS: u32 = 1;
+
+/// The header protocol of ethernet frames, `ETHERNET-ISO88023`.
+const HEADER_ETHERNET: u32 = 1;
+
+/// The interface type of ethernet, `ethernetCsmacd`.
+const IF_TYPE_ETHERNET: u32 = 6;
+
+/// The interface counters exported in counter samples.
+///
+/// The sampling receiver counts all ingress octets and packets by itself. The remaining fields are
+/// not visible to the agent, fill them from the statistics of the device or of other endpoints
+/// with [`Agent::counters_mut`]. Counters wrap around just like their SNMP equivalents.
+///
+/// [`Agent::counters_mut`]: struct.Agent.html#method.counters_mut
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Counters {
+    /// The speed of the interface in bits per second, zero if unknown.
+    pub speed: u64,
+    /// The number of received octets, including the ethernet header.
+    pub in_octets: u64,
+    /// The number of received frames to a unicast address.
+    pub in_unicast: u32,
+    /// The number of received frames to a multicast address.
+    pub in_multicast: u32,
+    /// The number of received frames to the broadcast address.
+    pub in_broadcast: u32,
+    /// The number of received frames discarded without an error.
+    pub in_discards: u32,
+    /// The number of received frames discarded due to an error.
+    pub in_errors: u32,
+    /// The number of received frames of an unknown protocol.
+    pub in_unknown_protos: u32,
+    /// The number of sent octets.
+    pub out_octets: u64,
+    /// The number of sent frames to a unicast address.
+    pub out_unicast: u32,
+    /// The number of sent frames to a multicast address.
+    pub out_multicast: u32,
+    /// The number of sent frames to the broadcast address.
+    pub out_broadcast: u32,
+    /// The number of frames discarded before sending without an error.
+    pub out_discards: u32,
+    /// The number of frames that could not be sent due to an error.
+    pub out_errors: u32,
+}
+
+/// Samples ingress frames of one interface and exports them to a collector.
+///
+/// Sampled frames are encoded into the queue immediately and leave with the next datagram. When
+/// the queue is full further samples are dropped and counted, the collector learns of them from
+/// the `drops` field of the following samples.
+pub struct Agent<'a> {
+    agent_addr: IpAddress,
+    collector: IpAddress,
+    src_port: u16,
+    dst_port: u16,
+    sub_agent: u32,
+    if_index: u32,
+    rate: u32,
+    skip: u32,
+    rng: Option<&'a mut dyn Rng>,
+    max_header: usize,
+    max_len: usize,
+    queue: ByteRing<'a>,
+    counters: Counters,
+    counter_interval: Option<Duration>,
+    next_counters: Option<Instant>,
+    boot: Option<Instant>,
+    pool: u32,
+    drops: u32,
+    datagram_seq: u32,
+    flow_seq: u32,
+    counter_seq: u32,
+    sent: u64,
+}
+
+/// A receiver sampling the frames passed to the wrapped receiver.
+pub struct Sampler<'r, 'a, I> {
+    agent: &'r mut Agent<'a>,
+    inner: I,
+}
+
+/// Writes big-endian values, the encoding of XDR, to a buffer.
+struct Writer<'b> {
+    buffer: &'b mut [u8],
+    pos: usize,
+}
+
+impl<'a> Agent<'a> {
+    /// The default sampling rate, one in that many frames.
+    pub const DEFAULT_RATE: u32 = 1024;
+
+    /// The default number of header bytes exported of each sampled frame.
+    pub const DEFAULT_MAX_HEADER: usize = 128;
+
+    /// The default length of datagrams.
+    ///
+    /// Collectors expect unfragmented datagrams. Samples that would exceed this length are sent
+    /// in the next datagram instead.
+    pub const DEFAULT_MAX_LEN: usize = 1400;
+
+    /// The default interval of counter samples.
+    pub const DEFAULT_COUNTER_INTERVAL: Duration = Duration::from_secs(20);
+
+    /// Create an agent exporting to a collector.
+    ///
+    /// The `agent_addr` identifies the agent towards the collector and is also used as the source
+    /// address of datagrams, it must be an address of the ip endpoint. Datagrams are sent from
+    /// `src_port`, which the udp endpoint must accept. The `if_index` identifies the sampled
+    /// interface. The queue holds samples until they are sent and must be at least large enough
+    /// for one sample with the maximum header length.
+    pub fn new(
+        agent_addr: IpAddress,
+        collector: IpAddress,
+        src_port: u16,
+        if_index: u32,
+        queue: ByteRing<'a>,
+    ) -> Self {
+        Agent {
+            agent_addr,
+            collector,
+            src_port,
+            dst_port: SFLOW_PORT,
+            sub_agent: 0,
+            if_index,
+            rate: Self::DEFAULT_RATE,
+            skip: Self::DEFAULT_RATE,
+            rng: None,
+            max_header: Self::DEFAULT_MAX_HEADER,
+            max_len: Self::DEFAULT_MAX_LEN,
+            queue,
+            counters: Counters::default(),
+            counter_interval: Some(Self::DEFAULT_COUNTER_INTERVAL),
+            next_counters: None,
+            boot: None,
+            pool: 0,
+            drops: 0,
+            datagram_seq: 0,
+            flow_seq: 0,
+            counter_seq: 0,
+            sent: 0,
+        }
+    }
+
+    /// Change the port of the collector, [`SFLOW_PORT`] by default.
+    ///
+    /// [`SFLOW_PORT`]: constant.SFLOW_PORT.html
+    pub fn set_collector_port(&mut self, port: u16) {
+        self.dst_port = port;
+    }
+
+    /// Change the id distinguishing several agents with the same address, `0` by default.
+    pub fn set_sub_agent(&mut self, sub_agent: u32) {
+        self.sub_agent = sub_agent;
+    }
+
+    /// Get the sampling rate.
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    /// Sample one in `rate` frames on average, or none for `0`.
+    pub fn set_rate(&mut self, rate: u32) {
+        self.rate = rate;
+        self.skip = self.next_skip();
+    }
+
+    /// Randomize the number of frames skipped between samples.
+    ///
+    /// Without a random source exactly every `rate`-th frame is sampled, which may alias with
+    /// periodic traffic patterns. The skip is otherwise drawn uniformly so that the average rate
+    /// is preserved, as recommended by the sFlow specification.
+    pub fn set_rng(&mut self, rng: &'a mut dyn Rng) {
+        self.rng = Some(rng);
+        self.skip = self.next_skip();
+    }
+
+    /// Change the number of header bytes exported of each sampled frame.
+    ///
+    /// At most 256 bytes are exported, which is also the upper limit of most collectors.
+    pub fn set_max_header(&mut self, len: usize) {
+        self.max_header = len.min(MAX_HEADER);
+    }
+
+    /// Change the maximum length of datagrams.
+    ///
+    /// Datagrams must fit into the packet buffers and the mtu of the link. The length is raised
+    /// to fit at least one sample of each kind.
+    pub fn set_max_len(&mut self, len: usize) {
+        let min = MAX_DATAGRAM_HEADER + MAX_SAMPLE + COUNTER_SAMPLE_LEN;
+        self.max_len = len.clamp(min, usize::from(u16::MAX));
+    }
+
+    /// Change the interval of counter samples, or disable them with `None`.
+    pub fn set_counter_interval(&mut self, interval: Option<Duration>) {
+        self.counter_interval = interval;
+        self.next_counters = None;
+    }
+
+    /// Get the interface counters.
+    pub fn counters(&self) -> &Counters {
+        &self.counters
+    }
+
+    /// Get the interface counters to update those not maintained by the agent.
+    pub fn counters_mut(&mut self) -> &mut Counters {
+        &mut self.counters
+    }
+
+    /// The number of frames seen by the sampler, including those not sampled.
+    pub fn sample_pool(&self) -> u32 {
+        self.pool
+    }
+
+    /// The number of samples dropped because the queue was full.
+    pub fn drops(&self) -> u32 {
+        self.drops
+    }
+
+    /// The number of datagrams sent to the collector.
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    /// Check if samples are waiting to be sent.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Sample the frames received by an ethernet receiver.
+    pub fn recv<I>(&mut self, inner: I) -> Sampler<'_, 'a, I> {
+        Sampler { agent: self, inner }
+    }
+
+    /// Sample the frames received by a function.
+    pub fn recv_with<F>(&mut self, inner: F) -> Sampler<'_, 'a, FnHandler<F>> {
+        self.recv(FnHandler(inner))
+    }
+
+    /// Get the time at which the agent wants to send its next datagram.
+    ///
+    /// Offer it a packet buffer at that point.
+    pub fn poll(&self, now: Instant) -> Expiration {
+        if !self.queue.is_empty() {
+            return Expiration::When(now);
+        }
+
+        match (self.counter_interval, self.next_counters) {
+            (None, _) => Expiration::Never,
+            (Some(_), Some(next)) => Expiration::When(next),
+            (Some(_), None) => Expiration::When(now),
+        }
+    }
+
+    /// Get the time of the next datagram, with the current time of a clock.
+    pub fn tick<C: Clock + ?Sized>(&self, clock: &C) -> Expiration {
+        self.poll(clock.now())
+    }
+
+    /// Count a received frame and sample it if its turn has come.
+    pub fn observe(&mut self, frame: &[u8], now: Instant) {
+        self.boot.get_or_insert(now);
+        self.count(frame);
+
+        self.pool = self.pool.wrapping_add(1);
+        if self.rate == 0 {
+            return;
+        }
+
+        self.skip = self.skip.saturating_sub(1);
+        if self.skip > 0 {
+            return;
+        }
+
+        self.skip = self.next_skip();
+        self.sample(frame);
+    }
+
+    fn next_skip(&mut self) -> u32 {
+        match (self.rate, self.rng.as_mut()) {
+            (0, _) | (1, _) | (_, None) => self.rate,
+            // Uniform in `1..2 * rate`, which has the mean `rate`.
+            (rate, Some(rng)) => {
+                let range = 2 * u64::from(rate) - 1;
+                1 + (rng.next_u64() % range) as u32
+            },
+        }
+    }
+
+    fn count(&mut self, frame: &[u8]) {
+        let counters = &mut self.counters;
+        counters.in_octets = counters.in_octets.wrapping_add(frame.len() as u64);
+        let dst_addr = match frame.get(..6) {
+            Some(addr) => EthernetAddress::from_bytes(addr),
+            None => return,
+        };
+
+        let count = if dst_addr.is_broadcast() {
+            &mut counters.in_broadcast
+        } else if dst_addr.is_multicast() {
+            &mut counters.in_multicast
+        } else {
+            &mut counters.in_unicast
+        };
+        *count = count.wrapping_add(1);
+    }
+
+    /// Encode a flow sample of the frame into the queue.
+    fn sample(&mut self, frame: &[u8]) {
+        let header = &frame[..frame.len().min(self.max_header)];
+        let padded = pad(header.len());
+        let len = FLOW_SAMPLE_LEN + padded;
+        if self.queue.window() < len {
+            self.drops = self.drops.wrapping_add(1);
+            return;
+        }
+
+        let mut buffer = [0; MAX_SAMPLE];
+        let mut writer = Writer { buffer: &mut buffer[..len], pos: 0 };
+        writer.u32(FORMAT_FLOW_SAMPLE);
+        writer.u32((len - 8) as u32);
+        writer.u32(self.flow_seq);
+        writer.u32(self.if_index);
+        writer.u32(self.rate);
+        writer.u32(self.pool);
+        writer.u32(self.drops);
+        writer.u32(self.if_index);
+        // The output interface is not known.
+        writer.u32(0);
+        writer.u32(1);
+
+        writer.u32(FORMAT_RAW_HEADER);
+        writer.u32((16 + padded) as u32);
+        writer.u32(HEADER_ETHERNET);
+        writer.u32(frame.len() as u32);
+        // The frame check sequence is not part of the buffer.
+        writer.u32(0);
+        writer.u32(header.len() as u32);
+        writer.bytes(header);
+
+        self.queue.enqueue_slice(&buffer[..len]);
+        self.flow_seq = self.flow_seq.wrapping_add(1);
+    }
+
+    fn counters_due(&self, now: Instant) -> bool {
+        match (self.counter_interval, self.next_counters) {
+            (None, _) => false,
+            (Some(_), Some(next)) => next <= now,
+            (Some(_), None) => true,
+        }
+    }
+
+    /// The length of all queued samples that fit into a datagram, and their number.
+    fn queued(&mut self, available: usize) -> (usize, u32) {
+        let (mut len, mut count) = (0, 0);
+        while len < self.queue.len() {
+            let mut tag = [0; 8];
+            self.queue.read_allocated(len, &mut tag);
+            let sample = 8 + u32::from_be_bytes([tag[4], tag[5], tag[6], tag[7]]) as usize;
+            if len + sample > available {
+                break;
+            }
+            len += sample;
+            count += 1;
+        }
+        (len, count)
+    }
+
+    /// Send a datagram with the queued samples and the counters, if they are due.
+    fn send_datagram<P: PayloadMut>(&mut self, raw: udp::RawPacket<P>, now: Instant)
+        -> Result<()>
+    {
+        let address_len = match self.agent_addr {
+            IpAddress::Ipv4(_) => 4,
+            IpAddress::Ipv6(_) => 16,
+            _ => return Err(Error::Illegal),
+        };
+
+        let counters = self.counters_due(now);
+        let header_len = 24 + address_len;
+        let counters_len = if counters { COUNTER_SAMPLE_LEN } else { 0 };
+        let (samples_len, count) = self.queued(self.max_len - header_len - counters_len);
+        if count == 0 && !counters {
+            return Ok(());
+        }
+
+        let init = udp::Init {
+            source: self.agent_addr.into(),
+            src_port: self.src_port,
+            dst_addr: self.collector,
+            dst_port: self.dst_port,
+            payload: header_len + samples_len + counters_len,
+            dscp: 0,
+        };
+
+        let mut packet = raw.prepare(init)?;
+        let payload = packet.packet.payload_mut_slice();
+        let boot = *self.boot.get_or_insert(now);
+
+        let mut writer = Writer { buffer: &mut payload[..header_len], pos: 0 };
+        writer.u32(VERSION);
+        match self.agent_addr {
+            IpAddress::Ipv4(addr) => {
+                writer.u32(1);
+                writer.bytes(addr.as_bytes());
+            },
+            IpAddress::Ipv6(addr) => {
+                writer.u32(2);
+                writer.bytes(addr.as_bytes());
+            },
+            _ => unreachable!(),
+        }
+        writer.u32(self.sub_agent);
+        writer.u32(self.datagram_seq);
+        writer.u32((now - boot).as_millis() as u32);
+        writer.u32(count + u32::from(counters));
+
+        let end = header_len + samples_len;
+        self.queue.read_allocated(0, &mut payload[header_len..end]);
+        if counters {
+            self.counter_sample(&mut payload[end..]);
+        }
+
+        packet.send()?;
+        self.queue.dequeue_allocated(samples_len);
+        self.datagram_seq = self.datagram_seq.wrapping_add(1);
+        if counters {
+            self.counter_seq = self.counter_seq.wrapping_add(1);
+            self.next_counters = self.counter_interval.map(|interval| now + interval);
+        }
+        Ok(())
+    }
+
+    fn counter_sample(&self, buffer: &mut [u8]) {
+        let counters = &self.counters;
+        let mut writer = Writer { buffer, pos: 0 };
+        writer.u32(FORMAT_COUNTER_SAMPLE);
+        writer.u32((COUNTER_SAMPLE_LEN - 8) as u32);
+        writer.u32(self.counter_seq);
+        writer.u32(self.if_index);
+        writer.u32(1);
+
+        writer.u32(FORMAT_GENERIC_COUNTERS);
+        writer.u32(GENERIC_COUNTERS_LEN as u32);
+        writer.u32(self.if_index);
+        writer.u32(IF_TYPE_ETHERNET);
+        writer.u64(counters.speed);
+        // The direction is unknown, the interface is up.
+        writer.u32(0);
+        writer.u32(3);
+        writer.u64(counters.in_octets);
+        writer.u32(counters.in_unicast);
+        writer.u32(counters.in_multicast);
+        writer.u32(counters.in_broadcast);
+        writer.u32(counters.in_discards);
+        writer.u32(counters.in_errors);
+        writer.u32(counters.in_unknown_protos);
+        writer.u64(counters.out_octets);
+        writer.u32(counters.out_unicast);
+        writer.u32(counters.out_multicast);
+        writer.u32(counters.out_broadcast);
+        writer.u32(counters.out_discards);
+        writer.u32(counters.out_errors);
+        // Not in promiscuous mode.
+        writer.u32(2);
+    }
+}
+
+impl Writer<'_> {
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_be_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_be_bytes());
+    }
+
+    /// Write bytes, padded with zeros to a multiple of four.
+    fn bytes(&mut self, bytes: &[u8]) {
+        let end = self.pos + bytes.len();
+        self.buffer[self.pos..end].copy_from_slice(bytes);
+        let padded = self.pos + pad(bytes.len());
+        for byte in &mut self.buffer[end..padded] {
+            *byte = 0;
+        }
+        self.pos = padded;
+    }
+}
+
+impl<P: PayloadMut> udp::Send<P> for Agent<'_> {
+    fn send(&mut self, raw: udp::RawPacket<P>) {
+        let now = raw.handle.info().timestamp();
+        if self.queue.is_empty() && !self.counters_due(now) {
+            return;
+        }
+
+        if self.send_datagram(raw, now).is_ok() {
+            self.sent += 1;
+        }
+    }
+}
+
+impl<P, I> eth::Recv<P> for Sampler<'_, '_, I>
+where
+    P: Payload,
+    I: eth::Recv<P>,
+{
+    fn receive(&mut self, packet: eth::InPacket<P>) {
+        let now = packet.handle.info().timestamp();
+        self.agent.observe(packet.frame.as_bytes(), now);
+        self.inner.receive(packet)
+    }
+}
+
+/// The length of data padded to a multiple of four.
+fn pad(len: usize) -> usize {
+    len.next_multiple_of(4)
+}
@@ -0,0 +1,30 @@
+//! Packet sampling and export with sFlow version 5.
+//!
+//! An [`Agent`] samples one in every `N` ingress frames of an interface and exports the leading
+//! bytes of each sampled frame to a collector, together with periodic interface counters. This
+//! gives visibility into the traffic of a deployment using `ethox` as a fast-path without
+//! capturing every packet.
+//!
+//! The agent wraps the ethernet receiver with [`Agent::recv`], which counts every frame before it
+//! is passed on and copies the sampled headers into a queue provided by the caller. The frames
+//! themselves are not modified. Samples leave in the order they were taken, as many as fit into
+//! one datagram, whenever the agent is offered a packet buffer on top of the udp layer. Drive its
+//! timer with [`Agent::tick`] to also send counter samples in the absence of traffic.
+//!
+//! Only the ingress counters are maintained by the agent. Copy the other [`Counters`] from the
+//! statistics of the device or the endpoints before they are due to be sent.
+//!
+//! [`Agent`]: struct.Agent.html
+//! [`Agent::recv`]: struct.Agent.html#method.recv
+//! [`Agent::tick`]: struct.Agent.html#method.tick
+//! [`Counters`]: struct.Counters.html
+mod agent;
+#[cfg(test)]
+mod tests;
+
+pub use agent::{
+    Agent,
+    Counters,
+    Sampler,
+    SFLOW_PORT,
+};
@@ -0,0 +1,158 @@
+use super::*;
+use crate::layer::eth;
+use crate::layer::udp::testing::{Host, IP_ADDR_HOST, IP_ADDR_PEER as IP_ADDR_COLLECTOR};
+use crate::layer::udp::testing::{MAC_ADDR_HOST, MAC_ADDR_PEER as MAC_ADDR_COLLECTOR};
+use crate::managed::Slice;
+use crate::nic::{external::External, Device};
+use crate::storage::RingBuffer;
+use crate::time::{Duration, Expiration, Instant};
+use crate::wire::EthernetAddress;
+
+const AGENT_PORT: u16 = 6344;
+const IF_INDEX: u32 = 3;
+
+/// Let the agent send at most one datagram and return its payload.
+fn send(agent: &mut Agent, now: Instant) -> Option<Vec<u8>> {
+    Host::new(vec![AGENT_PORT]).send_datagram(now, (AGENT_PORT, SFLOW_PORT), agent)
+}
+
+fn agent(queue: usize) -> Agent<'static> {
+    let queue = RingBuffer::new(vec![0; queue]);
+    Agent::new(IP_ADDR_HOST.into(), IP_ADDR_COLLECTOR.into(), AGENT_PORT, IF_INDEX, queue)
+}
+
+fn frame(dst_addr: EthernetAddress, len: usize) -> Vec<u8> {
+    let mut frame = vec![0xee; len];
+    frame[..6].copy_from_slice(dst_addr.as_bytes());
+    frame[6..12].copy_from_slice(MAC_ADDR_COLLECTOR.as_bytes());
+    frame
+}
+
+fn word(data: &[u8], idx: usize) -> u32 {
+    u32::from_be_bytes([data[4*idx], data[4*idx + 1], data[4*idx + 2], data[4*idx + 3]])
+}
+
+#[test]
+fn flow_samples() {
+    let mut agent = agent(1024);
+    agent.set_rate(2);
+    agent.set_counter_interval(None);
+
+    let start = Instant::from_secs(10);
+    agent.observe(&frame(MAC_ADDR_HOST, 60), start);
+    agent.observe(&frame(EthernetAddress::BROADCAST, 200), start);
+    agent.observe(&frame(EthernetAddress([0x01, 0, 0x5e, 0, 0, 1]), 70), start);
+    assert_eq!(agent.sample_pool(), 3);
+    assert_eq!(agent.counters().in_octets, 330);
+    assert_eq!(agent.counters().in_unicast, 1);
+    assert_eq!(agent.counters().in_broadcast, 1);
+    assert_eq!(agent.counters().in_multicast, 1);
+    assert_eq!(agent.poll(start), Expiration::When(start));
+
+    let now = start + Duration::from_millis(1500);
+    let datagram = send(&mut agent, now).unwrap();
+    assert_eq!(send(&mut agent, now), None);
+    assert!(agent.is_empty());
+    assert_eq!(agent.sent(), 1);
+    assert_eq!(agent.poll(now), Expiration::Never);
+
+    // The datagram header.
+    assert_eq!(word(&datagram, 0), 5);
+    assert_eq!(word(&datagram, 1), 1);
+    assert_eq!(&datagram[8..12], IP_ADDR_HOST.as_bytes());
+    assert_eq!(word(&datagram, 4), 0);
+    assert_eq!(word(&datagram, 5), 1500);
+    assert_eq!(word(&datagram, 6), 1);
+
+    // The flow sample of the second frame, with its header truncated.
+    let sample = &datagram[28..];
+    assert_eq!(word(sample, 0), 1);
+    assert_eq!(word(sample, 1) as usize, sample.len() - 8);
+    assert_eq!(word(sample, 3), IF_INDEX);
+    assert_eq!(word(sample, 4), 2);
+    assert_eq!(word(sample, 5), 2);
+    assert_eq!(word(sample, 6), 0);
+    assert_eq!(word(sample, 9), 1);
+    let record = &sample[40..];
+    assert_eq!(word(record, 0), 1);
+    assert_eq!(word(record, 3), 200);
+    assert_eq!(word(record, 5), Agent::DEFAULT_MAX_HEADER as u32);
+    assert_eq!(&record[24..30], EthernetAddress::BROADCAST.as_bytes());
+    assert_eq!(record.len(), 24 + Agent::DEFAULT_MAX_HEADER);
+}
+
+#[test]
+fn counter_samples() {
+    let mut agent = agent(1024);
+    agent.set_rate(0);
+    agent.set_counter_interval(Some(Duration::from_secs(1)));
+    agent.counters_mut().out_octets = 4242;
+
+    let start = Instant::from_secs(0);
+    agent.observe(&frame(MAC_ADDR_HOST, 64), start);
+    assert!(agent.is_empty());
+    assert_eq!(agent.poll(start), Expiration::When(start));
+
+    let datagram = send(&mut agent, start).unwrap();
+    assert_eq!(word(&datagram, 6), 1);
+    let sample = &datagram[28..];
+    assert_eq!(word(sample, 0), 2);
+    assert_eq!(word(sample, 1) as usize, sample.len() - 8);
+    assert_eq!(word(sample, 2), 0);
+    assert_eq!(word(sample, 3), IF_INDEX);
+    let record = &sample[20..];
+    assert_eq!(word(record, 0), 1);
+    assert_eq!(word(record, 1), 88);
+    assert_eq!(word(record, 2), IF_INDEX);
+    // The octet counters are 64-bit.
+    assert_eq!(word(record, 9), 64);
+    assert_eq!(word(record, 10), 1);
+    assert_eq!(word(record, 17), 4242);
+
+    // The next counters are due after the interval.
+    let next = start + Duration::from_secs(1);
+    assert_eq!(agent.poll(start), Expiration::When(next));
+    assert_eq!(send(&mut agent, start), None);
+    let datagram = send(&mut agent, next).unwrap();
+    assert_eq!(word(&datagram, 4), 1);
+    assert_eq!(word(&datagram[28..], 2), 1);
+}
+
+#[test]
+fn queue_full() {
+    let mut agent = agent(128);
+    agent.set_rate(1);
+    agent.set_counter_interval(None);
+    agent.set_max_header(32);
+
+    let now = Instant::from_secs(0);
+    for _ in 0..3 {
+        agent.observe(&frame(MAC_ADDR_HOST, 60), now);
+    }
+    // Each sample takes 96 bytes.
+    assert_eq!(agent.drops(), 2);
+
+    let datagram = send(&mut agent, now).unwrap();
+    assert_eq!(word(&datagram, 6), 1);
+    agent.observe(&frame(MAC_ADDR_HOST, 60), now);
+    let datagram = send(&mut agent, now).unwrap();
+    // The collector learns about the dropped samples.
+    assert_eq!(word(&datagram[28..], 2), 1);
+    assert_eq!(word(&datagram[28..], 6), 2);
+}
+
+#[test]
+fn sampler() {
+    let mut agent = agent(1024);
+    agent.set_rate(1);
+    agent.set_counter_interval(None);
+
+    let mut nic = External::new_recv(Slice::One(frame(MAC_ADDR_HOST, 64)));
+    let mut eth = eth::Endpoint::new(MAC_ADDR_HOST);
+    let mut received = false;
+    let recv = nic.rx(1, eth.recv(agent.recv_with(|_: eth::InPacket<_>| received = true)));
+    assert_eq!(recv, Ok(1));
+    assert!(received);
+    assert_eq!(agent.sample_pool(), 1);
+    assert!(!agent.is_empty());
+}
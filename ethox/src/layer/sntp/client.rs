@@ -0,0 +1,301 @@
+use core::cell::Cell;
+
+use crate::layer::{udp, Error, Result};
+use crate::time::{Clock, Duration, Expiration, Instant};
+use crate::wire::{IpAddress, IpSubnet, Ipv4Subnet, Ipv6Subnet, Payload, PayloadMut};
+use crate::wire::{ntp_packet, NtpLeap, NtpMode, NtpRepr, NtpTimestamp, NTP_PORT};
+
+/// The shortest interval between queries permitted by rfc4330 section 10.
+const MIN_POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How long to wait for a response before querying again.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A measurement of the offset between the local time and unix time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Sample {
+    /// Milliseconds to add to a local instant to get the milliseconds since the unix epoch.
+    pub offset: i64,
+    /// The round trip delay of the query, excluding processing time on the server.
+    pub delay: Duration,
+    /// The stratum of the server.
+    pub stratum: u8,
+    /// The local time at which the response was received.
+    pub measured_at: Instant,
+}
+
+/// An SNTP client querying a single server.
+///
+/// A query is sent with the first packet buffer offered to the client and then repeated in a
+/// fixed poll interval. The client uses the local timestamps of the nic as its transmit timestamp
+/// and only accepts responses that echo the timestamp of its latest query.
+pub struct Client {
+    server: IpAddress,
+    src_port: u16,
+    poll_interval: Duration,
+    /// When to send the next query, `None` if it is due immediately.
+    query_at: Option<Instant>,
+    /// The latest unanswered query, with its local send time.
+    pending: Option<(Instant, NtpTimestamp)>,
+    sample: Option<Sample>,
+    denied: bool,
+}
+
+/// A clock providing unix time, based on the local clock and the offset measured by a client.
+///
+/// Before the first sample, the local time is returned unchanged. Afterwards, instants count the
+/// milliseconds since the unix epoch. Since corrections of the offset could step the time
+/// backwards, the clock instead stays at the latest returned time until the corrected time has
+/// caught up.
+///
+/// Timers of the layers are based on the timestamps of the nic and should be driven by the local
+/// clock instead. This clock is intended for timestamping data with the time of day.
+#[derive(Debug)]
+pub struct Disciplined<C> {
+    clock: C,
+    offset: Option<i64>,
+    latest: Cell<Option<Instant>>,
+}
+
+impl Client {
+    /// The default interval between queries.
+    pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(64);
+
+    /// Create a client for a server, sending its queries from a local port.
+    pub fn new(server: IpAddress, src_port: u16) -> Self {
+        Client {
+            server,
+            src_port,
+            poll_interval: Self::DEFAULT_POLL_INTERVAL,
+            query_at: None,
+            pending: None,
+            sample: None,
+            denied: false,
+        }
+    }
+
+    /// Change the interval between queries.
+    ///
+    /// Intervals shorter than 15 seconds are not permitted and extended to that duration.
+    pub fn set_poll_interval(&mut self, interval: Duration) {
+        self.poll_interval = interval.max(MIN_POLL_INTERVAL);
+    }
+
+    /// The interval between queries.
+    pub fn poll_interval(&self) -> Duration {
+        self.poll_interval
+    }
+
+    /// The server being queried.
+    pub fn server(&self) -> IpAddress {
+        self.server
+    }
+
+    /// The latest measurement, if any.
+    pub fn sample(&self) -> Option<&Sample> {
+        self.sample.as_ref()
+    }
+
+    /// Check if the server has denied access with a kiss-o'-death packet.
+    ///
+    /// The client does not send any further queries in this case, see rfc4330 section 8.
+    pub fn is_denied(&self) -> bool {
+        self.denied
+    }
+
+    /// Query a new server, keeping the current sample until a new one is measured.
+    pub fn set_server(&mut self, server: IpAddress) {
+        self.server = server;
+        self.query_at = None;
+        self.pending = None;
+        self.denied = false;
+    }
+
+    /// Convert a local time to unix time, with the offset of the latest sample.
+    pub fn unix_time(&self, now: Instant) -> Option<Instant> {
+        self.sample.map(|sample| sample.unix_time(now))
+    }
+
+    /// Get the time at which the client wants to send its next query.
+    ///
+    /// Offer it a packet buffer at that point.
+    pub fn poll(&self, now: Instant) -> Expiration {
+        if self.denied {
+            return Expiration::Never;
+        }
+
+        Expiration::When(self.query_at.unwrap_or(now))
+    }
+
+    /// Get the time of the next query, with the current time of a clock.
+    pub fn tick<C: Clock + ?Sized>(&self, clock: &C) -> Expiration {
+        self.poll(clock.now())
+    }
+
+    fn send_query<P: PayloadMut>(&mut self, raw: udp::RawPacket<P>, now: Instant) -> Result<()> {
+        let source = match self.server {
+            IpAddress::Ipv4(_) => IpSubnet::from(Ipv4Subnet::ANY),
+            IpAddress::Ipv6(_) => IpSubnet::from(Ipv6Subnet::ANY),
+            _ => return Err(Error::Illegal),
+        };
+
+        let transmit = NtpTimestamp::from_unix_millis(now.total_millis());
+        let repr = NtpRepr::request(transmit);
+        let init = udp::Init {
+            source: source.into(),
+            src_port: self.src_port,
+            dst_addr: self.server,
+            dst_port: NTP_PORT,
+            payload: repr.buffer_len(),
+            dscp: 0,
+        };
+
+        let mut packet = raw.prepare(init)?;
+        repr.emit(ntp_packet::new_unchecked_mut(packet.packet.payload_mut_slice()));
+        packet.send()?;
+        self.pending = Some((now, transmit));
+        Ok(())
+    }
+
+    /// Validate a response and compute the sample, rfc4330 section 5.
+    fn measure(&self, repr: &NtpRepr, now: Instant) -> Option<Sample> {
+        let (sent_at, transmit) = self.pending?;
+        if repr.originate_timestamp != transmit
+            || repr.mode != NtpMode::Server
+            || repr.leap == NtpLeap::Alarm
+            || repr.stratum == 0
+            || repr.stratum > 15
+            || repr.transmit_timestamp == NtpTimestamp::ZERO
+        {
+            return None;
+        }
+
+        let receive = repr.receive_timestamp.to_unix_millis();
+        let transmit = repr.transmit_timestamp.to_unix_millis();
+        let (sent, received) = (sent_at.total_millis(), now.total_millis());
+
+        let offset = ((receive - sent) + (transmit - received)) / 2;
+        let delay = (received - sent) - (transmit - receive);
+        Some(Sample {
+            offset,
+            delay: Duration::from_millis(delay.max(0) as u64),
+            stratum: repr.stratum,
+            measured_at: now,
+        })
+    }
+}
+
+impl Sample {
+    /// Convert a local time to unix time.
+    pub fn unix_time(&self, local: Instant) -> Instant {
+        Instant::from_millis(local.total_millis() + self.offset)
+    }
+}
+
+impl<C: Clock> Disciplined<C> {
+    /// Wrap a local clock, which must use the same reference point as the nic.
+    pub fn new(clock: C) -> Self {
+        Disciplined {
+            clock,
+            offset: None,
+            latest: Cell::new(None),
+        }
+    }
+
+    /// Adjust the offset to a new measurement.
+    pub fn discipline(&mut self, sample: &Sample) {
+        if self.offset.is_none() {
+            // The first step from local to unix time is always forward.
+            self.latest.set(None);
+        }
+        self.offset = Some(sample.offset);
+    }
+
+    /// Check if the clock provides unix time.
+    pub fn is_synchronized(&self) -> bool {
+        self.offset.is_some()
+    }
+
+    /// The current unix time, if synchronized.
+    pub fn unix_time(&self) -> Option<Instant> {
+        self.offset.map(|_| self.now())
+    }
+
+    /// The wrapped local clock.
+    pub fn local(&self) -> &C {
+        &self.clock
+    }
+}
+
+impl<C: Clock> Clock for Disciplined<C> {
+    fn now(&self) -> Instant {
+        let local = self.clock.now();
+        let now = Instant::from_millis(local.total_millis() + self.offset.unwrap_or(0));
+        let now = self.latest.get().map_or(now, |latest| latest.max(now));
+        self.latest.set(Some(now));
+        now
+    }
+}
+
+impl<P: Payload> udp::Recv<P> for Client {
+    fn receive(&mut self, packet: udp::Packet<P>) {
+        let udp::Packet { handle, packet } = packet;
+        let udp_repr = packet.repr();
+        if udp_repr.src_port != NTP_PORT || udp_repr.dst_port != self.src_port {
+            return;
+        }
+
+        if packet.get_ref().repr().src_addr() != self.server {
+            return;
+        }
+
+        let repr = match ntp_packet::new_checked(packet.payload_slice())
+            .and_then(NtpRepr::parse)
+        {
+            Ok(repr) => repr,
+            Err(_) => return,
+        };
+
+        let pending = match self.pending {
+            Some((_, transmit)) => transmit,
+            None => return,
+        };
+
+        // Kiss-o'-death, only trusted when answering our query.
+        let now = handle.info().timestamp();
+        if repr.kiss_code().is_some() && repr.originate_timestamp == pending {
+            match &repr.reference_id {
+                b"DENY" | b"RSTR" => self.denied = true,
+                _ => self.poll_interval *= 2,
+            }
+            self.pending = None;
+            self.query_at = Some(now + self.poll_interval);
+            return;
+        }
+
+        if let Some(sample) = self.measure(&repr, now) {
+            self.sample = Some(sample);
+            self.pending = None;
+            self.query_at = Some(now + self.poll_interval);
+        }
+    }
+}
+
+impl<P: PayloadMut> udp::Send<P> for Client {
+    fn send(&mut self, raw: udp::RawPacket<P>) {
+        if self.denied {
+            return;
+        }
+
+        let now = raw.handle.info().timestamp();
+        if let Some(query_at) = self.query_at {
+            if now < query_at {
+                return;
+            }
+        }
+
+        // A failure, such as a missing route, is retried with the next timeout as well.
+        let _ = self.send_query(raw, now);
+        self.query_at = Some(now + RESPONSE_TIMEOUT);
+    }
+}
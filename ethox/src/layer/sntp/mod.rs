@@ -0,0 +1,30 @@
+//! A simple network time protocol (SNTP) client, rfc4330.
+//!
+//! Embedded targets often have a steady timer but no battery backed clock that knows the time of
+//! day. The client periodically queries a single configured server and measures the offset between
+//! the timestamps of the nic and unix time, which allows timestamping data with the wall clock.
+//! The time of the nic is never adjusted, the offset is merely exposed in a [`Sample`].
+//!
+//! The client sits on top of the udp layer and implements both its receiving and sending side.
+//! The udp endpoint must accept the source port configured for the client. Drive its timers with
+//! [`Client::tick`] and offer it a packet buffer whenever they have expired.
+//!
+//! For a source of wall clock time that can be passed around as a [`Clock`], wrap the local clock
+//! in a [`Disciplined`] clock and update it with each new sample.
+//!
+//! Only unicast mode is supported, there is no selection among multiple servers and no filtering
+//! of samples beyond the sanity checks of rfc4330 section 5.
+//!
+//! [`Client::tick`]: struct.Client.html#method.tick
+//! [`Clock`]: ../../time/trait.Clock.html
+//! [`Disciplined`]: struct.Disciplined.html
+//! [`Sample`]: struct.Sample.html
+mod client;
+#[cfg(test)]
+mod tests;
+
+pub use client::{
+    Client,
+    Disciplined,
+    Sample,
+};
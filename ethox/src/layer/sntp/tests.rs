@@ -0,0 +1,132 @@
+use super::*;
+use crate::managed::Slice;
+use crate::nic::{external::External, Device};
+use crate::layer::{arp, eth, ip, udp};
+use crate::time::{Clock, Duration, Expiration, Instant};
+use crate::wire::{EthernetAddress, IpCidr, Ipv4Address};
+use crate::wire::{ethernet_frame, ipv4_packet, udp_packet, ntp_packet};
+use crate::wire::{NtpMode, NtpTimestamp, NTP_PORT};
+
+const MAC_ADDR_HOST: EthernetAddress = EthernetAddress([0x52, 0x54, 0, 0, 0, 1]);
+const MAC_ADDR_SERVER: EthernetAddress = EthernetAddress([0x52, 0x54, 0, 0, 0, 2]);
+const IP_ADDR_HOST: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
+const IP_ADDR_SERVER: Ipv4Address = Ipv4Address::new(10, 0, 0, 2);
+const CLIENT_PORT: u16 = 4123;
+
+/// The unix time of the server, in milliseconds.
+const UNIX_TIME: i64 = 1_700_000_000_000;
+
+/// Turn a sent query into the answer of the server, received and sent after some delay.
+fn answer(buffer: &mut [u8], stratum: u8, reference_id: [u8; 4]) {
+    let eth = ethernet_frame::new_unchecked_mut(buffer);
+    assert_eq!(eth.dst_addr(), MAC_ADDR_SERVER);
+    eth.set_dst_addr(MAC_ADDR_HOST);
+    eth.set_src_addr(MAC_ADDR_SERVER);
+    let ip = ipv4_packet::new_unchecked_mut(eth.payload_mut_slice());
+    assert_eq!(ip.dst_addr(), IP_ADDR_SERVER);
+    ip.set_dst_addr(IP_ADDR_HOST);
+    ip.set_src_addr(IP_ADDR_SERVER);
+    ip.fill_checksum();
+    let udp = udp_packet::new_unchecked_mut(ip.payload_mut_slice());
+    assert_eq!(udp.dst_port(), NTP_PORT);
+    udp.set_dst_port(CLIENT_PORT);
+    udp.set_src_port(NTP_PORT);
+
+    let ntp = ntp_packet::new_unchecked_mut(udp.payload_mut_slice());
+    assert_eq!(ntp.mode(), NtpMode::Client);
+    let originate = ntp.transmit_timestamp();
+    assert_ne!(originate, NtpTimestamp::ZERO);
+    ntp.set_mode(NtpMode::Server);
+    ntp.set_stratum(stratum);
+    ntp.set_reference_id(reference_id);
+    ntp.set_originate_timestamp(originate);
+    ntp.set_receive_timestamp(NtpTimestamp::from_unix_millis(UNIX_TIME + 40));
+    ntp.set_transmit_timestamp(NtpTimestamp::from_unix_millis(UNIX_TIME + 60));
+    udp.fill_checksum(IP_ADDR_SERVER.into(), IP_ADDR_HOST.into());
+}
+
+#[test]
+fn offset() {
+    let mut eth = eth::Endpoint::new(MAC_ADDR_HOST);
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut neighbors[..]);
+        eth_cache.fill(IP_ADDR_SERVER.into(), MAC_ADDR_SERVER, None).unwrap();
+        eth_cache
+    };
+    let mut routes = [ip::Route::unspecified(); 1];
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_HOST.into(), 24),
+        ip::Routes::new(&mut routes[..]),
+        neighbors);
+    let mut udp = udp::Endpoint::new(CLIENT_PORT);
+
+    let mut client = Client::new(IP_ADDR_SERVER.into(), CLIENT_PORT);
+    let now = Instant::from_millis(1000);
+    assert_eq!(client.poll(now), Expiration::When(now));
+
+    let mut nic = External::new_send(Slice::One(vec![0; 128]));
+    nic.set_current_time(now);
+    assert_eq!(nic.tx(1, eth.send(ip.send(udp.send(&mut client)))), Ok(1));
+    assert!(client.poll(now) > Expiration::When(now));
+
+    // The server answers 100ms later, having spent 20ms processing the query.
+    answer(nic.get_mut(0).unwrap(), 2, *b"GPS\0");
+    nic.receive_all();
+    nic.set_current_time(Instant::from_millis(1100));
+    assert_eq!(nic.rx(1, eth.recv(ip.recv(udp.recv(&mut client)))), Ok(1));
+
+    let sample = *client.sample().expect("Measured the offset");
+    assert_eq!(sample.offset, UNIX_TIME - 1000);
+    assert_eq!(sample.delay, Duration::from_millis(80));
+    assert_eq!(sample.stratum, 2);
+    assert_eq!(client.unix_time(Instant::from_millis(1100)),
+        Some(Instant::from_millis(UNIX_TIME + 100)));
+    assert_eq!(client.poll(Instant::from_millis(1100)),
+        Expiration::When(Instant::from_millis(1100) + Client::DEFAULT_POLL_INTERVAL));
+
+    // A replayed answer no longer matches an outstanding query.
+    nic.receive_all();
+    assert_eq!(nic.rx(1, eth.recv(ip.recv(udp.recv(&mut client)))), Ok(1));
+    assert_eq!(client.sample(), Some(&sample));
+
+    let local = core::cell::Cell::new(Instant::from_millis(2000));
+    let mut clock = Disciplined::new(|| local.get());
+    assert_eq!(clock.now(), Instant::from_millis(2000));
+    clock.discipline(&sample);
+    assert!(clock.is_synchronized());
+    assert_eq!(clock.now(), Instant::from_millis(UNIX_TIME + 1000));
+
+    // A correction backwards does not move the clock backwards.
+    clock.discipline(&Sample { offset: sample.offset - 500, ..sample });
+    local.set(Instant::from_millis(2100));
+    assert_eq!(clock.now(), Instant::from_millis(UNIX_TIME + 1000));
+    local.set(Instant::from_millis(2600));
+    assert_eq!(clock.now(), Instant::from_millis(UNIX_TIME + 1100));
+}
+
+#[test]
+fn kiss_of_death() {
+    let mut eth = eth::Endpoint::new(MAC_ADDR_HOST);
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut neighbors[..]);
+        eth_cache.fill(IP_ADDR_SERVER.into(), MAC_ADDR_SERVER, None).unwrap();
+        eth_cache
+    };
+    let mut routes = [ip::Route::unspecified(); 1];
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_HOST.into(), 24),
+        ip::Routes::new(&mut routes[..]),
+        neighbors);
+    let mut udp = udp::Endpoint::new(CLIENT_PORT);
+    let mut client = Client::new(IP_ADDR_SERVER.into(), CLIENT_PORT);
+
+    let mut nic = External::new_send(Slice::One(vec![0; 128]));
+    assert_eq!(nic.tx(1, eth.send(ip.send(udp.send(&mut client)))), Ok(1));
+    answer(nic.get_mut(0).unwrap(), 0, *b"DENY");
+    nic.receive_all();
+    assert_eq!(nic.rx(1, eth.recv(ip.recv(udp.recv(&mut client)))), Ok(1));
+
+    assert!(client.is_denied());
+    assert_eq!(client.sample(), None);
+    assert_eq!(client.poll(Instant::from_secs(1000)), Expiration::Never);
+}
@@ -0,0 +1,478 @@
+//! Composition of the common layers of a host.
+//!
+//! Most hosts use the same pipeline of an ethernet and an ip endpoint below the transport
+//! protocols udp, tcp and icmp. Assembling it by hand requires nesting the receivers and senders
+//! of each layer in the right order, and an ip handler that routes each packet to the transport
+//! protocol it belongs to. The [`Stack`] owns the lower endpoints and borrows them into the
+//! handlers passed to the nic, while [`Transport`] collects the receivers of the transport layers
+//! into a single ip handler.
+//!
+//! ```
+//! use ethox::layer::{arp, eth, icmp, ip, udp};
+//! use ethox::layer::stack::{Stack, Transport};
+//! use ethox::managed::Slice;
+//! use ethox::nic::{Device, loopback::Loopback};
+//! use ethox::wire::{EthernetAddress, IpCidr, Ipv4Address};
+//!
+//! let mut stack = Stack::new(
+//!     eth::Endpoint::new(EthernetAddress([0, 1, 2, 3, 4, 5])),
+//!     ip::Endpoint::new(
+//!         IpCidr::new(Ipv4Address::new(10, 0, 0, 1).into(), 24),
+//!         ip::Routes::new(Slice::empty()),
+//!         arp::NeighborCache::new(Slice::empty())));
+//! let mut udp = udp::Endpoint::new(53);
+//! let mut icmp = icmp::Endpoint::new();
+//! let mut nic = Loopback::<Vec<u8>>::new(vec![0; 1 << 12].into());
+//!
+//! // Handle all datagrams and pings, in the same pass.
+//! let transport = Transport::new()
+//!     .udp(udp.recv_with(|_: udp::Packet<_>| { /* answer the request */ }))
+//!     .icmp(icmp.recv_with(|_: icmp::InPacket<_>| { }));
+//! nic.rx(10, stack.recv(transport)).unwrap();
+//!
+//! // Sending works the same as with the endpoints directly.
+//! nic.tx(10, stack.send(udp.send_with(|_: udp::RawPacket<_>| { }))).unwrap();
+//! ```
+//!
+//! An [`Interface`] goes one step further and also owns the device and the udp and tcp endpoints.
+//! Each call to its `poll` receives packets, drives the timers, and sends packets, while an
+//! [`Application`] handles the datagrams and segments of the transport layers.
+//!
+//! [`Application`]: trait.Application.html
+//! [`Interface`]: struct.Interface.html
+//! [`Stack`]: struct.Stack.html
+//! [`Transport`]: struct.Transport.html
+use crate::nic::Device;
+use crate::time::{Expiration, Instant};
+use crate::wire::{IpAddress, IpProtocol, Payload, PayloadMut};
+
+use super::{eth, ip, tcp, udp, Result};
+
+/// The ethernet and ip endpoint of a host.
+///
+/// Both endpoints are public and can be configured directly in between receiving and sending.
+pub struct Stack<'a> {
+    /// The ethernet endpoint at the bottom of the stack.
+    pub eth: eth::Endpoint<'a>,
+    /// The ip endpoint on top of the ethernet endpoint.
+    pub ip: ip::Endpoint<'a>,
+}
+
+/// Routes the packets of an ip endpoint to the transport layers.
+///
+/// The handlers are usually the receivers of the transport endpoints, which borrow them for a
+/// single receive pass. Packets of protocols without a handler are not accepted, so that the ip
+/// endpoint may answer them according to its [`IcmpPolicy`]. Use an [`ip::DispatchTable`] for
+/// other protocols than these three.
+///
+/// [`IcmpPolicy`]: ../ip/struct.IcmpPolicy.html
+/// [`ip::DispatchTable`]: ../ip/struct.DispatchTable.html
+pub struct Transport<U, T, I> {
+    udp: U,
+    tcp: T,
+    icmp: I,
+}
+
+/// The handler of a transport protocol that is not in use.
+///
+/// Does not accept any packets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Unused;
+
+impl<'a> Stack<'a> {
+    /// Assemble the stack from its endpoints.
+    pub fn new(eth: eth::Endpoint<'a>, ip: ip::Endpoint<'a>) -> Self {
+        Stack { eth, ip }
+    }
+
+    /// Receive frames, handing the ip packets to an upper layer.
+    pub fn recv<H>(&mut self, handler: H) -> eth::Receiver<'_, 'a, ip::Receiver<'_, 'a, H>> {
+        self.eth.recv(self.ip.recv(handler))
+    }
+
+    /// Send frames, filling the ip packets with an upper layer.
+    pub fn send<H>(&mut self, handler: H) -> eth::Sender<'_, 'a, ip::Sender<'_, 'a, H>> {
+        self.eth.send(self.ip.send(handler))
+    }
+}
+
+impl Transport<Unused, Unused, Unused> {
+    /// Create a router without any transport handler.
+    pub fn new() -> Self {
+        Transport {
+            udp: Unused,
+            tcp: Unused,
+            icmp: Unused,
+        }
+    }
+}
+
+impl Default for Transport<Unused, Unused, Unused> {
+    fn default() -> Self {
+        Transport::new()
+    }
+}
+
+impl<U, T, I> Transport<U, T, I> {
+    /// Set the handler of udp datagrams, typically a `udp::Receiver`.
+    pub fn udp<H>(self, handler: H) -> Transport<H, T, I> {
+        Transport { udp: handler, tcp: self.tcp, icmp: self.icmp }
+    }
+
+    /// Set the handler of tcp segments, typically a `tcp::Receiver`.
+    pub fn tcp<H>(self, handler: H) -> Transport<U, H, I> {
+        Transport { udp: self.udp, tcp: handler, icmp: self.icmp }
+    }
+
+    /// Set the handler of icmp messages, typically an `icmp::Receiver`.
+    pub fn icmp<H>(self, handler: H) -> Transport<U, T, H> {
+        Transport { udp: self.udp, tcp: self.tcp, icmp: handler }
+    }
+
+    /// Unwrap the three handlers.
+    pub fn into_inner(self) -> (U, T, I) {
+        (self.udp, self.tcp, self.icmp)
+    }
+}
+
+impl<P, U, T, I> ip::Recv<P> for Transport<U, T, I>
+    where P: Payload, U: ip::Recv<P>, T: ip::Recv<P>, I: ip::Recv<P>,
+{
+    fn receive(&mut self, frame: ip::InPacket<P>) {
+        match frame.packet.repr().protocol() {
+            IpProtocol::Udp | IpProtocol::UdpLite => self.udp.receive(frame),
+            IpProtocol::Tcp => self.tcp.receive(frame),
+            IpProtocol::Icmp | IpProtocol::Icmpv6 => self.icmp.receive(frame),
+            _ => (),
+        }
+    }
+
+    fn accepts_foreign(&self, dst_addr: IpAddress) -> bool {
+        self.udp.accepts_foreign(dst_addr)
+            || self.tcp.accepts_foreign(dst_addr)
+            || self.icmp.accepts_foreign(dst_addr)
+    }
+
+    fn accepts_protocol(&self, protocol: IpProtocol) -> bool {
+        match protocol {
+            IpProtocol::Udp | IpProtocol::UdpLite => self.udp.accepts_protocol(protocol),
+            IpProtocol::Tcp => self.tcp.accepts_protocol(protocol),
+            IpProtocol::Icmp | IpProtocol::Icmpv6 => self.icmp.accepts_protocol(protocol),
+            _ => false,
+        }
+    }
+}
+
+impl<P: Payload> ip::Recv<P> for Unused {
+    fn receive(&mut self, _: ip::InPacket<P>) { }
+
+    fn accepts_protocol(&self, _: IpProtocol) -> bool {
+        false
+    }
+}
+
+impl<P: Payload> ip::Send<P> for Unused {
+    fn send(&mut self, _: ip::RawPacket<P>) { }
+}
+
+/// The user logic driven by an [`Interface`].
+///
+/// Each method is called by the matching transport layer while polling the interface. The
+/// default implementations ignore received packets and send nothing.
+///
+/// [`Interface`]: struct.Interface.html
+pub trait Application<P: PayloadMut> {
+    /// Inspect one received udp datagram.
+    fn udp_receive(&mut self, _packet: udp::Packet<P>) { }
+
+    /// Fill in one available packet buffer with a udp datagram.
+    fn udp_send(&mut self, _raw: udp::RawPacket<P>) { }
+
+    /// Inspect one received tcp segment.
+    fn tcp_receive(&mut self, _packet: tcp::InPacket<P>) { }
+
+    /// Fill in one available packet buffer with a tcp segment.
+    fn tcp_send(&mut self, _raw: tcp::RawPacket<P>) { }
+}
+
+/// A device with the stack and transport endpoints on top of it.
+///
+/// This trades some flexibility of composing the handlers for a single `poll` per iteration of
+/// the event loop. All parts are public and can be configured directly in between polls. The udp
+/// and tcp endpoints are optional, packets of a protocol without endpoint are not accepted.
+///
+/// ```
+/// use ethox::layer::{arp, eth, ip, udp};
+/// use ethox::layer::stack::{Application, Interface, Stack};
+/// use ethox::managed::Slice;
+/// use ethox::nic::loopback::Loopback;
+/// use ethox::time::Instant;
+/// use ethox::wire::{EthernetAddress, IpCidr, Ipv4Address, PayloadMut};
+///
+/// struct Echo;
+///
+/// impl<P: PayloadMut> Application<P> for Echo {
+///     fn udp_receive(&mut self, packet: udp::Packet<P>) {
+///         // Answer each datagram.
+///     }
+/// }
+///
+/// let stack = Stack::new(
+///     eth::Endpoint::new(EthernetAddress([0, 1, 2, 3, 4, 5])),
+///     ip::Endpoint::new(
+///         IpCidr::new(Ipv4Address::new(10, 0, 0, 1).into(), 24),
+///         ip::Routes::new(Slice::empty()),
+///         arp::NeighborCache::new(Slice::empty())));
+/// let nic = Loopback::<Vec<u8>>::new(vec![0; 1 << 12].into());
+/// let mut interface = Interface::new(nic, stack);
+/// interface.udp = Some(udp::Endpoint::new(7));
+///
+/// let next = interface.poll(Instant::ZERO, &mut Echo).unwrap();
+/// ```
+pub struct Interface<'a, D> {
+    /// The device sending and receiving frames.
+    pub device: D,
+    /// The ethernet and ip endpoint.
+    pub stack: Stack<'a>,
+    /// The udp endpoint, if any.
+    pub udp: Option<udp::Endpoint<'a>>,
+    /// The tcp endpoint, if any.
+    pub tcp: Option<tcp::Endpoint<'a>>,
+    batch: usize,
+}
+
+/// Routes the received packets of an interface to its transport endpoints.
+struct Upper<'i, 'a, A> {
+    udp: Option<&'i mut udp::Endpoint<'a>>,
+    tcp: Option<&'i mut tcp::Endpoint<'a>>,
+    app: &'i mut A,
+}
+
+/// Presents an application as the handler of a transport layer.
+struct Handler<'i, A>(&'i mut A);
+
+impl<'a, D: Device> Interface<'a, D> {
+    /// The number of packets received and sent in each poll by default.
+    pub const DEFAULT_BATCH: usize = 32;
+
+    /// Create an interface without transport endpoints.
+    pub fn new(device: D, stack: Stack<'a>) -> Self {
+        Interface {
+            device,
+            stack,
+            udp: None,
+            tcp: None,
+            batch: Self::DEFAULT_BATCH,
+        }
+    }
+
+    /// Set the maximum number of packets received and sent in each poll.
+    pub fn set_batch(&mut self, batch: usize) {
+        self.batch = batch;
+    }
+
+    /// Receive packets, drive the timers, and send packets.
+    ///
+    /// Received datagrams and segments are handed to the application. Afterwards the application
+    /// can fill up to a batch of packet buffers for each transport endpoint. Returns the time at
+    /// which the timers need to be driven next, poll again by then at the latest.
+    pub fn poll<A>(&mut self, now: Instant, app: &mut A) -> Result<Expiration>
+    where
+        D::Handle: Sized,
+        D::Payload: PayloadMut + Sized,
+        A: Application<D::Payload>,
+    {
+        let Interface { device, stack, udp, tcp, batch } = self;
+
+        device.rx(*batch, stack.recv(Upper {
+            udp: udp.as_mut(),
+            tcp: tcp.as_mut(),
+            app: &mut *app,
+        }))?;
+
+        let mut next = stack.ip.poll(now);
+        if let Some(tcp) = tcp {
+            next = next.min(tcp.poll(now));
+        }
+
+        if let Some(udp) = udp {
+            device.tx(*batch, stack.send(udp.send(Handler(&mut *app))))?;
+        }
+        if let Some(tcp) = tcp {
+            device.tx(*batch, stack.send(tcp.send(Handler(&mut *app))))?;
+        }
+
+        Ok(next)
+    }
+
+    /// Unwrap the device and the endpoints.
+    pub fn into_inner(self)
+        -> (D, Stack<'a>, Option<udp::Endpoint<'a>>, Option<tcp::Endpoint<'a>>)
+    {
+        (self.device, self.stack, self.udp, self.tcp)
+    }
+}
+
+impl<P, A> ip::Recv<P> for Upper<'_, '_, A>
+    where P: PayloadMut, A: Application<P>,
+{
+    fn receive(&mut self, frame: ip::InPacket<P>) {
+        let app = Handler(&mut *self.app);
+        match frame.packet.repr().protocol() {
+            IpProtocol::Udp | IpProtocol::UdpLite => if let Some(udp) = &mut self.udp {
+                udp.recv(app).receive(frame)
+            },
+            IpProtocol::Tcp => if let Some(tcp) = &mut self.tcp {
+                tcp.recv(app).receive(frame)
+            },
+            _ => (),
+        }
+    }
+
+    fn accepts_protocol(&self, protocol: IpProtocol) -> bool {
+        match protocol {
+            IpProtocol::Udp => self.udp.is_some(),
+            IpProtocol::UdpLite => self.udp.as_ref().is_some_and(|udp| udp.lite()),
+            IpProtocol::Tcp => self.tcp.is_some(),
+            _ => false,
+        }
+    }
+}
+
+impl<P: PayloadMut, A: Application<P>> udp::Recv<P> for Handler<'_, A> {
+    fn receive(&mut self, packet: udp::Packet<P>) {
+        self.0.udp_receive(packet)
+    }
+}
+
+impl<P: PayloadMut, A: Application<P>> udp::Send<P> for Handler<'_, A> {
+    fn send(&mut self, raw: udp::RawPacket<P>) {
+        self.0.udp_send(raw)
+    }
+}
+
+impl<P: PayloadMut, A: Application<P>> tcp::Recv<P> for Handler<'_, A> {
+    fn receive(&mut self, packet: tcp::InPacket<P>) {
+        self.0.tcp_receive(packet)
+    }
+}
+
+impl<P: PayloadMut, A: Application<P>> tcp::Send<P> for Handler<'_, A> {
+    fn send(&mut self, raw: tcp::RawPacket<P>) {
+        self.0.tcp_send(raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::{arp, udp};
+    use crate::nic::{loopback::Loopback, Device};
+    use crate::wire::{EthernetAddress, IpCidr, IpSubnet, Ipv4Address, Ipv4Subnet, PayloadMut};
+
+    const MAC_ADDR_HOST: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 5]);
+    const IP_ADDR_HOST: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
+
+    #[test]
+    fn transport() {
+        let mut neighbors = [arp::Neighbor::default(); 1];
+        let mut neighbors = arp::NeighborCache::new(&mut neighbors[..]);
+        neighbors.fill(IP_ADDR_HOST.into(), MAC_ADDR_HOST, None).unwrap();
+        let mut routes = [ip::Route::unspecified(); 1];
+        let mut stack = Stack::new(
+            eth::Endpoint::new(MAC_ADDR_HOST),
+            ip::Endpoint::new(IpCidr::new(IP_ADDR_HOST.into(), 24),
+                ip::Routes::new(&mut routes[..]),
+                neighbors));
+        let mut udp = udp::Endpoint::new(80);
+
+        // Datagrams are sent to the host itself.
+        let mut nic = Loopback::<Vec<u8>>::new(vec![0; 1 << 12].into());
+
+        let sent = nic.tx(1, stack.send(udp.send_with(|frame: udp::RawPacket<_>| {
+            let init = udp::Init {
+                source: IpSubnet::from(Ipv4Subnet::ANY).into(),
+                src_port: 80,
+                dst_addr: IP_ADDR_HOST.into(),
+                dst_port: 80,
+                payload: 4,
+                dscp: 0,
+            };
+            let mut prepared = frame.prepare(init).unwrap();
+            prepared.packet.payload_mut_slice().copy_from_slice(b"ping");
+            prepared.send().unwrap();
+        })));
+        assert_eq!(sent, Ok(1));
+
+        let mut received = 0;
+        let transport = Transport::new()
+            .udp(udp.recv_with(|frame: udp::Packet<_>| {
+                assert_eq!(frame.packet.payload_slice(), b"ping");
+                received += 1;
+            }));
+        assert!(ip::Recv::<Vec<u8>>::accepts_protocol(&transport, IpProtocol::Udp));
+        assert!(!ip::Recv::<Vec<u8>>::accepts_protocol(&transport, IpProtocol::Tcp));
+
+        assert_eq!(nic.rx(1, stack.recv(transport)), Ok(1));
+        assert_eq!(received, 1);
+    }
+
+    #[test]
+    fn interface() {
+        #[derive(Default)]
+        struct Ping {
+            sent: usize,
+            received: usize,
+        }
+
+        impl<P: PayloadMut> Application<P> for Ping {
+            fn udp_receive(&mut self, packet: udp::Packet<P>) {
+                assert_eq!(packet.packet.payload_slice(), b"ping");
+                self.received += 1;
+            }
+
+            fn udp_send(&mut self, raw: udp::RawPacket<P>) {
+                if self.sent > 0 {
+                    return;
+                }
+
+                let init = udp::Init {
+                    source: IpSubnet::from(Ipv4Subnet::ANY).into(),
+                    src_port: 80,
+                    dst_addr: IP_ADDR_HOST.into(),
+                    dst_port: 80,
+                    payload: 4,
+                    dscp: 0,
+                };
+                let mut prepared = raw.prepare(init).unwrap();
+                prepared.packet.payload_mut_slice().copy_from_slice(b"ping");
+                prepared.send().unwrap();
+                self.sent += 1;
+            }
+        }
+
+        let mut neighbors = [arp::Neighbor::default(); 1];
+        let mut neighbors = arp::NeighborCache::new(&mut neighbors[..]);
+        neighbors.fill(IP_ADDR_HOST.into(), MAC_ADDR_HOST, None).unwrap();
+        let mut routes = [ip::Route::unspecified(); 1];
+        let stack = Stack::new(
+            eth::Endpoint::new(MAC_ADDR_HOST),
+            ip::Endpoint::new(IpCidr::new(IP_ADDR_HOST.into(), 24),
+                ip::Routes::new(&mut routes[..]),
+                neighbors));
+        let nic = Loopback::<Vec<u8>>::new(vec![0; 1 << 12].into());
+        let mut interface = Interface::new(nic, stack);
+        let mut app = Ping::default();
+
+        // Without an endpoint nothing is sent.
+        assert_eq!(interface.poll(Instant::ZERO, &mut app), Ok(Expiration::Never));
+        assert_eq!(app.sent, 0);
+
+        // The datagram sent in the first poll is received in the second.
+        interface.udp = Some(udp::Endpoint::new(80));
+        interface.poll(Instant::ZERO, &mut app).unwrap();
+        assert_eq!((app.sent, app.received), (1, 0));
+        interface.poll(Instant::ZERO, &mut app).unwrap();
+        assert_eq!((app.sent, app.received), (1, 1));
+    }
+}
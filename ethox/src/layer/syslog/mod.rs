@@ -0,0 +1,109 @@
+//! A syslog sender, rfc5424 messages transported over udp as in rfc5426.
+//!
+//! Devices without a host operating system can ship their logs to a collector with the
+//! [`Sender`]. Messages are formatted immediately into a byte queue provided by the caller and
+//! leave in the order they were logged, one datagram each, whenever the sender is offered a packet
+//! buffer. A token bucket limits the rate of datagrams so that a burst of log messages can not
+//! flood the link, messages wait in the queue in the meantime. When the queue is full new messages
+//! are dropped and counted.
+//!
+//! The sender sits on top of the udp layer and only implements its sending side. Drive its timer
+//! with [`Sender::tick`] and offer it a packet buffer whenever it has expired.
+//!
+//! Timestamps are unix time, which an embedded device usually learns from an [`sntp`] client.
+//! Without one the timestamp of a message is left empty, as the protocol allows.
+//!
+//! [`Sender`]: struct.Sender.html
+//! [`Sender::tick`]: struct.Sender.html#method.tick
+//! [`sntp`]: ../sntp/index.html
+mod sender;
+#[cfg(test)]
+mod tests;
+
+pub use sender::{
+    Limit,
+    Message,
+    Sender,
+    SYSLOG_PORT,
+};
+
+/// The severity of a message, rfc5424 section 6.2.1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The system is unusable.
+    Emergency = 0,
+    /// Action must be taken immediately.
+    Alert = 1,
+    /// Critical conditions.
+    Critical = 2,
+    /// Error conditions.
+    Error = 3,
+    /// Warning conditions.
+    Warning = 4,
+    /// Normal but significant condition.
+    Notice = 5,
+    /// Informational messages.
+    Informational = 6,
+    /// Debug-level messages.
+    Debug = 7,
+}
+
+/// The facility producing a message, rfc5424 section 6.2.1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Facility {
+    /// Kernel messages.
+    Kernel = 0,
+    /// User-level messages.
+    User = 1,
+    /// Mail system.
+    Mail = 2,
+    /// System daemons.
+    Daemon = 3,
+    /// Security and authorization messages.
+    Auth = 4,
+    /// Messages generated internally by syslogd.
+    Syslog = 5,
+    /// Line printer subsystem.
+    Lpr = 6,
+    /// Network news subsystem.
+    News = 7,
+    /// UUCP subsystem.
+    Uucp = 8,
+    /// Clock daemon.
+    Cron = 9,
+    /// Private security and authorization messages.
+    AuthPriv = 10,
+    /// FTP daemon.
+    Ftp = 11,
+    /// NTP subsystem.
+    Ntp = 12,
+    /// Log audit.
+    Audit = 13,
+    /// Log alert.
+    Alert = 14,
+    /// Clock daemon.
+    Clock = 15,
+    /// Local use 0.
+    Local0 = 16,
+    /// Local use 1.
+    Local1 = 17,
+    /// Local use 2.
+    Local2 = 18,
+    /// Local use 3.
+    Local3 = 19,
+    /// Local use 4.
+    Local4 = 20,
+    /// Local use 5.
+    Local5 = 21,
+    /// Local use 6.
+    Local6 = 22,
+    /// Local use 7.
+    Local7 = 23,
+}
+
+impl Facility {
+    /// The priority value of a message of this facility with a severity.
+    pub fn priority(self, severity: Severity) -> u8 {
+        (self as u8) * 8 + severity as u8
+    }
+}
@@ -0,0 +1,367 @@
+use core::fmt::{self, Write as _};
+
+use crate::layer::{udp, Error, Result};
+use crate::storage::ByteRing;
+use crate::time::{Clock, Duration, Expiration, Instant};
+use crate::wire::{IpAddress, IpSubnet, Ipv4Subnet, Ipv6Subnet, PayloadMut};
+
+use super::{Facility, Severity};
+
+/// The well-known port of syslog collectors, rfc5426 section 3.3.
+pub const SYSLOG_PORT: u16 = 514;
+
+/// The bytes in front of each queued message, holding its length.
+const LEN_PREFIX: usize = 2;
+
+/// The longest header fields permitted by rfc5424 section 6.
+const MAX_HOSTNAME: usize = 255;
+const MAX_APP_NAME: usize = 48;
+const MAX_MSG_ID: usize = 32;
+
+const MILLIS_PER_DAY: i64 = 86_400_000;
+
+/// The rate limit of a sender.
+///
+/// Up to `burst` messages are sent back to back, afterwards one more each `interval`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Limit {
+    /// The number of messages that may be sent at once.
+    pub burst: u32,
+    /// The time it takes to permit one more message.
+    pub interval: Duration,
+}
+
+/// A message to be logged.
+#[derive(Clone, Copy, Debug)]
+pub struct Message<'m> {
+    /// How urgent the message is.
+    pub severity: Severity,
+    /// The time at which the event occurred, in milliseconds since the unix epoch.
+    pub timestamp: Option<Instant>,
+    /// Identifies the type of the message, empty if there is none.
+    pub msg_id: &'m str,
+    /// The free-form text of the message.
+    pub text: fmt::Arguments<'m>,
+}
+
+/// Sends log messages to a single collector.
+///
+/// Messages are formatted into the queue when they are logged, prefixed with their length. The
+/// queue must be large enough to hold the longest message that should not be dropped.
+pub struct Sender<'a> {
+    collector: IpAddress,
+    src_port: u16,
+    dst_port: u16,
+    hostname: &'a str,
+    app_name: &'a str,
+    facility: Facility,
+    queue: ByteRing<'a>,
+    max_len: usize,
+    limit: Limit,
+    tokens: u32,
+    last: Option<Instant>,
+    sent: u64,
+    dropped: u64,
+}
+
+/// Writes a message into the free space of the queue, behind the length prefix.
+struct Record<'r, 'a> {
+    queue: &'r mut ByteRing<'a>,
+    len: usize,
+    max_len: usize,
+}
+
+impl Limit {
+    /// Ten messages per second, with bursts of up to sixteen.
+    pub const DEFAULT: Limit = Limit {
+        burst: 16,
+        interval: Duration::from_millis(100),
+    };
+}
+
+impl Default for Limit {
+    fn default() -> Self {
+        Limit::DEFAULT
+    }
+}
+
+impl<'a> Sender<'a> {
+    /// The default length of messages, longer ones are truncated.
+    ///
+    /// Datagrams of this size fit into the usual Ethernet mtu, also over IPv6.
+    pub const DEFAULT_MAX_LEN: usize = 1024;
+
+    /// Create a sender of messages to a collector.
+    ///
+    /// The messages are sent from `src_port`, which the udp endpoint must accept. The `hostname`
+    /// and `app_name` are included in the header of each message and should consist of printable
+    /// ascii only, other characters are replaced.
+    pub fn new(
+        collector: IpAddress,
+        src_port: u16,
+        queue: ByteRing<'a>,
+        hostname: &'a str,
+        app_name: &'a str,
+    ) -> Self {
+        Sender {
+            collector,
+            src_port,
+            dst_port: SYSLOG_PORT,
+            hostname,
+            app_name,
+            facility: Facility::User,
+            queue,
+            max_len: Self::DEFAULT_MAX_LEN,
+            limit: Limit::DEFAULT,
+            tokens: Limit::DEFAULT.burst,
+            last: None,
+            sent: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Change the port of the collector, which is the well-known port by default.
+    pub fn set_collector_port(&mut self, port: u16) {
+        self.dst_port = port;
+    }
+
+    /// Change the facility of all messages, `User` by default.
+    pub fn set_facility(&mut self, facility: Facility) {
+        self.facility = facility;
+    }
+
+    /// Change the rate limit.
+    ///
+    /// The bucket is filled up again, so a new burst is permitted immediately.
+    pub fn set_limit(&mut self, limit: Limit) {
+        self.limit = limit;
+        self.tokens = limit.burst;
+        self.last = None;
+    }
+
+    /// Change the length at which messages are truncated.
+    ///
+    /// Datagrams must fit into the packet buffers and the mtu of the link. Messages already queued
+    /// are not affected.
+    pub fn set_max_len(&mut self, len: usize) {
+        self.max_len = len.min(usize::from(u16::MAX));
+    }
+
+    /// The number of messages sent to the collector.
+    pub fn sent(&self) -> u64 {
+        self.sent
+    }
+
+    /// The number of messages dropped because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Check if messages are waiting to be sent.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Log a message without a timestamp or message id.
+    pub fn log(&mut self, severity: Severity, text: fmt::Arguments) -> Result<()> {
+        self.push(&Message {
+            severity,
+            timestamp: None,
+            msg_id: "",
+            text,
+        })
+    }
+
+    /// Format a message into the queue.
+    ///
+    /// Fails with `Exhausted` if the queue has no room for it, in which case the message is
+    /// counted as dropped.
+    pub fn push(&mut self, message: &Message) -> Result<()> {
+        let (hostname, app_name) = (self.hostname, self.app_name);
+        let priority = self.facility.priority(message.severity);
+        let mut record = Record {
+            queue: &mut self.queue,
+            len: 0,
+            max_len: self.max_len,
+        };
+
+        let formatted = write!(record, "<{}>1 ", priority)
+            .and_then(|_| record.timestamp(message.timestamp))
+            .and_then(|_| record.put(b" "))
+            .and_then(|_| record.field(hostname, MAX_HOSTNAME))
+            .and_then(|_| record.put(b" "))
+            .and_then(|_| record.field(app_name, MAX_APP_NAME))
+            // There is no process id and no structured data.
+            .and_then(|_| record.put(b" - "))
+            .and_then(|_| record.field(message.msg_id, MAX_MSG_ID))
+            .and_then(|_| record.put(b" - "))
+            .and_then(|_| record.write_fmt(message.text));
+
+        let len = record.len;
+        let prefix = (len as u16).to_be_bytes();
+        if formatted.is_err() || self.queue.write_unallocated(0, &prefix) < LEN_PREFIX {
+            self.dropped += 1;
+            return Err(Error::Exhausted);
+        }
+
+        self.queue.enqueue_unallocated(LEN_PREFIX + len);
+        Ok(())
+    }
+
+    /// Get the time at which the sender wants to send its next message.
+    ///
+    /// Offer it a packet buffer at that point.
+    pub fn poll(&self, now: Instant) -> Expiration {
+        if self.queue.is_empty() {
+            return Expiration::Never;
+        }
+
+        match self.last {
+            Some(last) if self.tokens == 0 => Expiration::When(last + self.limit.interval),
+            _ => Expiration::When(now),
+        }
+    }
+
+    /// Get the time of the next message, with the current time of a clock.
+    pub fn tick<C: Clock + ?Sized>(&self, clock: &C) -> Expiration {
+        self.poll(clock.now())
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let last = match self.last {
+            Some(last) if last <= now => last,
+            // Also restart if the clock jumped backwards.
+            _ => {
+                self.last = Some(now);
+                return;
+            },
+        };
+
+        let interval = self.limit.interval.as_millis().max(1);
+        let refills = (now - last).as_millis() / interval;
+        if refills == 0 {
+            return;
+        }
+
+        let refills = refills.min(u128::from(self.limit.burst)) as u32;
+        self.tokens = self.tokens.saturating_add(refills).min(self.limit.burst);
+        self.last = Some(now);
+    }
+
+    /// Send the oldest message of the queue.
+    fn send_record<P: PayloadMut>(&mut self, raw: udp::RawPacket<P>) -> Result<()> {
+        let source = match self.collector {
+            IpAddress::Ipv4(_) => IpSubnet::from(Ipv4Subnet::ANY),
+            IpAddress::Ipv6(_) => IpSubnet::from(Ipv6Subnet::ANY),
+            _ => return Err(Error::Illegal),
+        };
+
+        let mut prefix = [0; LEN_PREFIX];
+        self.queue.read_allocated(0, &mut prefix);
+        let len = usize::from(u16::from_be_bytes(prefix));
+
+        let init = udp::Init {
+            source: source.into(),
+            src_port: self.src_port,
+            dst_addr: self.collector,
+            dst_port: self.dst_port,
+            payload: len,
+            dscp: 0,
+        };
+
+        let mut packet = raw.prepare(init)?;
+        self.queue.read_allocated(LEN_PREFIX, packet.packet.payload_mut_slice());
+        packet.send()?;
+        self.queue.dequeue_allocated(LEN_PREFIX + len);
+        Ok(())
+    }
+}
+
+impl Record<'_, '_> {
+    /// Append bytes, silently truncating the message at the maximum length.
+    fn put(&mut self, bytes: &[u8]) -> fmt::Result {
+        let len = bytes.len().min(self.max_len - self.len);
+        let written = self.queue.write_unallocated(LEN_PREFIX + self.len, &bytes[..len]);
+        self.len += written;
+        if written < len {
+            // The queue is full.
+            return Err(fmt::Error);
+        }
+        Ok(())
+    }
+
+    /// Append a header field, replacing characters that are not printable ascii.
+    fn field(&mut self, value: &str, max_len: usize) -> fmt::Result {
+        if value.is_empty() {
+            return self.put(b"-");
+        }
+
+        for &byte in value.as_bytes().iter().take(max_len) {
+            let byte = if byte.is_ascii_graphic() { byte } else { b'_' };
+            self.put(&[byte])?;
+        }
+        Ok(())
+    }
+
+    /// Append a timestamp in the format of rfc3339, in UTC.
+    fn timestamp(&mut self, timestamp: Option<Instant>) -> fmt::Result {
+        let millis = match timestamp {
+            Some(timestamp) => timestamp.total_millis(),
+            None => return self.put(b"-"),
+        };
+
+        let (year, month, day) = civil_from_days(millis.div_euclid(MILLIS_PER_DAY));
+        if !(0..=9999).contains(&year) {
+            return self.put(b"-");
+        }
+
+        let millis = millis.rem_euclid(MILLIS_PER_DAY);
+        write!(self, "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+            year, month, day,
+            millis / 3_600_000, millis / 60_000 % 60, millis / 1000 % 60, millis % 1000)
+    }
+}
+
+impl fmt::Write for Record<'_, '_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // Do not cut a character in half when truncating.
+        let mut len = s.len().min(self.max_len - self.len);
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.put(&s.as_bytes()[..len])
+    }
+}
+
+impl<P: PayloadMut> udp::Send<P> for Sender<'_> {
+    fn send(&mut self, raw: udp::RawPacket<P>) {
+        let now = raw.handle.info().timestamp();
+        self.refill(now);
+        if self.queue.is_empty() || self.tokens == 0 {
+            return;
+        }
+
+        // Failed attempts, such as for a missing route, are rate limited as well and the message
+        // is retried with the next token.
+        self.tokens -= 1;
+        if self.send_record(raw).is_ok() {
+            self.sent += 1;
+        }
+    }
+}
+
+/// The proleptic gregorian date of a day since the unix epoch.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524
+        - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Months starting with March, so that the leap day is last.
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,70 @@
+use super::*;
+use crate::layer::Error;
+use crate::layer::udp::testing::{Host, IP_ADDR_PEER as IP_ADDR_COLLECTOR};
+use crate::storage::RingBuffer;
+use crate::time::{Duration, Expiration, Instant};
+
+const SENDER_PORT: u16 = 5140;
+
+/// Let the sender send at most one datagram and return its payload.
+fn send(sender: &mut Sender, now: Instant) -> Option<Vec<u8>> {
+    Host::new(vec![SENDER_PORT]).send_datagram(now, (SENDER_PORT, SYSLOG_PORT), sender)
+}
+
+fn sender(queue: usize) -> Sender<'static> {
+    let queue = RingBuffer::new(vec![0; queue]);
+    Sender::new(IP_ADDR_COLLECTOR.into(), SENDER_PORT, queue, "sensor 1", "ethox")
+}
+
+#[test]
+fn format() {
+    let mut sender = sender(1024);
+    sender.set_facility(Facility::Local4);
+    sender.push(&Message {
+        severity: Severity::Warning,
+        timestamp: Some(Instant::from_millis(1_700_000_000_123i64)),
+        msg_id: "TEMP",
+        text: format_args!("temperature at {} °C", 71),
+    }).unwrap();
+    sender.log(Severity::Debug, format_args!("")).unwrap();
+
+    let now = Instant::from_secs(0);
+    assert_eq!(send(&mut sender, now).unwrap(),
+        "<164>1 2023-11-14T22:13:20.123Z sensor_1 ethox - TEMP - temperature at 71 °C".as_bytes());
+    assert_eq!(send(&mut sender, now).unwrap(), b"<167>1 - sensor_1 ethox - - - ");
+    assert_eq!(send(&mut sender, now), None);
+    assert_eq!(sender.poll(now), Expiration::Never);
+    assert_eq!(sender.sent(), 2);
+
+    // Long messages are truncated, but not within a character.
+    sender.set_max_len(36);
+    sender.log(Severity::Error, format_args!("{}", "°".repeat(8))).unwrap();
+    assert_eq!(send(&mut sender, now).unwrap(),
+        "<163>1 - sensor_1 ethox - - - °°°".as_bytes());
+}
+
+#[test]
+fn rate_limit() {
+    let mut sender = sender(64);
+    sender.set_limit(Limit { burst: 2, interval: Duration::from_secs(1) });
+    sender.log(Severity::Notice, format_args!("0")).unwrap();
+    sender.log(Severity::Notice, format_args!("1")).unwrap();
+    // The queue has room for exactly two messages.
+    assert_eq!(sender.log(Severity::Notice, format_args!("2")), Err(Error::Exhausted));
+    assert_eq!(sender.dropped(), 1);
+
+    let start = Instant::from_secs(0);
+    assert_eq!(sender.poll(start), Expiration::When(start));
+    assert!(send(&mut sender, start).unwrap().ends_with(b"0"));
+    sender.log(Severity::Notice, format_args!("3")).unwrap();
+    assert!(send(&mut sender, start).unwrap().ends_with(b"1"));
+
+    // The burst is used up.
+    assert_eq!(send(&mut sender, start), None);
+    let refill = start + Duration::from_secs(1);
+    assert_eq!(sender.poll(start), Expiration::When(refill));
+    assert_eq!(send(&mut sender, start + Duration::from_millis(500)), None);
+    assert!(send(&mut sender, refill).unwrap().ends_with(b"3"));
+    assert!(sender.is_empty());
+    assert_eq!(sender.sent(), 3);
+}
@@ -10,7 +10,11 @@ use core::ops::Range;
 use crate::time::{Duration, Expiration, Instant};
 use crate::wire::{IpAddress, TcpFlags, TcpRepr, TcpSeqNumber};
 
+use super::events::{Event, Snapshot};
+use super::options::Options;
+use super::pacing::Pacer;
 use super::endpoint::{
+    BufferSpace,
     Entry,
     EntryKey,
     FourTuple,
@@ -93,6 +97,26 @@ pub struct Connection {
     /// Counter of duplicated acks.
     pub duplicate_ack: u8,
 
+    /// The differentiated services code point of all segments sent on the connection.
+    pub dscp: u8,
+
+    /// The configuration of the connection.
+    ///
+    /// Change it with `set_options` so that the timeouts derived from it follow.
+    pub options: Options,
+
+    /// Counters of the segments exchanged on the connection.
+    pub statistics: Statistics,
+
+    /// Retransmissions and keepalive probes since the remote last sent an acceptable segment.
+    pub retransmissions: u32,
+
+    /// The round trip time estimation.
+    pub round_trip: RoundTrip,
+
+    /// The release time of the next new segment when the connection is paced.
+    pub pacer: Pacer,
+
     /// The sending state.
     ///
     /// In RFC793 this is referred to as `SND`.
@@ -120,6 +144,11 @@ pub struct Send {
     /// The time of the last valid packet.
     pub last_time: Instant,
 
+    /// Since when the oldest unacknowledged sequence number has been outstanding.
+    ///
+    /// Restarts with each acknowledgement of new data, the user timeout is measured from it.
+    pub progress: Instant,
+
     /// Number of bytes available for sending in total.
     ///
     /// In contrast to `unacked` this is the number of bytes that have not yet been sent. The
@@ -138,12 +167,26 @@ pub struct Send {
     /// Guaranteed to be at most 14 so that shifting the window in a `u32`/`i32` is always safe.
     pub window_scale: u8,
 
+    /// The largest window the receiver has offered, in bytes.
+    ///
+    /// In RFC1122 this is referred to as `Max(SND.WND)`.
+    pub max_window: u32,
+
     /// The initial sequence number.
     ///
     /// This is read-only and only kept for potentially reading it for debugging later. It
     /// essentially provides a way of tracking the sent data. In RFC793 this is referred to as
     /// `ISS`.
     pub initial_seq: TcpSeqNumber,
+
+    /// The user requested to close the sending direction.
+    ///
+    /// A FIN is sent after all data available in the send buffer, as if the buffer itself had
+    /// indicated its end.
+    pub shutdown: bool,
+
+    /// The most data that may be in flight, the share of the endpoint's buffer limits.
+    pub limit: u32,
 }
 
 /// The connection state relevant for incoming segments.
@@ -176,6 +219,20 @@ pub struct Receive {
     /// Guaranteed to be at most 14 so that shifting the window in a `u32`/`i32` is always safe.
     pub window_scale: u8,
 
+    /// The right edge of the window last offered to the remote.
+    pub edge: TcpSeqNumber,
+
+    /// The right edge of the window in the last acknowledgment sent.
+    ///
+    /// When the offered edge moves past it, the remote learns of the reopened window with a
+    /// window update.
+    pub advertised: TcpSeqNumber,
+
+    /// The largest window offered to the remote, in bytes.
+    ///
+    /// An empty receive buffer offers all of its space, so this approximates its size.
+    pub max_window: u32,
+
     /// The initial receive sequence number.
     ///
     /// This is read-only and only kept for potentially reading it for debugging later. It
@@ -205,12 +262,13 @@ pub enum State {
     /// An open connection.
     Established,
 
-    /// Closed our side of the connection.
+    /// Closed our side of the connection, our FIN has not been acknowledged yet.
+    FinWait1,
+
+    /// Closed our side of the connection and the remote acknowledged it.
     ///
-    /// This is split into two states (FinWait1 and FinWait2) in the RFC where we track whether our
-    /// own FIN has been ack'ed. This is of importance for answering CLOSE calls but can be
-    /// supplemented in the Io implementation. Transition to the TimeWait state works the same.
-    FinWait,
+    /// Incoming data is still received until the remote closes its side as well.
+    FinWait2,
 
     /// Closed both sides but we don't know the other knows.
     Closing,
@@ -225,6 +283,88 @@ pub enum State {
     LastAck,
 }
 
+/// Counters of the traffic on a connection.
+///
+/// These are updated as segments arrive or are chosen for sending and never reset while the
+/// connection exists. Tools can sample them periodically and report the difference, similar to
+/// the statistics of a socket in other stacks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Statistics {
+    /// The number of segments sent, including retransmissions and pure ACKs.
+    pub segments_sent: u64,
+
+    /// The number of data bytes sent, including retransmissions.
+    pub bytes_sent: u64,
+
+    /// The number of data segments that were retransmitted.
+    pub retransmits: u64,
+
+    /// The number of data bytes that were retransmitted.
+    pub bytes_retransmitted: u64,
+
+    /// The number of segments that arrived.
+    pub segments_received: u64,
+
+    /// The number of data bytes in arriving segments, including duplicates.
+    pub bytes_received: u64,
+}
+
+/// Estimates the round trip time of a connection.
+///
+/// Follows RFC6298 with a single timed segment at a time. Retransmitted segments are never timed
+/// (Karn's algorithm) as their acknowledgment is ambiguous.
+#[derive(Clone, Copy, Debug, Default, Hash)]
+pub struct RoundTrip {
+    /// The smoothed round trip time, `SRTT`, if any sample was taken yet.
+    pub smoothed: Option<Duration>,
+
+    /// The round trip time variation, `RTTVAR`.
+    pub variation: Duration,
+
+    /// The end of the timed segment and the time at which it was sent.
+    pub timed: Option<(TcpSeqNumber, Instant)>,
+}
+
+/// A snapshot of the internal state of a connection.
+///
+/// Similar to the `TCP_INFO` socket option of other stacks. This is a copy and does not change
+/// when the connection progresses, sample it again to observe changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Info {
+    /// The current state of the state machine.
+    pub state: State,
+
+    /// The smoothed round trip time, if it has been measured yet.
+    pub smoothed_rtt: Option<Duration>,
+
+    /// The round trip time variation.
+    pub rtt_variation: Duration,
+
+    /// The current retransmission timeout.
+    pub retransmission_timeout: Duration,
+
+    /// The congestion window in bytes.
+    pub congestion_window: u32,
+
+    /// The slow start threshold in bytes.
+    pub ssthresh: u32,
+
+    /// The sequence space sent but not yet acknowledged.
+    pub in_flight: u32,
+
+    /// The number of retransmitted segments.
+    pub retransmits: u64,
+
+    /// The send window indicated by the remote, in bytes.
+    pub send_window: u32,
+
+    /// The receive window we indicated to the remote, in bytes.
+    pub receive_window: u32,
+
+    /// The maximum segment size used for sending.
+    pub sender_maximum_segment_size: u16,
+}
+
 /// Models TCP Reno flow control and congestion avoidance.
 #[derive(Clone, Copy, Debug, Hash)]
 pub struct Flow {
@@ -277,6 +417,9 @@ pub struct AvailableBytes {
     /// Set when no more data will come.
     pub fin: bool,
 
+    /// Request the PSH flag on the segment carrying the last available byte.
+    pub push: bool,
+
     /// The total number of bytes buffered for retransmission and newly available.
     pub total: usize,
 }
@@ -319,8 +462,17 @@ pub struct InPacket {
     /// The sender address.
     pub from: IpAddress,
 
+    /// The local destination address.
+    pub to: IpAddress,
+
     /// The arrival time of the packet at the nic.
     pub time: Instant,
+
+    /// The largest segment the device can send towards the sender.
+    ///
+    /// Derived from the maximum transmission unit less the ip and tcp headers, `None` if the
+    /// device does not limit the packet size.
+    pub segment_size: Option<u16>,
 }
 
 /// An outgoing segment.
@@ -341,6 +493,9 @@ pub struct Segment {
 pub struct OutSignals {
     pub delete: bool,
 
+    /// The connection gave up on an unresponsive remote.
+    pub aborted: bool,
+
     /// A packet was selected to be generated.
     ///
     /// Some packets (ACKs or during connection closing) are only generated after the data of an
@@ -363,15 +518,28 @@ pub trait Endpoint {
 
     fn remove(&mut self, index: SlotKey);
 
+    /// Update the timer and the buffered data of the connection after its state changed.
+    fn reschedule(&mut self, index: SlotKey);
+
+    /// The buffered data the connection is permitted to hold under the limits of the endpoint.
+    fn allowance(&self, index: SlotKey) -> BufferSpace;
+
     fn find_tuple(&mut self, tuple: FourTuple) -> Option<Entry>;
 
-    fn source_port(&mut self, addr: IpAddress) -> Option<u16>;
+    fn source_port(&mut self, local: IpAddress, remote: IpAddress, remote_port: u16)
+        -> Option<u16>;
 
     fn listen(&mut self, ip: IpAddress, port: u16) -> Option<SlotKey>;
 
     fn open(&mut self, tuple: FourTuple) -> Option<SlotKey>;
 
+    /// The options of newly created connections.
+    fn options(&self) -> Options;
+
     fn initial_seq_num(&mut self, id: FourTuple, time: Instant) -> TcpSeqNumber;
+
+    /// Pass an event of a connection to the observer, if any.
+    fn notify(&mut self, index: SlotKey, event: Event);
 }
 
 /// The interface to a single active connection on an endpoint.
@@ -402,6 +570,7 @@ struct InnerRepr {
     max_seg_size: Option<u16>,
     sack_permitted: bool,
     sack_ranges:  [Option<(u32, u32)>; 3],
+    user_timeout: Option<u16>,
     payload_len:  u16,
 }
 
@@ -427,14 +596,24 @@ impl Connection {
             restart_timeout: Duration::from_millis(0),
             selective_acknowledgements: false,
             duplicate_ack: 0,
+            dscp: 0,
+            options: Options::DEFAULT,
+            statistics: Statistics::default(),
+            retransmissions: 0,
+            round_trip: RoundTrip::default(),
+            pacer: Pacer::default(),
             send: Send {
                 unacked: TcpSeqNumber::default(),
                 next: TcpSeqNumber::default(),
                 last_time: Instant::from_millis(0),
+                progress: Instant::from_millis(0),
                 unsent: 0,
                 window: 0,
                 window_scale: 0,
+                max_window: 0,
                 initial_seq: TcpSeqNumber::default(),
+                shutdown: false,
+                limit: u32::MAX,
             },
             recv: Receive {
                 next: TcpSeqNumber::default(),
@@ -442,19 +621,121 @@ impl Connection {
                 last_time: Instant::from_millis(0),
                 window: 0,
                 window_scale: 0,
+                edge: TcpSeqNumber::default(),
+                advertised: TcpSeqNumber::default(),
+                max_window: 0,
                 initial_seq: TcpSeqNumber::default(),
             },
         }
     }
 
+    /// Take a snapshot of the connection internals.
+    pub fn info(&self) -> Info {
+        Info {
+            state: self.current,
+            smoothed_rtt: self.round_trip.smoothed,
+            rtt_variation: self.round_trip.variation,
+            retransmission_timeout: self.retransmission_timeout,
+            congestion_window: self.flow_control.congestion_window,
+            ssthresh: self.flow_control.ssthresh,
+            in_flight: self.send.in_flight(),
+            retransmits: self.statistics.retransmits,
+            send_window: self.send.window(),
+            receive_window: u32::from(self.recv.window) << self.recv.window_scale,
+            sender_maximum_segment_size: self.sender_maximum_segment_size,
+        }
+    }
+
+    /// Change the configuration of the connection.
+    ///
+    /// The ACK delay and the idle restart apply immediately. A new initial retransmission timeout
+    /// only applies while no round trip time has been measured, afterwards the estimate is used.
+    pub fn set_options(&mut self, options: Options) {
+        self.options = options;
+        self.ack_timeout = options.ack_delay;
+        self.restart_timeout = options.idle_restart;
+        if self.round_trip.smoothed.is_none() {
+            self.retransmission_timeout = options.initial_rto;
+        }
+    }
+
+    /// Close the sending direction of the connection.
+    ///
+    /// Queues a FIN after all data that the send buffer has available at that point. Incoming
+    /// data is still received until the remote closes its direction as well. Calling this more
+    /// than once has no further effect.
+    pub fn shutdown(&mut self) {
+        self.send.shutdown = true;
+    }
+
+    /// Offer the space of the receive buffer to the remote as the window.
+    ///
+    /// The space is capped by the `receive_window` option. To avoid the silly window syndrome, a
+    /// window that has shrunk is reopened only in steps of a full segment or of half the buffer.
+    pub fn offer_window(&mut self, available: usize) {
+        let cap = usize::try_from(self.options.receive_window).unwrap_or(usize::MAX);
+        let available = u32::try_from(available.min(cap)).unwrap_or(u32::MAX);
+        self.recv.max_window = self.recv.max_window.max(available);
+
+        // See: https://tools.ietf.org/html/rfc1122#page-97
+        let offered = if self.recv.edge > self.recv.next {
+            (self.recv.edge - self.recv.next) as u32
+        } else {
+            0
+        };
+        let threshold = u32::from(self.receiver_maximum_segment_size)
+            .min(self.recv.max_window / 2);
+        let window = if available > offered && available - offered < threshold {
+            offered
+        } else {
+            available
+        };
+
+        // UNWRAP: The window is at most a `u32`, which fits into `usize` on supported platforms.
+        self.recv.update_window(usize::try_from(window).unwrap());
+        let window = u32::from(self.recv.window) << self.recv.window_scale;
+        self.recv.edge = self.recv.next + window as usize;
+    }
+
+    /// The data buffered for the connection, accounted against the limits of its endpoint.
+    ///
+    /// The receive direction holds the window offered to the remote, the send direction the data
+    /// in flight.
+    pub fn buffered(&self) -> BufferSpace {
+        let recv = match self.current {
+            State::Closed | State::Listen | State::SynSent => 0,
+            _ if self.recv.edge > self.recv.next => self.recv.edge - self.recv.next,
+            _ => 0,
+        };
+
+        BufferSpace {
+            // UNWRAP: a `u32` fits into `usize` on supported platforms.
+            send: usize::try_from(self.send.in_flight()).unwrap(),
+            recv,
+        }
+    }
+
+    /// Check if the remote has closed its sending direction.
+    ///
+    /// This is the case after its FIN has been received and acknowledged to the reader.
+    pub fn is_remote_closed(&self) -> bool {
+        matches!(self.current,
+            State::CloseWait | State::LastAck | State::Closing | State::TimeWait)
+    }
+
     /// Handle an arriving packet.
     pub fn arrives(&mut self, incoming: &InPacket, entry: EntryKey) -> Signals {
+        self.statistics.segments_received += 1;
+        self.statistics.bytes_received += u64::from(incoming.segment.payload_len);
+
         match self.current {
             State::Closed => self.arrives_closed(incoming),
             State::Listen => self.arrives_listen(incoming, entry),
             State::SynSent => self.arrives_syn_sent(incoming, entry),
-            State::Established | State::FinWait => self.arrives_established(incoming, entry),
-            _ => unimplemented!(),
+            State::SynReceived => self.arrives_syn_received(incoming, entry),
+            State::Established | State::FinWait1 | State::FinWait2 | State::CloseWait
+                | State::Closing | State::LastAck | State::TimeWait
+                => self.arrives_established(incoming, entry),
         }
     }
 
@@ -471,6 +752,7 @@ impl Connection {
         self.send.initial_seq = entry.initial_seq_num(time);
         self.send.unacked = self.send.initial_seq;
         self.send.next = self.send.initial_seq + 1;
+        self.send.progress = time;
         // Schedule 'immediate' transmission.
         self.retransmission_timer = time;
 
@@ -500,6 +782,7 @@ impl Connection {
                 max_seg_size: None,
                 sack_permitted: false,
                 sack_ranges: [None; 3],
+                user_timeout: None,
                 payload_len: 0,
             }.send_back(segment));
         } else {
@@ -512,6 +795,7 @@ impl Connection {
                 max_seg_size: None,
                 sack_permitted: false,
                 sack_ranges: [None; 3],
+                user_timeout: None,
                 payload_len: 0,
             }.send_back(segment));
         }
@@ -531,7 +815,7 @@ impl Connection {
         //
         // The harder part seems to be that syn cookies require a new operation within Signals.
 
-        let InPacket { segment, from, time, } = incoming;
+        let InPacket { segment, from, to, time, segment_size } = incoming;
         let mut signals = Signals::default();
 
         if segment.flags.rst() {
@@ -548,6 +832,7 @@ impl Connection {
                 max_seg_size: None,
                 sack_permitted: false,
                 sack_ranges: [None; 3],
+                user_timeout: None,
                 payload_len: 0,
             }.send_back(segment));
             return signals;
@@ -558,39 +843,97 @@ impl Connection {
             return signals;
         }
 
+        // The listener may be bound to an unspecified address. The connection itself uses the
+        // local address to which the SYN was sent.
         let current_four = entry.four_tuple();
         let new_four = FourTuple {
+            local: *to,
             remote: *from,
-            .. current_four
+            local_port: current_four.local_port,
+            remote_port: segment.src_port,
         };
-        entry.set_four_tuple(new_four);
+
+        if entry.set_four_tuple(new_four).is_err() {
+            // The tuple belongs to another connection already. Refuse the attempt as a closed
+            // port would, the listener remains available.
+            return self.arrives_closed(incoming);
+        }
         self.recv.next = segment.seq_number + 1;
         self.recv.initial_seq = segment.seq_number;
+        self.send.window_scale = segment.window_scale.unwrap_or(0);
+        self.send.set_window(segment.window_len);
+
+        self.set_segment_sizes(segment.max_seg_size, *segment_size);
+        self.options.remote_user_timeout(segment.user_timeout);
 
         let isn = entry.initial_seq_num(*time);
         self.send.next = isn + 1;
         self.send.unacked = isn;
         self.send.initial_seq = isn;
+        self.send.progress = *time;
 
-        signals.answer = Some(InnerRepr {
-            flags: TcpFlags::RST,
-            seq_number: isn,
-            ack_number: Some(self.ack_all()),
-            window_len: self.recv.window,
-            window_scale: None,
-            max_seg_size: None,
-            sack_permitted: false,
-            sack_ranges: [None; 3],
-            payload_len: 0,
-        }.send_to(new_four));
-
+        self.change_state(State::SynReceived);
+        self.rearm_retransmission_timer(*time);
+        signals.answer = Some(self.send_open(true, new_four));
         signals
     }
 
+    /// Handle an incoming packet after we have answered a SYN.
+    fn arrives_syn_received(&mut self, incoming: &InPacket, entry: EntryKey)
+        -> Signals
+    {
+        let segment = &incoming.segment;
+
+        if segment.flags.rst() {
+            if self.recv.in_window(segment.seq_number) {
+                return self.remote_reset_connection();
+            }
+            return Signals::default();
+        }
+
+        if segment.flags.syn() {
+            // Our SYN-ACK was probably lost, repeat it.
+            if segment.seq_number == self.recv.initial_seq {
+                return Signals {
+                    answer: Some(self.send_open(true, entry.four_tuple())),
+                    ..Signals::default()
+                };
+            }
+            return Signals::default();
+        }
+
+        let ack = match segment.ack_number {
+            None => return Signals::default(),
+            Some(ack) => ack,
+        };
+
+        if ack <= self.send.initial_seq || ack > self.send.next {
+            return Signals {
+                answer: Some(InnerRepr {
+                    flags: TcpFlags::RST,
+                    seq_number: ack,
+                    ack_number: None,
+                    window_len: 0,
+                    window_scale: None,
+                    max_seg_size: None,
+                    sack_permitted: false,
+                    sack_ranges: [None; 3],
+                    user_timeout: None,
+                    payload_len: 0,
+                }.send_back(segment)),
+                ..Signals::default()
+            };
+        }
+
+        // The handshake is complete, the segment itself is handled as on an open connection.
+        self.change_state(State::Established);
+        self.arrives_established(incoming, entry)
+    }
+
     fn arrives_syn_sent(&mut self, incoming: &InPacket, entry: EntryKey)
         -> Signals
     {
-        let InPacket { segment, from: _, time, } = incoming;
+        let InPacket { segment, time, segment_size, .. } = incoming;
 
         if let Some(ack) = segment.ack_number {
             if ack <= self.send.initial_seq || ack > self.send.next {
@@ -609,6 +952,7 @@ impl Connection {
                     max_seg_size: None,
                     sack_permitted: false,
                     sack_ranges: [None; 3],
+                    user_timeout: None,
                     payload_len: 0,
                 }.send_back(segment));
                 return signals;
@@ -631,17 +975,15 @@ impl Connection {
 
         self.recv.initial_seq = segment.seq_number;
         self.recv.next = segment.seq_number + 1;
-        self.send.window = segment.window_len;
         self.send.window_scale = segment.window_scale.unwrap_or(0);
+        self.send.set_window(segment.window_len);
 
-        // TODO: better mss
-        self.sender_maximum_segment_size = segment.max_seg_size
-            .unwrap_or(536)
-            .max(536);
-        self.receiver_maximum_segment_size = self.sender_maximum_segment_size;
+        self.set_segment_sizes(segment.max_seg_size, *segment_size);
+        self.options.remote_user_timeout(segment.user_timeout);
 
         if let Some(ack) = segment.ack_number {
             self.send.unacked = ack;
+            self.send.progress = *time;
         }
 
         // The SYN didn't actually ack our SYN. So change to SYN-RECEIVED.
@@ -653,6 +995,8 @@ impl Connection {
             return signals;
         }
 
+        self.recv.last_time = *time;
+        self.retransmissions = 0;
         self.change_state(State::Established);
         // The rfc would immediately ack etc. We may want to send data and that requires the
         // cooperation of io. Defer but mark as ack required immediately.
@@ -660,26 +1004,38 @@ impl Connection {
         return Signals::default();
     }
 
-    fn arrives_established(&mut self, incoming: &InPacket, entry: EntryKey) -> Signals {
-        // TODO: time for RTT estimation, ...
-        let InPacket { segment, from: _, time, } = incoming;
+    fn arrives_established(&mut self, incoming: &InPacket, mut entry: EntryKey) -> Signals {
+        let InPacket { segment, time, .. } = incoming;
 
         let acceptable = self.ingress_acceptable(segment);
 
-        if !acceptable {
-            if segment.flags.rst() {
+        if segment.flags.rst() {
+            // See: https://tools.ietf.org/html/rfc5961#section-3.2
+            // Only a reset with exactly the next expected sequence number is accepted. Others in
+            // the window could be blind guesses by an attacker, a challenge ACK makes the remote
+            // send a valid reset if the connection is really gone.
+            if !acceptable {
+                return Signals::default();
+            } else if segment.seq_number == self.recv.next {
                 return self.remote_reset_connection();
+            } else {
+                return self.signal_challenge_ack(*time, &mut entry);
             }
+        }
 
+        if !acceptable {
             // TODO: find out why this triggers in a nice tcp connection (python -m http.server)
             return self.signal_ack_all(entry.four_tuple());
         }
 
-        if segment.flags.syn() {
-            debug_assert!(self.recv.in_window(segment.seq_number));
+        self.recv.last_time = *time;
+        self.retransmissions = 0;
 
-            // This is not acceptable, reset the connection.
-            return self.signal_reset_connection(segment, entry);
+        if segment.flags.syn() {
+            // See: https://tools.ietf.org/html/rfc5961#section-4.2
+            // Never reset the connection on a SYN, it may have been injected. The challenge ACK
+            // makes a remote that really restarted send a reset with the correct sequence number.
+            return self.signal_challenge_ack(*time, &mut entry);
         }
 
         let ack = match segment.ack_number {
@@ -695,7 +1051,17 @@ impl Connection {
                 return self.signal_ack_all(entry.four_tuple());
             },
             AckUpdate::Duplicate => {
-                self.duplicate_ack = self.duplicate_ack.saturating_add(1);
+                // See: https://tools.ietf.org/html/rfc5681#section-2
+                // Only an ack without data and the same window is a duplicate of the previous
+                // one. Others are still the most recent window advertisement of the remote.
+                let duplicate = segment.payload_len == 0
+                    && segment.window_len == self.send.window
+                    && self.send.in_flight() > 0;
+                if duplicate {
+                    self.duplicate_ack = self.duplicate_ack.saturating_add(1);
+                } else {
+                    self.send.set_window(segment.window_len);
+                }
                 /*
                 self.flow_control.ssthresh = unimplemented!();
                 self.flow_control.congestion_window = unimplemented!();
@@ -709,12 +1075,24 @@ impl Connection {
                     self.flow_control.congestion_window = self.flow_control.ssthresh;
                     self.duplicate_ack = 0;
                 }
-                self.send.window = segment.window_len;
+                self.send.set_window(segment.window_len);
+                self.send.progress = *time;
                 self.window_update(segment, new_bytes);
+                self.round_trip.acked(ack, *time);
+                // Restart the timer for the remaining outstanding data.
+                self.rearm_retransmission_timer(*time);
             },
         }
 
-        // URG lol
+        // Our FIN was the last thing outstanding, the connection is done.
+        if self.current == State::LastAck && self.send.unacked == self.send.next {
+            self.change_state(State::Closed);
+            let mut signals = Signals::default();
+            signals.delete = true;
+            return signals;
+        }
+
+        // Urgent data is not cut from the stream but received inline, see RFC6093.
 
         let segment_ack = ReceivedSegment {
             syn: segment.flags.syn(),
@@ -766,27 +1144,28 @@ impl Connection {
         return signals;
     }
 
-    /// Close due to invalid incoming packet.
+    /// Choose the segment sizes from the option of a SYN and the local transmission unit.
     ///
-    /// As opposed to `remote_reset_connection` this one is proactive and we send the RST.
-    fn signal_reset_connection(&mut self, _segment: &TcpRepr, entry: EntryKey) -> Signals {
-        self.change_state(State::Closed);
+    /// A remote without the option accepts 536 bytes, see RFC1122. Segments of either side never
+    /// exceed what the local device can carry, and that limit is also the size announced in our
+    /// own SYN.
+    fn set_segment_sizes(&mut self, remote: Option<u16>, local: Option<u16>) {
+        let remote = remote.unwrap_or(536).max(536);
+        let (sender, receiver) = match local {
+            Some(local) => (remote.min(local), local),
+            None => (remote, remote),
+        };
+        self.sender_maximum_segment_size = sender;
+        self.receiver_maximum_segment_size = receiver;
+    }
 
-        let mut signals = Signals::default();
-        signals.reset = true;
-        signals.delete = true;
-        signals.answer = Some(InnerRepr {
-            flags: TcpFlags::RST,
-            seq_number: self.send.next,
-            ack_number: Some(self.ack_all()),
-            window_len: 0,
-            window_scale: None,
-            max_seg_size: None,
-            sack_permitted: false,
-            sack_ranges: [None; 3],
-            payload_len: 0,
-        }.send_to(entry.four_tuple()));
-        signals
+    /// Send an ack for all data in reply to a suspicious segment, if the rate limit permits.
+    fn signal_challenge_ack(&mut self, time: Instant, entry: &mut EntryKey) -> Signals {
+        if !entry.challenge_ack(time) {
+            return Signals::default();
+        }
+
+        self.signal_ack_all(entry.four_tuple())
     }
 
     /// Explicitly send an ack for all data, now.
@@ -814,15 +1193,36 @@ impl Connection {
             max_seg_size: None,
             sack_permitted: false,
             sack_ranges: [None; 3],
+            user_timeout: None,
             payload_len: 0,
         }.send_to(remote)
     }
 
+    /// A RST aborting the connection, acknowledging everything received.
+    pub(crate) fn repr_reset(&mut self, remote: FourTuple) -> TcpRepr {
+        let mut repr = self.repr_ack_all(remote);
+        repr.flags = TcpFlags::RST;
+        repr.window_len = 0;
+        repr
+    }
+
+    /// Check if the connection has not finished closing with the remote.
+    ///
+    /// This is the case from the SYN of the remote until both FINs have been exchanged. Afterwards
+    /// the connection only lingers in its time wait state.
+    pub(crate) fn is_draining(&self) -> bool {
+        matches!(self.current,
+            State::SynReceived | State::Established | State::CloseWait | State::FinWait1
+                | State::FinWait2 | State::Closing | State::LastAck)
+    }
+
     /// Send a SYN.
     ///
     /// If `ack` is true then it also acknowledges received segments (i.e. this is a passive open).
     fn send_open(&mut self, ack: bool, to: FourTuple) -> TcpRepr {
         let ack_number = if ack { Some(self.ack_all()) } else { None };
+        // The SYN offers no window, the first offer is announced with an update.
+        self.recv.advertised = self.recv.next;
         InnerRepr {
             flags: TcpFlags::SYN,
             seq_number: self.send.initial_seq,
@@ -832,28 +1232,162 @@ impl Connection {
             max_seg_size: None,
             sack_permitted: false,
             sack_ranges: [None; 3],
+            user_timeout: self.options.user_timeout_option(),
             payload_len: 0,
         }.send_to(to)
     }
 
+    /// The next point in time at which a timer of the connection fires.
+    ///
+    /// This includes delayed ACKs, retransmissions of data or of the initial SYN, and the end of
+    /// the time wait period. A send phase on the connection at that time handles the timer.
+    pub fn next_timer(&self) -> Expiration {
+        match self.current {
+            State::Established | State::CloseWait | State::FinWait1 | State::FinWait2
+                | State::Closing | State::LastAck =>
+            {
+                let retransmission = if self.send.in_flight() > 0 {
+                    Expiration::When(self.retransmission_timer)
+                } else {
+                    Expiration::Never
+                };
+                retransmission
+                    .min(self.ack_timer)
+                    .min(self.keepalive_timer())
+                    .min(self.user_timer())
+                    .min(self.pacer.timer())
+            },
+            State::SynSent | State::SynReceived => {
+                Expiration::When(self.retransmission_timer).min(self.user_timer())
+            },
+            State::TimeWait => Expiration::When(self.retransmission_timer),
+            State::Closed | State::Listen => Expiration::Never,
+        }
+    }
+
+    /// The time at which an idle connection is probed.
+    fn keepalive_timer(&self) -> Expiration {
+        match self.options.keepalive {
+            Some(idle) if self.may_send_fin() && self.send.in_flight() == 0 => {
+                let last_time = self.recv.last_time.max(self.send.last_time);
+                Expiration::When(last_time + idle)
+            },
+            _ => Expiration::Never,
+        }
+    }
+
+    /// The time at which the connection gives up on unacknowledged data.
+    fn user_timer(&self) -> Expiration {
+        match self.options.user_timeout {
+            Some(timeout) if self.send.in_flight() > 0 => {
+                Expiration::When(self.send.progress + timeout)
+            },
+            _ => Expiration::Never,
+        }
+    }
+
+    /// Check if the connection should give up on an unresponsive remote.
+    ///
+    /// This is the case at the end of the user timeout, or when another retransmission or probe
+    /// is due after the maximum number of them.
+    fn gives_up(&self, time: Instant) -> bool {
+        let retransmit = self.send.in_flight() > 0 && self.retransmission_timer <= time
+            || Expiration::When(time) >= self.keepalive_timer();
+        let exhausted = self.options.max_retransmissions
+            .is_some_and(|max| retransmit && self.retransmissions >= max);
+        exhausted || Expiration::When(time) >= self.user_timer()
+    }
+
+    /// Reset and delete a connection that gave up.
+    ///
+    /// A remote that never answered our SYN does not know about the connection, it does not get
+    /// a reset.
+    fn abort(&mut self, tuple: FourTuple) -> OutSignals {
+        let segment = match self.current {
+            State::SynSent => None,
+            _ => Some(Segment {
+                repr: self.repr_reset(tuple),
+                range: 0..0,
+            }),
+        };
+
+        self.change_state(State::Closed);
+        OutSignals {
+            delete: true,
+            aborted: true,
+            segment,
+        }
+    }
+
+    /// Check if the time wait period of the connection has passed.
+    pub(crate) fn time_wait_expired(&self, time: Instant) -> bool {
+        self.current == State::TimeWait
+            && self.recv.acked == self.recv.next
+            && time >= self.retransmission_timer
+    }
+
     /// Choose a next data segment to send.
     ///
     /// May choose to send an empty range for cases where there is no data to send but a delayed
     /// ACK is expected.
-    pub fn next_send_segment(&mut self, mut available: AvailableBytes, time: Instant, entry: EntryKey)
+    pub fn next_send_segment(&mut self, available: AvailableBytes, time: Instant, entry: EntryKey)
         -> OutSignals
     {
+        let signals = self.select_next_segment(available, time, entry, false);
+        self.count_sent(&signals, time);
+        signals
+    }
+
+    /// Choose a next segment like `next_send_segment` but defer an ACK without data.
+    ///
+    /// Instead of sending it now, the delayed ACK timer fires immediately so that the next send
+    /// phase acknowledges everything received until then with a single segment.
+    pub(crate) fn next_data_segment(
+        &mut self,
+        available: AvailableBytes,
+        time: Instant,
+        entry: EntryKey,
+    ) -> OutSignals
+    {
+        let signals = self.select_next_segment(available, time, entry, true);
+        self.count_sent(&signals, time);
+        signals
+    }
+
+    fn count_sent(&mut self, signals: &OutSignals, time: Instant) {
+        if let Some(segment) = &signals.segment {
+            self.send.last_time = time;
+            self.statistics.segments_sent += 1;
+            self.statistics.bytes_sent += segment.range.len() as u64;
+        }
+    }
+
+    fn select_next_segment(
+        &mut self,
+        mut available: AvailableBytes,
+        time: Instant,
+        entry: EntryKey,
+        defer_ack: bool,
+    ) -> OutSignals {
+        available.fin |= self.send.shutdown;
+
+        let open = !matches!(self.current, State::Closed | State::Listen | State::TimeWait);
+        if open && self.gives_up(time) {
+            return self.abort(entry.four_tuple());
+        }
+
         match self.current {
             State::Established | State::CloseWait => {
-                self.select_send_segment(available, time, entry)
+                self.select_send_segment(available, time, entry, defer_ack)
                     .map(OutSignals::segment)
                     .unwrap_or_else(OutSignals::none)
             },
             // When we have already sent our FIN, never send *new* data.
-            State::FinWait | State::Closing | State::LastAck => {
-                available.total = available.total.min(self.send.next - self.send.unacked);
-                // FIXME: ensure fin bit is set for retransmissions of last segment.
-                self.select_send_segment(available, time, entry)
+            State::FinWait1 | State::FinWait2 | State::Closing | State::LastAck => {
+                // The FIN occupies the last sequence number in flight but no buffer space.
+                let in_flight = self.send.next - self.send.unacked;
+                available.total = available.total.min(in_flight.saturating_sub(1));
+                self.select_send_segment(available, time, entry, defer_ack)
                     .map(OutSignals::segment)
                     .unwrap_or_else(OutSignals::none)
             },
@@ -872,9 +1406,13 @@ impl Connection {
         }
     }
 
-    fn select_send_segment(&mut self, available: AvailableBytes, time: Instant, entry: EntryKey)
-        -> Option<Segment>
-    {
+    fn select_send_segment(
+        &mut self,
+        available: AvailableBytes,
+        time: Instant,
+        entry: EntryKey,
+        defer_ack: bool,
+    ) -> Option<Segment> {
         // Convert the input to `u32`, our window can never be that large anyways.
         let byte_window = u32::try_from(available.total)
             .ok().unwrap_or_else(u32::max_value);
@@ -891,7 +1429,7 @@ impl Connection {
             return self.fast_retransmit(available, time, entry);
         }
 
-        if self.retransmission_timer < time {
+        if self.retransmission_timer <= time && self.send.in_flight() > 0 {
             // Choose segments to retransmit, in contrast to `fast_retransmit` this may influence
             // multiple next packets.
             return self.timeout_retransmit(available, time, entry);
@@ -903,24 +1441,40 @@ impl Connection {
             // TODO: congestion flow control
             // .min(self.flow_control.congestion_window);
         let sent = self.send.in_flight();
-        let max_sent = window.min(byte_window);
-
-        if sent < max_sent {
+        let max_sent = window.min(byte_window).min(self.send.limit);
+        // All data has been sent but the FIN has not, it does not need any window.
+        let only_fin = available.fin && sent == byte_window && self.may_send_fin();
+        let end = sent.saturating_add(self.sender_maximum_segment_size.into())
+            .min(max_sent)
+            .max(sent);
+        // See: https://tools.ietf.org/html/rfc896
+        // A small segment waits for the data in flight to be acknowledged, unless it ends with
+        // the FIN.
+        let small = end - sent < u32::from(self.sender_maximum_segment_size)
+            && !(available.fin && end == byte_window);
+        let nagle = self.options.nagle && sent > 0 && small;
+        // See: https://tools.ietf.org/html/rfc1122#page-98
+        // When the window of the remote rather than the data limits the segment, it waits for a
+        // full segment or half the largest window the remote offered. The data in flight will
+        // open the window again, without any the segment is sent regardless.
+        let silly = sent > 0 && small && window < byte_window
+            && end - sent < self.send.max_window / 2;
+
+        if (sent < max_sent || only_fin) && !nagle && !silly && self.may_pace(time) {
             // Send one new segment of new data.
-            let end = sent.saturating_add(self.sender_maximum_segment_size.into()).min(max_sent);
             // UNWRAP: Available was larger than `end` so these will not fail (even on 16-bit
             // platforms where the buffer may be smaller than the `u32` window). Math:
             // `sent_u32 <= end_u32 <= available_u32 <= available_usize`
             let sent = usize::try_from(sent).unwrap();
             let end = usize::try_from(end).unwrap();
             let range = sent..end;
-            assert!(range.len() > 0);
+            assert!(range.len() > 0 || only_fin);
 
             let is_fin = available.fin && end as usize == available.total;
 
             if is_fin {
                 match self.current {
-                    State::Established => self.change_state(State::FinWait),
+                    State::Established => self.change_state(State::FinWait1),
                     State::CloseWait => self.change_state(State::LastAck),
                     _ => (),
                 }
@@ -932,8 +1486,22 @@ impl Connection {
             if is_fin {
                 repr.flags = TcpFlags::FIN;
             }
+            // See: https://tools.ietf.org/html/rfc1122#page-82
+            // Only the segment that empties the buffer is pushed.
+            if available.push && !range.is_empty() && end == available.total {
+                repr.flags.set_psh(true);
+            }
+
+            // See: https://tools.ietf.org/html/rfc6298#section-5
+            // Start the timer with the first outstanding segment.
+            if sent == 0 {
+                self.rearm_retransmission_timer(time);
+                self.send.progress = time;
+            }
 
             self.send.next = self.send.next + range.len() + usize::from(is_fin);
+            self.round_trip.sent(self.send.next, time);
+            self.paced(range.len(), time);
 
             return Some(Segment {
                 repr,
@@ -943,13 +1511,42 @@ impl Connection {
 
         // There is nothing to send but we may need to ack anyways.
         if self.should_ack() || Expiration::When(time) >= self.ack_timer {
+            if defer_ack {
+                self.ack_timer = self.ack_timer.min(Expiration::When(time));
+                return None;
+            }
+
             self.rearm_ack_timer(time);
             return Some(self.segment_ack_all(entry.four_tuple()));
         }
 
+        // See: https://tools.ietf.org/html/rfc1122#page-101
+        // The probe repeats the last acknowledged sequence number, which the remote answers with
+        // an ACK. It is sent again after every idle period.
+        if Expiration::When(time) >= self.keepalive_timer() {
+            let mut repr = self.repr_ack_all(entry.four_tuple());
+            repr.seq_number = self.send.next - 1;
+            self.retransmissions += 1;
+            return Some(Segment {
+                repr,
+                range: 0..0,
+            });
+        }
+
         None
     }
 
+    /// Check if a FIN can still be sent as new data.
+    fn may_send_fin(&self) -> bool {
+        matches!(self.current, State::Established | State::CloseWait)
+    }
+
+    /// Check if our FIN was sent but not acknowledged yet.
+    fn fin_sent(&self) -> bool {
+        matches!(self.current, State::FinWait1 | State::Closing | State::LastAck)
+            && self.send.unacked != self.send.next
+    }
+
     fn select_syn_retransmit(&mut self, time: Instant, entry: EntryKey)
         -> Option<Segment>
     {
@@ -957,6 +1554,7 @@ impl Connection {
             return None;
         }
 
+        self.retransmissions += 1;
         let ack = match self.current {
             State::SynReceived => true,
             State::SynSent => false,
@@ -981,6 +1579,7 @@ impl Connection {
         -> Option<Segment>
     {
         self.rearm_retransmission_timer(time);
+        self.retransmissions += 1;
         self.segment_retransmit(available, entry.four_tuple())
     }
 
@@ -1003,7 +1602,10 @@ impl Connection {
             .min(u32::from(self.sender_maximum_segment_size))
             .min(byte_window);
 
-        if to_send == 0 {
+        // Only our FIN is outstanding, it is retransmitted without data.
+        let only_fin = available.fin && byte_window == 0 && self.fin_sent();
+
+        if to_send == 0 && !only_fin {
             return None;
         }
 
@@ -1015,6 +1617,10 @@ impl Connection {
         repr.seq_number = self.send.unacked;
         repr.payload_len = to_send as u16;
 
+        self.statistics.retransmits += 1;
+        self.statistics.bytes_retransmitted += u64::from(to_send);
+        self.round_trip.timed = None;
+
         Some(Segment {
             repr,
             range,
@@ -1034,9 +1640,11 @@ impl Connection {
             Some(segment) => OutSignals {
                 segment: Some(segment),
                 delete: false,
+                aborted: false,
             },
             None => OutSignals {
                 delete: time >= self.retransmission_timer,
+                aborted: false,
                 segment: None,
             },
         }
@@ -1066,8 +1674,16 @@ impl Connection {
     /// longer rely on the accuracy of subsequent connection state. The remote may also get
     /// incorrect ACKs, and connection resets might occur.
     ///
+    /// A segment starting beyond the next expected sequence number is out-of-order and can not be
+    /// acknowledged until the hole before it has been filled. It is ignored instead. The
+    /// acknowledged sequence number also never moves backwards.
+    ///
     /// [`arrives`]: #method.arrives
     pub fn set_recv_ack(&mut self, meta: ReceivedSegment) {
+        if meta.begin > self.recv.next {
+            return;
+        }
+
         let end = meta.sequence_end();
         let acked_all = self.send.next == self.send.unacked;
 
@@ -1075,21 +1691,33 @@ impl Connection {
             (State::Established, true, _) | (State::SynReceived, true, _) => {
                 self.change_state(State::CloseWait);
             },
-            (State::FinWait, true, true) | (State::Closing, _, true) => {
+            (State::FinWait1, false, true) => {
+                self.change_state(State::FinWait2);
+            },
+            (State::FinWait1, true, true)
+                | (State::FinWait2, true, _)
+                | (State::Closing, _, true) =>
+            {
                 self.change_state(State::TimeWait);
                 // We could have a segment lifetime estimation here, but use the retransmission
                 // timeout instead. Works as well, I guess.
                 self.retransmission_timer = meta.timestamp + 2*self.retransmission_timeout;
             },
-            (State::FinWait, true, false) => {
+            (State::FinWait1, true, false) => {
                 self.change_state(State::Closing);
             },
             _ => (),
         }
 
-        self.recv.next = end;
-        let new_timer = Expiration::When(meta.timestamp + self.ack_timeout);
-        self.ack_timer = self.ack_timer.min(new_timer);
+        if end > self.recv.next {
+            self.recv.next = end;
+        }
+
+        // A segment without data, SYN or FIN is not acknowledged itself.
+        if meta.sequence_len() > 0 {
+            let new_timer = Expiration::When(meta.timestamp + self.ack_timeout);
+            self.ack_timer = self.ack_timer.min(new_timer);
+        }
     }
 
     /// Get the sequence number of the last byte acknowledged by the other side.
@@ -1099,11 +1727,13 @@ impl Connection {
     pub fn get_send_ack(&self) -> TcpSeqNumber {
         match self.current {
             // If our SYN has not been acked, advance beyond the SYN.
-            State::SynSent => self.send.unacked + 1,
+            State::SynSent | State::SynReceived => self.send.unacked + 1,
             // Don't include our FIN even if it has already been acked.
-            State::FinWait | State::Closing | State::TimeWait | State::LastAck
+            State::FinWait1 | State::Closing | State::LastAck
                 if self.send.unacked == self.send.next
                     => self.send.unacked - 1,
+            // Here our FIN has definitely been acked.
+            State::FinWait2 | State::TimeWait => self.send.unacked - 1,
             _ => self.send.unacked,
         }
     }
@@ -1116,6 +1746,7 @@ impl Connection {
     /// counter.
     fn ack_all(&mut self) -> TcpSeqNumber {
         self.recv.acked = self.recv.next;
+        self.recv.advertised = self.recv.edge;
         self.ack_timer = Expiration::Never;
         self.recv.next
     }
@@ -1123,9 +1754,38 @@ impl Connection {
     /// Determine whether to send an ACK.
     ///
     /// This is currently always true when there is any sequence space to ack but that may change
-    /// for delayed acks.
+    /// for delayed acks. A window that was reopened after reading from the receive buffer is
+    /// announced as well, the remote may be waiting for it.
     fn should_ack(&self) -> bool {
-        self.recv.acked < self.recv.next
+        self.recv.acked < self.recv.next || self.recv.edge > self.recv.advertised
+    }
+
+    /// Check if pacing allows a new segment at this time.
+    fn may_pace(&mut self, time: Instant) -> bool {
+        match self.options.pacing {
+            Some(_) => self.pacer.may_send(time),
+            None => true,
+        }
+    }
+
+    /// Delay the next new segment after sending one of `len` bytes.
+    fn paced(&mut self, len: usize, time: Instant) {
+        let pacing = match self.options.pacing {
+            Some(pacing) => pacing,
+            None => return,
+        };
+
+        // The congestion window only applies once it has been opened.
+        let flow = &self.flow_control;
+        let window = match flow.congestion_window {
+            0 => self.send.window(),
+            congestion => self.send.window().min(congestion),
+        };
+        let slow_start = flow.congestion_window <= flow.ssthresh;
+        let rtt = self.round_trip.smoothed;
+        if let Some(interval) = pacing.interval(len, window, slow_start, rtt) {
+            self.pacer.sent(time, interval);
+        }
     }
 
     fn rearm_ack_timer(&mut self, time: Instant) {
@@ -1188,6 +1848,12 @@ impl Send {
         u32::from(self.window) << self.window_scale
     }
 
+    /// Update the window from an incoming (unscaled) window field.
+    fn set_window(&mut self, window: u16) {
+        self.window = window;
+        self.max_window = self.max_window.max(self.window());
+    }
+
     /// Get the segments in flight.
     fn in_flight(&self) -> u32 {
         assert!(self.unacked <= self.next);
@@ -1195,6 +1861,43 @@ impl Send {
     }
 }
 
+impl RoundTrip {
+    /// Start timing a sent segment, unless one is already timed.
+    fn sent(&mut self, end: TcpSeqNumber, time: Instant) {
+        if self.timed.is_none() {
+            self.timed = Some((end, time));
+        }
+    }
+
+    /// Take a sample if the timed segment was acknowledged.
+    fn acked(&mut self, ack: TcpSeqNumber, time: Instant) {
+        match self.timed {
+            Some((end, sent)) if end <= ack => {
+                self.timed = None;
+                self.sample(time - sent);
+            },
+            _ => (),
+        }
+    }
+
+    /// Update the estimates with a new measurement.
+    ///
+    /// See: https://tools.ietf.org/html/rfc6298#section-2
+    fn sample(&mut self, rtt: Duration) {
+        match self.smoothed {
+            None => {
+                self.smoothed = Some(rtt);
+                self.variation = rtt / 2;
+            },
+            Some(smoothed) => {
+                let deviation = smoothed.abs_diff(rtt);
+                self.variation = (self.variation * 3 + deviation) / 4;
+                self.smoothed = Some((smoothed * 7 + rtt) / 8);
+            },
+        }
+    }
+}
+
 impl ReceivedSegment {
     /// Compute the total length in sequence space, including SYN or FIN.
     pub fn sequence_len(&self) -> usize {
@@ -1206,13 +1909,26 @@ impl ReceivedSegment {
     /// Only ack part of the segment until some sequence point.
     ///
     /// Takes care of removing the FIN flag if the acked part does not cover every data byte until
-    /// that point.
+    /// that point. The data length is adjusted to end at the acknowledged sequence number. This
+    /// may also extend it beyond the segment when a reassembly buffer completed some previously
+    /// received out-of-order data.
     pub fn acked_until(&self, ack: TcpSeqNumber) -> Self {
+        let fin = self.fin && ack >= self.data_end();
+        let data_begin = self.data_begin();
+        let data_len = if fin {
+            // Nothing can follow the FIN.
+            self.data_len
+        } else if ack > data_begin {
+            ack - data_begin
+        } else {
+            0
+        };
+
         ReceivedSegment {
             syn: self.syn,
-            fin: self.fin && ack + 1 >= self.sequence_end(),
+            fin,
             begin: self.begin,
-            data_len: self.data_len,
+            data_len,
             timestamp: self.timestamp,
         }
     }
@@ -1249,6 +1965,7 @@ impl OutSignals {
         OutSignals {
             segment: Some(segment),
             delete: false,
+            aborted: false,
         }
     }
 }
@@ -1300,19 +2017,78 @@ impl<'a> Operator<'a> {
 
     pub(crate) fn arrives(&mut self, incoming: &InPacket) -> Signals {
         let (entry_key, connection) = self.entry().into_key_value();
-        connection.arrives(incoming, entry_key)
+        let before = Snapshot::new(connection);
+        let signals = connection.arrives(incoming, entry_key);
+        self.endpoint.reschedule(self.connection_key);
+
+        let connection = self.connection();
+        let established = before.established(connection);
+        let acked = before.data_acked(connection);
+        let remote_fin = before.remote_fin(connection);
+        if established {
+            self.notify(Event::Established);
+        }
+        if acked > 0 {
+            self.notify(Event::DataAcked(acked));
+        }
+        if remote_fin {
+            self.notify(Event::RemoteFin);
+        }
+        if signals.reset {
+            self.notify(Event::Reset);
+        }
+        signals
+    }
+
+    pub(crate) fn notify(&mut self, event: Event) {
+        self.endpoint.notify(self.connection_key, event)
     }
 
     pub(crate) fn next_send_segment(&mut self, available: AvailableBytes, time: Instant)
         -> OutSignals
     {
+        self.limit_send();
         let (entry_key, connection) = self.entry().into_key_value();
-        connection.next_send_segment(available, time, entry_key)
+        let signals = connection.next_send_segment(available, time, entry_key);
+        self.endpoint.reschedule(self.connection_key);
+        if signals.delete {
+            self.notify(Event::Timeout);
+        }
+        signals
+    }
+
+    pub(crate) fn next_data_segment(&mut self, available: AvailableBytes, time: Instant)
+        -> OutSignals
+    {
+        self.limit_send();
+        let (entry_key, connection) = self.entry().into_key_value();
+        let signals = connection.next_data_segment(available, time, entry_key);
+        self.endpoint.reschedule(self.connection_key);
+        if signals.delete {
+            self.notify(Event::Timeout);
+        }
+        signals
     }
 
     pub(crate) fn open(&mut self, time: Instant) -> Result<(), crate::layer::Error> {
         let (entry_key, connection) = self.entry().into_key_value();
-        connection.open(time, entry_key)
+        let result = connection.open(time, entry_key);
+        self.endpoint.reschedule(self.connection_key);
+        result
+    }
+
+    /// Offer a receive window, within the share of the connection in the endpoint's buffers.
+    pub(crate) fn offer_window(&mut self, available: usize) {
+        let allowance = self.endpoint.allowance(self.connection_key);
+        self.connection_mut().offer_window(available.min(allowance.recv));
+        self.endpoint.reschedule(self.connection_key);
+    }
+
+    /// Limit the data in flight to the share of the connection in the endpoint's buffers.
+    fn limit_send(&mut self) {
+        let allowance = self.endpoint.allowance(self.connection_key);
+        let limit = u32::try_from(allowance.send).unwrap_or(u32::MAX);
+        self.connection_mut().send.limit = limit;
     }
 
     /// Remove the connection and close the operator.
@@ -1358,6 +2134,7 @@ impl InnerRepr {
             max_seg_size: self.max_seg_size,
             sack_permitted: self.sack_permitted,
             sack_ranges: self.sack_ranges,
+            user_timeout: self.user_timeout,
             payload_len: self.payload_len,
         }
     }
@@ -1365,16 +2142,17 @@ impl InnerRepr {
 
 #[cfg(test)]
 mod tests {
-    use crate::layer::tcp::endpoint::{EntryKey, FourTuple, PortMap};
+    use crate::layer::tcp::endpoint::{ChallengeAcks, EntryKey, FourTuple, PortMap};
     use crate::layer::tcp::IsnGenerator;
-    use crate::time::Instant;
-    use crate::wire::IpAddress;
-    use super::{AvailableBytes, Connection};
+    use crate::time::{Duration, Instant};
+    use crate::wire::{IpAddress, TcpFlags, TcpRepr, TcpSeqNumber};
+    use super::{
+        AvailableBytes, Connection, InPacket, ReceivedSegment, RoundTrip, State, Statistics};
 
     struct NoRemap;
 
     impl PortMap for NoRemap {
-        fn remap(&mut self, _: FourTuple, _: FourTuple) {
+        fn remap(&mut self, _: FourTuple, _: FourTuple) -> Result<(), crate::layer::Error> {
             panic!("Should not get remapped");
         }
     }
@@ -1388,6 +2166,7 @@ mod tests {
         let mut connection = simple_connection();
         let isn = IsnGenerator::from_key(0, 0);
         let mut no_remap = NoRemap;
+        let mut challenge = ChallengeAcks::new(1, Duration::from_secs(1));
         let mut four = FourTuple {
             local: IpAddress::v4(192, 0, 10, 1),
             remote: IpAddress::v4(192, 0, 10, 2),
@@ -1398,11 +2177,383 @@ mod tests {
         let time_start = Instant::from_secs(0);
         let time_resend = Instant::from_secs(3);
 
-        let entry = EntryKey::fake(&mut no_remap, &isn, &mut four);
+        let entry = EntryKey::fake(&mut no_remap, &isn, &mut challenge, &mut four);
         assert!(connection.open(time_start, entry).is_ok());
 
-        let entry = EntryKey::fake(&mut no_remap, &isn, &mut four);
-        let available = AvailableBytes { fin: false, total: 0 };
+        let entry = EntryKey::fake(&mut no_remap, &isn, &mut challenge, &mut four);
+        let available = AvailableBytes { fin: false, push: false, total: 0 };
         let _resent = connection.next_send_segment(available, time_resend, entry);
     }
+
+    #[test]
+    fn statistics() {
+        let mut connection = simple_connection();
+        let isn = IsnGenerator::from_key(0, 0);
+        let mut no_remap = NoRemap;
+        let mut challenge = ChallengeAcks::new(1, Duration::from_secs(1));
+        let mut four = FourTuple {
+            local: IpAddress::v4(192, 0, 10, 1),
+            remote: IpAddress::v4(192, 0, 10, 2),
+            local_port: 80,
+            remote_port: 80,
+        };
+
+        connection.current = State::Established;
+        connection.sender_maximum_segment_size = 1000;
+        connection.send.unacked = TcpSeqNumber(100);
+        connection.send.next = TcpSeqNumber(100);
+        connection.send.window = 4000;
+        connection.restart_timeout = Duration::from_secs(10);
+        connection.retransmission_timeout = Duration::from_secs(1);
+        connection.retransmission_timer = Instant::from_secs(1);
+        let available = AvailableBytes { fin: false, push: false, total: 500 };
+
+        let entry = EntryKey::fake(&mut no_remap, &isn, &mut challenge, &mut four);
+        let sent = connection.next_send_segment(available, Instant::from_secs(0), entry);
+        assert_eq!(sent.segment.unwrap().range, 0..500);
+
+        // The segment was not acknowledged in time.
+        let entry = EntryKey::fake(&mut no_remap, &isn, &mut challenge, &mut four);
+        let resent = connection.next_send_segment(available, Instant::from_secs(2), entry);
+        assert_eq!(resent.segment.unwrap().range, 0..500);
+
+        assert_eq!(connection.statistics, Statistics {
+            segments_sent: 2,
+            bytes_sent: 1000,
+            retransmits: 1,
+            bytes_retransmitted: 500,
+            ..Statistics::default()
+        });
+    }
+
+    #[test]
+    fn blind_reset() {
+        let mut connection = simple_connection();
+        let isn = IsnGenerator::from_key(0, 0);
+        let mut no_remap = NoRemap;
+        let mut challenge = ChallengeAcks::new(1, Duration::from_secs(1));
+        let mut four = FourTuple {
+            local: IpAddress::v4(192, 0, 10, 1),
+            remote: IpAddress::v4(192, 0, 10, 2),
+            local_port: 80,
+            remote_port: 80,
+        };
+
+        connection.current = State::Established;
+        connection.recv.next = TcpSeqNumber(1000);
+        connection.recv.acked = TcpSeqNumber(1000);
+        connection.recv.window = 1000;
+
+        let time = Instant::from_secs(0);
+        let segment = |flags, seq| InPacket {
+            segment: TcpRepr {
+                src_port: 80,
+                dst_port: 80,
+                flags,
+                seq_number: TcpSeqNumber(seq),
+                ack_number: None,
+                window_len: 0,
+                window_scale: None,
+                max_seg_size: None,
+                sack_permitted: false,
+                sack_ranges: [None; 3],
+                user_timeout: None,
+                payload_len: 0,
+            },
+            from: IpAddress::v4(192, 0, 10, 2),
+            to: IpAddress::v4(192, 0, 10, 1),
+            time,
+            segment_size: None,
+        };
+
+        // Outside the window, silently dropped.
+        let entry = EntryKey::fake(&mut no_remap, &isn, &mut challenge, &mut four);
+        let signals = connection.arrives(&segment(TcpFlags::RST, 5000), entry);
+        assert!(!signals.reset && signals.answer.is_none());
+
+        // In the window but not exact, challenged.
+        let entry = EntryKey::fake(&mut no_remap, &isn, &mut challenge, &mut four);
+        let signals = connection.arrives(&segment(TcpFlags::RST, 1100), entry);
+        assert!(!signals.reset);
+        assert_eq!(signals.answer.unwrap().ack_number, Some(TcpSeqNumber(1000)));
+
+        // A SYN is challenged as well but the rate limit has been reached.
+        let entry = EntryKey::fake(&mut no_remap, &isn, &mut challenge, &mut four);
+        let signals = connection.arrives(&segment(TcpFlags::SYN, 1100), entry);
+        assert!(!signals.reset && signals.answer.is_none());
+        assert_eq!(connection.current, State::Established);
+
+        // The exact sequence number resets.
+        let entry = EntryKey::fake(&mut no_remap, &isn, &mut challenge, &mut four);
+        let signals = connection.arrives(&segment(TcpFlags::RST, 1000), entry);
+        assert!(signals.reset && signals.delete);
+        assert_eq!(connection.current, State::Closed);
+    }
+
+    #[test]
+    fn half_close() {
+        let mut connection = simple_connection();
+        let isn = IsnGenerator::from_key(0, 0);
+        let mut no_remap = NoRemap;
+        let mut challenge = ChallengeAcks::new(1, Duration::from_secs(1));
+        let mut four = FourTuple {
+            local: IpAddress::v4(192, 0, 10, 1),
+            remote: IpAddress::v4(192, 0, 10, 2),
+            local_port: 80,
+            remote_port: 80,
+        };
+
+        connection.current = State::Established;
+        connection.sender_maximum_segment_size = 1000;
+        connection.send.unacked = TcpSeqNumber(100);
+        connection.send.next = TcpSeqNumber(100);
+        connection.send.window = 4000;
+        connection.recv.next = TcpSeqNumber(1000);
+        connection.recv.acked = TcpSeqNumber(1000);
+        connection.recv.window = 1000;
+        connection.restart_timeout = Duration::from_secs(10);
+        connection.retransmission_timeout = Duration::from_secs(1);
+        connection.retransmission_timer = Instant::from_secs(1);
+
+        let time = Instant::from_secs(0);
+        let segment = |flags, ack| InPacket {
+            segment: TcpRepr {
+                src_port: 80,
+                dst_port: 80,
+                flags,
+                seq_number: TcpSeqNumber(1000),
+                ack_number: Some(TcpSeqNumber(ack)),
+                window_len: 4000,
+                window_scale: None,
+                max_seg_size: None,
+                sack_permitted: false,
+                sack_ranges: [None; 3],
+                user_timeout: None,
+                payload_len: 0,
+            },
+            from: IpAddress::v4(192, 0, 10, 2),
+            to: IpAddress::v4(192, 0, 10, 1),
+            time,
+            segment_size: None,
+        };
+
+        // All data has been sent and acknowledged, the FIN is sent on its own.
+        connection.shutdown();
+        let available = AvailableBytes { fin: false, push: false, total: 0 };
+        let entry = EntryKey::fake(&mut no_remap, &isn, &mut challenge, &mut four);
+        let sent = connection.next_send_segment(available, time, entry);
+        let fin = sent.segment.unwrap();
+        assert!(fin.repr.flags.fin());
+        assert_eq!(fin.range, 0..0);
+        assert_eq!(connection.current, State::FinWait1);
+
+        // Retransmitted without data as well.
+        let entry = EntryKey::fake(&mut no_remap, &isn, &mut challenge, &mut four);
+        let resent = connection.next_send_segment(available, Instant::from_secs(2), entry);
+        let fin = resent.segment.unwrap();
+        assert!(fin.repr.flags.fin());
+        assert_eq!(fin.repr.seq_number, TcpSeqNumber(100));
+
+        let entry = EntryKey::fake(&mut no_remap, &isn, &mut challenge, &mut four);
+        let signals = connection.arrives(&segment(TcpFlags::default(), 101), entry);
+        assert!(!signals.delete);
+        assert_eq!(connection.current, State::FinWait2);
+        assert!(!connection.is_remote_closed());
+
+        // The remote closes its side.
+        let entry = EntryKey::fake(&mut no_remap, &isn, &mut challenge, &mut four);
+        let _ = connection.arrives(&segment(TcpFlags::FIN, 101), entry);
+        assert_eq!(connection.current, State::TimeWait);
+        assert!(connection.is_remote_closed());
+    }
+
+    #[test]
+    fn passive_close() {
+        let mut connection = simple_connection();
+        let isn = IsnGenerator::from_key(0, 0);
+        let mut no_remap = NoRemap;
+        let mut challenge = ChallengeAcks::new(1, Duration::from_secs(1));
+        let mut four = FourTuple {
+            local: IpAddress::v4(192, 0, 10, 1),
+            remote: IpAddress::v4(192, 0, 10, 2),
+            local_port: 80,
+            remote_port: 80,
+        };
+
+        connection.current = State::Established;
+        connection.sender_maximum_segment_size = 1000;
+        connection.send.unacked = TcpSeqNumber(100);
+        connection.send.next = TcpSeqNumber(100);
+        connection.send.window = 4000;
+        connection.recv.next = TcpSeqNumber(1000);
+        connection.recv.acked = TcpSeqNumber(1000);
+        connection.recv.window = 1000;
+        connection.restart_timeout = Duration::from_secs(10);
+        connection.retransmission_timeout = Duration::from_secs(1);
+        connection.retransmission_timer = Instant::from_secs(1);
+
+        let time = Instant::from_secs(0);
+        let segment = |flags, seq, ack| InPacket {
+            segment: TcpRepr {
+                src_port: 80,
+                dst_port: 80,
+                flags,
+                seq_number: TcpSeqNumber(seq),
+                ack_number: Some(TcpSeqNumber(ack)),
+                window_len: 4000,
+                window_scale: None,
+                max_seg_size: None,
+                sack_permitted: false,
+                sack_ranges: [None; 3],
+                user_timeout: None,
+                payload_len: 0,
+            },
+            from: IpAddress::v4(192, 0, 10, 2),
+            to: IpAddress::v4(192, 0, 10, 1),
+            time,
+            segment_size: None,
+        };
+
+        let entry = EntryKey::fake(&mut no_remap, &isn, &mut challenge, &mut four);
+        let _ = connection.arrives(&segment(TcpFlags::FIN, 1000, 100), entry);
+        assert_eq!(connection.current, State::CloseWait);
+
+        // Remaining data is still sent, followed by the FIN.
+        connection.shutdown();
+        let available = AvailableBytes { fin: false, push: false, total: 200 };
+        let entry = EntryKey::fake(&mut no_remap, &isn, &mut challenge, &mut four);
+        let sent = connection.next_send_segment(available, time, entry);
+        let last = sent.segment.unwrap();
+        assert!(last.repr.flags.fin());
+        assert_eq!(last.range, 0..200);
+        assert_eq!(connection.current, State::LastAck);
+
+        let entry = EntryKey::fake(&mut no_remap, &isn, &mut challenge, &mut four);
+        let signals = connection.arrives(&segment(TcpFlags::default(), 1001, 301), entry);
+        assert!(signals.delete);
+        assert_eq!(connection.current, State::Closed);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut rtt = RoundTrip::default();
+        rtt.sent(TcpSeqNumber(100), Instant::from_millis(0));
+        // Only one segment is timed.
+        rtt.sent(TcpSeqNumber(200), Instant::from_millis(10));
+        rtt.acked(TcpSeqNumber(50), Instant::from_millis(20));
+        assert_eq!(rtt.smoothed, None);
+
+        rtt.acked(TcpSeqNumber(100), Instant::from_millis(40));
+        assert_eq!(rtt.smoothed, Some(Duration::from_millis(40)));
+        assert_eq!(rtt.variation, Duration::from_millis(20));
+        assert_eq!(rtt.timed, None);
+
+        rtt.sent(TcpSeqNumber(300), Instant::from_millis(100));
+        rtt.acked(TcpSeqNumber(300), Instant::from_millis(180));
+        assert_eq!(rtt.smoothed, Some(Duration::from_millis(45)));
+        assert_eq!(rtt.variation, Duration::from_millis(25));
+
+        let mut connection = simple_connection();
+        connection.current = State::Established;
+        connection.round_trip = rtt;
+        connection.send.unacked = TcpSeqNumber(100);
+        connection.send.next = TcpSeqNumber(400);
+        connection.send.window = 1000;
+        connection.send.window_scale = 2;
+        let info = connection.info();
+        assert_eq!(info.state, State::Established);
+        assert_eq!(info.smoothed_rtt, Some(Duration::from_millis(45)));
+        assert_eq!(info.in_flight, 300);
+        assert_eq!(info.send_window, 4000);
+    }
+
+    #[test]
+    fn out_of_order_ack() {
+        let mut connection = simple_connection();
+        let next = TcpSeqNumber(100);
+        connection.recv.next = next;
+
+        let segment = |begin, data_len| ReceivedSegment {
+            syn: false,
+            fin: false,
+            data_len,
+            begin,
+            timestamp: Instant::from_secs(0),
+        };
+
+        // Can not be acked across the hole.
+        connection.set_recv_ack(segment(next + 10, 10));
+        assert_eq!(connection.recv.next, next);
+
+        // A buffer completed the hole and the segment after it.
+        connection.set_recv_ack(segment(next, 10).acked_until(next + 20));
+        assert_eq!(connection.recv.next, next + 20);
+
+        // Retransmitted data does not move the ack backwards.
+        connection.set_recv_ack(segment(next, 10));
+        assert_eq!(connection.recv.next, next + 20);
+    }
+
+    #[test]
+    fn window_without_ack() {
+        let mut connection = simple_connection();
+        let isn = IsnGenerator::from_key(0, 0);
+        let mut no_remap = NoRemap;
+        let mut challenge = ChallengeAcks::new(1, Duration::from_secs(1));
+        let mut four = FourTuple {
+            local: IpAddress::v4(192, 0, 10, 1),
+            remote: IpAddress::v4(192, 0, 10, 2),
+            local_port: 80,
+            remote_port: 80,
+        };
+
+        // We have not sent anything yet and the remote advertised no window in its SYN-ACK.
+        connection.current = State::Established;
+        connection.sender_maximum_segment_size = 1000;
+        connection.send.unacked = TcpSeqNumber(100);
+        connection.send.next = TcpSeqNumber(100);
+        connection.send.window = 0;
+        connection.recv.next = TcpSeqNumber(1000);
+        connection.recv.acked = TcpSeqNumber(1000);
+        connection.recv.window = 1000;
+        connection.restart_timeout = Duration::from_secs(10);
+        connection.retransmission_timeout = Duration::from_secs(1);
+        connection.retransmission_timer = Instant::from_secs(1);
+
+        let time = Instant::from_secs(0);
+        let segment = |seq, payload_len| InPacket {
+            segment: TcpRepr {
+                src_port: 80,
+                dst_port: 80,
+                flags: TcpFlags::default(),
+                seq_number: TcpSeqNumber(seq),
+                ack_number: Some(TcpSeqNumber(100)),
+                window_len: 4000,
+                window_scale: None,
+                max_seg_size: None,
+                sack_permitted: false,
+                sack_ranges: [None; 3],
+                user_timeout: None,
+                payload_len,
+            },
+            from: IpAddress::v4(192, 0, 10, 2),
+            to: IpAddress::v4(192, 0, 10, 1),
+            time,
+            segment_size: None,
+        };
+
+        // Data segments acknowledge nothing new but still open the window.
+        let entry = EntryKey::fake(&mut no_remap, &isn, &mut challenge, &mut four);
+        let _ = connection.arrives(&segment(1000, 500), entry);
+        let entry = EntryKey::fake(&mut no_remap, &isn, &mut challenge, &mut four);
+        let _ = connection.arrives(&segment(1500, 500), entry);
+        assert_eq!(connection.send.window, 4000);
+        assert_eq!(connection.duplicate_ack, 0);
+
+        // So that we can answer with data.
+        let available = AvailableBytes { fin: false, push: false, total: 500 };
+        let entry = EntryKey::fake(&mut no_remap, &isn, &mut challenge, &mut four);
+        let sent = connection.next_send_segment(available, time, entry);
+        assert_eq!(sent.segment.unwrap().range, 0..500);
+    }
 }
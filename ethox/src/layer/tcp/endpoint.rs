@@ -9,19 +9,29 @@
 //! Selective ACKs: https://tools.ietf.org/html/rfc2018
 //! RST handling specifically: https://www.snellman.net/blog/archive/2016-02-01-tcp-rst/
 //!     OS comparison in particular
-use crate::layer::ip;
-use crate::managed::{Map, SlotMap, slotmap::Key};
-use crate::wire::{IpAddress, TcpPacket, TcpSeqNumber};
+use core::cell::RefCell;
+
+use crate::layer::{ip, Detail, Error, Failure, Operation, Origin};
+use crate::layer::ports::{Registry, Transport};
+use crate::managed::{Map, SlotMap, TimerWheel, slotmap::Key};
+use crate::rand::Rng;
+use crate::wire::{IpAddress, Ipv4Address, Ipv6Address, IpProtocol, TcpPacket, TcpSeqNumber};
 use crate::wire::PayloadMut;
-use crate::time::{Duration, Expiration, Instant};
+use crate::time::{Clock, Duration, Expiration, Instant};
 
 use super::connection::{
     Connection,
     Flow,
+    Info,
+    RoundTrip,
     Send,
     State,
-    Receive};
+    Receive,
+    Statistics};
+use super::events::{ConnectionEvents, Event};
+use super::options::Options;
 use super::packet::{In, Raw};
+use super::pacing::Pacer;
 use super::siphash::IsnGenerator;
 
 /// Handles TCP connection states.
@@ -29,6 +39,49 @@ pub struct Endpoint<'a> {
     ports: Map<'a, FourTuple, Key>,
     states: SlotMap<'a, Slot>,
     isn_generator: IsnGenerator,
+    rng: Option<&'a mut dyn Rng>,
+    events: Option<&'a mut dyn ConnectionEvents>,
+    registry: Option<&'a RefCell<Registry<'a>>>,
+    next_port: u16,
+    timers: Option<TimerWheel<'a>>,
+    challenge_acks: ChallengeAcks,
+    coalesce_acks: bool,
+    buffers: Budget,
+    options: Options,
+    last_failure: Option<Failure>,
+    drain: Option<Drain>,
+}
+
+/// The progress of closing all connections of an endpoint.
+///
+/// See [`Endpoint::close_all`].
+///
+/// [`Endpoint::close_all`]: struct.Endpoint.html#method.close_all
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Drain {
+    /// The time at which remaining connections are reset.
+    pub deadline: Instant,
+
+    /// The number of connections that have not finished closing.
+    pub remaining: usize,
+
+    /// The number of connections that were reset at the deadline.
+    pub reset: usize,
+}
+
+/// An amount of buffered data in each direction, in bytes.
+///
+/// Describes both the limits of an endpoint and the data its connections hold against them. See
+/// [`Endpoint::set_buffer_limits`].
+///
+/// [`Endpoint::set_buffer_limits`]: struct.Endpoint.html#method.set_buffer_limits
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BufferSpace {
+    /// Data sent but not yet acknowledged, which the send buffers keep for retransmission.
+    pub send: usize,
+
+    /// The receive windows offered to the remotes, data that may arrive at any time.
+    pub recv: usize,
 }
 
 /// The TCP connection identifier, with four components.
@@ -43,8 +96,8 @@ pub struct FourTuple {
     /// The address identifying this machine/device for incoming segments.
     ///
     /// This address is not necessarily accurate (or unicast) until a connection has been
-    /// established. In particular, it is used also for the netmask of a passively opened
-    /// port and then modified when an incoming SYN is accepted.
+    /// established. In particular, a passively opened port may be bound to an unspecified address
+    /// and this is then replaced by the actual destination when an incoming SYN is accepted.
     pub local: IpAddress,
     /// The address to which outgoing segments are sent.
     ///
@@ -69,12 +122,17 @@ pub struct FourTuple {
 pub struct Slot {
     addr: FourTuple,
     connection: Connection,
+    /// The buffered data last accounted for the connection.
+    charge: BufferSpace,
 }
 
 /// The index of a connection.
 ///
 /// Useful for storing in other structs to reference the connection at another point in time. Note
 /// that the index will be invalidated when the connection itself is closed.
+///
+/// Each key carries the generation of its slot. When the slot of a closed connection is reused
+/// for another connection, lookups with the old key return `None` instead of the new connection.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct SlotKey {
     key: Key,
@@ -121,6 +179,7 @@ pub struct Entry<'a> {
     key: SlotKey,
     ports: &'a mut dyn PortMap,
     isn: &'a IsnGenerator,
+    challenge_acks: &'a mut ChallengeAcks,
     slot: &'a mut Slot,
 }
 
@@ -131,15 +190,41 @@ pub struct Entry<'a> {
 pub struct EntryKey<'a> {
     ports: &'a mut dyn PortMap,
     isn: &'a IsnGenerator,
+    challenge_acks: &'a mut ChallengeAcks,
     key_in_slot: &'a mut FourTuple,
 }
 
+/// The rate limit of challenge ACKs, shared by all connections of an endpoint.
+///
+/// A challenge ACK is sent in response to a suspicious RST or SYN segment that could have been
+/// injected by an off-path attacker. Limiting them avoids amplification and also hides the number
+/// of connections that received such segments, see RFC5961.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ChallengeAcks {
+    burst: u32,
+    interval: Duration,
+    tokens: u32,
+    last: Option<Instant>,
+}
+
+/// The buffered data of all connections of an endpoint and its limits.
+#[derive(Clone, Copy, Debug)]
+struct Budget {
+    limits: BufferSpace,
+    used: BufferSpace,
+    /// The number of connections sharing the limits, including listening slots.
+    connections: usize,
+}
+
 /// Provides remapping a `SlotKey` under a different four tuple.
 ///
 /// Erases the lifetime from the underlying `Map` itself.
 pub(crate) trait PortMap {
-    /// Note: does not permit failure so we must never expose it.
-    fn remap(&mut self, old: FourTuple, new: FourTuple);
+    /// Move the key mapped under `old` to `new`.
+    ///
+    /// Fails with `Illegal` if `old` is not mapped or `new` is already taken by another
+    /// connection. The mapping is unchanged on failure.
+    fn remap(&mut self, old: FourTuple, new: FourTuple) -> Result<(), crate::layer::Error>;
 }
 
 impl Endpoint<'_> {
@@ -169,6 +254,7 @@ impl Endpoint<'_> {
             key: SlotKey { key: index.key },
             ports: &mut self.ports,
             isn: &mut self.isn_generator,
+            challenge_acks: &mut self.challenge_acks,
             slot,
         })
     }
@@ -203,14 +289,188 @@ impl Endpoint<'_> {
         };
 
         self.ports.entry(addr).remove();
-        let _ = self.states.remove(index.key);
+        if let Some(slot) = self.states.remove(index.key) {
+            self.buffers.release(slot.charge);
+            self.buffers.connections -= 1;
+        }
+        if let Some(timers) = &mut self.timers {
+            timers.cancel(index.key.index());
+        }
+    }
+
+    /// Drop all connections using a local address.
+    ///
+    /// Call this after the address was removed from the ip layer, see [`ip::Change`]. No reset is
+    /// sent as segments from the address can no longer be sent, the remotes find out with their
+    /// next segment or their own timeouts. Listening slots bound to exactly that address are
+    /// removed as well while those on an unspecified address remain. Returns the number of
+    /// removed slots.
+    ///
+    /// [`ip::Change`]: ../ip/enum.Change.html
+    pub fn reset_address(&mut self, address: IpAddress) -> usize {
+        let mut removed = 0;
+        loop {
+            let key = self.states.keys()
+                .find(|&key| self.states.get(key).is_some_and(|slot| slot.addr.local == address));
+            match key {
+                Some(key) => self.remove(SlotKey { key }),
+                None => return removed,
+            }
+            removed += 1;
+        }
+    }
+
+    /// Remove a connection whose timer ended it.
+    fn expire(&mut self, index: SlotKey) {
+        self.notify(index, Event::Timeout);
+        self.remove(index);
     }
 
-    /// Opens a new port for listening.
+    fn notify(&mut self, index: SlotKey, event: Event) {
+        if let Some(events) = &mut self.events {
+            event.dispatch(&mut **events, index);
+        }
+    }
+
+    /// Close all connections, resetting those that did not finish by the deadline.
+    ///
+    /// Listening sockets and connections still waiting for the SYN of their remote are removed
+    /// right away. All other connections queue a FIN after the data their send buffers currently
+    /// have available, as with [`Open::shutdown`]. The handlers of the connections should keep
+    /// writing to them on the following send opportunities so that the data and the FIN are sent.
+    ///
+    /// Connections that have not exchanged FINs in both directions by the deadline are then
+    /// reset, one for each packet offered to a sender of the endpoint. [`poll`] reports the
+    /// deadline while any connection remains and [`drain`] the progress.
+    ///
+    /// [`Open::shutdown`]: struct.Open.html#method.shutdown
+    /// [`poll`]: #method.poll
+    /// [`drain`]: #method.drain
+    pub fn close_all(&mut self, deadline: Instant) {
+        loop {
+            let states = &self.states;
+            let pending = states.keys().find(|&key| states
+                .get(key)
+                .is_some_and(|slot| {
+                    let connection = &slot.connection;
+                    let closing = connection.is_draining() && connection.send.shutdown;
+                    !closing && connection.current != State::TimeWait
+                }));
+            let key = match pending {
+                Some(key) => SlotKey { key },
+                None => break,
+            };
+
+            let connection = &mut self.states.get_mut(key.key).unwrap().connection;
+            if connection.is_draining() {
+                connection.shutdown();
+            } else {
+                self.remove(key);
+            }
+        }
+
+        self.drain = Some(Drain {
+            deadline,
+            remaining: 0,
+            reset: self.drain.map_or(0, |drain| drain.reset),
+        });
+    }
+
+    /// The progress of closing all connections, if it was started.
+    ///
+    /// See [`close_all`].
     ///
-    /// The source address is chosen during the first send operation on the create connection.
-    /// Returns the key to use to inspect or modify the connection state and parameters.
-    fn listen(&mut self, ip: IpAddress, port: u16)
+    /// [`close_all`]: #method.close_all
+    pub fn drain(&self) -> Option<Drain> {
+        let drain = self.drain?;
+        Some(Drain {
+            remaining: self.remaining(),
+            ..drain
+        })
+    }
+
+    /// The number of connections that have not finished closing.
+    fn remaining(&self) -> usize {
+        self.states.keys()
+            .filter_map(|key| self.states.get(key))
+            .filter(|slot| slot.connection.is_draining())
+            .count()
+    }
+
+    /// A connection to reset since the deadline for closing has passed.
+    fn overdue(&self, now: Instant) -> Option<SlotKey> {
+        match self.drain {
+            Some(drain) if drain.deadline <= now => (),
+            _ => return None,
+        }
+
+        self.states.keys()
+            .find(|&key| self.states.get(key).is_some_and(|slot| slot.connection.is_draining()))
+            .map(|key| SlotKey { key })
+    }
+
+    /// Update the buffered data charged to a connection.
+    fn account(&mut self, index: SlotKey) {
+        let slot = match self.states.get_mut(index.key) {
+            Some(slot) => slot,
+            None => return,
+        };
+
+        let charge = slot.connection.buffered();
+        self.buffers.release(slot.charge);
+        self.buffers.charge(charge);
+        slot.charge = charge;
+    }
+
+    /// The buffered data a connection is permitted to hold currently.
+    fn allowance(&self, index: SlotKey) -> BufferSpace {
+        match self.states.get(index.key) {
+            Some(slot) => self.buffers.allowance(slot.charge),
+            None => BufferSpace::default(),
+        }
+    }
+
+    /// Update the timer of a connection in the timer wheel.
+    fn reschedule(&mut self, index: SlotKey) {
+        let timers = match &mut self.timers {
+            Some(timers) => timers,
+            None => return,
+        };
+
+        let next = match self.states.get(index.key) {
+            Some(slot) => slot.connection.next_timer(),
+            None => return,
+        };
+
+        match next {
+            Expiration::When(deadline) => { let _ = timers.schedule(index.key.index(), deadline); },
+            Expiration::Never => timers.cancel(index.key.index()),
+        }
+    }
+
+    /// Opens a new port for listening on a local address.
+    ///
+    /// The slot accepts a single connection attempt and then becomes the state of that connection,
+    /// the returned key stays valid for it. Open another listening slot to accept more connections
+    /// on the same port. Returns the key to use to inspect or modify the connection state and
+    /// parameters, or `None` if no slot is available or the port is already listening.
+    ///
+    /// The address may be unspecified to accept connections to any local address of the ip
+    /// layer. `IpAddress::Unspecified` matches both protocol versions, while the unspecified
+    /// address of one version (`0.0.0.0` or `::`) only matches that version. A listener bound to
+    /// the exact destination address of a SYN takes precedence over these wildcards.
+    pub fn listen(&mut self, ip: IpAddress, port: u16)
+        -> Option<SlotKey>
+    {
+        self.listen_with(ip, port, self.options)
+    }
+
+    /// Opens a port for listening with options other than those of the endpoint.
+    ///
+    /// Otherwise the same as [`listen`], the accepted connection keeps the options.
+    ///
+    /// [`listen`]: #method.listen
+    pub fn listen_with(&mut self, ip: IpAddress, port: u16, options: Options)
         -> Option<SlotKey>
     {
         let key = FourTuple {
@@ -223,9 +483,90 @@ impl Endpoint<'_> {
 
         let (key, state) = self.create_state(key)?;
         state.connection.current = State::Listen;
+        state.connection.set_options(options);
         Some(key)
     }
 
+    /// Bind a port and listen on it.
+    ///
+    /// Reserves the port for tcp in the registry of the endpoint, if any, before opening a
+    /// listening slot as with [`listen`]. Fails with `Illegal` if the port is already bound, for
+    /// example by another endpoint sharing the registry, and with `Exhausted` if no slot or
+    /// reservation is available. Further slots on a bound port, to accept more connections or on
+    /// other addresses, are opened with [`listen`].
+    ///
+    /// The reservation is not released when the slot is removed, release it in the registry once
+    /// the port is no longer used.
+    ///
+    /// [`listen`]: #method.listen
+    pub fn bind(&mut self, ip: IpAddress, port: u16) -> Result<SlotKey, crate::layer::Error> {
+        if let Some(registry) = self.registry {
+            registry.borrow_mut().reserve(Transport::Tcp, port)?;
+        }
+
+        match self.listen(ip, port) {
+            Some(key) => Ok(key),
+            None => {
+                if let Some(registry) = self.registry {
+                    registry.borrow_mut().release(Transport::Tcp, port);
+                }
+                Err(crate::layer::Error::Exhausted)
+            },
+        }
+    }
+
+    /// Opens a port for listening on several local addresses.
+    ///
+    /// Opens one listening slot for each address as with [`listen`] and stores their keys in the
+    /// same order in `keys`. Either all or none of the slots are opened. Fails with `Illegal` if
+    /// there are fewer keys than addresses and with `Exhausted` if a slot could not be opened.
+    ///
+    /// [`listen`]: #method.listen
+    pub fn listen_all(&mut self, ips: &[IpAddress], port: u16, keys: &mut [Option<SlotKey>])
+        -> Result<(), crate::layer::Error>
+    {
+        if keys.len() < ips.len() {
+            return Err(crate::layer::Error::Illegal);
+        }
+
+        for (idx, &ip) in ips.iter().enumerate() {
+            match self.listen(ip, port) {
+                Some(key) => keys[idx] = Some(key),
+                None => {
+                    for key in keys[..idx].iter_mut().filter_map(Option::take) {
+                        self.remove(key);
+                    }
+                    return Err(crate::layer::Error::Exhausted);
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Find the listener for an incoming connection attempt.
+    ///
+    /// Prefers an exact local address over the unspecified address of its protocol version over
+    /// the generic unspecified address.
+    fn find_listener(&self, tuple: FourTuple) -> Option<SlotKey> {
+        let unspecified = match tuple.local {
+            IpAddress::Ipv4(_) => IpAddress::Ipv4(Ipv4Address::UNSPECIFIED),
+            IpAddress::Ipv6(_) => IpAddress::Ipv6(Ipv6Address::UNSPECIFIED),
+            _ => IpAddress::Unspecified,
+        };
+
+        [tuple.local, unspecified, IpAddress::Unspecified].iter()
+            .map(|&local| FourTuple {
+                local,
+                local_port: tuple.local_port,
+                remote: IpAddress::Unspecified,
+                remote_port: 0,
+            })
+            .filter_map(|listener| self.ports.get(&listener))
+            .map(|&key| SlotKey { key })
+            .next()
+    }
+
     /// Actively try to connect to a remote TCP.
     ///
     /// This is not public as the caller controls the complete tuple.
@@ -240,6 +581,10 @@ impl Endpoint<'_> {
     fn create_state(&mut self, addr: FourTuple)
         -> Option<(SlotKey, &mut Slot)>
     {
+        if !self.buffers.admits() {
+            return None;
+        }
+
         let connection = self.create_connection();
 
         let vacant = self.ports
@@ -255,7 +600,9 @@ impl Endpoint<'_> {
 
         slot.connection = connection;
         slot.addr = addr;
+        slot.charge = BufferSpace::default();
         vacant.insert(key);
+        self.buffers.connections += 1;
 
         let key = SlotKey {
             key,
@@ -274,7 +621,7 @@ impl Endpoint<'_> {
             previous: State::Closed,
             flow_control: Flow {
                 congestion_window: 0,
-                ssthresh: u32::max_value(),
+                ssthresh: self.options.initial_ssthresh,
                 recover: TcpSeqNumber::default(),
             },
             receive_window: 0,
@@ -282,20 +629,30 @@ impl Endpoint<'_> {
             receiver_maximum_segment_size: 0,
             last_ack_receive_offset: TcpSeqNumber::default(),
             ack_timer: Expiration::Never,
-            ack_timeout: Duration::from_millis(500),
+            ack_timeout: self.options.ack_delay,
             retransmission_timer: Instant::from_millis(0),
-            retransmission_timeout: Duration::from_millis(3000),
-            restart_timeout: Duration::from_millis(30000),
+            retransmission_timeout: self.options.initial_rto,
+            restart_timeout: self.options.idle_restart,
             selective_acknowledgements: false,
             duplicate_ack: 0,
+            dscp: 0,
+            options: self.options,
+            statistics: Statistics::default(),
+            retransmissions: 0,
+            round_trip: RoundTrip::default(),
+            pacer: Pacer::default(),
             send: Send {
                 unacked: TcpSeqNumber::default(),
                 next: TcpSeqNumber::default(),
                 last_time: Instant::from_millis(0),
+                progress: Instant::from_millis(0),
                 unsent: 0,
                 window: 0,
                 window_scale: 0,
+                max_window: 0,
                 initial_seq: TcpSeqNumber::default(),
+                shutdown: false,
+                limit: u32::MAX,
             },
             recv: Receive {
                 acked: TcpSeqNumber::default(),
@@ -303,6 +660,9 @@ impl Endpoint<'_> {
                 last_time: Instant::from_millis(0),
                 window: 0,
                 window_scale: 0,
+                edge: TcpSeqNumber::default(),
+                advertised: TcpSeqNumber::default(),
+                max_window: 0,
                 initial_seq: TcpSeqNumber::default(),
             },
         }
@@ -326,13 +686,69 @@ impl Slot {
     pub(crate) fn connection(&self) -> &Connection {
         &self.connection
     }
+
+    /// Get the traffic counters of the connection.
+    pub fn statistics(&self) -> Statistics {
+        self.connection.statistics
+    }
+
+    /// Take a snapshot of the connection internals.
+    pub fn info(&self) -> Info {
+        self.connection.info()
+    }
+
+        /// Get the differentiated services code point used for the connection.
+    pub fn dscp(&self) -> u8 {
+        self.connection.dscp
+    }
+
+    /// Mark all future segments of the connection with a differentiated services code point.
+    pub fn set_dscp(&mut self, dscp: u8) {
+        self.connection.dscp = dscp;
+    }
+
+    /// Get the options of the connection.
+    pub fn options(&self) -> Options {
+        self.connection.options
+    }
+
+    /// Change the options of the connection.
+    ///
+    /// A new initial retransmission timeout has no effect once a round trip time was measured.
+    pub fn set_options(&mut self, options: Options) {
+        self.connection.set_options(options);
+    }
 }
 
 impl<'ep> Endpoint<'ep> {
     /// Create a new endpoint.
     ///
-    /// The map and states are **not** cleared.
-    pub fn new(
+    /// The map and states are **not** cleared. Prefer a map created with [`Map::hashed`] when the
+    /// endpoint should handle many connections, the lookup of each incoming segment is then
+    /// independent of their number.
+    ///
+    /// [`Map::hashed`]: ../../managed/enum.Map.html#method.hashed
+    ///
+    /// This is a `const fn`, as are the constructors of a map of pairs and of the slot map. An
+    /// endpoint over storage with a `'static` lifetime requires no runtime initialization, which
+    /// allows placing it in a `static` on embedded targets.
+    ///
+    /// ```
+    /// use ethox::layer::tcp::{Endpoint, FourTuple, IsnGenerator, Slot};
+    /// use ethox::managed::{slotmap, List, Map, Slice, SlotMap};
+    ///
+    /// const fn endpoint(
+    ///     ports: &'static mut [(FourTuple, slotmap::Key)],
+    ///     states: &'static mut [Slot],
+    ///     slots: &'static mut [slotmap::Slot],
+    /// ) -> Endpoint<'static> {
+    ///     Endpoint::new(
+    ///         Map::Pairs(List::new(Slice::Borrowed(ports))),
+    ///         SlotMap::new(Slice::Borrowed(states), Slice::Borrowed(slots)),
+    ///         IsnGenerator::from_secret_key_bytes([0; 16]))
+    /// }
+    /// ```
+    pub const fn new(
         ports: Map<'ep, FourTuple, Key>,
         states: SlotMap<'ep, Slot>,
         isn_generator: IsnGenerator,
@@ -341,9 +757,286 @@ impl<'ep> Endpoint<'ep> {
             ports,
             states,
             isn_generator,
+            rng: None,
+            events: None,
+            registry: None,
+            next_port: Self::EPHEMERAL_PORTS.0,
+            timers: None,
+            challenge_acks: ChallengeAcks::new(
+                Self::CHALLENGE_ACK_LIMIT.0,
+                Self::CHALLENGE_ACK_LIMIT.1),
+            coalesce_acks: false,
+            buffers: Budget::new(),
+            options: Options::DEFAULT,
+            last_failure: None,
+            drain: None,
+        }
+    }
+
+    /// The default rate limit of challenge ACKs.
+    ///
+    /// Permits bursts of ten with one additional ACK every ten milliseconds.
+    pub const CHALLENGE_ACK_LIMIT: (u32, Duration) = (10, Duration::from_millis(10));
+
+    /// Change the rate limit of challenge ACKs.
+    ///
+    /// Challenge ACKs answer RST and SYN segments on synchronized connections whose sequence
+    /// number is within the window but does not exactly match (RFC5961). At most `burst` of them
+    /// are sent at once, with one more permitted after each `interval`. A burst of zero disables
+    /// challenge ACKs entirely.
+    pub fn set_challenge_ack_limit(&mut self, burst: u32, interval: Duration) {
+        self.challenge_acks = ChallengeAcks::new(burst, interval);
+    }
+
+    /// Limit the data buffered by all connections together.
+    ///
+    /// The buffers themselves are provided by the user, the endpoint instead limits the data they
+    /// must hold: the receive windows offered to the remotes and the sent data that is not yet
+    /// acknowledged. Each connection, counting listening slots, is permitted an equal share of
+    /// the limits but no more than the other connections leave unused. It offers a smaller window
+    /// and holds back new data while at its share, so that a single busy connection can not
+    /// starve the others. Opening or listening on a further connection fails when its share would
+    /// not hold a segment of the default size, `bind` and `open` report this as `Exhausted`.
+    ///
+    /// A window that was already offered is never withdrawn, connections above their share after
+    /// a change of the limits or after new connections were opened return to it as the remotes
+    /// fill their windows and acknowledge data. The endpoint has no limits by default.
+    pub fn set_buffer_limits(&mut self, limits: BufferSpace) {
+        self.buffers.limits = limits;
+    }
+
+    /// The limits of the data buffered by all connections together.
+    pub fn buffer_limits(&self) -> BufferSpace {
+        self.buffers.limits
+    }
+
+    /// The data currently buffered by all connections together.
+    ///
+    /// See [`set_buffer_limits`] for what is accounted.
+    ///
+    /// [`set_buffer_limits`]: #method.set_buffer_limits
+    pub fn buffered(&self) -> BufferSpace {
+        self.buffers.used
+    }
+
+    /// Set whether to coalesce the ACKs for segments received in one batch.
+    ///
+    /// By default, an `Open` packet with received data answers with an ACK right away when it is
+    /// written and has no data to piggy-back it on. For a burst of segments on one connection this
+    /// sends an ACK for each of them. With coalescing, such a pure ACK is deferred instead: the
+    /// delayed ACK timer of the connection fires immediately and the next send phase acknowledges
+    /// all segments of the batch, with a single window update, at once. Run the sender after each
+    /// receive batch, for example whenever [`poll`] says so.
+    ///
+    /// [`poll`]: #method.poll
+    pub fn set_ack_coalescing(&mut self, coalesce: bool) {
+        self.coalesce_acks = coalesce;
+    }
+
+    /// The context of the last segment that was dropped as malformed.
+    ///
+    /// A wrong checksum is recorded as a `Wire` detail with the `WrongChecksum` error.
+    pub fn last_failure(&self) -> Option<Failure> {
+        self.last_failure
+    }
+
+    /// The range of ephemeral ports chosen for active opens, inclusive.
+    ///
+    /// This is the dynamic port range assigned by IANA.
+    pub const EPHEMERAL_PORTS: (u16, u16) = Registry::EPHEMERAL_PORTS;
+
+    /// Get the options of new connections.
+    pub fn options(&self) -> Options {
+        self.options
+    }
+
+    /// Change the options of connections created afterwards.
+    ///
+    /// Existing connections keep their options, change those through their [`Slot`].
+    ///
+    /// [`Slot`]: struct.Slot.html#method.set_options
+    pub fn set_options(&mut self, options: Options) {
+        self.options = options;
+    }
+
+    /// Set the random source for selecting ephemeral ports.
+    ///
+    /// Without a random source the ports are assigned sequentially which makes them easy to guess
+    /// for off-path attackers. See the [`rand`] module on where to get one.
+    ///
+    /// [`rand`]: ../../rand/index.html
+    pub fn set_rng(&mut self, rng: &'ep mut dyn Rng) {
+        self.rng = Some(rng);
+    }
+
+    /// Share a registry of bound ports with other endpoints.
+    ///
+    /// Ports bound with [`bind`] are reserved in it, and ephemeral ports of active opens skip all
+    /// ports it has reserved for tcp. The registry must not be borrowed while the endpoint binds
+    /// a port or sends. See the [`ports`] module.
+    ///
+    /// [`bind`]: #method.bind
+    /// [`ports`]: ../ports/index.html
+    pub fn set_registry(&mut self, registry: &'ep RefCell<Registry<'ep>>) {
+        self.registry = Some(registry);
+    }
+
+    /// Register an observer of connection events.
+    ///
+    /// See [`ConnectionEvents`] for the events and when they are reported.
+    ///
+    /// [`ConnectionEvents`]: trait.ConnectionEvents.html
+    pub fn set_events(&mut self, events: &'ep mut dyn ConnectionEvents) {
+        self.events = Some(events);
+    }
+
+    /// Remove the observer of connection events.
+    pub fn clear_events(&mut self) {
+        self.events = None;
+    }
+
+    /// Choose a free ephemeral port for a connection to a remote.
+    ///
+    /// Starts at a random offset, or after the last chosen port, and returns the first port that
+    /// does not complete the tuple of an existing connection and is not bound in the registry.
+    fn ephemeral_port(&mut self, local: IpAddress, remote: IpAddress, remote_port: u16)
+        -> Option<u16>
+    {
+        let registry = self.registry.map(RefCell::borrow);
+        let (first, last) = Self::EPHEMERAL_PORTS;
+        let count = u32::from(last - first) + 1;
+        let start = match &mut self.rng {
+            Some(rng) => (rng.next_u64() % u64::from(count)) as u32,
+            None => u32::from(self.next_port.max(first) - first),
+        };
+
+        let port = (0..count)
+            .map(|offset| first + ((start + offset) % count) as u16)
+            .find(|&local_port| {
+                let tuple = FourTuple { local, local_port, remote, remote_port };
+                self.ports.get(&tuple).is_none()
+                    && !registry.as_ref()
+                        .is_some_and(|registry| registry.is_reserved(Transport::Tcp, local_port))
+            })?;
+        drop(registry);
+
+        self.next_port = port.checked_add(1).unwrap_or(first);
+        Some(port)
+    }
+
+    /// Get the generator of initial sequence numbers.
+    pub fn isn_generator(&self) -> &IsnGenerator {
+        &self.isn_generator
+    }
+
+    /// Get a mutable reference to the generator of initial sequence numbers.
+    ///
+    /// Use it to rotate the secret key, which does not affect any already open connection.
+    pub fn isn_generator_mut(&mut self) -> &mut IsnGenerator {
+        &mut self.isn_generator
+    }
+
+    /// Track the timers of connections with a timer wheel.
+    ///
+    /// Without a wheel, [`poll`] inspects all connection states which takes time linear in their
+    /// number. The wheel should have at least the capacity of the connection states, as the timer
+    /// of a connection is identified by the index of its slot. Connections in slots beyond the
+    /// capacity of the wheel are not tracked.
+    ///
+    /// [`poll`]: #method.poll
+    pub fn set_timer_wheel(&mut self, timers: TimerWheel<'ep>) {
+        self.timers = Some(timers);
+        let capacity = self.timers.as_ref().map_or(0, TimerWheel::capacity);
+        for idx in 0..capacity {
+            if let Some(key) = self.states.key_at(idx) {
+                self.reschedule(SlotKey { key });
+            }
         }
     }
 
+    /// Drive the timers of all connections.
+    ///
+    /// Connections whose time wait period has passed are removed. Returns the earliest time at
+    /// which a connection needs to send a segment, such as a delayed ACK or a retransmission. Run a
+    /// sender on the endpoint at that time even if no packets have been received in the meantime.
+    /// With a timer wheel the returned time may be earlier, simply poll again at that time.
+    pub fn poll(&mut self, now: Instant) -> Expiration {
+        if self.timers.is_some() {
+            return self.poll_wheel(now).min(self.drain_deadline());
+        }
+
+        loop {
+            let states = &self.states;
+            let expired = states.keys().find(|&key| states
+                .get(key)
+                .is_some_and(|slot| slot.connection.time_wait_expired(now)));
+            match expired {
+                Some(key) => self.expire(SlotKey { key }),
+                None => break,
+            }
+        }
+
+        let next = self.states.keys()
+            .filter_map(|key| self.states.get(key))
+            .map(|slot| slot.connection.next_timer())
+            .min()
+            .unwrap_or(Expiration::Never);
+        next.min(self.drain_deadline())
+    }
+
+    /// The deadline of closing all connections while any of them remain.
+    fn drain_deadline(&self) -> Expiration {
+        match self.drain {
+            Some(drain) if self.remaining() > 0 => Expiration::When(drain.deadline),
+            _ => Expiration::Never,
+        }
+    }
+
+    /// Drive the timers of connections tracked in the timer wheel.
+    ///
+    /// The wheel only holds an approximation of the timers, they are checked against the actual
+    /// connection state when they fire. Due connections are checked again on the next tick until
+    /// a sender has handled their timer.
+    fn poll_wheel(&mut self, now: Instant) -> Expiration {
+        let mut due = Expiration::Never;
+
+        while let Some(idx) = self.timers.as_mut().and_then(|timers| timers.pop_expired(now)) {
+            let key = match self.states.key_at(idx) {
+                Some(key) => SlotKey { key },
+                None => continue,
+            };
+
+            let connection = &self.states.get(key.key).unwrap().connection;
+            if connection.time_wait_expired(now) {
+                self.expire(key);
+                continue;
+            }
+
+            let retry = match connection.next_timer() {
+                Expiration::When(deadline) if deadline <= now => {
+                    due = due.min(Expiration::When(deadline));
+                    now + Duration::from_millis(1)
+                },
+                Expiration::When(deadline) => deadline,
+                Expiration::Never => continue,
+            };
+
+            let _ = self.timers.as_mut().unwrap().schedule(idx, retry);
+        }
+
+        let next = self.timers.as_ref().map_or(Expiration::Never, TimerWheel::next_expiration);
+        due.min(next)
+    }
+
+    /// Drive the timers with the current time of a clock.
+    ///
+    /// See [`poll`] for details.
+    ///
+    /// [`poll`]: #method.poll
+    pub fn tick<C: Clock + ?Sized>(&mut self, clock: &C) -> Expiration {
+        self.poll(clock.now())
+    }
+
     /// Create a TCP receiver using this endpoint.
     pub fn recv<H>(&mut self, handler: H) -> Receiver<'_, 'ep, H> {
         Receiver { endpoint: self.borrow(), handler }
@@ -365,6 +1058,7 @@ impl<'a> Entry<'a> {
         let entry_key = EntryKey {
             ports: self.ports,
             isn: self.isn,
+            challenge_acks: self.challenge_acks,
             key_in_slot: &mut self.slot.addr,
         };
 
@@ -382,6 +1076,11 @@ impl<'a> Entry<'a> {
     pub fn connection(&mut self) -> &mut Connection {
         &mut self.slot.connection
     }
+
+    /// Change the options of the connection.
+    pub fn set_options(&mut self, options: Options) {
+        self.slot.connection.set_options(options)
+    }
 }
 
 impl EntryKey<'_> {
@@ -394,13 +1093,19 @@ impl EntryKey<'_> {
         *self.key_in_slot
     }
 
+    /// Consume one challenge ACK of the endpoint's rate limit.
+    pub(crate) fn challenge_ack(&mut self, time: Instant) -> bool {
+        self.challenge_acks.permits(time)
+    }
+
     /// Move the connection state to a new connection tuple.
     ///
-    /// # Panics
-    /// `new` must not be taken by any other connection yet.
-    pub fn set_four_tuple(&mut self, new: FourTuple) {
-        self.ports.remap(*self.key_in_slot, new);
+    /// Fails if `new` is already taken by another connection, in which case the connection stays
+    /// mapped under its current tuple.
+    pub fn set_four_tuple(&mut self, new: FourTuple) -> Result<(), crate::layer::Error> {
+        self.ports.remap(*self.key_in_slot, new)?;
         *self.key_in_slot = new;
+        Ok(())
     }
 }
 
@@ -409,9 +1114,99 @@ impl<'a> EntryKey<'a> {
     pub(crate) fn fake(
         ports: &'a mut dyn PortMap,
         isn: &'a IsnGenerator,
+        challenge_acks: &'a mut ChallengeAcks,
         key_in_slot: &'a mut FourTuple,
     ) -> EntryKey<'a> {
-        EntryKey { ports, isn, key_in_slot, }
+        EntryKey { ports, isn, challenge_acks, key_in_slot, }
+    }
+}
+
+impl ChallengeAcks {
+    pub(crate) const fn new(burst: u32, interval: Duration) -> Self {
+        ChallengeAcks {
+            burst,
+            interval,
+            tokens: burst,
+            last: None,
+        }
+    }
+
+    /// Consume one token of the rate limit.
+    pub(crate) fn permits(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        match self.tokens.checked_sub(1) {
+            Some(tokens) => {
+                self.tokens = tokens;
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let last = match self.last {
+            Some(last) if last <= now => last,
+            // Also restart if the clock jumped backwards.
+            _ => {
+                self.last = Some(now);
+                return;
+            },
+        };
+
+        let interval = self.interval.as_millis().max(1);
+        let refills = (now - last).as_millis() / interval;
+        if refills == 0 {
+            return;
+        }
+
+        let refills = refills.min(u128::from(self.burst)) as u32;
+        self.tokens = self.tokens.saturating_add(refills).min(self.burst);
+        self.last = Some(now);
+    }
+}
+
+impl Budget {
+    /// The smallest share of the limits with which a connection is created.
+    ///
+    /// The default maximum segment size, see RFC1122.
+    const MINIMUM_SHARE: usize = 536;
+
+    const fn new() -> Self {
+        Budget {
+            limits: BufferSpace { send: usize::MAX, recv: usize::MAX },
+            used: BufferSpace { send: 0, recv: 0 },
+            connections: 0,
+        }
+    }
+
+    /// Check if another connection still gets a share large enough for a segment.
+    fn admits(&self) -> bool {
+        let count = self.connections + 1;
+        self.limits.send / count >= Self::MINIMUM_SHARE
+            && self.limits.recv / count >= Self::MINIMUM_SHARE
+    }
+
+    /// The data a connection holding `charge` is permitted to hold.
+    fn allowance(&self, charge: BufferSpace) -> BufferSpace {
+        let share = |limit: usize, used: usize, own: usize| {
+            let others = used - own;
+            (limit / self.connections.max(1)).min(limit.saturating_sub(others))
+        };
+
+        BufferSpace {
+            send: share(self.limits.send, self.used.send, charge.send),
+            recv: share(self.limits.recv, self.used.recv, charge.recv),
+        }
+    }
+
+    fn charge(&mut self, charge: BufferSpace) {
+        self.used.send += charge.send;
+        self.used.recv += charge.recv;
+    }
+
+    fn release(&mut self, charge: BufferSpace) {
+        self.used.send -= charge.send;
+        self.used.recv -= charge.recv;
     }
 }
 
@@ -420,6 +1215,7 @@ impl Default for Slot {
        Slot {
            addr: FourTuple::default(),
            connection: Connection::zeroed(),
+           charge: BufferSpace::default(),
        }
     }
 }
@@ -445,18 +1241,24 @@ impl super::connection::Endpoint for Endpoint<'_> {
         if self.ports.entry(tuple).occupied().is_some() {
             Endpoint::entry_from_tuple(self, tuple)
         } else {
-            Endpoint::entry_from_tuple(self, FourTuple {
-                local: tuple.local,
-                local_port: tuple.local_port,
-                remote: IpAddress::Unspecified,
-                remote_port: 0,
-            })
+            let key = self.find_listener(tuple)?;
+            Endpoint::entry(self, key)
         }
     }
 
-    fn source_port(&mut self, _: IpAddress) -> Option<u16> {
-        // FIXME: find a suitable source port....
-        Some(80)
+    fn reschedule(&mut self, index: SlotKey) {
+        Endpoint::account(self, index);
+        Endpoint::reschedule(self, index)
+    }
+
+    fn allowance(&self, index: SlotKey) -> BufferSpace {
+        Endpoint::allowance(self, index)
+    }
+
+    fn source_port(&mut self, local: IpAddress, remote: IpAddress, remote_port: u16)
+        -> Option<u16>
+    {
+        Endpoint::ephemeral_port(self, local, remote, remote_port)
     }
 
     fn listen(&mut self, ip: IpAddress, port: u16) -> Option<SlotKey> {
@@ -467,25 +1269,52 @@ impl super::connection::Endpoint for Endpoint<'_> {
         Endpoint::open(self, tuple)
     }
 
+    fn options(&self) -> Options {
+        Endpoint::options(self)
+    }
+
     fn initial_seq_num(&mut self, id: FourTuple, time: Instant) -> TcpSeqNumber {
         Endpoint::initial_seq_num(self, id, time)
     }
+
+    fn notify(&mut self, index: SlotKey, event: Event) {
+        Endpoint::notify(self, index, event)
+    }
 }
 
 impl PortMap for Map<'_, FourTuple, Key> {
-    fn remap(&mut self, old: FourTuple, new: FourTuple) {
-        let old = self.entry(old)
-            .occupied()
-            // FIXME: unwrap justified? Seems like it may not.
-            .unwrap();
-        let value = *old.get();
-        old.remove();
-
-        self.entry(new)
-            .vacant()
-            // FIXME: nearly justified but how to ensure it was not mapped?
-            .unwrap()
-            .insert(value);
+    fn remap(&mut self, old: FourTuple, new: FourTuple) -> Result<(), crate::layer::Error> {
+        if old == new {
+            return Ok(());
+        }
+
+        // Check before removing the old mapping, so that a collision changes nothing.
+        if self.get(&new).is_some() {
+            return Err(crate::layer::Error::Illegal);
+        }
+
+        let value = match self.entry(old).occupied() {
+            Some(entry) => {
+                let value = *entry.get();
+                entry.remove();
+                value
+            },
+            None => return Err(crate::layer::Error::Illegal),
+        };
+
+        // Removing the old mapping made room for the new one.
+        match self.entry(new).vacant() {
+            Some(entry) => {
+                entry.insert(value);
+                Ok(())
+            },
+            None => {
+                if let Some(entry) = self.entry(old).vacant() {
+                    entry.insert(value);
+                }
+                Err(crate::layer::Error::Exhausted)
+            },
+        }
     }
 }
 
@@ -498,24 +1327,41 @@ where
         let ip::InPacket { mut handle, packet } = ip_packet;
 
         let repr = packet.repr();
+        let src_addr = repr.src_addr();
         let capabilities = handle.info().capabilities();
         let checksum = capabilities.tcp().rx_checksum(repr);
 
         let packet = match TcpPacket::new_checked(packet, checksum) {
             Ok(packet) => packet,
-            // TODO: error logging.
-            Err(_) => return (),
+            Err(err) => {
+                net_debug!("tcp: dropped malformed segment from {}: {:?}", src_addr, err);
+                let detail = Detail::Wire(err);
+                let failure = Failure::new(Error::Illegal, Origin::Tcp, Operation::Receive, detail);
+                self.endpoint.inner.last_failure = Some(failure);
+                return;
+            },
         };
 
-        let arrived = match In::from_arriving(self.endpoint.inner, handle.borrow_mut(), packet) {
+        let coalesce_acks = self.endpoint.inner.coalesce_acks;
+        let arrived = In::from_arriving(self.endpoint.inner, handle.borrow_mut(), packet);
+        let mut arrived = match arrived {
             Ok(arrived) => arrived,
-
-            // TODO: error logging.
-            Err(_) => return (),
+            Err(err) => {
+                net_debug!("tcp: dropped segment from {}: {:?}", src_addr, err);
+                return;
+            },
         };
 
+        if let In::Open(open) = &mut arrived {
+            open.coalesce_ack(coalesce_acks);
+        }
+
         self.handler.receive(arrived)
     }
+
+    fn accepts_protocol(&self, protocol: IpProtocol) -> bool {
+        protocol == IpProtocol::Tcp
+    }
 }
 
 impl<H, P> ip::Send<P> for Sender<'_, '_, H>
@@ -525,12 +1371,374 @@ where
 {
     fn send(&mut self, ip_raw: ip::RawPacket<P>) {
         let ip::RawPacket { mut handle, payload } = ip_raw;
+        let overdue = self.endpoint.inner.overdue(handle.info().timestamp());
 
         let raw = Raw {
             ip: ip::RawPacket { handle: handle.borrow_mut(), payload },
             endpoint: self.endpoint.inner,
         };
 
+        // Connections that did not close by the deadline take precedence.
+        if let Some(key) = overdue {
+            // Without a route to the remote the connection is dropped silently.
+            let _ = raw.reset(key);
+            let endpoint = &mut *self.endpoint.inner;
+            endpoint.remove(key);
+            if let Some(drain) = &mut endpoint.drain {
+                drain.reset += 1;
+            }
+            return;
+        }
+
         self.handler.send(raw)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managed::{List, Slice};
+
+    struct Fixed(u64);
+
+    impl Rng for Fixed {
+        fn next_u64(&mut self) -> u64 {
+            self.0
+        }
+    }
+
+    /// Remembers the last connection that timed out.
+    struct Timeouts<'a>(&'a core::cell::Cell<Option<SlotKey>>);
+
+    impl ConnectionEvents for Timeouts<'_> {
+        fn timeout(&mut self, key: SlotKey) {
+            self.0.set(Some(key));
+        }
+    }
+
+    #[test]
+    fn ephemeral_ports() {
+        let mut pairs = [Default::default(); 4];
+        let mut slots = [Default::default(); 4];
+        let mut keys = [Default::default(); 4];
+        let mut endpoint = Endpoint::new(
+            Map::Pairs(List::new(Slice::from(&mut pairs[..]))),
+            SlotMap::new(Slice::from(&mut slots[..]), Slice::from(&mut keys[..])),
+            IsnGenerator::from_key(0, 0));
+
+        let local = IpAddress::v4(192, 0, 2, 1);
+        let remote = IpAddress::v4(192, 0, 2, 2);
+        let (first, _) = Endpoint::EPHEMERAL_PORTS;
+
+        // Sequential without a random source.
+        let port = endpoint.ephemeral_port(local, remote, 80).unwrap();
+        assert_eq!(port, first);
+        let port = endpoint.ephemeral_port(local, remote, 80).unwrap();
+        assert_eq!(port, first + 1);
+
+        let mut rng = Fixed(100);
+        endpoint.set_rng(&mut rng);
+        let port = endpoint.ephemeral_port(local, remote, 80).unwrap();
+        assert_eq!(port, first + 100);
+
+        // An occupied tuple is skipped.
+        let tuple = FourTuple { local, local_port: port, remote, remote_port: 80 };
+        assert!(endpoint.open(tuple).is_some());
+        assert_eq!(endpoint.ephemeral_port(local, remote, 80), Some(first + 101));
+        assert_eq!(endpoint.ephemeral_port(local, remote, 443), Some(first + 100));
+    }
+
+    #[test]
+    fn bind_registry() {
+        use crate::layer::ports::Binding;
+
+        let mut bindings = [Binding { transport: Transport::Tcp, port: 0 }; 4];
+        let registry = RefCell::new(Registry::new(&mut bindings[..]));
+        let mut pairs = [Default::default(); 4];
+        let mut slots = [Default::default(); 4];
+        let mut keys = [Default::default(); 4];
+        let mut endpoint = Endpoint::new(
+            Map::Pairs(List::new(Slice::from(&mut pairs[..]))),
+            SlotMap::new(Slice::from(&mut slots[..]), Slice::from(&mut keys[..])),
+            IsnGenerator::from_key(0, 0));
+        endpoint.set_registry(&registry);
+
+        let local = IpAddress::v4(192, 0, 2, 1);
+        let remote = IpAddress::v4(192, 0, 2, 2);
+        let (first, _) = Endpoint::EPHEMERAL_PORTS;
+
+        // The same port may be bound for udp, but only once for tcp.
+        registry.borrow_mut().reserve(Transport::Udp, 5000).unwrap();
+        assert!(endpoint.bind(local, 5000).is_ok());
+        assert_eq!(endpoint.bind(local, 5000), Err(Error::Illegal));
+        assert!(endpoint.listen(IpAddress::v4(192, 0, 2, 3), 5000).is_some());
+
+        // Ephemeral ports bound for tcp are skipped, those bound for udp are not.
+        registry.borrow_mut().reserve(Transport::Tcp, first).unwrap();
+        registry.borrow_mut().reserve(Transport::Udp, first + 1).unwrap();
+        assert_eq!(endpoint.ephemeral_port(local, remote, 80), Some(first + 1));
+    }
+
+    #[test]
+    fn wildcard_listen() {
+        use super::super::connection::{Endpoint as _, InPacket};
+        use crate::wire::{TcpFlags, TcpRepr};
+
+        let mut pairs = [Default::default(); 4];
+        let mut slots = [Default::default(); 4];
+        let mut keys = [Default::default(); 4];
+        let mut endpoint = Endpoint::new(
+            Map::Pairs(List::new(Slice::from(&mut pairs[..]))),
+            SlotMap::new(Slice::from(&mut slots[..]), Slice::from(&mut keys[..])),
+            IsnGenerator::from_key(0, 0));
+
+        let exact = IpAddress::v4(192, 0, 2, 1);
+        let other = IpAddress::v4(192, 0, 2, 3);
+        let remote = IpAddress::v4(192, 0, 2, 2);
+        let any = endpoint.listen(IpAddress::Unspecified, 80).unwrap();
+        let bound = endpoint.listen(exact, 80).unwrap();
+
+        let tuple = |local| FourTuple { local, local_port: 80, remote, remote_port: 49152 };
+        assert_eq!(endpoint.find_tuple(tuple(exact)).unwrap().slot_key(), bound);
+        assert_eq!(endpoint.find_tuple(tuple(other)).unwrap().slot_key(), any);
+        assert!(endpoint.find_tuple(FourTuple { local_port: 81, ..tuple(other) }).is_none());
+
+        let syn = TcpRepr {
+            src_port: 49152,
+            dst_port: 80,
+            flags: TcpFlags::SYN,
+            seq_number: TcpSeqNumber(1000),
+            ack_number: None,
+            window_len: 1000,
+            window_scale: None,
+            max_seg_size: None,
+            sack_permitted: false,
+            sack_ranges: [None; 3],
+            user_timeout: None,
+            payload_len: 0,
+        };
+        let arrives = |endpoint: &mut Endpoint, segment| {
+            let entry = endpoint.find_tuple(tuple(other)).unwrap();
+            let (entry_key, connection) = entry.into_key_value();
+            let incoming = InPacket {
+                segment,
+                from: remote,
+                to: other,
+                time: Instant::from_secs(0),
+                segment_size: None,
+            };
+            connection.arrives(&incoming, entry_key)
+        };
+
+        // The SYN is answered from the actual local address.
+        let signals = arrives(&mut endpoint, syn);
+        let syn_ack = signals.answer.unwrap();
+        assert!(syn_ack.flags.syn());
+        assert_eq!(syn_ack.ack_number, Some(TcpSeqNumber(1001)));
+        assert_eq!(endpoint.get(any).unwrap().four_tuple(), tuple(other));
+        assert_eq!(endpoint.get(any).unwrap().info().state, State::SynReceived);
+
+        let ack = TcpRepr {
+            flags: TcpFlags::default(),
+            seq_number: TcpSeqNumber(1001),
+            ack_number: Some(syn_ack.seq_number + 1),
+            ..syn
+        };
+        let _ = arrives(&mut endpoint, ack);
+        assert_eq!(endpoint.get(any).unwrap().info().state, State::Established);
+
+        // The wildcard can be bound again.
+        assert!(endpoint.listen(IpAddress::Unspecified, 80).is_some());
+    }
+
+    #[test]
+    fn colliding_accept() {
+        use super::super::connection::InPacket;
+        use crate::wire::{TcpFlags, TcpRepr};
+
+        let mut pairs = [Default::default(); 2];
+        let mut slots = [Default::default(); 2];
+        let mut keys = [Default::default(); 2];
+        let mut endpoint = Endpoint::new(
+            Map::Pairs(List::new(Slice::from(&mut pairs[..]))),
+            SlotMap::new(Slice::from(&mut slots[..]), Slice::from(&mut keys[..])),
+            IsnGenerator::from_key(0, 0));
+
+        let local = IpAddress::v4(192, 0, 2, 1);
+        let remote = IpAddress::v4(192, 0, 2, 2);
+        let tuple = FourTuple { local, local_port: 80, remote, remote_port: 49152 };
+        let listener = endpoint.listen(IpAddress::Unspecified, 80).unwrap();
+        let existing = endpoint.open(tuple).unwrap();
+
+        // A SYN handed to the listener directly whose tuple is already taken.
+        let incoming = InPacket {
+            segment: TcpRepr {
+                src_port: 49152,
+                dst_port: 80,
+                flags: TcpFlags::SYN,
+                seq_number: TcpSeqNumber(1000),
+                ack_number: None,
+                window_len: 1000,
+                window_scale: None,
+                max_seg_size: None,
+                sack_permitted: false,
+                sack_ranges: [None; 3],
+                user_timeout: None,
+                payload_len: 0,
+            },
+            from: remote,
+            to: local,
+            time: Instant::from_secs(0),
+            segment_size: None,
+        };
+        let (entry_key, connection) = endpoint.entry(listener).unwrap().into_key_value();
+        let signals = connection.arrives(&incoming, entry_key);
+
+        assert!(signals.answer.unwrap().flags.rst());
+        assert_eq!(endpoint.get(listener).unwrap().info().state, State::Listen);
+        assert_eq!(endpoint.key_from_tuple(tuple), Some(existing));
+        let wildcard = FourTuple {
+            local: IpAddress::Unspecified,
+            local_port: 80,
+            remote: IpAddress::Unspecified,
+            remote_port: 0,
+        };
+        assert_eq!(endpoint.key_from_tuple(wildcard), Some(listener));
+    }
+
+    #[test]
+    fn listen_all() {
+        let mut pairs = [Default::default(); 2];
+        let mut slots = [Default::default(); 2];
+        let mut keys = [Default::default(); 2];
+        let mut endpoint = Endpoint::new(
+            Map::Pairs(List::new(Slice::from(&mut pairs[..]))),
+            SlotMap::new(Slice::from(&mut slots[..]), Slice::from(&mut keys[..])),
+            IsnGenerator::from_key(0, 0));
+
+        let first = IpAddress::v4(192, 0, 2, 1);
+        let second = IpAddress::v4(192, 0, 2, 3);
+        let mut listeners = [None; 3];
+
+        // Not enough slots, nothing is opened.
+        let all = [first, second, IpAddress::Unspecified];
+        assert!(endpoint.listen_all(&all, 80, &mut listeners[..2]).is_err());
+        assert!(endpoint.listen_all(&all, 80, &mut listeners).is_err());
+        assert_eq!(listeners, [None; 3]);
+
+        assert!(endpoint.listen_all(&all[..2], 80, &mut listeners).is_ok());
+        for (&ip, key) in all.iter().zip(&listeners[..2]) {
+            let key = key.unwrap();
+            assert_eq!(endpoint.get(key).unwrap().four_tuple().local, ip);
+        }
+    }
+
+    #[test]
+    fn stale_key() {
+        let mut pairs = [Default::default(); 1];
+        let mut slots = [Default::default(); 1];
+        let mut keys = [Default::default(); 1];
+        let mut endpoint = Endpoint::new(
+            Map::Pairs(List::new(Slice::from(&mut pairs[..]))),
+            SlotMap::new(Slice::from(&mut slots[..]), Slice::from(&mut keys[..])),
+            IsnGenerator::from_key(0, 0));
+
+        let local = IpAddress::v4(192, 0, 2, 1);
+        let old = endpoint.listen(local, 80).unwrap();
+        endpoint.remove(old);
+
+        // The single slot is reused, the old key must not alias the new listener.
+        let new = endpoint.listen(local, 81).unwrap();
+        assert_ne!(old, new);
+        assert!(endpoint.get(old).is_none());
+        assert!(endpoint.get_mut(old).is_none());
+        assert_eq!(endpoint.get(new).unwrap().four_tuple().local_port, 81);
+
+        // Removing with the stale key leaves the new connection intact.
+        endpoint.remove(old);
+        assert!(endpoint.get(new).is_some());
+        assert_eq!(endpoint.key_from_tuple(endpoint.get(new).unwrap().four_tuple()), Some(new));
+    }
+
+    #[test]
+    fn time_wait_expires() {
+        let timed_out = core::cell::Cell::new(None);
+        let mut events = Timeouts(&timed_out);
+        let mut pairs = [Default::default(); 2];
+        let mut slots = [Default::default(); 2];
+        let mut keys = [Default::default(); 2];
+        let mut endpoint = Endpoint::new(
+            Map::Pairs(List::new(Slice::from(&mut pairs[..]))),
+            SlotMap::new(Slice::from(&mut slots[..]), Slice::from(&mut keys[..])),
+            IsnGenerator::from_key(0, 0));
+        endpoint.set_events(&mut events);
+
+        let start = Instant::from_secs(0);
+        let end = Instant::from_secs(4);
+        let local = IpAddress::v4(192, 0, 2, 1);
+        assert!(endpoint.listen(local, 80).is_some());
+        assert_eq!(endpoint.poll(start), Expiration::Never);
+
+        let tuple = FourTuple {
+            local,
+            local_port: 80,
+            remote: IpAddress::v4(192, 0, 2, 2),
+            remote_port: 49152,
+        };
+        let key = endpoint.open(tuple).unwrap();
+        let connection = &mut endpoint.get_mut(key).unwrap().connection;
+        connection.change_state(State::TimeWait);
+        connection.retransmission_timer = end;
+
+        assert_eq!(endpoint.poll(start), Expiration::When(end));
+        assert!(endpoint.get(key).is_some());
+        assert_eq!(timed_out.get(), None);
+        assert_eq!(endpoint.tick(&|| end), Expiration::Never);
+        assert!(endpoint.get(key).is_none());
+        assert_eq!(endpoint.key_from_tuple(tuple), None);
+        assert_eq!(timed_out.get(), Some(key));
+    }
+
+    #[test]
+    fn timer_wheel() {
+        let mut pairs = [Default::default(); 2];
+        let mut slots = [Default::default(); 2];
+        let mut keys = [Default::default(); 2];
+        let mut timers = [Default::default(); 2];
+        let mut endpoint = Endpoint::new(
+            Map::Pairs(List::new(Slice::from(&mut pairs[..]))),
+            SlotMap::new(Slice::from(&mut slots[..]), Slice::from(&mut keys[..])),
+            IsnGenerator::from_key(0, 0));
+
+        let start = Instant::from_secs(0);
+        let ack = Instant::from_secs(1);
+        let end = Instant::from_secs(4);
+        let tuple = FourTuple {
+            local: IpAddress::v4(192, 0, 2, 1),
+            local_port: 80,
+            remote: IpAddress::v4(192, 0, 2, 2),
+            remote_port: 49152,
+        };
+
+        let key = endpoint.open(tuple).unwrap();
+        let connection = &mut endpoint.get_mut(key).unwrap().connection;
+        connection.change_state(State::Established);
+        connection.ack_timer = Expiration::When(ack);
+        endpoint.set_timer_wheel(TimerWheel::new(Slice::from(&mut timers[..]), start));
+        // The wheel may wake up early to move the timer to a finer level.
+        assert!(endpoint.poll(start) <= Expiration::When(ack));
+
+        // The timer stays due until handled.
+        assert_eq!(endpoint.poll(ack), Expiration::When(ack));
+        assert_eq!(endpoint.poll(ack + Duration::from_millis(5)), Expiration::When(ack));
+
+        let connection = &mut endpoint.get_mut(key).unwrap().connection;
+        connection.change_state(State::TimeWait);
+        connection.retransmission_timer = end;
+        endpoint.reschedule(key);
+        assert!(endpoint.poll(ack) <= Expiration::When(end));
+        assert!(endpoint.get(key).is_some());
+        assert_eq!(endpoint.poll(end), Expiration::Never);
+        assert!(endpoint.get(key).is_none());
+    }
+}
@@ -0,0 +1,123 @@
+//! Notifications about connection changes.
+use crate::wire::TcpSeqNumber;
+
+use super::connection::{Connection, State};
+use super::endpoint::SlotKey;
+
+/// An observer of the connections of an endpoint.
+///
+/// Registered with [`Endpoint::set_events`], the endpoint calls it while it handles segments and
+/// timers. Applications can keep their own state for each connection up to date with it instead
+/// of inspecting all connection states after each batch. All methods do nothing by default.
+///
+/// The callbacks run while the endpoint is borrowed for the packet, they can not access the
+/// connection. Record what is needed and act on it afterwards.
+///
+/// [`Endpoint::set_events`]: struct.Endpoint.html#method.set_events
+pub trait ConnectionEvents {
+    /// The handshake of a connection completed.
+    fn established(&mut self, key: SlotKey) {
+        let _ = key;
+    }
+
+    /// The remote acknowledged data sent on the connection.
+    ///
+    /// The number of bytes excludes the sequence space of SYN and FIN.
+    fn data_acked(&mut self, key: SlotKey, bytes: usize) {
+        let _ = (key, bytes);
+    }
+
+    /// The remote closed its direction of the connection.
+    ///
+    /// Called once the FIN was accepted, that is when the data before it has been read.
+    fn remote_fin(&mut self, key: SlotKey) {
+        let _ = key;
+    }
+
+    /// The remote reset the connection.
+    ///
+    /// The connection state is removed after the callback returns.
+    fn reset(&mut self, key: SlotKey) {
+        let _ = key;
+    }
+
+    /// A timer of the endpoint removed the connection.
+    ///
+    /// This happens at the end of the time wait period of a connection that was closed, and when
+    /// a connection gives up on an unresponsive remote.
+    fn timeout(&mut self, key: SlotKey) {
+        let _ = key;
+    }
+}
+
+/// An event on a connection, dispatched to the observer of an endpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    Established,
+    DataAcked(usize),
+    RemoteFin,
+    Reset,
+    Timeout,
+}
+
+/// The parts of a connection that are compared to find events.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Snapshot {
+    state: State,
+    unacked: TcpSeqNumber,
+    remote_closed: bool,
+}
+
+impl Event {
+    pub(crate) fn dispatch(self, events: &mut dyn ConnectionEvents, key: SlotKey) {
+        match self {
+            Event::Established => events.established(key),
+            Event::DataAcked(bytes) => events.data_acked(key, bytes),
+            Event::RemoteFin => events.remote_fin(key),
+            Event::Reset => events.reset(key),
+            Event::Timeout => events.timeout(key),
+        }
+    }
+}
+
+impl Snapshot {
+    pub(crate) fn new(connection: &Connection) -> Self {
+        Snapshot {
+            state: connection.current,
+            unacked: connection.send.unacked,
+            remote_closed: connection.is_remote_closed(),
+        }
+    }
+
+    /// Whether the connection completed its handshake since the snapshot.
+    pub(crate) fn established(&self, connection: &Connection) -> bool {
+        let opening = |state| matches!(state, State::SynSent | State::SynReceived);
+        opening(self.state) && !opening(connection.current) && connection.current != State::Closed
+    }
+
+    /// Whether the remote closed its direction since the snapshot.
+    pub(crate) fn remote_fin(&self, connection: &Connection) -> bool {
+        !self.remote_closed && connection.is_remote_closed()
+    }
+
+    /// The number of data bytes acknowledged since the snapshot.
+    pub(crate) fn data_acked(&self, connection: &Connection) -> usize {
+        let send = &connection.send;
+        // Nothing was sent before, the initial sequence number was only just chosen.
+        if matches!(self.state, State::Closed | State::Listen) || send.unacked <= self.unacked {
+            return 0;
+        }
+
+        let mut acked = send.unacked - self.unacked;
+        // The SYN occupies the first sequence number.
+        if matches!(self.state, State::SynSent | State::SynReceived) {
+            acked -= 1;
+        }
+        // And our FIN the last one.
+        let fin_sent = matches!(self.state, State::FinWait1 | State::Closing | State::LastAck);
+        if fin_sent && send.unacked == send.next && acked > 0 {
+            acked -= 1;
+        }
+        acked
+    }
+}
@@ -0,0 +1,322 @@
+//! Hooks for protocols framing the byte stream of a connection.
+//!
+//! Protocols such as TLS split the stream into records and answer some of them on their own, for
+//! example during a handshake. A [`Framing`] is offered the received bytes in place, directly in
+//! the receive ring, and writes its output directly into the send ring. An implementation on top
+//! of a TLS library hands the received slice to the library and lets it encrypt into the send
+//! ring, so that the stream is not copied through another pair of buffers. With the `std`
+//! feature, the [`SendRing`] implements `std::io::Write` for libraries expecting a writer.
+//!
+//! The [`Framed`] handler drives a framing for a single connection, in the same way as the
+//! [`Stream`] handler moves the data of a connection without a framing.
+//!
+//! ```
+//! use ethox::layer::tcp::framing::{write_length_prefixed, Framing, LengthPrefixed};
+//! use ethox::layer::tcp::stream::SendRing;
+//!
+//! // Answer every frame with its length.
+//! let mut framing = LengthPrefixed::new(|frame: &[u8], send: &mut SendRing| {
+//!     let _ = write_length_prefixed(send, &[frame.len() as u8]);
+//! });
+//!
+//! let mut send = SendRing::new(vec![0; 64]);
+//! // One complete and one partial frame.
+//! assert_eq!(framing.receive(b"\x00\x03abc\x00\x04d", &mut send), 5);
+//! assert_eq!(send.len(), 3);
+//! ```
+//!
+//! [`Framing`]: trait.Framing.html
+//! [`Framed`]: struct.Framed.html
+//! [`Stream`]: ../stream/struct.Stream.html
+//! [`SendRing`]: ../stream/struct.SendRing.html
+use byteorder::{ByteOrder, NetworkEndian};
+
+use crate::layer::{Error, Result};
+use crate::wire::PayloadMut;
+
+use super::stream::{RecvRing, SendRing};
+use super::{InPacket, RawPacket, Recv, Send, SlotKey};
+
+/// A protocol framing the byte stream of a connection.
+pub trait Framing {
+    /// Process contiguous received bytes of the stream.
+    ///
+    /// Returns the number of bytes consumed from the start of `data`, the rest is offered again
+    /// together with the following bytes. Any output, such as an answer, is written to `send`.
+    ///
+    /// When no byte is consumed although more data has been received, the data is moved to be
+    /// contiguous and offered once more. Consequently, the receive storage must be able to hold
+    /// the largest frame.
+    fn receive(&mut self, data: &[u8], send: &mut SendRing) -> usize;
+
+    /// Write output that is not an immediate reaction to received data.
+    ///
+    /// Called before sending on the connection. Does nothing by default.
+    fn transmit(&mut self, send: &mut SendRing) {
+        let _ = send;
+    }
+}
+
+/// A tcp handler for a single, existing connection with framed data.
+///
+/// Whenever a packet arrives on the connection, its data is read into the receive ring and handed
+/// to the framing before an answer with data from the send ring is written. When sending, the
+/// framing may write to the send ring first.
+pub struct Framed<'a, F> {
+    key: Option<SlotKey>,
+    recv: RecvRing<'a>,
+    send: SendRing<'a>,
+    framing: F,
+}
+
+/// A framing of the stream into frames with a two byte length prefix, in network byte order.
+///
+/// Each complete frame is passed to a handler, without its prefix. Frames are sent with
+/// [`write_length_prefixed`].
+///
+/// [`write_length_prefixed`]: fn.write_length_prefixed.html
+pub struct LengthPrefixed<H> {
+    handler: H,
+}
+
+/// The length of the prefix of a frame.
+const PREFIX_LEN: usize = 2;
+
+/// Write one frame with its length prefix.
+///
+/// The frame is written completely or not at all. Fails with `BadSize` if the frame is longer
+/// than the prefix can indicate, with `Exhausted` if the send ring has not enough space left and
+/// with `Illegal` if it has been closed.
+pub fn write_length_prefixed(send: &mut SendRing, frame: &[u8]) -> Result<()> {
+    if frame.len() > usize::from(u16::MAX) {
+        return Err(Error::BadSize);
+    }
+
+    if send.is_closed() {
+        return Err(Error::Illegal);
+    }
+
+    if send.window() < PREFIX_LEN + frame.len() {
+        return Err(Error::Exhausted);
+    }
+
+    let mut prefix = [0; PREFIX_LEN];
+    NetworkEndian::write_u16(&mut prefix, frame.len() as u16);
+    send.write(&prefix);
+    send.write(frame);
+    Ok(())
+}
+
+impl<'a, F: Framing> Framed<'a, F> {
+    /// Create a handler for an existing connection.
+    pub fn new(key: SlotKey, recv: RecvRing<'a>, send: SendRing<'a>, framing: F) -> Self {
+        Framed {
+            key: Some(key),
+            recv,
+            send,
+            framing,
+        }
+    }
+
+    /// Get a reference to the framing.
+    pub fn framing(&self) -> &F {
+        &self.framing
+    }
+
+    /// Get a mutable reference to the framing.
+    pub fn framing_mut(&mut self) -> &mut F {
+        &mut self.framing
+    }
+
+    /// Get a reference to the receive ring.
+    ///
+    /// It only contains data that the framing has not consumed yet.
+    pub fn recv(&self) -> &RecvRing<'a> {
+        &self.recv
+    }
+
+    /// Get a mutable reference to the send ring.
+    pub fn send_mut(&mut self) -> &mut SendRing<'a> {
+        &mut self.send
+    }
+
+    /// Get the key of the connection, unless it was closed.
+    pub fn connection_key(&self) -> Option<SlotKey> {
+        self.key
+    }
+
+    /// Check if the connection was closed.
+    pub fn is_closed(&self) -> bool {
+        self.key.is_none()
+    }
+
+    /// Unwrap the framing.
+    pub fn into_inner(self) -> F {
+        self.framing
+    }
+
+    /// Offer all received data to the framing.
+    fn process(&mut self) {
+        let Framed { recv, send, framing, .. } = self;
+        while !recv.is_empty() {
+            let mut contiguous = 0;
+            let consumed = recv.read_with(|data| {
+                contiguous = data.len();
+                framing.receive(data, send)
+            });
+
+            if consumed > 0 {
+                continue;
+            }
+
+            if contiguous == recv.len() {
+                break;
+            }
+
+            recv.make_contiguous();
+        }
+    }
+}
+
+impl<H> LengthPrefixed<H>
+    where H: FnMut(&[u8], &mut SendRing)
+{
+    /// Create a framing passing complete frames to a handler.
+    pub fn new(handler: H) -> Self {
+        LengthPrefixed { handler }
+    }
+
+    /// Get a mutable reference to the handler.
+    pub fn handler_mut(&mut self) -> &mut H {
+        &mut self.handler
+    }
+}
+
+impl<H> Framing for LengthPrefixed<H>
+    where H: FnMut(&[u8], &mut SendRing)
+{
+    fn receive(&mut self, data: &[u8], send: &mut SendRing) -> usize {
+        let mut consumed = 0;
+        loop {
+            let rest = &data[consumed..];
+            if rest.len() < PREFIX_LEN {
+                break consumed;
+            }
+
+            let end = PREFIX_LEN + usize::from(NetworkEndian::read_u16(rest));
+            if rest.len() < end {
+                break consumed;
+            }
+
+            (self.handler)(&rest[PREFIX_LEN..end], send);
+            consumed += end;
+        }
+    }
+}
+
+impl<'a, F, P> Recv<P> for &'_ mut Framed<'a, F>
+where
+    F: Framing,
+    P: PayloadMut,
+{
+    fn receive(&mut self, packet: InPacket<P>) {
+        let key = match self.key {
+            Some(key) => key,
+            None => return,
+        };
+
+        // Not a packet for our connection. Ignore.
+        if packet.key() != Some(key) {
+            return;
+        }
+
+        match packet {
+            InPacket::Stray(_) | InPacket::Sending(_) => (),
+            InPacket::Closed(_) | InPacket::Closing(_) => self.key = None,
+            InPacket::Open(mut open) => {
+                open.read(&mut self.recv);
+                self.process();
+                self.framing.transmit(&mut self.send);
+                if let Ok(Err(_closing)) = open.write(&mut self.send) {
+                    self.key = None;
+                }
+            },
+        }
+    }
+}
+
+impl<'a, F, P> Send<P> for &'_ mut Framed<'a, F>
+where
+    F: Framing,
+    P: PayloadMut,
+{
+    fn send(&mut self, packet: RawPacket<P>) {
+        let key = match self.key {
+            Some(key) => key,
+            None => return,
+        };
+
+        let mut open = match packet.attach(key) {
+            Ok(open) => open,
+            // The connection no longer exists.
+            Err(_) => return self.key = None,
+        };
+
+        open.offer_window(&self.recv);
+        self.framing.transmit(&mut self.send);
+        if let Ok(Err(_closing)) = open.write(&mut self.send) {
+            self.key = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::tcp::{ReceivedSegment, RecvBuf};
+    use crate::time::Instant;
+    use crate::wire::TcpSeqNumber;
+
+    fn segment(begin: TcpSeqNumber, data_len: usize) -> ReceivedSegment {
+        ReceivedSegment {
+            syn: false,
+            fin: false,
+            data_len,
+            begin,
+            timestamp: Instant::from_millis(0),
+        }
+    }
+
+    #[test]
+    fn frames_across_wrap() {
+        let isn = TcpSeqNumber(0);
+        let mut frames = vec![];
+        let framing = LengthPrefixed::new(|frame: &[u8], send: &mut SendRing| {
+            frames.push(frame.to_vec());
+            write_length_prefixed(send, b"ok").unwrap();
+        });
+
+        let recv = RecvRing::new(vec![0; 8]);
+        let send = SendRing::new(vec![0; 16]);
+        let mut framed = Framed {
+            key: None,
+            recv,
+            send,
+            framing,
+        };
+
+        framed.recv.receive(b"\x00\x02ab\x00", segment(isn, 5));
+        framed.process();
+        assert_eq!(framed.recv().len(), 1);
+
+        // The second frame wraps around the end of the receive storage.
+        framed.recv.receive(b"\x04cdef", segment(isn + 5, 5));
+        framed.process();
+        assert!(framed.recv().is_empty());
+        assert_eq!(framed.send_mut().len(), 8);
+        assert_eq!(write_length_prefixed(framed.send_mut(), &[0; 7]), Err(Error::Exhausted));
+
+        drop(framed);
+        assert_eq!(frames, [b"ab".to_vec(), b"cdef".to_vec()]);
+    }
+}
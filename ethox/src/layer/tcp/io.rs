@@ -6,6 +6,7 @@ use core::borrow::{Borrow, BorrowMut};
 use core::convert::TryFrom;
 
 use crate::alloc::vec::Vec;
+use crate::time::Instant;
 use crate::wire::TcpSeqNumber;
 use crate::storage::assembler::{Assembler, Contig};
 
@@ -58,6 +59,27 @@ pub struct RecvInto<B> {
     asm: Assembler<[Contig; 4]>,
 }
 
+/// A reassembly window for out-of-order data in front of another receiver.
+///
+/// Segments arriving in order are passed through to the inner receiver unchanged. Segments after a
+/// hole in the sequence space are instead copied into the user provided storage and delivered to
+/// the inner receiver as one contiguous run once the hole has been filled. This makes it possible
+/// for receivers that only handle in-order data, such as a `Sink`, to avoid the retransmission of
+/// everything following a lost segment.
+///
+/// The storage bounds how far ahead data is kept, anything beyond it is dropped and must be
+/// retransmitted. A FIN on an out-of-order segment is not kept either.
+pub struct Reorder<B, R> {
+    /// Buffer of out-of-order bytes.
+    buffer: B,
+    /// The receiver of all in-order data.
+    inner: R,
+    /// The sequence number corresponding to the start of the buffer.
+    next: Option<TcpSeqNumber>,
+    /// The ranges of out-of-order data in the buffer.
+    asm: Assembler<[Contig; 4]>,
+}
+
 impl<Buffer: Borrow<[u8]>> SendFrom<Buffer> {
     /// Create a buffered sender.
     pub fn new(data: Buffer) -> Self {
@@ -325,11 +347,102 @@ impl RecvInto<Vec<u8>> {
     }
 }
 
+impl<Buffer: BorrowMut<[u8]>, R: RecvBuf> Reorder<Buffer, R> {
+    /// Create a reassembly window with some storage in front of a receiver.
+    pub fn new(buffer: Buffer, inner: R) -> Self {
+        Reorder {
+            buffer,
+            inner,
+            next: None,
+            asm: Assembler::new([Contig::default(); 4]),
+        }
+    }
+
+    /// Get a reference to the inner receiver.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner receiver.
+    ///
+    /// The inner receiver must not acknowledge data on its own, otherwise the buffered data may no
+    /// longer align with its stream.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwrap the inner receiver, dropping all buffered data.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Check if there is out-of-order data waiting for a hole to be filled.
+    pub fn is_reordering(&self) -> bool {
+        !self.asm.is_empty()
+    }
+
+    /// Buffer some data at an offset from the next expected byte.
+    fn store(&mut self, offset: usize, data: &[u8]) {
+        let buffer = self.buffer.borrow_mut();
+        if offset >= buffer.len() {
+            return;
+        }
+
+        let length = data.len().min(buffer.len() - offset);
+        let (start, size) = match (u32::try_from(offset), u32::try_from(length)) {
+            (Ok(start), Ok(size)) => (start, size),
+            _ => return,
+        };
+
+        // Never pop any data here, that is the job of `deliver`.
+        if self.asm.bounded_add(start, size, 0).is_ok() {
+            buffer[offset..offset + length].copy_from_slice(&data[..length]);
+        }
+    }
+
+    /// Deliver buffered data that has become contiguous after the inner receiver progressed.
+    fn deliver(&mut self, next: TcpSeqNumber, ack: TcpSeqNumber, timestamp: Instant) {
+        let progress = ack - next;
+        // Do not pop more data than the inner receiver can accept.
+        let limit = progress.saturating_add(self.inner.window());
+        let limit = u32::try_from(limit).unwrap_or(u32::MAX);
+        // UNWRAP: Differences of sequence numbers are bounded by `i32::MAX`.
+        let assembled = match self.asm.bounded_add(0, u32::try_from(progress).unwrap(), limit) {
+            Ok(assembled) => assembled as usize,
+            Err(()) => {
+                // Can not track the progress, simply forget all buffered data.
+                self.asm = Assembler::new([Contig::default(); 4]);
+                progress
+            },
+        };
+
+        let buffer = self.buffer.borrow_mut();
+        let end = assembled.min(buffer.len());
+        if end > progress {
+            let run = ReceivedSegment {
+                syn: false,
+                fin: false,
+                data_len: end - progress,
+                begin: ack,
+                timestamp,
+            };
+            self.inner.receive(&buffer[progress..end], run);
+        }
+
+        // Move the remaining out-of-order data to the front.
+        let remaining = self.asm.iter().last().map_or(0, |(_, end)| end as usize);
+        let remaining = remaining.min(buffer.len() - end);
+        buffer.copy_within(end..end + remaining, 0);
+        self.next = Some(next + assembled);
+    }
+}
+
 impl SendBuf for Empty {
     fn available(&self) -> AvailableBytes {
         AvailableBytes {
             total: 0,
             fin: true,
+            push: false,
         }
     }
 
@@ -365,6 +478,7 @@ impl<B: Borrow<[u8]>> SendBuf for SendFrom<B> {
         AvailableBytes {
             total: self.data.borrow().len() - self.consumed,
             fin: self.fin,
+            push: false,
         }
     }
 
@@ -430,3 +544,103 @@ impl<B: BorrowMut<[u8]>> RecvBuf for RecvInto<B> {
         self.buffer.borrow()[self.mark..].len()
     }
 }
+
+impl<B: BorrowMut<[u8]>, R: RecvBuf> RecvBuf for Reorder<B, R> {
+    fn receive(&mut self, data: &[u8], segment: ReceivedSegment) {
+        let next = match self.next {
+            Some(next) => next,
+            // The first segment determines the start of the stream.
+            None => {
+                self.inner.receive(data, segment);
+                self.next = Some(self.inner.ack());
+                return;
+            },
+        };
+
+        let begin = segment.data_begin();
+        if begin > next {
+            return self.store(begin - next, data);
+        }
+
+        self.inner.receive(data, segment);
+        let ack = self.inner.ack();
+        if ack > next {
+            self.deliver(next, ack, segment.timestamp);
+        }
+    }
+
+    fn ack(&mut self) -> TcpSeqNumber {
+        self.inner.ack()
+    }
+
+    fn window(&self) -> usize {
+        self.inner.window()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A receiver only accepting data in order.
+    #[derive(Default)]
+    struct Stream {
+        data: Vec<u8>,
+        next: Option<TcpSeqNumber>,
+    }
+
+    impl RecvBuf for Stream {
+        fn receive(&mut self, data: &[u8], segment: ReceivedSegment) {
+            let next = self.next.get_or_insert(segment.data_begin());
+            if segment.data_begin() <= *next && *next < segment.data_end() {
+                self.data.extend_from_slice(&data[*next - segment.data_begin()..]);
+                *next = segment.data_end();
+            }
+        }
+
+        fn ack(&mut self) -> TcpSeqNumber {
+            self.next.unwrap()
+        }
+
+        fn window(&self) -> usize {
+            1 << 16
+        }
+    }
+
+    fn segment(begin: TcpSeqNumber, data_len: usize) -> ReceivedSegment {
+        ReceivedSegment {
+            syn: false,
+            fin: false,
+            data_len,
+            begin,
+            timestamp: Instant::from_millis(0),
+        }
+    }
+
+    #[test]
+    fn reorder() {
+        let isn = TcpSeqNumber(-2);
+        let data: Vec<u8> = (0..16).collect();
+        let mut reorder = Reorder::new(vec![0; 8], Stream::default());
+
+        reorder.receive(&data[..4], segment(isn, 4));
+        assert_eq!(reorder.ack(), isn + 4);
+
+        // A hole, the data is buffered but not acknowledged.
+        reorder.receive(&data[8..12], segment(isn + 8, 4));
+        assert!(reorder.is_reordering());
+        assert_eq!(reorder.ack(), isn + 4);
+        // Beyond the storage, dropped.
+        reorder.receive(&data[12..16], segment(isn + 12, 4));
+
+        // Filling the hole delivers the buffered run.
+        reorder.receive(&data[4..8], segment(isn + 4, 4));
+        assert!(!reorder.is_reordering());
+        assert_eq!(reorder.ack(), isn + 12);
+        assert_eq!(reorder.get_ref().data, &data[..12]);
+
+        reorder.receive(&data[12..16], segment(isn + 12, 4));
+        assert_eq!(reorder.ack(), isn + 16);
+        assert_eq!(reorder.into_inner().data, data);
+    }
+}
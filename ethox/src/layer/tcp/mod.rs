@@ -19,7 +19,8 @@
 //! * A timer timeout is used to retransmit queue packets. We have no such timer but instead can
 //!   utilize being called in `send` to check for that timeout. An alternate check can be deferred
 //!   to user code at any point where a send buffer (i.e. a `Raw` packet) is available. The raw
-//!   buffer is returned if it was not consumed.
+//!   buffer is returned if it was not consumed. During idle periods, [`Endpoint::tick`] reports
+//!   when the next timer fires so that a sender can be run at that time.
 //! * Data can sometimes be piggy-backed on some packets that contain status communication. Note
 //!   however that for some networks there is a cost associated with large packet, mostly as higher
 //!   drop rates. This may be the case due to badly configured buffers but also congestion avoidance
@@ -34,6 +35,7 @@
 //! descriptors) and a slotmap of these indices to connections.
 //!
 //! [`Endpoint`]: struct.Endpoint.html
+//! [`Endpoint::tick`]: struct.Endpoint.html#method.tick
 //! [`SlotKey`]: struct.SlotKey.html
 //!
 //! Unlike standard stacks where state and user must be assumed to be in different protection
@@ -65,6 +67,87 @@
 //!
 //! [`Open`]: struct.Open.html
 //!
+//! ## Sending and receiving data
+//!
+//! The data of a connection is exchanged with user provided buffers implementing [`SendBuf`] and
+//! [`RecvBuf`], which the connection logic fills and drains while handling its packets. See the
+//! [`stream`] module for ring buffers and a handler moving data automatically. Protocols on top of
+//! the stream, such as TLS, can be plugged in with the [`framing`] module. The [`samples`] module
+//! has the echo, discard and chargen services for testing against other implementations.
+//!
+//! Space freed by reading from the receive buffer is offered to the remote with
+//! [`Open::offer_window`] before writing, which sends a window update when the window reopens.
+//!
+//! With fixed memory for many connections, the endpoint can limit the data buffered by all of
+//! them together with [`Endpoint::set_buffer_limits`]. Each connection then offers a window and
+//! sends unacknowledged data only within its fair share of the limits.
+//!
+//! A send buffer requests the PSH flag through [`AvailableBytes::push`], and the handler sees it
+//! on received data as [`UserSignals::push`]. Urgent data is not removed from the stream, only its
+//! end is reported by [`Open::urgent_end`].
+//!
+//! A proxy forwards the data received on one connection with [`Open::splice`] to another. The
+//! data stays in the buffer of the received packet, only its headers are rewritten, and the send
+//! buffer of the other connection keeps a copy for retransmissions in a [`SpliceBuf`].
+//!
+//! [`AvailableBytes::push`]: struct.AvailableBytes.html#structfield.push
+//! [`UserSignals::push`]: struct.UserSignals.html#structfield.push
+//! [`Open::urgent_end`]: struct.Open.html#method.urgent_end
+//! [`Open::offer_window`]: struct.Open.html#method.offer_window
+//! [`Endpoint::set_buffer_limits`]: struct.Endpoint.html#method.set_buffer_limits
+//! [`Open::splice`]: struct.Open.html#method.splice
+//! [`SpliceBuf`]: stream/trait.SpliceBuf.html
+//! [`SendBuf`]: stream/trait.SendBuf.html
+//! [`RecvBuf`]: stream/trait.RecvBuf.html
+//! [`stream`]: stream/index.html
+//! [`framing`]: framing/index.html
+//! [`samples`]: samples/index.html
+//!
+//! ## Connection events
+//!
+//! Instead of inspecting the state of each connection after every batch, an application can
+//! register a [`ConnectionEvents`] observer on the endpoint. It is told when a handshake completes,
+//! when sent data is acknowledged, when the remote closes or resets the connection, and when a
+//! timer removes it.
+//!
+//! [`ConnectionEvents`]: trait.ConnectionEvents.html
+//!
+//! ## Connection options
+//!
+//! Timeouts, keepalive probes, Nagle's algorithm and the advertised window are configured with
+//! [`Options`]. The endpoint applies its options to each connection it creates, a listening slot
+//! or an active open can also be given their own. The options of an existing connection are
+//! changed through its [`Slot`].
+//!
+//! With a user timeout or a maximum number of retransmissions, a connection does not wait
+//! forever for a dead remote. It is reset and removed instead, which the handler sees as
+//! [`UserSignals::timed_out`] on the closing packet.
+//!
+//! A connection configured with [`Pacing`] spaces its new segments over the round trip or at a
+//! fixed rate, instead of sending its window in a burst. The endpoint's `tick` reports when the
+//! next held back segment may be sent.
+//!
+//! [`Options`]: struct.Options.html
+//! [`Pacing`]: enum.Pacing.html
+//! [`UserSignals::timed_out`]: struct.UserSignals.html#structfield.timed_out
+//! [`Slot`]: struct.Slot.html
+//!
+//! ## Closing connections
+//!
+//! A connection is closed gracefully with [`Open::shutdown`]. This queues a FIN after all data
+//! the send buffer has available, alternatively the send buffer can indicate its own end. The
+//! connection still receives data until the remote closes its direction as well, which the
+//! handler observes as [`UserSignals::half_closed`]. [`Endpoint::remove`] drops the connection
+//! state immediately instead.
+//!
+//! To shut down, [`Endpoint::close_all`] closes every connection at once and resets those that
+//! have not finished closing by a deadline.
+//!
+//! [`Open::shutdown`]: struct.Open.html#method.shutdown
+//! [`UserSignals::half_closed`]: struct.UserSignals.html#structfield.half_closed
+//! [`Endpoint::remove`]: struct.Endpoint.html#method.remove
+//! [`Endpoint::close_all`]: struct.Endpoint.html#method.close_all
+//!
 //! ## Deviations
 //!
 //! As a guide to the statemachine I had originally planned to use a paper proposing a formally
@@ -100,34 +183,59 @@ use crate::wire::PayloadMut;
 
 mod connection;
 mod endpoint;
+mod events;
+pub mod framing;
 pub mod io;
+mod options;
+mod pacing;
 mod packet;
+pub mod samples;
 mod socket;
+pub mod stream;
 
 mod siphash;
+#[cfg(test)]
+mod tests;
 
 pub use connection::{
     AvailableBytes,
-    ReceivedSegment};
+    Info,
+    ReceivedSegment,
+    RoundTrip,
+    State,
+    Statistics};
 
 pub use endpoint::{
+    BufferSpace,
+    Drain,
     FourTuple,
     Slot,
     SlotKey,
     Endpoint};
 
+pub use events::ConnectionEvents;
+
+pub use options::Options;
+
+pub use pacing::Pacing;
+
 pub use packet::{
     In as InPacket,
     Open,
     Raw as RawPacket,
-    RecvBuf,
-    SendBuf,
     Sending,
     Stray,
     UserSignals};
 
 pub use socket::{
-    Client};
+    connect_dual,
+    Client,
+    DualClient};
+
+pub use stream::{
+    RecvBuf,
+    SendBuf,
+    SpliceBuf};
 
 // publically exposed for initialization.
 pub use siphash::IsnGenerator;
@@ -0,0 +1,127 @@
+//! Per-connection configuration.
+use core::convert::TryFrom;
+
+use crate::time::Duration;
+
+use super::pacing::Pacing;
+
+/// The configurable parameters of a connection, similar to socket options.
+///
+/// The endpoint holds the options for new connections, see [`Endpoint::set_options`]. They can
+/// be chosen for a single connection when it is created with [`Endpoint::listen_with`] or
+/// [`Raw::open_with`], and changed afterwards through [`Slot::set_options`].
+///
+/// [`Endpoint::set_options`]: struct.Endpoint.html#method.set_options
+/// [`Endpoint::listen_with`]: struct.Endpoint.html#method.listen_with
+/// [`Raw::open_with`]: struct.RawPacket.html#method.open_with
+/// [`Slot::set_options`]: struct.Slot.html#method.set_options
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Options {
+    /// Hold back segments smaller than the maximum segment size while data is in flight.
+    ///
+    /// This is Nagle's algorithm, see RFC896. It trades latency of small writes for fewer
+    /// segments. A FIN is never held back.
+    pub nagle: bool,
+
+    /// Probe an idle connection after this duration without segments in either direction.
+    ///
+    /// The probe carries the already acknowledged sequence number before `SND.NXT` and thus
+    /// makes a live remote answer with an ACK, see RFC1122 section 4.2.3.6. Disabled by default.
+    pub keepalive: Option<Duration>,
+
+    /// The time data may stay unacknowledged before the connection gives up.
+    ///
+    /// The connection is then reset and removed, and the handler sees `timed_out` in the user
+    /// signals of the packet. The timeout is advertised to the remote with the User Timeout
+    /// Option of RFC5482. Without a configured timeout, the connection adopts the one advertised
+    /// by the remote, but never less than 100 seconds.
+    pub user_timeout: Option<Duration>,
+
+    /// The number of consecutive retransmissions before the connection gives up.
+    ///
+    /// Keepalive probes count as retransmissions, any acceptable segment from the remote resets
+    /// the count. The connection is aborted like at the end of the user timeout.
+    pub max_retransmissions: Option<u32>,
+
+    /// The retransmission timeout before the first round trip time measurement.
+    ///
+    /// RFC6298 recommends 1 second, the default is the more conservative 3 seconds of RFC1122.
+    pub initial_rto: Duration,
+
+    /// The longest time an ACK for received data is delayed.
+    ///
+    /// For compliance with RFC1122 this MUST NOT be greater than 500ms.
+    pub ack_delay: Duration,
+
+    /// The idle time after which the congestion window restarts, see RFC5681 section 4.1.
+    pub idle_restart: Duration,
+
+    /// The largest receive window advertised to the remote, in bytes.
+    ///
+    /// The window is also never larger than the space the receive buffer offers.
+    pub receive_window: u32,
+
+    /// The initial slow start threshold, in bytes.
+    pub initial_ssthresh: u32,
+
+    /// Space new segments over time instead of sending them in bursts.
+    ///
+    /// Disabled by default, see the [`Pacing`] variants for the available rates.
+    ///
+    /// [`Pacing`]: enum.Pacing.html
+    pub pacing: Option<Pacing>,
+}
+
+impl Options {
+    /// The options of an endpoint that has not been configured otherwise.
+    pub const DEFAULT: Self = Options {
+        nagle: false,
+        keepalive: None,
+        user_timeout: None,
+        max_retransmissions: None,
+        initial_rto: Duration::from_millis(3000),
+        ack_delay: Duration::from_millis(500),
+        idle_restart: Duration::from_millis(30000),
+        receive_window: u32::MAX,
+        initial_ssthresh: u32::MAX,
+        pacing: None,
+    };
+}
+
+impl Options {
+    /// The shortest user timeout adopted from the remote, see RFC5482 section 3.1.
+    const USER_TIMEOUT_LIMIT: Duration = Duration::from_secs(100);
+
+    /// The encoded User Timeout Option advertising the user timeout, if any.
+    ///
+    /// Timeouts that do not fit 15 bits of seconds are rounded down to minutes.
+    pub(crate) fn user_timeout_option(&self) -> Option<u16> {
+        let secs = self.user_timeout?.as_secs();
+        Some(match u16::try_from(secs) {
+            Ok(secs) if secs <= 0x7fff => secs,
+            _ => 0x8000 | u16::try_from(secs / 60).unwrap_or(u16::MAX).min(0x7fff),
+        })
+    }
+
+    /// Adopt the user timeout advertised by the remote, unless one was configured.
+    pub(crate) fn remote_user_timeout(&mut self, option: Option<u16>) {
+        let option = match option {
+            Some(option) if self.user_timeout.is_none() => option,
+            _ => return,
+        };
+
+        let amount = u64::from(option & 0x7fff);
+        let remote = if option & 0x8000 != 0 {
+            Duration::from_secs(amount * 60)
+        } else {
+            Duration::from_secs(amount)
+        };
+        self.user_timeout = Some(remote.max(Self::USER_TIMEOUT_LIMIT));
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options::DEFAULT
+    }
+}
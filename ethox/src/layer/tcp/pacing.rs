@@ -0,0 +1,150 @@
+//! Spacing the segments of a connection over time.
+//!
+//! Without pacing, a sender transmits its whole window as soon as it opens, in a burst at the
+//! line rate of the nic. On a path with shallow buffers such a burst overflows a queue even when
+//! the average rate would fit. A paced sender instead spreads its segments over the round trip, by
+//! holding back each new segment until a release time derived from the size of its predecessor
+//! and the pacing rate.
+//!
+//! The release time is checked against the timestamp of each transmit opportunity, i.e. the
+//! time of the raw packet offered to the sender. When a segment is held back, the connection
+//! reports its release time as a timer so that the endpoint's `tick` wakes the sender in time.
+//! Retransmissions, acknowledgments and other control segments are never delayed.
+use core::convert::TryFrom;
+
+use crate::time::{Duration, Expiration, Instant};
+
+/// The rate at which a connection sends new data.
+///
+/// Configured with [`Options::pacing`].
+///
+/// [`Options::pacing`]: struct.Options.html#structfield.pacing
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Pacing {
+    /// Derive the rate from the window and the smoothed round trip time.
+    ///
+    /// The rate sends the window over a round trip, scaled up by a gain so that the window can
+    /// still grow: twice the rate in slow start and five quarters afterwards, like Linux does.
+    /// Segments are not paced before the first round trip time measurement.
+    Window,
+
+    /// A fixed rate, in bytes of payload per second.
+    ///
+    /// A rate of zero does not pace at all.
+    Rate(u64),
+}
+
+/// The release time of the next segment of a connection.
+#[derive(Clone, Copy, Debug, Default, Hash)]
+pub struct Pacer {
+    /// The time at which the next segment may be sent, in microseconds.
+    ///
+    /// This is finer than the resolution of `Instant` so that the gaps between segments sent
+    /// within the same millisecond add up correctly.
+    release: i64,
+
+    /// If a segment was held back and waits for the release time.
+    held: bool,
+}
+
+/// The window of the rate is sent over a round trip multiplied by this gain, in percent.
+const SLOW_START_GAIN: u64 = 200;
+const CONGESTION_AVOIDANCE_GAIN: u64 = 125;
+
+impl Pacing {
+    /// The time it takes to send some bytes at the pacing rate, in microseconds.
+    ///
+    /// Returns `None` when the rate is unknown or unlimited.
+    pub(crate) fn interval(
+        &self,
+        len: usize,
+        window: u32,
+        slow_start: bool,
+        smoothed_rtt: Option<Duration>,
+    ) -> Option<u64> {
+        let len = u64::try_from(len).unwrap_or(u64::MAX);
+        match *self {
+            Pacing::Rate(0) => None,
+            Pacing::Rate(rate) => Some(len.saturating_mul(1_000_000) / rate),
+            Pacing::Window => {
+                let rtt = u64::try_from(smoothed_rtt?.as_micros()).unwrap_or(u64::MAX);
+                let gain = if slow_start { SLOW_START_GAIN } else { CONGESTION_AVOIDANCE_GAIN };
+                let window = u64::from(window).saturating_mul(gain);
+                if window == 0 {
+                    return None;
+                }
+                Some(len.saturating_mul(rtt).saturating_mul(100) / window)
+            },
+        }
+    }
+}
+
+impl Pacer {
+    /// Check if a new segment may be sent at a transmit opportunity.
+    ///
+    /// Remembers to wake the sender otherwise.
+    pub(crate) fn may_send(&mut self, time: Instant) -> bool {
+        self.held = micros(time) < self.release;
+        !self.held
+    }
+
+    /// Account for a new segment that takes `interval` to send at the pacing rate.
+    pub(crate) fn sent(&mut self, time: Instant, interval: u64) {
+        // An idle sender does not accumulate credit for a burst. A transmit opportunity may come
+        // up to a millisecond, the resolution of the timer, after the release time.
+        let start = self.release.max(micros(time) - 1000);
+        self.release = start.saturating_add(i64::try_from(interval).unwrap_or(i64::MAX));
+        self.held = false;
+    }
+
+    /// The time at which a held back segment is released.
+    pub(crate) fn timer(&self) -> Expiration {
+        if !self.held {
+            return Expiration::Never;
+        }
+
+        // Round up to the next full millisecond, sending is not allowed before.
+        let millis = self.release / 1000 + i64::from(self.release % 1000 != 0);
+        Expiration::When(Instant::from_millis(millis))
+    }
+}
+
+fn micros(time: Instant) -> i64 {
+    time.total_millis().saturating_mul(1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_rate() {
+        let pacing = Pacing::Rate(1_000_000);
+        let interval = pacing.interval(1000, 0, false, None).unwrap();
+        assert_eq!(interval, 1000);
+
+        let mut pacer = Pacer::default();
+        let start = Instant::from_millis(10);
+        assert!(pacer.may_send(start));
+        pacer.sent(start, interval);
+        pacer.sent(start, interval);
+        pacer.sent(start, interval);
+        assert!(!pacer.may_send(start));
+        assert!(!pacer.may_send(Instant::from_millis(11)));
+        assert_eq!(pacer.timer(), Expiration::When(Instant::from_millis(12)));
+        assert!(pacer.may_send(Instant::from_millis(12)));
+        assert_eq!(pacer.timer(), Expiration::Never);
+
+        assert_eq!(Pacing::Rate(0).interval(1000, 0, false, None), None);
+    }
+
+    #[test]
+    fn window_rate() {
+        let rtt = Some(Duration::from_millis(10));
+        // No measurement, no pacing.
+        assert_eq!(Pacing::Window.interval(1000, 10_000, true, None), None);
+        // Twice the window over a round trip.
+        assert_eq!(Pacing::Window.interval(1000, 10_000, true, rtt), Some(500));
+        assert_eq!(Pacing::Window.interval(1000, 10_000, false, rtt), Some(800));
+    }
+}
@@ -3,12 +3,17 @@
 //! The interface differs from other layers in that the `In` packet has many different variants it
 //! represents, depending on the state of the underlying connection.
 use crate::layer::ip;
-use crate::wire::{Payload, PayloadMut};
+use crate::wire::{Payload, PayloadMut, PayloadVectored};
 use crate::wire::{IpAddress, Ipv4Subnet, Ipv6Subnet, IpSubnet, IpProtocol};
 use crate::wire::{TcpPacket, TcpRepr, TcpSeqNumber};
+use crate::wire::ip::checksum;
 
-use super::connection::{AvailableBytes, Endpoint, InPacket, Operator, OutSignals, ReceivedSegment, Segment, Signals};
+use super::connection::{
+    Endpoint, Info, InPacket, Operator, OutSignals, ReceivedSegment, Segment, Signals, State};
 use super::endpoint::{FourTuple, SlotKey};
+use super::events::Event;
+use super::options::Options;
+use super::stream::{RecvBuf, SendBuf, SpliceBuf};
 
 /// An incoming tcp packet.
 ///
@@ -39,46 +44,6 @@ pub enum In<'a, P: PayloadMut> {
     Stray(Stray<'a, P>),
 }
 
-/// A user defined (re-)transmission buffer.
-/// TODO: a better guide on how to customize
-pub trait SendBuf {
-    /// Check the available data.
-    ///
-    /// This should be the total of sent-but-unacknowledged bytes and unsent bytes.
-    fn available(&self) -> AvailableBytes;
-
-    /// Fill in some (re-)transmitted data.
-    ///
-    /// The tcp connection layer will take care to never call this with a buffer outside the
-    /// indicated available data length. Bytes that have already been sent are not supposed to
-    /// change afterwards, i.e. the `SendBuf` is also utilized as the retransmit buffer.
-    fn fill(&mut self, buf: &mut [u8], begin: TcpSeqNumber);
-
-    /// Notify the buffer that some data can be safely discarded.
-    ///
-    /// The tcp layer will ensure that no data before the new `begin` is requested again.
-    fn ack(&mut self, begin: TcpSeqNumber);
-}
-
-/// A user defined segment reassembly buffer.
-/// TODO: a better guide on how to customize
-pub trait RecvBuf {
-    /// Accept some incoming data.
-    ///
-    /// Report back the new unreceived byte. This allows the receive buffer to choose the strategy
-    /// for partially acknowledged data.
-    fn receive(&mut self, buf: &[u8], segment: ReceivedSegment);
-
-    /// Get the highest completed sequence number.
-    fn ack(&mut self) -> TcpSeqNumber;
-
-    /// Get the current window size.
-    ///
-    /// Shrinking the window size without having accepted new data is allowed but strongly
-    /// discouraged.
-    fn window(&self) -> usize;
-}
-
 /// Informational signals to the user.
 ///
 /// These are intended to be asynchronous 'signals' to the user space process in the original tcp
@@ -91,18 +56,41 @@ pub struct UserSignals {
 
     /// The tcp data stream was closed by the remote end.
     ///
-    /// The actual connection may still be half-open until our side closes the connection as well.
+    /// Set only on the packet that delivered the FIN to the reader. The actual connection may
+    /// still be half-open until our side closes the connection as well, see [`Open::shutdown`].
     ///
-    /// WIP: this is not implemented yet and always `false`.
+    /// [`Open::shutdown`]: struct.Open.html#method.shutdown
     pub half_closed: bool,
 
     /// There is new data to be read.
     pub data: bool,
 
+    /// The remote pushed the new data.
+    ///
+    /// Set along with `data` when the segment had the PSH flag. The sender asks for the buffered
+    /// data to be handed to the application rather than waiting for more to arrive.
+    pub push: bool,
+
+    /// The segment marks urgent data in the stream.
+    ///
+    /// The urgent data is not removed from the stream but delivered inline, as recommended by
+    /// RFC6093. Its end is available from [`Open::urgent_end`].
+    ///
+    /// [`Open::urgent_end`]: struct.Open.html#method.urgent_end
+    pub urgent: bool,
+
     /// A listening socket returned to its listen state.
     ///
     /// WIP: this is not implemented yet and always `false`.
     pub relisten: bool,
+
+    /// The connection was reset because the remote did not respond in time.
+    ///
+    /// Either its user timeout passed or it exhausted its retransmissions, see [`Options`]. The
+    /// connection has been removed.
+    ///
+    /// [`Options`]: struct.Options.html
+    pub timed_out: bool,
 }
 
 /// Packet representation *after* it has been applied to its connection.
@@ -163,6 +151,8 @@ pub struct Open<'a, P: PayloadMut> {
     operator: Operator<'a>,
     signals: UserSignals,
     packet: OpenPacket<'a, P>,
+    /// Defer a pure ACK to the next send phase, see `Endpoint::set_ack_coalescing`.
+    coalesce_ack: bool,
 }
 
 /// A valid tcp packet not belonging to a connection.
@@ -243,15 +233,22 @@ impl<'a, P: PayloadMut> In<'a, P> {
         };
 
         let from = tcp.inner().repr().src_addr();
+        let to = tcp.inner().repr().dst_addr();
         let time = ip_control.info().timestamp();
         let in_packet = InPacket {
             segment: tcp.repr(),
             from,
+            to,
             time,
+            segment_size: segment_size(&ip_control, from),
         };
 
+        let remote_closed = operator.connection().is_remote_closed();
         let mut signals = operator.arrives(&in_packet);
-        let user = UserSignals::new(&signals);
+        let mut user = UserSignals::new(&signals);
+        user.half_closed = !remote_closed && operator.connection().is_remote_closed();
+        user.push = user.data && in_packet.segment.flags.psh();
+        user.urgent = user.data && in_packet.segment.flags.urg();
 
         // Deleting the connection nothing to be sent.
         if signals.delete && signals.answer.is_none() {
@@ -278,12 +275,14 @@ impl<'a, P: PayloadMut> In<'a, P> {
                         Some(segment) => OpenPacket::In { tcp, segment },
                         None => OpenPacket::Control { tcp },
                     },
+                    coalesce_ack: false,
                 }));
             },
         };
 
         // Prepare the answer packet itself.
-        control_answer(tcp, answer, ip_control)?;
+        let dscp = operator.connection().dscp;
+        control_answer(tcp, answer, ip_control, dscp)?;
 
         // We need to close the connection. The sent packet should be an RST.
         if signals.delete {
@@ -335,12 +334,113 @@ impl<'a, P: PayloadMut> Open<'a, P> {
     /// Receive data contained in the TCP segment.
     pub fn read(&mut self, with: &mut impl RecvBuf) {
         let connection = self.operator.connection_mut();
-        connection.recv.update_window(with.window());
+        let mut remote_fin = false;
+
+        match &self.packet {
+            OpenPacket::In { tcp, segment } => {
+                let remote_closed = connection.is_remote_closed();
+                with.receive(tcp.payload_slice(), *segment);
+                let progress = segment.acked_until(with.ack());
+                connection.set_recv_ack(progress);
+                remote_fin = !remote_closed && connection.is_remote_closed();
+                self.signals.half_closed |= remote_fin;
+            },
+            // A FIN without data was already accepted by the connection, the buffer only learns
+            // that the stream has ended.
+            OpenPacket::Control { tcp } if self.signals.half_closed => {
+                let repr = tcp.repr();
+                with.receive(&[], ReceivedSegment {
+                    syn: false,
+                    fin: true,
+                    data_len: 0,
+                    begin: repr.seq_number,
+                    timestamp: self.ip.info().timestamp(),
+                });
+            },
+            _ => (),
+        }
+
+        // Only the space remaining after the segment is offered to the remote.
+        self.operator.offer_window(with.window());
+
+        if remote_fin {
+            self.operator.notify(Event::RemoteFin);
+        }
+    }
+
+    /// The sequence number following the urgent data marked by the segment.
+    ///
+    /// Only segments with data that are accepted by the connection report the mark. The urgent
+    /// pointer may point beyond the data of the segment itself, the urgent data then arrives with
+    /// later segments. Its bytes are part of the stream regardless and read like any other.
+    pub fn urgent_end(&self) -> Option<TcpSeqNumber> {
+        match &self.packet {
+            OpenPacket::In { tcp, .. } if tcp.flags().urg() => {
+                Some(tcp.seq_number() + usize::from(tcp.urgent_at()))
+            },
+            _ => None,
+        }
+    }
+
+    /// Offer the space of the receive buffer to the remote as the window.
+    ///
+    /// Reading from the buffer frees space that the remote does not know about, a sender that has
+    /// filled the window waits for it. After offering it, the next write sends a window update if
+    /// the window reopened by at least a full segment or half the buffer.
+    pub fn offer_window(&mut self, with: &impl RecvBuf) {
+        let current = self.operator.connection().current;
+        // Before the handshake completes there is no receive sequence to offer a window for.
+        if matches!(current, State::Established | State::FinWait1 | State::FinWait2) {
+            self.operator.offer_window(with.window());
+        }
+    }
+
+    /// Check if the remote has closed its sending direction.
+    ///
+    /// No more data will be received on the connection after this. It is fully closed once our
+    /// own sending direction has been shut down as well.
+    pub fn is_remote_closed(&self) -> bool {
+        self.operator.connection().is_remote_closed()
+    }
+
+    /// Close the sending direction of the connection.
+    ///
+    /// A FIN is queued after all data the send buffer currently has available, the buffer does
+    /// not need to indicate its end itself. The connection keeps receiving until the remote
+    /// closes as well, signalled by [`UserSignals::half_closed`]. Afterwards, it is removed once
+    /// all segments have been acknowledged, which is reported as a `Closed` or `Closing` packet.
+    ///
+    /// [`UserSignals::half_closed`]: struct.UserSignals.html#structfield.half_closed
+    pub fn shutdown(&mut self) {
+        self.operator.connection_mut().shutdown()
+    }
+
+    /// Get the user signals of the packet.
+    pub fn user_signals(&self) -> UserSignals {
+        self.signals
+    }
+
+    pub(crate) fn coalesce_ack(&mut self, coalesce: bool) {
+        self.coalesce_ack = coalesce;
+    }
 
-        if let OpenPacket::In { tcp, segment } = &self.packet {
-            with.receive(tcp.payload_slice(), *segment);
-            let progress = segment.acked_until(with.ack());
-            connection.set_recv_ack(progress);
+    /// Take a snapshot of the connection internals.
+    pub fn info(&self) -> Info {
+        self.operator.connection().info()
+    }
+
+    /// Abandon the connection without sending any further segments.
+    ///
+    /// The connection state is deleted immediately and the remote is not notified. This is
+    /// intended for connections that are not yet (or not anymore) interesting, such as redundant
+    /// connection attempts.
+    pub fn abort(self) -> Closing<'a> {
+        let previous = self.operator.key();
+        let endpoint = self.operator.delete();
+        Closing {
+            endpoint,
+            previous,
+            signals: self.signals,
         }
     }
 
@@ -351,7 +451,151 @@ impl<'a, P: PayloadMut> Open<'a, P> {
     ///
     /// Any data that is currently held as an incoming packet will be lost, even if this method fails.
     pub fn write(self, with: &mut impl SendBuf) -> Result<Result<Sending<'a>, Closing<'a>>, crate::layer::Error> {
-        let Open { ip, mut operator, signals: mut user, packet, } = self;
+        self.write_with(with, fill)
+    }
+
+    /// Try to send parts of the available data from a separate segment of the buffer.
+    ///
+    /// This is the same as `write` except that the data is placed in a segment of its own instead
+    /// of behind the headers, for devices with scatter-gather support. See [`PayloadVectored`].
+    ///
+    /// [`PayloadVectored`]: ../../wire/trait.PayloadVectored.html
+    pub fn write_vectored(self, with: &mut impl SendBuf)
+        -> Result<Result<Sending<'a>, Closing<'a>>, crate::layer::Error>
+        where P: PayloadVectored,
+    {
+        self.write_with(with, |raw_ip, operator, repr, with, begin| {
+            let ip::RawPacket { handle, payload } = raw_ip;
+            payload.clear_segments();
+            if repr.payload_len > 0 {
+                payload.push_segment(repr.payload_len.into())?;
+                if let Some(segment) = payload.segment_mut(0) {
+                    with.fill(segment.as_mut_slice(), begin);
+                }
+            }
+            let payload_sum = checksum::segments(&*payload);
+
+            let header = TcpRepr { payload_len: 0, ..repr };
+            let raw_ip = ip::RawPacket { handle, payload };
+            let mut out_ip = prepare(raw_ip, operator, header)?;
+
+            let ip_repr = out_ip.repr();
+            TcpPacket::new_unchecked(out_ip.payload_mut_slice(), header).fill_checksum_detached(
+                ip_repr.src_addr(), ip_repr.dst_addr(), repr.payload_len.into(), payload_sum);
+
+            out_ip.send_vectored()
+        })
+    }
+
+    /// Check if the data of the segment can be spliced into another connection.
+    ///
+    /// Only in-order segments with data and neither SYN nor FIN qualify, which `recv` has to
+    /// confirm with [`RecvBuf::can_skip`]. The target must be another established connection,
+    /// with enough space in `send` for all of the data.
+    ///
+    /// [`RecvBuf::can_skip`]: stream/trait.RecvBuf.html#method.can_skip
+    pub fn can_splice(&self, to: SlotKey, recv: &impl RecvBuf, send: &impl SpliceBuf) -> bool {
+        let (tcp, segment) = match &self.packet {
+            OpenPacket::In { tcp, segment } => (tcp, segment),
+            _ => return false,
+        };
+
+        let established = self.operator.endpoint.get(to).is_some_and(|slot| {
+            matches!(slot.connection().current, State::Established | State::CloseWait)
+        });
+
+        established
+            && to != self.key()
+            && recv.can_skip(segment)
+            && !segment.syn
+            && !segment.fin
+            && segment.data_len > 0
+            && segment.data_len == tcp.payload_slice().len()
+            && segment.data_len <= send.spare()
+            && ends_frame(tcp)
+    }
+
+    /// Forward the data of the segment to another connection in the same buffer.
+    ///
+    /// Intended for proxies, the data is not read into `recv` but appended to `send`, the send
+    /// buffer of the target connection. When the target connection sends exactly that data next,
+    /// only the headers of the received packet are rewritten and the data stays in place.
+    /// Otherwise, the target connection sends as if `write` had been called with `send`. The
+    /// space left in `send` is offered as the receive window, propagating the back pressure of
+    /// the target connection to its source.
+    ///
+    /// Check [`can_splice`] first and read the data as usual if it is not possible. Otherwise,
+    /// this fails with `Illegal` and the segment is dropped without being acknowledged.
+    ///
+    /// [`can_splice`]: #method.can_splice
+    pub fn splice(self, to: SlotKey, recv: &mut impl RecvBuf, send: &mut impl SpliceBuf)
+        -> Result<Result<Sending<'a>, Closing<'a>>, crate::layer::Error>
+    {
+        if !self.can_splice(to, recv, send) {
+            return Err(crate::layer::Error::Illegal);
+        }
+
+        let Open { ip, mut operator, packet, .. } = self;
+        let (tcp, segment) = match packet {
+            OpenPacket::In { tcp, segment } => (tcp, segment),
+            _ => unreachable!("Only received data is spliced"),
+        };
+
+        recv.skip(segment);
+        send.append(tcp.payload_slice());
+        let connection = operator.connection_mut();
+        connection.set_recv_ack(segment);
+        operator.offer_window(recv.window().min(send.spare()));
+
+        // UNWRAP: the target connection was checked to exist.
+        let mut operator = Operator::new(operator.endpoint, to).unwrap();
+        let tcp_seq = operator.connection().get_send_ack();
+        send.ack(tcp_seq);
+        let available = send.available();
+        let time = ip.info().timestamp();
+
+        let signals = operator.next_send_segment(available, time);
+        let mut user = UserSignals::default();
+        user.update(&signals);
+
+        if let Some(Segment { repr, range }) = signals.segment {
+            let spliced = available.total - segment.data_len..available.total;
+            if range == spliced {
+                reframe(tcp, ip, &mut operator, repr)?;
+            } else {
+                let raw_ip = ip::RawPacket {
+                    handle: ip,
+                    payload: tcp.into_inner().into_inner().into_inner(),
+                };
+
+                fill(raw_ip, &mut operator, repr, send, tcp_seq + range.start)?;
+            }
+        }
+
+        Ok(if signals.delete {
+            let previous = operator.key();
+            let endpoint = operator.delete();
+            Err(Closing {
+                endpoint,
+                previous,
+                signals: user,
+            })
+        } else {
+            Ok(Sending {
+                operator,
+                signals: user,
+            })
+        })
+    }
+
+    /// Select the next segment and let `send` fill and send it.
+    fn write_with<W: SendBuf>(
+        self,
+        with: &mut W,
+        send: impl FnOnce(ip::RawPacket<'a, P>, &mut Operator<'a>, TcpRepr, &mut W, TcpSeqNumber)
+            -> Result<(), crate::layer::Error>,
+    ) -> Result<Result<Sending<'a>, Closing<'a>>, crate::layer::Error> {
+        let Open { ip, mut operator, signals: mut user, packet, coalesce_ack } = self;
         let payload: &'a mut P = match packet {
             OpenPacket::In { tcp, .. } | OpenPacket::Control { tcp }
                 => tcp.into_inner().into_inner().into_inner(),
@@ -359,27 +603,31 @@ impl<'a, P: PayloadMut> Open<'a, P> {
         };
 
         let tcp_seq = operator.connection().get_send_ack();
-        with.ack(tcp_seq);
+        // A listening connection has not chosen its initial sequence number yet.
+        if operator.connection().current != State::Listen {
+            with.ack(tcp_seq);
+        }
         let available = with.available();
         let time = ip.info().timestamp();
 
-        let signals = operator.next_send_segment(available, time);
+        let signals = if coalesce_ack {
+            operator.next_data_segment(available, time)
+        } else {
+            operator.next_send_segment(available, time)
+        };
         user.update(&signals);
 
-        if let Some(Segment { repr, range }) = signals.segment {
+        if let Some(Segment { mut repr, range }) = signals.segment {
+            if repr.flags.syn() {
+                repr.max_seg_size = segment_size(&ip, operator.four_tuple().remote);
+            }
+
             let raw_ip = ip::RawPacket {
                 handle: ip,
                 payload,
             };
 
-            let mut out_ip = prepare(raw_ip, &mut operator, repr)?;
-
-            let ip_repr = out_ip.repr();
-            let mut tcp = TcpPacket::new_unchecked(out_ip.payload_mut_slice(), repr);
-            with.fill(tcp.payload_mut_slice(), tcp_seq + range.start);
-            tcp.fill_checksum(ip_repr.src_addr(), ip_repr.dst_addr());
-
-            out_ip.send()?;
+            send(raw_ip, &mut operator, repr, with, tcp_seq + range.start)?;
         }
 
         Ok(if signals.delete {
@@ -402,8 +650,16 @@ impl<'a, P: PayloadMut> Open<'a, P> {
 impl<'a, P: PayloadMut> Raw<'a, P> {
     /// Create a new connection.
     pub fn open(self, addr: IpAddress, port: u16) -> Result<Open<'a, P>, crate::layer::Error> {
+        let options = self.endpoint.options();
+        self.open_with(addr, port, options)
+    }
+
+    /// Create a new connection with options other than those of the endpoint.
+    pub fn open_with(self, addr: IpAddress, port: u16, options: Options)
+        -> Result<Open<'a, P>, crate::layer::Error>
+    {
         let local = self.source(addr)?;
-        let local_port = self.endpoint.source_port(local)
+        let local_port = self.endpoint.source_port(local, addr, port)
             .ok_or(crate::layer::Error::Exhausted)?;
 
         let new = FourTuple {
@@ -419,6 +675,7 @@ impl<'a, P: PayloadMut> Raw<'a, P> {
         };
 
         let time = self.ip.handle.info().timestamp();
+        operator.connection_mut().set_options(options);
         assert!(operator.open(time).is_ok());
 
         let ip::RawPacket {
@@ -431,6 +688,7 @@ impl<'a, P: PayloadMut> Raw<'a, P> {
             operator,
             signals: UserSignals::default(),
             packet: OpenPacket::Out { raw },
+            coalesce_ack: false,
         })
     }
 
@@ -454,9 +712,27 @@ impl<'a, P: PayloadMut> Raw<'a, P> {
             operator,
             signals: UserSignals::default(),
             packet: OpenPacket::Out { raw },
+            coalesce_ack: false,
         })
     }
 
+    /// Reset a connection with this packet and remove it.
+    pub(crate) fn reset(self, key: SlotKey) -> Result<(), crate::layer::Error> {
+        let mut operator = Operator::new(self.endpoint, key)
+            .ok_or(crate::layer::Error::Illegal)?;
+        let tuple = operator.four_tuple();
+        let repr = operator.connection_mut().repr_reset(tuple);
+
+        let mut out_ip = prepare(self.ip, &mut operator, repr)?;
+        let ip_repr = out_ip.repr();
+        TcpPacket::new_unchecked(out_ip.payload_mut_slice(), repr)
+            .fill_checksum(ip_repr.src_addr(), ip_repr.dst_addr());
+        out_ip.send()?;
+
+        operator.delete();
+        Ok(())
+    }
+
     fn source(&self, dst: IpAddress) -> Result<IpAddress, crate::layer::Error> {
         // Find a suitable ip source address.
         let source = match dst {
@@ -506,6 +782,14 @@ impl<'a, P: PayloadMut> Closed<'a, P> {
             endpoint: self.endpoint,
         }
     }
+
+    /// Exchange the buffer of the packet for a spare, see [`eth::InPacket::retain`].
+    ///
+    /// [`eth::InPacket::retain`]: ../eth/struct.InPacket.html#method.retain
+    pub fn retain(self, spare: &mut P) -> Result<(), crate::layer::Error> {
+        let ip::RawPacket { mut handle, payload } = self.into_raw().ip;
+        handle.retain(payload, spare)
+    }
 }
 
 impl<'a, P: PayloadMut> Stray<'a, P> {
@@ -524,6 +808,14 @@ impl<'a, P: PayloadMut> Stray<'a, P> {
             endpoint: self.endpoint,
         }
     }
+
+    /// Exchange the buffer of the packet for a spare, see [`eth::InPacket::retain`].
+    ///
+    /// [`eth::InPacket::retain`]: ../eth/struct.InPacket.html#method.retain
+    pub fn retain(self, spare: &mut P) -> Result<(), crate::layer::Error> {
+        let ip::RawPacket { mut handle, payload } = self.into_raw().ip;
+        handle.retain(payload, spare)
+    }
 }
 
 impl UserSignals {
@@ -531,25 +823,100 @@ impl UserSignals {
         UserSignals {
             reset: signals.reset,
             data: signals.receive.is_some(),
+            push: false,
+            urgent: false,
             half_closed: false,
             relisten: false,
+            timed_out: false,
         }
     }
 
-    fn update(&mut self, _signals: &OutSignals) {
-        // TODO: anything to set?
+    fn update(&mut self, signals: &OutSignals) {
+        self.timed_out |= signals.aborted;
     }
 }
 
+/// The maximum segment size fitting the maximum transmission unit towards a destination.
+fn segment_size(ip: &ip::Handle, dst_addr: IpAddress) -> Option<u16> {
+    let max = ip.max_payload(dst_addr)?.saturating_sub(20);
+    Some(max.min(usize::from(u16::MAX)) as u16)
+}
+
+/// Prepare a segment with data from the send buffer and send it.
+fn fill<'a, P: PayloadMut>(
+    raw_ip: ip::RawPacket<'a, P>,
+    operator: &mut Operator<'a>,
+    repr: TcpRepr,
+    with: &mut impl SendBuf,
+    begin: TcpSeqNumber,
+) -> Result<(), crate::layer::Error> {
+    let mut out_ip = prepare(raw_ip, operator, repr)?;
+
+    let ip_repr = out_ip.repr();
+    let mut tcp = TcpPacket::new_unchecked(out_ip.payload_mut_slice(), repr);
+    with.fill(tcp.payload_mut_slice(), begin);
+    tcp.fill_checksum(ip_repr.src_addr(), ip_repr.dst_addr());
+
+    out_ip.send()
+}
+
+/// Check that the data of a segment ends its frame, without padding behind it.
+///
+/// Only then is it the common tail of the frame that is preserved when the headers are rewritten.
+fn ends_frame<P: Payload>(tcp: &TcpPacket<ip::IpPacket<'_, P>>) -> bool {
+    let frame = match tcp.inner() {
+        ip::IpPacket::V4(packet) => packet.get_ref().payload_slice(),
+        ip::IpPacket::V6(packet) => packet.get_ref().payload_slice(),
+    };
+
+    tcp.payload_slice().as_ptr_range().end == frame.as_ptr_range().end
+}
+
+/// Rewrite the headers of a received segment for sending its data on another connection.
+fn reframe<'a, P: PayloadMut>(
+    tcp: TcpPacket<ip::IpPacket<'a, P>>,
+    ip: ip::Handle<'a>,
+    operator: &mut Operator,
+    repr: TcpRepr,
+) -> Result<(), crate::layer::Error> {
+    let tuple = operator.four_tuple();
+    let packet = ip::InPacket {
+        handle: ip,
+        packet: tcp.into_inner(),
+    };
+
+    // The data is the common tail of both packets and remains in place.
+    let ip::InPacket { handle, mut packet } = packet.reinit(ip::Init {
+        source: ip::Source::Exact(tuple.local),
+        dst_addr: tuple.remote,
+        protocol: IpProtocol::Tcp,
+        payload: repr.header_len() + usize::from(repr.payload_len),
+        hop_limit: None,
+        dscp: operator.connection().dscp,
+    })?.into_incoming();
+
+    let ip_repr = packet.repr();
+    repr.emit(TcpPacket::new_unchecked(&mut packet, repr));
+    TcpPacket::new_unchecked(&mut packet, repr)
+        .fill_checksum(ip_repr.src_addr(), ip_repr.dst_addr());
+
+    ip::OutPacket::new_unchecked(handle, packet)
+        .send()
+}
+
 fn control_answer<'a, P: PayloadMut>(
     tcp: TcpPacket<ip::IpPacket<'a, P>>,
-    answer: TcpRepr,
+    mut answer: TcpRepr,
     ip: ip::Handle<'a>,
+    dscp: u8,
 ) -> Result<(), crate::layer::Error> {
     assert_eq!(answer.payload_len, 0, "Control answer can not handle data");
 
     let raw_buffer = tcp.into_inner();
     let ip_repr = raw_buffer.repr();
+    if answer.flags.syn() {
+        answer.max_seg_size = segment_size(&ip, ip_repr.src_addr());
+    }
     let ip_payload_len = answer.header_len();
 
     let packet = ip::InPacket {
@@ -563,6 +930,8 @@ fn control_answer<'a, P: PayloadMut>(
         dst_addr: ip_repr.src_addr(),
         protocol: IpProtocol::Tcp,
         payload: ip_payload_len,
+        hop_limit: None,
+        dscp,
     })?.into_incoming();
 
     // FIXME: make initialization nicer.
@@ -587,6 +956,8 @@ fn prepare<'a, P: PayloadMut>(
         source: ip::Source::Exact(tuple.local),
         protocol: IpProtocol::Tcp,
         payload: repr.header_len() + usize::from(repr.payload_len),
+        hop_limit: None,
+        dscp: operator.connection().dscp,
     })?;
 
     let ip::InPacket { handle, mut packet } = init_ip.into_incoming();
@@ -0,0 +1,398 @@
+//! Sample services for interoperability and throughput tests.
+//!
+//! The simple services of RFC862 ([`Echo`]), RFC863 ([`Discard`]) and RFC864 ([`Chargen`]) have
+//! well known behaviour that any other implementation can check, for example with `nc` or a
+//! traffic generator. Each handler serves a single connection, usually accepted on a listening
+//! slot of the endpoint on the port of the service. Create another handler for each further
+//! connection.
+//!
+//! All of them keep the remote flow-controlled by their buffers. The echo service only accepts as
+//! much data as it has space to send back, while the other two accept everything without storing
+//! it. A service closes its sending direction once the remote has closed its own.
+//!
+//! ```
+//! use ethox::layer::tcp::samples::Chargen;
+//!
+//! // The first line of a character generator, to check the received data against.
+//! let mut line = [0; 74];
+//! Chargen::pattern(0, &mut line);
+//! assert!(line.starts_with(b" !\"#$%&"));
+//! assert!(line.ends_with(b"\r\n"));
+//! ```
+//!
+//! [`Echo`]: struct.Echo.html
+//! [`Discard`]: struct.Discard.html
+//! [`Chargen`]: struct.Chargen.html
+use crate::managed::Slice;
+use crate::wire::{PayloadMut, TcpSeqNumber};
+
+use super::io::SendFrom;
+use super::stream::{RecvRing, SendRing};
+use super::{AvailableBytes, InPacket, Open, RawPacket, ReceivedSegment, Recv, RecvBuf, Send};
+use super::{SendBuf, SlotKey};
+
+/// Returns all received data to the remote, RFC862.
+///
+/// The data passes from the receive storage to the send storage as far as the latter has space.
+/// Data that does not fit stays in the receive storage and keeps the receive window closed until
+/// the remote has acknowledged some of the echoed data.
+pub struct Echo<'a> {
+    key: Option<SlotKey>,
+    recv: RecvRing<'a>,
+    send: SendRing<'a>,
+    echoed: usize,
+}
+
+/// Drops all received data, RFC863.
+///
+/// No data is stored, the remote is offered the maximum receive window of the connection.
+pub struct Discard {
+    key: Option<SlotKey>,
+    recv: Counter,
+    send: SendFrom<&'static [u8]>,
+}
+
+/// Sends lines of printable characters without end, RFC864.
+///
+/// Each line contains 72 characters followed by CR LF. The lines start with consecutive
+/// characters of the 95 printable ASCII characters. The data is generated when a segment is sent,
+/// including retransmissions, and not stored. Received data is dropped.
+pub struct Chargen {
+    key: Option<SlotKey>,
+    recv: Counter,
+    send: Pattern,
+}
+
+/// Accepts all in-order data without storing it.
+#[derive(Default)]
+struct Counter {
+    /// The next expected sequence number.
+    next: Option<TcpSeqNumber>,
+    /// The number of bytes accepted.
+    count: usize,
+    /// The remote will not send any more data.
+    fin: bool,
+}
+
+/// Generates the character pattern of `Chargen`.
+#[derive(Default)]
+struct Pattern {
+    /// The sequence number of the first unacknowledged byte.
+    at: Option<TcpSeqNumber>,
+    /// The offset of that byte in the pattern.
+    acked: usize,
+    /// The offset following the last byte that was sent.
+    sent: usize,
+    /// No more data is generated.
+    fin: bool,
+}
+
+/// The length of a line of the character generator, including the line break.
+const LINE: usize = 74;
+
+/// The amount of data the character generator has available at any time.
+///
+/// Plenty to fill the largest window but far from overflowing the sequence space.
+const GENERATED: usize = 1 << 24;
+
+impl<'a> Echo<'a> {
+    /// The well known port of the service.
+    pub const PORT: u16 = 7;
+
+    /// Create a handler for an existing connection.
+    pub fn new<R, S>(key: SlotKey, recv: R, send: S) -> Self
+        where R: Into<Slice<'a, u8>>, S: Into<Slice<'a, u8>>,
+    {
+        Echo {
+            key: Some(key),
+            recv: RecvRing::new(recv),
+            send: SendRing::new(send),
+            echoed: 0,
+        }
+    }
+
+    /// The number of bytes echoed so far.
+    ///
+    /// This includes data waiting to be sent and data not yet acknowledged by the remote.
+    pub fn echoed(&self) -> usize {
+        self.echoed
+    }
+
+    /// Get the key of the connection, unless it was closed.
+    pub fn connection_key(&self) -> Option<SlotKey> {
+        self.key
+    }
+
+    /// Check if the connection was closed.
+    pub fn is_closed(&self) -> bool {
+        self.key.is_none()
+    }
+
+    /// Move received data to the send buffer, as much as fits.
+    fn echo(&mut self) {
+        let Echo { recv, send, .. } = self;
+        // The data may wrap around the end of the receive storage.
+        loop {
+            let moved = recv.read_with(|data| send.write(data));
+            if moved == 0 {
+                break;
+            }
+            self.echoed += moved;
+        }
+
+        if recv.is_closed() && recv.is_empty() {
+            send.close();
+        }
+    }
+}
+
+impl Discard {
+    /// The well known port of the service.
+    pub const PORT: u16 = 9;
+
+    /// Create a handler for an existing connection.
+    pub fn new(key: SlotKey) -> Self {
+        Discard {
+            key: Some(key),
+            recv: Counter::default(),
+            send: SendFrom::new(&[]),
+        }
+    }
+
+    /// The number of bytes received so far.
+    pub fn received(&self) -> usize {
+        self.recv.count
+    }
+
+    /// Get the key of the connection, unless it was closed.
+    pub fn connection_key(&self) -> Option<SlotKey> {
+        self.key
+    }
+
+    /// Check if the connection was closed.
+    pub fn is_closed(&self) -> bool {
+        self.key.is_none()
+    }
+}
+
+impl Chargen {
+    /// The well known port of the service.
+    pub const PORT: u16 = 19;
+
+    /// Create a handler for an existing connection.
+    pub fn new(key: SlotKey) -> Self {
+        Chargen {
+            key: Some(key),
+            recv: Counter::default(),
+            send: Pattern::default(),
+        }
+    }
+
+    /// Fill a buffer with the character pattern, starting at an offset into the stream.
+    pub fn pattern(offset: usize, buf: &mut [u8]) {
+        for (offset, byte) in (offset..).zip(buf) {
+            let line = offset / LINE;
+            *byte = match offset % LINE {
+                72 => b'\r',
+                73 => b'\n',
+                column => b' ' + ((line + column) % 95) as u8,
+            };
+        }
+    }
+
+    /// The number of bytes sent so far.
+    pub fn generated(&self) -> usize {
+        self.send.sent
+    }
+
+    /// The number of bytes received so far.
+    pub fn received(&self) -> usize {
+        self.recv.count
+    }
+
+    /// Get the key of the connection, unless it was closed.
+    pub fn connection_key(&self) -> Option<SlotKey> {
+        self.key
+    }
+
+    /// Check if the connection was closed.
+    pub fn is_closed(&self) -> bool {
+        self.key.is_none()
+    }
+}
+
+impl RecvBuf for Counter {
+    fn receive(&mut self, data: &[u8], segment: ReceivedSegment) {
+        let next = self.next.get_or_insert(segment.data_begin());
+        if self.fin || segment.data_begin() > *next {
+            return;
+        }
+
+        let end = segment.data_begin() + data.len();
+        if end > *next {
+            self.count += end - *next;
+            *next = end;
+        }
+
+        if segment.fin && *next == segment.data_end() {
+            self.fin = true;
+            *next += 1;
+        }
+    }
+
+    fn ack(&mut self) -> TcpSeqNumber {
+        self.next.expect("Must not be called before any isn indication")
+    }
+
+    fn window(&self) -> usize {
+        usize::MAX
+    }
+}
+
+impl SendBuf for Pattern {
+    fn available(&self) -> AvailableBytes {
+        AvailableBytes {
+            total: if self.fin { self.sent - self.acked } else { GENERATED },
+            fin: self.fin,
+            push: false,
+        }
+    }
+
+    fn fill(&mut self, buf: &mut [u8], begin: TcpSeqNumber) {
+        let at = self.at.expect("Fill must not be called before isn indication");
+        let offset = self.acked + (begin - at);
+        Chargen::pattern(offset, buf);
+        self.sent = self.sent.max(offset + buf.len());
+    }
+
+    fn ack(&mut self, ack: TcpSeqNumber) {
+        let previous = *self.at.get_or_insert(ack);
+        if ack > previous {
+            // The FIN is acknowledged after all data.
+            self.acked = (self.acked + (ack - previous)).min(self.sent);
+            self.at = Some(ack);
+        }
+    }
+}
+
+/// Read a packet of a connection and answer, then check if the remote closed.
+fn serve<P: PayloadMut>(open: &mut Open<P>, recv: &mut impl RecvBuf) -> bool {
+    open.read(recv);
+    open.is_remote_closed()
+}
+
+impl<P: PayloadMut> Recv<P> for &'_ mut Echo<'_> {
+    fn receive(&mut self, packet: InPacket<P>) {
+        if self.key.is_none() || packet.key() != self.key {
+            return;
+        }
+
+        match packet {
+            InPacket::Stray(_) | InPacket::Sending(_) => (),
+            InPacket::Closed(_) | InPacket::Closing(_) => self.key = None,
+            InPacket::Open(mut open) => {
+                open.read(&mut self.recv);
+                self.echo();
+                open.offer_window(&self.recv);
+                if let Ok(Err(_closing)) = open.write(&mut self.send) {
+                    self.key = None;
+                }
+            },
+        }
+    }
+}
+
+impl<P: PayloadMut> Send<P> for &'_ mut Echo<'_> {
+    fn send(&mut self, packet: RawPacket<P>) {
+        let key = match self.key {
+            Some(key) => key,
+            None => return,
+        };
+
+        let mut open = match packet.attach(key) {
+            Ok(open) => open,
+            // The connection no longer exists.
+            Err(_) => return self.key = None,
+        };
+
+        // Acknowledged data made room for more of the received data, reopen its window.
+        self.echo();
+        open.offer_window(&self.recv);
+        if let Ok(Err(_closing)) = open.write(&mut self.send) {
+            self.key = None;
+        }
+    }
+}
+
+impl<P: PayloadMut> Recv<P> for &'_ mut Discard {
+    fn receive(&mut self, packet: InPacket<P>) {
+        if self.key.is_none() || packet.key() != self.key {
+            return;
+        }
+
+        match packet {
+            InPacket::Stray(_) | InPacket::Sending(_) => (),
+            InPacket::Closed(_) | InPacket::Closing(_) => self.key = None,
+            InPacket::Open(mut open) => {
+                if serve(&mut open, &mut self.recv) {
+                    self.send.fin();
+                }
+                if let Ok(Err(_closing)) = open.write(&mut self.send) {
+                    self.key = None;
+                }
+            },
+        }
+    }
+}
+
+impl<P: PayloadMut> Send<P> for &'_ mut Discard {
+    fn send(&mut self, packet: RawPacket<P>) {
+        let key = match self.key {
+            Some(key) => key,
+            None => return,
+        };
+
+        match packet.attach(key) {
+            Ok(open) => if let Ok(Err(_closing)) = open.write(&mut self.send) {
+                self.key = None;
+            },
+            Err(_) => self.key = None,
+        }
+    }
+}
+
+impl<P: PayloadMut> Recv<P> for &'_ mut Chargen {
+    fn receive(&mut self, packet: InPacket<P>) {
+        if self.key.is_none() || packet.key() != self.key {
+            return;
+        }
+
+        match packet {
+            InPacket::Stray(_) | InPacket::Sending(_) => (),
+            InPacket::Closed(_) | InPacket::Closing(_) => self.key = None,
+            InPacket::Open(mut open) => {
+                // Stop after the data that has been sent already.
+                self.send.fin |= serve(&mut open, &mut self.recv);
+                if let Ok(Err(_closing)) = open.write(&mut self.send) {
+                    self.key = None;
+                }
+            },
+        }
+    }
+}
+
+impl<P: PayloadMut> Send<P> for &'_ mut Chargen {
+    fn send(&mut self, packet: RawPacket<P>) {
+        let key = match self.key {
+            Some(key) => key,
+            None => return,
+        };
+
+        match packet.attach(key) {
+            Ok(open) => if let Ok(Err(_closing)) = open.write(&mut self.send) {
+                self.key = None;
+            },
+            Err(_) => self.key = None,
+        }
+    }
+}
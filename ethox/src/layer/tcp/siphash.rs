@@ -1,11 +1,12 @@
 //! Initial sequence number generation, as recommended by rfc6528.
 //!
 //! Uses a keyed cryptographic hash function (SipHash-2-4) instead of appending the secret key to
-//! the four tuple for hashing. That should be better anyways. Hash function SipHash-2-4 from:
-//!
-//! > SipHash: a fast short-input PRFJean-Philippe Aumasson1and Daniel J. Bernstein
+//! the four tuple for hashing. That should be better anyways. The hash function itself is shared
+//! with other layers in `crate::siphash`.
 use super::endpoint::FourTuple;
-use crate::time::Instant;
+use crate::rand::Rng;
+use crate::siphash::State;
+use crate::time::{Duration, Instant};
 use crate::wire::{IpAddress, Ipv6Address, TcpSeqNumber};
 
 /// An initial sequence number generator based on SipHash-2-4.
@@ -25,22 +26,23 @@ use crate::wire::{IpAddress, Ipv6Address, TcpSeqNumber};
 ///   to compute and unlikely to have a practical advantage. Note that any attacker is highly limited
 ///   in modifications to the hash input and a collision (second pre-image) is not her goal.
 /// * SipHash-0-x, there exist key recovery attacks and it only has marginal extra 
+///
+/// ## Key rotation
+///
+/// The secret key can be replaced at any time with [`rekey`], or periodically when an interval is
+/// configured with [`set_rekey_interval`] and [`maintain`] is called regularly. Established
+/// connections are not affected by this as the initial sequence number is only computed once when
+/// a connection is opened and then stored in its state. However, new connections for a four tuple
+/// that was recently in use can no longer be expected to start above the sequence space of the
+/// previous connection, so avoid very short intervals.
+///
+/// [`rekey`]: #method.rekey
+/// [`set_rekey_interval`]: #method.set_rekey_interval
+/// [`maintain`]: #method.maintain
 pub struct IsnGenerator {
     keys: (u64, u64),
-}
-
-// Yes, that's the initial values, as ASCII text.
-const IV: [&[u8; 8]; 4] = [
-    b"somepseu",
-    b"dorandom",
-    b"lygenera",
-    b"tedbytes"];
-
-struct State {
-    v0: u64,
-    v1: u64,
-    v2: u64,
-    v3: u64,
+    rekey_interval: Option<Duration>,
+    last_rekey: Option<Instant>,
 }
 
 impl IsnGenerator {
@@ -51,46 +53,111 @@ impl IsnGenerator {
     /// the internal key state.
     #[cfg(feature = "std")]
     pub fn from_std_hash() -> Self {
-        use std::hash::{Hasher, BuildHasher};
-        use std::collections::hash_map::RandomState;
-
-        let hash = RandomState::new().build_hasher();
-        let x0 = {
-            let mut hash = hash.clone();
-            hash.write_u64(0);
-            hash.finish()
-        };
-        let x1 = {
-            let mut hash = hash.clone();
-            hash.write_u64(1);
-            hash.finish()
-        };
+        let mut rng = crate::rand::StdRng::new();
+        let x0 = rng.next_u64();
+        let x1 = rng.next_u64();
+        IsnGenerator::from_keys(x0, x1)
+    }
 
-        IsnGenerator {
-            keys: (x0, x1),
-        }
+    /// Create a generator with a secret key drawn from a random source.
+    ///
+    /// The source should be cryptographically secure, see the [`rand`] module.
+    ///
+    /// [`rand`]: ../../rand/index.html
+    pub fn from_rng<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        IsnGenerator::from_secret_key_bytes(Self::random_key(rng))
     }
 
     /// Create a generator with some pre-defined secret key.
     ///
     /// Really, create the key with some cryptographic random means or derive them from some other
     /// key with a key derivation function.
-    pub fn from_secret_key_bytes(bytes: [u8; 16]) -> Self {
-        use core::convert::TryInto;
-        let a = u64::from_le_bytes(bytes[..8].try_into().unwrap());
-        let b = u64::from_le_bytes(bytes[8..].try_into().unwrap());
-        IsnGenerator { keys: (a, b), }
+    ///
+    /// This is a `const fn` but a key compiled into the binary is shared by all devices and all
+    /// boots. Replace it with [`rekey_from`] as soon as a random source is available.
+    ///
+    /// [`rekey_from`]: #method.rekey_from
+    pub const fn from_secret_key_bytes(bytes: [u8; 16]) -> Self {
+        let [a0, a1, a2, a3, a4, a5, a6, a7, b0, b1, b2, b3, b4, b5, b6, b7] = bytes;
+        let a = u64::from_le_bytes([a0, a1, a2, a3, a4, a5, a6, a7]);
+        let b = u64::from_le_bytes([b0, b1, b2, b3, b4, b5, b6, b7]);
+        IsnGenerator::from_keys(a, b)
     }
 
     /// Create a generator with a pre-defined key.
     #[cfg(test)]
     pub fn from_key(a: u64, b: u64) -> Self {
-        IsnGenerator { keys: (a, b), }
+        IsnGenerator::from_keys(a, b)
+    }
+
+    const fn from_keys(a: u64, b: u64) -> Self {
+        IsnGenerator {
+            keys: (a, b),
+            rekey_interval: None,
+            last_rekey: None,
+        }
+    }
+
+    /// Replace the secret key.
+    ///
+    /// Only affects the initial sequence numbers of connections opened afterwards.
+    pub fn rekey(&mut self, bytes: [u8; 16], now: Instant) {
+        let IsnGenerator { keys, .. } = IsnGenerator::from_secret_key_bytes(bytes);
+        self.keys = keys;
+        self.last_rekey = Some(now);
+    }
+
+    /// Replace the secret key with one drawn from a random source.
+    pub fn rekey_from<R: Rng + ?Sized>(&mut self, rng: &mut R, now: Instant) {
+        self.rekey(Self::random_key(rng), now)
+    }
+
+    /// Configure the interval for periodically replacing the key in `maintain`.
+    ///
+    /// Set to `None` to disable key rotation, which is the default.
+    pub fn set_rekey_interval(&mut self, interval: Option<Duration>) {
+        self.rekey_interval = interval;
+    }
+
+    /// Get the interval after which the key is replaced.
+    pub fn rekey_interval(&self) -> Option<Duration> {
+        self.rekey_interval
+    }
+
+    /// Check if the key is due to be replaced.
+    ///
+    /// Always false if no rekey interval has been configured. Otherwise true if the key has not
+    /// been rotated yet or the interval has passed since the last rotation.
+    pub fn needs_rekey(&self, now: Instant) -> bool {
+        match (self.rekey_interval, self.last_rekey) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(interval), Some(last)) => now >= last + interval,
+        }
+    }
+
+    /// Replace the key with one drawn from a random source if it is due.
+    ///
+    /// Returns whether the key was replaced. Call this regularly, for example before sending.
+    pub fn maintain<R: Rng + ?Sized>(&mut self, rng: &mut R, now: Instant) -> bool {
+        let due = self.needs_rekey(now);
+        if due {
+            self.rekey_from(rng, now);
+        }
+        due
+    }
+
+    fn random_key<R: Rng + ?Sized>(rng: &mut R) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        rng.fill_bytes(&mut bytes);
+        bytes
     }
 
     /// Get the initial sequence number for a connection.
     ///
-    /// The value varies every 4ms or when the underlying secret key is updated.
+    /// The value advances with a timer of 4 microseconds, as recommended, and varies when the
+    /// underlying secret key is updated. Since timestamps only have a resolution of milliseconds the
+    /// timer advances in steps of 250.
     ///
     /// # Panics
     ///
@@ -158,7 +225,9 @@ impl IsnGenerator {
             _ => panic!("Should not be called, four tuple needs to be concrete ip addresses"),
         };
 
-        TcpSeqNumber(num as i32) + (time.millis()/4000) as usize
+        // The timer wraps around, as does the sequence space.
+        let timer = time.millis().wrapping_mul(250) as i32;
+        TcpSeqNumber((num as i32).wrapping_add(timer))
     }
 
     fn ipv6_to_messages(addr: Ipv6Address) -> (u64, u64) {
@@ -169,92 +238,41 @@ impl IsnGenerator {
     }
 }
 
-impl State {
-    const SIP_C: usize = 2;
-    const SIP_D: usize = 4;
-
-    fn init(k0: u64, k1: u64) -> Self {
-        State {
-            v0: u64::from_be_bytes(*IV[0]) ^ k0,
-            v1: u64::from_be_bytes(*IV[1]) ^ k1,
-            v2: u64::from_be_bytes(*IV[2]) ^ k0,
-            v3: u64::from_be_bytes(*IV[3]) ^ k1,
-        }
-    }
-
-    fn round(&mut self) {
-        self.v0 = self.v0.wrapping_add(self.v1);
-        self.v1 = self.v1.rotate_left(13);
-        self.v1 ^= self.v0;
-        self.v0 = self.v0.rotate_left(32);
-        self.v2 = self.v2.wrapping_add(self.v3);
-        self.v3 = self.v3.rotate_left(16);
-        self.v3 ^= self.v2;
-        self.v0 = self.v0.wrapping_add(self.v3);
-        self.v3 = self.v3.rotate_left(21);
-        self.v3 ^= self.v0;
-        self.v2 = self.v2.wrapping_add(self.v1);
-        self.v1 = self.v1.rotate_left(17);
-        self.v1 ^= self.v2;
-        self.v2 = self.v2.rotate_left(32);
-    }
-
-    /// Process a single portion of the message.
-    ///
-    /// Note that all users need to manually add absorbing the length in the last block. This is
-    /// slightly easier to read since it arranges the input to only have 8-btye blocks in all cases
-    /// which separates the length block completely and makes it a constant.
-    fn absorb(&mut self, m: u64) {
-        self.v3 ^= m;
-        (0..Self::SIP_C).for_each(|_| self.round());
-        self.v0 ^= m;
-    }
-
-    /// Do the finalization rounds.
-    fn finalize(mut self) -> u64 {
-        self.v2 ^= 0xff;
-        (0..Self::SIP_D).for_each(|_| self.round());
-        self.v0 ^ self.v1 ^ self.v2 ^ self.v3
-    }
-}
-
 #[cfg(test)]
 mod tests {
-    use core::fmt;
     use super::*;
 
-    struct DebugState<'a>(&'a State);
+    #[test]
+    fn rekey() {
+        use crate::layer::loss::Xoroshiro256;
 
-    impl fmt::Debug for DebugState<'_> {
-        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-            write!(f, "{:x} ", self.0.v0)?;
-            write!(f, "{:x} ", self.0.v1)?;
-            write!(f, "{:x} ", self.0.v2)?;
-            write!(f, "{:x} ", self.0.v3)
-        }
-    }
+        let tuple = FourTuple {
+            local: IpAddress::v4(192, 0, 10, 1),
+            remote: IpAddress::v4(192, 0, 10, 2),
+            local_port: 80,
+            remote_port: 4000,
+        };
+        let start = Instant::from_secs(0);
 
-    impl super::State {
-        fn debug(&self) -> DebugState {
-            DebugState(self)
-        }
-    }
+        let mut isn = IsnGenerator::from_key(0, 0);
+        let first = isn.get_isn(tuple, start);
+        // Advances with the 4µs timer.
+        assert_eq!(isn.get_isn(tuple, start + Duration::from_millis(1)), first + 250);
+        // Negative instants are fine as well.
+        let _ = isn.get_isn(tuple, Instant::from_millis(-1));
 
-    /// See the paper–Appendix A
-    #[test]
-    fn manual_test_vectors() {
-        let k0 = u64::from_le_bytes(0x0001020304050607_u64.to_be_bytes());
-        let k1 = u64::from_le_bytes(0x08090a0b0c0d0e0f_u64.to_be_bytes());
+        // No rotation unless configured.
+        let mut rng = Xoroshiro256::new(1);
+        assert!(!isn.maintain(&mut rng, start));
+        assert_eq!(isn.get_isn(tuple, start), first);
 
-        let mut state = State::init(k0, k1);
-        println!("{:?}", state.debug());
-        let m0 = u64::from_le_bytes(0x0001020304050607_u64.to_be_bytes());
-        state.absorb(m0);
-        println!("{:?}", state.debug());
-        let m1 = u64::from_le_bytes(0x08090a0b0c0d0e0f_u64.to_be_bytes());
-        state.absorb(m1);
-        println!("{:?}", state.debug());
+        let interval = Duration::from_secs(60);
+        isn.set_rekey_interval(Some(interval));
+        assert!(isn.maintain(&mut rng, start));
+        let rotated = isn.get_isn(tuple, start);
+        assert_ne!(rotated, first);
 
-        assert_eq!(state.finalize(), 0xa129ca6149be45e5);
+        assert!(!isn.maintain(&mut rng, start + Duration::from_secs(59)));
+        assert!(isn.maintain(&mut rng, start + interval));
     }
 }
@@ -3,8 +3,9 @@
 //! An actual socket layer requires allocation all buffers and depends on a few details in the
 //! layer below and these do not (that was not the end goal but some may be added in the future),
 //! but it tries to give a slightly more familiar interface.
-use super::{InPacket, RawPacket, Recv, RecvBuf, Send, SendBuf, SlotKey};
-use crate::wire::{IpAddress, PayloadMut};
+use super::{InPacket, RawPacket, Recv, RecvBuf, Send, SendBuf, SlotKey, State};
+use crate::time::{Duration, Expiration, Instant};
+use crate::wire::{IpAddress, Ipv4Address, Ipv6Address, PayloadMut};
 
 /// A tcp handler for a client (actively opened connection).
 ///
@@ -27,6 +28,8 @@ pub struct Client<R, S> {
     state: ClientState,
     recv: R,
     send: S,
+    /// The user requested to close the sending direction.
+    shutdown: bool,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -60,6 +63,7 @@ where
             },
             recv,
             send,
+            shutdown: false,
         }
     }
 }
@@ -95,6 +99,17 @@ impl<R, S> Client<R, S> {
         &mut self.send
     }
 
+    /// Close the sending direction of the connection.
+    ///
+    /// The FIN is queued on the connection the next time the client handles one of its packets,
+    /// after all data available in the send buffer at that point. The client keeps receiving
+    /// until the remote closes as well. See [`Open::shutdown`] for details.
+    ///
+    /// [`Open::shutdown`]: ../struct.Open.html#method.shutdown
+    pub fn shutdown(&mut self) {
+        self.shutdown = true;
+    }
+
     /// Check if the connection was closed.
     pub fn is_closed(&self) -> bool {
         match self.state {
@@ -143,6 +158,9 @@ where
             },
             InPacket::Open(mut open) => {
                 open.read(&mut self.recv);
+                if self.shutdown {
+                    open.shutdown();
+                }
                 let _ = open.write(&mut self.send);
             },
         }
@@ -156,7 +174,7 @@ where
     P: PayloadMut,
 {
     fn send(&mut self, packet: RawPacket<P>) {
-        let open = match self.state {
+        let mut open = match self.state {
             ClientState::Uninstantiated { remote, remote_port } => {
                 match packet.open(remote, remote_port) {
                     Ok(open) => {
@@ -182,7 +200,288 @@ where
             ClientState::Finished => return,
         };
 
+        if self.shutdown {
+            open.shutdown();
+        }
+
+        open.offer_window(&self.recv);
         // TODO: error handling.
         let _ = open.write(&mut self.send);
     }
 }
+
+/// A client racing connection attempts to an ipv6 and an ipv4 address of the same host.
+///
+/// This follows the Happy Eyeballs algorithm (RFC8305). The ipv6 attempt is started first, the
+/// ipv4 attempt after a short delay unless the former has failed already. The first attempt to
+/// be established wins and the other one is aborted. Afterwards, the client behaves exactly like
+/// a [`Client`] on the winning connection.
+///
+/// The stack has no timers of its own so the second attempt is only started when the client is
+/// invoked as a sender after the delay. Query [`next_timer`] for that point in time.
+///
+/// Create it with [`connect_dual`].
+///
+/// [`Client`]: struct.Client.html
+/// [`next_timer`]: #method.next_timer
+/// [`connect_dual`]: fn.connect_dual.html
+pub struct DualClient<R, S> {
+    client: Client<R, S>,
+    race: Race,
+    /// The losing attempt that still needs to be aborted.
+    cancel: Option<SlotKey>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Race {
+    Racing {
+        /// The preferred attempt first, then the fallback.
+        attempts: [Attempt; 2],
+        remote_port: u16,
+        /// The delay between starting the two attempts.
+        delay: Duration,
+        /// The time at which the second attempt is started.
+        fallback: Option<Instant>,
+        /// Which attempt to retransmit on next.
+        turn: usize,
+    },
+    Settled,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Attempt {
+    Pending(IpAddress),
+    Connecting(SlotKey),
+    Failed,
+}
+
+/// Connect to a dual-stack host by racing an ipv6 and an ipv4 connection attempt.
+///
+/// The ipv4 attempt is staggered by 250ms, the delay recommended in RFC8305. Use
+/// [`DualClient::set_delay`] to change it before the first packet is sent.
+///
+/// [`DualClient::set_delay`]: struct.DualClient.html#method.set_delay
+pub fn connect_dual<R, S>(
+    v6: Ipv6Address,
+    v4: Ipv4Address,
+    remote_port: u16,
+    recv: R,
+    send: S,
+) -> DualClient<R, S>
+where
+    R: RecvBuf,
+    S: SendBuf,
+{
+    DualClient {
+        client: Client {
+            state: ClientState::Finished,
+            recv,
+            send,
+            shutdown: false,
+        },
+        race: Race::Racing {
+            attempts: [Attempt::Pending(v6.into()), Attempt::Pending(v4.into())],
+            remote_port,
+            delay: DualClient::<R, S>::DEFAULT_DELAY,
+            fallback: None,
+            turn: 0,
+        },
+        cancel: None,
+    }
+}
+
+impl<R, S> DualClient<R, S> {
+    /// The delay between the two connection attempts.
+    pub const DEFAULT_DELAY: Duration = Duration::from_millis(250);
+
+    /// Change the delay before the ipv4 attempt is started.
+    ///
+    /// Has no effect once the first attempt has been started.
+    pub fn set_delay(&mut self, delay: Duration) {
+        if let Race::Racing { attempts, delay: current, .. } = &mut self.race {
+            if let Attempt::Pending(_) = attempts[0] {
+                *current = delay;
+            }
+        }
+    }
+
+    /// The point in time at which the client needs to send to start its second attempt.
+    pub fn next_timer(&self) -> Expiration {
+        match self.race {
+            Race::Racing { attempts: [Attempt::Connecting(_), Attempt::Pending(_)], fallback, .. }
+                => fallback.map(Expiration::When).unwrap_or(Expiration::Never),
+            _ => Expiration::Never,
+        }
+    }
+
+    /// Check if the race has been decided.
+    pub fn is_settled(&self) -> bool {
+        self.race == Race::Settled
+    }
+
+    /// Get the client of the established connection.
+    ///
+    /// Returns `None` while the connection attempts are still running.
+    pub fn client(&self) -> Option<&Client<R, S>> {
+        match self.race {
+            Race::Settled => Some(&self.client),
+            Race::Racing { .. } => None,
+        }
+    }
+
+    /// Get the mutable client of the established connection.
+    ///
+    /// Returns `None` while the connection attempts are still running.
+    pub fn client_mut(&mut self) -> Option<&mut Client<R, S>> {
+        match self.race {
+            Race::Settled => Some(&mut self.client),
+            Race::Racing { .. } => None,
+        }
+    }
+
+    /// Check if both connection attempts failed or the winning connection was closed.
+    pub fn is_closed(&self) -> bool {
+        match &self.race {
+            Race::Settled => self.client.is_closed(),
+            Race::Racing { attempts, .. } => attempts
+                .iter()
+                .all(|&attempt| attempt == Attempt::Failed),
+        }
+    }
+
+    /// Get the key of the winning connection, once it has been established.
+    pub fn connection_key(&self) -> Option<SlotKey> {
+        self.client()?.connection_key()
+    }
+
+    /// Decide the race for one of the attempts.
+    fn settle(&mut self, winner: SlotKey) {
+        if let Race::Racing { attempts, .. } = &self.race {
+            self.cancel = attempts.iter()
+                .filter_map(|attempt| match *attempt {
+                    Attempt::Connecting(key) if key != winner => Some(key),
+                    _ => None,
+                })
+                .next();
+        }
+
+        self.client.state = ClientState::InStack { key: winner };
+        self.race = Race::Settled;
+    }
+}
+
+impl Attempt {
+    fn key(self) -> Option<SlotKey> {
+        match self {
+            Attempt::Connecting(key) => Some(key),
+            _ => None,
+        }
+    }
+}
+
+impl<R, S, P> Recv<P> for &'_ mut DualClient<R, S>
+where
+    R: RecvBuf,
+    S: SendBuf,
+    P: PayloadMut,
+{
+    fn receive(&mut self, packet: InPacket<P>) {
+        let key = match packet.key() {
+            Some(key) => key,
+            None => return,
+        };
+
+        if self.cancel == Some(key) {
+            if let InPacket::Open(open) = packet {
+                open.abort();
+                self.cancel = None;
+            }
+            return;
+        }
+
+        let attempts = match &mut self.race {
+            Race::Settled => return Recv::receive(&mut &mut self.client, packet),
+            Race::Racing { attempts, .. } => attempts,
+        };
+
+        let attempt = match attempts.iter_mut().find(|attempt| attempt.key() == Some(key)) {
+            Some(attempt) => attempt,
+            None => return,
+        };
+
+        match &packet {
+            InPacket::Open(open) if open.info().state == State::Established => {
+                self.settle(key);
+                Recv::receive(&mut &mut self.client, packet);
+            },
+            InPacket::Closed(_) | InPacket::Closing(_) => *attempt = Attempt::Failed,
+            _ => (),
+        }
+    }
+}
+
+impl<R, S, P> Send<P> for &'_ mut DualClient<R, S>
+where
+    R: RecvBuf,
+    S: SendBuf,
+    P: PayloadMut,
+{
+    fn send(&mut self, packet: RawPacket<P>) {
+        let packet = match self.cancel.take() {
+            Some(key) => match packet.attach(key) {
+                Ok(open) => {
+                    open.abort();
+                    return;
+                },
+                Err(packet) => packet,
+            },
+            None => packet,
+        };
+
+        let (attempts, remote_port, delay, fallback, turn) = match &mut self.race {
+            Race::Settled => return Send::send(&mut &mut self.client, packet),
+            Race::Racing { attempts, remote_port, delay, fallback, turn }
+                => (attempts, *remote_port, *delay, fallback, turn),
+        };
+
+        let time = packet.ip.handle.info().timestamp();
+        let start = match attempts {
+            [Attempt::Pending(_), _] => Some(0),
+            [Attempt::Failed, Attempt::Pending(_)] => Some(1),
+            [_, Attempt::Pending(_)] if fallback.is_some_and(|at| at <= time) => Some(1),
+            _ => None,
+        };
+
+        if let Some(idx) = start {
+            let remote = match attempts[idx] {
+                Attempt::Pending(remote) => remote,
+                _ => unreachable!(),
+            };
+
+            match packet.open(remote, remote_port) {
+                Ok(open) => {
+                    attempts[idx] = Attempt::Connecting(open.key());
+                    let _ = open.write(&mut self.client.send);
+                },
+                Err(crate::layer::Error::Exhausted) => (),
+                Err(_) => attempts[idx] = Attempt::Failed,
+            }
+
+            if idx == 0 {
+                *fallback = Some(time + delay);
+            }
+
+            return;
+        }
+
+        // Alternate between the attempts to drive their retransmissions.
+        *turn = (*turn + 1) % attempts.len();
+        let attempt = &mut attempts[*turn];
+        if let Attempt::Connecting(key) = *attempt {
+            match packet.attach(key) {
+                Ok(open) => { let _ = open.write(&mut self.client.send); },
+                Err(_) => *attempt = Attempt::Failed,
+            }
+        }
+    }
+}
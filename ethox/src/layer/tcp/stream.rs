@@ -0,0 +1,624 @@
+//! Byte stream buffers of a connection.
+//!
+//! The connection logic never segments or reassembles data itself. Instead, it pulls outgoing data
+//! from a [`SendBuf`] when preparing a segment and pushes the data of incoming segments into a
+//! [`RecvBuf`]. The receive buffer also decides what is acknowledged and which window is
+//! advertised, so that the remote is flow-controlled by the space the application leaves in it.
+//!
+//! The ring buffers [`SendRing`] and [`RecvRing`] are ready-made implementations on top of fixed
+//! storage. The application writes to and reads from them at its own pace while the [`Stream`]
+//! handler moves the data between them and the connection during rx and tx processing. The send
+//! ring is also a [`SpliceBuf`] for data forwarded from another connection.
+//!
+//! ```
+//! use ethox::layer::tcp::stream::{RecvRing, SendRing};
+//!
+//! let mut send = SendRing::new(vec![0; 1024]);
+//! assert_eq!(send.write(b"GET / HTTP/1.0\r\n\r\n"), 18);
+//! send.close();
+//!
+//! let mut recv = RecvRing::new(vec![0; 1024]);
+//! let mut response = [0; 128];
+//! // Nothing arrived yet.
+//! assert_eq!(recv.read(&mut response), 0);
+//! ```
+//!
+//! [`SendBuf`]: trait.SendBuf.html
+//! [`RecvBuf`]: trait.RecvBuf.html
+//! [`SendRing`]: struct.SendRing.html
+//! [`RecvRing`]: struct.RecvRing.html
+//! [`Stream`]: struct.Stream.html
+//! [`SpliceBuf`]: trait.SpliceBuf.html
+use crate::managed::Slice;
+use crate::storage::ByteRing;
+use crate::storage::assembler::{Assembler, Contig};
+use crate::wire::{PayloadMut, TcpSeqNumber};
+
+use super::{AvailableBytes, InPacket, RawPacket, ReceivedSegment, Recv, Send, SlotKey};
+
+/// A user defined (re-)transmission buffer.
+///
+/// The connection pulls data from the buffer whenever it may send a segment. All data is addressed
+/// by its sequence number. The first call to `ack` indicates the sequence number of the first
+/// byte, which is then followed by all data in the order it was indicated as `available`.
+pub trait SendBuf {
+    /// Check the available data.
+    ///
+    /// This should be the total of sent-but-unacknowledged bytes and unsent bytes.
+    fn available(&self) -> AvailableBytes;
+
+    /// Fill in some (re-)transmitted data.
+    ///
+    /// The tcp connection layer will take care to never call this with a buffer outside the
+    /// indicated available data length. Bytes that have already been sent are not supposed to
+    /// change afterwards, i.e. the `SendBuf` is also utilized as the retransmit buffer.
+    fn fill(&mut self, buf: &mut [u8], begin: TcpSeqNumber);
+
+    /// Notify the buffer that some data can be safely discarded.
+    ///
+    /// The tcp layer will ensure that no data before the new `begin` is requested again.
+    fn ack(&mut self, begin: TcpSeqNumber);
+}
+
+/// A send buffer accepting data spliced from another connection.
+///
+/// The spliced data is sent in the buffer of the segment that delivered it, the send buffer only
+/// keeps a copy for retransmissions. See [`Open::splice`].
+///
+/// [`Open::splice`]: ../struct.Open.html#method.splice
+pub trait SpliceBuf: SendBuf {
+    /// The number of bytes that can currently be appended.
+    fn spare(&self) -> usize;
+
+    /// Append data after all available data.
+    ///
+    /// Never called with more data than `spare` permits.
+    fn append(&mut self, data: &[u8]);
+}
+
+/// A user defined segment reassembly buffer.
+///
+/// The connection pushes the data of all acceptable segments into the buffer, possibly out of
+/// order or overlapping already received data. Afterwards, it acknowledges the remote up to the
+/// sequence number returned by `ack` and advertises the size of the `window`.
+pub trait RecvBuf {
+    /// Accept some incoming data.
+    ///
+    /// Report back the new unreceived byte. This allows the receive buffer to choose the strategy
+    /// for partially acknowledged data.
+    fn receive(&mut self, buf: &[u8], segment: ReceivedSegment);
+
+    /// Get the highest completed sequence number.
+    fn ack(&mut self) -> TcpSeqNumber;
+
+    /// Get the current window size.
+    ///
+    /// Shrinking the window size without having accepted new data is allowed but strongly
+    /// discouraged.
+    fn window(&self) -> usize;
+
+    /// Check if the data of a segment may be spliced into another connection.
+    ///
+    /// This requires that the data directly follows all data read so far, it would otherwise be
+    /// forwarded out of order. The default never permits it.
+    fn can_skip(&self, segment: &ReceivedSegment) -> bool {
+        let _ = segment;
+        false
+    }
+
+    /// Pass over the data of a segment that was spliced into another connection.
+    ///
+    /// The data is not stored, the buffer only accounts for it as if it had been received and
+    /// read immediately. Only called after `can_skip` permitted it.
+    fn skip(&mut self, segment: ReceivedSegment) {
+        let _ = segment;
+    }
+}
+
+/// A send buffer in a ring of fixed storage.
+///
+/// Data written by the application is kept until it has been acknowledged by the remote, the
+/// remaining storage limits how much more can be written.
+#[derive(Debug)]
+pub struct SendRing<'a> {
+    ring: ByteRing<'a>,
+    /// The sequence number of the first byte in the ring.
+    at: Option<TcpSeqNumber>,
+    /// The application will not write any more data.
+    fin: bool,
+    /// Push the data whenever the buffer is emptied.
+    push: bool,
+}
+
+/// A receive buffer in a ring of fixed storage.
+///
+/// The free storage is advertised as the receive window. Out-of-order segments are reassembled in
+/// the free part as well and become readable once all preceding data arrived.
+#[derive(Debug)]
+pub struct RecvRing<'a> {
+    ring: ByteRing<'a>,
+    /// The sequence number following the last readable byte.
+    next: Option<TcpSeqNumber>,
+    /// Out-of-order data after the readable bytes.
+    asm: Assembler<[Contig; 4]>,
+    /// The remote will not send any more data.
+    fin: bool,
+}
+
+/// A tcp handler for the data stream of a single, existing connection.
+///
+/// Whenever a packet arrives on the connection, its data is read into the receive buffer and
+/// possibly an answer with data from the send buffer is written. When sending, it attaches to the
+/// connection and writes new data or retransmissions. This works with any connection, such as one
+/// accepted on a listening socket, while the [`Client`] also performs the active open.
+///
+/// [`Client`]: ../struct.Client.html
+pub struct Stream<R, S> {
+    key: Option<SlotKey>,
+    recv: R,
+    send: S,
+}
+
+impl<'a> SendRing<'a> {
+    /// Create an empty send buffer.
+    pub fn new<S>(storage: S) -> Self
+        where S: Into<Slice<'a, u8>>,
+    {
+        SendRing {
+            ring: ByteRing::new(storage),
+            at: None,
+            fin: false,
+            push: false,
+        }
+    }
+
+    /// Append as much data as fits, returning the number of bytes written.
+    ///
+    /// Nothing is written once the stream has been closed.
+    pub fn write(&mut self, data: &[u8]) -> usize {
+        if self.fin {
+            return 0;
+        }
+
+        self.ring.enqueue_slice(data)
+    }
+
+    /// Write data in place.
+    ///
+    /// The closure is called with contiguous free storage and returns how many bytes it filled.
+    /// Those are appended to the stream. Nothing is written once the stream has been closed.
+    pub fn write_with<F>(&mut self, f: F) -> usize
+        where F: FnOnce(&mut [u8]) -> usize,
+    {
+        if self.fin {
+            return 0;
+        }
+
+        self.ring.enqueue_many_with(|free| {
+            let written = f(free);
+            (written, ())
+        }).0
+    }
+
+    /// The number of bytes that can currently be written.
+    pub fn window(&self) -> usize {
+        self.ring.window()
+    }
+
+    /// The number of bytes not yet acknowledged by the remote.
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Check if all written data has been acknowledged.
+    pub fn is_empty(&self) -> bool {
+        self.ring.is_empty()
+    }
+
+    /// Indicate that no more data will be written.
+    ///
+    /// The FIN is sent after all data in the buffer.
+    pub fn close(&mut self) {
+        self.fin = true;
+    }
+
+    /// Check if the stream has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.fin
+    }
+
+    /// Set the PSH flag on the segment that sends the last written byte.
+    ///
+    /// This asks the remote to hand the data to its application without waiting for more. Off
+    /// by default.
+    pub fn set_push(&mut self, push: bool) {
+        self.push = push;
+    }
+}
+
+impl<'a> RecvRing<'a> {
+    /// Create an empty receive buffer.
+    pub fn new<S>(storage: S) -> Self
+        where S: Into<Slice<'a, u8>>,
+    {
+        RecvRing {
+            ring: ByteRing::new(storage),
+            next: None,
+            asm: Assembler::new([Contig::default(); 4]),
+            fin: false,
+        }
+    }
+
+    /// Read received data, returning the number of bytes read.
+    ///
+    /// This frees storage and consequently opens the receive window again.
+    pub fn read(&mut self, data: &mut [u8]) -> usize {
+        self.ring.dequeue_slice(data)
+    }
+
+    /// Read received data in place.
+    ///
+    /// The closure is called with the contiguous readable bytes and returns how many of them it
+    /// consumed. Those are removed from the buffer.
+    pub fn read_with<F>(&mut self, f: F) -> usize
+        where F: FnOnce(&[u8]) -> usize,
+    {
+        self.ring.dequeue_many_with(|data| {
+            let consumed = f(data);
+            (consumed, ())
+        }).0
+    }
+
+    /// Move the readable data to the start of the storage.
+    ///
+    /// Afterwards, `read_with` offers all readable data at once. This copies the storage and is
+    /// only needed when a reader requires more data than is contiguous.
+    pub fn make_contiguous(&mut self) {
+        self.ring.make_contiguous();
+    }
+
+    /// The number of bytes that can be read.
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    /// Check if there is no data to read.
+    pub fn is_empty(&self) -> bool {
+        self.ring.is_empty()
+    }
+
+    /// Check if the remote closed the stream.
+    ///
+    /// There may still be data to read.
+    pub fn is_closed(&self) -> bool {
+        self.fin
+    }
+}
+
+impl<R, S> Stream<R, S> {
+    /// Create a handler for the data stream of an existing connection.
+    pub fn new(key: SlotKey, recv: R, send: S) -> Self {
+        Stream {
+            key: Some(key),
+            recv,
+            send,
+        }
+    }
+
+    /// Get a reference to the receive buffer.
+    pub fn recv(&self) -> &R {
+        &self.recv
+    }
+
+    /// Get a mutable reference to the receive buffer.
+    pub fn recv_mut(&mut self) -> &mut R {
+        &mut self.recv
+    }
+
+    /// Get a reference to the send buffer.
+    pub fn send(&self) -> &S {
+        &self.send
+    }
+
+    /// Get a mutable reference to the send buffer.
+    pub fn send_mut(&mut self) -> &mut S {
+        &mut self.send
+    }
+
+    /// Get the key of the connection, unless it was closed.
+    pub fn connection_key(&self) -> Option<SlotKey> {
+        self.key
+    }
+
+    /// Check if the connection was closed.
+    pub fn is_closed(&self) -> bool {
+        self.key.is_none()
+    }
+
+    /// Unwrap the receive and send buffer.
+    pub fn into_inner(self) -> (R, S) {
+        (self.recv, self.send)
+    }
+}
+
+impl SendBuf for SendRing<'_> {
+    fn available(&self) -> AvailableBytes {
+        AvailableBytes {
+            total: self.ring.len(),
+            fin: self.fin,
+            push: self.push,
+        }
+    }
+
+    fn fill(&mut self, buf: &mut [u8], begin: TcpSeqNumber) {
+        let at = self.at.expect("Fill must not be called before isn indication");
+        let read = self.ring.read_allocated(begin - at, buf);
+        assert_eq!(read, buf.len(), "Requested data outside the available bytes");
+    }
+
+    fn ack(&mut self, ack: TcpSeqNumber) {
+        let previous = *self.at.get_or_insert(ack);
+        if ack > previous {
+            let acked = (ack - previous).min(self.ring.len());
+            self.ring.dequeue_allocated(acked);
+            self.at = Some(ack);
+        }
+    }
+}
+
+impl SpliceBuf for SendRing<'_> {
+    fn spare(&self) -> usize {
+        if self.fin {
+            0
+        } else {
+            self.ring.window()
+        }
+    }
+
+    fn append(&mut self, data: &[u8]) {
+        let written = self.ring.enqueue_slice(data);
+        assert_eq!(written, data.len(), "Appended data beyond the spare storage");
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::Write for SendRing<'_> {
+    /// Append as many bytes as possible.
+    ///
+    /// A full buffer reports that it would block, a closed stream that its pipe is broken.
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.fin {
+            return Err(std::io::ErrorKind::BrokenPipe.into());
+        }
+
+        std::io::Write::write(&mut self.ring, buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl RecvBuf for RecvRing<'_> {
+    fn receive(&mut self, mut data: &[u8], segment: ReceivedSegment) {
+        let next = self.next.get_or_insert(segment.data_begin());
+        if self.fin {
+            return;
+        }
+
+        let begin = segment.data_begin();
+        let offset = if begin < *next {
+            let skip = *next - begin;
+            if skip > data.len() {
+                return;
+            }
+            data = &data[skip..];
+            0
+        } else {
+            begin - *next
+        };
+
+        let window = self.ring.window();
+        if offset > window {
+            return;
+        }
+
+        let length = data.len().min(window - offset);
+        let written = self.ring.write_unallocated(offset, &data[..length]);
+        debug_assert_eq!(written, length);
+
+        // AS: the window is small enough for the sequence space, and so is `offset + length`.
+        let new_data = match self.asm.add(offset as u32, length as u32) {
+            Ok(new) => new as usize,
+            // Can not track the data, drop it.
+            Err(()) => return,
+        };
+
+        self.ring.enqueue_unallocated(new_data);
+        *next += new_data;
+
+        // The FIN is only accepted with all data before it.
+        if segment.fin && *next == segment.data_end() {
+            self.fin = true;
+            *next += 1;
+        }
+    }
+
+    fn ack(&mut self) -> TcpSeqNumber {
+        self.next.expect("Must not be called before any isn indication")
+    }
+
+    fn window(&self) -> usize {
+        self.ring.window()
+    }
+
+    fn can_skip(&self, segment: &ReceivedSegment) -> bool {
+        let in_order = self.next.is_none_or(|next| next == segment.data_begin());
+        // Unread or out-of-order data would be overtaken by the skipped one.
+        in_order && !self.fin && self.ring.is_empty() && self.asm.is_empty()
+    }
+
+    fn skip(&mut self, segment: ReceivedSegment) {
+        let next = self.next.get_or_insert(segment.data_begin());
+        *next += segment.data_len;
+    }
+}
+
+impl<R, S, P> Recv<P> for &'_ mut Stream<R, S>
+where
+    R: RecvBuf,
+    S: SendBuf,
+    P: PayloadMut,
+{
+    fn receive(&mut self, packet: InPacket<P>) {
+        let key = match self.key {
+            Some(key) => key,
+            None => return,
+        };
+
+        // Not a packet for our connection. Ignore.
+        if packet.key() != Some(key) {
+            return;
+        }
+
+        match packet {
+            InPacket::Stray(_) | InPacket::Sending(_) => (),
+            InPacket::Closed(_) | InPacket::Closing(_) => self.key = None,
+            InPacket::Open(mut open) => {
+                open.read(&mut self.recv);
+                if let Ok(Err(_closing)) = open.write(&mut self.send) {
+                    self.key = None;
+                }
+            },
+        }
+    }
+}
+
+impl<R, S, P> Send<P> for &'_ mut Stream<R, S>
+where
+    R: RecvBuf,
+    S: SendBuf,
+    P: PayloadMut,
+{
+    fn send(&mut self, packet: RawPacket<P>) {
+        let key = match self.key {
+            Some(key) => key,
+            None => return,
+        };
+
+        let mut open = match packet.attach(key) {
+            Ok(open) => open,
+            // The connection no longer exists.
+            Err(_) => return self.key = None,
+        };
+
+        open.offer_window(&self.recv);
+        if let Ok(Err(_closing)) = open.write(&mut self.send) {
+            self.key = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::Instant;
+
+    fn segment(begin: TcpSeqNumber, data_len: usize, fin: bool) -> ReceivedSegment {
+        ReceivedSegment {
+            syn: false,
+            fin,
+            data_len,
+            begin,
+            timestamp: Instant::from_millis(0),
+        }
+    }
+
+    #[test]
+    fn send_ring() {
+        let isn = TcpSeqNumber(100);
+        let mut send = SendRing::new(vec![0; 8]);
+        assert_eq!(send.write(b"0123456789"), 8);
+        send.ack(isn);
+        assert_eq!(send.available().total, 8);
+
+        let mut buf = [0; 4];
+        send.fill(&mut buf, isn + 2);
+        assert_eq!(&buf, b"2345");
+
+        send.ack(isn + 6);
+        assert_eq!(send.len(), 2);
+        assert_eq!(send.write(b"89"), 2);
+        send.fill(&mut buf, isn + 6);
+        assert_eq!(&buf, b"6789");
+
+        send.close();
+        assert_eq!(send.write(b"a"), 0);
+        assert!(send.available().fin);
+    }
+
+    #[test]
+    fn recv_ring() {
+        let isn = TcpSeqNumber(-4);
+        let mut recv = RecvRing::new(vec![0; 8]);
+
+        recv.receive(b"0123", segment(isn, 4, false));
+        assert_eq!(recv.ack(), isn + 4);
+        assert_eq!(recv.window(), 4);
+
+        // Out of order, and partially beyond the window.
+        recv.receive(b"67890", segment(isn + 6, 5, true));
+        assert_eq!(recv.ack(), isn + 4);
+
+        let mut buf = [0; 8];
+        assert_eq!(recv.read(&mut buf[..2]), 2);
+        assert_eq!(&buf[..2], b"01");
+
+        recv.receive(b"45", segment(isn + 4, 2, false));
+        assert_eq!(recv.ack(), isn + 8);
+        assert_eq!(recv.len(), 6);
+        assert_eq!(recv.read_with(|data| data.len()), 6);
+
+        // The retransmitted remainder including the FIN.
+        recv.receive(b"890", segment(isn + 8, 3, true));
+        assert_eq!(recv.ack(), isn + 12);
+        assert!(recv.is_closed());
+        assert_eq!(recv.read(&mut buf), 3);
+        assert_eq!(&buf[..3], b"890");
+    }
+
+    proptest::proptest! {
+        /// Segments arriving out of order, duplicated or dropped and then retransmitted in order
+        /// still produce the original stream.
+        #[test]
+        fn recv_ring_reassembles(
+            data in proptest::collection::vec(0u8.., 1..64),
+            mut cuts in proptest::collection::vec(0usize..64, 0..8),
+            arrivals in proptest::collection::vec(0usize..16, 0..16),
+        ) {
+            cuts.push(0);
+            cuts.push(data.len());
+            cuts.retain(|&cut| cut <= data.len());
+            cuts.sort();
+            cuts.dedup();
+            let segments: Vec<_> = cuts.windows(2).map(|cut| cut[0]..cut[1]).collect();
+
+            let isn = TcpSeqNumber(i32::max_value() - 16);
+            let mut recv = RecvRing::new(vec![0; 64]);
+            recv.receive(&[], segment(isn, 0, false));
+
+            let retransmit = 0..segments.len();
+            for idx in arrivals.into_iter().map(|idx| idx % segments.len()).chain(retransmit) {
+                let range = segments[idx].clone();
+                let last = range.end == data.len();
+                recv.receive(&data[range.clone()], segment(isn + range.start, range.len(), last));
+            }
+
+            let mut buf = [0; 64];
+            proptest::prop_assert_eq!(recv.ack(), isn + data.len() + 1);
+            proptest::prop_assert_eq!(recv.read(&mut buf), data.len());
+            proptest::prop_assert_eq!(&buf[..data.len()], &data[..]);
+            proptest::prop_assert!(recv.is_closed());
+        }
+    }
+}
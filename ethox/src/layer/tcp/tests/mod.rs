@@ -0,0 +1,565 @@
+//! Tcp layer tests.
+//!
+//! For the lack of proper end-to-end connection testing—which would require a very lengthy setup
+//! we instead tests components and pieces. A better test suite would implement some protocol on
+//! top of tcp and test against other implementations. Due to the abundance of options and allowed
+//! implementation specific behaviour it has proven quite hard to conduct this as a black-box test.
+//! Hence, see also the example binary for tcp echo.
+//!
+//! The `script` module instead drives a single endpoint against a scripted remote in virtual time,
+//! so that the segments exchanged during handshakes, retransmissions and closes can be checked one
+//! by one.
+mod samples;
+mod script;
+mod splice;
+
+use script::run;
+
+#[test]
+fn passive_open() {
+    run("
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024 <mss 536>
+        0.000 > S. 0:0(0) ack 1 win 0
+        +0.010 < . 1:1(0) ack 1 win 1024
+        +0.000 > . 1:1(0) ack 1 win 4096
+        0.010 state Established
+    ");
+}
+
+#[test]
+fn active_open() {
+    run("
+        0.000 connect 80
+        0.000 > S 0:0(0)
+        0.000 state SynSent
+        +0.010 < S. 0:0(0) ack 1 win 1024 <mss 536>
+        0.010 > . 1:1(0) ack 1
+        0.010 state Established
+    ");
+}
+
+#[test]
+fn mtu() {
+    run("
+        0.000 mtu 576
+        0.000 listen 80
+        // The announced segment size leaves room for the ip and tcp headers.
+        0.000 < S 0:0(0) win 4096 <mss 1460>
+        0.000 > S. 0:0(0) ack 1 <mss 536>
+        +0.010 < . 1:1(0) ack 1 win 4096
+        +0.000 > . 1:1(0) ack 1 win 4096
+
+        // Segments are limited by the local transmission unit, not the one of the remote.
+        0.020 write 600
+        0.020 > . 1:537(536) ack 1
+        0.020 > . 537:601(64) ack 1
+    ");
+}
+
+#[test]
+fn mtu_active_open() {
+    run("
+        0.000 mtu 576
+        0.000 connect 80
+        0.000 > S 0:0(0) <mss 536>
+        +0.010 < S. 0:0(0) ack 1 win 4096 <mss 1460>
+        0.010 > . 1:1(0) ack 1
+        0.010 state Established
+
+        0.020 write 600
+        0.020 > . 1:537(536) ack 1
+        0.020 > . 537:601(64) ack 1
+    ");
+}
+
+#[test]
+fn jumbo() {
+    run("
+        0.000 mtu 9000
+        0.000 listen 80
+        0.000 < S 0:0(0) win 16384 <mss 8960>
+        0.000 > S. 0:0(0) ack 1 <mss 8960>
+        +0.010 < . 1:1(0) ack 1 win 16384
+        +0.000 > . 1:1(0) ack 1 win 4096
+
+        // A single segment carries more than a standard frame.
+        0.020 write 4000
+        0.020 > . 1:4001(4000) ack 1
+        +0.010 < . 1:1(0) ack 4001 win 16384
+    ");
+}
+
+#[test]
+fn syn_retransmission() {
+    run("
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024
+        0.000 > S. 0:0(0) ack 1
+        // The handshake is not completed, retransmit after the initial timeout.
+        3.000 > S. 0:0(0) ack 1
+        +0.100 < . 1:1(0) ack 1 win 1024
+        +0.000 > . 1:1(0) ack 1 win 4096
+        3.100 state Established
+    ");
+}
+
+#[test]
+fn data_retransmission() {
+    run("
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024 <mss 536>
+        0.000 > S. 0:0(0) ack 1
+        +0.010 < . 1:1(0) ack 1 win 1024
+        +0.000 > . 1:1(0) ack 1 win 4096
+
+        // Data is acknowledged immediately, offering the remaining buffer.
+        0.020 < P. 1:101(100) ack 1 win 1024
+        0.020 > . 1:1(0) ack 101 win 3996
+        0.030 read 100
+
+        // The timer starts with the first outstanding segment.
+        0.030 write 200
+        0.030 > . 1:201(200) ack 101
+        3.030 > . 1:201(200) ack 101
+        +0.010 < . 101:101(0) ack 201 win 1024
+    ");
+}
+
+#[test]
+fn early_data() {
+    run("
+        0.000 listen 80
+        // The listening connection is offered buffers before any segment arrives.
+        1.000 < S 0:0(0) win 1024 <mss 536>
+        1.000 > S. 0:0(0) ack 1
+
+        // Data written before the handshake completes is sent afterwards, from its first byte.
+        1.005 write 100
+        +0.005 < . 1:1(0) ack 1 win 1024
+        1.010 > . 1:101(100) ack 1
+        +0.010 < . 1:1(0) ack 101 win 1024
+    ");
+}
+
+#[test]
+fn ack_coalescing() {
+    run("
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024 <mss 536>
+        0.000 > S. 0:0(0) ack 1
+        +0.010 < . 1:1(0) ack 1 win 1024
+        +0.000 > . 1:1(0) ack 1 win 4096
+
+        // Without coalescing each segment of a batch is acknowledged.
+        0.020 < P. 1:101(100) ack 1 win 1024
+        0.020 < P. 101:201(100) ack 1 win 1024
+        0.020 > . 1:1(0) ack 101 win 3996
+        0.020 > . 1:1(0) ack 201 win 3896
+        0.030 read 200
+
+        // With it, the whole batch is acknowledged once afterwards. The space that was read is
+        // less than a segment, the window is not reopened yet.
+        0.030 coalesce
+        0.040 < P. 201:301(100) ack 1 win 1024
+        0.040 < P. 301:401(100) ack 1 win 1024
+        0.040 > . 1:1(0) ack 401 win 3696
+        0.050 read 200
+    ");
+}
+
+#[test]
+fn passive_close() {
+    run("
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024 <mss 536>
+        0.000 > S. 0:0(0) ack 1
+        +0.010 < . 1:1(0) ack 1 win 1024
+        +0.000 > . 1:1(0) ack 1 win 4096
+
+        0.020 < F. 1:1(0) ack 1 win 1024
+        0.020 > . 1:1(0) ack 2
+        0.020 state CloseWait
+        0.020 eof
+
+        0.030 close
+        0.030 > F. 1:1(0) ack 2
+        0.030 state LastAck
+        +0.010 < . 2:2(0) ack 2 win 1024
+    ");
+}
+
+#[test]
+fn active_close() {
+    run("
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024 <mss 536>
+        0.000 > S. 0:0(0) ack 1
+        +0.010 < . 1:1(0) ack 1 win 1024
+        +0.000 > . 1:1(0) ack 1 win 4096
+
+        0.020 close
+        0.020 > F. 1:1(0) ack 1
+        0.020 state FinWait1
+        +0.010 < . 1:1(0) ack 2 win 1024
+        0.035 state FinWait2
+
+        0.040 < F. 1:1(0) ack 2 win 1024
+        0.040 > . 2:2(0) ack 2
+        0.040 state TimeWait
+    ");
+}
+
+#[test]
+#[should_panic(expected = "unexpected segment")]
+fn missing_expectation() {
+    run("
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024
+    ");
+}
+
+#[test]
+#[should_panic(expected = "script line 4")]
+fn mismatched_expectation() {
+    run("
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024
+        0.000 > S. 0:0(0) ack 2
+    ");
+}
+
+#[test]
+fn connection_events() {
+    run("
+        0.000 connect 80
+        0.000 > S 0:0(0)
+        0.000 events
+        +0.010 < S. 0:0(0) ack 1 win 1024 <mss 536>
+        0.010 > . 1:1(0) ack 1
+        0.010 events established
+
+        0.020 write 200
+        0.020 > . 1:201(200) ack 1
+        +0.010 < . 1:1(0) ack 101 win 1024
+        +0.010 < P. 1:51(50) ack 201 win 1024
+        0.040 > . 201:201(0) ack 51
+        0.040 events acked:100 acked:100 push
+        0.050 read 50
+
+        // The FIN is reported once the stream buffer accepted the data before it.
+        0.060 < FP. 51:101(50) ack 201 win 1024
+        0.060 > . 201:201(0) ack 102
+        0.060 events push fin
+        0.070 read 50
+
+        0.080 close
+        0.080 > F. 201:201(0) ack 102
+        +0.010 < . 102:102(0) ack 202 win 1024
+        0.090 events
+    ");
+}
+
+#[test]
+fn reset_event() {
+    run("
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024 <mss 536>
+        0.000 > S. 0:0(0) ack 1
+        +0.010 < . 1:1(0) ack 1 win 1024
+        +0.000 > . 1:1(0) ack 1 win 4096
+        0.010 events established
+        0.020 < R 1:1(0)
+        0.020 events reset
+    ");
+}
+
+#[test]
+fn close_all() {
+    run("
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024 <mss 536>
+        0.000 > S. 0:0(0) ack 1
+        +0.010 < . 1:1(0) ack 1 win 1024
+        +0.000 > . 1:1(0) ack 1 win 4096
+
+        0.020 write 100
+        0.020 > . 1:101(100) ack 1
+        0.025 closeall 1.000
+        0.025 > F. 101:101(0) ack 1
+        0.025 drain 1 0
+        +0.010 < . 1:1(0) ack 102 win 1024
+        0.035 state FinWait2
+
+        // The remote never closes its side and is reset at the deadline.
+        1.000 > R. 102:102(0) ack 1
+        1.000 drain 0 1
+    ");
+}
+
+#[test]
+fn nagle() {
+    run("
+        0.000 option nagle
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024 <mss 536>
+        0.000 > S. 0:0(0) ack 1
+        +0.010 < . 1:1(0) ack 1 win 1024
+        +0.000 > . 1:1(0) ack 1 win 4096
+
+        0.020 write 100
+        0.020 > . 1:101(100) ack 1
+        // A small segment waits for the acknowledgement of the data in flight.
+        0.030 write 100
+        +0.010 < . 1:1(0) ack 101 win 1024
+        0.040 > . 101:201(100) ack 1
+        +0.010 < . 1:1(0) ack 201 win 1024
+    ");
+}
+
+#[test]
+fn pacing() {
+    run("
+        0.000 option pacing 100000
+        0.000 listen 80
+        0.000 < S 0:0(0) win 4096 <mss 536>
+        0.000 > S. 0:0(0) ack 1
+        +0.010 < . 1:1(0) ack 1 win 4096
+        +0.000 > . 1:1(0) ack 1 win 4096
+
+        // A full segment takes 5.36ms at the rate, the window would allow all of them at once.
+        0.020 write 1500
+        0.020 > . 1:537(536) ack 1
+        0.025 > . 537:1073(536) ack 1
+        // Acknowledgments are not held back.
+        0.026 < . 1:1(0) ack 1073 win 4096
+        0.030 > . 1073:1501(428) ack 1
+        +0.010 < . 1:1(0) ack 1501 win 4096
+    ");
+}
+
+#[test]
+fn buffer_limits() {
+    run("
+        0.000 buffers 1000 2048
+        0.000 listen 80
+        0.000 < S 0:0(0) win 4096 <mss 536>
+        0.000 > S. 0:0(0) ack 1
+        +0.010 < . 1:1(0) ack 1 win 4096
+        // The window is capped by the receive limit.
+        +0.000 > . 1:1(0) ack 1 win 2048
+
+        // Only the send limit may be in flight, even though the remote window is larger.
+        0.020 write 1500
+        0.020 > . 1:537(536) ack 1
+        0.020 > . 537:1001(464) ack 1
+        0.030 < . 1:1(0) ack 1001 win 4096
+        0.030 > . 1001:1501(500) ack 1
+        +0.010 < . 1:1(0) ack 1501 win 4096
+    ");
+}
+
+#[test]
+#[should_panic(expected = "could not listen")]
+fn buffer_limits_exhausted() {
+    run("
+        // Two connections would get less than a segment each.
+        0.000 buffers 1000 4096
+        0.000 listen 80
+        0.000 listen 81
+    ");
+}
+
+#[test]
+fn reset_address() {
+    run("
+        0.000 listen 80
+        0.000 < S 0:0(0) win 4096 <mss 536>
+        0.000 > S. 0:0(0) ack 1
+        +0.010 < . 1:1(0) ack 1 win 4096
+        +0.000 > . 1:1(0) ack 1 win 4096
+
+        // The connection is gone without a reset, its data is no longer acknowledged.
+        0.020 unassign
+        0.030 < . 1:101(100) ack 1 win 4096
+    ");
+}
+
+#[test]
+fn keepalive() {
+    run("
+        0.000 option keepalive 10.000
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024 <mss 536>
+        0.000 > S. 0:0(0) ack 1
+        +0.010 < . 1:1(0) ack 1 win 1024
+        +0.000 > . 1:1(0) ack 1 win 4096
+
+        // The probe repeats the last acknowledged sequence number after the idle time.
+        10.010 > . 0:0(0) ack 1
+        +0.010 < . 1:1(0) ack 1 win 1024
+        20.020 > . 0:0(0) ack 1
+    ");
+}
+
+#[test]
+fn receive_window() {
+    run("
+        0.000 option window 1000
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024 <mss 536>
+        0.000 > S. 0:0(0) ack 1
+        +0.010 < . 1:1(0) ack 1 win 1024
+        +0.000 > . 1:1(0) ack 1 win 1000
+
+        // The advertised window is limited by the option rather than the buffer.
+        0.020 < P. 1:101(100) ack 1 win 1024
+        0.020 > . 1:1(0) ack 101 win 900
+        0.030 read 100
+    ");
+}
+
+#[test]
+fn max_retransmissions() {
+    run("
+        0.000 option retries 2
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024 <mss 536>
+        0.000 > S. 0:0(0) ack 1
+        +0.010 < . 1:1(0) ack 1 win 1024
+        +0.000 > . 1:1(0) ack 1 win 4096
+        0.010 events established
+
+        0.020 write 100
+        0.020 > . 1:101(100) ack 1
+        3.020 > . 1:101(100) ack 1
+        6.020 > . 1:101(100) ack 1
+        // The remote is gone, instead of a third retransmission the connection is reset.
+        9.020 > R. 101:101(0) ack 1
+        9.020 events timeout
+    ");
+}
+
+#[test]
+fn user_timeout() {
+    run("
+        0.000 option uto 5.000
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024 <mss 536>
+        0.000 > S. 0:0(0) ack 1 <uto 5>
+        +0.010 < . 1:1(0) ack 1 win 1024
+        +0.000 > . 1:1(0) ack 1 win 4096
+
+        0.020 write 100
+        0.020 > . 1:101(100) ack 1
+        3.020 > . 1:101(100) ack 1
+        5.020 > R. 101:101(0) ack 1
+        5.020 events established timeout
+    ");
+}
+
+#[test]
+fn remote_user_timeout() {
+    // The timeout of the remote is adopted, but not below 100 seconds.
+    run("
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024 <mss 536,uto 30>
+        0.000 > S. 0:0(0) ack 1 <uto 100>
+    ");
+    // The highest bit selects minutes.
+    run("
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024 <mss 536,uto 32770>
+        0.000 > S. 0:0(0) ack 1 <uto 120>
+    ");
+}
+
+#[test]
+fn silly_window_sender() {
+    run("
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024 <mss 536>
+        0.000 > S. 0:0(0) ack 1
+        +0.010 < . 1:1(0) ack 1 win 1024
+        +0.000 > . 1:1(0) ack 1 win 4096
+
+        // The rest of the window is less than a segment and half the window, it waits.
+        0.020 write 2000
+        0.020 > . 1:537(536) ack 1
+        +0.010 < . 1:1(0) ack 537 win 1024
+        0.030 > . 537:1073(536) ack 1
+        +0.010 < . 1:1(0) ack 1073 win 1024
+        0.040 > . 1073:1609(536) ack 1
+        // The last small segment is limited by the data instead, it is sent right away.
+        0.040 > . 1609:2001(392) ack 1
+        +0.010 < . 1:1(0) ack 2001 win 1024
+    ");
+}
+
+#[test]
+fn silly_window_receiver() {
+    run("
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024 <mss 536>
+        0.000 > S. 0:0(0) ack 1
+        +0.010 < . 1:1(0) ack 1 win 1024
+        +0.000 > . 1:1(0) ack 1 win 4096
+
+        0.020 < P. 1:101(100) ack 1 win 1024
+        0.020 > . 1:1(0) ack 101 win 3996
+        0.030 read 100
+        // Reading less than a segment does not reopen the window.
+        0.040 < P. 101:701(600) ack 1 win 1024
+        0.040 > . 1:1(0) ack 701 win 3396
+        0.050 read 600
+        // Reading a full segment does, the remote learns of it with a window update.
+        0.050 > . 1:1(0) ack 701 win 4096
+        0.060 < P. 701:801(100) ack 1 win 1024
+        0.060 > . 1:1(0) ack 801 win 3996
+        0.070 read 100
+    ");
+}
+
+#[test]
+fn push() {
+    run("
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024 <mss 536>
+        0.000 > S. 0:0(0) ack 1
+        +0.010 < . 1:1(0) ack 1 win 1024
+        +0.000 > . 1:1(0) ack 1 win 4096
+
+        // Only the segment with the last written byte is pushed.
+        0.020 push
+        0.020 write 600
+        0.020 > . 1:537(536) ack 1
+        0.020 > P. 537:601(64) ack 1
+        +0.010 < . 1:1(0) ack 601 win 1024
+
+        // The handler learns which received data was pushed.
+        0.040 < . 1:101(100) ack 601 win 1024
+        0.040 > . 601:601(0) ack 101
+        0.040 events established acked:600
+        0.050 < P. 101:201(100) ack 601 win 1024
+        0.050 > . 601:601(0) ack 201
+        0.050 events push
+        0.060 read 200
+    ");
+}
+
+#[test]
+fn urgent_inline() {
+    run("
+        0.000 listen 80
+        0.000 < S 0:0(0) win 1024 <mss 536>
+        0.000 > S. 0:0(0) ack 1
+        +0.010 < . 1:1(0) ack 1 win 1024
+        +0.000 > . 1:1(0) ack 1 win 4096
+
+        // The urgent data is marked but stays part of the stream.
+        0.020 < UP. 1:101(100) ack 1 win 1024 urg 50
+        0.020 > . 1:1(0) ack 101
+        0.020 events established push urgent:51
+        0.030 read 100
+    ");
+}
@@ -0,0 +1,172 @@
+//! The sample services against a client on the same endpoint.
+use crate::layer::{arp, eth, ip, tcp};
+use crate::layer::tcp::samples::{Chargen, Discard, Echo};
+use crate::layer::tcp::stream::{RecvRing, SendRing};
+use crate::managed::{List, Map, Slice, SlotMap};
+use crate::nic::{loopback::Loopback, Device};
+use crate::wire::{EthernetAddress, IpCidr, Ipv4Address, PayloadMut};
+
+const MAC_ADDR: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 5]);
+const IP_ADDR: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
+
+type Client = tcp::Client<RecvRing<'static>, SendRing<'static>>;
+
+/// A service and a client connected to it.
+struct Pair<S> {
+    service: S,
+    client: Client,
+}
+
+struct Host {
+    nic: Loopback<'static, Vec<u8>>,
+    eth: eth::Endpoint<'static>,
+    ip: ip::Endpoint<'static>,
+    tcp: tcp::Endpoint<'static>,
+}
+
+/// Dispatch packets to the client or the service.
+macro_rules! pair {
+    ($service:ty) => {
+        impl<P: PayloadMut> tcp::Recv<P> for &'_ mut Pair<$service> {
+            fn receive(&mut self, packet: tcp::InPacket<P>) {
+                if packet.key().is_some() && packet.key() == self.client.connection_key() {
+                    (&mut self.client).receive(packet)
+                } else {
+                    (&mut self.service).receive(packet)
+                }
+            }
+        }
+    };
+}
+
+pair!(Echo<'_>);
+pair!(Discard);
+pair!(Chargen);
+
+impl Host {
+    fn new() -> Self {
+        let mut neighbors = arp::NeighborCache::new(vec![arp::Neighbor::default(); 1]);
+        neighbors.fill(IP_ADDR.into(), MAC_ADDR, None).unwrap();
+        Host {
+            nic: Loopback::new(Slice::Many(vec![vec![0; 1514]; 8])),
+            eth: eth::Endpoint::new(MAC_ADDR),
+            ip: ip::Endpoint::new(IpCidr::new(IP_ADDR.into(), 24),
+                ip::Routes::new(vec![ip::Route::unspecified(); 1]),
+                neighbors),
+            tcp: tcp::Endpoint::new(
+                Map::Pairs(List::new(Slice::Many(vec![Default::default(); 4]))),
+                SlotMap::new(
+                    Slice::Many(vec![Default::default(); 4]),
+                    Slice::Many(vec![Default::default(); 4])),
+                tcp::IsnGenerator::from_secret_key_bytes([0; 16])),
+        }
+    }
+
+    fn client(&mut self, port: u16) -> Client {
+        let ring = RecvRing::new(vec![0; 1024]);
+        let send = SendRing::new(vec![0; 1024]);
+        tcp::Client::new(IP_ADDR.into(), port, ring, send)
+    }
+
+    /// Let both sides send once and deliver all packets.
+    fn round<S>(&mut self, pair: &mut Pair<S>)
+        where
+            for<'s> &'s mut S: tcp::Send<Vec<u8>>,
+            for<'s> &'s mut Pair<S>: tcp::Recv<Vec<u8>>,
+    {
+        let Host { nic, eth, ip, tcp } = self;
+        let _ = nic.tx(1, eth.send(ip.send(tcp.send(&mut pair.service))));
+        let _ = nic.tx(1, eth.send(ip.send(tcp.send(&mut pair.client))));
+        while nic.pending() > 0 {
+            let pending = nic.pending();
+            let _ = nic.rx(pending, eth.recv(ip.recv(tcp.recv(&mut *pair))));
+        }
+    }
+}
+
+#[test]
+fn echo() {
+    let mut host = Host::new();
+    let key = host.tcp.listen(IP_ADDR.into(), Echo::PORT).unwrap();
+    let mut pair = Pair {
+        service: Echo::new(key, vec![0; 512], vec![0; 512]),
+        client: host.client(Echo::PORT),
+    };
+
+    // More than both buffers of the service can hold.
+    let data: Vec<u8> = (0..2000).map(|i| i as u8).collect();
+    let mut written = 0;
+    let mut received = Vec::new();
+
+    for _ in 0..64 {
+        written += pair.client.send_mut().write(&data[written..]);
+        host.round(&mut pair);
+        let mut buffer = [0; 1024];
+        let read = pair.client.recv_mut().read(&mut buffer);
+        received.extend_from_slice(&buffer[..read]);
+    }
+
+    assert_eq!(received, data);
+    assert_eq!(pair.service.echoed(), data.len());
+}
+
+#[test]
+fn discard() {
+    let mut host = Host::new();
+    let key = host.tcp.listen(IP_ADDR.into(), Discard::PORT).unwrap();
+    let mut pair = Pair {
+        service: Discard::new(key),
+        client: host.client(Discard::PORT),
+    };
+
+    let data = [0x42; 4000];
+    let mut written = 0;
+    for _ in 0..64 {
+        written += pair.client.send_mut().write(&data[written..]);
+        if written == data.len() {
+            pair.client.send_mut().close();
+        }
+        host.round(&mut pair);
+    }
+
+    assert_eq!(pair.service.received(), data.len());
+    assert!(pair.client.recv().is_closed());
+    assert!(pair.client.recv().is_empty());
+}
+
+#[test]
+fn chargen() {
+    let mut host = Host::new();
+    let key = host.tcp.listen(IP_ADDR.into(), Chargen::PORT).unwrap();
+    let mut pair = Pair {
+        service: Chargen::new(key),
+        client: host.client(Chargen::PORT),
+    };
+
+    let mut received = Vec::new();
+    for _ in 0..16 {
+        host.round(&mut pair);
+        let mut buffer = [0; 1024];
+        let read = pair.client.recv_mut().read(&mut buffer);
+        received.extend_from_slice(&buffer[..read]);
+    }
+
+    assert!(received.len() > 1024);
+    let mut expected = vec![0; received.len()];
+    Chargen::pattern(0, &mut expected);
+    assert_eq!(received, expected);
+    assert_eq!(&received[..4], b" !\"#");
+    assert_eq!(&received[72..78], b"\r\n!\"#$");
+
+    // The generator stops once the client closes.
+    pair.client.send_mut().close();
+    for _ in 0..16 {
+        host.round(&mut pair);
+        let mut buffer = [0; 1024];
+        let read = pair.client.recv_mut().read(&mut buffer);
+        received.extend_from_slice(&buffer[..read]);
+    }
+
+    assert!(pair.client.recv().is_closed());
+    assert_eq!(pair.service.generated(), received.len());
+}
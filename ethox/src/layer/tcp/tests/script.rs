@@ -0,0 +1,867 @@
+//! A scripted remote driving the tcp endpoint in virtual time.
+//!
+//! Scripts use a notation close to that of packetdrill, with one event per line:
+//!
+//! ```text
+//! // The local side listens on port 80.
+//! 0.000 listen 80
+//! 0.000 < S 0:0(0) win 1024 <mss 536>
+//! 0.000 > S. 0:0(0) ack 1
+//! +0.010 < . 1:1(0) ack 1 win 1024
+//! 0.010 state Established
+//! ```
+//!
+//! Each line starts with the time of the event in seconds, either absolute or relative to the
+//! previous event when prefixed with `+`. Segments of the remote (`<`) are received by the
+//! endpoint. Segments expected from the endpoint (`>`) must be sent exactly at that time, and all
+//! given fields must match. The sequence numbers of the remote are absolute while those of the
+//! local side are relative to its initial sequence number, in both directions.
+//!
+//! Consecutive segments of the remote at the same time are received in a single batch.
+//!
+//! No segment other than those expected may be sent. Before each other event, and at the end of
+//! the script, the endpoint is asked to send and must not produce anything. The remaining events
+//! are actions of the local application: `listen PORT`, `connect PORT`, `write LEN`, `read LEN`,
+//! `close`, the assertion `state STATE` on the connection, the assertion `eof` that all data
+//! has been read and the remote closed the stream, and `coalesce` to enable the coalescing of
+//! acknowledgements on the endpoint.
+//!
+//! `closeall DEADLINE` closes all connections of the endpoint with an absolute deadline, and the
+//! assertion `drain REMAINING RESET` checks its progress.
+//!
+//! The assertion `events ...` checks the connection events reported since the previous one, in
+//! order: `established`, `acked:LEN`, `fin`, `reset` and `timeout`. Received data with the PSH
+//! flag is reported as `push`, and the end of urgent data as `urgent:SEQ`.
+//!
+//! `push` sets the PSH flag on the segment carrying the last written byte. The URG flag of a
+//! segment is written as `U`, and its urgent pointer as `urg PTR`.
+//!
+//! `mtu LEN` announces a maximum transmission unit of the device for all later packets.
+//!
+//! `buffers SEND RECV` limits the data buffered by all connections of the endpoint.
+//!
+//! `unassign` drops the connections of the local address, as if it had been removed.
+//!
+//! `option nagle`, `option keepalive IDLE`, `option window LEN`, `option uto TIMEOUT` and
+//! `option retries COUNT` change the options of the endpoint for connections created afterwards.
+//! The user timeout option of a segment is written as `<uto SECS>`.
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::layer::{self, arp, eth, ip, tcp};
+use crate::layer::tcp::stream::{RecvRing, SendRing, Stream};
+use crate::managed::{List, Map, Slice, SlotMap};
+use crate::nic::{self, Capabilities, Device, Personality};
+use crate::nic::common::{EnqueueFlag, PacketInfo};
+use crate::time::{Duration, Instant};
+use crate::wire::{ethernet_frame, ipv4_packet, Checksum, PayloadMut};
+use crate::wire::{EthernetAddress, EthernetProtocol, EthernetRepr, IpCidr, IpProtocol};
+use crate::wire::{Ipv4Address, Ipv4Repr, TcpChecksum, TcpFlags, TcpPacket, TcpRepr, TcpSeqNumber};
+
+const MAC_ADDR: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 5]);
+const IP_ADDR: Ipv4Address = Ipv4Address::new(192, 0, 2, 1);
+const REMOTE_MAC_ADDR: EthernetAddress = EthernetAddress([6, 5, 4, 3, 2, 1]);
+const REMOTE_IP_ADDR: Ipv4Address = Ipv4Address::new(192, 0, 2, 2);
+
+/// The port of the remote when the local side listens.
+const REMOTE_PORT: u16 = 40000;
+
+/// Storage of each of the application's stream buffers.
+const BUFFER: usize = 1 << 12;
+
+/// Run a script, panicking with the offending line on the first mismatch.
+pub(crate) fn run(script: &str) {
+    let mut harness = Harness::new();
+    let mut last = 0;
+
+    for (idx, line) in script.lines().enumerate() {
+        let line = match line.find("//") {
+            Some(comment) => &line[..comment],
+            None => line,
+        }.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let result = Event::parse(line, last)
+            .and_then(|event| {
+                last = event.time;
+                harness.event(event)
+            });
+        if let Err(err) = result {
+            panic!("script line {}: `{}`: {}", idx + 1, line, err);
+        }
+    }
+
+    if let Err(err) = harness.receive().and_then(|()| harness.expect_silence()) {
+        panic!("end of script: {}", err);
+    }
+}
+
+/// One line of the script.
+struct Event {
+    /// Time since the start of the script, in milliseconds.
+    time: i64,
+    action: Action,
+}
+
+enum Action {
+    Listen(u16),
+    Connect(u16),
+    Inbound(Segment),
+    Outbound(Segment),
+    Write(usize),
+    Read(usize),
+    Close,
+    Eof,
+    State(tcp::State),
+    Coalesce,
+    Events(Vec<String>),
+    CloseAll(i64),
+    Drain(usize, usize),
+    Option(Setting),
+    Push,
+    Mtu(usize),
+    Buffers(usize, usize),
+    Unassign,
+}
+
+/// A change of the connection options.
+enum Setting {
+    Nagle,
+    Keepalive(i64),
+    Window(u32),
+    UserTimeout(i64),
+    Retries(u32),
+    Pacing(u64),
+}
+
+/// A segment as written in the script.
+#[derive(Default)]
+struct Segment {
+    flags: TcpFlags,
+    seq: u32,
+    len: u16,
+    ack: Option<u32>,
+    window: Option<u16>,
+    mss: Option<u16>,
+    window_scale: Option<u8>,
+    user_timeout: Option<u16>,
+    urgent: u16,
+}
+
+/// The endpoint under test, attached to a scripted remote.
+struct Harness {
+    nic: Remote,
+    eth: eth::Endpoint<'static>,
+    ip: ip::Endpoint<'static>,
+    tcp: tcp::Endpoint<'static>,
+    socket: Socket,
+    /// The initial sequence number of the local side, once it has sent its SYN.
+    isn: Option<TcpSeqNumber>,
+    /// The local and remote port of the connection.
+    ports: Option<(u16, u16)>,
+    /// The events reported by the endpoint and not yet checked.
+    events: Rc<RefCell<Vec<String>>>,
+}
+
+/// Records connection events in the notation of the script.
+struct Recorder(Rc<RefCell<Vec<String>>>);
+
+/// Records the user signals of received data before handing the packet to the socket.
+struct Receiver<'a> {
+    socket: &'a mut Socket,
+    events: &'a RefCell<Vec<String>>,
+}
+
+enum Socket {
+    None,
+    Listen(Stream<RecvRing<'static>, SendRing<'static>>),
+    Connect(tcp::Client<RecvRing<'static>, SendRing<'static>>),
+}
+
+/// A nic delivering the segments of the script and capturing everything sent.
+struct Remote {
+    inbound: VecDeque<Vec<u8>>,
+    outbound: VecDeque<Vec<u8>>,
+    info: PacketInfo,
+}
+
+struct Handle(EnqueueFlag);
+
+type Result<T> = core::result::Result<T, String>;
+
+impl Event {
+    fn parse(line: &str, last: i64) -> Result<Self> {
+        let mut words = line.split_whitespace();
+        let time = words.next().ok_or("missing time")?;
+        let time = match time.strip_prefix('+') {
+            Some(relative) => last + parse_millis(relative)?,
+            None => parse_millis(time)?,
+        };
+
+        let action = match words.next().ok_or("missing event")? {
+            "listen" => Action::Listen(parse_number(words.next())?),
+            "connect" => Action::Connect(parse_number(words.next())?),
+            "<" => Action::Inbound(Segment::parse(&mut words)?),
+            ">" => Action::Outbound(Segment::parse(&mut words)?),
+            "write" => Action::Write(parse_number(words.next())?),
+            "read" => Action::Read(parse_number(words.next())?),
+            "close" => Action::Close,
+            "eof" => Action::Eof,
+            "state" => Action::State(parse_state(words.next())?),
+            "coalesce" => Action::Coalesce,
+            "push" => Action::Push,
+            "mtu" => Action::Mtu(parse_number(words.next())?),
+            "unassign" => Action::Unassign,
+            "buffers" => Action::Buffers(parse_number(words.next())?, parse_number(words.next())?),
+            "closeall" => Action::CloseAll(parse_millis(words.next().ok_or("missing deadline")?)?),
+            "drain" => Action::Drain(parse_number(words.next())?, parse_number(words.next())?),
+            "events" => Action::Events(words.by_ref().map(String::from).collect()),
+            "option" => Action::Option(match words.next().ok_or("missing option")? {
+                "nagle" => Setting::Nagle,
+                "keepalive" => Setting::Keepalive(
+                    parse_millis(words.next().ok_or("missing idle time")?)?),
+                "window" => Setting::Window(parse_number(words.next())?),
+                "uto" => Setting::UserTimeout(
+                    parse_millis(words.next().ok_or("missing timeout")?)?),
+                "retries" => Setting::Retries(parse_number(words.next())?),
+                "pacing" => Setting::Pacing(parse_number(words.next())?),
+                other => return Err(format!("unknown option `{}`", other)),
+            }),
+            other => return Err(format!("unknown event `{}`", other)),
+        };
+
+        match words.next() {
+            None => Ok(Event { time, action }),
+            Some(word) => Err(format!("unexpected `{}`", word)),
+        }
+    }
+}
+
+impl Segment {
+    fn parse<'a>(words: &mut impl Iterator<Item=&'a str>) -> Result<Self> {
+        let mut segment = Segment::default();
+
+        for flag in words.next().ok_or("missing flags")?.chars() {
+            match flag {
+                'S' => segment.flags.set_syn(true),
+                'F' => segment.flags.set_fin(true),
+                'R' => segment.flags.set_rst(true),
+                'P' => segment.flags.set_psh(true),
+                'U' => segment.flags.set_urg(true),
+                '.' => segment.flags.set_ack(true),
+                other => return Err(format!("unknown flag `{}`", other)),
+            }
+        }
+
+        // The sequence range in the form `begin:end(len)`.
+        let range = words.next().ok_or("missing sequence numbers")?;
+        let (begin, rest) = split_once(range, ':')?;
+        let (end, len) = split_once(rest, '(')?;
+        segment.seq = parse_number(Some(begin))?;
+        let end: u32 = parse_number(Some(end))?;
+        segment.len = parse_number(len.strip_suffix(')'))?;
+        if end.wrapping_sub(segment.seq) != u32::from(segment.len) {
+            return Err(format!("inconsistent sequence range `{}`", range));
+        }
+
+        while let Some(word) = words.next() {
+            match word {
+                "ack" => segment.ack = Some(parse_number(words.next())?),
+                "win" => segment.window = Some(parse_number(words.next())?),
+                "urg" => segment.urgent = parse_number(words.next())?,
+                // Options, such as `<mss 536,wscale 7>`.
+                option if option.starts_with('<') => {
+                    let mut options = String::from(&option[1..]);
+                    while !options.ends_with('>') {
+                        options.push(' ');
+                        options.push_str(words.next().ok_or("unterminated options")?);
+                    }
+
+                    for option in options.trim_end_matches('>').split(',') {
+                        let mut option = option.split_whitespace();
+                        match option.next() {
+                            Some("mss") => segment.mss = Some(parse_number(option.next())?),
+                            Some("wscale") =>
+                                segment.window_scale = Some(parse_number(option.next())?),
+                            Some("uto") =>
+                                segment.user_timeout = Some(parse_number(option.next())?),
+                            other => return Err(format!("unknown option `{:?}`", other)),
+                        }
+                    }
+                },
+                other => return Err(format!("unexpected `{}`", other)),
+            }
+        }
+
+        if segment.ack.is_some() && !segment.flags.ack() {
+            return Err("ack number without the ACK flag".into());
+        }
+
+        Ok(segment)
+    }
+
+    /// Check an outgoing segment against the expectation.
+    // `Option::is_none_or` is too new for the supported toolchains.
+    #[allow(clippy::unnecessary_map_or)]
+    fn matches(&self, repr: &TcpRepr, isn: TcpSeqNumber) -> bool {
+        const CHECKED: u16 = TcpFlags::SYN.0 | TcpFlags::FIN.0 | TcpFlags::RST.0
+            | TcpFlags::PSH.0 | TcpFlags::ACK.0;
+
+        repr.flags.0 & CHECKED == self.flags.0
+            && relative(repr.seq_number, isn) == self.seq
+            && repr.payload_len == self.len
+            && self.ack.map_or(true, |ack| repr.ack_number == Some(TcpSeqNumber(ack as i32)))
+            && self.window.map_or(true, |window| repr.window_len == window)
+            && self.mss.map_or(true, |mss| repr.max_seg_size == Some(mss))
+            && self.window_scale.map_or(true, |scale| repr.window_scale == Some(scale))
+            && self.user_timeout.map_or(true, |uto| repr.user_timeout == Some(uto))
+    }
+}
+
+impl Harness {
+    fn new() -> Self {
+        let mut neighbors = arp::NeighborCache::new(vec![arp::Neighbor::default(); 1]);
+        neighbors.fill(REMOTE_IP_ADDR.into(), REMOTE_MAC_ADDR, None).unwrap();
+
+        let mut tcp = tcp::Endpoint::new(
+            Map::Pairs(List::new(Slice::Many(vec![Default::default(); 2]))),
+            SlotMap::new(
+                Slice::Many(vec![Default::default(); 2]),
+                Slice::Many(vec![Default::default(); 2])),
+            tcp::IsnGenerator::from_secret_key_bytes([0; 16]));
+        let events = Rc::default();
+        // The endpoint requires an observer for its whole lifetime.
+        tcp.set_events(Box::leak(Box::new(Recorder(Rc::clone(&events)))));
+
+        Harness {
+            nic: Remote {
+                inbound: VecDeque::new(),
+                outbound: VecDeque::new(),
+                info: PacketInfo {
+                    timestamp: Instant::from_millis(0),
+                    capabilities: Capabilities::no_support(),
+                    hardware_timestamp: None,
+                },
+            },
+            eth: eth::Endpoint::new(MAC_ADDR),
+            ip: ip::Endpoint::new(IpCidr::new(IP_ADDR.into(), 24),
+                ip::Routes::new(vec![ip::Route::unspecified(); 1]),
+                neighbors),
+            tcp,
+            socket: Socket::None,
+            isn: None,
+            ports: None,
+            events,
+        }
+    }
+
+    fn event(&mut self, event: Event) -> Result<()> {
+        let now = Instant::from_millis(event.time);
+        if now < self.nic.info.timestamp {
+            return Err("time must not go backwards".into());
+        }
+
+        if let Action::Inbound(segment) = &event.action {
+            if !self.nic.inbound.is_empty() && now == self.nic.info.timestamp {
+                let frame = self.frame(segment)?;
+                self.nic.inbound.push_back(frame);
+                return Ok(());
+            }
+        }
+
+        self.receive()?;
+
+        if let Action::Outbound(expected) = event.action {
+            return self.expect(now, expected);
+        }
+
+        self.expect_silence()?;
+        self.advance(now);
+
+        match event.action {
+            Action::Listen(port) => {
+                let key = self.tcp.listen(IP_ADDR.into(), port)
+                    .ok_or("could not listen")?;
+                self.ports = Some((port, REMOTE_PORT));
+                self.socket = Socket::Listen(Stream::new(key,
+                    RecvRing::new(vec![0; BUFFER]),
+                    SendRing::new(vec![0; BUFFER])));
+            },
+            Action::Connect(port) => {
+                self.socket = Socket::Connect(tcp::Client::new(REMOTE_IP_ADDR.into(), port,
+                    RecvRing::new(vec![0; BUFFER]),
+                    SendRing::new(vec![0; BUFFER])));
+            },
+            Action::Inbound(segment) => {
+                let frame = self.frame(&segment)?;
+                self.nic.inbound.push_back(frame);
+            },
+            Action::Write(len) => {
+                let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+                let written = self.socket.send()?.write(&data);
+                if written != len {
+                    return Err(format!("only {} bytes could be written", written));
+                }
+            },
+            Action::Read(len) => {
+                let mut data = vec![0; len + 1];
+                let read = self.socket.recv()?.read(&mut data);
+                if read != len {
+                    return Err(format!("{} bytes were available", read));
+                }
+            },
+            Action::Close => self.socket.send()?.close(),
+            Action::Eof => {
+                let recv = self.socket.recv()?;
+                if !recv.is_closed() || !recv.is_empty() {
+                    return Err(format!("{} bytes available, closed: {}",
+                        recv.len(), recv.is_closed()));
+                }
+            },
+            Action::State(expected) => {
+                let key = self.socket.key().ok_or("no connection")?;
+                let state = self.tcp.get(key).ok_or("connection was removed")?
+                    .info().state;
+                if state != expected {
+                    return Err(format!("state is {:?}", state));
+                }
+            },
+            Action::Coalesce => self.tcp.set_ack_coalescing(true),
+            Action::Push => self.socket.send()?.set_push(true),
+            Action::Mtu(len) => *self.nic.info.capabilities.mtu_mut() = Some(len),
+            Action::Unassign => {
+                self.tcp.reset_address(IP_ADDR.into());
+            },
+            Action::Buffers(send, recv) => {
+                self.tcp.set_buffer_limits(tcp::BufferSpace { send, recv });
+            },
+            Action::Option(setting) => {
+                let mut options = self.tcp.options();
+                match setting {
+                    Setting::Nagle => options.nagle = true,
+                    Setting::Keepalive(idle) => {
+                        options.keepalive = Some(Duration::from_millis(idle as u64));
+                    },
+                    Setting::Window(len) => options.receive_window = len,
+                    Setting::UserTimeout(timeout) => {
+                        options.user_timeout = Some(Duration::from_millis(timeout as u64));
+                    },
+                    Setting::Retries(count) => options.max_retransmissions = Some(count),
+                    Setting::Pacing(rate) => options.pacing = Some(tcp::Pacing::Rate(rate)),
+                }
+                self.tcp.set_options(options);
+            },
+            Action::CloseAll(deadline) => self.tcp.close_all(Instant::from_millis(deadline)),
+            Action::Drain(remaining, reset) => {
+                let drain = self.tcp.drain().ok_or("not draining")?;
+                if (drain.remaining, drain.reset) != (remaining, reset) {
+                    return Err(format!("{} remaining, {} reset", drain.remaining, drain.reset));
+                }
+            },
+            Action::Events(expected) => {
+                let events = self.events.replace(Vec::new());
+                if events != expected {
+                    return Err(format!("events were {:?}", events));
+                }
+            },
+            Action::Outbound(_) => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    /// Check the next sent segment, which must not be sent any earlier.
+    fn expect(&mut self, now: Instant, expected: Segment) -> Result<()> {
+        if self.nic.outbound.is_empty() && now > self.nic.info.timestamp {
+            self.send(now - Duration::from_millis(1))?;
+            if let Some(early) = self.nic.outbound.pop_front() {
+                let early = self.segment(&early)?;
+                return Err(format!("segment sent early: {}", self.describe(&early)));
+            }
+        }
+
+        self.advance(now);
+        if self.nic.outbound.is_empty() {
+            self.send(now)?;
+        }
+
+        let frame = self.nic.outbound.pop_front()
+            .ok_or("no segment was sent")?;
+        let segment = self.segment(&frame)?;
+
+        if self.isn.is_none() && segment.flags.syn() {
+            self.isn = Some(segment.seq_number);
+            self.ports = Some((segment.src_port, segment.dst_port));
+        }
+
+        let isn = self.isn.ok_or("segment sent before the SYN")?;
+        if !expected.matches(&segment, isn) {
+            return Err(format!("sent {}", self.describe(&segment)));
+        }
+
+        Ok(())
+    }
+
+    /// Receive the pending batch of remote segments.
+    fn receive(&mut self) -> Result<()> {
+        let pending = self.nic.inbound.len();
+        if pending == 0 {
+            return Ok(());
+        }
+
+        let mut receiver = Receiver { socket: &mut self.socket, events: &self.events };
+        self.nic.rx(pending, self.eth.recv(self.ip.recv(self.tcp.recv(&mut receiver))))
+            .map(drop)
+            .map_err(|err| format!("receiving failed: {:?}", err))
+    }
+
+    /// Ensure that nothing is sent at the current time.
+    fn expect_silence(&mut self) -> Result<()> {
+        if self.nic.outbound.is_empty() {
+            self.send(self.nic.info.timestamp)?;
+        }
+
+        match self.nic.outbound.pop_front() {
+            None => Ok(()),
+            Some(frame) => {
+                let segment = self.segment(&frame)?;
+                Err(format!("unexpected segment {}", self.describe(&segment)))
+            },
+        }
+    }
+
+    /// Let the endpoint send at most one segment at the given time.
+    fn send(&mut self, time: Instant) -> Result<()> {
+        let previous = self.nic.info.timestamp;
+        self.advance(time);
+        let socket = &mut self.socket;
+        let sent = self.nic.tx(1, self.eth.send(self.ip.send(self.tcp.send(socket))));
+        self.nic.info.timestamp = previous.max(time);
+        sent.map(drop).map_err(|err| format!("sending failed: {:?}", err))
+    }
+
+    fn advance(&mut self, now: Instant) {
+        self.nic.info.timestamp = now;
+        self.tcp.poll(now);
+    }
+
+    /// Build the frame of a remote segment.
+    fn frame(&mut self, segment: &Segment) -> Result<Vec<u8>> {
+        let (local_port, remote_port) = self.ports.ok_or("no connection to send to")?;
+        let ack_number = match segment.ack {
+            None if segment.flags.ack() => return Err("missing ack number".into()),
+            None => None,
+            Some(ack) => {
+                let isn = self.isn.ok_or("acknowledgement before the SYN")?;
+                Some(isn + ack as usize)
+            },
+        };
+
+        let repr = TcpRepr {
+            src_port: remote_port,
+            dst_port: local_port,
+            flags: segment.flags,
+            seq_number: TcpSeqNumber(segment.seq as i32),
+            ack_number,
+            window_len: segment.window.unwrap_or(u16::MAX),
+            window_scale: segment.window_scale,
+            max_seg_size: segment.mss,
+            sack_permitted: false,
+            sack_ranges: [None; 3],
+            user_timeout: segment.user_timeout,
+            payload_len: segment.len,
+        };
+
+        let tcp_len = repr.buffer_len();
+        let mut buffer = vec![0; 14 + 20 + tcp_len];
+        let frame = ethernet_frame::new_unchecked_mut(&mut buffer);
+        EthernetRepr {
+            src_addr: REMOTE_MAC_ADDR,
+            dst_addr: MAC_ADDR,
+            ethertype: EthernetProtocol::Ipv4,
+        }.emit(frame);
+
+        let packet = ipv4_packet::new_unchecked_mut(frame.payload_mut_slice());
+        Ipv4Repr {
+            src_addr: REMOTE_IP_ADDR,
+            dst_addr: IP_ADDR,
+            protocol: IpProtocol::Tcp,
+            payload_len: tcp_len,
+            hop_limit: 64,
+        }.emit(packet, Checksum::Manual);
+
+        let payload = packet.payload_mut_slice();
+        repr.emit(TcpPacket::new_unchecked(&mut *payload, repr));
+        let mut packet = TcpPacket::new_unchecked(payload, repr);
+        packet.set_urgent_at(segment.urgent);
+        packet.fill_checksum(REMOTE_IP_ADDR.into(), IP_ADDR.into());
+
+        Ok(buffer)
+    }
+
+    /// Parse a segment sent by the endpoint.
+    fn segment(&self, frame: &[u8]) -> Result<TcpRepr> {
+        let frame = ethernet_frame::new_checked(frame)
+            .map_err(|err| format!("invalid ethernet frame: {:?}", err))?;
+        let packet = ipv4_packet::new_checked(frame.payload_slice())
+            .map_err(|err| format!("invalid ipv4 packet: {:?}", err))?;
+        let checksum = TcpChecksum::Manual {
+            src_addr: IP_ADDR.into(),
+            dst_addr: REMOTE_IP_ADDR.into(),
+        };
+        TcpRepr::parse(&packet.payload_slice(), checksum)
+            .map_err(|err| format!("invalid tcp segment: {:?}", err))
+    }
+
+    /// Format a sent segment in the notation of the script.
+    fn describe<'a>(&self, repr: &'a TcpRepr) -> impl fmt::Display + 'a {
+        Describe { repr, isn: self.isn.unwrap_or(repr.seq_number) }
+    }
+}
+
+struct Describe<'a> {
+    repr: &'a TcpRepr,
+    isn: TcpSeqNumber,
+}
+
+impl fmt::Display for Describe<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let repr = self.repr;
+        let flags = [
+            (repr.flags.syn(), 'S'),
+            (repr.flags.fin(), 'F'),
+            (repr.flags.rst(), 'R'),
+            (repr.flags.psh(), 'P'),
+            (repr.ack_number.is_some(), '.'),
+        ];
+        for &(_, flag) in flags.iter().filter(|&&(set, _)| set) {
+            write!(f, "{}", flag)?;
+        }
+
+        let seq = relative(repr.seq_number, self.isn);
+        let end = seq.wrapping_add(u32::from(repr.payload_len));
+        write!(f, " {}:{}({})", seq, end, repr.payload_len)?;
+        if let Some(ack) = repr.ack_number {
+            write!(f, " ack {}", ack.0 as u32)?;
+        }
+        write!(f, " win {}", repr.window_len)?;
+        if let Some(mss) = repr.max_seg_size {
+            write!(f, " <mss {}>", mss)?;
+        }
+        Ok(())
+    }
+}
+
+impl Socket {
+    fn recv(&mut self) -> Result<&mut RecvRing<'static>> {
+        match self {
+            Socket::None => Err("no socket".into()),
+            Socket::Listen(stream) => Ok(stream.recv_mut()),
+            Socket::Connect(client) => Ok(client.recv_mut()),
+        }
+    }
+
+    fn send(&mut self) -> Result<&mut SendRing<'static>> {
+        match self {
+            Socket::None => Err("no socket".into()),
+            Socket::Listen(stream) => Ok(stream.send_mut()),
+            Socket::Connect(client) => Ok(client.send_mut()),
+        }
+    }
+
+    fn key(&self) -> Option<tcp::SlotKey> {
+        match self {
+            Socket::None => None,
+            Socket::Listen(stream) => stream.connection_key(),
+            Socket::Connect(client) => client.connection_key(),
+        }
+    }
+}
+
+impl tcp::ConnectionEvents for Recorder {
+    fn established(&mut self, _: tcp::SlotKey) {
+        self.0.borrow_mut().push("established".into());
+    }
+
+    fn data_acked(&mut self, _: tcp::SlotKey, bytes: usize) {
+        self.0.borrow_mut().push(format!("acked:{}", bytes));
+    }
+
+    fn remote_fin(&mut self, _: tcp::SlotKey) {
+        self.0.borrow_mut().push("fin".into());
+    }
+
+    fn reset(&mut self, _: tcp::SlotKey) {
+        self.0.borrow_mut().push("reset".into());
+    }
+
+    fn timeout(&mut self, _: tcp::SlotKey) {
+        self.0.borrow_mut().push("timeout".into());
+    }
+}
+
+impl<P: PayloadMut> tcp::Recv<P> for &'_ mut Socket {
+    fn receive(&mut self, packet: tcp::InPacket<P>) {
+        match self {
+            Socket::None => (),
+            Socket::Listen(stream) => tcp::Recv::receive(&mut &mut *stream, packet),
+            Socket::Connect(client) => tcp::Recv::receive(&mut &mut *client, packet),
+        }
+    }
+}
+
+impl<P: PayloadMut> tcp::Recv<P> for &'_ mut Receiver<'_> {
+    fn receive(&mut self, packet: tcp::InPacket<P>) {
+        if let tcp::InPacket::Open(open) = &packet {
+            let mut events = self.events.borrow_mut();
+            if open.user_signals().push {
+                events.push("push".into());
+            }
+            if let Some(end) = open.urgent_end() {
+                events.push(format!("urgent:{}", end.0 as u32));
+            }
+        }
+
+        tcp::Recv::receive(&mut &mut *self.socket, packet)
+    }
+}
+
+impl<P: PayloadMut> tcp::Send<P> for &'_ mut Socket {
+    fn send(&mut self, packet: tcp::RawPacket<P>) {
+        match self {
+            Socket::None => (),
+            Socket::Listen(stream) => tcp::Send::send(&mut &mut *stream, packet),
+            Socket::Connect(client) => tcp::Send::send(&mut &mut *client, packet),
+        }
+    }
+}
+
+impl Device for Remote {
+    type Handle = Handle;
+    type Payload = Vec<u8>;
+
+    fn personality(&self) -> Personality {
+        Personality::baseline()
+    }
+
+    fn tx(&mut self, max: usize, mut sender: impl nic::Send<Handle, Vec<u8>>)
+        -> layer::Result<usize>
+    {
+        let mut count = 0;
+
+        while count < max {
+            let mtu = self.info.capabilities.mtu().unwrap_or(1500);
+            let mut buffer = vec![0; 14 + mtu];
+            let mut flag = Handle(EnqueueFlag::set_true(self.info));
+            sender.send(nic::Packet {
+                handle: &mut flag,
+                payload: &mut buffer,
+            });
+
+            if !flag.0.was_sent() {
+                break;
+            }
+
+            self.outbound.push_back(buffer);
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    fn rx(&mut self, max: usize, mut receptor: impl nic::Recv<Handle, Vec<u8>>)
+        -> layer::Result<usize>
+    {
+        let mut count = 0;
+
+        while count < max {
+            let mut buffer = match self.inbound.pop_front() {
+                Some(buffer) => buffer,
+                None => break,
+            };
+
+            // Answers are sent in place of the received segment.
+            let mut flag = Handle(EnqueueFlag::set_true(self.info));
+            receptor.receive(nic::Packet {
+                handle: &mut flag,
+                payload: &mut buffer,
+            });
+
+            if flag.0.was_sent() {
+                self.outbound.push_back(buffer);
+            }
+
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
+
+impl nic::Handle for Handle {
+    fn queue(&mut self) -> layer::Result<()> {
+        self.0.queue()
+    }
+
+    fn info(&self) -> &dyn nic::Info {
+        self.0.info()
+    }
+}
+
+fn parse_millis(time: &str) -> Result<i64> {
+    let (secs, fraction) = match time.find('.') {
+        Some(dot) => (&time[..dot], &time[dot + 1..]),
+        None => (time, ""),
+    };
+
+    if fraction.len() > 3 {
+        return Err(format!("time `{}` is more precise than milliseconds", time));
+    }
+
+    let secs: i64 = parse_number(Some(secs))?;
+    let millis: i64 = match fraction {
+        "" => 0,
+        fraction => parse_number::<i64>(Some(fraction))? * 10i64.pow(3 - fraction.len() as u32),
+    };
+
+    Ok(secs * 1000 + millis)
+}
+
+fn parse_number<T: core::str::FromStr>(word: Option<&str>) -> Result<T> {
+    let word = word.ok_or("missing number")?;
+    word.parse().map_err(|_| format!("invalid number `{}`", word))
+}
+
+fn parse_state(word: Option<&str>) -> Result<tcp::State> {
+    use tcp::State::*;
+    let state = match word.ok_or("missing state")? {
+        "Closed" => Closed,
+        "Listen" => Listen,
+        "SynSent" => SynSent,
+        "SynReceived" => SynReceived,
+        "Established" => Established,
+        "FinWait1" => FinWait1,
+        "FinWait2" => FinWait2,
+        "Closing" => Closing,
+        "TimeWait" => TimeWait,
+        "CloseWait" => CloseWait,
+        "LastAck" => LastAck,
+        other => return Err(format!("unknown state `{}`", other)),
+    };
+    Ok(state)
+}
+
+fn split_once(word: &str, at: char) -> Result<(&str, &str)> {
+    let idx = word.find(at).ok_or_else(|| format!("missing `{}` in `{}`", at, word))?;
+    Ok((&word[..idx], &word[idx + at.len_utf8()..]))
+}
+
+/// The offset of a sequence number from the initial one.
+fn relative(seq: TcpSeqNumber, isn: TcpSeqNumber) -> u32 {
+    seq.0.wrapping_sub(isn.0) as u32
+}
@@ -0,0 +1,107 @@
+//! A proxy on a single endpoint, splicing the data of one connection into another.
+use crate::layer::{arp, eth, ip, tcp};
+use crate::layer::tcp::stream::{RecvRing, SendRing, Stream};
+use crate::managed::{List, Map, Slice, SlotMap};
+use crate::nic::{loopback::Loopback, Device};
+use crate::wire::{EthernetAddress, IpCidr, Ipv4Address, PayloadMut};
+
+const MAC_ADDR: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 5]);
+const IP_ADDR: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
+
+type Buffers = Stream<RecvRing<'static>, SendRing<'static>>;
+type Client = tcp::Client<RecvRing<'static>, SendRing<'static>>;
+
+/// The connections of a proxy between a server and a client.
+///
+/// The proxy connects to the server on the `front` and accepts the client on the `back`.
+struct Proxy {
+    server: Buffers,
+    front: Client,
+    back: Buffers,
+    client: Client,
+    spliced: usize,
+}
+
+impl<P: PayloadMut> tcp::Recv<P> for &'_ mut Proxy {
+    fn receive(&mut self, packet: tcp::InPacket<P>) {
+        let key = packet.key();
+        if key.is_none() || key == self.server.connection_key() {
+            return (&mut self.server).receive(packet);
+        } else if key == self.back.connection_key() {
+            return (&mut self.back).receive(packet);
+        } else if key == self.client.connection_key() {
+            return (&mut self.client).receive(packet);
+        }
+
+        let mut open = match (packet, self.back.connection_key()) {
+            (tcp::InPacket::Open(open), Some(to))
+                if open.can_splice(to, self.front.recv(), self.back.send()) =>
+            {
+                let recv = self.front.recv_mut();
+                let sent = open.splice(to, recv, self.back.send_mut());
+                assert!(matches!(sent, Ok(Ok(_))));
+                return self.spliced += 1;
+            },
+            (tcp::InPacket::Open(open), _) => open,
+            (packet, _) => return (&mut self.front).receive(packet),
+        };
+
+        open.read(self.front.recv_mut());
+        let _ = open.write(self.front.send_mut());
+    }
+}
+
+#[test]
+fn splice() {
+    let mut neighbors = arp::NeighborCache::new(vec![arp::Neighbor::default(); 1]);
+    neighbors.fill(IP_ADDR.into(), MAC_ADDR, None).unwrap();
+    let mut nic = Loopback::new(Slice::Many(vec![vec![0; 1514]; 8]));
+    let mut eth = eth::Endpoint::new(MAC_ADDR);
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR.into(), 24),
+        ip::Routes::new(vec![ip::Route::unspecified(); 1]),
+        neighbors);
+    let mut tcp = tcp::Endpoint::new(
+        Map::Pairs(List::new(Slice::Many(vec![Default::default(); 4]))),
+        SlotMap::new(
+            Slice::Many(vec![Default::default(); 4]),
+            Slice::Many(vec![Default::default(); 4])),
+        tcp::IsnGenerator::from_secret_key_bytes([0; 16]));
+
+    let ring = || RecvRing::new(vec![0; 1024]);
+    let send = || SendRing::new(vec![0; 1024]);
+    let mut proxy = Proxy {
+        server: Stream::new(tcp.listen(IP_ADDR.into(), 80).unwrap(), ring(), send()),
+        front: tcp::Client::new(IP_ADDR.into(), 80, ring(), send()),
+        back: Stream::new(tcp.listen(IP_ADDR.into(), 8080).unwrap(), ring(), send()),
+        client: tcp::Client::new(IP_ADDR.into(), 8080, ring(), send()),
+        spliced: 0,
+    };
+
+    let data: Vec<u8> = (0..200).map(|i| i as u8).collect();
+    let mut received = Vec::new();
+
+    for round in 0..16 {
+        if round == 8 {
+            assert_eq!(proxy.server.send_mut().write(&data), data.len());
+        }
+
+        let _ = nic.tx(1, eth.send(ip.send(tcp.send(&mut proxy.server))));
+        let _ = nic.tx(1, eth.send(ip.send(tcp.send(&mut proxy.front))));
+        let _ = nic.tx(1, eth.send(ip.send(tcp.send(&mut proxy.back))));
+        let _ = nic.tx(1, eth.send(ip.send(tcp.send(&mut proxy.client))));
+        while nic.pending() > 0 {
+            let pending = nic.pending();
+            let _ = nic.rx(pending, eth.recv(ip.recv(tcp.recv(&mut proxy))));
+        }
+
+        let mut buffer = [0; 256];
+        let read = proxy.client.recv_mut().read(&mut buffer);
+        received.extend_from_slice(&buffer[..read]);
+    }
+
+    assert_eq!(proxy.spliced, 1);
+    assert_eq!(received, data);
+    assert!(proxy.front.recv().is_empty());
+    // The server has its data acknowledged by the proxy.
+    assert!(proxy.server.send().is_empty());
+}
@@ -0,0 +1,213 @@
+use crate::layer::{udp, Result};
+use crate::time::{Clock, Expiration, Instant};
+use crate::wire::{IpAddress, Payload, PayloadMut};
+use crate::wire::{tftp_packet, TftpErrorCode, TftpMode, TftpOptions, TftpRepr, TFTP_PORT};
+
+use super::{Direction, Status, Storage};
+use super::transfer::{Next, Transfer};
+
+/// A TFTP client reading or writing a single file.
+///
+/// The request is sent with the first packet buffer offered to the client, from a fixed local
+/// port. The server answers from a port of its own, which is used for the rest of the transfer.
+pub struct Client<'a, S> {
+    storage: S,
+    filename: &'a [u8],
+    options: TftpOptions,
+    transfer: Transfer,
+    transfer_size: Option<u64>,
+}
+
+impl<'a, S: Storage> Client<'a, S> {
+    /// Create a client reading a file from a server.
+    pub fn read(server: IpAddress, filename: &'a [u8], local_port: u16, storage: S) -> Self {
+        Client::new(server, filename, local_port, Direction::Receive, storage)
+    }
+
+    /// Create a client writing a file to a server.
+    pub fn write(server: IpAddress, filename: &'a [u8], local_port: u16, storage: S) -> Self {
+        Client::new(server, filename, local_port, Direction::Send, storage)
+    }
+
+    fn new(
+        server: IpAddress,
+        filename: &'a [u8],
+        local_port: u16,
+        direction: Direction,
+        storage: S,
+    ) -> Self {
+        Client {
+            storage,
+            filename,
+            options: TftpOptions::default(),
+            transfer: Transfer::new(server, TFTP_PORT, local_port, direction, Next::Request),
+            transfer_size: None,
+        }
+    }
+
+    /// Change the options requested from the server.
+    ///
+    /// Only has an effect before the request has been sent. Request a transfer size of `0` when
+    /// reading to learn the size of the file. When writing, the size is replaced with the one
+    /// known to the storage, or not requested if it is unknown.
+    pub fn set_options(&mut self, options: TftpOptions) {
+        self.options = options;
+    }
+
+    /// The state of the transfer.
+    pub fn transfer(&self) -> &Transfer {
+        &self.transfer
+    }
+
+    /// The progress of the transfer.
+    pub fn status(&self) -> Status {
+        self.transfer.status()
+    }
+
+    /// The size of the file as acknowledged by the server, if requested.
+    pub fn transfer_size(&self) -> Option<u64> {
+        self.transfer_size
+    }
+
+    /// Get a reference to the storage.
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// Get a mutable reference to the storage.
+    pub fn storage_mut(&mut self) -> &mut S {
+        &mut self.storage
+    }
+
+    /// Unwrap the storage.
+    pub fn into_storage(self) -> S {
+        self.storage
+    }
+
+    /// Get the time at which the client wants to send its next packet.
+    ///
+    /// Offer it a packet buffer at that point.
+    pub fn poll(&self, now: Instant) -> Expiration {
+        self.transfer.poll(now)
+    }
+
+    /// Get the time of the next packet, with the current time of a clock.
+    pub fn tick<C: Clock + ?Sized>(&self, clock: &C) -> Expiration {
+        self.poll(clock.now())
+    }
+
+    fn send_request<P: PayloadMut>(&mut self, raw: udp::RawPacket<P>, now: Instant)
+        -> Result<()>
+    {
+        let direction = self.transfer.direction();
+        if !self.transfer.is_opened() {
+            match self.storage.open(0, self.filename, direction) {
+                Ok(size) => {
+                    self.transfer.set_opened();
+                    if direction == Direction::Send {
+                        self.options.transfer_size = self.options.transfer_size.and(size);
+                    }
+                },
+                Err(code) => {
+                    self.transfer.end(Status::Local(code), &mut self.storage, 0);
+                    return Ok(());
+                },
+            }
+        }
+
+        let (filename, mode, options) = (self.filename, TftpMode::Octet, self.options);
+        let repr = match direction {
+            Direction::Receive => TftpRepr::ReadRequest { filename, mode, options },
+            Direction::Send => TftpRepr::WriteRequest { filename, mode, options },
+        };
+
+        let result = self.transfer.send_repr(raw, &repr);
+        self.transfer.sent(now);
+        result
+    }
+
+    /// Adopt the options acknowledged by the server.
+    fn acknowledged(&mut self, options: &TftpOptions) {
+        let requested = &self.options;
+        // Only requested options may be acknowledged, and the block size must not grow.
+        let valid = options.block_size <= requested.block_size
+            && (options.timeout.is_none() || options.timeout == requested.timeout)
+            && (options.transfer_size.is_none() || requested.transfer_size.is_some());
+
+        if !valid {
+            return self.transfer.abort(TftpErrorCode::OptionRefused, &mut self.storage, 0);
+        }
+
+        self.transfer.negotiate(options);
+        self.transfer_size = options.transfer_size;
+        self.transfer.proceed(match self.transfer.direction() {
+            Direction::Receive => Next::Ack,
+            Direction::Send => Next::Data,
+        });
+    }
+
+    /// Process the first answer of the server.
+    fn answer(&mut self, repr: &TftpRepr, port: u16) {
+        match (*repr, self.transfer.direction()) {
+            (TftpRepr::OptionAck { options }, _) => {
+                self.transfer.set_peer_port(port);
+                self.acknowledged(&options);
+            },
+            // The options were not acknowledged, the defaults are used instead.
+            (TftpRepr::Data { block: 1, .. }, Direction::Receive) => {
+                self.transfer.set_peer_port(port);
+                self.transfer.proceed(Next::Ack);
+                self.transfer.receive(repr, &mut self.storage, 0);
+            },
+            (TftpRepr::Ack { block: 0 }, Direction::Send) => {
+                self.transfer.set_peer_port(port);
+                self.transfer.proceed(Next::Data);
+            },
+            (TftpRepr::Error { .. }, _) => {
+                self.transfer.receive(repr, &mut self.storage, 0);
+            },
+            _ => (),
+        }
+    }
+}
+
+impl<P: Payload, S: Storage> udp::Recv<P> for Client<'_, S> {
+    fn receive(&mut self, packet: udp::Packet<P>) {
+        let udp::Packet { handle: _, packet } = packet;
+        let udp_repr = packet.repr();
+        let (server, port) = self.transfer.peer();
+        if udp_repr.dst_port != self.transfer.local_port()
+            || packet.get_ref().repr().src_addr() != server
+        {
+            return;
+        }
+
+        let repr = match tftp_packet::new_checked(packet.payload_slice())
+            .and_then(TftpRepr::parse)
+        {
+            Ok(repr) => repr,
+            Err(_) => return,
+        };
+
+        if self.transfer.next() == Next::Request {
+            self.answer(&repr, udp_repr.src_port);
+        } else if udp_repr.src_port == port {
+            self.transfer.receive(&repr, &mut self.storage, 0);
+        }
+    }
+}
+
+impl<P: PayloadMut, S: Storage> udp::Send<P> for Client<'_, S> {
+    fn send(&mut self, raw: udp::RawPacket<P>) {
+        let now = raw.handle.info().timestamp();
+        self.transfer.expire(&mut self.storage, 0, now);
+        if !self.transfer.is_due(now) {
+            return;
+        }
+
+        let _ = match self.transfer.next() {
+            Next::Request => self.send_request(raw, now),
+            _ => self.transfer.send(raw, &mut self.storage, 0),
+        };
+    }
+}
@@ -0,0 +1,114 @@
+//! A TFTP server and client, rfc1350.
+//!
+//! Both sit on top of the udp layer and implement its receiving and sending side. Files are
+//! accessed through a [`Storage`], which reads and writes the blocks of a transfer directly from
+//! and into the packet buffers. Blocks are read again from the storage when they have to be
+//! retransmitted, so that no transfer needs a buffer of its own.
+//!
+//! Transfers are in octet mode only. The block size, timeout interval and transfer size options of
+//! rfc2348 and rfc2349 are negotiated when requested. Lost packets are retransmitted after the
+//! timeout interval, up to five times, before a transfer is given up.
+//!
+//! The [`Server`] answers requests arriving at the well-known port. Each transfer is assigned a
+//! slot and a local port of its own, the first port of the server plus the index of the slot. The
+//! udp endpoint must accept all of these ports. The [`Client`] performs a single read or write of
+//! a file. Drive the timers of both with their `tick` method and offer them a packet buffer
+//! whenever they have expired.
+//!
+//! Packets from unknown transfer IDs are ignored instead of being answered with an error, and
+//! requests are dropped silently while all transfer slots of the server are in use.
+//!
+//! [`Storage`]: trait.Storage.html
+//! [`Server`]: struct.Server.html
+//! [`Client`]: struct.Client.html
+use crate::wire::TftpErrorCode;
+
+mod client;
+mod server;
+mod transfer;
+#[cfg(test)]
+mod tests;
+
+pub use client::Client;
+pub use server::Server;
+pub use transfer::Transfer;
+
+/// Whether the file of a transfer is sent or received by the local side.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// The file is read from the storage and sent to the peer.
+    Send,
+    /// The file is received from the peer and written to the storage.
+    Receive,
+}
+
+/// The progress of a transfer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// The transfer has not finished yet.
+    Running,
+    /// All blocks of the file have been transferred.
+    Complete,
+    /// The peer stopped answering.
+    TimedOut,
+    /// The peer aborted the transfer with an error.
+    Remote(TftpErrorCode),
+    /// The transfer was aborted locally, and the peer informed with an error.
+    Local(TftpErrorCode),
+}
+
+/// Access to the files of transfers.
+///
+/// Each method is passed the `id` of the transfer, which distinguishes concurrent transfers of a
+/// server. It is the index of the transfer slot of the server and always `0` for a client.
+pub trait Storage {
+    /// Open a file for a new transfer.
+    ///
+    /// Returns the size of the file if it is known, which is offered to the peer when it requests
+    /// the transfer size option. The returned error code is sent to the peer.
+    fn open(&mut self, id: usize, filename: &[u8], direction: Direction)
+        -> Result<Option<u64>, TftpErrorCode>;
+
+    /// Read data of a file that is being sent, starting at an offset.
+    ///
+    /// Must fill the whole buffer unless the end of the file is reached, and return the number of
+    /// bytes read. A block is read again when it needs to be retransmitted.
+    fn read(&mut self, id: usize, offset: u64, buffer: &mut [u8]) -> Result<usize, TftpErrorCode>;
+
+    /// Write data of a file that is being received, at an offset.
+    ///
+    /// Each block is written exactly once and in order.
+    fn write(&mut self, id: usize, offset: u64, data: &[u8]) -> Result<(), TftpErrorCode>;
+
+    /// Close the file of a transfer that has been opened successfully.
+    ///
+    /// The transfer was `complete` if all data has been transferred, otherwise it failed.
+    fn close(&mut self, id: usize, complete: bool);
+}
+
+impl Status {
+    /// Check if the transfer has finished, successfully or not.
+    pub fn is_finished(self) -> bool {
+        self != Status::Running
+    }
+}
+
+impl<S: Storage + ?Sized> Storage for &'_ mut S {
+    fn open(&mut self, id: usize, filename: &[u8], direction: Direction)
+        -> Result<Option<u64>, TftpErrorCode>
+    {
+        (**self).open(id, filename, direction)
+    }
+
+    fn read(&mut self, id: usize, offset: u64, buffer: &mut [u8]) -> Result<usize, TftpErrorCode> {
+        (**self).read(id, offset, buffer)
+    }
+
+    fn write(&mut self, id: usize, offset: u64, data: &[u8]) -> Result<(), TftpErrorCode> {
+        (**self).write(id, offset, data)
+    }
+
+    fn close(&mut self, id: usize, complete: bool) {
+        (**self).close(id, complete)
+    }
+}
@@ -0,0 +1,218 @@
+use core::convert::TryFrom;
+
+use crate::layer::udp;
+use crate::managed::Slice;
+use crate::time::{Clock, Expiration, Instant};
+use crate::wire::{IpAddress, Payload, PayloadMut};
+use crate::wire::{tftp_packet, TftpErrorCode, TftpMode, TftpOptions, TftpRepr};
+use crate::wire::{TFTP_MAX_BLOCK_SIZE, TFTP_MIN_BLOCK_SIZE, TFTP_PORT};
+
+use super::{Direction, Storage};
+use super::transfer::{Next, Transfer};
+
+/// A TFTP server with a fixed number of concurrent transfers.
+///
+/// Requests for files are passed to the storage, which decides if they exist and may be
+/// transferred. Errors, including the ones of the storage, are answered with an error packet.
+/// When sending, the packet buffers are offered to the transfers in turn.
+pub struct Server<'a, S> {
+    storage: S,
+    transfers: Slice<'a, Option<Transfer>>,
+    first_port: u16,
+    max_block_size: u16,
+    /// The transfer that is offered the next packet buffer.
+    next: usize,
+}
+
+impl<'a, S: Storage> Server<'a, S> {
+    /// The default of the largest negotiated block size.
+    ///
+    /// Data packets of this size fit into the usual Ethernet mtu, also over IPv6.
+    pub const DEFAULT_MAX_BLOCK_SIZE: u16 = 1024;
+
+    /// Create a server with slots for its transfers.
+    ///
+    /// The transfer in each slot uses the local port `first_port` plus the index of the slot.
+    pub fn new(storage: S, transfers: Slice<'a, Option<Transfer>>, first_port: u16) -> Self {
+        Server {
+            storage,
+            transfers,
+            first_port,
+            max_block_size: Self::DEFAULT_MAX_BLOCK_SIZE,
+            next: 0,
+        }
+    }
+
+    /// Change the largest block size that is accepted when a client requests one.
+    ///
+    /// Data packets must fit into the packet buffers and the mtu of the link. The size is limited
+    /// to the range permitted by rfc2348.
+    pub fn set_max_block_size(&mut self, size: u16) {
+        self.max_block_size = size.clamp(TFTP_MIN_BLOCK_SIZE, TFTP_MAX_BLOCK_SIZE);
+    }
+
+    /// Get a reference to the storage.
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// Get a mutable reference to the storage.
+    pub fn storage_mut(&mut self) -> &mut S {
+        &mut self.storage
+    }
+
+    /// The slots of the transfers, with the ongoing ones.
+    pub fn transfers(&self) -> &[Option<Transfer>] {
+        &self.transfers
+    }
+
+    /// Get the time at which the server wants to send its next packet.
+    ///
+    /// Offer it a packet buffer at that point.
+    pub fn poll(&self, now: Instant) -> Expiration {
+        self.transfers.iter()
+            .flatten()
+            .map(|transfer| transfer.poll(now))
+            .min()
+            .unwrap_or(Expiration::Never)
+    }
+
+    /// Get the time of the next packet, with the current time of a clock.
+    pub fn tick<C: Clock + ?Sized>(&self, clock: &C) -> Expiration {
+        self.poll(clock.now())
+    }
+
+    /// Start a transfer for a new request.
+    fn request(&mut self, repr: &TftpRepr, peer: (IpAddress, u16), local: IpAddress) {
+        let (filename, mode, options, direction) = match *repr {
+            TftpRepr::ReadRequest { filename, mode, options } =>
+                (filename, mode, options, Direction::Send),
+            TftpRepr::WriteRequest { filename, mode, options } =>
+                (filename, mode, options, Direction::Receive),
+            _ => return,
+        };
+
+        // A retransmission of a request that is already being served.
+        if self.transfers.iter().flatten().any(|transfer| transfer.peer() == peer) {
+            return;
+        }
+
+        let id = match self.transfers.iter().position(Option::is_none) {
+            Some(id) => id,
+            None => return,
+        };
+
+        let local_port = match u16::try_from(id).ok()
+            .and_then(|id| self.first_port.checked_add(id))
+        {
+            Some(port) => port,
+            None => return,
+        };
+
+        let mut transfer = Transfer::new(peer.0, peer.1, local_port, direction, Next::Done);
+        transfer.set_local(local);
+
+        if mode != TftpMode::Octet {
+            transfer.abort(TftpErrorCode::IllegalOperation, &mut self.storage, id);
+        } else {
+            match self.storage.open(id, filename, direction) {
+                Ok(size) => {
+                    transfer.set_opened();
+                    let accepted = self.accept(&options, direction, size);
+                    transfer.negotiate(&accepted);
+                    transfer.proceed(if !accepted.is_empty() {
+                        Next::OptionAck(accepted)
+                    } else if direction == Direction::Send {
+                        Next::Data
+                    } else {
+                        Next::Ack
+                    });
+                },
+                Err(code) => transfer.abort(code, &mut self.storage, id),
+            }
+        }
+
+        self.transfers[id] = Some(transfer);
+    }
+
+    /// Choose the options to acknowledge.
+    fn accept(&self, options: &TftpOptions, direction: Direction, size: Option<u64>)
+        -> TftpOptions
+    {
+        TftpOptions {
+            block_size: options.block_size.map(|size| size.min(self.max_block_size)),
+            timeout: options.timeout,
+            transfer_size: match direction {
+                Direction::Send => options.transfer_size.and(size),
+                Direction::Receive => options.transfer_size,
+            },
+        }
+    }
+
+    /// Free the slots of transfers that have ended.
+    fn collect(&mut self) {
+        for slot in self.transfers.iter_mut() {
+            if slot.is_some_and(|transfer| transfer.is_done()) {
+                *slot = None;
+            }
+        }
+    }
+}
+
+impl<P: Payload, S: Storage> udp::Recv<P> for Server<'_, S> {
+    fn receive(&mut self, packet: udp::Packet<P>) {
+        let udp::Packet { handle: _, packet } = packet;
+        let udp_repr = packet.repr();
+        let ip_repr = packet.get_ref().repr();
+        let peer = (ip_repr.src_addr(), udp_repr.src_port);
+
+        let repr = match tftp_packet::new_checked(packet.payload_slice())
+            .and_then(TftpRepr::parse)
+        {
+            Ok(repr) => repr,
+            Err(_) => return,
+        };
+
+        if udp_repr.dst_port == TFTP_PORT {
+            return self.request(&repr, peer, ip_repr.dst_addr());
+        }
+
+        let id = usize::from(udp_repr.dst_port.wrapping_sub(self.first_port));
+        match self.transfers.get_mut(id) {
+            Some(Some(transfer)) if transfer.peer() == peer => {
+                transfer.receive(&repr, &mut self.storage, id);
+            },
+            _ => return,
+        }
+
+        self.collect();
+    }
+}
+
+impl<P: PayloadMut, S: Storage> udp::Send<P> for Server<'_, S> {
+    fn send(&mut self, raw: udp::RawPacket<P>) {
+        let now = raw.handle.info().timestamp();
+        for (id, slot) in self.transfers.iter_mut().enumerate() {
+            if let Some(transfer) = slot {
+                transfer.expire(&mut self.storage, id, now);
+            }
+        }
+        self.collect();
+
+        let count = self.transfers.len();
+        let due = (0..count)
+            .map(|offset| (self.next + offset) % count)
+            .find(|&id| self.transfers[id].is_some_and(|transfer| transfer.is_due(now)));
+
+        let id = match due {
+            Some(id) => id,
+            None => return,
+        };
+
+        self.next = id + 1;
+        if let Some(transfer) = &mut self.transfers[id] {
+            let _ = transfer.send(raw, &mut self.storage, id);
+        }
+        self.collect();
+    }
+}
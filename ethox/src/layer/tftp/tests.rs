@@ -0,0 +1,189 @@
+use super::*;
+use crate::managed::Slice;
+use crate::layer::udp::testing::{Host, IP_ADDR_PEER as IP_ADDR_SERVER};
+use crate::time::{Duration, Expiration, Instant};
+use crate::wire::{ethernet_frame, ipv4_packet, udp_packet, tftp_packet};
+use crate::wire::{TftpOpCode, TftpOptions, TFTP_PORT};
+
+const CLIENT_PORT: u16 = 2000;
+const FIRST_PORT: u16 = 3000;
+
+/// Files in memory.
+#[derive(Default)]
+struct Memory {
+    file: Vec<u8>,
+    written: Vec<u8>,
+    closed: Vec<(usize, bool)>,
+}
+
+impl Storage for Memory {
+    fn open(&mut self, _: usize, filename: &[u8], direction: Direction)
+        -> Result<Option<u64>, TftpErrorCode>
+    {
+        match direction {
+            Direction::Send if filename == b"boot.img" => Ok(Some(self.file.len() as u64)),
+            Direction::Send => Err(TftpErrorCode::FileNotFound),
+            Direction::Receive => Ok(None),
+        }
+    }
+
+    fn read(&mut self, _: usize, offset: u64, buffer: &mut [u8]) -> Result<usize, TftpErrorCode> {
+        let rest = self.file.get(offset as usize..).unwrap_or(&[]);
+        let len = rest.len().min(buffer.len());
+        buffer[..len].copy_from_slice(&rest[..len]);
+        Ok(len)
+    }
+
+    fn write(&mut self, _: usize, offset: u64, data: &[u8]) -> Result<(), TftpErrorCode> {
+        assert_eq!(offset, self.written.len() as u64);
+        self.written.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn close(&mut self, id: usize, complete: bool) {
+        self.closed.push((id, complete));
+    }
+}
+
+/// The operation and block number of a sent frame.
+fn header(frame: &[u8]) -> (TftpOpCode, u16) {
+    let eth = ethernet_frame::new_unchecked(frame);
+    let ip = ipv4_packet::new_unchecked(eth.payload_slice());
+    let udp = udp_packet::new_unchecked(ip.payload_slice());
+    let tftp = tftp_packet::new_unchecked(udp.payload_slice());
+    (tftp.opcode(), tftp.block())
+}
+
+fn content(len: usize) -> Vec<u8> {
+    (0..len).map(|idx| idx as u8).collect()
+}
+
+/// Exchange packets until both sides are idle, possibly losing some on the way.
+fn run(
+    client: &mut Client<Memory>,
+    server: &mut Server<Memory>,
+    mut lose: impl FnMut((TftpOpCode, u16)) -> bool,
+) {
+    let mut client_host = Host::new(vec![CLIENT_PORT]);
+    let mut server_host = Host::peer(vec![TFTP_PORT, FIRST_PORT, FIRST_PORT + 1]);
+
+    let mut now = Instant::from_secs(0);
+    for _ in 0..100 {
+        let mut idle = true;
+        if let Some(frame) = client_host.send(now, &mut *client) {
+            idle = false;
+            if !lose(header(&frame)) {
+                server_host.receive(now, frame, &mut *server);
+            }
+        }
+
+        if let Some(frame) = server_host.send(now, &mut *server) {
+            idle = false;
+            if !lose(header(&frame)) {
+                client_host.receive(now, frame, &mut *client);
+            }
+        }
+
+        if client.poll(now) == Expiration::Never && server.poll(now) == Expiration::Never {
+            return;
+        }
+
+        if idle {
+            now += Duration::from_millis(500);
+        }
+    }
+
+    panic!("Transfer did not end");
+}
+
+#[test]
+fn read_with_options() {
+    let mut server = Server::new(
+        Memory { file: content(1300), ..Memory::default() },
+        Slice::Many(vec![None; 2]),
+        FIRST_PORT);
+    let mut client = Client::read(IP_ADDR_SERVER.into(), b"boot.img", CLIENT_PORT,
+        Memory::default());
+    client.set_options(TftpOptions {
+        block_size: Some(1468),
+        timeout: None,
+        transfer_size: Some(0),
+    });
+
+    let mut blocks = vec![];
+    run(&mut client, &mut server, |(opcode, block)| {
+        if opcode == TftpOpCode::Data {
+            blocks.push(block);
+        }
+        false
+    });
+
+    // The block size is limited by the server.
+    assert_eq!(client.status(), Status::Complete);
+    assert_eq!(client.transfer().block_size(), Server::<Memory>::DEFAULT_MAX_BLOCK_SIZE);
+    assert_eq!(client.transfer().bytes(), 1300);
+    assert_eq!(client.transfer_size(), Some(1300));
+    assert_eq!(blocks, [1, 2]);
+    assert_eq!(client.storage().written, content(1300));
+    assert_eq!(client.storage().closed, [(0, true)]);
+    assert_eq!(server.storage().closed, [(0, true)]);
+    assert!(server.transfers().iter().all(Option::is_none));
+}
+
+#[test]
+fn write_with_loss() {
+    let mut server = Server::new(Memory::default(), Slice::Many(vec![None; 2]), FIRST_PORT);
+    let mut client = Client::write(IP_ADDR_SERVER.into(), b"boot.img", CLIENT_PORT,
+        Memory { file: content(1024), ..Memory::default() });
+
+    let mut lost = vec![];
+    let mut sent = vec![];
+    run(&mut client, &mut server, |header| {
+        sent.push(header);
+        // Lose the first acknowledgment of the first block and the first final block.
+        let lose = header == (TftpOpCode::Ack, 1) || header == (TftpOpCode::Data, 3);
+        if lose && !lost.contains(&header) {
+            lost.push(header);
+            return true;
+        }
+        false
+    });
+
+    assert_eq!(client.status(), Status::Complete);
+    assert_eq!(client.transfer().bytes(), 1024);
+    assert_eq!(server.storage().written, content(1024));
+    assert_eq!(server.storage().closed, [(0, true)]);
+    assert_eq!(lost.len(), 2);
+    // Both lost packets were retransmitted, the duplicate block once acknowledged again.
+    let count = |header| sent.iter().filter(|&&sent| sent == header).count();
+    assert_eq!(count((TftpOpCode::Data, 1)), 2);
+    assert_eq!(count((TftpOpCode::Ack, 1)), 2);
+    assert_eq!(count((TftpOpCode::Data, 3)), 2);
+    assert_eq!(count((TftpOpCode::Data, 2)), 1);
+}
+
+#[test]
+fn errors() {
+    let mut server = Server::new(Memory::default(), Slice::Many(vec![None; 1]), FIRST_PORT);
+    let mut client = Client::read(IP_ADDR_SERVER.into(), b"missing", CLIENT_PORT,
+        Memory::default());
+    run(&mut client, &mut server, |_| false);
+
+    assert_eq!(client.status(), Status::Remote(TftpErrorCode::FileNotFound));
+    assert_eq!(client.storage().closed, [(0, false)]);
+    assert!(server.storage().closed.is_empty());
+    assert!(server.transfers().iter().all(Option::is_none));
+
+    // A server that never answers.
+    let mut client = Client::read(IP_ADDR_SERVER.into(), b"boot.img", CLIENT_PORT,
+        Memory::default());
+    let mut requests = 0;
+    run(&mut client, &mut server, |(opcode, _)| {
+        assert_eq!(opcode, TftpOpCode::ReadRequest);
+        requests += 1;
+        true
+    });
+
+    assert_eq!(client.status(), Status::TimedOut);
+    assert_eq!(requests, 6);
+}
@@ -0,0 +1,391 @@
+use crate::layer::{ip, udp, Error, Result};
+use crate::time::{Duration, Expiration, Instant};
+use crate::wire::{IpAddress, IpSubnet, Ipv4Subnet, Ipv6Subnet, PayloadMut};
+use crate::wire::{tftp_packet, TftpErrorCode, TftpOpCode, TftpOptions, TftpRepr};
+use crate::wire::{TFTP_DEFAULT_BLOCK_SIZE, TFTP_HEADER_LEN};
+
+use super::{Direction, Status, Storage};
+
+/// How often a packet is retransmitted before the transfer is given up.
+const MAX_RETRIES: u8 = 5;
+
+/// The retransmission timeout of transfers without a negotiated one.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The state of a single transfer, of a server or a client.
+#[derive(Clone, Copy, Debug)]
+pub struct Transfer {
+    peer: IpAddress,
+    peer_port: u16,
+    /// The address to send from, chosen by the ip layer if not set.
+    local: Option<IpAddress>,
+    local_port: u16,
+    direction: Direction,
+    block_size: u16,
+    timeout: Duration,
+    /// The number of blocks that have been acknowledged.
+    blocks: u64,
+    /// The number of bytes in the acknowledged blocks.
+    bytes: u64,
+    /// The length of the block that has been sent but not yet acknowledged.
+    sent_len: usize,
+    next: Next,
+    /// When to send next, immediately if not set.
+    send_at: Option<Instant>,
+    retries: u8,
+    opened: bool,
+    status: Status,
+}
+
+/// The packet that is sent next, and retransmitted on timeout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Next {
+    /// The request of a client.
+    Request,
+    /// The accepted options of a request.
+    OptionAck(TftpOptions),
+    /// The block following the acknowledged ones.
+    Data,
+    /// The acknowledgment of the latest block.
+    Ack,
+    /// The final acknowledgment, which is repeated for retransmissions of the last block until
+    /// the timeout has passed once.
+    Dally,
+    /// An error aborting the transfer.
+    Error(TftpErrorCode),
+    /// Nothing, the transfer has ended.
+    Done,
+}
+
+impl Transfer {
+    pub(super) fn new(
+        peer: IpAddress,
+        peer_port: u16,
+        local_port: u16,
+        direction: Direction,
+        next: Next,
+    ) -> Self {
+        Transfer {
+            peer,
+            peer_port,
+            local: None,
+            local_port,
+            direction,
+            block_size: TFTP_DEFAULT_BLOCK_SIZE,
+            timeout: DEFAULT_TIMEOUT,
+            blocks: 0,
+            bytes: 0,
+            sent_len: 0,
+            next,
+            send_at: None,
+            retries: 0,
+            opened: false,
+            status: Status::Running,
+        }
+    }
+
+    /// The address and port of the peer.
+    pub fn peer(&self) -> (IpAddress, u16) {
+        (self.peer, self.peer_port)
+    }
+
+    /// The local port of the transfer.
+    pub fn local_port(&self) -> u16 {
+        self.local_port
+    }
+
+    /// Whether the file is sent or received.
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// The size of the data blocks, as negotiated.
+    pub fn block_size(&self) -> u16 {
+        self.block_size
+    }
+
+    /// The number of bytes whose transfer has been acknowledged.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// The progress of the transfer.
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    pub(super) fn next(&self) -> Next {
+        self.next
+    }
+
+    /// Check if the transfer has ended and nothing remains to be sent.
+    pub(super) fn is_done(&self) -> bool {
+        self.next == Next::Done
+    }
+
+    pub(super) fn set_local(&mut self, local: IpAddress) {
+        self.local = Some(local);
+    }
+
+    pub(super) fn set_peer_port(&mut self, port: u16) {
+        self.peer_port = port;
+    }
+
+    /// Remember that the storage has opened the file, which must be closed in the end.
+    pub(super) fn set_opened(&mut self) {
+        self.opened = true;
+    }
+
+    /// Check if the storage has opened the file.
+    pub(super) fn is_opened(&self) -> bool {
+        self.opened
+    }
+
+    /// Adopt the options accepted by the server.
+    pub(super) fn negotiate(&mut self, options: &TftpOptions) {
+        if let Some(size) = options.block_size {
+            self.block_size = size;
+        }
+        if let Some(timeout) = options.timeout {
+            self.timeout = Duration::from_secs(timeout.into());
+        }
+    }
+
+    /// Continue with another packet that is sent immediately.
+    pub(super) fn proceed(&mut self, next: Next) {
+        self.next = next;
+        self.send_at = None;
+        self.retries = 0;
+    }
+
+    /// End the transfer without informing the peer.
+    pub(super) fn end<S: Storage>(&mut self, status: Status, storage: &mut S, id: usize) {
+        self.finish(status, storage, id);
+        self.next = Next::Done;
+    }
+
+    /// Abort the transfer, informing the peer with an error.
+    pub(super) fn abort<S: Storage>(&mut self, code: TftpErrorCode, storage: &mut S, id: usize) {
+        self.finish(Status::Local(code), storage, id);
+        self.proceed(Next::Error(code));
+    }
+
+    /// Get the time at which the next packet is sent.
+    pub(super) fn poll(&self, now: Instant) -> Expiration {
+        match self.next {
+            Next::Done => Expiration::Never,
+            _ => Expiration::When(self.send_at.unwrap_or(now)),
+        }
+    }
+
+    /// Check if a packet is to be sent.
+    pub(super) fn is_due(&self, now: Instant) -> bool {
+        self.next != Next::Done && self.send_at.is_none_or(|at| at <= now)
+    }
+
+    /// Handle an expired retransmission timer.
+    ///
+    /// Gives up after too many retransmissions and ends the transfer after dallying. Otherwise,
+    /// the packet remains due for a retransmission.
+    pub(super) fn expire<S: Storage>(&mut self, storage: &mut S, id: usize, now: Instant) {
+        match self.send_at {
+            Some(at) if at <= now && self.next != Next::Done => (),
+            _ => return,
+        }
+
+        if self.next == Next::Dally {
+            self.next = Next::Done;
+        } else if self.retries >= MAX_RETRIES {
+            self.end(Status::TimedOut, storage, id);
+        }
+    }
+
+    /// Process a packet of the peer, other than a request or option acknowledgment.
+    pub(super) fn receive<S: Storage>(&mut self, repr: &TftpRepr, storage: &mut S, id: usize) {
+        let next_block = self.block_number(1);
+        match (*repr, self.direction, self.next) {
+            (_, _, Next::Done) => (),
+            (TftpRepr::Error { code, .. }, _, _) => {
+                self.end(Status::Remote(code), storage, id);
+            },
+            (TftpRepr::Ack { block: 0 }, Direction::Send, Next::OptionAck(_)) => {
+                self.proceed(Next::Data);
+            },
+            // Only blocks that have been sent can be acknowledged.
+            (TftpRepr::Ack { block }, Direction::Send, Next::Data)
+                if block == next_block && self.send_at.is_some() =>
+            {
+                self.blocks += 1;
+                self.bytes += self.sent_len as u64;
+                if self.sent_len < usize::from(self.block_size) {
+                    self.end(Status::Complete, storage, id);
+                } else {
+                    self.proceed(Next::Data);
+                }
+            },
+            (TftpRepr::Data { block, data }, Direction::Receive, Next::OptionAck(_) | Next::Ack)
+                if block == next_block =>
+            {
+                self.write(data, storage, id);
+            },
+            (TftpRepr::Data { block, .. }, Direction::Receive, Next::Ack | Next::Dally)
+                if block == self.block_number(0) =>
+            {
+                // Our acknowledgment was lost, repeat it.
+                self.send_at = None;
+            },
+            // Duplicates are not answered, to avoid the sorcerer's apprentice syndrome.
+            _ => (),
+        }
+    }
+
+    /// Send the pending packet, except for the request of a client.
+    pub(super) fn send<P, S>(&mut self, raw: udp::RawPacket<P>, storage: &mut S, id: usize)
+        -> Result<()>
+    where
+        P: PayloadMut,
+        S: Storage,
+    {
+        let now = raw.handle.info().timestamp();
+        let result = match self.next {
+            Next::Request | Next::Done => return Err(Error::Illegal),
+            Next::OptionAck(options) => self.send_repr(raw, &TftpRepr::OptionAck { options }),
+            Next::Data => self.send_data(raw, storage, id),
+            Next::Ack | Next::Dally => {
+                let block = self.block_number(0);
+                self.send_repr(raw, &TftpRepr::Ack { block })
+            },
+            Next::Error(code) => {
+                self.next = Next::Done;
+                return self.send_repr(raw, &TftpRepr::Error { code, message: &[] });
+            },
+        };
+
+        // The storage failed and the error is pending instead.
+        if let Next::Error(_) = self.next {
+            return result;
+        }
+
+        self.sent(now);
+        result
+    }
+
+    /// Start the retransmission timer after a packet has been sent.
+    ///
+    /// Also used when sending failed, for example for a missing route. The packet is then repeated
+    /// with the next timeout, just like a lost one.
+    pub(super) fn sent(&mut self, now: Instant) {
+        if self.send_at.is_some() {
+            self.retries += 1;
+        }
+        self.send_at = Some(now + self.timeout);
+    }
+
+    /// Send a packet that is completely described by its representation.
+    pub(super) fn send_repr<P: PayloadMut>(&self, raw: udp::RawPacket<P>, repr: &TftpRepr)
+        -> Result<()>
+    {
+        let mut packet = raw.prepare(self.init(repr.buffer_len())?)?;
+        repr.emit(tftp_packet::new_unchecked_mut(packet.packet.payload_mut_slice()));
+        packet.send()
+    }
+
+    /// Send the next block, read directly into the packet.
+    fn send_data<P, S>(&mut self, raw: udp::RawPacket<P>, storage: &mut S, id: usize)
+        -> Result<()>
+    where
+        P: PayloadMut,
+        S: Storage,
+    {
+        let block_size = usize::from(self.block_size);
+        let mut packet = raw.prepare(self.init(TFTP_HEADER_LEN + block_size)?)?;
+        let len = match self.read(packet.packet.payload_mut_slice(), storage, id) {
+            Some(len) => len,
+            // The error is sent with the next packet.
+            None => return Ok(()),
+        };
+
+        if len < block_size {
+            // The last block is read again after resizing the packet to fit it.
+            packet = packet.reinit(self.init(TFTP_HEADER_LEN + len)?)?;
+            if self.read(packet.packet.payload_mut_slice(), storage, id) != Some(len) {
+                return Ok(());
+            }
+        }
+
+        let tftp = tftp_packet::new_unchecked_mut(packet.packet.payload_mut_slice());
+        tftp.set_opcode(TftpOpCode::Data);
+        tftp.set_block(self.block_number(1));
+        self.sent_len = len;
+        packet.send()
+    }
+
+    /// Read the next block into a data packet, or abort on failure.
+    fn read<S: Storage>(&mut self, payload: &mut [u8], storage: &mut S, id: usize)
+        -> Option<usize>
+    {
+        let buffer = tftp_packet::new_unchecked_mut(payload).data_mut();
+        match storage.read(id, self.bytes, buffer) {
+            Ok(len) => Some(len.min(buffer.len())),
+            Err(code) => {
+                self.abort(code, storage, id);
+                None
+            },
+        }
+    }
+
+    /// Write a newly received block.
+    fn write<S: Storage>(&mut self, data: &[u8], storage: &mut S, id: usize) {
+        if data.len() > usize::from(self.block_size) {
+            return self.abort(TftpErrorCode::IllegalOperation, storage, id);
+        }
+
+        if let Err(code) = storage.write(id, self.bytes, data) {
+            return self.abort(code, storage, id);
+        }
+
+        self.blocks += 1;
+        self.bytes += data.len() as u64;
+        if data.len() < usize::from(self.block_size) {
+            self.finish(Status::Complete, storage, id);
+            self.proceed(Next::Dally);
+        } else {
+            self.proceed(Next::Ack);
+        }
+    }
+
+    /// End the transfer, closing the file.
+    fn finish<S: Storage>(&mut self, status: Status, storage: &mut S, id: usize) {
+        if self.status.is_finished() {
+            return;
+        }
+
+        self.status = status;
+        if self.opened {
+            storage.close(id, status == Status::Complete);
+        }
+    }
+
+    /// The block number following the acknowledged blocks by some offset, wrapping around.
+    fn block_number(&self, offset: u64) -> u16 {
+        (self.blocks + offset) as u16
+    }
+
+    fn init(&self, payload: usize) -> Result<udp::Init> {
+        let source = match (self.local, self.peer) {
+            (Some(local), _) => ip::Source::Exact(local),
+            (None, IpAddress::Ipv4(_)) => IpSubnet::from(Ipv4Subnet::ANY).into(),
+            (None, IpAddress::Ipv6(_)) => IpSubnet::from(Ipv6Subnet::ANY).into(),
+            _ => return Err(Error::Illegal),
+        };
+
+        Ok(udp::Init {
+            source,
+            src_port: self.local_port,
+            dst_addr: self.peer,
+            dst_port: self.peer_port,
+            payload,
+            dscp: 0,
+        })
+    }
+}
@@ -0,0 +1,105 @@
+use crate::managed::Slice;
+use crate::wire::Payload;
+
+use super::{Packet, RawPacket, Recv, Send};
+
+/// A handler for the datagrams of one local port.
+pub struct PortRoute<H> {
+    /// The destination port of all datagrams routed to the handler.
+    pub port: u16,
+    /// The handler of the matching datagrams.
+    pub handler: H,
+}
+
+/// Distributes received datagrams to handlers by their destination port.
+///
+/// The endpoint hands all datagrams of its open ports to a single handler. The dispatch is such a
+/// handler that passes each datagram on to the route of its destination port, so that several
+/// services such as dns, dhcp and the application itself can share one endpoint. When several
+/// routes have the same port the first of them is used. Datagrams of ports without a route are
+/// counted and dropped.
+///
+/// When sending, the packet buffers are offered to the handlers in turn.
+///
+/// Services of different types can be routed with trait objects as handlers.
+///
+/// ## Example
+///
+/// ```
+/// use ethox::layer::udp::{self, PortDispatch, PortRoute};
+/// use ethox::managed::Slice;
+/// use ethox::wire::Payload;
+///
+/// fn dispatch<'a, P: Payload>(dns: &'a mut dyn udp::Recv<P>, dhcp: &'a mut dyn udp::Recv<P>) {
+///     let mut dispatch = PortDispatch::new(Slice::Many(vec![
+///         PortRoute { port: 53, handler: dns },
+///         PortRoute { port: 68, handler: dhcp },
+///     ]));
+///     // Hand `&mut dispatch` to the receiver of a udp endpoint open on both ports.
+///     assert_eq!(dispatch.route(68), Some(1));
+/// }
+/// ```
+pub struct PortDispatch<'a, H> {
+    routes: Slice<'a, PortRoute<H>>,
+    next: usize,
+    dropped: u64,
+}
+
+impl<'a, H> PortDispatch<'a, H> {
+    /// Create a dispatch over some routes.
+    pub fn new(routes: Slice<'a, PortRoute<H>>) -> Self {
+        PortDispatch {
+            routes,
+            next: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Get a reference to the routes.
+    pub fn routes(&self) -> &[PortRoute<H>] {
+        &self.routes
+    }
+
+    /// Get a mutable reference to the routes, for example to change their ports.
+    pub fn routes_mut(&mut self) -> &mut [PortRoute<H>] {
+        &mut self.routes
+    }
+
+    /// The number of datagrams to ports without a route.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Find the route of a destination port.
+    pub fn route(&self, port: u16) -> Option<usize> {
+        self.routes.iter()
+            .position(|route| route.port == port)
+    }
+}
+
+impl<P, H> Recv<P> for PortDispatch<'_, H>
+    where P: Payload, H: Recv<P>,
+{
+    fn receive(&mut self, packet: Packet<P>) {
+        let route = self.route(packet.packet.repr().dst_port);
+
+        match route.and_then(|idx| self.routes.get_mut(idx)) {
+            Some(route) => route.handler.receive(packet),
+            None => self.dropped += 1,
+        }
+    }
+}
+
+impl<P, H> Send<P> for PortDispatch<'_, H>
+    where P: Payload, H: Send<P>,
+{
+    fn send(&mut self, packet: RawPacket<P>) {
+        if self.routes.is_empty() {
+            return;
+        }
+
+        let idx = self.next % self.routes.len();
+        self.next = idx + 1;
+        self.routes[idx].handler.send(packet)
+    }
+}
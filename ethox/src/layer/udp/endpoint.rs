@@ -1,6 +1,8 @@
-use crate::layer::{ip, FnHandler};
+use crate::layer::{ip, Detail, Error, Failure, FnHandler, Operation, Origin};
+use crate::layer::ports::{Registry, Transport};
 use crate::managed::Slice;
-use crate::wire::{IpProtocol, Payload, PayloadMut, UdpPacket};
+use crate::wire::{Icmpv4DstUnreachable, IpProtocol, Payload, PayloadMut};
+use crate::wire::{UdpChecksum, UdpPacket};
 
 use super::{Recv, Send};
 use super::packet::{Handle, Packet, RawPacket};
@@ -16,6 +18,37 @@ pub struct Endpoint<'a> {
 
     /// Whether to filter incoming packets based on port.
     filter_ports: bool,
+
+    /// Whether to receive UDP-Lite datagrams as well.
+    lite: bool,
+
+    /// Whether datagrams may be sent to broadcast addresses.
+    broadcast: bool,
+
+    /// Counters of the received datagrams.
+    statistics: Statistics,
+
+    /// The context of the last dropped datagram.
+    last_failure: Option<Failure>,
+}
+
+/// Counters of the datagrams arriving at an endpoint.
+///
+/// Since there are no connections, the counters cover all open ports together. An application
+/// with a dedicated endpoint for one flow can use them as the statistics of that flow.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Statistics {
+    /// The number of datagrams passed to the upper layer.
+    pub received: u64,
+
+    /// The number of payload bytes in datagrams passed to the upper layer.
+    pub received_bytes: u64,
+
+    /// The number of datagrams dropped due to an invalid length or checksum.
+    pub malformed: u64,
+
+    /// The number of datagrams dropped as their destination port was not open.
+    pub closed_port: u64,
 }
 
 /// An endpoint borrowed for receiving.
@@ -29,14 +62,14 @@ pub struct Receiver<'a, 'e, H> {
 /// An endpoint borrowed for sending.
 pub struct Sender<'a, 'e, H> {
     // FIXME: I don't know, maybe we should need it for selecting a source port?
-    _endpoint: UdpEndpoint<'a, 'e>,
+    endpoint: UdpEndpoint<'a, 'e>,
 
     /// The upper protocol sender.
     handler: H,
 }
 
 struct UdpEndpoint<'a, 'e> {
-    inner: &'a Endpoint<'e>,
+    inner: &'a mut Endpoint<'e>,
 }
 
 
@@ -48,6 +81,10 @@ impl<'a> Endpoint<'a> {
         Endpoint {
             ports: ports.into(),
             filter_ports: true,
+            lite: false,
+            broadcast: false,
+            statistics: Statistics::default(),
+            last_failure: None,
         }
     }
 
@@ -60,6 +97,10 @@ impl<'a> Endpoint<'a> {
         Endpoint {
             ports: Slice::empty(),
             filter_ports: false,
+            lite: false,
+            broadcast: false,
+            statistics: Statistics::default(),
+            last_failure: None,
         }
     }
 
@@ -75,7 +116,7 @@ impl<'a> Endpoint<'a> {
 
     /// Send packets using this mutably borrowed endpoint.
     pub fn send<H>(&mut self, handler: H) -> Sender<'_, 'a, H> {
-        Sender { _endpoint: self.get_mut(), handler, }
+        Sender { endpoint: self.get_mut(), handler, }
     }
 
     /// Send packets using this mutably borrowed endpoint and a function.
@@ -92,6 +133,78 @@ impl<'a> Endpoint<'a> {
         self.filter_ports = filter_ports;
     }
 
+    /// Set whether to receive UDP-Lite datagrams as well.
+    ///
+    /// UDP-Lite (RFC3828) is a separate ip protocol with the same ports and header as UDP, but its
+    /// checksum may cover only a prefix of the datagram. The handler receives these datagrams like
+    /// any other, their representation has a `checksum_coverage`. The checksum of the covered
+    /// part is always verified since devices do not offload it. Disabled by default.
+    ///
+    /// Send UDP-Lite datagrams with [`RawPacket::prepare_lite`].
+    ///
+    /// [`RawPacket::prepare_lite`]: struct.RawPacket.html#method.prepare_lite
+    pub fn set_lite(&mut self, lite: bool) {
+        self.lite = lite;
+    }
+
+    /// Check if UDP-Lite datagrams are received.
+    pub fn lite(&self) -> bool {
+        self.lite
+    }
+
+    /// Set whether datagrams may be sent to broadcast addresses.
+    ///
+    /// Like `SO_BROADCAST` of a socket, this guards against flooding the link by accident. When
+    /// disabled, preparing a datagram to the limited broadcast `255.255.255.255` or the broadcast
+    /// address of an own subnet fails with `Illegal`. When enabled, such datagrams are framed
+    /// with the ethernet broadcast address. Disabled by default.
+    pub fn set_broadcast(&mut self, broadcast: bool) {
+        self.broadcast = broadcast;
+    }
+
+    /// Check if datagrams may be sent to broadcast addresses.
+    pub fn broadcast(&self) -> bool {
+        self.broadcast
+    }
+
+    /// Reserve the open ports of the endpoint in a registry.
+    ///
+    /// Detects another endpoint that has already bound one of the ports for udp, which fails with
+    /// `Illegal`. Either all or none of the ports are reserved. The same ports may be bound for
+    /// tcp. See the [`ports`] module.
+    ///
+    /// [`ports`]: ../ports/index.html
+    pub fn reserve_ports(&self, registry: &mut Registry) -> crate::layer::Result<()> {
+        let ports = self.ports.as_slice();
+        for (idx, &port) in ports.iter().enumerate() {
+            if let Err(err) = registry.reserve(Transport::Udp, port) {
+                for &port in &ports[..idx] {
+                    registry.release(Transport::Udp, port);
+                }
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the counters of received datagrams.
+    pub fn statistics(&self) -> Statistics {
+        self.statistics
+    }
+
+    /// The context of the last datagram that was dropped.
+    ///
+    /// Tells whether it was malformed, including which check failed, or sent to a closed port.
+    pub fn last_failure(&self) -> Option<Failure> {
+        self.last_failure
+    }
+
+    fn record_drop(&mut self, detail: Detail) {
+        let failure = Failure::new(Error::Illegal, Origin::Udp, Operation::Receive, detail);
+        self.last_failure = Some(failure);
+    }
+
     fn accepts(&self, port: u16) -> bool {
         !self.filter_ports || self.ports.as_slice().contains(&port)
     }
@@ -105,7 +218,7 @@ impl<'a> Endpoint<'a> {
 
 impl<P, H> ip::Recv<P> for Receiver<'_, '_, H>
 where
-    P: Payload,
+    P: PayloadMut,
     H: Recv<P>,
 {
     fn receive(&mut self, ip::InPacket { handle, packet }: ip::InPacket<P>) {
@@ -116,22 +229,54 @@ where
             IpProtocol::Udp => {
                 match UdpPacket::new_checked(packet, checksum) {
                     Ok(packet) => packet,
-                    Err(_) => return,
+                    Err(err) => {
+                        self.endpoint.inner.statistics.malformed += 1;
+                        return self.endpoint.inner.record_drop(Detail::Wire(err));
+                    },
+                }
+            },
+            IpProtocol::UdpLite if self.endpoint.inner.lite => {
+                let ip_repr = packet.repr();
+                let checksum = UdpChecksum::Manual {
+                    src_addr: ip_repr.src_addr(),
+                    dst_addr: ip_repr.dst_addr(),
+                };
+                match UdpPacket::new_checked_lite(packet, checksum) {
+                    Ok(packet) => packet,
+                    Err(err) => {
+                        self.endpoint.inner.statistics.malformed += 1;
+                        return self.endpoint.inner.record_drop(Detail::Wire(err));
+                    },
                 }
             },
             _ => return,
         };
 
-        if !self.endpoint.inner.accepts(packet.repr().dst_port) {
-            // FIXME: we might send ICMP unreachable but may want to have a silent configuration
-            // that does not.
+        let dst_port = packet.repr().dst_port;
+        if !self.endpoint.inner.accepts(dst_port) {
+            self.endpoint.inner.statistics.closed_port += 1;
+            self.endpoint.inner.record_drop(Detail::ClosedPort(dst_port));
+            // Answer if the ip policy permits, otherwise the packet is silently dropped.
+            let packet = ip::InPacket { handle, packet: packet.into_inner() };
+            let _ = packet
+                .unreachable(Icmpv4DstUnreachable::PortUnreachable)
+                .and_then(ip::OutPacket::send);
             return
         }
 
-        let handle = Handle::new(handle);
+        let statistics = &mut self.endpoint.inner.statistics;
+        statistics.received += 1;
+        statistics.received_bytes += packet.payload_slice().len() as u64;
+
+        let handle = Handle::new(handle, self.endpoint.inner.broadcast);
         let packet = Packet::new(handle, packet);
         self.handler.receive(packet);
     }
+
+    fn accepts_protocol(&self, protocol: IpProtocol) -> bool {
+        protocol == IpProtocol::Udp
+            || (self.endpoint.inner.lite && protocol == IpProtocol::UdpLite)
+    }
 }
 
 impl<P, H> ip::Send<P> for Sender<'_, '_, H>
@@ -141,7 +286,7 @@ where
 {
     fn send<'a>(&mut self, packet: ip::RawPacket<'a, P>) {
         let ip::RawPacket { handle, payload } = packet;
-        let handle = Handle::new(handle);
+        let handle = Handle::new(handle, self.endpoint.inner.broadcast);
         let packet = RawPacket::new(handle, payload);
 
         self.handler.send(packet)
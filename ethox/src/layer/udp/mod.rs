@@ -5,17 +5,39 @@
 //! possible to respond dynamically at any port without settting up logic prior to a packet
 //! arriving (e.g. dynamic port knocking) but also simplifies implementation by enforcing clear cut
 //! separation of concerns.
+//!
+//! Several services on one endpoint are served by routing their datagrams with a
+//! [`PortDispatch`] by destination port, instead of matching on the port in a hand-written
+//! handler.
+//!
+//! Datagrams to broadcast addresses are refused unless enabled with
+//! [`Endpoint::set_broadcast`], similar to the `SO_BROADCAST` option of sockets.
+//!
+//! [`Endpoint::set_broadcast`]: struct.Endpoint.html#method.set_broadcast
+//! [`PortDispatch`]: struct.PortDispatch.html
 use crate::wire::Payload;
+#[cfg(feature = "std")]
+use crate::wire::{pretty_print::Formatter, udp_packet};
 
+mod dispatch;
 mod endpoint;
 mod packet;
+pub mod quic;
 #[cfg(test)]
 mod tests;
+#[cfg(test)]
+pub(crate) mod testing;
+
+pub use dispatch::{
+    PortDispatch,
+    PortRoute,
+};
 
 pub use endpoint::{
     Endpoint,
     Receiver,
     Sender,
+    Statistics,
 };
 
 pub use packet::{
@@ -23,6 +45,7 @@ pub use packet::{
     Init,
     Packet,
     RawPacket,
+    VectoredPacket,
 };
 
 /// A UDP receiver.
@@ -45,8 +68,19 @@ pub trait Send<P: Payload> {
     fn send(&mut self, raw: RawPacket<P>);
 }
 
+/// Available only on `std` because it prints to standard error, or to the logger with the `log`
+/// feature.
+///
+/// The listing starts at the enclosing ip header so that the addresses are visible as well.
+#[cfg(feature = "std")]
+impl<P: Payload> Recv<P> for Formatter<udp_packet> {
+    fn receive(&mut self, frame: Packet<P>) {
+        net_print!("{}", frame.packet.get_ref());
+    }
+}
+
 impl<P, C> Recv<P> for &'_ mut C
-    where P: Payload, C: Recv<P>,
+    where P: Payload, C: Recv<P> + ?Sized,
 {
     fn receive(&mut self, frame: Packet<P>) {
         (**self).receive(frame)
@@ -54,7 +88,7 @@ impl<P, C> Recv<P> for &'_ mut C
 }
 
 impl<P, C> Send<P> for &'_ mut C
-    where P: Payload, C: Send<P>,
+    where P: Payload, C: Send<P> + ?Sized,
 {
     fn send(&mut self, frame: RawPacket<P>) {
         (**self).send(frame)
@@ -2,8 +2,10 @@ use core::convert::TryFrom;
 
 use crate::nic::Info;
 use crate::layer::{Error, Result, ip};
-use crate::wire::{Payload, PayloadMut};
-use crate::wire::{IpAddress, IpProtocol, UdpChecksum, UdpPacket, UdpRepr, udp_packet};
+use crate::wire::{Payload, PayloadMut, PayloadVectored};
+use crate::wire::{EthernetFrame, EthernetRepr, IpAddress, IpProtocol, IpRepr};
+use crate::wire::{UdpChecksum, UdpPacket, UdpRepr, udp_packet};
+use crate::wire::ip::checksum;
 
 /// An incoming UDP packet.
 pub struct Packet<'a, P: Payload> {
@@ -21,6 +23,20 @@ pub struct RawPacket<'a, P: Payload> {
     pub payload: &'a mut P,
 }
 
+/// An outgoing UDP packet whose payload is stored in a segment of its own.
+///
+/// Created with [`RawPacket::prepare_vectored`] on buffers supporting scatter-gather. The headers
+/// are in the contiguous part of the buffer and complete their length fields only when sent.
+///
+/// [`RawPacket::prepare_vectored`]: struct.RawPacket.html#method.prepare_vectored
+pub struct VectoredPacket<'a, P: PayloadVectored> {
+    /// A reference to the UDP endpoint state.
+    pub handle: Handle<'a>,
+    payload: &'a mut P,
+    eth_repr: EthernetRepr,
+    ip_repr: IpRepr,
+}
+
 /// A reference to the endpoint of layers below (phy + eth + ip + udp).
 ///
 /// This is not really useful on its own but should instead be used either within a `Packet` or a
@@ -28,7 +44,8 @@ pub struct RawPacket<'a, P: Payload> {
 /// struct to fulfill their task.
 pub struct Handle<'a> {
     pub(crate) inner: ip::Handle<'a>,
-    // Nothing more, there is no logic here.
+    /// Whether the endpoint permits sending to broadcast addresses.
+    pub(crate) broadcast: bool,
 }
 
 /// An initializer for a UDP packet.
@@ -54,6 +71,7 @@ pub struct Handle<'a> {
 ///         dst_addr: IpAddress::v4(192, 168, 0, 1),
 ///         dst_port: 43,
 ///         payload: HELLO.len(),
+///         dscp: 0,
 ///     };
 ///
 ///     let mut out = raw.prepare(init)?;
@@ -75,14 +93,18 @@ pub struct Init {
     pub dst_port: u16,
     /// The length of the payload which is sent.
     pub payload: usize,
+    /// The differentiated services code point, passed directly to the ip layer below.
+    pub dscp: u8,
 }
 
 impl<'a> Handle<'a> {
     pub(crate) fn new(
         handle: ip::Handle<'a>,
+        broadcast: bool,
     ) -> Self {
         Handle {
             inner: handle,
+            broadcast,
         }
     }
 
@@ -91,10 +113,16 @@ impl<'a> Handle<'a> {
         self.inner.info()
     }
 
+    /// Request the hardware timestamp of the packet when it is sent.
+    pub fn request_tx_timestamp(&mut self, id: u32) -> Result<()> {
+        self.inner.request_tx_timestamp(id)
+    }
+
     /// Proof to the compiler that we can shorten the lifetime arbitrarily.
     pub fn borrow_mut(&mut self) -> Handle {
         Handle {
             inner: self.inner.borrow_mut(),
+            broadcast: self.broadcast,
         }
     }
 }
@@ -129,13 +157,49 @@ impl<'a, P: Payload> Packet<'a, P> {
         RawPacket::new(self.handle, self.packet.into_inner().into_raw())
     }
 
+    /// Exchange the buffer of the packet for a spare, see [`eth::InPacket::retain`].
+    ///
+    /// [`eth::InPacket::retain`]: ../eth/struct.InPacket.html#method.retain
+    pub fn retain(self, spare: &mut P) -> Result<()>
+        where P: PayloadMut,
+    {
+        let RawPacket { mut handle, payload } = self.deinit();
+        handle.inner.retain(payload, spare)
+    }
+
+    /// Change the differentiated services code point of the enclosing ip packet.
+    ///
+    /// Overrides the code point of the `Init` for a packet that is about to be sent.
+    pub fn set_dscp(&mut self, dscp: u8)
+        where P: PayloadMut,
+    {
+        self.packet.get_mut().set_dscp(dscp)
+    }
+
+    /// Mark the enclosing ip packet with explicit congestion notification bits.
+    ///
+    /// The bits are `0b10` or `0b01` for an ECN capable transport and `0b11` for congestion
+    /// experienced, see rfc3168.
+    pub fn set_ecn(&mut self, ecn: u8)
+        where P: PayloadMut,
+    {
+        self.packet.get_mut().set_ecn(ecn)
+    }
+
     /// Called last after having initialized the payload.
     pub fn send(mut self) -> Result<()>
         where P: PayloadMut,
     {
         let capabilities = self.handle.info().capabilities();
         let ip_repr = self.packet.get_ref().repr();
-        let checksum = capabilities.udp().tx_checksum(ip_repr);
+        let checksum = match self.packet.repr().checksum_coverage {
+            // Devices do not offload the partial checksum.
+            Some(_) => UdpChecksum::Manual {
+                src_addr: ip_repr.src_addr(),
+                dst_addr: ip_repr.dst_addr(),
+            },
+            None => capabilities.udp().tx_checksum(ip_repr),
+        };
         self.packet.fill_checksum(checksum);
         let lower = ip::OutPacket::new_unchecked(
             self.handle.inner,
@@ -155,8 +219,49 @@ impl<'a, P: Payload + PayloadMut> RawPacket<'a, P> {
         }
     }
 
+    /// Reserve headroom in front of the packet that is prepared next.
+    ///
+    /// See [`eth::RawPacket::reserve_headroom`] for details.
+    ///
+    /// [`eth::RawPacket::reserve_headroom`]: ../eth/struct.RawPacket.html#method.reserve_headroom
+    pub fn reserve_headroom(&mut self, headroom: usize) -> Result<()> {
+        self.payload.set_headroom(headroom)?;
+        Ok(())
+    }
+
     /// Initialize to a valid ip packet.
+    ///
+    /// Fails with `Illegal` for a broadcast destination unless the endpoint permits it, see
+    /// [`Endpoint::set_broadcast`].
+    ///
+    /// [`Endpoint::set_broadcast`]: struct.Endpoint.html#method.set_broadcast
     pub fn prepare(self, init: Init) -> Result<Packet<'a, P>> {
+        self.prepare_with(init, None)
+    }
+
+    /// Initialize to a UDP-Lite datagram with a partial checksum.
+    ///
+    /// The checksum covers the header and the first `coverage` bytes of the payload, errors in
+    /// the rest of the payload are not detected. A coverage of the whole payload or more is sent
+    /// as zero, which means the whole datagram. See [`Endpoint::set_lite`] for receiving.
+    ///
+    /// [`Endpoint::set_lite`]: struct.Endpoint.html#method.set_lite
+    pub fn prepare_lite(self, init: Init, coverage: usize) -> Result<Packet<'a, P>> {
+        let coverage = if coverage >= init.payload {
+            0
+        } else {
+            // Can not fail, smaller than the payload length which is checked when preparing.
+            u16::try_from(coverage + 8).map_err(|_| Error::BadSize)?
+        };
+        self.prepare_with(init, Some(coverage))
+    }
+
+    fn prepare_with(self, init: Init, coverage: Option<u16>) -> Result<Packet<'a, P>> {
+        let broadcast = self.handle.broadcast;
+        if !broadcast && self.handle.inner.is_broadcast(init.dst_addr) {
+            return Err(Error::Illegal);
+        }
+
         let lower = ip::RawPacket::new(
             self.handle.inner,
             self.payload);
@@ -168,16 +273,18 @@ impl<'a, P: Payload + PayloadMut> RawPacket<'a, P> {
         let lower_init = ip::Init {
             source: init.source,
             dst_addr: init.dst_addr,
-            protocol: IpProtocol::Udp,
+            protocol: if coverage.is_some() { IpProtocol::UdpLite } else { IpProtocol::Udp },
             payload: packet_len,
+            hop_limit: None,
+            dscp: init.dscp,
         };
 
         let prepared = lower.prepare(lower_init)?;
         let ip::InPacket { handle, mut packet } = prepared.into_incoming();
-        let repr = init.initialize(&mut packet)?;
+        let repr = init.initialize(&mut packet, coverage)?;
 
         // Reconstruct the handle.
-        let handle = Handle::new(handle);
+        let handle = Handle::new(handle, broadcast);
 
         Ok(Packet {
             handle,
@@ -186,14 +293,82 @@ impl<'a, P: Payload + PayloadMut> RawPacket<'a, P> {
     }
 }
 
+impl<'a, P: PayloadVectored> RawPacket<'a, P> {
+    /// Initialize a packet whose payload is placed in a separate segment.
+    ///
+    /// Only the headers are written to the contiguous buffer while a segment with the length of
+    /// the payload is appended to it. A device with scatter-gather DMA can then transmit the
+    /// payload from a buffer of its own, instead of having it copied behind the headers.
+    pub fn prepare_vectored(self, init: Init) -> Result<VectoredPacket<'a, P>> {
+        let RawPacket { handle, payload } = self;
+        payload.clear_segments();
+        payload.push_segment(init.payload)?;
+
+        // The length fields only cover the headers until the packet is sent.
+        let headers = Init { payload: 0, ..init };
+        let Packet { handle, packet } = RawPacket::new(handle, payload).prepare(headers)?;
+        let ip = packet.into_inner();
+        let ip_repr = ip.repr();
+        let frame = ip.into_inner();
+        let eth_repr = frame.repr();
+
+        Ok(VectoredPacket {
+            handle,
+            payload: frame.into_inner(),
+            eth_repr,
+            ip_repr,
+        })
+    }
+}
+
+impl<'a, P: PayloadVectored> VectoredPacket<'a, P> {
+    /// A mutable slice containing the payload segment.
+    pub fn payload_mut_slice(&mut self) -> &mut [u8] {
+        match self.payload.segment_mut(0) {
+            Some(segment) => segment.as_mut_slice(),
+            None => &mut [],
+        }
+    }
+
+    /// Called last after having initialized the payload.
+    pub fn send(self) -> Result<()> {
+        let VectoredPacket { handle, payload, eth_repr, ip_repr } = self;
+        let length = u16::try_from(8 + payload.segments_len())
+            .map_err(|_| Error::BadSize)?;
+        let payload_sum = checksum::segments(&*payload);
+        let capabilities = handle.info().capabilities();
+        let udp_checksum = capabilities.udp().tx_checksum(ip_repr.clone());
+
+        let frame = EthernetFrame::new_unchecked(payload, eth_repr);
+        let mut packet = ip::IpPacket::new_unchecked(frame, ip_repr);
+        let header = udp_packet::new_unchecked_mut(packet.payload_mut().as_mut_slice());
+        header.set_len(length);
+        match udp_checksum {
+            // Checksum optional, so we don't fill it.
+            UdpChecksum::Lazy { src_addr: IpAddress::Ipv4(_), dst_addr: IpAddress::Ipv4(_) }
+            | UdpChecksum::Ignored => (),
+            UdpChecksum::Manual { src_addr, dst_addr }
+            | UdpChecksum::Lazy { src_addr, dst_addr } => {
+                header.fill_checksum_detached(src_addr, dst_addr, payload_sum)
+            },
+        }
+
+        ip::OutPacket::new_unchecked(handle.inner, packet)
+            .send_vectored()
+    }
+}
+
 impl Init {
-    fn initialize(&self, payload: &mut impl PayloadMut) -> Result<UdpRepr> {
+    fn initialize(&self, payload: &mut impl PayloadMut, coverage: Option<u16>)
+        -> Result<UdpRepr>
+    {
         let repr = UdpRepr {
             src_port: self.src_port,
             dst_port: self.dst_port,
             // Can't overflow, already inited ip with that length.
             length: u16::try_from(self.payload + 8)
                 .map_err(|_| Error::BadSize)?,
+            checksum_coverage: coverage,
         };
 
         // Assumes length was already dealt with.
@@ -0,0 +1,133 @@
+//! Routing of QUIC datagrams by their connection ID.
+//!
+//! A QUIC implementation picks the connection IDs that its peers use to address it. By choosing
+//! them with a common prefix per connection, or per worker, the [`Demux`] can hand each incoming
+//! datagram of a port to the handler responsible for it without decrypting anything. Long header
+//! packets with an unknown connection ID are the first packets of new connections, chosen by the
+//! client, and go to a separate handler if there is one.
+//!
+//! The handlers send their datagrams with the usual udp packets. The ECN bits required by the
+//! congestion control of QUIC are set per packet with [`Packet::set_ecn`] before sending, and
+//! read on received packets with [`IpPacket::ecn`] of the enclosing ip packet.
+//!
+//! [`Demux`]: struct.Demux.html
+//! [`Packet::set_ecn`]: ../struct.Packet.html#method.set_ecn
+//! [`IpPacket::ecn`]: ../../ip/enum.IpPacket.html#method.ecn
+use crate::managed::Slice;
+use crate::wire::{Payload, QuicConnectionId, QuicHeader};
+
+use super::{Packet, RawPacket, Recv, Send};
+
+/// A handler for the datagrams of connection IDs with a common prefix.
+pub struct Route<H> {
+    /// The prefix of all connection IDs routed to the handler.
+    pub prefix: QuicConnectionId,
+    /// The handler of the matching datagrams.
+    pub handler: H,
+}
+
+/// Distributes the QUIC datagrams of one port to handlers.
+///
+/// A datagram goes to the route with the longest prefix of its destination connection ID. Long
+/// header packets matching no route go to the route chosen with [`accept_with`]. All other
+/// datagrams, and those that are not valid QUIC packets, are counted and dropped.
+///
+/// When sending, the packet buffers are offered to the handlers in turn.
+///
+/// [`accept_with`]: #method.accept_with
+pub struct Demux<'a, H> {
+    port: u16,
+    short_len: usize,
+    routes: Slice<'a, Route<H>>,
+    accept: Option<usize>,
+    next: usize,
+    dropped: u64,
+}
+
+impl<'a, H> Demux<'a, H> {
+    /// Create a demultiplexer for datagrams to a local port.
+    ///
+    /// The `short_len` is the length of all connection IDs issued by the local endpoint, which
+    /// short headers do not encode.
+    pub fn new(port: u16, short_len: usize, routes: Slice<'a, Route<H>>) -> Self {
+        Demux {
+            port,
+            short_len,
+            routes,
+            accept: None,
+            next: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Choose the route receiving packets for new connections.
+    ///
+    /// These are long header packets whose connection ID matches no prefix.
+    pub fn accept_with(&mut self, route: Option<usize>) {
+        self.accept = route;
+    }
+
+    /// Get a reference to the routes.
+    pub fn routes(&self) -> &[Route<H>] {
+        &self.routes
+    }
+
+    /// Get a mutable reference to the routes, for example to change their prefixes.
+    pub fn routes_mut(&mut self) -> &mut [Route<H>] {
+        &mut self.routes
+    }
+
+    /// The number of datagrams that could not be routed.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Find the route of a header.
+    pub fn route(&self, header: &QuicHeader) -> Option<usize> {
+        let dst = header.dst();
+        let matching = self.routes.iter()
+            .enumerate()
+            .filter(|(_, route)| dst.starts_with(&route.prefix))
+            .max_by_key(|(_, route)| route.prefix.len())
+            .map(|(idx, _)| idx);
+
+        match matching {
+            Some(idx) => Some(idx),
+            None if header.is_long() => self.accept,
+            None => None,
+        }
+    }
+}
+
+impl<P, H> Recv<P> for Demux<'_, H>
+    where P: Payload, H: Recv<P>,
+{
+    fn receive(&mut self, packet: Packet<P>) {
+        if packet.packet.repr().dst_port != self.port {
+            return;
+        }
+
+        let route = QuicHeader::parse(packet.packet.payload_slice(), self.short_len)
+            .ok()
+            .and_then(|header| self.route(&header));
+
+        match route.and_then(|idx| self.routes.get_mut(idx)) {
+            Some(route) => route.handler.receive(packet),
+            None => self.dropped += 1,
+        }
+    }
+}
+
+impl<P, H> Send<P> for Demux<'_, H>
+    where P: Payload, H: Send<P>,
+{
+    fn send(&mut self, packet: RawPacket<P>) {
+        if self.routes.is_empty() {
+            return;
+        }
+
+        let idx = self.next % self.routes.len();
+        self.next = idx + 1;
+        self.routes[idx].handler.send(packet)
+    }
+}
@@ -0,0 +1,109 @@
+//! Utilities for testing protocols on top of udp.
+//!
+//! Each [`Host`] is a complete stack on a nic holding a single packet. Two hosts on the same
+//! subnet exchange frames by handing them over explicitly, which lets tests inspect or drop them.
+use crate::layer::{arp, eth, ip, udp};
+use crate::managed::Slice;
+use crate::nic::{external::External, Device};
+use crate::time::Instant;
+use crate::wire::{EthernetAddress, IpCidr, Ipv4Address};
+use crate::wire::{ethernet_frame, ipv4_packet, udp_packet};
+
+pub(crate) const MAC_ADDR_HOST: EthernetAddress = EthernetAddress([0x52, 0x54, 0, 0, 0, 1]);
+pub(crate) const MAC_ADDR_PEER: EthernetAddress = EthernetAddress([0x52, 0x54, 0, 0, 0, 2]);
+pub(crate) const IP_ADDR_HOST: Ipv4Address = Ipv4Address::new(10, 0, 0, 1);
+pub(crate) const IP_ADDR_PEER: Ipv4Address = Ipv4Address::new(10, 0, 0, 2);
+
+/// The layers of one host, with a nic holding a single packet.
+pub(crate) struct Host {
+    eth: eth::Endpoint<'static>,
+    ip: ip::Endpoint<'static>,
+    udp: udp::Endpoint<'static>,
+    nic: External<Slice<'static, Vec<u8>>>,
+    addr: Ipv4Address,
+    peer_mac: EthernetAddress,
+    peer_addr: Ipv4Address,
+}
+
+impl Host {
+    /// The host at `IP_ADDR_HOST`, with the peer already resolved.
+    pub(crate) fn new(ports: Vec<u16>) -> Self {
+        Host::with_addresses(MAC_ADDR_HOST, IP_ADDR_HOST, MAC_ADDR_PEER, IP_ADDR_PEER, ports)
+    }
+
+    /// The peer at `IP_ADDR_PEER`, with the host already resolved.
+    pub(crate) fn peer(ports: Vec<u16>) -> Self {
+        Host::with_addresses(MAC_ADDR_PEER, IP_ADDR_PEER, MAC_ADDR_HOST, IP_ADDR_HOST, ports)
+    }
+
+    fn with_addresses(
+        mac: EthernetAddress,
+        addr: Ipv4Address,
+        peer_mac: EthernetAddress,
+        peer_addr: Ipv4Address,
+        ports: Vec<u16>,
+    ) -> Self {
+        let mut neighbors = arp::NeighborCache::new(vec![arp::Neighbor::default(); 1]);
+        neighbors.fill(peer_addr.into(), peer_mac, None).unwrap();
+        Host {
+            eth: eth::Endpoint::new(mac),
+            ip: ip::Endpoint::new(IpCidr::new(addr.into(), 24),
+                ip::Routes::new(vec![ip::Route::unspecified(); 1]),
+                neighbors),
+            udp: udp::Endpoint::new(ports),
+            nic: External::new_send(Slice::One(vec![0; 1500])),
+            addr,
+            peer_mac,
+            peer_addr,
+        }
+    }
+
+    /// Let a handler send at most one frame.
+    pub(crate) fn send(&mut self, now: Instant, handler: impl udp::Send<Vec<u8>>)
+        -> Option<Vec<u8>>
+    {
+        *self.nic.get_mut(0).unwrap() = vec![0; 1500];
+        self.nic.send_all();
+        self.nic.set_current_time(now);
+        let sent = self.nic.tx(1, self.eth.send(self.ip.send(self.udp.send(handler))));
+        match sent {
+            Ok(1) => self.nic.get(0).cloned(),
+            _ => None,
+        }
+    }
+
+    /// Let a handler send at most one datagram between two ports and return its payload.
+    ///
+    /// Panics if the datagram is not addressed from this host to its peer.
+    pub(crate) fn send_datagram(
+        &mut self,
+        now: Instant,
+        (src_port, dst_port): (u16, u16),
+        handler: impl udp::Send<Vec<u8>>,
+    ) -> Option<Vec<u8>> {
+        let frame = self.send(now, handler)?;
+        let eth = ethernet_frame::new_unchecked(&frame[..]);
+        let ip = ipv4_packet::new_unchecked(eth.payload_slice());
+        let udp = udp_packet::new_unchecked(ip.payload_slice());
+        assert_eq!(eth.dst_addr(), self.peer_mac);
+        assert_eq!(ip.src_addr(), self.addr);
+        assert_eq!(ip.dst_addr(), self.peer_addr);
+        assert_eq!(udp.src_port(), src_port);
+        assert_eq!(udp.dst_port(), dst_port);
+        Some(udp.payload_slice().to_vec())
+    }
+
+    /// Receive a frame of the peer.
+    pub(crate) fn receive(
+        &mut self,
+        now: Instant,
+        frame: Vec<u8>,
+        handler: impl udp::Recv<Vec<u8>>,
+    ) {
+        *self.nic.get_mut(0).unwrap() = frame;
+        self.nic.receive_all();
+        self.nic.set_current_time(now);
+        let received = self.nic.rx(1, self.eth.recv(self.ip.recv(self.udp.recv(handler))));
+        assert_eq!(received, Ok(1));
+    }
+}
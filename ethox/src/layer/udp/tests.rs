@@ -1,8 +1,11 @@
 use crate::managed::Slice;
-use crate::nic::{external::External, Device};
-use crate::layer::{arp, eth, ip, udp};
+use crate::nic::{external::External, loopback::Loopback, Device};
+use crate::layer::{arp, eth, ip, udp, Detail};
 use crate::wire::{EthernetAddress, Ipv4Address, IpCidr, IpSubnet, Ipv4Subnet, Payload, PayloadMut};
-use crate::wire::{ethernet_frame, ipv4_packet};
+#[cfg(feature = "alloc")]
+use crate::wire::{PayloadVectored, Segmented};
+use crate::wire::{ethernet_frame, icmpv4_packet, ipv4_packet, udp_packet};
+use crate::wire::{Checksum, Icmpv4DstUnreachable, Icmpv4Repr, IpProtocol};
 
 const MAC_ADDR_SRC: EthernetAddress = EthernetAddress([0, 1, 2, 3, 4, 5]);
 const IP_ADDR_SRC: Ipv4Address = Ipv4Address::new(127, 0, 0, 1);
@@ -25,6 +28,7 @@ fn simple_send<P: PayloadMut>(frame: udp::RawPacket<P>) {
         dst_addr: IP_ADDR_DST.into(),
         dst_port: 80,
         payload: PAYLOAD_BYTES.len(),
+        dscp: 0,
     };
     let mut prepared = frame.prepare(init)
         .expect("Found no valid routes");
@@ -40,6 +44,17 @@ fn simple_recv<P: Payload>(frame: udp::Packet<P>) {
     assert_eq!(frame.packet.payload().as_slice(), &PAYLOAD_BYTES[..]);
 }
 
+/// Retarget a sent packet to self.
+fn retarget(buffer: &mut [u8]) {
+    let eth = ethernet_frame::new_unchecked_mut(buffer);
+    eth.set_dst_addr(MAC_ADDR_SRC);
+    eth.set_src_addr(MAC_ADDR_DST);
+    let ip = ipv4_packet::new_unchecked_mut(eth.payload_mut_slice());
+    ip.set_dst_addr(IP_ADDR_SRC);
+    ip.set_src_addr(IP_ADDR_DST);
+    ip.fill_checksum();
+}
+
 #[test]
 fn simple() {
     let mut nic = External::new_send(Slice::One(vec![0; 1024]));
@@ -64,17 +79,7 @@ fn simple() {
         udp.send_with(simple_send))));
     assert_eq!(sent, Ok(1));
 
-    {
-        // Retarget the packet to self.
-        let buffer = nic.get_mut(0).unwrap();
-        let eth = ethernet_frame::new_unchecked_mut(buffer);
-        eth.set_dst_addr(MAC_ADDR_SRC);
-        eth.set_src_addr(MAC_ADDR_DST);
-        let ip = ipv4_packet::new_unchecked_mut(eth.payload_mut_slice());
-        ip.set_dst_addr(IP_ADDR_SRC);
-        ip.set_src_addr(IP_ADDR_DST);
-        ip.fill_checksum();
-    }
+    retarget(nic.get_mut(0).unwrap());
 
     // Set the buffer to be received.
     nic.receive_all();
@@ -82,4 +87,641 @@ fn simple() {
     let recv = nic.rx(1, eth.recv(ip.recv(
         udp.recv_with(simple_recv))));
    assert_eq!(recv, Ok(1)); 
+
+    let statistics = udp.statistics();
+    assert_eq!(statistics.received, 1);
+    assert_eq!(statistics.received_bytes, PAYLOAD_BYTES.len() as u64);
+}
+
+#[test]
+fn jumbo() {
+    const PAYLOAD: usize = 9000 - 20 - 8;
+    let data: Vec<u8> = (0..PAYLOAD).map(|i| (i * 7) as u8).collect();
+
+    let mut nic = External::new_send(Slice::One(vec![0; 14 + 9000]));
+    nic.set_mtu(Some(9000));
+
+    let mut eth = eth::Endpoint::new(MAC_ADDR_SRC);
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut neighbors[..]);
+        eth_cache.fill(IP_ADDR_DST.into(), MAC_ADDR_DST, None).unwrap();
+        eth_cache
+    };
+    let mut ip = [ip::Route::unspecified(); 2];
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_SRC.into(), 24),
+        ip::Routes::new(&mut ip[..]),
+        neighbors);
+    let mut udp = udp::Endpoint::new(80);
+
+    // A datagram filling the whole unit is sent with a valid checksum.
+    let sent = nic.tx(1, eth.send(ip.send(udp.send_with(|frame: udp::RawPacket<_>| {
+        let init = udp::Init {
+            source: IpSubnet::from(Ipv4Subnet::ANY).into(),
+            src_port: 80,
+            dst_addr: IP_ADDR_DST.into(),
+            dst_port: 80,
+            payload: PAYLOAD,
+            dscp: 0,
+        };
+        let mut prepared = frame.prepare(init).unwrap();
+        prepared.packet.payload_mut().copy_from_slice(&data);
+        prepared.send().unwrap();
+    }))));
+    assert_eq!(sent, Ok(1));
+    retarget(nic.get_mut(0).unwrap());
+
+    nic.receive_all();
+    let recv = nic.rx(1, eth.recv(ip.recv(udp.recv_with(|frame: udp::Packet<_>| {
+        assert_eq!(frame.packet.payload().as_slice(), &data[..]);
+    }))));
+    assert_eq!(recv, Ok(1));
+    assert_eq!(udp.statistics().received_bytes, PAYLOAD as u64);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn vectored() {
+    let mut nic = External::new_send(Slice::One(Segmented::new(vec![0; 1024], 1)));
+
+    let mut eth = eth::Endpoint::new(MAC_ADDR_SRC);
+
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut neighbors[..]);
+        eth_cache.fill(IP_ADDR_DST.into(), MAC_ADDR_DST, None).unwrap();
+        eth_cache
+    };
+    let mut ip = [ip::Route::unspecified(); 2];
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_SRC.into(), 24),
+        ip::Routes::new(&mut ip[..]),
+        neighbors);
+
+    let mut udp = udp::Endpoint::new(80);
+
+    let sent = nic.tx(1, eth.send(ip.send(
+        udp.send_with(|frame: udp::RawPacket<_>| {
+            let init = udp::Init {
+                source: IpSubnet::from(Ipv4Subnet::ANY).into(),
+                src_port: 80,
+                dst_addr: IP_ADDR_DST.into(),
+                dst_port: 80,
+                payload: PAYLOAD_BYTES.len(),
+                dscp: 0,
+            };
+            let mut prepared = frame.prepare_vectored(init).unwrap();
+            prepared.payload_mut_slice().copy_from_slice(&PAYLOAD_BYTES[..]);
+            prepared.send().unwrap();
+        }))));
+    assert_eq!(sent, Ok(1));
+
+    // Only the headers were written to the contiguous buffer.
+    let buffer = nic.get_mut(0).unwrap();
+    assert_eq!(buffer.payload().len(), 14 + 20 + 8);
+    assert_eq!(buffer.segment(0).unwrap().as_slice(), &PAYLOAD_BYTES[..]);
+
+    let mut frame = buffer.to_vec();
+    let packet = ipv4_packet::new_unchecked(ethernet_frame::new_unchecked(&frame).payload_slice());
+    assert_eq!(usize::from(packet.total_len()), 20 + 8 + PAYLOAD_BYTES.len());
+    let datagram = udp_packet::new_unchecked(packet.payload_slice());
+    assert_eq!(usize::from(datagram.len()), 8 + PAYLOAD_BYTES.len());
+
+    retarget(&mut frame);
+    let mut nic = External::new_recv(Slice::One(frame));
+    let recv = nic.rx(1, eth.recv(ip.recv(
+        udp.recv_with(simple_recv))));
+    assert_eq!(recv, Ok(1));
+    assert_eq!(udp.statistics().received, 1);
+}
+
+#[test]
+fn dscp() {
+    const EXPEDITED: u8 = 46;
+
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+
+    let mut eth = eth::Endpoint::new(MAC_ADDR_SRC);
+
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut neighbors[..]);
+        eth_cache.fill(IP_ADDR_DST.into(), MAC_ADDR_DST, None).unwrap();
+        eth_cache
+    };
+    let mut ip = [ip::Route::unspecified(); 2];
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_SRC.into(), 24),
+        ip::Routes::new(&mut ip[..]),
+        neighbors);
+
+    let mut udp = udp::Endpoint::new(80);
+
+    let sent = nic.tx(1, eth.send(ip.send(
+        udp.send_with(|frame: udp::RawPacket<_>| {
+            let init = udp::Init {
+                source: IpSubnet::from(Ipv4Subnet::ANY).into(),
+                src_port: 80,
+                dst_addr: IP_ADDR_DST.into(),
+                dst_port: 80,
+                payload: 0,
+                dscp: EXPEDITED,
+            };
+            frame.prepare(init).unwrap().send().unwrap();
+        }))));
+    assert_eq!(sent, Ok(1));
+    retarget(nic.get_mut(0).unwrap());
+    nic.receive_all();
+
+    let mut received = false;
+    let recv = nic.rx(1, eth.recv(ip.recv(
+        udp.recv_with(|frame: udp::Packet<_>| {
+            assert_eq!(frame.packet.get_ref().dscp(), EXPEDITED);
+            received = true;
+        }))));
+    assert_eq!(recv, Ok(1));
+    assert!(received);
+}
+
+#[test]
+fn broadcast() {
+    fn send_to<P: PayloadMut>(frame: udp::RawPacket<P>, dst_addr: Ipv4Address)
+        -> crate::layer::Result<()>
+    {
+        let init = udp::Init {
+            source: IpSubnet::from(Ipv4Subnet::ANY).into(),
+            src_port: 67,
+            dst_addr: dst_addr.into(),
+            dst_port: 68,
+            payload: 0,
+            dscp: 0,
+        };
+        frame.prepare(init)?.send()
+    }
+
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+
+    let mut eth = eth::Endpoint::new(MAC_ADDR_SRC);
+
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let mut ip = [ip::Route::unspecified(); 2];
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_SRC.into(), 24),
+        ip::Routes::new(&mut ip[..]),
+        arp::NeighborCache::new(&mut neighbors[..]));
+
+    let mut udp = udp::Endpoint::new(67);
+    assert!(!udp.broadcast());
+
+    let subnet = Ipv4Address::new(127, 0, 0, 255);
+    for &dst_addr in &[Ipv4Address::BROADCAST, subnet] {
+        let mut result = None;
+        let sent = nic.tx(1, eth.send(ip.send(
+            udp.send_with(|frame: udp::RawPacket<_>| {
+                result = Some(send_to(frame, dst_addr));
+            }))));
+        assert_eq!(sent, Ok(0));
+        assert_eq!(result, Some(Err(crate::layer::Error::Illegal)));
+    }
+
+    udp.set_broadcast(true);
+    for &dst_addr in &[Ipv4Address::BROADCAST, subnet] {
+        nic.reset_send();
+        let sent = nic.tx(1, eth.send(ip.send(
+            udp.send_with(|frame: udp::RawPacket<_>| {
+                send_to(frame, dst_addr).unwrap();
+            }))));
+        assert_eq!(sent, Ok(1));
+
+        let buffer = nic.get_mut(0).unwrap();
+        let frame = ethernet_frame::new_unchecked(&buffer[..]);
+        assert_eq!(frame.dst_addr(), EthernetAddress::BROADCAST);
+        let ip = ipv4_packet::new_unchecked(frame.payload_slice());
+        assert_eq!(ip.dst_addr(), dst_addr);
+    }
+}
+
+#[test]
+fn retain() {
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+
+    let mut eth = eth::Endpoint::new(MAC_ADDR_SRC);
+
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut neighbors[..]);
+        eth_cache.fill(IP_ADDR_DST.into(), MAC_ADDR_DST, None).unwrap();
+        eth_cache
+    };
+    let mut ip = [ip::Route::unspecified(); 2];
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_SRC.into(), 24),
+        ip::Routes::new(&mut ip[..]),
+        neighbors);
+
+    let mut udp = udp::Endpoint::new(80);
+
+    let sent = nic.tx(1, eth.send(ip.send(
+        udp.send_with(simple_send))));
+    assert_eq!(sent, Ok(1));
+    retarget(nic.get_mut(0).unwrap());
+    nic.receive_all();
+
+    let mut retained = vec![0; 16];
+    let recv = nic.rx(1, eth.recv(ip.recv(
+        udp.recv_with(|frame: udp::Packet<Vec<u8>>| {
+            frame.retain(&mut retained).expect("Device permits retaining");
+        }))));
+    assert_eq!(recv, Ok(1));
+
+    // The spare took the place of the packet buffer.
+    assert_eq!(nic.get_mut(0).unwrap().len(), 16);
+    assert!(retained.ends_with(&PAYLOAD_BYTES[..]));
+}
+
+#[test]
+fn port_unreachable() {
+    let mut nic = Loopback::<Vec<u8>>::new(vec![0; 1 << 12].into());
+
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut neighbors[..]);
+        eth_cache.fill(IP_ADDR_DST.into(), MAC_ADDR_DST, None).unwrap();
+        eth_cache
+    };
+    let mut routes = [ip::Route::unspecified(); 1];
+    let mut eth = eth::Endpoint::new(MAC_ADDR_SRC);
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_SRC.into(), 24),
+        ip::Routes::new(&mut routes[..]),
+        neighbors);
+
+    let mut other_neighbors = [arp::Neighbor::default(); 1];
+    let other_neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut other_neighbors[..]);
+        eth_cache.fill(IP_ADDR_SRC.into(), MAC_ADDR_SRC, None).unwrap();
+        eth_cache
+    };
+    let mut other_routes = [ip::Route::unspecified(); 1];
+    let mut other_eth = eth::Endpoint::new(MAC_ADDR_DST);
+    let mut other_ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_DST.into(), 24),
+        ip::Routes::new(&mut other_routes[..]),
+        other_neighbors);
+
+    // Only port 81 is open, the datagram goes to port 80.
+    let mut udp = udp::Endpoint::new(81);
+    let mut other_udp = udp::Endpoint::new(80);
+    ip.set_icmp_policy(ip::IcmpPolicy::UNREACHABLE);
+
+    let send_to_host = |frame: udp::RawPacket<_>| {
+        let init = udp::Init {
+            source: IpSubnet::from(Ipv4Subnet::ANY).into(),
+            src_port: 80,
+            dst_addr: IP_ADDR_SRC.into(),
+            dst_port: 80,
+            payload: PAYLOAD_BYTES.len(),
+            dscp: 0,
+        };
+        let mut prepared = frame.prepare(init).unwrap();
+        prepared.packet.payload_mut().copy_from_slice(&PAYLOAD_BYTES[..]);
+        prepared.send().unwrap();
+    };
+
+    let sent = nic.tx(1, other_eth.send(other_ip.send(
+        other_udp.send_with(send_to_host))));
+    assert_eq!(sent, Ok(1));
+    let recv = nic.rx(1, eth.recv(ip.recv(
+        udp.recv_with(|_: udp::Packet<_>| panic!("Port is closed")))));
+    assert_eq!(recv, Ok(1));
+    assert_eq!(udp.statistics().closed_port, 1);
+    assert_eq!(udp.last_failure().map(|failure| failure.detail), Some(Detail::ClosedPort(80)));
+
+    let mut answered = false;
+    let recv = nic.rx(1, other_eth.recv(other_ip.recv_with(|packet: ip::InPacket<_>| {
+        assert_eq!(packet.packet.repr().protocol(), IpProtocol::Icmp);
+        let icmp = icmpv4_packet::new_checked(packet.packet.payload().as_slice()).unwrap();
+        match Icmpv4Repr::parse(icmp, Checksum::Manual).unwrap() {
+            Icmpv4Repr::DstUnreachable { reason, header } => {
+                assert_eq!(reason, Icmpv4DstUnreachable::PortUnreachable);
+                assert_eq!(header.src_addr, IP_ADDR_DST);
+                assert_eq!(header.dst_addr, IP_ADDR_SRC);
+                assert_eq!(header.protocol, IpProtocol::Udp);
+            },
+            other => panic!("Unexpected icmp message {:?}", other),
+        }
+        answered = true;
+    })));
+    assert_eq!(recv, Ok(1));
+    assert!(answered);
+}
+
+#[test]
+fn hardware_timestamps() {
+    use crate::nic::HardwareTimestamp;
+    use crate::wire::{ptp_packet, PtpMessage, PtpPortIdentity, PtpRepr, PtpTimestamp};
+    use crate::wire::PTP_EVENT_PORT;
+
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+    let sent_at = HardwareTimestamp { seconds: 1_700_000_000, nanos: 250 };
+    nic.set_hardware_time(Some(sent_at));
+    assert!(nic.personality().capabilities().timestamping().tx());
+
+    let mut eth = eth::Endpoint::new(MAC_ADDR_SRC);
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut neighbors[..]);
+        eth_cache.fill(IP_ADDR_DST.into(), MAC_ADDR_DST, None).unwrap();
+        eth_cache
+    };
+    let mut ip = [ip::Route::unspecified(); 2];
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_SRC.into(), 24),
+        ip::Routes::new(&mut ip[..]),
+        neighbors);
+    let mut udp = udp::Endpoint::new(PTP_EVENT_PORT);
+
+    let sync = PtpRepr {
+        message: PtpMessage::Sync { origin: PtpTimestamp::default() },
+        domain: 0,
+        flags: 0,
+        correction: 0,
+        source_port: PtpPortIdentity::from_ethernet(MAC_ADDR_SRC, 1),
+        sequence_id: 7,
+        log_message_interval: 0,
+    };
+
+    let sent = nic.tx(1, eth.send(ip.send(udp.send_with(|frame: udp::RawPacket<_>| {
+        let init = udp::Init {
+            source: IpSubnet::from(Ipv4Subnet::ANY).into(),
+            src_port: PTP_EVENT_PORT,
+            dst_addr: IP_ADDR_DST.into(),
+            dst_port: PTP_EVENT_PORT,
+            payload: sync.buffer_len(),
+            dscp: 0,
+        };
+        let mut prepared = frame.prepare(init).unwrap();
+        sync.emit(ptp_packet::new_unchecked_mut(prepared.packet.payload_mut_slice()));
+        prepared.handle.request_tx_timestamp(u32::from(sync.sequence_id)).unwrap();
+        prepared.send().unwrap();
+    }))));
+    assert_eq!(sent, Ok(1));
+
+    let reported = nic.tx_timestamp().expect("Requested a timestamp");
+    assert_eq!(reported.id, 7);
+    assert_eq!(PtpTimestamp::from(reported.timestamp).nanos, 250);
+    assert_eq!(nic.tx_timestamp(), None);
+
+    retarget(nic.get_mut(0).unwrap());
+    nic.receive_all();
+    let received_at = HardwareTimestamp::from_nanos(1_700_000_000_000_001_000);
+    nic.set_hardware_time(Some(received_at));
+
+    let recv = nic.rx(1, eth.recv(ip.recv(udp.recv_with(|packet: udp::Packet<_>| {
+        assert_eq!(packet.handle.info().hardware_timestamp(), Some(received_at));
+        let message = ptp_packet::new_checked(packet.packet.payload_slice()).unwrap();
+        assert_eq!(PtpRepr::parse(message), Ok(sync));
+        assert_eq!(received_at.nanos_since(sent_at), 750);
+    }))));
+    assert_eq!(recv, Ok(1));
+}
+
+#[test]
+fn quic_demux() {
+    use crate::layer::udp::quic::{Demux, Route};
+    use crate::wire::QuicConnectionId;
+
+    struct Connection {
+        received: usize,
+        ecn: u8,
+    }
+
+    impl<P: Payload> udp::Recv<P> for Connection {
+        fn receive(&mut self, frame: udp::Packet<P>) {
+            self.received += 1;
+            self.ecn = frame.packet.get_ref().ecn();
+        }
+    }
+
+    // A short header for an established connection and an initial packet of a new one.
+    static DATAGRAMS: [&[u8]; 3] = [
+        &[0x40, 0x01, 0x02, 0x03, 0x04, 0xff],
+        &[0xc0, 0x00, 0x00, 0x00, 0x01, 0x02, 0xaa, 0xbb, 0x00],
+        &[0x40, 0x09, 0x09, 0x09, 0x09, 0xff],
+    ];
+
+    let mut nic = External::new_send(Slice::Many(vec![vec![0; 1024]; 3]));
+
+    let mut eth = eth::Endpoint::new(MAC_ADDR_SRC);
+
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut neighbors[..]);
+        eth_cache.fill(IP_ADDR_DST.into(), MAC_ADDR_DST, None).unwrap();
+        eth_cache
+    };
+    let mut ip = [ip::Route::unspecified(); 2];
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_SRC.into(), 24),
+        ip::Routes::new(&mut ip[..]),
+        neighbors);
+
+    let mut udp = udp::Endpoint::new(443);
+
+    let mut next = 0;
+    let sent = nic.tx(3, eth.send(ip.send(
+        udp.send_with(|frame: udp::RawPacket<_>| {
+            let datagram = DATAGRAMS[next];
+            let init = udp::Init {
+                source: IpSubnet::from(Ipv4Subnet::ANY).into(),
+                src_port: 443,
+                dst_addr: IP_ADDR_DST.into(),
+                dst_port: 443,
+                payload: datagram.len(),
+                dscp: 0,
+            };
+            let mut packet = frame.prepare(init).unwrap();
+            packet.packet.payload_mut_slice().copy_from_slice(datagram);
+            packet.set_ecn(0b10);
+            packet.send().unwrap();
+            next += 1;
+        }))));
+    assert_eq!(sent, Ok(3));
+    for idx in 0..3 {
+        retarget(nic.get_mut(idx).unwrap());
+    }
+    nic.receive_all();
+
+    let connection = |prefix: &[u8]| Route {
+        prefix: QuicConnectionId::new(prefix).unwrap(),
+        handler: Connection { received: 0, ecn: 0 },
+    };
+    let mut demux = Demux::new(443, 4, Slice::Many(vec![
+        connection(&[0x01]),
+        connection(&[0x01, 0x02]),
+        connection(&[0xff]),
+    ]));
+    // The long header has an unknown connection ID, the last short header matches no route.
+    demux.accept_with(Some(2));
+
+    let recv = nic.rx(3, eth.recv(ip.recv(udp.recv(&mut demux))));
+    assert_eq!(recv, Ok(3));
+
+    let routes = demux.routes();
+    assert_eq!(routes[0].handler.received, 0);
+    assert_eq!(routes[1].handler.received, 1);
+    assert_eq!(routes[1].handler.ecn, 0b10);
+    assert_eq!(routes[2].handler.received, 1);
+    assert_eq!(demux.dropped(), 1);
+}
+
+#[test]
+fn reserve_ports() {
+    use crate::layer::ports::{Binding, Registry, Transport};
+    use crate::layer::Error;
+
+    let mut bindings = [Binding { transport: Transport::Udp, port: 0 }; 4];
+    let mut registry = Registry::new(&mut bindings[..]);
+    registry.reserve(Transport::Tcp, 80).unwrap();
+
+    // The port is bound for tcp only, udp may use it as well.
+    let udp = udp::Endpoint::new(vec![80, 81]);
+    assert_eq!(udp.reserve_ports(&mut registry), Ok(()));
+
+    // A second endpoint on one of the ports is detected, and reserves none of its ports.
+    let other = udp::Endpoint::new(vec![82, 81]);
+    assert_eq!(other.reserve_ports(&mut registry), Err(Error::Illegal));
+    assert!(!registry.is_reserved(Transport::Udp, 82));
+}
+
+#[test]
+fn lite() {
+    let mut nic = External::new_send(Slice::One(vec![0; 1024]));
+
+    let mut eth = eth::Endpoint::new(MAC_ADDR_SRC);
+
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut neighbors[..]);
+        eth_cache.fill(IP_ADDR_DST.into(), MAC_ADDR_DST, None).unwrap();
+        eth_cache
+    };
+    let mut ip = [ip::Route::unspecified(); 2];
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_SRC.into(), 24),
+        ip::Routes::new(&mut ip[..]),
+        neighbors);
+
+    let mut udp = udp::Endpoint::new(80);
+
+    let sent = nic.tx(1, eth.send(ip.send(udp.send_with(|frame: udp::RawPacket<_>| {
+        let init = udp::Init {
+            source: IpSubnet::from(Ipv4Subnet::ANY).into(),
+            src_port: 80,
+            dst_addr: IP_ADDR_DST.into(),
+            dst_port: 80,
+            payload: PAYLOAD_BYTES.len(),
+            dscp: 0,
+        };
+        // Only the first byte of the payload is covered by the checksum.
+        let mut prepared = frame.prepare_lite(init, 1).unwrap();
+        prepared.packet.payload_mut().copy_from_slice(&PAYLOAD_BYTES[..]);
+        prepared.send().unwrap();
+    }))));
+    assert_eq!(sent, Ok(1));
+
+    let buffer = nic.get_mut(0).unwrap();
+    retarget(buffer);
+    let ip_packet = ipv4_packet::new_unchecked_mut(
+        ethernet_frame::new_unchecked_mut(buffer).payload_mut_slice());
+    assert_eq!(ip_packet.protocol(), IpProtocol::UdpLite);
+    let udp_packet = udp_packet::new_unchecked_mut(ip_packet.payload_mut_slice());
+    assert_eq!(udp_packet.checksum_coverage(), 9);
+    // Corrupt the uncovered part of the payload.
+    udp_packet.payload_mut_slice_lite()[PAYLOAD_BYTES.len() - 1] ^= 0xff;
+
+    // Without UDP-Lite enabled the datagram is not for this endpoint.
+    nic.receive_all();
+    let recv = nic.rx(1, eth.recv(ip.recv(udp.recv_with(|_: udp::Packet<_>| {
+        panic!("Received a UDP-Lite datagram");
+    }))));
+    assert_eq!(recv, Ok(1));
+
+    udp.set_lite(true);
+    nic.receive_all();
+    let mut received = false;
+    let recv = nic.rx(1, eth.recv(ip.recv(udp.recv_with(|frame: udp::Packet<_>| {
+        assert_eq!(frame.packet.repr().checksum_coverage, Some(9));
+        let payload = frame.packet.payload().as_slice();
+        assert_eq!(payload[..PAYLOAD_BYTES.len() - 1], PAYLOAD_BYTES[..PAYLOAD_BYTES.len() - 1]);
+        received = true;
+    }))));
+    assert_eq!(recv, Ok(1));
+    assert!(received);
+}
+
+#[test]
+fn port_dispatch() {
+    use crate::layer::FnHandler;
+    use crate::layer::udp::{PortDispatch, PortRoute};
+
+    struct Service {
+        received: usize,
+    }
+
+    impl<P: Payload> udp::Recv<P> for Service {
+        fn receive(&mut self, frame: udp::Packet<P>) {
+            assert_eq!(frame.packet.payload().as_slice(), &PAYLOAD_BYTES[..]);
+            self.received += 1;
+        }
+    }
+
+    static PORTS: [u16; 3] = [53, 68, 80];
+
+    let mut nic = External::new_send(Slice::Many(vec![vec![0; 1024]; 3]));
+
+    let mut eth = eth::Endpoint::new(MAC_ADDR_SRC);
+
+    let mut neighbors = [arp::Neighbor::default(); 1];
+    let neighbors = {
+        let mut eth_cache = arp::NeighborCache::new(&mut neighbors[..]);
+        eth_cache.fill(IP_ADDR_DST.into(), MAC_ADDR_DST, None).unwrap();
+        eth_cache
+    };
+    let mut ip = [ip::Route::unspecified(); 2];
+    let mut ip = ip::Endpoint::new(IpCidr::new(IP_ADDR_SRC.into(), 24),
+        ip::Routes::new(&mut ip[..]),
+        neighbors);
+
+    let mut udp = udp::Endpoint::new(vec![53, 68, 80]);
+
+    let mut next = 0;
+    let sent = nic.tx(3, eth.send(ip.send(
+        udp.send_with(|frame: udp::RawPacket<_>| {
+            let init = udp::Init {
+                source: IpSubnet::from(Ipv4Subnet::ANY).into(),
+                src_port: PORTS[next],
+                dst_addr: IP_ADDR_DST.into(),
+                dst_port: PORTS[next],
+                payload: PAYLOAD_BYTES.len(),
+                dscp: 0,
+            };
+            let mut packet = frame.prepare(init).unwrap();
+            packet.packet.payload_mut_slice().copy_from_slice(&PAYLOAD_BYTES[..]);
+            packet.send().unwrap();
+            next += 1;
+        }))));
+    assert_eq!(sent, Ok(3));
+    for idx in 0..3 {
+        retarget(nic.get_mut(idx).unwrap());
+    }
+    nic.receive_all();
+
+    // Two services of different types, nothing listens on port 80.
+    let mut dns = Service { received: 0 };
+    let mut dhcp_count = 0;
+    let mut dhcp = FnHandler(|_: udp::Packet<_>| dhcp_count += 1);
+    let mut dispatch = PortDispatch::new(Slice::Many(vec![
+        PortRoute { port: 53, handler: &mut dns as &mut dyn udp::Recv<_> },
+        PortRoute { port: 68, handler: &mut dhcp },
+    ]));
+
+    let recv = nic.rx(3, eth.recv(ip.recv(udp.recv(&mut dispatch))));
+    assert_eq!(recv, Ok(3));
+    assert_eq!(dispatch.dropped(), 1);
+    drop(dispatch);
+
+    assert_eq!(dns.received, 1);
+    assert_eq!(dhcp_count, 1);
 }
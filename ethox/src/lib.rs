@@ -66,10 +66,13 @@
     not(doctest)),
 no_std)]
 
+#[macro_use] mod logging;
 pub mod nic;
 pub mod layer;
 pub mod managed;
 #[macro_use] mod macros;
+pub mod rand;
+mod siphash;
 pub mod storage;
 pub mod time;
 pub mod wire;
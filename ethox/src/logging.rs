@@ -0,0 +1,45 @@
+//! Logging of layer events, through `log` or `defmt` if enabled.
+//!
+//! The layers report events that are otherwise silent, such as dropped packets, with the macros
+//! of this module. Without one of the features they expand to nothing but still evaluate their
+//! arguments, so that no variable becomes unused. Arguments must implement `Debug` for `defmt`,
+//! which receives them wrapped in `Debug2Format`, and the trait of their placeholder for `log`.
+//!
+//! When both features are enabled, events go to `defmt` only. The pretty printing receivers are
+//! not affected by `defmt` and keep writing to `log`.
+#[cfg(all(feature = "log", not(feature = "defmt")))]
+macro_rules! net_log {
+    (debug, $($arg:tt)*) => { ::log::debug!($($arg)*) };
+}
+
+#[cfg(feature = "defmt")]
+macro_rules! net_log {
+    (debug, $fmt:literal $(, $arg:expr)* $(,)?) => {
+        ::defmt::debug!($fmt $(, ::defmt::Debug2Format(&$arg))*)
+    };
+}
+
+#[cfg(not(any(feature = "log", feature = "defmt")))]
+macro_rules! net_log {
+    ($level:ident, $fmt:literal $(, $arg:expr)* $(,)?) => {{
+        $( let _ = &$arg; )*
+    }};
+}
+
+/// Log an unusual event, such as a packet dropped for an error.
+macro_rules! net_debug {
+    ($($arg:tt)*) => { net_log!(debug, $($arg)*) };
+}
+
+/// Print the output of the pretty printing receivers.
+///
+/// Goes to standard error, or to the logger if `log` is enabled.
+#[cfg(all(feature = "std", feature = "log"))]
+macro_rules! net_print {
+    ($($arg:tt)*) => { ::log::info!($($arg)*) };
+}
+
+#[cfg(all(feature = "std", not(feature = "log")))]
+macro_rules! net_print {
+    ($($arg:tt)*) => { eprintln!($($arg)*) };
+}
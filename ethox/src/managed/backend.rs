@@ -0,0 +1,176 @@
+//! Traits for plugging storage of other crates into the containers.
+//!
+//! The containers of this module work on a [`Slice`] or a [`Map`], each with variants for owned
+//! and borrowed memory. A storage backend that is neither, such as the statically sized
+//! collections of `heapless`, can implement one of these traits instead. With the feature
+//! `heapless` this is provided for `heapless::Vec` and `heapless::IndexMap`.
+//!
+//! [`Slice`]: enum.Slice.html
+//! [`Map`]: enum.Map.html
+use super::Slice;
+
+/// Memory for a fixed number of elements, owned by a container of another crate.
+///
+/// Such storage can be borrowed with [`Slice::from_storage`] to back any container built on a
+/// slice, including a [`SlotMap`] and the pairs or hash table of a [`Map`].
+///
+/// [`Slice::from_storage`]: enum.Slice.html#method.from_storage
+/// [`SlotMap`]: slotmap/struct.SlotMap.html
+/// [`Map`]: enum.Map.html
+pub trait SliceStorage<T> {
+    /// Make all of the capacity available as initialized elements.
+    fn as_full_slice(&mut self) -> &mut [T];
+}
+
+/// A map owned by a container of another crate.
+///
+/// Such a map is used through the [`Map::Backend`] variant. The map does not need any order of
+/// keys but a fixed capacity, since it is never asked to insert into a full map.
+///
+/// [`Map::Backend`]: enum.Map.html#variant.Backend
+pub trait MapStorage<K, V> {
+    /// The number of mapped keys.
+    fn len(&self) -> usize;
+
+    /// Check if no key is mapped.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of keys that can be mapped at the same time.
+    fn capacity(&self) -> usize;
+
+    /// Returns a reference to the value corresponding to the key.
+    fn get(&self, key: &K) -> Option<&V>;
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    fn get_mut(&mut self, key: &K) -> Option<&mut V>;
+
+    /// Insert a key that is not yet mapped.
+    ///
+    /// Only called while the map is not full, an implementation may panic otherwise.
+    fn insert(&mut self, key: K, value: V) -> &mut V;
+
+    /// Remove the mapping of a key, if any.
+    fn remove(&mut self, key: &K);
+}
+
+impl<T> SliceStorage<T> for [T] {
+    fn as_full_slice(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+impl<T, const N: usize> SliceStorage<T> for [T; N] {
+    fn as_full_slice(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+impl<'a, T: 'a> Slice<'a, T> {
+    /// Borrow all of the capacity of a storage backend.
+    pub fn from_storage<S>(storage: &'a mut S) -> Self
+        where S: SliceStorage<T> + ?Sized
+    {
+        Slice::Borrowed(storage.as_full_slice())
+    }
+}
+
+#[cfg(feature = "heapless")]
+mod heapless_impls {
+    use core::hash::{BuildHasher, Hash};
+    use heapless::{IndexMap, Vec};
+    use heapless::Entry;
+
+    use super::{MapStorage, Slice, SliceStorage};
+
+    /// Fills the vector with default values up to its capacity.
+    impl<T: Default, const N: usize> SliceStorage<T> for Vec<T, N> {
+        fn as_full_slice(&mut self) -> &mut [T] {
+            while self.push(T::default()).is_ok() {}
+            self
+        }
+    }
+
+    impl<'a, T: Default, const N: usize> From<&'a mut Vec<T, N>> for Slice<'a, T> {
+        fn from(vec: &'a mut Vec<T, N>) -> Self {
+            Slice::from_storage(vec)
+        }
+    }
+
+    impl<K, V, S, const N: usize> MapStorage<K, V> for IndexMap<K, V, S, N>
+        where K: Eq + Hash, S: BuildHasher,
+    {
+        fn len(&self) -> usize {
+            IndexMap::len(self)
+        }
+
+        fn capacity(&self) -> usize {
+            IndexMap::capacity(self)
+        }
+
+        fn get(&self, key: &K) -> Option<&V> {
+            IndexMap::get(self, key)
+        }
+
+        fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+            IndexMap::get_mut(self, key)
+        }
+
+        fn insert(&mut self, key: K, value: V) -> &mut V {
+            match self.entry(key) {
+                Entry::Occupied(occupied) => {
+                    let slot = occupied.into_mut();
+                    *slot = value;
+                    slot
+                },
+                Entry::Vacant(vacant) => match vacant.insert(value) {
+                    Ok(slot) => slot,
+                    Err(_) => panic!("Map was not full"),
+                },
+            }
+        }
+
+        fn remove(&mut self, key: &K) {
+            IndexMap::swap_remove(self, key);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use heapless::FnvIndexMap;
+        use crate::managed::{Map, SlotMap, Slot};
+        use super::*;
+
+        #[test]
+        fn slot_map() {
+            let mut elements: Vec<u32, 4> = Vec::new();
+            let mut slots: Vec<Slot, 4> = Vec::new();
+            let mut map = SlotMap::new((&mut elements).into(), (&mut slots).into());
+
+            let key = map.insert(42).unwrap();
+            assert_eq!(map.get(key).copied(), Some(42));
+            for value in 0..3 {
+                assert!(map.insert(value).is_some());
+            }
+            assert!(map.insert(4).is_none());
+        }
+
+        #[test]
+        fn index_map() {
+            let mut storage: FnvIndexMap<u16, u16, 2> = FnvIndexMap::new();
+            let mut map = Map::Backend(&mut storage);
+
+            assert_eq!(map.entry(1).or_insert(10).copied(), Some(10));
+            assert_eq!(map.entry(2).or_insert(20).copied(), Some(20));
+            assert!(map.entry(3).is_full());
+            assert_eq!(map.entry(1).and_modify(|val| *val += 1).or_insert(0).copied(), Some(11));
+
+            let occupied = map.entry(2).occupied().unwrap();
+            assert_eq!(occupied.remove_key(), 2);
+            assert_eq!(map.get(&2), None);
+            assert!(map.entry(3).vacant().is_some());
+            assert_eq!(storage.len(), 1);
+        }
+    }
+}
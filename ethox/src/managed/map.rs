@@ -1,4 +1,6 @@
-use super::List;
+use core::hash::{Hash, Hasher};
+
+use super::{List, MapStorage, Slice};
 use crate::alloc::collections::btree_map;
 
 /// A map on owned or non-owned data.
@@ -17,6 +19,38 @@ pub enum Map<'a, K: Ord, V> {
     /// contain a `Map` and thus must match on its (public) variants to be written without
     /// `#[cfg(..)]` tricks to toggle match arms.
     Btree(btree_map::BTreeMap<K, V>),
+
+    /// A hash table with open addressing over a slice of slots.
+    ///
+    /// Lookups take constant expected time independent of the number of entries, which makes this
+    /// the preferred option for large maps without allocation such as the connection table of a
+    /// tcp endpoint. See [`Hashed`] for sizing considerations.
+    ///
+    /// [`Hashed`]: struct.Hashed.html
+    Hashed(Hashed<'a, K, V>),
+
+    /// A map owned by a container of another crate.
+    ///
+    /// This makes it possible to back the map with static storage such as a `heapless::IndexMap`
+    /// but adds a dynamic call to each access. See [`MapStorage`] for implementing a backend.
+    ///
+    /// [`MapStorage`]: trait.MapStorage.html
+    Backend(&'a mut dyn MapStorage<K, V>),
+}
+
+/// A hash table with open addressing over borrowed or owned slots.
+///
+/// Collisions are resolved with linear probing and removal shifts following entries back instead
+/// of leaving tombstones, so that lookups never degrade after many insertions and removals. The
+/// table can be filled completely but probe sequences grow long when it is nearly full. Provide
+/// about a quarter more slots than the expected number of entries.
+///
+/// The hash function is fixed and not keyed, the keys should not be entirely chosen by a remote
+/// for which this could enable collision attacks.
+pub struct Hashed<'a, K, V> {
+    slots: Slice<'a, Option<(K, V)>>,
+    len: usize,
+    hash: fn(&K) -> u64,
 }
 
 /// An entry of the map.
@@ -47,6 +81,14 @@ enum Occupied<'map, 'a, K, V> {
         key: K,
     },
     Btree(btree_map::OccupiedEntry<'map, K, V>),
+    Hashed {
+        table: &'map mut Hashed<'a, K, V>,
+        index: usize,
+    },
+    Backend {
+        map: &'map mut (dyn MapStorage<K, V> + 'a),
+        key: K,
+    },
 }
 
 /// A reference to a missing entry of a map.
@@ -63,6 +105,147 @@ enum Vacant<'map, 'a, K, V> {
         key: K,
     },
     Btree(btree_map::VacantEntry<'map, K, V>),
+    Hashed {
+        table: &'map mut Hashed<'a, K, V>,
+        index: usize,
+        key: K,
+    },
+    Backend {
+        map: &'map mut (dyn MapStorage<K, V> + 'a),
+        key: K,
+    },
+}
+
+/// The 64-bit FNV-1a hash.
+///
+/// Cheap for the short keys of a network stack, and available without `std`.
+struct Fnv(u64);
+
+impl<'a, K, V> Hashed<'a, K, V> {
+    /// Create a table with all slots of the slice.
+    ///
+    /// All previous content of the slots is dropped.
+    pub fn new(mut slots: Slice<'a, Option<(K, V)>>) -> Self where K: Hash {
+        slots.as_mut_slice()
+            .iter_mut()
+            .for_each(|slot| *slot = None);
+        Hashed {
+            slots,
+            len: 0,
+            hash: Fnv::hash_key::<K>,
+        }
+    }
+
+    /// The number of entries in the table.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if the table contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of entries the table can hold.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// The preferred slot of a key.
+    fn home(&self, key: &K) -> usize {
+        ((self.hash)(key) % self.slots.len() as u64) as usize
+    }
+
+    /// Find the slot of a key or the slot where it would be inserted.
+    ///
+    /// Returns `Err(None)` when the key was not found and there are no free slots left.
+    fn find(&self, key: &K) -> Result<usize, Option<usize>> where K: Eq {
+        let slots = self.slots.as_slice();
+        if slots.is_empty() {
+            return Err(None);
+        }
+
+        let home = self.home(key);
+        for offset in 0..slots.len() {
+            let index = (home + offset) % slots.len();
+            match &slots[index] {
+                None => return Err(Some(index)),
+                Some((k, _)) if k == key => return Ok(index),
+                Some(_) => (),
+            }
+        }
+
+        Err(None)
+    }
+
+    fn get(&self, key: &K) -> Option<&V> where K: Eq {
+        let index = self.find(key).ok()?;
+        self.slots[index].as_ref().map(|(_, val)| val)
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> where K: Eq {
+        let index = self.find(key).ok()?;
+        self.slots[index].as_mut().map(|(_, val)| val)
+    }
+
+    fn entry(&self, index: usize) -> &(K, V) {
+        self.slots[index].as_ref().expect("Slot was occupied")
+    }
+
+    fn entry_mut(&mut self, index: usize) -> &mut (K, V) {
+        self.slots[index].as_mut().expect("Slot was occupied")
+    }
+
+    fn insert_at(&mut self, index: usize, key: K, value: V) -> &mut V {
+        self.len += 1;
+        let slot = &mut self.slots[index];
+        *slot = Some((key, value));
+        &mut slot.as_mut().unwrap().1
+    }
+
+    /// Remove an entry, moving later entries of its probe sequence back.
+    fn remove_at(&mut self, index: usize) -> (K, V) {
+        let len = self.slots.len();
+        let removed = self.slots[index].take().expect("Slot was occupied");
+        self.len -= 1;
+
+        let mut hole = index;
+        let mut next = (index + 1) % len;
+        while let Some((key, _)) = &self.slots[next] {
+            let home = self.home(key);
+            // Move the entry if the hole lies on the way from its home to its current slot.
+            if (next + len - home) % len >= (next + len - hole) % len {
+                self.slots[hole] = self.slots[next].take();
+                hole = next;
+            }
+            next = (next + 1) % len;
+        }
+
+        removed
+    }
+}
+
+impl Fnv {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    fn hash_key<K: Hash>(key: &K) -> u64 {
+        let mut fnv = Fnv(Self::OFFSET);
+        key.hash(&mut fnv);
+        fnv.finish()
+    }
+}
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(Self::PRIME);
+        }
+    }
 }
 
 
@@ -76,6 +259,8 @@ impl<K: Ord, V> Map<'_, K, V> {
                 .find(|(k, _)| k == key)
                 .map(|(_, val)| val),
             Map::Btree(tree) => tree.get(key),
+            Map::Hashed(table) => table.get(key),
+            Map::Backend(map) => map.get(key),
         }
     }
 
@@ -88,11 +273,22 @@ impl<K: Ord, V> Map<'_, K, V> {
                 .find(|(k, _)| k == key)
                 .map(|(_, val)| val),
             Map::Btree(tree) => tree.get_mut(key),
+            Map::Hashed(table) => table.get_mut(key),
+            Map::Backend(map) => map.get_mut(key),
         }
     }
 }
 
 impl<'a, K: Ord, V> Map<'a, K, V> {
+    /// Create a map with a hash table in the given slots.
+    ///
+    /// Shorthand for wrapping [`Hashed::new`].
+    ///
+    /// [`Hashed::new`]: struct.Hashed.html#method.new
+    pub fn hashed(slots: Slice<'a, Option<(K, V)>>) -> Self where K: Hash {
+        Map::Hashed(Hashed::new(slots))
+    }
+
     /// Gets the entry corresponding to the key.
     pub fn entry(&mut self, key: K) -> Entry<'_, 'a, K, V> {
         match self {
@@ -119,6 +315,41 @@ impl<'a, K: Ord, V> Map<'a, K, V> {
                 }
             },
             Map::Btree(tree) => tree.entry(key).into(),
+            Map::Hashed(table) => match table.find(&key) {
+                Ok(index) => Entry::Occupied(OccupiedEntry {
+                    inner: Occupied::Hashed {
+                        table,
+                        index,
+                    },
+                }),
+                Err(Some(index)) => Entry::Vacant(VacantEntry {
+                    inner: Vacant::Hashed {
+                        table,
+                        index,
+                        key,
+                    },
+                }),
+                Err(None) => Entry::Full,
+            },
+            Map::Backend(map) => {
+                if map.get(&key).is_some() {
+                    Entry::Occupied(OccupiedEntry {
+                        inner: Occupied::Backend {
+                            map: &mut **map,
+                            key,
+                        },
+                    })
+                } else if map.len() >= map.capacity() {
+                    Entry::Full
+                } else {
+                    Entry::Vacant(VacantEntry {
+                        inner: Vacant::Backend {
+                            map: &mut **map,
+                            key,
+                        },
+                    })
+                }
+            },
         }
     }
 }
@@ -152,14 +383,58 @@ impl<'map, 'a, K: Ord, V> Entry<'map, 'a, K, V> {
             occupied.remove()
         }
     }
+
+    /// Ensure a value is present by inserting the default if the entry is vacant.
+    ///
+    /// Returns a mutable reference to the value in the entry, or `None` if the map was full.
+    pub fn or_insert(self, default: V) -> Option<&'map mut V> {
+        self.or_insert_with(|| default)
+    }
+
+    /// Ensure a value is present by inserting the result of a function if the entry is vacant.
+    ///
+    /// The function is only called when the value is actually inserted. Returns a mutable
+    /// reference to the value in the entry, or `None` if the map was full.
+    pub fn or_insert_with(self, default: impl FnOnce() -> V) -> Option<&'map mut V> {
+        match self {
+            Entry::Occupied(occ) => Some(occ.into_mut()),
+            Entry::Vacant(vac) => Some(vac.insert(default())),
+            Entry::Full => None,
+        }
+    }
+
+    /// Modify the value of an occupied entry before any potential insert.
+    pub fn and_modify(mut self, f: impl FnOnce(&mut V)) -> Self {
+        if let Entry::Occupied(occ) = &mut self {
+            f(occ.get_mut());
+        }
+        self
+    }
+
+    /// Check if the map was full and the key was not found.
+    pub fn is_full(&self) -> bool {
+        matches!(self, Entry::Full)
+    }
 }
 
 impl<'map, K: Ord, V> OccupiedEntry<'map, '_, K, V> {
+    /// Returns a reference to the key of the entry.
+    pub fn key(&self) -> &K {
+        match &self.inner {
+            Occupied::Pairs { key, .. } => key,
+            Occupied::Btree(btree) => btree.key(),
+            Occupied::Hashed { table, index } => &table.entry(*index).0,
+            Occupied::Backend { key, .. } => key,
+        }
+    }
+
     /// Returns a reference to the value of the entry.
     pub fn get(&self) -> &V {
         match &self.inner {
             Occupied::Pairs { list, index, .. } => &list[*index].1,
             Occupied::Btree(btree) => btree.get(),
+            Occupied::Hashed { table, index } => &table.entry(*index).1,
+            Occupied::Backend { map, key } => map.get(key).expect("Element was present"),
         }
     }
 
@@ -168,6 +443,8 @@ impl<'map, K: Ord, V> OccupiedEntry<'map, '_, K, V> {
         match &mut self.inner {
             Occupied::Pairs { list, index, .. } => &mut list[*index].1,
             Occupied::Btree(btree) => btree.get_mut(),
+            Occupied::Hashed { table, index } => &mut table.entry_mut(*index).1,
+            Occupied::Backend { map, key } => map.get_mut(key).expect("Element was present"),
         }
     }
 
@@ -181,6 +458,8 @@ impl<'map, K: Ord, V> OccupiedEntry<'map, '_, K, V> {
         match self.inner {
             Occupied::Pairs { list, index, .. } => &mut list[index].1,
             Occupied::Btree(btree) => btree.into_mut(),
+            Occupied::Hashed { table, index } => &mut table.entry_mut(index).1,
+            Occupied::Backend { map, key } => map.get_mut(&key).expect("Element was present"),
         }
     }
 
@@ -193,6 +472,8 @@ impl<'map, K: Ord, V> OccupiedEntry<'map, '_, K, V> {
         match self.inner {
             Occupied::Pairs { list, index, .. } => { list.remove_at(index).expect("Element was present"); },
             Occupied::Btree(btree) => { btree.remove_entry(); },
+            Occupied::Hashed { table, index } => { table.remove_at(index); },
+            Occupied::Backend { map, key } => map.remove(&key),
         }
     }
 
@@ -204,16 +485,30 @@ impl<'map, K: Ord, V> OccupiedEntry<'map, '_, K, V> {
         match self.inner {
             Occupied::Pairs { list, index, key } => { list.remove_at(index).expect("Element was present"); key },
             Occupied::Btree(btree) => btree.remove_entry().0,
+            Occupied::Hashed { table, index } => table.remove_at(index).0,
+            Occupied::Backend { map, key } => { map.remove(&key); key },
         }
     }
 }
 
 impl<'map, K: Ord, V> VacantEntry<'map, '_, K, V> {
+    /// Returns a reference to the key used to create this entry.
+    pub fn key(&self) -> &K {
+        match &self.inner {
+            Vacant::Pairs { key, .. } => key,
+            Vacant::Btree(btree) => btree.key(),
+            Vacant::Hashed { key, .. } => key,
+            Vacant::Backend { key, .. } => key,
+        }
+    }
+
     /// Take ownership of the key used to create this entry.
     pub fn into_key(self) -> K {
         match self.inner {
             Vacant::Pairs { key, .. } => key,
             Vacant::Btree(btree) => btree.into_key(),
+            Vacant::Hashed { key, .. } => key,
+            Vacant::Backend { key, .. } => key,
         }
     }
 
@@ -227,6 +522,8 @@ impl<'map, K: Ord, V> VacantEntry<'map, '_, K, V> {
                 &mut empty.1
             },
             Vacant::Btree(btree) => btree.insert(value),
+            Vacant::Hashed { table, index, key } => table.insert_at(index, key, value),
+            Vacant::Backend { map, key } => map.insert(key, value),
         }
     }
 }
@@ -245,3 +542,80 @@ for Entry<'a, '_, K, V> {
        }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashed_entries() {
+        let mut slots = [None; 8];
+        let mut map: Map<u32, u32> = Map::hashed(Slice::from(&mut slots[..]));
+
+        for key in 0..8 {
+            assert_eq!(map.entry(key).or_insert(key * 2).copied(), Some(key * 2));
+        }
+
+        assert!(map.entry(8).is_full());
+        assert_eq!(map.entry(3).and_modify(|val| *val += 1).or_insert(0).copied(), Some(7));
+        assert_eq!(map.get(&3), Some(&7));
+
+        // Removal must not hide any later entry in a probe sequence.
+        map.entry(5).remove();
+        assert_eq!(map.get(&5), None);
+        for key in (0..8).filter(|&key| key != 5 && key != 3) {
+            assert_eq!(map.get(&key), Some(&(key * 2)));
+        }
+
+        let vacant = map.entry(8).vacant().expect("Slot was freed");
+        assert_eq!(vacant.key(), &8);
+        vacant.insert(16);
+        assert_eq!(map.get(&8), Some(&16));
+    }
+
+    #[test]
+    fn hashed_collisions() {
+        // Only a few slots so that almost all keys collide.
+        let mut slots = [None; 3];
+        let mut map: Map<u32, ()> = Map::hashed(Slice::from(&mut slots[..]));
+
+        for round in 0..64u32 {
+            let keys = [round, round + 100, round + 200];
+            for &key in keys.iter() {
+                assert!(map.entry(key).vacant().map(|vac| vac.insert(())).is_some());
+            }
+            assert!(map.entry(round + 300).is_full());
+
+            map.entry(keys[round as usize % 3]).remove();
+            for (idx, key) in keys.iter().enumerate() {
+                assert_eq!(map.get(key).is_some(), idx != round as usize % 3);
+            }
+
+            for &key in keys.iter() {
+                map.entry(key).remove();
+            }
+            match &map {
+                Map::Hashed(table) => assert!(table.is_empty()),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn pairs_or_insert_with() {
+        let mut pairs = [(0u32, 0u32); 2];
+        let mut map = Map::Pairs(List::new(Slice::from(&mut pairs[..])));
+
+        let mut calls = 0;
+        for &key in [1, 2, 1, 3].iter() {
+            let value = map.entry(key).or_insert_with(|| { calls += 1; key });
+            assert_eq!(value.is_some(), key != 3);
+        }
+        assert_eq!(calls, 2);
+
+        let occupied = map.entry(2).occupied().unwrap();
+        assert_eq!(occupied.key(), &2);
+        assert_eq!(occupied.remove_key(), 2);
+        assert!(map.entry(3).or_insert(3).is_some());
+    }
+}
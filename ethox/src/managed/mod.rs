@@ -1,18 +1,32 @@
 //! An assortment of non-owning containers.
 //!
 //! All of these containers have some option to construct them from one (or more) slices of the
-//! underlying types instead of allocating resources dynamically.
+//! underlying types instead of allocating resources dynamically. Storage of other crates can be
+//! plugged in through the traits of [`SliceStorage`] and [`MapStorage`].
+//!
+//! [`SliceStorage`]: trait.SliceStorage.html
+//! [`MapStorage`]: trait.MapStorage.html
+mod backend;
 mod map;
 mod ordered;
 mod partial;
+pub mod pool;
 mod slice;
 pub mod slotmap;
+pub mod wheel;
 
-pub use self::map::Map;
+pub use self::backend::{MapStorage, SliceStorage};
+pub use self::map::{Map, Hashed};
 pub use self::ordered::Ordered;
 pub use self::partial::Partial;
+pub use self::pool::Pool;
 pub use self::slice::Slice;
 pub use self::slotmap::{SlotMap, Slot};
+pub use self::wheel::{TimerWheel, Timer};
+
+// The ring buffers live with the other containers of `storage` but are just as well suited as the
+// backing storage of streams and queues without allocation.
+pub use crate::storage::{ByteRing, RingBuffer};
 
 /// A sort of `Vec` on initialized data.
 pub type List<'a, T> = Partial<Slice<'a, T>>;
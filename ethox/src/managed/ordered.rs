@@ -25,6 +25,16 @@ impl<'a, T> Ordered<'a, T> {
         }
     }
 
+    /// Create an empty ordered slice in a constant expression.
+    ///
+    /// Same as `new` but without the conversion.
+    pub const fn from_slice(inner: Slice<'a, T>) -> Self {
+        Ordered {
+            inner,
+            start: 0,
+        }
+    }
+
     /// Get a mutable reference to the element that would be pushed next.
     pub fn init(&mut self) -> Option<&mut T> {
         self.inner.as_mut_slice().get_mut(self.start)
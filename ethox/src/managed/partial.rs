@@ -30,7 +30,7 @@ use crate::wire::{Reframe, Payload, PayloadError, PayloadMut, payload};
 /// the current length unless the request can not be fulfilled with the current buffer size. Only
 /// in that case will it resize the underlying buffer.
 // TODO: implement PartialEq, Eq, PartialOrd, Ord
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct Partial<C> {
     inner: C,
     end: usize,
@@ -38,7 +38,7 @@ pub struct Partial<C> {
 
 impl<C> Partial<C> {
     /// Make an instance that initially refers to an empty part.
-    pub fn new(container: C) -> Self {
+    pub const fn new(container: C) -> Self {
         Partial {
             inner: container,
             end: 0,
@@ -105,6 +105,10 @@ pub struct OccupiedEntry<'a, K, V> {
 }
 
 impl<K, V> OccupiedEntry<'_, K, V> {
+    pub fn key(&self) -> &K {
+        match self.void { }
+    }
+
     pub fn get(&self) -> &V {
         match self.void { }
     }
@@ -17,6 +17,10 @@ pub struct Drain<'a, T> {
 }
 
 impl<T> Vec<T> {
+    pub const fn len(&self) -> usize {
+        match self.data { }
+    }
+
     pub fn as_slice(&self) -> &[T] {
         match self.data { }
     }
@@ -0,0 +1,235 @@
+//! A pool of packet buffers that can be leased out of a device.
+//!
+//! Packet buffers are usually owned by the device and only lent to the layers during a single
+//! callback. Keeping a packet for later, for example to reassemble or reorder it, then requires a
+//! copy. When the device instead stores each packet in a [`Buffer`] slot then the callback can
+//! [`take`] the whole buffer out of it. The device refills empty slots from a [`Pool`] and the
+//! owner of a leased buffer returns it to the pool when it is done.
+//!
+//! The pool does not allocate on its own. It manages buffers that were provided at construction,
+//! which can be owning (for example `Vec<u8>`) or borrowed chunks of a single larger region.
+//!
+//! ```
+//! # use ethox::managed::{Pool, pool::Buffer, Slice};
+//! # use ethox::wire::Payload;
+//! let mut memory = [0; 64];
+//! let (first, second) = memory.split_at_mut(32);
+//! let mut buffers = [first, second];
+//! let mut pool = Pool::new(Slice::Borrowed(&mut buffers[..]));
+//!
+//! // The device fills its slot.
+//! let mut slot = Buffer::default();
+//! assert!(pool.refill(&mut slot));
+//!
+//! // In a callback, take ownership of the packet.
+//! let packet = slot.take().unwrap();
+//! assert_eq!(packet.payload().len(), 32);
+//!
+//! // Return the buffer when done with it.
+//! assert!(pool.release(packet).is_ok());
+//! ```
+//!
+//! [`Buffer`]: struct.Buffer.html
+//! [`take`]: struct.Buffer.html#method.take
+//! [`Pool`]: struct.Pool.html
+use core::mem;
+
+use super::{List, Slice};
+use crate::wire::{Payload, PayloadError, PayloadMut, Reframe, payload};
+
+/// A fixed-capacity pool of packet buffers.
+///
+/// Buffers that are not currently leased are stored in the backing slice. It is never grown, so at
+/// most as many buffers as initially provided can be returned to the pool.
+pub struct Pool<'a, B> {
+    free: List<'a, B>,
+}
+
+/// A slot for a packet buffer from a pool.
+///
+/// This is the payload type of devices supporting buffer leasing. It implements `Payload` and
+/// `PayloadMut` by delegating to the contained buffer. An empty slot acts as an empty payload that
+/// can not be resized.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Buffer<B> {
+    inner: Option<B>,
+}
+
+impl<'a, B: Default> Pool<'a, B> {
+    /// Create a pool from initial buffers.
+    ///
+    /// All buffers in the slice are available for leasing.
+    pub fn new(buffers: Slice<'a, B>) -> Self {
+        Pool {
+            free: List::new_full(buffers),
+        }
+    }
+
+    /// Lease out a buffer.
+    pub fn lease(&mut self) -> Option<B> {
+        self.free.pop().map(mem::take)
+    }
+
+    /// Return a buffer to the pool.
+    ///
+    /// Returns the buffer back if the pool is already full.
+    pub fn release(&mut self, buffer: B) -> Result<(), B> {
+        match self.free.push() {
+            Some(slot) => {
+                *slot = buffer;
+                Ok(())
+            },
+            None => Err(buffer),
+        }
+    }
+
+    /// Fill an empty slot with a buffer from the pool.
+    ///
+    /// Returns `true` if the slot contains a buffer afterwards.
+    pub fn refill(&mut self, slot: &mut Buffer<B>) -> bool {
+        if slot.inner.is_none() {
+            slot.inner = self.lease();
+        }
+        slot.inner.is_some()
+    }
+
+    /// Return the buffer of a slot to the pool, emptying it.
+    ///
+    /// The buffer stays in the slot if the pool is already full.
+    pub fn reclaim(&mut self, slot: &mut Buffer<B>) {
+        if let Some(buffer) = slot.inner.take() {
+            if let Err(buffer) = self.release(buffer) {
+                slot.inner = Some(buffer);
+            }
+        }
+    }
+
+    /// The number of buffers available for leasing.
+    pub fn available(&self) -> usize {
+        self.free.len()
+    }
+
+    /// The maximum number of buffers in the pool.
+    pub fn capacity(&self) -> usize {
+        self.free.capacity()
+    }
+}
+
+impl<B> Buffer<B> {
+    /// Create a slot containing a buffer.
+    pub fn new(buffer: B) -> Self {
+        Buffer { inner: Some(buffer) }
+    }
+
+    /// Create an empty slot.
+    pub fn empty() -> Self {
+        Buffer { inner: None }
+    }
+
+    /// Take ownership of the buffer, leaving the slot empty.
+    pub fn take(&mut self) -> Option<B> {
+        self.inner.take()
+    }
+
+    /// Put a buffer into the slot, returning the previous one.
+    pub fn replace(&mut self, buffer: B) -> Option<B> {
+        self.inner.replace(buffer)
+    }
+
+    /// Check if the slot contains no buffer.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_none()
+    }
+
+    /// Get a reference to the contained buffer.
+    pub fn get(&self) -> Option<&B> {
+        self.inner.as_ref()
+    }
+
+    /// Get a mutable reference to the contained buffer.
+    pub fn get_mut(&mut self) -> Option<&mut B> {
+        self.inner.as_mut()
+    }
+}
+
+impl<B: Payload> Payload for Buffer<B> {
+    fn payload(&self) -> &payload {
+        match &self.inner {
+            Some(buffer) => buffer.payload(),
+            None => (&[][..]).into(),
+        }
+    }
+}
+
+impl<B: PayloadMut> PayloadMut for Buffer<B> {
+    fn payload_mut(&mut self) -> &mut payload {
+        match &mut self.inner {
+            Some(buffer) => buffer.payload_mut(),
+            None => (&mut [][..]).into(),
+        }
+    }
+
+    fn resize(&mut self, length: usize) -> Result<(), PayloadError> {
+        match &mut self.inner {
+            Some(buffer) => buffer.resize(length),
+            None if length == 0 => Ok(()),
+            None => Err(PayloadError::BadSize),
+        }
+    }
+
+    fn reframe(&mut self, reframe: Reframe) -> Result<(), PayloadError> {
+        match &mut self.inner {
+            Some(buffer) => buffer.reframe(reframe),
+            None => self.resize(reframe.length),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managed::Partial;
+
+    #[test]
+    fn lease_and_return() {
+        let mut buffers = [vec![0u8; 8], vec![0u8; 8]];
+        let mut pool = Pool::new(Slice::Borrowed(&mut buffers[..]));
+        assert_eq!(pool.available(), 2);
+        assert_eq!(pool.capacity(), 2);
+
+        let mut slots = [Buffer::empty(), Buffer::empty(), Buffer::empty()];
+        assert!(pool.refill(&mut slots[0]));
+        assert!(pool.refill(&mut slots[1]));
+        assert!(!pool.refill(&mut slots[2]));
+        assert_eq!(pool.available(), 0);
+
+        // A callback takes ownership of the packet.
+        let leased = slots[0].take().unwrap();
+        assert!(slots[0].is_empty());
+        assert_eq!(slots[0].payload().len(), 0);
+        assert_eq!(slots[0].resize(1), Err(PayloadError::BadSize));
+
+        assert_eq!(pool.release(leased), Ok(()));
+        assert!(pool.refill(&mut slots[2]));
+
+        pool.reclaim(&mut slots[1]);
+        pool.reclaim(&mut slots[2]);
+        assert_eq!(pool.available(), 2);
+        assert_eq!(pool.release(vec![]), Err(vec![]));
+    }
+
+    #[test]
+    fn partial_payload() {
+        let mut buffers = [Partial::new(vec![0u8; 8])];
+        let mut pool = Pool::new(Slice::Borrowed(&mut buffers[..]));
+        let mut slot = Buffer::empty();
+        assert!(pool.refill(&mut slot));
+
+        assert_eq!(slot.resize(4), Ok(()));
+        assert_eq!(slot.payload().len(), 4);
+        slot.payload_mut().as_mut_slice().copy_from_slice(&[1, 2, 3, 4]);
+
+        let leased = slot.take().unwrap();
+        assert_eq!(leased.as_slice(), &[1, 2, 3, 4]);
+    }
+}
@@ -32,6 +32,22 @@ impl<'a, T: 'a> Slice<'a, T> {
         Slice::One(T::default())
     }
 
+    /// Returns the number of elements in the slice.
+    ///
+    /// Same as the length of the dereferenced slice but usable in constant expressions.
+    pub const fn len(&self) -> usize {
+        match self {
+            Slice::One(_) => 1,
+            Slice::Many(vec) => vec.len(),
+            Slice::Borrowed(slice) => slice.len(),
+        }
+    }
+
+    /// Check if the slice contains no elements.
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     /// Returns a reference to the contained data as a slice.
     pub fn as_slice(&self) -> &[T] {
         match self {
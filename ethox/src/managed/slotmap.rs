@@ -61,6 +61,10 @@ pub struct SlotMap<'a, T> {
 /// The index remains valid until the entry is removed. If accessing the slotmap with the index
 /// again after the entry was removed will fail, even if the index where the element was previously
 /// stored has been reused for another element.
+///
+/// This is ensured by a generation counter of the map. Each reserved entry gets a new generation
+/// which is stored in its slot and in the key, a lookup compares both. The counter would need to
+/// wrap around all of `isize` before a key could alias a later entry in the same slot.
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 pub struct Key {
     idx: usize,
@@ -126,6 +130,20 @@ impl<T> SlotMap<'_, T> {
         self.elements.get_mut(index.idx)
     }
 
+    /// Iterate over the keys of all occupied entries.
+    pub fn keys(&self) -> impl Iterator<Item=Key> + '_ {
+        (0..self.slots.len()).filter_map(move |idx| self.key_at(idx))
+    }
+
+    /// Get the key of the element at an index, if it is occupied.
+    pub(crate) fn key_at(&self, idx: usize) -> Option<Key> {
+        let generation = self.slots
+            .get(idx)?
+            .generation_id
+            .generation().ok()?;
+        Some(Key { idx, generation })
+    }
+
     /// Reserve a new entry.
     pub fn reserve(&mut self) -> Option<(Key, &mut T)> {
         let index = self.free()?;
@@ -215,16 +233,27 @@ impl<T> SlotMap<'_, T> {
     }
 }
 
+impl Key {
+    /// The index of the element in the backing storage.
+    pub(crate) fn index(self) -> usize {
+        self.idx
+    }
+}
+
 impl<'a, T> SlotMap<'a, T> {
     /// Create a slot map.
     ///
     /// The capacity is the minimum of the capacity of the element and slot slices.
-    pub fn new(elements: Slice<'a, T>, slots: Slice<'a, Slot>) -> Self {
-        let capacity = elements.len().min(slots.len());
+    pub const fn new(elements: Slice<'a, T>, slots: Slice<'a, Slot>) -> Self {
+        let capacity = if elements.len() < slots.len() {
+            elements.len()
+        } else {
+            slots.len()
+        };
         SlotMap {
             elements,
             slots: List::new(slots),
-            generation: Generation::default(),
+            generation: Generation::FIRST,
             free_top: 0,
             indices: IndexComputer::from_capacity(capacity),
         }
@@ -250,8 +279,8 @@ impl GenerationOrFreelink {
 }
 
 impl IndexComputer {
-    pub(crate) fn from_capacity(capacity: usize) -> Self {
-        assert!(capacity < isize::max_value() as usize);
+    pub(crate) const fn from_capacity(capacity: usize) -> Self {
+        assert!(capacity < isize::MAX as usize);
         IndexComputer(capacity)
     }
 
@@ -266,7 +295,7 @@ impl IndexComputer {
         assert!(offset <= length);
         let base = base + 1;
 
-        if length - offset <= base {
+        if offset <= length - base {
             offset + base // Fine within the range
         } else {
             // Wrap once, mod (length + 1), result again in range
@@ -301,6 +330,8 @@ impl IndexComputer {
 }
 
 impl Generation {
+    const FIRST: Generation = Generation(1);
+
     pub(crate) fn advance(&mut self) {
         assert!(self.0 > 0);
         self.0 = self.0.wrapping_add(1).max(1)
@@ -320,7 +351,7 @@ impl Offset {
 
 impl Default for Generation {
     fn default() -> Self {
-        Generation(1)
+        Generation::FIRST
     }
 }
 
@@ -380,4 +411,46 @@ mod tests {
         assert_eq!(map.get(key), None);
         assert_eq!(map.get(new_key), None);
     }
+
+    #[test]
+    fn keys() {
+        let mut elements = [0u32; 3];
+        let mut slots = [Slot::default(); 3];
+
+        let mut map = SlotMap::new(
+            Slice::Borrowed(&mut elements[..]),
+            Slice::Borrowed(&mut slots[..]));
+        let first = map.insert(1).unwrap();
+        let second = map.insert(2).unwrap();
+        map.remove(first).unwrap();
+
+        assert!(map.keys().eq(Some(second)));
+    }
+
+    #[test]
+    fn reused_slot() {
+        let mut elements = [0u32; 2];
+        let mut slots = [Slot::default(); 2];
+
+        let mut map = SlotMap::new(
+            Slice::Borrowed(&mut elements[..]),
+            Slice::Borrowed(&mut slots[..]));
+        let fixed = map.insert(0).unwrap();
+        let mut stale = map.insert(1).unwrap();
+
+        for value in 2..64 {
+            map.remove(stale).unwrap();
+            let key = map.insert(value).unwrap();
+            // Same storage but not the same key.
+            assert_eq!(key.index(), stale.index());
+            assert_ne!(key, stale);
+            assert_eq!(map.get(stale), None);
+            assert_eq!(map.get_mut(stale), None);
+            assert_eq!(map.remove(stale), None);
+            assert_eq!(map.get(key).cloned(), Some(value));
+            stale = key;
+        }
+
+        assert_eq!(map.get(fixed).cloned(), Some(0));
+    }
 }
@@ -0,0 +1,426 @@
+//! A hierarchical timer wheel.
+//!
+//! See the documentation of [`TimerWheel`] for details.
+//!
+//! [`TimerWheel`]: struct.TimerWheel.html
+use core::convert::TryFrom;
+
+use crate::time::{Expiration, Instant};
+use super::Slice;
+
+/// Number of slots on each level of the wheel.
+const SLOTS: usize = 64;
+
+/// The number of bits of the tick count handled by each level.
+const SLOT_BITS: u32 = 6;
+
+/// Number of levels, covering about 4.6 hours with millisecond ticks.
+const LEVELS: usize = 4;
+
+/// Bucket containing timers which have already expired.
+const EXPIRED: usize = SLOTS * LEVELS;
+
+/// Marks the end of a list or an unscheduled timer.
+const NIL: usize = usize::MAX;
+
+/// The state of a single timer in the wheel.
+///
+/// The wheel does not create the storage of its timers, it merely manages one given to it at
+/// construction time. This type is opaque and only required to create that storage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Timer {
+    /// The tick at which the timer expires.
+    deadline: u64,
+    /// The bucket which contains this timer or `NIL` if not scheduled.
+    bucket: usize,
+    prev: usize,
+    next: usize,
+}
+
+/// A hierarchical timer wheel based on external memory.
+///
+/// Manages a fixed number of timers, each identified by its index in the storage. A timer is
+/// either idle or scheduled at some deadline. Scheduling, cancelling and popping an expired timer
+/// take constant time. Advancing the wheel is proportional to the number of occupied slots it
+/// passes, not to the number of timers or the elapsed time.
+///
+/// The wheel has a resolution of one millisecond. Each of its levels consists of 64 slots with the
+/// slots on the first level spanning one millisecond, those on the second level 64 milliseconds,
+/// and so on. A timer is sorted into the level matching the distance to its deadline and moved
+/// down to a more precise level when the wheel reaches its slot. Deadlines beyond the range of
+/// the wheel are supported but cost an additional move for each turn of the highest level.
+///
+/// ## Usage
+///
+/// ```
+/// # use ethox::managed::{Slice, TimerWheel, Timer};
+/// # use ethox::time::Instant;
+/// let mut timers = [Timer::default(); 16];
+/// let start = Instant::from_millis(0);
+///
+/// let mut wheel = TimerWheel::new(Slice::Borrowed(&mut timers[..]), start);
+/// wheel.schedule(3, Instant::from_millis(100));
+/// wheel.schedule(5, Instant::from_millis(10));
+///
+/// assert_eq!(wheel.pop_expired(Instant::from_millis(50)), Some(5));
+/// assert_eq!(wheel.pop_expired(Instant::from_millis(50)), None);
+/// assert_eq!(wheel.pop_expired(Instant::from_millis(100)), Some(3));
+/// ```
+#[derive(Debug)]
+pub struct TimerWheel<'a> {
+    timers: Slice<'a, Timer>,
+    /// The heads of the list of each bucket, including the expired list.
+    heads: [usize; SLOTS * LEVELS + 1],
+    /// Bitmap of non-empty slots for each level.
+    occupied: [u64; LEVELS],
+    /// The instant corresponding to tick zero.
+    epoch: Instant,
+    /// The current tick of the wheel.
+    current: u64,
+}
+
+impl<'a> TimerWheel<'a> {
+    /// Create a wheel with all timers idle.
+    ///
+    /// The current time of the wheel is set to `now`. Deadlines before this are treated as expired
+    /// immediately when scheduled.
+    pub fn new(mut timers: Slice<'a, Timer>, now: Instant) -> Self {
+        for timer in timers.iter_mut() {
+            *timer = Timer::default();
+        }
+
+        TimerWheel {
+            timers,
+            heads: [NIL; SLOTS * LEVELS + 1],
+            occupied: [0; LEVELS],
+            epoch: now,
+            current: 0,
+        }
+    }
+}
+
+impl TimerWheel<'_> {
+    /// The number of timers managed by this wheel.
+    pub fn capacity(&self) -> usize {
+        self.timers.len()
+    }
+
+    /// Schedule a timer, replacing its previous deadline.
+    ///
+    /// Returns `None` if the index is out of bounds.
+    pub fn schedule(&mut self, index: usize, deadline: Instant) -> Option<()> {
+        if index >= self.timers.len() {
+            return None;
+        }
+
+        self.unlink(index);
+        self.timers[index].deadline = self.ticks(deadline);
+        self.place(index);
+        Some(())
+    }
+
+    /// Cancel a timer.
+    ///
+    /// Does nothing if the timer was not scheduled or the index is out of bounds.
+    pub fn cancel(&mut self, index: usize) {
+        if index < self.timers.len() {
+            self.unlink(index);
+        }
+    }
+
+    /// Get the deadline of a scheduled timer.
+    pub fn deadline(&self, index: usize) -> Option<Instant> {
+        let timer = self.timers.get(index)?;
+        if timer.bucket == NIL {
+            return None;
+        }
+
+        Some(self.instant(timer.deadline))
+    }
+
+    /// Advance the wheel and remove one timer which expired at or before `now`.
+    ///
+    /// The timer is idle afterwards. Call this repeatedly until it returns `None` to handle all
+    /// expired timers. Timers are not necessarily returned in the order of their deadlines.
+    pub fn pop_expired(&mut self, now: Instant) -> Option<usize> {
+        let now = self.ticks(now);
+        loop {
+            let expired = self.heads[EXPIRED];
+            if expired != NIL {
+                self.unlink(expired);
+                return Some(expired);
+            }
+
+            match self.next_slot() {
+                Some((level, slot, start)) if start <= now => {
+                    self.current = self.current.max(start);
+                    self.cascade(level, slot);
+                },
+                _ => {
+                    self.current = self.current.max(now);
+                    return None;
+                },
+            }
+        }
+    }
+
+    /// The next time at which a timer may expire.
+    ///
+    /// This is never later than the earliest deadline of all scheduled timers but may be earlier,
+    /// when the wheel needs to move timers between its levels. It is `Never` if no timer is
+    /// scheduled.
+    pub fn next_expiration(&self) -> Expiration {
+        if self.heads[EXPIRED] != NIL {
+            return Expiration::When(self.instant(self.current));
+        }
+
+        match self.next_slot() {
+            Some((_, _, start)) => Expiration::When(self.instant(start)),
+            None => Expiration::Never,
+        }
+    }
+
+    /// Find the occupied slot with the earliest start.
+    fn next_slot(&self) -> Option<(usize, usize, u64)> {
+        (0..LEVELS)
+            .filter_map(|level| {
+                let (slot, start) = self.next_slot_on(level)?;
+                Some((level, slot, start))
+            })
+            .min_by_key(|&(_, _, start)| start)
+    }
+
+    fn next_slot_on(&self, level: usize) -> Option<(usize, u64)> {
+        let occupied = self.occupied[level];
+        if occupied == 0 {
+            return None;
+        }
+
+        let shift = SLOT_BITS * level as u32;
+        let slot_range = 1u64 << shift;
+        let level_range = slot_range << SLOT_BITS;
+
+        let now_slot = (self.current >> shift) as u32 % SLOTS as u32;
+        let rotated = occupied.rotate_right(now_slot);
+        // On the upper levels the current slot only contains timers of the next turn.
+        let distance = match rotated & !1 {
+            later if level > 0 && later != 0 => later.trailing_zeros(),
+            _ => rotated.trailing_zeros(),
+        };
+        let slot = ((now_slot + distance) % SLOTS as u32) as usize;
+
+        let level_start = self.current & !(level_range - 1);
+        let mut start = level_start + slot as u64 * slot_range;
+        if level > 0 && start <= self.current {
+            // Only beyond the range of the wheel, the slot is in the next turn of the top level.
+            start += level_range;
+        }
+
+        Some((slot, start))
+    }
+
+    /// Move all timers of a slot to their new position relative to the current tick.
+    ///
+    /// The list is detached first as timers beyond the range of the wheel return into the same
+    /// slot of the top level.
+    fn cascade(&mut self, level: usize, slot: usize) {
+        let bucket = level * SLOTS + slot;
+        let mut index = self.heads[bucket];
+        self.heads[bucket] = NIL;
+        self.occupied[level] &= !(1 << slot);
+
+        while index != NIL {
+            let next = self.timers[index].next;
+            self.timers[index].bucket = NIL;
+            self.place(index);
+            index = next;
+        }
+    }
+
+    /// Link an unscheduled timer into the bucket matching its deadline.
+    fn place(&mut self, index: usize) {
+        let deadline = self.timers[index].deadline;
+        if deadline <= self.current {
+            return self.link(index, EXPIRED);
+        }
+
+        let masked = (self.current ^ deadline) | (SLOTS as u64 - 1);
+        let significant = 63 - masked.leading_zeros();
+        let level = ((significant / SLOT_BITS) as usize).min(LEVELS - 1);
+        let slot = (deadline >> (SLOT_BITS * level as u32)) as usize % SLOTS;
+        self.link(index, level * SLOTS + slot);
+    }
+
+    fn link(&mut self, index: usize, bucket: usize) {
+        let head = self.heads[bucket];
+        if head != NIL {
+            self.timers[head].prev = index;
+        }
+
+        let timer = &mut self.timers[index];
+        timer.bucket = bucket;
+        timer.prev = NIL;
+        timer.next = head;
+        self.heads[bucket] = index;
+
+        if bucket != EXPIRED {
+            self.occupied[bucket / SLOTS] |= 1 << (bucket % SLOTS);
+        }
+    }
+
+    fn unlink(&mut self, index: usize) {
+        let Timer { bucket, prev, next, .. } = self.timers[index];
+        if bucket == NIL {
+            return;
+        }
+
+        if prev == NIL {
+            self.heads[bucket] = next;
+        } else {
+            self.timers[prev].next = next;
+        }
+
+        if next != NIL {
+            self.timers[next].prev = prev;
+        }
+
+        self.timers[index].bucket = NIL;
+
+        if bucket != EXPIRED && self.heads[bucket] == NIL {
+            self.occupied[bucket / SLOTS] &= !(1 << (bucket % SLOTS));
+        }
+    }
+
+    /// Convert an instant to the ticks since the epoch of the wheel, saturating.
+    fn ticks(&self, instant: Instant) -> u64 {
+        let millis = instant.total_millis().saturating_sub(self.epoch.total_millis());
+        if millis < 0 { 0 } else { millis as u64 }
+    }
+
+    fn instant(&self, ticks: u64) -> Instant {
+        let ticks = i64::try_from(ticks).unwrap_or(i64::MAX);
+        Instant::from_millis(self.epoch.total_millis().saturating_add(ticks))
+    }
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Timer {
+            deadline: 0,
+            bucket: NIL,
+            prev: NIL,
+            next: NIL,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::Duration;
+
+    fn drain(wheel: &mut TimerWheel, now: Instant) -> Vec<usize> {
+        let mut expired: Vec<_> = core::iter::from_fn(|| wheel.pop_expired(now)).collect();
+        expired.sort();
+        expired
+    }
+
+    #[test]
+    fn levels() {
+        let mut timers = [Timer::default(); 8];
+        let start = Instant::from_millis(1_000);
+        let mut wheel = TimerWheel::new(Slice::Borrowed(&mut timers[..]), start);
+
+        let deadlines = [0, 1, 63, 64, 4_095, 4_096, 300_000, 20_000_000];
+        for (index, &offset) in deadlines.iter().enumerate() {
+            wheel.schedule(index, start + Duration::from_millis(offset)).unwrap();
+        }
+
+        assert_eq!(drain(&mut wheel, start), vec![0]);
+        for (index, &offset) in deadlines.iter().enumerate().skip(1) {
+            let deadline = start + Duration::from_millis(offset);
+            assert!(wheel.next_expiration() <= Expiration::When(deadline));
+            assert_eq!(drain(&mut wheel, deadline - Duration::from_millis(1)), vec![]);
+            assert_eq!(drain(&mut wheel, deadline), vec![index]);
+        }
+
+        assert_eq!(wheel.next_expiration(), Expiration::Never);
+    }
+
+    #[test]
+    fn reschedule() {
+        let mut timers = [Timer::default(); 4];
+        let start = Instant::from_millis(0);
+        let mut wheel = TimerWheel::new(Slice::Borrowed(&mut timers[..]), start);
+
+        wheel.schedule(0, Instant::from_millis(500)).unwrap();
+        wheel.schedule(1, Instant::from_millis(500)).unwrap();
+        wheel.schedule(2, Instant::from_millis(700)).unwrap();
+        assert_eq!(wheel.deadline(2), Some(Instant::from_millis(700)));
+        assert!(wheel.schedule(4, Instant::from_millis(0)).is_none());
+
+        wheel.cancel(1);
+        wheel.schedule(2, Instant::from_millis(200)).unwrap();
+        assert_eq!(wheel.deadline(1), None);
+
+        assert_eq!(drain(&mut wheel, Instant::from_millis(300)), vec![2]);
+        assert_eq!(drain(&mut wheel, Instant::from_millis(1_000)), vec![0]);
+        assert_eq!(wheel.deadline(0), None);
+
+        // Deadlines in the past expire immediately.
+        wheel.schedule(3, Instant::from_millis(10)).unwrap();
+        assert_eq!(wheel.next_expiration(), Expiration::When(Instant::from_millis(1_000)));
+        assert_eq!(drain(&mut wheel, Instant::from_millis(1_000)), vec![3]);
+    }
+
+    #[test]
+    fn wrapping() {
+        let mut timers = [Timer::default(); 2];
+        let start = Instant::from_millis(0);
+        let mut wheel = TimerWheel::new(Slice::Borrowed(&mut timers[..]), start);
+
+        // Advance the wheel past a number of turns of the lower levels.
+        let now = Instant::from_millis(123_457);
+        assert_eq!(drain(&mut wheel, now), vec![]);
+
+        wheel.schedule(0, now + Duration::from_millis(70)).unwrap();
+        wheel.schedule(1, now + Duration::from_secs(24 * 3600)).unwrap();
+        assert_eq!(drain(&mut wheel, now + Duration::from_millis(69)), vec![]);
+        assert_eq!(drain(&mut wheel, now + Duration::from_millis(70)), vec![0]);
+        assert_eq!(drain(&mut wheel, now + Duration::from_secs(24 * 3600 - 1)), vec![]);
+        assert_eq!(drain(&mut wheel, now + Duration::from_secs(24 * 3600)), vec![1]);
+    }
+
+    #[test]
+    fn random() {
+        use crate::layer::loss::Xoroshiro256;
+
+        let mut timers = [Timer::default(); 32];
+        let mut deadlines = [None; 32];
+        let mut rng = Xoroshiro256::new(0x5eed);
+        let mut now = Instant::from_millis(0);
+        let mut wheel = TimerWheel::new(Slice::Borrowed(&mut timers[..]), now);
+
+        for _ in 0..2_000 {
+            let index = (rng.next() % 32) as usize;
+            let range = [64, 4_096, 1 << 20, 1 << 26][(rng.next() % 4) as usize];
+            let deadline = now + Duration::from_millis(rng.next() % range);
+            wheel.schedule(index, deadline).unwrap();
+            deadlines[index] = Some(deadline);
+
+            now += Duration::from_millis(rng.next() % 5_000);
+            let earliest = deadlines.iter().copied().min_by_key(|deadline| {
+                Expiration::from(*deadline)
+            }).flatten().into();
+            assert!(wheel.next_expiration() <= earliest);
+
+            let expected: Vec<_> = (0..32)
+                .filter(|&index| deadlines[index].is_some_and(|deadline| deadline <= now))
+                .collect();
+            assert_eq!(drain(&mut wheel, now), expected);
+            for index in expected {
+                deadlines[index] = None;
+            }
+        }
+    }
+}
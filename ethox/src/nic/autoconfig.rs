@@ -0,0 +1,270 @@
+//! Derive a device configuration from its advertised personality.
+//!
+//! The layers query the capabilities of each packet buffer for checksum handling, and callers of
+//! [`Device::rx`] and [`Device::tx`] choose the number of packets processed per call. Choosing
+//! these badly is not an error but can be silently slow, for example by calling the device for
+//! every single packet even though it could process a whole ring at once. An [`Autoconfig`] is
+//! initialized from the [`Personality`] of a device at startup and selects the preferred settings,
+//! which can then be selectively overridden before wrapping the device into a [`Tuned`] device.
+//!
+//! Segmentation offloads are not yet modelled by the personality and thus not configured here.
+//!
+//! [`Device::rx`]: ../trait.Device.html#tymethod.rx
+//! [`Device::tx`]: ../trait.Device.html#tymethod.tx
+//! [`Autoconfig`]: struct.Autoconfig.html
+//! [`Personality`]: ../struct.Personality.html
+//! [`Tuned`]: struct.Tuned.html
+use crate::layer::Result;
+use crate::time::Instant;
+use crate::wire::Payload;
+
+use super::{Capabilities, Device, Handle, HardwareTimestamp, Info, Packet, Personality, Recv, Send};
+
+/// A configuration selected from the personality of a device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Autoconfig {
+    capabilities: Capabilities,
+    rx_batch: usize,
+    tx_batch: usize,
+}
+
+/// A device using an automatic configuration.
+///
+/// All packet buffers report the capabilities of the configuration instead of those of the
+/// underlying device. The wrapped device is otherwise used unchanged. Use [`rx_auto`] and
+/// [`tx_auto`] to process packets in the configured batch sizes.
+///
+/// [`rx_auto`]: #method.rx_auto
+/// [`tx_auto`]: #method.tx_auto
+pub struct Tuned<'a, D>(pub D, pub &'a Autoconfig);
+
+/// A handle wrapper reporting the configured capabilities.
+///
+/// This pretends to be static but internally wraps a reference to the underlying handle, for the
+/// same reasons and with the same precautions as the handle of the `Lossy` layer.
+pub struct TunedHandle<H: ?Sized> {
+    capabilities: Capabilities,
+    handle: *mut H,
+}
+
+impl Autoconfig {
+    /// Select the preferred configuration of a device personality.
+    ///
+    /// All checksums which the device offloads are not computed by the stack and the batch sizes
+    /// are those preferred by the device.
+    pub fn new(personality: &Personality) -> Self {
+        Autoconfig {
+            capabilities: *personality.capabilities(),
+            rx_batch: personality.rx_batch().max(1),
+            tx_batch: personality.tx_batch().max(1),
+        }
+    }
+
+    /// Select the preferred configuration of a device.
+    pub fn from_device<D: Device + ?Sized>(device: &D) -> Self {
+        Self::new(&device.personality())
+    }
+
+    /// Get the selected capabilities.
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    /// Mutably get the capabilities, to override the checksum handling.
+    ///
+    /// Forcing manual checksums is always possible, for example when the device is known to
+    /// advertise offloads that are broken. Ignoring checksums that the device does not offload is
+    /// only correct where both sides of a link ignore checksums entirely.
+    pub fn capabilities_mut(&mut self) -> &mut Capabilities {
+        &mut self.capabilities
+    }
+
+    /// Get the number of packets to receive per call.
+    pub fn rx_batch(&self) -> usize {
+        self.rx_batch
+    }
+
+    /// Mutably get the number of packets to receive per call.
+    pub fn rx_batch_mut(&mut self) -> &mut usize {
+        &mut self.rx_batch
+    }
+
+    /// Get the number of packets to send per call.
+    pub fn tx_batch(&self) -> usize {
+        self.tx_batch
+    }
+
+    /// Mutably get the number of packets to send per call.
+    pub fn tx_batch_mut(&mut self) -> &mut usize {
+        &mut self.tx_batch
+    }
+
+    /// Wrap a device to use this configuration.
+    pub fn tune<D>(&self, device: D) -> Tuned<'_, D> {
+        Tuned(device, self)
+    }
+}
+
+impl<D: Device> Tuned<'_, D> {
+    /// Receive one batch of the configured size.
+    pub fn rx_auto(&mut self, receiver: impl Recv<TunedHandle<D::Handle>, D::Payload>)
+        -> Result<usize>
+    {
+        let max = self.1.rx_batch;
+        self.rx(max, receiver)
+    }
+
+    /// Send one batch of the configured size.
+    pub fn tx_auto(&mut self, sender: impl Send<TunedHandle<D::Handle>, D::Payload>)
+        -> Result<usize>
+    {
+        let max = self.1.tx_batch;
+        self.tx(max, sender)
+    }
+}
+
+impl<H: ?Sized> TunedHandle<H> {
+    /// Instantiate behind a reference with short enough lifetime to ensure it doesn't escape.
+    fn new<'a>(
+        uninit: &'a mut core::mem::MaybeUninit<Self>,
+        capabilities: Capabilities,
+        handle: &'a mut H,
+    ) -> &'a mut Self {
+        unsafe {
+            (*uninit.as_mut_ptr()).capabilities = capabilities;
+            (*uninit.as_mut_ptr()).handle = handle;
+            // Initialized all fields
+            &mut *uninit.as_mut_ptr()
+        }
+    }
+}
+
+impl<H, P, I> Recv<H, P> for Tuned<'_, I>
+where
+    H: Handle + ?Sized,
+    P: Payload + ?Sized,
+    I: Recv<TunedHandle<H>, P>,
+{
+    fn receive(&mut self, packet: Packet<H, P>) {
+        let Packet { handle, payload } = packet;
+        let mut handle_mem = core::mem::MaybeUninit::uninit();
+        let handle = TunedHandle::new(
+            &mut handle_mem,
+            self.1.capabilities,
+            handle);
+
+        self.0.receive(Packet {
+            handle,
+            payload,
+        })
+    }
+}
+
+impl<H, P, I> Send<H, P> for Tuned<'_, I>
+where
+    H: Handle + ?Sized,
+    P: Payload + ?Sized,
+    I: Send<TunedHandle<H>, P>,
+{
+    fn send(&mut self, packet: Packet<H, P>) {
+        let Packet { handle, payload } = packet;
+        let mut handle_mem = core::mem::MaybeUninit::uninit();
+        let handle = TunedHandle::new(
+            &mut handle_mem,
+            self.1.capabilities,
+            handle);
+
+        self.0.send(Packet {
+            handle,
+            payload,
+        })
+    }
+}
+
+impl<H: Handle + ?Sized> Handle for TunedHandle<H> {
+    fn queue(&mut self) -> Result<()> {
+        unsafe { &mut *self.handle }.queue()
+    }
+
+    fn info(&self) -> &dyn Info {
+        self
+    }
+
+    fn retain(&mut self) -> Result<()> {
+        unsafe { &mut *self.handle }.retain()
+    }
+
+    fn request_tx_timestamp(&mut self, id: u32) -> Result<()> {
+        unsafe { &mut *self.handle }.request_tx_timestamp(id)
+    }
+}
+
+impl<H: Handle + ?Sized> Info for TunedHandle<H> {
+    fn timestamp(&self) -> Instant {
+        unsafe { &*self.handle }.info().timestamp()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    fn hardware_timestamp(&self) -> Option<HardwareTimestamp> {
+        unsafe { &*self.handle }.info().hardware_timestamp()
+    }
+}
+
+impl<D: Device> Device for Tuned<'_, D> {
+    type Handle = TunedHandle<D::Handle>;
+    type Payload = D::Payload;
+
+    fn personality(&self) -> Personality {
+        let mut personality = self.0.personality();
+        *personality.capabilities_mut() = self.1.capabilities;
+        *personality.rx_batch_mut() = self.1.rx_batch;
+        *personality.tx_batch_mut() = self.1.tx_batch;
+        personality
+    }
+
+    fn tx(&mut self, max: usize, sender: impl Send<Self::Handle, Self::Payload>)
+        -> Result<usize>
+    {
+        self.0.tx(max, Tuned(sender, self.1))
+    }
+
+    fn rx(&mut self, max: usize, receptor: impl Recv<Self::Handle, Self::Payload>)
+        -> Result<usize>
+    {
+        self.0.rx(max, Tuned(receptor, self.1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::FnHandler;
+    use crate::managed::Slice;
+    use crate::nic::loopback::Loopback;
+    use crate::nic::{Protocol, tests::LengthIo};
+    use crate::wire::Checksum;
+
+    #[test]
+    fn overrides() {
+        let mut buffers = [vec![0u8; 64], vec![0u8; 64], vec![0u8; 64], vec![0u8; 64]];
+        let loopback = Loopback::new(Slice::Borrowed(&mut buffers[..]));
+
+        let mut config = Autoconfig::from_device(&loopback);
+        assert_eq!(config.rx_batch(), 4);
+        assert_eq!(config.tx_batch(), 4);
+        assert_eq!(config.capabilities(), &Capabilities::no_support());
+
+        *config.tx_batch_mut() = 2;
+        *config.capabilities_mut().ipv4_mut() = Protocol::offloaded();
+        let mut device = config.tune(loopback);
+
+        assert_eq!(device.tx_auto(LengthIo), Ok(2));
+        assert_eq!(device.rx_auto(FnHandler(|packet: Packet<TunedHandle<_>, Vec<u8>>| {
+            let capabilities = packet.handle.info().capabilities();
+            assert_eq!(capabilities.ipv4().rx_checksum(), Checksum::Ignored);
+        })), Ok(2));
+    }
+}
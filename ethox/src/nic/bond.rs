@@ -0,0 +1,282 @@
+//! Aggregation of several devices into a single logical link.
+//!
+//! A [`Bond`] combines devices connected to the same network such that the endpoints above
+//! survive the failure of any single one of them. The link state of each member is queried with
+//! [`Device::link_up`] on every operation, there is no separate monitoring.
+//!
+//! In [`Mode::ActiveBackup`] only one member is used at a time. When its link goes down the bond
+//! fails over to the next member that has a link and keeps using it, even after the original
+//! member recovers. Frames received on the other members are left in their queues.
+//!
+//! In [`Mode::BalanceXor`] all members with a link are used. Packets are received from each of
+//! them while transmission is distributed by a flow hash, see [`Bond::tx_hashed`], so that the
+//! packets of a single flow are not reordered. Note that the members of a bond share the same
+//! ethernet address and in this mode the switch needs to be configured for a static aggregation
+//! of the ports.
+//!
+//! [`Bond`]: struct.Bond.html
+//! [`Bond::tx_hashed`]: struct.Bond.html#method.tx_hashed
+//! [`Device::link_up`]: ../trait.Device.html#method.link_up
+//! [`Mode::ActiveBackup`]: enum.Mode.html#variant.ActiveBackup
+//! [`Mode::BalanceXor`]: enum.Mode.html#variant.BalanceXor
+use crate::managed::Slice;
+use crate::wire::EthernetAddress;
+
+use super::{Device, Personality, Recv, Result, Send, TxTimestamp};
+
+/// The policy of a bond for using its members.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// Use a single member, failing over to another one when it loses its link.
+    ActiveBackup,
+    /// Use all members with a link, selecting one by a flow hash for each transmission.
+    BalanceXor,
+}
+
+/// Multiple devices combined into one.
+///
+/// All members must have the same handle and payload types and should have the same
+/// personality. The personality of the bond is the one of its first member.
+pub struct Bond<'a, D> {
+    members: Slice<'a, D>,
+    mode: Mode,
+    /// The member in use in active-backup mode.
+    active: Option<usize>,
+    /// The member receiving the next unhashed transmission in balance-xor mode.
+    next: usize,
+}
+
+impl<'a, D: Device> Bond<'a, D> {
+    /// Combine some devices into a bond.
+    ///
+    /// In active-backup mode the first member that has a link becomes active.
+    pub fn new(members: Slice<'a, D>, mode: Mode) -> Self {
+        let mut bond = Bond {
+            members,
+            mode,
+            active: None,
+            next: 0,
+        };
+        bond.update();
+        bond
+    }
+
+    /// The policy of using the members.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// The devices combined in the bond.
+    pub fn members(&self) -> &[D] {
+        &self.members
+    }
+
+    /// Mutably borrow the devices combined in the bond.
+    pub fn members_mut(&mut self) -> &mut [D] {
+        &mut self.members
+    }
+
+    /// The index of the member in use in active-backup mode.
+    ///
+    /// This is as of the last operation and `None` if no member had a link.
+    pub fn active(&self) -> Option<usize> {
+        self.active
+    }
+
+    /// Check the link state of the members and fail over if necessary.
+    ///
+    /// This is done automatically before each operation. Returns the active member.
+    pub fn update(&mut self) -> Option<usize> {
+        let current = self.active
+            .filter(|&idx| self.members.get(idx).is_some_and(D::link_up));
+        self.active = current.or_else(|| self.members.iter().position(D::link_up));
+        self.active
+    }
+
+    /// The number of members that currently have a link.
+    pub fn links_up(&self) -> usize {
+        self.members.iter().filter(|member| member.link_up()).count()
+    }
+
+    /// Transmit packets of a flow with a particular hash.
+    ///
+    /// In balance-xor mode the hash selects among the members that have a link, so that packets
+    /// with equal hash are sent over the same member as long as the link states do not change.
+    /// The hash is ignored in active-backup mode. See [`layer2_hash`] for a simple choice.
+    ///
+    /// [`layer2_hash`]: fn.layer2_hash.html
+    pub fn tx_hashed(&mut self, hash: u32, max: usize, sender: impl Send<D::Handle, D::Payload>)
+        -> Result<usize>
+    {
+        let member = match self.mode {
+            Mode::ActiveBackup => self.update(),
+            Mode::BalanceXor => self.nth_up(hash as usize),
+        };
+
+        match member {
+            Some(idx) => self.members[idx].tx(max, sender),
+            None => Ok(0),
+        }
+    }
+
+    /// Find the member with a link at some index among all those with a link.
+    fn nth_up(&self, idx: usize) -> Option<usize> {
+        let count = self.links_up();
+        if count == 0 {
+            return None;
+        }
+
+        self.members.iter()
+            .enumerate()
+            .filter(|(_, member)| member.link_up())
+            .nth(idx % count)
+            .map(|(idx, _)| idx)
+    }
+}
+
+/// A flow hash of the ethernet addresses of a frame.
+///
+/// This is symmetric, and the same for all traffic between two hosts.
+pub fn layer2_hash(src_addr: EthernetAddress, dst_addr: EthernetAddress) -> u32 {
+    let fold = |addr: EthernetAddress| {
+        let [_, _, a, b, c, d] = addr.0;
+        u32::from_be_bytes([a, b, c, d])
+    };
+    fold(src_addr) ^ fold(dst_addr)
+}
+
+impl<D: Device> Device for Bond<'_, D> {
+    type Handle = D::Handle;
+    type Payload = D::Payload;
+
+    fn personality(&self) -> Personality {
+        match self.members.first() {
+            Some(member) => member.personality(),
+            None => Personality::baseline(),
+        }
+    }
+
+    /// Transmit on the active member, or on the next member in balance-xor mode.
+    ///
+    /// Without a flow hash, the calls are distributed round-robin over the members with a link in
+    /// balance-xor mode. Use [`tx_hashed`] instead to avoid reordering packets of a flow.
+    ///
+    /// [`tx_hashed`]: #method.tx_hashed
+    fn tx(&mut self, max: usize, sender: impl Send<Self::Handle, Self::Payload>)
+        -> Result<usize>
+    {
+        let hash = self.next as u32;
+        self.next = self.next.wrapping_add(1);
+        self.tx_hashed(hash, max, sender)
+    }
+
+    /// Receive from the active member, or all members with a link in balance-xor mode.
+    fn rx(&mut self, max: usize, mut receiver: impl Recv<Self::Handle, Self::Payload>)
+        -> Result<usize>
+    {
+        if self.mode == Mode::ActiveBackup {
+            return match self.update() {
+                Some(idx) => self.members[idx].rx(max, receiver),
+                None => Ok(0),
+            };
+        }
+
+        let mut count = 0;
+        for member in self.members.iter_mut().filter(|member| member.link_up()) {
+            if count >= max {
+                break;
+            }
+            count += member.rx(max - count, &mut receiver)?;
+        }
+
+        Ok(count)
+    }
+
+    /// There is a link as long as any member has one.
+    fn link_up(&self) -> bool {
+        self.members.iter().any(D::link_up)
+    }
+
+    /// Retrieve a timestamp from any of the members.
+    ///
+    /// Note that the hardware clocks of the members are not necessarily synchronized.
+    fn tx_timestamp(&mut self) -> Option<TxTimestamp> {
+        self.members.iter_mut().find_map(D::tx_timestamp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::FnHandler;
+    use crate::nic::{external::{External, Handle}, Packet};
+    use crate::nic::tests::LengthIo;
+
+    type Member = External<Vec<Vec<u8>>>;
+
+    fn ignore(_: Packet<Handle, Vec<u8>>) { }
+
+    fn members() -> Slice<'static, Member> {
+        vec![
+            External::new_send(vec![vec![0; 64]; 4]),
+            External::new_send(vec![vec![0; 64]; 4]),
+            External::new_send(vec![vec![0; 64]; 4]),
+        ].into()
+    }
+
+    #[test]
+    fn active_backup() {
+        let mut bond = Bond::new(members(), Mode::ActiveBackup);
+        assert_eq!(bond.active(), Some(0));
+        assert_eq!(bond.tx(1, LengthIo), Ok(1));
+        assert_eq!(bond.members()[0].to_send(), 3);
+
+        // Fail over to the next member, and stay there after recovery.
+        bond.members_mut()[0].set_link_up(false);
+        assert_eq!(bond.tx(1, LengthIo), Ok(1));
+        assert_eq!(bond.active(), Some(1));
+        assert_eq!(bond.members()[1].to_send(), 3);
+        bond.members_mut()[0].set_link_up(true);
+        assert_eq!(bond.tx(2, LengthIo), Ok(2));
+        assert_eq!(bond.members()[1].to_send(), 1);
+        assert_eq!(bond.active(), Some(1));
+
+        // Only the active member is received from.
+        bond.members_mut()[0].receive_all();
+        bond.members_mut()[1].receive_all();
+        assert_eq!(bond.rx(8, FnHandler(ignore)), Ok(4));
+        assert_eq!(bond.members()[0].to_recv(), 4);
+
+        for member in bond.members_mut() {
+            member.set_link_up(false);
+        }
+        assert!(!bond.link_up());
+        assert_eq!(bond.tx(1, LengthIo), Ok(0));
+        assert_eq!(bond.active(), None);
+    }
+
+    #[test]
+    fn balance_xor() {
+        let mut bond = Bond::new(members(), Mode::BalanceXor);
+        assert_eq!(bond.tx_hashed(1, 1, LengthIo), Ok(1));
+        assert_eq!(bond.tx_hashed(4, 1, LengthIo), Ok(1));
+        assert_eq!(bond.members()[1].to_send(), 2);
+
+        // The hash selects among the remaining members.
+        bond.members_mut()[0].set_link_up(false);
+        assert_eq!(bond.tx_hashed(1, 1, LengthIo), Ok(1));
+        assert_eq!(bond.members()[2].to_send(), 3);
+
+        // Received from all members with a link.
+        for member in bond.members_mut() {
+            member.receive_all();
+        }
+        assert_eq!(bond.rx(6, FnHandler(ignore)), Ok(6));
+        assert_eq!(bond.members()[0].to_recv(), 4);
+        assert_eq!(bond.members()[2].to_recv(), 2);
+
+        let a = EthernetAddress([0, 1, 2, 3, 4, 5]);
+        let b = EthernetAddress([0, 1, 2, 3, 4, 6]);
+        assert_eq!(layer2_hash(a, b), layer2_hash(b, a));
+    }
+}
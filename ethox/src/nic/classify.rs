@@ -0,0 +1,300 @@
+//! A programmable classification stage for received frames.
+//!
+//! A [`Classify`] receiver sits directly on the device and consults a [`Classifier`] with the
+//! parsed ethernet and ip headers of each frame, before any layer sees it. The classifier decides
+//! on an [`Action`]: which of several handlers receives the frame, if it is dropped instead,
+//! whether another handler gets to see a mirror of it first, and a mark which the handlers can
+//! query from the packet handle. This allows steering traffic by header fields and port mirroring
+//! without modifying the handlers themselves.
+//!
+//! The handlers are a tuple of receivers, indexed by their position. Each may be a complete layer
+//! stack such as `eth.recv(ip.recv(..))` or a plain function.
+//!
+//! [`Action`]: struct.Action.html
+//! [`Classifier`]: trait.Classifier.html
+//! [`Classify`]: struct.Classify.html
+use crate::layer::Result;
+use crate::wire::{Checksum, Payload, Error as WireError};
+use crate::wire::{EthernetFrame, EthernetProtocol, EthernetRepr, IpRepr};
+use crate::wire::{Ipv4Repr, Ipv6Repr, ipv4_packet, ipv6_packet};
+
+use super::{Info, Packet, Recv};
+
+/// The parsed headers of a received frame.
+#[derive(Clone, Debug)]
+pub struct Headers<'a> {
+    /// The ethernet header.
+    pub eth: EthernetRepr,
+    /// The ip header, if the frame contains an ip packet that could be parsed.
+    pub ip: Option<IpRepr>,
+    /// The payload of the innermost parsed header.
+    ///
+    /// This is the transport header and payload for ip packets.
+    pub payload: &'a [u8],
+}
+
+/// The decision on a received frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Action {
+    /// The index of the handler receiving the frame, or `None` to drop it.
+    pub handler: Option<usize>,
+    /// The index of a handler receiving the frame before, for example to record or copy it.
+    pub mirror: Option<usize>,
+    /// A mark for the frame that the handlers can query from the handle.
+    pub mark: Option<u32>,
+}
+
+/// Decides on the action for each received frame.
+///
+/// Implemented for all closures with a matching signature.
+pub trait Classifier {
+    /// Choose an action based on the headers of a frame.
+    fn classify(&mut self, headers: &Headers) -> Action;
+}
+
+/// The handlers among which a classifier chooses.
+///
+/// Implemented for tuples of up to six receivers.
+pub trait Handlers<H: super::Handle + ?Sized, P: Payload + ?Sized> {
+    /// Receive a packet with the handler at an index.
+    ///
+    /// The packet is dropped if there is no such handler.
+    fn receive(&mut self, idx: usize, packet: Packet<H, P>);
+}
+
+/// A receiver classifying each frame before dispatching it to one of several handlers.
+pub struct Classify<C, T> {
+    classifier: C,
+    handlers: T,
+}
+
+/// The handle of a classified packet, providing its mark.
+///
+/// All other methods are forwarded to the handle of the device.
+pub struct Handle<'a, H: ?Sized> {
+    inner: &'a mut H,
+    mark: Option<u32>,
+}
+
+impl<'a> Headers<'a> {
+    /// Parse the headers of a frame.
+    ///
+    /// Fails only if the ethernet header is malformed. Ip packets whose header can not be parsed
+    /// are presented as unknown ethernet payload. Checksums are not verified.
+    pub fn parse(frame: &'a [u8]) -> core::result::Result<Self, WireError> {
+        let frame = EthernetFrame::new_checked(frame)?;
+        let eth = frame.repr();
+        let payload = frame.payload_bytes();
+
+        let ip = match eth.ethertype {
+            EthernetProtocol::Ipv4 => ipv4_packet::new_checked(payload)
+                .and_then(|packet| Ok((Ipv4Repr::parse(packet, Checksum::Ignored)?, packet)))
+                .ok()
+                .map(|(repr, packet)| (IpRepr::Ipv4(repr), packet.payload_slice())),
+            EthernetProtocol::Ipv6 => ipv6_packet::new_checked(payload)
+                .and_then(|packet| Ok((Ipv6Repr::parse(packet)?, packet)))
+                .ok()
+                .map(|(repr, packet)| (IpRepr::Ipv6(repr), packet.payload_slice())),
+            _ => None,
+        };
+
+        Ok(match ip {
+            Some((ip, payload)) => Headers { eth, ip: Some(ip), payload },
+            None => Headers { eth, ip: None, payload },
+        })
+    }
+}
+
+impl Action {
+    /// Drop the frame.
+    pub const DROP: Action = Action { handler: None, mirror: None, mark: None };
+
+    /// Pass the frame to a handler.
+    pub const fn pass(handler: usize) -> Self {
+        Action { handler: Some(handler), mirror: None, mark: None }
+    }
+
+    /// Additionally pass the frame to a mirror handler first.
+    pub const fn mirrored(self, mirror: usize) -> Self {
+        Action { mirror: Some(mirror), ..self }
+    }
+
+    /// Attach a mark to the frame.
+    pub const fn marked(self, mark: u32) -> Self {
+        Action { mark: Some(mark), ..self }
+    }
+}
+
+impl<C, T> Classify<C, T> {
+    /// Classify frames for a tuple of handlers.
+    pub fn new(classifier: C, handlers: T) -> Self {
+        Classify { classifier, handlers }
+    }
+
+    /// Get a mutable reference to the classifier, for example to update its rules.
+    pub fn classifier_mut(&mut self) -> &mut C {
+        &mut self.classifier
+    }
+
+    /// Unwrap the classifier and handlers.
+    pub fn into_inner(self) -> (C, T) {
+        (self.classifier, self.handlers)
+    }
+}
+
+impl<H: ?Sized> Handle<'_, H> {
+    /// The mark chosen by the classifier, if any.
+    pub fn mark(&self) -> Option<u32> {
+        self.mark
+    }
+}
+
+impl<F> Classifier for F
+    where F: FnMut(&Headers) -> Action,
+{
+    fn classify(&mut self, headers: &Headers) -> Action {
+        self(headers)
+    }
+}
+
+impl<H, P, C, T> Recv<H, P> for Classify<C, T>
+where
+    H: super::Handle + ?Sized,
+    P: Payload + ?Sized,
+    C: Classifier,
+    T: for<'h> Handlers<Handle<'h, H>, P>,
+{
+    fn receive(&mut self, packet: Packet<H, P>) {
+        let action = match Headers::parse(packet.payload.payload().as_slice()) {
+            Ok(headers) => self.classifier.classify(&headers),
+            Err(_) => return,
+        };
+
+        let mut handle = Handle {
+            inner: packet.handle,
+            mark: action.mark,
+        };
+
+        if let Some(mirror) = action.mirror {
+            self.handlers.receive(mirror, Packet {
+                handle: &mut handle,
+                payload: &mut *packet.payload,
+            });
+        }
+
+        if let Some(handler) = action.handler {
+            self.handlers.receive(handler, Packet {
+                handle: &mut handle,
+                payload: packet.payload,
+            });
+        }
+    }
+}
+
+impl<H: super::Handle + ?Sized> super::Handle for Handle<'_, H> {
+    fn queue(&mut self) -> Result<()> {
+        self.inner.queue()
+    }
+
+    fn info(&self) -> &dyn Info {
+        self.inner.info()
+    }
+
+    fn retain(&mut self) -> Result<()> {
+        self.inner.retain()
+    }
+
+    fn request_tx_timestamp(&mut self, id: u32) -> Result<()> {
+        self.inner.request_tx_timestamp(id)
+    }
+}
+
+macro_rules! tuple_handlers {
+    ($($idx:tt $name:ident),+) => {
+        impl<H, P, $($name),+> Handlers<H, P> for ($($name,)+)
+        where
+            H: super::Handle + ?Sized,
+            P: Payload + ?Sized,
+            $($name: Recv<H, P>),+
+        {
+            fn receive(&mut self, idx: usize, packet: Packet<H, P>) {
+                match idx {
+                    $($idx => self.$idx.receive(packet),)+
+                    _ => (),
+                }
+            }
+        }
+    };
+}
+
+tuple_handlers!(0 A);
+tuple_handlers!(0 A, 1 B);
+tuple_handlers!(0 A, 1 B, 2 C);
+tuple_handlers!(0 A, 1 B, 2 C, 3 D);
+tuple_handlers!(0 A, 1 B, 2 C, 3 D, 4 E);
+tuple_handlers!(0 A, 1 B, 2 C, 3 D, 4 E, 5 F);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::FnHandler;
+    use crate::managed::Slice;
+    use crate::nic::{external, Device};
+    use crate::wire::{EthernetAddress, IpProtocol, Ipv4Address, ethernet_frame};
+
+    type In<'a, 'h> = Packet<'a, Handle<'h, external::Handle>, Vec<u8>>;
+
+    fn frame(ethertype: EthernetProtocol) -> Vec<u8> {
+        let mut buffer = vec![0; 64];
+        let eth = ethernet_frame::new_unchecked_mut(&mut buffer);
+        EthernetRepr {
+            src_addr: EthernetAddress([0x52, 0x54, 0, 0, 0, 1]),
+            dst_addr: EthernetAddress([0x52, 0x54, 0, 0, 0, 2]),
+            ethertype,
+        }.emit(eth);
+        if ethertype == EthernetProtocol::Ipv4 {
+            Ipv4Repr {
+                src_addr: Ipv4Address::new(10, 0, 0, 1),
+                dst_addr: Ipv4Address::new(10, 0, 0, 2),
+                protocol: IpProtocol::Udp,
+                payload_len: 30,
+                hop_limit: 64,
+            }.emit(ipv4_packet::new_unchecked_mut(eth.payload_mut_slice()), Checksum::Ignored);
+        }
+        buffer
+    }
+
+    #[test]
+    fn steering() {
+        let mut nic = external::External::new_recv(Slice::Many(vec![
+            frame(EthernetProtocol::Ipv4),
+            frame(EthernetProtocol::Arp),
+            frame(EthernetProtocol::Unknown(0xBEEF)),
+        ]));
+
+        let (mut ip, mut other, mut mirrored) = (0, 0, 0);
+        let classifier = |headers: &Headers| match (headers.eth.ethertype, &headers.ip) {
+            (_, Some(IpRepr::Ipv4(repr))) => {
+                assert_eq!(repr.protocol, IpProtocol::Udp);
+                assert_eq!(headers.payload.len(), 30);
+                Action::pass(0).mirrored(2).marked(7)
+            },
+            (EthernetProtocol::Arp, _) => Action::pass(1),
+            _ => Action::DROP,
+        };
+        let handlers = (
+            FnHandler(|packet: In| {
+                assert_eq!(packet.handle.mark(), Some(7));
+                ip += 1;
+            }),
+            FnHandler(|packet: In| {
+                assert_eq!(packet.handle.mark(), None);
+                other += 1;
+            }),
+            FnHandler(|_: In| mirrored += 1),
+        );
+
+        assert_eq!(nic.rx(3, Classify::new(classifier, handlers)), Ok(3));
+        assert_eq!((ip, other, mirrored), (1, 1, 1));
+    }
+}
@@ -2,12 +2,14 @@
 use crate::layer::{Error, Result};
 use crate::time::Instant;
 
-use super::{Capabilities, Handle, Info};
+use super::{Capabilities, Handle, HardwareTimestamp, Info};
 
 /// A handle representation allowing to set a flag for queueing a packet.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct EnqueueFlag {
     flag: FlagState,
+    retain: RetainState,
+    tx_timestamp: TimestampState,
     info: PacketInfo,
 }
 
@@ -23,6 +25,8 @@ pub struct PacketInfo {
     pub timestamp: Instant,
     /// The capabilities offered for a packet buffer.
     pub capabilities: Capabilities,
+    /// The hardware timestamp of a received packet, if the device supports them.
+    pub hardware_timestamp: Option<HardwareTimestamp>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -31,11 +35,25 @@ enum FlagState {
     SetTrue(bool),
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RetainState {
+    NotPossible,
+    Allowed(bool),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TimestampState {
+    NotPossible,
+    Allowed(Option<u32>),
+}
+
 impl EnqueueFlag {
     /// Create a flag signalling that the buffer can not be queued.
     pub fn not_possible(info: PacketInfo) -> Self {
         EnqueueFlag {
             flag: FlagState::NotPossible,
+            retain: RetainState::NotPossible,
+            tx_timestamp: TimestampState::NotPossible,
             info,
         }
     }
@@ -44,6 +62,8 @@ impl EnqueueFlag {
     pub fn set_true(info: PacketInfo) -> Self {
         EnqueueFlag {
             flag: FlagState::SetTrue(false),
+            retain: RetainState::NotPossible,
+            tx_timestamp: TimestampState::NotPossible,
             info,
         }
     }
@@ -54,6 +74,44 @@ impl EnqueueFlag {
     pub fn was_sent(&self) -> bool {
         self.flag.was_sent()
     }
+
+    /// Permit exchanging the buffer with a spare.
+    ///
+    /// Only use this when the device does not rely on the identity of its buffers, see
+    /// [`Handle::retain`].
+    ///
+    /// [`Handle::retain`]: ../trait.Handle.html#method.retain
+    pub fn allow_retain(self) -> Self {
+        EnqueueFlag {
+            retain: RetainState::Allowed(false),
+            ..self
+        }
+    }
+
+    /// Query if the buffer may have been exchanged with a spare.
+    pub fn was_retained(&self) -> bool {
+        self.retain == RetainState::Allowed(true)
+    }
+
+    /// Permit requesting a hardware timestamp of the sent packet.
+    ///
+    /// See [`Handle::request_tx_timestamp`].
+    ///
+    /// [`Handle::request_tx_timestamp`]: ../trait.Handle.html#method.request_tx_timestamp
+    pub fn allow_tx_timestamp(self) -> Self {
+        EnqueueFlag {
+            tx_timestamp: TimestampState::Allowed(None),
+            ..self
+        }
+    }
+
+    /// Query the identifier with which a hardware timestamp was requested, if any.
+    pub fn tx_timestamp_requested(&self) -> Option<u32> {
+        match self.tx_timestamp {
+            TimestampState::NotPossible => None,
+            TimestampState::Allowed(id) => id,
+        }
+    }
 }
 
 impl FlagState {
@@ -80,6 +138,26 @@ impl Handle for EnqueueFlag {
     fn info(&self) -> &dyn Info {
         &self.info
     }
+
+    fn retain(&mut self) -> Result<()> {
+        match self.retain {
+            RetainState::NotPossible => Err(Error::Illegal),
+            RetainState::Allowed(_) => {
+                self.retain = RetainState::Allowed(true);
+                Ok(())
+            },
+        }
+    }
+
+    fn request_tx_timestamp(&mut self, id: u32) -> Result<()> {
+        match self.tx_timestamp {
+            TimestampState::NotPossible => Err(Error::Illegal),
+            TimestampState::Allowed(_) => {
+                self.tx_timestamp = TimestampState::Allowed(Some(id));
+                Ok(())
+            },
+        }
+    }
 }
 
 impl Info for PacketInfo {
@@ -90,4 +168,8 @@ impl Info for PacketInfo {
     fn capabilities(&self) -> Capabilities {
         self.capabilities
     }
+
+    fn hardware_timestamp(&self) -> Option<HardwareTimestamp> {
+        self.hardware_timestamp
+    }
 }
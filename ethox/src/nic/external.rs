@@ -3,7 +3,8 @@ use core::ops::{Deref, DerefMut};
 use crate::wire::Payload;
 use crate::time::Instant;
 
-use super::{Capabilities, Info, Personality, Recv, Send, Result};
+use super::{Capabilities, HardwareTimestamp, Info, Personality, Recv, Send, Result};
+use super::{Timestamping, TxTimestamp};
 use super::common::{EnqueueFlag, PacketInfo};
 
 /// The [`nic::Handle`] of [`External`].
@@ -17,6 +18,12 @@ pub struct Handle(EnqueueFlag);
 /// This implementation can be used in a number of ways. Firstly, it is good to mock a real
 /// interface in tests, allowing full control over the behaviour between operations. Secondly, it
 /// can be used as a temporary software buffer for virtualization purposes.
+///
+/// The buffers before the split are received and those after it are sent, both in order and up
+/// to the requested maximum in each call. A burst of prepared packets can be replayed by
+/// resetting the receive position with [`reset_receive`] after it was consumed.
+///
+/// [`reset_receive`]: #method.reset_receive
 pub struct External<T> {
     /// Backing buffer, accessible as a slice of packet payloads.
     buffer: T,
@@ -32,6 +39,12 @@ pub struct External<T> {
 
     /// The info struct just copied for each packet.
     info: PacketInfo,
+
+    /// The reported link state.
+    link_up: bool,
+
+    /// The timestamp of the latest sent packet that requested one.
+    tx_timestamp: Option<TxTimestamp>,
 }
 
 impl<T> External<T> {
@@ -62,7 +75,10 @@ impl<T, P> External<T> where T: Deref<Target=[P]> {
             info: PacketInfo {
                 timestamp: Instant::from_millis(0),
                 capabilities: Capabilities::no_support(),
+                hardware_timestamp: None,
             },
+            link_up: true,
+            tx_timestamp: None,
         }
     }
 
@@ -77,7 +93,10 @@ impl<T, P> External<T> where T: Deref<Target=[P]> {
             info: PacketInfo {
                 timestamp: Instant::from_millis(0),
                 capabilities: Capabilities::no_support(),
+                hardware_timestamp: None,
             },
+            link_up: true,
+            tx_timestamp: None,
         }
     }
 
@@ -119,11 +138,49 @@ impl<T, P> External<T> where T: Deref<Target=[P]> {
         self.buffer.get_mut(idx)
     }
 
+    /// Copy the buffer at the specified index into all other buffers.
+    ///
+    /// Prepares a burst of identical packets, for example from a single sent packet before
+    /// receiving all of them. Does nothing if the index is out of bounds.
+    pub fn replicate(&mut self, idx: usize)
+        where T: DerefMut, P: Clone,
+    {
+        if idx >= self.buffer.len() {
+            return;
+        }
+
+        let (head, tail) = self.buffer.split_at_mut(idx);
+        let (source, tail) = tail.split_first_mut().unwrap();
+        head.iter_mut()
+            .chain(tail)
+            .for_each(|buffer| buffer.clone_from(source));
+    }
+
+    /// Change the link state reported by the device, simulating a link failure.
+    pub fn set_link_up(&mut self, link_up: bool) {
+        self.link_up = link_up;
+    }
+
     /// Update the timestamp on all future received packets.
     pub fn set_current_time(&mut self, instant: Instant) {
         self.info.timestamp = instant;
     }
 
+    /// Announce a maximum transmission unit for all future packets, or none with `None`.
+    ///
+    /// The buffers are not checked against it, this only restricts the layers.
+    pub fn set_mtu(&mut self, mtu: Option<usize>) {
+        *self.info.capabilities.mtu_mut() = mtu;
+    }
+
+    /// Emulate hardware timestamping, or disable it with `None`.
+    ///
+    /// All future received packets carry this hardware timestamp and sent packets can request
+    /// it. Only the timestamp of the latest such packet is kept until it is retrieved.
+    pub fn set_hardware_time(&mut self, timestamp: Option<HardwareTimestamp>) {
+        self.info.hardware_timestamp = timestamp;
+    }
+
     /// Returns the index of the next to be received packet.
     fn next_recv(&self) -> usize {
         self.recv
@@ -144,51 +201,82 @@ where
     type Payload = P;
 
     fn personality(&self) -> Personality {
-        Personality::baseline()
+        let mut personality = Personality::baseline();
+        // A whole burst of prepared buffers is handled in a single call.
+        *personality.rx_batch_mut() = self.buffer.len();
+        *personality.tx_batch_mut() = self.buffer.len();
+        *personality.capabilities_mut().mtu_mut() = self.info.capabilities.mtu();
+        if self.info.hardware_timestamp.is_some() {
+            *personality.capabilities_mut().timestamping_mut() = Timestamping::all();
+        }
+        personality
     }
 
     fn tx(&mut self, max: usize, mut sender: impl Send<Self::Handle, Self::Payload>)
         -> Result<usize> 
     {
-        if max == 0 || self.to_send() == 0 {
-            return Ok(0)
-        }
+        let mut count = 0;
+
+        while count < max && self.to_send() > 0 {
+            let next_id = self.next_send();
+            let buffer = &mut self.buffer[next_id];
+
+            let mut flag = EnqueueFlag::set_true(self.info);
+            if self.info.hardware_timestamp.is_some() {
+                flag = flag.allow_tx_timestamp();
+            }
 
-        let next_id = self.next_send();
-        let buffer = &mut self.buffer[next_id];
+            let mut flag = Handle(flag);
+            sender.send(super::Packet {
+                handle: &mut flag,
+                payload: buffer,
+            });
 
-        let mut flag = Handle(EnqueueFlag::set_true(self.info));
-        sender.send(super::Packet {
-            handle: &mut flag,
-            payload: buffer,
-        });
+            if !flag.0.was_sent() {
+                break;
+            }
+
+            if let (Some(id), Some(timestamp)) =
+                (flag.0.tx_timestamp_requested(), self.info.hardware_timestamp)
+            {
+                self.tx_timestamp = Some(TxTimestamp { id, timestamp });
+            }
 
-        if flag.0.was_sent() {
             self.sent += 1;
-            Ok(1)
-        } else {
-            Ok(0)
+            count += 1;
         }
+
+        Ok(count)
     }
 
     fn rx(&mut self, max: usize, mut receptor: impl Recv<Self::Handle, Self::Payload>)
         -> Result<usize>
     {
-        if max == 0 || self.to_recv() == 0 {
-            return Ok(0)
+        let mut count = 0;
+
+        while count < max && self.to_recv() > 0 {
+            let next_id = self.next_recv();
+            let buffer = &mut self.buffer[next_id];
+
+            let mut flag = Handle(EnqueueFlag::not_possible(self.info).allow_retain());
+            receptor.receive(super::Packet {
+                handle: &mut flag,
+                payload: buffer,
+            });
+
+            self.recv += 1;
+            count += 1;
         }
 
-        let next_id = self.next_recv();
-        let buffer = &mut self.buffer[next_id];
+        Ok(count)
+    }
 
-        let mut flag = Handle(EnqueueFlag::not_possible(self.info));
-        receptor.receive(super::Packet {
-            handle: &mut flag,
-            payload: buffer,
-        });
+    fn link_up(&self) -> bool {
+        self.link_up
+    }
 
-        self.recv += 1;
-        Ok(1)
+    fn tx_timestamp(&mut self) -> Option<TxTimestamp> {
+        self.tx_timestamp.take()
     }
 }
 
@@ -200,4 +288,12 @@ impl super::Handle for Handle {
     fn info(&self) -> &dyn Info {
         self.0.info()
     }
+
+    fn retain(&mut self) -> Result<()> {
+        self.0.retain()
+    }
+
+    fn request_tx_timestamp(&mut self, id: u32) -> Result<()> {
+        self.0.request_tx_timestamp(id)
+    }
 }
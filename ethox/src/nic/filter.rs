@@ -0,0 +1,32 @@
+use crate::wire::EthernetAddress;
+
+/// The destination addresses of frames that should be received.
+///
+/// Describes the configuration of an ethernet endpoint such that a device can program its
+/// hardware filters accordingly, see [`Device::set_filter`]. The broadcast address and the group
+/// of all IPv6 nodes are always accepted.
+///
+/// [`Device::set_filter`]: trait.Device.html#method.set_filter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Filter<'a> {
+    /// The unicast address of the endpoint.
+    pub addr: EthernetAddress,
+    /// Additional multicast groups whose frames are accepted.
+    pub multicast: &'a [EthernetAddress],
+    /// Accept frames to all multicast groups.
+    pub all_multicast: bool,
+    /// Accept all frames regardless of their destination.
+    pub promiscuous: bool,
+}
+
+impl Filter<'_> {
+    /// Check if a frame with this destination passes the filter.
+    pub fn accepts(&self, dst_addr: EthernetAddress) -> bool {
+        self.promiscuous
+            || self.addr == dst_addr
+            || dst_addr.is_broadcast()
+            || dst_addr == EthernetAddress::IPV6_ALL_NODES
+            || (dst_addr.is_multicast() && self.all_multicast)
+            || self.multicast.contains(&dst_addr)
+    }
+}
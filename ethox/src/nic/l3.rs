@@ -0,0 +1,102 @@
+//! Emulation of an ethernet link on top of an ip tunnel.
+//!
+//! Tunnel interfaces such as `utun` or wintun adapters exchange bare ip packets while all layers
+//! of ethox expect ethernet frames from a `nic::Device`. The tunnel is presented as a
+//! point-to-point link to a single peer that owns every address: incoming packets get an ethernet
+//! header addressed from that peer, outgoing frames are stripped of their header and arp requests
+//! are answered locally with the address of the peer.
+use crate::wire::{arp_packet, ethernet_frame};
+use crate::wire::{ArpOperation, ArpRepr, EthernetAddress, EthernetProtocol, EthernetRepr};
+
+/// The ethernet side of a tunnel.
+#[derive(Debug)]
+pub(crate) struct EthernetTunnel {
+    hardware_addr: EthernetAddress,
+    peer_addr: EthernetAddress,
+    /// An answer to a previously sent arp request that has not yet been received.
+    pending_arp: Option<ArpRepr>,
+}
+
+/// The length of the emulated ethernet header.
+pub(crate) const HEADER_LEN: usize = 14;
+
+impl EthernetTunnel {
+    /// The default address of the emulated peer, a locally administered unicast address.
+    pub(crate) const PEER_ADDR: EthernetAddress = EthernetAddress([0x02, 0, 0, 0, 0, 0x01]);
+
+    pub(crate) fn new(hardware_addr: EthernetAddress, peer_addr: EthernetAddress) -> Self {
+        EthernetTunnel {
+            hardware_addr,
+            peer_addr,
+            pending_arp: None,
+        }
+    }
+
+    pub(crate) fn peer_addr(&self) -> EthernetAddress {
+        self.peer_addr
+    }
+
+    /// Write the header for an incoming ip packet.
+    ///
+    /// The ip packet must be located right after the first `HEADER_LEN` bytes of the frame.
+    pub(crate) fn frame_ip(&self, frame: &mut [u8], ethertype: EthernetProtocol) {
+        let frame = ethernet_frame::new_unchecked_mut(frame);
+        EthernetRepr {
+            src_addr: self.peer_addr,
+            dst_addr: self.hardware_addr,
+            ethertype,
+        }.emit(frame);
+    }
+
+    /// Write the answer to a previous arp request into the frame, if there is any.
+    ///
+    /// Returns the length of the complete frame. The answer is discarded if the frame is too small.
+    pub(crate) fn answer_arp(&mut self, frame: &mut [u8]) -> Option<usize> {
+        let repr = self.pending_arp.take()?;
+        let len = HEADER_LEN + repr.buffer_len();
+        let frame = frame.get_mut(..len)?;
+
+        self.frame_ip(frame, EthernetProtocol::Arp);
+        repr.emit(arp_packet::new_unchecked_mut(&mut frame[HEADER_LEN..]));
+        Some(len)
+    }
+
+    /// Inspect an outgoing frame.
+    ///
+    /// Returns the protocol of the ip packet following the ethernet header if it should be sent
+    /// through the tunnel. Arp requests are remembered to be answered instead.
+    pub(crate) fn transmit(&mut self, frame: &[u8]) -> Option<EthernetProtocol> {
+        let frame = ethernet_frame::new_checked(frame).ok()?;
+        match frame.ethertype() {
+            ethertype @ EthernetProtocol::Ipv4 | ethertype @ EthernetProtocol::Ipv6 => Some(ethertype),
+            EthernetProtocol::Arp => {
+                self.remember_arp(frame.payload_slice());
+                None
+            },
+            _ => None,
+        }
+    }
+
+    fn remember_arp(&mut self, packet: &[u8]) {
+        let repr = match arp_packet::new_checked(packet).and_then(ArpRepr::parse) {
+            Ok(repr) => repr,
+            Err(_) => return,
+        };
+
+        if let ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr,
+            source_protocol_addr,
+            target_protocol_addr,
+            ..
+        } = repr {
+            self.pending_arp = Some(ArpRepr::EthernetIpv4 {
+                operation: ArpOperation::Reply,
+                source_hardware_addr: self.peer_addr,
+                source_protocol_addr: target_protocol_addr,
+                target_hardware_addr: source_hardware_addr,
+                target_protocol_addr: source_protocol_addr,
+            });
+        }
+    }
+}
@@ -39,15 +39,31 @@ impl<'r, C: PayloadMut> Loopback<'r, C> {
             info: PacketInfo {
                 timestamp: Instant::from_millis(0),
                 capabilities: Capabilities::no_support(),
+                hardware_timestamp: None,
             },
         }
     }
 
+    /// The number of packets that have been sent but not yet received.
+    ///
+    /// Packets queued as an answer while receiving are appended to these. Receiving only this
+    /// many packets thus skips those answers which lets two stacks take turns on one device.
+    pub fn pending(&self) -> usize {
+        self.sent
+    }
+
     /// Update the timestamp on all future received packets.
     pub fn set_current_time(&mut self, instant: Instant) {
         self.info.timestamp = instant;
     }
 
+    /// Announce a maximum transmission unit for all future packets, or none with `None`.
+    ///
+    /// This should leave room for the ethernet header in the buffers.
+    pub fn set_mtu(&mut self, mtu: Option<usize>) {
+        *self.info.capabilities.mtu_mut() = mtu;
+    }
+
     fn next_recv(&mut self) -> Option<(AckRecv, &mut C)> {
         if self.sent == 0 {
             return None
@@ -95,7 +111,12 @@ impl<'a, C: PayloadMut> super::Device for Loopback<'a, C> {
     type Payload = C;
 
     fn personality(&self) -> Personality {
-        Personality::baseline()
+        let mut personality = Personality::baseline();
+        // All buffers can be processed in a single call.
+        *personality.rx_batch_mut() = self.buffer_count();
+        *personality.tx_batch_mut() = self.buffer_count();
+        *personality.capabilities_mut() = self.info.capabilities;
+        personality
     }
 
     fn tx(&mut self, max: usize, mut sender: impl Send<Self::Handle, Self::Payload>)
@@ -137,7 +158,7 @@ impl<'a, C: PayloadMut> super::Device for Loopback<'a, C> {
                 Some(packet) => packet,
             };
 
-            let mut flag = Handle(EnqueueFlag::set_true(info));
+            let mut flag = Handle(EnqueueFlag::set_true(info).allow_retain());
             receptor.receive(super::Packet {
                 handle: &mut flag,
                 payload: packet,
@@ -171,6 +192,10 @@ impl super::Handle for Handle {
     fn info(&self) -> &dyn Info {
         self.0.info()
     }
+
+    fn retain(&mut self) -> Result<()> {
+        self.0.retain()
+    }
 }
 
 impl AckRecv<'_> {
@@ -0,0 +1,599 @@
+//! A device exchanging packets through rings in shared memory.
+//!
+//! The rings and descriptors follow the layout of memif, the shared memory interface of VPP and
+//! libmemif, with a single region holding one ring in each direction and all packet buffers. Two
+//! processes mapping the same region can exchange packets without copying them, as can two
+//! devices of the same process in tests. The control protocol of memif over a unix socket,
+//! through which the region is usually passed, is not implemented. The region has to be shared
+//! by other means, for example with [`SharedMemory`] on unix systems.
+//!
+//! One side of the connection has the role of the [`Client`], called slave in memif, which
+//! initializes the region and owns all buffers. The other side, the [`Server`], attaches to an
+//! initialized region. Packets from the client to the server pass through the first ring, in the
+//! other direction the client provides empty buffers in the second ring which the server fills.
+//!
+//! [`SharedMemory`]: ../sys/struct.SharedMemory.html
+//! [`Client`]: enum.Role.html#variant.Client
+//! [`Server`]: enum.Role.html#variant.Server
+#![allow(unsafe_code)]
+use core::marker::PhantomData;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicU16, AtomicU32, Ordering};
+
+use crate::layer::{Error, Result};
+use crate::time::Instant;
+use crate::wire::{payload, Payload, PayloadError, PayloadMut, Reframe};
+
+use super::common::{EnqueueFlag, PacketInfo};
+use super::{Capabilities, Device, Packet, Personality, Recv, Send};
+
+/// The value marking an initialized ring.
+const COOKIE: u32 = 0x03E3_1F20;
+
+/// The side reading the ring polls for packets instead of waiting for an interrupt.
+const RING_FLAG_MASK_INT: u16 = 1;
+
+/// The packet continues in the buffer of the next descriptor.
+const DESC_FLAG_NEXT: u16 = 1;
+
+/// The offset of the head index in a ring.
+const RING_HEAD: usize = 6;
+/// The offset of the tail index, on a cache line of its own.
+const RING_TAIL: usize = 64;
+/// The offset of the descriptors.
+const RING_DESC: usize = 128;
+const DESC_LEN: usize = 16;
+
+/// The ring from the client to the server.
+const CLIENT_TO_SERVER: usize = 0;
+/// The ring from the server to the client.
+const SERVER_TO_CLIENT: usize = 1;
+
+/// The role of a device in the connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Role {
+    /// Initializes the region and owns the buffers.
+    Client,
+    /// Attaches to a region initialized by a client.
+    Server,
+}
+
+/// The dimensions of the rings and buffers in a region.
+///
+/// Both sides must use the same configuration for a region.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Config {
+    /// The binary logarithm of the number of descriptors in each ring.
+    pub log2_ring_size: u8,
+    /// The size of each packet buffer.
+    pub buffer_size: u32,
+}
+
+/// A device on a memif region.
+///
+/// Each call to `rx` or `tx` processes the packets available in the respective ring and then
+/// publishes the new position of its index with a single atomic store. Packets are handed to the
+/// layers in place, within the buffers of the region. An answer to a received packet is copied to
+/// a free buffer of the sending ring, if there is none the handle refuses to queue it. Packets
+/// spanning multiple buffers are not supported and dropped when received.
+///
+/// There is no clock in the device, set the timestamp of the packets with [`set_current_time`]
+/// before each call.
+///
+/// [`set_current_time`]: #method.set_current_time
+#[derive(Debug)]
+pub struct Memif<'a> {
+    region: NonNull<u8>,
+    len: usize,
+    role: Role,
+    config: Config,
+    /// The position in the sending ring of this side, the head for the client and the tail for
+    /// the server.
+    send: u16,
+    /// The position up to which packets have been received, the tail of the receiving ring.
+    recv: u16,
+    info: PacketInfo,
+    memory: PhantomData<&'a mut [u8]>,
+}
+
+/// A packet buffer in the region.
+///
+/// This is the payload of all packets of a [`Memif`]. It only exists while it is lent to the
+/// layers, it can not be created or cloned outside of the device.
+///
+/// [`Memif`]: struct.Memif.html
+#[derive(Debug)]
+pub struct Slot {
+    ptr: NonNull<u8>,
+    capacity: usize,
+    len: usize,
+}
+
+/// A view of a descriptor of a ring.
+#[derive(Clone, Copy)]
+struct Descriptor {
+    flags: u16,
+    length: u32,
+    offset: u32,
+}
+
+impl Config {
+    /// The number of descriptors in each ring.
+    pub fn ring_size(&self) -> usize {
+        1 << self.log2_ring_size
+    }
+
+    /// The size of the region for this configuration.
+    pub fn region_size(&self) -> usize {
+        self.buffers() + 2*self.ring_size()*self.buffer_size as usize
+    }
+
+    /// The size of a ring with its descriptors.
+    fn ring_len(&self) -> usize {
+        RING_DESC + self.ring_size()*DESC_LEN
+    }
+
+    /// The offset of the first buffer.
+    fn buffers(&self) -> usize {
+        2*self.ring_len()
+    }
+}
+
+impl Default for Config {
+    /// Rings of 256 descriptors with buffers for a standard ethernet frame.
+    fn default() -> Self {
+        Config {
+            log2_ring_size: 8,
+            buffer_size: 2048,
+        }
+    }
+}
+
+impl<'a> Memif<'a> {
+    /// Create a device on a region.
+    ///
+    /// As a client, initializes the rings and descriptors of the region. As a server, fails with
+    /// `Illegal` while the region has not been initialized and can be retried later. A region that
+    /// is too small or misaligned results in `BadSize`.
+    ///
+    /// # Safety
+    ///
+    /// The region must be valid for reads and writes of `len` bytes for the lifetime `'a`. It must
+    /// not be accessed other than by at most one other device of the opposite role, possibly in
+    /// another process.
+    pub unsafe fn new(region: NonNull<u8>, len: usize, role: Role, config: Config)
+        -> Result<Self>
+    {
+        let size = config.region_size();
+        if len < size || region.as_ptr().align_offset(64) != 0 || config.log2_ring_size > 15 {
+            return Err(Error::BadSize);
+        }
+
+        let mut memif = Memif {
+            region,
+            len,
+            role,
+            config,
+            send: 0,
+            recv: 0,
+            info: PacketInfo {
+                timestamp: Instant::ZERO,
+                capabilities: Capabilities::no_support(),
+                hardware_timestamp: None,
+            },
+            memory: PhantomData,
+        };
+
+        match role {
+            Role::Client => memif.initialize(),
+            Role::Server => {
+                let ready = |ring| memif.cookie(ring).load(Ordering::Acquire) == COOKIE;
+                if !ready(CLIENT_TO_SERVER) || !ready(SERVER_TO_CLIENT) {
+                    return Err(Error::Illegal);
+                }
+                // Continue where a previous server stopped.
+                memif.send = memif.tail(SERVER_TO_CLIENT).load(Ordering::Acquire);
+                memif.recv = memif.tail(CLIENT_TO_SERVER).load(Ordering::Acquire);
+            },
+        }
+
+        Ok(memif)
+    }
+
+    /// The role of this side.
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// The configuration of the region.
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    /// Update the timestamp on all future packets.
+    pub fn set_current_time(&mut self, instant: Instant) {
+        self.info.timestamp = instant;
+    }
+
+    /// Get a mutable reference to the capabilities of the packets.
+    ///
+    /// Packets in shared memory are never corrupted in transit, two trusting sides may agree to
+    /// skip the checksums.
+    pub fn capabilities_mut(&mut self) -> &mut Capabilities {
+        &mut self.info.capabilities
+    }
+
+    fn initialize(&mut self) {
+        let size = self.config.ring_size();
+        for ring in [CLIENT_TO_SERVER, SERVER_TO_CLIENT].iter().copied() {
+            self.cookie(ring).store(0, Ordering::Relaxed);
+            unsafe { ptr::write_volatile(self.ring(ring).add(4) as *mut u16, RING_FLAG_MASK_INT) };
+            self.head(ring).store(0, Ordering::Relaxed);
+            self.tail(ring).store(0, Ordering::Relaxed);
+
+            for idx in 0..size {
+                let buffer = ring*size + idx;
+                let offset = self.config.buffers() + buffer*self.config.buffer_size as usize;
+                self.set_descriptor(ring, idx as u16, Descriptor {
+                    flags: 0,
+                    length: self.config.buffer_size,
+                    offset: offset as u32,
+                });
+            }
+        }
+
+        // All buffers from the server are free.
+        self.head(SERVER_TO_CLIENT).store(size as u16, Ordering::Release);
+        self.cookie(CLIENT_TO_SERVER).store(COOKIE, Ordering::Release);
+        self.cookie(SERVER_TO_CLIENT).store(COOKIE, Ordering::Release);
+    }
+
+    /// The ring on which this side sends.
+    fn send_ring(&self) -> usize {
+        match self.role {
+            Role::Client => CLIENT_TO_SERVER,
+            Role::Server => SERVER_TO_CLIENT,
+        }
+    }
+
+    /// The ring on which this side receives.
+    fn recv_ring(&self) -> usize {
+        match self.role {
+            Role::Client => SERVER_TO_CLIENT,
+            Role::Server => CLIENT_TO_SERVER,
+        }
+    }
+
+    /// The number of descriptors this side may fill for sending.
+    fn free(&self) -> u16 {
+        let ring = self.send_ring();
+        match self.role {
+            // The server frees descriptors by advancing the tail.
+            Role::Client => {
+                let tail = self.tail(ring).load(Ordering::Acquire);
+                self.config.ring_size() as u16 - self.send.wrapping_sub(tail)
+            },
+            // The client provides empty descriptors by advancing the head.
+            Role::Server => self.head(ring).load(Ordering::Acquire).wrapping_sub(self.send),
+        }
+    }
+
+    /// The number of filled descriptors this side may receive.
+    fn available(&self) -> u16 {
+        let ring = self.recv_ring();
+        let end = match self.role {
+            Role::Client => self.tail(ring).load(Ordering::Acquire),
+            Role::Server => self.head(ring).load(Ordering::Acquire),
+        };
+        end.wrapping_sub(self.recv)
+    }
+
+    /// Publish the descriptors filled for sending.
+    fn publish_send(&self) {
+        let ring = self.send_ring();
+        match self.role {
+            Role::Client => self.head(ring).store(self.send, Ordering::Release),
+            Role::Server => self.tail(ring).store(self.send, Ordering::Release),
+        }
+    }
+
+    /// Return the received descriptors to the other side.
+    fn publish_recv(&mut self) {
+        let ring = self.recv_ring();
+        match self.role {
+            Role::Client => {
+                // Refill the consumed descriptors with their empty buffers.
+                let size = self.config.ring_size() as u16;
+                self.head(ring).store(self.recv.wrapping_add(size), Ordering::Release);
+            },
+            Role::Server => self.tail(ring).store(self.recv, Ordering::Release),
+        }
+    }
+
+    /// The slot of a descriptor, if it lies within the region.
+    fn slot(&self, ring: usize, idx: u16, len: Option<usize>) -> Option<Slot> {
+        let descriptor = self.descriptor(ring, idx);
+        let offset = descriptor.offset as usize;
+        let capacity = match self.role {
+            Role::Client => self.config.buffer_size as usize,
+            Role::Server => descriptor.length as usize,
+        };
+
+        if offset < self.config.buffers() || offset.checked_add(capacity)? > self.len {
+            return None;
+        }
+
+        Some(Slot {
+            ptr: unsafe { NonNull::new_unchecked(self.region.as_ptr().add(offset)) },
+            capacity,
+            len: len.unwrap_or(capacity).min(capacity),
+        })
+    }
+
+    /// Queue a sent packet of some length.
+    fn queue(&mut self, len: usize) {
+        let ring = self.send_ring();
+        let idx = self.send;
+        let mut descriptor = self.descriptor(ring, idx);
+        descriptor.flags = 0;
+        descriptor.length = len as u32;
+        self.set_descriptor(ring, idx, descriptor);
+        self.send = self.send.wrapping_add(1);
+    }
+
+    /// Copy a received packet to the next free slot for sending.
+    fn answer(&mut self, from: &Slot) {
+        if let Some(mut to) = self.slot(self.send_ring(), self.send, None) {
+            if to.resize(from.len).is_ok() {
+                to.payload_mut().as_mut_slice().copy_from_slice(from.payload().as_slice());
+                self.queue(from.len);
+            }
+        }
+    }
+
+    fn ring(&self, ring: usize) -> *mut u8 {
+        unsafe { self.region.as_ptr().add(ring*self.config.ring_len()) }
+    }
+
+    fn cookie(&self, ring: usize) -> &AtomicU32 {
+        unsafe { &*(self.ring(ring) as *const AtomicU32) }
+    }
+
+    fn head(&self, ring: usize) -> &AtomicU16 {
+        unsafe { &*(self.ring(ring).add(RING_HEAD) as *const AtomicU16) }
+    }
+
+    fn tail(&self, ring: usize) -> &AtomicU16 {
+        unsafe { &*(self.ring(ring).add(RING_TAIL) as *const AtomicU16) }
+    }
+
+    fn descriptor_ptr(&self, ring: usize, idx: u16) -> *mut u8 {
+        let slot = usize::from(idx) & (self.config.ring_size() - 1);
+        unsafe { self.ring(ring).add(RING_DESC + slot*DESC_LEN) }
+    }
+
+    fn descriptor(&self, ring: usize, idx: u16) -> Descriptor {
+        let ptr = self.descriptor_ptr(ring, idx);
+        unsafe {
+            Descriptor {
+                flags: ptr::read_volatile(ptr as *const u16),
+                length: ptr::read_volatile(ptr.add(4) as *const u32),
+                offset: ptr::read_volatile(ptr.add(8) as *const u32),
+            }
+        }
+    }
+
+    fn set_descriptor(&self, ring: usize, idx: u16, descriptor: Descriptor) {
+        let ptr = self.descriptor_ptr(ring, idx);
+        unsafe {
+            ptr::write_volatile(ptr as *mut u16, descriptor.flags);
+            // All buffers are in the only region.
+            ptr::write_volatile(ptr.add(2) as *mut u16, 0);
+            ptr::write_volatile(ptr.add(4) as *mut u32, descriptor.length);
+            ptr::write_volatile(ptr.add(8) as *mut u32, descriptor.offset);
+        }
+    }
+}
+
+impl Device for Memif<'_> {
+    type Handle = EnqueueFlag;
+    type Payload = Slot;
+
+    fn personality(&self) -> Personality {
+        let mut personality = Personality::baseline();
+        *personality.capabilities_mut() = self.info.capabilities;
+        *personality.rx_batch_mut() = self.config.ring_size();
+        *personality.tx_batch_mut() = self.config.ring_size();
+        personality
+    }
+
+    fn tx(&mut self, max: usize, mut sender: impl Send<Self::Handle, Self::Payload>)
+        -> Result<usize>
+    {
+        let ring = self.send_ring();
+        let free = usize::from(self.free());
+        let mut count = 0;
+
+        while count < max.min(free) {
+            let mut slot = match self.slot(ring, self.send, None) {
+                Some(slot) => slot,
+                None => return Err(Error::Illegal),
+            };
+
+            let mut handle = EnqueueFlag::set_true(self.info);
+            sender.send(Packet {
+                handle: &mut handle,
+                payload: &mut slot,
+            });
+
+            if !handle.was_sent() {
+                break;
+            }
+
+            self.queue(slot.len);
+            count += 1;
+        }
+
+        self.publish_send();
+        Ok(count)
+    }
+
+    fn rx(&mut self, max: usize, mut receptor: impl Recv<Self::Handle, Self::Payload>)
+        -> Result<usize>
+    {
+        let ring = self.recv_ring();
+        let available = usize::from(self.available());
+        let mut count = 0;
+
+        while count < max.min(available) {
+            let descriptor = self.descriptor(ring, self.recv);
+            let slot = if descriptor.flags & DESC_FLAG_NEXT == 0 {
+                self.slot(ring, self.recv, Some(descriptor.length as usize))
+            } else {
+                None
+            };
+            self.recv = self.recv.wrapping_add(1);
+            count += 1;
+
+            let mut slot = match slot {
+                Some(slot) => slot,
+                None => continue,
+            };
+
+            let mut handle = if self.free() > 0 {
+                EnqueueFlag::set_true(self.info)
+            } else {
+                EnqueueFlag::not_possible(self.info)
+            };
+
+            receptor.receive(Packet {
+                handle: &mut handle,
+                payload: &mut slot,
+            });
+
+            if handle.was_sent() {
+                self.answer(&slot);
+            }
+        }
+
+        self.publish_recv();
+        self.publish_send();
+        Ok(count)
+    }
+}
+
+impl Payload for Slot {
+    fn payload(&self) -> &payload {
+        unsafe { core::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }.into()
+    }
+}
+
+impl PayloadMut for Slot {
+    fn payload_mut(&mut self) -> &mut payload {
+        unsafe { core::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }.into()
+    }
+
+    fn resize(&mut self, length: usize) -> core::result::Result<(), PayloadError> {
+        if length <= self.capacity {
+            self.len = length;
+            Ok(())
+        } else {
+            Err(PayloadError::BadSize)
+        }
+    }
+
+    fn reframe(&mut self, reframe: Reframe) -> core::result::Result<(), PayloadError> {
+        self.resize(reframe.length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::boxed::Box;
+
+    use super::*;
+    use crate::layer::FnHandler;
+    use crate::nic::Handle as _;
+    use crate::nic::tests::LengthIo;
+
+    #[repr(align(64))]
+    struct Region([u8; 1 << 14]);
+
+    const CONFIG: Config = Config {
+        log2_ring_size: 2,
+        buffer_size: 1536,
+    };
+
+    fn pair(region: &mut Region) -> (Memif<'_>, Memif<'_>) {
+        let ptr = NonNull::from(&mut region.0).cast();
+        let len = region.0.len();
+        assert!(CONFIG.region_size() <= len);
+        unsafe {
+            assert_eq!(Memif::new(ptr, len, Role::Server, CONFIG).err(), Some(Error::Illegal));
+            let client = Memif::new(ptr, len, Role::Client, CONFIG).unwrap();
+            let server = Memif::new(ptr, len, Role::Server, CONFIG).unwrap();
+            (client, server)
+        }
+    }
+
+    #[test]
+    fn exchange() {
+        let mut region = Box::new(Region([0; 1 << 14]));
+        let (mut client, mut server) = pair(&mut region);
+
+        // The ring to the server fills up after four packets.
+        assert_eq!(client.tx(8, LengthIo), Ok(4));
+        assert_eq!(client.tx(1, LengthIo), Ok(0));
+        assert_eq!(server.rx(8, LengthIo), Ok(4));
+        assert_eq!(client.tx(8, LengthIo), Ok(4));
+        assert_eq!(server.rx(2, LengthIo), Ok(2));
+        assert_eq!(server.rx(8, LengthIo), Ok(2));
+
+        // The client provided all its buffers for packets of the server.
+        assert_eq!(server.tx(8, LengthIo), Ok(4));
+        assert_eq!(client.rx(8, LengthIo), Ok(4));
+        assert_eq!(server.tx(8, LengthIo), Ok(4));
+        assert_eq!(client.rx(8, LengthIo), Ok(4));
+    }
+
+    #[test]
+    fn answer() {
+        let mut region = Box::new(Region([0; 1 << 14]));
+        let (mut client, mut server) = pair(&mut region);
+
+        let sent = client.tx(1, FnHandler(|packet: Packet<EnqueueFlag, Slot>| {
+            packet.payload.resize(4).unwrap();
+            packet.payload.payload_mut().as_mut_slice().copy_from_slice(b"ping");
+            packet.handle.queue().unwrap();
+        }));
+        assert_eq!(sent, Ok(1));
+
+        let received = server.rx(1, FnHandler(|packet: Packet<EnqueueFlag, Slot>| {
+            assert_eq!(packet.payload.payload().as_slice(), b"ping");
+            packet.payload.payload_mut().as_mut_slice().copy_from_slice(b"pong");
+            packet.handle.queue().unwrap();
+        }));
+        assert_eq!(received, Ok(1));
+
+        let received = client.rx(1, FnHandler(|packet: Packet<EnqueueFlag, Slot>| {
+            assert_eq!(packet.payload.payload().as_slice(), b"pong");
+        }));
+        assert_eq!(received, Ok(1));
+    }
+
+    #[test]
+    fn bad_offset() {
+        let mut region = Box::new(Region([0; 1 << 14]));
+        let (mut client, mut server) = pair(&mut region);
+        assert_eq!(client.tx(1, LengthIo), Ok(1));
+
+        // A descriptor pointing outside the region is skipped.
+        let mut descriptor = client.descriptor(CLIENT_TO_SERVER, 0);
+        descriptor.offset = 1 << 14;
+        client.set_descriptor(CLIENT_TO_SERVER, 0, descriptor);
+        let received = server.rx(1, FnHandler(|_: Packet<EnqueueFlag, Slot>| {
+            panic!("Received a packet outside the region");
+        }));
+        assert_eq!(received, Ok(1));
+    }
+}
@@ -1,14 +1,29 @@
 //! Encapsulates a network interface card.
 //!
 //! Also permits software emulation or implementation of one as well, of course.
+pub mod autoconfig;
+pub mod bond;
+pub mod classify;
 pub mod common;
 pub mod loopback;
+pub mod memif;
 pub mod external;
+mod filter;
 mod personality;
+#[cfg(feature = "smoltcp")]
+pub mod phy;
+#[cfg(feature = "alloc")]
+pub mod virtio;
+pub mod rss;
+mod timestamp;
 
-#[cfg(feature = "sys")]
+#[cfg(all(feature = "sys", unix))]
 #[path="sys/mod.rs"]
 mod sys_internal;
+#[cfg(all(feature = "sys", feature = "std", windows))]
+pub mod wintun;
+#[cfg(all(feature = "sys", any(target_os = "macos", windows)))]
+mod l3;
 
 use crate::wire::Payload;
 use crate::layer::{Result, FnHandler};
@@ -16,15 +31,18 @@ use crate::layer::{Result, FnHandler};
 use crate::wire::{ethernet_frame, pretty_print::{Formatter, PrettyPrinter}};
 use crate::time::Instant;
 
+pub use self::filter::Filter;
 pub use self::personality::{
     Capabilities,
     Personality,
-    Protocol};
+    Protocol,
+    Timestamping};
+pub use self::timestamp::{HardwareTimestamp, TxTimestamp};
 
-#[cfg(feature = "sys")]
+#[cfg(all(feature = "sys", unix))]
 pub use self::sys_internal::exports as sys;
 
-pub use crate::layer::loss::{Lossy, PrngLoss};
+pub use crate::layer::loss::{GilbertElliott, Lossy, PrngLoss};
 
 /// A reference to memory holding packet data and a handle.
 ///
@@ -60,6 +78,28 @@ pub trait Handle {
     /// Note that technically the information may change after a call to `queue` or in the future
     /// after changing the target interface of an outgoing packet. That is intentional.
     fn info(&self) -> &dyn Info;
+
+    /// Permit exchanging the payload buffer for a spare one.
+    ///
+    /// When this succeeds, the caller may swap the payload with another valid instance of the
+    /// payload type and keep the original buffer, for example to process a received packet after
+    /// the callback returned without copying it. The device then continues to use the spare. The
+    /// default implementation refuses, devices must explicitly opt in to this contract.
+    fn retain(&mut self) -> Result<()> {
+        Err(crate::layer::Error::Illegal)
+    }
+
+    /// Request the hardware timestamp of this packet when it is sent.
+    ///
+    /// The timestamp is reported afterwards by [`Device::tx_timestamp`], with the identifier
+    /// passed here. Devices without transmit timestamping refuse with `Illegal`, which is also
+    /// the default implementation. See [`Timestamping`] to find out in advance.
+    ///
+    /// [`Device::tx_timestamp`]: trait.Device.html#method.tx_timestamp
+    /// [`Timestamping`]: struct.Timestamping.html
+    fn request_tx_timestamp(&mut self, _id: u32) -> Result<()> {
+        Err(crate::layer::Error::Illegal)
+    }
     // TODO: multiple interfaces (=zerocopy forwarding).
 }
 
@@ -79,6 +119,14 @@ pub trait Info {
     /// Indicates pre-checked checksums for incoming packets and hardware support for checksums of
     /// outgoing packets across the layers of the network stack.
     fn capabilities(&self) -> Capabilities;
+
+    /// The time at which a received packet arrived, by the clock of the hardware.
+    ///
+    /// Only available on devices with receive timestamping, the default implementation returns
+    /// `None`.
+    fn hardware_timestamp(&self) -> Option<HardwareTimestamp> {
+        None
+    }
 }
 
 /// A layer 2 device.
@@ -111,8 +159,79 @@ pub trait Device {
     /// Receive packet utilizing the `receptor`.
     ///
     /// Dequeue up to `max` received packets and provide them to the receiver callback.
+    ///
+    /// A device whose handles permit [`Handle::retain`] must not rely on the identity of its
+    /// payload buffers. After the callback, the buffer may have been exchanged for any other
+    /// instance of the payload type which the device must then use in its place.
+    ///
+    /// [`Handle::retain`]: trait.Handle.html#method.retain
     fn rx(&mut self, max: usize, receiver: impl Recv<Self::Handle, Self::Payload>)
         -> Result<usize>;
+
+    /// Program the hardware filter for the destination of received frames.
+    ///
+    /// The device may afterwards receive more frames than the filter accepts, the ethernet
+    /// endpoint checks destinations in software regardless. Get the filter matching its
+    /// configuration from [`eth::Endpoint::filter`]. The default implementation refuses with
+    /// `Illegal`, for devices without any hardware filter.
+    ///
+    /// [`eth::Endpoint::filter`]: ../layer/eth/struct.Endpoint.html#method.filter
+    fn set_filter(&mut self, _filter: Filter) -> Result<()> {
+        Err(crate::layer::Error::Illegal)
+    }
+
+    /// Query whether the device currently has a link to the network.
+    ///
+    /// A [`bond`] fails over to other devices while this is `false`. The default implementation
+    /// always reports a link, for devices that can not tell.
+    ///
+    /// [`bond`]: bond/index.html
+    fn link_up(&self) -> bool {
+        true
+    }
+
+    /// Retrieve the hardware timestamp of a sent packet.
+    ///
+    /// Returns timestamps of packets for which one was requested with
+    /// [`Handle::request_tx_timestamp`], in the order the device took them. As the timestamp is
+    /// only known after the packet left the device it may not yet be available directly after
+    /// the call to `tx`. The default implementation never returns a timestamp.
+    ///
+    /// [`Handle::request_tx_timestamp`]: trait.Handle.html#method.request_tx_timestamp
+    fn tx_timestamp(&mut self) -> Option<TxTimestamp> {
+        None
+    }
+}
+
+/// A device with multiple independent receive and transmit queues.
+///
+/// Each queue is a separate [`Device`]. Mutably borrowing all of them at once makes it possible to
+/// split the queues across threads, each polling its queue with its own set of endpoints, without
+/// sharing or aliasing the state of a single endpoint. The [`rss`] module can be used to find out
+/// which queue, and thus which set of endpoints, will receive the packets of a particular flow.
+///
+/// A slice of devices is trivially a multi-queue device as well. This can be used to group
+/// software devices or separately opened per-queue file descriptors.
+///
+/// [`Device`]: trait.Device.html
+/// [`rss`]: rss/index.html
+pub trait MultiQueue {
+    /// The device type of a single queue.
+    type Queue: Device;
+
+    /// The number of queues of this device.
+    fn queue_count(&self) -> usize;
+
+    /// Borrow all queues at the same time.
+    ///
+    /// The index of each queue in the slice is the same as the one used by the device for
+    /// distributing received packets.
+    fn queues(&mut self) -> &mut [Self::Queue];
+
+    /// Borrow a single queue.
+    fn queue(&mut self, idx: usize) -> Option<&mut Self::Queue> {
+        self.queues().get_mut(idx)
+    }
 }
 
 /// A raw network packet receiver.
@@ -152,6 +271,18 @@ pub trait Send<H: Handle + ?Sized, P: Payload + ?Sized> {
     }
 }
 
+impl<D: Device> MultiQueue for [D] {
+    type Queue = D;
+
+    fn queue_count(&self) -> usize {
+        self.len()
+    }
+
+    fn queues(&mut self) -> &mut [D] {
+        self
+    }
+}
+
 impl<F, H: Handle + ?Sized, P: Payload + ?Sized> Recv<H, P> for FnHandler<F>
     where F: FnMut(Packet<H, P>)
 {
@@ -196,13 +327,14 @@ impl<F, H: Handle + ?Sized, P: Payload + ?Sized> Send<H, P> for &'_ mut F
     }
 }
 
-/// Available only on `std` because it prints to standard out.
+/// Available only on `std` because it prints to standard error, or to the logger with the `log`
+/// feature.
 #[cfg(feature = "std")]
 impl<H: Handle + ?Sized, P: Payload + ?Sized> Recv<H, P> for Formatter<ethernet_frame> {
     fn receive(&mut self, frame: Packet<H, P>) {
         let printer = PrettyPrinter::<ethernet_frame>
             ::new("", frame.payload.payload().as_slice());
-        eprintln!("{}", printer);
+        net_print!("{}", printer);
     }
 }
 
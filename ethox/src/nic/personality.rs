@@ -9,6 +9,8 @@ use crate::wire::{Checksum, IpRepr, UdpChecksum, TcpChecksum};
 #[derive(Clone, Debug)]
 pub struct Personality {
     capabilities: Capabilities,
+    rx_batch: usize,
+    tx_batch: usize,
 }
 
 /// Operations supported natively by the card.
@@ -22,6 +24,8 @@ pub struct Capabilities {
     icmpv4: Protocol,
     udp: Udp,
     tcp: Tcp,
+    timestamping: Timestamping,
+    mtu: Option<usize>,
 }
 
 /// The extent of support for a specific protocol.
@@ -58,6 +62,20 @@ pub struct Tcp {
     inner: Protocol,
 }
 
+/// Support for hardware timestamps of packets.
+///
+/// Precision time protocols such as PTP require the time at which a frame passed the physical
+/// layer, without the jitter of queueing and interrupt handling in software. See
+/// [`Info::hardware_timestamp`] and [`Handle::request_tx_timestamp`].
+///
+/// [`Info::hardware_timestamp`]: trait.Info.html#method.hardware_timestamp
+/// [`Handle::request_tx_timestamp`]: trait.Handle.html#method.request_tx_timestamp
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timestamping {
+    rx: bool,
+    tx: bool,
+}
+
 impl Personality {
     /// A personality with no extras.
     ///
@@ -67,6 +85,8 @@ impl Personality {
     pub fn baseline() -> Self {
         Personality {
             capabilities: Capabilities::no_support(),
+            rx_batch: 1,
+            tx_batch: 1,
         }
     }
 
@@ -79,6 +99,31 @@ impl Personality {
     pub fn capabilities_mut(&mut self) -> &mut Capabilities {
         &mut self.capabilities
     }
+
+    /// The number of packets the device can efficiently receive in a single call to `rx`.
+    ///
+    /// This is only a hint. It is usually the number of buffers that can be dequeued at once
+    /// without additional system calls or descriptor ring updates. The baseline is `1`.
+    pub fn rx_batch(&self) -> usize {
+        self.rx_batch
+    }
+
+    /// Mutably get the receive batch size hint.
+    pub fn rx_batch_mut(&mut self) -> &mut usize {
+        &mut self.rx_batch
+    }
+
+    /// The number of packets the device can efficiently send in a single call to `tx`.
+    ///
+    /// This is only a hint, see `rx_batch`. The baseline is `1`.
+    pub fn tx_batch(&self) -> usize {
+        self.tx_batch
+    }
+
+    /// Mutably get the transmit batch size hint.
+    pub fn tx_batch_mut(&mut self) -> &mut usize {
+        &mut self.tx_batch
+    }
 }
 
 impl Capabilities {
@@ -91,6 +136,8 @@ impl Capabilities {
             icmpv4: Protocol::no_support(),
             udp: Udp::no_support(),
             tcp: Tcp::no_support(),
+            timestamping: Timestamping::no_support(),
+            mtu: None,
         }
     }
 
@@ -99,6 +146,11 @@ impl Capabilities {
         &self.icmpv4
     }
 
+    /// Mutably get ICMPv4 support descriptor.
+    pub fn icmpv4_mut(&mut self) -> &mut Protocol {
+        &mut self.icmpv4
+    }
+
     /// Check IPv4 support descriptor.
     pub fn ipv4(&self) -> &Protocol {
         &self.ipv4
@@ -128,6 +180,31 @@ impl Capabilities {
     pub fn tcp_mut(&mut self) -> &mut Tcp {
         &mut self.tcp
     }
+
+    /// Check hardware timestamping support.
+    pub fn timestamping(&self) -> &Timestamping {
+        &self.timestamping
+    }
+
+    /// Mutably get hardware timestamping support.
+    pub fn timestamping_mut(&mut self) -> &mut Timestamping {
+        &mut self.timestamping
+    }
+
+    /// The maximum transmission unit of the link.
+    ///
+    /// This is the largest network layer packet that can be sent, not counting the ethernet
+    /// header. The ip layer refuses to prepare larger packets and tcp announces and uses a maximum
+    /// segment size that fits. `None`, the baseline, when the link does not impose a limit other
+    /// than the size of the packet buffers.
+    pub fn mtu(&self) -> Option<usize> {
+        self.mtu
+    }
+
+    /// Mutably get the maximum transmission unit of the link.
+    pub fn mtu_mut(&mut self) -> &mut Option<usize> {
+        &mut self.mtu
+    }
 }
 
 impl Protocol {
@@ -174,6 +251,44 @@ impl Protocol {
     }
 }
 
+impl Timestamping {
+    /// Create a descriptor without any hardware timestamps.
+    pub fn no_support() -> Self {
+        Timestamping {
+            rx: false,
+            tx: false,
+        }
+    }
+
+    /// Create a descriptor for timestamps of all received and sent packets.
+    pub fn all() -> Self {
+        Timestamping {
+            rx: true,
+            tx: true,
+        }
+    }
+
+    /// Check if received packets carry a hardware timestamp.
+    pub fn rx(&self) -> bool {
+        self.rx
+    }
+
+    /// Mutably get the receive timestamp support.
+    pub fn rx_mut(&mut self) -> &mut bool {
+        &mut self.rx
+    }
+
+    /// Check if timestamps of sent packets can be requested.
+    pub fn tx(&self) -> bool {
+        self.tx
+    }
+
+    /// Mutably get the transmit timestamp support.
+    pub fn tx_mut(&mut self) -> &mut bool {
+        &mut self.tx
+    }
+}
+
 impl Udp {
     /// Create a UDP descriptor with no supported features.
     pub fn no_support() -> Self {
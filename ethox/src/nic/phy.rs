@@ -0,0 +1,313 @@
+//! Adapter for the device drivers of smoltcp.
+//!
+//! Available with the `smoltcp` feature. Many drivers for the ethernet peripherals of
+//! microcontrollers, including those built on top of `embedded-hal`, implement the
+//! `smoltcp::phy::Device` trait. Wrapping such a driver in a [`Phy`] makes it usable as an ethox
+//! [`Device`] without glue code for each driver.
+//!
+//! The driver hands out tokens through which a single frame is received into or sent from memory
+//! it controls, and only for the duration of a closure. The adapter copies the frames from and to
+//! an owned buffer, in which the layers process them as usual.
+//!
+//! [`Device`]: ../trait.Device.html
+//! [`Phy`]: struct.Phy.html
+use smoltcp::phy::{self, RxToken as _, TxToken as _};
+
+use crate::layer::{Error, Result};
+use crate::managed::Partial;
+use crate::time::Instant;
+use crate::wire::{Checksum, Payload, PayloadMut};
+
+use super::common::{EnqueueFlag, PacketInfo};
+use super::{Capabilities, Device, Packet, Personality, Protocol, Recv, Send};
+
+/// A smoltcp device with buffer, usable as a network device.
+///
+/// The buffer must be large enough for the largest frame of the driver, its maximum transmission
+/// unit. Received frames that do not fit fail the receive call with `BadSize`, as do frames to be
+/// sent that exceed the maximum transmission unit.
+///
+/// The driver must be of the ethernet medium. The checksum capabilities of the driver are
+/// translated to the capabilities of the packets, and its maximum burst size to the batch size of
+/// the personality. Smoltcp drivers are not aware of the time on their own, set the timestamp of
+/// the packets with [`set_current_time`] before each call.
+///
+/// [`set_current_time`]: #method.set_current_time
+#[derive(Debug)]
+pub struct Phy<D, C> {
+    device: D,
+    buffer: Partial<C>,
+    timestamp: Instant,
+}
+
+impl<D: phy::Device, C: PayloadMut> Phy<D, C> {
+    /// Wrap a driver with a buffer for the frames.
+    pub fn new(device: D, buffer: C) -> Self {
+        Phy {
+            device,
+            buffer: Partial::new(buffer),
+            timestamp: Instant::ZERO,
+        }
+    }
+
+    /// Update the timestamp passed to the driver and on all future packets.
+    pub fn set_current_time(&mut self, instant: Instant) {
+        self.timestamp = instant;
+    }
+
+    /// Get a reference to the driver.
+    pub fn get_ref(&self) -> &D {
+        &self.device
+    }
+
+    /// Get a mutable reference to the driver.
+    pub fn get_mut(&mut self) -> &mut D {
+        &mut self.device
+    }
+
+    /// Unwrap the driver and the buffer.
+    pub fn into_inner(self) -> (D, C) {
+        (self.device, self.buffer.into_inner())
+    }
+
+    /// The timestamp in the representation of smoltcp.
+    fn now(&self) -> smoltcp::time::Instant {
+        smoltcp::time::Instant::from_millis(self.timestamp.total_millis())
+    }
+
+    fn info(&self) -> PacketInfo {
+        PacketInfo {
+            timestamp: self.timestamp,
+            capabilities: capabilities(&self.device.capabilities()),
+            hardware_timestamp: None,
+        }
+    }
+
+    /// Resize the partial buffer to the length of a full frame.
+    fn recycle(&mut self) -> usize {
+        let capacity = self.buffer
+            .inner()
+            .payload()
+            .as_slice()
+            .len();
+        let length = capacity.min(self.device.capabilities().max_transmission_unit);
+        self.buffer.set_len_unchecked(length);
+        length
+    }
+}
+
+impl<D: phy::Device, C: PayloadMut> Device for Phy<D, C> {
+    type Handle = EnqueueFlag;
+    type Payload = Partial<C>;
+
+    /// A description of the device.
+    ///
+    /// Processes as many packets per call as the driver permits in a burst.
+    fn personality(&self) -> Personality {
+        let caps = self.device.capabilities();
+        let mut personality = Personality::baseline();
+        *personality.capabilities_mut() = capabilities(&caps);
+        if let Some(burst) = caps.max_burst_size {
+            *personality.rx_batch_mut() = burst.max(1);
+            *personality.tx_batch_mut() = burst.max(1);
+        }
+        personality
+    }
+
+    fn tx(&mut self, max: usize, mut sender: impl Send<Self::Handle, Self::Payload>)
+        -> Result<usize>
+    {
+        let mut count = 0;
+        let info = self.info();
+        let now = self.now();
+
+        while count < max {
+            let mtu = self.recycle();
+            let token = match self.device.transmit(now) {
+                Some(token) => token,
+                None => break,
+            };
+
+            let mut handle = EnqueueFlag::set_true(info);
+            sender.send(Packet {
+                handle: &mut handle,
+                payload: &mut self.buffer,
+            });
+
+            if !handle.was_sent() {
+                break;
+            }
+
+            let frame = self.buffer.payload().as_slice();
+            if frame.len() > mtu {
+                return Err(Error::BadSize);
+            }
+
+            token.consume(frame.len(), |buffer| buffer.copy_from_slice(frame));
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    fn rx(&mut self, max: usize, mut receptor: impl Recv<Self::Handle, Self::Payload>)
+        -> Result<usize>
+    {
+        let mut count = 0;
+        let info = self.info();
+        let now = self.now();
+
+        while count < max {
+            let mtu = self.recycle();
+            let (rx, tx) = match self.device.receive(now) {
+                Some(tokens) => tokens,
+                None => break,
+            };
+
+            let buffer = &mut self.buffer;
+            let len = rx.consume(|frame| {
+                let target = buffer.payload_mut().as_mut_slice().get_mut(..frame.len())?;
+                target.copy_from_slice(frame);
+                Some(frame.len())
+            });
+
+            let len = len.ok_or(Error::BadSize)?;
+            self.buffer.set_len_unchecked(len);
+
+            let mut handle = EnqueueFlag::set_true(info).allow_retain();
+            receptor.receive(Packet {
+                handle: &mut handle,
+                payload: &mut self.buffer,
+            });
+
+            if handle.was_sent() {
+                let frame = self.buffer.payload().as_slice();
+                if frame.len() > mtu {
+                    return Err(Error::BadSize);
+                }
+
+                tx.consume(frame.len(), |buffer| buffer.copy_from_slice(frame));
+            }
+
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
+
+/// Translate the checksums and transmission unit of the driver to the packet capabilities.
+///
+/// Smoltcp names the checksums the stack must compute, ethox those that the card handles. The
+/// transmission unit of an ethernet driver includes the ethernet header.
+fn capabilities(caps: &phy::DeviceCapabilities) -> Capabilities {
+    let checksum = &caps.checksum;
+    let mut capabilities = Capabilities::no_support();
+    *capabilities.mtu_mut() = Some(caps.max_transmission_unit.saturating_sub(14));
+    *capabilities.ipv4_mut() = protocol(&checksum.ipv4);
+    *capabilities.icmpv4_mut() = protocol(&checksum.icmpv4);
+    *capabilities.udp_mut() = protocol(&checksum.udp).into();
+    *capabilities.tcp_mut() = protocol(&checksum.tcp).into();
+    capabilities
+}
+
+fn protocol(checksum: &phy::Checksum) -> Protocol {
+    let by_nic = |computed: bool| if computed { Checksum::Manual } else { Checksum::Ignored };
+    let mut protocol = Protocol::no_support();
+    *protocol.rx_checksum_mut() = by_nic(checksum.rx());
+    *protocol.tx_checksum_mut() = by_nic(checksum.tx());
+    protocol
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::nic::tests::LengthIo;
+
+    /// A smoltcp driver looping sent frames back.
+    #[derive(Default)]
+    struct Queue {
+        frames: VecDeque<Vec<u8>>,
+        checksum: phy::ChecksumCapabilities,
+    }
+
+    struct QueueRx(Vec<u8>);
+
+    struct QueueTx<'a>(&'a mut VecDeque<Vec<u8>>);
+
+    impl phy::Device for Queue {
+        type RxToken<'a> = QueueRx;
+        type TxToken<'a> = QueueTx<'a>;
+
+        fn receive(&mut self, _: smoltcp::time::Instant)
+            -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)>
+        {
+            let frame = self.frames.pop_front()?;
+            Some((QueueRx(frame), QueueTx(&mut self.frames)))
+        }
+
+        fn transmit(&mut self, _: smoltcp::time::Instant) -> Option<Self::TxToken<'_>> {
+            Some(QueueTx(&mut self.frames))
+        }
+
+        fn capabilities(&self) -> phy::DeviceCapabilities {
+            let mut caps = phy::DeviceCapabilities::default();
+            caps.max_transmission_unit = 1514;
+            caps.max_burst_size = Some(4);
+            caps.checksum = self.checksum.clone();
+            caps
+        }
+    }
+
+    impl phy::RxToken for QueueRx {
+        fn consume<R, F: FnOnce(&[u8]) -> R>(self, f: F) -> R {
+            f(&self.0)
+        }
+    }
+
+    impl phy::TxToken for QueueTx<'_> {
+        fn consume<R, F: FnOnce(&mut [u8]) -> R>(self, len: usize, f: F) -> R {
+            let mut frame = vec![0; len];
+            let result = f(&mut frame);
+            self.0.push_back(frame);
+            result
+        }
+    }
+
+    #[test]
+    fn loopback() {
+        let mut phy = Phy::new(Queue::default(), vec![0; 2048]);
+        assert_eq!(phy.personality().tx_batch(), 4);
+        assert_eq!(phy.personality().capabilities().mtu(), Some(1500));
+
+        assert_eq!(phy.tx(2, LengthIo), Ok(2));
+        assert_eq!(phy.get_ref().frames.len(), 2);
+        assert!(phy.get_ref().frames.iter().all(|frame| frame.len() == 1514));
+
+        assert_eq!(phy.rx(4, LengthIo), Ok(2));
+        assert!(phy.get_ref().frames.is_empty());
+    }
+
+    #[test]
+    fn oversized() {
+        let mut phy = Phy::new(Queue::default(), vec![0; 64]);
+        phy.get_mut().frames.push_back(vec![0; 128]);
+        assert_eq!(phy.rx(1, LengthIo), Err(Error::BadSize));
+    }
+
+    #[test]
+    fn checksums() {
+        let mut queue = Queue::default();
+        queue.checksum.ipv4 = phy::Checksum::Tx;
+        let phy = Phy::new(queue, vec![0; 64]);
+
+        let personality = phy.personality();
+        let ipv4 = personality.capabilities().ipv4();
+        assert_eq!(ipv4.rx_checksum(), Checksum::Ignored);
+        assert_eq!(ipv4.tx_checksum(), Checksum::Manual);
+        assert_eq!(personality.capabilities().icmpv4().rx_checksum(), Checksum::Manual);
+    }
+}
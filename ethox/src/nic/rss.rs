@@ -0,0 +1,215 @@
+//! Helpers for sharding endpoints across the queues of a multi-queue device.
+//!
+//! Devices with receive side scaling (RSS) hash some header fields of each incoming packet and
+//! use the result to select one of their receive queues. When every queue is polled by its own
+//! thread with its own set of endpoints, all packets of one connection must end up at the queue
+//! that owns the connection state. The types in this module replicate the calculation of the
+//! device so that the owner of a connection can be determined in software, for example when
+//! choosing which shard should open an outgoing tcp connection or to which shard a udp flow
+//! belongs.
+//!
+//! The hash is the Toeplitz hash as specified by Microsoft for RSS which is implemented by
+//! virtually all hardware supporting the feature. The hash result is then mapped to a queue with
+//! an indirection table, see [`Redirection`].
+//!
+//! [`Redirection`]: struct.Redirection.html
+use crate::managed::Slice;
+use crate::wire::IpAddress;
+
+/// The Toeplitz hash function with a secret key.
+///
+/// The key must be the same as the one configured in the device. Most drivers use the default key
+/// from the Microsoft specification unless configured otherwise, this is available as
+/// [`Toeplitz::default`]. Note that hashing with that key is not symmetric, the two directions of
+/// a connection are usually delivered on different queues. Use a [`Toeplitz::symmetric`] key on
+/// the device and here if both directions should be processed by the same shard.
+///
+/// [`Toeplitz::default`]: #method.default
+/// [`Toeplitz::symmetric`]: #method.symmetric
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Toeplitz {
+    key: [u8; 40],
+}
+
+/// An indirection table mapping hash values to queue indices.
+///
+/// The table is indexed with the low bits of the hash, that is the hash modulo the length of the
+/// table. It should usually have a power-of-two length as is customary for devices.
+#[derive(Debug)]
+pub struct Redirection<'a> {
+    table: Slice<'a, usize>,
+}
+
+impl Toeplitz {
+    /// The default key from the Microsoft RSS specification.
+    pub const DEFAULT_KEY: [u8; 40] = [
+        0x6d, 0x5a, 0x56, 0xda, 0x25, 0x5b, 0x0e, 0xc2,
+        0x41, 0x67, 0x25, 0x3d, 0x43, 0xa3, 0x8f, 0xb0,
+        0xd0, 0xca, 0x2b, 0xcb, 0xae, 0x7b, 0x30, 0xb4,
+        0x77, 0xcb, 0x2d, 0xa3, 0x80, 0x30, 0xf2, 0x0c,
+        0x6a, 0x42, 0xb7, 0x3b, 0xbe, 0xac, 0x01, 0xfa,
+    ];
+
+    /// Create a hasher with a custom key.
+    pub fn new(key: [u8; 40]) -> Self {
+        Toeplitz { key }
+    }
+
+    /// A hasher with a key that yields the same hash for both directions of a flow.
+    ///
+    /// A key consisting of a repeated 16-bit pattern makes the hash invariant under swapping of
+    /// source and destination (both addresses and ports at the same time).
+    pub fn symmetric() -> Self {
+        let mut key = [0; 40];
+        for pair in key.chunks_mut(2) {
+            pair.copy_from_slice(&[0x6d, 0x5a]);
+        }
+        Toeplitz { key }
+    }
+
+    /// Hash some input data.
+    ///
+    /// The key is only long enough to hash 36 bytes of input which is enough for an IPv6
+    /// address pair with ports. Additional input bytes are hashed with an implicitly zero
+    /// extended key and thus do not affect the result.
+    pub fn hash(&self, input: &[u8]) -> u32 {
+        let mut result = 0;
+        // The 32 bits of the key aligned with the current bit of the input.
+        let mut window = u32::from_be_bytes([self.key[0], self.key[1], self.key[2], self.key[3]]);
+
+        for (idx, byte) in input.iter().enumerate() {
+            let next = self.key.get(idx + 4).cloned().unwrap_or(0);
+            for bit in 0..8 {
+                if byte & (0x80 >> bit) != 0 {
+                    result ^= window;
+                }
+                window = (window << 1) | u32::from((next >> (7 - bit)) & 1);
+            }
+        }
+
+        result
+    }
+
+    /// Hash the addresses of a packet.
+    ///
+    /// Returns `None` if the addresses are not of the same, specified ip version.
+    pub fn hash_addresses(&self, src_addr: IpAddress, dst_addr: IpAddress) -> Option<u32> {
+        let mut input = [0; 36];
+        let len = Self::write_addresses(&mut input, src_addr, dst_addr)?;
+        Some(self.hash(&input[..len]))
+    }
+
+    /// Hash the addresses and ports of a tcp or udp packet.
+    ///
+    /// Returns `None` if the addresses are not of the same, specified ip version.
+    pub fn hash_ports(
+        &self,
+        src_addr: IpAddress,
+        src_port: u16,
+        dst_addr: IpAddress,
+        dst_port: u16,
+    ) -> Option<u32> {
+        let mut input = [0; 36];
+        let len = Self::write_addresses(&mut input, src_addr, dst_addr)?;
+        input[len..len + 2].copy_from_slice(&src_port.to_be_bytes());
+        input[len + 2..len + 4].copy_from_slice(&dst_port.to_be_bytes());
+        Some(self.hash(&input[..len + 4]))
+    }
+
+    fn write_addresses(input: &mut [u8; 36], src_addr: IpAddress, dst_addr: IpAddress)
+        -> Option<usize>
+    {
+        let (src, dst) = match (src_addr, dst_addr) {
+            (IpAddress::Ipv4(_), IpAddress::Ipv4(_))
+            | (IpAddress::Ipv6(_), IpAddress::Ipv6(_)) => (src_addr.as_bytes(), dst_addr.as_bytes()),
+            _ => return None,
+        };
+
+        let len = src.len();
+        input[..len].copy_from_slice(src);
+        input[len..2*len].copy_from_slice(dst);
+        Some(2*len)
+    }
+}
+
+impl Default for Toeplitz {
+    fn default() -> Self {
+        Toeplitz::new(Self::DEFAULT_KEY)
+    }
+}
+
+impl<'a> Redirection<'a> {
+    /// Use a preconfigured table.
+    ///
+    /// Each entry should be the index of a queue. The table must not be empty.
+    pub fn new<S>(table: S) -> Self
+        where S: Into<Slice<'a, usize>>,
+    {
+        let table = table.into();
+        assert!(!table.is_empty(), "Redirection table must not be empty");
+        Redirection { table }
+    }
+
+    /// Distribute the entries of the table evenly onto a number of queues.
+    ///
+    /// This is the default configuration of most drivers.
+    pub fn round_robin<S>(table: S, queues: usize) -> Self
+        where S: Into<Slice<'a, usize>>,
+    {
+        assert!(queues > 0, "Must distribute onto at least one queue");
+        let mut redirection = Redirection::new(table);
+        for (idx, entry) in redirection.table.iter_mut().enumerate() {
+            *entry = idx % queues;
+        }
+        redirection
+    }
+
+    /// Get the queue for a hash value.
+    pub fn queue(&self, hash: u32) -> usize {
+        self.table[hash as usize % self.table.len()]
+    }
+
+    /// Get the table entries.
+    pub fn table(&self) -> &[usize] {
+        &self.table
+    }
+
+    /// Get mutable access to the table entries, to rebalance queues.
+    pub fn table_mut(&mut self) -> &mut [usize] {
+        &mut self.table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // From the verification suite of the Microsoft RSS specification.
+    const SRC: IpAddress = IpAddress::Ipv4(crate::wire::Ipv4Address::new(66, 9, 149, 187));
+    const DST: IpAddress = IpAddress::Ipv4(crate::wire::Ipv4Address::new(161, 142, 100, 80));
+
+    #[test]
+    fn verification() {
+        let hash = Toeplitz::default();
+        assert_eq!(hash.hash_addresses(SRC, DST), Some(0x323e_8fc2));
+        assert_eq!(hash.hash_ports(SRC, 2794, DST, 1766), Some(0x51cc_c178));
+    }
+
+    #[test]
+    fn symmetric() {
+        let hash = Toeplitz::symmetric();
+        assert_eq!(
+            hash.hash_ports(SRC, 2794, DST, 1766),
+            hash.hash_ports(DST, 1766, SRC, 2794));
+        assert_eq!(hash.hash_ports(SRC, 1, IpAddress::v6(0, 0, 0, 0, 0, 0, 0, 1), 2), None);
+    }
+
+    #[test]
+    fn redirection() {
+        let mut table = [0; 8];
+        let redirection = Redirection::round_robin(&mut table[..], 3);
+        assert_eq!(redirection.table(), &[0, 1, 2, 0, 1, 2, 0, 1]);
+        assert_eq!(redirection.queue(0x51cc_c178), 0);
+        assert_eq!(redirection.queue(0x51cc_c17a), 2);
+    }
+}
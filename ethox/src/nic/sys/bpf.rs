@@ -2,150 +2,370 @@
 // Copyright (C) 2019 Andreas Molzer <andreas.molzer@tum.de>
 //
 // in large parts from `smoltcp` originally distributed under 0-clause BSD
-use std::io;
 use std::os::unix::io::{AsRawFd, RawFd};
+use std::vec::Vec;
+use core::{mem, ptr};
 
 use libc;
+use super::{now, ifreq, Errno, FdResult, IoctlResult, IoLenResult, LibcResult};
 
-use super::{ifreq, ifreq_for};
+use crate::nic::{self, Capabilities, Device, Packet, Personality};
+use crate::nic::common::{EnqueueFlag, PacketInfo};
+use crate::managed::Partial;
+use crate::wire::PayloadMut;
 
-/// set interface
+/// The alignment of packets within a read buffer.
 #[cfg(target_os = "macos")]
-const BIOCSETIF: libc::c_ulong = 0x8020426c;
-/// get buffer length
-#[cfg(target_os = "macos")]
-const BIOCGBLEN: libc::c_ulong = 0x40044266;
-/// set immediate/nonblocking read
-#[cfg(target_os = "macos")]
-const BIOCIMMEDIATE: libc::c_ulong = 0x80044270;
-// TODO: check if this is same for OSes other than macos
-#[cfg(target_os = "macos")]
-const BPF_HDRLEN: usize = 18;
+const BPF_ALIGNMENT: usize = mem::size_of::<i32>();
+#[cfg(not(target_os = "macos"))]
+const BPF_ALIGNMENT: usize = mem::size_of::<libc::c_long>();
 
-macro_rules! try_ioctl {
-    ($fd:expr,$cmd:expr,$req:expr) => {
-        unsafe {
-            if libc::ioctl($fd, $cmd, $req) == -1 {
-                return Err(io::Error::last_os_error());
-            }
-        }
-    };
+/// A static descriptor for a bpf device bound to an interface.
+///
+/// Contains the file descriptor and a pre-filled `ifreq` structure with the interface name that is
+/// required for `ioctl` calls. This offers the raw methods for reading and writing but does not
+/// encapsulate an actual `nic::Device`. Wrap it in a [`Bpf`] with a buffer for this.
+///
+/// Note that a single read returns a buffer containing possibly many captured packets, each
+/// prefixed with a `bpf_hdr`.
+///
+/// [`Bpf`]: struct.Bpf.html
+#[derive(Debug)]
+pub struct BpfDesc {
+    lower: libc::c_int,
+    ifreq: BpfIfreq,
 }
 
+/// A bpf device with buffer, usable as a network device on macOS and FreeBSD.
+///
+/// This is the equivalent of a [`RawSocket`] on Linux, sending and receiving complete ethernet
+/// frames on an existing interface. The device holds an additional read buffer with the size
+/// required by the kernel. Captured packets are copied from it one at a time into the packet
+/// buffer, which should be large enough for the interface mtu, while the `nic::Device`
+/// implementation sends at most one buffer at a time.
+///
+/// [`RawSocket`]: struct.RawSocket.html
 #[derive(Debug)]
-pub struct BpfDevice {
-    fd: libc::c_int,
-    ifreq: ifreq,
+pub struct Bpf<C> {
+    inner: BpfDesc,
+    buffer: Partial<C>,
+    read: Vec<u8>,
+    /// The range of the read buffer that is yet to be processed.
+    cursor: usize,
+    filled: usize,
+    last_err: Option<Errno>,
+    capabilities: Capabilities,
+}
+
+/// A complete `ifreq` as expected by `BIOCSETIF`.
+///
+/// The shared `ifreq` only declares the name but the kernel copies the whole structure.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct BpfIfreq {
+    name: ifreq,
+    data: [u8; 16],
+}
+
+enum Received {
+    NoData,
+    Ok,
+    Err(crate::layer::Error),
 }
 
-impl AsRawFd for BpfDevice {
+impl AsRawFd for BpfDesc {
     fn as_raw_fd(&self) -> RawFd {
-        self.fd
+        self.lower
     }
 }
 
-fn open_device() -> io::Result<libc::c_int> {
-    unsafe {
-        for i in 0..256 {
-            let dev = format!("/dev/bpf{}", i).as_ptr() as *const libc::c_char;
-            match libc::open(dev, libc::O_RDWR) {
-                -1 => continue,
-                fd => return Ok(fd),
-            };
-        }
+impl<C> AsRawFd for Bpf<C> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
     }
-    // at this point, all 256 BPF devices were busy and we weren't able to open any
-    Err(io::Error::last_os_error())
 }
 
-impl BpfDevice {
-    pub fn new(name: &str) -> io::Result<BpfDevice> {
-        Ok(BpfDevice {
-            fd: open_device()?,
-            ifreq: ifreq_for(name),
-        })
+/// Open the cloning device or, where there is none, the first free numbered device.
+fn open_device() -> Result<libc::c_int, Errno> {
+    let mut path = *b"/dev/bpf\0\0\0\0";
+    let mut last_err = Errno(libc::ENOENT);
+
+    for i in 0..256 {
+        // `/dev/bpf` with no number is tried first.
+        if i > 0 {
+            let number = (i - 1).to_string();
+            path[8..8 + number.len()].copy_from_slice(number.as_bytes());
+            path[8 + number.len()] = 0;
+        }
+
+        let lower = unsafe {
+            libc::open(
+                path.as_ptr() as *const libc::c_char,
+                libc::O_RDWR | libc::O_NONBLOCK)
+        };
+
+        match FdResult(lower).errno() {
+            Ok(()) => return Ok(lower),
+            Err(err) => last_err = err,
+        }
     }
 
-    pub fn bind_interface(&mut self) -> io::Result<()> {
-        try_ioctl!(self.fd, BIOCSETIF, &mut self.ifreq);
+    // At this point, all BPF devices were busy or did not exist.
+    Err(last_err)
+}
 
-        Ok(())
+impl BpfDesc {
+    /// Open a bpf device for the named interface.
+    ///
+    /// Note that this does *not* yet set the interface for the file descriptor. Call
+    /// [`bind_interface`] afterwards.
+    ///
+    /// [`bind_interface`]: #method.bind_interface
+    pub fn new(name: &str) -> Result<BpfDesc, Errno> {
+        let lower = open_device()?;
+        Ok(BpfDesc {
+            lower,
+            ifreq: BpfIfreq {
+                name: ifreq::new(name),
+                data: [0; 16],
+            },
+        })
     }
 
-    /// This in fact does not return the interface's mtu,
-    /// but it returns the size of the buffer that the app needs to allocate
-    /// for the BPF device
+    /// Attach the device to the interface.
     ///
-    /// The `SIOGIFMTU` cant be called on a BPF descriptor. There is a workaround
-    /// to get the actual interface mtu, but this should work better
+    /// Also configures the device to return packets immediately and to send frames with the
+    /// source address chosen by the caller.
+    pub fn bind_interface(&mut self) -> Result<(), Errno> {
+        let mut enable: libc::c_uint = 1;
+        let res = unsafe { libc::ioctl(self.lower, libc::BIOCIMMEDIATE, &mut enable) };
+        IoctlResult(res).errno()?;
+        let res = unsafe { libc::ioctl(self.lower, libc::BIOCSHDRCMPLT, &mut enable) };
+        IoctlResult(res).errno()?;
+        let res = unsafe { libc::ioctl(self.lower, libc::BIOCSETIF, &mut self.ifreq) };
+        IoctlResult(res).errno()
+    }
+
+    /// Get the length of the buffer required for reading.
     ///
-    /// To get the interface MTU, you would need to create a raw socket first,
-    /// and then call `SIOGIFMTU` for the same interface your BPF device is "bound" to.
-    /// This MTU that you would get would not include the length of `struct bpf_hdr`
-    /// which gets prepended to every packet by BPF,
-    /// and your packet will be truncated if it has the length of the MTU.
+    /// This is not the interface mtu. Reads with any other buffer length fail.
+    pub fn buffer_len(&mut self) -> Result<usize, Errno> {
+        let mut len: libc::c_uint = 0;
+        let res = unsafe { libc::ioctl(self.lower, libc::BIOCGBLEN, &mut len) };
+        IoctlResult(res).errno()?;
+        Ok(len as usize)
+    }
+
+    /// Read captured packets into the buffer.
+    pub fn recv(&mut self, buffer: &mut [u8]) -> Result<usize, Errno> {
+        let len = unsafe {
+            libc::read(
+                self.lower,
+                buffer.as_mut_ptr() as *mut libc::c_void,
+                buffer.len())
+        };
+        IoLenResult(len).errno()?;
+        Ok(len as usize)
+    }
+
+    /// Send a single frame from the buffer.
+    pub fn send(&mut self, buffer: &[u8]) -> Result<usize, Errno> {
+        let len = unsafe {
+            libc::write(
+                self.lower,
+                buffer.as_ptr() as *const libc::c_void,
+                buffer.len())
+        };
+        IoLenResult(len).errno()?;
+        Ok(len as usize)
+    }
+}
+
+impl<C: PayloadMut> Bpf<C> {
+    /// Open a bpf device for an interface by name with one buffer for packets.
+    pub fn new(name: &str, buffer: C) -> Result<Self, Errno> {
+        let mut inner = BpfDesc::new(name)?;
+        inner.bind_interface()?;
+        Self::with_descriptor(inner, buffer)
+    }
+
+    /// Wrap an existing descriptor with a buffer into a device.
     ///
-    /// The buffer size for BPF is usually 4096 bytes, MTU is typically 1500 bytes.
-    /// You could do something like `mtu += BPF_HDRLEN`,
-    /// but you must change the buffer size the BPF device expects using `BIOCSBLEN` accordingly,
-    /// and you must set it before setting the interface with the `BIOCSETIF` ioctl.
+    /// The descriptor needs to already be bound to the interface.
+    pub fn with_descriptor(mut inner: BpfDesc, buffer: C) -> Result<Self, Errno> {
+        let read = vec![0; inner.buffer_len()?];
+        Ok(Bpf {
+            inner,
+            buffer: Partial::new(buffer),
+            read,
+            cursor: 0,
+            filled: 0,
+            last_err: None,
+            capabilities: Capabilities::no_support(),
+        })
+    }
+
+    /// Get the currently configured capabilities.
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    /// Get a mutable reference to the capability configuration.
     ///
-    /// The reason I said this should work better is because you might see some unexpected behavior,
-    /// truncated/unaligned packets, I/O errors on read()
-    /// if you change the buffer size to the actual MTU of the interface.
-    pub fn interface_mtu(&mut self) -> io::Result<usize> {
-        let mut bufsize: libc::c_int = 1;
-        try_ioctl!(self.fd, BIOCIMMEDIATE, &mut bufsize as *mut libc::c_int);
-        try_ioctl!(self.fd, BIOCGBLEN, &mut bufsize as *mut libc::c_int);
+    /// Allows disabling of checksum tests.
+    pub fn capabilities_mut(&mut self) -> &mut Capabilities {
+        &mut self.capabilities
+    }
 
-        Ok(bufsize as usize)
+    /// Take the last io error returned by the OS.
+    pub fn last_err(&mut self) -> Option<Errno> {
+        self.last_err.take()
     }
 
-    pub fn recv(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
-        unsafe {
-            let len = libc::read(
-                self.fd,
-                buffer.as_mut_ptr() as *mut libc::c_void,
-                buffer.len(),
-            );
+    /// Resize the partial buffer to its full length.
+    fn recycle(&mut self) {
+        let length = self.buffer
+            .inner()
+            .payload()
+            .as_slice()
+            .len();
+        self.buffer.set_len_unchecked(length);
+    }
+
+    /// Send the current buffer as a frame.
+    fn send(&mut self) -> nic::Result<()> {
+        let result = self.inner.send(self.buffer.payload_mut().as_mut_slice());
+        match result {
+            Ok(_) => Ok(()),
+            Err(err) => Err(self.store_err(err))
+        }
+    }
 
-            if len == -1 || len < BPF_HDRLEN as isize {
-                return Err(io::Error::last_os_error());
+    fn recv(&mut self) -> Received {
+        if self.cursor >= self.filled {
+            self.cursor = 0;
+            self.filled = 0;
+            match self.inner.recv(&mut self.read) {
+                Ok(len) => self.filled = len,
+                Err(ref err) if err.0 == libc::EWOULDBLOCK => return Received::NoData,
+                Err(err) => return Received::Err(self.store_err(err)),
             }
+        }
 
-            let len = len as usize;
+        let base = self.cursor;
+        let remaining = &self.read[base..self.filled];
+        if remaining.len() < mem::size_of::<libc::bpf_hdr>() {
+            self.cursor = self.filled;
+            return Received::NoData;
+        }
 
-            libc::memmove(
-                buffer.as_mut_ptr() as *mut libc::c_void,
-                &buffer[BPF_HDRLEN] as *const u8 as *const libc::c_void,
-                len - BPF_HDRLEN,
-            );
+        let header = unsafe {
+            ptr::read_unaligned(remaining.as_ptr() as *const libc::bpf_hdr)
+        };
+        let start = base + usize::from(header.bh_hdrlen);
+        let end = start + header.bh_caplen as usize;
+        self.cursor += word_align(end - base).max(mem::size_of::<libc::bpf_hdr>());
 
-            Ok(len)
+        if end > self.filled {
+            return Received::NoData;
         }
-    }
 
-    pub fn send(&mut self, buffer: &[u8]) -> io::Result<usize> {
-        unsafe {
-            let len = libc::write(
-                self.fd,
-                buffer.as_ptr() as *const libc::c_void,
-                buffer.len(),
-            );
+        self.recycle();
+        let captured = &self.read[start..end];
+        let buffer = self.buffer.payload_mut().as_mut_slice();
+        // Drop packets that do not fit, they would appear truncated.
+        if captured.len() > buffer.len() {
+            return Received::NoData;
+        }
 
-            if len == -1 {
-                Err(io::Error::last_os_error()).unwrap()
-            }
+        buffer[..captured.len()].copy_from_slice(captured);
+        self.buffer.set_len_unchecked(captured.len());
+        Received::Ok
+    }
 
-            Ok(len as usize)
+    fn store_err(&mut self, err: Errno) -> crate::layer::Error {
+        let as_nic = crate::layer::Error::Illegal;
+        self.last_err = Some(err);
+        as_nic
+    }
+
+    fn current_info(&self) -> PacketInfo {
+        PacketInfo {
+            timestamp: now().unwrap(),
+            capabilities: self.capabilities,
+            hardware_timestamp: None,
         }
     }
 }
 
-impl Drop for BpfDevice {
+fn word_align(len: usize) -> usize {
+    (len + BPF_ALIGNMENT - 1) & !(BPF_ALIGNMENT - 1)
+}
+
+impl Drop for BpfDesc {
     fn drop(&mut self) {
-        unsafe {
-            libc::close(self.fd);
+        unsafe { libc::close(self.lower); }
+    }
+}
+
+impl<C: PayloadMut> Device for Bpf<C> {
+    type Handle = EnqueueFlag;
+    type Payload = Partial<C>;
+
+    fn personality(&self) -> Personality {
+        Personality::baseline()
+    }
+
+    fn tx(&mut self, _: usize, mut sender: impl nic::Send<Self::Handle, Self::Payload>)
+        -> nic::Result<usize>
+    {
+        let mut handle = EnqueueFlag::set_true(self.current_info());
+        self.recycle();
+        sender.send(Packet {
+            handle: &mut handle,
+            payload: &mut self.buffer,
+        });
+
+        if handle.was_sent() {
+            self.send()?;
+            Ok(1)
+        } else {
+            Ok(0)
         }
     }
+
+    /// Receive up to `max` of the packets captured by a single read.
+    fn rx(&mut self, max: usize, mut receptor: impl nic::Recv<Self::Handle, Self::Payload>)
+        -> nic::Result<usize>
+    {
+        let mut count = 0;
+
+        while count < max {
+            match self.recv() {
+                Received::Ok => (),
+                Received::Err(err) => return Err(err),
+                Received::NoData if self.cursor < self.filled => continue,
+                Received::NoData => break,
+            }
+
+            let mut handle = EnqueueFlag::set_true(self.current_info()).allow_retain();
+            receptor.receive(Packet {
+                handle: &mut handle,
+                payload: &mut self.buffer,
+            });
+
+            if handle.was_sent() {
+                self.send()?;
+            }
+
+            count += 1;
+
+            // Only process the packets of a single read per call.
+            if self.cursor >= self.filled {
+                break;
+            }
+        }
+
+        Ok(count)
+    }
 }
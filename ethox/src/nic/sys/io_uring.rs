@@ -0,0 +1,796 @@
+//! A raw socket device driven by an io_uring.
+//!
+//! The kernel interface is used directly through system calls as the `libc` crate only provides
+//! the system call numbers. The structures declared here follow `linux/io_uring.h`.
+use core::{mem, ptr};
+use core::sync::atomic::{AtomicU32, Ordering};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::vec::Vec;
+
+use libc;
+use super::{now, Errno, FdResult, LibcResult};
+use super::raw_socket::RawSocketDesc;
+
+use crate::nic::{self, Capabilities, Device, Packet, Personality};
+use crate::nic::common::{EnqueueFlag, PacketInfo};
+use crate::managed::Partial;
+use crate::wire::PayloadMut;
+
+const IORING_OFF_SQ_RING: libc::off_t = 0;
+const IORING_OFF_CQ_RING: libc::off_t = 0x800_0000;
+const IORING_OFF_SQES: libc::off_t = 0x1000_0000;
+
+const IORING_ENTER_GETEVENTS: libc::c_uint = 1;
+const IORING_REGISTER_BUFFERS: libc::c_uint = 0;
+
+const IORING_OP_READ_FIXED: u8 = 4;
+const IORING_OP_WRITE_FIXED: u8 = 5;
+const IORING_OP_ASYNC_CANCEL: u8 = 14;
+const IORING_OP_READ: u8 = 22;
+const IORING_OP_WRITE: u8 = 23;
+
+/// User data of cancellations, distinct from all buffer indices.
+const CANCEL_USER_DATA: u64 = u64::MAX;
+
+/// An io_uring instance with mapped submission and completion queues.
+///
+/// This offers the raw methods for queueing reads and writes on some file descriptor and for
+/// reaping their completions but does not encapsulate an actual `nic::Device`. Wrap it in a
+/// [`IoUringSocket`] together with a raw socket and buffers for this.
+///
+/// [`IoUringSocket`]: struct.IoUringSocket.html
+#[derive(Debug)]
+pub struct IoUringDesc {
+    fd: libc::c_int,
+    sq: SubmissionQueue,
+    cq: CompletionQueue,
+    /// Number of entries queued but not yet submitted to the kernel.
+    pending: u32,
+}
+
+/// A raw socket whose packets are read and written through an io_uring.
+///
+/// All buffers are registered with the kernel, avoiding the page mapping of each operation. They
+/// are divided into receive and transmit buffers at construction. All idle receive buffers have a
+/// read queued at all times and the `nic::Device` implementation hands out those whose read has
+/// completed. Writes are queued for all buffers marked for sending, either from transmit buffers
+/// or when answering a received packet in-place, and submitted with a single system call per
+/// call to `rx` or `tx`. A completed write returns the buffer to its previous purpose.
+///
+/// This is an intermediate between the classic [`RawSocket`], which performs one system call per
+/// packet, and kernel bypass drivers.
+///
+/// Uses the errno principle for storing the last underlying error on a failed operation.
+///
+/// [`RawSocket`]: struct.RawSocket.html
+#[derive(Debug)]
+pub struct IoUringSocket<C> {
+    /// The kernel may write into the buffers while an operation is outstanding. Closing the ring
+    /// does not wait for them, the kernel tears them down asynchronously, so dropping the device
+    /// first cancels all operations and reaps their completions.
+    ring: IoUringDesc,
+    socket: RawSocketDesc,
+    buffers: Vec<Buffer<C>>,
+    /// The number of buffers at the start of `buffers` used for receiving.
+    receive: usize,
+    last_err: Option<Errno>,
+    capabilities: Capabilities,
+}
+
+#[derive(Debug)]
+struct Buffer<C> {
+    payload: Partial<C>,
+    state: State,
+    /// The memory registered for this buffer.
+    registered: libc::iovec,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Idle,
+    Reading,
+    Received,
+    Writing,
+}
+
+#[derive(Debug)]
+struct SubmissionQueue {
+    /// Kept only for unmapping on drop.
+    _map: Mmap,
+    _entries_map: Mmap,
+    head: *const AtomicU32,
+    tail: *const AtomicU32,
+    mask: u32,
+    array: *mut u32,
+    entries: *mut Sqe,
+    size: u32,
+}
+
+#[derive(Debug)]
+struct CompletionQueue {
+    /// Kept only for unmapping on drop.
+    _map: Mmap,
+    head: *const AtomicU32,
+    tail: *const AtomicU32,
+    mask: u32,
+    entries: *const Cqe,
+}
+
+#[derive(Debug)]
+struct Mmap {
+    addr: *mut libc::c_void,
+    len: usize,
+}
+
+/// A completed operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Completion {
+    /// The user data of the submission.
+    pub user_data: u64,
+    /// The result, a length or a negated errno value.
+    pub result: i32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    rw_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+impl IoUringDesc {
+    /// Create a ring with at least the given number of submission entries.
+    ///
+    /// The completion queue is twice as large, as is the default of the kernel.
+    pub fn new(entries: u32) -> Result<Self, Errno> {
+        let mut params = Params::default();
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_setup,
+                entries as libc::c_long,
+                &mut params as *mut Params)
+        } as libc::c_int;
+        FdResult(fd).errno()?;
+
+        // Close the ring if any mapping fails.
+        let close = CloseOnDrop(fd);
+
+        let sq_len = params.sq_off.array as usize + params.sq_entries as usize * mem::size_of::<u32>();
+        let cq_len = params.cq_off.cqes as usize + params.cq_entries as usize * mem::size_of::<Cqe>();
+        let sqes_len = params.sq_entries as usize * mem::size_of::<Sqe>();
+
+        let sq_map = Mmap::new(fd, sq_len, IORING_OFF_SQ_RING)?;
+        let cq_map = Mmap::new(fd, cq_len, IORING_OFF_CQ_RING)?;
+        let sqes_map = Mmap::new(fd, sqes_len, IORING_OFF_SQES)?;
+
+        let sq = unsafe {
+            SubmissionQueue {
+                head: sq_map.offset(params.sq_off.head) as *const AtomicU32,
+                tail: sq_map.offset(params.sq_off.tail) as *const AtomicU32,
+                mask: *(sq_map.offset(params.sq_off.ring_mask) as *const u32),
+                array: sq_map.offset(params.sq_off.array) as *mut u32,
+                entries: sqes_map.addr as *mut Sqe,
+                size: params.sq_entries,
+                _map: sq_map,
+                _entries_map: sqes_map,
+            }
+        };
+
+        let cq = unsafe {
+            CompletionQueue {
+                head: cq_map.offset(params.cq_off.head) as *const AtomicU32,
+                tail: cq_map.offset(params.cq_off.tail) as *const AtomicU32,
+                mask: *(cq_map.offset(params.cq_off.ring_mask) as *const u32),
+                entries: cq_map.offset(params.cq_off.cqes) as *const Cqe,
+                _map: cq_map,
+            }
+        };
+
+        mem::forget(close);
+        Ok(IoUringDesc {
+            fd,
+            sq,
+            cq,
+            pending: 0,
+        })
+    }
+
+    /// Register buffers for fixed reads and writes.
+    ///
+    /// # Safety
+    /// The memory described by the `iovec`s must stay valid until the completions of all fixed
+    /// operations on it have been reaped. Closing the ring does not wait for them.
+    pub unsafe fn register_buffers(&mut self, buffers: &[libc::iovec]) -> Result<(), Errno> {
+        let res = libc::syscall(
+            libc::SYS_io_uring_register,
+            self.fd as libc::c_long,
+            IORING_REGISTER_BUFFERS as libc::c_long,
+            buffers.as_ptr(),
+            buffers.len() as libc::c_long) as libc::c_int;
+        FdResult(res).errno()
+    }
+
+    /// Queue a read from a file descriptor into a buffer.
+    ///
+    /// Uses the registered buffer with index `fixed`, if given, which must contain the buffer.
+    /// Returns `false` if the submission queue is full.
+    ///
+    /// # Safety
+    /// The buffer must stay valid until the completion of the operation has been reaped.
+    pub unsafe fn queue_read(
+        &mut self,
+        fd: RawFd,
+        buffer: *mut u8,
+        len: usize,
+        fixed: Option<u16>,
+        user_data: u64,
+    ) -> bool {
+        let opcode = if fixed.is_some() { IORING_OP_READ_FIXED } else { IORING_OP_READ };
+        self.queue(Sqe {
+            opcode,
+            fd,
+            addr: buffer as u64,
+            len: len as u32,
+            user_data,
+            buf_index: fixed.unwrap_or(0),
+            ..Sqe::default()
+        })
+    }
+
+    /// Queue a write of a buffer to a file descriptor.
+    ///
+    /// Uses the registered buffer with index `fixed`, if given, which must contain the buffer.
+    /// Returns `false` if the submission queue is full.
+    ///
+    /// # Safety
+    /// The buffer must stay valid until the completion of the operation has been reaped.
+    pub unsafe fn queue_write(
+        &mut self,
+        fd: RawFd,
+        buffer: *const u8,
+        len: usize,
+        fixed: Option<u16>,
+        user_data: u64,
+    ) -> bool {
+        let opcode = if fixed.is_some() { IORING_OP_WRITE_FIXED } else { IORING_OP_WRITE };
+        self.queue(Sqe {
+            opcode,
+            fd,
+            addr: buffer as u64,
+            len: len as u32,
+            user_data,
+            buf_index: fixed.unwrap_or(0),
+            ..Sqe::default()
+        })
+    }
+
+    /// Submit all queued entries to the kernel.
+    ///
+    /// Waits for at least `wait` completions to become available.
+    pub fn submit(&mut self, wait: u32) -> Result<(), Errno> {
+        if self.pending == 0 && wait == 0 {
+            return Ok(());
+        }
+
+        let flags = if wait > 0 { IORING_ENTER_GETEVENTS } else { 0 };
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_enter,
+                self.fd as libc::c_long,
+                self.pending as libc::c_long,
+                wait as libc::c_long,
+                flags as libc::c_long,
+                ptr::null::<libc::sigset_t>(),
+                0 as libc::c_long)
+        } as libc::c_int;
+        FdResult(res).errno()?;
+
+        self.pending -= res as u32;
+        Ok(())
+    }
+
+    /// Queue the cancellation of an earlier operation, identified by its user data.
+    ///
+    /// The cancellation has a completion of its own, and the operation still completes as well,
+    /// usually with `-ECANCELED`. Returns `false` if the submission queue is full.
+    pub fn queue_cancel(&mut self, target: u64, user_data: u64) -> bool {
+        self.queue(Sqe {
+            opcode: IORING_OP_ASYNC_CANCEL,
+            fd: -1,
+            addr: target,
+            user_data,
+            ..Sqe::default()
+        })
+    }
+
+    /// Take the next completion, if any.
+    pub fn complete(&mut self) -> Option<Completion> {
+        let cq = &self.cq;
+        let (head, tail) = unsafe {
+            ((*cq.head).load(Ordering::Relaxed), (*cq.tail).load(Ordering::Acquire))
+        };
+
+        if head == tail {
+            return None;
+        }
+
+        let cqe = unsafe { ptr::read(cq.entries.add((head & cq.mask) as usize)) };
+        unsafe { (*cq.head).store(head.wrapping_add(1), Ordering::Release) };
+
+        Some(Completion {
+            user_data: cqe.user_data,
+            result: cqe.res,
+        })
+    }
+
+    fn queue(&mut self, sqe: Sqe) -> bool {
+        let sq = &self.sq;
+        let (head, tail) = unsafe {
+            ((*sq.head).load(Ordering::Acquire), (*sq.tail).load(Ordering::Relaxed))
+        };
+
+        if tail.wrapping_sub(head) >= sq.size {
+            return false;
+        }
+
+        let index = tail & sq.mask;
+        unsafe {
+            ptr::write(sq.entries.add(index as usize), sqe);
+            ptr::write(sq.array.add(index as usize), index);
+            (*sq.tail).store(tail.wrapping_add(1), Ordering::Release);
+        }
+
+        self.pending += 1;
+        true
+    }
+}
+
+impl<C: PayloadMut> IoUringSocket<C> {
+    /// Open a raw socket by name, with buffers for receiving and for sending.
+    pub fn new<R, T>(name: &str, receive: R, transmit: T) -> Result<Self, Errno>
+    where
+        R: IntoIterator<Item=C>,
+        T: IntoIterator<Item=C>,
+    {
+        let mut socket = RawSocketDesc::new(name)?;
+        socket.bind_interface()?;
+        Self::with_descriptor(socket, receive, transmit)
+    }
+
+    /// Wrap an existing descriptor and buffers into a device.
+    ///
+    /// The socket needs to already be bound to the interface otherwise errors to all calls will be
+    /// the consequence. It is switched to blocking mode as the ring will otherwise complete reads
+    /// immediately when no packet is available.
+    pub fn with_descriptor<R, T>(socket: RawSocketDesc, receive: R, transmit: T)
+        -> Result<Self, Errno>
+    where
+        R: IntoIterator<Item=C>,
+        T: IntoIterator<Item=C>,
+    {
+        let fd = socket.as_raw_fd();
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        FdResult(flags).errno()?;
+        let res = unsafe { libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK) };
+        FdResult(res).errno()?;
+
+        let mut buffers: Vec<Buffer<C>> = receive.into_iter()
+            .map(Buffer::new)
+            .collect();
+        let receive = buffers.len();
+        buffers.extend(transmit.into_iter().map(Buffer::new));
+        assert!(buffers.len() <= usize::from(u16::MAX), "Too many buffers to register");
+
+        let mut ring = IoUringDesc::new(buffers.len().max(1) as u32)?;
+        let iovecs: Vec<libc::iovec> = buffers.iter_mut()
+            .map(|buffer| buffer.register())
+            .collect();
+        // The buffers are owned by the device and dropped only after all their operations have
+        // completed. Should a payload be reallocated then the fixed operations are no longer used
+        // for it.
+        if !iovecs.is_empty() {
+            unsafe { ring.register_buffers(&iovecs)? };
+        }
+
+        let mut device = IoUringSocket {
+            ring,
+            socket,
+            buffers,
+            receive,
+            last_err: None,
+            capabilities: Capabilities::no_support(),
+        };
+
+        for idx in 0..device.receive {
+            device.queue_read(idx);
+        }
+
+        device.ring.submit(0)?;
+        Ok(device)
+    }
+
+    /// Get the currently configured capabilities.
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    /// Get a mutable reference to the capability configuration.
+    ///
+    /// Allows disabling of checksum tests.
+    pub fn capabilities_mut(&mut self) -> &mut Capabilities {
+        &mut self.capabilities
+    }
+
+    /// Take the last io error returned by the OS.
+    pub fn last_err(&mut self) -> Option<Errno> {
+        self.last_err.take()
+    }
+
+    /// Reap all available completions and requeue reads.
+    fn reap(&mut self) {
+        while let Some(Completion { user_data, result }) = self.ring.complete() {
+            let idx = user_data as usize;
+            let buffer = &mut self.buffers[idx];
+            match (buffer.state, result) {
+                (State::Reading, len) if len >= 0 => {
+                    buffer.payload.set_len_unchecked(len as usize);
+                    buffer.state = State::Received;
+                },
+                (_, err) => {
+                    if err < 0 && err != -libc::EAGAIN && err != -libc::EINTR {
+                        self.last_err = Some(Errno(-err));
+                    }
+                    buffer.state = State::Idle;
+                },
+            }
+        }
+
+        for idx in 0..self.receive {
+            if self.buffers[idx].state == State::Idle {
+                self.queue_read(idx);
+            }
+        }
+    }
+
+    fn queue_read(&mut self, idx: usize) {
+        let fd = self.socket.as_raw_fd();
+        let buffer = &mut self.buffers[idx];
+        buffer.recycle();
+        let fixed = buffer.fixed(idx);
+        let slice = buffer.payload.payload_mut().as_mut_slice();
+        let queued = unsafe {
+            self.ring.queue_read(fd, slice.as_mut_ptr(), slice.len(), fixed, idx as u64)
+        };
+        // The ring has space for all buffers.
+        debug_assert!(queued);
+        buffer.state = State::Reading;
+    }
+
+    fn queue_write(&mut self, idx: usize) {
+        let fd = self.socket.as_raw_fd();
+        let buffer = &mut self.buffers[idx];
+        let fixed = buffer.fixed(idx);
+        let slice = buffer.payload.payload_mut().as_mut_slice();
+        let queued = unsafe {
+            self.ring.queue_write(fd, slice.as_ptr(), slice.len(), fixed, idx as u64)
+        };
+        debug_assert!(queued);
+        buffer.state = State::Writing;
+    }
+
+    fn submit(&mut self) -> nic::Result<()> {
+        match self.ring.submit(0) {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                self.last_err = Some(err);
+                Err(crate::layer::Error::Illegal)
+            },
+        }
+    }
+
+    fn current_info(&self) -> PacketInfo {
+        PacketInfo {
+            timestamp: now().unwrap(),
+            capabilities: self.capabilities,
+            hardware_timestamp: None,
+        }
+    }
+}
+
+impl<C> IoUringSocket<C> {
+    /// Cancel all outstanding operations and wait until each has completed.
+    ///
+    /// Returns `false` if the ring failed before all completions were reaped.
+    fn cancel_all(&mut self) -> bool {
+        for idx in 0..self.buffers.len() {
+            if !self.buffers[idx].is_outstanding() {
+                continue;
+            }
+
+            // The queue only holds entries not yet consumed by the kernel.
+            let queued = self.ring.queue_cancel(idx as u64, CANCEL_USER_DATA)
+                || (self.ring.submit(0).is_ok()
+                    && self.ring.queue_cancel(idx as u64, CANCEL_USER_DATA));
+            if !queued {
+                return false;
+            }
+        }
+
+        loop {
+            while let Some(Completion { user_data, .. }) = self.ring.complete() {
+                if let Some(buffer) = self.buffers.get_mut(user_data as usize) {
+                    buffer.state = State::Idle;
+                }
+            }
+
+            if !self.buffers.iter().any(Buffer::is_outstanding) {
+                return true;
+            }
+
+            match self.ring.submit(1) {
+                Ok(()) | Err(Errno(libc::EINTR)) => (),
+                Err(_) => return false,
+            }
+        }
+    }
+}
+
+impl<C> Buffer<C> {
+    /// If the kernel may still access the buffer.
+    fn is_outstanding(&self) -> bool {
+        matches!(self.state, State::Reading | State::Writing)
+    }
+}
+
+impl<C: PayloadMut> Buffer<C> {
+    fn new(payload: C) -> Self {
+        Buffer {
+            payload: Partial::new(payload),
+            state: State::Idle,
+            registered: libc::iovec {
+                iov_base: ptr::null_mut(),
+                iov_len: 0,
+            },
+        }
+    }
+
+    fn register(&mut self) -> libc::iovec {
+        self.recycle();
+        let slice = self.payload.payload_mut().as_mut_slice();
+        self.registered = libc::iovec {
+            iov_base: slice.as_mut_ptr() as *mut libc::c_void,
+            iov_len: slice.len(),
+        };
+        self.registered
+    }
+
+    /// Resize the partial buffer to its full length.
+    fn recycle(&mut self) {
+        let length = self.payload
+            .inner()
+            .payload()
+            .as_slice()
+            .len();
+        self.payload.set_len_unchecked(length);
+    }
+
+    /// The registered index if the payload is still within the registered memory.
+    fn fixed(&mut self, idx: usize) -> Option<u16> {
+        let base = self.registered.iov_base as usize;
+        let slice = self.payload.payload_mut().as_mut_slice();
+        let start = slice.as_ptr() as usize;
+        let within = start >= base
+            && start + slice.len() <= base + self.registered.iov_len;
+        if within { Some(idx as u16) } else { None }
+    }
+}
+
+impl<C: PayloadMut> Device for IoUringSocket<C> {
+    type Handle = EnqueueFlag;
+    type Payload = Partial<C>;
+
+    fn personality(&self) -> Personality {
+        let mut personality = Personality::baseline();
+        *personality.rx_batch_mut() = self.receive.max(1);
+        *personality.tx_batch_mut() = (self.buffers.len() - self.receive).max(1);
+        personality
+    }
+
+    fn tx(&mut self, max: usize, mut sender: impl nic::Send<Self::Handle, Self::Payload>)
+        -> nic::Result<usize>
+    {
+        self.reap();
+
+        let mut count = 0;
+        for idx in self.receive..self.buffers.len() {
+            if count == max {
+                break;
+            }
+
+            if self.buffers[idx].state != State::Idle {
+                continue;
+            }
+
+            let mut handle = EnqueueFlag::set_true(self.current_info());
+            let buffer = &mut self.buffers[idx];
+            buffer.recycle();
+            sender.send(Packet {
+                handle: &mut handle,
+                payload: &mut buffer.payload,
+            });
+
+            if handle.was_sent() {
+                self.queue_write(idx);
+                count += 1;
+            }
+        }
+
+        self.submit()?;
+        Ok(count)
+    }
+
+    fn rx(&mut self, max: usize, mut receptor: impl nic::Recv<Self::Handle, Self::Payload>)
+        -> nic::Result<usize>
+    {
+        self.reap();
+
+        let mut count = 0;
+        for idx in 0..self.receive {
+            if count == max {
+                break;
+            }
+
+            if self.buffers[idx].state != State::Received {
+                continue;
+            }
+
+            let mut handle = EnqueueFlag::set_true(self.current_info()).allow_retain();
+            receptor.receive(Packet {
+                handle: &mut handle,
+                payload: &mut self.buffers[idx].payload,
+            });
+
+            if handle.was_sent() {
+                self.queue_write(idx);
+            } else {
+                self.queue_read(idx);
+            }
+
+            count += 1;
+        }
+
+        self.submit()?;
+        Ok(count)
+    }
+}
+
+impl AsRawFd for IoUringDesc {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl<C> AsRawFd for IoUringSocket<C> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+impl Mmap {
+    fn new(fd: libc::c_int, len: usize, offset: libc::off_t) -> Result<Self, Errno> {
+        let addr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                fd,
+                offset)
+        };
+
+        if addr == libc::MAP_FAILED {
+            return Err(Errno::new());
+        }
+
+        Ok(Mmap { addr, len })
+    }
+
+    fn offset(&self, offset: u32) -> *mut u8 {
+        debug_assert!((offset as usize) < self.len);
+        unsafe { (self.addr as *mut u8).add(offset as usize) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.addr, self.len); }
+    }
+}
+
+struct CloseOnDrop(libc::c_int);
+
+impl Drop for CloseOnDrop {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.0); }
+    }
+}
+
+impl<C> Drop for IoUringSocket<C> {
+    fn drop(&mut self) {
+        // Leak the buffers rather than free memory that the kernel may still write into.
+        if !self.cancel_all() {
+            mem::forget(mem::take(&mut self.buffers));
+        }
+    }
+}
+
+impl Drop for IoUringDesc {
+    fn drop(&mut self) {
+        // Unmapping happens afterwards when the queues are dropped. Operations still in flight
+        // are torn down by the kernel in the background.
+        unsafe { libc::close(self.fd); }
+    }
+}
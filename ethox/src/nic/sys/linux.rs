@@ -7,6 +7,12 @@ use libc;
 
 pub(crate) const ETH_P_ALL:    libc::c_short = 0x0003;
 
+pub(crate) const SOL_PACKET:   libc::c_int = 263;
+pub(crate) const PACKET_ADD_MEMBERSHIP:  libc::c_int = 1;
+pub(crate) const PACKET_DROP_MEMBERSHIP: libc::c_int = 2;
+pub(crate) const PACKET_MR_PROMISC:  libc::c_int = 1;
+pub(crate) const PACKET_MR_ALLMULTI: libc::c_int = 2;
+
 /// Adds a method to open a tap.
 ///
 /// This is an extension trait implemented for `ifreq` in Linux.
@@ -6,6 +6,7 @@
 //
 // Applies to files in this folder unless otherwise noted. These are:
 // * `bpf.rs`
+// * `io_uring.rs`
 // * `linux.rs`
 // * `mod.rs`
 // * `raw_socket.rs`
@@ -17,7 +18,7 @@ use std::{io, ptr};
 use std::os::unix::io::RawFd;
 
 use libc;
-use crate::time::Instant;
+use crate::time::{Clock, Instant};
 #[cfg(feature = "std")]
 use crate::time::Duration;
 
@@ -28,6 +29,15 @@ mod linux;
 mod raw_socket;
 #[cfg(target_os = "linux")]
 mod tap_interface;
+#[cfg(all(target_os = "linux", feature = "std"))]
+mod io_uring;
+#[cfg(feature = "std")]
+mod shm;
+
+#[cfg(all(any(target_os = "macos", target_os = "freebsd"), feature = "std"))]
+mod bpf;
+#[cfg(target_os = "macos")]
+mod utun;
 
 /// Module importing all types that should be exported.
 ///
@@ -38,9 +48,18 @@ pub mod exports {
     pub use super::tap_interface::{TapInterface, TapInterfaceDesc};
     #[cfg(target_os = "linux")]
     pub use super::raw_socket::{RawSocket, RawSocketDesc};
+    #[cfg(all(target_os = "linux", feature = "std"))]
+    pub use super::io_uring::{Completion, IoUringDesc, IoUringSocket};
+    #[cfg(feature = "std")]
+    pub use super::shm::SharedMemory;
+    #[cfg(all(any(target_os = "macos", target_os = "freebsd"), feature = "std"))]
+    pub use super::bpf::{Bpf, BpfDesc};
+    #[cfg(target_os = "macos")]
+    pub use super::utun::{Utun, UtunDesc};
     #[cfg(feature = "std")]
     pub use super::wait as sys_wait;
     pub use super::Errno;
+    pub use super::MonotonicClock;
 }
 
 #[cfg(feature = "std")]
@@ -127,9 +146,16 @@ trait LibcResult: Copy {
 
 impl Errno {
     /// Retrieve the current value of `errno`.
+    #[cfg(target_os = "linux")]
     pub fn new() -> Errno {
         Errno(unsafe { *libc::__errno_location() })
     }
+
+    /// Retrieve the current value of `errno`.
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    pub fn new() -> Errno {
+        Errno(unsafe { *libc::__error() })
+    }
 }
 
 impl LibcResult for FdResult {
@@ -157,6 +183,16 @@ impl From<Errno> for io::Error {
     }
 }
 
+/// The maximum transmission unit of an interface, as far as frames fit into a buffer.
+///
+/// Frames carry an ethernet header in front of the network layer packet. Falls back to the
+/// buffer size when the interface did not report its unit.
+#[cfg(target_os = "linux")]
+fn buffer_mtu(interface: Option<usize>, buffer: usize) -> usize {
+    let fits = buffer.saturating_sub(14);
+    interface.map_or(fits, |mtu| mtu.min(fits))
+}
+
 impl ifreq {
     fn new(name: &str) -> Self {
         let mut ifr_name = [0; libc::IF_NAMESIZE];
@@ -171,6 +207,19 @@ impl ifreq {
     }
 }
 
+/// The monotonic clock of the operating system.
+///
+/// This is the time source of the packet timestamps of all interfaces in this module. Use it to
+/// drive the timers of endpoints during idle periods.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MonotonicClock;
+
+impl Clock for MonotonicClock {
+    fn now(&self) -> Instant {
+        now().expect("The monotonic clock is always supported")
+    }
+}
+
 fn now() -> Result<Instant, Errno> {
    let ts = unsafe {
        let mut ts = mem::MaybeUninit::<libc::timespec>::uninit();
@@ -7,12 +7,12 @@ use core::mem;
 use std::os::unix::io::{RawFd, AsRawFd};
 
 use libc;
-use super::{ifreq, linux, now, Errno, FdResult, LibcResult, IoLenResult};
+use super::{buffer_mtu, ifreq, linux, now, Errno, FdResult, LibcResult, IoLenResult};
 
-use crate::nic::{self, Capabilities, Device, Packet, Personality};
+use crate::nic::{self, Capabilities, Device, Filter, Packet, Personality};
 use crate::nic::common::{EnqueueFlag, PacketInfo};
 use crate::managed::Partial;
-use crate::wire::PayloadMut;
+use crate::wire::{Payload, PayloadMut};
 
 mod tap_traits {
     #[cfg(target_os = "linux")]
@@ -42,8 +42,11 @@ pub struct RawSocketDesc {
 ///
 /// Uses the errno principle for storing the last underlying error on a failed operation.
 ///
-/// The device capabilities are mutable and not automatically deduced. For example, a local veth
-/// pair (connecting two network namespaces) could be allowed to elide all checksums.
+/// The device capabilities are mutable and not automatically deduced, except for the maximum
+/// transmission unit. It is queried from the interface and limited to frames fitting into the
+/// buffer. For jumbo frames, provide a buffer for the ethernet header and the unit of the
+/// interface. A local veth pair (connecting two network namespaces) could be allowed to elide all
+/// checksums.
 ///
 /// The `nic::Device` implementation always sends and receives at most one buffer at a time. It
 /// will also block on sending but is non-blocking during receiving. This is not quite a bug. It's
@@ -57,6 +60,10 @@ pub struct RawSocket<C> {
     buffer: Partial<C>,
     last_err: Option<Errno>,
     capabilities: Capabilities,
+    /// The interface is in promiscuous mode through this socket.
+    promiscuous: bool,
+    /// The interface receives all multicast frames through this socket.
+    all_multicast: bool,
 }
 
 enum Received {
@@ -136,14 +143,15 @@ impl RawSocketDesc {
     /// Receive a single frame into the buffer.
     ///
     /// Note that the socket will have been opened with `O_NONBLOCK` so that this only returns an
-    /// `Ok` when a buffer is ready.
+    /// `Ok` when a buffer is ready. Returns the full length of the frame, which is larger than the
+    /// buffer if the frame was truncated.
     pub fn recv(&mut self, buffer: &mut [u8]) -> Result<usize, Errno> {
         let len = unsafe {
             libc::recv(
                 self.lower,
                 buffer.as_mut_ptr() as *mut libc::c_void,
                 buffer.len(),
-                0)
+                libc::MSG_TRUNC)
         };
         IoLenResult(len).errno()?;
         Ok(len as usize)
@@ -161,6 +169,44 @@ impl RawSocketDesc {
         IoLenResult(len).errno()?;
         Ok(len as usize)
     }
+
+    /// Add or drop a membership of the socket, changing the receive mode of the interface.
+    ///
+    /// The `kind` is one of the `PACKET_MR_*` constants for which no address is required, such
+    /// as promiscuous mode. The interface leaves the mode when no socket holds a membership.
+    pub fn set_membership(&mut self, kind: libc::c_int, enable: bool) -> Result<(), Errno> {
+        #[repr(C)]
+        struct Request {
+            mr_ifindex: libc::c_int,
+            mr_type: libc::c_ushort,
+            mr_alen: libc::c_ushort,
+            mr_address: [u8; 8],
+        }
+
+        let request = Request {
+            mr_ifindex: self.ifreq.get_if_index(self.lower)?,
+            mr_type: kind as libc::c_ushort,
+            mr_alen: 0,
+            mr_address: [0; 8],
+        };
+
+        let option = if enable {
+            linux::PACKET_ADD_MEMBERSHIP
+        } else {
+            linux::PACKET_DROP_MEMBERSHIP
+        };
+
+        let res = unsafe {
+            libc::setsockopt(
+                self.lower,
+                linux::SOL_PACKET,
+                option,
+                &request as *const Request as *const libc::c_void,
+                mem::size_of::<Request>() as libc::socklen_t)
+        };
+
+        FdResult(res).errno()
+    }
 }
 
 impl<C: PayloadMut> RawSocket<C> {
@@ -176,14 +222,19 @@ impl<C: PayloadMut> RawSocket<C> {
     /// The socket needs to already be bound to the interface otherwise errors to all calls will be
     /// the consequence.
     pub fn with_descriptor(
-        inner: RawSocketDesc,
+        mut inner: RawSocketDesc,
         buffer: C,
     ) -> Result<Self, Errno> {
+        let mut capabilities = Capabilities::no_support();
+        let mtu = buffer_mtu(inner.interface_mtu().ok(), buffer.payload().len());
+        *capabilities.mtu_mut() = Some(mtu);
         Ok(RawSocket {
             inner,
             buffer: Partial::new(buffer),
             last_err: None,
-            capabilities: Capabilities::no_support(),
+            capabilities,
+            promiscuous: false,
+            all_multicast: false,
         })
     }
 
@@ -227,6 +278,9 @@ impl<C: PayloadMut> RawSocket<C> {
         self.recycle();
         let result = self.inner.recv(self.buffer.payload_mut().as_mut_slice());
         match result {
+            Ok(len) if len > self.buffer.payload().len() => {
+                Received::Err(crate::layer::Error::BadSize)
+            },
             Ok(len) => {
                 self.buffer.set_len_unchecked(len);
                 Received::Ok
@@ -236,6 +290,20 @@ impl<C: PayloadMut> RawSocket<C> {
         }
     }
 
+    /// Change a receive mode of the interface if it differs from the current one.
+    fn update_membership(&mut self, kind: libc::c_int, current: bool, enable: bool)
+        -> nic::Result<()>
+    {
+        if current == enable {
+            return Ok(());
+        }
+
+        match self.inner.set_membership(kind, enable) {
+            Ok(()) => Ok(()),
+            Err(err) => Err(self.store_err(err)),
+        }
+    }
+
     fn store_err(&mut self, err: Errno) -> crate::layer::Error {
         let as_nic = crate::layer::Error::Illegal;
         self.last_err = Some(err);
@@ -246,6 +314,7 @@ impl<C: PayloadMut> RawSocket<C> {
         PacketInfo {
             timestamp: now().unwrap(),
             capabilities: self.capabilities,
+            hardware_timestamp: None,
         }
     }
 }
@@ -265,7 +334,9 @@ impl<C: PayloadMut> Device for RawSocket<C> {
     /// Could be dynamically configured but the optimizer and the user is likely happier if the
     /// implementation does not take advantage of this fact.
     fn personality(&self) -> Personality {
-        Personality::baseline()
+        let mut personality = Personality::baseline();
+        *personality.capabilities_mut() = self.capabilities;
+        personality
     }
 
     fn tx(&mut self, _: usize, mut sender: impl nic::Send<Self::Handle, Self::Payload>)
@@ -294,7 +365,7 @@ impl<C: PayloadMut> Device for RawSocket<C> {
             Received::NoData => return Ok(0),
         }
 
-        let mut handle = EnqueueFlag::set_true(self.current_info());
+        let mut handle = EnqueueFlag::set_true(self.current_info()).allow_retain();
         receptor.receive(Packet {
             handle: &mut handle,
             payload: &mut self.buffer,
@@ -306,5 +377,17 @@ impl<C: PayloadMut> Device for RawSocket<C> {
 
         Ok(1)
     }
-}
 
+    /// Put the interface into promiscuous or all-multicast mode as required.
+    ///
+    /// The interface has no filter for individual multicast groups, those are received by
+    /// accepting all multicast frames instead.
+    fn set_filter(&mut self, filter: Filter) -> nic::Result<()> {
+        let all_multicast = filter.all_multicast || !filter.multicast.is_empty();
+        self.update_membership(linux::PACKET_MR_PROMISC, self.promiscuous, filter.promiscuous)?;
+        self.promiscuous = filter.promiscuous;
+        self.update_membership(linux::PACKET_MR_ALLMULTI, self.all_multicast, all_multicast)?;
+        self.all_multicast = all_multicast;
+        Ok(())
+    }
+}
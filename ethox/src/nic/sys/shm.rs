@@ -0,0 +1,81 @@
+use std::ffi::CString;
+use std::ptr::{self, NonNull};
+
+use super::{Errno, FdResult, LibcResult};
+
+/// A file mapped into memory, shared with all processes mapping the same file.
+///
+/// Provides the region of a [`Memif`] device for exchanging packets between processes. The file
+/// is usually placed on a memory backed file system such as `/dev/shm`.
+///
+/// [`Memif`]: ../memif/struct.Memif.html
+#[derive(Debug)]
+pub struct SharedMemory {
+    addr: NonNull<u8>,
+    len: usize,
+}
+
+impl SharedMemory {
+    /// Open or create the file at the path and map its first `len` bytes.
+    ///
+    /// The file is extended to `len` bytes if it is shorter, a new file is filled with zeroes.
+    pub fn open(path: &str, len: usize) -> Result<Self, Errno> {
+        let path = CString::new(path).map_err(|_| Errno(libc::EINVAL))?;
+        let fd = unsafe {
+            libc::open(path.as_ptr(), libc::O_RDWR | libc::O_CREAT | libc::O_CLOEXEC, 0o600)
+        };
+        FdResult(fd).errno()?;
+
+        let result = Self::map(fd, len);
+        unsafe { libc::close(fd); }
+        result
+    }
+
+    fn map(fd: libc::c_int, len: usize) -> Result<Self, Errno> {
+        let mut stat = unsafe { core::mem::zeroed::<libc::stat>() };
+        FdResult(unsafe { libc::fstat(fd, &mut stat) }).errno()?;
+        if (stat.st_size as u64) < len as u64 {
+            FdResult(unsafe { libc::ftruncate(fd, len as libc::off_t) }).errno()?;
+        }
+
+        let addr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd,
+                0)
+        };
+
+        if addr == libc::MAP_FAILED {
+            return Err(Errno::new());
+        }
+
+        Ok(SharedMemory {
+            addr: NonNull::new(addr as *mut u8).ok_or(Errno(libc::ENOMEM))?,
+            len,
+        })
+    }
+
+    /// The start of the mapping, aligned to a page.
+    pub fn as_ptr(&self) -> NonNull<u8> {
+        self.addr
+    }
+
+    /// The length of the mapping.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if the mapping is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.addr.as_ptr() as *mut libc::c_void, self.len); }
+    }
+}
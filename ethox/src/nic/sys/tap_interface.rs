@@ -6,7 +6,7 @@
 use std::os::unix::io::{RawFd, AsRawFd};
 
 use libc;
-use super::{now, Errno, FdResult, IoLenResult, LibcResult, ifreq};
+use super::{buffer_mtu, now, Errno, FdResult, IoLenResult, LibcResult, ifreq};
 
 use crate::nic::{self, Capabilities, Device, Packet, Personality};
 use crate::nic::common::{EnqueueFlag, PacketInfo};
@@ -45,11 +45,17 @@ pub struct TapInterfaceDesc {
 /// operation. But it arguably could instead simply yield no buffer in any rx-tx block while the
 /// buffer is already in-use. However, this implementation was slightly simpler and tap interface
 /// is not the main use case. Patches are accepted.
+///
+/// The maximum transmission unit of the packets is that of the interface, limited to frames that
+/// fit into the buffer. For jumbo frames, raise the unit of the interface and provide a buffer for
+/// the ethernet header and the unit.
 #[derive(Debug)]
 pub struct TapInterface<C> {
     inner: TapInterfaceDesc,
     buffer: Partial<C>,
     last_err: Option<Errno>,
+    /// The maximum transmission unit, queried from the interface.
+    mtu: usize,
 }
 
 enum Received {
@@ -113,7 +119,7 @@ impl TapInterfaceDesc {
 
         FdResult(lower).errno()?;
 
-        let mtu = self.ifreq.get_mtu(self.lower)
+        let mtu = self.ifreq.get_mtu(lower)
             .map(|mtu| mtu as usize);
 
         unsafe { libc::close(lower); }
@@ -159,10 +165,12 @@ impl<C: PayloadMut> TapInterface<C> {
         buffer: C,
     ) -> Result<Self, Errno> {
         inner.attach_interface()?;
+        let mtu = buffer_mtu(inner.interface_mtu().ok(), buffer.payload().len());
         Ok(TapInterface {
             inner,
             buffer: Partial::new(buffer),
             last_err: None,
+            mtu,
         })
     }
 
@@ -209,12 +217,20 @@ impl<C: PayloadMut> TapInterface<C> {
         as_nic
     }
 
-    fn current_info() -> PacketInfo {
+    fn current_info(&self) -> PacketInfo {
         PacketInfo {
             timestamp: now().unwrap(),
-            capabilities: Capabilities::no_support(),
+            capabilities: self.capabilities(),
+            hardware_timestamp: None,
         }
     }
+
+    /// The capabilities of all packets, only the maximum transmission unit is known.
+    fn capabilities(&self) -> Capabilities {
+        let mut capabilities = Capabilities::no_support();
+        *capabilities.mtu_mut() = Some(self.mtu);
+        capabilities
+    }
 }
 
 impl Drop for TapInterfaceDesc {
@@ -232,13 +248,15 @@ impl<C: PayloadMut> Device for TapInterface<C> {
     /// Could be dynamically configured but the optimizer and the user is likely happier if the
     /// implementation does not take advantage of this fact.
     fn personality(&self) -> Personality {
-        Personality::baseline()
+        let mut personality = Personality::baseline();
+        *personality.capabilities_mut() = self.capabilities();
+        personality
     }
 
     fn tx(&mut self, _: usize, mut sender: impl nic::Send<Self::Handle, Self::Payload>)
         -> nic::Result<usize>
     {
-        let mut handle = EnqueueFlag::set_true(Self::current_info());
+        let mut handle = EnqueueFlag::set_true(self.current_info());
         self.recycle();
         sender.send(Packet {
             handle: &mut handle,
@@ -262,7 +280,7 @@ impl<C: PayloadMut> Device for TapInterface<C> {
             Received::NoData => return Ok(0),
         }
 
-        let mut handle = EnqueueFlag::set_true(Self::current_info());
+        let mut handle = EnqueueFlag::set_true(self.current_info()).allow_retain();
         receptor.receive(Packet {
             handle: &mut handle,
             payload: &mut self.buffer,
@@ -0,0 +1,326 @@
+#[cfg(feature = "std")]
+use std::os::unix::io::{RawFd, AsRawFd};
+use core::mem;
+
+use libc;
+use super::{now, Errno, FdResult, IoLenResult, LibcResult};
+use crate::nic::l3::{EthernetTunnel, HEADER_LEN};
+
+use crate::nic::{self, Capabilities, Device, Packet, Personality};
+use crate::nic::common::{EnqueueFlag, PacketInfo};
+use crate::managed::Partial;
+use crate::wire::{EthernetAddress, EthernetProtocol, PayloadMut};
+
+/// A static descriptor for a macOS `utun` tunnel interface.
+///
+/// Contains the file descriptor of the kernel control socket. This offers the raw methods for
+/// reading and writing ip packets, each prefixed with its four byte protocol family, but does not
+/// encapsulate an actual `nic::Device`. Wrap it in a [`Utun`] with a buffer for this.
+///
+/// [`Utun`]: struct.Utun.html
+#[derive(Debug)]
+pub struct UtunDesc {
+    lower: libc::c_int,
+}
+
+/// A utun interface with buffer, usable as a network device.
+///
+/// The tunnel transports bare ip packets. For use with the ethernet layer, the device emulates a
+/// point-to-point link to a single peer: incoming packets are prefixed with an ethernet header
+/// addressed from the peer to the configured hardware address, outgoing frames are stripped of
+/// theirs, and arp requests for any address are answered with the hardware address of the peer.
+/// Other protocols are silently dropped. The buffer must consequently be large enough for the
+/// interface mtu and a 14 byte ethernet header.
+///
+/// Like the [`TapInterface`], the `nic::Device` implementation always sends and receives at most
+/// one buffer at a time.
+///
+/// [`TapInterface`]: struct.TapInterface.html
+#[derive(Debug)]
+pub struct Utun<C> {
+    inner: UtunDesc,
+    buffer: Partial<C>,
+    tunnel: EthernetTunnel,
+    last_err: Option<Errno>,
+}
+
+enum Received {
+    NoData,
+    Ok,
+    Err(crate::layer::Error),
+}
+
+#[cfg(feature = "std")]
+impl AsRawFd for UtunDesc {
+    fn as_raw_fd(&self) -> RawFd {
+        self.lower
+    }
+}
+
+#[cfg(feature = "std")]
+impl<C> AsRawFd for Utun<C> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+static UTUN_CONTROL_NAME: &[u8] = b"com.apple.net.utun_control";
+
+/// Length of the protocol family prefix of each packet.
+const FAMILY_LEN: usize = 4;
+
+impl UtunDesc {
+    /// Create a new tunnel interface.
+    ///
+    /// With a `unit` the interface is called `utun<unit>` which must not yet exist, otherwise the
+    /// kernel chooses the next free unit. Query the chosen one with [`unit`].
+    ///
+    /// [`unit`]: #method.unit
+    pub fn new(unit: Option<u32>) -> Result<UtunDesc, Errno> {
+        let lower = unsafe {
+            libc::socket(libc::PF_SYSTEM, libc::SOCK_DGRAM, libc::SYSPROTO_CONTROL)
+        };
+
+        FdResult(lower).errno()?;
+        // Take ownership of the socket.
+        let desc = UtunDesc { lower };
+
+        let mut info: libc::ctl_info = unsafe { mem::zeroed() };
+        for (i, byte) in UTUN_CONTROL_NAME.iter().enumerate() {
+            info.ctl_name[i] = *byte as libc::c_char;
+        }
+
+        let res = unsafe { libc::ioctl(lower, libc::CTLIOCGINFO, &mut info) };
+        FdResult(res).errno()?;
+
+        let mut addr: libc::sockaddr_ctl = unsafe { mem::zeroed() };
+        addr.sc_len = mem::size_of::<libc::sockaddr_ctl>() as libc::c_uchar;
+        addr.sc_family = libc::AF_SYSTEM as libc::c_uchar;
+        addr.ss_sysaddr = libc::AF_SYS_CONTROL as u16;
+        addr.sc_id = info.ctl_id;
+        // Unit 0 lets the kernel choose, others are offset by one.
+        addr.sc_unit = unit.map_or(0, |unit| unit + 1);
+
+        let res = unsafe {
+            libc::connect(
+                lower,
+                &addr as *const libc::sockaddr_ctl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_ctl>() as libc::socklen_t)
+        };
+        FdResult(res).errno()?;
+
+        let flags = unsafe { libc::fcntl(lower, libc::F_GETFL) };
+        FdResult(flags).errno()?;
+        let res = unsafe { libc::fcntl(lower, libc::F_SETFL, flags | libc::O_NONBLOCK) };
+        FdResult(res).errno()?;
+
+        Ok(desc)
+    }
+
+    /// Get the unit of the interface, which is named `utun<unit>`.
+    pub fn unit(&self) -> Result<u32, Errno> {
+        let mut addr: libc::sockaddr_ctl = unsafe { mem::zeroed() };
+        let mut len = mem::size_of::<libc::sockaddr_ctl>() as libc::socklen_t;
+        let res = unsafe {
+            libc::getpeername(
+                self.lower,
+                &mut addr as *mut libc::sockaddr_ctl as *mut libc::sockaddr,
+                &mut len)
+        };
+        FdResult(res).errno()?;
+        Ok(addr.sc_unit - 1)
+    }
+
+    /// Receive a single packet, prefixed with its protocol family.
+    pub fn recv(&mut self, buffer: &mut [u8]) -> Result<usize, Errno> {
+        let len = unsafe {
+            libc::read(
+                self.lower,
+                buffer.as_mut_ptr() as *mut libc::c_void,
+                buffer.len())
+        };
+        IoLenResult(len).errno()?;
+        Ok(len as usize)
+    }
+
+    /// Send a single packet, prefixed with its protocol family.
+    pub fn send(&mut self, buffer: &[u8]) -> Result<usize, Errno> {
+        let len = unsafe {
+            libc::write(
+                self.lower,
+                buffer.as_ptr() as *const libc::c_void,
+                buffer.len())
+        };
+        IoLenResult(len).errno()?;
+        Ok(len as usize)
+    }
+}
+
+impl<C: PayloadMut> Utun<C> {
+    /// Create a tunnel interface with one buffer for packets.
+    ///
+    /// The hardware address should be the one configured for the ethernet layer.
+    pub fn new(unit: Option<u32>, hardware_addr: EthernetAddress, buffer: C)
+        -> Result<Self, Errno>
+    {
+        let inner = UtunDesc::new(unit)?;
+        Ok(Self::with_descriptor(inner, hardware_addr, buffer))
+    }
+
+    /// Wrap an existing descriptor with a buffer into a device.
+    pub fn with_descriptor(inner: UtunDesc, hardware_addr: EthernetAddress, buffer: C) -> Self {
+        Utun {
+            inner,
+            buffer: Partial::new(buffer),
+            tunnel: EthernetTunnel::new(hardware_addr, EthernetTunnel::PEER_ADDR),
+            last_err: None,
+        }
+    }
+
+    /// The hardware address of the emulated peer.
+    pub fn peer_addr(&self) -> EthernetAddress {
+        self.tunnel.peer_addr()
+    }
+
+    /// Get the underlying descriptor.
+    pub fn descriptor(&self) -> &UtunDesc {
+        &self.inner
+    }
+
+    /// Take the last io error returned by the OS.
+    pub fn last_err(&mut self) -> Option<Errno> {
+        self.last_err.take()
+    }
+
+    /// Resize the partial buffer to its full length.
+    fn recycle(&mut self) {
+        let length = self.buffer
+            .inner()
+            .payload()
+            .as_slice()
+            .len();
+        self.buffer.set_len_unchecked(length);
+    }
+
+    /// Send the current buffer as a frame.
+    fn send(&mut self) -> nic::Result<()> {
+        let frame = self.buffer.payload_mut().as_mut_slice();
+        let family = match self.tunnel.transmit(frame) {
+            Some(EthernetProtocol::Ipv4) => libc::AF_INET,
+            Some(EthernetProtocol::Ipv6) => libc::AF_INET6,
+            _ => return Ok(()),
+        };
+
+        // Overwrite the end of the ethernet header with the protocol family.
+        let packet = &mut frame[HEADER_LEN - FAMILY_LEN..];
+        packet[..FAMILY_LEN].copy_from_slice(&(family as u32).to_be_bytes());
+
+        match self.inner.send(packet) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(self.store_err(err))
+        }
+    }
+
+    fn recv(&mut self) -> Received {
+        self.recycle();
+        let frame = self.buffer.payload_mut().as_mut_slice();
+        if let Some(len) = self.tunnel.answer_arp(frame) {
+            self.buffer.set_len_unchecked(len);
+            return Received::Ok;
+        }
+
+        if frame.len() <= HEADER_LEN {
+            return Received::Err(crate::layer::Error::BadSize);
+        }
+
+        // Receive such that the protocol family overlaps the end of the ethernet header.
+        let result = self.inner.recv(&mut frame[HEADER_LEN - FAMILY_LEN..]);
+        let len = match result {
+            Ok(len) if len >= FAMILY_LEN => len,
+            Ok(_) => return Received::NoData,
+            Err(ref err) if err.0 == libc::EWOULDBLOCK => return Received::NoData,
+            Err(err) => return Received::Err(self.store_err(err)),
+        };
+
+        let mut family = [0; FAMILY_LEN];
+        family.copy_from_slice(&frame[HEADER_LEN - FAMILY_LEN..HEADER_LEN]);
+        let ethertype = match u32::from_be_bytes(family) as libc::c_int {
+            libc::AF_INET => EthernetProtocol::Ipv4,
+            libc::AF_INET6 => EthernetProtocol::Ipv6,
+            _ => return Received::NoData,
+        };
+
+        self.tunnel.frame_ip(frame, ethertype);
+        self.buffer.set_len_unchecked(len - FAMILY_LEN + HEADER_LEN);
+        Received::Ok
+    }
+
+    fn store_err(&mut self, err: Errno) -> crate::layer::Error {
+        let as_nic = crate::layer::Error::Illegal;
+        self.last_err = Some(err);
+        as_nic
+    }
+
+    fn current_info() -> PacketInfo {
+        PacketInfo {
+            timestamp: now().unwrap(),
+            capabilities: Capabilities::no_support(),
+            hardware_timestamp: None,
+        }
+    }
+}
+
+impl Drop for UtunDesc {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.lower); }
+    }
+}
+
+impl<C: PayloadMut> Device for Utun<C> {
+    type Handle = EnqueueFlag;
+    type Payload = Partial<C>;
+
+    fn personality(&self) -> Personality {
+        Personality::baseline()
+    }
+
+    fn tx(&mut self, _: usize, mut sender: impl nic::Send<Self::Handle, Self::Payload>)
+        -> nic::Result<usize>
+    {
+        let mut handle = EnqueueFlag::set_true(Self::current_info());
+        self.recycle();
+        sender.send(Packet {
+            handle: &mut handle,
+            payload: &mut self.buffer,
+        });
+
+        if handle.was_sent() {
+            self.send()?;
+            Ok(1)
+        } else {
+            Ok(0)
+        }
+    }
+
+    fn rx(&mut self, _: usize, mut receptor: impl nic::Recv<Self::Handle, Self::Payload>)
+        -> nic::Result<usize>
+    {
+        match self.recv() {
+            Received::Ok => (),
+            Received::Err(err) => return Err(err),
+            Received::NoData => return Ok(0),
+        }
+
+        let mut handle = EnqueueFlag::set_true(Self::current_info()).allow_retain();
+        receptor.receive(Packet {
+            handle: &mut handle,
+            payload: &mut self.buffer,
+        });
+
+        if handle.was_sent() {
+            self.send()?;
+        }
+
+        Ok(1)
+    }
+}
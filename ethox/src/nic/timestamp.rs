@@ -0,0 +1,83 @@
+use core::fmt;
+
+use crate::wire::PtpTimestamp;
+
+/// A time stamp taken by the clock of the network hardware.
+///
+/// Devices with hardware timestamping sample their own clock when a frame passes the physical
+/// layer. This clock is unrelated to the [`Instant`] of the packet info and is usually kept in
+/// the PTP timescale by a time synchronization daemon, with nanosecond resolution.
+///
+/// [`Instant`]: ../time/struct.Instant.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HardwareTimestamp {
+    /// Whole seconds since the epoch of the hardware clock.
+    pub seconds: u64,
+    /// Nanoseconds within the second, always less than `1_000_000_000`.
+    pub nanos: u32,
+}
+
+/// The hardware timestamp of a transmitted packet.
+///
+/// Reported by [`Device::tx_timestamp`] for packets whose timestamp was requested with
+/// [`Handle::request_tx_timestamp`].
+///
+/// [`Device::tx_timestamp`]: trait.Device.html#method.tx_timestamp
+/// [`Handle::request_tx_timestamp`]: trait.Handle.html#method.request_tx_timestamp
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TxTimestamp {
+    /// The identifier chosen when requesting the timestamp.
+    pub id: u32,
+    /// The time at which the packet left the device.
+    pub timestamp: HardwareTimestamp,
+}
+
+impl HardwareTimestamp {
+    const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+    /// Create a timestamp from a number of nanoseconds since the epoch.
+    pub fn from_nanos(nanos: u64) -> Self {
+        HardwareTimestamp {
+            seconds: nanos / Self::NANOS_PER_SEC,
+            nanos: (nanos % Self::NANOS_PER_SEC) as u32,
+        }
+    }
+
+    /// The total number of nanoseconds since the epoch.
+    ///
+    /// Saturates for timestamps more than 584 years after the epoch.
+    pub fn total_nanos(self) -> u64 {
+        self.seconds
+            .saturating_mul(Self::NANOS_PER_SEC)
+            .saturating_add(self.nanos.into())
+    }
+
+    /// The signed difference to an earlier timestamp, in nanoseconds.
+    pub fn nanos_since(self, earlier: HardwareTimestamp) -> i64 {
+        self.total_nanos().wrapping_sub(earlier.total_nanos()) as i64
+    }
+}
+
+impl fmt::Display for HardwareTimestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{:09}", self.seconds, self.nanos)
+    }
+}
+
+impl From<HardwareTimestamp> for PtpTimestamp {
+    fn from(timestamp: HardwareTimestamp) -> Self {
+        PtpTimestamp {
+            seconds: timestamp.seconds,
+            nanos: timestamp.nanos,
+        }
+    }
+}
+
+impl From<PtpTimestamp> for HardwareTimestamp {
+    fn from(timestamp: PtpTimestamp) -> Self {
+        HardwareTimestamp {
+            seconds: timestamp.seconds,
+            nanos: timestamp.nanos,
+        }
+    }
+}
@@ -0,0 +1,924 @@
+//! A driver for virtio network devices.
+//!
+//! Virtio is the paravirtualized device interface of most hypervisors. This module implements the
+//! driver side of a virtio-net device with split virtqueues, as needed in a guest kernel, a
+//! unikernel or on bare-metal. The transport over which the device is discovered and configured,
+//! PCI or memory mapped registers, is left to an implementation of [`Transport`]. Only devices
+//! offering `VIRTIO_F_VERSION_1` are supported, legacy devices are refused.
+//!
+//! The checksum offloads of the device are negotiated and reflected in the capabilities of the
+//! packets. With `VIRTIO_NET_F_CSUM` the device completes the tcp and udp checksums of sent
+//! packets, and with `VIRTIO_NET_F_GUEST_CSUM` it flags received packets whose checksum it has
+//! already validated. Segmentation offloads and mergeable receive buffers are not negotiated,
+//! each receive buffer must hold an entire frame.
+//!
+//! [`Transport`]: trait.Transport.html
+#![allow(unsafe_code)]
+use core::marker::PhantomData;
+use core::sync::atomic::{fence, Ordering};
+use core::{mem, ptr};
+
+use byteorder::{ByteOrder, LittleEndian, NetworkEndian};
+
+use crate::alloc::vec::Vec;
+use crate::layer::{Error, Result};
+use crate::managed::Partial;
+use crate::time::Instant;
+use crate::wire::{EthernetAddress, EthernetProtocol, IpAddress, IpProtocol, Checksum};
+use crate::wire::{ethernet_frame, ipv4_packet, ipv6_packet, Payload, PayloadMut};
+use crate::wire::ip::checksum;
+
+use super::common::{EnqueueFlag, PacketInfo};
+use super::{Capabilities, Device, Packet, Personality, Protocol, Recv, Send};
+
+/// The device completes the checksum of sent packets.
+pub const VIRTIO_NET_F_CSUM: u64 = 1 << 0;
+/// The device validates the checksum of received packets.
+pub const VIRTIO_NET_F_GUEST_CSUM: u64 = 1 << 1;
+/// The device has a hardware address in its configuration.
+pub const VIRTIO_NET_F_MAC: u64 = 1 << 5;
+/// The device reports the link status in its configuration.
+pub const VIRTIO_NET_F_STATUS: u64 = 1 << 16;
+/// The device complies with version 1 of the specification.
+pub const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+
+/// All features the driver accepts.
+const DRIVER_FEATURES: u64 = VIRTIO_NET_F_CSUM
+    | VIRTIO_NET_F_GUEST_CSUM
+    | VIRTIO_NET_F_MAC
+    | VIRTIO_NET_F_STATUS
+    | VIRTIO_F_VERSION_1;
+
+const STATUS_ACKNOWLEDGE: u8 = 1;
+const STATUS_DRIVER: u8 = 2;
+const STATUS_DRIVER_OK: u8 = 4;
+const STATUS_FEATURES_OK: u8 = 8;
+const STATUS_FAILED: u8 = 128;
+
+const DESC_F_NEXT: u16 = 1;
+const DESC_F_WRITE: u16 = 2;
+const AVAIL_F_NO_INTERRUPT: u16 = 1;
+const USED_F_NO_NOTIFY: u16 = 1;
+
+const HDR_F_NEEDS_CSUM: u8 = 1;
+const HDR_F_DATA_VALID: u8 = 2;
+
+/// The length of the header preceding each frame.
+const HEADER_LEN: usize = 12;
+
+const CONFIG_MAC: usize = 0;
+const CONFIG_STATUS: usize = 6;
+const STATUS_LINK_UP: u16 = 1;
+
+const RECEIVE_QUEUE: u16 = 0;
+const TRANSMIT_QUEUE: u16 = 1;
+
+/// The transport through which a virtio device is configured.
+///
+/// This corresponds to the common configuration of a virtio device, accessed either through
+/// PCI capabilities or memory mapped registers. The methods correspond directly to the fields of
+/// the same name in the specification.
+///
+/// # Safety
+///
+/// The device writes to memory by the addresses returned from `dma_address`. These must be the
+/// addresses at which the device accesses the memory of the driver. Setting the status to zero
+/// must reset the device, after which it no longer accesses any memory of the driver.
+pub unsafe trait Transport {
+    /// Read the features offered by the device.
+    fn device_features(&mut self) -> u64;
+
+    /// Write the features accepted by the driver.
+    fn set_driver_features(&mut self, features: u64);
+
+    /// Read the device status.
+    fn status(&mut self) -> u8;
+
+    /// Write the device status.
+    fn set_status(&mut self, status: u8);
+
+    /// The maximum size of a queue, or `0` if the queue is not available.
+    fn max_queue_size(&mut self, queue: u16) -> u16;
+
+    /// Configure and enable a queue.
+    fn setup_queue(&mut self, queue: u16, size: u16, rings: QueueAddresses);
+
+    /// Notify the device of new buffers in a queue.
+    fn notify(&mut self, queue: u16);
+
+    /// Read from the device specific configuration.
+    fn read_config(&self, offset: usize, data: &mut [u8]);
+
+    /// The address of the driver memory as seen by the device.
+    ///
+    /// The default implementation uses the address itself, for identity mapped memory.
+    fn dma_address(&self, addr: *const u8) -> u64 {
+        addr as usize as u64
+    }
+}
+
+/// The device addresses of the parts of a virtqueue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueueAddresses {
+    /// The descriptor table.
+    pub descriptors: u64,
+    /// The available ring, written by the driver.
+    pub driver: u64,
+    /// The used ring, written by the device.
+    pub device: u64,
+}
+
+/// A virtio network device, usable as a network device.
+///
+/// All buffers are divided into receive and transmit buffers at construction. Each buffer is
+/// referenced by a chain of two descriptors, one for the header of the frame and one for the
+/// frame itself. All idle receive buffers are available to the device at all times and the
+/// `nic::Device` implementation hands out those the device has filled. Packets to be sent, either
+/// from transmit buffers or when answering a received packet, are made available to the device
+/// with a single notification per call to `rx` or `tx`. An answer is copied to an idle transmit
+/// buffer, if there is none the handle refuses to queue it.
+///
+/// The memory of the queues and the buffers must be accessible to the device. The device is
+/// reset when the driver is dropped. There is no clock in the device, set the timestamp of the
+/// packets with [`set_current_time`] before each call.
+///
+/// [`set_current_time`]: #method.set_current_time
+#[derive(Debug)]
+pub struct VirtioNet<'a, T: Transport, C> {
+    transport: T,
+    receive_queue: Virtqueue<'a>,
+    transmit_queue: Virtqueue<'a>,
+    /// The header of each buffer is part of the element, so the vector must never reallocate.
+    buffers: Vec<Buffer<C>>,
+    /// The number of buffers at the start of `buffers` used for receiving.
+    receive: usize,
+    features: u64,
+    timestamp: Instant,
+}
+
+#[derive(Debug)]
+struct Buffer<C> {
+    header: [u8; HEADER_LEN],
+    payload: Partial<C>,
+    state: State,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Idle,
+    Device,
+}
+
+/// A split virtqueue in memory provided by the caller.
+#[derive(Debug)]
+struct Virtqueue<'a> {
+    size: u16,
+    descriptors: *mut Descriptor,
+    /// The flags, index and ring of the available ring.
+    avail: *mut u16,
+    /// The flags and index of the used ring, followed by its elements.
+    used: *mut u16,
+    /// The index of the next entry in the available ring, not yet published.
+    next_avail: u16,
+    /// The index of the next entry in the used ring to be processed.
+    last_used: u16,
+    memory: PhantomData<&'a mut [u8]>,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct UsedElem {
+    id: u32,
+    len: u32,
+}
+
+/// The memory required for a queue of a number of buffers.
+///
+/// The memory for each queue passed to [`VirtioNet::new`] must be at least this large and aligned
+/// to 16 bytes.
+///
+/// [`VirtioNet::new`]: struct.VirtioNet.html#method.new
+pub fn queue_memory_size(buffers: usize) -> usize {
+    Virtqueue::layout(queue_size(buffers)).2
+}
+
+/// The size of a queue with two descriptors for each buffer.
+fn queue_size(buffers: usize) -> usize {
+    (2*buffers).max(1).next_power_of_two()
+}
+
+impl<'a, T: Transport, C: PayloadMut> VirtioNet<'a, T, C> {
+    /// Initialize the device with memory for the queues and buffers for receiving and sending.
+    ///
+    /// Negotiates the features, sets up the receive and transmit queue and makes all receive
+    /// buffers available to the device. The status of the device is set to failed when the device
+    /// does not comply with version 1 or refuses the features, and the call returns `Illegal`.
+    /// Queue memory that is too small or misaligned results in `BadSize`, and a device queue too
+    /// small for the number of buffers in `Exhausted`.
+    pub fn new<R, S>(
+        mut transport: T,
+        receive_memory: &'a mut [u8],
+        transmit_memory: &'a mut [u8],
+        receive: R,
+        transmit: S,
+    ) -> Result<Self>
+    where
+        R: IntoIterator<Item=C>,
+        S: IntoIterator<Item=C>,
+    {
+        let mut buffers: Vec<Buffer<C>> = receive.into_iter()
+            .map(Buffer::new)
+            .collect();
+        let receive = buffers.len();
+        buffers.extend(transmit.into_iter().map(Buffer::new));
+        let transmit = buffers.len() - receive;
+
+        let features = match Self::negotiate(&mut transport) {
+            Ok(features) => features,
+            Err(err) => {
+                transport.set_status(STATUS_FAILED);
+                return Err(err);
+            },
+        };
+
+        let queues = Self::queues(
+            &mut transport, receive_memory, receive, transmit_memory, transmit);
+        let (receive_queue, transmit_queue) = match queues {
+            Ok(queues) => queues,
+            Err(err) => {
+                transport.set_status(STATUS_FAILED);
+                return Err(err);
+            },
+        };
+
+        let mut device = VirtioNet {
+            transport,
+            receive_queue,
+            transmit_queue,
+            buffers,
+            receive,
+            features,
+            timestamp: Instant::ZERO,
+        };
+
+        for idx in 0..device.receive {
+            device.queue_read(idx);
+        }
+
+        let status = device.transport.status();
+        device.transport.set_status(status | STATUS_DRIVER_OK);
+        device.flush_receive();
+        Ok(device)
+    }
+
+    /// The features negotiated with the device.
+    pub fn features(&self) -> u64 {
+        self.features
+    }
+
+    /// The hardware address of the device, if it has one.
+    pub fn hardware_addr(&self) -> Option<EthernetAddress> {
+        if self.features & VIRTIO_NET_F_MAC == 0 {
+            return None;
+        }
+
+        let mut addr = [0; 6];
+        self.transport.read_config(CONFIG_MAC, &mut addr);
+        Some(EthernetAddress(addr))
+    }
+
+    /// Update the timestamp on all future packets.
+    pub fn set_current_time(&mut self, instant: Instant) {
+        self.timestamp = instant;
+    }
+
+    /// Get a reference to the transport.
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Get a mutable reference to the transport.
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    fn negotiate(transport: &mut T) -> Result<u64> {
+        // See: https://docs.oasis-open.org/virtio/virtio/v1.1/virtio-v1.1.html#x1-920001
+        transport.set_status(0);
+        transport.set_status(STATUS_ACKNOWLEDGE);
+        transport.set_status(STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+
+        let features = transport.device_features() & DRIVER_FEATURES;
+        if features & VIRTIO_F_VERSION_1 == 0 {
+            return Err(Error::Illegal);
+        }
+
+        transport.set_driver_features(features);
+        transport.set_status(STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_FEATURES_OK);
+        if transport.status() & STATUS_FEATURES_OK == 0 {
+            return Err(Error::Illegal);
+        }
+
+        Ok(features)
+    }
+
+    fn queues(
+        transport: &mut T,
+        receive_memory: &'a mut [u8],
+        receive: usize,
+        transmit_memory: &'a mut [u8],
+        transmit: usize,
+    ) -> Result<(Virtqueue<'a>, Virtqueue<'a>)> {
+        let receive = Virtqueue::new(RECEIVE_QUEUE, receive_memory, receive, transport)?;
+        let transmit = Virtqueue::new(TRANSMIT_QUEUE, transmit_memory, transmit, transport)?;
+        Ok((receive, transmit))
+    }
+
+    /// The capabilities of packets, with received ones possibly validated by the device.
+    fn capabilities(&self, validated: bool) -> Capabilities {
+        let by_nic = |offload: bool| if offload { Checksum::Ignored } else { Checksum::Manual };
+        let mut protocol = Protocol::no_support();
+        *protocol.tx_checksum_mut() = by_nic(self.features & VIRTIO_NET_F_CSUM != 0);
+        *protocol.rx_checksum_mut() = by_nic(validated);
+
+        let mut capabilities = Capabilities::no_support();
+        *capabilities.udp_mut() = protocol.into();
+        *capabilities.tcp_mut() = protocol.into();
+        capabilities
+    }
+
+    fn info(&self, header: Option<&[u8; HEADER_LEN]>) -> PacketInfo {
+        let validated = header.is_some_and(|header| {
+            self.features & VIRTIO_NET_F_GUEST_CSUM != 0
+                && header[0] & (HDR_F_NEEDS_CSUM | HDR_F_DATA_VALID) != 0
+        });
+
+        PacketInfo {
+            timestamp: self.timestamp,
+            capabilities: self.capabilities(validated),
+            hardware_timestamp: None,
+        }
+    }
+
+    /// Reap the sent buffers.
+    fn reap(&mut self) {
+        while let Some((id, _)) = self.transmit_queue.pop_used() {
+            let idx = self.receive + usize::from(id / 2);
+            if let Some(buffer) = self.buffers.get_mut(idx) {
+                buffer.state = State::Idle;
+            }
+        }
+    }
+
+    fn idle_transmit(&self) -> Option<usize> {
+        (self.receive..self.buffers.len())
+            .find(|&idx| self.buffers[idx].state == State::Idle)
+    }
+
+    fn queue_read(&mut self, idx: usize) {
+        let buffer = &mut self.buffers[idx];
+        buffer.recycle();
+        buffer.header = [0; HEADER_LEN];
+        let header = self.transport.dma_address(buffer.header.as_ptr());
+        let frame = buffer.payload.payload_mut().as_mut_slice();
+        let frame = (self.transport.dma_address(frame.as_ptr()), frame.len());
+        buffer.state = State::Device;
+
+        let head = (2*idx) as u16;
+        self.receive_queue.set_descriptor(head, header, HEADER_LEN, DESC_F_WRITE | DESC_F_NEXT);
+        self.receive_queue.set_descriptor(head + 1, frame.0, frame.1, DESC_F_WRITE);
+        self.receive_queue.push(head);
+    }
+
+    fn queue_write(&mut self, idx: usize) {
+        let offload = self.features & VIRTIO_NET_F_CSUM != 0;
+        let buffer = &mut self.buffers[idx];
+        buffer.header = [0; HEADER_LEN];
+        let frame = buffer.payload.payload_mut().as_mut_slice();
+        if let Some((start, offset)) = offload.then(|| partial_checksum(frame)).flatten() {
+            buffer.header[0] = HDR_F_NEEDS_CSUM;
+            LittleEndian::write_u16(&mut buffer.header[6..8], start);
+            LittleEndian::write_u16(&mut buffer.header[8..10], offset);
+        }
+
+        let header = self.transport.dma_address(buffer.header.as_ptr());
+        let frame = (self.transport.dma_address(frame.as_ptr()), frame.len());
+        buffer.state = State::Device;
+
+        let head = (2*(idx - self.receive)) as u16;
+        self.transmit_queue.set_descriptor(head, header, HEADER_LEN, DESC_F_NEXT);
+        self.transmit_queue.set_descriptor(head + 1, frame.0, frame.1, 0);
+        self.transmit_queue.push(head);
+    }
+
+    /// Copy the payload of a received buffer to a transmit buffer and queue it.
+    fn answer(&mut self, from: usize, to: usize) -> Result<()> {
+        let (receive, transmit) = self.buffers.split_at_mut(self.receive);
+        let frame = receive[from].payload.payload().as_slice();
+        let target = &mut transmit[to - self.receive];
+        target.recycle();
+        target.payload.resize(frame.len()).map_err(|_| Error::BadSize)?;
+        target.payload.payload_mut().as_mut_slice().copy_from_slice(frame);
+        self.queue_write(to);
+        Ok(())
+    }
+
+    fn flush_receive(&mut self) {
+        if self.receive_queue.publish() {
+            self.transport.notify(RECEIVE_QUEUE);
+        }
+    }
+
+    fn flush_transmit(&mut self) {
+        if self.transmit_queue.publish() {
+            self.transport.notify(TRANSMIT_QUEUE);
+        }
+    }
+}
+
+impl<C: PayloadMut> Buffer<C> {
+    fn new(payload: C) -> Self {
+        Buffer {
+            header: [0; HEADER_LEN],
+            payload: Partial::new(payload),
+            state: State::Idle,
+        }
+    }
+
+    /// Resize the partial buffer to its full length.
+    fn recycle(&mut self) {
+        let length = self.payload
+            .inner()
+            .payload()
+            .as_slice()
+            .len();
+        self.payload.set_len_unchecked(length);
+    }
+}
+
+impl<'a> Virtqueue<'a> {
+    /// The offsets of the available and used ring, and the total size.
+    fn layout(size: usize) -> (usize, usize, usize) {
+        let avail = mem::size_of::<Descriptor>()*size;
+        let used = (avail + 6 + 2*size + 3) & !3;
+        (avail, used, used + 6 + mem::size_of::<UsedElem>()*size)
+    }
+
+    fn new<T: Transport>(index: u16, memory: &'a mut [u8], buffers: usize, transport: &mut T)
+        -> Result<Self>
+    {
+        let size = queue_size(buffers);
+        let (avail, used, len) = Self::layout(size);
+        if memory.len() < len || memory.as_ptr().align_offset(16) != 0 {
+            return Err(Error::BadSize);
+        }
+
+        let max = transport.max_queue_size(index);
+        if max == 0 {
+            return Err(Error::Illegal);
+        }
+
+        if size > usize::from(max) {
+            return Err(Error::Exhausted);
+        }
+
+        for byte in memory[..len].iter_mut() {
+            *byte = 0;
+        }
+
+        let base = memory.as_mut_ptr();
+        let queue = Virtqueue {
+            size: size as u16,
+            descriptors: base as *mut Descriptor,
+            avail: base.wrapping_add(avail) as *mut u16,
+            used: base.wrapping_add(used) as *mut u16,
+            next_avail: 0,
+            last_used: 0,
+            memory: PhantomData,
+        };
+
+        // The driver polls the device, it never waits for interrupts.
+        unsafe { ptr::write_volatile(queue.avail, AVAIL_F_NO_INTERRUPT.to_le()) };
+
+        transport.setup_queue(index, queue.size, QueueAddresses {
+            descriptors: transport.dma_address(base),
+            driver: transport.dma_address(base.wrapping_add(avail)),
+            device: transport.dma_address(base.wrapping_add(used)),
+        });
+
+        Ok(queue)
+    }
+
+    fn set_descriptor(&mut self, idx: u16, addr: u64, len: usize, flags: u16) {
+        assert!(idx < self.size);
+        let descriptor = Descriptor {
+            addr: addr.to_le(),
+            len: (len as u32).to_le(),
+            flags: flags.to_le(),
+            next: (idx + 1).to_le(),
+        };
+        unsafe { ptr::write_volatile(self.descriptors.add(usize::from(idx)), descriptor) };
+    }
+
+    /// Add a descriptor chain to the available ring, without publishing it.
+    fn push(&mut self, head: u16) {
+        let slot = usize::from(self.next_avail % self.size);
+        unsafe { ptr::write_volatile(self.avail.add(2 + slot), head.to_le()) };
+        self.next_avail = self.next_avail.wrapping_add(1);
+    }
+
+    /// Publish the pushed descriptor chains, returning if the device must be notified.
+    fn publish(&mut self) -> bool {
+        let published = unsafe { u16::from_le(ptr::read_volatile(self.avail.add(1))) };
+        if published == self.next_avail {
+            return false;
+        }
+
+        // The descriptors must be visible before the index.
+        fence(Ordering::SeqCst);
+        unsafe { ptr::write_volatile(self.avail.add(1), self.next_avail.to_le()) };
+        // And the index before reading the flags of the device.
+        fence(Ordering::SeqCst);
+        let flags = unsafe { u16::from_le(ptr::read_volatile(self.used)) };
+        flags & USED_F_NO_NOTIFY == 0
+    }
+
+    /// Take the next descriptor chain returned by the device.
+    fn pop_used(&mut self) -> Option<(u16, u32)> {
+        let idx = unsafe { u16::from_le(ptr::read_volatile(self.used.add(1))) };
+        if idx == self.last_used {
+            return None;
+        }
+
+        // The element must not be read before the index.
+        fence(Ordering::Acquire);
+        let slot = usize::from(self.last_used % self.size);
+        let elems = self.used.wrapping_add(2) as *const UsedElem;
+        let elem = unsafe { ptr::read_volatile(elems.add(slot)) };
+        self.last_used = self.last_used.wrapping_add(1);
+        Some((u32::from_le(elem.id) as u16, u32::from_le(elem.len)))
+    }
+}
+
+/// Prepare the checksum offload of a tcp or udp packet.
+///
+/// The device sums the packet from the start offset and adds it to the value at the checksum
+/// offset, which must hold the sum of the pseudo header. Returns both offsets, or `None` when the
+/// frame is not a tcp or udp packet. The checksum is then left to the layers.
+fn partial_checksum(frame: &mut [u8]) -> Option<(u16, u16)> {
+    let eth = ethernet_frame::new_checked(frame).ok()?;
+    let (header_len, protocol, src_addr, dst_addr, len) = match eth.ethertype() {
+        EthernetProtocol::Ipv4 => {
+            let ip = ipv4_packet::new_checked(eth.payload_slice()).ok()?;
+            if ip.more_frags() || ip.frag_offset() != 0 {
+                return None;
+            }
+            let header_len = usize::from(ip.header_len());
+            let len = usize::from(ip.total_len()).checked_sub(header_len)?;
+            (header_len, ip.protocol(), IpAddress::from(ip.src_addr()),
+                IpAddress::from(ip.dst_addr()), len)
+        },
+        EthernetProtocol::Ipv6 => {
+            let ip = ipv6_packet::new_checked(eth.payload_slice()).ok()?;
+            (ip.header_len(), ip.next_header(), IpAddress::from(ip.src_addr()),
+                IpAddress::from(ip.dst_addr()), usize::from(ip.payload_len()))
+        },
+        _ => return None,
+    };
+
+    let offset = match protocol {
+        IpProtocol::Tcp => 16,
+        IpProtocol::Udp => 6,
+        _ => return None,
+    };
+
+    let start = ethernet_frame::header_len() + header_len;
+    let field = frame.get_mut(start + offset..start + offset + 2)?;
+    let sum = checksum::pseudo_header(&src_addr, &dst_addr, protocol, len as u32);
+    NetworkEndian::write_u16(field, sum);
+    Some((start as u16, offset as u16))
+}
+
+impl<T: Transport, C: PayloadMut> Device for VirtioNet<'_, T, C> {
+    type Handle = EnqueueFlag;
+    type Payload = Partial<C>;
+
+    fn personality(&self) -> Personality {
+        let mut personality = Personality::baseline();
+        *personality.capabilities_mut() = self.capabilities(false);
+        *personality.rx_batch_mut() = self.receive.max(1);
+        *personality.tx_batch_mut() = (self.buffers.len() - self.receive).max(1);
+        personality
+    }
+
+    fn tx(&mut self, max: usize, mut sender: impl Send<Self::Handle, Self::Payload>)
+        -> Result<usize>
+    {
+        self.reap();
+
+        let info = self.info(None);
+        let mut count = 0;
+        for idx in self.receive..self.buffers.len() {
+            if count == max {
+                break;
+            }
+
+            if self.buffers[idx].state != State::Idle {
+                continue;
+            }
+
+            let mut handle = EnqueueFlag::set_true(info);
+            let buffer = &mut self.buffers[idx];
+            buffer.recycle();
+            sender.send(Packet {
+                handle: &mut handle,
+                payload: &mut buffer.payload,
+            });
+
+            if handle.was_sent() {
+                self.queue_write(idx);
+                count += 1;
+            }
+        }
+
+        self.flush_transmit();
+        Ok(count)
+    }
+
+    fn rx(&mut self, max: usize, mut receptor: impl Recv<Self::Handle, Self::Payload>)
+        -> Result<usize>
+    {
+        self.reap();
+
+        let mut count = 0;
+        let mut result = Ok(());
+        while count < max {
+            let (id, len) = match self.receive_queue.pop_used() {
+                Some(used) => used,
+                None => break,
+            };
+
+            let idx = usize::from(id / 2);
+            let info = self.info(Some(&self.buffers[idx].header));
+            let answer = self.idle_transmit();
+            let mut handle = match answer {
+                Some(_) => EnqueueFlag::set_true(info),
+                None => EnqueueFlag::not_possible(info),
+            }.allow_retain();
+
+            let buffer = &mut self.buffers[idx];
+            buffer.recycle();
+            let len = (len as usize).saturating_sub(HEADER_LEN).min(buffer.payload.len());
+            buffer.payload.set_len_unchecked(len);
+            receptor.receive(Packet {
+                handle: &mut handle,
+                payload: &mut buffer.payload,
+            });
+
+            if let (true, Some(to)) = (handle.was_sent(), answer) {
+                result = result.and(self.answer(idx, to));
+            }
+
+            self.queue_read(idx);
+            count += 1;
+        }
+
+        self.flush_receive();
+        self.flush_transmit();
+        result.map(|()| count)
+    }
+
+    fn link_up(&self) -> bool {
+        if self.features & VIRTIO_NET_F_STATUS == 0 {
+            return true;
+        }
+
+        let mut status = [0; 2];
+        self.transport.read_config(CONFIG_STATUS, &mut status);
+        LittleEndian::read_u16(&status) & STATUS_LINK_UP != 0
+    }
+}
+
+impl<T: Transport, C> Drop for VirtioNet<'_, T, C> {
+    fn drop(&mut self) {
+        // The device must no longer access the queues and buffers.
+        self.transport.set_status(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::boxed::Box;
+
+    use super::*;
+    use crate::layer::FnHandler;
+    use crate::nic::tests::LengthIo;
+    use crate::nic::Handle as _;
+
+    const MAC: [u8; 6] = [0x52, 0x54, 0, 0x12, 0x34, 0x56];
+
+    #[repr(align(16))]
+    struct Memory([u8; 1024]);
+
+    /// A device looping all sent frames back to the receive queue.
+    #[derive(Default)]
+    struct Loop {
+        features: u64,
+        accepted: u64,
+        status: u8,
+        queues: [Option<(u16, QueueAddresses)>; 2],
+        /// The available entries of the transmit queue already processed.
+        sent: u16,
+        /// The available entries of the receive queue already used.
+        filled: u16,
+        /// The header of the last sent frame.
+        header: [u8; HEADER_LEN],
+    }
+
+    unsafe impl Transport for Loop {
+        fn device_features(&mut self) -> u64 {
+            self.features
+        }
+
+        fn set_driver_features(&mut self, features: u64) {
+            self.accepted = features;
+        }
+
+        fn status(&mut self) -> u8 {
+            self.status
+        }
+
+        fn set_status(&mut self, status: u8) {
+            self.status = status;
+        }
+
+        fn max_queue_size(&mut self, _: u16) -> u16 {
+            256
+        }
+
+        fn setup_queue(&mut self, queue: u16, size: u16, rings: QueueAddresses) {
+            self.queues[usize::from(queue)] = Some((size, rings));
+        }
+
+        fn notify(&mut self, queue: u16) {
+            if queue == TRANSMIT_QUEUE {
+                unsafe { self.transmit() }
+            }
+        }
+
+        fn read_config(&self, offset: usize, data: &mut [u8]) {
+            let mut config = [0; 8];
+            config[..6].copy_from_slice(&MAC);
+            config[6] = STATUS_LINK_UP as u8;
+            data.copy_from_slice(&config[offset..][..data.len()]);
+        }
+    }
+
+    impl Loop {
+        unsafe fn transmit(&mut self) {
+            let (size, tx) = self.queues[1].unwrap();
+            while self.sent != read(tx.driver + 2) {
+                let head = read(tx.driver + 4 + 2*u64::from(self.sent % size));
+                let mut frame = vec![];
+                let mut desc = head;
+                loop {
+                    let base = tx.descriptors + 16*u64::from(desc);
+                    let addr = ptr::read_volatile(base as *const u64);
+                    let len = ptr::read_volatile((base + 8) as *const u32) as usize;
+                    frame.extend_from_slice(core::slice::from_raw_parts(addr as *const u8, len));
+                    if read(base + 12) & DESC_F_NEXT == 0 {
+                        break;
+                    }
+                    desc = read(base + 14);
+                }
+
+                self.header.copy_from_slice(&frame[..HEADER_LEN]);
+                self.receive(&frame);
+                Self::used(tx.device, size, head, 0);
+                self.sent = self.sent.wrapping_add(1);
+            }
+        }
+
+        unsafe fn receive(&mut self, mut data: &[u8]) {
+            let (size, rx) = self.queues[0].unwrap();
+            if self.filled == read(rx.driver + 2) {
+                return;
+            }
+
+            let head = read(rx.driver + 4 + 2*u64::from(self.filled % size));
+            let total = data.len();
+            let mut desc = head;
+            loop {
+                let base = rx.descriptors + 16*u64::from(desc);
+                let addr = ptr::read_volatile(base as *const u64);
+                let len = ptr::read_volatile((base + 8) as *const u32) as usize;
+                let len = len.min(data.len());
+                ptr::copy_nonoverlapping(data.as_ptr(), addr as *mut u8, len);
+                data = &data[len..];
+                if read(base + 12) & DESC_F_NEXT == 0 {
+                    break;
+                }
+                desc = read(base + 14);
+            }
+
+            Self::used(rx.device, size, head, total as u32);
+            self.filled = self.filled.wrapping_add(1);
+        }
+
+        unsafe fn used(ring: u64, size: u16, head: u16, len: u32) {
+            let idx = read(ring + 2);
+            let elem = (ring + 4 + 8*u64::from(idx % size)) as *mut u32;
+            ptr::write_volatile(elem, u32::from(head));
+            ptr::write_volatile(elem.add(1), len);
+            ptr::write_volatile((ring + 2) as *mut u16, idx.wrapping_add(1));
+        }
+    }
+
+    unsafe fn read(addr: u64) -> u16 {
+        ptr::read_volatile(addr as *const u16)
+    }
+
+    fn device(features: u64, memory: &mut [Memory; 2]) -> Result<VirtioNet<'_, Loop, Vec<u8>>> {
+        let transport = Loop {
+            features,
+            ..Loop::default()
+        };
+        let [rx, tx] = memory;
+        VirtioNet::new(transport, &mut rx.0, &mut tx.0,
+            vec![vec![0; 1514]; 4], vec![vec![0; 1514]; 4])
+    }
+
+    fn memory() -> Box<[Memory; 2]> {
+        Box::new([Memory([0; 1024]), Memory([0; 1024])])
+    }
+
+    #[test]
+    fn loopback() {
+        assert!(queue_memory_size(4) <= 1024);
+        let mut memory = memory();
+        let features = VIRTIO_F_VERSION_1 | VIRTIO_NET_F_MAC | VIRTIO_NET_F_STATUS | 1 << 40;
+        let mut device = device(features, &mut memory).unwrap();
+
+        assert_eq!(device.features(), features & !(1 << 40));
+        assert_eq!(device.transport().status & STATUS_DRIVER_OK, STATUS_DRIVER_OK);
+        assert_eq!(device.hardware_addr(), Some(EthernetAddress(MAC)));
+        assert!(device.link_up());
+
+        assert_eq!(device.tx(6, LengthIo), Ok(4));
+        // All transmit buffers are in use until reaped in the next call.
+        assert_eq!(device.rx(6, LengthIo), Ok(4));
+        assert_eq!(device.tx(2, LengthIo), Ok(2));
+        assert_eq!(device.rx(6, LengthIo), Ok(2));
+        assert_eq!(device.transport().header, [0; HEADER_LEN]);
+    }
+
+    #[test]
+    fn legacy_refused() {
+        let mut memory = memory();
+        assert_eq!(device(VIRTIO_NET_F_MAC, &mut memory).err(), Some(Error::Illegal));
+    }
+
+    #[test]
+    fn checksum_offload() {
+        let mut memory = memory();
+        let features = VIRTIO_F_VERSION_1 | VIRTIO_NET_F_CSUM | VIRTIO_NET_F_GUEST_CSUM;
+        let mut device = device(features, &mut memory).unwrap();
+        assert_ne!(device.personality().capabilities(), &Capabilities::no_support());
+
+        // An udp datagram from 10.0.0.1:1 to 10.0.0.2:2 without payload.
+        let mut udp = [0; 42];
+        udp[12..14].copy_from_slice(&[0x08, 0x00]);
+        udp[14..24].copy_from_slice(&[0x45, 0, 0, 28, 0, 0, 0, 0, 64, 17]);
+        udp[26..34].copy_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+        udp[34..40].copy_from_slice(&[0, 1, 0, 2, 0, 8]);
+
+        let sent = device.tx(1, FnHandler(|packet: Packet<EnqueueFlag, Partial<Vec<u8>>>| {
+            packet.payload.resize(udp.len()).unwrap();
+            packet.payload.payload_mut().as_mut_slice().copy_from_slice(&udp);
+            packet.handle.queue().unwrap();
+        }));
+        assert_eq!(sent, Ok(1));
+
+        let header = device.transport().header;
+        assert_eq!(header[0], HDR_F_NEEDS_CSUM);
+        assert_eq!(LittleEndian::read_u16(&header[6..8]), 34);
+        assert_eq!(LittleEndian::read_u16(&header[8..10]), 6);
+
+        // The looped frame is marked as needing a checksum, which the receiver need not check.
+        let received = device.rx(1, FnHandler(|packet: Packet<EnqueueFlag, Partial<Vec<u8>>>| {
+            let capabilities = packet.handle.info().capabilities();
+            assert_ne!(capabilities.udp(), Capabilities::no_support().udp());
+            assert_eq!(packet.payload.payload().as_slice().len(), 42);
+        }));
+        assert_eq!(received, Ok(1));
+    }
+}
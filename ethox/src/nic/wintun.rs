@@ -0,0 +1,459 @@
+//! A device on the Windows wintun driver.
+//!
+//! Wintun is a layer 3 tunnel driver. Its library, `wintun.dll`, must be distributed alongside
+//! the application. It is loaded at runtime so that no import library is required for building.
+//!
+//! The driver exchanges packets with the application through two rings in shared memory. Packets
+//! are received as pointers into the receive ring that are released afterwards, and sent by
+//! allocating space in the transmit ring before committing it. As with all tunnels the device
+//! emulates an ethernet link to a single peer towards the layers, see [`Wintun`] for details.
+//!
+//! [`Wintun`]: struct.Wintun.html
+#![allow(unsafe_code)]
+use core::{mem, ptr};
+use std::ffi::OsStr;
+use std::io;
+use std::os::windows::ffi::OsStrExt;
+use std::vec::Vec;
+
+use super::l3::{EthernetTunnel, HEADER_LEN};
+use super::{Capabilities, Device, Packet, Personality};
+use super::common::{EnqueueFlag, PacketInfo};
+use crate::layer;
+use crate::managed::Partial;
+use crate::time::Instant;
+use crate::wire::{EthernetAddress, EthernetProtocol, PayloadMut};
+
+type Handle = *mut core::ffi::c_void;
+type Module = *mut core::ffi::c_void;
+
+const ERROR_NO_MORE_ITEMS: i32 = 259;
+const ERROR_HANDLE_EOF: i32 = 38;
+const ERROR_BUFFER_OVERFLOW: i32 = 111;
+const LOAD_LIBRARY_SEARCH_APPLICATION_DIR: u32 = 0x200;
+const LOAD_LIBRARY_SEARCH_SYSTEM32: u32 = 0x800;
+
+/// The largest packet supported by the driver.
+pub const MAX_IP_PACKET_SIZE: usize = 0xffff;
+
+/// The smallest ring capacity supported by the driver.
+pub const MIN_RING_CAPACITY: u32 = 0x2_0000;
+
+/// The largest ring capacity supported by the driver.
+pub const MAX_RING_CAPACITY: u32 = 0x400_0000;
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn LoadLibraryExW(name: *const u16, file: Handle, flags: u32) -> Module;
+    fn GetProcAddress(module: Module, name: *const u8) -> *mut core::ffi::c_void;
+    fn FreeLibrary(module: Module) -> i32;
+    fn GetTickCount64() -> u64;
+}
+
+/// The entry points of a loaded `wintun.dll`.
+#[derive(Debug)]
+pub struct Library {
+    module: Module,
+    create_adapter: unsafe extern "system" fn(*const u16, *const u16, *const u8) -> Handle,
+    open_adapter: unsafe extern "system" fn(*const u16) -> Handle,
+    close_adapter: unsafe extern "system" fn(Handle),
+    start_session: unsafe extern "system" fn(Handle, u32) -> Handle,
+    end_session: unsafe extern "system" fn(Handle),
+    get_read_wait_event: unsafe extern "system" fn(Handle) -> Handle,
+    receive_packet: unsafe extern "system" fn(Handle, *mut u32) -> *mut u8,
+    release_receive_packet: unsafe extern "system" fn(Handle, *const u8),
+    allocate_send_packet: unsafe extern "system" fn(Handle, u32) -> *mut u8,
+    send_packet: unsafe extern "system" fn(Handle, *const u8),
+}
+
+/// A static descriptor for a session on a wintun adapter.
+///
+/// This offers the raw methods for receiving and sending ip packets through the rings but does
+/// not encapsulate an actual `nic::Device`. Wrap it in a [`Wintun`] with a buffer for this.
+///
+/// [`Wintun`]: struct.Wintun.html
+#[derive(Debug)]
+pub struct WintunDesc {
+    library: Library,
+    adapter: Handle,
+    session: Handle,
+}
+
+/// A wintun adapter with buffer, usable as a network device.
+///
+/// The tunnel transports bare ip packets. For use with the ethernet layer, the device emulates a
+/// point-to-point link to a single peer: incoming packets are prefixed with an ethernet header
+/// addressed from the peer to the configured hardware address, outgoing frames are stripped of
+/// theirs, and arp requests for any address are answered with the hardware address of the peer.
+/// Other protocols are silently dropped. The buffer must consequently be large enough for the
+/// interface mtu and a 14 byte ethernet header.
+///
+/// Since the header must be prepended, packets are copied between the rings and the buffer. The
+/// `nic::Device` implementation processes up to the requested number of packets per call, all
+/// available in the receive ring when receiving and as long as there is space in the transmit
+/// ring when sending. A full transmit ring results in `Exhausted`.
+///
+/// Uses the errno principle for storing the last underlying error on a failed operation.
+#[derive(Debug)]
+pub struct Wintun<C> {
+    inner: WintunDesc,
+    buffer: Partial<C>,
+    tunnel: EthernetTunnel,
+    last_err: Option<io::Error>,
+}
+
+enum Received {
+    NoData,
+    Ok,
+    Err(layer::Error),
+}
+
+/// Encode a string for the wide string arguments.
+fn wide(name: &str) -> Vec<u16> {
+    OsStr::new(name).encode_wide().chain(Some(0)).collect()
+}
+
+impl Library {
+    /// Load `wintun.dll` from the application directory or the system directory.
+    pub fn load() -> io::Result<Self> {
+        Self::load_from("wintun.dll")
+    }
+
+    /// Load the library from a specific path.
+    pub fn load_from(path: &str) -> io::Result<Self> {
+        let path = wide(path);
+        let module = unsafe {
+            LoadLibraryExW(
+                path.as_ptr(),
+                ptr::null_mut(),
+                LOAD_LIBRARY_SEARCH_APPLICATION_DIR | LOAD_LIBRARY_SEARCH_SYSTEM32)
+        };
+
+        if module.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        let library = unsafe { Self::resolve(module) };
+        if library.is_err() {
+            unsafe { FreeLibrary(module); }
+        }
+
+        library
+    }
+
+    unsafe fn resolve(module: Module) -> io::Result<Self> {
+        Ok(Library {
+            module,
+            create_adapter: symbol(module, b"WintunCreateAdapter\0")?,
+            open_adapter: symbol(module, b"WintunOpenAdapter\0")?,
+            close_adapter: symbol(module, b"WintunCloseAdapter\0")?,
+            start_session: symbol(module, b"WintunStartSession\0")?,
+            end_session: symbol(module, b"WintunEndSession\0")?,
+            get_read_wait_event: symbol(module, b"WintunGetReadWaitEvent\0")?,
+            receive_packet: symbol(module, b"WintunReceivePacket\0")?,
+            release_receive_packet: symbol(module, b"WintunReleaseReceivePacket\0")?,
+            allocate_send_packet: symbol(module, b"WintunAllocateSendPacket\0")?,
+            send_packet: symbol(module, b"WintunSendPacket\0")?,
+        })
+    }
+}
+
+/// Look up a function of the library.
+///
+/// The type `F` must be the function pointer type of the symbol.
+unsafe fn symbol<F: Copy>(module: Module, name: &[u8]) -> io::Result<F> {
+    debug_assert_eq!(mem::size_of::<F>(), mem::size_of::<Handle>());
+    let function = GetProcAddress(module, name.as_ptr());
+    if function.is_null() {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(mem::transmute_copy(&function))
+    }
+}
+
+impl Drop for Library {
+    fn drop(&mut self) {
+        unsafe { FreeLibrary(self.module); }
+    }
+}
+
+impl WintunDesc {
+    /// Open an existing adapter by name and start a session on it.
+    ///
+    /// The `capacity` of the rings must be a power of two between [`MIN_RING_CAPACITY`] and
+    /// [`MAX_RING_CAPACITY`].
+    ///
+    /// [`MIN_RING_CAPACITY`]: constant.MIN_RING_CAPACITY.html
+    /// [`MAX_RING_CAPACITY`]: constant.MAX_RING_CAPACITY.html
+    pub fn open(library: Library, name: &str, capacity: u32) -> io::Result<Self> {
+        let name = wide(name);
+        let adapter = unsafe { (library.open_adapter)(name.as_ptr()) };
+        Self::start(library, adapter, capacity)
+    }
+
+    /// Create a new adapter and start a session on it.
+    ///
+    /// The adapter is removed again when the descriptor is dropped. Requires administrator
+    /// privileges.
+    pub fn create(library: Library, name: &str, tunnel_type: &str, capacity: u32)
+        -> io::Result<Self>
+    {
+        let name = wide(name);
+        let tunnel_type = wide(tunnel_type);
+        let adapter = unsafe {
+            (library.create_adapter)(name.as_ptr(), tunnel_type.as_ptr(), ptr::null())
+        };
+        Self::start(library, adapter, capacity)
+    }
+
+    fn start(library: Library, adapter: Handle, capacity: u32) -> io::Result<Self> {
+        if adapter.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        let session = unsafe { (library.start_session)(adapter, capacity) };
+        if session.is_null() {
+            let err = io::Error::last_os_error();
+            unsafe { (library.close_adapter)(adapter) };
+            return Err(err);
+        }
+
+        Ok(WintunDesc {
+            library,
+            adapter,
+            session,
+        })
+    }
+
+    /// Get the event that is signalled when packets are available for receiving.
+    ///
+    /// The event is owned by the session and must not be closed.
+    pub fn read_wait_event(&self) -> Handle {
+        unsafe { (self.library.get_read_wait_event)(self.session) }
+    }
+
+    /// Receive a single packet, copying it into the buffer.
+    ///
+    /// Returns `Ok(None)` if no packet is available. Packets that do not fit into the buffer are
+    /// dropped with an error.
+    pub fn recv(&mut self, buffer: &mut [u8]) -> io::Result<Option<usize>> {
+        let mut len = 0;
+        let packet = unsafe { (self.library.receive_packet)(self.session, &mut len) };
+        if packet.is_null() {
+            let err = io::Error::last_os_error();
+            return match err.raw_os_error() {
+                Some(ERROR_NO_MORE_ITEMS) => Ok(None),
+                _ => Err(err),
+            };
+        }
+
+        let len = len as usize;
+        let result = match buffer.get_mut(..len) {
+            Some(buffer) => {
+                buffer.copy_from_slice(unsafe { core::slice::from_raw_parts(packet, len) });
+                Ok(Some(len))
+            },
+            None => Err(io::Error::from_raw_os_error(ERROR_BUFFER_OVERFLOW)),
+        };
+
+        unsafe { (self.library.release_receive_packet)(self.session, packet) };
+        result
+    }
+
+    /// Send a single packet from the buffer.
+    pub fn send(&mut self, buffer: &[u8]) -> io::Result<()> {
+        if buffer.len() > MAX_IP_PACKET_SIZE {
+            return Err(io::Error::from_raw_os_error(ERROR_BUFFER_OVERFLOW));
+        }
+
+        let packet = unsafe {
+            (self.library.allocate_send_packet)(self.session, buffer.len() as u32)
+        };
+
+        if packet.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        unsafe {
+            ptr::copy_nonoverlapping(buffer.as_ptr(), packet, buffer.len());
+            (self.library.send_packet)(self.session, packet);
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for WintunDesc {
+    fn drop(&mut self) {
+        unsafe {
+            (self.library.end_session)(self.session);
+            (self.library.close_adapter)(self.adapter);
+        }
+    }
+}
+
+impl<C: PayloadMut> Wintun<C> {
+    /// Wrap a session with a buffer into a device.
+    ///
+    /// The hardware address should be the one configured for the ethernet layer.
+    pub fn new(inner: WintunDesc, hardware_addr: EthernetAddress, buffer: C) -> Self {
+        Wintun {
+            inner,
+            buffer: Partial::new(buffer),
+            tunnel: EthernetTunnel::new(hardware_addr, EthernetTunnel::PEER_ADDR),
+            last_err: None,
+        }
+    }
+
+    /// The hardware address of the emulated peer.
+    pub fn peer_addr(&self) -> EthernetAddress {
+        self.tunnel.peer_addr()
+    }
+
+    /// Get the underlying session.
+    pub fn descriptor(&self) -> &WintunDesc {
+        &self.inner
+    }
+
+    /// Take the last io error returned by the OS.
+    pub fn last_err(&mut self) -> Option<io::Error> {
+        self.last_err.take()
+    }
+
+    /// Resize the partial buffer to its full length.
+    fn recycle(&mut self) {
+        let length = self.buffer
+            .inner()
+            .payload()
+            .as_slice()
+            .len();
+        self.buffer.set_len_unchecked(length);
+    }
+
+    /// Send the current buffer as a frame.
+    fn send(&mut self) -> layer::Result<()> {
+        let frame = self.buffer.payload_mut().as_mut_slice();
+        if self.tunnel.transmit(frame).is_none() {
+            return Ok(());
+        }
+
+        match self.inner.send(&frame[HEADER_LEN..]) {
+            Ok(()) => Ok(()),
+            Err(err) => Err(self.store_err(err)),
+        }
+    }
+
+    fn recv(&mut self) -> Received {
+        self.recycle();
+        let frame = self.buffer.payload_mut().as_mut_slice();
+        if let Some(len) = self.tunnel.answer_arp(frame) {
+            self.buffer.set_len_unchecked(len);
+            return Received::Ok;
+        }
+
+        if frame.len() <= HEADER_LEN {
+            return Received::Err(layer::Error::BadSize);
+        }
+
+        let len = match self.inner.recv(&mut frame[HEADER_LEN..]) {
+            Ok(Some(len)) if len > 0 => len,
+            Ok(_) => return Received::NoData,
+            Err(err) => return Received::Err(self.store_err(err)),
+        };
+
+        let ethertype = match frame[HEADER_LEN] >> 4 {
+            4 => EthernetProtocol::Ipv4,
+            6 => EthernetProtocol::Ipv6,
+            _ => return Received::NoData,
+        };
+
+        self.tunnel.frame_ip(frame, ethertype);
+        self.buffer.set_len_unchecked(HEADER_LEN + len);
+        Received::Ok
+    }
+
+    fn store_err(&mut self, err: io::Error) -> layer::Error {
+        let as_nic = match err.raw_os_error() {
+            Some(ERROR_BUFFER_OVERFLOW) => layer::Error::Exhausted,
+            Some(ERROR_HANDLE_EOF) => layer::Error::Unreachable,
+            _ => layer::Error::Illegal,
+        };
+        self.last_err = Some(err);
+        as_nic
+    }
+
+    fn current_info() -> PacketInfo {
+        PacketInfo {
+            timestamp: Instant::from_millis(unsafe { GetTickCount64() } as i64),
+            capabilities: Capabilities::no_support(),
+            hardware_timestamp: None,
+        }
+    }
+}
+
+impl<C: PayloadMut> Device for Wintun<C> {
+    type Handle = EnqueueFlag;
+    type Payload = Partial<C>;
+
+    /// A description of the device.
+    ///
+    /// The rings hold many packets, allowing large batches. The driver offloads no checksums.
+    fn personality(&self) -> Personality {
+        let mut personality = Personality::baseline();
+        *personality.rx_batch_mut() = 32;
+        *personality.tx_batch_mut() = 32;
+        personality
+    }
+
+    fn tx(&mut self, max: usize, mut sender: impl super::Send<Self::Handle, Self::Payload>)
+        -> layer::Result<usize>
+    {
+        let mut count = 0;
+
+        while count < max {
+            let mut handle = EnqueueFlag::set_true(Self::current_info());
+            self.recycle();
+            sender.send(Packet {
+                handle: &mut handle,
+                payload: &mut self.buffer,
+            });
+
+            if !handle.was_sent() {
+                break;
+            }
+
+            self.send()?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    fn rx(&mut self, max: usize, mut receptor: impl super::Recv<Self::Handle, Self::Payload>)
+        -> layer::Result<usize>
+    {
+        let mut count = 0;
+
+        while count < max {
+            match self.recv() {
+                Received::Ok => (),
+                Received::Err(err) => return Err(err),
+                Received::NoData => break,
+            }
+
+            let mut handle = EnqueueFlag::set_true(Self::current_info()).allow_retain();
+            receptor.receive(Packet {
+                handle: &mut handle,
+                payload: &mut self.buffer,
+            });
+
+            if handle.was_sent() {
+                self.send()?;
+            }
+
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
@@ -0,0 +1,142 @@
+//! Pluggable sources of randomness.
+//!
+//! Several protocols require unpredictable values: initial sequence numbers of TCP connections,
+//! ephemeral ports, or the transaction identifiers of DHCP and DNS. This library does not assume
+//! any particular entropy source to be available as that would be at odds with `no_std` targets.
+//! Instead, all these draw their values from a user provided implementation of [`Rng`].
+//!
+//! Implement the trait for a hardware random number generator or a cryptographically secure
+//! generator seeded from one. The pseudo-random [`Xoroshiro256`] of the loss layer implements it
+//! as well but is suitable for tests only, its output is easily predictable.
+//!
+//! With the `std` feature, [`StdRng`] is available as a default source. It is keyed from the
+//! random state of the standard library's hash maps which in turn are seeded by the operating
+//! system.
+//!
+//! ## Seeding on bare metal
+//!
+//! Targets without an operating system need to bring their own entropy. Preferably, forward
+//! `next_u64` to a hardware generator directly. If only a small amount of entropy is available
+//! during startup, for example from the jitter of an uninitialized oscillator or a unique device
+//! secret, use it to key a cryptographically secure stream cipher or hash function in counter mode
+//! and implement the trait for that. Avoid deriving the seed from values an attacker can observe
+//! or guess, such as the mac address or the time since boot.
+//!
+//! The generator is then handed to the components requiring randomness, for example when creating
+//! the [`IsnGenerator`] of the tcp layer or via [`tcp::Endpoint::set_rng`] for the selection of
+//! ephemeral ports.
+//!
+//! [`Rng`]: trait.Rng.html
+//! [`StdRng`]: struct.StdRng.html
+//! [`Xoroshiro256`]: ../layer/loss/struct.Xoroshiro256.html
+//! [`IsnGenerator`]: ../layer/tcp/struct.IsnGenerator.html
+//! [`tcp::Endpoint::set_rng`]: ../layer/tcp/struct.Endpoint.html#method.set_rng
+
+/// A source of random numbers.
+///
+/// The quality requirements depend on the use. Values that protect against off-path attackers,
+/// such as the secret key of the [`IsnGenerator`], should come from a cryptographically secure
+/// source.
+///
+/// [`IsnGenerator`]: ../layer/tcp/struct.IsnGenerator.html
+pub trait Rng {
+    /// Generate the next random value.
+    fn next_u64(&mut self) -> u64;
+
+    /// Fill a buffer with random bytes.
+    ///
+    /// The default implementation fills the buffer in chunks of `next_u64`.
+    fn fill_bytes(&mut self, bytes: &mut [u8]) {
+        for chunk in bytes.chunks_mut(8) {
+            let value = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&value[..chunk.len()]);
+        }
+    }
+}
+
+impl<R: Rng + ?Sized> Rng for &'_ mut R {
+    fn next_u64(&mut self) -> u64 {
+        (**self).next_u64()
+    }
+
+    fn fill_bytes(&mut self, bytes: &mut [u8]) {
+        (**self).fill_bytes(bytes)
+    }
+}
+
+/// A generator keyed from the standard `RandomState`.
+///
+/// Produces the hash of an incrementing counter with a hasher from a new instance of
+/// `RandomState`. The standard hasher is a keyed pseudo-random function, so the output is
+/// unpredictable as long as the key is not known.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+pub struct StdRng {
+    hasher: std::collections::hash_map::DefaultHasher,
+    counter: u64,
+}
+
+#[cfg(feature = "std")]
+impl StdRng {
+    /// Create a generator with a new random key.
+    pub fn new() -> Self {
+        use std::hash::BuildHasher;
+        use std::collections::hash_map::RandomState;
+
+        StdRng {
+            hasher: RandomState::new().build_hasher(),
+            counter: 0,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for StdRng {
+    fn default() -> Self {
+        StdRng::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Rng for StdRng {
+    fn next_u64(&mut self) -> u64 {
+        use std::hash::Hasher;
+
+        let mut hash = self.hasher.clone();
+        hash.write_u64(self.counter);
+        self.counter = self.counter.wrapping_add(1);
+        hash.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rng;
+
+    struct Counter(u64);
+
+    impl Rng for Counter {
+        fn next_u64(&mut self) -> u64 {
+            self.0 += 1;
+            self.0
+        }
+    }
+
+    #[test]
+    fn fill_bytes() {
+        let mut rng = Counter(0);
+        let mut bytes = [0; 12];
+        rng.fill_bytes(&mut bytes);
+        assert_eq!(bytes, [1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn std_rng() {
+        let mut rng = super::StdRng::new();
+        let mut copy = rng.clone();
+        let first = rng.next_u64();
+        assert_ne!(first, rng.next_u64());
+        assert_eq!(first, copy.next_u64());
+    }
+}
@@ -0,0 +1,112 @@
+//! The keyed hash function SipHash-2-4.
+//!
+//! Used where an output must not be predictable without knowing a secret key, such as initial
+//! sequence numbers of TCP connections and stable interface identifiers of IPv6 addresses. Hash
+//! function SipHash-2-4 from:
+//!
+//! > SipHash: a fast short-input PRFJean-Philippe Aumasson1and Daniel J. Bernstein
+
+// Yes, that's the initial values, as ASCII text.
+const IV: [&[u8; 8]; 4] = [
+    b"somepseu",
+    b"dorandom",
+    b"lygenera",
+    b"tedbytes"];
+
+/// The state of a SipHash-2-4 computation.
+pub(crate) struct State {
+    v0: u64,
+    v1: u64,
+    v2: u64,
+    v3: u64,
+}
+
+impl State {
+    const SIP_C: usize = 2;
+    const SIP_D: usize = 4;
+
+    pub(crate) fn init(k0: u64, k1: u64) -> Self {
+        State {
+            v0: u64::from_be_bytes(*IV[0]) ^ k0,
+            v1: u64::from_be_bytes(*IV[1]) ^ k1,
+            v2: u64::from_be_bytes(*IV[2]) ^ k0,
+            v3: u64::from_be_bytes(*IV[3]) ^ k1,
+        }
+    }
+
+    fn round(&mut self) {
+        self.v0 = self.v0.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(13);
+        self.v1 ^= self.v0;
+        self.v0 = self.v0.rotate_left(32);
+        self.v2 = self.v2.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(16);
+        self.v3 ^= self.v2;
+        self.v0 = self.v0.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(21);
+        self.v3 ^= self.v0;
+        self.v2 = self.v2.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(17);
+        self.v1 ^= self.v2;
+        self.v2 = self.v2.rotate_left(32);
+    }
+
+    /// Process a single portion of the message.
+    ///
+    /// Note that all users need to manually add absorbing the length in the last block. This is
+    /// slightly easier to read since it arranges the input to only have 8-btye blocks in all cases
+    /// which separates the length block completely and makes it a constant.
+    pub(crate) fn absorb(&mut self, m: u64) {
+        self.v3 ^= m;
+        (0..Self::SIP_C).for_each(|_| self.round());
+        self.v0 ^= m;
+    }
+
+    /// Do the finalization rounds.
+    pub(crate) fn finalize(mut self) -> u64 {
+        self.v2 ^= 0xff;
+        (0..Self::SIP_D).for_each(|_| self.round());
+        self.v0 ^ self.v1 ^ self.v2 ^ self.v3
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt;
+    use super::*;
+
+    struct DebugState<'a>(&'a State);
+
+    impl fmt::Debug for DebugState<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{:x} ", self.0.v0)?;
+            write!(f, "{:x} ", self.0.v1)?;
+            write!(f, "{:x} ", self.0.v2)?;
+            write!(f, "{:x} ", self.0.v3)
+        }
+    }
+
+    impl super::State {
+        fn debug(&self) -> DebugState {
+            DebugState(self)
+        }
+    }
+
+    /// See the paper–Appendix A
+    #[test]
+    fn manual_test_vectors() {
+        let k0 = u64::from_le_bytes(0x0001020304050607_u64.to_be_bytes());
+        let k1 = u64::from_le_bytes(0x08090a0b0c0d0e0f_u64.to_be_bytes());
+
+        let mut state = State::init(k0, k1);
+        println!("{:?}", state.debug());
+        let m0 = u64::from_le_bytes(0x0001020304050607_u64.to_be_bytes());
+        state.absorb(m0);
+        println!("{:?}", state.debug());
+        let m1 = u64::from_le_bytes(0x08090a0b0c0d0e0f_u64.to_be_bytes());
+        state.absorb(m1);
+        println!("{:?}", state.debug());
+
+        assert_eq!(state.finalize(), 0xa129ca6149be45e5);
+    }
+}
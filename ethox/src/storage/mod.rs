@@ -9,7 +9,7 @@ pub mod assembler;
 mod ring_buffer;
 
 pub use self::assembler::Assembler;
-pub use self::ring_buffer::RingBuffer;
+pub use self::ring_buffer::{ByteRing, RingBuffer};
 
 /// A trait for setting a value to a known state.
 ///
@@ -366,6 +366,88 @@ impl<'a, T: 'a> RingBuffer<'a, T> {
     }
 }
 
+/// This is the "slice view" interface: it exposes allocated or unallocated elements as the (at
+/// most) two contiguous slices they occupy in the storage, in order.
+impl<'a, T: 'a> RingBuffer<'a, T> {
+    /// Return the allocated elements as two contiguous slices.
+    ///
+    /// The second slice is empty unless the allocated elements wrap around the end of the
+    /// storage. Nothing is dequeued, see `dequeue_allocated` for consuming the elements.
+    pub fn as_slices(&self) -> (&[T], &[T]) {
+        let (first, second) = self.allocated_ranges();
+        let (head, tail) = self.storage.split_at(self.read_at);
+        (&tail[..first], &head[..second])
+    }
+
+    /// Return the allocated elements as two contiguous mutable slices.
+    pub fn as_mut_slices(&mut self) -> (&mut [T], &mut [T]) {
+        let (first, second) = self.allocated_ranges();
+        let (head, tail) = self.storage.split_at_mut(self.read_at);
+        (&mut tail[..first], &mut head[..second])
+    }
+
+    /// Return the unallocated elements as two contiguous mutable slices.
+    ///
+    /// Fill a prefix of the elements in place and then commit them with `enqueue_unallocated`.
+    pub fn unallocated_mut(&mut self) -> (&mut [T], &mut [T]) {
+        if self.length == 0 {
+            // Same as in `enqueue_many_with`, optimize for contiguous space.
+            self.read_at = 0;
+        }
+
+        let write_at = self.get_idx(self.length);
+        let first = self.contiguous_window();
+        let second = self.window() - first;
+        let (head, tail) = self.storage.split_at_mut(write_at);
+        (&mut tail[..first], &mut head[..second])
+    }
+
+    /// Move the allocated elements to the start of the storage and return them as one slice.
+    ///
+    /// Unallocated elements keep their position relative to the allocated ones. This copies the
+    /// whole storage, prefer `as_slices` where two slices can be handled.
+    pub fn make_contiguous(&mut self) -> &mut [T] {
+        let read_at = self.read_at;
+        self.storage.rotate_left(read_at);
+        self.read_at = 0;
+        &mut self.storage[..self.length]
+    }
+
+    /// The lengths of the allocated elements after `read_at` and at the start of the storage.
+    fn allocated_ranges(&self) -> (usize, usize) {
+        let first = cmp::min(self.length, self.capacity() - self.read_at);
+        (first, self.length - first)
+    }
+}
+
+/// A ring buffer of bytes, such as the data of a stream.
+pub type ByteRing<'a> = RingBuffer<'a, u8>;
+
+#[cfg(feature = "std")]
+impl std::io::Read for ByteRing<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        Ok(self.dequeue_slice(buf))
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::Write for ByteRing<'_> {
+    /// Enqueue as many bytes as possible.
+    ///
+    /// A full ring reports that it would block instead of a successful write of no bytes, which
+    /// would be interpreted as the end of the stream.
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.enqueue_slice(buf) {
+            0 if !buf.is_empty() => Err(std::io::ErrorKind::WouldBlock.into()),
+            written => Ok(written),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 impl<'a, T: 'a> From<Slice<'a, T>> for RingBuffer<'a, T> {
     fn from(slice: Slice<'a, T>) -> RingBuffer<'a, T> {
         RingBuffer::new(slice)
@@ -744,4 +826,39 @@ mod test {
         let large = ring.enqueue_many(8);
         assert_eq!(large.len(), 8);
     }
+
+    #[test]
+    fn test_buffer_slices() {
+        let mut ring = RingBuffer::new(vec![0; 8]);
+        assert_eq!(ring.as_slices(), (&[][..], &[][..]));
+
+        assert_eq!(ring.enqueue_slice(b"012345"), 6);
+        assert_eq!(ring.dequeue_many(4), b"0123");
+        assert_eq!(ring.as_slices(), (&b"45"[..], &b""[..]));
+
+        {
+            let (first, second) = ring.unallocated_mut();
+            assert_eq!((first.len(), second.len()), (2, 4));
+            first.copy_from_slice(b"67");
+            second[..1].copy_from_slice(b"8");
+        }
+        ring.enqueue_unallocated(3);
+        assert_eq!(ring.as_slices(), (&b"4567"[..], &b"8"[..]));
+
+        {
+            let (first, second) = ring.as_mut_slices();
+            first[0] = b'x';
+            second[0] = b'y';
+        }
+        assert_eq!(ring.make_contiguous(), b"x567y");
+        assert_eq!(ring.as_slices(), (&b"x567y"[..], &b""[..]));
+
+        let mut data = [0; 8];
+        assert_eq!(ring.dequeue_slice(&mut data), 5);
+        assert_eq!(&data[..5], b"x567y");
+
+        // An empty ring offers all its storage contiguously.
+        let (first, second) = ring.unallocated_mut();
+        assert_eq!((first.len(), second.len()), (8, 0));
+    }
 }
@@ -5,9 +5,11 @@ absolute and relative time.
 
  - [Instant] is used to represent absolute time.
  - [Duration] is used to represent relative time.
+ - [Clock] is a source of the current time for driving timers.
 
 [Instant]: struct.Instant.html
 [Duration]: struct.Duration.html
+[Clock]: trait.Clock.html
 */
 use core::{cmp, fmt, ops};
 pub use core::time::Duration;
@@ -42,9 +44,38 @@ pub enum Expiration {
     Never,
 }
 
+/// A source of the current time.
+///
+/// Packets carry the timestamp of their reception or transmission from the nic and most layers
+/// base their timers on it. Some timers however must fire even when no traffic is flowing, such as
+/// retransmissions of a tcp connection that has not received an answer. Endpoints with such timers
+/// offer a `tick` method that reads the time from a clock.
+///
+/// The clock must use the same reference point as the timestamps of the nic, see [`Instant`].
+/// Closures returning an `Instant` implement this trait as well, which is the easiest way to wrap
+/// a hardware timer on bare metal targets.
+///
+/// [`Instant`]: struct.Instant.html
+pub trait Clock {
+    /// Get the current time.
+    ///
+    /// Consecutive calls should never go backwards.
+    fn now(&self) -> Instant;
+}
+
+/// A monotonic clock based on the standard library.
+///
+/// The reference point is shared with conversions from `std::time::Instant`.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StdClock;
+
 use Expiration::{When, Never};
 
 impl Instant {
+    /// The instant at zero milliseconds.
+    pub const ZERO: Instant = Instant { millis: 0 };
+
     /// Create a new `Instant` from a number of milliseconds.
     pub fn from_millis<T: Into<i64>>(millis: T) -> Instant {
         Instant { millis: millis.into() }
@@ -130,6 +161,19 @@ impl Into<::std::time::SystemTime> for Instant {
     }
 }
 
+impl<F: Fn() -> Instant> Clock for F {
+    fn now(&self) -> Instant {
+        self()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for StdClock {
+    fn now(&self) -> Instant {
+        Instant::from(::std::time::Instant::now())
+    }
+}
+
 impl fmt::Display for Instant {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}.{}s", self.secs(), self.millis())
@@ -230,6 +274,17 @@ mod test {
         assert_eq!(Instant::from_millis(7) - Duration::from_millis(5), Instant::from_millis(2));
     }
 
+    #[test]
+    fn test_clock() {
+        let clock = || Instant::from_millis(42);
+        assert_eq!(clock.now(), Instant::from_millis(42));
+
+        #[cfg(feature = "std")] {
+            let early = StdClock.now();
+            assert!(early <= StdClock.now());
+        }
+    }
+
     #[test]
     fn test_instant_getters() {
         let instant = Instant::from_millis(5674);
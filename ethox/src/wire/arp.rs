@@ -105,6 +105,8 @@ impl arp {
 
     /// Ensure that no accessor method will panic if called.
     /// Returns `Err(Error::Truncated)` if the buffer is too short.
+    /// Returns `Err(Error::Unrecognized)` if the address lengths are not those of ethernet and
+    /// ipv4, the only ones the address accessors can represent.
     ///
     /// The result of this check is invalidated by calling [set_hardware_len] or
     /// [set_protocol_len].
@@ -117,6 +119,8 @@ impl arp {
             Err(Error::Truncated)
         } else if len < field::TPA(self.hardware_len(), self.protocol_len()).end {
             Err(Error::Truncated)
+        } else if self.hardware_len() != 6 || self.protocol_len() != 4 {
+            Err(Error::Unrecognized)
         } else {
             Ok(())
         }
@@ -527,6 +531,15 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_check_len() {
+        assert_eq!(arp::new_checked(&PACKET_BYTES[..27]).err(), Some(Error::Truncated));
+
+        let mut bytes = PACKET_BYTES;
+        bytes[4] = 4;
+        assert_eq!(arp::new_checked(&bytes[..]).err(), Some(Error::Unrecognized));
+    }
+
     #[test]
     fn test_parse() {
         let packet = arp::new_unchecked(&PACKET_BYTES[..]);
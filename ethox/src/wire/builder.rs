@@ -0,0 +1,496 @@
+//! Builders composing packet headers directly in a buffer.
+//!
+//! The `Repr` types cover well-formed packets, and the byte wrappers allow any change but leave
+//! the order of writes, lengths and checksums to the caller. The builders are a middle ground for
+//! crafting unusual packets: every header field can be set, while the type of a builder tracks
+//! whether its headers are still being written. Only once they are finalized, which fills in the
+//! lengths and the checksums that cover the header alone, is the payload accessible. Checksums
+//! covering the payload are filled in when the builder is finished.
+//!
+//! ```
+//! use ethox::wire::{Ipv4Address, Ipv4Builder, IpProtocol, TcpFlags, TcpSeqNumber};
+//!
+//! let mut buffer = [0; 64];
+//! let mut packet = Ipv4Builder::new(&mut buffer[..]).unwrap()
+//!     .src_addr(Ipv4Address::new(192, 0, 2, 1))
+//!     .dst_addr(Ipv4Address::new(192, 0, 2, 2))
+//!     .tcp().unwrap()
+//!     .src_port(49152)
+//!     .dst_port(80)
+//!     .seq_number(TcpSeqNumber(1))
+//!     .flags(TcpFlags::SYN | TcpFlags::FIN)
+//!     .payload(4).unwrap();
+//! packet.payload_mut().copy_from_slice(b"ping");
+//! let packet = packet.finish();
+//! assert_eq!(packet.len(), 44);
+//! ```
+use core::marker::PhantomData;
+use byteorder::{ByteOrder, NetworkEndian};
+
+use super::{Error, Result};
+use super::{IpAddress, IpProtocol, Ipv4Address, TcpFlags, TcpSeqNumber};
+use super::ip::checksum;
+use super::ipv4_packet;
+use super::tcp::field as tcp;
+
+/// The state of a builder whose header fields can still be changed.
+#[derive(Debug)]
+pub enum Fields {}
+
+/// The state of a builder whose headers are final and whose payload can be written.
+#[derive(Debug)]
+pub enum Finalized {}
+
+/// Composes an IPv4 header at the start of a buffer.
+#[derive(Debug)]
+pub struct Ipv4Builder<'a, S> {
+    buffer: &'a mut [u8],
+    /// The length of the packet, known once finalized.
+    len: usize,
+    state: PhantomData<S>,
+}
+
+/// Composes a TCP header, possibly behind an IPv4 header built with [`Ipv4Builder::tcp`].
+///
+/// [`Ipv4Builder::tcp`]: struct.Ipv4Builder.html#method.tcp
+#[derive(Debug)]
+pub struct TcpBuilder<'a, S> {
+    buffer: &'a mut [u8],
+    /// The length of the enclosing IPv4 header at the start of the buffer, or zero.
+    offset: usize,
+    /// The addresses of the pseudo header for the checksum.
+    src_addr: IpAddress,
+    dst_addr: IpAddress,
+    /// The length of the packet, known once finalized.
+    len: usize,
+    state: PhantomData<S>,
+}
+
+/// The length of an IPv4 header without options.
+const IPV4_HEADER_LEN: usize = 20;
+
+/// The length of a TCP header without options.
+const TCP_HEADER_LEN: usize = tcp::URGENT.end;
+
+/// The maximum length of the options of both an IPv4 and a TCP header.
+const MAX_OPTIONS_LEN: usize = 40;
+
+impl<'a> Ipv4Builder<'a, Fields> {
+    /// Start a header at the beginning of a buffer.
+    ///
+    /// The header has no options, a hop limit of 64, and all other fields zeroed. Fails with
+    /// `Truncated` if the buffer can not hold it.
+    pub fn new(buffer: &'a mut [u8]) -> Result<Self> {
+        if buffer.len() < IPV4_HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+
+        for byte in &mut buffer[..IPV4_HEADER_LEN] {
+            *byte = 0;
+        }
+        let packet = ipv4_packet::new_unchecked_mut(buffer);
+        packet.set_version(4);
+        packet.set_header_len(IPV4_HEADER_LEN as u8);
+        packet.set_hop_limit(64);
+
+        Ok(Ipv4Builder { buffer, len: 0, state: PhantomData })
+    }
+
+    /// Set the source address.
+    pub fn src_addr(mut self, addr: Ipv4Address) -> Self {
+        self.packet().set_src_addr(addr);
+        self
+    }
+
+    /// Set the destination address.
+    pub fn dst_addr(mut self, addr: Ipv4Address) -> Self {
+        self.packet().set_dst_addr(addr);
+        self
+    }
+
+    /// Set the protocol of the payload.
+    pub fn protocol(mut self, protocol: IpProtocol) -> Self {
+        self.packet().set_protocol(protocol);
+        self
+    }
+
+    /// Set the hop limit, also known as time to live.
+    pub fn hop_limit(mut self, hop_limit: u8) -> Self {
+        self.packet().set_hop_limit(hop_limit);
+        self
+    }
+
+    /// Set the identification used for reassembling fragments.
+    pub fn ident(mut self, ident: u16) -> Self {
+        self.packet().set_ident(ident);
+        self
+    }
+
+    /// Set the differentiated services code point.
+    pub fn dscp(mut self, dscp: u8) -> Self {
+        self.packet().set_dscp(dscp);
+        self
+    }
+
+    /// Set the explicit congestion notification bits.
+    pub fn ecn(mut self, ecn: u8) -> Self {
+        self.packet().set_ecn(ecn);
+        self
+    }
+
+    /// Set the flag that forbids fragmenting the packet.
+    pub fn dont_frag(mut self, dont_frag: bool) -> Self {
+        self.packet().set_dont_frag(dont_frag);
+        self
+    }
+
+    /// Set the flag that more fragments of the packet follow.
+    pub fn more_frags(mut self, more_frags: bool) -> Self {
+        self.packet().set_more_frags(more_frags);
+        self
+    }
+
+    /// Set the offset of a fragment in its packet, in octets.
+    pub fn frag_offset(mut self, offset: u16) -> Self {
+        self.packet().set_frag_offset(offset);
+        self
+    }
+
+    /// Set the encoded options of the header, replacing previous ones.
+    ///
+    /// The options are copied without inspecting them. Fails with `Malformed` unless their length
+    /// is a multiple of four and at most 40 octets, or with `Truncated` if the buffer can not hold
+    /// them.
+    pub fn options(mut self, options: &[u8]) -> Result<Self> {
+        check_options(options)?;
+
+        let header_len = IPV4_HEADER_LEN + options.len();
+        if self.buffer.len() < header_len {
+            return Err(Error::Truncated);
+        }
+
+        self.buffer[IPV4_HEADER_LEN..header_len].copy_from_slice(options);
+        self.packet().set_header_len(header_len as u8);
+        Ok(self)
+    }
+
+    /// Finalize the header for a payload of some length.
+    ///
+    /// Fills in the total length and the header checksum. Fails with `Truncated` if the buffer can
+    /// not hold the packet, or with `Malformed` if the packet would exceed the maximum length.
+    pub fn payload(mut self, payload_len: usize) -> Result<Ipv4Builder<'a, Finalized>> {
+        let len = self.header_len()
+            .checked_add(payload_len)
+            .filter(|&len| len <= usize::from(u16::MAX))
+            .ok_or(Error::Malformed)?;
+        if self.buffer.len() < len {
+            return Err(Error::Truncated);
+        }
+
+        let packet = self.packet();
+        packet.set_total_len(len as u16);
+        packet.fill_checksum();
+        Ok(Ipv4Builder { buffer: self.buffer, len, state: PhantomData })
+    }
+
+    /// Continue with a TCP header as the payload.
+    ///
+    /// Sets the protocol of the header, which is finalized together with the TCP header.
+    pub fn tcp(self) -> Result<TcpBuilder<'a, Fields>> {
+        let mut this = self.protocol(IpProtocol::Tcp);
+        let packet = this.packet();
+        let (src_addr, dst_addr) = (packet.src_addr(), packet.dst_addr());
+        let offset = this.header_len();
+        TcpBuilder::with_offset(this.buffer, offset, src_addr.into(), dst_addr.into())
+    }
+
+    fn packet(&mut self) -> &mut ipv4_packet {
+        ipv4_packet::new_unchecked_mut(self.buffer)
+    }
+
+    fn header_len(&self) -> usize {
+        usize::from(ipv4_packet::new_unchecked(self.buffer).header_len())
+    }
+}
+
+impl<'a> Ipv4Builder<'a, Finalized> {
+    /// Access the payload behind the header.
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let header_len = usize::from(ipv4_packet::new_unchecked(self.buffer).header_len());
+        &mut self.buffer[header_len..self.len]
+    }
+
+    /// Finish the packet, returning the part of the buffer that it occupies.
+    pub fn finish(self) -> &'a mut [u8] {
+        &mut self.buffer[..self.len]
+    }
+}
+
+impl<'a> TcpBuilder<'a, Fields> {
+    /// Start a header at the beginning of a buffer.
+    ///
+    /// The addresses are those of the enclosing ip header, which are covered by the checksum. The
+    /// header has no options and all other fields zeroed. Fails with `Truncated` if the buffer
+    /// can not hold it, or with `Malformed` unless both addresses are of the same family.
+    pub fn new(buffer: &'a mut [u8], src_addr: IpAddress, dst_addr: IpAddress) -> Result<Self> {
+        Self::with_offset(buffer, 0, src_addr, dst_addr)
+    }
+
+    fn with_offset(buffer: &'a mut [u8], offset: usize, src_addr: IpAddress, dst_addr: IpAddress)
+        -> Result<Self>
+    {
+        match (src_addr, dst_addr) {
+            (IpAddress::Ipv4(_), IpAddress::Ipv4(_)) => (),
+            (IpAddress::Ipv6(_), IpAddress::Ipv6(_)) => (),
+            _ => return Err(Error::Malformed),
+        }
+
+        if buffer.len() < offset + TCP_HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+
+        for byte in &mut buffer[offset..offset + TCP_HEADER_LEN] {
+            *byte = 0;
+        }
+
+        let mut builder = TcpBuilder {
+            buffer,
+            offset,
+            src_addr,
+            dst_addr,
+            len: 0,
+            state: PhantomData,
+        };
+        builder.set_header_len(TCP_HEADER_LEN);
+        Ok(builder)
+    }
+
+    /// Set the source port.
+    pub fn src_port(mut self, port: u16) -> Self {
+        NetworkEndian::write_u16(&mut self.header()[tcp::SRC_PORT], port);
+        self
+    }
+
+    /// Set the destination port.
+    pub fn dst_port(mut self, port: u16) -> Self {
+        NetworkEndian::write_u16(&mut self.header()[tcp::DST_PORT], port);
+        self
+    }
+
+    /// Set the sequence number.
+    pub fn seq_number(mut self, seq: TcpSeqNumber) -> Self {
+        NetworkEndian::write_i32(&mut self.header()[tcp::SEQ_NUM], seq.0);
+        self
+    }
+
+    /// Set the acknowledgment number, without changing the `ACK` flag.
+    pub fn ack_number(mut self, ack: TcpSeqNumber) -> Self {
+        NetworkEndian::write_i32(&mut self.header()[tcp::ACK_NUM], ack.0);
+        self
+    }
+
+    /// Set all flag bits, replacing the previous ones.
+    ///
+    /// Any combination is permitted, including contradictory ones such as `SYN` with `FIN`.
+    pub fn flags(mut self, flags: TcpFlags) -> Self {
+        let field = &mut self.header()[tcp::FLAGS];
+        let raw = NetworkEndian::read_u16(field) & 0xf000;
+        NetworkEndian::write_u16(field, raw | (flags.0 & 0x1ff));
+        self
+    }
+
+    /// Set the window field, without any scaling.
+    pub fn window_len(mut self, window_len: u16) -> Self {
+        NetworkEndian::write_u16(&mut self.header()[tcp::WIN_SIZE], window_len);
+        self
+    }
+
+    /// Set the urgent pointer.
+    pub fn urgent_at(mut self, urgent_at: u16) -> Self {
+        NetworkEndian::write_u16(&mut self.header()[tcp::URGENT], urgent_at);
+        self
+    }
+
+    /// Set the encoded options of the header, replacing previous ones.
+    ///
+    /// The options are copied without inspecting them, for example use [`TcpOption::emit`] to
+    /// encode them. Fails with `Malformed` unless their length is a multiple of four and at most
+    /// 40 octets, or with `Truncated` if the buffer can not hold them.
+    ///
+    /// [`TcpOption::emit`]: enum.TcpOption.html#method.emit
+    pub fn options(mut self, options: &[u8]) -> Result<Self> {
+        check_options(options)?;
+
+        let header_len = TCP_HEADER_LEN + options.len();
+        if self.buffer.len() < self.offset + header_len {
+            return Err(Error::Truncated);
+        }
+
+        self.header()[TCP_HEADER_LEN..header_len].copy_from_slice(options);
+        self.set_header_len(header_len);
+        Ok(self)
+    }
+
+    /// Finalize the header for a payload of some length.
+    ///
+    /// Also fills in the total length and checksum of an enclosing IPv4 header. Fails with
+    /// `Truncated` if the buffer can not hold the packet, or with `Malformed` if it would exceed
+    /// the maximum length of an IPv4 packet.
+    pub fn payload(self, payload_len: usize) -> Result<TcpBuilder<'a, Finalized>> {
+        let len = (self.offset + self.header_len())
+            .checked_add(payload_len)
+            .ok_or(Error::Malformed)?;
+        if self.offset > 0 && len > usize::from(u16::MAX) {
+            return Err(Error::Malformed);
+        }
+        if self.buffer.len() < len {
+            return Err(Error::Truncated);
+        }
+
+        if self.offset > 0 {
+            let ip = ipv4_packet::new_unchecked_mut(self.buffer);
+            ip.set_total_len(len as u16);
+            ip.fill_checksum();
+        }
+
+        Ok(TcpBuilder {
+            buffer: self.buffer,
+            offset: self.offset,
+            src_addr: self.src_addr,
+            dst_addr: self.dst_addr,
+            len,
+            state: PhantomData,
+        })
+    }
+
+    fn header(&mut self) -> &mut [u8] {
+        &mut self.buffer[self.offset..]
+    }
+
+    fn header_len(&self) -> usize {
+        let raw = NetworkEndian::read_u16(&self.buffer[self.offset..][tcp::FLAGS]);
+        usize::from(raw >> 12) * 4
+    }
+
+    fn set_header_len(&mut self, header_len: usize) {
+        let field = &mut self.header()[tcp::FLAGS];
+        let raw = NetworkEndian::read_u16(field) & 0x0fff;
+        NetworkEndian::write_u16(field, raw | (header_len as u16 / 4) << 12);
+    }
+}
+
+impl<'a> TcpBuilder<'a, Finalized> {
+    /// Access the payload behind the header.
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let raw = NetworkEndian::read_u16(&self.buffer[self.offset..][tcp::FLAGS]);
+        let start = self.offset + usize::from(raw >> 12) * 4;
+        &mut self.buffer[start..self.len]
+    }
+
+    /// Finish the packet, returning the part of the buffer that it occupies.
+    ///
+    /// Fills in the checksum over the header and payload. The returned packet includes the
+    /// enclosing IPv4 header, if any.
+    pub fn finish(self) -> &'a mut [u8] {
+        let segment = &mut self.buffer[self.offset..self.len];
+        NetworkEndian::write_u16(&mut segment[tcp::CHECKSUM], 0);
+        let sum = !checksum::combine(&[
+            checksum::pseudo_header(&self.src_addr, &self.dst_addr, IpProtocol::Tcp,
+                                    segment.len() as u32),
+            checksum::data(segment),
+        ]);
+        NetworkEndian::write_u16(&mut segment[tcp::CHECKSUM], sum);
+        &mut self.buffer[..self.len]
+    }
+}
+
+/// Check that options fill whole words and fit into the header length field.
+fn check_options(options: &[u8]) -> Result<()> {
+    if options.len() & 0x3 != 0 || options.len() > MAX_OPTIONS_LEN {
+        return Err(Error::Malformed);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::wire::{TcpChecksum, TcpPacket, TcpRepr};
+
+    const SRC_ADDR: Ipv4Address = Ipv4Address([192, 0, 2, 1]);
+    const DST_ADDR: Ipv4Address = Ipv4Address([192, 0, 2, 2]);
+
+    #[test]
+    fn ipv4() {
+        let mut buffer = [0xaa; 64];
+        let mut packet = Ipv4Builder::new(&mut buffer[..]).unwrap()
+            .src_addr(SRC_ADDR)
+            .dst_addr(DST_ADDR)
+            .protocol(IpProtocol::Unknown(0xfd))
+            .options(&[0x94, 0x04, 0, 0]).unwrap()
+            .payload(4).unwrap();
+        packet.payload_mut().copy_from_slice(b"ping");
+        let packet = packet.finish();
+        assert_eq!(packet.len(), 28);
+
+        let packet = ipv4_packet::new_checked(packet).unwrap();
+        assert!(packet.verify_checksum());
+        assert_eq!(packet.header_len(), 24);
+        assert_eq!(packet.hop_limit(), 64);
+        assert_eq!(packet.src_addr(), SRC_ADDR);
+        assert_eq!(packet.payload_slice(), b"ping");
+    }
+
+    #[test]
+    fn tcp() {
+        let mut buffer = [0xaa; 128];
+        let mut packet = Ipv4Builder::new(&mut buffer[..]).unwrap()
+            .src_addr(SRC_ADDR)
+            .dst_addr(DST_ADDR)
+            .tcp().unwrap()
+            .src_port(49152)
+            .dst_port(80)
+            .seq_number(TcpSeqNumber(7))
+            .ack_number(TcpSeqNumber(42))
+            .flags(TcpFlags::SYN | TcpFlags::ACK)
+            .window_len(1024)
+            .options(&[0x02, 0x04, 0x05, 0xb4]).unwrap()
+            .payload(4).unwrap();
+        packet.payload_mut().copy_from_slice(b"ping");
+        let packet = packet.finish();
+        assert_eq!(packet.len(), 20 + 24 + 4);
+
+        let ip = ipv4_packet::new_checked(packet).unwrap();
+        assert!(ip.verify_checksum());
+        assert_eq!(ip.protocol(), IpProtocol::Tcp);
+        let checksum = TcpChecksum::Manual {
+            src_addr: SRC_ADDR.into(),
+            dst_addr: DST_ADDR.into(),
+        };
+        let tcp = TcpPacket::new_checked(ip.payload_slice(), checksum).unwrap();
+        let repr: TcpRepr = tcp.repr();
+        assert_eq!(repr.src_port, 49152);
+        assert_eq!(repr.seq_number, TcpSeqNumber(7));
+        assert_eq!(repr.ack_number, Some(TcpSeqNumber(42)));
+        assert_eq!(repr.max_seg_size, Some(1460));
+        assert_eq!(tcp.payload_slice(), b"ping");
+    }
+
+    #[test]
+    fn limits() {
+        let mut buffer = [0; 30];
+        assert_eq!(Ipv4Builder::new(&mut buffer[..19]).unwrap_err(), Error::Truncated);
+        let builder = Ipv4Builder::new(&mut buffer[..]).unwrap();
+        let builder = builder.options(&[0; 3]).unwrap_err();
+        assert_eq!(builder, Error::Malformed);
+
+        let builder = Ipv4Builder::new(&mut buffer[..]).unwrap();
+        assert_eq!(builder.payload(11).unwrap_err(), Error::Truncated);
+        let builder = Ipv4Builder::new(&mut buffer[..]).unwrap();
+        assert_eq!(builder.tcp().unwrap_err(), Error::Truncated);
+
+        let v6 = IpAddress::v6(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+        let err = TcpBuilder::new(&mut buffer[..], SRC_ADDR.into(), v6).unwrap_err();
+        assert_eq!(err, Error::Malformed);
+    }
+}
@@ -0,0 +1,660 @@
+//! DHCPv6 messages between clients and servers, rfc8415.
+//!
+//! Relay agent messages are not supported.
+use core::fmt;
+use byteorder::{ByteOrder, NetworkEndian};
+
+use super::{Error, Result};
+use super::Ipv6Address;
+
+/// The udp port on which clients listen.
+pub const CLIENT_PORT: u16 = 546;
+
+/// The udp port on which servers and relay agents listen.
+pub const SERVER_PORT: u16 = 547;
+
+/// The link-scoped multicast address of all relay agents and servers, `ff02::1:2`.
+pub const ALL_SERVERS: Ipv6Address = Ipv6Address([
+    0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 2]);
+
+enum_with_unknown! {
+    /// The type of a DHCPv6 message.
+    pub doc enum MessageType(u8) {
+        /// Solicit
+        Solicit            =  1,
+        /// Advertise
+        Advertise          =  2,
+        /// Request
+        Request            =  3,
+        /// Confirm
+        Confirm            =  4,
+        /// Renew
+        Renew              =  5,
+        /// Rebind
+        Rebind             =  6,
+        /// Reply
+        Reply              =  7,
+        /// Release
+        Release            =  8,
+        /// Decline
+        Decline            =  9,
+        /// Reconfigure
+        Reconfigure        = 10,
+        /// Information request
+        InformationRequest = 11,
+    }
+}
+
+impl fmt::Display for MessageType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MessageType::Solicit            => write!(f, "solicit"),
+            MessageType::Advertise          => write!(f, "advertise"),
+            MessageType::Request            => write!(f, "request"),
+            MessageType::Confirm            => write!(f, "confirm"),
+            MessageType::Renew              => write!(f, "renew"),
+            MessageType::Rebind             => write!(f, "rebind"),
+            MessageType::Reply              => write!(f, "reply"),
+            MessageType::Release            => write!(f, "release"),
+            MessageType::Decline            => write!(f, "decline"),
+            MessageType::Reconfigure        => write!(f, "reconfigure"),
+            MessageType::InformationRequest => write!(f, "information request"),
+            MessageType::Unknown(id)        => write!(f, "{}", id),
+        }
+    }
+}
+
+enum_with_unknown! {
+    /// The status code of a status option.
+    pub doc enum StatusCode(u16) {
+        /// Success
+        Success      = 0,
+        /// Failure, reason unspecified
+        UnspecFail   = 1,
+        /// The server has no addresses available to assign
+        NoAddrsAvail = 2,
+        /// The client record is unavailable
+        NoBinding    = 3,
+        /// The prefix is not appropriate for the link
+        NotOnLink    = 4,
+        /// The client must use the multicast address
+        UseMulticast = 5,
+        /// The server has no prefixes available to delegate
+        NoPrefixAvail = 6,
+    }
+}
+
+byte_wrapper! {
+    /// A byte slice containing a potential DHCPv6 message.
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct dhcpv6([u8]);
+}
+
+// Format of a client/server message
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |    msg-type   |               transaction-id                  |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                                                               |
+// .                            options                            .
+// .                 (variable number and length)                  .
+// |                                                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// See https://tools.ietf.org/html/rfc8415#section-8 for details.
+mod field {
+    use crate::wire::field::{Field, Rest};
+
+    pub(crate) const MSG_TYPE: usize = 0;
+    pub(crate) const XID:      Field = 1..4;
+    pub(crate) const OPTIONS:  Rest  = 4..;
+}
+
+impl dhcpv6 {
+    /// Imbue a raw octet buffer with DHCPv6 message structure.
+    pub fn new_unchecked(data: &[u8]) -> &Self {
+        Self::__from_macro_new_unchecked(data)
+    }
+
+    /// Imbue a mutable octet buffer with DHCPv6 message structure.
+    pub fn new_unchecked_mut(data: &mut [u8]) -> &mut Self {
+        Self::__from_macro_new_unchecked_mut(data)
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(data: &[u8]) -> Result<&Self> {
+        let packet = Self::new_unchecked(data);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// View the message as a raw byte slice.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// View the message as a mutable raw byte slice.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error::Truncated)` if the buffer is too short.
+    pub fn check_len(&self) -> Result<()> {
+        if self.0.len() < field::OPTIONS.start {
+            Err(Error::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The length of a message with options of a total length.
+    pub fn buffer_len(options_len: usize) -> usize {
+        field::OPTIONS.start + options_len
+    }
+
+    pub fn msg_type(&self) -> MessageType {
+        MessageType::from(self.0[field::MSG_TYPE])
+    }
+
+    /// The 24-bit transaction id.
+    pub fn transaction_id(&self) -> u32 {
+        NetworkEndian::read_u24(&self.0[field::XID])
+    }
+
+    /// Iterate over the options of the message.
+    pub fn options(&self) -> Options<'_> {
+        Options::new(&self.0[field::OPTIONS])
+    }
+
+    pub fn set_msg_type(&mut self, value: MessageType) {
+        self.0[field::MSG_TYPE] = value.into()
+    }
+
+    /// Set the transaction id, only the lower 24 bits are used.
+    pub fn set_transaction_id(&mut self, value: u32) {
+        NetworkEndian::write_u24(&mut self.0[field::XID], value & 0xff_ffff)
+    }
+
+    /// The options area of the message, for writing options with [`Dhcpv6Option::emit`].
+    ///
+    /// [`Dhcpv6Option::emit`]: enum.Dhcpv6Option.html#method.emit
+    pub fn options_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.0[field::OPTIONS]
+    }
+}
+
+impl AsRef<[u8]> for dhcpv6 {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl AsMut<[u8]> for dhcpv6 {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.as_bytes_mut()
+    }
+}
+
+/// An iterator over the options of a message or an encapsulating option.
+///
+/// Yields an error and ends when an option is malformed.
+#[derive(Clone, Debug)]
+pub struct Options<'a> {
+    bytes: &'a [u8],
+}
+
+/// An option of a DHCPv6 message.
+///
+/// See https://tools.ietf.org/html/rfc8415#section-21 for details.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dhcpv6Option<'a> {
+    /// The DUID identifying the client.
+    ClientId(&'a [u8]),
+    /// The DUID identifying the server.
+    ServerId(&'a [u8]),
+    /// An identity association for non-temporary addresses.
+    IaNa(IdentityAssociation<'a>),
+    /// An address within an identity association.
+    IaAddress(IaAddress<'a>),
+    /// The option codes requested by the client, as big-endian 16-bit values.
+    OptionRequest(&'a [u8]),
+    /// The preference of the server.
+    Preference(u8),
+    /// The time since the start of the exchange, in hundredths of a second.
+    ElapsedTime(u16),
+    /// The status of the message or the encapsulating option.
+    Status {
+        code: StatusCode,
+        message: &'a [u8],
+    },
+    /// The client accepts a two-message exchange.
+    RapidCommit,
+    /// An identity association for prefix delegation.
+    IaPd(IdentityAssociation<'a>),
+    /// A prefix within an identity association for prefix delegation.
+    IaPrefix(IaPrefix<'a>),
+    /// Any other option.
+    Unknown {
+        code: u16,
+        data: &'a [u8],
+    },
+}
+
+/// The contents of an identity association option, for addresses or prefixes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdentityAssociation<'a> {
+    /// The identifier chosen by the client.
+    pub iaid: u32,
+    /// Seconds after which the client should renew with the same server.
+    pub t1: u32,
+    /// Seconds after which the client should rebind with any server.
+    pub t2: u32,
+    /// The encapsulated options, iterate them with [`options`].
+    ///
+    /// [`options`]: #method.options
+    pub options: &'a [u8],
+}
+
+/// The contents of an address option.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IaAddress<'a> {
+    pub address: Ipv6Address,
+    /// Seconds for which the address is preferred, [`DHCPV6_INFINITE`] for no limit.
+    ///
+    /// [`DHCPV6_INFINITE`]: constant.DHCPV6_INFINITE.html
+    pub preferred_lifetime: u32,
+    /// Seconds for which the address is valid, [`DHCPV6_INFINITE`] for no limit.
+    ///
+    /// [`DHCPV6_INFINITE`]: constant.DHCPV6_INFINITE.html
+    pub valid_lifetime: u32,
+    /// The encapsulated options.
+    pub options: &'a [u8],
+}
+
+/// The contents of a delegated prefix option.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IaPrefix<'a> {
+    /// Seconds for which the prefix is preferred, [`DHCPV6_INFINITE`] for no limit.
+    ///
+    /// [`DHCPV6_INFINITE`]: constant.DHCPV6_INFINITE.html
+    pub preferred_lifetime: u32,
+    /// Seconds for which the prefix is valid, [`DHCPV6_INFINITE`] for no limit.
+    ///
+    /// [`DHCPV6_INFINITE`]: constant.DHCPV6_INFINITE.html
+    pub valid_lifetime: u32,
+    pub prefix_len: u8,
+    pub prefix: Ipv6Address,
+    /// The encapsulated options.
+    pub options: &'a [u8],
+}
+
+/// The lifetime value representing an unlimited lifetime.
+pub const INFINITE: u32 = u32::MAX;
+
+mod option {
+    use crate::wire::field::{Field, Rest};
+
+    pub(crate) const CODE:   Field = 0..2;
+    pub(crate) const LENGTH: Field = 2..4;
+    pub(crate) const DATA:   Rest  = 4..;
+
+    pub(crate) const CLIENT_ID:      u16 = 1;
+    pub(crate) const SERVER_ID:      u16 = 2;
+    pub(crate) const IA_NA:          u16 = 3;
+    pub(crate) const IA_ADDR:        u16 = 5;
+    pub(crate) const OPTION_REQUEST: u16 = 6;
+    pub(crate) const PREFERENCE:     u16 = 7;
+    pub(crate) const ELAPSED_TIME:   u16 = 8;
+    pub(crate) const STATUS_CODE:    u16 = 13;
+    pub(crate) const RAPID_COMMIT:   u16 = 14;
+    pub(crate) const IA_PD:          u16 = 25;
+    pub(crate) const IA_PREFIX:      u16 = 26;
+
+    // Relative to the data of the option.
+    pub(crate) const IA_ID:          Field = 0..4;
+    pub(crate) const IA_T1:          Field = 4..8;
+    pub(crate) const IA_T2:          Field = 8..12;
+    pub(crate) const IA_OPTIONS:     Rest  = 12..;
+
+    pub(crate) const ADDR_ADDRESS:   Field = 0..16;
+    pub(crate) const ADDR_PREFERRED: Field = 16..20;
+    pub(crate) const ADDR_VALID:     Field = 20..24;
+    pub(crate) const ADDR_OPTIONS:   Rest  = 24..;
+
+    pub(crate) const PREFIX_PREFERRED: Field = 0..4;
+    pub(crate) const PREFIX_VALID:     Field = 4..8;
+    pub(crate) const PREFIX_LEN:       usize = 8;
+    pub(crate) const PREFIX_PREFIX:    Field = 9..25;
+    pub(crate) const PREFIX_OPTIONS:   Rest  = 25..;
+
+    pub(crate) const STATUS:         Field = 0..2;
+    pub(crate) const STATUS_MESSAGE: Rest  = 2..;
+}
+
+impl<'a> Options<'a> {
+    /// Iterate over the options in a buffer, such as the encapsulated options of another option.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Options { bytes }
+    }
+}
+
+impl<'a> IdentityAssociation<'a> {
+    /// Iterate over the encapsulated options.
+    pub fn options(&self) -> Options<'a> {
+        Options::new(self.options)
+    }
+
+    fn parse(data: &'a [u8]) -> Result<Self> {
+        if data.len() < option::IA_OPTIONS.start {
+            return Err(Error::Malformed);
+        }
+
+        Ok(IdentityAssociation {
+            iaid: NetworkEndian::read_u32(&data[option::IA_ID]),
+            t1: NetworkEndian::read_u32(&data[option::IA_T1]),
+            t2: NetworkEndian::read_u32(&data[option::IA_T2]),
+            options: &data[option::IA_OPTIONS],
+        })
+    }
+
+    fn emit(&self, data: &mut [u8]) {
+        NetworkEndian::write_u32(&mut data[option::IA_ID], self.iaid);
+        NetworkEndian::write_u32(&mut data[option::IA_T1], self.t1);
+        NetworkEndian::write_u32(&mut data[option::IA_T2], self.t2);
+        data[option::IA_OPTIONS].copy_from_slice(self.options);
+    }
+}
+
+impl<'a> IaAddress<'a> {
+    /// Iterate over the encapsulated options.
+    pub fn options(&self) -> Options<'a> {
+        Options::new(self.options)
+    }
+}
+
+impl<'a> IaPrefix<'a> {
+    /// Iterate over the encapsulated options.
+    pub fn options(&self) -> Options<'a> {
+        Options::new(self.options)
+    }
+}
+
+impl<'a> Dhcpv6Option<'a> {
+    fn parse(code: u16, data: &'a [u8]) -> Result<Self> {
+        let option = match code {
+            option::CLIENT_ID => Dhcpv6Option::ClientId(data),
+            option::SERVER_ID => Dhcpv6Option::ServerId(data),
+            option::IA_NA => Dhcpv6Option::IaNa(IdentityAssociation::parse(data)?),
+            option::IA_PD => Dhcpv6Option::IaPd(IdentityAssociation::parse(data)?),
+            option::IA_ADDR if data.len() >= option::ADDR_OPTIONS.start => {
+                Dhcpv6Option::IaAddress(IaAddress {
+                    address: Ipv6Address::from_bytes(&data[option::ADDR_ADDRESS]),
+                    preferred_lifetime: NetworkEndian::read_u32(&data[option::ADDR_PREFERRED]),
+                    valid_lifetime: NetworkEndian::read_u32(&data[option::ADDR_VALID]),
+                    options: &data[option::ADDR_OPTIONS],
+                })
+            },
+            option::IA_PREFIX if data.len() >= option::PREFIX_OPTIONS.start => {
+                let prefix_len = data[option::PREFIX_LEN];
+                if prefix_len > 128 {
+                    return Err(Error::Malformed);
+                }
+                Dhcpv6Option::IaPrefix(IaPrefix {
+                    preferred_lifetime: NetworkEndian::read_u32(&data[option::PREFIX_PREFERRED]),
+                    valid_lifetime: NetworkEndian::read_u32(&data[option::PREFIX_VALID]),
+                    prefix_len,
+                    prefix: Ipv6Address::from_bytes(&data[option::PREFIX_PREFIX]),
+                    options: &data[option::PREFIX_OPTIONS],
+                })
+            },
+            option::OPTION_REQUEST if data.len().is_multiple_of(2) => Dhcpv6Option::OptionRequest(data),
+            option::PREFERENCE if data.len() == 1 => Dhcpv6Option::Preference(data[0]),
+            option::ELAPSED_TIME if data.len() == 2 => {
+                Dhcpv6Option::ElapsedTime(NetworkEndian::read_u16(data))
+            },
+            option::STATUS_CODE if data.len() >= option::STATUS_MESSAGE.start => {
+                Dhcpv6Option::Status {
+                    code: NetworkEndian::read_u16(&data[option::STATUS]).into(),
+                    message: &data[option::STATUS_MESSAGE],
+                }
+            },
+            option::RAPID_COMMIT if data.is_empty() => Dhcpv6Option::RapidCommit,
+            option::IA_ADDR | option::IA_PREFIX | option::OPTION_REQUEST | option::PREFERENCE
+                | option::ELAPSED_TIME | option::STATUS_CODE | option::RAPID_COMMIT
+                => return Err(Error::Malformed),
+            code => Dhcpv6Option::Unknown { code, data },
+        };
+        Ok(option)
+    }
+
+    fn code(&self) -> u16 {
+        match self {
+            Dhcpv6Option::ClientId(_) => option::CLIENT_ID,
+            Dhcpv6Option::ServerId(_) => option::SERVER_ID,
+            Dhcpv6Option::IaNa(_) => option::IA_NA,
+            Dhcpv6Option::IaAddress(_) => option::IA_ADDR,
+            Dhcpv6Option::OptionRequest(_) => option::OPTION_REQUEST,
+            Dhcpv6Option::Preference(_) => option::PREFERENCE,
+            Dhcpv6Option::ElapsedTime(_) => option::ELAPSED_TIME,
+            Dhcpv6Option::Status { .. } => option::STATUS_CODE,
+            Dhcpv6Option::RapidCommit => option::RAPID_COMMIT,
+            Dhcpv6Option::IaPd(_) => option::IA_PD,
+            Dhcpv6Option::IaPrefix(_) => option::IA_PREFIX,
+            Dhcpv6Option::Unknown { code, .. } => *code,
+        }
+    }
+
+    fn data_len(&self) -> usize {
+        match self {
+            Dhcpv6Option::ClientId(duid) | Dhcpv6Option::ServerId(duid) => duid.len(),
+            Dhcpv6Option::IaNa(ia) | Dhcpv6Option::IaPd(ia) => {
+                option::IA_OPTIONS.start + ia.options.len()
+            },
+            Dhcpv6Option::IaAddress(addr) => option::ADDR_OPTIONS.start + addr.options.len(),
+            Dhcpv6Option::OptionRequest(codes) => codes.len(),
+            Dhcpv6Option::Preference(_) => 1,
+            Dhcpv6Option::ElapsedTime(_) => 2,
+            Dhcpv6Option::Status { message, .. } => option::STATUS_MESSAGE.start + message.len(),
+            Dhcpv6Option::RapidCommit => 0,
+            Dhcpv6Option::IaPrefix(prefix) => {
+                option::PREFIX_OPTIONS.start + prefix.options.len()
+            },
+            Dhcpv6Option::Unknown { data, .. } => data.len(),
+        }
+    }
+
+    /// The number of bytes occupied by the option, including its code and length.
+    pub fn buffer_len(&self) -> usize {
+        option::DATA.start + self.data_len()
+    }
+
+    /// Write the option to the start of a buffer.
+    ///
+    /// Encapsulated options are copied as they are, emit them into a separate buffer first.
+    ///
+    /// # Panics
+    /// This method panics if the buffer is shorter than [`buffer_len`].
+    ///
+    /// [`buffer_len`]: #method.buffer_len
+    pub fn emit(&self, buffer: &mut [u8]) {
+        let len = self.data_len();
+        NetworkEndian::write_u16(&mut buffer[option::CODE], self.code());
+        NetworkEndian::write_u16(&mut buffer[option::LENGTH], len as u16);
+        let data = &mut buffer[option::DATA][..len];
+        match *self {
+            Dhcpv6Option::ClientId(bytes)
+            | Dhcpv6Option::ServerId(bytes)
+            | Dhcpv6Option::OptionRequest(bytes)
+            | Dhcpv6Option::Unknown { data: bytes, .. } => data.copy_from_slice(bytes),
+            Dhcpv6Option::IaNa(ia) | Dhcpv6Option::IaPd(ia) => ia.emit(data),
+            Dhcpv6Option::IaAddress(addr) => {
+                data[option::ADDR_ADDRESS].copy_from_slice(addr.address.as_bytes());
+                let preferred = addr.preferred_lifetime;
+                NetworkEndian::write_u32(&mut data[option::ADDR_PREFERRED], preferred);
+                NetworkEndian::write_u32(&mut data[option::ADDR_VALID], addr.valid_lifetime);
+                data[option::ADDR_OPTIONS].copy_from_slice(addr.options);
+            },
+            Dhcpv6Option::Preference(preference) => data[0] = preference,
+            Dhcpv6Option::ElapsedTime(elapsed) => NetworkEndian::write_u16(data, elapsed),
+            Dhcpv6Option::Status { code, message } => {
+                NetworkEndian::write_u16(&mut data[option::STATUS], code.into());
+                data[option::STATUS_MESSAGE].copy_from_slice(message);
+            },
+            Dhcpv6Option::RapidCommit => (),
+            Dhcpv6Option::IaPrefix(prefix) => {
+                let preferred = prefix.preferred_lifetime;
+                NetworkEndian::write_u32(&mut data[option::PREFIX_PREFERRED], preferred);
+                NetworkEndian::write_u32(&mut data[option::PREFIX_VALID], prefix.valid_lifetime);
+                data[option::PREFIX_LEN] = prefix.prefix_len;
+                data[option::PREFIX_PREFIX].copy_from_slice(prefix.prefix.as_bytes());
+                data[option::PREFIX_OPTIONS].copy_from_slice(prefix.options);
+            },
+        }
+    }
+}
+
+impl<'a> Iterator for Options<'a> {
+    type Item = Result<Dhcpv6Option<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bytes.is_empty() {
+            return None;
+        }
+
+        let option = if self.bytes.len() < option::DATA.start {
+            Err(Error::Truncated)
+        } else {
+            let code = NetworkEndian::read_u16(&self.bytes[option::CODE]);
+            let len = usize::from(NetworkEndian::read_u16(&self.bytes[option::LENGTH]));
+            match self.bytes[option::DATA].get(..len) {
+                Some(data) => {
+                    self.bytes = &self.bytes[option::DATA.start + len..];
+                    Dhcpv6Option::parse(code, data)
+                },
+                None => Err(Error::Truncated),
+            }
+        };
+
+        if option.is_err() {
+            self.bytes = &[];
+        }
+        Some(option)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static ADVERTISE_BYTES: [u8; 91] = [
+        0x02, 0x12, 0x34, 0x56,
+        // Client id, DUID-LL.
+        0x00, 0x01, 0x00, 0x0a, 0x00, 0x03, 0x00, 0x01,
+        0x52, 0x54, 0x00, 0x00, 0x00, 0x01,
+        // Server id, DUID-LL.
+        0x00, 0x02, 0x00, 0x0a, 0x00, 0x03, 0x00, 0x01,
+        0x52, 0x54, 0x00, 0x00, 0x00, 0x02,
+        // IA_NA with one address.
+        0x00, 0x03, 0x00, 0x28, 0x00, 0x00, 0x00, 0x01,
+        0x00, 0x00, 0x0e, 0x10, 0x00, 0x00, 0x15, 0x18,
+        0x00, 0x05, 0x00, 0x18, 0x20, 0x01, 0x0d, 0xb8,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x42, 0x00, 0x00, 0x1c, 0x20,
+        0x00, 0x00, 0x2a, 0x30,
+        // Preference.
+        0x00, 0x07, 0x00, 0x01, 0xff,
+        // Elapsed time.
+        0x00, 0x08, 0x00, 0x02, 0x00, 0x64,
+        // Rapid commit.
+        0x00, 0x0e, 0x00, 0x00,
+    ];
+
+    static CLIENT_DUID: [u8; 10] = [0x00, 0x03, 0x00, 0x01, 0x52, 0x54, 0x00, 0x00, 0x00, 0x01];
+    static SERVER_DUID: [u8; 10] = [0x00, 0x03, 0x00, 0x01, 0x52, 0x54, 0x00, 0x00, 0x00, 0x02];
+
+    fn address() -> IaAddress<'static> {
+        IaAddress {
+            address: Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x42),
+            preferred_lifetime: 7200,
+            valid_lifetime: 10800,
+            options: &[],
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let packet = dhcpv6::new_checked(&ADVERTISE_BYTES[..]).unwrap();
+        assert_eq!(packet.msg_type(), MessageType::Advertise);
+        assert_eq!(packet.transaction_id(), 0x12_3456);
+
+        let mut options = packet.options();
+        assert_eq!(options.next(), Some(Ok(Dhcpv6Option::ClientId(&CLIENT_DUID[..]))));
+        assert_eq!(options.next(), Some(Ok(Dhcpv6Option::ServerId(&SERVER_DUID[..]))));
+        let ia = match options.next() {
+            Some(Ok(Dhcpv6Option::IaNa(ia))) => ia,
+            other => panic!("Unexpected option {:?}", other),
+        };
+        assert_eq!((ia.iaid, ia.t1, ia.t2), (1, 3600, 5400));
+        let mut ia_options = ia.options();
+        assert_eq!(ia_options.next(), Some(Ok(Dhcpv6Option::IaAddress(address()))));
+        assert_eq!(ia_options.next(), None);
+        assert_eq!(options.next(), Some(Ok(Dhcpv6Option::Preference(255))));
+        assert_eq!(options.next(), Some(Ok(Dhcpv6Option::ElapsedTime(100))));
+        assert_eq!(options.next(), Some(Ok(Dhcpv6Option::RapidCommit)));
+        assert_eq!(options.next(), None);
+    }
+
+    #[test]
+    fn test_emit() {
+        let mut ia_options = [0; 28];
+        Dhcpv6Option::IaAddress(address()).emit(&mut ia_options);
+
+        let options = [
+            Dhcpv6Option::ClientId(&CLIENT_DUID[..]),
+            Dhcpv6Option::ServerId(&SERVER_DUID[..]),
+            Dhcpv6Option::IaNa(IdentityAssociation {
+                iaid: 1,
+                t1: 3600,
+                t2: 5400,
+                options: &ia_options[..],
+            }),
+            Dhcpv6Option::Preference(255),
+            Dhcpv6Option::ElapsedTime(100),
+            Dhcpv6Option::RapidCommit,
+        ];
+        let len = options.iter().map(Dhcpv6Option::buffer_len).sum();
+        assert_eq!(dhcpv6::buffer_len(len), ADVERTISE_BYTES.len());
+
+        let mut bytes = [0xa5; 91];
+        let packet = dhcpv6::new_unchecked_mut(&mut bytes[..]);
+        packet.set_msg_type(MessageType::Advertise);
+        packet.set_transaction_id(0x12_3456);
+        let mut buffer = packet.options_mut_slice();
+        for option in options.iter() {
+            option.emit(buffer);
+            buffer = &mut buffer[option.buffer_len()..];
+        }
+        assert_eq!(&bytes[..], &ADVERTISE_BYTES[..]);
+    }
+
+    #[test]
+    fn test_malformed() {
+        // An option exceeding the message.
+        let packet = dhcpv6::new_checked(&ADVERTISE_BYTES[..20]).unwrap();
+        let mut options = packet.options();
+        assert!(options.next().unwrap().is_ok());
+        assert_eq!(options.next(), Some(Err(Error::Truncated)));
+        assert_eq!(options.next(), None);
+
+        // An option with a wrong fixed length.
+        let mut bytes = ADVERTISE_BYTES;
+        bytes[79] = 2;
+        let packet = dhcpv6::new_checked(&bytes[..]).unwrap();
+        assert_eq!(packet.options().nth(3), Some(Err(Error::Malformed)));
+        assert!(dhcpv6::new_checked(&ADVERTISE_BYTES[..3]).is_err());
+    }
+}
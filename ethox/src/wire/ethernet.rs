@@ -1,7 +1,8 @@
 use core::{fmt, str::FromStr, ops};
 use byteorder::{ByteOrder, NetworkEndian};
 
-use crate::wire::{Error, Reframe, Result, Payload, PayloadError, PayloadMut, payload};
+use crate::wire::{Error, Reframe, Result, Payload, PayloadError, PayloadMut, PayloadParts, payload};
+use crate::wire::Ipv6Address;
 
 enum_with_unknown! {
     /// Ethernet protocol type.
@@ -10,6 +11,8 @@ enum_with_unknown! {
         Arp  = 0x0806,
         Ipv6 = 0x86DD,
         JumboFrame = 0x8870,
+        Ptp  = 0x88F7,
+        WakeOnLan = 0x0842,
     }
 }
 
@@ -20,11 +23,42 @@ impl fmt::Display for EtherType {
             EtherType::Ipv6 => write!(f, "IPv6"),
             EtherType::Arp  => write!(f, "ARP"),
             EtherType::JumboFrame => write!(f, "JumboFrame"),
+            EtherType::Ptp  => write!(f, "PTP"),
+            EtherType::WakeOnLan => write!(f, "Wake-on-LAN"),
             EtherType::Unknown(id) => write!(f, "0x{:04x}", id)
         }
     }
 }
 
+impl EtherType {
+    /// The largest value of the field that denotes a length instead of a protocol, IEEE 802.3.
+    pub const MAX_LENGTH: u16 = 1500;
+
+    /// Construct the field of an IEEE 802.3 frame carrying a payload of the given length.
+    ///
+    /// Returns `None` if the length can not be represented.
+    pub fn from_length(len: usize) -> Option<EtherType> {
+        if len <= usize::from(Self::MAX_LENGTH) {
+            Some(EtherType::Unknown(len as u16))
+        } else {
+            None
+        }
+    }
+
+    /// Interpret the field as the payload length of an IEEE 802.3 frame.
+    ///
+    /// Such frames do not name their protocol. Instead their payload starts with an LLC header,
+    /// see [`llc`]. Returns `None` for all Ethernet II protocol types.
+    ///
+    /// [`llc`]: struct.llc_header.html
+    pub fn frame_length(self) -> Option<usize> {
+        match self {
+            EtherType::Unknown(len) if len <= Self::MAX_LENGTH => Some(len.into()),
+            _ => None,
+        }
+    }
+}
+
 /// A six-octet Ethernet II address.
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
 pub struct Address(pub [u8; 6]);
@@ -33,6 +67,19 @@ impl Address {
     /// The broadcast address.
     pub const BROADCAST: Address = Address([0xff; 6]);
 
+    /// The multicast address of the IPv6 link-local all nodes group, `ff02::1`.
+    ///
+    /// Every IPv6 node is a member of this group, it receives router advertisements for example.
+    pub const IPV6_ALL_NODES: Address = Address([0x33, 0x33, 0, 0, 0, 1]);
+
+    /// The multicast address to which an IPv6 multicast group is mapped, rfc2464.
+    ///
+    /// The result is meaningless if the address is not multicast.
+    pub const fn from_ipv6_multicast(addr: Ipv6Address) -> Address {
+        let Ipv6Address([.., a, b, c, d]) = addr;
+        Address([0x33, 0x33, a, b, c, d])
+    }
+
     /// Construct an Ethernet address from a sequence of octets, in big-endian.
     ///
     /// # Panics
@@ -70,6 +117,7 @@ impl Address {
     }
 }
 
+/// Error emitted when parsing an ethernet address fails.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ParseAddressError {
     kind: ParseAddressErrorKind,
@@ -103,15 +151,21 @@ impl FromStr for Address {
 
     fn from_str(src: &str) -> core::result::Result<Self, ParseAddressError> {
         let mut parsed = [0; 6];
-        let mut components = src.split(':');
+        // Accept the separator of the `Display` representation as well.
+        let separator = if src.contains('-') { '-' } else { ':' };
+        let mut components = src.split(separator);
         for c in parsed.iter_mut() {
             let part = components
                 .next()
                 .ok_or(ParseAddressError {
                     kind: ParseAddressErrorKind::SeparatorError,
                 })?;
+            let valid = (1..=2).contains(&part.len())
+                && part.bytes().all(|b| b.is_ascii_hexdigit());
             *c = u8::from_str_radix(part, 16)
-                .map_err(|_| ParseAddressError {
+                .ok()
+                .filter(|_| valid)
+                .ok_or(ParseAddressError {
                     kind: ParseAddressErrorKind::ComponentError,
                 })?;
         }
@@ -241,6 +295,15 @@ impl ethernet {
     pub fn payload_mut_slice(&mut self) -> &mut [u8] {
         &mut self.0[field::PAYLOAD]
     }
+
+    /// Return the payload of an IEEE 802.3 frame, without trailing padding.
+    ///
+    /// Returns `None` for Ethernet II frames and `Err(Error::Truncated)` if the length field
+    /// exceeds the buffer.
+    pub fn llc_payload_slice(&self) -> Option<Result<&[u8]>> {
+        let len = self.ethertype().frame_length()?;
+        Some(self.payload_slice().get(..len).ok_or(Error::Truncated))
+    }
 }
 
 impl AsRef<[u8]> for ethernet {
@@ -352,8 +415,12 @@ impl<T: Payload + PayloadMut> PayloadMut for Frame<T> {
 
 impl<T: Payload> fmt::Display for Frame<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "EthernetII src={} dst={} type={}",
-               self.src_addr(), self.dst_addr(), self.ethertype())
+        match self.ethertype().frame_length() {
+            Some(len) => write!(f, "IEEE802.3 src={} dst={} len={}",
+                                self.src_addr(), self.dst_addr(), len),
+            None => write!(f, "EthernetII src={} dst={} type={}",
+                           self.src_addr(), self.dst_addr(), self.ethertype()),
+        }
     }
 }
 
@@ -381,7 +448,17 @@ impl PrettyPrint for ethernet {
                 indent.increase(f)?;
                 super::ipv6_packet::pretty_print(&frame.payload(), f, indent)
             }
-            _ => Ok(())
+            _ => match frame.llc_payload_slice() {
+                Some(Ok(payload)) => {
+                    indent.increase(f)?;
+                    super::llc_header::pretty_print(payload, f, indent)
+                }
+                Some(Err(err)) => {
+                    indent.increase(f)?;
+                    write!(f, "{}({})", indent, err)
+                }
+                None => Ok(()),
+            }
         }
     }
 }
@@ -405,6 +482,16 @@ impl Repr {
         })
     }
 
+    /// Parse the header of an Ethernet II frame stored in parts.
+    ///
+    /// Only the header must be contained in the first part, see [`PayloadParts`].
+    ///
+    /// [`PayloadParts`]: trait.PayloadParts.html
+    pub fn parse_parts<P: PayloadParts + ?Sized>(frame: &P) -> Result<Repr> {
+        let header = frame.contiguous(field::PAYLOAD.start).ok_or(Error::Truncated)?;
+        Repr::parse(ethernet::new_unchecked(header))
+    }
+
     /// Return the length of a header that will be emitted from this high-level representation.
     pub fn header_len(&self) -> usize {
         field::PAYLOAD.start
@@ -431,6 +518,17 @@ mod test {
         assert!(Address::BROADCAST.is_multicast());
         assert!(Address::BROADCAST.is_local());
     }
+
+    #[test]
+    fn test_parse() {
+        let addr = Address([0x02, 0x00, 0x5e, 0x10, 0x00, 0xff]);
+        assert_eq!("02:00:5e:10:00:ff".parse(), Ok(addr));
+        assert_eq!(addr.to_string().parse(), Ok(addr));
+        for invalid in ["", "02:00:5e:10:00", "02:00:5e:10:00:ff:00", "02-00:5e:10:00:ff",
+                        "02:00:5e:10:00:100", "02:00:5e:10:+0:ff"].iter() {
+            assert!(invalid.parse::<Address>().is_err(), "{}", invalid);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -523,3 +621,47 @@ mod test_ipv6 {
         assert_eq!(frame.as_bytes(), &FRAME_BYTES[..]);
     }
 }
+
+#[cfg(test)]
+mod test_llc {
+    // Tests of IEEE 802.3 frames with an LLC header.
+    use super::*;
+    use crate::wire::{LlcRepr, llc_header};
+
+    static FRAME_BYTES: [u8; 24] =
+        [0x01, 0x80, 0xc2, 0x00, 0x00, 0x00,
+         0x11, 0x12, 0x13, 0x14, 0x15, 0x16,
+         0x00, 0x05,
+         0x42, 0x42, 0x03, 0x00, 0x00,
+         // Padding
+         0x00, 0x00, 0x00, 0x00, 0x00];
+
+    #[test]
+    fn test_deconstruct() {
+        let frame = ethernet::new_unchecked(&FRAME_BYTES[..]);
+        assert_eq!(frame.ethertype(), EtherType::Unknown(5));
+        assert_eq!(frame.ethertype().frame_length(), Some(5));
+        let payload = frame.llc_payload_slice().unwrap().unwrap();
+        assert_eq!(payload.len(), 5);
+        let header = llc_header::new_checked(payload).unwrap();
+        assert_eq!(LlcRepr::parse(header), Ok(LlcRepr::Llc {
+            dsap: llc_header::SAP_STP,
+            ssap: llc_header::SAP_STP,
+            control: 0x03,
+        }));
+        assert_eq!(header.payload_slice(), &[0, 0]);
+    }
+
+    #[test]
+    fn test_length() {
+        assert_eq!(EtherType::from_length(1500), Some(EtherType::Unknown(1500)));
+        assert_eq!(EtherType::from_length(1501), None);
+        assert_eq!(EtherType::Ipv4.frame_length(), None);
+        assert_eq!(EtherType::Unknown(0x0600).frame_length(), None);
+
+        let mut bytes = FRAME_BYTES;
+        bytes[13] = 20;
+        let frame = ethernet::new_unchecked(&bytes[..]);
+        assert_eq!(frame.llc_payload_slice(), Some(Err(Error::Truncated)));
+    }
+}
@@ -4,7 +4,7 @@ use byteorder::{ByteOrder, NetworkEndian};
 use super::{Payload, PayloadMut};
 use super::{Error, Checksum, Result};
 use super::ip::checksum;
-use super::{Ipv4Packet, Ipv4Repr, ipv4_packet};
+use super::{Ipv4Repr, ipv4_packet};
 
 enum_with_unknown! {
     /// Internet protocol control message type.
@@ -159,6 +159,19 @@ enum_with_unknown! {
     }
 }
 
+impl fmt::Display for TimeExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeExceeded::TtlExpired =>
+                write!(f, "time to live exceeded in transit"),
+            TimeExceeded::FragExpired =>
+                write!(f, "fragment reassembly time exceeded"),
+            TimeExceeded::Unknown(id) =>
+                write!(f, "{}", id)
+        }
+    }
+}
+
 enum_with_unknown! {
     /// Internet protocol control message subtype for type "Parameter Problem".
     pub doc enum ParamProblem(u8) {
@@ -421,6 +434,13 @@ impl<T: PayloadMut> Packet<T> {
 }
 
 impl<T> Packet<T> {
+    /// Get an immutable reference to the whole buffer.
+    ///
+    /// Useful if the buffer is some other packet encapsulation.
+    pub fn get_ref(&self) -> &T {
+        &self.buffer
+    }
+
     /// Return the raw underlying buffer.
     pub fn into_inner(self) -> T {
         self.buffer
@@ -453,6 +473,10 @@ pub enum Repr {
         reason: DstUnreachable,
         header: Ipv4Repr,
     },
+    TimeExceeded {
+        reason: TimeExceeded,
+        header: Ipv4Repr,
+    },
     #[doc(hidden)]
     __Nonexhaustive
 }
@@ -493,22 +517,16 @@ impl Repr {
             },
 
             (Message::DstUnreachable, code) => {
-                let ip_packet = Ipv4Packet::new_checked(packet.payload_slice(), checksum)?;
-
-                let payload = ip_packet.payload_slice();
-                // RFC 792 requires exactly eight bytes to be returned.
-                // We allow more, since there isn't a reason not to, but require at least eight.
-                if payload.len() < 8 { return Err(Error::Truncated) }
-
                 Ok(Repr::DstUnreachable {
                     reason: DstUnreachable::from(code),
-                    header: Ipv4Repr {
-                        src_addr: ip_packet.src_addr(),
-                        dst_addr: ip_packet.dst_addr(),
-                        protocol: ip_packet.protocol(),
-                        payload_len: payload.len(),
-                        hop_limit: ip_packet.hop_limit(),
-                    },
+                    header: quoted_header(packet)?,
+                })
+            }
+
+            (Message::TimeExceeded, code) => {
+                Ok(Repr::TimeExceeded {
+                    reason: TimeExceeded::from(code),
+                    header: quoted_header(packet)?,
                 })
             }
 
@@ -526,7 +544,8 @@ impl Repr {
             Repr::EchoReply { payload, .. } => {
                 field::HEADER_END + payload
             },
-            Repr::DstUnreachable { header, .. } => {
+            Repr::DstUnreachable { header, .. } |
+            Repr::TimeExceeded { header, .. } => {
                 // Be strict in what to emit. Exactly eight beytes as required.
                 field::HEADER_END + header.buffer_len() + 8
             }
@@ -561,6 +580,14 @@ impl Repr {
                 header.emit(ip_packet, checksum);
             },
 
+            &Repr::TimeExceeded { reason, header, } => {
+                packet.set_msg_type(Message::TimeExceeded);
+                packet.set_msg_code(reason.into());
+
+                let ip_packet = ipv4_packet::new_unchecked_mut(packet.payload_mut_slice());
+                header.emit(ip_packet, checksum);
+            },
+
             &Repr::__Nonexhaustive => unreachable!()
         }
 
@@ -574,6 +601,33 @@ impl Repr {
     }
 }
 
+/// Parse the ip header quoted in an error message.
+fn quoted_header(packet: &icmpv4) -> Result<Ipv4Repr> {
+    // The quoted datagram is truncated, its total length refers to the original.
+    let quoted = packet.payload_slice();
+    let ip_packet = ipv4_packet::new_unchecked(quoted);
+    if quoted.len() < 20 || quoted.len() < usize::from(ip_packet.header_len()) {
+        return Err(Error::Truncated)
+    }
+
+    let header_len = ip_packet.header_len();
+    if header_len < 20 || u16::from(header_len) > ip_packet.total_len() {
+        return Err(Error::Malformed)
+    }
+
+    // RFC 792 requires exactly eight bytes to be returned.
+    // We allow more, since there isn't a reason not to, but require at least eight.
+    if quoted.len() < usize::from(header_len) + 8 { return Err(Error::Truncated) }
+
+    Ok(Ipv4Repr {
+        src_addr: ip_packet.src_addr(),
+        dst_addr: ip_packet.dst_addr(),
+        protocol: ip_packet.protocol(),
+        payload_len: usize::from(ip_packet.total_len() - u16::from(header_len)),
+        hop_limit: ip_packet.hop_limit(),
+    })
+}
+
 impl<T: Payload> fmt::Display for Packet<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match Repr::parse(&self, Checksum::Manual) {
@@ -603,6 +657,9 @@ impl fmt::Display for Repr {
             &Repr::DstUnreachable { reason, .. } =>
                 write!(f, "ICMPv4 destination unreachable ({})",
                        reason),
+            &Repr::TimeExceeded { reason, .. } =>
+                write!(f, "ICMPv4 time exceeded ({})",
+                       reason),
             &Repr::__Nonexhaustive => unreachable!()
         }
     }
@@ -625,10 +682,13 @@ impl PrettyPrint for icmpv4 {
         };
 
         write!(f, "{}{}", indent, repr)?;
-        match packet.msg_type() {
-            Message::DstUnreachable => {
+        match repr {
+            // Only the header and the first eight bytes of the original datagram are quoted, so
+            // it can not be printed as a complete packet.
+            Repr::DstUnreachable { header, .. } |
+            Repr::TimeExceeded { header, .. } => {
                 indent.increase(f)?;
-                ipv4_packet::pretty_print(packet.payload_slice(), f, indent)
+                write!(f, "{}{}", indent, header)
             }
             _ => Ok(())
         }
@@ -638,6 +698,7 @@ impl PrettyPrint for icmpv4 {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::wire::{IpProtocol, Ipv4Address};
 
     static ECHO_PACKET_BYTES: [u8; 12] =
         [0x08, 0x00, 0x8e, 0xfe,
@@ -706,4 +767,24 @@ mod test {
         assert_eq!(Packet::new_checked(&bytes[..4], Checksum::Ignored), Err(Error::Truncated));
         Packet::new_checked(&bytes[..], Checksum::Ignored).unwrap();
     }
+
+    #[test]
+    fn test_time_exceeded() {
+        let header = Ipv4Repr {
+            src_addr: Ipv4Address::new(10, 0, 0, 1),
+            dst_addr: Ipv4Address::new(10, 0, 0, 2),
+            protocol: IpProtocol::Udp,
+            payload_len: 32,
+            hop_limit: 1,
+        };
+        let repr = Repr::TimeExceeded { reason: TimeExceeded::TtlExpired, header };
+        let mut bytes = vec![0; repr.buffer_len()];
+        assert_eq!(bytes.len(), 36);
+
+        let packet = icmpv4::new_unchecked_mut(&mut bytes[..]);
+        repr.emit(packet, Checksum::Manual);
+        assert_eq!(packet.msg_type(), Message::TimeExceeded);
+        assert_eq!(packet.msg_code(), 0);
+        assert_eq!(Repr::parse(packet, Checksum::Manual), Ok(repr));
+    }
 }
@@ -1,5 +1,7 @@
 use core::fmt;
-use core::convert::From;
+use core::convert::{From, TryFrom};
+use core::net;
+use core::str::FromStr;
 
 use crate::wire::{Error, Checksum, Result};
 use super::{Ipv4Address, Ipv4Cidr, Ipv4Repr, Ipv4Subnet, ipv4_packet};
@@ -52,7 +54,9 @@ enum_with_unknown! {
         Ipv6Frag  = 0x2c,
         Icmpv6    = 0x3a,
         Ipv6NoNxt = 0x3b,
-        Ipv6Opts  = 0x3c
+        Ipv6Opts  = 0x3c,
+        Sctp      = 0x84,
+        UdpLite   = 0x88
     }
 }
 
@@ -69,6 +73,8 @@ impl fmt::Display for Protocol {
             Protocol::Icmpv6      => write!(f, "ICMPv6"),
             Protocol::Ipv6NoNxt   => write!(f, "IPv6-NoNxt"),
             Protocol::Ipv6Opts    => write!(f, "IPv6-Opts"),
+            Protocol::Sctp        => write!(f, "SCTP"),
+            Protocol::UdpLite     => write!(f, "UDP-Lite"),
             Protocol::Unknown(id) => write!(f, "0x{:02x}", id)
         }
     }
@@ -146,6 +152,16 @@ impl Address {
         }
     }
 
+    /// Query whether the address is only valid on the local link.
+    pub fn is_link_local(&self) -> bool {
+        match self {
+            Address::Unspecified     => false,
+            Address::Ipv4(addr)      => addr.is_link_local(),
+            Address::Ipv6(addr)      => addr.is_link_local(),
+            Address::__Nonexhaustive => unreachable!()
+        }
+    }
+
     /// Query whether the address falls into the "unspecified" range.
     pub fn is_unspecified(&self) -> bool {
         match self {
@@ -195,30 +211,50 @@ impl Address {
     }
 }
 
-#[cfg(feature = "std")]
-impl From<::std::net::IpAddr> for Address {
-    fn from(x: ::std::net::IpAddr) -> Address {
+impl From<net::IpAddr> for Address {
+    fn from(x: net::IpAddr) -> Address {
         match x {
-            ::std::net::IpAddr::V4(ipv4) => Address::Ipv4(ipv4.into()),
-            ::std::net::IpAddr::V6(ipv6) => Address::Ipv6(ipv6.into()),
+            net::IpAddr::V4(ipv4) => Address::Ipv4(ipv4.into()),
+            net::IpAddr::V6(ipv6) => Address::Ipv6(ipv6.into()),
         }
     }
 }
 
-#[cfg(feature = "std")]
-impl From<::std::net::Ipv4Addr> for Address {
-    fn from(ipv4: ::std::net::Ipv4Addr) -> Address {
+impl From<net::Ipv4Addr> for Address {
+    fn from(ipv4: net::Ipv4Addr) -> Address {
         Address::Ipv4(ipv4.into())
     }
 }
 
-#[cfg(feature = "std")]
-impl From<::std::net::Ipv6Addr> for Address {
-    fn from(ipv6: ::std::net::Ipv6Addr) -> Address {
+impl From<net::Ipv6Addr> for Address {
+    fn from(ipv6: net::Ipv6Addr) -> Address {
         Address::Ipv6(ipv6.into())
     }
 }
 
+impl TryFrom<Address> for net::IpAddr {
+    type Error = UnspecifiedAddressError;
+
+    /// Convert a specified address.
+    ///
+    /// The unspecified address has no version and thus no equivalent.
+    fn try_from(addr: Address) -> core::result::Result<Self, UnspecifiedAddressError> {
+        match addr {
+            Address::Ipv4(ipv4) => Ok(net::IpAddr::V4(ipv4.into())),
+            Address::Ipv6(ipv6) => Ok(net::IpAddr::V6(ipv6.into())),
+            Address::Unspecified | Address::__Nonexhaustive => Err(UnspecifiedAddressError {
+                _private: (),
+            }),
+        }
+    }
+}
+
+/// Error emitted when converting an unspecified address to a type that requires a version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnspecifiedAddressError {
+    _private: (),
+}
+
 impl Default for Address {
     fn default() -> Address {
         Address::Unspecified
@@ -248,6 +284,48 @@ impl fmt::Display for Address {
     }
 }
 
+impl FromStr for Address {
+    type Err = ParseAddressError;
+
+    /// Parse an IPv4 or IPv6 address.
+    ///
+    /// The unspecified address is written as `*`, same as in its `Display` representation.
+    fn from_str(src: &str) -> core::result::Result<Self, ParseAddressError> {
+        if src == "*" {
+            Ok(Address::Unspecified)
+        } else if src.contains(':') {
+            src.parse().map(Address::Ipv6)
+        } else {
+            src.parse().map(Address::Ipv4)
+        }
+    }
+}
+
+/// Error emitted when parsing an IP address fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseAddressError {
+    _private: (),
+}
+
+/// Error emitted when parsing a CIDR specifier fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseCidrError {
+    kind: ParseCidrErrorKind,
+}
+
+/// The general kind of failure during parsing of a CIDR.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ParseCidrErrorKind {
+    /// The subnet prefix was missing entirely.
+    NoSubnet,
+
+    /// The address part is invalid.
+    AddrParseError,
+
+    /// The subnet prefix is invalid.
+    InvalidPrefix,
+}
+
 /// A specification of a CIDR block, containing an address and a variable-length
 /// subnet masking prefix length.
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
@@ -413,6 +491,71 @@ impl fmt::Display for Cidr {
     }
 }
 
+impl FromStr for Cidr {
+    type Err = ParseCidrError;
+
+    /// Parse an IPv4 or IPv6 address with prefix length.
+    fn from_str(src: &str) -> core::result::Result<Self, ParseCidrError> {
+        if src.contains(':') {
+            src.parse().map(Cidr::Ipv6)
+        } else {
+            src.parse().map(Cidr::Ipv4)
+        }
+    }
+}
+
+impl ParseAddressError {
+    pub(crate) fn new() -> Self {
+        ParseAddressError { _private: () }
+    }
+}
+
+impl ParseCidrError {
+    pub(crate) fn address() -> Self {
+        ParseCidrError { kind: ParseCidrErrorKind::AddrParseError }
+    }
+}
+
+impl fmt::Display for UnspecifiedAddressError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("the unspecified address has no IP version")
+    }
+}
+
+impl fmt::Display for ParseAddressError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid IP address syntax")
+    }
+}
+
+impl fmt::Display for ParseCidrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self.kind {
+            ParseCidrErrorKind::NoSubnet => "missing subnet prefix separator",
+            ParseCidrErrorKind::AddrParseError => "invalid address",
+            ParseCidrErrorKind::InvalidPrefix => "invalid cidr prefix",
+        })
+    }
+}
+
+/// Parse a decimal number without sign or redundant leading zeros.
+pub(crate) fn parse_decimal(src: &str) -> Option<u32> {
+    let valid = (1..=3).contains(&src.len())
+        && src.bytes().all(|b| b.is_ascii_digit())
+        && (src == "0" || !src.starts_with('0'));
+    if valid { src.parse().ok() } else { None }
+}
+
+/// Split a CIDR specifier into the address part and a prefix length of at most `max`.
+pub(crate) fn split_cidr(src: &str, max: u8) -> core::result::Result<(&str, u8), ParseCidrError> {
+    let subnet = src.find('/')
+        .ok_or(ParseCidrError { kind: ParseCidrErrorKind::NoSubnet })?;
+    let prefix_len = parse_decimal(&src[subnet + 1..])
+        .filter(|&prefix| prefix <= u32::from(max))
+        .ok_or(ParseCidrError { kind: ParseCidrErrorKind::InvalidPrefix })?;
+    Ok((&src[..subnet], prefix_len as u8))
+}
+
 /// An internet endpoint address.
 ///
 /// An endpoint can be constructed from a port, in which case the address is unspecified.
@@ -437,9 +580,8 @@ impl Endpoint {
     }
 }
 
-#[cfg(feature = "std")]
-impl From<::std::net::SocketAddr> for Endpoint {
-    fn from(x: ::std::net::SocketAddr) -> Endpoint {
+impl From<net::SocketAddr> for Endpoint {
+    fn from(x: net::SocketAddr) -> Endpoint {
         Endpoint {
             addr: x.ip().into(),
             port: x.port(),
@@ -447,26 +589,33 @@ impl From<::std::net::SocketAddr> for Endpoint {
     }
 }
 
-#[cfg(feature = "std")]
-impl From<::std::net::SocketAddrV4> for Endpoint {
-    fn from(x: ::std::net::SocketAddrV4) -> Endpoint {
+impl From<net::SocketAddrV4> for Endpoint {
+    fn from(x: net::SocketAddrV4) -> Endpoint {
         Endpoint {
-            addr: x.ip().clone().into(),
+            addr: (*x.ip()).into(),
             port: x.port(),
         }
     }
 }
 
-#[cfg(feature = "std")]
-impl From<::std::net::SocketAddrV6> for Endpoint {
-    fn from(x: ::std::net::SocketAddrV6) -> Endpoint {
+impl From<net::SocketAddrV6> for Endpoint {
+    fn from(x: net::SocketAddrV6) -> Endpoint {
         Endpoint {
-            addr: x.ip().clone().into(),
+            addr: (*x.ip()).into(),
             port: x.port(),
         }
     }
 }
 
+impl TryFrom<Endpoint> for net::SocketAddr {
+    type Error = UnspecifiedAddressError;
+
+    fn try_from(endpoint: Endpoint) -> core::result::Result<Self, UnspecifiedAddressError> {
+        let ip = net::IpAddr::try_from(endpoint.addr)?;
+        Ok(net::SocketAddr::new(ip, endpoint.port))
+    }
+}
+
 impl fmt::Display for Endpoint {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}:{}", self.addr, self.port)
@@ -755,6 +904,7 @@ impl Repr {
 }
 
 pub(crate) mod checksum {
+    use core::convert::TryInto;
     use byteorder::{ByteOrder, NetworkEndian};
 
     use super::*;
@@ -765,35 +915,137 @@ pub(crate) mod checksum {
     }
 
     /// Compute an RFC 1071 compliant checksum (without the final complement).
-    pub(crate) fn data(mut data: &[u8]) -> u16 {
-        let mut accum = 0;
-
-        // For each 32-byte chunk...
-        const CHUNK_SIZE: usize = 32;
-        while data.len() >= CHUNK_SIZE {
-            let mut d = &data[..CHUNK_SIZE];
-            // ... take by 2 bytes and sum them.
-            while d.len() >= 2 {
-                accum += NetworkEndian::read_u16(d) as u32;
-                d = &d[2..];
+    ///
+    /// Uses vector instructions where available, decided at runtime with `std` and at compile time
+    /// otherwise. All variants compute the same result as `data_u64`. Only x86-64 has vectorized
+    /// variants so far, other architectures including aarch64 always use `data_u64`.
+    pub(crate) fn data(data: &[u8]) -> u16 {
+        #[cfg(all(feature = "std", target_arch = "x86_64"))] {
+            if std::is_x86_feature_detected!("avx2") {
+                // SAFETY: the cpu supports the target feature.
+                return unsafe { x86::data_avx2(data) };
             }
+        }
 
-            data = &data[CHUNK_SIZE..];
+        #[cfg(all(target_arch = "x86_64", target_feature = "sse2"))] {
+            // SAFETY: the target feature is enabled at compile time.
+            return unsafe { x86::data_sse2(data) };
         }
 
-        // Sum the rest that does not fit the last 32-byte chunk,
-        // taking by 2 bytes.
-        while data.len() >= 2 {
-            accum += NetworkEndian::read_u16(data) as u32;
-            data = &data[2..];
+        #[allow(unreachable_code)]
+        data_u64(data)
+    }
+
+    /// Compute the checksum with 64-bit additions.
+    ///
+    /// The one's complement sum is independent of the word size, as long as the carries are
+    /// added back in, so sum eight bytes at a time and fold the result to 16 bits.
+    pub(crate) fn data_u64(data: &[u8]) -> u16 {
+        let mut words = data.chunks_exact(8);
+        let mut accum = 0u64;
+        for word in &mut words {
+            let word = u64::from_be_bytes(word.try_into().unwrap());
+            let (sum, carry) = accum.overflowing_add(word);
+            accum = sum + u64::from(carry);
+        }
+
+        let mut rest = words.remainder();
+        while rest.len() >= 2 {
+            accum = fold_add(accum, NetworkEndian::read_u16(rest).into());
+            rest = &rest[2..];
         }
 
         // Add the last remaining odd byte, if any.
-        if let Some(&value) = data.first() {
-            accum += (value as u32) << 8;
+        if let Some(&value) = rest.first() {
+            accum = fold_add(accum, u64::from(value) << 8);
         }
 
-        propagate_carries(accum)
+        fold(accum)
+    }
+
+    /// Add with end-around carry.
+    fn fold_add(accum: u64, value: u64) -> u64 {
+        let (sum, carry) = accum.overflowing_add(value);
+        sum + u64::from(carry)
+    }
+
+    /// Fold a 64-bit one's complement sum into 16 bits.
+    fn fold(accum: u64) -> u16 {
+        let accum = (accum >> 32) + (accum & 0xffff_ffff);
+        propagate_carries((accum >> 32) as u32 + accum as u32)
+    }
+
+    /// Vectorized checksums.
+    ///
+    /// The lanes sum little endian words, which yields the byte swapped checksum. This is byte
+    /// order independence as shown in RFC 1071. The 16-bit words are added to 32-bit lanes, which
+    /// can not overflow for up to 65536 additions. Any rest that does not fill a vector is handled
+    /// by the scalar implementation.
+    #[cfg(target_arch = "x86_64")]
+    mod x86 {
+        use core::arch::x86_64::*;
+        use super::{combine, data_u64, fold, fold_add};
+
+        /// Vectors summed before the lanes are reduced, each adds two words to every lane.
+        const BLOCK: usize = 1 << 15;
+
+        /// # Safety
+        ///
+        /// The cpu must support the `sse2` target feature.
+        #[cfg(target_feature = "sse2")]
+        #[target_feature(enable = "sse2")]
+        pub(super) unsafe fn data_sse2(data: &[u8]) -> u16 {
+            let mut vectors = data.chunks_exact(16);
+            let mut accum = 0;
+            let low = _mm_set1_epi32(0xffff);
+
+            while vectors.len() > 0 {
+                let mut lanes = _mm_setzero_si128();
+                for vector in vectors.by_ref().take(BLOCK) {
+                    // SAFETY: the chunk has 16 bytes, unaligned loads are permitted.
+                    let vector = unsafe { _mm_loadu_si128(vector.as_ptr() as *const __m128i) };
+                    lanes = _mm_add_epi32(lanes, _mm_and_si128(vector, low));
+                    lanes = _mm_add_epi32(lanes, _mm_srli_epi32(vector, 16));
+                }
+
+                let mut sums = [0u32; 4];
+                // SAFETY: the array has 16 bytes, unaligned stores are permitted.
+                unsafe { _mm_storeu_si128(sums.as_mut_ptr() as *mut __m128i, lanes) };
+                accum = sums.iter().fold(accum, |accum, &sum| fold_add(accum, sum.into()));
+            }
+
+            let vectorized = fold(accum).swap_bytes();
+            combine(&[vectorized, data_u64(vectors.remainder())])
+        }
+
+        /// # Safety
+        ///
+        /// The cpu must support the `avx2` target feature.
+        #[cfg(any(feature = "std", test))]
+        #[target_feature(enable = "avx2")]
+        pub(super) unsafe fn data_avx2(data: &[u8]) -> u16 {
+            let mut vectors = data.chunks_exact(32);
+            let mut accum = 0;
+            let low = _mm256_set1_epi32(0xffff);
+
+            while vectors.len() > 0 {
+                let mut lanes = _mm256_setzero_si256();
+                for vector in vectors.by_ref().take(BLOCK) {
+                    // SAFETY: the chunk has 32 bytes, unaligned loads are permitted.
+                    let vector = unsafe { _mm256_loadu_si256(vector.as_ptr() as *const __m256i) };
+                    lanes = _mm256_add_epi32(lanes, _mm256_and_si256(vector, low));
+                    lanes = _mm256_add_epi32(lanes, _mm256_srli_epi32(vector, 16));
+                }
+
+                let mut sums = [0u32; 8];
+                // SAFETY: the array has 32 bytes, unaligned stores are permitted.
+                unsafe { _mm256_storeu_si256(sums.as_mut_ptr() as *mut __m256i, lanes) };
+                accum = sums.iter().fold(accum, |accum, &sum| fold_add(accum, sum.into()));
+            }
+
+            let vectorized = fold(accum).swap_bytes();
+            combine(&[vectorized, data_u64(vectors.remainder())])
+        }
     }
 
     /// Combine several RFC 1071 compliant checksums.
@@ -805,6 +1057,22 @@ pub(crate) mod checksum {
         propagate_carries(accum)
     }
 
+    /// Compute the checksum of all segments of a payload, as if they were contiguous.
+    ///
+    /// A segment of odd length shifts all following data by one byte. Their checksums are byte
+    /// swapped accordingly, again by the byte order independence shown in RFC 1071.
+    pub(crate) fn segments(payload: &(impl crate::wire::PayloadVectored + ?Sized)) -> u16 {
+        let mut accum = 0;
+        let mut odd = false;
+        for segment in (0..payload.segment_count()).filter_map(|idx| payload.segment(idx)) {
+            let sum = data(segment);
+            let sum = if odd { sum.swap_bytes() } else { sum };
+            accum = combine(&[accum, sum]);
+            odd ^= segment.len() % 2 == 1;
+        }
+        accum
+    }
+
     /// Compute an IP pseudo header checksum.
     pub(crate) fn pseudo_header(src_addr: &Address, dst_addr: &Address,
                          protocol: Protocol, length: u32) -> u16 {
@@ -845,23 +1113,108 @@ pub(crate) mod checksum {
             Ok(())
         }
     }
+
+    /// The variants of the checksum, public for the benchmarks only.
+    pub mod variants {
+        /// The checksum with the fastest variant available, as used for packets.
+        pub fn data(data: &[u8]) -> u16 {
+            super::data(data)
+        }
+
+        /// The checksum with 64-bit additions.
+        pub fn data_u64(data: &[u8]) -> u16 {
+            super::data_u64(data)
+        }
+
+        /// The checksum with `sse2` instructions, if enabled at compile time.
+        pub fn data_sse2(data: &[u8]) -> Option<u16> {
+            #[cfg(all(target_arch = "x86_64", target_feature = "sse2"))] {
+                // SAFETY: the target feature is enabled at compile time.
+                return Some(unsafe { super::x86::data_sse2(data) });
+            }
+
+            #[allow(unreachable_code)]
+            None
+        }
+
+        /// The checksum with `avx2` instructions, if the cpu supports them.
+        #[cfg(feature = "std")]
+        pub fn data_avx2(data: &[u8]) -> Option<u16> {
+            #[cfg(target_arch = "x86_64")] {
+                if std::is_x86_feature_detected!("avx2") {
+                    // SAFETY: the cpu supports the target feature.
+                    return Some(unsafe { super::x86::data_avx2(data) });
+                }
+            }
+
+            None
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// The plain RFC 1071 algorithm, summing one word at a time.
+        fn reference(data: &[u8]) -> u16 {
+            let mut accum = 0u64;
+            for word in data.chunks(2) {
+                let high = u64::from(word[0]) << 8;
+                let low = word.get(1).copied().map_or(0, u64::from);
+                accum += high | low;
+            }
+            fold(accum)
+        }
+
+        #[test]
+        fn variants() {
+            let pattern: std::vec::Vec<u8> = (0..1031u32)
+                .map(|i| (i.wrapping_mul(0x9e37_79b9) >> 24) as u8)
+                .collect();
+            // All lengths around the vector sizes and all odd remainders.
+            for len in 0..pattern.len() {
+                let data = &pattern[..len];
+                let expected = reference(data);
+                assert_eq!(data_u64(data), expected, "{}", len);
+                assert_eq!(super::data(data), expected, "{}", len);
+                #[cfg(target_arch = "x86_64")] {
+                    if std::is_x86_feature_detected!("avx2") {
+                        assert_eq!(unsafe { x86::data_avx2(data) }, expected, "{}", len);
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn no_lane_overflow() {
+            // More than one block of vectors, with the maximum value in every lane.
+            let data = std::vec![0xff; 3 << 20 | 7];
+            let expected = reference(&data);
+            assert_eq!(data_u64(&data), expected);
+            assert_eq!(super::data(&data), expected);
+            #[cfg(target_arch = "x86_64")] {
+                assert_eq!(unsafe { x86::data_sse2(&data) }, expected);
+                if std::is_x86_feature_detected!("avx2") {
+                    assert_eq!(unsafe { x86::data_avx2(&data) }, expected);
+                }
+            }
+        }
+    }
 }
 
-use super::pretty_print::PrettyIndent;
+use super::pretty_print::{PrettyIndent, PrettyPrint};
 
 pub(crate) fn pretty_print_ip_payload<T: Into<Repr>>(f: &mut fmt::Formatter, indent: &mut PrettyIndent,
                                               ip_repr: T, payload: &[u8]) -> fmt::Result {
-    use crate::wire::{TcpChecksum, TcpPacket, UdpChecksum, UdpRepr, udp_packet};
+    use crate::wire::{TcpChecksum, TcpPacket, UdpChecksum, UdpRepr, icmpv4_packet, udp_packet};
     use crate::wire::ip::checksum::format_checksum;
 
     let repr = ip_repr.into();
     match repr.protocol() {
-        /*
         Protocol::Icmp => {
             indent.increase(f)?;
-            Icmpv4Packet::<&[u8]>::pretty_print(&payload.as_ref(), f, indent)
+            icmpv4_packet::pretty_print(payload, f, indent)
         }
-        */
         Protocol::Udp => {
             indent.increase(f)?;
             match udp_packet::new_checked(payload.as_ref()) {
@@ -879,6 +1232,23 @@ pub(crate) fn pretty_print_ip_payload<T: Into<Repr>>(f: &mut fmt::Formatter, ind
                 }
             }
         }
+        Protocol::UdpLite => {
+            indent.increase(f)?;
+            match udp_packet::new_checked_lite(payload) {
+                Err(err) => write!(f, "{}({})", indent, err),
+                Ok(udp_packet) => {
+                    match UdpRepr::parse_lite(udp_packet, UdpChecksum::Ignored) {
+                        Err(err) => write!(f, "{}({})", indent, err),
+                        Ok(udp_repr) => {
+                            write!(f, "{}{}", indent, udp_repr)?;
+                            let valid = udp_packet.verify_checksum_lite(
+                                repr.src_addr(), repr.dst_addr());
+                            format_checksum(f, valid)
+                        }
+                    }
+                }
+            }
+        }
         Protocol::Tcp => {
             indent.increase(f)?;
             match TcpPacket::<&[u8]>::new_checked(payload.as_ref(), TcpChecksum::Ignored) {
@@ -910,9 +1280,43 @@ pub(crate) mod test {
     pub(crate) const MOCK_UNSPECIFIED: IpAddress = IpAddress::Ipv6(Ipv6Address::UNSPECIFIED);
 
     use super::*;
-    use crate::wire::{IpAddress, IpProtocol,IpCidr};
+    use crate::wire::{IpAddress, IpEndpoint, IpProtocol,IpCidr};
     use crate::wire::{Ipv4Address, Ipv4Repr};
 
+    #[test]
+    fn parse() {
+        assert_eq!("*".parse(), Ok(IpAddress::Unspecified));
+        assert_eq!("192.0.2.1".parse(), Ok(IpAddress::v4(192, 0, 2, 1)));
+        assert_eq!("fe80::1".parse(), Ok(MOCK_IP_ADDR_1));
+        assert!("fe80::1/64".parse::<IpAddress>().is_err());
+
+        assert_eq!("192.0.2.1/24".parse(), Ok(IpCidr::new(IpAddress::v4(192, 0, 2, 1), 24)));
+        assert_eq!("fe80::1/64".parse(), Ok(IpCidr::new(MOCK_IP_ADDR_1, 64)));
+        assert!("*/0".parse::<IpCidr>().is_err());
+        assert!("192.0.2.1/64".parse::<IpCidr>().is_err());
+    }
+
+    #[test]
+    fn net_conversions() {
+        use core::convert::TryFrom;
+        use core::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+        let v4 = Ipv4Addr::new(192, 0, 2, 1);
+        assert_eq!(Ipv4Address::from(v4), Ipv4Address::new(192, 0, 2, 1));
+        assert_eq!(Ipv4Addr::from(Ipv4Address::new(192, 0, 2, 1)), v4);
+
+        let v6 = Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+        assert_eq!(IpAddress::from(v6), MOCK_IP_ADDR_1);
+        assert_eq!(IpAddr::try_from(MOCK_IP_ADDR_1), Ok(IpAddr::V6(v6)));
+        assert!(IpAddr::try_from(IpAddress::Unspecified).is_err());
+
+        let socket = SocketAddr::new(IpAddr::V4(v4), 80);
+        let endpoint = IpEndpoint::from(socket);
+        assert_eq!(endpoint, IpEndpoint::new(IpAddress::v4(192, 0, 2, 1), 80));
+        assert_eq!(SocketAddr::try_from(endpoint), Ok(socket));
+        assert!(SocketAddr::try_from(IpEndpoint::from(80)).is_err());
+    }
+
     macro_rules! generate_common_tests {
         ($name:ident, $repr:ident, $ip_repr:path, $ip_addr:path,
          $addr_from:path, $nxthdr:ident, $bytes_a:expr, $bytes_b:expr,
@@ -1,11 +1,12 @@
 use core::{fmt, ops};
-#[cfg(feature = "std")]
+use core::convert::TryFrom;
 use core::str::FromStr;
 use byteorder::{ByteOrder, NetworkEndian};
 
-use super::{Reframe, Payload, PayloadError, PayloadMut, payload};
+use super::{Reframe, Payload, PayloadError, PayloadMut, PayloadParts, payload};
 use super::{Error, Checksum, Result};
-use super::ip::{checksum, pretty_print_ip_payload};
+use super::ip::{checksum, parse_decimal, pretty_print_ip_payload, split_cidr};
+use super::ip::{ParseAddressError, ParseCidrError};
 use super::field::Field;
 
 pub(crate) use super::IpProtocol as Protocol;
@@ -127,16 +128,14 @@ impl Address {
     }
 }
 
-#[cfg(feature = "std")]
-impl From<::std::net::Ipv4Addr> for Address {
-    fn from(x: ::std::net::Ipv4Addr) -> Address {
+impl From<core::net::Ipv4Addr> for Address {
+    fn from(x: core::net::Ipv4Addr) -> Address {
         Address(x.octets())
     }
 }
 
-#[cfg(feature = "std")]
-impl From<Address> for ::std::net::Ipv4Addr {
-    fn from(Address(x): Address) -> ::std::net::Ipv4Addr {
+impl From<Address> for core::net::Ipv4Addr {
+    fn from(Address(x): Address) -> core::net::Ipv4Addr {
         x.into()
     }
 }
@@ -381,71 +380,41 @@ impl Subnet {
     }
 }
 
-/// Error emitted when parsing an IPv4 CIDR specifier fails.
-#[cfg(feature = "std")]
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub struct ParseCidrError {
-    kind: ParseCidrErrorKind,
-}
-
-/// The general kind of failure during parsing of an IPv4 CIDR.
-#[cfg(feature = "std")]
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-enum ParseCidrErrorKind {
-    /// The subnet prefix was missing entirely.
-    NoSubnet,
-
-    /// The IPv4 address part is invalid.
-    AddrParseError,
-
-    /// The subnet prefix is invalid.
-    InvalidPrefix,
-}
-
 impl fmt::Display for Cidr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}/{}", self.address, self.prefix_len)
     }
 }
 
-#[cfg(feature = "std")]
-impl fmt::Display for ParseCidrError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.write_str(match self.kind {
-            ParseCidrErrorKind::NoSubnet => "missing subnet prefix separator",
-            ParseCidrErrorKind::AddrParseError => "invalid address",
-            ParseCidrErrorKind::InvalidPrefix => "invalid cidr prefix",
-        })
+impl FromStr for Address {
+    type Err = ParseAddressError;
+
+    /// Parse an address in dotted decimal notation, such as `192.0.2.1`.
+    fn from_str(src: &str) -> core::result::Result<Self, ParseAddressError> {
+        let mut octets = [0; 4];
+        let mut parts = src.split('.');
+        for octet in octets.iter_mut() {
+            let part = parts.next().ok_or_else(ParseAddressError::new)?;
+            *octet = parse_decimal(part)
+                .and_then(|value| u8::try_from(value).ok())
+                .ok_or_else(ParseAddressError::new)?;
+        }
+
+        match parts.next() {
+            Some(_) => Err(ParseAddressError::new()),
+            None => Ok(Address(octets)),
+        }
     }
 }
 
-#[cfg(feature = "std")]
 impl FromStr for Cidr {
     type Err = ParseCidrError;
 
-    fn from_str(src :&str) -> core::result::Result<Self, ParseCidrError> {
-        let subnet = src.find('/')
-            .ok_or(ParseCidrError {
-                kind: ParseCidrErrorKind::NoSubnet,
-            })?;
-        let address: std::net::Ipv4Addr = src[..subnet]
-            .parse()
-            .map_err(|_| ParseCidrError {
-                kind: ParseCidrErrorKind::AddrParseError,
-            })?;
-        let prefix_len = src[subnet+1..]
-            .parse()
-            .map_err(|_| ParseCidrError {
-                kind: ParseCidrErrorKind::InvalidPrefix,
-            })
-            .and_then(|prefix| if prefix <= 32 {
-                Ok(prefix)
-            } else {
-                Err(ParseCidrError {
-                    kind: ParseCidrErrorKind::InvalidPrefix,
-                })
-            })?;
-        Ok(Cidr { address: address.into(), prefix_len })
+    /// Parse an address with prefix length, such as `192.0.2.1/24`.
+    fn from_str(src: &str) -> core::result::Result<Self, ParseCidrError> {
+        let (address, prefix_len) = split_cidr(src, 32)?;
+        let address = address.parse().map_err(|_| ParseCidrError::address())?;
+        Ok(Cidr { address, prefix_len })
     }
 }
 
@@ -475,6 +444,12 @@ mod field {
     pub(crate) const CHECKSUM: Field = 10..12;
     pub(crate) const SRC_ADDR: Field = 12..16;
     pub(crate) const DST_ADDR: Field = 16..20;
+
+    pub(crate) const OPT_END: u8 = 0x00;
+    pub(crate) const OPT_NOP: u8 = 0x01;
+    pub(crate) const OPT_RR:  u8 = 0x07;
+    pub(crate) const OPT_TS:  u8 = 0x44;
+    pub(crate) const OPT_RA:  u8 = 0x94;
 }
 
 impl ipv4 {
@@ -740,6 +715,22 @@ impl ipv4 {
         header_end..total_len
     }
 
+    /// Return the options between the fixed header and the payload.
+    ///
+    /// Use [`Ipv4Option::parse`] to split them into single options.
+    ///
+    /// [`Ipv4Option::parse`]: enum.Ipv4Option.html#method.parse
+    pub fn options(&self) -> &[u8] {
+        let header_end = usize::from(self.header_len()).max(field::DST_ADDR.end);
+        &self.0[field::DST_ADDR.end..header_end]
+    }
+
+    /// Return the options as a mutable byte slice.
+    pub fn options_mut(&mut self) -> &mut [u8] {
+        let header_end = usize::from(self.header_len()).max(field::DST_ADDR.end);
+        &mut self.0[field::DST_ADDR.end..header_end]
+    }
+
     /// Return the payload as a byte slice.
     pub fn payload_slice(&self) -> &[u8] {
         let range = self.payload_range();
@@ -813,6 +804,38 @@ impl<T: Payload> Packet<T> {
 }
 
 impl<T: Payload + PayloadMut> Packet<T> {
+    /// Change the time to live of the packet.
+    ///
+    /// Updates both the header and the representation. The checksum is not recalculated, call
+    /// [`fill_checksum`] afterwards if necessary.
+    ///
+    /// [`fill_checksum`]: #method.fill_checksum
+    pub fn set_hop_limit(&mut self, hop_limit: u8) {
+        ipv4::new_unchecked_mut(self.buffer.payload_mut())
+            .set_hop_limit(hop_limit);
+        self.repr.hop_limit = hop_limit;
+    }
+
+    /// Change the differentiated services code point of the packet.
+    ///
+    /// Like [`set_hop_limit`], this does not update the checksum.
+    ///
+    /// [`set_hop_limit`]: #method.set_hop_limit
+    pub fn set_dscp(&mut self, dscp: u8) {
+        ipv4::new_unchecked_mut(self.buffer.payload_mut())
+            .set_dscp(dscp);
+    }
+
+    /// Change the explicit congestion notification bits of the packet.
+    ///
+    /// Like [`set_hop_limit`], this does not update the checksum.
+    ///
+    /// [`set_hop_limit`]: #method.set_hop_limit
+    pub fn set_ecn(&mut self, ecn: u8) {
+        ipv4::new_unchecked_mut(self.buffer.payload_mut())
+            .set_ecn(ecn);
+    }
+
     /// Recalculate the checksum if necessary.
     ///
     /// Note that the checksum test can be elided even in a checked parse of the ipv4 frame. This
@@ -877,6 +900,174 @@ impl<T: Payload + PayloadMut> PayloadMut for Packet<T> {
     }
 }
 
+/// A single option of the IPv4 header, rfc791 section 3.1.
+///
+/// The route and timestamp options are filled in by the routers along the path. Their `pointer`
+/// is the one-based octet offset within the option, counting from its type octet, of the next
+/// free slot. `data` holds all slots, also those not yet filled.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HeaderOption<'a> {
+    /// Marks the last option. Trailing bytes are ignored.
+    EndOfList,
+    /// An option without effect, used for alignment.
+    NoOperation,
+    /// Record the addresses of the routers forwarding the packet.
+    RecordRoute {
+        /// The offset of the next free address slot.
+        pointer: u8,
+        /// The slots for recorded addresses, four octets each.
+        data: &'a [u8],
+    },
+    /// Record the time at which routers forwarded the packet, rfc791 and rfc781.
+    Timestamp {
+        /// The offset of the next free slot.
+        pointer: u8,
+        /// The number of routers that could not record a timestamp for lack of space.
+        overflow: u8,
+        /// Whether only timestamps (0) or also addresses (1) are recorded, or whether the
+        /// addresses are prespecified (3).
+        flags: u8,
+        /// The slots for timestamps, possibly prefixed with addresses.
+        data: &'a [u8],
+    },
+    /// Ask routers to examine the packet more closely, rfc2113.
+    RouterAlert(u16),
+    /// Some option not handled within the library itself.
+    Unknown { kind: u8, data: &'a [u8] },
+}
+
+impl<'a> HeaderOption<'a> {
+    /// The longest options permitted by the header length field.
+    pub const MAX_LEN: usize = 40;
+
+    /// Split the first option from a buffer.
+    ///
+    /// The buffer should be the options of an IPv4 header.
+    pub fn parse(buffer: &'a [u8]) -> Result<(&'a [u8], HeaderOption<'a>)> {
+        let (length, option): (usize, HeaderOption);
+        match *buffer.first().ok_or(Error::Truncated)? {
+            field::OPT_END => {
+                length = 1;
+                option = HeaderOption::EndOfList;
+            }
+            field::OPT_NOP => {
+                length = 1;
+                option = HeaderOption::NoOperation;
+            }
+            kind => {
+                length = buffer.get(1).copied().ok_or(Error::Truncated)?.into();
+                let data = buffer.get(2..length).ok_or(Error::Truncated)?;
+                match (kind, length) {
+                    (field::OPT_RR, 3..=255) => option = HeaderOption::RecordRoute {
+                        pointer: data[0],
+                        data: &data[1..],
+                    },
+                    (field::OPT_RR, _) =>
+                        return Err(Error::Malformed),
+                    (field::OPT_TS, 4..=255) => option = HeaderOption::Timestamp {
+                        pointer: data[0],
+                        overflow: data[1] >> 4,
+                        flags: data[1] & 0x0f,
+                        data: &data[2..],
+                    },
+                    (field::OPT_TS, _) =>
+                        return Err(Error::Malformed),
+                    (field::OPT_RA, 4) =>
+                        option = HeaderOption::RouterAlert(NetworkEndian::read_u16(data)),
+                    (field::OPT_RA, _) =>
+                        return Err(Error::Malformed),
+                    (_, _) =>
+                        option = HeaderOption::Unknown { kind, data },
+                }
+            }
+        }
+        Ok((&buffer[length..], option))
+    }
+
+    /// Get the buffer length required to encode this header option.
+    pub fn buffer_len(&self) -> usize {
+        match self {
+            HeaderOption::EndOfList => 1,
+            HeaderOption::NoOperation => 1,
+            HeaderOption::RecordRoute { data, .. } => 3 + data.len(),
+            HeaderOption::Timestamp { data, .. } => 4 + data.len(),
+            HeaderOption::RouterAlert(_) => 4,
+            HeaderOption::Unknown { data, .. } => 2 + data.len(),
+        }
+    }
+
+    /// Get the length of the header options encoding a list of options.
+    ///
+    /// The options are padded to a multiple of four octets, as required by the header length.
+    pub fn options_len(options: &[HeaderOption]) -> usize {
+        let len: usize = options.iter().map(HeaderOption::buffer_len).sum();
+        len.div_ceil(4) * 4
+    }
+
+    /// Write a list of options into the options of a header, padding the rest.
+    ///
+    /// Returns `Err(Error::Truncated)` if the options do not fit into the buffer.
+    pub fn emit_all(options: &[HeaderOption], buffer: &mut [u8]) -> Result<()> {
+        if HeaderOption::options_len(options) > buffer.len() {
+            return Err(Error::Truncated);
+        }
+
+        let mut tail = buffer;
+        for option in options {
+            tail = option.emit(tail);
+        }
+        HeaderOption::EndOfList.emit(tail);
+        Ok(())
+    }
+
+    /// Write the encoding into the buffer.
+    ///
+    /// Returns the remaining tail into which no data has been written.
+    pub fn emit<'b>(&self, buffer: &'b mut [u8]) -> &'b mut [u8] {
+        let length;
+        match *self {
+            HeaderOption::EndOfList => {
+                length = buffer.len().min(1);
+                // There may be padding space which also should be initialized.
+                for p in buffer.iter_mut() {
+                    *p = field::OPT_END;
+                }
+            }
+            HeaderOption::NoOperation => {
+                length = 1;
+                buffer[0] = field::OPT_NOP;
+            }
+            HeaderOption::RecordRoute { pointer, data } => {
+                length = self.buffer_len();
+                buffer[0] = field::OPT_RR;
+                buffer[2] = pointer;
+                buffer[3..length].copy_from_slice(data);
+            }
+            HeaderOption::Timestamp { pointer, overflow, flags, data } => {
+                length = self.buffer_len();
+                buffer[0] = field::OPT_TS;
+                buffer[2] = pointer;
+                buffer[3] = overflow << 4 | flags & 0x0f;
+                buffer[4..length].copy_from_slice(data);
+            }
+            HeaderOption::RouterAlert(value) => {
+                length = self.buffer_len();
+                buffer[0] = field::OPT_RA;
+                NetworkEndian::write_u16(&mut buffer[2..4], value);
+            }
+            HeaderOption::Unknown { kind, data } => {
+                length = self.buffer_len();
+                buffer[0] = kind;
+                buffer[2..length].copy_from_slice(data);
+            }
+        }
+        if length > 1 {
+            buffer[1] = length as u8;
+        }
+        &mut buffer[length..]
+    }
+}
+
 /// A high-level representation of an Internet Protocol version 4 packet header.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Repr {
@@ -918,6 +1109,37 @@ impl Repr {
         })
     }
 
+    /// Parse an Internet Protocol version 4 packet stored in parts.
+    ///
+    /// Only the header, including its options, must be contained in the first part while the
+    /// payload may continue in the second, see [`PayloadParts`]. Performs the same checks as
+    /// `parse`, the checksum covers only the header.
+    ///
+    /// [`PayloadParts`]: trait.PayloadParts.html
+    pub fn parse_parts<P: PayloadParts + ?Sized>(packet: &P, checksum: Checksum)
+        -> Result<Repr>
+    {
+        let fixed = packet.contiguous(field::DST_ADDR.end).ok_or(Error::Truncated)?;
+        let header_len = usize::from(ipv4::new_unchecked(fixed).header_len());
+        let header = packet.contiguous(header_len.max(field::DST_ADDR.end))
+            .ok_or(Error::Truncated)?;
+        let header = ipv4::new_unchecked(header);
+
+        if header_len > usize::from(header.total_len()) { return Err(Error::Malformed) }
+        if header.version() != 4 { return Err(Error::Malformed) }
+        if checksum.manual() && !header.verify_checksum() { return Err(Error::WrongChecksum) }
+        if header.more_frags() || header.frag_offset() != 0 { return Err(Error::Unsupported) }
+        if packet.parts_len() < usize::from(header.total_len()) { return Err(Error::Truncated) }
+
+        Ok(Repr {
+            src_addr:    header.src_addr(),
+            dst_addr:    header.dst_addr(),
+            protocol:    header.protocol(),
+            payload_len: usize::from(header.total_len()) - header_len,
+            hop_limit:   header.hop_limit()
+        })
+    }
+
     /// Return the length of a header that will be emitted from this high-level representation.
     pub fn buffer_len(&self) -> usize {
         // We never emit any options.
@@ -1043,6 +1265,23 @@ mod test {
          0x00, 0x00, 0x00, 0x00,
          0x00, 0xff];
 
+    #[test]
+    fn test_parse_address() {
+        assert_eq!("192.0.2.1".parse(), Ok(Address([192, 0, 2, 1])));
+        assert_eq!("0.0.0.0".parse(), Ok(Address::UNSPECIFIED));
+        for invalid in ["", "1.2.3", "1.2.3.4.5", "1.2.3.256", "1.2..4", "01.2.3.4", "+1.2.3.4",
+                        "1.2.3.4 "].iter() {
+            assert!(invalid.parse::<Address>().is_err(), "{}", invalid);
+        }
+
+        let cidr: Cidr = "192.0.2.1/24".parse().unwrap();
+        assert_eq!(cidr, Cidr::new(Address([192, 0, 2, 1]), 24));
+        assert_eq!(cidr.to_string().parse(), Ok(cidr));
+        assert!("192.0.2.1".parse::<Cidr>().is_err());
+        assert!("192.0.2.1/33".parse::<Cidr>().is_err());
+        assert!("192.0.2/24".parse::<Cidr>().is_err());
+    }
+
     #[test]
     fn test_deconstruct() {
         let packet = ipv4::new_unchecked(&PACKET_BYTES[..]);
@@ -1137,6 +1376,30 @@ mod test {
         assert_eq!(repr, packet_repr());
     }
 
+    #[test]
+    fn test_parse_parts() {
+        use crate::wire::RingSlice;
+
+        // The payload wraps around the end of the ring.
+        let mut ring = [0; 28];
+        ring[8..].copy_from_slice(&REPR_PACKET_BYTES[..20]);
+        ring[..4].copy_from_slice(&REPR_PACKET_BYTES[20..]);
+        let packet = RingSlice::from_ring(&mut ring, 8, 24).unwrap();
+        assert_eq!(Repr::parse_parts(&packet, Checksum::Manual), Ok(packet_repr()));
+
+        // The header itself must not wrap.
+        let mut ring = [0; 28];
+        ring[12..].copy_from_slice(&REPR_PACKET_BYTES[..16]);
+        ring[..8].copy_from_slice(&REPR_PACKET_BYTES[16..]);
+        let packet = RingSlice::from_ring(&mut ring, 12, 24).unwrap();
+        assert_eq!(Repr::parse_parts(&packet, Checksum::Manual), Err(Error::Truncated));
+
+        let contiguous = &REPR_PACKET_BYTES[..];
+        assert_eq!(Repr::parse_parts(contiguous, Checksum::Manual), Ok(packet_repr()));
+        let truncated = &REPR_PACKET_BYTES[..23];
+        assert_eq!(Repr::parse_parts(truncated, Checksum::Manual), Err(Error::Truncated));
+    }
+
     #[test]
     fn test_parse_bad_version() {
         let mut bytes = vec![0; 24];
@@ -1164,6 +1427,48 @@ mod test {
         assert_eq!(packet.as_bytes(), &REPR_PACKET_BYTES[..]);
     }
 
+    static OPTION_BYTES: [u8; 16] =
+        [0x94, 0x04, 0x00, 0x00,
+         0x07, 0x0b, 0x08, 0x0a,
+         0x00, 0x00, 0x01, 0x00,
+         0x00, 0x00, 0x00, 0x00];
+
+    #[test]
+    fn test_options() {
+        let route = [0x0a, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00];
+        let options = [
+            HeaderOption::RouterAlert(0),
+            HeaderOption::RecordRoute { pointer: 8, data: &route },
+        ];
+        assert_eq!(HeaderOption::options_len(&options), 16);
+        let mut bytes = [0xa5; 16];
+        HeaderOption::emit_all(&options, &mut bytes).unwrap();
+        assert_eq!(bytes, OPTION_BYTES);
+        assert_eq!(HeaderOption::emit_all(&options, &mut [0; 12]), Err(Error::Truncated));
+
+        let mut parsed = vec![];
+        let mut buffer = &OPTION_BYTES[..];
+        while !buffer.is_empty() {
+            let (tail, option) = HeaderOption::parse(buffer).unwrap();
+            parsed.push(option);
+            buffer = tail;
+        }
+        // The padding is parsed as the end of the list.
+        assert_eq!(&parsed[..2], &options[..]);
+        assert_eq!(parsed[2], HeaderOption::EndOfList);
+
+        let (_, timestamp) = HeaderOption::parse(&[0x44, 0x08, 0x05, 0x21, 0, 0, 0, 0]).unwrap();
+        assert_eq!(timestamp, HeaderOption::Timestamp {
+            pointer: 5,
+            overflow: 2,
+            flags: 1,
+            data: &[0; 4],
+        });
+        assert_eq!(HeaderOption::parse(&[0x94, 0x03, 0x00]), Err(Error::Malformed));
+        assert_eq!(HeaderOption::parse(&[0x07, 0x01]), Err(Error::Truncated));
+        assert_eq!(HeaderOption::parse(&[0x07, 0x07, 0x04]), Err(Error::Truncated));
+    }
+
     #[test]
     fn test_unspecified() {
         assert!(Address::UNSPECIFIED.is_unspecified());
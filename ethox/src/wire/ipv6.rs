@@ -1,9 +1,11 @@
 use core::{fmt, ops};
+use core::str::FromStr;
 use byteorder::{ByteOrder, NetworkEndian};
 
-use super::{Error, Result, Payload, PayloadError, PayloadMut, Reframe, payload};
+use super::{Error, Result, Payload, PayloadError, PayloadMut, PayloadParts, Reframe, payload};
 use super::{Ipv4Address, EthernetAddress};
-use super::ip::pretty_print_ip_payload;
+use super::ipv6ext::ExtHeaders;
+use super::ip::{pretty_print_ip_payload, split_cidr, ParseAddressError, ParseCidrError};
 pub(crate) use super::IpProtocol as Protocol;
 
 /// Minimum MTU required of all links supporting IPv6. See [RFC 8200 § 5].
@@ -255,9 +257,8 @@ impl Address {
     /// unicast.
     pub fn solicited_node_multicast(&self) -> Address {
         assert!(self.is_unicast());
-        let mut bytes = [0xff, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
-        bytes[14..].copy_from_slice(&self.0[14..]);
+        let mut bytes = Cidr::SOLICITED_NODE_PREFIX.address.0;
+        bytes[13..].copy_from_slice(&self.0[13..]);
         Address(bytes)
     }
 
@@ -275,16 +276,14 @@ impl Address {
     }
 }
 
-#[cfg(feature = "std")]
-impl From<::std::net::Ipv6Addr> for Address {
-    fn from(x: ::std::net::Ipv6Addr) -> Address {
+impl From<core::net::Ipv6Addr> for Address {
+    fn from(x: core::net::Ipv6Addr) -> Address {
         Address(x.octets())
     }
 }
 
-#[cfg(feature = "std")]
-impl From<Address> for ::std::net::Ipv6Addr {
-    fn from(Address(x): Address) -> ::std::net::Ipv6Addr {
+impl From<Address> for core::net::Ipv6Addr {
+    fn from(Address(x): Address) -> core::net::Ipv6Addr {
         x.into()
     }
 }
@@ -372,6 +371,69 @@ impl fmt::Display for Address {
     }
 }
 
+impl FromStr for Address {
+    type Err = ParseAddressError;
+
+    /// Parse an address in the text representation of RFC 4291.
+    ///
+    /// One run of zero groups may be compressed with `::` and the last two groups may be written
+    /// as an IPv4 address in dotted decimal notation, such as `::ffff:192.0.2.1`.
+    fn from_str(src: &str) -> core::result::Result<Self, ParseAddressError> {
+        let mut parts = [0; 8];
+        match src.find("::") {
+            None => {
+                if parse_groups(src, &mut parts, true)? != 8 {
+                    return Err(ParseAddressError::new());
+                }
+            },
+            Some(split) => {
+                let mut tail = [0; 8];
+                let head_len = parse_groups(&src[..split], &mut parts, false)?;
+                let tail_len = parse_groups(&src[split + 2..], &mut tail, true)?;
+                // The compression must replace at least one group.
+                if head_len + tail_len > 7 {
+                    return Err(ParseAddressError::new());
+                }
+                parts[8 - tail_len..].copy_from_slice(&tail[..tail_len]);
+            },
+        }
+
+        Ok(Address::from_parts(&parts))
+    }
+}
+
+/// Parse colon separated groups, returning the number of groups.
+///
+/// An empty string contains no groups. If permitted, the last group may be an IPv4 address which
+/// fills two groups.
+fn parse_groups(src: &str, parts: &mut [u16; 8], ipv4: bool)
+    -> core::result::Result<usize, ParseAddressError>
+{
+    if src.is_empty() {
+        return Ok(0);
+    }
+
+    let mut count = 0;
+    let mut groups = src.split(':').peekable();
+    while let Some(group) = groups.next() {
+        if ipv4 && groups.peek().is_none() && group.contains('.') {
+            let Ipv4Address([a, b, c, d]) = group.parse()?;
+            let rest = parts.get_mut(count..count + 2).ok_or_else(ParseAddressError::new)?;
+            rest.copy_from_slice(&[u16::from_be_bytes([a, b]), u16::from_be_bytes([c, d])]);
+            count += 2;
+            continue;
+        }
+
+        let valid = (1..=4).contains(&group.len())
+            && group.bytes().all(|b| b.is_ascii_hexdigit());
+        let part = parts.get_mut(count).filter(|_| valid).ok_or_else(ParseAddressError::new)?;
+        *part = u16::from_str_radix(group, 16).map_err(|_| ParseAddressError::new())?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
 /// An IPv6 CIDR host: an address and a variable-length subnet masking prefix length.
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default)]
 pub struct Cidr {
@@ -512,6 +574,17 @@ impl fmt::Display for Cidr {
     }
 }
 
+impl FromStr for Cidr {
+    type Err = ParseCidrError;
+
+    /// Parse an address with prefix length, such as `2001:db8::1/64`.
+    fn from_str(src: &str) -> core::result::Result<Self, ParseCidrError> {
+        let (address, prefix_len) = split_cidr(src, 128)?;
+        let address = address.parse().map_err(|_| ParseCidrError::address())?;
+        Ok(Cidr { address, prefix_len })
+    }
+}
+
 /// A read/write wrapper around an Internet Protocol version 6 packet buffer.
 #[derive(Debug, PartialEq, Clone)]
 pub struct Packet<T: Payload> {
@@ -746,6 +819,11 @@ impl ipv6 {
         &self.0[range]
     }
 
+    /// Iterate over the extension headers at the start of the payload.
+    pub fn extension_headers(&self) -> ExtHeaders<'_> {
+        ExtHeaders::new(self.next_header(), self.payload_slice())
+    }
+
     /// Return a mutable pointer to the payload.
     #[inline]
     pub fn payload_mut_slice(&mut self) -> &mut [u8] {
@@ -803,6 +881,20 @@ impl<T: Payload> Packet<T> {
 }
 
 impl<T: PayloadMut> Packet<T> {
+    /// Change the hop limit of the packet.
+    ///
+    /// Updates both the header and the representation.
+    pub fn set_hop_limit(&mut self, hop_limit: u8) {
+        ipv6::new_unchecked_mut(self.buffer.payload_mut())
+            .set_hop_limit(hop_limit);
+        self.repr.hop_limit = hop_limit;
+    }
+
+    /// Change the traffic class of the packet.
+    pub fn set_traffic_class(&mut self, traffic_class: u8) {
+        ipv6::new_unchecked_mut(self.buffer.payload_mut())
+            .set_traffic_class(traffic_class);
+    }
 }
 
 impl<T: Payload> ops::Deref for Packet<T> {
@@ -889,6 +981,26 @@ impl Repr {
         })
     }
 
+    /// Parse an Internet Protocol version 6 packet stored in parts.
+    ///
+    /// Only the fixed header must be contained in the first part while the payload may continue
+    /// in the second, see [`PayloadParts`].
+    ///
+    /// [`PayloadParts`]: trait.PayloadParts.html
+    pub fn parse_parts<P: PayloadParts + ?Sized>(packet: &P) -> Result<Repr> {
+        let header = packet.contiguous(field::DST_ADDR.end).ok_or(Error::Truncated)?;
+        let header = ipv6::new_unchecked(header);
+        if packet.parts_len() < header.total_len() { return Err(Error::Truncated); }
+        if header.version() != 6 { return Err(Error::Malformed); }
+        Ok(Repr {
+            src_addr:    header.src_addr(),
+            dst_addr:    header.dst_addr(),
+            next_header: header.next_header(),
+            payload_len: header.payload_len() as usize,
+            hop_limit:   header.hop_limit()
+        })
+    }
+
     /// Return the length of a header that will be emitted from this high-level representation.
     pub fn buffer_len(&self) -> usize {
         // This function is not strictly necessary, but it can make client code more readable.
@@ -965,6 +1077,15 @@ mod test {
         assert!(!Address::LINK_LOCAL_ALL_NODES.is_loopback());
     }
 
+    #[test]
+    fn test_solicited_node() {
+        let addr = Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0x0102, 0x0304);
+        let solicited = Address::new(0xff02, 0, 0, 0, 0, 1, 0xff02, 0x0304);
+        assert_eq!(addr.solicited_node_multicast(), solicited);
+        assert!(Cidr::SOLICITED_NODE_PREFIX.subnet().contains(solicited));
+        assert!(addr.accepts(solicited));
+    }
+
     #[test]
     fn test_basic_link_local() {
         assert!(!LINK_LOCAL_ADDR.is_unspecified());
@@ -999,6 +1120,29 @@ mod test {
                    format!("{}", Address::from(Ipv4Address::new(192, 168, 1, 1))));
     }
 
+    #[test]
+    fn test_address_parse() {
+        for addr in ["ff02::1", "fe80::7f00:0:1", "::", "::1", "1:2:3:4:5:6:7:8",
+                     "::ffff:192.168.1.1"].iter() {
+            assert_eq!(addr.parse::<Address>().map(|addr| addr.to_string()), Ok(addr.to_string()));
+        }
+
+        assert_eq!("FE80:0000::0001".parse(), Ok(LINK_LOCAL_ADDR));
+        assert_eq!("1::".parse(), Ok(Address::new(1, 0, 0, 0, 0, 0, 0, 0)));
+        assert_eq!("64:ff9b::192.0.2.1".parse(),
+                   Ok(Address::new(0x64, 0xff9b, 0, 0, 0, 0, 0xc000, 0x0201)));
+
+        for invalid in ["", ":", ":::", "1::2::3", "1:2:3:4:5:6:7", "1:2:3:4:5:6:7:8:9",
+                        "1::2:3:4:5:6:7:8", "12345::", "g::", "+1::", "1.2.3.4::", "::1:",
+                        "::256.0.0.1", "1:2:3:4:5:6:7:1.2.3.4"].iter() {
+            assert!(invalid.parse::<Address>().is_err(), "{}", invalid);
+        }
+
+        assert_eq!("fe80::1/64".parse(), Ok(Cidr::new(LINK_LOCAL_ADDR, 64)));
+        assert!("fe80::1/129".parse::<Cidr>().is_err());
+        assert!("fe80::1".parse::<Cidr>().is_err());
+    }
+
     #[test]
     fn test_new() {
         assert_eq!(Address::new(0xff02, 0, 0, 0, 0, 0, 0, 1),
@@ -1193,6 +1337,22 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_parse_parts() {
+        use crate::wire::RingSlice;
+
+        let mut ring = [0; 56];
+        ring[16..].copy_from_slice(&REPR_PACKET_BYTES[..40]);
+        ring[..12].copy_from_slice(&REPR_PACKET_BYTES[40..]);
+        let packet = RingSlice::from_ring(&mut ring, 16, 52).unwrap();
+        assert_eq!(Repr::parse_parts(&packet), Ok(packet_repr()));
+
+        let packet = RingSlice::from_ring(&mut ring, 16, 51).unwrap();
+        assert_eq!(Repr::parse_parts(&packet), Err(Error::Truncated));
+        let packet = RingSlice::from_ring(&mut ring, 20, 52).unwrap();
+        assert_eq!(Repr::parse_parts(&packet), Err(Error::Truncated));
+    }
+
     #[test]
     fn test_packet_deconstruction() {
         let packet = ipv6::new_unchecked(&REPR_PACKET_BYTES[..]);
@@ -0,0 +1,213 @@
+use super::{Error, Result};
+use super::ipv6fragment::Header as FragmentHeader;
+use super::ipv6hopbyhop::Header as OptionsHeader;
+use super::ipv6routing::Header as RoutingHeader;
+pub(crate) use super::IpProtocol as Protocol;
+
+/// One extension header of an IPv6 packet.
+///
+/// The headers are wrappers around the buffer of exactly the extension header. Parse them further
+/// with the `Repr` of their kind.
+#[derive(Debug, PartialEq)]
+pub enum ExtHeader<'a> {
+    /// Options examined by every node along the path.
+    HopByHop(OptionsHeader<&'a [u8]>),
+    /// A list of intermediate nodes to visit.
+    Routing(RoutingHeader<&'a [u8]>),
+    /// A fragment of a larger packet.
+    Fragment(FragmentHeader<&'a [u8]>),
+    /// Options examined only by the destination, in the format of the hop-by-hop options.
+    DestinationOptions(OptionsHeader<&'a [u8]>),
+}
+
+/// An iterator over the extension headers of an IPv6 packet, in order.
+///
+/// Iteration stops at the first header that is not an extension header, or after returning an
+/// error. The headers behind a fragment header are part of the fragmented data and are not
+/// parsed, unless the fragment is the only one of its packet.
+#[derive(Clone, Debug)]
+pub struct ExtHeaders<'a> {
+    next_header: Protocol,
+    data: &'a [u8],
+    offset: usize,
+    done: bool,
+}
+
+/// The length of the fragment header.
+const FRAGMENT_LEN: usize = 8;
+
+impl ExtHeader<'_> {
+    /// The type of the header following this header.
+    pub fn next_header(&self) -> Protocol {
+        match self {
+            ExtHeader::HopByHop(header) => header.next_header(),
+            ExtHeader::Routing(header) => header.next_header(),
+            ExtHeader::Fragment(header) => header.next_header(),
+            ExtHeader::DestinationOptions(header) => header.next_header(),
+        }
+    }
+
+    /// The length of the header, in octets.
+    pub fn buffer_len(&self) -> usize {
+        match self {
+            ExtHeader::HopByHop(header) => extended_len(header.header_len()),
+            ExtHeader::Routing(header) => extended_len(header.header_len()),
+            ExtHeader::Fragment(_) => FRAGMENT_LEN,
+            ExtHeader::DestinationOptions(header) => extended_len(header.header_len()),
+        }
+    }
+}
+
+impl<'a> ExtHeaders<'a> {
+    /// Iterate the extension headers in the payload of a packet.
+    ///
+    /// The `next_header` is the next header field of the fixed header.
+    pub fn new(next_header: Protocol, payload: &'a [u8]) -> Self {
+        ExtHeaders {
+            next_header,
+            data: payload,
+            offset: 0,
+            done: false,
+        }
+    }
+
+    /// The type of the header following the headers returned so far.
+    ///
+    /// When the iteration has ended without error, this is the upper layer protocol.
+    pub fn next_header(&self) -> Protocol {
+        self.next_header
+    }
+
+    /// The offset in the payload of the header following the headers returned so far.
+    ///
+    /// When the iteration has ended without error, this is the start of the upper layer payload.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    fn parse(&self) -> Result<ExtHeader<'a>> {
+        let data = &self.data[self.offset..];
+        // All extension headers start with the next header and a length field.
+        if data.len() < 2 {
+            return Err(Error::Truncated);
+        }
+
+        let len = match self.next_header {
+            Protocol::Ipv6Frag => FRAGMENT_LEN,
+            _ => extended_len(data[1]),
+        };
+        let data = data.get(..len).ok_or(Error::Truncated)?;
+
+        Ok(match self.next_header {
+            Protocol::HopByHop => ExtHeader::HopByHop(OptionsHeader::new_checked(data)?),
+            Protocol::Ipv6Route => ExtHeader::Routing(RoutingHeader::new_checked(data)?),
+            Protocol::Ipv6Frag => ExtHeader::Fragment(FragmentHeader::new_checked(data)?),
+            Protocol::Ipv6Opts => ExtHeader::DestinationOptions(OptionsHeader::new_checked(data)?),
+            _ => unreachable!("Not an extension header"),
+        })
+    }
+}
+
+impl<'a> Iterator for ExtHeaders<'a> {
+    type Item = Result<ExtHeader<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_header {
+            _ if self.done => return None,
+            Protocol::HopByHop | Protocol::Ipv6Route
+                | Protocol::Ipv6Frag | Protocol::Ipv6Opts => (),
+            _ => return None,
+        }
+
+        let header = match self.parse() {
+            Ok(header) => header,
+            Err(err) => {
+                self.done = true;
+                return Some(Err(err));
+            },
+        };
+
+        self.next_header = header.next_header();
+        self.offset += header.buffer_len();
+        if let ExtHeader::Fragment(ref fragment) = header {
+            // Only the first fragment contains the following headers.
+            if fragment.frag_offset() != 0 || fragment.more_frags() {
+                self.done = true;
+            }
+        }
+
+        Some(Ok(header))
+    }
+}
+
+/// The length of a header with a length field in 8-octet units, not including the first 8 octets.
+fn extended_len(length_field: u8) -> usize {
+    usize::from(length_field) * 8 + 8
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // A hop-by-hop header with a router alert option, a fragment header and a destination
+    // options header with padding, followed by udp.
+    static HEADERS: [u8; 32] =
+        [0x2c, 0x00, 0x05, 0x02,
+         0x00, 0x00, 0x01, 0x00,
+         0x3c, 0x00, 0x00, 0x00,
+         0x00, 0x00, 0x00, 0x00,
+         0x11, 0x01, 0x01, 0x0c,
+         0x00, 0x00, 0x00, 0x00,
+         0x00, 0x00, 0x00, 0x00,
+         0x00, 0x00, 0x00, 0x00];
+
+    #[test]
+    fn test_iterate() {
+        let mut headers = ExtHeaders::new(Protocol::HopByHop, &HEADERS);
+        match headers.next() {
+            Some(Ok(ExtHeader::HopByHop(header))) => assert_eq!(header.options().len(), 6),
+            other => panic!("Unexpected {:?}", other),
+        }
+        match headers.next() {
+            Some(Ok(ExtHeader::Fragment(header))) => assert_eq!(header.ident(), 0),
+            other => panic!("Unexpected {:?}", other),
+        }
+        assert_eq!(headers.offset(), 16);
+        match headers.next() {
+            Some(Ok(header @ ExtHeader::DestinationOptions(_))) => {
+                assert_eq!(header.buffer_len(), 16);
+                assert_eq!(header.next_header(), Protocol::Udp);
+            },
+            other => panic!("Unexpected {:?}", other),
+        }
+        assert!(headers.next().is_none());
+        assert_eq!(headers.next_header(), Protocol::Udp);
+        assert_eq!(headers.offset(), 32);
+    }
+
+    #[test]
+    fn test_fragment() {
+        let mut bytes = HEADERS;
+        // The second fragment of a packet, its data is not parsed.
+        bytes[11] = 0x08;
+        let mut headers = ExtHeaders::new(Protocol::HopByHop, &bytes);
+        assert!(matches!(headers.next(), Some(Ok(ExtHeader::HopByHop(_)))));
+        assert!(matches!(headers.next(), Some(Ok(ExtHeader::Fragment(_)))));
+        assert!(headers.next().is_none());
+        assert_eq!(headers.next_header(), Protocol::Ipv6Opts);
+        assert_eq!(headers.offset(), 16);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let mut headers = ExtHeaders::new(Protocol::HopByHop, &HEADERS[..20]);
+        assert!(matches!(headers.next(), Some(Ok(ExtHeader::HopByHop(_)))));
+        assert!(matches!(headers.next(), Some(Ok(ExtHeader::Fragment(_)))));
+        assert_eq!(headers.next(), Some(Err(Error::Truncated)));
+        assert!(headers.next().is_none());
+
+        // The length field may indicate a header longer than any packet.
+        let mut headers = ExtHeaders::new(Protocol::Ipv6Opts, &[0x11, 0xff, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(headers.next(), Some(Err(Error::Truncated)));
+    }
+}
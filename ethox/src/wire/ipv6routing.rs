@@ -91,8 +91,8 @@ mod field {
     // Length of the header is in 8-octet units, not including the first 8 octets. The first four
     // octets are the next header type, the header length, routing type and segments left.
     pub(crate) fn DATA(length_field: u8) -> Field {
-        let bytes = length_field * 8 + 8;
-        4..bytes as usize
+        let bytes = usize::from(length_field) * 8 + 8;
+        4..bytes
     }
 
     // The Type 2 Routing Header has the following format:
@@ -142,16 +142,22 @@ mod field {
 /// Core getter methods relevant to any routing type.
 impl<T: AsRef<[u8]>> Header<T> {
     /// Create a raw octet buffer with an IPv6 Routing Header structure.
-    pub fn new(buffer: T) -> Header<T> {
+    pub fn new_unchecked(buffer: T) -> Header<T> {
         Header { buffer }
     }
 
+    /// Create a raw octet buffer with an IPv6 Routing Header structure.
+    #[deprecated = "Use `new_unchecked` instead."]
+    pub fn new(buffer: T) -> Header<T> {
+        Self::new_unchecked(buffer)
+    }
+
     /// Shorthand for a combination of [new_unchecked] and [check_len].
     ///
     /// [new_unchecked]: #method.new_unchecked
     /// [check_len]: #method.check_len
     pub fn new_checked(buffer: T) -> Result<Header<T>> {
-        let header = Self::new(buffer);
+        let header = Self::new_unchecked(buffer);
         header.check_len()?;
         Ok(header)
     }
@@ -168,7 +174,7 @@ impl<T: AsRef<[u8]>> Header<T> {
             return Err(Error::Truncated);
         }
 
-        if len < field::DATA(self.header_len()).end as usize {
+        if len < field::DATA(self.header_len()).end {
             return Err(Error::Truncated);
         }
 
@@ -579,36 +585,38 @@ mod test {
     #[test]
     fn test_check_len() {
         // less than min header size
-        assert_eq!(Err(Error::Truncated), Header::new(&BYTES_TYPE2[..3]).check_len());
-        assert_eq!(Err(Error::Truncated), Header::new(&BYTES_SRH_FULL[..3]).check_len());
-        assert_eq!(Err(Error::Truncated), Header::new(&BYTES_SRH_ELIDED[..3]).check_len());
+        assert_eq!(Err(Error::Truncated), Header::new_unchecked(&BYTES_TYPE2[..3]).check_len());
+        assert_eq!(Err(Error::Truncated), Header::new_unchecked(&BYTES_SRH_FULL[..3]).check_len());
+        assert_eq!(Err(Error::Truncated),
+            Header::new_unchecked(&BYTES_SRH_ELIDED[..3]).check_len());
         // less than specfied length field
-        assert_eq!(Err(Error::Truncated), Header::new(&BYTES_TYPE2[..23]).check_len());
-        assert_eq!(Err(Error::Truncated), Header::new(&BYTES_SRH_FULL[..39]).check_len());
-        assert_eq!(Err(Error::Truncated), Header::new(&BYTES_SRH_ELIDED[..11]).check_len());
+        assert_eq!(Err(Error::Truncated), Header::new_unchecked(&BYTES_TYPE2[..23]).check_len());
+        assert_eq!(Err(Error::Truncated), Header::new_unchecked(&BYTES_SRH_FULL[..39]).check_len());
+        assert_eq!(Err(Error::Truncated),
+            Header::new_unchecked(&BYTES_SRH_ELIDED[..11]).check_len());
         // valid
-        assert_eq!(Ok(()), Header::new(&BYTES_TYPE2[..]).check_len());
-        assert_eq!(Ok(()), Header::new(&BYTES_SRH_FULL[..]).check_len());
-        assert_eq!(Ok(()), Header::new(&BYTES_SRH_ELIDED[..]).check_len());
+        assert_eq!(Ok(()), Header::new_unchecked(&BYTES_TYPE2[..]).check_len());
+        assert_eq!(Ok(()), Header::new_unchecked(&BYTES_SRH_FULL[..]).check_len());
+        assert_eq!(Ok(()), Header::new_unchecked(&BYTES_SRH_ELIDED[..]).check_len());
     }
 
     #[test]
     fn test_header_deconstruct() {
-        let header = Header::new(&BYTES_TYPE2[..]);
+        let header = Header::new_unchecked(&BYTES_TYPE2[..]);
         assert_eq!(header.next_header(), Protocol::Tcp);
         assert_eq!(header.header_len(), 2);
         assert_eq!(header.routing_type(), Type::Type2);
         assert_eq!(header.segments_left(), 1);
         assert_eq!(header.home_address(), Address::LOOPBACK);
 
-        let header = Header::new(&BYTES_SRH_FULL[..]);
+        let header = Header::new_unchecked(&BYTES_SRH_FULL[..]);
         assert_eq!(header.next_header(), Protocol::Tcp);
         assert_eq!(header.header_len(), 4);
         assert_eq!(header.routing_type(), Type::Rpl);
         assert_eq!(header.segments_left(), 2);
         assert_eq!(header.addresses(), &BYTES_SRH_FULL[8..]);
 
-        let header = Header::new(&BYTES_SRH_ELIDED[..]);
+        let header = Header::new_unchecked(&BYTES_SRH_ELIDED[..]);
         assert_eq!(header.next_header(), Protocol::Tcp);
         assert_eq!(header.header_len(), 1);
         assert_eq!(header.routing_type(), Type::Rpl);
@@ -616,6 +624,15 @@ mod test {
         assert_eq!(header.addresses(), &BYTES_SRH_ELIDED[8..]);
     }
 
+    #[test]
+    #[allow(deprecated)]
+    fn test_deprecated_new() {
+        let header = Header::new(&BYTES_TYPE2[..]);
+        assert_eq!(Ok(()), header.check_len());
+        assert_eq!(header.routing_type(), Type::Type2);
+        assert_eq!(header.home_address(), Address::LOOPBACK);
+    }
+
     #[test]
     fn test_repr_parse_valid() {
         let header = Header::new_checked(&BYTES_TYPE2[..]).unwrap();
@@ -634,17 +651,17 @@ mod test {
     #[test]
     fn test_repr_emit() {
         let mut bytes = [0u8; 24];
-        let mut header = Header::new(&mut bytes[..]);
+        let mut header = Header::new_unchecked(&mut bytes[..]);
         REPR_TYPE2.emit(&mut header);
         assert_eq!(header.into_inner(), &BYTES_TYPE2[..]);
 
         let mut bytes = [0u8; 40];
-        let mut header = Header::new(&mut bytes[..]);
+        let mut header = Header::new_unchecked(&mut bytes[..]);
         REPR_SRH_FULL.emit(&mut header);
         assert_eq!(header.into_inner(), &BYTES_SRH_FULL[..]);
 
         let mut bytes = [0u8; 16];
-        let mut header = Header::new(&mut bytes[..]);
+        let mut header = Header::new_unchecked(&mut bytes[..]);
         REPR_SRH_ELIDED.emit(&mut header);
         assert_eq!(header.into_inner(), &BYTES_SRH_ELIDED[..]);
     }
@@ -0,0 +1,362 @@
+//! IEEE 802.2 logical link control headers, with the SNAP extension.
+//!
+//! Frames in the original IEEE 802.3 format carry the length of their payload instead of an
+//! ethertype, see [`EthernetProtocol::frame_length`]. Their payload then starts with an LLC header
+//! addressing the service access points of sender and receiver. The subnetwork access protocol
+//! (SNAP) extends this header with an organization code and a protocol identifier, which for the
+//! zero organization is again an ethertype.
+//!
+//! [`EthernetProtocol::frame_length`]: enum.EthernetProtocol.html#method.frame_length
+use core::fmt;
+use byteorder::{ByteOrder, NetworkEndian};
+
+use super::{Error, Result};
+use super::EthernetProtocol;
+
+byte_wrapper! {
+    /// A byte sequence starting with an LLC header.
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct llc([u8]);
+}
+
+// Format of an LLC header with SNAP extension
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     DSAP      |     SSAP      |    Control    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |          Organization code (OUI)              |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |         Protocol id           |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// The control field of information and supervisory frames is two octets long instead.
+mod field {
+    use crate::wire::field::Field;
+
+    pub(crate) const DSAP:     usize = 0;
+    pub(crate) const SSAP:     usize = 1;
+    pub(crate) const CONTROL:  usize = 2;
+    pub(crate) const OUI:      Field = 3..6;
+    pub(crate) const PROTOCOL: Field = 6..8;
+}
+
+impl llc {
+    /// The service access point announcing a SNAP extension.
+    pub const SAP_SNAP: u8 = 0xaa;
+    /// The service access point of the spanning tree protocol.
+    pub const SAP_STP: u8 = 0x42;
+    /// The control field of an unnumbered information frame.
+    pub const CONTROL_UI: u8 = 0x03;
+
+    /// Imbue a raw octet buffer with LLC header structure.
+    pub fn new_unchecked(data: &[u8]) -> &Self {
+        Self::__from_macro_new_unchecked(data)
+    }
+
+    /// Imbue a mutable octet buffer with LLC header structure.
+    pub fn new_unchecked_mut(data: &mut [u8]) -> &mut Self {
+        Self::__from_macro_new_unchecked_mut(data)
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(data: &[u8]) -> Result<&Self> {
+        let header = Self::new_unchecked(data);
+        header.check_len()?;
+        Ok(header)
+    }
+
+    /// View the header as a raw byte slice.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// View the header as a mutable raw byte slice.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    ///
+    /// Returns `Err(Error::Truncated)` if the buffer is too short for the header, including its
+    /// SNAP extension if the access points announce one.
+    pub fn check_len(&self) -> Result<()> {
+        if self.0.len() <= field::CONTROL {
+            return Err(Error::Truncated);
+        }
+
+        if self.0.len() < self.header_len() {
+            Err(Error::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Return the destination service access point.
+    pub fn dsap(&self) -> u8 {
+        self.0[field::DSAP]
+    }
+
+    /// Return the source service access point.
+    pub fn ssap(&self) -> u8 {
+        self.0[field::SSAP]
+    }
+
+    /// Return the length of the control field, one octet for unnumbered frames and two otherwise.
+    pub fn control_len(&self) -> usize {
+        if self.0[field::CONTROL] & 0x03 == 0x03 { 1 } else { 2 }
+    }
+
+    /// Return the control field.
+    pub fn control(&self) -> u16 {
+        match self.control_len() {
+            1 => self.0[field::CONTROL].into(),
+            _ => NetworkEndian::read_u16(&self.0[field::CONTROL..field::CONTROL + 2]),
+        }
+    }
+
+    /// Query whether the header is followed by a SNAP extension.
+    pub fn is_snap(&self) -> bool {
+        self.dsap() == Self::SAP_SNAP
+            && self.ssap() == Self::SAP_SNAP
+            && self.0[field::CONTROL] == Self::CONTROL_UI
+    }
+
+    /// Return the length of the header, including the SNAP extension.
+    pub fn header_len(&self) -> usize {
+        if self.is_snap() {
+            field::PROTOCOL.end
+        } else {
+            field::CONTROL + self.control_len()
+        }
+    }
+
+    /// Return the organization code of the SNAP extension.
+    ///
+    /// # Panics
+    /// This function may panic if the header has no SNAP extension.
+    pub fn oui(&self) -> [u8; 3] {
+        let mut oui = [0; 3];
+        oui.copy_from_slice(&self.0[field::OUI]);
+        oui
+    }
+
+    /// Return the protocol identifier of the SNAP extension.
+    ///
+    /// # Panics
+    /// This function may panic if the header has no SNAP extension.
+    pub fn protocol(&self) -> EthernetProtocol {
+        NetworkEndian::read_u16(&self.0[field::PROTOCOL]).into()
+    }
+
+    /// Set the destination service access point.
+    pub fn set_dsap(&mut self, value: u8) {
+        self.0[field::DSAP] = value
+    }
+
+    /// Set the source service access point.
+    pub fn set_ssap(&mut self, value: u8) {
+        self.0[field::SSAP] = value
+    }
+
+    /// Set the control field.
+    ///
+    /// Values of unnumbered frames occupy one octet, all others two.
+    ///
+    /// # Panics
+    /// This function may panic if the buffer is too short for the field.
+    pub fn set_control(&mut self, value: u16) {
+        if value & 0x03 == 0x03 && value <= 0xff {
+            self.0[field::CONTROL] = value as u8;
+        } else {
+            NetworkEndian::write_u16(&mut self.0[field::CONTROL..field::CONTROL + 2], value);
+        }
+    }
+
+    /// Set the organization code of the SNAP extension.
+    pub fn set_oui(&mut self, value: [u8; 3]) {
+        self.0[field::OUI].copy_from_slice(&value)
+    }
+
+    /// Set the protocol identifier of the SNAP extension.
+    pub fn set_protocol(&mut self, value: EthernetProtocol) {
+        NetworkEndian::write_u16(&mut self.0[field::PROTOCOL], value.into())
+    }
+
+    /// Return the data following the header.
+    pub fn payload_slice(&self) -> &[u8] {
+        &self.0[self.header_len()..]
+    }
+
+    /// Return the data following the header, mutably.
+    pub fn payload_mut_slice(&mut self) -> &mut [u8] {
+        let start = self.header_len();
+        &mut self.0[start..]
+    }
+}
+
+impl AsRef<[u8]> for llc {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsMut<[u8]> for llc {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+/// A high-level representation of an LLC header.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Repr {
+    /// A plain header addressing service access points.
+    Llc {
+        dsap: u8,
+        ssap: u8,
+        control: u16,
+    },
+    /// An unnumbered information header with SNAP extension.
+    Snap {
+        oui: [u8; 3],
+        protocol: EthernetProtocol,
+    },
+}
+
+impl Repr {
+    /// Parse an LLC header and return a high-level representation.
+    pub fn parse(header: &llc) -> Result<Repr> {
+        header.check_len()?;
+        if header.is_snap() {
+            Ok(Repr::Snap {
+                oui: header.oui(),
+                protocol: header.protocol(),
+            })
+        } else {
+            Ok(Repr::Llc {
+                dsap: header.dsap(),
+                ssap: header.ssap(),
+                control: header.control(),
+            })
+        }
+    }
+
+    /// Return the length of the header that will be emitted from this representation.
+    pub fn header_len(&self) -> usize {
+        match self {
+            Repr::Llc { control, .. } if *control & 0x03 == 0x03 && *control <= 0xff => 3,
+            Repr::Llc { .. } => 4,
+            Repr::Snap { .. } => field::PROTOCOL.end,
+        }
+    }
+
+    /// Return the length of a buffer required to hold the header and a payload.
+    pub fn buffer_len(&self, payload_len: usize) -> usize {
+        self.header_len() + payload_len
+    }
+
+    /// Emit a high-level representation into a buffer.
+    ///
+    /// # Panics
+    /// This function panics if the buffer is shorter than the header.
+    pub fn emit(&self, header: &mut llc) {
+        match *self {
+            Repr::Llc { dsap, ssap, control } => {
+                header.set_dsap(dsap);
+                header.set_ssap(ssap);
+                header.set_control(control);
+            },
+            Repr::Snap { oui, protocol } => {
+                header.set_dsap(llc::SAP_SNAP);
+                header.set_ssap(llc::SAP_SNAP);
+                header.set_control(llc::CONTROL_UI.into());
+                header.set_oui(oui);
+                header.set_protocol(protocol);
+            },
+        }
+    }
+}
+
+impl fmt::Display for Repr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Repr::Llc { dsap, ssap, control } =>
+                write!(f, "LLC dsap=0x{:02x} ssap=0x{:02x} ctrl=0x{:02x}", dsap, ssap, control),
+            Repr::Snap { oui, protocol } =>
+                write!(f, "SNAP oui={:02x}-{:02x}-{:02x} type={}",
+                       oui[0], oui[1], oui[2], protocol),
+        }
+    }
+}
+
+use super::pretty_print::{PrettyPrint, PrettyIndent};
+
+impl PrettyPrint for llc {
+    fn pretty_print(buffer: &[u8], f: &mut fmt::Formatter,
+                    indent: &mut PrettyIndent) -> fmt::Result {
+        match Repr::parse(llc::new_unchecked(buffer)) {
+            Err(err) => write!(f, "{}({})", indent, err),
+            Ok(repr) => write!(f, "{}{}", indent, repr),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static STP_BYTES: [u8; 5] = [0x42, 0x42, 0x03, 0x00, 0x00];
+
+    static SNAP_BYTES: [u8; 10] = [0xaa, 0xaa, 0x03, 0x00, 0x00, 0x0c, 0x20, 0x00, 0xde, 0xad];
+
+    #[test]
+    fn test_deconstruct() {
+        let header = llc::new_checked(&STP_BYTES[..]).unwrap();
+        assert!(!header.is_snap());
+        assert_eq!(header.header_len(), 3);
+        assert_eq!(header.payload_slice(), &[0, 0]);
+        assert_eq!(Repr::parse(header), Ok(Repr::Llc {
+            dsap: llc::SAP_STP,
+            ssap: llc::SAP_STP,
+            control: 0x03,
+        }));
+
+        let header = llc::new_checked(&SNAP_BYTES[..]).unwrap();
+        assert!(header.is_snap());
+        assert_eq!(header.payload_slice(), &[0xde, 0xad]);
+        assert_eq!(Repr::parse(header), Ok(Repr::Snap {
+            oui: [0x00, 0x00, 0x0c],
+            protocol: EthernetProtocol::Unknown(0x2000),
+        }));
+    }
+
+    #[test]
+    fn test_truncated() {
+        assert_eq!(llc::new_checked(&SNAP_BYTES[..2]), Err(Error::Truncated));
+        assert_eq!(llc::new_checked(&SNAP_BYTES[..7]), Err(Error::Truncated));
+        // An information frame has a two octet control field.
+        assert_eq!(llc::new_checked(&[0x42, 0x42, 0x00][..]), Err(Error::Truncated));
+    }
+
+    #[test]
+    fn test_construct() {
+        let repr = Repr::Snap {
+            oui: [0x00, 0x00, 0x0c],
+            protocol: EthernetProtocol::Unknown(0x2000),
+        };
+        let mut bytes = [0; 10];
+        assert_eq!(repr.buffer_len(2), bytes.len());
+        let header = llc::new_unchecked_mut(&mut bytes[..]);
+        repr.emit(header);
+        header.payload_mut_slice().copy_from_slice(&[0xde, 0xad]);
+        assert_eq!(&bytes, &SNAP_BYTES);
+
+        let repr = Repr::Llc { dsap: 0x06, ssap: 0x06, control: 0x1234 };
+        let mut bytes = [0; 4];
+        assert_eq!(repr.header_len(), 4);
+        repr.emit(llc::new_unchecked_mut(&mut bytes[..]));
+        assert_eq!(Repr::parse(llc::new_unchecked(&bytes[..])), Ok(repr));
+    }
+}
@@ -17,12 +17,17 @@ functionality.
    checksum fields and the payload can still be accessed normally. This happens in the uppercase
    Frame or Packet family of structs, e.g. [`ArpPacket`] or [`UdpPacket`].
 
+For crafting packets that no `Repr` describes, such as for tests of other stacks, the builders
+e.g. [`Ipv4Builder`] compose headers field by field. Their type ensures that the payload is only
+written once lengths and checksums of the headers can be filled in.
+
 [`ethernet_frame`]: struct.ethernet_frame.html
 [`udp_packet`]: struct.udp_packet.html
 [`ArpRepr`]: enum.ArpRepr.html
 [`Ipv4Repr`]: struct.Ipv4Repr.html
 [`ArpPacket`]: struct.ArpPacket.html
 [`UdpPacket`]: struct.UdpPacket.html
+[`Ipv4Builder`]: struct.Ipv4Builder.html
 
 [^tcp]: The TCP structures differ since i haven't gotten around to reworking them. It does not have
 a dynamically sized byte wrapper so its `Packet` implements this functionality as well but come
@@ -138,6 +143,7 @@ pub mod pretty_print;
 
 mod ethernet;
 mod error;
+mod llc;
 pub(crate) mod arp;
 pub(crate) mod ip;
 mod ipv4;
@@ -146,17 +152,26 @@ mod ipv6option;
 mod ipv6hopbyhop;
 mod ipv6fragment;
 mod ipv6routing;
+mod ipv6ext;
 mod icmpv4;
 // mod icmpv6;
 // mod icmp;
 // #[cfg(feature = "proto-igmp")]
 // mod igmp;
-// mod ndisc;
+mod ndisc;
 // mod ndiscoption;
 // mod mld;
 mod udp;
 mod tcp;
 // pub(crate) mod dhcpv4;
+mod dhcpv6;
+mod ntp;
+mod ptp;
+mod quic;
+mod sctp;
+mod tftp;
+mod wol;
+mod builder;
 
 #[path = "payload.rs"]
 mod payload_impl;
@@ -172,7 +187,19 @@ pub enum Checksum {
     Ignored,
 }
 
-pub use self::payload_impl::{Reframe, Payload, PayloadMut, Error as PayloadError, payload};
+pub use self::payload_impl::{
+    Reframe,
+    Payload,
+    PayloadMut,
+    PayloadVectored,
+    PayloadParts,
+    PayloadPartsMut,
+    Headroom,
+    RingSlice,
+    Error as PayloadError,
+    payload};
+#[cfg(feature = "alloc")]
+pub use self::payload_impl::Segmented;
 pub use self::payload_ext::{ReframePayload, PayloadMutExt};
 
 /// The result type of a reframing operation on [`PayloadMut`].
@@ -192,8 +219,13 @@ pub use self::ethernet::{
     EtherType as EthernetProtocol,
     Address as EthernetAddress,
     Frame as EthernetFrame,
+    ParseAddressError as ParseEthernetAddressError,
     Repr as EthernetRepr};
 
+pub use self::llc::{
+    llc as llc_header,
+    Repr as LlcRepr};
+
 pub use self::error::{
     Error,
     Result};
@@ -212,11 +244,18 @@ pub use self::ip::{
     Endpoint as IpEndpoint,
     Repr as IpRepr,
     Cidr as IpCidr,
-    Subnet as IpSubnet};
+    ParseAddressError as ParseIpAddressError,
+    ParseCidrError,
+    Subnet as IpSubnet,
+    UnspecifiedAddressError};
+
+#[doc(hidden)]
+pub use self::ip::checksum::variants as checksum_variants;
 
 pub use self::ipv4::{
     ipv4 as ipv4_packet,
     Address as Ipv4Address,
+    HeaderOption as Ipv4Option,
     Packet as Ipv4Packet,
     Repr as Ipv4Repr,
     Cidr as Ipv4Cidr,
@@ -231,6 +270,7 @@ pub use self::ipv6::{
     Repr as Ipv6Repr,
     Cidr as Ipv6Cidr,
     Subnet as Ipv6Subnet,
+    Scope as Ipv6Scope,
     MIN_MTU as IPV6_MIN_MTU};
 
 pub use self::ipv6option::{
@@ -251,6 +291,10 @@ pub use self::ipv6routing::{
     Header as Ipv6RoutingHeader,
     Repr as Ipv6RoutingRepr};
 
+pub use self::ipv6ext::{
+    ExtHeader as Ipv6ExtHeader,
+    ExtHeaders as Ipv6ExtHeaders};
+
 pub use self::icmpv4::{
     icmpv4 as icmpv4_packet,
     Message as Icmpv4Message,
@@ -279,12 +323,14 @@ pub use self::icmpv6::{
 pub use self::icmp::Repr as IcmpRepr;
 */
 
-/*
 pub use self::ndisc::{
-    Repr as NdiscRepr,
-    RouterFlags as NdiscRouterFlags,
-    NeighborFlags as NdiscNeighborFlags};
+    ndisc as ndisc_packet,
+    Message as NdiscMessage,
+    NdiscOption,
+    Options as NdiscOptions,
+    PrefixInformation as NdiscPrefixInformation};
 
+/*
 pub use self::ndiscoption::{
     NdiscOption,
     Repr as NdiscOptionRepr,
@@ -314,6 +360,89 @@ pub use self::tcp::{
     Repr as TcpRepr,
     Flags as TcpFlags};
 
+pub use self::dhcpv6::{
+    dhcpv6 as dhcpv6_packet,
+    MessageType as Dhcpv6MessageType,
+    StatusCode as Dhcpv6StatusCode,
+    Dhcpv6Option,
+    Options as Dhcpv6Options,
+    IdentityAssociation as Dhcpv6IdentityAssociation,
+    IaAddress as Dhcpv6IaAddress,
+    IaPrefix as Dhcpv6IaPrefix,
+    CLIENT_PORT as DHCPV6_CLIENT_PORT,
+    SERVER_PORT as DHCPV6_SERVER_PORT,
+    ALL_SERVERS as DHCPV6_ALL_SERVERS,
+    INFINITE as DHCPV6_INFINITE};
+
+pub use self::ntp::{
+    ntp as ntp_packet,
+    Leap as NtpLeap,
+    Mode as NtpMode,
+    Repr as NtpRepr,
+    Timestamp as NtpTimestamp,
+    HEADER_LEN as NTP_HEADER_LEN,
+    PORT as NTP_PORT};
+
+pub use self::ptp::{
+    ptp as ptp_packet,
+    Message as PtpMessage,
+    MessageType as PtpMessageType,
+    PortIdentity as PtpPortIdentity,
+    Repr as PtpRepr,
+    Timestamp as PtpTimestamp,
+    HEADER_LEN as PTP_HEADER_LEN,
+    EVENT_PORT as PTP_EVENT_PORT,
+    GENERAL_PORT as PTP_GENERAL_PORT,
+    PRIMARY_IPV4 as PTP_PRIMARY_IPV4,
+    PRIMARY_ETHERNET as PTP_PRIMARY_ETHERNET,
+    PDELAY_ETHERNET as PTP_PDELAY_ETHERNET};
+
+pub use self::quic::{
+    ConnectionId as QuicConnectionId,
+    Header as QuicHeader,
+    MAX_CID_LEN as QUIC_MAX_CID_LEN};
+
+pub use self::sctp::{
+    sctp as sctp_packet,
+    sctp_chunk,
+    Chunk as SctpChunk,
+    ChunkType as SctpChunkType,
+    Chunks as SctpChunks,
+    InitParams as SctpInitParams,
+    Repr as SctpRepr,
+    crc32c,
+    skip_unrecognized as sctp_skip_unrecognized,
+    CHUNK_HEADER_LEN as SCTP_CHUNK_HEADER_LEN,
+    DATA_HEADER_LEN as SCTP_DATA_HEADER_LEN,
+    HEADER_LEN as SCTP_HEADER_LEN};
+
+pub use self::tftp::{
+    tftp as tftp_packet,
+    ErrorCode as TftpErrorCode,
+    Mode as TftpMode,
+    OpCode as TftpOpCode,
+    Options as TftpOptions,
+    Repr as TftpRepr,
+    DEFAULT_BLOCK_SIZE as TFTP_DEFAULT_BLOCK_SIZE,
+    HEADER_LEN as TFTP_HEADER_LEN,
+    MAX_BLOCK_SIZE as TFTP_MAX_BLOCK_SIZE,
+    MIN_BLOCK_SIZE as TFTP_MIN_BLOCK_SIZE,
+    PORT as TFTP_PORT};
+
+pub use self::wol::{
+    wol as wol_packet,
+    find as wol_find,
+    Password as WolPassword,
+    Repr as WolRepr,
+    MAGIC_LEN as WOL_MAGIC_LEN,
+    PORT as WOL_PORT};
+
+pub use self::builder::{
+    Fields as BuilderFields,
+    Finalized as BuilderFinalized,
+    Ipv4Builder,
+    TcpBuilder};
+
 #[cfg(feature = "proto-dhcpv4")]
 pub use self::dhcpv4::{
     Packet as DhcpPacket,
@@ -1,539 +1,628 @@
+//! Neighbor discovery messages, rfc4861.
+//!
+//! Only router advertisements are fully supported. The header fields of the other messages can
+//! be inspected through the common accessors and their options are available as well. Neighbor
+//! solicitations and advertisements additionally expose their target address.
+use core::fmt;
 use byteorder::{ByteOrder, NetworkEndian};
 
 use super::{Error, Result};
-use super::icmpv6::{field, Message, Packet};
-use super::{EthernetAddress, Ipv6Repr, Ipv6Packet};
-use super::{NdiscOption, NdiscOptionRepr, NdiscOptionType};
-use super::{NdiscPrefixInformation, NdiscRedirectedHeader};
-use time::Duration;
-use super::Ipv6Address;
-
-bitflags! {
-    pub struct RouterFlags: u8 {
-        const MANAGED = 0b10000000;
-        const OTHER   = 0b01000000;
+use super::ip::checksum;
+use super::{EthernetAddress, IpAddress, IpProtocol, Ipv6Address, Ipv6Cidr, Ipv6Subnet};
+use crate::time::Duration;
+
+enum_with_unknown! {
+    /// The type of a neighbor discovery message.
+    pub doc enum Message(u8) {
+        /// Router solicitation
+        RouterSolicit  = 133,
+        /// Router advertisement
+        RouterAdvert   = 134,
+        /// Neighbor solicitation
+        NeighborSolicit = 135,
+        /// Neighbor advertisement
+        NeighborAdvert = 136,
+        /// Redirect
+        Redirect       = 137,
     }
 }
 
-bitflags! {
-    pub struct NeighborFlags: u8 {
-        const ROUTER    = 0b10000000;
-        const SOLICITED = 0b01000000;
-        const OVERRIDE  = 0b00100000;
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Message::RouterSolicit   => write!(f, "router solicitation"),
+            Message::RouterAdvert    => write!(f, "router advertisement"),
+            Message::NeighborSolicit => write!(f, "neighbor solicitation"),
+            Message::NeighborAdvert  => write!(f, "neighbor advertisement"),
+            Message::Redirect        => write!(f, "redirect"),
+            Message::Unknown(id)     => write!(f, "{}", id),
+        }
     }
 }
 
-/// Getters for the Router Advertisement message header.
-/// See [RFC 4861 § 4.2].
-///
-/// [RFC 4861 § 4.2]: https://tools.ietf.org/html/rfc4861#section-4.2
-impl<T: AsRef<[u8]>> Packet<T> {
-    /// Return the current hop limit field.
-    #[inline]
+byte_wrapper! {
+    /// A byte slice containing a potential neighbor discovery message.
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct ndisc([u8]);
+}
+
+// Format of a router advertisement
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     Type      |     Code      |          Checksum             |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// | Cur Hop Limit |M|O|  Reserved |       Router Lifetime         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                         Reachable Time                        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                          Retrans Timer                        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |   Options ...
+// +-+-+-+-+-+-+-+-+-+-+-+-+-
+//
+// See https://tools.ietf.org/html/rfc4861#section-4.2 for details.
+mod field {
+    #![allow(non_snake_case)]
+    use crate::wire::field::{Field, Rest};
+    use super::Message;
+
+    pub(crate) const TYPE:       usize = 0;
+    pub(crate) const CODE:       usize = 1;
+    pub(crate) const CHECKSUM:   Field = 2..4;
+
+    pub(crate) const CUR_HOP_LIMIT: usize = 4;
+    pub(crate) const ROUTER_FLAGS:  usize = 5;
+    pub(crate) const ROUTER_LT:     Field = 6..8;
+    pub(crate) const REACHABLE_TM:  Field = 8..12;
+    pub(crate) const RETRANS_TM:    Field = 12..16;
+
+    // Neighbor solicitation and advertisement, following a reserved word.
+    pub(crate) const TARGET:        Field = 8..24;
+
+    /// The options following the fixed part of each message.
+    pub(crate) fn OPTIONS(message: Message) -> Rest {
+        match message {
+            Message::RouterSolicit => 8..,
+            Message::RouterAdvert => RETRANS_TM.end..,
+            // Reserved word and target address.
+            Message::NeighborSolicit | Message::NeighborAdvert => 24..,
+            // Reserved word, target and destination address.
+            Message::Redirect => 40..,
+            Message::Unknown(_) => CHECKSUM.end..,
+        }
+    }
+}
+
+impl ndisc {
+    /// Flag of a router advertisement for managed address configuration.
+    pub const FLAG_MANAGED: u8 = 0x80;
+    /// Flag of a router advertisement for other configuration.
+    pub const FLAG_OTHER: u8 = 0x40;
+
+    /// Imbue a raw octet buffer with neighbor discovery message structure.
+    pub fn new_unchecked(data: &[u8]) -> &Self {
+        Self::__from_macro_new_unchecked(data)
+    }
+
+    /// Imbue a mutable octet buffer with neighbor discovery message structure.
+    pub fn new_unchecked_mut(data: &mut [u8]) -> &mut Self {
+        Self::__from_macro_new_unchecked_mut(data)
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(data: &[u8]) -> Result<&Self> {
+        let packet = Self::new_unchecked(data);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// View the message as a raw byte slice.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// View the message as a mutable raw byte slice.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    ///
+    /// Returns `Err(Error::Truncated)` if the buffer is shorter than the fixed part of its message
+    /// type. The accessors specific to router advertisements may still panic for other types.
+    pub fn check_len(&self) -> Result<()> {
+        if self.0.len() < field::CHECKSUM.end {
+            return Err(Error::Truncated);
+        }
+
+        if self.0.len() < field::OPTIONS(self.msg_type()).start {
+            Err(Error::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn msg_type(&self) -> Message {
+        Message::from(self.0[field::TYPE])
+    }
+
+    pub fn msg_code(&self) -> u8 {
+        self.0[field::CODE]
+    }
+
+    pub fn checksum(&self) -> u16 {
+        NetworkEndian::read_u16(&self.0[field::CHECKSUM])
+    }
+
+    /// The hop limit that hosts should use for outgoing packets, 0 if unspecified.
     pub fn current_hop_limit(&self) -> u8 {
-        let data = self.buffer.as_ref();
-        data[field::CUR_HOP_LIMIT]
+        self.0[field::CUR_HOP_LIMIT]
     }
 
-    /// Return the Router Advertisement flags.
-    #[inline]
-    pub fn router_flags(&self) -> RouterFlags {
-        let data = self.buffer.as_ref();
-        RouterFlags::from_bits_truncate(data[field::ROUTER_FLAGS])
+    /// The flags of a router advertisement, see [`FLAG_MANAGED`] and [`FLAG_OTHER`].
+    ///
+    /// [`FLAG_MANAGED`]: #associatedconstant.FLAG_MANAGED
+    /// [`FLAG_OTHER`]: #associatedconstant.FLAG_OTHER
+    pub fn router_flags(&self) -> u8 {
+        self.0[field::ROUTER_FLAGS]
     }
 
-    /// Return the router lifetime field.
-    #[inline]
+    /// The lifetime of the advertising router as a default router.
+    ///
+    /// A lifetime of zero means that the router must not be used as a default router.
     pub fn router_lifetime(&self) -> Duration {
-        let data = self.buffer.as_ref();
-        Duration::from_secs(NetworkEndian::read_u16(&data[field::ROUTER_LT]) as u64)
+        let secs = NetworkEndian::read_u16(&self.0[field::ROUTER_LT]);
+        Duration::from_secs(secs.into())
     }
 
-    /// Return the reachable time field.
-    #[inline]
+    /// The time a neighbor is assumed reachable after a confirmation, 0 if unspecified.
     pub fn reachable_time(&self) -> Duration {
-        let data = self.buffer.as_ref();
-        Duration::from_millis(NetworkEndian::read_u32(&data[field::REACHABLE_TM]) as u64)
+        let millis = NetworkEndian::read_u32(&self.0[field::REACHABLE_TM]);
+        Duration::from_millis(millis.into())
     }
 
-    /// Return the retransmit time field.
-    #[inline]
+    /// The time between retransmitted neighbor solicitations, 0 if unspecified.
     pub fn retrans_time(&self) -> Duration {
-        let data = self.buffer.as_ref();
-        Duration::from_millis(NetworkEndian::read_u32(&data[field::RETRANS_TM]) as u64)
+        let millis = NetworkEndian::read_u32(&self.0[field::RETRANS_TM]);
+        Duration::from_millis(millis.into())
     }
-}
 
-/// Common getters for the [Neighbor Solicitation], [Neighbor Advertisement], and
-/// [Redirect] message types.
-///
-/// [Neighbor Solicitation]: https://tools.ietf.org/html/rfc4861#section-4.3
-/// [Neighbor Advertisement]: https://tools.ietf.org/html/rfc4861#section-4.4
-/// [Redirect]: https://tools.ietf.org/html/rfc4861#section-4.5
-impl<T: AsRef<[u8]>> Packet<T> {
-    /// Return the target address field.
-    #[inline]
+    /// The target address of a neighbor solicitation or advertisement.
+    ///
+    /// # Panics
+    /// This function may panic if the message is of another type.
     pub fn target_addr(&self) -> Ipv6Address {
-        let data = self.buffer.as_ref();
-        Ipv6Address::from_bytes(&data[field::TARGET_ADDR])
+        Ipv6Address::from_bytes(&self.0[field::TARGET])
     }
-}
 
+    /// Iterate over the options of the message.
+    pub fn options(&self) -> Options<'_> {
+        Options {
+            bytes: &self.0[field::OPTIONS(self.msg_type())],
+        }
+    }
 
-/// Getters for the Neighbor Solicitation message header.
-/// See [RFC 4861 § 4.3].
-///
-/// [RFC 4861 § 4.3]: https://tools.ietf.org/html/rfc4861#section-4.3
-impl<T: AsRef<[u8]>> Packet<T> {
-    /// Return the Neighbor Solicitation flags.
-    #[inline]
-    pub fn neighbor_flags(&self) -> NeighborFlags {
-        let data = self.buffer.as_ref();
-        NeighborFlags::from_bits_truncate(data[field::NEIGH_FLAGS])
+    /// Validate the checksum, with the pseudo header of the enclosing ipv6 packet.
+    pub fn verify_checksum(&self, src_addr: Ipv6Address, dst_addr: Ipv6Address) -> bool {
+        if cfg!(fuzzing) { return true }
+
+        checksum::combine(&[
+            self.pseudo_header(src_addr, dst_addr),
+            checksum::data(&self.0),
+        ]) == !0
     }
-}
 
-/// Getters for the Redirect message header.
-/// See [RFC 4861 § 4.5].
-///
-/// [RFC 4861 § 4.5]: https://tools.ietf.org/html/rfc4861#section-4.5
-impl<T: AsRef<[u8]>> Packet<T> {
-    /// Return the destination address field.
-    #[inline]
-    pub fn dest_addr(&self) -> Ipv6Address {
-        let data = self.buffer.as_ref();
-        Ipv6Address::from_bytes(&data[field::DEST_ADDR])
+    pub fn set_msg_type(&mut self, value: Message) {
+        self.0[field::TYPE] = value.into()
+    }
+
+    pub fn set_msg_code(&mut self, value: u8) {
+        self.0[field::CODE] = value
+    }
+
+    pub fn set_checksum(&mut self, value: u16) {
+        NetworkEndian::write_u16(&mut self.0[field::CHECKSUM], value)
     }
-}
 
-/// Setters for the Router Advertisement message header.
-/// See [RFC 4861 § 4.2].
-///
-/// [RFC 4861 § 4.2]: https://tools.ietf.org/html/rfc4861#section-4.2
-impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
-    /// Set the current hop limit field.
-    #[inline]
     pub fn set_current_hop_limit(&mut self, value: u8) {
-        let data = self.buffer.as_mut();
-        data[field::CUR_HOP_LIMIT] = value;
+        self.0[field::CUR_HOP_LIMIT] = value
     }
 
-    /// Set the Router Advertisement flags.
-    #[inline]
-    pub fn set_router_flags(&mut self, flags: RouterFlags) {
-        self.buffer.as_mut()[field::ROUTER_FLAGS] = flags.bits();
+    pub fn set_router_flags(&mut self, value: u8) {
+        self.0[field::ROUTER_FLAGS] = value
     }
 
-    /// Set the router lifetime field.
-    #[inline]
+    /// Set the router lifetime, saturating at the largest representable number of seconds.
     pub fn set_router_lifetime(&mut self, value: Duration) {
-        let data = self.buffer.as_mut();
-        NetworkEndian::write_u16(&mut data[field::ROUTER_LT], value.secs() as u16);
+        let secs = value.as_secs().min(u16::MAX.into()) as u16;
+        NetworkEndian::write_u16(&mut self.0[field::ROUTER_LT], secs)
     }
 
-    /// Set the reachable time field.
-    #[inline]
+    /// Set the reachable time, saturating at the largest representable number of milliseconds.
     pub fn set_reachable_time(&mut self, value: Duration) {
-        let data = self.buffer.as_mut();
-        NetworkEndian::write_u32(&mut data[field::REACHABLE_TM], value.total_millis() as u32);
+        let millis = value.as_millis().min(u32::MAX.into()) as u32;
+        NetworkEndian::write_u32(&mut self.0[field::REACHABLE_TM], millis)
     }
 
-    /// Set the retransmit time field.
-    #[inline]
+    /// Set the retransmission time, saturating at the largest representable number of
+    /// milliseconds.
     pub fn set_retrans_time(&mut self, value: Duration) {
-        let data = self.buffer.as_mut();
-        NetworkEndian::write_u32(&mut data[field::RETRANS_TM], value.total_millis() as u32);
+        let millis = value.as_millis().min(u32::MAX.into()) as u32;
+        NetworkEndian::write_u32(&mut self.0[field::RETRANS_TM], millis)
     }
-}
 
-/// Common setters for the [Neighbor Solicitation], [Neighbor Advertisement], and
-/// [Redirect] message types.
-///
-/// [Neighbor Solicitation]: https://tools.ietf.org/html/rfc4861#section-4.3
-/// [Neighbor Advertisement]: https://tools.ietf.org/html/rfc4861#section-4.4
-/// [Redirect]: https://tools.ietf.org/html/rfc4861#section-4.5
-impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
-    /// Set the target address field.
-    #[inline]
+    /// Set the target address of a neighbor solicitation or advertisement.
+    ///
+    /// # Panics
+    /// This function may panic if the message is of another type.
     pub fn set_target_addr(&mut self, value: Ipv6Address) {
-        let data = self.buffer.as_mut();
-        data[field::TARGET_ADDR].copy_from_slice(value.as_bytes());
+        self.0[field::TARGET].copy_from_slice(value.as_bytes())
+    }
+
+    /// The options area of the message, for writing options with [`NdiscOption::emit`].
+    ///
+    /// [`NdiscOption::emit`]: enum.NdiscOption.html#method.emit
+    pub fn options_mut_slice(&mut self) -> &mut [u8] {
+        let options = field::OPTIONS(self.msg_type());
+        &mut self.0[options]
+    }
+
+    /// Compute and fill in the checksum, with the pseudo header of the enclosing ipv6 packet.
+    pub fn fill_checksum(&mut self, src_addr: Ipv6Address, dst_addr: Ipv6Address) {
+        self.set_checksum(0);
+        let checksum = !checksum::combine(&[
+            self.pseudo_header(src_addr, dst_addr),
+            checksum::data(&self.0),
+        ]);
+        self.set_checksum(checksum)
+    }
+
+    fn pseudo_header(&self, src_addr: Ipv6Address, dst_addr: Ipv6Address) -> u16 {
+        checksum::pseudo_header(
+            &IpAddress::Ipv6(src_addr),
+            &IpAddress::Ipv6(dst_addr),
+            IpProtocol::Icmpv6,
+            self.0.len() as u32)
     }
 }
 
-/// Setters for the Neighbor Solicitation message header.
-/// See [RFC 4861 § 4.3].
-///
-/// [RFC 4861 § 4.3]: https://tools.ietf.org/html/rfc4861#section-4.3
-impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
-    /// Set the Neighbor Solicitation flags.
-    #[inline]
-    pub fn set_neighbor_flags(&mut self, flags: NeighborFlags) {
-        self.buffer.as_mut()[field::NEIGH_FLAGS] = flags.bits();
+impl AsRef<[u8]> for ndisc {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
     }
 }
 
-/// Setters for the Redirect message header.
-/// See [RFC 4861 § 4.5].
-///
-/// [RFC 4861 § 4.5]: https://tools.ietf.org/html/rfc4861#section-4.5
-impl<T: AsRef<[u8]> + AsMut<[u8]>> Packet<T> {
-    /// Set the destination address field.
-    #[inline]
-    pub fn set_dest_addr(&mut self, value: Ipv6Address) {
-        let data = self.buffer.as_mut();
-        data[field::DEST_ADDR].copy_from_slice(value.as_bytes());
+impl AsMut<[u8]> for ndisc {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.as_bytes_mut()
     }
 }
 
-/// A high-level representation of an Neighbor Discovery packet header.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-pub enum Repr<'a> {
-    RouterSolicit {
-        lladdr: Option<EthernetAddress>
-    },
-    RouterAdvert {
-        hop_limit: u8,
-        flags: RouterFlags,
-        router_lifetime: Duration,
-        reachable_time: Duration,
-        retrans_time: Duration,
-        lladdr: Option<EthernetAddress>,
-        mtu: Option<u32>,
-        prefix_info: Option<NdiscPrefixInformation>
-    },
-    NeighborSolicit {
-        target_addr: Ipv6Address,
-        lladdr: Option<EthernetAddress>
-    },
-    NeighborAdvert {
-        flags: NeighborFlags,
-        target_addr: Ipv6Address,
-        lladdr: Option<EthernetAddress>
+/// An iterator over the options of a neighbor discovery message.
+///
+/// Yields an error and ends when an option is malformed, in which case the whole message should
+/// be discarded.
+#[derive(Clone, Debug)]
+pub struct Options<'a> {
+    bytes: &'a [u8],
+}
+
+/// An option of a neighbor discovery message.
+///
+/// See https://tools.ietf.org/html/rfc4861#section-4.6 for details.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NdiscOption<'a> {
+    /// The link-layer address of the sender.
+    SourceLinkLayerAddr(EthernetAddress),
+    /// The link-layer address of the target.
+    TargetLinkLayerAddr(EthernetAddress),
+    /// A prefix for on-link determination and address autoconfiguration.
+    PrefixInformation(PrefixInformation),
+    /// The recommended mtu of the link.
+    Mtu(u32),
+    /// Any other option, with the data following the type and length bytes.
+    Unknown {
+        kind: u8,
+        data: &'a [u8],
     },
-    Redirect {
-        target_addr: Ipv6Address,
-        dest_addr: Ipv6Address,
-        lladdr: Option<EthernetAddress>,
-        redirected_hdr: Option<NdiscRedirectedHeader<'a>>
+}
+
+/// The contents of a prefix information option.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrefixInformation {
+    /// The number of leading bits of the prefix that are valid.
+    pub prefix_len: u8,
+    /// The prefix can be used for on-link determination.
+    pub on_link: bool,
+    /// The prefix can be used for stateless address autoconfiguration.
+    pub autonomous: bool,
+    /// Seconds for which the prefix is valid, [`INFINITE`] for no limit.
+    ///
+    /// [`INFINITE`]: #associatedconstant.INFINITE
+    pub valid_lifetime: u32,
+    /// Seconds for which addresses generated from the prefix are preferred, [`INFINITE`] for no
+    /// limit.
+    ///
+    /// [`INFINITE`]: #associatedconstant.INFINITE
+    pub preferred_lifetime: u32,
+    /// The prefix itself.
+    pub prefix: Ipv6Address,
+}
+
+mod option {
+    use crate::wire::field::Field;
+
+    pub(crate) const TYPE:   usize = 0;
+    pub(crate) const LENGTH: usize = 1;
+
+    pub(crate) const SOURCE_LL_ADDR: u8 = 1;
+    pub(crate) const TARGET_LL_ADDR: u8 = 2;
+    pub(crate) const PREFIX_INFO:    u8 = 3;
+    pub(crate) const MTU:            u8 = 5;
+
+    pub(crate) const LL_ADDR:        Field = 2..8;
+
+    pub(crate) const PREFIX_LEN:     usize = 2;
+    pub(crate) const PREFIX_FLAGS:   usize = 3;
+    pub(crate) const VALID_LT:       Field = 4..8;
+    pub(crate) const PREFERRED_LT:   Field = 8..12;
+    pub(crate) const PREFIX:         Field = 16..32;
+
+    pub(crate) const MTU_VALUE:      Field = 4..8;
+
+    pub(crate) const FLAG_ON_LINK:    u8 = 0x80;
+    pub(crate) const FLAG_AUTONOMOUS: u8 = 0x40;
+}
+
+impl PrefixInformation {
+    /// The lifetime value representing an unlimited lifetime.
+    pub const INFINITE: u32 = u32::MAX;
+
+    /// The advertised prefix as a subnet.
+    pub fn subnet(&self) -> Ipv6Subnet {
+        Ipv6Cidr::new(self.prefix, self.prefix_len).subnet()
     }
 }
 
-impl<'a> Repr<'a> {
-    /// Parse an NDISC packet and return a high-level representation of the
-    /// packet.
-    pub fn parse<T>(packet: &Packet<&'a T>)
-                   -> Result<Repr<'a>>
-                where T: AsRef<[u8]> + ?Sized {
-        match packet.msg_type() {
-            Message::RouterSolicit => {
-                let lladdr = if packet.payload().len() > 0 {
-                    let opt = NdiscOption::new_checked(packet.payload())?;
-                    match opt.option_type() {
-                        NdiscOptionType::SourceLinkLayerAddr => Some(opt.link_layer_addr()),
-                        _ => { return Err(Error::Unrecognized); }
-                    }
-                } else {
-                    None
-                };
-                Ok(Repr::RouterSolicit { lladdr })
-            },
-            Message::RouterAdvert => {
-                let mut offset = 0;
-                let (mut lladdr, mut mtu, mut prefix_info) = (None, None, None);
-                while packet.payload().len() - offset > 0 {
-                    let pkt = NdiscOption::new_checked(&packet.payload()[offset..])?;
-                    let opt = NdiscOptionRepr::parse(&pkt)?;
-                    match opt {
-                        NdiscOptionRepr::SourceLinkLayerAddr(addr) => lladdr = Some(addr),
-                        NdiscOptionRepr::Mtu(val) => mtu = Some(val),
-                        NdiscOptionRepr::PrefixInformation(info) => prefix_info = Some(info),
-                        _ => { return Err(Error::Unrecognized); }
-                    }
-                    offset += opt.buffer_len();
+impl<'a> NdiscOption<'a> {
+    /// Parse a single option, the buffer must contain exactly its length.
+    fn parse(bytes: &'a [u8]) -> Result<Self> {
+        let kind = bytes[option::TYPE];
+        let option = match (kind, bytes.len()) {
+            (option::SOURCE_LL_ADDR, 8) => NdiscOption::SourceLinkLayerAddr(
+                EthernetAddress::from_bytes(&bytes[option::LL_ADDR])),
+            (option::TARGET_LL_ADDR, 8) => NdiscOption::TargetLinkLayerAddr(
+                EthernetAddress::from_bytes(&bytes[option::LL_ADDR])),
+            (option::PREFIX_INFO, 32) => {
+                let prefix_len = bytes[option::PREFIX_LEN];
+                if prefix_len > 128 {
+                    return Err(Error::Malformed);
                 }
-                Ok(Repr::RouterAdvert {
-                    hop_limit: packet.current_hop_limit(),
-                    flags: packet.router_flags(),
-                    router_lifetime: packet.router_lifetime(),
-                    reachable_time: packet.reachable_time(),
-                    retrans_time: packet.retrans_time(),
-                    lladdr, mtu, prefix_info
-                })
-            },
-            Message::NeighborSolicit => {
-                let lladdr = if packet.payload().len() > 0 {
-                    let opt = NdiscOption::new_checked(packet.payload())?;
-                    match opt.option_type() {
-                        NdiscOptionType::SourceLinkLayerAddr => Some(opt.link_layer_addr()),
-                        _ => { return Err(Error::Unrecognized); }
-                    }
-                } else {
-                    None
-                };
-                Ok(Repr::NeighborSolicit {
-                    target_addr: packet.target_addr(), lladdr
-                })
-            },
-            Message::NeighborAdvert => {
-                let lladdr = if packet.payload().len() > 0 {
-                    let opt = NdiscOption::new_checked(packet.payload())?;
-                    match opt.option_type() {
-                        NdiscOptionType::TargetLinkLayerAddr => Some(opt.link_layer_addr()),
-                        _ => { return Err(Error::Unrecognized); }
-                    }
-                } else {
-                    None
-                };
-                Ok(Repr::NeighborAdvert {
-                    flags: packet.neighbor_flags(),
-                    target_addr: packet.target_addr(),
-                    lladdr
+                let flags = bytes[option::PREFIX_FLAGS];
+                NdiscOption::PrefixInformation(PrefixInformation {
+                    prefix_len,
+                    on_link: flags & option::FLAG_ON_LINK != 0,
+                    autonomous: flags & option::FLAG_AUTONOMOUS != 0,
+                    valid_lifetime: NetworkEndian::read_u32(&bytes[option::VALID_LT]),
+                    preferred_lifetime: NetworkEndian::read_u32(&bytes[option::PREFERRED_LT]),
+                    prefix: Ipv6Address::from_bytes(&bytes[option::PREFIX]),
                 })
             },
-            Message::Redirect => {
-                let mut offset = 0;
-                let (mut lladdr, mut redirected_hdr) = (None, None);
-                while packet.payload().len() - offset > 0 {
-                    let opt = NdiscOption::new_checked(&packet.payload()[offset..])?;
-                    match opt.option_type() {
-                        NdiscOptionType::SourceLinkLayerAddr => {
-                            lladdr = Some(opt.link_layer_addr());
-                            offset += 8;
-                        },
-                        NdiscOptionType::RedirectedHeader => {
-                            if opt.data_len() < 6 {
-                                return Err(Error::Truncated)
-                            } else {
-                                let ip_packet =
-                                    Ipv6Packet::new_unchecked(&opt.data()[offset + 8..]);
-                                let ip_repr = Ipv6Repr::parse(&ip_packet)?;
-                                let data = &opt.data()[offset + 8 + ip_repr.buffer_len()..];
-                                redirected_hdr = Some(NdiscRedirectedHeader {
-                                    header: ip_repr, data
-                                });
-                                offset += 8 + ip_repr.buffer_len() + data.len();
-                            }
-                        }
-                        _ => { return Err(Error::Unrecognized); }
-                    }
-                }
-                Ok(Repr::Redirect {
-                    target_addr: packet.target_addr(),
-                    dest_addr: packet.dest_addr(),
-                    lladdr, redirected_hdr
-                })
+            (option::MTU, 8) => NdiscOption::Mtu(
+                NetworkEndian::read_u32(&bytes[option::MTU_VALUE])),
+            (option::SOURCE_LL_ADDR, _) | (option::TARGET_LL_ADDR, _)
+                | (option::PREFIX_INFO, _) | (option::MTU, _) => return Err(Error::Malformed),
+            (kind, _) => NdiscOption::Unknown {
+                kind,
+                data: &bytes[option::LENGTH + 1..],
             },
-            _ => Err(Error::Unrecognized)
-        }
+        };
+        Ok(option)
     }
 
+    /// The number of bytes occupied by the option, including padding.
     pub fn buffer_len(&self) -> usize {
         match self {
-            &Repr::RouterSolicit { lladdr } => {
-                match lladdr {
-                    Some(_) => field::UNUSED.end + 8,
-                    None => field::UNUSED.end,
-                }
+            NdiscOption::SourceLinkLayerAddr(_) => 8,
+            NdiscOption::TargetLinkLayerAddr(_) => 8,
+            NdiscOption::PrefixInformation(_) => 32,
+            NdiscOption::Mtu(_) => 8,
+            NdiscOption::Unknown { data, .. } => (data.len() + 2).div_ceil(8) * 8,
+        }
+    }
+
+    /// Write the option to the start of a buffer.
+    ///
+    /// # Panics
+    /// This method panics if the buffer is shorter than [`buffer_len`].
+    ///
+    /// [`buffer_len`]: #method.buffer_len
+    pub fn emit(&self, buffer: &mut [u8]) {
+        let len = self.buffer_len();
+        let buffer = &mut buffer[..len];
+        for byte in buffer.iter_mut() {
+            *byte = 0;
+        }
+        buffer[option::LENGTH] = (len / 8) as u8;
+        match *self {
+            NdiscOption::SourceLinkLayerAddr(addr) => {
+                buffer[option::TYPE] = option::SOURCE_LL_ADDR;
+                buffer[option::LL_ADDR].copy_from_slice(addr.as_bytes());
             },
-            &Repr::RouterAdvert { lladdr, mtu, prefix_info, .. } => {
-                let mut offset = 0;
-                if lladdr.is_some() {
-                    offset += 8;
-                }
-                if mtu.is_some() {
-                    offset += 8;
+            NdiscOption::TargetLinkLayerAddr(addr) => {
+                buffer[option::TYPE] = option::TARGET_LL_ADDR;
+                buffer[option::LL_ADDR].copy_from_slice(addr.as_bytes());
+            },
+            NdiscOption::PrefixInformation(info) => {
+                buffer[option::TYPE] = option::PREFIX_INFO;
+                buffer[option::PREFIX_LEN] = info.prefix_len;
+                if info.on_link {
+                    buffer[option::PREFIX_FLAGS] |= option::FLAG_ON_LINK;
                 }
-                if prefix_info.is_some() {
-                    offset += 32;
+                if info.autonomous {
+                    buffer[option::PREFIX_FLAGS] |= option::FLAG_AUTONOMOUS;
                 }
-                field::RETRANS_TM.end + offset
+                NetworkEndian::write_u32(&mut buffer[option::VALID_LT], info.valid_lifetime);
+                let preferred = info.preferred_lifetime;
+                NetworkEndian::write_u32(&mut buffer[option::PREFERRED_LT], preferred);
+                buffer[option::PREFIX].copy_from_slice(info.prefix.as_bytes());
             },
-            &Repr::NeighborSolicit { lladdr, .. } | &Repr::NeighborAdvert { lladdr, .. } => {
-                match lladdr {
-                    Some(_) => field::TARGET_ADDR.end + 8,
-                    None => field::TARGET_ADDR.end,
-                }
+            NdiscOption::Mtu(mtu) => {
+                buffer[option::TYPE] = option::MTU;
+                NetworkEndian::write_u32(&mut buffer[option::MTU_VALUE], mtu);
+            },
+            NdiscOption::Unknown { kind, data } => {
+                buffer[option::TYPE] = kind;
+                buffer[option::LENGTH + 1..][..data.len()].copy_from_slice(data);
             },
-            &Repr::Redirect { lladdr, redirected_hdr, .. } => {
-                let mut offset = 0;
-                if lladdr.is_some() {
-                    offset += 8;
-                }
-                if let Some(NdiscRedirectedHeader { header, data }) = redirected_hdr {
-                    offset += 8 + header.buffer_len() + data.len();
-                }
-                field::DEST_ADDR.end + offset
-            }
         }
     }
+}
 
-    pub fn emit<T>(&self, packet: &mut Packet<&mut T>)
-            where T: AsRef<[u8]> + AsMut<[u8]> + ?Sized {
-        match self {
-            &Repr::RouterSolicit { lladdr } => {
-                packet.set_msg_type(Message::RouterSolicit);
-                packet.set_msg_code(0);
-                packet.clear_reserved();
-                if let Some(lladdr) = lladdr {
-                    let mut opt_pkt = NdiscOption::new_unchecked(packet.payload_mut());
-                    NdiscOptionRepr::SourceLinkLayerAddr(lladdr).emit(&mut opt_pkt);
-                }
-            },
+impl<'a> Iterator for Options<'a> {
+    type Item = Result<NdiscOption<'a>>;
 
-            &Repr::RouterAdvert { hop_limit, flags, router_lifetime, reachable_time,
-                                  retrans_time, lladdr, mtu, prefix_info } => {
-                packet.set_msg_type(Message::RouterAdvert);
-                packet.set_msg_code(0);
-                packet.set_current_hop_limit(hop_limit);
-                packet.set_router_flags(flags);
-                packet.set_router_lifetime(router_lifetime);
-                packet.set_reachable_time(reachable_time);
-                packet.set_retrans_time(retrans_time);
-                let mut offset = 0;
-                if let Some(lladdr) = lladdr {
-                    let mut opt_pkt =
-                        NdiscOption::new_unchecked(packet.payload_mut());
-                    NdiscOptionRepr::SourceLinkLayerAddr(lladdr).emit(&mut opt_pkt);
-                    offset += 8;
-                }
-                if let Some(mtu) = mtu {
-                    let mut opt_pkt =
-                        NdiscOption::new_unchecked(&mut packet.payload_mut()[offset..]);
-                    NdiscOptionRepr::Mtu(mtu).emit(&mut opt_pkt);
-                    offset += 8;
-                }
-                if let Some(prefix_info) = prefix_info {
-                    let mut opt_pkt =
-                        NdiscOption::new_unchecked(&mut packet.payload_mut()[offset..]);
-                    NdiscOptionRepr::PrefixInformation(prefix_info).emit(&mut opt_pkt)
-                }
-            },
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bytes.is_empty() {
+            return None;
+        }
 
-            &Repr::NeighborSolicit { target_addr, lladdr } => {
-                packet.set_msg_type(Message::NeighborSolicit);
-                packet.set_msg_code(0);
-                packet.clear_reserved();
-                packet.set_target_addr(target_addr);
-                if let Some(lladdr) = lladdr {
-                    let mut opt_pkt =
-                        NdiscOption::new_unchecked(packet.payload_mut());
-                    NdiscOptionRepr::SourceLinkLayerAddr(lladdr).emit(&mut opt_pkt);
-                }
-            },
+        // Options have a length of a multiple of 8 bytes and it is never zero.
+        let len = match self.bytes.get(option::LENGTH) {
+            Some(&len) => usize::from(len) * 8,
+            None => 0,
+        };
 
-            &Repr::NeighborAdvert { flags, target_addr, lladdr } => {
-                packet.set_msg_type(Message::NeighborAdvert);
-                packet.set_msg_code(0);
-                packet.clear_reserved();
-                packet.set_neighbor_flags(flags);
-                packet.set_target_addr(target_addr);
-                if let Some(lladdr) = lladdr {
-                    let mut opt_pkt =
-                        NdiscOption::new_unchecked(packet.payload_mut());
-                    NdiscOptionRepr::TargetLinkLayerAddr(lladdr).emit(&mut opt_pkt);
-                }
-            },
+        if len == 0 || len > self.bytes.len() {
+            self.bytes = &[];
+            return Some(Err(Error::Malformed));
+        }
 
-            &Repr::Redirect { target_addr, dest_addr, lladdr, redirected_hdr } => {
-                packet.set_msg_type(Message::Redirect);
-                packet.set_msg_code(0);
-                packet.clear_reserved();
-                packet.set_target_addr(target_addr);
-                packet.set_dest_addr(dest_addr);
-                let offset = match lladdr {
-                    Some(lladdr) => {
-                        let mut opt_pkt =
-                            NdiscOption::new_unchecked(packet.payload_mut());
-                        NdiscOptionRepr::TargetLinkLayerAddr(lladdr).emit(&mut opt_pkt);
-                        8
-                    },
-                    None => 0,
-                };
-                if let Some(redirected_hdr) = redirected_hdr {
-                    let mut opt_pkt =
-                        NdiscOption::new_unchecked(&mut packet.payload_mut()[offset..]);
-                    NdiscOptionRepr::RedirectedHeader(redirected_hdr).emit(&mut opt_pkt);
-                }
-            },
+        let (option, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        let option = NdiscOption::parse(option);
+        if option.is_err() {
+            self.bytes = &[];
         }
+        Some(option)
     }
 }
 
 #[cfg(test)]
 mod test {
-    use phy::ChecksumCapabilities;
     use super::*;
-    use wire::Icmpv6Repr;
-    use wire::ip::test::{MOCK_IP_ADDR_1, MOCK_IP_ADDR_2};
-
-    static ROUTER_ADVERT_BYTES: [u8; 24] =
-        [0x86, 0x00, 0xa9, 0xde,
-         0x40, 0x80, 0x03, 0x84,
-         0x00, 0x00, 0x03, 0x84,
-         0x00, 0x00, 0x03, 0x84,
-         0x01, 0x01, 0x52, 0x54,
-         0x00, 0x12, 0x34, 0x56];
-    static SOURCE_LINK_LAYER_OPT: [u8; 8] =
-        [0x01, 0x01, 0x52, 0x54,
-         0x00, 0x12, 0x34, 0x56];
-
-    fn create_repr<'a>() -> Icmpv6Repr<'a> {
-        Icmpv6Repr::Ndisc(Repr::RouterAdvert {
-            hop_limit: 64,
-            flags: RouterFlags::MANAGED,
-            router_lifetime: Duration::from_secs(900),
-            reachable_time: Duration::from_millis(900),
-            retrans_time: Duration::from_millis(900),
-            lladdr: Some(EthernetAddress([0x52, 0x54, 0x00, 0x12, 0x34, 0x56])),
-            mtu: None,
-            prefix_info: None
-        })
+
+    static ROUTER: Ipv6Address = Ipv6Address([
+        0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+    static ALL_NODES: Ipv6Address = Ipv6Address([
+        0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+
+    static ROUTER_ADVERT_BYTES: [u8; 64] = [
+        0x86, 0x00, 0x00, 0x00, 0x40, 0x80, 0x07, 0x08,
+        0x00, 0x00, 0x75, 0x30, 0x00, 0x00, 0x03, 0xe8,
+        0x01, 0x01, 0x52, 0x54, 0x00, 0x12, 0x34, 0x56,
+        0x05, 0x01, 0x00, 0x00, 0x00, 0x00, 0x05, 0xdc,
+        0x03, 0x04, 0x40, 0xc0, 0x00, 0x27, 0x8d, 0x00,
+        0x00, 0x09, 0x3a, 0x80, 0x00, 0x00, 0x00, 0x00,
+        0x20, 0x01, 0x0d, 0xb8, 0x00, 0x01, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    fn prefix() -> PrefixInformation {
+        PrefixInformation {
+            prefix_len: 64,
+            on_link: true,
+            autonomous: true,
+            valid_lifetime: 2_592_000,
+            preferred_lifetime: 604_800,
+            prefix: Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 0),
+        }
     }
 
     #[test]
-    fn test_router_advert_deconstruct() {
-        let packet = Packet::new_unchecked(&ROUTER_ADVERT_BYTES[..]);
+    fn test_router_advert() {
+        let mut bytes = ROUTER_ADVERT_BYTES;
+        let packet = ndisc::new_unchecked_mut(&mut bytes[..]);
+        packet.fill_checksum(ROUTER, ALL_NODES);
+        let packet = ndisc::new_checked(&bytes[..]).unwrap();
+        assert!(packet.verify_checksum(ROUTER, ALL_NODES));
         assert_eq!(packet.msg_type(), Message::RouterAdvert);
         assert_eq!(packet.msg_code(), 0);
         assert_eq!(packet.current_hop_limit(), 64);
-        assert_eq!(packet.router_flags(), RouterFlags::MANAGED);
-        assert_eq!(packet.router_lifetime(), Duration::from_secs(900));
-        assert_eq!(packet.reachable_time(), Duration::from_millis(900));
-        assert_eq!(packet.retrans_time(), Duration::from_millis(900));
-        assert_eq!(packet.payload(), &SOURCE_LINK_LAYER_OPT[..]);
+        assert_eq!(packet.router_flags(), ndisc::FLAG_MANAGED);
+        assert_eq!(packet.router_lifetime(), Duration::from_secs(1800));
+        assert_eq!(packet.reachable_time(), Duration::from_secs(30));
+        assert_eq!(packet.retrans_time(), Duration::from_secs(1));
+
+        let mut options = packet.options();
+        assert_eq!(options.next(), Some(Ok(NdiscOption::SourceLinkLayerAddr(
+            EthernetAddress([0x52, 0x54, 0x00, 0x12, 0x34, 0x56])))));
+        assert_eq!(options.next(), Some(Ok(NdiscOption::Mtu(1500))));
+        assert_eq!(options.next(), Some(Ok(NdiscOption::PrefixInformation(prefix()))));
+        assert_eq!(options.next(), None);
     }
 
     #[test]
-    fn test_router_advert_construct() {
-        let mut bytes = vec![0x0; 24];
-        let mut packet = Packet::new_unchecked(&mut bytes);
+    fn test_construct_router_advert() {
+        let mut bytes = [0xa5; 64];
+        let packet = ndisc::new_unchecked_mut(&mut bytes[..]);
         packet.set_msg_type(Message::RouterAdvert);
         packet.set_msg_code(0);
         packet.set_current_hop_limit(64);
-        packet.set_router_flags(RouterFlags::MANAGED);
-        packet.set_router_lifetime(Duration::from_secs(900));
-        packet.set_reachable_time(Duration::from_millis(900));
-        packet.set_retrans_time(Duration::from_millis(900));
-        packet.payload_mut().copy_from_slice(&SOURCE_LINK_LAYER_OPT[..]);
-        packet.fill_checksum(&MOCK_IP_ADDR_1, &MOCK_IP_ADDR_2);
-        assert_eq!(&packet.into_inner()[..], &ROUTER_ADVERT_BYTES[..]);
+        packet.set_router_flags(ndisc::FLAG_MANAGED);
+        packet.set_router_lifetime(Duration::from_secs(1800));
+        packet.set_reachable_time(Duration::from_secs(30));
+        packet.set_retrans_time(Duration::from_secs(1));
+
+        let options = [
+            NdiscOption::SourceLinkLayerAddr(EthernetAddress([0x52, 0x54, 0x00, 0x12, 0x34, 0x56])),
+            NdiscOption::Mtu(1500),
+            NdiscOption::PrefixInformation(prefix()),
+        ];
+        let mut buffer = packet.options_mut_slice();
+        for option in options.iter() {
+            option.emit(buffer);
+            buffer = &mut buffer[option.buffer_len()..];
+        }
+        packet.fill_checksum(ROUTER, ALL_NODES);
+
+        let mut expected = ROUTER_ADVERT_BYTES;
+        ndisc::new_unchecked_mut(&mut expected[..]).fill_checksum(ROUTER, ALL_NODES);
+        assert_eq!(&bytes[..], &expected[..]);
     }
 
     #[test]
-    fn test_router_advert_repr_parse() {
-        let packet = Packet::new_unchecked(&ROUTER_ADVERT_BYTES[..]);
-        assert_eq!(Icmpv6Repr::parse(&MOCK_IP_ADDR_1, &MOCK_IP_ADDR_2,
-                                     &packet, &ChecksumCapabilities::default()).unwrap(),
-                   create_repr());
+    fn test_malformed_options() {
+        let mut bytes = ROUTER_ADVERT_BYTES;
+        // An option with a length of zero ends the iteration with an error.
+        bytes[25] = 0;
+        let packet = ndisc::new_checked(&bytes[..]).unwrap();
+        let mut options = packet.options();
+        assert!(options.next().unwrap().is_ok());
+        assert_eq!(options.next(), Some(Err(Error::Malformed)));
+        assert_eq!(options.next(), None);
+
+        // So does an option exceeding the message.
+        let packet = ndisc::new_checked(&ROUTER_ADVERT_BYTES[..56]).unwrap();
+        assert_eq!(packet.options().nth(2), Some(Err(Error::Malformed)));
+        assert!(ndisc::new_checked(&ROUTER_ADVERT_BYTES[..15]).is_err());
     }
 
     #[test]
-    fn test_router_advert_repr_emit() {
-        let mut bytes = vec![0x2a; 24];
-        let mut packet = Packet::new_unchecked(&mut bytes[..]);
-        create_repr().emit(&MOCK_IP_ADDR_1, &MOCK_IP_ADDR_2,
-                           &mut packet, &ChecksumCapabilities::default());
-        assert_eq!(&packet.into_inner()[..], &ROUTER_ADVERT_BYTES[..]);
+    fn test_neighbor_solicit() {
+        let mut bytes = [0u8; 24];
+        let packet = ndisc::new_unchecked_mut(&mut bytes[..]);
+        packet.set_msg_type(Message::NeighborSolicit);
+        packet.set_target_addr(ROUTER);
+        assert_eq!(&bytes[..4], &[0x87, 0x00, 0x00, 0x00]);
+        assert_eq!(&bytes[8..], ROUTER.as_bytes());
+
+        let packet = ndisc::new_checked(&bytes[..]).unwrap();
+        assert_eq!(packet.target_addr(), ROUTER);
+        assert_eq!(packet.options().next(), None);
+        assert!(ndisc::new_checked(&bytes[..23]).is_err());
     }
 }
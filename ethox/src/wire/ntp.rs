@@ -0,0 +1,500 @@
+//! NTP packets as used by simple clients and servers, rfc4330.
+//!
+//! Only the fixed header is supported, extension fields and the optional key identifier and
+//! message digest of authenticated packets are ignored.
+use core::fmt;
+use byteorder::{ByteOrder, NetworkEndian};
+
+use super::{Error, Result};
+
+/// The udp port on which servers listen.
+pub const PORT: u16 = 123;
+
+/// Seconds between the NTP epoch (1900) and the unix epoch (1970).
+const UNIX_OFFSET: i64 = 2_208_988_800;
+
+/// The length of the era of the 32-bit seconds counter, in seconds.
+const ERA: i64 = 1 << 32;
+
+enum_with_unknown! {
+    /// The leap second indicator of a packet.
+    pub doc enum Leap(u8) {
+        /// No warning
+        NoWarning = 0,
+        /// The last minute of the day has 61 seconds
+        Insert = 1,
+        /// The last minute of the day has 59 seconds
+        Delete = 2,
+        /// The clock of the sender is not synchronized
+        Alarm = 3,
+    }
+}
+
+enum_with_unknown! {
+    /// The association mode of a packet.
+    pub doc enum Mode(u8) {
+        /// Symmetric active
+        SymmetricActive = 1,
+        /// Symmetric passive
+        SymmetricPassive = 2,
+        /// Client
+        Client = 3,
+        /// Server
+        Server = 4,
+        /// Broadcast
+        Broadcast = 5,
+    }
+}
+
+/// A timestamp in the 64-bit NTP format.
+///
+/// Counts seconds since the start of the current era, with a binary fraction of a second. The
+/// first era started 1900-01-01 and ends in 2036. As recommended by rfc4330 section 3, timestamps
+/// with the most significant bit cleared are assumed to be in the following era, which extends
+/// the range up to the year 2104.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    /// Seconds since the start of the era.
+    pub seconds: u32,
+    /// Fractions of a second, in units of 2^-32 seconds.
+    pub fraction: u32,
+}
+
+byte_wrapper! {
+    /// A byte slice containing a potential NTP packet.
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct ntp([u8]);
+}
+
+// Format of an NTP packet header
+//
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |LI | VN  |Mode |    Stratum    |     Poll      |   Precision   |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                          Root Delay                           |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                       Root Dispersion                         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                     Reference Identifier                      |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                   Reference Timestamp (64)                    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                   Originate Timestamp (64)                    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                    Receive Timestamp (64)                     |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                    Transmit Timestamp (64)                    |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// See https://tools.ietf.org/html/rfc4330#section-4 for details.
+mod field {
+    use crate::wire::field::Field;
+
+    pub(crate) const FLAGS:           usize = 0;
+    pub(crate) const STRATUM:         usize = 1;
+    pub(crate) const POLL:            usize = 2;
+    pub(crate) const PRECISION:       usize = 3;
+    pub(crate) const ROOT_DELAY:      Field = 4..8;
+    pub(crate) const ROOT_DISPERSION: Field = 8..12;
+    pub(crate) const REFERENCE_ID:    Field = 12..16;
+    pub(crate) const REFERENCE:       Field = 16..24;
+    pub(crate) const ORIGINATE:       Field = 24..32;
+    pub(crate) const RECEIVE:         Field = 32..40;
+    pub(crate) const TRANSMIT:        Field = 40..48;
+}
+
+/// The length of an NTP packet without extensions.
+pub const HEADER_LEN: usize = field::TRANSMIT.end;
+
+impl Timestamp {
+    /// The zero timestamp, meaning unknown or unsynchronized.
+    pub const ZERO: Timestamp = Timestamp { seconds: 0, fraction: 0 };
+
+    /// Create a timestamp from its 64-bit network representation.
+    pub fn from_bits(bits: u64) -> Self {
+        Timestamp {
+            seconds: (bits >> 32) as u32,
+            fraction: bits as u32,
+        }
+    }
+
+    /// The 64-bit network representation of the timestamp.
+    pub fn to_bits(self) -> u64 {
+        u64::from(self.seconds) << 32 | u64::from(self.fraction)
+    }
+
+    /// Convert milliseconds since the unix epoch.
+    ///
+    /// Times outside the two supported eras wrap around.
+    pub fn from_unix_millis(millis: i64) -> Self {
+        let seconds = millis.div_euclid(1000) + UNIX_OFFSET;
+        let millis = millis.rem_euclid(1000) as u64;
+        Timestamp {
+            seconds: seconds.rem_euclid(ERA) as u32,
+            // Round up, such that the conversion back to milliseconds is exact.
+            fraction: (millis << 32).div_ceil(1000) as u32,
+        }
+    }
+
+    /// Milliseconds since the unix epoch, rounded down.
+    pub fn to_unix_millis(self) -> i64 {
+        let era = if self.seconds & 0x8000_0000 == 0 { ERA } else { 0 };
+        let seconds = i64::from(self.seconds) + era - UNIX_OFFSET;
+        let millis = (u64::from(self.fraction) * 1000) >> 32;
+        seconds * 1000 + millis as i64
+    }
+}
+
+impl ntp {
+    /// Imbue a raw octet buffer with NTP packet structure.
+    pub fn new_unchecked(data: &[u8]) -> &Self {
+        Self::__from_macro_new_unchecked(data)
+    }
+
+    /// Imbue a mutable octet buffer with NTP packet structure.
+    pub fn new_unchecked_mut(data: &mut [u8]) -> &mut Self {
+        Self::__from_macro_new_unchecked_mut(data)
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(data: &[u8]) -> Result<&Self> {
+        let packet = Self::new_unchecked(data);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// View the packet as a raw byte slice.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// View the packet as a mutable raw byte slice.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error::Truncated)` if the buffer is too short.
+    pub fn check_len(&self) -> Result<()> {
+        if self.0.len() < HEADER_LEN {
+            Err(Error::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn leap(&self) -> Leap {
+        Leap::from(self.0[field::FLAGS] >> 6)
+    }
+
+    pub fn version(&self) -> u8 {
+        (self.0[field::FLAGS] >> 3) & 0x7
+    }
+
+    pub fn mode(&self) -> Mode {
+        Mode::from(self.0[field::FLAGS] & 0x7)
+    }
+
+    pub fn stratum(&self) -> u8 {
+        self.0[field::STRATUM]
+    }
+
+    /// The maximum interval between messages, as a power of two in seconds.
+    pub fn poll(&self) -> i8 {
+        self.0[field::POLL] as i8
+    }
+
+    /// The precision of the clock, as a power of two in seconds.
+    pub fn precision(&self) -> i8 {
+        self.0[field::PRECISION] as i8
+    }
+
+    /// The round trip delay to the primary source, in fixed point seconds with 16 fraction bits.
+    pub fn root_delay(&self) -> i32 {
+        NetworkEndian::read_i32(&self.0[field::ROOT_DELAY])
+    }
+
+    /// The error relative to the primary source, in fixed point seconds with 16 fraction bits.
+    pub fn root_dispersion(&self) -> u32 {
+        NetworkEndian::read_u32(&self.0[field::ROOT_DISPERSION])
+    }
+
+    /// The reference source, or a kiss code in packets of stratum 0.
+    pub fn reference_id(&self) -> [u8; 4] {
+        let mut id = [0; 4];
+        id.copy_from_slice(&self.0[field::REFERENCE_ID]);
+        id
+    }
+
+    pub fn reference_timestamp(&self) -> Timestamp {
+        Timestamp::from_bits(NetworkEndian::read_u64(&self.0[field::REFERENCE]))
+    }
+
+    pub fn originate_timestamp(&self) -> Timestamp {
+        Timestamp::from_bits(NetworkEndian::read_u64(&self.0[field::ORIGINATE]))
+    }
+
+    pub fn receive_timestamp(&self) -> Timestamp {
+        Timestamp::from_bits(NetworkEndian::read_u64(&self.0[field::RECEIVE]))
+    }
+
+    pub fn transmit_timestamp(&self) -> Timestamp {
+        Timestamp::from_bits(NetworkEndian::read_u64(&self.0[field::TRANSMIT]))
+    }
+
+    pub fn set_leap(&mut self, value: Leap) {
+        let flags = self.0[field::FLAGS] & 0x3f;
+        self.0[field::FLAGS] = flags | u8::from(value) << 6;
+    }
+
+    pub fn set_version(&mut self, value: u8) {
+        let flags = self.0[field::FLAGS] & 0xc7;
+        self.0[field::FLAGS] = flags | (value & 0x7) << 3;
+    }
+
+    pub fn set_mode(&mut self, value: Mode) {
+        let flags = self.0[field::FLAGS] & 0xf8;
+        self.0[field::FLAGS] = flags | (u8::from(value) & 0x7);
+    }
+
+    pub fn set_stratum(&mut self, value: u8) {
+        self.0[field::STRATUM] = value
+    }
+
+    pub fn set_poll(&mut self, value: i8) {
+        self.0[field::POLL] = value as u8
+    }
+
+    pub fn set_precision(&mut self, value: i8) {
+        self.0[field::PRECISION] = value as u8
+    }
+
+    pub fn set_root_delay(&mut self, value: i32) {
+        NetworkEndian::write_i32(&mut self.0[field::ROOT_DELAY], value)
+    }
+
+    pub fn set_root_dispersion(&mut self, value: u32) {
+        NetworkEndian::write_u32(&mut self.0[field::ROOT_DISPERSION], value)
+    }
+
+    pub fn set_reference_id(&mut self, value: [u8; 4]) {
+        self.0[field::REFERENCE_ID].copy_from_slice(&value)
+    }
+
+    pub fn set_reference_timestamp(&mut self, value: Timestamp) {
+        NetworkEndian::write_u64(&mut self.0[field::REFERENCE], value.to_bits())
+    }
+
+    pub fn set_originate_timestamp(&mut self, value: Timestamp) {
+        NetworkEndian::write_u64(&mut self.0[field::ORIGINATE], value.to_bits())
+    }
+
+    pub fn set_receive_timestamp(&mut self, value: Timestamp) {
+        NetworkEndian::write_u64(&mut self.0[field::RECEIVE], value.to_bits())
+    }
+
+    pub fn set_transmit_timestamp(&mut self, value: Timestamp) {
+        NetworkEndian::write_u64(&mut self.0[field::TRANSMIT], value.to_bits())
+    }
+}
+
+impl AsRef<[u8]> for ntp {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl AsMut<[u8]> for ntp {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.as_bytes_mut()
+    }
+}
+
+/// A high-level representation of an NTP packet header.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Repr {
+    pub leap: Leap,
+    pub version: u8,
+    pub mode: Mode,
+    pub stratum: u8,
+    pub poll: i8,
+    pub precision: i8,
+    pub root_delay: i32,
+    pub root_dispersion: u32,
+    pub reference_id: [u8; 4],
+    pub reference_timestamp: Timestamp,
+    pub originate_timestamp: Timestamp,
+    pub receive_timestamp: Timestamp,
+    pub transmit_timestamp: Timestamp,
+}
+
+impl Repr {
+    /// The version of the protocol, rfc5905.
+    pub const VERSION: u8 = 4;
+
+    /// A client request sent at some time, rfc4330 section 5.
+    ///
+    /// Servers copy the transmit timestamp into the originate timestamp of their response. It need
+    /// not be a valid time, only unique among outstanding requests.
+    pub fn request(transmit_timestamp: Timestamp) -> Self {
+        Repr {
+            leap: Leap::NoWarning,
+            version: Self::VERSION,
+            mode: Mode::Client,
+            stratum: 0,
+            poll: 0,
+            precision: 0,
+            root_delay: 0,
+            root_dispersion: 0,
+            reference_id: [0; 4],
+            reference_timestamp: Timestamp::ZERO,
+            originate_timestamp: Timestamp::ZERO,
+            receive_timestamp: Timestamp::ZERO,
+            transmit_timestamp,
+        }
+    }
+
+    /// Parse an NTP packet and return a high-level representation.
+    pub fn parse(packet: &ntp) -> Result<Repr> {
+        packet.check_len()?;
+        Ok(Repr {
+            leap: packet.leap(),
+            version: packet.version(),
+            mode: packet.mode(),
+            stratum: packet.stratum(),
+            poll: packet.poll(),
+            precision: packet.precision(),
+            root_delay: packet.root_delay(),
+            root_dispersion: packet.root_dispersion(),
+            reference_id: packet.reference_id(),
+            reference_timestamp: packet.reference_timestamp(),
+            originate_timestamp: packet.originate_timestamp(),
+            receive_timestamp: packet.receive_timestamp(),
+            transmit_timestamp: packet.transmit_timestamp(),
+        })
+    }
+
+    /// Return the length of the packet that will be emitted from this high-level representation.
+    pub fn buffer_len(&self) -> usize {
+        HEADER_LEN
+    }
+
+    /// Emit a high-level representation into an NTP packet.
+    pub fn emit(&self, packet: &mut ntp) {
+        packet.set_leap(self.leap);
+        packet.set_version(self.version);
+        packet.set_mode(self.mode);
+        packet.set_stratum(self.stratum);
+        packet.set_poll(self.poll);
+        packet.set_precision(self.precision);
+        packet.set_root_delay(self.root_delay);
+        packet.set_root_dispersion(self.root_dispersion);
+        packet.set_reference_id(self.reference_id);
+        packet.set_reference_timestamp(self.reference_timestamp);
+        packet.set_originate_timestamp(self.originate_timestamp);
+        packet.set_receive_timestamp(self.receive_timestamp);
+        packet.set_transmit_timestamp(self.transmit_timestamp);
+    }
+
+    /// The kiss code of a kiss-o'-death packet, rfc4330 section 8.
+    ///
+    /// Such packets have stratum 0 and carry an ascii code in place of the reference identifier.
+    pub fn kiss_code(&self) -> Option<[u8; 4]> {
+        if self.stratum == 0 {
+            Some(self.reference_id)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let millis = (u64::from(self.fraction) * 1000) >> 32;
+        write!(f, "{}.{:03}", self.seconds, millis)
+    }
+}
+
+impl fmt::Display for Repr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "NTPv{} mode={:?} stratum={} xmt={}",
+            self.version, self.mode, self.stratum, self.transmit_timestamp)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static PACKET_BYTES: [u8; 48] = [
+        0x24, 0x02, 0x06, 0xe9,
+        0x00, 0x00, 0x00, 0x10,
+        0x00, 0x00, 0x00, 0x20,
+        0xc0, 0xa8, 0x00, 0x01,
+        0xe0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0xe0, 0x00, 0x00, 0x01, 0x80, 0x00, 0x00, 0x00,
+        0xe0, 0x00, 0x00, 0x02, 0x40, 0x00, 0x00, 0x00,
+        0xe0, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    fn packet_repr() -> Repr {
+        Repr {
+            leap: Leap::NoWarning,
+            version: 4,
+            mode: Mode::Server,
+            stratum: 2,
+            poll: 6,
+            precision: -23,
+            root_delay: 0x10,
+            root_dispersion: 0x20,
+            reference_id: [192, 168, 0, 1],
+            reference_timestamp: Timestamp { seconds: 0xe000_0000, fraction: 0 },
+            originate_timestamp: Timestamp { seconds: 0xe000_0001, fraction: 0x8000_0000 },
+            receive_timestamp: Timestamp { seconds: 0xe000_0002, fraction: 0x4000_0000 },
+            transmit_timestamp: Timestamp { seconds: 0xe000_0003, fraction: 0 },
+        }
+    }
+
+    #[test]
+    fn test_deconstruct() {
+        let packet = ntp::new_checked(&PACKET_BYTES[..]).unwrap();
+        assert_eq!(packet.leap(), Leap::NoWarning);
+        assert_eq!(packet.version(), 4);
+        assert_eq!(packet.mode(), Mode::Server);
+        assert_eq!(packet.precision(), -23);
+        assert_eq!(Repr::parse(packet), Ok(packet_repr()));
+    }
+
+    #[test]
+    fn test_construct() {
+        let mut bytes = [0xa5; 48];
+        let packet = ntp::new_unchecked_mut(&mut bytes[..]);
+        packet_repr().emit(packet);
+        assert_eq!(&bytes[..], &PACKET_BYTES[..]);
+    }
+
+    #[test]
+    fn test_truncated() {
+        assert_eq!(ntp::new_checked(&PACKET_BYTES[..47]), Err(Error::Truncated));
+    }
+
+    #[test]
+    fn test_unix_time() {
+        assert_eq!(Timestamp::from_unix_millis(0).seconds, 2_208_988_800);
+        let millis = 1_700_000_000_250;
+        let timestamp = Timestamp::from_unix_millis(millis);
+        assert_eq!(timestamp.fraction, 0x4000_0000);
+        assert_eq!(timestamp.to_unix_millis(), millis);
+
+        // After the rollover of the first era in 2036.
+        let millis = 2_200_000_000_000;
+        let timestamp = Timestamp::from_unix_millis(millis);
+        assert!(timestamp.seconds < 0x8000_0000);
+        assert_eq!(timestamp.to_unix_millis(), millis);
+    }
+}
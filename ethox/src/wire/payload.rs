@@ -3,6 +3,9 @@ use core::ops;
 
 use crate::managed::Slice;
 
+#[cfg(feature = "alloc")]
+pub use self::segmented::Segmented;
+
 /// A specialized, internal variant of `Borrow<payload>`.
 ///
 /// This ensures that the implementation is also consistent and always resolves to the same memory
@@ -42,6 +45,128 @@ pub trait PayloadMut: Payload {
 
     /// Retrieve the mutable, inner payload.
     fn payload_mut(&mut self) -> &mut payload;
+
+    /// The number of bytes in front of the payload into which it can grow.
+    ///
+    /// Buffers without such a reserve have no headroom, which is the default.
+    fn headroom(&self) -> usize {
+        0
+    }
+
+    /// Reserve some bytes of headroom in front of the payload.
+    ///
+    /// Moves the start of the payload such that exactly `headroom` bytes precede it while keeping
+    /// its length. Just as with `resize`, the content of the payload is not preserved. The default
+    /// only supports having no headroom at all.
+    fn set_headroom(&mut self, headroom: usize) -> Result<(), Error> {
+        if headroom == 0 {
+            Ok(())
+        } else {
+            Err(Error::BadSize)
+        }
+    }
+
+    /// Grow the payload at its front.
+    ///
+    /// The current content is preserved and afterwards starts `length` bytes into the payload.
+    /// This is cheap when the headroom suffices. The default implementation has none and instead
+    /// reframes the buffer and moves all of the content.
+    fn prepend(&mut self, length: usize) -> Result<(), Error> {
+        let current = self.payload().len();
+        self.reframe(Reframe {
+            length: current + length,
+            range: 0..current,
+        })?;
+        self.payload_mut().copy_within(0..current, length);
+        Ok(())
+    }
+
+    /// Shrink the payload at its front, the opposite of `prepend`.
+    ///
+    /// The remaining content is preserved and now starts at the beginning of the payload. Buffers
+    /// with a reserve add the removed bytes to their headroom, the default moves the content and
+    /// resizes the buffer.
+    fn trim_front(&mut self, length: usize) -> Result<(), Error> {
+        let current = self.payload().len();
+        if length > current {
+            return Err(Error::BadSize);
+        }
+        self.payload_mut().copy_within(length..current, 0);
+        self.resize(current - length)
+    }
+}
+
+/// A payload whose packet may continue in separate segments.
+///
+/// Devices with scatter-gather DMA transmit a single packet from several buffers. This trait
+/// exposes such a packet as its contiguous part, the usual `payload`, followed by a small number
+/// of segments. Layers keep all headers in the contiguous part and may only place the data of the
+/// innermost protocol into segments. That way the user data need not be copied behind the headers
+/// and the contiguous buffer only has to provide room for the headers.
+///
+/// The methods of `PayloadMut` operate on the contiguous part alone. Buffers offered by a device
+/// for sending must not have any segments.
+pub trait PayloadVectored: PayloadMut {
+    /// The number of segments following the contiguous part.
+    fn segment_count(&self) -> usize;
+
+    /// Retrieve a segment by its index.
+    fn segment(&self, idx: usize) -> Option<&payload>;
+
+    /// Retrieve a mutable segment by its index.
+    fn segment_mut(&mut self, idx: usize) -> Option<&mut payload>;
+
+    /// Append a new segment of the given length.
+    ///
+    /// The content of the segment is unspecified, just as that of new bytes when resizing. Fails if
+    /// no further segment or not that much memory is available.
+    fn push_segment(&mut self, length: usize) -> Result<(), Error>;
+
+    /// Remove all segments.
+    fn clear_segments(&mut self);
+
+    /// The combined length of all segments.
+    fn segments_len(&self) -> usize {
+        (0..self.segment_count())
+            .filter_map(|idx| self.segment(idx))
+            .map(|segment| segment.len())
+            .sum()
+    }
+}
+
+/// A payload stored in up to two consecutive parts.
+///
+/// Descriptor rings of network cards often place packets into one contiguous memory region from
+/// which a packet may wrap around, continuing at the start of the region. Such a packet can not be
+/// viewed as a single slice without copying it. This trait exposes it as two parts instead, the
+/// second of which is empty for contiguous packets. Parsers that only need the headers accept such
+/// a packet as long as the headers lie within the first part, see for example
+/// [`Ipv4Repr::parse_parts`].
+///
+/// All buffers implementing `Payload` are also payloads in parts, with an empty second part.
+///
+/// [`Ipv4Repr::parse_parts`]: struct.Ipv4Repr.html#method.parse_parts
+pub trait PayloadParts {
+    /// Get the two consecutive parts of the packet.
+    fn payload_parts(&self) -> (&payload, &payload);
+
+    /// The combined length of both parts.
+    fn parts_len(&self) -> usize {
+        let (first, second) = self.payload_parts();
+        first.len() + second.len()
+    }
+
+    /// Get a prefix of the packet, if it is contained in the first part.
+    fn contiguous(&self, length: usize) -> Option<&payload> {
+        let (first, _) = self.payload_parts();
+        first.get(..length).map(Into::into)
+    }
+}
+
+/// The mutable variant of `PayloadParts`.
+pub trait PayloadPartsMut: PayloadParts {
+    /// Get the two consecutive, mutable parts of the packet.
+    fn payload_parts_mut(&mut self) -> (&mut payload, &mut payload);
 }
 
 /// Groups parameters and utilities for payload reframing.
@@ -111,6 +236,74 @@ impl Reframe {
     }
 }
 
+/// A payload buffer with a reserve in front of its content.
+///
+/// Wraps another buffer of which only a suffix forms the payload. The bytes before it are the
+/// headroom into which headers can be prepended without moving the content, for example when an
+/// upper layer encapsulates a prepared packet into a tunnel or inserts a VLAN tag.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Headroom<P> {
+    inner: P,
+    start: usize,
+}
+
+impl<P: PayloadMut> Headroom<P> {
+    /// Wrap a buffer, initially without any headroom.
+    pub fn new(inner: P) -> Self {
+        Headroom {
+            inner,
+            start: 0,
+        }
+    }
+
+    /// Unwrap the buffer, including the bytes of the headroom.
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+}
+
+/// A packet in a ring buffer, possibly wrapping around at its end.
+///
+/// This is the most simple implementation of `PayloadParts`, borrowing the two parts from the
+/// memory of the ring.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct RingSlice<'a> {
+    first: &'a mut [u8],
+    second: &'a mut [u8],
+}
+
+impl<'a> RingSlice<'a> {
+    /// Wrap the two parts of a packet.
+    pub fn new(first: &'a mut [u8], second: &'a mut [u8]) -> Self {
+        RingSlice {
+            first,
+            second,
+        }
+    }
+
+    /// Borrow a packet of some length starting at an offset into a ring.
+    ///
+    /// Returns `None` if the offset is not within the ring or the ring is shorter than the packet.
+    pub fn from_ring(ring: &'a mut [u8], start: usize, length: usize) -> Option<Self> {
+        if start >= ring.len() || length > ring.len() {
+            return None;
+        }
+
+        let (head, tail) = ring.split_at_mut(start);
+        Some(if length <= tail.len() {
+            RingSlice::new(&mut tail[..length], &mut [])
+        } else {
+            let wrapped = length - tail.len();
+            RingSlice::new(tail, &mut head[..wrapped])
+        })
+    }
+
+    /// Unwrap the two parts.
+    pub fn into_inner(self) -> (&'a mut [u8], &'a mut [u8]) {
+        (self.first, self.second)
+    }
+}
+
 impl<'a> From<&'a [u8]> for &'a payload {
     fn from(val: &'a [u8]) -> &'a payload {
         payload::__from_macro_new_unchecked(val)
@@ -229,6 +422,107 @@ impl<P: PayloadMut + ?Sized> PayloadMut for &'_ mut P {
     fn reframe(&mut self, reframe: Reframe) -> Result<(), Error> {
         (**self).reframe(reframe)
     }
+
+    fn headroom(&self) -> usize {
+        (**self).headroom()
+    }
+
+    fn set_headroom(&mut self, headroom: usize) -> Result<(), Error> {
+        (**self).set_headroom(headroom)
+    }
+
+    fn prepend(&mut self, length: usize) -> Result<(), Error> {
+        (**self).prepend(length)
+    }
+
+    fn trim_front(&mut self, length: usize) -> Result<(), Error> {
+        (**self).trim_front(length)
+    }
+}
+
+impl<P: PayloadVectored + ?Sized> PayloadVectored for &'_ mut P {
+    fn segment_count(&self) -> usize {
+        (**self).segment_count()
+    }
+
+    fn segment(&self, idx: usize) -> Option<&payload> {
+        (**self).segment(idx)
+    }
+
+    fn segment_mut(&mut self, idx: usize) -> Option<&mut payload> {
+        (**self).segment_mut(idx)
+    }
+
+    fn push_segment(&mut self, length: usize) -> Result<(), Error> {
+        (**self).push_segment(length)
+    }
+
+    fn clear_segments(&mut self) {
+        (**self).clear_segments()
+    }
+}
+
+impl<P: Payload> Payload for Headroom<P> {
+    fn payload(&self) -> &payload {
+        self.inner.payload().as_slice()[self.start..].into()
+    }
+}
+
+impl<P: PayloadMut> PayloadMut for Headroom<P> {
+    fn payload_mut(&mut self) -> &mut payload {
+        let start = self.start;
+        (&mut self.inner.payload_mut().as_mut_slice()[start..]).into()
+    }
+
+    fn resize(&mut self, length: usize) -> Result<(), Error> {
+        self.inner.resize(self.start + length)
+    }
+
+    fn reframe(&mut self, reframe: Reframe) -> Result<(), Error> {
+        let start = self.start;
+        self.inner.reframe(Reframe {
+            length: start + reframe.length,
+            range: start + reframe.range.start..start + reframe.range.end,
+        })
+    }
+
+    fn headroom(&self) -> usize {
+        self.start
+    }
+
+    fn set_headroom(&mut self, headroom: usize) -> Result<(), Error> {
+        let length = self.payload().len();
+        self.inner.resize(headroom + length)?;
+        self.start = headroom;
+        Ok(())
+    }
+
+    fn prepend(&mut self, length: usize) -> Result<(), Error> {
+        if length <= self.start {
+            self.start -= length;
+            return Ok(());
+        }
+
+        // Use up all of the headroom and move the content by the rest.
+        let start = self.start;
+        let current = self.inner.payload().len();
+        let missing = length - start;
+        self.inner.reframe(Reframe {
+            length: current + missing,
+            range: start..current,
+        })?;
+        self.inner.payload_mut().copy_within(start..current, length);
+        self.start = 0;
+        Ok(())
+    }
+
+    fn trim_front(&mut self, length: usize) -> Result<(), Error> {
+        if length > self.payload().len() {
+            return Err(Error::BadSize);
+        }
+        self.start += length;
+        Ok(())
+    }
 }
 
 impl Payload for Slice<'_, u8> {
@@ -283,6 +577,30 @@ impl PayloadMut for Slice<'_, u8> {
     }
 }
 
+impl<P: Payload + ?Sized> PayloadParts for P {
+    fn payload_parts(&self) -> (&payload, &payload) {
+        (self.payload(), [][..].into())
+    }
+}
+
+impl<P: PayloadMut + ?Sized> PayloadPartsMut for P {
+    fn payload_parts_mut(&mut self) -> (&mut payload, &mut payload) {
+        (self.payload_mut(), (&mut [][..]).into())
+    }
+}
+
+impl PayloadParts for RingSlice<'_> {
+    fn payload_parts(&self) -> (&payload, &payload) {
+        (self.first.payload(), self.second.payload())
+    }
+}
+
+impl PayloadPartsMut for RingSlice<'_> {
+    fn payload_parts_mut(&mut self) -> (&mut payload, &mut payload) {
+        (self.first.payload_mut(), self.second.payload_mut())
+    }
+}
+
 mod std_impls {
     use crate::alloc::vec::Vec;
     use super::{Error, Reframe, Payload, PayloadMut, payload};
@@ -308,3 +626,171 @@ mod std_impls {
         }
     }
 }
+
+/// Buffers of the `bytes` crate, so they can be handed to devices and layers without a copy.
+///
+/// A `BytesMut` grows at its end like a `Vec` and gives up the front on `trim_front` without
+/// moving the remaining content, but has no headroom to prepend into. A frozen `Bytes` can only
+/// be read.
+#[cfg(feature = "bytes")]
+mod bytes_impls {
+    use bytes::{Buf, Bytes, BytesMut};
+    use super::{Error, Reframe, Payload, PayloadMut, payload};
+
+    impl Payload for Bytes {
+        fn payload(&self) -> &payload {
+            self.as_ref().into()
+        }
+    }
+
+    impl Payload for BytesMut {
+        fn payload(&self) -> &payload {
+            self.as_ref().into()
+        }
+    }
+
+    impl PayloadMut for BytesMut {
+        fn payload_mut(&mut self) -> &mut payload {
+            self.as_mut().into()
+        }
+
+        fn resize(&mut self, length: usize) -> Result<(), Error> {
+            BytesMut::resize(self, length, 0u8);
+            Ok(())
+        }
+
+        fn reframe(&mut self, reframe: Reframe) -> Result<(), Error> {
+            // We always preserve the full prefix.
+            PayloadMut::resize(self, reframe.length)
+        }
+
+        fn trim_front(&mut self, length: usize) -> Result<(), Error> {
+            if length > self.len() {
+                return Err(Error::BadSize);
+            }
+            self.advance(length);
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::wire::{ipv4_packet, Checksum, Ipv4Address, Ipv4Packet, Ipv4Repr, IpProtocol};
+
+        #[test]
+        fn ipv4_in_bytes() {
+            let repr = Ipv4Repr {
+                src_addr: Ipv4Address::new(10, 0, 0, 1),
+                dst_addr: Ipv4Address::new(10, 0, 0, 2),
+                protocol: IpProtocol::Udp,
+                payload_len: 4,
+                hop_limit: 64,
+            };
+
+            let mut buffer = BytesMut::new();
+            PayloadMut::resize(&mut buffer, repr.buffer_len() + 4).unwrap();
+            repr.emit(ipv4_packet::new_unchecked_mut(&mut buffer), Checksum::Manual);
+
+            let frozen = buffer.freeze();
+            let packet = Ipv4Packet::new_checked(frozen, Checksum::Manual).unwrap();
+            assert_eq!(packet.repr(), repr);
+        }
+
+        #[test]
+        fn trim_front() {
+            let mut buffer = BytesMut::from(&b"headerdata"[..]);
+            buffer.trim_front(6).unwrap();
+            assert_eq!(buffer.payload().as_slice(), b"data");
+            assert_eq!(buffer.trim_front(5), Err(Error::BadSize));
+            buffer.prepend(2).unwrap();
+            assert_eq!(&buffer.payload().as_slice()[2..], b"data");
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+mod segmented {
+    use crate::alloc::vec;
+    use crate::alloc::vec::Vec;
+    use super::{Error, Reframe, Payload, PayloadMut, PayloadVectored, payload};
+
+    /// A contiguous buffer followed by separately allocated segments.
+    ///
+    /// This is the most simple implementation of `PayloadVectored`. It is mostly useful for
+    /// testing the scatter-gather paths of layers, or as a building block for devices that
+    /// assemble the frame on their own.
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    pub struct Segmented {
+        head: Vec<u8>,
+        segments: Vec<Vec<u8>>,
+        max_segments: usize,
+    }
+
+    impl Segmented {
+        /// Create a buffer with the given contiguous part and room for some segments.
+        pub fn new(head: Vec<u8>, max_segments: usize) -> Self {
+            Segmented {
+                head,
+                segments: Vec::new(),
+                max_segments,
+            }
+        }
+
+        /// Copy the complete packet into a contiguous vector.
+        pub fn to_vec(&self) -> Vec<u8> {
+            let mut packet = self.head.clone();
+            for segment in &self.segments {
+                packet.extend_from_slice(segment);
+            }
+            packet
+        }
+    }
+
+    impl Payload for Segmented {
+        fn payload(&self) -> &payload {
+            self.head.payload()
+        }
+    }
+
+    impl PayloadMut for Segmented {
+        fn payload_mut(&mut self) -> &mut payload {
+            self.head.payload_mut()
+        }
+
+        fn resize(&mut self, length: usize) -> Result<(), Error> {
+            PayloadMut::resize(&mut self.head, length)
+        }
+
+        fn reframe(&mut self, reframe: Reframe) -> Result<(), Error> {
+            self.head.reframe(reframe)
+        }
+    }
+
+    impl PayloadVectored for Segmented {
+        fn segment_count(&self) -> usize {
+            self.segments.len()
+        }
+
+        fn segment(&self, idx: usize) -> Option<&payload> {
+            self.segments.get(idx).map(|segment| segment.payload())
+        }
+
+        fn segment_mut(&mut self, idx: usize) -> Option<&mut payload> {
+            self.segments.get_mut(idx).map(|segment| segment.payload_mut())
+        }
+
+        fn push_segment(&mut self, length: usize) -> Result<(), Error> {
+            if self.segments.len() >= self.max_segments {
+                return Err(Error::BadSize);
+            }
+
+            self.segments.push(vec![0; length]);
+            Ok(())
+        }
+
+        fn clear_segments(&mut self) {
+            self.segments.clear()
+        }
+    }
+}
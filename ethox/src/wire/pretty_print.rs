@@ -129,3 +129,64 @@ impl<T: PrettyPrint + ?Sized> Default for Formatter<T> {
         Formatter { _inner: PhantomData::default() } 
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::wire::{PrettyPrinter, ethernet_frame};
+
+    static UDP_FRAME_BYTES: [u8; 46] =
+        [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x11, 0x12,
+         0x13, 0x14, 0x15, 0x16, 0x08, 0x00, 0x45, 0x00,
+         0x00, 0x20, 0x00, 0x00, 0x40, 0x00, 0x40, 0x11,
+         0xb6, 0xc9, 0xc0, 0x00, 0x02, 0x01, 0xc0, 0x00,
+         0x02, 0x02, 0xbf, 0x00, 0x00, 0x35, 0x00, 0x0c,
+         0x11, 0x9d, 0xaa, 0x00, 0x00, 0xff];
+
+    static TCP_FRAME_BYTES: [u8; 54] =
+        [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x11, 0x12,
+         0x13, 0x14, 0x15, 0x16, 0x08, 0x00, 0x45, 0x00,
+         0x00, 0x28, 0x00, 0x00, 0x40, 0x00, 0x40, 0x06,
+         0xb6, 0xcc, 0xc0, 0x00, 0x02, 0x01, 0xc0, 0x00,
+         0x02, 0x02, 0x9c, 0x40, 0x00, 0x50, 0x01, 0x02,
+         0x03, 0x04, 0x00, 0x00, 0x00, 0x00, 0x50, 0x02,
+         0x10, 0x00, 0x7b, 0x48, 0x00, 0x00];
+
+    static ICMP_FRAME_BYTES: [u8; 70] =
+        [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x11, 0x12,
+         0x13, 0x14, 0x15, 0x16, 0x08, 0x00, 0x45, 0x00,
+         0x00, 0x38, 0x00, 0x00, 0x40, 0x00, 0x40, 0x01,
+         0xb6, 0xc1, 0xc0, 0x00, 0x02, 0x01, 0xc0, 0x00,
+         0x02, 0x02, 0x03, 0x03, 0x2c, 0x1e, 0x00, 0x00,
+         0x00, 0x00, 0x45, 0x00, 0x00, 0x20, 0x00, 0x00,
+         0x40, 0x00, 0x40, 0x11, 0xb6, 0xc9, 0xc0, 0x00,
+         0x02, 0x02, 0xc0, 0x00, 0x02, 0x01, 0x00, 0x35,
+         0xbf, 0x00, 0x00, 0x0c, 0x11, 0x9d];
+
+    fn listing(frame: &[u8]) -> String {
+        format!("{}", PrettyPrinter::<ethernet_frame>::new("", frame))
+    }
+
+    #[test]
+    fn nested_udp() {
+        assert_eq!(listing(&UDP_FRAME_BYTES), concat!(
+            "EthernetII src=11-12-13-14-15-16 dst=01-02-03-04-05-06 type=IPv4\n\\ ",
+            "IPv4 src=192.0.2.1 dst=192.0.2.2 proto=UDP\n \\ UDP src=48896 dst=53 len=4"));
+    }
+
+    #[test]
+    fn nested_tcp() {
+        assert_eq!(listing(&TCP_FRAME_BYTES), concat!(
+            "EthernetII src=11-12-13-14-15-16 dst=01-02-03-04-05-06 type=IPv4\n\\ ",
+            "IPv4 src=192.0.2.1 dst=192.0.2.2 proto=TCP\n \\ TCP src=40000 dst=80 flags=syn ",
+            "seq=16909060 win=4096 len=0"));
+    }
+
+    #[test]
+    fn nested_icmp() {
+        assert_eq!(listing(&ICMP_FRAME_BYTES), concat!(
+            "EthernetII src=11-12-13-14-15-16 dst=01-02-03-04-05-06 type=IPv4\n\\ ",
+            "IPv4 src=192.0.2.1 dst=192.0.2.2 proto=ICMP\n \\ ",
+            "ICMPv4 destination unreachable (destination port unreachable)\n  \\ ",
+            "IPv4 src=192.0.2.2 dst=192.0.2.1 proto=UDP"));
+    }
+}
@@ -0,0 +1,641 @@
+//! Messages of the precision time protocol, PTPv2 as in IEEE 1588-2008.
+//!
+//! The messages are carried directly in ethernet frames or in udp datagrams. Event messages are
+//! timestamped by the hardware when sent and received, see [`Timestamping`], and are sent to
+//! their own udp port. Management and signaling messages as well as the TLVs of announce
+//! messages are not supported.
+//!
+//! [`Timestamping`]: ../nic/struct.Timestamping.html
+use core::fmt;
+use byteorder::{ByteOrder, NetworkEndian};
+
+use super::{EthernetAddress, Error, Ipv4Address, Result};
+
+/// The udp port of event messages.
+pub const EVENT_PORT: u16 = 319;
+
+/// The udp port of general messages.
+pub const GENERAL_PORT: u16 = 320;
+
+/// The multicast group of all messages except peer delay messages, over IPv4.
+pub const PRIMARY_IPV4: Ipv4Address = Ipv4Address([224, 0, 1, 129]);
+
+/// The multicast address of all messages except peer delay messages, over ethernet.
+pub const PRIMARY_ETHERNET: EthernetAddress = EthernetAddress([0x01, 0x1b, 0x19, 0, 0, 0]);
+
+/// The multicast address of peer delay messages, over ethernet.
+pub const PDELAY_ETHERNET: EthernetAddress = EthernetAddress([0x01, 0x80, 0xc2, 0, 0, 0x0e]);
+
+enum_with_unknown! {
+    /// The type of a PTP message.
+    pub doc enum MessageType(u8) {
+        /// Sync, an event message
+        Sync = 0x0,
+        /// Delay request, an event message
+        DelayReq = 0x1,
+        /// Peer delay request, an event message
+        PdelayReq = 0x2,
+        /// Peer delay response, an event message
+        PdelayResp = 0x3,
+        /// Follow up
+        FollowUp = 0x8,
+        /// Delay response
+        DelayResp = 0x9,
+        /// Peer delay response follow up
+        PdelayRespFollowUp = 0xa,
+        /// Announce
+        Announce = 0xb,
+        /// Signaling
+        Signaling = 0xc,
+        /// Management
+        Management = 0xd,
+    }
+}
+
+/// A timestamp in the PTP format.
+///
+/// Seconds are 48 bits wide on the wire, the time is usually in the TAI timescale.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    /// Whole seconds, of which only the lower 48 bits are transmitted.
+    pub seconds: u64,
+    /// Nanoseconds within the second.
+    pub nanos: u32,
+}
+
+/// The identity of a port of a PTP clock.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PortIdentity {
+    /// The identity of the clock, usually derived from an ethernet address.
+    pub clock: [u8; 8],
+    /// The number of the port, starting at 1.
+    pub port: u16,
+}
+
+byte_wrapper! {
+    /// A byte slice containing a potential PTP message.
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct ptp([u8]);
+}
+
+// Format of the common header of PTP messages
+//
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// | Trsp  |  Type | Rsvd  |Version|         Message length        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     Domain    |   Reserved    |             Flags             |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                       Correction (64)                         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                           Reserved                            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                    Source port identity (80)                  |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |          Sequence id          |    Control    | Log interval  |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// The header is followed by a timestamp (80 bits) in all supported messages, and by a requesting
+// port identity in responses. See IEEE 1588-2008 section 13.3 for details.
+mod field {
+    use crate::wire::field::Field;
+
+    pub(crate) const TYPE:        usize = 0;
+    pub(crate) const VERSION:     usize = 1;
+    pub(crate) const LENGTH:      Field = 2..4;
+    pub(crate) const DOMAIN:      usize = 4;
+    pub(crate) const FLAGS:       Field = 6..8;
+    pub(crate) const CORRECTION:  Field = 8..16;
+    pub(crate) const SOURCE_PORT: Field = 20..30;
+    pub(crate) const SEQUENCE_ID: Field = 30..32;
+    pub(crate) const CONTROL:     usize = 32;
+    pub(crate) const INTERVAL:    usize = 33;
+    pub(crate) const TIMESTAMP:   Field = 34..44;
+    pub(crate) const REQUESTING:  Field = 44..54;
+}
+
+/// The length of the common header.
+pub const HEADER_LEN: usize = field::TIMESTAMP.start;
+
+impl MessageType {
+    /// Check if messages of this type are timestamped on transmission and reception.
+    pub fn is_event(self) -> bool {
+        matches!(self, MessageType::Sync | MessageType::DelayReq
+            | MessageType::PdelayReq | MessageType::PdelayResp)
+    }
+
+    /// The length of a message of this type, for the supported types.
+    pub fn message_len(self) -> Option<usize> {
+        match self {
+            MessageType::Sync | MessageType::DelayReq | MessageType::FollowUp
+                => Some(field::TIMESTAMP.end),
+            MessageType::PdelayReq | MessageType::PdelayResp | MessageType::DelayResp
+            | MessageType::PdelayRespFollowUp
+                => Some(field::REQUESTING.end),
+            _ => None,
+        }
+    }
+
+    /// The value of the deprecated control field, IEEE 1588-2008 table 23.
+    fn control(self) -> u8 {
+        match self {
+            MessageType::Sync => 0,
+            MessageType::DelayReq => 1,
+            MessageType::FollowUp => 2,
+            MessageType::DelayResp => 3,
+            MessageType::Management => 4,
+            _ => 5,
+        }
+    }
+}
+
+impl Timestamp {
+    const NANOS_PER_SEC: i128 = 1_000_000_000;
+
+    /// Parse a timestamp from its 10 byte representation.
+    pub fn parse(bytes: &[u8]) -> Self {
+        Timestamp {
+            seconds: NetworkEndian::read_u48(&bytes[..6]),
+            nanos: NetworkEndian::read_u32(&bytes[6..10]),
+        }
+    }
+
+    /// Emit the 10 byte representation of the timestamp.
+    pub fn emit(&self, bytes: &mut [u8]) {
+        NetworkEndian::write_u48(&mut bytes[..6], self.seconds & 0xffff_ffff_ffff);
+        NetworkEndian::write_u32(&mut bytes[6..10], self.nanos);
+    }
+
+    /// The total number of nanoseconds.
+    pub fn total_nanos(&self) -> i128 {
+        i128::from(self.seconds) * Self::NANOS_PER_SEC + i128::from(self.nanos)
+    }
+
+    /// The signed difference to an earlier timestamp, in nanoseconds.
+    pub fn nanos_since(&self, earlier: Timestamp) -> i128 {
+        self.total_nanos() - earlier.total_nanos()
+    }
+}
+
+impl PortIdentity {
+    /// Parse a port identity from its 10 byte representation.
+    pub fn parse(bytes: &[u8]) -> Self {
+        let mut clock = [0; 8];
+        clock.copy_from_slice(&bytes[..8]);
+        PortIdentity {
+            clock,
+            port: NetworkEndian::read_u16(&bytes[8..10]),
+        }
+    }
+
+    /// Emit the 10 byte representation of the port identity.
+    pub fn emit(&self, bytes: &mut [u8]) {
+        bytes[..8].copy_from_slice(&self.clock);
+        NetworkEndian::write_u16(&mut bytes[8..10], self.port);
+    }
+
+    /// The identity of a port on a clock identified by an ethernet address, IEEE 1588-2008 7.5.2.2.
+    pub fn from_ethernet(addr: EthernetAddress, port: u16) -> Self {
+        let [a, b, c, d, e, f] = addr.0;
+        PortIdentity {
+            clock: [a, b, c, 0xff, 0xfe, d, e, f],
+            port,
+        }
+    }
+}
+
+impl ptp {
+    /// Imbue a raw octet buffer with PTP message structure.
+    pub fn new_unchecked(data: &[u8]) -> &Self {
+        Self::__from_macro_new_unchecked(data)
+    }
+
+    /// Imbue a mutable octet buffer with PTP message structure.
+    pub fn new_unchecked_mut(data: &mut [u8]) -> &mut Self {
+        Self::__from_macro_new_unchecked_mut(data)
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(data: &[u8]) -> Result<&Self> {
+        let packet = Self::new_unchecked(data);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// View the message as a raw byte slice.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// View the message as a mutable raw byte slice.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    ///
+    /// Returns `Err(Error::Truncated)` if the buffer is shorter than the header or the length
+    /// required by the message type, and `Err(Error::Malformed)` if the message length field is
+    /// smaller than either.
+    pub fn check_len(&self) -> Result<()> {
+        if self.0.len() < HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+
+        let required = self.msg_type().message_len().unwrap_or(HEADER_LEN);
+        let length = usize::from(self.message_len());
+        if length < required {
+            Err(Error::Malformed)
+        } else if self.0.len() < length {
+            Err(Error::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn transport_specific(&self) -> u8 {
+        self.0[field::TYPE] >> 4
+    }
+
+    pub fn msg_type(&self) -> MessageType {
+        MessageType::from(self.0[field::TYPE] & 0xf)
+    }
+
+    pub fn version(&self) -> u8 {
+        self.0[field::VERSION] & 0xf
+    }
+
+    /// The length of the message including the header.
+    pub fn message_len(&self) -> u16 {
+        NetworkEndian::read_u16(&self.0[field::LENGTH])
+    }
+
+    pub fn domain(&self) -> u8 {
+        self.0[field::DOMAIN]
+    }
+
+    pub fn flags(&self) -> u16 {
+        NetworkEndian::read_u16(&self.0[field::FLAGS])
+    }
+
+    /// The correction of the timestamps, in nanoseconds multiplied by 2^16.
+    pub fn correction(&self) -> i64 {
+        NetworkEndian::read_i64(&self.0[field::CORRECTION])
+    }
+
+    pub fn source_port(&self) -> PortIdentity {
+        PortIdentity::parse(&self.0[field::SOURCE_PORT])
+    }
+
+    pub fn sequence_id(&self) -> u16 {
+        NetworkEndian::read_u16(&self.0[field::SEQUENCE_ID])
+    }
+
+    pub fn control(&self) -> u8 {
+        self.0[field::CONTROL]
+    }
+
+    /// The logarithm to base 2 of the interval between messages, in seconds.
+    pub fn log_message_interval(&self) -> i8 {
+        self.0[field::INTERVAL] as i8
+    }
+
+    /// The origin, receive or response timestamp, depending on the message type.
+    ///
+    /// # Panics
+    /// This function may panic if the message is too short for a timestamp.
+    pub fn timestamp(&self) -> Timestamp {
+        Timestamp::parse(&self.0[field::TIMESTAMP])
+    }
+
+    /// The port identity of the request that a response answers.
+    ///
+    /// # Panics
+    /// This function may panic if the message is too short for a port identity.
+    pub fn requesting_port(&self) -> PortIdentity {
+        PortIdentity::parse(&self.0[field::REQUESTING])
+    }
+
+    pub fn set_transport_specific(&mut self, value: u8) {
+        let raw = self.0[field::TYPE] & 0x0f;
+        self.0[field::TYPE] = raw | value << 4;
+    }
+
+    pub fn set_msg_type(&mut self, value: MessageType) {
+        let raw = self.0[field::TYPE] & 0xf0;
+        self.0[field::TYPE] = raw | (u8::from(value) & 0xf);
+    }
+
+    pub fn set_version(&mut self, value: u8) {
+        self.0[field::VERSION] = value & 0xf;
+    }
+
+    pub fn set_message_len(&mut self, value: u16) {
+        NetworkEndian::write_u16(&mut self.0[field::LENGTH], value)
+    }
+
+    pub fn set_domain(&mut self, value: u8) {
+        self.0[field::DOMAIN] = value;
+    }
+
+    pub fn set_flags(&mut self, value: u16) {
+        NetworkEndian::write_u16(&mut self.0[field::FLAGS], value)
+    }
+
+    pub fn set_correction(&mut self, value: i64) {
+        NetworkEndian::write_i64(&mut self.0[field::CORRECTION], value)
+    }
+
+    pub fn set_source_port(&mut self, value: PortIdentity) {
+        value.emit(&mut self.0[field::SOURCE_PORT])
+    }
+
+    pub fn set_sequence_id(&mut self, value: u16) {
+        NetworkEndian::write_u16(&mut self.0[field::SEQUENCE_ID], value)
+    }
+
+    pub fn set_control(&mut self, value: u8) {
+        self.0[field::CONTROL] = value;
+    }
+
+    pub fn set_log_message_interval(&mut self, value: i8) {
+        self.0[field::INTERVAL] = value as u8;
+    }
+
+    pub fn set_timestamp(&mut self, value: Timestamp) {
+        value.emit(&mut self.0[field::TIMESTAMP])
+    }
+
+    pub fn set_requesting_port(&mut self, value: PortIdentity) {
+        value.emit(&mut self.0[field::REQUESTING])
+    }
+
+    /// Clear the reserved fields of the header.
+    pub fn clear_reserved(&mut self) {
+        self.0[field::VERSION] &= 0xf;
+        self.0[field::DOMAIN + 1] = 0;
+        for byte in &mut self.0[field::CORRECTION.end..field::SOURCE_PORT.start] {
+            *byte = 0;
+        }
+    }
+}
+
+impl AsRef<[u8]> for ptp {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl AsMut<[u8]> for ptp {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.as_bytes_mut()
+    }
+}
+
+/// The body of a supported PTP message.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Message {
+    /// A sync message of a master, with the approximate or exact origin timestamp.
+    Sync {
+        origin: Timestamp,
+    },
+    /// A delay request of a slave.
+    DelayReq {
+        origin: Timestamp,
+    },
+    /// A peer delay request.
+    PdelayReq {
+        origin: Timestamp,
+    },
+    /// A peer delay response, with the time the request was received.
+    PdelayResp {
+        request_receipt: Timestamp,
+        requesting_port: PortIdentity,
+    },
+    /// The precise origin timestamp of a preceding sync message.
+    FollowUp {
+        precise_origin: Timestamp,
+    },
+    /// The time at which the master received a delay request.
+    DelayResp {
+        receive: Timestamp,
+        requesting_port: PortIdentity,
+    },
+    /// The precise origin timestamp of a preceding peer delay response.
+    PdelayRespFollowUp {
+        response_origin: Timestamp,
+        requesting_port: PortIdentity,
+    },
+}
+
+/// A high-level representation of a PTP message.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Repr {
+    pub message: Message,
+    pub domain: u8,
+    pub flags: u16,
+    pub correction: i64,
+    pub source_port: PortIdentity,
+    pub sequence_id: u16,
+    pub log_message_interval: i8,
+}
+
+impl Message {
+    /// The type of the message.
+    pub fn msg_type(&self) -> MessageType {
+        match self {
+            Message::Sync { .. } => MessageType::Sync,
+            Message::DelayReq { .. } => MessageType::DelayReq,
+            Message::PdelayReq { .. } => MessageType::PdelayReq,
+            Message::PdelayResp { .. } => MessageType::PdelayResp,
+            Message::FollowUp { .. } => MessageType::FollowUp,
+            Message::DelayResp { .. } => MessageType::DelayResp,
+            Message::PdelayRespFollowUp { .. } => MessageType::PdelayRespFollowUp,
+        }
+    }
+
+    fn timestamp(&self) -> Timestamp {
+        match *self {
+            Message::Sync { origin }
+            | Message::DelayReq { origin }
+            | Message::PdelayReq { origin } => origin,
+            Message::PdelayResp { request_receipt, .. } => request_receipt,
+            Message::FollowUp { precise_origin } => precise_origin,
+            Message::DelayResp { receive, .. } => receive,
+            Message::PdelayRespFollowUp { response_origin, .. } => response_origin,
+        }
+    }
+
+    fn requesting_port(&self) -> Option<PortIdentity> {
+        match *self {
+            Message::PdelayResp { requesting_port, .. }
+            | Message::DelayResp { requesting_port, .. }
+            | Message::PdelayRespFollowUp { requesting_port, .. } => Some(requesting_port),
+            _ => None,
+        }
+    }
+}
+
+impl Repr {
+    /// The version of the protocol.
+    pub const VERSION: u8 = 2;
+
+    /// Parse a PTP message and return a high-level representation.
+    ///
+    /// Returns `Err(Error::Unrecognized)` for message types without a representation.
+    pub fn parse(packet: &ptp) -> Result<Repr> {
+        packet.check_len()?;
+
+        if packet.version() != Self::VERSION {
+            return Err(Error::Unrecognized);
+        }
+
+        let timestamp = || packet.timestamp();
+        let requesting_port = || packet.requesting_port();
+        let message = match packet.msg_type() {
+            MessageType::Sync => Message::Sync { origin: timestamp() },
+            MessageType::DelayReq => Message::DelayReq { origin: timestamp() },
+            MessageType::PdelayReq => Message::PdelayReq { origin: timestamp() },
+            MessageType::PdelayResp => Message::PdelayResp {
+                request_receipt: timestamp(),
+                requesting_port: requesting_port(),
+            },
+            MessageType::FollowUp => Message::FollowUp { precise_origin: timestamp() },
+            MessageType::DelayResp => Message::DelayResp {
+                receive: timestamp(),
+                requesting_port: requesting_port(),
+            },
+            MessageType::PdelayRespFollowUp => Message::PdelayRespFollowUp {
+                response_origin: timestamp(),
+                requesting_port: requesting_port(),
+            },
+            _ => return Err(Error::Unrecognized),
+        };
+
+        Ok(Repr {
+            message,
+            domain: packet.domain(),
+            flags: packet.flags(),
+            correction: packet.correction(),
+            source_port: packet.source_port(),
+            sequence_id: packet.sequence_id(),
+            log_message_interval: packet.log_message_interval(),
+        })
+    }
+
+    /// Return the length of the message that will be emitted from this high-level representation.
+    pub fn buffer_len(&self) -> usize {
+        self.message.msg_type()
+            .message_len()
+            .expect("All represented messages have a fixed length")
+    }
+
+    /// Emit a high-level representation into a PTP message.
+    pub fn emit(&self, packet: &mut ptp) {
+        let msg_type = self.message.msg_type();
+        packet.clear_reserved();
+        packet.set_transport_specific(0);
+        packet.set_msg_type(msg_type);
+        packet.set_version(Self::VERSION);
+        packet.set_message_len(self.buffer_len() as u16);
+        packet.set_domain(self.domain);
+        packet.set_flags(self.flags);
+        packet.set_correction(self.correction);
+        packet.set_source_port(self.source_port);
+        packet.set_sequence_id(self.sequence_id);
+        packet.set_control(msg_type.control());
+        packet.set_log_message_interval(self.log_message_interval);
+        packet.set_timestamp(self.message.timestamp());
+        if let Some(requesting_port) = self.message.requesting_port() {
+            packet.set_requesting_port(requesting_port);
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{:09}", self.seconds, self.nanos)
+    }
+}
+
+impl fmt::Display for Repr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PTPv2 {:?} domain={} seq={} ts={}",
+            self.message.msg_type(), self.domain, self.sequence_id, self.message.timestamp())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static SYNC_BYTES: [u8; 44] = [
+        0x00, 0x02, 0x00, 0x2c,
+        0x00, 0x00, 0x02, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        0x52, 0x54, 0x00, 0xff, 0xfe, 0x00, 0x00, 0x01, 0x00, 0x01,
+        0x00, 0x2a, 0x00, 0x00,
+        0x00, 0x00, 0x65, 0x53, 0xf1, 0x00, 0x00, 0x00, 0x01, 0xf4,
+    ];
+
+    fn sync_repr() -> Repr {
+        Repr {
+            message: Message::Sync {
+                origin: Timestamp { seconds: 1_700_000_000, nanos: 500 },
+            },
+            domain: 0,
+            flags: 0x0200,
+            correction: 1 << 16,
+            source_port: PortIdentity::from_ethernet(
+                EthernetAddress([0x52, 0x54, 0, 0, 0, 1]), 1),
+            sequence_id: 42,
+            log_message_interval: 0,
+        }
+    }
+
+    #[test]
+    fn test_deconstruct() {
+        let packet = ptp::new_checked(&SYNC_BYTES[..]).unwrap();
+        assert_eq!(packet.msg_type(), MessageType::Sync);
+        assert!(packet.msg_type().is_event());
+        assert_eq!(packet.version(), 2);
+        assert_eq!(packet.sequence_id(), 42);
+        assert_eq!(Repr::parse(packet), Ok(sync_repr()));
+    }
+
+    #[test]
+    fn test_construct() {
+        let mut bytes = [0xa5; 44];
+        let packet = ptp::new_unchecked_mut(&mut bytes[..]);
+        sync_repr().emit(packet);
+        assert_eq!(&bytes[..], &SYNC_BYTES[..]);
+    }
+
+    #[test]
+    fn test_delay_resp() {
+        let repr = Repr {
+            message: Message::DelayResp {
+                receive: Timestamp { seconds: 1, nanos: 2 },
+                requesting_port: PortIdentity { clock: [7; 8], port: 3 },
+            },
+            ..sync_repr()
+        };
+        let mut bytes = [0; 54];
+        assert_eq!(repr.buffer_len(), bytes.len());
+        repr.emit(ptp::new_unchecked_mut(&mut bytes[..]));
+        let packet = ptp::new_checked(&bytes[..]).unwrap();
+        assert!(!packet.msg_type().is_event());
+        assert_eq!(Repr::parse(packet), Ok(repr));
+    }
+
+    #[test]
+    fn test_truncated() {
+        assert_eq!(ptp::new_checked(&SYNC_BYTES[..33]), Err(Error::Truncated));
+        assert_eq!(ptp::new_checked(&SYNC_BYTES[..43]), Err(Error::Truncated));
+    }
+}
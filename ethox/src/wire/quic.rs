@@ -0,0 +1,182 @@
+//! The version independent header fields of QUIC packets, rfc8999.
+//!
+//! Only the connection IDs are parsed, which suffices to route datagrams to the connection they
+//! belong to. Everything after them is protected and left to a QUIC implementation.
+use core::fmt;
+use byteorder::{ByteOrder, NetworkEndian};
+
+use super::{Error, Result};
+
+/// The longest connection ID of QUIC version 1, rfc9000 section 17.2.
+pub const MAX_CID_LEN: usize = 20;
+
+/// The bit of the first byte distinguishing long from short headers.
+const LONG_HEADER: u8 = 0x80;
+
+/// A connection ID of at most 20 bytes.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConnectionId {
+    len: u8,
+    bytes: [u8; MAX_CID_LEN],
+}
+
+/// The invariant header of a QUIC packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Header {
+    /// A long header, used while establishing a connection and for version negotiation.
+    Long {
+        /// The version of the packet, `0` for version negotiation.
+        version: u32,
+        /// The connection ID chosen by the receiver.
+        dst: ConnectionId,
+        /// The connection ID chosen by the sender.
+        src: ConnectionId,
+    },
+    /// A short header, used after the connection has been established.
+    Short {
+        /// The connection ID chosen by the receiver.
+        dst: ConnectionId,
+    },
+}
+
+impl ConnectionId {
+    /// The connection ID of length zero.
+    pub const EMPTY: ConnectionId = ConnectionId { len: 0, bytes: [0; MAX_CID_LEN] };
+
+    /// Create a connection ID from its bytes, if it is not longer than 20 bytes.
+    pub fn new(id: &[u8]) -> Option<Self> {
+        if id.len() > MAX_CID_LEN {
+            return None;
+        }
+
+        let mut bytes = [0; MAX_CID_LEN];
+        bytes[..id.len()].copy_from_slice(id);
+        Some(ConnectionId { len: id.len() as u8, bytes })
+    }
+
+    /// The bytes of the connection ID.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len()]
+    }
+
+    /// The length of the connection ID.
+    pub fn len(&self) -> usize {
+        usize::from(self.len)
+    }
+
+    /// Check if this is the connection ID of length zero.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Check if the connection ID starts with the bytes of another one.
+    pub fn starts_with(&self, prefix: &ConnectionId) -> bool {
+        self.as_bytes().starts_with(prefix.as_bytes())
+    }
+
+    /// Read a connection ID with a preceding length byte, returning it and the remaining data.
+    fn parse_with_len(data: &[u8]) -> Result<(Self, &[u8])> {
+        let (&len, data) = data.split_first().ok_or(Error::Truncated)?;
+        let len = usize::from(len);
+        if data.len() < len {
+            return Err(Error::Truncated);
+        }
+
+        let id = ConnectionId::new(&data[..len]).ok_or(Error::Unsupported)?;
+        Ok((id, &data[len..]))
+    }
+}
+
+impl Header {
+    /// Parse the header at the start of a udp payload.
+    ///
+    /// Short headers do not encode the length of the connection ID, which is instead chosen by
+    /// the receiving endpoint for all of its connection IDs. Connection IDs of long headers that
+    /// are longer than permitted by QUIC version 1 are `Unsupported`.
+    pub fn parse(datagram: &[u8], short_len: usize) -> Result<Self> {
+        let first = *datagram.first().ok_or(Error::Truncated)?;
+        if first & LONG_HEADER == 0 {
+            let id = datagram.get(1..1 + short_len).ok_or(Error::Truncated)?;
+            let dst = ConnectionId::new(id).ok_or(Error::Unsupported)?;
+            return Ok(Header::Short { dst });
+        }
+
+        if datagram.len() < 5 {
+            return Err(Error::Truncated);
+        }
+
+        let version = NetworkEndian::read_u32(&datagram[1..5]);
+        let (dst, rest) = ConnectionId::parse_with_len(&datagram[5..])?;
+        let (src, _) = ConnectionId::parse_with_len(rest)?;
+        Ok(Header::Long { version, dst, src })
+    }
+
+    /// The connection ID chosen by the receiver of the packet.
+    pub fn dst(&self) -> &ConnectionId {
+        match self {
+            Header::Long { dst, .. } | Header::Short { dst } => dst,
+        }
+    }
+
+    /// Check if this is a long header.
+    pub fn is_long(&self) -> bool {
+        matches!(self, Header::Long { .. })
+    }
+}
+
+impl fmt::Debug for ConnectionId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ConnectionId({})", self)
+    }
+}
+
+impl fmt::Display for ConnectionId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in self.as_bytes() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static LONG_BYTES: [u8; 16] = [
+        0xc0, 0x00, 0x00, 0x00, 0x01,
+        0x04, 0xde, 0xad, 0xbe, 0xef,
+        0x02, 0x12, 0x34,
+        0x00, 0x41, 0x00,
+    ];
+
+    #[test]
+    fn long_header() {
+        let header = Header::parse(&LONG_BYTES, 8).unwrap();
+        assert!(header.is_long());
+        assert_eq!(header, Header::Long {
+            version: 1,
+            dst: ConnectionId::new(&[0xde, 0xad, 0xbe, 0xef]).unwrap(),
+            src: ConnectionId::new(&[0x12, 0x34]).unwrap(),
+        });
+        assert_eq!(header.dst().to_string(), "deadbeef");
+
+        assert_eq!(Header::parse(&LONG_BYTES[..8], 8), Err(Error::Truncated));
+        let mut long_cid = LONG_BYTES;
+        long_cid[5] = 21;
+        assert_eq!(Header::parse(&long_cid, 8), Err(Error::Truncated));
+    }
+
+    #[test]
+    fn short_header() {
+        let datagram = [0x40, 1, 2, 3, 4, 0xff, 0xff];
+        let header = Header::parse(&datagram, 4).unwrap();
+        assert_eq!(header, Header::Short { dst: ConnectionId::new(&[1, 2, 3, 4]).unwrap() });
+        assert!(header.dst().starts_with(&ConnectionId::new(&[1, 2]).unwrap()));
+        assert!(!header.dst().starts_with(&ConnectionId::new(&[2]).unwrap()));
+
+        assert_eq!(Header::parse(&datagram, 0).unwrap().dst(), &ConnectionId::EMPTY);
+        assert_eq!(Header::parse(&datagram, 8), Err(Error::Truncated));
+        assert_eq!(Header::parse(&[0; 32], 21), Err(Error::Unsupported));
+    }
+}
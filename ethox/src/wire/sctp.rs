@@ -0,0 +1,898 @@
+//! SCTP packets, rfc4960.
+//!
+//! A packet consists of a common header followed by a sequence of chunks. The chunks of the base
+//! protocol are represented by [`Chunk`], while optional parameters and error causes are skipped
+//! when parsing. Gap ack blocks and duplicate TSNs of selective acknowledgments are validated but
+//! not represented, as are the optional parameters of the association setup. Use the byte level
+//! accessors of [`sctp_chunk`] for anything beyond that.
+//!
+//! [`Chunk`]: enum.Chunk.html
+//! [`sctp_chunk`]: struct.sctp_chunk.html
+use byteorder::{ByteOrder, LittleEndian, NetworkEndian};
+
+use super::{Checksum, Error, Result};
+use super::field::Field;
+
+enum_with_unknown! {
+    /// The type of a chunk.
+    pub doc enum ChunkType(u8) {
+        /// Payload data
+        Data = 0,
+        /// Initiation
+        Init = 1,
+        /// Initiation acknowledgment
+        InitAck = 2,
+        /// Selective acknowledgment
+        Sack = 3,
+        /// Heartbeat request
+        Heartbeat = 4,
+        /// Heartbeat acknowledgment
+        HeartbeatAck = 5,
+        /// Abort
+        Abort = 6,
+        /// Shutdown
+        Shutdown = 7,
+        /// Shutdown acknowledgment
+        ShutdownAck = 8,
+        /// Operation error
+        Error = 9,
+        /// State cookie
+        CookieEcho = 10,
+        /// Cookie acknowledgment
+        CookieAck = 11,
+        /// Shutdown complete
+        ShutdownComplete = 14,
+    }
+}
+
+byte_wrapper! {
+    /// A byte slice containing a potential SCTP packet.
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct sctp([u8]);
+}
+
+byte_wrapper! {
+    /// A byte slice containing a single chunk of an SCTP packet.
+    ///
+    /// The slice ends with the value of the chunk, without its padding.
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct sctp_chunk([u8]);
+}
+
+// Format of the SCTP common header
+//
+//  0                   1                   2                   3
+//  0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     Source Port Number        |     Destination Port Number   |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                      Verification Tag                         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                           Checksum                            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |   Chunk Type  | Chunk  Flags  |        Chunk Length           |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// \                          Chunk Value                          /
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+//
+// See https://tools.ietf.org/html/rfc4960#section-3 for details.
+mod field {
+    use crate::wire::field::{Field, Rest};
+
+    pub(crate) const SRC_PORT:         Field = 0..2;
+    pub(crate) const DST_PORT:         Field = 2..4;
+    pub(crate) const VERIFICATION_TAG: Field = 4..8;
+    pub(crate) const CHECKSUM:         Field = 8..12;
+    pub(crate) const CHUNKS:           Rest = 12..;
+
+    pub(crate) const CHUNK_TYPE:   usize = 0;
+    pub(crate) const CHUNK_FLAGS:  usize = 1;
+    pub(crate) const CHUNK_LENGTH: Field = 2..4;
+    pub(crate) const CHUNK_VALUE:  Rest = 4..;
+
+    // Fields of the individual chunks, relative to the start of the chunk.
+    pub(crate) const DATA_TSN:        Field = 4..8;
+    pub(crate) const DATA_STREAM:     Field = 8..10;
+    pub(crate) const DATA_STREAM_SEQ: Field = 10..12;
+    pub(crate) const DATA_PPID:       Field = 12..16;
+    pub(crate) const DATA_PAYLOAD:    Rest = 16..;
+
+    pub(crate) const INIT_TAG:         Field = 4..8;
+    pub(crate) const INIT_A_RWND:      Field = 8..12;
+    pub(crate) const INIT_OUTBOUND:    Field = 12..14;
+    pub(crate) const INIT_INBOUND:     Field = 14..16;
+    pub(crate) const INIT_INITIAL_TSN: Field = 16..20;
+    pub(crate) const INIT_PARAMETERS:  Rest = 20..;
+
+    pub(crate) const SACK_CUMULATIVE_TSN: Field = 4..8;
+    pub(crate) const SACK_A_RWND:         Field = 8..12;
+    pub(crate) const SACK_GAP_BLOCKS:     Field = 12..14;
+    pub(crate) const SACK_DUPLICATES:     Field = 14..16;
+    pub(crate) const SACK_BLOCKS:         Rest = 16..;
+
+    pub(crate) const SHUTDOWN_CUMULATIVE_TSN: Field = 4..8;
+}
+
+mod flags {
+    /// The data chunk is to be delivered unordered.
+    pub(crate) const UNORDERED: u8 = 0x04;
+    /// The data chunk is the first fragment of a message.
+    pub(crate) const BEGIN: u8 = 0x02;
+    /// The data chunk is the last fragment of a message.
+    pub(crate) const END: u8 = 0x01;
+    /// The verification tag of an abort or shutdown complete is reflected.
+    pub(crate) const REFLECTED: u8 = 0x01;
+}
+
+/// The parameter type of the state cookie of an initiation acknowledgment.
+const STATE_COOKIE: u16 = 7;
+
+/// The length of the common header.
+pub const HEADER_LEN: usize = field::CHUNKS.start;
+
+/// The length of the header of each chunk.
+pub const CHUNK_HEADER_LEN: usize = field::CHUNK_VALUE.start;
+
+/// The length of the header of a data chunk, before its user data.
+pub const DATA_HEADER_LEN: usize = field::DATA_PAYLOAD.start;
+
+impl sctp {
+    /// Imbue a raw octet buffer with SCTP packet structure.
+    pub fn new_unchecked(data: &[u8]) -> &Self {
+        Self::__from_macro_new_unchecked(data)
+    }
+
+    /// Imbue a mutable octet buffer with SCTP packet structure.
+    pub fn new_unchecked_mut(data: &mut [u8]) -> &mut Self {
+        Self::__from_macro_new_unchecked_mut(data)
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(data: &[u8]) -> Result<&Self> {
+        let packet = Self::new_unchecked(data);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// View the packet as a raw byte slice.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// View the packet as a mutable raw byte slice.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error::Truncated)` if the buffer is too short.
+    ///
+    /// The chunks are checked only while iterating over them.
+    pub fn check_len(&self) -> Result<()> {
+        if self.0.len() < HEADER_LEN {
+            Err(Error::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn src_port(&self) -> u16 {
+        NetworkEndian::read_u16(&self.0[field::SRC_PORT])
+    }
+
+    pub fn dst_port(&self) -> u16 {
+        NetworkEndian::read_u16(&self.0[field::DST_PORT])
+    }
+
+    pub fn verification_tag(&self) -> u32 {
+        NetworkEndian::read_u32(&self.0[field::VERIFICATION_TAG])
+    }
+
+    /// The CRC32c checksum.
+    ///
+    /// Unlike all other fields, the checksum is transmitted in little endian byte order, see
+    /// rfc3309 and the examples of rfc3720.
+    pub fn checksum(&self) -> u32 {
+        LittleEndian::read_u32(&self.0[field::CHECKSUM])
+    }
+
+    /// Iterate over the chunks of the packet.
+    pub fn chunks(&self) -> Chunks<'_> {
+        Chunks { rest: &self.0[field::CHUNKS] }
+    }
+
+    pub fn set_src_port(&mut self, value: u16) {
+        NetworkEndian::write_u16(&mut self.0[field::SRC_PORT], value)
+    }
+
+    pub fn set_dst_port(&mut self, value: u16) {
+        NetworkEndian::write_u16(&mut self.0[field::DST_PORT], value)
+    }
+
+    pub fn set_verification_tag(&mut self, value: u32) {
+        NetworkEndian::write_u32(&mut self.0[field::VERIFICATION_TAG], value)
+    }
+
+    pub fn set_checksum(&mut self, value: u32) {
+        LittleEndian::write_u32(&mut self.0[field::CHECKSUM], value)
+    }
+
+    /// The bytes following the common header, to be filled with chunks before sending.
+    pub fn chunks_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.0[field::CHUNKS]
+    }
+
+    /// Compute the checksum over the whole packet and fill it in.
+    pub fn fill_checksum(&mut self) {
+        self.set_checksum(0);
+        let checksum = crc32c(&self.0);
+        self.set_checksum(checksum)
+    }
+
+    /// Validate the checksum of the packet.
+    pub fn verify_checksum(&self) -> bool {
+        let (header, rest) = self.0.split_at(field::CHECKSUM.start);
+        let crc = crc32c_update(!0, header);
+        let crc = crc32c_update(crc, &[0; 4]);
+        let crc = crc32c_update(crc, &rest[4..]);
+        !crc == self.checksum()
+    }
+}
+
+impl sctp_chunk {
+    /// Imbue a raw octet buffer with SCTP chunk structure.
+    pub fn new_unchecked(data: &[u8]) -> &Self {
+        Self::__from_macro_new_unchecked(data)
+    }
+
+    /// Imbue a mutable octet buffer with SCTP chunk structure.
+    pub fn new_unchecked_mut(data: &mut [u8]) -> &mut Self {
+        Self::__from_macro_new_unchecked_mut(data)
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(data: &[u8]) -> Result<&Self> {
+        let chunk = Self::new_unchecked(data);
+        chunk.check_len()?;
+        Ok(chunk)
+    }
+
+    /// View the chunk as a raw byte slice.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    ///
+    /// Returns `Err(Error::Truncated)` if the buffer is shorter than the length field, and
+    /// `Err(Error::Malformed)` if the length field is shorter than the chunk header.
+    pub fn check_len(&self) -> Result<()> {
+        if self.0.len() < CHUNK_HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+
+        let length = usize::from(self.length());
+        if length < CHUNK_HEADER_LEN {
+            Err(Error::Malformed)
+        } else if self.0.len() < length {
+            Err(Error::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn chunk_type(&self) -> ChunkType {
+        ChunkType::from(self.0[field::CHUNK_TYPE])
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.0[field::CHUNK_FLAGS]
+    }
+
+    /// The length of the chunk including its header, but without padding.
+    pub fn length(&self) -> u16 {
+        NetworkEndian::read_u16(&self.0[field::CHUNK_LENGTH])
+    }
+
+    /// The value of the chunk, following its header.
+    pub fn value(&self) -> &[u8] {
+        &self.0[field::CHUNK_VALUE.start..usize::from(self.length())]
+    }
+
+    pub fn set_chunk_type(&mut self, value: ChunkType) {
+        self.0[field::CHUNK_TYPE] = value.into()
+    }
+
+    pub fn set_flags(&mut self, value: u8) {
+        self.0[field::CHUNK_FLAGS] = value
+    }
+
+    pub fn set_length(&mut self, value: u16) {
+        NetworkEndian::write_u16(&mut self.0[field::CHUNK_LENGTH], value)
+    }
+
+    fn read_u32(&self, field: Field) -> u32 {
+        NetworkEndian::read_u32(&self.0[field])
+    }
+
+    fn read_u16(&self, field: Field) -> u16 {
+        NetworkEndian::read_u16(&self.0[field])
+    }
+
+    fn write_u32(&mut self, field: Field, value: u32) {
+        NetworkEndian::write_u32(&mut self.0[field], value)
+    }
+
+    fn write_u16(&mut self, field: Field, value: u16) {
+        NetworkEndian::write_u16(&mut self.0[field], value)
+    }
+
+    /// Check that the chunk is long enough for the fixed fields of its type.
+    fn check_value_len(&self, len: usize) -> Result<()> {
+        if usize::from(self.length()) < len {
+            Err(Error::Malformed)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl AsRef<[u8]> for sctp {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl AsMut<[u8]> for sctp {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.as_bytes_mut()
+    }
+}
+
+/// An iterator over the chunks of a packet.
+///
+/// Yields an error for a truncated or malformed chunk and stops afterwards.
+#[derive(Clone, Debug)]
+pub struct Chunks<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = Result<&'a sctp_chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() {
+            return None;
+        }
+
+        let chunk = match sctp_chunk::new_checked(self.rest) {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                self.rest = &[];
+                return Some(Err(err));
+            },
+        };
+
+        let length = usize::from(chunk.length());
+        let chunk = sctp_chunk::new_unchecked(&self.rest[..length]);
+        // The padding of the last chunk may be missing.
+        self.rest = self.rest.get(padded(length)..).unwrap_or(&[]);
+        Some(Ok(chunk))
+    }
+}
+
+/// The parameters of an initiation and its acknowledgment.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct InitParams {
+    /// The verification tag that the receiver must put into all packets of the association.
+    pub initiate_tag: u32,
+    /// The advertised receiver window credit, in bytes.
+    pub a_rwnd: u32,
+    /// The number of streams the sender wants to open.
+    pub outbound_streams: u16,
+    /// The number of streams the sender allows the receiver to open.
+    pub inbound_streams: u16,
+    /// The first transmission sequence number that the sender will use.
+    pub initial_tsn: u32,
+}
+
+/// A high-level representation of the common header of an SCTP packet.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Repr {
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub verification_tag: u32,
+}
+
+/// A high-level representation of a chunk.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Chunk<'a> {
+    /// User data.
+    Data {
+        /// The transmission sequence number of the chunk.
+        tsn: u32,
+        /// The stream to which the data belongs.
+        stream: u16,
+        /// The sequence number of the message within the stream.
+        stream_seq: u16,
+        /// The payload protocol identifier, opaque to SCTP.
+        ppid: u32,
+        /// The message may be delivered out of order.
+        unordered: bool,
+        /// The chunk contains the first fragment of a message.
+        begin: bool,
+        /// The chunk contains the last fragment of a message.
+        end: bool,
+        /// The user data.
+        data: &'a [u8],
+    },
+    /// The initiation of an association.
+    Init(InitParams),
+    /// The acknowledgment of an initiation.
+    InitAck {
+        /// The parameters of the responder.
+        params: InitParams,
+        /// The state cookie that must be echoed by the initiator.
+        cookie: &'a [u8],
+    },
+    /// A selective acknowledgment.
+    Sack {
+        /// The last transmission sequence number received in order.
+        cumulative_tsn: u32,
+        /// The advertised receiver window credit, in bytes.
+        a_rwnd: u32,
+    },
+    /// A request to probe the reachability of the peer.
+    Heartbeat {
+        /// The heartbeat information parameter, returned unchanged in the acknowledgment.
+        info: &'a [u8],
+    },
+    /// The answer to a heartbeat request.
+    HeartbeatAck {
+        /// The heartbeat information parameter of the request.
+        info: &'a [u8],
+    },
+    /// The abort of an association.
+    Abort {
+        /// The packet carries the verification tag of the sender of the packet being answered.
+        reflected: bool,
+    },
+    /// The request of a graceful shutdown.
+    Shutdown {
+        /// The last transmission sequence number received in order.
+        cumulative_tsn: u32,
+    },
+    /// The acknowledgment of a shutdown.
+    ShutdownAck,
+    /// An error that does not terminate the association.
+    Error {
+        /// The error causes, not parsed any further.
+        causes: &'a [u8],
+    },
+    /// The state cookie received in the acknowledgment of an initiation.
+    CookieEcho {
+        /// The state cookie.
+        cookie: &'a [u8],
+    },
+    /// The acknowledgment of the state cookie, completing the association setup.
+    CookieAck,
+    /// The completion of a graceful shutdown.
+    ShutdownComplete {
+        /// The packet carries the verification tag of the sender of the packet being answered.
+        reflected: bool,
+    },
+}
+
+impl Repr {
+    /// Parse the common header of an SCTP packet.
+    ///
+    /// The checksum is verified unless it has been checked or offloaded elsewhere.
+    pub fn parse(packet: &sctp, checksum: Checksum) -> Result<Self> {
+        packet.check_len()?;
+        if checksum.manual() && !packet.verify_checksum() {
+            return Err(Error::WrongChecksum);
+        }
+
+        Ok(Repr {
+            src_port: packet.src_port(),
+            dst_port: packet.dst_port(),
+            verification_tag: packet.verification_tag(),
+        })
+    }
+
+    /// Return the length of the common header.
+    pub fn buffer_len(&self) -> usize {
+        HEADER_LEN
+    }
+
+    /// Emit the common header, with a zero checksum.
+    ///
+    /// Call [`sctp_packet::fill_checksum`] after having emitted all chunks.
+    ///
+    /// [`sctp_packet::fill_checksum`]: struct.sctp_packet.html#method.fill_checksum
+    pub fn emit(&self, packet: &mut sctp) {
+        packet.set_src_port(self.src_port);
+        packet.set_dst_port(self.dst_port);
+        packet.set_verification_tag(self.verification_tag);
+        packet.set_checksum(0);
+    }
+}
+
+impl InitParams {
+    fn parse(chunk: &sctp_chunk) -> Result<Self> {
+        chunk.check_value_len(field::INIT_PARAMETERS.start)?;
+        Ok(InitParams {
+            initiate_tag: chunk.read_u32(field::INIT_TAG),
+            a_rwnd: chunk.read_u32(field::INIT_A_RWND),
+            outbound_streams: chunk.read_u16(field::INIT_OUTBOUND),
+            inbound_streams: chunk.read_u16(field::INIT_INBOUND),
+            initial_tsn: chunk.read_u32(field::INIT_INITIAL_TSN),
+        })
+    }
+
+    fn emit(&self, chunk: &mut sctp_chunk) {
+        chunk.write_u32(field::INIT_TAG, self.initiate_tag);
+        chunk.write_u32(field::INIT_A_RWND, self.a_rwnd);
+        chunk.write_u16(field::INIT_OUTBOUND, self.outbound_streams);
+        chunk.write_u16(field::INIT_INBOUND, self.inbound_streams);
+        chunk.write_u32(field::INIT_INITIAL_TSN, self.initial_tsn);
+    }
+}
+
+impl<'a> Chunk<'a> {
+    /// Parse a chunk and return a high-level representation.
+    ///
+    /// Chunks of an unknown type are `Unrecognized`. The two highest bits of their type tell the
+    /// receiver whether to skip them or to stop processing the packet, see rfc4960 section 3.2.
+    pub fn parse(chunk: &'a sctp_chunk) -> Result<Self> {
+        chunk.check_len()?;
+        let flags = chunk.flags();
+        match chunk.chunk_type() {
+            ChunkType::Data => {
+                // A data chunk without any user data is a protocol violation.
+                chunk.check_value_len(field::DATA_PAYLOAD.start + 1)?;
+                Ok(Chunk::Data {
+                    tsn: chunk.read_u32(field::DATA_TSN),
+                    stream: chunk.read_u16(field::DATA_STREAM),
+                    stream_seq: chunk.read_u16(field::DATA_STREAM_SEQ),
+                    ppid: chunk.read_u32(field::DATA_PPID),
+                    unordered: flags & flags::UNORDERED != 0,
+                    begin: flags & flags::BEGIN != 0,
+                    end: flags & flags::END != 0,
+                    data: &chunk.value()[field::DATA_PAYLOAD.start - CHUNK_HEADER_LEN..],
+                })
+            },
+            ChunkType::Init => Ok(Chunk::Init(InitParams::parse(chunk)?)),
+            ChunkType::InitAck => {
+                let params = InitParams::parse(chunk)?;
+                let cookie = find_parameter(&chunk.0[field::INIT_PARAMETERS.start..], STATE_COOKIE)?
+                    .ok_or(Error::Malformed)?;
+                Ok(Chunk::InitAck { params, cookie })
+            },
+            ChunkType::Sack => {
+                chunk.check_value_len(field::SACK_BLOCKS.start)?;
+                let blocks = usize::from(chunk.read_u16(field::SACK_GAP_BLOCKS))
+                    + usize::from(chunk.read_u16(field::SACK_DUPLICATES));
+                chunk.check_value_len(field::SACK_BLOCKS.start + 4*blocks)?;
+                Ok(Chunk::Sack {
+                    cumulative_tsn: chunk.read_u32(field::SACK_CUMULATIVE_TSN),
+                    a_rwnd: chunk.read_u32(field::SACK_A_RWND),
+                })
+            },
+            ChunkType::Heartbeat => Ok(Chunk::Heartbeat { info: chunk.value() }),
+            ChunkType::HeartbeatAck => Ok(Chunk::HeartbeatAck { info: chunk.value() }),
+            ChunkType::Abort => Ok(Chunk::Abort {
+                reflected: flags & flags::REFLECTED != 0,
+            }),
+            ChunkType::Shutdown => {
+                chunk.check_value_len(field::SHUTDOWN_CUMULATIVE_TSN.end)?;
+                Ok(Chunk::Shutdown {
+                    cumulative_tsn: chunk.read_u32(field::SHUTDOWN_CUMULATIVE_TSN),
+                })
+            },
+            ChunkType::ShutdownAck => Ok(Chunk::ShutdownAck),
+            ChunkType::Error => Ok(Chunk::Error { causes: chunk.value() }),
+            ChunkType::CookieEcho => Ok(Chunk::CookieEcho { cookie: chunk.value() }),
+            ChunkType::CookieAck => Ok(Chunk::CookieAck),
+            ChunkType::ShutdownComplete => Ok(Chunk::ShutdownComplete {
+                reflected: flags & flags::REFLECTED != 0,
+            }),
+            ChunkType::Unknown(_) => Err(Error::Unrecognized),
+        }
+    }
+
+    /// The type of the chunk.
+    pub fn chunk_type(&self) -> ChunkType {
+        match self {
+            Chunk::Data { .. } => ChunkType::Data,
+            Chunk::Init(_) => ChunkType::Init,
+            Chunk::InitAck { .. } => ChunkType::InitAck,
+            Chunk::Sack { .. } => ChunkType::Sack,
+            Chunk::Heartbeat { .. } => ChunkType::Heartbeat,
+            Chunk::HeartbeatAck { .. } => ChunkType::HeartbeatAck,
+            Chunk::Abort { .. } => ChunkType::Abort,
+            Chunk::Shutdown { .. } => ChunkType::Shutdown,
+            Chunk::ShutdownAck => ChunkType::ShutdownAck,
+            Chunk::Error { .. } => ChunkType::Error,
+            Chunk::CookieEcho { .. } => ChunkType::CookieEcho,
+            Chunk::CookieAck => ChunkType::CookieAck,
+            Chunk::ShutdownComplete { .. } => ChunkType::ShutdownComplete,
+        }
+    }
+
+    /// Return the length of the chunk when emitted, without padding.
+    pub fn buffer_len(&self) -> usize {
+        match self {
+            Chunk::Data { data, .. } => DATA_HEADER_LEN + data.len(),
+            Chunk::Init(_) => field::INIT_PARAMETERS.start,
+            Chunk::InitAck { cookie, .. } => field::INIT_PARAMETERS.start + 4 + cookie.len(),
+            Chunk::Sack { .. } => field::SACK_BLOCKS.start,
+            Chunk::Heartbeat { info }
+            | Chunk::HeartbeatAck { info } => CHUNK_HEADER_LEN + info.len(),
+            Chunk::Shutdown { .. } => field::SHUTDOWN_CUMULATIVE_TSN.end,
+            Chunk::Error { causes } => CHUNK_HEADER_LEN + causes.len(),
+            Chunk::CookieEcho { cookie } => CHUNK_HEADER_LEN + cookie.len(),
+            Chunk::Abort { .. }
+            | Chunk::ShutdownAck
+            | Chunk::CookieAck
+            | Chunk::ShutdownComplete { .. } => CHUNK_HEADER_LEN,
+        }
+    }
+
+    /// Return the length of the chunk when emitted, padded to a multiple of four bytes.
+    ///
+    /// Chunks following this one in the same packet start at this offset.
+    pub fn padded_len(&self) -> usize {
+        padded(self.buffer_len())
+    }
+
+    /// Emit a high-level representation into a buffer of at least `buffer_len` bytes.
+    ///
+    /// Padding up to `padded_len` is zeroed as far as it fits into the buffer.
+    pub fn emit(&self, chunk: &mut sctp_chunk) {
+        let len = self.buffer_len();
+        chunk.set_chunk_type(self.chunk_type());
+        chunk.set_flags(0);
+        // Callers must ensure the length fits, chunks are limited by the ip packet anyways.
+        chunk.set_length(len as u16);
+
+        match *self {
+            Chunk::Data { tsn, stream, stream_seq, ppid, unordered, begin, end, data } => {
+                let flags = if unordered { flags::UNORDERED } else { 0 }
+                    | if begin { flags::BEGIN } else { 0 }
+                    | if end { flags::END } else { 0 };
+                chunk.set_flags(flags);
+                chunk.write_u32(field::DATA_TSN, tsn);
+                chunk.write_u16(field::DATA_STREAM, stream);
+                chunk.write_u16(field::DATA_STREAM_SEQ, stream_seq);
+                chunk.write_u32(field::DATA_PPID, ppid);
+                chunk.0[field::DATA_PAYLOAD.start..len].copy_from_slice(data);
+            },
+            Chunk::Init(params) => params.emit(chunk),
+            Chunk::InitAck { params, cookie } => {
+                params.emit(chunk);
+                let parameter = &mut chunk.0[field::INIT_PARAMETERS.start..len];
+                NetworkEndian::write_u16(&mut parameter[0..2], STATE_COOKIE);
+                // Fits as it is part of the chunk.
+                NetworkEndian::write_u16(&mut parameter[2..4], (4 + cookie.len()) as u16);
+                parameter[4..].copy_from_slice(cookie);
+            },
+            Chunk::Sack { cumulative_tsn, a_rwnd } => {
+                chunk.write_u32(field::SACK_CUMULATIVE_TSN, cumulative_tsn);
+                chunk.write_u32(field::SACK_A_RWND, a_rwnd);
+                chunk.write_u16(field::SACK_GAP_BLOCKS, 0);
+                chunk.write_u16(field::SACK_DUPLICATES, 0);
+            },
+            Chunk::Heartbeat { info: value }
+            | Chunk::HeartbeatAck { info: value }
+            | Chunk::Error { causes: value }
+            | Chunk::CookieEcho { cookie: value } => {
+                chunk.0[field::CHUNK_VALUE.start..len].copy_from_slice(value);
+            },
+            Chunk::Abort { reflected }
+            | Chunk::ShutdownComplete { reflected } => {
+                chunk.set_flags(if reflected { flags::REFLECTED } else { 0 });
+            },
+            Chunk::Shutdown { cumulative_tsn } => {
+                chunk.write_u32(field::SHUTDOWN_CUMULATIVE_TSN, cumulative_tsn);
+            },
+            Chunk::ShutdownAck | Chunk::CookieAck => (),
+        }
+
+        let padding_end = padded(len).min(chunk.0.len());
+        for byte in &mut chunk.0[len..padding_end] {
+            *byte = 0;
+        }
+    }
+}
+
+/// Check if an unrecognized chunk type permits processing the rest of the packet.
+///
+/// The highest bit of the type is set for such chunks, see rfc4960 section 3.2.
+pub fn skip_unrecognized(chunk_type: ChunkType) -> bool {
+    u8::from(chunk_type) & 0x80 != 0
+}
+
+/// Round a length up to the padding of chunks and parameters.
+fn padded(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Find the value of the first parameter of a type.
+fn find_parameter(mut parameters: &[u8], kind: u16) -> Result<Option<&[u8]>> {
+    while !parameters.is_empty() {
+        if parameters.len() < 4 {
+            return Err(Error::Malformed);
+        }
+
+        let length = usize::from(NetworkEndian::read_u16(&parameters[2..4]));
+        if length < 4 || length > parameters.len() {
+            return Err(Error::Malformed);
+        }
+
+        if NetworkEndian::read_u16(&parameters[0..2]) == kind {
+            return Ok(Some(&parameters[4..length]));
+        }
+
+        parameters = parameters.get(padded(length)..).unwrap_or(&[]);
+    }
+
+    Ok(None)
+}
+
+/// The CRC32c (Castagnoli) of a byte sequence, as used for the SCTP checksum.
+pub fn crc32c(data: &[u8]) -> u32 {
+    !crc32c_update(!0, data)
+}
+
+/// Update a running CRC32c without the final inversion.
+fn crc32c_update(mut crc: u32, data: &[u8]) -> u32 {
+    // The reflected Castagnoli polynomial.
+    const POLYNOMIAL: u32 = 0x82f6_3b78;
+
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (POLYNOMIAL & mask);
+        }
+    }
+
+    crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::wire::Checksum;
+
+    static INIT_BYTES: [u8; 40] = [
+        0x13, 0x88, 0x0b, 0xb8,
+        0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x1a,
+        0x12, 0x34, 0x56, 0x78,
+        0x00, 0x01, 0x00, 0x00,
+        0x00, 0x01, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x2a,
+        // An unknown optional parameter that is skipped.
+        0x80, 0x08, 0x00, 0x06,
+        0xaa, 0xbb, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn crc32c_vectors() {
+        // See rfc3720, appendix B.4.
+        assert_eq!(crc32c(&[0; 32]), 0x8a91_36aa);
+        assert_eq!(crc32c(&[0xff; 32]), 0x62a8_ab43);
+    }
+
+    #[test]
+    fn init() {
+        let mut bytes = INIT_BYTES;
+        sctp::new_unchecked_mut(&mut bytes).fill_checksum();
+        let packet = sctp::new_checked(&bytes).unwrap();
+        assert!(packet.verify_checksum());
+        assert_eq!(Repr::parse(packet, Checksum::Manual), Ok(Repr {
+            src_port: 5000,
+            dst_port: 3000,
+            verification_tag: 0,
+        }));
+
+        let mut chunks = packet.chunks();
+        let chunk = chunks.next().unwrap().unwrap();
+        assert!(chunks.next().is_none());
+        assert_eq!(Chunk::parse(chunk), Ok(Chunk::Init(InitParams {
+            initiate_tag: 0x1234_5678,
+            a_rwnd: 0x10000,
+            outbound_streams: 1,
+            inbound_streams: 1,
+            initial_tsn: 42,
+        })));
+
+        bytes[20] ^= 1;
+        let packet = sctp::new_checked(&bytes).unwrap();
+        assert_eq!(Repr::parse(packet, Checksum::Manual),
+            Err(Error::WrongChecksum));
+    }
+
+    #[test]
+    fn emit_chunks() {
+        let chunks = [
+            Chunk::Data {
+                tsn: 7,
+                stream: 0,
+                stream_seq: 3,
+                ppid: 51,
+                unordered: false,
+                begin: true,
+                end: true,
+                data: b"hello",
+            },
+            Chunk::InitAck {
+                params: InitParams {
+                    initiate_tag: 1,
+                    a_rwnd: 1500,
+                    outbound_streams: 1,
+                    inbound_streams: 1,
+                    initial_tsn: 9,
+                },
+                cookie: b"cookie",
+            },
+            Chunk::Sack { cumulative_tsn: 6, a_rwnd: 1000 },
+            Chunk::Shutdown { cumulative_tsn: 6 },
+            Chunk::CookieEcho { cookie: b"cookie" },
+            Chunk::ShutdownComplete { reflected: true },
+        ];
+
+        let mut bytes = [0xff; 128];
+        let mut offset = 0;
+        for chunk in &chunks {
+            let end = offset + chunk.padded_len();
+            chunk.emit(sctp_chunk::new_unchecked_mut(&mut bytes[offset..end]));
+            offset = end;
+        }
+
+        // The data chunk is padded with zeroes.
+        assert_eq!(&bytes[16..24], b"hello\0\0\0");
+
+        let mut parsed = Chunks { rest: &bytes[..offset] };
+        for chunk in &chunks {
+            let parsed = parsed.next().unwrap().unwrap();
+            assert_eq!(Chunk::parse(parsed).as_ref(), Ok(chunk));
+        }
+        assert!(parsed.next().is_none());
+    }
+
+    #[test]
+    fn malformed() {
+        // A chunk length shorter than the header.
+        let mut chunks = Chunks { rest: &[0x0b, 0x00, 0x00, 0x02] };
+        assert_eq!(chunks.next(), Some(Err(Error::Malformed)));
+        assert_eq!(chunks.next(), None);
+
+        // A data chunk without user data.
+        let bytes = [0u8, 0x03, 0x00, 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let chunk = sctp_chunk::new_checked(&bytes).unwrap();
+        assert_eq!(Chunk::parse(chunk), Err(Error::Malformed));
+
+        // An initiation acknowledgment without a cookie.
+        let mut bytes = [0; 20];
+        bytes.copy_from_slice(&INIT_BYTES[12..32]);
+        bytes[0] = ChunkType::InitAck.into();
+        bytes[3] = 20;
+        let chunk = sctp_chunk::new_checked(&bytes).unwrap();
+        assert_eq!(Chunk::parse(chunk), Err(Error::Malformed));
+
+        // Unknown chunks tell whether to skip them.
+        let chunk = sctp_chunk::new_checked(&[0xc1, 0x00, 0x00, 0x04]).unwrap();
+        assert_eq!(Chunk::parse(chunk), Err(Error::Unrecognized));
+        assert!(skip_unrecognized(chunk.chunk_type()));
+    }
+}
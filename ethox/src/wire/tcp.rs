@@ -84,7 +84,7 @@ pub struct Packet<T> {
     repr: Repr,
 }
 
-mod field {
+pub(crate) mod field {
     #![allow(non_snake_case)]
 
     use crate::wire::field::Field;
@@ -108,6 +108,7 @@ mod field {
     pub(crate) const OPT_WS:  u8 = 0x03;
     pub(crate) const OPT_SACKPERM: u8 = 0x04;
     pub(crate) const OPT_SACKRNG:  u8 = 0x05;
+    pub(crate) const OPT_UTO:  u8 = 0x1c;
 }
 
 impl<T: Payload> Packet<T> {
@@ -413,6 +414,25 @@ impl<T: PayloadMut> Packet<T> {
         self.set_checksum(checksum)
     }
 
+    /// Compute and fill in the checksum of a segment whose payload is stored apart.
+    ///
+    /// The buffer holds only the header. The payload is described by its length and its checksum
+    /// `payload_sum`, without the final complement.
+    pub(crate) fn fill_checksum_detached(&mut self, src_addr: IpAddress, dst_addr: IpAddress,
+                                         payload_len: usize, payload_sum: u16) {
+        self.set_checksum(0);
+        let checksum = {
+            let data = self.buffer.payload_mut().as_bytes_mut();
+            !checksum::combine(&[
+                checksum::pseudo_header(&src_addr, &dst_addr, IpProtocol::Tcp,
+                                        (data.len() + payload_len) as u32),
+                checksum::data(data),
+                payload_sum,
+            ])
+        };
+        self.set_checksum(checksum)
+    }
+
     /// Return a pointer to the options.
     #[inline]
     pub fn options_mut(&mut self) -> &mut [u8] {
@@ -625,6 +645,9 @@ pub enum TcpOption<'a> {
     /// Specifies the selectively acknowledged ranges.
     /// Should only be sent if the remote sent `SackPermitted` originally.
     SackRange([Option<(u32, u32)>; 3]),
+    /// Advertise the user timeout of the sender, see RFC 5482.
+    /// The highest bit selects minutes instead of seconds as the unit of the lower 15 bits.
+    UserTimeout(u16),
     /// Some user specified option not handled within the library itself.
     Unknown { kind: u8, data: &'a [u8] }
 }
@@ -663,6 +686,10 @@ impl<'a> TcpOption<'a> {
                         option = TcpOption::SackPermitted,
                     (field::OPT_SACKPERM, _) =>
                         return Err(Error::Malformed),
+                    (field::OPT_UTO, 4) =>
+                        option = TcpOption::UserTimeout(NetworkEndian::read_u16(data)),
+                    (field::OPT_UTO, _) =>
+                        return Err(Error::Malformed),
                     (field::OPT_SACKRNG, n) => {
                         if n < 10 || (n-2) % 8 != 0 {
                             return Err(Error::Malformed)
@@ -717,6 +744,7 @@ impl<'a> TcpOption<'a> {
             TcpOption::WindowScale(_) => 3,
             TcpOption::SackPermitted => 2,
             TcpOption::SackRange(s) => s.iter().filter(|s| s.is_some()).count() * 8 + 2,
+            TcpOption::UserTimeout(_) => 4,
             TcpOption::Unknown { data, .. } => 2 + data.len()
         }
     }
@@ -765,6 +793,10 @@ impl<'a> TcpOption<'a> {
                             NetworkEndian::write_u32(&mut buffer[pos+4..], second);
                         });
                     }
+                    TcpOption::UserTimeout(value) => {
+                        buffer[0] = field::OPT_UTO;
+                        NetworkEndian::write_u16(&mut buffer[2..], value)
+                    }
                     TcpOption::Unknown { kind, data: provided } => {
                         buffer[0] = kind;
                         buffer[2..].copy_from_slice(provided)
@@ -809,6 +841,9 @@ pub struct Repr {
     /// The selective acknowledgement ranges.
     /// See [`TcpOption::SackRange`](struct.TcpOption.html#variant.SackRange).
     pub sack_ranges:  [Option<(u32, u32)>; 3],
+    /// The user timeout option, if present.
+    /// See [`TcpOption::UserTimeout`](struct.TcpOption.html#variant.UserTimeout).
+    pub user_timeout: Option<u16>,
     /// The length of the segment carried by the packet.
     pub payload_len:  u16,
 }
@@ -854,6 +889,7 @@ impl Repr {
             max_seg_size: None,
             sack_permitted: false,
             sack_ranges: [None; 3],
+            user_timeout: None,
             payload_len: 0,
         });
         packet.check_len()?;
@@ -874,16 +910,17 @@ impl Repr {
         } else {
             None
         };
-        // The PSH flag is ignored.
-        // The URG flag and the urgent field is ignored. This behavior is standards-compliant,
-        // however, most deployed systems (e.g. Linux) are *not* standards-compliant, and would
-        // cut the byte at the urgent pointer from the stream.
+        // The PSH and URG flags are only kept in the flags, the urgent field is not part of the
+        // representation. Urgent data stays inline in the stream as recommended by RFC6093,
+        // however, most deployed systems (e.g. Linux) by default cut the byte at the urgent
+        // pointer from the stream.
 
         let mut max_seg_size = None;
         let mut window_scale = None;
         let mut options = packet.options();
         let mut sack_permitted = false;
         let mut sack_ranges = [None, None, None];
+        let mut user_timeout = None;
         while options.len() > 0 {
             let (next_options, option) = TcpOption::parse(options)?;
             match option {
@@ -907,6 +944,8 @@ impl Repr {
                     sack_permitted = true,
                 TcpOption::SackRange(slice) =>
                     sack_ranges = slice,
+                TcpOption::UserTimeout(value) =>
+                    user_timeout = Some(value),
                 _ => (),
             }
             options = next_options;
@@ -923,6 +962,7 @@ impl Repr {
             max_seg_size: max_seg_size,
             sack_permitted: sack_permitted,
             sack_ranges:   sack_ranges,
+            user_timeout,
             payload_len:  packet.payload_slice().len() as u16,
         })
     }
@@ -942,6 +982,9 @@ impl Repr {
         if self.sack_permitted {
             length += 2;
         }
+        if self.user_timeout.is_some() {
+            length += 4;
+        }
         let sack_range_len: usize = self.sack_ranges.iter().map(
             |o| o.map(|_| 8).unwrap_or(0)
             ).sum();
@@ -988,6 +1031,9 @@ impl Repr {
             if let Some(value) = self.max_seg_size {
                 let tmp = options; options = TcpOption::MaxSegmentSize(value).emit(tmp);
             }
+            if let Some(value) = self.user_timeout {
+                let tmp = options; options = TcpOption::UserTimeout(value).emit(tmp);
+            }
             if self.sack_permitted {
                 let tmp = options; options = TcpOption::SackPermitted.emit(tmp);
             } else if self.ack_number.is_some() && self.sack_ranges.iter().any(|s| s.is_some()) {
@@ -1049,6 +1095,8 @@ impl<'a, T: Payload + ?Sized> fmt::Display for Packet<&'a T> {
                     write!(f, " sACK")?,
                 TcpOption::SackRange(slice) =>
                     write!(f, " sACKr{:?}", slice)?, // debug print conveniently includes the []s
+                TcpOption::UserTimeout(value) =>
+                    write!(f, " uto={:#06x}", value)?,
                 TcpOption::Unknown { kind, .. } =>
                     write!(f, " opt({})", kind)?,
             }
@@ -1222,6 +1270,7 @@ mod test {
             max_seg_size: None,
             sack_permitted: false,
             sack_ranges:  [None, None, None],
+            user_timeout: None,
             payload_len:  PAYLOAD_BYTES.len() as _,
         }
     }
@@ -1247,6 +1296,75 @@ mod test {
         assert_eq!(&packet.into_inner()[..], &SYN_PACKET_BYTES[..]);
     }
 
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_emit_detached() {
+        use crate::wire::{PayloadVectored, Segmented};
+
+        // Split the payload at an odd offset.
+        let mut segments = Segmented::new(vec![], 2);
+        segments.push_segment(1).unwrap();
+        segments.push_segment(3).unwrap();
+        segments.segment_mut(0).unwrap().copy_from_slice(&PAYLOAD_BYTES[..1]);
+        segments.segment_mut(1).unwrap().copy_from_slice(&PAYLOAD_BYTES[1..]);
+
+        let repr = Repr { payload_len: 0, ..packet_repr() };
+        let mut bytes = vec![0xa5; repr.buffer_len()];
+        repr.emit(Packet::new_unchecked(&mut bytes, repr));
+        let mut packet = Packet::new_unchecked(&mut bytes, repr);
+        packet.fill_checksum_detached(SRC_ADDR.into(), DST_ADDR.into(),
+            PAYLOAD_BYTES.len(), checksum::segments(&segments));
+
+        let mut complete = packet.into_inner().clone();
+        complete.extend_from_slice(&segments.to_vec());
+        assert_eq!(&complete[..], &SYN_PACKET_BYTES[..]);
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_emit_parse_roundtrip(
+            src_port in 1u16..,
+            dst_port in 1u16..,
+            flags in 0u16..0x200,
+            seq_number: i32,
+            ack_number: Option<i32>,
+            window_len: u16,
+            window_scale in proptest::option::of(0u8..=14),
+            max_seg_size: Option<u16>,
+            sack_permitted: bool,
+            user_timeout: Option<u16>,
+            payload_len in 0u16..64,
+        ) {
+            let mut flags = Flags(flags);
+            flags.set_ack(ack_number.is_some());
+            let repr = Repr {
+                src_port,
+                dst_port,
+                flags,
+                seq_number: SeqNumber(seq_number),
+                ack_number: ack_number.map(SeqNumber),
+                window_len,
+                window_scale,
+                max_seg_size,
+                sack_permitted,
+                sack_ranges: [None; 3],
+                user_timeout,
+                payload_len,
+            };
+
+            let mut bytes = vec![0; repr.buffer_len()];
+            repr.emit(Packet::new_unchecked(&mut bytes, repr));
+            Packet::new_unchecked(&mut bytes, repr)
+                .fill_checksum(SRC_ADDR.into(), DST_ADDR.into());
+
+            let checksum = Checksum::Manual {
+                src_addr: SRC_ADDR.into(),
+                dst_addr: DST_ADDR.into(),
+            };
+            proptest::prop_assert_eq!(Repr::parse(&bytes, checksum), Ok(repr));
+        }
+    }
+
     #[test]
     fn test_header_len_multiple_of_4() {
         let mut repr = packet_repr();
@@ -1289,6 +1407,8 @@ mod test {
                                 0x00, 0x0d, 0x59, 0xf8, 0x00, 0x12, 0xb1, 0x28,
                                 0x00, 0x16, 0xe3, 0x60, 0x00, 0x26, 0x25, 0xa0,
                                 0x34, 0x3e, 0xfc, 0xea, 0x34, 0x40, 0xae, 0xf0]);
+        assert_option_parses!(TcpOption::UserTimeout(0x8005),
+                              &[0x1c, 0x04, 0x80, 0x05]);
         assert_option_parses!(TcpOption::Unknown { kind: 12, data: &[1, 2, 3][..] },
                               &[0x0c, 0x05, 0x01, 0x02, 0x03])
     }
@@ -1307,5 +1427,7 @@ mod test {
                    Err(Error::Malformed));
         assert_eq!(TcpOption::parse(&[0x3, 0x02]),
                    Err(Error::Malformed));
+        assert_eq!(TcpOption::parse(&[0x1c, 0x03, 0x00]),
+                   Err(Error::Malformed));
     }
 }
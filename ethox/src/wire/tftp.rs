@@ -0,0 +1,602 @@
+//! TFTP packets, rfc1350, with the option extension of rfc2347.
+//!
+//! The block size, timeout interval and transfer size options of rfc2348 and rfc2349 are
+//! supported. Other options are skipped when parsing, as the standard requires.
+use byteorder::{ByteOrder, NetworkEndian};
+
+use super::{Error, Result};
+
+/// The udp port on which servers listen for requests.
+pub const PORT: u16 = 69;
+
+/// The block size of transfers without a negotiated one.
+pub const DEFAULT_BLOCK_SIZE: u16 = 512;
+
+/// The smallest block size that can be negotiated, rfc2348.
+pub const MIN_BLOCK_SIZE: u16 = 8;
+
+/// The largest block size that can be negotiated, rfc2348.
+pub const MAX_BLOCK_SIZE: u16 = 65464;
+
+enum_with_unknown! {
+    /// The operation of a packet.
+    pub doc enum OpCode(u16) {
+        /// Read request
+        ReadRequest = 1,
+        /// Write request
+        WriteRequest = 2,
+        /// Data
+        Data = 3,
+        /// Acknowledgment
+        Ack = 4,
+        /// Error
+        Error = 5,
+        /// Option acknowledgment
+        OptionAck = 6,
+    }
+}
+
+enum_with_unknown! {
+    /// The code of an error packet.
+    pub doc enum ErrorCode(u16) {
+        /// Not defined, see the error message
+        Undefined = 0,
+        /// File not found
+        FileNotFound = 1,
+        /// Access violation
+        AccessViolation = 2,
+        /// Disk full or allocation exceeded
+        DiskFull = 3,
+        /// Illegal TFTP operation
+        IllegalOperation = 4,
+        /// Unknown transfer ID
+        UnknownTransferId = 5,
+        /// File already exists
+        FileExists = 6,
+        /// No such user
+        NoSuchUser = 7,
+        /// The requested options were refused, rfc2347
+        OptionRefused = 8,
+    }
+}
+
+/// The transfer mode of a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// Text with the line endings of the network virtual terminal.
+    NetAscii,
+    /// Raw bytes.
+    Octet,
+    /// Obsolete mail delivery, rfc1350.
+    Mail,
+}
+
+/// The options of a request or an option acknowledgment.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Options {
+    /// The number of bytes in each data block, rfc2348.
+    pub block_size: Option<u16>,
+    /// The retransmission timeout in seconds, rfc2349.
+    pub timeout: Option<u8>,
+    /// The size of the file in bytes, rfc2349.
+    ///
+    /// Zero in a read request, asking the server to provide the size.
+    pub transfer_size: Option<u64>,
+}
+
+byte_wrapper! {
+    /// A byte slice containing a potential TFTP packet.
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct tftp([u8]);
+}
+
+// Format of the TFTP packets
+//
+//  2 bytes    string    1 byte    string   1 byte   (string  1 byte  string  1 byte)*
+// +--------+----------+--------+----------+--------+--------+------+--------+------+
+// | 01/02  | Filename |   0    |   Mode   |   0    |  opt   |  0   |  value |  0   |
+// +--------+----------+--------+----------+--------+--------+------+--------+------+
+//
+//  2 bytes    2 bytes      n bytes
+// +--------+----------+------------+
+// |   03   |  Block # |    Data    |
+// +--------+----------+------------+
+//
+//  2 bytes    2 bytes
+// +--------+----------+
+// |   04   |  Block # |
+// +--------+----------+
+//
+//  2 bytes    2 bytes      string    1 byte
+// +--------+------------+----------+--------+
+// |   05   |  ErrorCode |  ErrMsg  |   0    |
+// +--------+------------+----------+--------+
+//
+//  2 bytes    (string  1 byte  string  1 byte)*
+// +--------+--------+------+--------+------+
+// |   06   |  opt   |  0   |  value |  0   |
+// +--------+--------+------+--------+------+
+//
+// See https://tools.ietf.org/html/rfc1350#section-5 and rfc2347 for details.
+mod field {
+    use crate::wire::field::{Field, Rest};
+
+    pub(crate) const OPCODE:  Field = 0..2;
+    pub(crate) const BLOCK:   Field = 2..4;
+    pub(crate) const CODE:    Field = 2..4;
+    pub(crate) const STRINGS: Rest = 2..;
+    pub(crate) const DATA:    Rest = 4..;
+}
+
+mod option {
+    pub(crate) const BLOCK_SIZE: &[u8] = b"blksize";
+    pub(crate) const TIMEOUT: &[u8] = b"timeout";
+    pub(crate) const TRANSFER_SIZE: &[u8] = b"tsize";
+}
+
+/// The length of the header of data, acknowledgment and error packets.
+pub const HEADER_LEN: usize = field::DATA.start;
+
+impl tftp {
+    /// Imbue a raw octet buffer with TFTP packet structure.
+    pub fn new_unchecked(data: &[u8]) -> &Self {
+        Self::__from_macro_new_unchecked(data)
+    }
+
+    /// Imbue a mutable octet buffer with TFTP packet structure.
+    pub fn new_unchecked_mut(data: &mut [u8]) -> &mut Self {
+        Self::__from_macro_new_unchecked_mut(data)
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(data: &[u8]) -> Result<&Self> {
+        let packet = Self::new_unchecked(data);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// View the packet as a raw byte slice.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// View the packet as a mutable raw byte slice.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    /// Returns `Err(Error::Truncated)` if the buffer is too short.
+    ///
+    /// Data, acknowledgment and error packets need a block number or error code, requests only
+    /// the operation.
+    pub fn check_len(&self) -> Result<()> {
+        if self.0.len() < field::OPCODE.end {
+            return Err(Error::Truncated);
+        }
+
+        match self.opcode() {
+            OpCode::Data | OpCode::Ack | OpCode::Error if self.0.len() < HEADER_LEN => {
+                Err(Error::Truncated)
+            },
+            _ => Ok(()),
+        }
+    }
+
+    pub fn opcode(&self) -> OpCode {
+        OpCode::from(NetworkEndian::read_u16(&self.0[field::OPCODE]))
+    }
+
+    /// The block number of a data or acknowledgment packet.
+    pub fn block(&self) -> u16 {
+        NetworkEndian::read_u16(&self.0[field::BLOCK])
+    }
+
+    /// The code of an error packet.
+    pub fn error_code(&self) -> ErrorCode {
+        ErrorCode::from(NetworkEndian::read_u16(&self.0[field::CODE]))
+    }
+
+    /// The data of a data packet.
+    pub fn data(&self) -> &[u8] {
+        &self.0[field::DATA]
+    }
+
+    /// The null terminated strings of requests, errors and option acknowledgments.
+    pub fn strings(&self) -> &[u8] {
+        match self.opcode() {
+            OpCode::Error => &self.0[field::DATA],
+            _ => &self.0[field::STRINGS],
+        }
+    }
+
+    pub fn set_opcode(&mut self, value: OpCode) {
+        NetworkEndian::write_u16(&mut self.0[field::OPCODE], value.into())
+    }
+
+    pub fn set_block(&mut self, value: u16) {
+        NetworkEndian::write_u16(&mut self.0[field::BLOCK], value)
+    }
+
+    pub fn set_error_code(&mut self, value: ErrorCode) {
+        NetworkEndian::write_u16(&mut self.0[field::CODE], value.into())
+    }
+
+    /// The data of a data packet, to be filled before sending.
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.0[field::DATA]
+    }
+}
+
+impl AsRef<[u8]> for tftp {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl AsMut<[u8]> for tftp {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.as_bytes_mut()
+    }
+}
+
+/// A high-level representation of a TFTP packet.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Repr<'a> {
+    /// A request to read a file from the server.
+    ReadRequest {
+        /// The name of the file, without its terminating null byte.
+        filename: &'a [u8],
+        /// The mode of the transfer.
+        mode: Mode,
+        /// The options requested by the client.
+        options: Options,
+    },
+    /// A request to write a file to the server.
+    WriteRequest {
+        /// The name of the file, without its terminating null byte.
+        filename: &'a [u8],
+        /// The mode of the transfer.
+        mode: Mode,
+        /// The options requested by the client.
+        options: Options,
+    },
+    /// A block of data.
+    Data {
+        /// The number of the block, starting with 1.
+        block: u16,
+        /// The data, shorter than the block size for the last block.
+        data: &'a [u8],
+    },
+    /// The acknowledgment of a block, or of options with block number 0.
+    Ack {
+        /// The number of the acknowledged block.
+        block: u16,
+    },
+    /// An error that terminates the transfer.
+    Error {
+        /// The error code.
+        code: ErrorCode,
+        /// A message for humans, without its terminating null byte.
+        message: &'a [u8],
+    },
+    /// The acknowledgment of the options of a request by the server, rfc2347.
+    OptionAck {
+        /// The options accepted by the server.
+        options: Options,
+    },
+}
+
+impl Mode {
+    /// Parse the mode string of a request, ignoring case.
+    pub fn parse(mode: &[u8]) -> Result<Self> {
+        if mode.eq_ignore_ascii_case(b"octet") {
+            Ok(Mode::Octet)
+        } else if mode.eq_ignore_ascii_case(b"netascii") {
+            Ok(Mode::NetAscii)
+        } else if mode.eq_ignore_ascii_case(b"mail") {
+            Ok(Mode::Mail)
+        } else {
+            Err(Error::Unrecognized)
+        }
+    }
+
+    /// The mode string of a request.
+    pub fn as_bytes(self) -> &'static [u8] {
+        match self {
+            Mode::NetAscii => b"netascii",
+            Mode::Octet => b"octet",
+            Mode::Mail => b"mail",
+        }
+    }
+}
+
+impl Options {
+    /// Check if no option is present.
+    pub fn is_empty(&self) -> bool {
+        *self == Options::default()
+    }
+
+    /// Parse a sequence of null terminated option names and values.
+    ///
+    /// Unknown options are skipped. Known ones with values out of range are `Malformed`.
+    pub fn parse(mut strings: &[u8]) -> Result<Self> {
+        let mut options = Options::default();
+        while !strings.is_empty() {
+            let (name, rest) = split_string(strings)?;
+            let (value, rest) = split_string(rest)?;
+            strings = rest;
+
+            if name.eq_ignore_ascii_case(option::BLOCK_SIZE) {
+                let size = parse_decimal(value)?;
+                if size < u64::from(MIN_BLOCK_SIZE) || size > u64::from(MAX_BLOCK_SIZE) {
+                    return Err(Error::Malformed);
+                }
+                options.block_size = Some(size as u16);
+            } else if name.eq_ignore_ascii_case(option::TIMEOUT) {
+                let timeout = parse_decimal(value)?;
+                if timeout == 0 || timeout > 255 {
+                    return Err(Error::Malformed);
+                }
+                options.timeout = Some(timeout as u8);
+            } else if name.eq_ignore_ascii_case(option::TRANSFER_SIZE) {
+                options.transfer_size = Some(parse_decimal(value)?);
+            }
+        }
+        Ok(options)
+    }
+
+    /// Return the length of the options when emitted.
+    pub fn buffer_len(&self) -> usize {
+        let mut len = 0;
+        if let Some(size) = self.block_size {
+            len += option::BLOCK_SIZE.len() + decimal_len(size.into()) + 2;
+        }
+        if let Some(timeout) = self.timeout {
+            len += option::TIMEOUT.len() + decimal_len(timeout.into()) + 2;
+        }
+        if let Some(size) = self.transfer_size {
+            len += option::TRANSFER_SIZE.len() + decimal_len(size) + 2;
+        }
+        len
+    }
+
+    /// Emit the options into a buffer of at least `buffer_len` bytes.
+    ///
+    /// Returns the number of bytes written.
+    pub fn emit(&self, buffer: &mut [u8]) -> usize {
+        let mut len = 0;
+        if let Some(size) = self.block_size {
+            len += emit_string(&mut buffer[len..], option::BLOCK_SIZE);
+            len += emit_decimal(&mut buffer[len..], size.into());
+        }
+        if let Some(timeout) = self.timeout {
+            len += emit_string(&mut buffer[len..], option::TIMEOUT);
+            len += emit_decimal(&mut buffer[len..], timeout.into());
+        }
+        if let Some(size) = self.transfer_size {
+            len += emit_string(&mut buffer[len..], option::TRANSFER_SIZE);
+            len += emit_decimal(&mut buffer[len..], size);
+        }
+        len
+    }
+}
+
+impl<'a> Repr<'a> {
+    /// Parse a TFTP packet and return a high-level representation.
+    pub fn parse(packet: &'a tftp) -> Result<Self> {
+        packet.check_len()?;
+        match packet.opcode() {
+            opcode @ OpCode::ReadRequest | opcode @ OpCode::WriteRequest => {
+                let (filename, rest) = split_string(packet.strings())?;
+                let (mode, rest) = split_string(rest)?;
+                let mode = Mode::parse(mode)?;
+                let options = Options::parse(rest)?;
+                Ok(match opcode {
+                    OpCode::ReadRequest => Repr::ReadRequest { filename, mode, options },
+                    _ => Repr::WriteRequest { filename, mode, options },
+                })
+            },
+            OpCode::Data => Ok(Repr::Data {
+                block: packet.block(),
+                data: packet.data(),
+            }),
+            OpCode::Ack => Ok(Repr::Ack {
+                block: packet.block(),
+            }),
+            OpCode::Error => {
+                // Some implementations omit the terminator of the message.
+                let message = packet.strings();
+                let message = split_string(message).map_or(message, |(message, _)| message);
+                Ok(Repr::Error {
+                    code: packet.error_code(),
+                    message,
+                })
+            },
+            OpCode::OptionAck => Ok(Repr::OptionAck {
+                options: Options::parse(packet.strings())?,
+            }),
+            OpCode::Unknown(_) => Err(Error::Unrecognized),
+        }
+    }
+
+    /// Return the length of a packet that will be emitted from this high-level representation.
+    pub fn buffer_len(&self) -> usize {
+        match self {
+            Repr::ReadRequest { filename, mode, options }
+            | Repr::WriteRequest { filename, mode, options } => {
+                field::STRINGS.start + filename.len() + mode.as_bytes().len() + 2
+                    + options.buffer_len()
+            },
+            Repr::Data { data, .. } => HEADER_LEN + data.len(),
+            Repr::Ack { .. } => HEADER_LEN,
+            Repr::Error { message, .. } => HEADER_LEN + message.len() + 1,
+            Repr::OptionAck { options } => field::STRINGS.start + options.buffer_len(),
+        }
+    }
+
+    /// Emit a high-level representation into a TFTP packet of at least `buffer_len` bytes.
+    pub fn emit(&self, packet: &mut tftp) {
+        match self {
+            Repr::ReadRequest { filename, mode, options }
+            | Repr::WriteRequest { filename, mode, options } => {
+                packet.set_opcode(match self {
+                    Repr::ReadRequest { .. } => OpCode::ReadRequest,
+                    _ => OpCode::WriteRequest,
+                });
+                let strings = &mut packet.0[field::STRINGS];
+                let mut len = emit_string(strings, filename);
+                len += emit_string(&mut strings[len..], mode.as_bytes());
+                options.emit(&mut strings[len..]);
+            },
+            Repr::Data { block, data } => {
+                packet.set_opcode(OpCode::Data);
+                packet.set_block(*block);
+                packet.data_mut()[..data.len()].copy_from_slice(data);
+            },
+            Repr::Ack { block } => {
+                packet.set_opcode(OpCode::Ack);
+                packet.set_block(*block);
+            },
+            Repr::Error { code, message } => {
+                packet.set_opcode(OpCode::Error);
+                packet.set_error_code(*code);
+                emit_string(packet.data_mut(), message);
+            },
+            Repr::OptionAck { options } => {
+                packet.set_opcode(OpCode::OptionAck);
+                options.emit(&mut packet.0[field::STRINGS]);
+            },
+        }
+    }
+}
+
+/// Split off a null terminated string, without its terminator.
+fn split_string(data: &[u8]) -> Result<(&[u8], &[u8])> {
+    let end = data.iter().position(|&byte| byte == 0).ok_or(Error::Malformed)?;
+    Ok((&data[..end], &data[end + 1..]))
+}
+
+/// Write a null terminated string, returning the number of bytes written.
+fn emit_string(buffer: &mut [u8], string: &[u8]) -> usize {
+    buffer[..string.len()].copy_from_slice(string);
+    buffer[string.len()] = 0;
+    string.len() + 1
+}
+
+/// Parse the decimal value of an option.
+fn parse_decimal(value: &[u8]) -> Result<u64> {
+    if value.is_empty() {
+        return Err(Error::Malformed);
+    }
+
+    value.iter().try_fold(0u64, |number, &digit| {
+        if !digit.is_ascii_digit() {
+            return Err(Error::Malformed);
+        }
+        number.checked_mul(10)
+            .and_then(|number| number.checked_add(u64::from(digit - b'0')))
+            .ok_or(Error::Malformed)
+    })
+}
+
+/// The number of decimal digits of a value.
+fn decimal_len(mut value: u64) -> usize {
+    let mut len = 1;
+    while value >= 10 {
+        value /= 10;
+        len += 1;
+    }
+    len
+}
+
+/// Write a null terminated decimal value, returning the number of bytes written.
+fn emit_decimal(buffer: &mut [u8], mut value: u64) -> usize {
+    let len = decimal_len(value);
+    for digit in buffer[..len].iter_mut().rev() {
+        *digit = b'0' + (value % 10) as u8;
+        value /= 10;
+    }
+    buffer[len] = 0;
+    len + 1
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static READ_BYTES: [u8; 37] = [
+        0x00, 0x01,
+        b'b', b'o', b'o', b't', b'.', b'i', b'm', b'g', 0x00,
+        b'o', b'c', b't', b'e', b't', 0x00,
+        b'b', b'l', b'k', b's', b'i', b'z', b'e', 0x00,
+        b'1', b'4', b'6', b'8', 0x00,
+        b't', b's', b'i', b'z', b'e', 0x00,
+        b'0',
+    ];
+
+    #[test]
+    fn read_request() {
+        let packet = tftp::new_checked(&READ_BYTES).unwrap();
+        // The value of the last option is not terminated.
+        assert_eq!(Repr::parse(packet), Err(Error::Malformed));
+
+        let mut bytes = [0; 38];
+        bytes[..37].copy_from_slice(&READ_BYTES);
+        let packet = tftp::new_checked(&bytes).unwrap();
+        let repr = Repr::parse(packet).unwrap();
+        assert_eq!(repr, Repr::ReadRequest {
+            filename: b"boot.img",
+            mode: Mode::Octet,
+            options: Options {
+                block_size: Some(1468),
+                timeout: None,
+                transfer_size: Some(0),
+            },
+        });
+
+        assert_eq!(repr.buffer_len(), bytes.len());
+        let mut emitted = [0xff; 38];
+        repr.emit(tftp::new_unchecked_mut(&mut emitted));
+        assert_eq!(emitted, bytes);
+    }
+
+    #[test]
+    fn options() {
+        let options = Options::parse(b"BLKSIZE\x00512\x00unknown\x00x\x00timeout\x002\x00")
+            .unwrap();
+        assert_eq!(options, Options {
+            block_size: Some(512),
+            timeout: Some(2),
+            transfer_size: None,
+        });
+
+        assert_eq!(Options::parse(b"blksize\x004\x00"), Err(Error::Malformed));
+        assert_eq!(Options::parse(b"timeout\x00256\x00"), Err(Error::Malformed));
+        assert_eq!(Options::parse(b"tsize\x00-1\x00"), Err(Error::Malformed));
+
+        let repr = Repr::OptionAck { options };
+        let mut bytes = [0; 24];
+        assert_eq!(repr.buffer_len(), bytes.len());
+        repr.emit(tftp::new_unchecked_mut(&mut bytes));
+        assert_eq!(&bytes[..], b"\x00\x06blksize\x00512\x00timeout\x002\x00");
+    }
+
+    #[test]
+    fn data_and_error() {
+        let packet = tftp::new_checked(b"\x00\x03\x00\x02abc").unwrap();
+        assert_eq!(Repr::parse(packet), Ok(Repr::Data { block: 2, data: b"abc" }));
+        assert_eq!(tftp::new_checked(b"\x00\x04\x00"), Err(Error::Truncated));
+
+        let repr = Repr::Error { code: ErrorCode::FileNotFound, message: b"no" };
+        let mut bytes = [0; 7];
+        assert_eq!(repr.buffer_len(), bytes.len());
+        repr.emit(tftp::new_unchecked_mut(&mut bytes));
+        assert_eq!(&bytes, b"\x00\x05\x00\x01no\x00");
+        assert_eq!(Repr::parse(tftp::new_unchecked(&bytes)), Ok(repr));
+        assert_eq!(Repr::parse(tftp::new_unchecked(&bytes[..6])), Ok(repr));
+    }
+}
@@ -1,4 +1,5 @@
 use core::{fmt, ops};
+use core::convert::TryFrom;
 use byteorder::{ByteOrder, NetworkEndian};
 
 use super::{Error, IpProtocol, IpAddress, Result};
@@ -52,6 +53,14 @@ impl udp {
         Ok(Self::new_unchecked_mut(data))
     }
 
+    /// Imbue a raw octet buffer with UDP-Lite packet structure, checking its coverage.
+    ///
+    /// The buffer must contain exactly the datagram as its length is not part of the header.
+    pub fn new_checked_lite(data: &[u8]) -> Result<&Self> {
+        Self::new_unchecked(data).check_coverage()?;
+        Ok(Self::new_unchecked(data))
+    }
+
     /// Unwrap the packet as a raw byte slice.
     pub fn as_bytes(&self) -> &[u8] {
         &self.0
@@ -86,6 +95,26 @@ impl udp {
         }
     }
 
+    /// Ensure that the buffer is a valid UDP-Lite datagram.
+    ///
+    /// Returns `Err(Error::Truncated)` if the buffer is too short for the header. Returns
+    /// `Err(Error::Malformed)` if the checksum coverage is neither zero, for the whole datagram,
+    /// nor between the header length and the length of the buffer. See RFC3828 section 3.1.
+    pub fn check_coverage(&self) -> Result<()> {
+        let buffer_len = self.0.len();
+        if buffer_len < field::CHECKSUM.end {
+            return Err(Error::Truncated);
+        }
+
+        match usize::from(self.checksum_coverage()) {
+            0 => Ok(()),
+            coverage if coverage < field::CHECKSUM.end || coverage > buffer_len => {
+                Err(Error::Malformed)
+            },
+            _ => Ok(()),
+        }
+    }
+
     /// Return the source port field.
     #[inline]
     pub fn src_port(&self) -> u16 {
@@ -104,6 +133,15 @@ impl udp {
         NetworkEndian::read_u16(&self.0[field::LENGTH])
     }
 
+    /// Return the checksum coverage field of a UDP-Lite datagram.
+    ///
+    /// UDP-Lite reuses the length field for the number of bytes covered by the checksum, zero
+    /// meaning the whole datagram.
+    #[inline]
+    pub fn checksum_coverage(&self) -> u16 {
+        self.len()
+    }
+
     /// Return the checksum field.
     #[inline]
     pub fn checksum(&self) -> u16 {
@@ -134,6 +172,57 @@ impl udp {
         NetworkEndian::write_u16(&mut self.0[field::CHECKSUM], value)
     }
 
+    /// Set the checksum coverage field of a UDP-Lite datagram.
+    #[inline]
+    pub fn set_checksum_coverage(&mut self, value: u16) {
+        self.set_len(value)
+    }
+
+    /// The number of bytes covered by the checksum of a UDP-Lite datagram.
+    fn covered_len(&self) -> usize {
+        match self.checksum_coverage() {
+            0 => self.0.len(),
+            coverage => usize::from(coverage),
+        }
+    }
+
+    /// Compute and fill in the checksum of a UDP-Lite datagram.
+    ///
+    /// The pseudo header contains the length of the whole buffer while only the covered bytes are
+    /// summed. Unlike UDP, the checksum is never omitted.
+    ///
+    /// # Panics
+    /// This function panics unless `src_addr` and `dst_addr` belong to the same family,
+    /// and that family is IPv4 or IPv6.
+    pub fn fill_checksum_lite(&mut self, src_addr: IpAddress, dst_addr: IpAddress) {
+        self.set_checksum(0);
+        let checksum = {
+            !checksum::combine(&[
+                checksum::pseudo_header(&src_addr, &dst_addr, IpProtocol::UdpLite,
+                                        self.0.len() as u32),
+                checksum::data(&self.0[..self.covered_len()])
+            ])
+        };
+        self.set_checksum(if checksum == 0 { 0xffff } else { checksum })
+    }
+
+    /// Validate the checksum of a UDP-Lite datagram.
+    ///
+    /// A zero checksum is never valid. Call [`check_coverage`] first.
+    ///
+    /// # Panics
+    /// This function panics unless `src_addr` and `dst_addr` belong to the same family,
+    /// and that family is IPv4 or IPv6.
+    ///
+    /// [`check_coverage`]: #method.check_coverage
+    pub fn verify_checksum_lite(&self, src_addr: IpAddress, dst_addr: IpAddress) -> bool {
+        self.checksum() != 0 && checksum::combine(&[
+            checksum::pseudo_header(&src_addr, &dst_addr, IpProtocol::UdpLite,
+                                    self.0.len() as u32),
+            checksum::data(&self.0[..self.covered_len()])
+        ]) == !0
+    }
+
     /// Compute and fill in the header checksum.
     ///
     /// # Panics
@@ -155,6 +244,24 @@ impl udp {
         self.set_checksum(if checksum == 0 { 0xffff } else { checksum })
     }
 
+    /// Compute and fill in the checksum of a packet whose payload is stored apart.
+    ///
+    /// The buffer holds only the header while the length field already covers the complete
+    /// payload. Its checksum is given as `payload_sum`, without the final complement.
+    pub(crate) fn fill_checksum_detached(&mut self, src_addr: IpAddress, dst_addr: IpAddress,
+                                         payload_sum: u16) {
+        self.set_checksum(0);
+        let checksum = {
+            !checksum::combine(&[
+                checksum::pseudo_header(&src_addr, &dst_addr, IpProtocol::Udp,
+                                        self.len() as u32),
+                checksum::data(&self.0[..field::CHECKSUM.end]),
+                payload_sum,
+            ])
+        };
+        self.set_checksum(if checksum == 0 { 0xffff } else { checksum })
+    }
+
     /// Validate the packet checksum.
     ///
     /// # Panics
@@ -182,6 +289,16 @@ impl udp {
         let len = self.len();
         &mut self.0[field::PAYLOAD(len)]
     }
+
+    /// Return the payload of a UDP-Lite datagram, the rest of the buffer after the header.
+    pub fn payload_slice_lite(&self) -> &[u8] {
+        &self.0[field::CHECKSUM.end..]
+    }
+
+    /// Return the mutable payload of a UDP-Lite datagram.
+    pub fn payload_mut_slice_lite(&mut self) -> &mut [u8] {
+        &mut self.0[field::CHECKSUM.end..]
+    }
 }
 
 impl<T: Payload> Packet<T> {
@@ -198,6 +315,18 @@ impl<T: Payload> Packet<T> {
         })
     }
 
+    /// Check the buffer as a UDP-Lite datagram, see [`UdpRepr::parse_lite`].
+    ///
+    /// [`UdpRepr::parse_lite`]: struct.UdpRepr.html#method.parse_lite
+    pub fn new_checked_lite(buffer: T, checksum: Checksum) -> Result<Self> {
+        let frame = udp::new_checked_lite(buffer.payload())?;
+        let repr = Repr::parse_lite(frame, checksum)?;
+        Ok(Packet {
+            buffer,
+            repr,
+        })
+    }
+
     /// Constructs a frame with assumed representation.
     ///
     /// The validity of the frame is never a safety invariant but wrong data can still lead to
@@ -217,6 +346,13 @@ impl<T: Payload> Packet<T> {
         &self.buffer
     }
 
+    /// Get a mutable reference to the whole buffer.
+    ///
+    /// The length of the buffer must not be changed.
+    pub(crate) fn get_mut(&mut self) -> &mut T {
+        &mut self.buffer
+    }
+
     /// Get the repr of the underlying frame.
     pub fn repr(&self) -> Repr {
         self.repr
@@ -230,8 +366,8 @@ impl<T: Payload> Packet<T> {
     /// Return the payload as a mutable byte slice.
     pub fn payload_mut_slice(&mut self) -> &mut [u8] where T: PayloadMut {
         // Keeps header values unchanged.
-        udp::new_unchecked_mut(self.buffer.payload_mut())
-            .payload_mut_slice()
+        let payload = field::PAYLOAD(self.repr.length);
+        &mut self.buffer.payload_mut().as_mut_slice()[payload]
     }
 }
 
@@ -244,6 +380,12 @@ impl<T: Payload + PayloadMut> Packet<T> {
     pub fn fill_checksum(&mut self, checksum: Checksum) {
         let buffer = udp::new_unchecked_mut(self.buffer.payload_mut());
         match checksum {
+            // Checksum is always required for UDP-Lite.
+            Checksum::Manual { src_addr, dst_addr }
+            | Checksum::Lazy { src_addr, dst_addr } if self.repr.checksum_coverage.is_some() => {
+                buffer.fill_checksum_lite(src_addr, dst_addr)
+            },
+
             // Checksum optional, so we don't fill it.
             Checksum::Lazy { src_addr: IpAddress::Ipv4(_), dst_addr: IpAddress::Ipv4(_) }
             | Checksum::Ignored => (),
@@ -273,8 +415,7 @@ impl<'a, T: Payload + ?Sized> Packet<&'a T> {
     /// Return a pointer to the payload.
     #[inline]
     pub fn payload_slice(&self) -> &'a [u8] {
-        udp::new_unchecked(self.buffer.payload())
-            .payload_slice()
+        &self.buffer.payload().as_slice()[field::PAYLOAD(self.repr.length)]
     }
 }
 
@@ -282,8 +423,7 @@ impl<T: Payload + PayloadMut> Packet<T> {
     /// Return a mutable pointer to the payload.
     #[inline]
     pub fn payload_mut(&mut self) -> &mut [u8] {
-        udp::new_unchecked_mut(self.buffer.payload_mut())
-            .payload_mut_slice()
+        self.payload_mut_slice()
     }
 }
 
@@ -298,15 +438,13 @@ impl<T: Payload> ops::Deref for Packet<T> {
 
 impl<T: Payload> Payload for Packet<T> {
     fn payload(&self) -> &payload {
-        self.payload_slice().into()
+        self.buffer.payload().as_slice()[field::PAYLOAD(self.repr.length)].into()
     }
 }
 
 impl<T: Payload + PayloadMut> PayloadMut for Packet<T> {
     fn payload_mut(&mut self) -> &mut payload {
-        udp::new_unchecked_mut(self.buffer.payload_mut())
-            .payload_mut_slice()
-            .into()
+        self.payload_mut_slice().into()
     }
 
     fn resize(&mut self, length: usize) -> core::result::Result<(), PayloadError> {
@@ -332,7 +470,14 @@ impl<T: AsRef<[u8]>> AsRef<[u8]> for Packet<T> {
 pub struct Repr {
     pub src_port: u16,
     pub dst_port: u16,
+    /// The length of the datagram, including the header.
     pub length: u16,
+    /// The checksum coverage of a UDP-Lite datagram, `None` for UDP.
+    ///
+    /// UDP-Lite (RFC3828) sends the coverage in place of the length field, zero meaning the whole
+    /// datagram. The checksum only protects the covered bytes, errors in the rest of the payload
+    /// are tolerated.
+    pub checksum_coverage: Option<u16>,
 }
 
 /// Abstraction for checksum behaviour.
@@ -379,6 +524,37 @@ impl Repr {
             src_port: packet.src_port(),
             dst_port: packet.dst_port(),
             length: packet.len(),
+            checksum_coverage: None,
+        })
+    }
+
+    /// Parse a UDP-Lite datagram and return a high-level representation.
+    ///
+    /// The packet must have been checked with [`udp::check_coverage`], its length is that of the
+    /// buffer. The checksum is mandatory with both `Manual` and `Lazy`.
+    ///
+    /// [`udp::check_coverage`]: struct.udp_packet.html#method.check_coverage
+    pub fn parse_lite(packet: &udp, checksum: Checksum) -> Result<Repr> {
+        packet.check_coverage()?;
+        let length = u16::try_from(packet.as_bytes().len())
+            .map_err(|_| Error::Malformed)?;
+
+        if packet.dst_port() == 0 { return Err(Error::Malformed) }
+        match checksum {
+            Checksum::Manual { src_addr, dst_addr }
+            | Checksum::Lazy { src_addr, dst_addr } => {
+                if !packet.verify_checksum_lite(src_addr, dst_addr) {
+                    return Err(Error::WrongChecksum)
+                }
+            },
+            Checksum::Ignored => (),
+        }
+
+        Ok(Repr {
+            src_port: packet.src_port(),
+            dst_port: packet.dst_port(),
+            length,
+            checksum_coverage: Some(packet.checksum_coverage()),
         })
     }
 
@@ -400,10 +576,14 @@ impl Repr {
     pub fn emit(&self, packet: &mut udp, checksum: Checksum) {
         packet.set_src_port(self.src_port);
         packet.set_dst_port(self.dst_port);
-        packet.set_len(self.length);
+        packet.set_len(self.checksum_coverage.unwrap_or(self.length));
 
         if let Checksum::Manual { src_addr, dst_addr, } = checksum {
-            packet.fill_checksum(src_addr, dst_addr)
+            if self.checksum_coverage.is_some() {
+                packet.fill_checksum_lite(src_addr, dst_addr)
+            } else {
+                packet.fill_checksum(src_addr, dst_addr)
+            }
         } else {
             // make sure we get a consistently zeroed checksum,
             // since implementations might rely on it
@@ -431,14 +611,19 @@ impl<T: Payload> fmt::Display for Packet<T> {
 
 impl fmt::Display for Repr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = if self.checksum_coverage.is_some() { "UDP-Lite" } else { "UDP" };
         let payload_len = usize::from(self.length)
             .checked_sub(field::CHECKSUM.end);
         if let Some(payload_len) = payload_len {
-            write!(f, "UDP src={} dst={} len={}",
-                self.src_port, self.dst_port, payload_len)
+            write!(f, "{} src={} dst={} len={}",
+                name, self.src_port, self.dst_port, payload_len)?;
         } else {
-            write!(f, "UDP src={} dst={} len=??",
-                self.src_port, self.dst_port)
+            write!(f, "{} src={} dst={} len=??",
+                name, self.src_port, self.dst_port)?;
+        }
+        match self.checksum_coverage {
+            Some(coverage) => write!(f, " cov={}", coverage),
+            None => Ok(()),
         }
     }
 }
@@ -519,6 +704,7 @@ mod test {
             src_port: 48896,
             dst_port: 53,
             length: PACKET_BYTES.len() as u16,
+            checksum_coverage: None,
         }
     }
 
@@ -533,6 +719,66 @@ mod test {
         assert_eq!(packet.payload_slice(), &PAYLOAD_BYTES[..]);
     }
 
+    fn lite_repr(coverage: u16) -> Repr {
+        Repr {
+            src_port: 48896,
+            dst_port: 53,
+            length: PACKET_BYTES.len() as u16,
+            checksum_coverage: Some(coverage),
+        }
+    }
+
+    #[test]
+    fn test_lite_coverage() {
+        let checksum = || Checksum::for_pseudo_header(SRC_ADDR, DST_ADDR);
+        let repr = lite_repr(10);
+        let mut bytes = vec![0; repr.buffer_len()];
+        let packet = udp::new_unchecked_mut(&mut bytes);
+        packet.payload_mut_slice_lite().copy_from_slice(&PAYLOAD_BYTES[..]);
+        repr.emit(packet, checksum());
+        assert_eq!(packet.checksum_coverage(), 10);
+        assert_eq!(Repr::parse_lite(packet, checksum()), Ok(repr));
+
+        // Bytes past the coverage may be corrupted, those covered may not.
+        bytes[11] ^= 0xff;
+        assert_eq!(Repr::parse_lite(udp::new_unchecked(&bytes), checksum()), Ok(repr));
+        bytes[9] ^= 0xff;
+        assert_eq!(Repr::parse_lite(udp::new_unchecked(&bytes), checksum()),
+            Err(Error::WrongChecksum));
+    }
+
+    #[test]
+    fn test_lite_full_coverage() {
+        let checksum = || Checksum::for_pseudo_header(SRC_ADDR, DST_ADDR);
+        let repr = lite_repr(0);
+        let mut bytes = vec![0; repr.buffer_len()];
+        let packet = udp::new_unchecked_mut(&mut bytes);
+        packet.payload_mut_slice_lite().copy_from_slice(&PAYLOAD_BYTES[..]);
+        repr.emit(packet, checksum());
+        assert_eq!(Repr::parse_lite(packet, checksum()), Ok(repr));
+
+        bytes[11] ^= 0xff;
+        assert_eq!(Repr::parse_lite(udp::new_unchecked(&bytes), checksum()),
+            Err(Error::WrongChecksum));
+    }
+
+    #[test]
+    fn test_lite_malformed() {
+        let mut bytes = vec![0; 12];
+        let packet = udp::new_unchecked_mut(&mut bytes);
+        packet.set_dst_port(53);
+        packet.set_checksum_coverage(4);
+        assert_eq!(packet.check_coverage(), Err(Error::Malformed));
+        packet.set_checksum_coverage(13);
+        assert_eq!(packet.check_coverage(), Err(Error::Malformed));
+        packet.set_checksum_coverage(12);
+        assert_eq!(packet.check_coverage(), Ok(()));
+
+        // The checksum is mandatory.
+        let checksum = Checksum::for_pseudo_header(SRC_ADDR, DST_ADDR);
+        assert_eq!(Repr::parse_lite(packet, checksum), Err(Error::WrongChecksum));
+    }
+
     #[test]
     fn test_emit() {
         let repr = packet_repr();
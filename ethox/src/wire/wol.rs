@@ -0,0 +1,259 @@
+//! Wake-on-LAN magic packets.
+//!
+//! A magic packet wakes a sleeping host whose network card watches for it. It consists of six
+//! bytes of `0xff` followed by sixteen repetitions of the ethernet address of that host, and
+//! optionally a SecureOn password of four or six bytes. There is no further framing: the packet
+//! is sent directly in an ethernet frame with its own ethertype, or as the payload of a udp
+//! datagram usually to [`PORT`]. A network card recognizes the pattern anywhere in a frame, which
+//! [`find`] imitates.
+//!
+//! [`PORT`]: constant.WOL_PORT.html
+//! [`find`]: fn.wol_find.html
+use super::{EthernetAddress, Error, Result};
+
+/// The udp port to which magic packets are sent by convention, the discard service.
+pub const PORT: u16 = 9;
+
+/// The length of a magic packet without a password.
+pub const MAGIC_LEN: usize = field::TARGET.end;
+
+byte_wrapper! {
+    /// A byte slice containing a potential magic packet.
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct wol([u8]);
+}
+
+// Format of a magic packet
+//
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |      Synchronization stream, 6 x 0xff         |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |      Target ethernet address, 16 times        |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |      SecureOn password, 0, 4 or 6 bytes       |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+mod field {
+    use crate::wire::field::{Field, Rest};
+
+    pub(crate) const SYNC:     Field = 0..6;
+    // Sixteen repetitions of an ethernet address.
+    pub(crate) const TARGET:   Field = 6..6 + 16*6;
+    pub(crate) const PASSWORD: Rest  = TARGET.end..;
+}
+
+/// The optional password of a magic packet, supported by some network cards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Password {
+    /// A password of four bytes, often written like an IPv4 address.
+    Short([u8; 4]),
+    /// A password of six bytes, often written like an ethernet address.
+    Long([u8; 6]),
+}
+
+/// A high-level representation of a magic packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Repr {
+    /// The ethernet address of the host to wake.
+    pub target: EthernetAddress,
+    /// The SecureOn password, if any.
+    pub password: Option<Password>,
+}
+
+impl wol {
+    /// Imbue a raw octet buffer with magic packet structure.
+    pub fn new_unchecked(data: &[u8]) -> &Self {
+        Self::__from_macro_new_unchecked(data)
+    }
+
+    /// Imbue a mutable octet buffer with magic packet structure.
+    pub fn new_unchecked_mut(data: &mut [u8]) -> &mut Self {
+        Self::__from_macro_new_unchecked_mut(data)
+    }
+
+    /// Shorthand for a combination of [new_unchecked] and [check_len].
+    ///
+    /// [new_unchecked]: #method.new_unchecked
+    /// [check_len]: #method.check_len
+    pub fn new_checked(data: &[u8]) -> Result<&Self> {
+        let packet = Self::new_unchecked(data);
+        packet.check_len()?;
+        Ok(packet)
+    }
+
+    /// Ensure that no accessor method will panic if called.
+    ///
+    /// Returns `Err(Error::Truncated)` if the buffer is too short.
+    pub fn check_len(&self) -> Result<()> {
+        if self.0.len() < MAGIC_LEN {
+            Err(Error::Truncated)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// View the packet as a raw byte slice.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// View the packet as a mutable raw byte slice.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+
+    /// The ethernet address of the host to wake, from its first repetition.
+    pub fn target(&self) -> EthernetAddress {
+        EthernetAddress::from_bytes(&self.0[field::TARGET][..6])
+    }
+
+    /// Check the synchronization stream and that all repetitions of the target are equal.
+    pub fn is_magic(&self) -> bool {
+        let target = &self.0[field::TARGET];
+        self.0[field::SYNC].iter().all(|&byte| byte == 0xff)
+            && target.chunks(6).all(|repetition| repetition == &target[..6])
+    }
+
+    /// The bytes following the repeated target, the password of a well-formed packet.
+    pub fn password(&self) -> &[u8] {
+        &self.0[field::PASSWORD]
+    }
+
+    /// Write the synchronization stream and all repetitions of the target.
+    pub fn set_target(&mut self, value: EthernetAddress) {
+        for byte in &mut self.0[field::SYNC] {
+            *byte = 0xff;
+        }
+        for repetition in self.0[field::TARGET].chunks_mut(6) {
+            repetition.copy_from_slice(value.as_bytes());
+        }
+    }
+
+    /// The bytes following the repeated target, mutably.
+    pub fn password_mut(&mut self) -> &mut [u8] {
+        &mut self.0[field::PASSWORD]
+    }
+}
+
+impl Password {
+    /// The bytes of the password.
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Password::Short(bytes) => bytes,
+            Password::Long(bytes) => bytes,
+        }
+    }
+}
+
+impl Repr {
+    /// A magic packet without a password.
+    pub fn new(target: EthernetAddress) -> Self {
+        Repr { target, password: None }
+    }
+
+    /// Parse a magic packet that fills the whole buffer.
+    ///
+    /// Returns `Err(Error::Malformed)` unless the pattern is intact and followed by no data or a
+    /// password of one of the supported lengths.
+    pub fn parse(packet: &wol) -> Result<Self> {
+        packet.check_len()?;
+        if !packet.is_magic() {
+            return Err(Error::Malformed);
+        }
+
+        let password = match packet.password() {
+            [] => None,
+            &[a, b, c, d] => Some(Password::Short([a, b, c, d])),
+            &[a, b, c, d, e, f] => Some(Password::Long([a, b, c, d, e, f])),
+            _ => return Err(Error::Malformed),
+        };
+
+        Ok(Repr {
+            target: packet.target(),
+            password,
+        })
+    }
+
+    /// The length of the emitted packet.
+    pub fn buffer_len(&self) -> usize {
+        MAGIC_LEN + self.password.map_or(0, |password| password.as_bytes().len())
+    }
+
+    /// Emit the packet into a buffer of exactly `buffer_len` bytes.
+    pub fn emit(&self, packet: &mut wol) {
+        packet.set_target(self.target);
+        if let Some(password) = self.password {
+            packet.password_mut().copy_from_slice(password.as_bytes());
+        }
+    }
+}
+
+/// Search a buffer for a magic packet, like a network card does.
+///
+/// Returns the target of the first intact pattern anywhere in the data, for example in the payload
+/// of an ethernet frame regardless of its protocol. A password can not be recognized this way.
+pub fn find(data: &[u8]) -> Option<EthernetAddress> {
+    if data.len() < MAGIC_LEN {
+        return None;
+    }
+
+    (0..=data.len() - MAGIC_LEN)
+        .map(|start| wol::new_unchecked(&data[start..start + MAGIC_LEN]))
+        .find(|packet| packet.is_magic())
+        .map(wol::target)
+}
+
+impl AsRef<[u8]> for wol {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl AsMut<[u8]> for wol {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.as_bytes_mut()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static TARGET: EthernetAddress = EthernetAddress([0x52, 0x54, 0, 0x12, 0x34, 0x56]);
+
+    #[test]
+    fn test_roundtrip() {
+        let repr = Repr::new(TARGET);
+        let mut bytes = vec![0; repr.buffer_len()];
+        repr.emit(wol::new_unchecked_mut(&mut bytes));
+        assert_eq!(&bytes[..6], &[0xff; 6]);
+        assert_eq!(&bytes[96..], TARGET.as_bytes());
+        assert_eq!(Repr::parse(wol::new_checked(&bytes).unwrap()), Ok(repr));
+
+        let repr = Repr { target: TARGET, password: Some(Password::Short([192, 0, 2, 1])) };
+        let mut bytes = vec![0; repr.buffer_len()];
+        repr.emit(wol::new_unchecked_mut(&mut bytes));
+        assert_eq!(bytes.len(), MAGIC_LEN + 4);
+        assert_eq!(Repr::parse(wol::new_checked(&bytes).unwrap()), Ok(repr));
+    }
+
+    #[test]
+    fn test_malformed() {
+        let mut bytes = vec![0; MAGIC_LEN + 1];
+        wol::new_unchecked_mut(&mut bytes).set_target(TARGET);
+        // A trailing byte is no password.
+        assert_eq!(Repr::parse(wol::new_unchecked(&bytes)), Err(Error::Malformed));
+        // A broken repetition.
+        bytes[50] ^= 1;
+        assert_eq!(Repr::parse(wol::new_unchecked(&bytes[..MAGIC_LEN])), Err(Error::Malformed));
+        assert_eq!(wol::new_checked(&bytes[..MAGIC_LEN - 1]), Err(Error::Truncated));
+    }
+
+    #[test]
+    fn test_find() {
+        let mut bytes = vec![0x42; MAGIC_LEN + 20];
+        assert_eq!(find(&bytes), None);
+        wol::new_unchecked_mut(&mut bytes[7..]).set_target(TARGET);
+        assert_eq!(find(&bytes), Some(TARGET));
+        assert_eq!(find(&bytes[8..]), None);
+    }
+}